@@ -0,0 +1,229 @@
+// BSATN, the binary encoding of SATS values in which the host passes rows, arguments, and events,
+// as implemented by `crates/sats/src/bsatn`.
+//
+// Integers and floats are little-endian, strings and arrays are prefixed by their length as a `u32`,
+// and products are their fields one after another.
+
+/** A value that can be BSATN encoded, e.g., a row of a table or an event. */
+export abstract class Serializable {
+  abstract serialize(w: Writer): void;
+}
+
+/**
+ * A value that can also be BSATN decoded.
+ *
+ * Its class must have a constructor taking no arguments,
+ * which `deserialize` then fills in, field by field.
+ */
+export abstract class Deserializable extends Serializable {
+  abstract deserialize(r: Reader): void;
+}
+
+/** A growable byte buffer into which values are BSATN encoded. */
+export class Writer {
+  private buf: Uint8Array = new Uint8Array(64);
+  private len: i32 = 0;
+
+  /** Makes room for `n` more bytes, returning where they start. */
+  private reserve(n: i32): usize {
+    const needed = this.len + n;
+    if (needed > this.buf.length) {
+      let cap = this.buf.length * 2;
+      while (cap < needed) cap *= 2;
+      const buf = new Uint8Array(cap);
+      buf.set(this.buf.subarray(0, this.len));
+      this.buf = buf;
+    }
+    const ptr = this.buf.dataStart + <usize>this.len;
+    this.len = needed;
+    return ptr;
+  }
+
+  writeBool(v: bool): void {
+    store<u8>(this.reserve(1), v ? 1 : 0);
+  }
+  writeU8(v: u8): void {
+    store<u8>(this.reserve(1), v);
+  }
+  writeI8(v: i8): void {
+    store<i8>(this.reserve(1), v);
+  }
+  writeU16(v: u16): void {
+    store<u16>(this.reserve(2), v);
+  }
+  writeI16(v: i16): void {
+    store<i16>(this.reserve(2), v);
+  }
+  writeU32(v: u32): void {
+    store<u32>(this.reserve(4), v);
+  }
+  writeI32(v: i32): void {
+    store<i32>(this.reserve(4), v);
+  }
+  writeU64(v: u64): void {
+    store<u64>(this.reserve(8), v);
+  }
+  writeI64(v: i64): void {
+    store<i64>(this.reserve(8), v);
+  }
+  writeF32(v: f32): void {
+    store<f32>(this.reserve(4), v);
+  }
+  writeF64(v: f64): void {
+    store<f64>(this.reserve(8), v);
+  }
+
+  /** Writes `bytes` as they are, without a length prefix. */
+  writeRaw(bytes: Uint8Array): void {
+    memory.copy(this.reserve(bytes.length), bytes.dataStart, bytes.length);
+  }
+
+  writeString(s: string): void {
+    const bytes = Uint8Array.wrap(String.UTF8.encode(s));
+    this.writeU32(bytes.length);
+    this.writeRaw(bytes);
+  }
+
+  /**
+   * Writes `value`, which is a `bool`, an integer or float of at most 64 bits,
+   * a `string`, a `Serializable`, or an `Array` of those.
+   */
+  write<T>(value: T): void {
+    if (isBoolean<T>()) {
+      this.writeBool(<bool>value);
+    } else if (isInteger<T>()) {
+      if (sizeof<T>() == 1) {
+        isSigned<T>() ? this.writeI8(<i8>value) : this.writeU8(<u8>value);
+      } else if (sizeof<T>() == 2) {
+        isSigned<T>() ? this.writeI16(<i16>value) : this.writeU16(<u16>value);
+      } else if (sizeof<T>() == 4) {
+        isSigned<T>() ? this.writeI32(<i32>value) : this.writeU32(<u32>value);
+      } else {
+        isSigned<T>() ? this.writeI64(<i64>value) : this.writeU64(<u64>value);
+      }
+    } else if (isFloat<T>()) {
+      sizeof<T>() == 4 ? this.writeF32(<f32>value) : this.writeF64(<f64>value);
+    } else if (isString<T>()) {
+      this.writeString(changetype<string>(value));
+    } else if (isArray<T>()) {
+      // @ts-ignore: `T` is an `Array` here.
+      const len = value.length;
+      this.writeU32(len);
+      // @ts-ignore: `T` is an `Array` here.
+      for (let i = 0; i < len; i++) this.write<valueof<T>>(unchecked(value[i]));
+    } else {
+      // @ts-ignore: `T` is a `Serializable` here.
+      value.serialize(this);
+    }
+  }
+
+  /** The bytes written so far. */
+  finish(): Uint8Array {
+    return this.buf.slice(0, this.len);
+  }
+}
+
+/** Encodes `value`, as with `Writer.write`. */
+export function encode<T>(value: T): Uint8Array {
+  const w = new Writer();
+  w.write<T>(value);
+  return w.finish();
+}
+
+/** A cursor over BSATN encoded bytes from which values are decoded. */
+export class Reader {
+  private pos: i32 = 0;
+
+  constructor(private bytes: Uint8Array) {}
+
+  /** The number of bytes left to read. */
+  remaining(): i32 {
+    return this.bytes.length - this.pos;
+  }
+
+  /** Consumes `n` bytes, returning where they start. */
+  private take(n: i32): usize {
+    if (n > this.remaining()) throw new Error("unexpected end of BSATN input");
+    const ptr = this.bytes.dataStart + <usize>this.pos;
+    this.pos += n;
+    return ptr;
+  }
+
+  readBool(): bool {
+    return load<u8>(this.take(1)) != 0;
+  }
+  readU8(): u8 {
+    return load<u8>(this.take(1));
+  }
+  readI8(): i8 {
+    return load<i8>(this.take(1));
+  }
+  readU16(): u16 {
+    return load<u16>(this.take(2));
+  }
+  readI16(): i16 {
+    return load<i16>(this.take(2));
+  }
+  readU32(): u32 {
+    return load<u32>(this.take(4));
+  }
+  readI32(): i32 {
+    return load<i32>(this.take(4));
+  }
+  readU64(): u64 {
+    return load<u64>(this.take(8));
+  }
+  readI64(): i64 {
+    return load<i64>(this.take(8));
+  }
+  readF32(): f32 {
+    return load<f32>(this.take(4));
+  }
+  readF64(): f64 {
+    return load<f64>(this.take(8));
+  }
+
+  /** Reads the next `n` bytes as they are. */
+  readRaw(n: i32): Uint8Array {
+    const start = this.pos;
+    this.take(n);
+    return this.bytes.slice(start, this.pos);
+  }
+
+  readString(): string {
+    const len = <i32>this.readU32();
+    return String.UTF8.decode(this.readRaw(len).buffer);
+  }
+
+  /** Reads a value of type `T`, which is any type `Writer.write` accepts that is also `Deserializable`. */
+  read<T>(): T {
+    if (isBoolean<T>()) {
+      return <T>this.readBool();
+    } else if (isInteger<T>()) {
+      if (sizeof<T>() == 1) return isSigned<T>() ? <T>this.readI8() : <T>this.readU8();
+      if (sizeof<T>() == 2) return isSigned<T>() ? <T>this.readI16() : <T>this.readU16();
+      if (sizeof<T>() == 4) return isSigned<T>() ? <T>this.readI32() : <T>this.readU32();
+      return isSigned<T>() ? <T>this.readI64() : <T>this.readU64();
+    } else if (isFloat<T>()) {
+      return sizeof<T>() == 4 ? <T>this.readF32() : <T>this.readF64();
+    } else if (isString<T>()) {
+      return changetype<T>(this.readString());
+    } else if (isArray<T>()) {
+      const len = <i32>this.readU32();
+      const arr = instantiate<T>(0);
+      // @ts-ignore: `T` is an `Array` here.
+      for (let i = 0; i < len; i++) arr.push(this.read<valueof<T>>());
+      return arr;
+    } else {
+      const value = instantiate<T>();
+      // @ts-ignore: `T` is a `Deserializable` here.
+      value.deserialize(this);
+      return value;
+    }
+  }
+}
+
+/** Decodes a value of type `T` from `bytes`, as with `Reader.read`. */
+export function decode<T>(bytes: Uint8Array): T {
+  return new Reader(bytes).read<T>();
+}
@@ -0,0 +1,33 @@
+// The exports the host calls into, as `crates/bindings/src/rt.rs` defines them for Rust.
+//
+// A module re-exports them from its entry file:
+// ```ts
+// export * from "@clockworklabs/spacetimedb-assemblyscript/assembly/exports";
+// ```
+
+import { ABI_VERSION, Buffer, INVALID_BUFFER, allocBuffer, allocStringBuffer, readBuffer } from "./sys";
+import { ReducerContext, callConnectDisconnect, callReducer, describeModule } from "./module";
+
+/** The version of the ABI the module targets, read by the host before instantiating it. */
+export const SPACETIME_ABI_VERSION: u32 = ABI_VERSION;
+
+function errorBuffer(err: string | null): Buffer {
+  return err == null ? INVALID_BUFFER : allocStringBuffer(err!);
+}
+
+export function __describe_module__(): Buffer {
+  return allocBuffer(describeModule());
+}
+
+export function __call_reducer__(id: u32, sender: Buffer, timestamp: u64, args: Buffer): Buffer {
+  const ctx = new ReducerContext(readBuffer(sender), timestamp);
+  return errorBuffer(callReducer(id, ctx, readBuffer(args)));
+}
+
+export function __identity_connected__(sender: Buffer, timestamp: u64): Buffer {
+  return errorBuffer(callConnectDisconnect(true, new ReducerContext(readBuffer(sender), timestamp)));
+}
+
+export function __identity_disconnected__(sender: Buffer, timestamp: u64): Buffer {
+  return errorBuffer(callConnectDisconnect(false, new ReducerContext(readBuffer(sender), timestamp)));
+}
@@ -0,0 +1,47 @@
+// Bindings for writing SpacetimeDB modules in AssemblyScript,
+// the counterpart of the `spacetimedb` crate for Rust modules.
+//
+// A module declares its tables with `Table`, its reducers with `reducer`,
+// and re-exports `./exports` from its entry file for the host to call into it.
+// It's published with `spacetime publish`, which runs it on the `assemblyscript` host type.
+
+export { Deserializable, Reader, Serializable, Writer, decode, encode } from "./bsatn";
+export {
+  Column,
+  ColumnAttr,
+  Event,
+  ReducerContext,
+  ReducerFn,
+  Savepoint,
+  Table,
+  cancelScheduled,
+  column,
+  connect,
+  disconnect,
+  init,
+  reducer,
+  remainingEnergy,
+  savepoint,
+  schedule,
+  update,
+} from "./module";
+export { Errno } from "./sys";
+export { AlgebraicType, ProductTypeElement, field } from "./types";
+
+import { LogLevel, consoleLog } from "./sys";
+
+export function logError(message: string): void {
+  consoleLog(LogLevel.ERROR, message);
+}
+
+export function logWarn(message: string): void {
+  consoleLog(LogLevel.WARN, message);
+}
+
+export function log(message: string): void {
+  consoleLog(LogLevel.INFO, message);
+}
+
+export function logDebug(message: string): void {
+  consoleLog(LogLevel.DEBUG, message);
+}
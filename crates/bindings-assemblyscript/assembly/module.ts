@@ -0,0 +1,320 @@
+// The tables, reducers, and events of a module, as it declares them,
+// and the `ModuleDef` describing them to the host, as `crates/bindings/src/rt.rs` builds it for Rust.
+
+import { Deserializable, Reader, Serializable, Writer, encode } from "./bsatn";
+import * as sys from "./sys";
+import { AlgebraicType, ProductTypeElement, field } from "./types";
+
+/** The attributes of a column, as `spacetimedb_lib::ColumnIndexAttribute`. */
+export namespace ColumnAttr {
+  export const UNSET: u8 = 0;
+  /** Unique and auto-incremented. */
+  export const IDENTITY: u8 = 1;
+  export const UNIQUE: u8 = 2;
+  export const INDEXED: u8 = 3;
+  export const AUTO_INC: u8 = 4;
+  /** The primary key, which is unique. */
+  export const PRIMARY_KEY: u8 = 5;
+  /** The primary key, auto-incremented. */
+  export const PRIMARY_KEY_AUTO: u8 = 6;
+}
+
+/** A column of a table. */
+export class Column {
+  constructor(public element: ProductTypeElement, public attr: u8) {}
+}
+
+/** A column named `name` of the type of `T`, as with `AlgebraicType.of`. */
+export function column<T>(name: string, attr: u8 = ColumnAttr.UNSET): Column {
+  return new Column(field<T>(name), attr);
+}
+
+/** The parts of a table that don't depend on the type of its rows. */
+export abstract class TableDef {
+  private tableId: u32 = u32.MAX_VALUE;
+
+  constructor(public name: string, public columns: Column[]) {}
+
+  /** The id of the table in the database. */
+  id(): u32 {
+    if (this.tableId == u32.MAX_VALUE) this.tableId = sys.getTableId(this.name);
+    return this.tableId;
+  }
+
+  /** The position of the column `name`, which must exist. */
+  protected colId(name: string): u32 {
+    for (let i = 0; i < this.columns.length; i++) {
+      if (this.columns[i].element.name == name) return i;
+    }
+    throw new Error("no column " + name + " in table " + this.name);
+  }
+
+  rowType(): AlgebraicType {
+    const elements: ProductTypeElement[] = [];
+    for (let i = 0; i < this.columns.length; i++) elements.push(this.columns[i].element);
+    return AlgebraicType.product(elements);
+  }
+}
+
+const TABLES: TableDef[] = [];
+
+/**
+ * A table whose rows are `T`s, the counterpart of `#[spacetimedb(table)]`.
+ *
+ * The `columns` must list the fields of `T` in the order that `T` serializes them.
+ * Creating the table registers it with the module, so it must be created at the top level:
+ * ```ts
+ * class Person extends Deserializable {
+ *   id: u32 = 0;
+ *   name: string = "";
+ *   serialize(w: Writer): void {
+ *     w.write(this.id);
+ *     w.write(this.name);
+ *   }
+ *   deserialize(r: Reader): void {
+ *     this.id = r.read<u32>();
+ *     this.name = r.read<string>();
+ *   }
+ * }
+ *
+ * const people = new Table<Person>("Person", [
+ *   column<u32>("id", ColumnAttr.PRIMARY_KEY_AUTO),
+ *   column<string>("name"),
+ * ]);
+ * ```
+ */
+export class Table<T extends Deserializable> extends TableDef {
+  constructor(name: string, columns: Column[]) {
+    super(name, columns);
+    TABLES.push(this);
+  }
+
+  /** Inserts `row`, filling in its auto-incremented columns, and returns it. Fails the reducer on error. */
+  insert(row: T): T {
+    const errno = this.tryInsert(row);
+    if (errno != sys.Errno.OK) throw new Error("insert failed: " + sys.Errno.message(errno));
+    return row;
+  }
+
+  /**
+   * Like `insert`, but returns the error, e.g., `Errno.UNIQUE_ALREADY_EXISTS`, rather than failing the reducer.
+   */
+  tryInsert(row: T): u16 {
+    const bytes = encode<T>(row);
+    const errno = sys.insert(this.id(), bytes);
+    if (errno == sys.Errno.OK) row.deserialize(new Reader(bytes));
+    return errno;
+  }
+
+  /** Returns every row of the table. */
+  iter(): T[] {
+    const rows: T[] = [];
+    const chunks = sys.iterAll(this.id());
+    for (let i = 0; i < chunks.length; i++) readRows<T>(chunks[i], rows);
+    return rows;
+  }
+
+  /** The number of rows in the table. */
+  count(): i32 {
+    return this.iter().length;
+  }
+
+  /** Returns the rows whose column `column` equals `value`. */
+  filterBy<V>(column: string, value: V): T[] {
+    const rows: T[] = [];
+    readRows<T>(sys.iterByColEq(this.id(), this.colId(column), encode<V>(value)), rows);
+    return rows;
+  }
+
+  /** Returns the row whose unique column `column` equals `value`, if any. */
+  findBy<V>(column: string, value: V): T | null {
+    const rows = this.filterBy<V>(column, value);
+    return rows.length > 0 ? rows[0] : null;
+  }
+
+  /** Deletes the rows whose column `column` equals `value`, returning how many were deleted. */
+  deleteBy<V>(column: string, value: V): u32 {
+    return sys.deleteByColEq(this.id(), this.colId(column), encode<V>(value));
+  }
+}
+
+/** Decodes the concatenated rows in `bytes` into `rows`. */
+function readRows<T extends Deserializable>(bytes: Uint8Array, rows: T[]): void {
+  const r = new Reader(bytes);
+  while (r.remaining() > 0) {
+    const row = instantiate<T>();
+    row.deserialize(r);
+    rows.push(row);
+  }
+}
+
+/** The context of a reducer call. */
+export class ReducerContext {
+  constructor(
+    /** The identity of the caller, as its 32 bytes. */
+    public sender: Uint8Array,
+    /** When the reducer was called, in microseconds since the Unix epoch. */
+    public timestamp: u64,
+  ) {}
+}
+
+/**
+ * A reducer, called with its context and a reader of its arguments, in order.
+ *
+ * Returning a string fails the reducer with it as the error, rolling back its changes,
+ * as does a `throw`.
+ */
+export type ReducerFn = (ctx: ReducerContext, args: Reader) => string | null;
+
+class ReducerDef {
+  constructor(public name: string, public args: ProductTypeElement[], public fn: ReducerFn) {}
+}
+
+const REDUCERS: ReducerDef[] = [];
+
+/** Registers the reducer `fn` under `name`, taking the arguments `args`, the counterpart of `#[spacetimedb(reducer)]`. */
+export function reducer(name: string, args: ProductTypeElement[], fn: ReducerFn): void {
+  REDUCERS.push(new ReducerDef(name, args, fn));
+}
+
+/** Registers `fn` to be called when the module is first published, the counterpart of `#[spacetimedb(init)]`. */
+export function init(fn: ReducerFn): void {
+  reducer("__init__", [], fn);
+}
+
+/** Registers `fn` to be called when the module is updated, the counterpart of `#[spacetimedb(update)]`. */
+export function update(fn: ReducerFn): void {
+  reducer("__update__", [], fn);
+}
+
+/** The parts of an event type that don't depend on the type of its values. */
+export abstract class EventDef {
+  constructor(public name: string, public fields: ProductTypeElement[]) {}
+}
+
+const EVENTS: EventDef[] = [];
+
+/** A type of event that reducers can emit to the subscribed clients, the counterpart of `#[spacetimedb(event)]`. */
+export class Event<T extends Serializable> extends EventDef {
+  constructor(name: string, fields: ProductTypeElement[]) {
+    super(name, fields);
+    EVENTS.push(this);
+  }
+
+  /** Emits `event` along with the current reducer's transaction, if it commits. */
+  emit(event: T): void {
+    sys.emitEvent(this.name, encode<T>(event));
+  }
+}
+
+/** Schedules the reducer `name` to be called with the BSATN encoded `args` at `time`, returning the id of the call. */
+export function schedule(name: string, time: u64, args: Uint8Array = new Uint8Array(0)): u64 {
+  return sys.scheduleReducer(name, args, time);
+}
+
+/** Cancels the scheduled call `id`. */
+export function cancelScheduled(id: u64): void {
+  sys.cancelReducer(id);
+}
+
+/** Returns the energy left in the budget of the current reducer call. */
+export function remainingEnergy(): u64 {
+  return sys.remainingEnergy();
+}
+
+/** A savepoint in the current reducer's transaction, taken by `savepoint`. */
+export class Savepoint {
+  constructor(private id: u32) {}
+
+  /** Undoes the changes made in the current reducer's transaction since this savepoint was taken. */
+  rollbackTo(): void {
+    sys.rollbackToSavepoint(this.id);
+  }
+
+  /** Keeps the changes made since this savepoint was taken. */
+  release(): void {
+    // An outer savepoint may have been rolled back to or released already, which released this one too.
+    sys.releaseSavepoint(this.id);
+  }
+}
+
+/** Takes a savepoint of the changes made so far in the current reducer's transaction. */
+export function savepoint(): Savepoint {
+  return new Savepoint(sys.savepoint());
+}
+
+let onConnect: ReducerFn | null = null;
+let onDisconnect: ReducerFn | null = null;
+
+/** Registers `fn` to be called when a client connects, the counterpart of `#[spacetimedb(connect)]`. */
+export function connect(fn: ReducerFn): void {
+  onConnect = fn;
+}
+
+/** Registers `fn` to be called when a client disconnects, the counterpart of `#[spacetimedb(disconnect)]`. */
+export function disconnect(fn: ReducerFn): void {
+  onDisconnect = fn;
+}
+
+/** Encodes the `ModuleDef` of the module. */
+export function describeModule(): Uint8Array {
+  const w = new Writer();
+
+  // typespace: the row type of every table, then the type of every event.
+  w.writeU32(TABLES.length + EVENTS.length);
+  for (let i = 0; i < TABLES.length; i++) TABLES[i].rowType().encode(w);
+  for (let i = 0; i < EVENTS.length; i++) AlgebraicType.product(EVENTS[i].fields).encode(w);
+
+  // tables
+  w.writeU32(TABLES.length);
+  for (let i = 0; i < TABLES.length; i++) {
+    const table = TABLES[i];
+    w.writeString(table.name);
+    w.writeU32(i);
+    w.writeU32(table.columns.length);
+    for (let j = 0; j < table.columns.length; j++) w.writeU8(table.columns[j].attr);
+    // indexes
+    w.writeU32(0);
+    // `StTableType::User`
+    w.writeU8(1);
+    // `StAccess::for_name`
+    w.writeU8(table.name.startsWith("_") ? 1 : 0);
+  }
+
+  // reducers
+  w.writeU32(REDUCERS.length);
+  for (let i = 0; i < REDUCERS.length; i++) {
+    w.writeString(REDUCERS[i].name);
+    const args = REDUCERS[i].args;
+    w.writeU32(args.length);
+    for (let j = 0; j < args.length; j++) args[j].encode(w);
+  }
+
+  // misc_exports: a `TypeAlias` naming the row type of every table, and an `Event` for every event.
+  w.writeU32(TABLES.length + EVENTS.length);
+  for (let i = 0; i < TABLES.length; i++) {
+    w.writeU8(0);
+    w.writeString(TABLES[i].name);
+    w.writeU32(i);
+  }
+  for (let i = 0; i < EVENTS.length; i++) {
+    w.writeU8(3);
+    w.writeString(EVENTS[i].name);
+    w.writeU32(TABLES.length + i);
+  }
+
+  return w.finish();
+}
+
+/** Calls the reducer `id`, in the order of registration, returning its error, if any. */
+export function callReducer(id: u32, ctx: ReducerContext, args: Uint8Array): string | null {
+  const fn = REDUCERS[id].fn;
+  return fn(ctx, new Reader(args));
+}
+
+/** Calls the handler of client connections, or disconnections, if the module registered one. */
+export function callConnectDisconnect(connected: bool, ctx: ReducerContext): string | null {
+  const fn = connected ? onConnect : onDisconnect;
+  if (fn == null) return null;
+  return fn!(ctx, new Reader(new Uint8Array(0)));
+}
@@ -0,0 +1,232 @@
+// The raw `spacetime` ABI, as declared in `crates/bindings-sys/src/lib.rs`,
+// and thin wrappers over it that deal in strings and byte arrays rather than pointers.
+//
+// Pointers and lengths are `usize`, as `*const u8` and `usize` are in Rust.
+
+/** The version of the ABI these bindings target, as `bindings-sys`'s `ABI_VERSION`. */
+export const ABI_VERSION: u32 = 0x0003_0006;
+
+/** A handle to a buffer in the host. */
+export type Buffer = u32;
+
+/** The buffer handle that refers to no buffer. */
+export const INVALID_BUFFER: Buffer = u32.MAX_VALUE;
+
+/** The errnos of `crates/bindings-sys/src/errno.rs`. */
+export namespace Errno {
+  export const OK: u16 = 0;
+  export const NO_SUCH_TABLE: u16 = 1;
+  export const LOOKUP_NOT_FOUND: u16 = 2;
+  export const UNIQUE_ALREADY_EXISTS: u16 = 3;
+  export const NO_SUCH_SAVEPOINT: u16 = 4;
+  export const QUOTA_EXCEEDED: u16 = 5;
+  export const NO_SUCH_REDUCER: u16 = 6;
+
+  export function message(errno: u16): string {
+    switch (errno) {
+      case NO_SUCH_TABLE:
+        return "No such table";
+      case LOOKUP_NOT_FOUND:
+        return "Value or range provided not found in table";
+      case UNIQUE_ALREADY_EXISTS:
+        return "Value with given unique identifier already exists";
+      case NO_SUCH_SAVEPOINT:
+        return "No such savepoint";
+      case QUOTA_EXCEEDED:
+        return "The database's quota was exceeded";
+      case NO_SUCH_REDUCER:
+        return "No such reducer";
+      default:
+        return "Unknown error " + errno.toString();
+    }
+  }
+}
+
+/** The log levels of `_console_log`. */
+export namespace LogLevel {
+  export const ERROR: u8 = 0;
+  export const WARN: u8 = 1;
+  export const INFO: u8 = 2;
+  export const DEBUG: u8 = 3;
+  export const TRACE: u8 = 4;
+  export const PANIC: u8 = 101;
+}
+
+@external("spacetime", "_get_table_id")
+declare function _get_table_id(name: usize, name_len: usize, out: usize): u16;
+
+@external("spacetime", "_iter_by_col_eq")
+declare function _iter_by_col_eq(table_id: u32, col_id: u32, value: usize, value_len: usize, out: usize): u16;
+
+@external("spacetime", "_insert")
+declare function _insert(table_id: u32, row: usize, row_len: usize): u16;
+
+@external("spacetime", "_delete_by_col_eq")
+declare function _delete_by_col_eq(table_id: u32, col_id: u32, value: usize, value_len: usize, out: usize): u16;
+
+@external("spacetime", "_remaining_energy")
+declare function _remaining_energy(out: usize): u16;
+
+@external("spacetime", "_savepoint")
+declare function _savepoint(out: usize): u16;
+
+@external("spacetime", "_rollback_to_savepoint")
+declare function _rollback_to_savepoint(id: u32): u16;
+
+@external("spacetime", "_release_savepoint")
+declare function _release_savepoint(id: u32): u16;
+
+@external("spacetime", "_iter_start")
+declare function _iter_start(table_id: u32, out: usize): u16;
+
+@external("spacetime", "_iter_next")
+declare function _iter_next(iter: u32, out: usize): u16;
+
+@external("spacetime", "_iter_drop")
+declare function _iter_drop(iter: u32): u16;
+
+@external("spacetime", "_console_log")
+declare function _console_log(
+  level: u8,
+  target: usize,
+  target_len: usize,
+  filename: usize,
+  filename_len: usize,
+  line_number: u32,
+  text: usize,
+  text_len: usize,
+): void;
+
+@external("spacetime", "_schedule_reducer")
+declare function _schedule_reducer(
+  name: usize,
+  name_len: usize,
+  args: usize,
+  args_len: usize,
+  time: u64,
+  out: usize,
+): void;
+
+@external("spacetime", "_cancel_reducer")
+declare function _cancel_reducer(id: u64): void;
+
+@external("spacetime", "_emit_event")
+declare function _emit_event(name: usize, name_len: usize, data: usize, data_len: usize): u16;
+
+@external("spacetime", "_buffer_len")
+declare function _buffer_len(bufh: Buffer): usize;
+
+@external("spacetime", "_buffer_consume")
+declare function _buffer_consume(bufh: Buffer, into: usize, len: usize): void;
+
+@external("spacetime", "_buffer_alloc")
+declare function _buffer_alloc(data: usize, data_len: usize): Buffer;
+
+// Where the host writes the `out` values of the calls above.
+const OUT = memory.data(8);
+
+/** Fails the current reducer call, as a `throw` would, if `errno` is an error. */
+function check(errno: u16, call: string): void {
+  if (errno != Errno.OK) {
+    throw new Error(call + " failed: " + Errno.message(errno));
+  }
+}
+
+function utf8(s: string): Uint8Array {
+  return Uint8Array.wrap(String.UTF8.encode(s));
+}
+
+/** Reads the contents of `buf`, consuming it. */
+export function readBuffer(buf: Buffer): Uint8Array {
+  const bytes = new Uint8Array(<i32>_buffer_len(buf));
+  _buffer_consume(buf, bytes.dataStart, bytes.length);
+  return bytes;
+}
+
+/** Creates a buffer in the host with `bytes` as its contents. */
+export function allocBuffer(bytes: Uint8Array): Buffer {
+  return _buffer_alloc(bytes.dataStart, bytes.length);
+}
+
+/** Creates a buffer in the host with the UTF-8 encoding of `s` as its contents. */
+export function allocStringBuffer(s: string): Buffer {
+  return allocBuffer(utf8(s));
+}
+
+export function getTableId(name: string): u32 {
+  const bytes = utf8(name);
+  check(_get_table_id(bytes.dataStart, bytes.length, OUT), "get_table_id");
+  return load<u32>(OUT);
+}
+
+/** Inserts the BSATN encoded `row`, which the host overwrites with the row as inserted. */
+export function insert(tableId: u32, row: Uint8Array): u16 {
+  return _insert(tableId, row.dataStart, row.length);
+}
+
+/** Returns the concatenated BSATN encodings of the rows whose column `colId` equals the BSATN encoded `value`. */
+export function iterByColEq(tableId: u32, colId: u32, value: Uint8Array): Uint8Array {
+  check(_iter_by_col_eq(tableId, colId, value.dataStart, value.length, OUT), "iter_by_col_eq");
+  return readBuffer(load<u32>(OUT));
+}
+
+/** Deletes the rows whose column `colId` equals the BSATN encoded `value`, returning how many were deleted. */
+export function deleteByColEq(tableId: u32, colId: u32, value: Uint8Array): u32 {
+  const errno = _delete_by_col_eq(tableId, colId, value.dataStart, value.length, OUT);
+  if (errno == Errno.LOOKUP_NOT_FOUND) return 0;
+  check(errno, "delete_by_col_eq");
+  return load<u32>(OUT);
+}
+
+/** Returns every row of the table `tableId`, BSATN encoded and concatenated into chunks. */
+export function iterAll(tableId: u32): Uint8Array[] {
+  check(_iter_start(tableId, OUT), "iter_start");
+  const iter = load<u32>(OUT);
+  const chunks: Uint8Array[] = [];
+  while (true) {
+    check(_iter_next(iter, OUT), "iter_next");
+    const buf = load<u32>(OUT);
+    if (buf == INVALID_BUFFER) break;
+    chunks.push(readBuffer(buf));
+  }
+  check(_iter_drop(iter), "iter_drop");
+  return chunks;
+}
+
+export function remainingEnergy(): u64 {
+  check(_remaining_energy(OUT), "remaining_energy");
+  return load<u64>(OUT);
+}
+
+export function savepoint(): u32 {
+  check(_savepoint(OUT), "savepoint");
+  return load<u32>(OUT);
+}
+
+export function rollbackToSavepoint(id: u32): void {
+  check(_rollback_to_savepoint(id), "rollback_to_savepoint");
+}
+
+export function releaseSavepoint(id: u32): u16 {
+  return _release_savepoint(id);
+}
+
+export function consoleLog(level: u8, text: string): void {
+  const bytes = utf8(text);
+  _console_log(level, 0, 0, 0, 0, 0, bytes.dataStart, bytes.length);
+}
+
+export function scheduleReducer(name: string, args: Uint8Array, time: u64): u64 {
+  const bytes = utf8(name);
+  _schedule_reducer(bytes.dataStart, bytes.length, args.dataStart, args.length, time, OUT);
+  return load<u64>(OUT);
+}
+
+export function cancelReducer(id: u64): void {
+  _cancel_reducer(id);
+}
+
+export function emitEvent(name: string, data: Uint8Array): void {
+  const bytes = utf8(name);
+  check(_emit_event(bytes.dataStart, bytes.length, data.dataStart, data.length), "emit_event");
+}
@@ -0,0 +1,157 @@
+// The SATS types of `crates/sats`, as needed to describe the tables and reducers of a module,
+// BSATN encoded the way the `Serialize` derives of `AlgebraicType` and friends encode them.
+
+import { Writer } from "./bsatn";
+
+// The variants of `BuiltinType`, in order.
+const BOOL: u8 = 0;
+const I8: u8 = 1;
+const U8: u8 = 2;
+const I16: u8 = 3;
+const U16: u8 = 4;
+const I32: u8 = 5;
+const U32: u8 = 6;
+const I64: u8 = 7;
+const U64: u8 = 8;
+const F32: u8 = 11;
+const F64: u8 = 12;
+const STRING: u8 = 13;
+const ARRAY: u8 = 14;
+
+/** The type of a column, a reducer argument, or an event. */
+export abstract class AlgebraicType {
+  abstract encode(w: Writer): void;
+
+  static bool(): AlgebraicType {
+    return new BuiltinType(BOOL);
+  }
+  static i8(): AlgebraicType {
+    return new BuiltinType(I8);
+  }
+  static u8(): AlgebraicType {
+    return new BuiltinType(U8);
+  }
+  static i16(): AlgebraicType {
+    return new BuiltinType(I16);
+  }
+  static u16(): AlgebraicType {
+    return new BuiltinType(U16);
+  }
+  static i32(): AlgebraicType {
+    return new BuiltinType(I32);
+  }
+  static u32(): AlgebraicType {
+    return new BuiltinType(U32);
+  }
+  static i64(): AlgebraicType {
+    return new BuiltinType(I64);
+  }
+  static u64(): AlgebraicType {
+    return new BuiltinType(U64);
+  }
+  static f32(): AlgebraicType {
+    return new BuiltinType(F32);
+  }
+  static f64(): AlgebraicType {
+    return new BuiltinType(F64);
+  }
+  static string(): AlgebraicType {
+    return new BuiltinType(STRING);
+  }
+  static array(elem: AlgebraicType): AlgebraicType {
+    return new ArrayType(elem);
+  }
+  static product(elements: ProductTypeElement[]): AlgebraicType {
+    return new ProductType(elements);
+  }
+
+  /**
+   * The type of the AssemblyScript type `T`,
+   * which is a `bool`, an integer or float of at most 64 bits, a `string`, or an `Array` of those.
+   *
+   * Other types, e.g., classes, must be spelled out with `AlgebraicType.product`.
+   */
+  static of<T>(): AlgebraicType {
+    if (isBoolean<T>()) return AlgebraicType.bool();
+    if (isInteger<T>()) {
+      if (sizeof<T>() == 1) return isSigned<T>() ? AlgebraicType.i8() : AlgebraicType.u8();
+      if (sizeof<T>() == 2) return isSigned<T>() ? AlgebraicType.i16() : AlgebraicType.u16();
+      if (sizeof<T>() == 4) return isSigned<T>() ? AlgebraicType.i32() : AlgebraicType.u32();
+      return isSigned<T>() ? AlgebraicType.i64() : AlgebraicType.u64();
+    }
+    if (isFloat<T>()) return sizeof<T>() == 4 ? AlgebraicType.f32() : AlgebraicType.f64();
+    if (isString<T>()) return AlgebraicType.string();
+    if (isArray<T>()) return AlgebraicType.array(AlgebraicType.of<valueof<T>>());
+    throw new Error("AlgebraicType.of: spell out the type of classes with AlgebraicType.product");
+  }
+}
+
+// The variants of `AlgebraicType`, in order.
+const PRODUCT: u8 = 1;
+const BUILTIN: u8 = 2;
+const REF: u8 = 3;
+
+class BuiltinType extends AlgebraicType {
+  constructor(private tag: u8) {
+    super();
+  }
+  encode(w: Writer): void {
+    w.writeU8(BUILTIN);
+    w.writeU8(this.tag);
+  }
+}
+
+class ArrayType extends AlgebraicType {
+  constructor(private elem: AlgebraicType) {
+    super();
+  }
+  encode(w: Writer): void {
+    w.writeU8(BUILTIN);
+    w.writeU8(ARRAY);
+    this.elem.encode(w);
+  }
+}
+
+class ProductType extends AlgebraicType {
+  constructor(private elements: ProductTypeElement[]) {
+    super();
+  }
+  encode(w: Writer): void {
+    w.writeU8(PRODUCT);
+    encodeElements(w, this.elements);
+  }
+}
+
+/** A reference to the type at `index` in the typespace of the module. */
+export class RefType extends AlgebraicType {
+  constructor(public index: u32) {
+    super();
+  }
+  encode(w: Writer): void {
+    w.writeU8(REF);
+    w.writeU32(this.index);
+  }
+}
+
+/** A named field of a product type, e.g., a column of a table or an argument of a reducer. */
+export class ProductTypeElement {
+  constructor(public name: string, public ty: AlgebraicType) {}
+
+  encode(w: Writer): void {
+    // `Some(name)`, as the name is an `Option<String>`.
+    w.writeU8(0);
+    w.writeString(this.name);
+    this.ty.encode(w);
+  }
+}
+
+/** Encodes `elements` as a `Vec<ProductTypeElement>`. */
+export function encodeElements(w: Writer, elements: ProductTypeElement[]): void {
+  w.writeU32(elements.length);
+  for (let i = 0; i < elements.length; i++) elements[i].encode(w);
+}
+
+/** A field named `name` of the type of `T`, as with `AlgebraicType.of`. */
+export function field<T>(name: string): ProductTypeElement {
+  return new ProductTypeElement(name, AlgebraicType.of<T>());
+}
@@ -0,0 +1,298 @@
+//! Lowers `async fn` reducers into a chain of ordinary reducers.
+//!
+//! Each `.await` statement at the top level of the body splits it into a step.
+//! At an `.await`, the variables in scope are saved to a hidden table
+//! and the awaited `spacetimedb::Continuation` is told to call a hidden resume reducer,
+//! which loads them back and runs the next step.
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Expr, FnArg, Ident, ItemFn, Pat, ReturnType, Stmt, Token, Type};
+
+/// An `async fn` reducer lowered into ordinary functions.
+pub(crate) struct AsyncReducer {
+    /// The reducer called by clients, which runs the first step.
+    pub entry: ItemFn,
+    /// The name and the function of the hidden reducer running the later steps,
+    /// if there was any `.await`.
+    pub resume: Option<(String, ItemFn)>,
+    /// The hidden table holding the variables captured at an `.await`.
+    pub state_table: TokenStream,
+}
+
+/// A variable saved across an `.await`.
+#[derive(Clone)]
+struct Capture {
+    ident: Ident,
+    mutability: Option<Token![mut]>,
+    ty: Type,
+}
+
+/// The statements up to, but excluding, an `.await`.
+struct Step {
+    stmts: Vec<Stmt>,
+    /// The variables in scope when the step starts.
+    captures: Vec<Capture>,
+    /// The continuation awaited at the end of the step, unless it's the last one.
+    awaits: Option<Expr>,
+}
+
+/// Lowers the `async fn` reducer `func`.
+pub(crate) fn lower(mut func: ItemFn) -> syn::Result<AsyncReducer> {
+    func.sig.asyncness = None;
+    let name = func.sig.ident.to_string();
+
+    match &func.sig.output {
+        ReturnType::Default => {}
+        ReturnType::Type(_, ty) if is_unit_result(ty) => {}
+        ReturnType::Type(_, ty) => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "an async reducer must return `()` or `Result<(), E>`",
+            ))
+        }
+    }
+
+    // The context is rebuilt from its `sender` at each step,
+    // the other arguments are captured like any variable.
+    let mut ctx = None;
+    let mut captures = Vec::new();
+    for arg in &func.sig.inputs {
+        let FnArg::Typed(arg) = arg else {
+            return Err(syn::Error::new_spanned(arg, "reducers cannot take `self`"));
+        };
+        let Pat::Ident(pat) = &*arg.pat else {
+            return Err(syn::Error::new_spanned(
+                &arg.pat,
+                "the arguments of an async reducer must be plain names",
+            ));
+        };
+        if is_reducer_context(&arg.ty) {
+            ctx = Some(pat.ident.clone());
+        } else {
+            captures.push(Capture {
+                ident: pat.ident.clone(),
+                mutability: pat.mutability,
+                ty: (*arg.ty).clone(),
+            });
+        }
+    }
+
+    let steps = split_steps(std::mem::take(&mut func.block.stmts), captures)?;
+    if steps.len() == 1 {
+        func.block.stmts = steps.into_iter().next().unwrap().stmts;
+        return Ok(AsyncReducer {
+            entry: func,
+            resume: None,
+            state_table: TokenStream::new(),
+        });
+    }
+
+    let table = format_ident!("__{}_suspended", name);
+    let resume_ident = format_ident!("__{}_resume", name);
+    let resume_name = resume_ident.to_string();
+    let ok = match &func.sig.output {
+        ReturnType::Default => quote!(),
+        ReturnType::Type(..) => quote!(::core::result::Result::Ok(())),
+    };
+    let sender = ctx.as_ref().map(|ctx| quote!(#ctx.sender,));
+
+    // Runs the step `k`, then suspends at its `.await`, if any.
+    let step_body = |k: usize| {
+        let Step { stmts, awaits, .. } = &steps[k];
+        let suspend = awaits.as_ref().map(|continuation| {
+            let next = (k + 1) as u32;
+            let saved = steps[k + 1].captures.iter().map(|c| &c.ident);
+            quote! {
+                let __continuation = #continuation;
+                spacetimedb::rt::suspend::<#table, _>(__continuation, #next, (#sender #(#saved,)*));
+                #ok
+            }
+        });
+        quote!(#(#stmts)* #suspend)
+    };
+
+    let entry_body = step_body(0);
+    func.block = syn::parse_quote!({ #entry_body });
+
+    let arms = (1..steps.len()).map(|k| {
+        let step = k as u32;
+        let idents = steps[k].captures.iter().map(|c| {
+            let (mutability, ident) = (&c.mutability, &c.ident);
+            quote!(#mutability #ident)
+        });
+        let tys = steps[k].captures.iter().map(|c| &c.ty);
+        let (sender_pat, sender_ty, rebuild_ctx) = match &ctx {
+            Some(ctx) => (
+                Some(quote!(__sender,)),
+                Some(quote!(spacetimedb::Identity,)),
+                Some(quote!(let #ctx = spacetimedb::rt::resumed_context(__ctx, __sender);)),
+            ),
+            None => (None, None, None),
+        };
+        let body = step_body(k);
+        quote! {
+            #step => {
+                let (#sender_pat #(#idents,)*) =
+                    spacetimedb::rt::decode_state::<(#sender_ty #(#tys,)*)>(&__state);
+                #rebuild_ctx
+                #body
+            }
+        }
+    });
+
+    let vis = &func.vis;
+    let output = &func.sig.output;
+    let resume: ItemFn = syn::parse_quote! {
+        #[allow(unused_variables, unused_mut)]
+        #vis fn #resume_ident(__ctx: spacetimedb::ReducerContext, __id: u64) #output {
+            let (__step, __state) = spacetimedb::rt::resume::<#table>(__id);
+            match __step {
+                #(#arms)*
+                __step => panic!("no step {} in the async reducer {}", __step, #name),
+            }
+        }
+    };
+
+    let state_table = quote! {
+        #[derive(spacetimedb::TableType)]
+        #[allow(non_camel_case_types)]
+        struct #table {
+            #[primarykey]
+            #[autoinc]
+            id: u64,
+            step: u32,
+            state: Vec<u8>,
+        }
+
+        impl spacetimedb::rt::SuspendedState for #table {
+            const RESUME_REDUCER: &'static str = #resume_name;
+
+            fn new(step: u32, state: Vec<u8>) -> Self {
+                Self { id: 0, step, state }
+            }
+
+            fn id(&self) -> u64 {
+                self.id
+            }
+
+            fn into_step(self) -> (u32, Vec<u8>) {
+                (self.step, self.state)
+            }
+        }
+    };
+
+    Ok(AsyncReducer {
+        entry: func,
+        resume: Some((resume_name, resume)),
+        state_table,
+    })
+}
+
+/// Splits `stmts` into steps at each `.await` statement,
+/// tracking the variables in scope, starting with `captures`.
+fn split_steps(stmts: Vec<Stmt>, mut captures: Vec<Capture>) -> syn::Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut current = Vec::new();
+    let mut step_captures = captures.clone();
+    // A `let` we cannot save, which is an error if an `.await` follows it.
+    let mut unsaveable: Option<Span> = None;
+
+    for stmt in stmts {
+        if let Stmt::Expr(Expr::Await(awaited), _) = &stmt {
+            let base = &awaited.base;
+            if let Some(span) = find_await(quote!(#base)) {
+                return Err(nested_await(span));
+            }
+            if let Some(span) = unsaveable {
+                return Err(syn::Error::new(
+                    span,
+                    "a variable kept across an `.await` in an async reducer needs a name and a type, \
+                     e.g. `let x: u32 = ...;`",
+                ));
+            }
+            steps.push(Step {
+                stmts: std::mem::take(&mut current),
+                captures: std::mem::replace(&mut step_captures, captures.clone()),
+                awaits: Some((*awaited.base).clone()),
+            });
+            continue;
+        }
+
+        if let Some(span) = find_await(quote!(#stmt)) {
+            return Err(nested_await(span));
+        }
+        if let Stmt::Local(local) = &stmt {
+            match &local.pat {
+                Pat::Wild(_) => {}
+                Pat::Type(pat) => match &*pat.pat {
+                    Pat::Wild(_) => {}
+                    Pat::Ident(ident) if ident.by_ref.is_none() && ident.subpat.is_none() => {
+                        captures.retain(|c| c.ident != ident.ident);
+                        captures.push(Capture {
+                            ident: ident.ident.clone(),
+                            mutability: ident.mutability,
+                            ty: (*pat.ty).clone(),
+                        });
+                    }
+                    _ => unsaveable = unsaveable.or(Some(local.pat.span())),
+                },
+                _ => unsaveable = unsaveable.or(Some(local.pat.span())),
+            }
+        }
+        current.push(stmt);
+    }
+
+    steps.push(Step {
+        stmts: current,
+        captures: step_captures,
+        awaits: None,
+    });
+    Ok(steps)
+}
+
+fn nested_await(span: Span) -> syn::Error {
+    syn::Error::new(
+        span,
+        "`.await` is only supported as a statement at the top level of an async reducer, \
+         e.g. `spacetimedb::sleep(duration).await;`",
+    )
+}
+
+/// Returns the span of the first `.await` in `tokens`, if any.
+fn find_await(tokens: TokenStream) -> Option<Span> {
+    let mut after_dot = false;
+    for tt in tokens {
+        match tt {
+            TokenTree::Group(group) => {
+                if let Some(span) = find_await(group.stream()) {
+                    return Some(span);
+                }
+                after_dot = false;
+            }
+            TokenTree::Punct(punct) => after_dot = punct.as_char() == '.',
+            TokenTree::Ident(ident) if after_dot && ident == "await" => return Some(ident.span()),
+            _ => after_dot = false,
+        }
+    }
+    None
+}
+
+/// Returns whether `ty` names `ReducerContext`.
+fn is_reducer_context(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().map_or(false, |seg| seg.ident == "ReducerContext"))
+}
+
+/// Returns whether `ty` looks like `Result<(), E>`.
+fn is_unit_result(ty: &Type) -> bool {
+    let Type::Path(path) = ty else { return false };
+    let Some(last) = path.path.segments.last() else {
+        return false;
+    };
+    let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+        return false;
+    };
+    last.ident == "Result"
+        && matches!(args.args.first(), Some(syn::GenericArgument::Type(Type::Tuple(t))) if t.elems.is_empty())
+}
@@ -6,6 +6,7 @@
 #[macro_use]
 mod macros;
 
+mod async_reducer;
 mod module;
 
 extern crate core;
@@ -17,6 +18,7 @@ use std::time::Duration;
 use module::{derive_deserialize, derive_satstype, derive_serialize};
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned, TokenStreamExt};
+use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
 use syn::{
@@ -32,21 +34,33 @@ mod sym {
     /// Matches `autoinc`.
     pub const AUTOINC: Symbol = Symbol("autoinc");
 
+    /// Matches `compress`.
+    pub const COMPRESS: Symbol = Symbol("compress");
+
     /// Matches `crate`.
     pub const CRATE: Symbol = Symbol("crate");
 
+    /// Matches `deferred`.
+    pub const DEFERRED: Symbol = Symbol("deferred");
+
     /// Matches `name`.
     pub const NAME: Symbol = Symbol("name");
 
     /// Matches `primarykey`.
     pub const PRIMARYKEY: Symbol = Symbol("primarykey");
 
+    /// Matches `row_version`.
+    pub const ROW_VERSION: Symbol = Symbol("row_version");
+
     /// Matches `sats`.
     pub const SATS: Symbol = Symbol("sats");
 
     /// Matches `unique`.
     pub const UNIQUE: Symbol = Symbol("unique");
 
+    /// Matches `zstd`.
+    pub const ZSTD: Symbol = Symbol("zstd");
+
     impl PartialEq<Symbol> for syn::Ident {
         fn eq(&self, sym: &Symbol) -> bool {
             self == sym.0
@@ -74,11 +88,27 @@ mod sym {
 /// The macro takes this `input`, which defines what the attribute does,
 /// and it is structured roughly like so:
 /// ```ignore
-/// input = table | init | connect | disconnect | migrate
-///       | reducer [, repeat = Duration]
-///       | index(btree | hash [, name = string] [, field_name:ident]*)
+/// input = init | connect | disconnect | migrate | event | before_reducer | after_reducer | identity_merged
+///       | table [, append_only | read_mostly] [, soft_delete] [, ttl = Duration, ttl_column = string]
+///       | reducer [, repeat = Duration] [, read_only [, http = string]] [, cooldown = Duration] [, priority = string]
+///         [, allow = string]*
+///       | index(btree | hash | fulltext | spatial [, name = string] [, field_name:ident]*)
+///       | http = string
 /// ```
 ///
+/// An `http` route, e.g. `"GET /leaderboard/:limit"`, is served by the host under the database's address.
+/// On a `read_only` reducer, the parameters of the path and query are its arguments,
+/// and its return value the body of the response.
+/// On its own, it goes on a `const` of SQL text, whose rows are the body of the response.
+///
+/// A `reducer` may be an `async fn` which `.await`s continuations, like `spacetimedb::sleep`,
+/// as statements at the top level of its body.
+/// Each `.await` ends the transaction, saving the named and typed variables in scope
+/// to a hidden table, and the rest of the body runs as a new reducer once the continuation completes.
+///
+/// An `identity_merged` reducer takes the `Identity` that has been linked to another one, and that other one,
+/// and is called by the host, in a single transaction, for the module to move the rows of the former to the latter.
+///
 /// For description of the field attributes on `#[spacetimedb(table)]` structs,
 /// see [`TableType`](spacetimedb_tabletype).
 #[proc_macro_attribute]
@@ -99,14 +129,31 @@ pub fn spacetimedb(macro_args: proc_macro::TokenStream, item: proc_macro::TokenS
 /// On `item`, route the macro `input` to the various interpretations.
 fn route_input(input: MacroInput, item: TokenStream) -> syn::Result<TokenStream> {
     match input {
-        MacroInput::Table => spacetimedb_table(item),
+        MacroInput::Table {
+            access_hint,
+            soft_delete,
+            ttl,
+        } => spacetimedb_table(access_hint, soft_delete, ttl, item),
         MacroInput::Init => spacetimedb_init(item),
-        MacroInput::Reducer { repeat } => spacetimedb_reducer(repeat, item),
+        MacroInput::Reducer {
+            repeat,
+            read_only,
+            cooldown,
+            priority,
+            allow,
+            http,
+        } => spacetimedb_reducer(repeat, read_only, cooldown, priority, allow, http, item),
         MacroInput::Connect => spacetimedb_connect_disconnect(item, true),
         MacroInput::Disconnect => spacetimedb_connect_disconnect(item, false),
+        MacroInput::BeforeReducer => spacetimedb_reducer_hook(item, true),
+        MacroInput::AfterReducer => spacetimedb_reducer_hook(item, false),
         MacroInput::Migrate => spacetimedb_migrate(item),
         MacroInput::Index { ty, name, field_names } => spacetimedb_index(ty, name, field_names, item),
+        MacroInput::SoftDelete => spacetimedb_soft_delete(item),
         MacroInput::Update => spacetimedb_update(item),
+        MacroInput::Event => spacetimedb_event(item),
+        MacroInput::IdentityMerged => spacetimedb_identity_merged(item),
+        MacroInput::Http(route) => spacetimedb_http(route, item),
     }
 }
 
@@ -121,20 +168,50 @@ fn duration_totokens(dur: Duration) -> TokenStream {
 
 /// Defines the input space of the `spacetimedb` macro.
 enum MacroInput {
-    Table,
+    Table {
+        access_hint: Option<AccessHint>,
+        /// Whether deleting a row only marks it as deleted.
+        soft_delete: bool,
+        /// How long rows are kept, and the name of the column of the timestamp they expire from.
+        ttl: Option<(Duration, syn::LitStr)>,
+    },
     Init,
     Reducer {
         repeat: Option<Duration>,
+        read_only: bool,
+        /// How long a caller has to wait between calls, if at all.
+        cooldown: Option<Duration>,
+        /// The variant of `Lane` the calls wait in, if not the normal one.
+        priority: Option<Ident>,
+        /// The roles allowed to call the reducer, or none if anyone can.
+        allow: Vec<String>,
+        /// The HTTP route the reducer serves, if any.
+        http: Option<HttpRouteAttr>,
     },
     Connect,
     Disconnect,
+    BeforeReducer,
+    AfterReducer,
     Migrate,
     Index {
         ty: IndexType,
         name: Option<String>,
         field_names: Vec<Ident>,
     },
+    /// Left by `table, soft_delete` on the struct for the `TableType` derive.
+    SoftDelete,
     Update,
+    Event,
+    IdentityMerged,
+    /// On a `const` of SQL text, served at the route.
+    Http(HttpRouteAttr),
+}
+
+/// The method and path of an HTTP route, as in `http = "GET /leaderboard/:limit"`.
+struct HttpRouteAttr {
+    /// The variant of `HttpMethod`.
+    method: Ident,
+    path: String,
 }
 
 /// Parse `f()` delimited by `,` until `input` is empty.
@@ -173,12 +250,65 @@ fn check_duplicate_meta<T>(x: &Option<T>, meta: &syn::meta::ParseNestedMeta<'_>)
 impl syn::parse::Parse for MacroInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         Ok(match_tok!(match input {
-            kw::table => Self::Table,
+            kw::table => {
+                // Eat an optional comma, and then if anything follows,
+                // it has to be `append_only`, `read_mostly`, `soft_delete`,
+                // or `ttl = Duration` along with `ttl_column = "column"`.
+                let mut access_hint = None;
+                let mut soft_delete = None;
+                let mut ttl = None;
+                let mut ttl_column = None;
+                comma_then_comma_delimited(input, || {
+                    match_tok!(match input {
+                        tok @ kw::append_only => {
+                            check_duplicate(&access_hint, tok.span)?;
+                            access_hint = Some(AccessHint::AppendOnly);
+                        }
+                        tok @ kw::read_mostly => {
+                            check_duplicate(&access_hint, tok.span)?;
+                            access_hint = Some(AccessHint::ReadMostly);
+                        }
+                        tok @ kw::soft_delete => {
+                            check_duplicate(&soft_delete, tok.span)?;
+                            soft_delete = Some(());
+                        }
+                        tok @ kw::ttl => {
+                            check_duplicate(&ttl, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            ttl = Some((input.call(parse_duration)?, tok.span));
+                        }
+                        tok @ kw::ttl_column => {
+                            check_duplicate(&ttl_column, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            ttl_column = Some(input.parse::<syn::LitStr>()?);
+                        }
+                    });
+                    Ok(())
+                })?;
+                let ttl = match (ttl, ttl_column) {
+                    (Some((ttl, _)), Some(column)) => Some((ttl, column)),
+                    (None, None) => None,
+                    (Some((_, span)), None) => return Err(syn::Error::new(span, "`ttl` requires `ttl_column`")),
+                    (None, Some(column)) => return Err(syn::Error::new(column.span(), "`ttl_column` requires `ttl`")),
+                };
+                Self::Table {
+                    access_hint,
+                    soft_delete: soft_delete.is_some(),
+                    ttl,
+                }
+            }
             kw::init => Self::Init,
             kw::reducer => {
                 // Eat an optional comma, and then if anything follows,
-                // it has to be `repeat = Duration`.
+                // it has to be `repeat = Duration`, `read_only`, `cooldown = Duration`,
+                // `priority = "high" | "normal" | "low"`, `http = "METHOD /path"`,
+                // or `allow = "role"`, which can be repeated.
                 let mut repeat = None;
+                let mut read_only = None;
+                let mut cooldown = None;
+                let mut priority = None;
+                let mut allow = Vec::new();
+                let mut http = None;
                 comma_then_comma_delimited(input, || {
                     match_tok!(match input {
                         tok @ kw::repeat => {
@@ -186,13 +316,49 @@ impl syn::parse::Parse for MacroInput {
                             input.parse::<Token![=]>()?;
                             repeat = Some(input.call(parse_duration)?);
                         }
+                        tok @ kw::read_only => {
+                            check_duplicate(&read_only, tok.span)?;
+                            read_only = Some(());
+                        }
+                        tok @ kw::cooldown => {
+                            check_duplicate(&cooldown, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            cooldown = Some(input.call(parse_duration)?);
+                        }
+                        tok @ kw::priority => {
+                            check_duplicate(&priority, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            priority = Some(input.call(parse_lane)?);
+                        }
+                        kw::allow => {
+                            input.parse::<Token![=]>()?;
+                            allow.push(input.parse::<syn::LitStr>()?.value());
+                        }
+                        tok @ kw::http => {
+                            check_duplicate(&http, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            http = Some((input.call(parse_http_route)?, tok.span));
+                        }
                     });
                     Ok(())
                 })?;
-                Self::Reducer { repeat }
+                // Requests to the routes of a module don't get to write to its database.
+                if let (Some((_, span)), None) = (&http, &read_only) {
+                    return Err(syn::Error::new(*span, "`http` requires `read_only`"));
+                }
+                Self::Reducer {
+                    repeat,
+                    read_only: read_only.is_some(),
+                    cooldown,
+                    priority,
+                    allow,
+                    http: http.map(|(route, _)| route),
+                }
             }
             kw::connect => Self::Connect,
             kw::disconnect => Self::Disconnect,
+            kw::before_reducer => Self::BeforeReducer,
+            kw::after_reducer => Self::AfterReducer,
             kw::migrate => Self::Migrate,
             kw::index => {
                 // Extract stuff in parens.
@@ -200,7 +366,7 @@ impl syn::parse::Parse for MacroInput {
                 syn::parenthesized!(in_parens in input);
                 let in_parens = &in_parens;
 
-                // Parse `btree` or `hash`.
+                // Parse `btree`, `hash`, `fulltext` or `spatial`.
                 let ty: IndexType = in_parens.parse()?;
 
                 // Find `name = $string_literal`.
@@ -220,7 +386,14 @@ impl syn::parse::Parse for MacroInput {
                 })?;
                 Self::Index { ty, name, field_names }
             }
+            kw::soft_delete => Self::SoftDelete,
             kw::update => Self::Update,
+            kw::event => Self::Event,
+            kw::identity_merged => Self::IdentityMerged,
+            kw::http => {
+                input.parse::<Token![=]>()?;
+                Self::Http(input.call(parse_http_route)?)
+            }
         }))
     }
 }
@@ -229,6 +402,8 @@ impl syn::parse::Parse for MacroInput {
 enum IndexType {
     BTree,
     Hash,
+    FullText,
+    Spatial,
 }
 
 impl syn::parse::Parse for IndexType {
@@ -236,6 +411,8 @@ impl syn::parse::Parse for IndexType {
         Ok(match_tok!(match input {
             kw::btree => Self::BTree,
             kw::hash => Self::Hash,
+            kw::fulltext => Self::FullText,
+            kw::spatial => Self::Spatial,
         }))
     }
 }
@@ -246,26 +423,62 @@ impl quote::ToTokens for IndexType {
     }
 }
 
+#[derive(Debug)]
+enum AccessHint {
+    AppendOnly,
+    ReadMostly,
+}
+
+impl quote::ToTokens for AccessHint {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append(Ident::new(&format!("{self:?}"), Span::call_site()))
+    }
+}
+
 mod kw {
     syn::custom_keyword!(table);
+    syn::custom_keyword!(append_only);
+    syn::custom_keyword!(read_mostly);
+    syn::custom_keyword!(soft_delete);
+    syn::custom_keyword!(ttl);
+    syn::custom_keyword!(ttl_column);
     syn::custom_keyword!(init);
     syn::custom_keyword!(reducer);
     syn::custom_keyword!(connect);
     syn::custom_keyword!(disconnect);
+    syn::custom_keyword!(before_reducer);
+    syn::custom_keyword!(after_reducer);
     syn::custom_keyword!(migrate);
     syn::custom_keyword!(index);
     syn::custom_keyword!(btree);
     syn::custom_keyword!(hash);
+    syn::custom_keyword!(fulltext);
+    syn::custom_keyword!(spatial);
     syn::custom_keyword!(name);
     syn::custom_keyword!(repeat);
+    syn::custom_keyword!(read_only);
+    syn::custom_keyword!(allow);
+    syn::custom_keyword!(cooldown);
+    syn::custom_keyword!(priority);
     syn::custom_keyword!(update);
+    syn::custom_keyword!(event);
+    syn::custom_keyword!(identity_merged);
+    syn::custom_keyword!(http);
 }
 
 /// Generates a reducer in place of `item`.
-fn spacetimedb_reducer(repeat: Option<Duration>, item: TokenStream) -> syn::Result<TokenStream> {
+fn spacetimedb_reducer(
+    repeat: Option<Duration>,
+    read_only: bool,
+    cooldown: Option<Duration>,
+    priority: Option<Ident>,
+    allow: Vec<String>,
+    http: Option<HttpRouteAttr>,
+    item: TokenStream,
+) -> syn::Result<TokenStream> {
     // TODO(kim): Find a better place for these. `core/host/wasm_common.rs` has similar
     // definitions, but we can't depend on `core` here.
-    const RESERVED_REDUCER_NAMES: &[&str] = &["__init__", "__migrate__", "__update__"];
+    const RESERVED_REDUCER_NAMES: &[&str] = &["__init__", "__migrate__", "__update__", "__identity_merged__"];
 
     let repeat_dur = repeat.map_or(ReducerExtra::None, ReducerExtra::Repeat);
     let original_function = syn::parse2::<ItemFn>(item)?;
@@ -279,14 +492,109 @@ fn spacetimedb_reducer(repeat: Option<Duration>, item: TokenStream) -> syn::Resu
         ));
     }
 
-    gen_reducer(original_function, &reducer_name, repeat_dur)
+    if let Some(asyncness) = original_function.sig.asyncness {
+        if read_only {
+            return Err(syn::Error::new_spanned(
+                asyncness,
+                "an async reducer cannot be read_only, as it saves its state at each `.await`",
+            ));
+        }
+
+        let lowered = async_reducer::lower(original_function)?;
+        let entry = gen_reducer(
+            lowered.entry,
+            &reducer_name,
+            repeat_dur,
+            false,
+            cooldown,
+            priority.as_ref(),
+            &allow,
+        )?;
+        // Only the module itself, through the awaited continuation, may resume the reducer,
+        // which it does in the same lane as it was called in.
+        let owner = ["owner".to_owned()];
+        let resume = lowered
+            .resume
+            .map(|(name, func)| gen_reducer(func, &name, ReducerExtra::None, false, None, priority.as_ref(), &owner))
+            .transpose()?;
+        let state_table = lowered.state_table;
+        return Ok(quote! {
+            #entry
+            #resume
+            #state_table
+        });
+    }
+
+    let reducer = gen_reducer(
+        original_function,
+        &reducer_name,
+        repeat_dur,
+        read_only,
+        cooldown,
+        priority.as_ref(),
+        &allow,
+    )?;
+    let http = http.map(|route| {
+        gen_http_route(
+            &route,
+            &reducer_name,
+            quote!(spacetimedb::rt::HttpTarget::Reducer(#reducer_name)),
+        )
+    });
+    Ok(quote! {
+        #reducer
+        #http
+    })
+}
+
+/// Registers the `const` of SQL text `item` to be served at `route`.
+fn spacetimedb_http(route: HttpRouteAttr, item: TokenStream) -> syn::Result<TokenStream> {
+    let original_const = syn::parse2::<syn::ItemConst>(item)?;
+    let ident = &original_const.ident;
+    let http = gen_http_route(
+        &route,
+        &ident.to_string(),
+        quote!(spacetimedb::rt::HttpTarget::Sql(#ident)),
+    );
+    Ok(quote! {
+        #original_const
+        #http
+    })
+}
+
+/// Generates the registration of the HTTP `route`, served by `target`, an `HttpTarget`.
+fn gen_http_route(route: &HttpRouteAttr, name: &str, target: TokenStream) -> TokenStream {
+    let HttpRouteAttr { method, path } = route;
+    let register_http_route_symbol = format!("__preinit__20_register_http_route_{name}");
+    quote! {
+        const _: () = {
+            struct __HttpRoute;
+            impl spacetimedb::rt::HttpRouteInfo for __HttpRoute {
+                const METHOD: spacetimedb::spacetimedb_lib::HttpMethod = spacetimedb::spacetimedb_lib::HttpMethod::#method;
+                const PATH: &'static str = #path;
+                const TARGET: spacetimedb::rt::HttpTarget = #target;
+            }
+            #[export_name = #register_http_route_symbol]
+            extern "C" fn __register_http_route() {
+                spacetimedb::rt::register_http_route::<__HttpRoute>()
+            }
+        };
+    }
 }
 
 /// Generates the special `__init__` "reducer" in place of `item`.
 fn spacetimedb_init(item: TokenStream) -> syn::Result<TokenStream> {
     let original_function = syn::parse2::<ItemFn>(item)?;
 
-    gen_reducer(original_function, "__init__", ReducerExtra::Init)
+    gen_reducer(
+        original_function,
+        "__init__",
+        ReducerExtra::Init,
+        false,
+        None,
+        None,
+        &[],
+    )
 }
 
 enum ReducerExtra {
@@ -295,7 +603,15 @@ enum ReducerExtra {
     Init,
 }
 
-fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtra) -> syn::Result<TokenStream> {
+fn gen_reducer(
+    original_function: ItemFn,
+    reducer_name: &str,
+    extra: ReducerExtra,
+    read_only: bool,
+    cooldown: Option<Duration>,
+    priority: Option<&Ident>,
+    allow: &[String],
+) -> syn::Result<TokenStream> {
     let func_name = &original_function.sig.ident;
     let vis = &original_function.vis;
 
@@ -348,6 +664,18 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
 
     let register_describer_symbol = format!("__preinit__20_register_describer_{reducer_name}");
 
+    let cooldown = match cooldown {
+        Some(cooldown) => {
+            let cooldown = duration_totokens(cooldown);
+            quote!(Some(#cooldown))
+        }
+        None => quote!(None),
+    };
+
+    let priority = priority.map(
+        |lane| quote!(const PRIORITY: spacetimedb::spacetimedb_lib::Lane = spacetimedb::spacetimedb_lib::Lane::#lane;),
+    );
+
     let (epilogue, repeater_impl) = match &extra {
         ReducerExtra::None | ReducerExtra::Init => (quote!(), quote!()),
         ReducerExtra::Repeat(repeat_dur) => {
@@ -370,14 +698,14 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
         fn __reducer(__sender: spacetimedb::sys::Buffer, __timestamp: u64, __args: &[u8]) -> spacetimedb::sys::Buffer {
             #(spacetimedb::rt::assert_reducerarg::<#arg_tys>();)*
             #(spacetimedb::rt::assert_reducerret::<#ret_ty>();)*
-            spacetimedb::rt::invoke_reducer(#func_name, __sender, __timestamp, __args, |_res| { #epilogue })
+            spacetimedb::rt::invoke_reducer(#reducer_name, #func_name, __sender, __timestamp, __args, |_res| { #epilogue })
         }
     };
 
     let generated_describe_function = quote! {
         #[export_name = #register_describer_symbol]
         pub extern "C" fn __register_describer() {
-            spacetimedb::rt::register_reducer::<_, _, #func_name>(#func_name)
+            spacetimedb::rt::register_reducer::<_, _, #func_name, _>(#func_name)
         }
     };
 
@@ -429,6 +757,10 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
                 #generated_function
                 __reducer
             };
+            const READ_ONLY: bool = #read_only;
+            const ALLOW: &'static [&'static str] = &[#(#allow),*];
+            const COOLDOWN: Option<::core::time::Duration> = #cooldown;
+            #priority
         }
         #repeater_impl
         #original_function
@@ -460,10 +792,104 @@ enum ColumnIndexAttribute {
     PrimaryKeyAuto = 6,
 }
 
-fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
+fn spacetimedb_table(
+    access_hint: Option<AccessHint>,
+    soft_delete: bool,
+    ttl: Option<(Duration, syn::LitStr)>,
+    item: TokenStream,
+) -> syn::Result<TokenStream> {
+    // Left for `spacetimedb_tabletype_impl` to find among the attributes of the struct.
+    let soft_delete = soft_delete.then(|| quote!(#[spacetimedb(soft_delete)]));
+
+    if access_hint.is_none() && ttl.is_none() {
+        return Ok(quote! {
+            #[derive(spacetimedb::TableType)]
+            #soft_delete
+            #item
+        });
+    }
+
+    let original_struct = syn::parse2::<ItemStruct>(item)?;
+    let original_struct_ident = &original_struct.ident;
+
+    let access_hint = access_hint.map(|access_hint| {
+        let register_access_hint_symbol = format!("__preinit__20_register_access_hint_{original_struct_ident}");
+        quote! {
+            impl spacetimedb::rt::HasAccessHint for #original_struct_ident {
+                const ACCESS_HINT: spacetimedb::spacetimedb_lib::AccessHint =
+                    spacetimedb::spacetimedb_lib::AccessHint::#access_hint;
+            }
+
+            const _: () = {
+                #[export_name = #register_access_hint_symbol]
+                extern "C" fn __register_access_hint() {
+                    spacetimedb::rt::register_access_hint::<#original_struct_ident>()
+                }
+            };
+        }
+    });
+
+    let ttl = ttl
+        .map(|(ttl, column)| {
+            let has_column = original_struct
+                .fields
+                .iter()
+                .any(|field| field.ident.as_ref().map_or(false, |ident| *ident == column.value()));
+            if !has_column {
+                return Err(syn::Error::new(column.span(), "no such field in the table"));
+            }
+            let ttl = duration_totokens(ttl);
+            let register_ttl_symbol = format!("__preinit__20_register_ttl_{original_struct_ident}");
+            Ok(quote! {
+                impl spacetimedb::rt::HasTtl for #original_struct_ident {
+                    const TTL: ::core::time::Duration = #ttl;
+                    const TTL_COLUMN: &'static str = #column;
+                }
+
+                const _: () = {
+                    #[export_name = #register_ttl_symbol]
+                    extern "C" fn __register_ttl() {
+                        spacetimedb::rt::register_ttl::<#original_struct_ident>()
+                    }
+                };
+            })
+        })
+        .transpose()?;
+
     Ok(quote! {
         #[derive(spacetimedb::TableType)]
+        #soft_delete
+        #original_struct
+
+        #access_hint
+        #ttl
+    })
+}
+
+/// Generates code for treating this type as an event,
+/// a message the module emits to its subscribed clients, rather than a table.
+fn spacetimedb_event(item: TokenStream) -> syn::Result<TokenStream> {
+    let input = syn::parse2::<syn::DeriveInput>(item.clone())?;
+    let sats_ty = module::sats_type_from_derive(&input, quote!(spacetimedb::spacetimedb_lib))?;
+
+    let ident = sats_ty.ident;
+    let event_name = &sats_ty.name;
+    let register_event_symbol = format!("__preinit__20_register_event_{event_name}");
+
+    Ok(quote! {
+        #[derive(spacetimedb::SpacetimeType)]
         #item
+
+        impl spacetimedb::EventType for #ident {
+            const EVENT_NAME: &'static str = #event_name;
+        }
+
+        const _: () = {
+            #[export_name = #register_event_symbol]
+            extern "C" fn __register_event() {
+                spacetimedb::rt::register_event::<#ident>()
+            }
+        };
     })
 }
 
@@ -472,6 +898,11 @@ fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
 /// Among other things, this derives `Serialize`, `Deserialize`,
 /// `SpacetimeType`, and `TableType` for our type.
 ///
+/// It also generates a module `{$struct_name}_cols`, in snake case,
+/// with a type for each field in camel case, e.g., `player_cols::Name` for the field `name` of `Player`.
+/// `Player::iter_cols::<(player_cols::Id, player_cols::Name)>()` then iterates over those two columns alone,
+/// which the host sends without the rest of each row.
+///
 /// A table type must be a `struct`, whose fields may be annotated with the following attributes:
 ///
 /// * `#[autoinc]`
@@ -489,10 +920,35 @@ fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
 ///
 ///    Creates an index and unique constraint for the annotated field.
 ///
+///    With `#[unique(deferred)]`, the constraint is only checked when the transaction commits,
+///    so a reducer may, e.g., swap the values of two rows, one update at a time.
+///    The transaction is rolled back if rows still share a value by then.
+///
 /// * `#[primarykey]`
 ///
-///    Similar to `#[unique]`, but generates additional CRUD methods.
-#[proc_macro_derive(TableType, attributes(sats, unique, autoinc, primarykey))]
+///    Similar to `#[unique]`, but also implements `spacetimedb::PrimaryKeyTable` for the table,
+///    generating `get` and `delete` methods that look rows up by the annotated field.
+///
+/// * `#[row_version]`
+///
+///    Marks a `u64` field as the version of the row, for optimistic concurrency.
+///
+///    The host sets it to one more than the version of the row a transaction replaces,
+///    by any means, e.g., `update_by_{$field_name}` or an SQL `UPDATE`, and to 0 on a new row,
+///    whatever the inserted value.
+///    `update_if_version(value, expected_version)` only updates the row with the same primary key
+///    while its version is still `expected_version`.
+///    The table must have a `#[primarykey]`.
+///
+/// * `#[compress]`
+///
+///    Has the host compress the values of a `String` field in memory, with a dictionary of the distinct values,
+///    which suits fields with few of them, e.g., the names of items.
+///
+///    With `#[compress(zstd)]`, each value is compressed on its own with zstd instead,
+///    which suits fields of long, repetitive text.
+///    Either way, reducers and queries see the values as they were inserted.
+#[proc_macro_derive(TableType, attributes(sats, unique, autoinc, primarykey, row_version, compress))]
 pub fn spacetimedb_tabletype(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let item = syn::parse_macro_input!(item as syn::DeriveInput);
     spacetimedb_tabletype_impl(item)
@@ -501,9 +957,13 @@ pub fn spacetimedb_tabletype(item: proc_macro::TokenStream) -> proc_macro::Token
 }
 
 enum ColumnAttr {
-    Unique(Span),
+    /// `#[unique]`, or `#[unique(deferred)]` if the flag is set.
+    Unique(Span, bool),
     Autoinc(Span),
     Primarykey(Span),
+    RowVersion(Span),
+    /// `#[compress]`, or `#[compress(zstd)]` if the flag is set.
+    Compress(Span, bool),
 }
 
 impl ColumnAttr {
@@ -512,14 +972,38 @@ impl ColumnAttr {
             return Ok(None);
         };
         Ok(if ident == sym::UNIQUE {
-            attr.meta.require_path_only()?;
-            Some(ColumnAttr::Unique(ident.span()))
+            let deferred = match &attr.meta {
+                syn::Meta::Path(_) => false,
+                _ => {
+                    let arg = attr.parse_args::<Ident>()?;
+                    if arg != sym::DEFERRED {
+                        return Err(syn::Error::new(arg.span(), "expected `deferred`"));
+                    }
+                    true
+                }
+            };
+            Some(ColumnAttr::Unique(ident.span(), deferred))
         } else if ident == sym::AUTOINC {
             attr.meta.require_path_only()?;
             Some(ColumnAttr::Autoinc(ident.span()))
         } else if ident == sym::PRIMARYKEY {
             attr.meta.require_path_only()?;
             Some(ColumnAttr::Primarykey(ident.span()))
+        } else if ident == sym::ROW_VERSION {
+            attr.meta.require_path_only()?;
+            Some(ColumnAttr::RowVersion(ident.span()))
+        } else if ident == sym::COMPRESS {
+            let zstd = match &attr.meta {
+                syn::Meta::Path(_) => false,
+                _ => {
+                    let arg = attr.parse_args::<Ident>()?;
+                    if arg != sym::ZSTD {
+                        return Err(syn::Error::new(arg.span(), "expected `zstd`"));
+                    }
+                    true
+                }
+            };
+            Some(ColumnAttr::Compress(ident.span(), zstd))
         } else {
             None
         })
@@ -536,13 +1020,14 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
     };
 
     let mut columns = Vec::<Column>::new();
+    let mut row_version = None;
+    let mut deferred_unique = Vec::new();
+    let mut compressed_columns = Vec::new();
 
     let get_table_id_func = quote! {
         fn table_id() -> u32 {
             static TABLE_ID: spacetimedb::rt::OnceCell<u32> = spacetimedb::rt::OnceCell::new();
-            *TABLE_ID.get_or_init(|| {
-                spacetimedb::get_table_id(<Self as spacetimedb::TableType>::TABLE_NAME)
-            })
+            *TABLE_ID.get_or_init(spacetimedb::rt::table_id::<Self>)
         }
     };
 
@@ -557,12 +1042,17 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             let Some(attr) = ColumnAttr::parse(attr)? else { continue };
             let duplicate = |span| syn::Error::new(span, "duplicate attribute");
             match attr {
-                ColumnAttr::Unique(span) => match col_attr {
-                    UnSet => col_attr = Unique,
-                    Identity | Unique | PrimaryKey | PrimaryKeyAuto => return Err(duplicate(span)),
-                    Indexed => unreachable!(),
-                    AutoInc => col_attr = Identity,
-                },
+                ColumnAttr::Unique(span, deferred) => {
+                    match col_attr {
+                        UnSet => col_attr = Unique,
+                        Identity | Unique | PrimaryKey | PrimaryKeyAuto => return Err(duplicate(span)),
+                        Indexed => unreachable!(),
+                        AutoInc => col_attr = Identity,
+                    }
+                    if deferred {
+                        deferred_unique.push(col_num);
+                    }
+                }
                 ColumnAttr::Autoinc(span) => match col_attr {
                     UnSet => col_attr = AutoInc,
                     Identity | AutoInc | PrimaryKeyAuto => return Err(duplicate(span)),
@@ -576,6 +1066,25 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                     AutoInc => col_attr = PrimaryKeyAuto,
                     Indexed => unreachable!(),
                 },
+                ColumnAttr::RowVersion(span) => {
+                    if row_version.is_some() {
+                        return Err(syn::Error::new(span, "a table can only have one `row_version` column"));
+                    }
+                    if !matches!(field.ty, syn::Type::Path(p) if p.path.is_ident("u64")) {
+                        return Err(syn::Error::new(span, "a `row_version` column must be a `u64`"));
+                    }
+                    row_version = Some((col_num, field));
+                }
+                ColumnAttr::Compress(span, zstd) => {
+                    if compressed_columns.iter().any(|(col_id, _)| *col_id == col_num) {
+                        return Err(duplicate(span));
+                    }
+                    if !matches!(field.ty, syn::Type::Path(p) if p.path.is_ident("String")) {
+                        return Err(syn::Error::new(span, "a `compress` column must be a `String`"));
+                    }
+                    let codec = if zstd { quote!(Zstd) } else { quote!(Dictionary) };
+                    compressed_columns.push((col_num, codec));
+                }
             }
         }
 
@@ -604,14 +1113,20 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
     }
 
     let mut indexes = vec![];
+    let mut search_funcs = vec![];
+    let mut soft_delete = false;
 
     for attr in sats_ty.original_attrs {
         if attr.path().segments.last().unwrap().ident != "spacetimedb" {
             continue;
         }
-        let args = attr.parse_args::<MacroInput>()?;
-        let MacroInput::Index { ty, name, field_names } = args else {
-            continue;
+        let (ty, name, field_names) = match attr.parse_args::<MacroInput>()? {
+            MacroInput::Index { ty, name, field_names } => (ty, name, field_names),
+            MacroInput::SoftDelete => {
+                soft_delete = true;
+                continue;
+            }
+            _ => continue,
         };
         let col_ids = field_names
             .iter()
@@ -623,6 +1138,38 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 Ok(col.index)
             })
             .collect::<syn::Result<Vec<_>>>()?;
+        match ty {
+            IndexType::FullText => {
+                let [field_name] = &*field_names else {
+                    return Err(syn::Error::new_spanned(attr, "a full-text index must have exactly one field"));
+                };
+                let column = columns.iter().find(|col| col.field.ident == Some(field_name)).unwrap();
+                let vis = column.field.vis;
+                let column_index = column.index;
+                let search_func_ident = format_ident!("search_by_{}", field_name);
+                search_funcs.push(quote! {
+                    #vis fn #search_func_ident(query: &str) -> impl Iterator<Item = Self> {
+                        spacetimedb::query::search_by_field::<Self, #column_index>(query)
+                    }
+                });
+            }
+            IndexType::Spatial => {
+                let [field_name] = &*field_names else {
+                    return Err(syn::Error::new_spanned(attr, "a spatial index must have exactly one field"));
+                };
+                let column = columns.iter().find(|col| col.field.ident == Some(field_name)).unwrap();
+                let vis = column.field.vis;
+                let column_type = column.field.ty;
+                let column_index = column.index;
+                let filter_func_ident = format_ident!("filter_by_{}_box", field_name);
+                search_funcs.push(quote! {
+                    #vis fn #filter_func_ident(min: &#column_type, max: &#column_type) -> impl Iterator<Item = Self> {
+                        spacetimedb::query::filter_by_box::<Self, #column_type, #column_index>(min, max)
+                    }
+                });
+            }
+            IndexType::BTree | IndexType::Hash => {}
+        }
         let name = name.as_deref().unwrap_or("default_index");
         indexes.push(quote!(spacetimedb::IndexDef {
             name: #name,
@@ -643,9 +1190,31 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
 
     let has_unique = !unique_columns.is_empty();
 
+    // The first `#[primarykey]` column, which `get` and `delete` look rows up by.
+    let primary_key = unique_columns.iter().copied().find(|col| {
+        matches!(
+            col.attr,
+            ColumnIndexAttribute::PrimaryKey | ColumnIndexAttribute::PrimaryKeyAuto
+        )
+    });
+
+    // The version column, along with the primary key `update_if_version` finds the row by.
+    let row_version = row_version
+        .map(|(col_num, version)| {
+            let primary_key = primary_key.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    version.ident,
+                    "a table with a `row_version` column needs a `primarykey`",
+                )
+            })?;
+            Ok((col_num, version.ident.unwrap(), primary_key))
+        })
+        .transpose()?;
+
     let mut unique_filter_funcs = Vec::with_capacity(unique_columns.len());
     let mut unique_update_funcs = Vec::with_capacity(unique_columns.len());
     let mut unique_delete_funcs = Vec::with_capacity(unique_columns.len());
+    let mut unique_restore_funcs = Vec::new();
     let mut unique_fields = Vec::with_capacity(unique_columns.len());
     for unique in unique_columns {
         let column_index = unique.index;
@@ -676,8 +1245,37 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 spacetimedb::query::delete_by_field::<Self, #column_type, #column_index>(#column_ident)
             }
         });
+
+        if soft_delete {
+            let restore_func_ident = format_ident!("restore_by_{}", column_ident);
+            unique_restore_funcs.push(quote! {
+                #vis fn #restore_func_ident(#column_ident: &#column_type) -> bool {
+                    spacetimedb::query::restore_by_field::<Self, #column_type, #column_index>(#column_ident)
+                }
+            });
+        }
     }
 
+    // The host bumps the version of the row replacing the current one,
+    // so the current row is looked up only once, to compare its version.
+    let update_if_version_func = row_version.map(|(_, version_ident, primary_key)| {
+        let vis = primary_key.field.vis;
+        let column_ident = primary_key.field.ident.unwrap();
+        let filter_func_ident = format_ident!("filter_by_{}", column_ident);
+        let update_func_ident = format_ident!("update_by_{}", column_ident);
+        quote! {
+            #vis fn update_if_version(value: Self, expected_version: u64) -> bool {
+                let #column_ident = value.#column_ident.clone();
+                match Self::#filter_func_ident(&#column_ident) {
+                    Some(current) if current.#version_ident == expected_version => {
+                        Self::#update_func_ident(&#column_ident, value)
+                    }
+                    _ => false,
+                }
+            }
+        }
+    });
+
     let non_primary_filter_func = nonunique_columns.into_iter().filter_map(|column| {
         let vis = column.field.vis;
         let column_ident = column.field.ident.unwrap();
@@ -709,6 +1307,43 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
     });
     let non_primary_filter_func = non_primary_filter_func.collect::<Vec<_>>();
 
+    let contains_filter_funcs = columns.iter().filter_map(|column| {
+        let vis = column.field.vis;
+        let column_ident = column.field.ident.unwrap();
+        let column_index = column.index;
+
+        // Only `Vec<T>` columns get a containment filter, so `T` is what the caller looks for.
+        let syn::Type::Path(p) = column.field.ty else {
+            return None;
+        };
+        let last = p.path.segments.last()?;
+        if last.ident != "Vec" {
+            return None;
+        }
+        let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+            return None;
+        };
+        let Some(syn::GenericArgument::Type(elem_type @ syn::Type::Path(elem))) = args.args.first() else {
+            return None;
+        };
+        // TODO: same janky check as for `filter_by_{}`, so that only `FilterableValue`s get a filter
+        if !matches!(
+            &*elem.path.segments.last()?.ident.to_string(),
+            "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "Hash" | "Identity" | "String" | "bool"
+        ) {
+            return None;
+        }
+
+        let filter_func_ident = format_ident!("filter_by_{}_contains", column_ident);
+
+        Some(quote! {
+            #vis fn #filter_func_ident<'a>(#column_ident: &'a #elem_type) -> impl Iterator<Item = Self> + 'a {
+                spacetimedb::query::filter_by_field_contains::<Self, #elem_type, #column_index>(#column_ident)
+            }
+        })
+    });
+    let contains_filter_funcs = contains_filter_funcs.collect::<Vec<_>>();
+
     let insert_result = if has_unique {
         quote!(std::result::Result<Self, spacetimedb::UniqueConstraintViolation<Self>>)
     } else {
@@ -722,13 +1357,28 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
         }
     };
 
-    let db_delete = quote! {
-        #[allow(unused_variables)]
-        pub fn delete(f: fn (#original_struct_ident) -> bool) -> usize {
-            panic!("Delete using a function is not supported yet!");
-        }
+    let db_delete = match primary_key {
+        Some(_) => quote! {
+            pub fn delete(pk: &<Self as spacetimedb::PrimaryKeyTable>::PrimaryKey) -> bool {
+                <Self as spacetimedb::PrimaryKeyTable>::delete(pk)
+            }
+        },
+        None => quote! {
+            #[allow(unused_variables)]
+            pub fn delete(f: fn (#original_struct_ident) -> bool) -> usize {
+                panic!("Delete using a function is not supported yet!");
+            }
+        },
     };
 
+    let db_get = primary_key.map(|_| {
+        quote! {
+            pub fn get(pk: &<Self as spacetimedb::PrimaryKeyTable>::PrimaryKey) -> Option<Self> {
+                <Self as spacetimedb::PrimaryKeyTable>::get(pk)
+            }
+        }
+    });
+
     let db_update = quote! {
         #[allow(unused_variables)]
         pub fn update(value: #original_struct_ident) -> bool {
@@ -741,11 +1391,39 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
         pub fn iter() -> spacetimedb::TableIter<Self> {
             <Self as spacetimedb::TableType>::iter()
         }
+
+        pub fn iter_cols<C: spacetimedb::Columns<Self>>() -> spacetimedb::ProjectedIter<Self, C> {
+            <Self as spacetimedb::TableType>::iter_cols::<C>()
+        }
+
+        pub fn table_version() -> u64 {
+            <Self as spacetimedb::TableType>::table_version()
+        }
     };
 
+    let db_soft_delete = soft_delete.then(|| {
+        quote! {
+            pub fn iter_deleted() -> impl Iterator<Item = (Self, spacetimedb::Timestamp)> {
+                spacetimedb::query::iter_deleted::<Self>()
+            }
+
+            pub fn purge_deleted() -> u32 {
+                spacetimedb::query::purge_deleted::<Self>()
+            }
+        }
+    });
+
     let deserialize_impl = derive_deserialize(&sats_ty);
     let serialize_impl = derive_serialize(&sats_ty);
     let schema_impl = derive_satstype(&sats_ty, false);
+    let (compressed_col_ids, compressed_codecs): (Vec<_>, Vec<_>) = compressed_columns.into_iter().unzip();
+    let row_version_cols = match row_version {
+        Some((col_num, _, primary_key)) => {
+            let key_col = primary_key.index;
+            quote!(Some((#col_num, #key_col)))
+        }
+        None => quote!(None),
+    };
     let column_attrs = columns
         .iter()
         .map(|col| Ident::new(&format!("{:?}", col.attr), Span::call_site()));
@@ -756,11 +1434,36 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 #(spacetimedb::spacetimedb_lib::ColumnIndexAttribute::#column_attrs),*
             ];
             const INDEXES: &'static [spacetimedb::IndexDef<'static>] = &[#(#indexes),*];
+            const SOFT_DELETE: bool = #soft_delete;
+            const DEFERRED_UNIQUE: &'static [u8] = &[#(#deferred_unique),*];
+            const COMPRESSED_COLUMNS: &'static [(u8, spacetimedb::spacetimedb_lib::Compression)] = &[
+                #((#compressed_col_ids, spacetimedb::spacetimedb_lib::Compression::#compressed_codecs)),*
+            ];
+            const ROW_VERSION: Option<(u8, u8)> = #row_version_cols;
             type InsertResult = #insert_result;
             #get_table_id_func
         }
     };
 
+    let primary_key_impl = primary_key.map(|column| {
+        let column_type = column.field.ty;
+        let column_index = column.index;
+        quote! {
+            impl spacetimedb::PrimaryKeyTable for #original_struct_ident {
+                type PrimaryKey = #column_type;
+                const PRIMARY_KEY_COLUMN: u8 = #column_index;
+
+                fn get(pk: &Self::PrimaryKey) -> Option<Self> {
+                    spacetimedb::query::filter_by_unique_field::<Self, #column_type, #column_index>(pk)
+                }
+
+                fn delete(pk: &Self::PrimaryKey) -> bool {
+                    spacetimedb::query::delete_by_field::<Self, #column_type, #column_index>(pk)
+                }
+            }
+        }
+    });
+
     let register_describer_symbol = format!("__preinit__20_register_describer_{table_name}");
 
     let describe_table_func = quote! {
@@ -783,6 +1486,39 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
         })*
     };
 
+    // A type for each column, e.g. `player_cols::Name`, to project rows onto with `iter_cols`.
+    let cols_mod = format_ident!("{}_cols", to_snake_case(&original_struct_ident.to_string()));
+    let cols_mod_vis = &item.vis;
+    let col_markers = fields
+        .iter()
+        .map(|f| format_ident!("{}", to_camel_case(&f.ident.unwrap().unraw().to_string())))
+        .collect::<Vec<_>>();
+    let col_marker_vis = fields.iter().map(|f| match f.vis {
+        // The markers are a module deeper than the fields they stand for.
+        syn::Visibility::Public(_) => quote!(pub),
+        syn::Visibility::Restricted(r) if r.path.is_ident("crate") => quote!(pub(crate)),
+        _ => quote!(pub(super)),
+    });
+    let col_marker_docs = field_names.iter().map(|name| format!(" The column `{name}`."));
+    let cols_mod_doc = format!(" The columns of `{original_struct_ident}`, as types to pass to `iter_cols`.");
+    let col_num = 0u8..;
+    let column_impls = quote! {
+        #[doc = #cols_mod_doc]
+        #cols_mod_vis mod #cols_mod {
+            #(
+                #[doc = #col_marker_docs]
+                #[allow(dead_code)]
+                #col_marker_vis struct #col_markers { _never: ::core::convert::Infallible }
+            )*
+        }
+
+        #(impl spacetimedb::Column for #cols_mod::#col_markers {
+            type Table = #original_struct_ident;
+            type Value = #field_types;
+            const INDEX: u8 = #col_num;
+        })*
+    };
+
     let filter_impl = quote! {
         const _: () = {
             #[derive(Debug, spacetimedb::Serialize, spacetimedb::Deserialize)]
@@ -833,22 +1569,30 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
 
         impl #original_struct_ident {
             #db_insert
+            #db_get
             #db_delete
             #db_update
             #(#unique_filter_funcs)*
             #(#unique_update_funcs)*
             #(#unique_delete_funcs)*
+            #(#unique_restore_funcs)*
+            #update_if_version_func
 
             #db_iter
+            #db_soft_delete
             #(#non_primary_filter_func)*
+            #(#contains_filter_funcs)*
+            #(#search_funcs)*
         }
 
         #schema_impl
         #deserialize_impl
         #serialize_impl
         #tabletype_impl
+        #primary_key_impl
 
         #field_access_impls
+        #column_impls
         #filter_impl
     };
 
@@ -882,14 +1626,56 @@ fn spacetimedb_index(
     Ok(output)
 }
 
+fn spacetimedb_soft_delete(item: TokenStream) -> syn::Result<TokenStream> {
+    let original_struct = syn::parse2::<ItemStruct>(item)?;
+    let original_struct_name = &original_struct.ident;
+
+    Ok(quote! {
+        #original_struct
+
+        const _: () = spacetimedb::rt::assert_table::<#original_struct_name>();
+    })
+}
+
 fn spacetimedb_migrate(item: TokenStream) -> syn::Result<TokenStream> {
     let original_function = syn::parse2::<ItemFn>(item)?;
-    gen_reducer(original_function, "__migrate__", ReducerExtra::None)
+    gen_reducer(
+        original_function,
+        "__migrate__",
+        ReducerExtra::None,
+        false,
+        None,
+        None,
+        &[],
+    )
 }
 
 fn spacetimedb_update(item: TokenStream) -> syn::Result<TokenStream> {
     let original_function = syn::parse2::<ItemFn>(item)?;
-    gen_reducer(original_function, "__update__", ReducerExtra::None)
+    gen_reducer(
+        original_function,
+        "__update__",
+        ReducerExtra::None,
+        false,
+        None,
+        None,
+        &[],
+    )
+}
+
+fn spacetimedb_identity_merged(item: TokenStream) -> syn::Result<TokenStream> {
+    let original_function = syn::parse2::<ItemFn>(item)?;
+    // Only the host, calling as the module's owner, may merge identities.
+    let owner = ["owner".to_owned()];
+    gen_reducer(
+        original_function,
+        "__identity_merged__",
+        ReducerExtra::None,
+        false,
+        None,
+        None,
+        &owner,
+    )
 }
 
 fn spacetimedb_connect_disconnect(item: TokenStream, connect: bool) -> syn::Result<TokenStream> {
@@ -919,6 +1705,40 @@ fn spacetimedb_connect_disconnect(item: TokenStream, connect: bool) -> syn::Resu
     Ok(emission)
 }
 
+/// Registers the function in `item` as a hook to run before or after every reducer.
+fn spacetimedb_reducer_hook(item: TokenStream, before: bool) -> syn::Result<TokenStream> {
+    let original_function = syn::parse2::<ItemFn>(item)?;
+    let func_name = &original_function.sig.ident;
+    let (register_hook_symbol, register_fn) = if before {
+        (
+            format!("__preinit__20_register_before_reducer_{func_name}"),
+            quote!(register_before_reducer),
+        )
+    } else {
+        (
+            format!("__preinit__20_register_after_reducer_{func_name}"),
+            quote!(register_after_reducer),
+        )
+    };
+
+    let emission = quote! {
+        const _: () = {
+            #[export_name = #register_hook_symbol]
+            extern "C" fn __register_reducer_hook() {
+                spacetimedb::rt::#register_fn(#func_name)
+            }
+        };
+
+        #original_function
+    };
+
+    if std::env::var("PROC_MACRO_DEBUG").is_ok() {
+        println!("{}", emission);
+    }
+
+    Ok(emission)
+}
+
 #[proc_macro]
 pub fn duration(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let dur = syn::parse_macro_input!(input with parse_duration);
@@ -933,6 +1753,49 @@ fn parse_duration(input: ParseStream) -> syn::Result<Duration> {
     humantime::parse_duration(&s).map_err(|e| syn::Error::new(span, format_args!("can't parse as duration: {e}")))
 }
 
+/// Parses an HTTP route, e.g. `"GET /leaderboard/:limit"`, into its variant of `HttpMethod` and its path.
+fn parse_http_route(input: ParseStream) -> syn::Result<HttpRouteAttr> {
+    let lit = input.parse::<syn::LitStr>()?;
+    let value = lit.value();
+    let err = |msg| syn::Error::new(lit.span(), msg);
+    let (method, path) = value
+        .split_once(' ')
+        .ok_or_else(|| err("http route must be a method and a path, e.g. \"GET /leaderboard\""))?;
+    let method = match method {
+        "GET" => "Get",
+        "POST" => "Post",
+        _ => return Err(err("http method must be \"GET\" or \"POST\"")),
+    };
+    let path = path.trim();
+    if !path.starts_with('/') {
+        return Err(err("http path must start with `/`"));
+    }
+    if path.split('/').any(|segment| segment == ":") {
+        return Err(err("http path parameters must be named, as in `/:name`"));
+    }
+    Ok(HttpRouteAttr {
+        method: Ident::new(method, lit.span()),
+        path: path.to_owned(),
+    })
+}
+
+/// Parses the priority of a reducer, `"high"`, `"normal"` or `"low"`, into its variant of `Lane`.
+fn parse_lane(input: ParseStream) -> syn::Result<Ident> {
+    let lit = input.parse::<syn::LitStr>()?;
+    let variant = match &*lit.value() {
+        "high" => "High",
+        "normal" => "Normal",
+        "low" => "Low",
+        _ => {
+            return Err(syn::Error::new(
+                lit.span(),
+                "priority must be \"high\", \"normal\" or \"low\"",
+            ))
+        }
+    };
+    Ok(Ident::new(variant, lit.span()))
+}
+
 #[proc_macro_derive(Deserialize, attributes(sats))]
 pub fn deserialize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
@@ -1208,3 +2071,31 @@ pub fn query(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+/// Converts a `CamelCase` type name into `snake_case`.
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Converts a `snake_case` field name into `CamelCase`.
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap();
+            first.to_uppercase().chain(chars).collect::<String>()
+        })
+        .collect()
+}
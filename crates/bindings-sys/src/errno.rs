@@ -7,12 +7,28 @@ pub const LOOKUP_NOT_FOUND: u16 = 2;
 /// Error code for when a unique constraint is violated.
 pub const UNIQUE_ALREADY_EXISTS: u16 = 3;
 
+/// Error code for a savepoint that doesn't exist, e.g., because it was already released.
+pub const NO_SUCH_SAVEPOINT: u16 = 4;
+
+/// Error code for a table or row that would take the database over its quota.
+pub const QUOTA_EXCEEDED: u16 = 5;
+
+/// Error code for a reducer that the module doesn't define.
+pub const NO_SUCH_REDUCER: u16 = 6;
+
+/// Error code for a blob over the size limit.
+pub const BLOB_TOO_LARGE: u16 = 7;
+
 macro_rules! errnos {
     ($mac:ident) => {
         $mac! {
             NO_SUCH_TABLE => "No such table",
             LOOKUP_NOT_FOUND => "Value or range provided not found in table",
             UNIQUE_ALREADY_EXISTS => "Value with given unique identifier already exists",
+            NO_SUCH_SAVEPOINT => "No such savepoint",
+            QUOTA_EXCEEDED => "The database's quota was exceeded",
+            NO_SUCH_REDUCER => "No such reducer",
+            BLOB_TOO_LARGE => "The blob is over the size limit",
         }
     };
 }
@@ -5,6 +5,8 @@ extern crate alloc;
 
 #[macro_use]
 mod errno;
+#[cfg(feature = "testing")]
+pub mod mock;
 
 use core::fmt;
 use core::mem::MaybeUninit;
@@ -34,12 +36,19 @@ use alloc::boxed::Box;
 /// can run a module declaring `X.Y` if and only if `X == A && Y <= B`.
 /// So, the minor version is intended for backwards-compatible changes, e.g. adding a new function,
 /// and the major version is for fully breaking changes.
-pub const ABI_VERSION: u32 = 0x0003_0000;
+/// A host also runs a module declaring the previous major version, `A - 1`,
+/// linking shims for the functions that version has and `A` dropped,
+/// so that modules can be republished against a new major version at their own pace.
+pub const ABI_VERSION: u32 = 0x0003_0010;
 
 /// Provides a raw set of sys calls which abstractions can be built atop of.
 pub mod raw {
     use core::mem::ManuallyDrop;
 
+    #[cfg(feature = "testing")]
+    pub use crate::mock::sys_calls::*;
+
+    #[cfg(not(feature = "testing"))]
     #[link(wasm_import_module = "spacetime")]
     extern "C" {
         /*
@@ -99,11 +108,57 @@ pub mod raw {
         pub fn _iter_by_col_eq(table_id: u32, col_id: u32, value: *const u8, value_len: usize, out: *mut Buffer)
             -> u16;
 
+        /// Finds all rows in the table identified by `table_id`,
+        /// where the row has a string column, identified by `col_id`,
+        /// with all the words of the UTF-8 `query`, in WASM memory, pointed to at by `query`.
+        ///
+        /// Words are compared case-insensitively, as defined by `spacetimedb_lib::fulltext::tokenize`.
+        /// A full-text index on the column is used when there's one.
+        ///
+        /// The rows found are bsatn encoded and then concatenated.
+        /// The resulting byte string from the concatenation is written
+        /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+        pub fn _iter_by_col_match(
+            table_id: u32,
+            col_id: u32,
+            query: *const u8,
+            query_len: usize,
+            out: *mut Buffer,
+        ) -> u16;
+
+        /// Finds all rows in the table identified by `table_id`,
+        /// where the row has a point column, identified by `col_id`,
+        /// within the box from `min` to `max`, edges included.
+        ///
+        /// The corners `(min, min_len)` and `(max, max_len)` are in WASM memory
+        /// and are bsatn encoded points, as defined by `spacetimedb_lib::spatial`.
+        /// A spatial index on the column is used when there's one.
+        ///
+        /// The rows found are bsatn encoded and then concatenated.
+        /// The resulting byte string from the concatenation is written
+        /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+        pub fn _iter_by_col_box(
+            table_id: u32,
+            col_id: u32,
+            min: *const u8,
+            min_len: usize,
+            max: *const u8,
+            max_len: usize,
+            out: *mut Buffer,
+        ) -> u16;
+
         /// Insert a row into the table identified by `table_id`,
         /// where the row is read from the byte slice `row_ptr` in WASM memory,
         /// lasting `row_len` bytes.
+        ///
+        /// Errors with `QUOTA_EXCEEDED` if the row would take the database over its quota.
         pub fn _insert(table_id: u32, row: *mut u8, row_len: usize) -> u16;
 
+        /// Like `_insert`, but when erroring with `UNIQUE_ALREADY_EXISTS`,
+        /// the id of the column whose unique constraint the row violates
+        /// is also written to the `conflict` pointer.
+        pub fn _try_insert(table_id: u32, row: *mut u8, row_len: usize, conflict: *mut u32) -> u16;
+
         /// Deletes all rows in the table identified by `table_id`
         /// where the column identified by `col_id` matches the byte string,
         /// in WASM memory, pointed to at by `value`.
@@ -116,6 +171,27 @@ pub mod raw {
         /// Returns an error if no columns were deleted or if the column wasn't found.
         pub fn _delete_by_col_eq(table_id: u32, col_id: u32, value: *const u8, value_len: usize, out: *mut u32) -> u16;
 
+        /// Moves rows from the table identified by `src_table_id` to the one identified by `dst_table_id`,
+        /// deleting the rows in the byte slice `(src_rows, src_rows_len)`
+        /// and inserting those in `(dst_rows, dst_rows_len)`, both in WASM memory.
+        /// The rows of each are bsatn encoded and then concatenated.
+        ///
+        /// Either all the rows are moved or, on error, none are.
+        ///
+        /// The number of rows deleted is written to the WASM pointer `out`.
+        ///
+        /// Errors with `LOOKUP_NOT_FOUND` if a row to delete wasn't found,
+        /// and with `UNIQUE_ALREADY_EXISTS` or `QUOTA_EXCEEDED` if a row couldn't be inserted.
+        pub fn _move_rows(
+            src_table_id: u32,
+            src_rows: *const u8,
+            src_rows_len: usize,
+            dst_table_id: u32,
+            dst_rows: *const u8,
+            dst_rows_len: usize,
+            out: *mut u32,
+        ) -> u16;
+
         /*
         /// Deletes the primary key pointed to at by `pk` in the table identified by `table_id`.
         pub fn _delete_pk(table_id: u32, pk: *const u8, pk_len: usize) -> u16;
@@ -131,6 +207,81 @@ pub mod raw {
         ) -> u16;
         */
 
+        /// Writes the energy left in the budget of the current reducer call into the `out` pointer.
+        pub fn _remaining_energy(out: *mut u64) -> u16;
+
+        /// Writes the offset in the commit log of the current transaction into the `out` pointer.
+        ///
+        /// That is the number of transactions with changes committed before it,
+        /// so each transaction committing changes gets a distinct and increasing offset.
+        pub fn _tx_offset(out: *mut u64) -> u16;
+
+        /// Writes the version of the table identified by `table_id` into the `out` pointer.
+        ///
+        /// That is the number of committed transactions that changed the rows of the table,
+        /// not counting the current one.
+        ///
+        /// Returns an error if the table does not exist.
+        pub fn _table_version(table_id: u32, out: *mut u64) -> u16;
+
+        /// Writes the BSATN-encoded `ProductType` of the rows stored in the table identified by `table_id`
+        /// to a fresh buffer, whose handle is written to the `out` pointer.
+        ///
+        /// That is the layout the first chunk of `_iter_start` describes, without iterating the table.
+        ///
+        /// Returns an error if the table does not exist.
+        pub fn _table_row_type(table_id: u32, out: *mut Buffer) -> u16;
+
+        /// Stores the blob in the byte slice `(data, data_len)` in WASM memory
+        /// under the key named by the UTF-8 slice `(key, key_len)`,
+        /// replacing the blob stored under it before, if any.
+        ///
+        /// The 32 byte hash of the blob is written to the `out` pointer.
+        ///
+        /// Errors with `BLOB_TOO_LARGE` if the blob is over the size limit
+        /// and with `QUOTA_EXCEEDED` if it would take the database over its quota.
+        pub fn _blob_put(key: *const u8, key_len: usize, data: *const u8, data_len: usize, out: *mut [u8; 32]) -> u16;
+
+        /// Reads the blob stored under the key named by the UTF-8 slice `(key, key_len)`
+        /// into a new buffer, whose handle is written to the `out` pointer.
+        ///
+        /// Errors with `LOOKUP_NOT_FOUND` if no blob is stored under the key.
+        pub fn _blob_get(key: *const u8, key_len: usize, out: *mut Buffer) -> u16;
+
+        /// Deletes the blob stored under the key named by the UTF-8 slice `(key, key_len)`.
+        ///
+        /// Errors with `LOOKUP_NOT_FOUND` if no blob is stored under the key.
+        pub fn _blob_delete(key: *const u8, key_len: usize) -> u16;
+
+        /// Stores the byte slice `(data, data_len)` in WASM memory out of the rows that will reference it.
+        ///
+        /// The encoded key those rows hold is written to a new buffer,
+        /// whose handle is written to the `out` pointer.
+        pub fn _large_bytes_put(data: *const u8, data_len: usize, out: *mut Buffer) -> u16;
+
+        /// Reads the bytes stored under the encoded key in the byte slice `(key, key_len)`
+        /// into a new buffer, whose handle is written to the `out` pointer.
+        ///
+        /// Errors with `LOOKUP_NOT_FOUND` if no bytes are stored under the key.
+        pub fn _large_bytes_get(key: *const u8, key_len: usize, out: *mut Buffer) -> u16;
+
+        /// Takes a savepoint of the changes made so far in the current transaction.
+        ///
+        /// The savepoint's id is written into the `out` pointer.
+        pub fn _savepoint(out: *mut u32) -> u16;
+
+        /// Undoes the changes made in the current transaction since the savepoint `id` was taken,
+        /// releasing it and all the savepoints taken after it.
+        ///
+        /// Errors with `NO_SUCH_SAVEPOINT` if there's no such savepoint, e.g., because it was already released.
+        pub fn _rollback_to_savepoint(id: u32) -> u16;
+
+        /// Releases the savepoint `id` and all the savepoints taken after it,
+        /// keeping the changes made since.
+        ///
+        /// Errors with `NO_SUCH_SAVEPOINT` if there's no such savepoint, e.g., because it was already released.
+        pub fn _release_savepoint(id: u32) -> u16;
+
         /// Start iteration on each row, as bytes, of a table identified by `table_id`.
         ///
         /// The iterator is registered in the host environment
@@ -147,6 +298,16 @@ pub mod raw {
         /// under an assigned index which is written to the `out` pointer provided.
         pub fn _iter_start_filtered(table_id: u32, filter: *const u8, filter_len: usize, out: *mut BufferIter) -> u16;
 
+        /// Like [`_iter_start`], start iteration on each row of a table identified by `table_id`,
+        /// but with each row projected onto the columns whose ids are in the slice `(cols, cols_len)`.
+        ///
+        /// Each row is encoded as the BSATN of those columns alone, in the order given,
+        /// and the schema the iterator yields first is that of the projection.
+        ///
+        /// The iterator is registered in the host environment
+        /// under an assigned index which is written to the `out` pointer provided.
+        pub fn _iter_start_projected(table_id: u32, cols: *const u8, cols_len: usize, out: *mut BufferIter) -> u16;
+
         /// Advances the registered iterator with the index given by `iter_key`.
         ///
         /// On success, the next element (the row as bytes) is written to a buffer.
@@ -178,6 +339,21 @@ pub mod raw {
             text_len: usize,
         );
 
+        /// Like [`_console_log`], but with the key-value pairs of the record as well,
+        /// passed bsatn encoded as a `Vec<LogField>` in the slice `(fields, fields_len)`.
+        pub fn _console_log_structured(
+            level: u8,
+            target: *const u8,
+            target_len: usize,
+            filename: *const u8,
+            filename_len: usize,
+            line_number: u32,
+            text: *const u8,
+            text_len: usize,
+            fields: *const u8,
+            fields_len: usize,
+        );
+
         /// Schedule a reducer to be called asynchronously at `time`.
         ///
         /// The reducer is named as the UTF-8 slice `(name, name_len)`,
@@ -199,6 +375,42 @@ pub mod raw {
         /// This assumes that the reducer hasn't already been executed.
         pub fn _cancel_reducer(id: u64);
 
+        /// Emits an event of the type named by the UTF-8 slice `(name, name_len)`,
+        /// encoded as BSATN in the slice `(data, data_len)`.
+        ///
+        /// The event is delivered to the subscribed clients
+        /// if the transaction of the current reducer commits.
+        pub fn _emit_event(name: *const u8, name_len: usize, data: *const u8, data_len: usize) -> u16;
+
+        /// Sets the value the current reducer returns to its caller,
+        /// encoded as BSATN in the slice `(data, data_len)`,
+        /// replacing any value set before.
+        ///
+        /// The value is delivered with the reducer's transaction if it commits.
+        pub fn _set_return_value(data: *const u8, data_len: usize) -> u16;
+
+        /// Sends a call of the reducer named by the UTF-8 slice `(reducer, reducer_len)`
+        /// of the database whose 16 byte address is at the `address` pointer,
+        /// with its arguments encoded as BSATN in the slice `(args, args_len)`.
+        ///
+        /// The call is made once, by the host, if and when the transaction of the current reducer commits.
+        /// The database must be running on the same node.
+        pub fn _send_message(
+            address: *const [u8; 16],
+            reducer: *const u8,
+            reducer_len: usize,
+            args: *const u8,
+            args_len: usize,
+        ) -> u16;
+
+        /// Describes the arguments of the reducer named by the UTF-8 slice `(name, name_len)`,
+        /// as a BSATN encoded `ProductType` whose type references are resolved.
+        ///
+        /// The description is written to a new buffer, whose handle is written to the `out` pointer.
+        ///
+        /// Errors with `NO_SUCH_REDUCER` if the module doesn't define such a reducer.
+        pub fn _describe_reducer(name: *const u8, name_len: usize, out: *mut Buffer) -> u16;
+
         /// Returns the length of buffer `bufh` without consuming the buffer handle.
         ///
         /// Returns an error if the buffer does not exist.
@@ -224,6 +436,10 @@ pub mod raw {
         BTree = 0,
         /// Indexing works by hashing the index key.
         Hash = 1,
+        /// Indexing works by putting each word of a string column into a b-tree.
+        FullText = 2,
+        /// Indexing works by putting the points of a column into a b-tree, ordered by `x` then `y`.
+        Spatial = 3,
     }
 
     /// The error log level. See [`_console_log`].
@@ -247,7 +463,7 @@ pub mod raw {
     #[repr(transparent)]
     pub struct Buffer {
         /// The actual handle. A key into a `ResourceSlab`.
-        raw: u32,
+        pub(crate) raw: u32,
     }
 
     impl Buffer {
@@ -273,7 +489,7 @@ pub mod raw {
     /// Represents table iterators, with a similar API to [`Buffer`].
     #[repr(transparent)]
     pub struct BufferIter {
-        raw: u32,
+        pub(crate) raw: u32,
     }
 
     impl BufferIter {
@@ -469,12 +685,49 @@ pub fn iter_by_col_eq(table_id: u32, col_id: u32, val: &[u8]) -> Result<Buffer,
     unsafe { call(|out| raw::_iter_by_col_eq(table_id, col_id, val.as_ptr(), val.len(), out)) }
 }
 
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a string column, identified by `col_id`,
+/// with all the words of `query`.
+///
+/// The rows found are bsatn encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+#[inline]
+pub fn iter_by_col_match(table_id: u32, col_id: u32, query: &str) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_iter_by_col_match(table_id, col_id, query.as_ptr(), query.len(), out)) }
+}
+
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a point column, identified by `col_id`,
+/// within the box from `min` to `max`, both bsatn encoded points.
+///
+/// The rows found are bsatn encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+#[inline]
+pub fn iter_by_col_box(table_id: u32, col_id: u32, min: &[u8], max: &[u8]) -> Result<Buffer, Errno> {
+    unsafe {
+        call(|out| raw::_iter_by_col_box(table_id, col_id, min.as_ptr(), min.len(), max.as_ptr(), max.len(), out))
+    }
+}
+
 /// Insert `row`, provided as a byte slice, into the table identified by `table_id`.
 #[inline]
 pub fn insert(table_id: u32, row: &mut [u8]) -> Result<(), Errno> {
     cvt(unsafe { raw::_insert(table_id, row.as_mut_ptr(), row.len()) })
 }
 
+/// Insert `row`, provided as a byte slice, into the table identified by `table_id`.
+///
+/// When `row` violates a unique constraint,
+/// the id of the column of that constraint is returned along with the error.
+#[inline]
+pub fn try_insert(table_id: u32, row: &mut [u8]) -> Result<(), (Errno, Option<u32>)> {
+    let mut conflict = u32::MAX;
+    cvt(unsafe { raw::_try_insert(table_id, row.as_mut_ptr(), row.len(), &mut conflict) })
+        .map_err(|errno| (errno, (conflict != u32::MAX).then_some(conflict)))
+}
+
 /// Deletes all rows in the table identified by `table_id`
 /// where the column identified by `col_id` equates to `value`.
 ///
@@ -485,6 +738,31 @@ pub fn delete_by_col_eq(table_id: u32, col_id: u32, value: &[u8]) -> Result<u32,
     unsafe { call(|out| raw::_delete_by_col_eq(table_id, col_id, value.as_ptr(), value.len(), out)) }
 }
 
+/// Deletes the rows `src_rows` from the table identified by `src_table_id`
+/// and inserts the rows `dst_rows` into the table identified by `dst_table_id`,
+/// where each is bsatn encoded rows, concatenated.
+///
+/// Either all the rows are moved or, on error, none are.
+///
+/// Returns the number of rows deleted
+/// or an error if a row to delete wasn't found or if a row couldn't be inserted.
+#[inline]
+pub fn move_rows(src_table_id: u32, src_rows: &[u8], dst_table_id: u32, dst_rows: &[u8]) -> Result<u32, Errno> {
+    unsafe {
+        call(|out| {
+            raw::_move_rows(
+                src_table_id,
+                src_rows.as_ptr(),
+                src_rows.len(),
+                dst_table_id,
+                dst_rows.as_ptr(),
+                dst_rows.len(),
+                out,
+            )
+        })
+    }
+}
+
 /*
 #[inline]
 pub fn delete_pk(table_id: u32, pk: &[u8]) -> Result<(), Errno> {
@@ -528,6 +806,15 @@ pub fn iter(table_id: u32, filter: Option<&[u8]>) -> Result<BufferIter, Errno> {
     }
 }
 
+/// Returns an iterator for each row of a table identified by `table_id`,
+/// projected onto the columns whose ids are in `cols`, as bytes.
+///
+/// Like [`iter`], the first item is the encoded schema, here that of the projection.
+#[inline]
+pub fn iter_projected(table_id: u32, cols: &[u8]) -> Result<BufferIter, Errno> {
+    unsafe { call(|out| raw::_iter_start_projected(table_id, cols.as_ptr(), cols.len(), out)) }
+}
+
 /// A log level that can be used in `console_log`.
 /// The variants are convertible into a raw `u8` log level.
 #[repr(u8)]
@@ -576,6 +863,38 @@ pub fn console_log(
     }
 }
 
+/// Log at `level` a `text` message occuring in `filename:line_number`
+/// with [`target`] being the module path at the `log!` invocation site,
+/// and `fields` being the key-value pairs of the record, bsatn encoded as a `Vec<LogField>`.
+///
+/// [`target`]: https://docs.rs/log/latest/log/struct.Record.html#method.target
+#[inline]
+pub fn console_log_structured(
+    level: LogLevel,
+    target: Option<&str>,
+    filename: Option<&str>,
+    line_number: Option<u32>,
+    text: &str,
+    fields: &[u8],
+) {
+    let opt_ptr = |b: Option<&str>| b.map_or(ptr::null(), |b| b.as_ptr());
+    let opt_len = |b: Option<&str>| b.map_or(0, |b| b.len());
+    unsafe {
+        raw::_console_log_structured(
+            level as u8,
+            opt_ptr(target),
+            opt_len(target),
+            opt_ptr(filename),
+            opt_len(filename),
+            line_number.unwrap_or(u32::MAX),
+            text.as_ptr(),
+            text.len(),
+            fields.as_ptr(),
+            fields.len(),
+        )
+    }
+}
+
 /// Schedule a reducer to be called asynchronously at `time`.
 ///
 /// The reducer is assigned `name` and is provided `args` as its argument.
@@ -592,6 +911,84 @@ pub fn schedule(name: &str, args: &[u8], time: u64) -> u64 {
     out
 }
 
+/// Returns the energy left in the budget of the current reducer call.
+#[inline]
+pub fn remaining_energy() -> Result<u64, Errno> {
+    unsafe { call(|out| raw::_remaining_energy(out)) }
+}
+
+/// Returns the offset in the commit log of the current transaction.
+#[inline]
+pub fn tx_offset() -> Result<u64, Errno> {
+    unsafe { call(|out| raw::_tx_offset(out)) }
+}
+
+/// Returns the version of the table identified by `table_id`,
+/// the number of committed transactions that changed its rows.
+#[inline]
+pub fn table_version(table_id: u32) -> Result<u64, Errno> {
+    unsafe { call(|out| raw::_table_version(table_id, out)) }
+}
+
+/// Returns a buffer with the BSATN-encoded layout of the rows stored in the table identified by `table_id`.
+#[inline]
+pub fn table_row_type(table_id: u32) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_table_row_type(table_id, out)) }
+}
+
+/// Stores the blob `data` under `key`, replacing the blob stored under it before, if any,
+/// returning the hash of `data`.
+#[inline]
+pub fn blob_put(key: &str, data: &[u8]) -> Result<[u8; 32], Errno> {
+    unsafe { call(|out| raw::_blob_put(key.as_ptr(), key.len(), data.as_ptr(), data.len(), out)) }
+}
+
+/// Returns a buffer holding the blob stored under `key`.
+#[inline]
+pub fn blob_get(key: &str) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_blob_get(key.as_ptr(), key.len(), out)) }
+}
+
+/// Deletes the blob stored under `key`.
+#[inline]
+pub fn blob_delete(key: &str) -> Result<(), Errno> {
+    cvt(unsafe { raw::_blob_delete(key.as_ptr(), key.len()) })
+}
+
+/// Stores `data` out of the rows that will reference it,
+/// returning a buffer holding the encoded key those rows hold.
+#[inline]
+pub fn large_bytes_put(data: &[u8]) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_large_bytes_put(data.as_ptr(), data.len(), out)) }
+}
+
+/// Returns a buffer holding the bytes stored under the encoded `key`.
+#[inline]
+pub fn large_bytes_get(key: &[u8]) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_large_bytes_get(key.as_ptr(), key.len(), out)) }
+}
+
+/// Takes a savepoint of the changes made so far in the current transaction,
+/// returning the savepoint's id.
+#[inline]
+pub fn savepoint() -> Result<u32, Errno> {
+    unsafe { call(|out| raw::_savepoint(out)) }
+}
+
+/// Undoes the changes made in the current transaction since the savepoint `id` was taken,
+/// releasing it and all the savepoints taken after it.
+#[inline]
+pub fn rollback_to_savepoint(id: u32) -> Result<(), Errno> {
+    cvt(unsafe { raw::_rollback_to_savepoint(id) })
+}
+
+/// Releases the savepoint `id` and all the savepoints taken after it,
+/// keeping the changes made since.
+#[inline]
+pub fn release_savepoint(id: u32) -> Result<(), Errno> {
+    cvt(unsafe { raw::_release_savepoint(id) })
+}
+
 /// Unschedule a reducer using the same `id` generated as when it was scheduled.
 ///
 /// This assumes that the reducer hasn't already been executed.
@@ -599,6 +996,34 @@ pub fn cancel_reducer(id: u64) {
     unsafe { raw::_cancel_reducer(id) }
 }
 
+/// Emits an event of the type `name`, encoded as BSATN in `data`,
+/// to be delivered to the subscribed clients if the current reducer's transaction commits.
+#[inline]
+pub fn emit_event(name: &str, data: &[u8]) -> Result<(), Errno> {
+    cvt(unsafe { raw::_emit_event(name.as_ptr(), name.len(), data.as_ptr(), data.len()) })
+}
+
+/// Sets the value the current reducer returns to its caller, encoded as BSATN in `data`,
+/// to be delivered if the reducer's transaction commits.
+#[inline]
+pub fn set_return_value(data: &[u8]) -> Result<(), Errno> {
+    cvt(unsafe { raw::_set_return_value(data.as_ptr(), data.len()) })
+}
+
+/// Sends a call of `reducer` of the database at `address` with the BSATN encoded `args`,
+/// to be made once the current reducer's transaction commits.
+#[inline]
+pub fn send_message(address: &[u8; 16], reducer: &str, args: &[u8]) -> Result<(), Errno> {
+    cvt(unsafe { raw::_send_message(address, reducer.as_ptr(), reducer.len(), args.as_ptr(), args.len()) })
+}
+
+/// Describes the arguments of the reducer `name`,
+/// returning a buffer holding them as a BSATN encoded `ProductType`.
+#[inline]
+pub fn describe_reducer(name: &str) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_describe_reducer(name.as_ptr(), name.len(), out)) }
+}
+
 pub use raw::{Buffer, BufferIter};
 
 impl Buffer {
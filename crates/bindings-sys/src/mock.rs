@@ -0,0 +1,748 @@
+//! An in-process stand-in for the host, replacing the sys calls with the `testing` feature,
+//! so that modules can be unit tested natively with `cargo test`.
+//!
+//! Buffers, iterators, logging, scheduling and events are handled here,
+//! while the tables are left to a [`MockHost`],
+//! which the `spacetimedb` crate implements in its `testing` module.
+//!
+//! The state of the mock is thread-local,
+//! so each `#[test]`, running on a thread of its own, starts out empty.
+//!
+//! To exercise the error handling of a module, the mock can also fail some of the sys calls on purpose,
+//! as chosen by a seeded [`FaultPlan`].
+
+#![deny(unsafe_op_in_unsafe_fn)]
+
+use core::mem::ManuallyDrop;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::raw::{Buffer, BufferIter};
+use crate::Errno;
+
+/// The tables of a mock host, to which the table sys calls are delegated.
+///
+/// Rows, values and schemas are exchanged bsatn encoded, as they are with a real host.
+pub trait MockHost {
+    /// Returns the id of the table `name`.
+    fn get_table_id(&mut self, name: &str) -> Result<u32, Errno>;
+    /// Creates the index `index_name` on the columns `col_ids` of the table `table_id`.
+    fn create_index(&mut self, index_name: &str, table_id: u32, index_type: u8, col_ids: &[u8]) -> Result<(), Errno>;
+    /// Returns the concatenated rows of the table `table_id` where the column `col_id` equals `value`.
+    fn iter_by_col_eq(&mut self, table_id: u32, col_id: u32, value: &[u8]) -> Result<Vec<u8>, Errno>;
+    /// Returns the concatenated rows of the table `table_id` where the string column `col_id` matches `query`.
+    fn iter_by_col_match(&mut self, table_id: u32, col_id: u32, query: &str) -> Result<Vec<u8>, Errno>;
+    /// Returns the concatenated rows of the table `table_id` where the point column `col_id`
+    /// lies within the box from `min` to `max`.
+    fn iter_by_col_box(&mut self, table_id: u32, col_id: u32, min: &[u8], max: &[u8]) -> Result<Vec<u8>, Errno>;
+    /// Inserts `row` into the table `table_id`,
+    /// writing the values generated for its auto-incremented columns back into `row`.
+    ///
+    /// When `row` violates a unique constraint, the id of the column of that constraint is also returned.
+    fn insert(&mut self, table_id: u32, row: &mut [u8]) -> Result<(), (Errno, Option<u32>)>;
+    /// Deletes the rows of the table `table_id` where the column `col_id` equals `value`,
+    /// returning how many were deleted.
+    fn delete_by_col_eq(&mut self, table_id: u32, col_id: u32, value: &[u8]) -> Result<u32, Errno>;
+    /// Deletes the concatenated rows `src_rows` from the table `src_table_id`
+    /// and inserts the concatenated rows `dst_rows` into the table `dst_table_id`, all or nothing,
+    /// returning how many rows were deleted.
+    fn move_rows(
+        &mut self,
+        src_table_id: u32,
+        src_rows: &[u8],
+        dst_table_id: u32,
+        dst_rows: &[u8],
+    ) -> Result<u32, Errno>;
+    /// Returns the schema of the table `table_id`, followed by each of its rows passing `filter`.
+    fn iter(&mut self, table_id: u32, filter: Option<&[u8]>) -> Result<Vec<Box<[u8]>>, Errno>;
+    /// Returns the schema of the projection of the table `table_id` onto the columns `cols`,
+    /// followed by each of its rows projected onto them.
+    fn iter_projected(&mut self, table_id: u32, cols: &[u8]) -> Result<Vec<Box<[u8]>>, Errno>;
+    /// Takes a savepoint of the tables, returning its id.
+    fn savepoint(&mut self) -> u32;
+    /// Restores the tables to the savepoint `id`, releasing it and all the savepoints taken after it.
+    fn rollback_to_savepoint(&mut self, id: u32) -> Result<(), Errno>;
+    /// Releases the savepoint `id` and all the savepoints taken after it.
+    fn release_savepoint(&mut self, id: u32) -> Result<(), Errno>;
+    /// Stores the blob `data` under `key`, returning its hash.
+    fn blob_put(&mut self, key: &str, data: &[u8]) -> Result<[u8; 32], Errno>;
+    /// Returns the blob stored under `key`.
+    fn blob_get(&mut self, key: &str) -> Result<Vec<u8>, Errno>;
+    /// Deletes the blob stored under `key`.
+    fn blob_delete(&mut self, key: &str) -> Result<(), Errno>;
+    /// Stores `data` out of the rows that will reference it, returning the encoded key they hold.
+    fn large_bytes_put(&mut self, data: &[u8]) -> Vec<u8>;
+    /// Returns the bytes stored under the encoded `key`.
+    fn large_bytes_get(&mut self, key: &[u8]) -> Result<Vec<u8>, Errno>;
+}
+
+/// Makes the [`MockHost`] of each thread.
+static HOST_FACTORY: Mutex<Option<fn() -> Box<dyn MockHost>>> = Mutex::new(None);
+
+/// Sets the function making the [`MockHost`] of each thread, when it first needs one.
+pub fn set_host_factory(factory: fn() -> Box<dyn MockHost>) {
+    *HOST_FACTORY.lock().unwrap() = Some(factory);
+}
+
+/// A reducer scheduled through the mock host, which it never runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledReducer {
+    /// The id the reducer was scheduled under.
+    pub id: u64,
+    /// The name of the reducer.
+    pub name: String,
+    /// The bsatn encoded arguments of the reducer.
+    pub args: Vec<u8>,
+    /// When the reducer is scheduled, in microseconds since the UNIX epoch.
+    pub time: u64,
+}
+
+/// The failures the mock host injects into the sys calls of the current thread, see [`inject_faults`].
+///
+/// Which calls fail is drawn from a generator seeded with `seed`,
+/// so that a test making the same calls sees the same failures on each run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultPlan {
+    /// The seed of the generator deciding which calls fail.
+    pub seed: u64,
+    /// The probability, from 0.0 to 1.0, that an insert fails with `UNIQUE_ALREADY_EXISTS`,
+    /// without inserting the row.
+    pub insert_exists: f64,
+    /// The probability, from 0.0 to 1.0, that a table iterator ends early,
+    /// after only some of the rows it would have returned.
+    pub partial_iter: f64,
+    /// The most microseconds by which a scheduled reducer is made to fire late.
+    ///
+    /// Each reducer scheduled is delayed by a random amount up to this,
+    /// which shows in the `time` of its [`ScheduledReducer`].
+    pub max_schedule_delay_micros: u64,
+}
+
+/// A [`FaultPlan`] being carried out.
+struct Faults {
+    plan: FaultPlan,
+    /// The state of the SplitMix64 generator seeded with `plan.seed`.
+    rng: u64,
+}
+
+impl Faults {
+    fn new(plan: FaultPlan) -> Self {
+        let rng = plan.seed;
+        Self { plan, rng }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns `true` with the probability `p`.
+    fn chance(&mut self, p: f64) -> bool {
+        // The top 53 bits make a uniformly distributed `f64` in `[0, 1)`.
+        p > 0.0 && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    /// Returns a random number in `0..=max`.
+    fn up_to(&mut self, max: u64) -> u64 {
+        match max.checked_add(1) {
+            Some(bound) => self.next_u64() % bound,
+            None => self.next_u64(),
+        }
+    }
+}
+
+/// A message sent through the mock host, which it never delivers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentMessage {
+    /// The address of the database the message is sent to.
+    pub address: [u8; 16],
+    /// The name of the reducer the message calls.
+    pub reducer: String,
+    /// The bsatn encoded arguments of the reducer.
+    pub args: Vec<u8>,
+}
+
+/// An event emitted through the mock host.
+struct EmittedEvent {
+    /// The name of the type of the event.
+    name: String,
+    /// The bsatn encoded event.
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct MockState {
+    host: Option<Box<dyn MockHost>>,
+    /// The next key of a buffer or an iterator, which draw from the same keys.
+    next_key: u32,
+    buffers: HashMap<u32, Box<[u8]>>,
+    iters: HashMap<u32, std::vec::IntoIter<Box<[u8]>>>,
+    next_schedule_id: u64,
+    scheduled: Vec<ScheduledReducer>,
+    events: Vec<EmittedEvent>,
+    sent: Vec<SentMessage>,
+    faults: Option<Faults>,
+}
+
+impl MockState {
+    fn next_key(&mut self) -> u32 {
+        let key = self.next_key;
+        // Skip `Buffer::INVALID`.
+        self.next_key = self.next_key.wrapping_add(1) % u32::MAX;
+        key
+    }
+
+    fn alloc_buffer(&mut self, data: Box<[u8]>) -> Buffer {
+        let raw = self.next_key();
+        self.buffers.insert(raw, data);
+        Buffer { raw }
+    }
+
+    fn host(&mut self) -> &mut dyn MockHost {
+        let host = self.host.get_or_insert_with(|| {
+            let factory = *HOST_FACTORY.lock().unwrap();
+            let factory = factory.expect(
+                "no mock host to run the sys call; \
+                 enable the `testing` feature of the `spacetimedb` crate rather than that of `spacetimedb-bindings-sys`",
+            );
+            factory()
+        });
+        &mut **host
+    }
+
+    /// Returns whether the insert about to be made should fail.
+    fn fail_insert(&mut self) -> bool {
+        let faults = self.faults.as_mut();
+        faults.map_or(false, |faults| faults.chance(faults.plan.insert_exists))
+    }
+
+    /// Drops some of the rows following the schema in `items`, if the iterator should end early.
+    fn truncate_iter(&mut self, items: &mut Vec<Box<[u8]>>) {
+        let Some(faults) = &mut self.faults else { return };
+        // The first item is the schema, which is always returned.
+        let rows = items.len().saturating_sub(1) as u64;
+        if rows > 0 && faults.chance(faults.plan.partial_iter) {
+            let kept = faults.up_to(rows - 1);
+            items.truncate(1 + kept as usize);
+        }
+    }
+
+    /// Returns `time` delayed as a scheduled reducer should fire.
+    fn schedule_time(&mut self, time: u64) -> u64 {
+        let Some(faults) = &mut self.faults else { return time };
+        let delay = faults.up_to(faults.plan.max_schedule_delay_micros);
+        time.saturating_add(delay)
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<MockState> = RefCell::default();
+}
+
+fn with_state<R>(f: impl FnOnce(&mut MockState) -> R) -> R {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+/// Clears the tables, scheduled reducers, emitted events, sent messages and fault plan of the current thread.
+pub fn reset() {
+    with_state(|state| *state = MockState::default())
+}
+
+/// Makes the sys calls of the current thread fail as planned by `plan`, from now on,
+/// replacing any plan set before.
+pub fn inject_faults(plan: FaultPlan) {
+    with_state(|state| state.faults = Some(Faults::new(plan)))
+}
+
+/// Stops injecting failures into the sys calls of the current thread.
+pub fn clear_faults() {
+    with_state(|state| state.faults = None)
+}
+
+/// Returns the reducers scheduled on the current thread which haven't been cancelled.
+pub fn scheduled_reducers() -> Vec<ScheduledReducer> {
+    with_state(|state| state.scheduled.clone())
+}
+
+/// Removes and returns the messages sent on the current thread, in the order they were sent.
+pub fn take_sent_messages() -> Vec<SentMessage> {
+    with_state(|state| std::mem::take(&mut state.sent))
+}
+
+/// Removes and returns the bsatn encoded events of the type `name` emitted on the current thread,
+/// in the order they were emitted.
+pub fn take_emitted_events(name: &str) -> Vec<Vec<u8>> {
+    with_state(|state| {
+        let (taken, kept) = std::mem::take(&mut state.events)
+            .into_iter()
+            .partition::<Vec<_>, _>(|event| event.name == name);
+        state.events = kept;
+        taken.into_iter().map(|event| event.data).collect()
+    })
+}
+
+/// Converts the result of a sys call into its status code, writing the successful value to `out`.
+///
+/// # Safety
+///
+/// `out` must be valid for writes.
+unsafe fn write_out<T>(res: Result<T, Errno>, out: *mut T) -> u16 {
+    match res {
+        Ok(val) => {
+            // SAFETY: The caller promised that `out` is valid for writes.
+            unsafe { out.write(val) };
+            0
+        }
+        Err(err) => err.code(),
+    }
+}
+
+/// Returns the slice `(ptr, len)`, which may be null when `len == 0`.
+///
+/// # Safety
+///
+/// Unless `len == 0`, `ptr` must be valid for reads of `len` bytes for `'a`.
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        // SAFETY: Upheld by the caller.
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+}
+
+/// Returns the UTF-8 string `(ptr, len)`, decoded lossily.
+///
+/// # Safety
+///
+/// Same as for [`slice`].
+unsafe fn str_lossy<'a>(ptr: *const u8, len: usize) -> std::borrow::Cow<'a, str> {
+    // SAFETY: Upheld by the caller.
+    String::from_utf8_lossy(unsafe { slice(ptr, len) })
+}
+
+/// The sys calls of [`crate::raw`], with the same signatures, served by the mock host.
+///
+/// They're documented on the `extern` block they stand in for.
+#[allow(clippy::missing_safety_doc)]
+pub(crate) mod sys_calls {
+    use super::*;
+
+    pub unsafe fn _get_table_id(name: *const u8, name_len: usize, out: *mut u32) -> u16 {
+        let name = unsafe { str_lossy(name, name_len) };
+        let res = with_state(|state| state.host().get_table_id(&name));
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _create_index(
+        index_name: *const u8,
+        index_name_len: usize,
+        table_id: u32,
+        index_type: u8,
+        col_ids: *const u8,
+        col_len: usize,
+    ) -> u16 {
+        let index_name = unsafe { str_lossy(index_name, index_name_len) };
+        let col_ids = unsafe { slice(col_ids, col_len) };
+        let res = with_state(|state| state.host().create_index(&index_name, table_id, index_type, col_ids));
+        res.err().map_or(0, Errno::code)
+    }
+
+    pub unsafe fn _iter_by_col_eq(
+        table_id: u32,
+        col_id: u32,
+        value: *const u8,
+        value_len: usize,
+        out: *mut Buffer,
+    ) -> u16 {
+        let value = unsafe { slice(value, value_len) };
+        let res = with_state(|state| {
+            let rows = state.host().iter_by_col_eq(table_id, col_id, value)?;
+            Ok(state.alloc_buffer(rows.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _iter_by_col_match(
+        table_id: u32,
+        col_id: u32,
+        query: *const u8,
+        query_len: usize,
+        out: *mut Buffer,
+    ) -> u16 {
+        let query = unsafe { str_lossy(query, query_len) };
+        let res = with_state(|state| {
+            let rows = state.host().iter_by_col_match(table_id, col_id, &query)?;
+            Ok(state.alloc_buffer(rows.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _iter_by_col_box(
+        table_id: u32,
+        col_id: u32,
+        min: *const u8,
+        min_len: usize,
+        max: *const u8,
+        max_len: usize,
+        out: *mut Buffer,
+    ) -> u16 {
+        let (min, max) = unsafe { (slice(min, min_len), slice(max, max_len)) };
+        let res = with_state(|state| {
+            let rows = state.host().iter_by_col_box(table_id, col_id, min, max)?;
+            Ok(state.alloc_buffer(rows.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _insert(table_id: u32, row: *mut u8, row_len: usize) -> u16 {
+        let row: &mut [u8] = if row_len == 0 {
+            &mut []
+        } else {
+            unsafe { std::slice::from_raw_parts_mut(row, row_len) }
+        };
+        let res = with_state(|state| {
+            if state.fail_insert() {
+                return Err((Errno::UNIQUE_ALREADY_EXISTS, None));
+            }
+            state.host().insert(table_id, row)
+        });
+        res.err().map_or(0, |(errno, _)| errno.code())
+    }
+
+    pub unsafe fn _try_insert(table_id: u32, row: *mut u8, row_len: usize, conflict: *mut u32) -> u16 {
+        let row: &mut [u8] = if row_len == 0 {
+            &mut []
+        } else {
+            unsafe { std::slice::from_raw_parts_mut(row, row_len) }
+        };
+        let res = with_state(|state| {
+            if state.fail_insert() {
+                // The injected failure has no conflicting column, so `conflict` is left alone.
+                return Err((Errno::UNIQUE_ALREADY_EXISTS, None));
+            }
+            state.host().insert(table_id, row)
+        });
+        match res {
+            Ok(()) => 0,
+            Err((errno, col_id)) => {
+                if let Some(col_id) = col_id {
+                    unsafe { *conflict = col_id };
+                }
+                errno.code()
+            }
+        }
+    }
+
+    pub unsafe fn _delete_by_col_eq(
+        table_id: u32,
+        col_id: u32,
+        value: *const u8,
+        value_len: usize,
+        out: *mut u32,
+    ) -> u16 {
+        let value = unsafe { slice(value, value_len) };
+        let res = with_state(|state| state.host().delete_by_col_eq(table_id, col_id, value));
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _move_rows(
+        src_table_id: u32,
+        src_rows: *const u8,
+        src_rows_len: usize,
+        dst_table_id: u32,
+        dst_rows: *const u8,
+        dst_rows_len: usize,
+        out: *mut u32,
+    ) -> u16 {
+        let src_rows = unsafe { slice(src_rows, src_rows_len) };
+        let dst_rows = unsafe { slice(dst_rows, dst_rows_len) };
+        let res = with_state(|state| state.host().move_rows(src_table_id, src_rows, dst_table_id, dst_rows));
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _remaining_energy(out: *mut u64) -> u16 {
+        // The mock host doesn't meter reducers.
+        unsafe { write_out(Ok(u64::MAX), out) }
+    }
+
+    pub unsafe fn _tx_offset(out: *mut u64) -> u16 {
+        // The mock host doesn't keep a commit log.
+        unsafe { write_out(Ok(0), out) }
+    }
+
+    pub unsafe fn _table_version(_table_id: u32, out: *mut u64) -> u16 {
+        // The mock host doesn't commit transactions, so no table has been changed by one.
+        unsafe { write_out(Ok(0), out) }
+    }
+
+    pub unsafe fn _table_row_type(table_id: u32, out: *mut Buffer) -> u16 {
+        let res = with_state(|state| {
+            // The schema of the table comes ahead of its rows.
+            let schema = state
+                .host()
+                .iter(table_id, None)?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            Ok(state.alloc_buffer(schema))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _blob_put(
+        key: *const u8,
+        key_len: usize,
+        data: *const u8,
+        data_len: usize,
+        out: *mut [u8; 32],
+    ) -> u16 {
+        let key = unsafe { str_lossy(key, key_len) };
+        let data = unsafe { slice(data, data_len) };
+        let res = with_state(|state| state.host().blob_put(&key, data));
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _blob_get(key: *const u8, key_len: usize, out: *mut Buffer) -> u16 {
+        let key = unsafe { str_lossy(key, key_len) };
+        let res = with_state(|state| {
+            let data = state.host().blob_get(&key)?;
+            Ok(state.alloc_buffer(data.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _blob_delete(key: *const u8, key_len: usize) -> u16 {
+        let key = unsafe { str_lossy(key, key_len) };
+        let res = with_state(|state| state.host().blob_delete(&key));
+        res.err().map_or(0, Errno::code)
+    }
+
+    pub unsafe fn _large_bytes_put(data: *const u8, data_len: usize, out: *mut Buffer) -> u16 {
+        let data = unsafe { slice(data, data_len) };
+        let res = with_state(|state| {
+            let key = state.host().large_bytes_put(data);
+            Ok(state.alloc_buffer(key.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _large_bytes_get(key: *const u8, key_len: usize, out: *mut Buffer) -> u16 {
+        let key = unsafe { slice(key, key_len) };
+        let res = with_state(|state| {
+            let data = state.host().large_bytes_get(key)?;
+            Ok(state.alloc_buffer(data.into()))
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _savepoint(out: *mut u32) -> u16 {
+        let id = with_state(|state| state.host().savepoint());
+        unsafe { write_out(Ok(id), out) }
+    }
+
+    pub unsafe fn _rollback_to_savepoint(id: u32) -> u16 {
+        let res = with_state(|state| state.host().rollback_to_savepoint(id));
+        res.err().map_or(0, Errno::code)
+    }
+
+    pub unsafe fn _release_savepoint(id: u32) -> u16 {
+        let res = with_state(|state| state.host().release_savepoint(id));
+        res.err().map_or(0, Errno::code)
+    }
+
+    pub unsafe fn _iter_start(table_id: u32, out: *mut BufferIter) -> u16 {
+        unsafe { iter_start(table_id, None, out) }
+    }
+
+    pub unsafe fn _iter_start_filtered(
+        table_id: u32,
+        filter: *const u8,
+        filter_len: usize,
+        out: *mut BufferIter,
+    ) -> u16 {
+        let filter = unsafe { slice(filter, filter_len) };
+        unsafe { iter_start(table_id, Some(filter), out) }
+    }
+
+    unsafe fn iter_start(table_id: u32, filter: Option<&[u8]>, out: *mut BufferIter) -> u16 {
+        let res = with_state(|state| {
+            let mut items = state.host().iter(table_id, filter)?;
+            state.truncate_iter(&mut items);
+            let raw = state.next_key();
+            state.iters.insert(raw, items.into_iter());
+            Ok(BufferIter { raw })
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _iter_start_projected(table_id: u32, cols: *const u8, cols_len: usize, out: *mut BufferIter) -> u16 {
+        let cols = unsafe { slice(cols, cols_len) };
+        let res = with_state(|state| {
+            let mut items = state.host().iter_projected(table_id, cols)?;
+            state.truncate_iter(&mut items);
+            let raw = state.next_key();
+            state.iters.insert(raw, items.into_iter());
+            Ok(BufferIter { raw })
+        });
+        unsafe { write_out(res, out) }
+    }
+
+    pub unsafe fn _iter_next(iter: ManuallyDrop<BufferIter>, out: *mut Buffer) -> u16 {
+        let buf = with_state(|state| {
+            let item = state
+                .iters
+                .get_mut(&iter.raw)
+                .expect("no such iterator in the mock host")
+                .next();
+            match item {
+                Some(item) => state.alloc_buffer(item),
+                None => Buffer::INVALID,
+            }
+        });
+        unsafe { write_out(Ok(buf), out) }
+    }
+
+    pub unsafe fn _iter_drop(iter: ManuallyDrop<BufferIter>) -> u16 {
+        with_state(|state| state.iters.remove(&iter.raw));
+        0
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn _console_log(
+        level: u8,
+        target: *const u8,
+        target_len: usize,
+        filename: *const u8,
+        filename_len: usize,
+        line_number: u32,
+        text: *const u8,
+        text_len: usize,
+    ) {
+        let level = match level {
+            crate::raw::LOG_LEVEL_ERROR => "ERROR",
+            crate::raw::LOG_LEVEL_WARN => "WARN",
+            crate::raw::LOG_LEVEL_INFO => "INFO",
+            crate::raw::LOG_LEVEL_DEBUG => "DEBUG",
+            crate::raw::LOG_LEVEL_TRACE => "TRACE",
+            _ => "PANIC",
+        };
+        let target = unsafe { str_lossy(target, target_len) };
+        let filename = unsafe { str_lossy(filename, filename_len) };
+        let text = unsafe { str_lossy(text, text_len) };
+        // `eprintln!` is captured by the test harness, like `println!`.
+        eprintln!("{level} {target} {filename}:{line_number}: {text}");
+    }
+
+    /// The fields are left out, as the mock can't decode them.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn _console_log_structured(
+        level: u8,
+        target: *const u8,
+        target_len: usize,
+        filename: *const u8,
+        filename_len: usize,
+        line_number: u32,
+        text: *const u8,
+        text_len: usize,
+        _fields: *const u8,
+        _fields_len: usize,
+    ) {
+        unsafe {
+            _console_log(
+                level,
+                target,
+                target_len,
+                filename,
+                filename_len,
+                line_number,
+                text,
+                text_len,
+            )
+        }
+    }
+
+    pub unsafe fn _schedule_reducer(
+        name: *const u8,
+        name_len: usize,
+        args: *const u8,
+        args_len: usize,
+        time: u64,
+        out: *mut u64,
+    ) {
+        let name = unsafe { str_lossy(name, name_len) }.into_owned();
+        let args = unsafe { slice(args, args_len) }.to_vec();
+        let id = with_state(|state| {
+            let id = state.next_schedule_id;
+            state.next_schedule_id += 1;
+            let time = state.schedule_time(time);
+            state.scheduled.push(ScheduledReducer { id, name, args, time });
+            id
+        });
+        unsafe { out.write(id) }
+    }
+
+    pub unsafe fn _cancel_reducer(id: u64) {
+        with_state(|state| state.scheduled.retain(|scheduled| scheduled.id != id))
+    }
+
+    pub unsafe fn _emit_event(name: *const u8, name_len: usize, data: *const u8, data_len: usize) -> u16 {
+        let name = unsafe { str_lossy(name, name_len) }.into_owned();
+        let data = unsafe { slice(data, data_len) }.to_vec();
+        with_state(|state| state.events.push(EmittedEvent { name, data }));
+        0
+    }
+
+    pub unsafe fn _set_return_value(_data: *const u8, _data_len: usize) -> u16 {
+        // Reducers are called directly in tests, which get their values from that call.
+        0
+    }
+
+    pub unsafe fn _send_message(
+        address: *const [u8; 16],
+        reducer: *const u8,
+        reducer_len: usize,
+        args: *const u8,
+        args_len: usize,
+    ) -> u16 {
+        let message = SentMessage {
+            // SAFETY: The caller promised that `address` is valid for reads.
+            address: unsafe { *address },
+            reducer: unsafe { str_lossy(reducer, reducer_len) }.into_owned(),
+            args: unsafe { slice(args, args_len) }.to_vec(),
+        };
+        with_state(|state| state.sent.push(message));
+        0
+    }
+
+    pub unsafe fn _describe_reducer(_name: *const u8, _name_len: usize, _out: *mut Buffer) -> u16 {
+        // Reducers are only described to a real host, when it loads the module.
+        Errno::NO_SUCH_REDUCER.code()
+    }
+
+    pub unsafe fn _buffer_len(bufh: ManuallyDrop<Buffer>) -> usize {
+        with_state(|state| {
+            state
+                .buffers
+                .get(&bufh.raw)
+                .expect("no such buffer in the mock host")
+                .len()
+        })
+    }
+
+    pub unsafe fn _buffer_consume(bufh: Buffer, into: *mut u8, len: usize) {
+        let data = with_state(|state| state.buffers.remove(&bufh.raw)).expect("no such buffer in the mock host");
+        assert_eq!(data.len(), len, "buffer consumed with the wrong length");
+        // SAFETY: The caller promised that `into` is valid for writes of `len` bytes.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), into, len) }
+    }
+
+    pub unsafe fn _buffer_alloc(data: *const u8, data_len: usize) -> Buffer {
+        let data = unsafe { slice(data, data_len) }.into();
+        with_state(|state| state.alloc_buffer(data))
+    }
+}
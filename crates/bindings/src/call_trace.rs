@@ -0,0 +1,111 @@
+//! Keeps track of the reducer being called and the table operations it has performed so far,
+//! which our panic hook logs along with a panic for post-mortem debugging.
+
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+
+use spacetimedb_lib::hash::{hash_bytes, Hash};
+use spacetimedb_lib::logging::{LogField, LogValue};
+
+scoped_tls::scoped_thread_local! {
+    static CURRENT_CALL: RefCell<CallTrace>
+}
+
+/// The most distinct runs of table operations kept for a call,
+/// so that a reducer looping over many tables doesn't grow the trace unbounded.
+const MAX_TABLE_OPS: usize = 64;
+
+/// Runs `f` as the call of the reducer `reducer` with the bsatn encoded `args`,
+/// recording the table operations it performs.
+pub(crate) fn with_call_traced<R>(reducer: &'static str, args: &[u8], f: impl FnOnce() -> R) -> R {
+    let trace = CallTrace {
+        reducer,
+        args_hash: hash_bytes(args),
+        ops: Vec::new(),
+        dropped_ops: 0,
+    };
+    CURRENT_CALL.set(&RefCell::new(trace), f)
+}
+
+/// A kind of operation on a table.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableOp {
+    Insert,
+    Delete,
+    Scan,
+}
+
+impl fmt::Display for TableOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Insert => "insert",
+            Self::Delete => "delete",
+            Self::Scan => "scan",
+        })
+    }
+}
+
+/// Records that the reducer being called, if any, performed `op` on the table identified by `table_id`.
+pub(crate) fn record(op: TableOp, table_id: u32) {
+    if CURRENT_CALL.is_set() {
+        CURRENT_CALL.with(|trace| trace.borrow_mut().record(op, table_id))
+    }
+}
+
+/// Returns the fields describing the reducer being called, if any, for the log of a panic in it.
+pub(crate) fn panic_fields() -> Option<Vec<LogField>> {
+    if !CURRENT_CALL.is_set() {
+        return None;
+    }
+    // The panic may have happened while recording, in which case the trace is left out.
+    CURRENT_CALL.with(|trace| Some(trace.try_borrow().ok()?.fields()))
+}
+
+/// The reducer being called and the table operations it has performed so far.
+struct CallTrace {
+    reducer: &'static str,
+    /// The hash of the bsatn encoded arguments,
+    /// identifying the arguments of a failing call without logging their possibly sensitive values.
+    args_hash: Hash,
+    /// The runs of consecutive operations of the same kind on the same table,
+    /// as the operation, the table id and the number of operations.
+    ops: Vec<(TableOp, u32, u32)>,
+    /// The number of operations performed after `ops` was full.
+    dropped_ops: u32,
+}
+
+impl CallTrace {
+    fn record(&mut self, op: TableOp, table_id: u32) {
+        match self.ops.last_mut() {
+            Some((last_op, last_table_id, count)) if *last_op == op && *last_table_id == table_id => {
+                *count = count.saturating_add(1);
+            }
+            _ if self.ops.len() == MAX_TABLE_OPS => self.dropped_ops = self.dropped_ops.saturating_add(1),
+            _ => self.ops.push((op, table_id, 1)),
+        }
+    }
+
+    fn fields(&self) -> Vec<LogField> {
+        // E.g., `insert 4097 x3, scan 4098 x1`.
+        let mut ops = String::new();
+        for (op, table_id, count) in &self.ops {
+            if !ops.is_empty() {
+                ops.push_str(", ");
+            }
+            write!(ops, "{op} {table_id} x{count}").unwrap();
+        }
+        if self.dropped_ops > 0 {
+            write!(ops, ", and {} more", self.dropped_ops).unwrap();
+        }
+
+        let field = |key: &str, value: String| LogField {
+            key: key.into(),
+            value: LogValue::Str(value),
+        };
+        vec![
+            field("reducer", self.reducer.into()),
+            field("args_hash", self.args_hash.to_hex()),
+            field("table_ops", ops),
+        ]
+    }
+}
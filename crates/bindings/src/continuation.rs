@@ -0,0 +1,43 @@
+//! Defines the continuations an `async` reducer can `.await`.
+
+use std::time::Duration;
+
+use spacetimedb_lib::bsatn;
+
+use crate::{rt, sys, Timestamp};
+
+/// Something an `async` reducer can `.await`,
+/// which calls back into the module once it has completed.
+///
+/// `#[spacetimedb(reducer)]` lowers each `.await` in an `async fn` into a call to [`Continuation::schedule`],
+/// having saved the variables in scope to a hidden table,
+/// and the rest of the function into a hidden reducer that picks them up again.
+pub trait Continuation {
+    /// Arranges for the reducer `resume` to be called with the single argument `id`
+    /// once `self` has completed.
+    fn schedule(self, resume: &'static str, id: u64);
+}
+
+/// A timer that completes at a point in time, as returned by [`sleep`] and [`sleep_until`].
+#[must_use = "a timer does nothing unless `.await`ed in an `async` reducer"]
+pub struct Sleep {
+    until: Timestamp,
+}
+
+/// Returns a timer completing `duration` from now, to `.await` in an `async` reducer.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(rt::schedule_in(duration))
+}
+
+/// Returns a timer completing at `time`, to `.await` in an `async` reducer.
+pub fn sleep_until(time: Timestamp) -> Sleep {
+    Sleep { until: time }
+}
+
+impl Continuation for Sleep {
+    fn schedule(self, resume: &'static str, id: u64) {
+        // The arguments `(id,)` are encoded as `id` alone.
+        let args = bsatn::to_vec(&id).unwrap();
+        sys::schedule(resume, &args, self.until.micros_since_epoch);
+    }
+}
@@ -0,0 +1,180 @@
+//! Defines a signed `Duration` abstraction, the difference between two timestamps.
+
+use std::num::TryFromIntError;
+use std::ops::{Add, Neg, Sub};
+
+use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, impl_st};
+
+use crate::Timestamp;
+
+/// A signed span of time measured in micro seconds,
+/// like the difference between two [`Timestamp`]s.
+///
+/// Unlike [`std::time::Duration`], this can be negative, e.g., when subtracting a later timestamp,
+/// and is stored in a column as an `i64` of micro seconds,
+/// so that it can be used in SQL arithmetic like `last_used + cooldown < '2023-08-01T12:00:00Z'`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    /// The number of micro seconds.
+    micros: i64,
+}
+
+impl Duration {
+    /// The duration of no time at all.
+    pub const ZERO: Self = Duration { micros: 0 };
+
+    /// Returns a duration of `micros` micro seconds.
+    pub const fn from_micros(micros: i64) -> Self {
+        Duration { micros }
+    }
+
+    /// Returns a duration of `millis` milli seconds.
+    ///
+    /// Panics when an `i64` of micro seconds is overflowed.
+    pub fn from_millis(millis: i64) -> Self {
+        Self::from_micros(
+            millis
+                .checked_mul(1_000)
+                .expect("overflow when converting millis to duration"),
+        )
+    }
+
+    /// Returns a duration of `secs` seconds.
+    ///
+    /// Panics when an `i64` of micro seconds is overflowed.
+    pub fn from_secs(secs: i64) -> Self {
+        Self::from_micros(
+            secs.checked_mul(1_000_000)
+                .expect("overflow when converting secs to duration"),
+        )
+    }
+
+    /// Returns the number of micro seconds in this duration.
+    pub const fn as_micros(&self) -> i64 {
+        self.micros
+    }
+
+    /// Returns whether this duration is less than zero.
+    pub const fn is_negative(&self) -> bool {
+        self.micros < 0
+    }
+
+    /// Returns the length of this duration as a [`std::time::Duration`], regardless of its sign.
+    pub const fn unsigned_abs(&self) -> std::time::Duration {
+        std::time::Duration::from_micros(self.micros.unsigned_abs())
+    }
+
+    /// Returns the sum of `self` and `other`.
+    ///
+    /// Returns `None` when an `i64` is overflowed.
+    pub fn checked_add(&self, other: Duration) -> Option<Self> {
+        self.micros.checked_add(other.micros).map(Self::from_micros)
+    }
+
+    /// Returns `other` subtracted from `self`.
+    ///
+    /// Returns `None` when an `i64` is overflowed.
+    pub fn checked_sub(&self, other: Duration) -> Option<Self> {
+        self.micros.checked_sub(other.micros).map(Self::from_micros)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add(rhs).expect("overflow when adding durations")
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub(rhs).expect("overflow when subtracting durations")
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Self::Output {
+        let micros = self.micros.checked_neg().expect("overflow when negating duration");
+        Self::from_micros(micros)
+    }
+}
+
+/// Fails if `duration` is more than `i64::MAX` micro seconds.
+impl TryFrom<std::time::Duration> for Duration {
+    type Error = TryFromIntError;
+
+    fn try_from(duration: std::time::Duration) -> Result<Self, Self::Error> {
+        duration.as_micros().try_into().map(Self::from_micros)
+    }
+}
+
+/// Fails if `duration` is negative.
+impl TryFrom<Duration> for std::time::Duration {
+    type Error = TryFromIntError;
+
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        duration.micros.try_into().map(std::time::Duration::from_micros)
+    }
+}
+
+impl Timestamp {
+    /// Returns a timestamp with the signed `duration` added to `self`.
+    ///
+    /// Returns `None` when a `u64` is overflowed.
+    pub fn checked_add_signed(&self, duration: Duration) -> Option<Self> {
+        let micros_since_epoch = self.micros_since_epoch.checked_add_signed(duration.micros)?;
+        Some(Self { micros_since_epoch })
+    }
+
+    /// Returns a timestamp with the signed `duration` subtracted from `self`.
+    ///
+    /// Returns `None` when a `u64` is overflowed.
+    pub fn checked_sub_signed(&self, duration: Duration) -> Option<Self> {
+        self.checked_add_signed(Duration::from_micros(duration.micros.checked_neg()?))
+    }
+
+    /// Returns the signed duration from `earlier` to `self`,
+    /// which is negative when `earlier` is actually after `self`.
+    ///
+    /// Returns `None` when an `i64` is overflowed.
+    pub fn checked_signed_duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        let micros = self.micros_since_epoch as i128 - earlier.micros_since_epoch as i128;
+        micros.try_into().ok().map(Duration::from_micros)
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, rhs: Timestamp) -> Self::Output {
+        self.checked_signed_duration_since(rhs)
+            .expect("overflow when subtracting timestamps")
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add_signed(rhs)
+            .expect("overflow when adding duration to timestamp")
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub_signed(rhs)
+            .expect("underflow when subtracting duration from timestamp")
+    }
+}
+
+impl_st!([] Duration, _ts => spacetimedb_lib::AlgebraicType::I64);
+impl_deserialize!([] Duration, de => i64::deserialize(de).map(Self::from_micros));
+impl_serialize!([] Duration, (self, ser) => self.micros.serialize(ser));
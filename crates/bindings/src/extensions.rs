@@ -0,0 +1,90 @@
+//! Defines the typed stash a reducer call carries, as returned by [`ReducerContext::extensions`].
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::ReducerContext;
+
+/// The values stashed during the current reducer call, keyed by their type.
+type Stash = RefCell<HashMap<TypeId, Box<dyn Any>>>;
+
+scoped_tls::scoped_thread_local! {
+    static CURRENT_EXTENSIONS: Stash
+}
+
+/// Runs `f` with a fresh, empty stash, which is dropped afterwards.
+pub(crate) fn with_extensions_set<R>(f: impl FnOnce() -> R) -> R {
+    CURRENT_EXTENSIONS.set(&Stash::default(), f)
+}
+
+/// Returns the stash of the current reducer call, if in the context of one.
+pub(crate) fn current_extensions() -> Option<Extensions> {
+    CURRENT_EXTENSIONS.is_set().then_some(Extensions { _priv: () })
+}
+
+/// A handle to the values stashed during a single reducer call, keyed by their type.
+///
+/// Before-reducer hooks and helper libraries can use it to pass data they've computed,
+/// e.g., the profile of the calling player, on to the reducer and to each other,
+/// instead of looking it up in a table again.
+/// The stash is emptied when the call ends,
+/// so nothing carries over to the next call, nor past an `.await` in an `async` reducer.
+///
+/// Values are returned by clone, so a value that is expensive to clone is best stashed in an `Rc`.
+/// Private types make for keys that no other library can overwrite.
+#[derive(Clone, Copy)]
+pub struct Extensions {
+    _priv: (),
+}
+
+impl ReducerContext {
+    /// Returns the typed stash of the current reducer call.
+    pub fn extensions(&self) -> Extensions {
+        Extensions { _priv: () }
+    }
+}
+
+impl Extensions {
+    /// Runs `f` with the stash of the current reducer call.
+    ///
+    /// Panics if not in the context of a reducer.
+    fn with<R>(self, f: impl FnOnce(&mut HashMap<TypeId, Box<dyn Any>>) -> R) -> R {
+        CURRENT_EXTENSIONS.with(|stash| f(&mut stash.borrow_mut()))
+    }
+
+    /// Stashes `value`, returning the value of type `T` it replaces, if any.
+    pub fn insert<T: 'static>(self, value: T) -> Option<T> {
+        let old = self.with(|stash| stash.insert(TypeId::of::<T>(), Box::new(value)))?;
+        Some(*old.downcast().unwrap())
+    }
+
+    /// Returns a clone of the stashed value of type `T`, if any.
+    pub fn get<T: Clone + 'static>(self) -> Option<T> {
+        self.with(|stash| stash.get(&TypeId::of::<T>())?.downcast_ref().cloned())
+    }
+
+    /// Returns a clone of the stashed value of type `T`,
+    /// first stashing the one computed by `f` if there's none.
+    ///
+    /// `f` may itself use the stash.
+    pub fn get_or_insert_with<T: Clone + 'static>(self, f: impl FnOnce() -> T) -> T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        let value = f();
+        self.insert(value.clone());
+        value
+    }
+
+    /// Returns whether a value of type `T` is stashed.
+    pub fn contains<T: 'static>(self) -> bool {
+        self.with(|stash| stash.contains_key(&TypeId::of::<T>()))
+    }
+
+    /// Removes and returns the stashed value of type `T`, if any.
+    pub fn remove<T: 'static>(self) -> Option<T> {
+        let old = self.with(|stash| stash.remove(&TypeId::of::<T>()))?;
+        Some(*old.downcast().unwrap())
+    }
+}
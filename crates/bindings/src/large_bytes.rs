@@ -0,0 +1,132 @@
+//! Defines `LargeBytes`, a byte string stored out of the rows that hold it.
+
+use std::fmt;
+use std::ops::Deref;
+
+use once_cell::unsync::OnceCell;
+use spacetimedb_lib::de::{Deserialize, Error};
+use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, impl_st, SpacetimeType};
+use spacetimedb_lib::ser::Serialize;
+use spacetimedb_lib::{DataKey, LargeBytesRef};
+
+use crate::sys;
+
+/// A byte string stored out of the rows that hold it, e.g., an image or a saved game.
+///
+/// A row holds a small reference to the bytes rather than the bytes themselves,
+/// so that large values don't bloat the commit log or the updates sent to subscribers,
+/// and the same bytes held by many rows are only stored once.
+/// The bytes of a row read from a table are only fetched from the database when first accessed.
+/// Up to 31 bytes are held by the reference itself.
+///
+/// Like tables, `LargeBytes` can only be created and read in a reducer.
+/// Bytes that no row references once the reducer returns are deleted.
+///
+/// ```ignore
+/// #[spacetimedb(table)]
+/// pub struct Avatar {
+///     #[primarykey]
+///     owner: Identity,
+///     image: LargeBytes,
+/// }
+///
+/// #[spacetimedb(reducer)]
+/// pub fn set_avatar(ctx: ReducerContext, image: Vec<u8>) {
+///     Avatar::insert(Avatar { owner: ctx.sender, image: LargeBytes::new(image) });
+/// }
+/// ```
+#[derive(Clone)]
+pub struct LargeBytes {
+    key: DataKey,
+    data: OnceCell<Vec<u8>>,
+}
+
+impl LargeBytes {
+    /// Stores `data` in the database, returning the value a column references it by.
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        let data = data.into();
+        let key = match DataKey::from_data(&data) {
+            key @ DataKey::Data(_) => key,
+            DataKey::Hash(_) => {
+                let key = sys::large_bytes_put(&data).expect("large_bytes_put failed").read();
+                DataKey::decode(&mut &key[..]).expect("large_bytes_put returned an invalid key")
+            }
+        };
+        Self {
+            key,
+            data: OnceCell::with_value(data),
+        }
+    }
+
+    /// Returns the bytes, fetching them from the database if they haven't been yet.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.get_or_init(|| match self.key {
+            DataKey::Data(data) => data.to_vec(),
+            DataKey::Hash(_) => sys::large_bytes_get(&self.key.to_bytes())
+                .expect("large_bytes_get failed")
+                .read()
+                .into_vec(),
+        })
+    }
+
+    /// Returns the bytes, fetching them from the database if they haven't been yet.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.as_bytes();
+        self.data.into_inner().unwrap()
+    }
+}
+
+impl Deref for LargeBytes {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for LargeBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl From<Vec<u8>> for LargeBytes {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<&[u8]> for LargeBytes {
+    fn from(data: &[u8]) -> Self {
+        Self::new(data)
+    }
+}
+
+/// As the bytes are stored under their hash, two values are equal when their keys are,
+/// so comparing them doesn't fetch any bytes.
+impl PartialEq for LargeBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for LargeBytes {}
+
+impl std::hash::Hash for LargeBytes {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl fmt::Debug for LargeBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LargeBytes").field(&self.key).finish()
+    }
+}
+
+impl_st!([] LargeBytes, ts => LargeBytesRef::make_type(ts));
+impl_serialize!([] LargeBytes, (self, ser) => LargeBytesRef::new(self.key).serialize(ser));
+impl_deserialize!([] LargeBytes, de => {
+    let key = LargeBytesRef::deserialize(de)?.key();
+    key.map(|key| Self { key, data: OnceCell::new() })
+        .ok_or_else(|| Error::custom("invalid reference to large bytes"))
+});
@@ -1,14 +1,25 @@
 //! Provides safe abstractions around `bindings-sys`
 //! and re-exports `#[spacetimedb]` and `#[duration]`.
 
+mod call_trace;
+mod continuation;
+mod duration;
+mod extensions;
 #[macro_use]
 mod io;
 mod impls;
+mod large_bytes;
 mod logger;
+mod row_layout;
 #[doc(hidden)]
 pub mod rt;
+mod table_handle;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod timestamp;
 
+use call_trace::TableOp;
+use row_layout::{decode_stored_row, StoredLayout};
 use spacetimedb_lib::buffer::{BufReader, BufWriter, Cursor, DecodeError};
 pub use spacetimedb_lib::de::{Deserialize, DeserializeOwned};
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, impl_st};
@@ -16,16 +27,26 @@ pub use spacetimedb_lib::ser::Serialize;
 use spacetimedb_lib::{bsatn, ColumnIndexAttribute, IndexType, PrimaryKey, ProductType, ProductValue};
 use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::rc::Rc;
 use std::{fmt, panic};
 
 pub use spacetimedb_bindings_macro::{duration, query, spacetimedb, TableType};
 
+pub use continuation::{sleep, sleep_until, Continuation, Sleep};
+pub use duration::Duration;
+pub use extensions::Extensions;
+pub use large_bytes::LargeBytes;
 pub use sats::SpacetimeType;
 pub use spacetimedb_lib;
 pub use spacetimedb_lib::sats;
+#[cfg(feature = "serde")]
+pub use spacetimedb_lib::sats::bsatn::serde::Serde;
+pub use spacetimedb_lib::Address;
 pub use spacetimedb_lib::AlgebraicValue;
+pub use spacetimedb_lib::Hash;
 pub use spacetimedb_lib::Identity;
-pub use timestamp::Timestamp;
+pub use table_handle::TableHandle;
+pub use timestamp::{Timestamp, TimestampOutOfRange};
 
 pub use spacetimedb_bindings_sys as sys;
 pub use sys::Errno;
@@ -51,6 +72,13 @@ pub struct ReducerContext {
     pub sender: Identity,
     /// The time at which the reducer was started.
     pub timestamp: Timestamp,
+    /// The offset in the commit log of the transaction the reducer runs in,
+    /// i.e., the number of transactions with changes committed to the database before it.
+    ///
+    /// Unlike `timestamp`, this never collides among the reducers whose changes are committed,
+    /// and increases with each, so it can order events or serve as an idempotency key.
+    /// Reducers which change nothing may share the offset of the next one.
+    pub tx_offset: u64,
 }
 
 impl ReducerContext {
@@ -59,10 +87,24 @@ impl ReducerContext {
         Self {
             sender: Identity::__dummy(),
             timestamp: Timestamp::UNIX_EPOCH,
+            tx_offset: 0,
         }
     }
 }
 
+/// A call to a reducer, as seen by the hooks registered with
+/// `#[spacetimedb(before_reducer)]` and `#[spacetimedb(after_reducer)]`.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct ReducerCall<'a> {
+    /// The name of the reducer being called.
+    pub name: &'a str,
+    /// The context the reducer is provided with.
+    pub ctx: ReducerContext,
+    /// The arguments to the reducer, as a BSATN-encoded product.
+    pub args: &'a [u8],
+}
+
 // #[cfg(target_arch = "wasm32")]
 // #[global_allocator]
 // static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
@@ -140,13 +182,15 @@ pub fn insert<T: TableType>(table_id: u32, row: T) -> T::InsertResult {
     }
     with_row_buf(|bytes| {
         // Encode the row as bsatn into the buffer `bytes`.
-        bsatn::to_writer(bytes, &row).unwrap();
+        encode_table_row(bytes, &row, 0);
 
         // Insert row into table.
-        // When table has an auto-incrementing column, we must re-decode the changed `bytes`.
-        let res = sys::insert(table_id, bytes).map(|()| {
-            if <T as HasAutoinc>::HAS_AUTOINC {
-                bsatn::from_slice(bytes).expect("decode error")
+        // When table has an auto-incrementing column, or a row version the host sets,
+        // we must re-decode the changed `bytes`.
+        call_trace::record(TableOp::Insert, table_id);
+        let res = sys::try_insert(table_id, bytes).map(|()| {
+            if <T as HasAutoinc>::HAS_AUTOINC || T::ROW_VERSION.is_some() {
+                decode_table_row::<T>(&mut &bytes[..]).0
             } else {
                 row
             }
@@ -155,6 +199,28 @@ pub fn insert<T: TableType>(table_id: u32, row: T) -> T::InsertResult {
     })
 }
 
+/// Encodes `row` as bsatn into `bytes`,
+/// followed by `deleted_at` if the table of `T` is [soft-deleting](TableType::SOFT_DELETE).
+fn encode_table_row<T: TableType>(bytes: &mut Vec<u8>, row: &T, deleted_at: u64) {
+    bsatn::to_writer(bytes, row).unwrap();
+    if T::SOFT_DELETE {
+        bsatn::to_writer(bytes, &deleted_at).unwrap();
+    }
+}
+
+/// Decodes a row of the table of `T` from `reader`,
+/// along with the micros since the UNIX epoch at which it was deleted.
+///
+/// That's 0 for a live row, and so for every row of a table that isn't soft-deleting.
+fn decode_table_row<'de, T: TableType>(reader: &mut impl BufReader<'de>) -> (T, u64) {
+    let row = bsatn::from_reader(reader).expect("Failed to decode row!");
+    let deleted_at = match T::SOFT_DELETE {
+        true => bsatn::from_reader(reader).expect("Failed to decode row!"),
+        false => 0,
+    };
+    (row, deleted_at)
+}
+
 /// Finds all rows in the table identified by `table_id`,
 /// where the row has a column, identified by `col_id`,
 /// with data matching `val` that can be serialized.
@@ -171,10 +237,49 @@ pub fn iter_by_col_eq(table_id: u32, col_id: u8, val: &impl Serialize) -> Result
     with_row_buf(|bytes| {
         // Encode `val` as bsatn into `bytes` and then use that.
         bsatn::to_writer(bytes, val).unwrap();
+        call_trace::record(TableOp::Scan, table_id);
         sys::iter_by_col_eq(table_id, col_id as u32, bytes)
     })
 }
 
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a string column, identified by `col_id`,
+/// that matches the full-text `query`.
+///
+/// Matching is defined by [`spacetimedb_lib::fulltext::matches`].
+///
+/// The rows found are bsatn encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+pub fn iter_by_col_match(table_id: u32, col_id: u8, query: &str) -> Result<Buffer> {
+    call_trace::record(TableOp::Scan, table_id);
+    sys::iter_by_col_match(table_id, col_id as u32, query)
+}
+
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a point column, identified by `col_id`,
+/// that lies within the box from `min` to `max`, both of which can be serialized.
+///
+/// Containment is defined by decoding `min` and `max` to `AlgebraicValue`s
+/// according to the column's schema and then [`spacetimedb_lib::spatial::in_box`].
+///
+/// The rows found are bsatn encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+///
+/// Panics when serialization fails.
+pub fn iter_by_col_box<T: Serialize>(table_id: u32, col_id: u8, min: &T, max: &T) -> Result<Buffer> {
+    with_row_buf(|bytes| {
+        // Encode both corners as bsatn into `bytes`, one after the other.
+        bsatn::to_writer(bytes, min).unwrap();
+        let min_len = bytes.len();
+        bsatn::to_writer(bytes, max).unwrap();
+        let (min, max) = bytes.split_at(min_len);
+        call_trace::record(TableOp::Scan, table_id);
+        sys::iter_by_col_box(table_id, col_id as u32, min, max)
+    })
+}
+
 /// Deletes all rows in the table identified by `table_id`
 /// where the column identified by `col_id` matches a `value` that can be serialized.
 ///
@@ -189,6 +294,7 @@ pub fn delete_by_col_eq(table_id: u32, col_id: u8, eq_value: &impl Serialize) ->
     with_row_buf(|bytes| {
         // Encode `val` as bsatn into `bytes` and then use that.
         bsatn::to_writer(bytes, eq_value).unwrap();
+        call_trace::record(TableOp::Delete, table_id);
         sys::delete_by_col_eq(table_id, col_id.into(), bytes)
     })
 }
@@ -247,6 +353,7 @@ fn buffer_table_iter(
         .expect("Couldn't decode the filter query");
 
     // Create the iterator.
+    call_trace::record(TableOp::Scan, table_id);
     let mut iter = sys::iter(table_id, filter.as_deref())?;
 
     // First item is an encoded schema.
@@ -257,25 +364,41 @@ fn buffer_table_iter(
 }
 
 /// A table iterator which yields `ProductValue`s.
-// type ProductValueTableIter = RawTableIter<ProductValue, ProductValueBufferDeserialize>;
+type ProductValueTableIter = RawTableIter<ProductValueBufferDeserialize>;
 
-// fn pv_table_iter(table_id: u32, filter: Option<spacetimedb_lib::filter::Expr>) -> Result<ProductValueTableIter> {
-//     let (iter, schema) = buffer_table_iter(table_id, filter)?;
-//     let deserializer = ProductValueBufferDeserialize::new(schema);
-//     Ok(RawTableIter::new(iter, deserializer))
-// }
+fn pv_table_iter(table_id: u32, filter: Option<spacetimedb_lib::filter::Expr>) -> Result<ProductValueTableIter> {
+    let (iter, schema) = buffer_table_iter(table_id, filter)?;
+    let deserializer = ProductValueBufferDeserialize::new(schema);
+    Ok(RawTableIter::new(iter, deserializer))
+}
 
 /// A table iterator which yields values of the `TableType` corresponding to the table.
 type TableTypeTableIter<T> = RawTableIter<TableTypeBufferDeserialize<T>>;
 
 fn table_iter<T: TableType>(table_id: u32, filter: Option<spacetimedb_lib::filter::Expr>) -> Result<TableIter<T>> {
-    // The TableType deserializer doesn't need the schema, as we have type-directed
-    // dispatch to deserialize any given `TableType`.
-    let (iter, _schema) = buffer_table_iter(table_id, filter)?;
-    let deserializer = TableTypeBufferDeserialize::new();
+    let (iter, schema) = buffer_table_iter(table_id, filter)?;
+    let deserializer = TableTypeBufferDeserialize::new(schema);
     Ok(RawTableIter::new(iter, deserializer).into())
 }
 
+/// A table iterator which yields the values of the columns `C` of the table `T`.
+type ProjectedTableIter<T, C> = RawTableIter<ProjectedBufferDeserialize<T, C>>;
+
+fn projected_table_iter<T: TableType, C: Columns<T>>(table_id: u32) -> Result<ProjectedIter<T, C>> {
+    // The deletion time of a soft-deleted row is the hidden column after those of `T`.
+    let deleted_at = T::SOFT_DELETE.then_some(T::COLUMN_ATTRS.len() as u8);
+    let cols = C::COL_IDS.iter().copied().chain(deleted_at).collect::<Vec<_>>();
+
+    call_trace::record(TableOp::Scan, table_id);
+    let mut iter = sys::iter_projected(table_id, &cols)?;
+    // The values are decoded by type, so the schema is skipped.
+    iter.next().expect("Missing schema").expect("Failed to get schema");
+    let deserializer = ProjectedBufferDeserialize { _marker: PhantomData };
+    Ok(ProjectedIter {
+        iter: RawTableIter::new(iter, deserializer),
+    })
+}
+
 /// A trait for deserializing mulitple items out of a single `BufReader`.
 ///
 /// Each `BufReader` holds a number of concatenated serialized objects.
@@ -288,41 +411,66 @@ trait BufferDeserialize {
 }
 
 /// Deserialize `ProductValue`s from `Buffer`s.
-// struct ProductValueBufferDeserialize {
-//     /// The schema to deserialize with.
-//     schema: ProductType,
-// }
+struct ProductValueBufferDeserialize {
+    /// The schema to deserialize with.
+    schema: ProductType,
+}
 
-// impl ProductValueBufferDeserialize {
-//     fn new(schema: ProductType) -> Self {
-//         Self { schema }
-//     }
-// }
+impl ProductValueBufferDeserialize {
+    fn new(schema: ProductType) -> Self {
+        Self { schema }
+    }
+}
 
-// impl BufferDeserialize for ProductValueBufferDeserialize {
-//     type Item = ProductValue;
+impl BufferDeserialize for ProductValueBufferDeserialize {
+    type Item = ProductValue;
 
-//     fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
-//         decode_row(&self.schema, &mut reader).expect("Failed to decode row!")
-//     }
-// }
+    fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
+        decode_row(&self.schema, &mut reader).expect("Failed to decode row!")
+    }
+}
 
 /// Deserialize bsatn values to a particular `T` where `T: TableType`.
 struct TableTypeBufferDeserialize<T> {
+    /// The layout the rows are stored in, if it isn't the current one of `T`.
+    stored_layout: Option<StoredLayout>,
     _marker: PhantomData<T>,
 }
 
-impl<T> TableTypeBufferDeserialize<T> {
-    fn new() -> Self {
-        Self { _marker: PhantomData }
+impl<T: TableType> TableTypeBufferDeserialize<T> {
+    fn new(schema: ProductType) -> Self {
+        Self {
+            stored_layout: StoredLayout::new::<T>(schema),
+            _marker: PhantomData,
+        }
     }
 }
 
 impl<T: TableType> BufferDeserialize for TableTypeBufferDeserialize<T> {
-    type Item = T;
+    /// A row along with when it was deleted, as in [`decode_table_row`].
+    type Item = (T, u64);
 
     fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
-        bsatn::from_reader(&mut reader).expect("Failed to decode row!")
+        decode_stored_row(self.stored_layout.as_ref(), &mut reader)
+    }
+}
+
+/// Deserialize the values of the columns `C` of the table `T`.
+struct ProjectedBufferDeserialize<T, C> {
+    _marker: PhantomData<(T, C)>,
+}
+
+impl<T: TableType, C: Columns<T>> BufferDeserialize for ProjectedBufferDeserialize<T, C> {
+    /// The values along with when the row was deleted, as in [`decode_table_row`].
+    type Item = (C::Values, u64);
+
+    fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
+        let values = C::decode(&mut reader);
+        let deleted_at = match T::SOFT_DELETE {
+            true => bsatn::from_reader(&mut reader).expect("Failed to decode row!"),
+            false => 0,
+        };
+        (values, deleted_at)
     }
 }
 
@@ -394,6 +542,8 @@ pub struct IndexDef<'a> {
 }
 
 /// A table iterator which yields values of the `TableType` corresponding to the table.
+///
+/// Rows deleted from a [soft-deleting](TableType::SOFT_DELETE) table are skipped.
 pub struct TableIter<T: TableType> {
     iter: TableTypeTableIter<T>,
 }
@@ -408,7 +558,89 @@ impl<T: TableType> Iterator for TableIter<T> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        self.iter.find_map(|(row, deleted_at)| (deleted_at == 0).then_some(row))
+    }
+}
+
+/// A table iterator which yields the values of the columns `C` of each row of the table `T`,
+/// as returned by [`TableType::iter_cols`].
+///
+/// Rows deleted from a [soft-deleting](TableType::SOFT_DELETE) table are skipped.
+pub struct ProjectedIter<T: TableType, C: Columns<T>> {
+    iter: ProjectedTableIter<T, C>,
+}
+
+impl<T: TableType, C: Columns<T>> Iterator for ProjectedIter<T, C> {
+    type Item = C::Values;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .find_map(|(values, deleted_at)| (deleted_at == 0).then_some(values))
+    }
+}
+
+/// A column of a table, as a type.
+///
+/// `#[spacetimedb(table)]` generates one for each field of the struct,
+/// in a module named after the struct, e.g., `player_cols::Name` for the field `name` of `Player`.
+pub trait Column {
+    /// The table the column belongs to.
+    type Table: TableType;
+    /// The type of the values in the column.
+    type Value: DeserializeOwned;
+    /// The index of the column among the columns of the table.
+    const INDEX: u8;
+}
+
+/// A tuple of [`Column`]s of the table `T`, to iterate over with [`TableType::iter_cols`].
+pub trait Columns<T: TableType> {
+    /// The values of the columns, as a tuple.
+    type Values;
+    /// The indices of the columns among the columns of the table, in order.
+    const COL_IDS: &'static [u8];
+
+    /// Decodes the values of the columns, encoded one after the other, from `reader`.
+    fn decode<'de>(reader: &mut impl BufReader<'de>) -> Self::Values;
+}
+
+macro_rules! impl_columns {
+    ($($C:ident),*) => {
+        impl<T: TableType, $($C: Column<Table = T>),*> Columns<T> for ($($C,)*) {
+            type Values = ($($C::Value,)*);
+            const COL_IDS: &'static [u8] = &[$($C::INDEX),*];
+
+            fn decode<'de>(reader: &mut impl BufReader<'de>) -> Self::Values {
+                ($(bsatn::from_reader::<$C::Value>(reader).expect("Failed to decode column!"),)*)
+            }
+        }
+    };
+}
+
+impl_columns!(A);
+impl_columns!(A, B);
+impl_columns!(A, B, C);
+impl_columns!(A, B, C, D);
+impl_columns!(A, B, C, D, E);
+impl_columns!(A, B, C, D, E, F);
+impl_columns!(A, B, C, D, E, F, G);
+impl_columns!(A, B, C, D, E, F, G, H);
+
+/// An iterator over the rows deleted from a [soft-deleting](TableType::SOFT_DELETE) table,
+/// yielding each along with when it was deleted.
+pub struct DeletedIter<T: TableType> {
+    iter: TableTypeTableIter<T>,
+}
+
+impl<T: TableType> Iterator for DeletedIter<T> {
+    type Item = (T, Timestamp);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|(row, deleted_at)| {
+            let deleted_at = Timestamp {
+                micros_since_epoch: deleted_at,
+            };
+            (deleted_at != Timestamp::UNIX_EPOCH).then_some((row, deleted_at))
+        })
     }
 }
 
@@ -419,6 +651,19 @@ pub trait TableType: SpacetimeType + DeserializeOwned + Serialize {
     const TABLE_NAME: &'static str;
     const COLUMN_ATTRS: &'static [ColumnIndexAttribute];
     const INDEXES: &'static [IndexDef<'static>];
+    /// Whether deleting a row only marks it as deleted, as in `#[spacetimedb(table, soft_delete)]`.
+    ///
+    /// The rows of such a table have a hidden `deleted_at` column after the fields of `Self`,
+    /// holding when the row was deleted, or 0 while it's live.
+    const SOFT_DELETE: bool = false;
+    /// The columns whose unique constraint is only checked when the transaction commits,
+    /// as in `#[unique(deferred)]`.
+    const DEFERRED_UNIQUE: &'static [u8] = &[];
+    /// The columns the host compresses in memory, and how, as in `#[compress]`.
+    const COMPRESSED_COLUMNS: &'static [(u8, spacetimedb_lib::Compression)] = &[];
+    /// The column the host bumps on every update of a row, as in `#[row_version]`,
+    /// along with the primary key column the row is identified by.
+    const ROW_VERSION: Option<(u8, u8)> = None;
     type InsertResult: sealed::InsertResult<T = Self>;
 
     /// Returns the ID of this table.
@@ -434,6 +679,23 @@ pub trait TableType: SpacetimeType + DeserializeOwned + Serialize {
         table_iter(Self::table_id(), None).unwrap()
     }
 
+    /// Returns an iterator over the values of the columns `C` in each row of this table.
+    ///
+    /// The host only sends those columns, so this is cheaper than [`Self::iter`]
+    /// when only a few of the columns of a wide table are needed.
+    fn iter_cols<C: Columns<Self>>() -> ProjectedIter<Self, C> {
+        projected_table_iter(Self::table_id()).unwrap()
+    }
+
+    /// Returns the version of this table, the number of committed transactions that changed its rows.
+    ///
+    /// The changes of the current transaction aren't counted.
+    /// A cache of the table's rows needs refreshing only once the version has moved past the one it was filled at,
+    /// which is also listed in the `st_table_version` system table for clients to query.
+    fn table_version() -> u64 {
+        sys::table_version(Self::table_id()).unwrap()
+    }
+
     /// Returns an iterator filtered by `filter` over the rows in this table.
     ///
     /// **NOTE:** Do not use directly. This is exposed as `query!(...)`.
@@ -443,30 +705,77 @@ pub trait TableType: SpacetimeType + DeserializeOwned + Serialize {
     }
 }
 
+/// A [`TableType`] with a `#[primarykey]` column, by which its rows can be looked up and deleted.
+///
+/// Implemented by `#[spacetimedb(table)]` for tables with a `#[primarykey]`,
+/// using the unique index of the column.
+pub trait PrimaryKeyTable: TableType {
+    /// The type of the primary key column.
+    type PrimaryKey: UniqueValue;
+
+    /// The index of the primary key column among the columns of the table.
+    const PRIMARY_KEY_COLUMN: u8;
+
+    /// Returns the row whose primary key is `pk`, if any.
+    fn get(pk: &Self::PrimaryKey) -> Option<Self>;
+
+    /// Deletes the row whose primary key is `pk`, returning whether there was one.
+    fn delete(pk: &Self::PrimaryKey) -> bool;
+}
+
 mod sealed {
     use super::*;
 
     /// A trait of result types which know how to convert a `Result<T: TableType>` into itself.
+    ///
+    /// The error comes with the id of the column whose unique constraint was violated, if any.
     pub trait InsertResult {
         type T: TableType;
-        fn from_res(res: Result<Self::T>) -> Self;
+        fn from_res(res: Result<Self::T, (Errno, Option<u32>)>) -> Self;
     }
 }
 
 /// A UNIQUE constraint violation on table type `T` was attempted.
-pub struct UniqueConstraintViolation<T: TableType>(PhantomData<T>);
+pub struct UniqueConstraintViolation<T: TableType> {
+    /// The name of the table.
+    pub table: &'static str,
+    /// The name of the column whose unique constraint was violated,
+    /// or `None` if the host didn't say which.
+    pub column: Option<String>,
+    _marker: PhantomData<T>,
+}
+impl<T: TableType> UniqueConstraintViolation<T> {
+    /// Returns the violation of the unique constraint on the column `col_id`, if known.
+    fn new(col_id: Option<u32>) -> Self {
+        Self {
+            table: T::TABLE_NAME,
+            column: col_id.and_then(column_name::<T>),
+            _marker: PhantomData,
+        }
+    }
+}
 impl<T: TableType> fmt::Debug for UniqueConstraintViolation<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "UniqueConstraintViolation({})", T::TABLE_NAME)
+        f.debug_struct("UniqueConstraintViolation")
+            .field("table", &self.table)
+            .field("column", &self.column)
+            .finish()
     }
 }
 impl<T: TableType> fmt::Display for UniqueConstraintViolation<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "not able to insert into table {}; duplicate unique column",
-            T::TABLE_NAME
-        )
+        match &self.column {
+            Some(column) => write!(
+                f,
+                "not able to insert into table {}; duplicate value in unique column {}",
+                self.table, column
+            ),
+            None => write!(
+                f,
+                "not able to insert into table {}; duplicate unique column",
+                self.table
+            ),
+        }
     }
 }
 impl<T: TableType> From<UniqueConstraintViolation<T>> for String {
@@ -478,18 +787,37 @@ impl<T: TableType> std::error::Error for UniqueConstraintViolation<T> {}
 
 impl<T: TableType> sealed::InsertResult for Result<T, UniqueConstraintViolation<T>> {
     type T = T;
-    fn from_res(res: Result<Self::T>) -> Self {
-        res.map_err(|e| match e {
-            Errno::UNIQUE_ALREADY_EXISTS => UniqueConstraintViolation(PhantomData),
-            _ => panic!("unexpected error from insert(): {e}"),
+    fn from_res(res: Result<Self::T, (Errno, Option<u32>)>) -> Self {
+        res.map_err(|(e, col_id)| match e {
+            Errno::UNIQUE_ALREADY_EXISTS => UniqueConstraintViolation::new(col_id),
+            _ => insert_failed::<T>(e),
         })
     }
 }
 
 impl<T: TableType> sealed::InsertResult for T {
     type T = T;
-    fn from_res(res: Result<Self::T>) -> Self {
-        res.unwrap_or_else(|e| panic!("unexpected error from insert(): {e}"))
+    fn from_res(res: Result<Self::T, (Errno, Option<u32>)>) -> Self {
+        res.unwrap_or_else(|(e, _)| insert_failed::<T>(e))
+    }
+}
+
+/// Returns the name of the column `col_id` of the table of `T`.
+fn column_name<T: TableType>(col_id: u32) -> Option<String> {
+    let mut module = rt::ModuleBuilder::default();
+    let row_type = rt::table_row_type::<T>(&mut module);
+    let row_type = module.module.typespace[row_type].as_product()?;
+    row_type.elements.get(col_id as usize)?.name.clone()
+}
+
+/// Fails the reducer for an error from inserting into the table of `T`.
+fn insert_failed<T: TableType>(e: Errno) -> ! {
+    match e {
+        Errno::QUOTA_EXCEEDED => panic!(
+            "not able to insert into table {}; the database's quota was exceeded",
+            T::TABLE_NAME
+        ),
+        _ => panic!("unexpected error from insert(): {e}"),
     }
 }
 
@@ -538,13 +866,19 @@ pub mod query {
     >(
         val: &T,
     ) -> Option<Table> {
+        find_by_unique_field::<Table, T, COL_IDX>(val).and_then(|(row, deleted_at)| (deleted_at == 0).then_some(row))
+    }
+
+    /// Finds the row of `Table` where the column at `COL_IDX` matches `val`,
+    /// along with when it was deleted, as in [`decode_table_row`].
+    fn find_by_unique_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(val: &T) -> Option<(Table, u64)> {
         // Find the row with a match.
         let slice: &mut &[u8] = &mut &*iter_by_col_eq(Table::table_id(), COL_IDX, val).unwrap().read();
         // We will always find either 0 or 1 rows here due to the unique constraint.
         match slice.remaining() {
             0 => None,
             _ => {
-                let t = bsatn::from_reader(slice).unwrap();
+                let t = decode_stored_row(StoredLayout::of::<Table>().as_deref(), slice);
                 assert_eq!(slice.remaining(), 0);
                 Some(t)
             }
@@ -564,20 +898,93 @@ pub mod query {
             .read();
         FilterByIter {
             cursor: Cursor::new(rows),
+            stored_layout: StoredLayout::of::<Table>(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Finds all rows of `Table` where the string column at `COL_IDX` matches the full-text `query`,
+    /// as defined by [`spacetimedb_lib::fulltext::matches`].
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `search_by_{$field_name}` on types with `#[spacetimedb(table)]`
+    /// for the fields in an `#[spacetimedb(index(fulltext))]`.
+    #[doc(hidden)]
+    pub fn search_by_field<Table: TableType, const COL_IDX: u8>(query: &str) -> FilterByIter<Table> {
+        let rows = iter_by_col_match(Table::table_id(), COL_IDX, query)
+            .expect("iter_by_col_match failed")
+            .read();
+        FilterByIter {
+            cursor: Cursor::new(rows),
+            stored_layout: StoredLayout::of::<Table>(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Finds all rows of `Table` where the point column at `COL_IDX` lies within the box from `min` to `max`,
+    /// as defined by [`spacetimedb_lib::spatial::in_box`].
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `filter_by_{$field_name}_box` on types with `#[spacetimedb(table)]`
+    /// for the fields in an `#[spacetimedb(index(spatial))]`.
+    #[doc(hidden)]
+    pub fn filter_by_box<Table: TableType, T: Serialize, const COL_IDX: u8>(min: &T, max: &T) -> FilterByIter<Table> {
+        let rows = iter_by_col_box(Table::table_id(), COL_IDX, min, max)
+            .expect("iter_by_col_box failed")
+            .read();
+        FilterByIter {
+            cursor: Cursor::new(rows),
+            stored_layout: StoredLayout::of::<Table>(),
             _phantom: PhantomData,
         }
     }
 
+    /// Finds all rows of `Table` where the array column at `COL_IDX` has `val` as an element,
+    /// as defined by `PartialEq for T`.
+    ///
+    /// For now, this scans the whole table.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `filter_by_{$field_name}_contains` on types with `#[spacetimedb(table)]`.
+    #[doc(hidden)]
+    pub fn filter_by_field_contains<'a, Table, T, const COL_IDX: u8>(val: &'a T) -> impl Iterator<Item = Table> + 'a
+    where
+        Table: TableType + FieldAccess<COL_IDX, Field = Vec<T>> + 'a,
+        T: FilterableValue,
+    {
+        Table::iter().filter(move |row| <Table as FieldAccess<COL_IDX>>::get_field(row).contains(val))
+    }
+
     /// Deletes the row of `Table` where the column at `COL_IDX` matches `val`,
     /// as defined by decoding to an `AlgebraicValue`
     /// according to the column's schema and then `Ord for AlgebraicValue`.
     ///
     /// Returns whether any rows were deleted.
     ///
+    /// When `Table` is [soft-deleting](TableType::SOFT_DELETE),
+    /// a live row is only marked as deleted now, keeping its unique values until purged.
+    ///
     /// **NOTE:** Do not use directly.
     /// This is exposed as `delete_by_{$field_name}` on types with `#[spacetimedb(table)]`.
     #[doc(hidden)]
     pub fn delete_by_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(val: &T) -> bool {
+        if Table::SOFT_DELETE {
+            return match find_by_unique_field::<Table, T, COL_IDX>(val) {
+                Some((row, 0)) => {
+                    // Never mark a row as deleted at 0, which would leave it live.
+                    let now = Timestamp::now().micros_since_epoch.max(1);
+                    replace_row::<Table, T, COL_IDX>(val, &row, now);
+                    true
+                }
+                _ => false,
+            };
+        }
+        hard_delete_by_field::<Table, T, COL_IDX>(val)
+    }
+
+    /// Deletes the row of `Table` where the column at `COL_IDX` matches `val`,
+    /// whether or not `Table` is soft-deleting.
+    fn hard_delete_by_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(val: &T) -> bool {
         let result = delete_by_col_eq(Table::table_id(), COL_IDX, val);
         match result {
             Err(_) => {
@@ -604,7 +1011,8 @@ pub mod query {
     #[doc(hidden)]
     pub fn update_by_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(old: &T, new: Table) -> bool {
         // Delete the existing row, if any.
-        delete_by_field::<Table, T, COL_IDX>(old);
+        // Even from a soft-deleting table, as `new` may keep the unique value of `old`.
+        hard_delete_by_field::<Table, T, COL_IDX>(old);
 
         // Insert the new row.
         Table::insert(new);
@@ -614,6 +1022,67 @@ pub mod query {
         true
     }
 
+    /// Restores the row of a soft-deleting `Table`, where the column at `COL_IDX` matches `val`,
+    /// if it was deleted.
+    ///
+    /// Returns whether a row was restored.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `restore_by_{$field_name}` on types with `#[spacetimedb(table, soft_delete)]`.
+    #[doc(hidden)]
+    pub fn restore_by_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(val: &T) -> bool {
+        match find_by_unique_field::<Table, T, COL_IDX>(val) {
+            Some((row, deleted_at)) if deleted_at != 0 => {
+                replace_row::<Table, T, COL_IDX>(val, &row, 0);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the row of `Table`, where the column at `COL_IDX` matches `val`,
+    /// with `row` as deleted at `deleted_at`.
+    fn replace_row<Table: TableType, T: UniqueValue, const COL_IDX: u8>(val: &T, row: &Table, deleted_at: u64) {
+        hard_delete_by_field::<Table, T, COL_IDX>(val);
+        with_row_buf(|bytes| {
+            encode_table_row(bytes, row, deleted_at);
+            call_trace::record(TableOp::Insert, Table::table_id());
+            sys::insert(Table::table_id(), bytes)
+        })
+        .unwrap_or_else(|e| insert_failed::<Table>(e))
+    }
+
+    /// Returns an iterator over the rows deleted from a soft-deleting `Table`.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `iter_deleted` on types with `#[spacetimedb(table, soft_delete)]`.
+    #[doc(hidden)]
+    pub fn iter_deleted<Table: TableType>() -> DeletedIter<Table> {
+        let (iter, schema) = buffer_table_iter(Table::table_id(), None).unwrap();
+        DeletedIter {
+            iter: RawTableIter::new(iter, TableTypeBufferDeserialize::new(schema)),
+        }
+    }
+
+    /// Permanently removes the rows deleted from a soft-deleting `Table`.
+    ///
+    /// Returns the number of rows removed.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `purge_deleted` on types with `#[spacetimedb(table, soft_delete)]`.
+    #[doc(hidden)]
+    pub fn purge_deleted<Table: TableType>() -> u32 {
+        // The hidden `deleted_at` column comes after all of the fields of `Table`.
+        let col_id = Table::COLUMN_ATTRS.len() as u8;
+        let deleted_at = iter_deleted::<Table>()
+            .map(|(_, deleted_at)| deleted_at)
+            .collect::<std::collections::BTreeSet<_>>();
+        deleted_at
+            .iter()
+            .map(|deleted_at| delete_by_col_eq(Table::table_id(), col_id, deleted_at).unwrap_or(0))
+            .sum()
+    }
+
     /// An iterator returned by `filter_by_field`,
     /// which yields all of the rows of a table where a particular column's value
     /// matches a given target value.
@@ -624,6 +1093,8 @@ pub mod query {
     pub struct FilterByIter<Table: TableType> {
         /// The buffer of rows returned by `iter_by_col_eq`.
         cursor: Cursor<Box<[u8]>>,
+        /// The layout the rows are stored in, if it isn't the current one of `Table`.
+        stored_layout: Option<Rc<StoredLayout>>,
 
         _phantom: PhantomData<Table>,
     }
@@ -636,7 +1107,13 @@ pub mod query {
 
         fn next(&mut self) -> Option<Self::Item> {
             let mut cursor = &self.cursor;
-            (cursor.remaining() != 0).then(|| bsatn::from_reader(&mut cursor).unwrap())
+            // Skip the rows deleted from a soft-deleting table.
+            while cursor.remaining() != 0 {
+                if let (row, 0) = decode_stored_row(self.stored_layout.as_deref(), &mut cursor) {
+                    return Some(row);
+                }
+            }
+            None
         }
     }
 }
@@ -727,3 +1204,216 @@ impl<R> ScheduleToken<R> {
 pub struct AnyReducer {
     _never: std::convert::Infallible,
 }
+
+/// Returns the energy left in the budget of the current reducer call.
+///
+/// Running out of energy aborts the reducer,
+/// so a reducer doing optional work can check this to skip it when its budget runs low:
+/// ```rust,ignore
+/// if spacetimedb::remaining_energy() > CLEANUP_COST {
+///     cleanup_expired_sessions();
+/// }
+/// ```
+///
+/// The budget is spent on the reducer's execution, the bytes of the rows it inserts,
+/// and the rows it reads, at the prices set for the reducer by the host.
+pub fn remaining_energy() -> u64 {
+    sys::remaining_energy().expect("remaining_energy failed")
+}
+
+/// A type declared with `#[spacetimedb(event)]`,
+/// a message the module can send to the clients subscribed to it.
+pub trait EventType: SpacetimeType + Serialize {
+    /// The name clients know the event by.
+    const EVENT_NAME: &'static str;
+
+    /// Emits this event, as with [`emit_event`].
+    fn emit(&self) {
+        emit_event(self)
+    }
+}
+
+/// Emits `event` to the clients subscribed to the module.
+///
+/// Events aren't stored in any table.
+/// They're sent along with the current reducer's transaction, in the order emitted,
+/// to every subscribed client, but only if the transaction commits:
+/// ```rust,ignore
+/// #[spacetimedb(event)]
+/// pub struct GoalScored {
+///     player: Identity,
+///     minute: u32,
+/// }
+///
+/// GoalScored { player: ctx.sender, minute }.emit();
+/// ```
+pub fn emit_event<T: EventType>(event: &T) {
+    let data = bsatn::to_vec(event).expect("unable to serialize event");
+    sys::emit_event(T::EVENT_NAME, &data).expect("emit_event failed")
+}
+
+/// Sends a call of the reducer `reducer` of the database at `database` with `args`,
+/// a tuple of its arguments, to be made once the current reducer's transaction commits.
+///
+/// The message is part of the transaction: it's sent if and only if the transaction commits,
+/// and the host then calls the reducer once, as the identity of this database,
+/// after the messages sent to the same database before it.
+/// The database must be running on the same node; the call waits until it is.
+/// ```rust,ignore
+/// #[spacetimedb(reducer)]
+/// pub fn transfer(ctx: ReducerContext, bank: Address, to: Identity, amount: u64) -> Result<(), String> {
+///     debit(ctx.sender, amount)?;
+///     spacetimedb::send_message(bank, "credit", (to, amount)).map_err(|e| e.to_string())
+/// }
+/// ```
+pub fn send_message<'de>(database: Address, reducer: &str, args: impl rt::Args<'de>) -> Result<()> {
+    let args = bsatn::to_vec(&rt::SerDeArgs(args)).expect("unable to serialize arguments");
+    sys::send_message(&database.as_slice(), reducer, &args)
+}
+
+/// Returns the names of the module's reducers, in the order they were registered,
+/// including those called by the host, e.g., `__init__`.
+pub fn reducers() -> &'static [String] {
+    rt::reducer_names()
+}
+
+/// Returns the arguments of the reducer `name`, with their types resolved,
+/// or `None` if the module has no such reducer.
+///
+/// Along with [`reducers`], this allows reducers to work with the others generically,
+/// e.g., a debug console that decodes a command into the arguments of the reducer it names:
+/// ```rust,ignore
+/// #[spacetimedb(reducer)]
+/// pub fn console(command: String, args: Vec<u8>) -> Result<(), String> {
+///     let ty = spacetimedb::describe_reducer(&command).ok_or("no such command")?;
+///     let args = ProductValue::decode(&ty, &mut &args[..]).map_err(|e| e.to_string())?;
+///     log::info!("{command}{args:?}");
+///     Ok(())
+/// }
+/// ```
+pub fn describe_reducer(name: &str) -> Option<ProductType> {
+    match sys::describe_reducer(name) {
+        Ok(buf) => Some(bsatn::from_slice(&buf.read()).expect("unable to decode reducer description")),
+        Err(Errno::NO_SUCH_REDUCER) => None,
+        Err(e) => panic!("describe_reducer failed: {e}"),
+    }
+}
+
+/// Moves the rows of the table `Src` for which `filter` holds to the table `Dst`,
+/// converted by `map_fn`, returning how many rows were moved.
+///
+/// The rows are deleted and inserted in a single call to the host, rather than one row at a time,
+/// and either all of them are moved or, if one can't be inserted, none are.
+/// This suits archiving rows, e.g., moving the finished matches to a history table:
+/// ```rust,ignore
+/// let archived = spacetimedb::move_rows(
+///     |m: &Match| m.finished,
+///     |m| MatchHistory { id: m.id, winner: m.winner },
+/// )?;
+/// ```
+///
+/// The rows are deleted from `Src` even if it's [soft-deleting](TableType::SOFT_DELETE),
+/// and the values generated for the auto-incremented columns of `Dst` aren't returned.
+pub fn move_rows<Src: TableType, Dst: TableType>(
+    filter: impl Fn(&Src) -> bool,
+    mut map_fn: impl FnMut(Src) -> Dst,
+) -> Result<u32> {
+    let mut src_rows = Vec::new();
+    let mut dst_rows = Vec::new();
+    for row in Src::iter().filter(|row| filter(row)) {
+        encode_table_row(&mut src_rows, &row, 0);
+        encode_table_row(&mut dst_rows, &map_fn(row), 0);
+    }
+    if src_rows.is_empty() {
+        return Ok(0);
+    }
+    call_trace::record(TableOp::Delete, Src::table_id());
+    call_trace::record(TableOp::Insert, Dst::table_id());
+    sys::move_rows(Src::table_id(), &src_rows, Dst::table_id(), &dst_rows)
+}
+
+/// Takes a savepoint of the changes made so far in the current reducer's transaction.
+///
+/// Calling [`Savepoint::rollback_to`] on the returned guard undoes the changes made since,
+/// without aborting the reducer, while dropping the guard keeps them.
+/// This allows attempting part of a reducer, e.g., an optional insert:
+/// ```rust,ignore
+/// let savepoint = spacetimedb::savepoint();
+/// if Bonus::insert(bonus).is_err() {
+///     savepoint.rollback_to();
+/// }
+/// ```
+///
+/// Savepoints nest: rolling back to or dropping a savepoint
+/// also releases all the savepoints taken after it.
+pub fn savepoint() -> Savepoint {
+    let id = sys::savepoint().expect("savepoint failed");
+    Savepoint { id }
+}
+
+/// A savepoint in the current reducer's transaction, taken by [`savepoint`].
+///
+/// Dropping it keeps the changes made since it was taken.
+#[must_use = "dropping a savepoint immediately releases it"]
+pub struct Savepoint {
+    id: u32,
+}
+
+impl Savepoint {
+    /// Undo the changes made in the current reducer's transaction since this savepoint was taken.
+    #[inline]
+    pub fn rollback_to(self) {
+        let id = self.id;
+        // The host releases the savepoint as part of rolling back to it.
+        std::mem::forget(self);
+        sys::rollback_to_savepoint(id).expect("rollback_to_savepoint failed")
+    }
+}
+
+impl Drop for Savepoint {
+    fn drop(&mut self) {
+        // An outer savepoint may have been rolled back to or released already,
+        // which released this one too, so `NO_SUCH_SAVEPOINT` is fine here.
+        let _ = sys::release_savepoint(self.id);
+    }
+}
+
+/// Stores the blob `data` under `key`, replacing the blob stored under it before, if any,
+/// returning the hash of `data`.
+///
+/// Blobs are small binary objects, e.g., avatars or maps, that are awkward to store in a column.
+/// Like rows, they're stored along with the current reducer's transaction, if it commits.
+/// Clients fetch them over HTTP by key, at `/database/blob/:name_or_address/*key`,
+/// and can compare the hash, which is also their `ETag`, to that of the copy they have:
+/// ```rust,ignore
+/// #[spacetimedb(reducer)]
+/// pub fn set_avatar(ctx: ReducerContext, png: Vec<u8>) -> Result<(), String> {
+///     let key = format!("avatars/{}", ctx.sender.to_hex());
+///     let hash = spacetimedb::blob_put(&key, &png).map_err(|e| e.to_string())?;
+///     log::info!("stored {key} with hash {hash}");
+///     Ok(())
+/// }
+/// ```
+///
+/// Errors with [`Errno::BLOB_TOO_LARGE`] if `data` is over [`MAX_BLOB_SIZE`](spacetimedb_lib::MAX_BLOB_SIZE) bytes.
+pub fn blob_put(key: &str, data: &[u8]) -> Result<Hash> {
+    sys::blob_put(key, data).map(|hash| Hash::from_arr(&hash))
+}
+
+/// Returns the blob stored under `key`, or `None` if there's none.
+pub fn blob_get(key: &str) -> Option<Vec<u8>> {
+    match sys::blob_get(key) {
+        Ok(buf) => Some(buf.read().into_vec()),
+        Err(Errno::LOOKUP_NOT_FOUND) => None,
+        Err(e) => panic!("blob_get failed: {e}"),
+    }
+}
+
+/// Deletes the blob stored under `key`, returning whether there was one.
+pub fn blob_delete(key: &str) -> bool {
+    match sys::blob_delete(key) {
+        Ok(()) => true,
+        Err(Errno::LOOKUP_NOT_FOUND) => false,
+        Err(e) => panic!("blob_delete failed: {e}"),
+    }
+}
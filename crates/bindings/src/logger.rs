@@ -1,6 +1,9 @@
 //! Defines our panic hook and that `log` will log to the console.
 
-use crate::sys;
+use crate::{call_trace, sys};
+use log::kv;
+use spacetimedb_lib::bsatn;
+use spacetimedb_lib::logging::{LogField, LogValue};
 use std::sync::Mutex;
 use std::{fmt, panic};
 
@@ -21,15 +24,26 @@ fn panic_hook(info: &panic::PanicInfo) {
         },
     };
 
-    // Log the panic message to the console.
+    // Log the panic message to the console,
+    // along with the reducer it happened in and the table operations that reducer performed, if any.
     let location = info.location();
-    sys::console_log(
-        sys::LogLevel::Panic,
-        None,
-        location.map(|l| l.file()),
-        location.map(|l| l.line()),
-        msg,
-    )
+    match call_trace::panic_fields() {
+        Some(fields) => sys::console_log_structured(
+            sys::LogLevel::Panic,
+            None,
+            location.map(|l| l.file()),
+            location.map(|l| l.line()),
+            msg,
+            &bsatn::to_vec(&fields).unwrap(),
+        ),
+        None => sys::console_log(
+            sys::LogLevel::Panic,
+            None,
+            location.map(|l| l.file()),
+            location.map(|l| l.line()),
+            msg,
+        ),
+    }
 }
 
 struct Logger {
@@ -60,8 +74,14 @@ impl log::Log for Logger {
         buf.clear();
         fmt::write(buf, *record.args()).unwrap();
 
-        // Log the buffer to the console.
-        sys::console_log(level, Some(record.target()), record.file(), record.line(), buf);
+        // Log the buffer to the console, along with the key-value pairs of the record, if any.
+        let fields = collect_fields(record.key_values());
+        if fields.is_empty() {
+            sys::console_log(level, Some(record.target()), record.file(), record.line(), buf);
+        } else {
+            let fields = bsatn::to_vec(&fields).unwrap();
+            sys::console_log_structured(level, Some(record.target()), record.file(), record.line(), buf, &fields);
+        }
 
         // If we allocated above `MAX_BUF_SIZE`, make sure we shrink below it.
         buf.shrink_to(MAX_BUF_SIZE);
@@ -70,6 +90,38 @@ impl log::Log for Logger {
     fn flush(&self) {}
 }
 
+/// Collects the key-value pairs of a record,
+/// keeping the type of values that are primitives and formatting the rest.
+fn collect_fields(source: &dyn kv::Source) -> Vec<LogField> {
+    struct Collect(Vec<LogField>);
+
+    impl<'kvs> kv::Visitor<'kvs> for Collect {
+        fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+            let value = if let Some(b) = value.to_bool() {
+                LogValue::Bool(b)
+            } else if let Some(i) = value.to_i64() {
+                LogValue::I64(i)
+            } else if let Some(u) = value.to_u64() {
+                LogValue::U64(u)
+            } else if let Some(f) = value.to_f64() {
+                LogValue::F64(f)
+            } else {
+                LogValue::Str(value.to_string())
+            };
+            self.0.push(LogField {
+                key: key.as_str().to_owned(),
+                value,
+            });
+            Ok(())
+        }
+    }
+
+    let mut collect = Collect(Vec::new());
+    // `Collect` never fails.
+    let _ = source.visit(&mut collect);
+    collect.0
+}
+
 /// Stores the buffer used for logging.
 static LOGGER: Logger = Logger {
     buf: Mutex::new(String::new()),
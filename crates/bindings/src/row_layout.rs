@@ -0,0 +1,111 @@
+//! Decoding the rows of a table stored in the layout of another version of its `TableType`.
+//!
+//! Rows are the BSATN of their columns in order, without tags telling which columns they have,
+//! so the version of their encoding is the layout the host sends ahead of them.
+//! When a new version of a module adds `Option` columns at the end of a table,
+//! the rows stored before still have the columns of the previous version.
+//! They're decoded in that layout and upgraded to the current one here,
+//! rather than by BSATN, which stays strict about the length of every other value.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use spacetimedb_lib::buffer::BufReader;
+use spacetimedb_lib::{bsatn, AlgebraicType, AlgebraicValue, ProductType};
+
+use crate::extensions::current_extensions;
+use crate::rt::{table_row_type, ModuleBuilder};
+use crate::{decode_row, decode_schema, decode_table_row, sys, TableType};
+
+/// The stored layouts of the tables read during the current reducer call, by table id,
+/// so each one is only fetched from the host once per call.
+#[derive(Clone, Default)]
+struct StoredLayouts(Rc<RefCell<HashMap<u32, Option<Rc<StoredLayout>>>>>);
+
+/// The layout rows are stored in, when it isn't the current layout of the rows of `T`.
+pub(crate) struct StoredLayout {
+    /// The layout of the stored rows, as sent by the host.
+    stored: ProductType,
+    /// The number of columns of `T`, without the hidden `deleted_at` of a soft-deleting table.
+    columns: usize,
+}
+
+impl StoredLayout {
+    /// Returns the layout `stored` of the rows of the table of `T`,
+    /// or `None` if it's the current one, in which rows decode as `T` as they are.
+    ///
+    /// Panics if `stored` lacks a column of `T` that isn't an `Option`, as it has no default.
+    pub(crate) fn new<T: TableType>(stored: ProductType) -> Option<Self> {
+        let columns = T::COLUMN_ATTRS.len();
+        let stored_columns = stored.elements.len().saturating_sub(T::SOFT_DELETE as usize);
+        if stored_columns == columns {
+            return None;
+        }
+
+        let mut module = ModuleBuilder::default();
+        let row_type = table_row_type::<T>(&mut module);
+        let current = module.module.typespace[row_type]
+            .as_product()
+            .expect("table type isn't a product");
+        for column in current.elements.iter().take(columns).skip(stored_columns) {
+            let is_option = matches!(&column.algebraic_type, AlgebraicType::Sum(sum) if sum.as_option().is_some());
+            assert!(
+                is_option,
+                "the rows of `{}` are stored without its column `{}`, which isn't an `Option` to default to `None`",
+                T::TABLE_NAME,
+                column.name.as_deref().unwrap_or_default(),
+            );
+        }
+        Some(Self { stored, columns })
+    }
+
+    /// Returns the layout the rows of the table of `T` are stored in,
+    /// or `None` if it's the current one, as in [`StoredLayout::new`].
+    ///
+    /// For reads that don't start with the layout, like `filter_by_*` and `find_by_*`.
+    pub(crate) fn of<T: TableType>() -> Option<Rc<Self>> {
+        let table_id = T::table_id();
+        let fetch = || {
+            let schema = sys::table_row_type(table_id).expect("table_row_type failed").read();
+            let stored = decode_schema(&mut &schema[..]).expect("Failed to decode schema!");
+            Self::new::<T>(stored).map(Rc::new)
+        };
+        let Some(extensions) = current_extensions() else {
+            return fetch();
+        };
+        let layouts = extensions.get_or_insert_with(StoredLayouts::default);
+        let cached = layouts.0.borrow().get(&table_id).cloned();
+        cached.unwrap_or_else(|| {
+            let layout = fetch();
+            layouts.0.borrow_mut().insert(table_id, layout.clone());
+            layout
+        })
+    }
+
+    /// Decodes a row stored in this layout from `reader` as a row of `T`,
+    /// along with when it was deleted, as in [`decode_table_row`].
+    ///
+    /// The columns of `T` missing from the end of the stored row are `None`,
+    /// and the stored columns after those of `T` are dropped.
+    pub(crate) fn decode_row<'de, T: TableType>(&self, reader: &mut impl BufReader<'de>) -> (T, u64) {
+        let mut row = decode_row(&self.stored, reader).expect("Failed to decode row!");
+        let deleted_at = T::SOFT_DELETE.then(|| row.elements.pop()).flatten();
+        row.elements.resize(self.columns, AlgebraicValue::OptionNone());
+        row.elements.extend(deleted_at);
+        let bytes = bsatn::to_vec(&row).unwrap();
+        decode_table_row(&mut &bytes[..])
+    }
+}
+
+/// Decodes a row of `T` from `reader`, stored in `layout`, or in the current layout of `T` if `None`,
+/// along with when it was deleted, as in [`decode_table_row`].
+pub(crate) fn decode_stored_row<'de, T: TableType>(
+    layout: Option<&StoredLayout>,
+    reader: &mut impl BufReader<'de>,
+) -> (T, u64) {
+    match layout {
+        None => decode_table_row(reader),
+        Some(layout) => layout.decode_row(reader),
+    }
+}
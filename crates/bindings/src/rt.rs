@@ -7,42 +7,64 @@ use std::marker::PhantomData;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use crate::call_trace::with_call_traced;
+use crate::extensions::with_extensions_set;
 use crate::timestamp::with_timestamp_set;
-use crate::{sys, ReducerContext, ScheduleToken, SpacetimeType, TableType, Timestamp};
+use crate::{
+    sys, Continuation, EventType, PrimaryKeyTable, ReducerCall, ReducerContext, ScheduleToken, SpacetimeType,
+    TableType, Timestamp, UniqueConstraintViolation,
+};
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::de::{self, Deserialize, SeqProductAccess};
 use spacetimedb_lib::sats::typespace::TypespaceBuilder;
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, AlgebraicType, AlgebraicTypeRef, ProductTypeElement};
 use spacetimedb_lib::ser::{Serialize, SerializeSeqProduct};
-use spacetimedb_lib::{bsatn, Identity, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias};
+use spacetimedb_lib::{
+    bsatn, AccessHint, ColumnCompression, DeferredUnique, EventDef, HttpHandler, HttpMethod, HttpRoute, Identity, Lane,
+    MiscModuleExport, ModuleDef, ReducerAllow, ReducerCooldown, ReducerDef, ReducerPriority, ReducerReturn, RowVersion,
+    TableAccessHint, TableDef, TableTtl, TypeAlias,
+};
 use sys::Buffer;
 
 pub use once_cell::sync::{Lazy, OnceCell};
 
 /// The `sender` invokes `reducer` at `timestamp` and provides it with the given `args`.
 ///
-/// The `epilogue` is executed after `reducer` has finished.
+/// The registered before-reducer hooks run first, in order,
+/// and the first one to return an error rejects the call with it.
+/// The `epilogue` is executed after `reducer` has finished,
+/// followed by the registered after-reducer hooks.
 ///
 /// Returns an invalid buffer on success
 /// and otherwise the error is written into the fresh one returned.
 pub fn invoke_reducer<'a, A: Args<'a>, T>(
+    name: &'static str,
     reducer: impl Reducer<'a, A, T>,
     sender: Buffer,
     timestamp: u64,
     args: &'a [u8],
     epilogue: impl FnOnce(Result<(), &str>),
 ) -> Buffer {
-    let ctx = assemble_context(sender, timestamp);
-
-    // Deserialize the arguments from a bsatn encoding.
-    let SerDeArgs(args) = bsatn::from_slice(args).expect("unable to decode args");
-
-    // Run the reducer with the timestamp set.
-    let res = with_timestamp_set(ctx.timestamp, || {
-        let res: Result<(), Box<str>> = reducer.invoke(ctx, args);
-        // Then run the epilogue.
-        epilogue(res.as_ref().map(|()| ()).map_err(|e| &**e));
-        res
+    // Trace the call, so that a panic in it is logged with the reducer, its arguments and what it did.
+    let res = with_call_traced(name, args, || {
+        let ctx = assemble_context(sender, timestamp);
+        let call = ReducerCall { name, ctx, args };
+
+        // Deserialize the arguments from a bsatn encoding.
+        let SerDeArgs(args) = bsatn::from_slice(args).expect("unable to decode args");
+
+        // Run the hooks and the reducer with the timestamp set, sharing a fresh stash.
+        with_timestamp_set(ctx.timestamp, || {
+            with_extensions_set(|| {
+                let res: Result<(), Box<str>> =
+                    run_before_reducer_hooks(&call).and_then(|()| reducer.invoke(ctx, args));
+                let res_ref = res.as_ref().map(|()| ()).map_err(|e| &**e);
+                // Then run the epilogue and the after-reducer hooks.
+                epilogue(res_ref);
+                run_after_reducer_hooks(&call, res_ref);
+                res
+            })
+        })
     });
 
     // Any error is pushed into a `Buffer`.
@@ -68,14 +90,16 @@ pub fn create_index(index_name: &str, table_id: u32, index_type: sys::raw::Index
 
 /// Runs the on-connect function `f` provided with a new reducer context
 /// created from `sender` and `timestamp`.
-pub fn invoke_connection_func<R: ReducerResult>(
+///
+/// There's no caller to return a value to, so `f` returns none.
+pub fn invoke_connection_func<R: ReducerResult<Return = ()>>(
     f: impl Fn(ReducerContext) -> R,
     sender: Buffer,
     timestamp: u64,
 ) -> Buffer {
     let ctx = assemble_context(sender, timestamp);
 
-    let res = with_timestamp_set(ctx.timestamp, || f(ctx).into_result());
+    let res = with_timestamp_set(ctx.timestamp, || with_extensions_set(|| f(ctx).into_result()));
     cvt_result(res)
 }
 
@@ -85,7 +109,13 @@ fn assemble_context(sender: Buffer, timestamp: u64) -> ReducerContext {
 
     let timestamp = Timestamp::UNIX_EPOCH + Duration::from_micros(timestamp);
 
-    ReducerContext { sender, timestamp }
+    let tx_offset = sys::tx_offset().expect("tx_offset failed");
+
+    ReducerContext {
+        sender,
+        timestamp,
+        tx_offset,
+    }
 }
 
 /// Converts `errno` into a string message.
@@ -107,9 +137,24 @@ fn cvt_result(res: Result<(), Box<str>>) -> Buffer {
 ///
 /// The type parameter `T` is used for determining whether there is a context argument.
 pub trait Reducer<'de, A: Args<'de>, T> {
+    /// The type of the value the reducer returns to its caller, `()` for none.
+    type Return: SpacetimeType + Serialize;
+
+    /// Runs the reducer, handing the value it returns, if any, to the host.
     fn invoke(&self, ctx: ReducerContext, args: A) -> Result<(), Box<str>>;
 }
 
+/// Hands `value`, returned by the running reducer, to the host to deliver to its caller.
+///
+/// A value that encodes to no bytes, e.g., `()`, isn't returned.
+fn set_return_value(value: &impl Serialize) -> Result<(), Box<str>> {
+    let bytes = bsatn::to_vec(value).expect("unable to encode return value");
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    sys::set_return_value(&bytes).map_err(cvt_errno)
+}
+
 /// A trait for types that can *describe* a reducer.
 pub trait ReducerInfo {
     /// The name of the reducer.
@@ -120,6 +165,25 @@ pub trait ReducerInfo {
 
     /// The function to call to invoke the reducer.
     const INVOKE: ReducerFn;
+
+    /// Whether the reducer only reads from the database,
+    /// in which case the host runs it in a read-only transaction and rejects any writes.
+    const READ_ONLY: bool = false;
+
+    /// The roles allowed to call the reducer, or none if anyone can.
+    ///
+    /// The host rejects calls from identities holding none of the roles.
+    const ALLOW: &'static [&'static str] = &[];
+
+    /// How long a caller has to wait between calls to the reducer, if at all.
+    ///
+    /// The host rejects the calls made sooner by the same identity.
+    const COOLDOWN: Option<Duration> = None;
+
+    /// The lane the calls to the reducer wait in to be run.
+    ///
+    /// The host runs the calls in a higher lane ahead of those in lower ones.
+    const PRIORITY: Lane = Lane::Normal;
 }
 
 /// A trait for reducer types knowing their repeat interval.
@@ -161,19 +225,25 @@ impl<'de, T: Args<'de>> ScheduleArgs<'de> for T {
 
 /// A trait of types representing the result of executing a reducer.
 pub trait ReducerResult {
-    /// Convert the result into form where there is no value
-    /// and the error message is a string.
-    fn into_result(self) -> Result<(), Box<str>>;
+    /// The type of the value returned to the caller, `()` for none.
+    type Return: SpacetimeType + Serialize;
+
+    /// Convert the result into form where the error message is a string.
+    fn into_result(self) -> Result<Self::Return, Box<str>>;
 }
 impl ReducerResult for () {
+    type Return = ();
+
     #[inline]
     fn into_result(self) -> Result<(), Box<str>> {
         Ok(self)
     }
 }
-impl<E: fmt::Debug> ReducerResult for Result<(), E> {
+impl<T: SpacetimeType + Serialize, E: fmt::Debug> ReducerResult for Result<T, E> {
+    type Return = T;
+
     #[inline]
-    fn into_result(self) -> Result<(), Box<str>> {
+    fn into_result(self) -> Result<T, Box<str>> {
         self.map_err(|e| format!("{e:?}").into())
     }
 }
@@ -287,10 +357,12 @@ macro_rules! impl_reducer {
             Func: Fn(ReducerContext, $($T),*) -> Ret,
             Ret: ReducerResult
         {
+            type Return = Ret::Return;
+
             fn invoke(&self, ctx: ReducerContext, args: ($($T,)*)) -> Result<(), Box<str>> {
                 #[allow(non_snake_case)]
                 let ($($T,)*) = args;
-                self(ctx, $($T),*).into_result()
+                set_return_value(&self(ctx, $($T),*).into_result()?)
             }
         }
 
@@ -300,10 +372,12 @@ macro_rules! impl_reducer {
             Func: Fn($($T),*) -> Ret,
             Ret: ReducerResult
         {
+            type Return = Ret::Return;
+
             fn invoke(&self, _ctx: ReducerContext, args: ($($T,)*)) -> Result<(), Box<str>> {
                 #[allow(non_snake_case)]
                 let ($($T,)*) = args;
-                self($($T),*).into_result()
+                set_return_value(&self($($T),*).into_result()?)
             }
         }
     };
@@ -317,7 +391,7 @@ macro_rules! impl_reducer {
 impl_reducer!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, AA, AB, AC, AD, AE, AF);
 
 /// Provides deserialization and serialization for any type `A: Args`.
-struct SerDeArgs<A>(A);
+pub(crate) struct SerDeArgs<A>(pub(crate) A);
 impl_deserialize!(
     [A: Args<'de>] SerDeArgs<A>,
     de => de.deserialize_product(ArgsVisitor { _marker: PhantomData }).map(Self)
@@ -376,6 +450,52 @@ impl RepeaterArgs for (Timestamp,) {
     }
 }
 
+/// A row of the hidden table in which an `async` reducer keeps the variables it captured at an `.await`,
+/// until its continuation calls the reducer [`Self::RESUME_REDUCER`] to resume it.
+pub trait SuspendedState:
+    PrimaryKeyTable<PrimaryKey = u64, InsertResult = Result<Self, UniqueConstraintViolation<Self>>>
+{
+    /// The name of the hidden reducer that resumes the `async` reducer.
+    const RESUME_REDUCER: &'static str;
+
+    /// Returns a row for the variables encoded in `state`, captured to resume at `step`.
+    fn new(step: u32, state: Vec<u8>) -> Self;
+
+    /// Returns the id of the row, assigned when it's inserted.
+    fn id(&self) -> u64;
+
+    /// Returns the step to resume at and the variables captured for it.
+    fn into_step(self) -> (u32, Vec<u8>);
+}
+
+/// Suspends an `async` reducer at an `.await` on `continuation`,
+/// saving the variables in `state` to resume at `step` once it completes.
+pub fn suspend<'de, S: SuspendedState, A: Args<'de>>(continuation: impl Continuation, step: u32, state: A) {
+    let state = bsatn::to_vec(&SerDeArgs(state)).expect("unable to encode the state of an async reducer");
+    let row = S::insert(S::new(step, state)).unwrap_or_else(|e| panic!("{e}"));
+    continuation.schedule(S::RESUME_REDUCER, row.id());
+}
+
+/// Takes the state of the `async` reducer suspended as `id`,
+/// returning the step to resume at and the variables captured for it.
+pub fn resume<S: SuspendedState>(id: u64) -> (u32, Vec<u8>) {
+    let row = S::get(&id).unwrap_or_else(|| panic!("no suspended state {id} in {}", S::TABLE_NAME));
+    S::delete(&id);
+    row.into_step()
+}
+
+/// Decodes the variables captured by an `async` reducer, as returned by [`resume`].
+pub fn decode_state<'a, A: Args<'a>>(state: &'a [u8]) -> A {
+    let SerDeArgs(state) = bsatn::from_slice(state).expect("unable to decode the state of an async reducer");
+    state
+}
+
+/// Returns the context of a step of an `async` reducer resumed in `ctx`,
+/// which was first called by `sender`.
+pub fn resumed_context(ctx: ReducerContext, sender: Identity) -> ReducerContext {
+    ReducerContext { sender, ..ctx }
+}
+
 /// Registers into `DESCRIBERS` a function `f` to modify the module builder.
 fn register_describer(f: fn(&mut ModuleBuilder)) {
     DESCRIBERS.lock().unwrap().push(f)
@@ -391,7 +511,7 @@ pub fn register_reftype<T: SpacetimeType>() {
 /// Registers a describer for the `TableType` `T`.
 pub fn register_table<T: TableType>() {
     register_describer(|module| {
-        let data = *T::make_type(module).as_ref().unwrap();
+        let data = table_row_type::<T>(module);
         let schema = TableDef {
             name: T::TABLE_NAME.into(),
             data,
@@ -400,7 +520,146 @@ pub fn register_table<T: TableType>() {
             table_type: StTableType::User,
             table_access: StAccess::for_name(T::TABLE_NAME),
         };
-        module.module.tables.push(schema)
+        module.module.tables.push(schema);
+        for &col_id in T::DEFERRED_UNIQUE {
+            let deferred = DeferredUnique {
+                table_name: T::TABLE_NAME.into(),
+                col_id,
+            };
+            module
+                .module
+                .misc_exports
+                .push(MiscModuleExport::DeferredUnique(deferred))
+        }
+        for &(col_id, codec) in T::COMPRESSED_COLUMNS {
+            let compression = ColumnCompression {
+                table_name: T::TABLE_NAME.into(),
+                col_id,
+                codec,
+            };
+            module
+                .module
+                .misc_exports
+                .push(MiscModuleExport::ColumnCompression(compression))
+        }
+        if let Some((col_id, key_col_id)) = T::ROW_VERSION {
+            let version = RowVersion {
+                table_name: T::TABLE_NAME.into(),
+                col_id,
+                key_col_id,
+            };
+            module.module.misc_exports.push(MiscModuleExport::RowVersion(version))
+        }
+    })
+}
+
+/// Returns the type of the rows of the table of the `TableType` `T`.
+///
+/// That's `T` itself, unless the table is [soft-deleting](TableType::SOFT_DELETE),
+/// in which case its rows have a hidden `deleted_at` column after the fields of `T`.
+pub(crate) fn table_row_type<T: TableType>(module: &mut ModuleBuilder) -> AlgebraicTypeRef {
+    let data = *T::make_type(module).as_ref().unwrap();
+    if !T::SOFT_DELETE {
+        return data;
+    }
+    let mut row_type = module.module.typespace[data]
+        .as_product()
+        .expect("table type isn't a product")
+        .clone();
+    row_type
+        .elements
+        .push(ProductTypeElement::new_named(AlgebraicType::U64, "deleted_at"));
+    module.module.typespace.add(AlgebraicType::Product(row_type))
+}
+
+/// Returns the id of the table of the `TableType` `T`.
+///
+/// With the `testing` feature, the mock host first learns the schema of the table.
+pub fn table_id<T: TableType>() -> u32 {
+    #[cfg(feature = "testing")]
+    crate::testing::register_table::<T>();
+    crate::get_table_id(T::TABLE_NAME)
+}
+
+/// Registers a describer for the `EventType` `T`.
+pub fn register_event<T: EventType>() {
+    register_describer(|module| {
+        let ty = *T::make_type(module).as_ref().unwrap();
+        let event = EventDef {
+            name: T::EVENT_NAME.into(),
+            ty,
+        };
+        module.module.misc_exports.push(MiscModuleExport::Event(event))
+    })
+}
+
+/// A table declared with an access hint,
+/// as in `#[spacetimedb(table, append_only)]` or `#[spacetimedb(table, read_mostly)]`.
+pub trait HasAccessHint: TableType {
+    const ACCESS_HINT: AccessHint;
+}
+
+/// Registers a describer for the access hint of the `TableType` `T`.
+pub fn register_access_hint<T: HasAccessHint>() {
+    register_describer(|module| {
+        let hint = TableAccessHint {
+            table_name: T::TABLE_NAME.into(),
+            hint: T::ACCESS_HINT,
+        };
+        module.module.misc_exports.push(MiscModuleExport::TableAccessHint(hint))
+    })
+}
+
+/// A table whose rows expire,
+/// as in `#[spacetimedb(table, ttl = "24h", ttl_column = "created_at")]`.
+pub trait HasTtl: TableType {
+    /// How long after the timestamp in [`Self::TTL_COLUMN`] a row is deleted.
+    const TTL: Duration;
+    /// The name of the `Timestamp` column rows expire from.
+    const TTL_COLUMN: &'static str;
+}
+
+/// Registers a describer for the expiry of the rows of the `TableType` `T`.
+pub fn register_ttl<T: HasTtl>() {
+    register_describer(|module| {
+        let ttl = TableTtl {
+            table_name: T::TABLE_NAME.into(),
+            ttl_micros: T::TTL.as_micros() as u64,
+            column: T::TTL_COLUMN.into(),
+        };
+        module.module.misc_exports.push(MiscModuleExport::TableTtl(ttl))
+    })
+}
+
+/// An HTTP endpoint the host serves for the module,
+/// as in `#[spacetimedb(reducer, read_only, http = "GET /leaderboard")]`
+/// or `#[spacetimedb(http = "GET /health")]` on a `const` of SQL text.
+pub trait HttpRouteInfo {
+    const METHOD: HttpMethod;
+    const PATH: &'static str;
+    const TARGET: HttpTarget;
+}
+
+/// What serves the requests to an [`HttpRouteInfo`].
+pub enum HttpTarget {
+    /// The read-only reducer of this name.
+    Reducer(&'static str),
+    /// This SQL query.
+    Sql(&'static str),
+}
+
+/// Registers a describer for the HTTP endpoint `R`.
+pub fn register_http_route<R: HttpRouteInfo>() {
+    register_describer(|module| {
+        let route = HttpRoute {
+            method: R::METHOD,
+            path: R::PATH.into(),
+            handler: match R::TARGET {
+                HttpTarget::Reducer(name) => HttpHandler::Reducer(name.into()),
+                HttpTarget::Sql(sql) => HttpHandler::Sql(sql.into()),
+            },
+        };
+        module.module.misc_exports.push(MiscModuleExport::HttpRoute(route))
     })
 }
 
@@ -414,20 +673,59 @@ impl From<crate::IndexDef<'_>> for spacetimedb_lib::IndexDef {
     }
 }
 
-/// Registers a describer for the reducer `I` with arguments `A`.
-pub fn register_reducer<'a, A: Args<'a>, T, I: ReducerInfo>(_: impl Reducer<'a, A, T>) {
+/// Registers a describer for the reducer `I` with arguments `A`, returning `R::Return`.
+pub fn register_reducer<'a, A: Args<'a>, T, I: ReducerInfo, R: Reducer<'a, A, T>>(_: R) {
     register_describer(|module| {
         let schema = A::schema::<I>(module);
         module.module.reducers.push(schema);
         module.reducers.push(I::INVOKE);
+        if I::READ_ONLY {
+            let name = I::NAME.into();
+            module.module.misc_exports.push(MiscModuleExport::ReadOnlyReducer(name));
+        }
+        if !I::ALLOW.is_empty() {
+            let allow = ReducerAllow {
+                reducer_name: I::NAME.into(),
+                roles: I::ALLOW.iter().map(|&role| role.into()).collect(),
+            };
+            module.module.misc_exports.push(MiscModuleExport::ReducerAllow(allow));
+        }
+        if let Some(cooldown) = I::COOLDOWN {
+            let cooldown = ReducerCooldown {
+                reducer_name: I::NAME.into(),
+                cooldown_micros: cooldown.as_micros() as u64,
+            };
+            module
+                .module
+                .misc_exports
+                .push(MiscModuleExport::ReducerCooldown(cooldown));
+        }
+        if I::PRIORITY != Lane::Normal {
+            let priority = ReducerPriority {
+                reducer_name: I::NAME.into(),
+                lane: I::PRIORITY,
+            };
+            module
+                .module
+                .misc_exports
+                .push(MiscModuleExport::ReducerPriority(priority));
+        }
+        let ty = R::Return::make_type(module);
+        if ty != AlgebraicType::UNIT_TYPE {
+            let ret = ReducerReturn {
+                reducer_name: I::NAME.into(),
+                ty,
+            };
+            module.module.misc_exports.push(MiscModuleExport::ReducerReturn(ret));
+        }
     })
 }
 
 /// A builder for a module.
 #[derive(Default)]
-struct ModuleBuilder {
+pub(crate) struct ModuleBuilder {
     /// The module definition.
-    module: ModuleDef,
+    pub(crate) module: ModuleDef,
     /// The reducers of the module.
     reducers: Vec<ReducerFn>,
     /// The type map from `T: 'static` Rust types to sats types.
@@ -470,10 +768,53 @@ impl TypespaceBuilder for ModuleBuilder {
 // Not actually a mutex; because WASM is single-threaded this basically just turns into a refcell.
 static DESCRIBERS: Mutex<Vec<fn(&mut ModuleBuilder)>> = Mutex::new(Vec::new());
 
+/// A hook run before every reducer, which rejects the call by returning an error.
+pub type BeforeReducerHook = fn(&ReducerCall<'_>) -> Result<(), String>;
+static BEFORE_REDUCER_HOOKS: Mutex<Vec<BeforeReducerHook>> = Mutex::new(Vec::new());
+
+/// A hook run after every reducer, provided with the result of the call.
+pub type AfterReducerHook = fn(&ReducerCall<'_>, Result<(), &str>);
+static AFTER_REDUCER_HOOKS: Mutex<Vec<AfterReducerHook>> = Mutex::new(Vec::new());
+
+/// Registers `hook` to run before every reducer.
+pub fn register_before_reducer(hook: BeforeReducerHook) {
+    BEFORE_REDUCER_HOOKS.lock().unwrap().push(hook)
+}
+
+/// Registers `hook` to run after every reducer.
+pub fn register_after_reducer(hook: AfterReducerHook) {
+    AFTER_REDUCER_HOOKS.lock().unwrap().push(hook)
+}
+
+/// Runs the before-reducer hooks on `call`, stopping at the first error.
+fn run_before_reducer_hooks(call: &ReducerCall<'_>) -> Result<(), Box<str>> {
+    // Copy the hooks out so that none of them runs with the lock held.
+    let hooks = BEFORE_REDUCER_HOOKS.lock().unwrap().clone();
+    hooks
+        .iter()
+        .try_for_each(|hook| hook(call).map_err(String::into_boxed_str))
+}
+
+/// Runs the after-reducer hooks on `call` and its result `res`.
+fn run_after_reducer_hooks(call: &ReducerCall<'_>, res: Result<(), &str>) {
+    let hooks = AFTER_REDUCER_HOOKS.lock().unwrap().clone();
+    for hook in hooks {
+        hook(call, res)
+    }
+}
+
 /// A reducer function takes in `(Sender, Timestamp, Args)` and writes to a new `Buffer`.
 pub type ReducerFn = fn(Buffer, u64, &[u8]) -> Buffer;
 static REDUCERS: OnceCell<Vec<ReducerFn>> = OnceCell::new();
 
+/// The names of the `REDUCERS`, in the same order.
+static REDUCER_NAMES: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Returns the names of the reducers of the module, once it has been described.
+pub fn reducer_names() -> &'static [String] {
+    REDUCER_NAMES.get().map_or(&[], Vec::as_slice)
+}
+
 /// Describes the module into a serialized form that is returned and writes the set of `REDUCERS`.
 #[no_mangle]
 extern "C" fn __describe_module__() -> Buffer {
@@ -488,6 +829,8 @@ extern "C" fn __describe_module__() -> Buffer {
 
     // Write the set of reducers.
     REDUCERS.set(module.reducers).ok().unwrap();
+    let names = module.module.reducers.into_iter().map(|reducer| reducer.name).collect();
+    REDUCER_NAMES.set(names).ok().unwrap();
 
     // Allocate the bsatn data into a fresh buffer.
     Buffer::alloc(&bytes)
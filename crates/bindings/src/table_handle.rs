@@ -0,0 +1,107 @@
+//! Defines a `TableHandle`, for operating on tables not known at compile time.
+
+use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
+
+use crate::call_trace::{self, TableOp};
+use crate::{
+    buffer_table_iter, decode_row, delete_by_col_eq, iter_by_col_eq, pv_table_iter, sys, with_row_buf, Result,
+};
+
+/// A handle to a table found by name at runtime,
+/// whose rows are read and written as `ProductValue`s of its schema.
+///
+/// This is the dynamic counterpart of the [`TableType`](crate::TableType)
+/// that `#[spacetimedb(table)]` generates for a table known at compile time.
+/// Rows are as the database stores them,
+/// so they include any hidden columns, e.g., `deleted_at` of a soft-deleting table.
+#[derive(Clone, Debug)]
+pub struct TableHandle {
+    /// The name of the table.
+    name: String,
+    /// The id of the table.
+    table_id: u32,
+    /// The type of the rows of the table.
+    schema: ProductType,
+}
+
+impl TableHandle {
+    /// Returns a handle to the table named `name`, or `None` if there's no such table.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let table_id = sys::get_table_id(name).ok()?;
+        // The first item of an iterator over the table is its schema.
+        let (_, schema) = buffer_table_iter(table_id, None).ok()?;
+        Some(Self {
+            name: name.to_owned(),
+            table_id,
+            schema,
+        })
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the id of the table.
+    pub fn table_id(&self) -> u32 {
+        self.table_id
+    }
+
+    /// Returns the type of the rows of the table.
+    pub fn schema(&self) -> &ProductType {
+        &self.schema
+    }
+
+    /// Returns the names and types of the columns of the table, in order.
+    pub fn columns(&self) -> impl Iterator<Item = (Option<&str>, &AlgebraicType)> {
+        self.schema
+            .elements
+            .iter()
+            .map(|col| (col.name.as_deref(), &col.algebraic_type))
+    }
+
+    /// Returns the id of the column named `name`, if the table has one.
+    pub fn column_id(&self, name: &str) -> Option<u8> {
+        let col_id = self
+            .schema
+            .elements
+            .iter()
+            .position(|col| col.name.as_deref() == Some(name))?;
+        col_id.try_into().ok()
+    }
+
+    /// Inserts `row` into the table,
+    /// returning it as inserted, with any auto-incremented columns filled in.
+    ///
+    /// The reducer fails if `row` doesn't fit the schema of the table.
+    pub fn insert_dyn(&self, row: ProductValue) -> Result<ProductValue> {
+        with_row_buf(|bytes| {
+            row.encode(bytes);
+            call_trace::record(TableOp::Insert, self.table_id);
+            sys::insert(self.table_id, bytes)?;
+            Ok(decode_row(&self.schema, &mut &bytes[..]).expect("Failed to decode row!"))
+        })
+    }
+
+    /// Returns an iterator over the rows of the table.
+    pub fn iter_dyn(&self) -> impl Iterator<Item = ProductValue> {
+        pv_table_iter(self.table_id, None).unwrap()
+    }
+
+    /// Returns the rows of the table where the column `col_id` equals `value`.
+    pub fn filter_dyn(&self, col_id: u8, value: &AlgebraicValue) -> Result<Vec<ProductValue>> {
+        let rows = iter_by_col_eq(self.table_id, col_id, value)?.read();
+        let mut rows = &rows[..];
+        let mut found = Vec::new();
+        while !rows.is_empty() {
+            found.push(decode_row(&self.schema, &mut rows).expect("Failed to decode row!"));
+        }
+        Ok(found)
+    }
+
+    /// Deletes the rows of the table where the column `col_id` equals `value`,
+    /// returning how many were deleted.
+    pub fn delete_dyn(&self, col_id: u8, value: &AlgebraicValue) -> Result<u32> {
+        delete_by_col_eq(self.table_id, col_id, value)
+    }
+}
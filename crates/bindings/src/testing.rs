@@ -0,0 +1,546 @@
+//! Unit testing of modules natively, without publishing them to a node.
+//!
+//! With the `testing` feature, the sys calls are served in-process by a mock host,
+//! whose tables are kept in memory, one set per thread.
+//! So each `#[test]` starts out with empty tables,
+//! and can call reducers as the plain functions they are:
+//!
+//! ```ignore
+//! #[test]
+//! fn add_person_inserts_a_row() {
+//!     let ctx = testing::reducer_context(Identity::__dummy(), Timestamp::UNIX_EPOCH);
+//!     testing::call_reducer(ctx, |ctx| add_person(ctx, "Alice".into()));
+//!     assert_eq!(Person::iter().count(), 1);
+//! }
+//! ```
+//!
+//! Reducers the module schedules aren't run, nor are the messages it sends delivered,
+//! but they can be inspected with [`scheduled_reducers`] and [`take_sent_messages`].
+//!
+//! To test how a module handles failures, [`inject_faults`] makes the mock host fail some calls
+//! as planned by a seeded [`FaultPlan`], the same ones on each run:
+//!
+//! ```ignore
+//! #[test]
+//! fn add_person_survives_failed_inserts() {
+//!     testing::inject_faults(FaultPlan {
+//!         seed: 42,
+//!         insert_exists: 1.0,
+//!         ..FaultPlan::default()
+//!     });
+//!     let ctx = testing::reducer_context(Identity::__dummy(), Timestamp::UNIX_EPOCH);
+//!     assert!(testing::call_reducer(ctx, |ctx| add_person(ctx, "Alice".into())).is_err());
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use spacetimedb_lib::de::DeserializeSeed;
+use spacetimedb_lib::filter::{Cmp, CmpArgs, Expr, Logic, Rhs, Unary};
+use spacetimedb_lib::hash::hash_bytes;
+use spacetimedb_lib::operator::{OpCmp, OpLogic, OpUnary};
+use spacetimedb_lib::sats::{AlgebraicType, BuiltinType, BuiltinValue, ProductType, Typespace};
+use spacetimedb_lib::{
+    bsatn, fulltext, spatial, AlgebraicValue, ColumnIndexAttribute, DataKey, ProductValue, MAX_BLOB_SIZE,
+};
+
+use crate::extensions::with_extensions_set;
+use crate::sys::mock::{self, MockHost};
+pub use crate::sys::mock::{FaultPlan, ScheduledReducer, SentMessage};
+use crate::timestamp::with_timestamp_set;
+use crate::{rt, DeserializeOwned, Errno, EventType, Identity, ReducerContext, TableType, Timestamp};
+
+/// Returns a context for calling a reducer as `sender` at `timestamp`.
+///
+/// Its `tx_offset` is 0, which a test can overwrite.
+pub fn reducer_context(sender: Identity, timestamp: Timestamp) -> ReducerContext {
+    ReducerContext {
+        sender,
+        timestamp,
+        tx_offset: 0,
+    }
+}
+
+/// Calls `reducer` with `ctx`, as the host would,
+/// so that [`Timestamp::now`] returns `ctx.timestamp` during the call,
+/// and [`ctx.extensions()`](ReducerContext::extensions) starts out empty.
+///
+/// Unlike with the host, the changes made by a reducer which fails or panics aren't rolled back.
+pub fn call_reducer<R>(ctx: ReducerContext, reducer: impl FnOnce(ReducerContext) -> R) -> R {
+    mock::set_host_factory(new_datastore);
+    with_timestamp_set(ctx.timestamp, || with_extensions_set(|| reducer(ctx)))
+}
+
+/// Clears the tables, scheduled reducers, emitted events, sent messages and fault plan of the current thread.
+pub fn reset() {
+    mock::reset()
+}
+
+/// Makes the mock host fail the calls of the current thread as planned by `plan`, from now on:
+/// inserts failing with [`Errno::UNIQUE_ALREADY_EXISTS`], table iterators ending early,
+/// and scheduled reducers delayed.
+///
+/// The failures are drawn from a generator seeded with `plan.seed`,
+/// so a test making the same calls sees the same failures on each run.
+pub fn inject_faults(plan: FaultPlan) {
+    mock::inject_faults(plan)
+}
+
+/// Stops the mock host from failing the calls of the current thread.
+pub fn clear_faults() {
+    mock::clear_faults()
+}
+
+/// Returns the reducers scheduled on the current thread which haven't been cancelled.
+pub fn scheduled_reducers() -> Vec<ScheduledReducer> {
+    mock::scheduled_reducers()
+}
+
+/// Removes and returns the messages sent to other databases on the current thread,
+/// in the order they were sent.
+pub fn take_sent_messages() -> Vec<SentMessage> {
+    mock::take_sent_messages()
+}
+
+/// Removes and returns the events of type `E` emitted on the current thread,
+/// in the order they were emitted.
+pub fn take_emitted_events<E: EventType + DeserializeOwned>() -> Vec<E> {
+    mock::take_emitted_events(E::EVENT_NAME)
+        .iter()
+        .map(|data| bsatn::from_slice(data).expect("failed to decode emitted event"))
+        .collect()
+}
+
+/// The schema of a table, as the mock host knows it.
+struct TableDesc {
+    name: &'static str,
+    typespace: Typespace,
+    row_type: ProductType,
+    column_attrs: &'static [ColumnIndexAttribute],
+    /// The version column and the key column, as in [`TableType::ROW_VERSION`].
+    row_version: Option<(u8, u8)>,
+}
+
+/// The tables registered with the mock host, indexed by their ids.
+///
+/// Shared by all threads, so that the table ids cached by `TableType::table_id` hold on each.
+static TABLES: Mutex<Vec<Arc<TableDesc>>> = Mutex::new(Vec::new());
+
+/// Makes the table of the `TableType` `T` known to the mock host.
+pub(crate) fn register_table<T: TableType>() {
+    mock::set_host_factory(new_datastore);
+
+    let mut tables = TABLES.lock().unwrap();
+    if tables.iter().any(|table| table.name == T::TABLE_NAME) {
+        return;
+    }
+    let mut module = rt::ModuleBuilder::default();
+    let data = rt::table_row_type::<T>(&mut module);
+    let typespace = module.module.typespace;
+    let row_type = typespace[data]
+        .as_product()
+        .expect("table type isn't a product")
+        .clone();
+    tables.push(Arc::new(TableDesc {
+        name: T::TABLE_NAME,
+        typespace,
+        row_type,
+        column_attrs: T::COLUMN_ATTRS,
+        row_version: T::ROW_VERSION,
+    }));
+}
+
+#[derive(Clone)]
+struct MockTable {
+    desc: Arc<TableDesc>,
+    rows: Vec<ProductValue>,
+    /// The versions of the deleted rows of a table with a row version, by key,
+    /// which a row inserted with the same key bumps.
+    ///
+    /// Unlike the host, which forgets them when the transaction ends,
+    /// they're kept until a row with the key is inserted again, even by a later reducer call.
+    deleted_versions: HashMap<AlgebraicValue, u64>,
+}
+
+impl MockTable {
+    fn column_type(&self, col_id: u32) -> Result<&AlgebraicType, Errno> {
+        let column = self.desc.row_type.elements.get(col_id as usize);
+        column.map(|col| &col.algebraic_type).ok_or(Errno::NO_SUCH_TABLE)
+    }
+
+    fn decode(&self, ty: &AlgebraicType, mut bytes: &[u8]) -> AlgebraicValue {
+        let de = bsatn::Deserializer::new(&mut bytes);
+        self.desc
+            .typespace
+            .with_type(ty)
+            .deserialize(de)
+            .expect("failed to decode value")
+    }
+
+    fn decode_row(&self, mut bytes: &[u8]) -> ProductValue {
+        let de = bsatn::Deserializer::new(&mut bytes);
+        let row_type = self.desc.typespace.with_type(&self.desc.row_type);
+        row_type.deserialize(de).expect("failed to decode row")
+    }
+
+    /// Decodes the concatenated rows in `bytes`.
+    fn decode_rows(&self, mut bytes: &[u8]) -> Vec<ProductValue> {
+        let mut rows = Vec::new();
+        while !bytes.is_empty() {
+            let de = bsatn::Deserializer::new(&mut bytes);
+            let row_type = self.desc.typespace.with_type(&self.desc.row_type);
+            rows.push(row_type.deserialize(de).expect("failed to decode row"));
+        }
+        rows
+    }
+
+    /// Returns the concatenated rows for which `f` holds for the value of the column `col_id`.
+    fn encode_rows_where(&self, col_id: u32, f: impl Fn(&AlgebraicValue) -> bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for row in self.rows.iter().filter(|row| f(&row.elements[col_id as usize])) {
+            bsatn::to_writer(&mut bytes, row).unwrap();
+        }
+        bytes
+    }
+}
+
+fn new_datastore() -> Box<dyn MockHost> {
+    Box::<MockDatastore>::default()
+}
+
+/// An in-memory datastore standing in for the host's.
+#[derive(Default)]
+struct MockDatastore {
+    tables: HashMap<u32, MockTable>,
+    /// The last values generated for the auto-incremented columns, by table and column.
+    ///
+    /// Like those of the host, they aren't restored by rolling back to a savepoint.
+    sequences: HashMap<(u32, usize), i128>,
+    /// The blobs, by key.
+    blobs: HashMap<String, Vec<u8>>,
+    /// The values of the `LargeBytes` columns, by encoded key.
+    ///
+    /// Unlike the host, the values no row references are never deleted.
+    large_values: HashMap<Vec<u8>, Vec<u8>>,
+    next_savepoint: u32,
+    savepoints: Vec<Savepoint>,
+}
+
+/// The tables and blobs as of a savepoint.
+struct Savepoint {
+    id: u32,
+    tables: HashMap<u32, MockTable>,
+    blobs: HashMap<String, Vec<u8>>,
+}
+
+impl MockDatastore {
+    fn table(&mut self, table_id: u32) -> Result<&mut MockTable, Errno> {
+        if !self.tables.contains_key(&table_id) {
+            let desc = TABLES.lock().unwrap().get(table_id as usize).cloned();
+            let desc = desc.ok_or(Errno::NO_SUCH_TABLE)?;
+            let table = MockTable {
+                desc,
+                rows: Vec::new(),
+                deleted_versions: HashMap::new(),
+            };
+            self.tables.insert(table_id, table);
+        }
+        Ok(self.tables.get_mut(&table_id).unwrap())
+    }
+
+    fn savepoint_pos(&self, id: u32) -> Result<usize, Errno> {
+        self.savepoints
+            .iter()
+            .position(|savepoint| savepoint.id == id)
+            .ok_or(Errno::NO_SUCH_SAVEPOINT)
+    }
+
+    /// Deletes `src_rows` and inserts `dst_rows`, as in [`MockHost::move_rows`],
+    /// but keeps the changes made before an error.
+    fn move_rows_partially(
+        &mut self,
+        src_table_id: u32,
+        src_rows: &[u8],
+        dst_table_id: u32,
+        dst_rows: &[u8],
+    ) -> Result<u32, Errno> {
+        let src_table = self.table(src_table_id)?;
+        let src_rows = src_table.decode_rows(src_rows);
+        let count = src_rows.len() as u32;
+        for row in src_rows {
+            let pos = src_table.rows.iter().position(|other| *other == row);
+            src_table.rows.remove(pos.ok_or(Errno::LOOKUP_NOT_FOUND)?);
+        }
+        let dst_rows = self.table(dst_table_id)?.decode_rows(dst_rows);
+        for row in dst_rows {
+            let mut bytes = bsatn::to_vec(&row).unwrap();
+            self.insert(dst_table_id, &mut bytes).map_err(|(errno, _)| errno)?;
+        }
+        Ok(count)
+    }
+}
+
+impl MockHost for MockDatastore {
+    fn get_table_id(&mut self, name: &str) -> Result<u32, Errno> {
+        let tables = TABLES.lock().unwrap();
+        let table_id = tables.iter().position(|table| table.name == name);
+        table_id.map(|id| id as u32).ok_or(Errno::NO_SUCH_TABLE)
+    }
+
+    fn create_index(&mut self, _: &str, table_id: u32, _: u8, _: &[u8]) -> Result<(), Errno> {
+        // Every lookup scans the rows, so indexes would only make the mock slower.
+        self.table(table_id).map(drop)
+    }
+
+    fn iter_by_col_eq(&mut self, table_id: u32, col_id: u32, value: &[u8]) -> Result<Vec<u8>, Errno> {
+        let table = self.table(table_id)?;
+        let value = table.decode(table.column_type(col_id)?, value);
+        Ok(table.encode_rows_where(col_id, |col| *col == value))
+    }
+
+    fn iter_by_col_match(&mut self, table_id: u32, col_id: u32, query: &str) -> Result<Vec<u8>, Errno> {
+        let table = self.table(table_id)?;
+        table.column_type(col_id)?;
+        Ok(table.encode_rows_where(col_id, |col| {
+            col.as_string().map_or(false, |text| fulltext::matches(text, query))
+        }))
+    }
+
+    fn iter_by_col_box(&mut self, table_id: u32, col_id: u32, min: &[u8], max: &[u8]) -> Result<Vec<u8>, Errno> {
+        let table = self.table(table_id)?;
+        let ty = table.column_type(col_id)?;
+        let (min, max) = (table.decode(ty, min), table.decode(ty, max));
+        Ok(table.encode_rows_where(col_id, |col| spatial::in_box(col, &min, &max)))
+    }
+
+    fn insert(&mut self, table_id: u32, bytes: &mut [u8]) -> Result<(), (Errno, Option<u32>)> {
+        let table = self.table(table_id).map_err(|e| (e, None))?;
+        let mut row = table.decode_row(bytes);
+        let desc = table.desc.clone();
+
+        for (col_id, attr) in desc.column_attrs.iter().enumerate() {
+            if attr.is_autoinc() && is_zero(&row.elements[col_id]) {
+                let seq = self.sequences.entry((table_id, col_id)).or_insert(0);
+                *seq += 1;
+                row.elements[col_id] = sequence_value(&desc.row_type.elements[col_id].algebraic_type, *seq);
+            }
+        }
+
+        let table = self.table(table_id).map_err(|e| (e, None))?;
+        if let Some((col_id, key_col_id)) = desc.row_version {
+            // Bump the version of the row this one replaces, as the host does.
+            let key = &row.elements[key_col_id as usize];
+            let version = table.deleted_versions.remove(key).map_or(0, |v| v.wrapping_add(1));
+            row.elements[col_id as usize] = AlgebraicValue::U64(version);
+        }
+        if table.rows.contains(&row) {
+            // Tables are sets of rows, so inserting a row again changes nothing.
+            return Ok(());
+        }
+        let violated = desc.column_attrs.iter().enumerate().position(|(col_id, attr)| {
+            attr.is_unique()
+                && table
+                    .rows
+                    .iter()
+                    .any(|other| other.elements[col_id] == row.elements[col_id])
+        });
+        if let Some(col_id) = violated {
+            return Err((Errno::UNIQUE_ALREADY_EXISTS, Some(col_id as u32)));
+        }
+
+        // Write the generated values back, as the host does.
+        let encoded = bsatn::to_vec(&row).unwrap();
+        bytes.copy_from_slice(&encoded);
+        table.rows.push(row);
+        Ok(())
+    }
+
+    fn delete_by_col_eq(&mut self, table_id: u32, col_id: u32, value: &[u8]) -> Result<u32, Errno> {
+        let table = self.table(table_id)?;
+        let value = table.decode(table.column_type(col_id)?, value);
+        let before = table.rows.len();
+        let (deleted, kept) = std::mem::take(&mut table.rows)
+            .into_iter()
+            .partition::<Vec<_>, _>(|row| row.elements[col_id as usize] == value);
+        table.rows = kept;
+        if let Some((version_col_id, key_col_id)) = table.desc.row_version {
+            for row in &deleted {
+                let key = row.elements[key_col_id as usize].clone();
+                let version = *row.elements[version_col_id as usize].as_u64().unwrap();
+                table.deleted_versions.insert(key, version);
+            }
+        }
+        match before - table.rows.len() {
+            0 => Err(Errno::LOOKUP_NOT_FOUND),
+            deleted => Ok(deleted as u32),
+        }
+    }
+
+    fn move_rows(
+        &mut self,
+        src_table_id: u32,
+        src_rows: &[u8],
+        dst_table_id: u32,
+        dst_rows: &[u8],
+    ) -> Result<u32, Errno> {
+        let savepoint = self.savepoint();
+        let res = self.move_rows_partially(src_table_id, src_rows, dst_table_id, dst_rows);
+        match res {
+            Ok(_) => self.release_savepoint(savepoint)?,
+            Err(_) => self.rollback_to_savepoint(savepoint)?,
+        }
+        res
+    }
+
+    fn iter(&mut self, table_id: u32, filter: Option<&[u8]>) -> Result<Vec<Box<[u8]>>, Errno> {
+        let table = self.table(table_id)?;
+        let desc = &table.desc;
+        let filter = filter.map(|filter| {
+            Expr::from_bytes(&desc.typespace, &desc.row_type.elements, filter).expect("failed to decode filter")
+        });
+
+        let mut schema = Vec::new();
+        desc.row_type.encode(&mut schema);
+        let rows = table
+            .rows
+            .iter()
+            .filter(|row| filter.as_ref().map_or(true, |filter| eval_filter(filter, row)))
+            .map(|row| bsatn::to_vec(row).unwrap().into_boxed_slice());
+        Ok(std::iter::once(schema.into_boxed_slice()).chain(rows).collect())
+    }
+
+    fn iter_projected(&mut self, table_id: u32, cols: &[u8]) -> Result<Vec<Box<[u8]>>, Errno> {
+        let table = self.table(table_id)?;
+        let elements = cols
+            .iter()
+            .map(|&col| table.desc.row_type.elements.get(col as usize).cloned())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Errno::NO_SUCH_TABLE)?;
+
+        let mut schema = Vec::new();
+        ProductType::new(elements).encode(&mut schema);
+        let rows = table.rows.iter().map(|row| {
+            let mut bytes = Vec::new();
+            for &col in cols {
+                bsatn::to_writer(&mut bytes, &row.elements[col as usize]).unwrap();
+            }
+            bytes.into_boxed_slice()
+        });
+        Ok(std::iter::once(schema.into_boxed_slice()).chain(rows).collect())
+    }
+
+    fn savepoint(&mut self) -> u32 {
+        let id = self.next_savepoint;
+        self.next_savepoint += 1;
+        self.savepoints.push(Savepoint {
+            id,
+            tables: self.tables.clone(),
+            blobs: self.blobs.clone(),
+        });
+        id
+    }
+
+    fn rollback_to_savepoint(&mut self, id: u32) -> Result<(), Errno> {
+        let pos = self.savepoint_pos(id)?;
+        let savepoint = self.savepoints.drain(pos..).next().unwrap();
+        self.tables = savepoint.tables;
+        self.blobs = savepoint.blobs;
+        Ok(())
+    }
+
+    fn release_savepoint(&mut self, id: u32) -> Result<(), Errno> {
+        let pos = self.savepoint_pos(id)?;
+        self.savepoints.truncate(pos);
+        Ok(())
+    }
+
+    fn blob_put(&mut self, key: &str, data: &[u8]) -> Result<[u8; 32], Errno> {
+        if data.len() > MAX_BLOB_SIZE {
+            return Err(Errno::BLOB_TOO_LARGE);
+        }
+        self.blobs.insert(key.to_owned(), data.to_vec());
+        Ok(hash_bytes(data).data)
+    }
+
+    fn blob_get(&mut self, key: &str) -> Result<Vec<u8>, Errno> {
+        self.blobs.get(key).cloned().ok_or(Errno::LOOKUP_NOT_FOUND)
+    }
+
+    fn blob_delete(&mut self, key: &str) -> Result<(), Errno> {
+        self.blobs.remove(key).map(drop).ok_or(Errno::LOOKUP_NOT_FOUND)
+    }
+
+    fn large_bytes_put(&mut self, data: &[u8]) -> Vec<u8> {
+        let key = DataKey::from_data(data).to_bytes();
+        self.large_values.insert(key.clone(), data.to_vec());
+        key
+    }
+
+    fn large_bytes_get(&mut self, key: &[u8]) -> Result<Vec<u8>, Errno> {
+        self.large_values.get(key).cloned().ok_or(Errno::LOOKUP_NOT_FOUND)
+    }
+}
+
+/// Returns whether the host would replace `value` of an auto-incremented column with a generated one.
+fn is_zero(value: &AlgebraicValue) -> bool {
+    match value.as_builtin() {
+        Some(BuiltinValue::I8(x)) => *x == 0,
+        Some(BuiltinValue::U8(x)) => *x == 0,
+        Some(BuiltinValue::I16(x)) => *x == 0,
+        Some(BuiltinValue::U16(x)) => *x == 0,
+        Some(BuiltinValue::I32(x)) => *x == 0,
+        Some(BuiltinValue::U32(x)) => *x == 0,
+        Some(BuiltinValue::I64(x)) => *x == 0,
+        Some(BuiltinValue::U64(x)) => *x == 0,
+        Some(BuiltinValue::I128(x)) => *x == 0,
+        Some(BuiltinValue::U128(x)) => *x == 0,
+        _ => false,
+    }
+}
+
+/// Converts the generated `value` to the integer type `ty` of an auto-incremented column.
+fn sequence_value(ty: &AlgebraicType, value: i128) -> AlgebraicValue {
+    match ty {
+        AlgebraicType::Builtin(BuiltinType::I8) => AlgebraicValue::I8(value as i8),
+        AlgebraicType::Builtin(BuiltinType::U8) => AlgebraicValue::U8(value as u8),
+        AlgebraicType::Builtin(BuiltinType::I16) => AlgebraicValue::I16(value as i16),
+        AlgebraicType::Builtin(BuiltinType::U16) => AlgebraicValue::U16(value as u16),
+        AlgebraicType::Builtin(BuiltinType::I32) => AlgebraicValue::I32(value as i32),
+        AlgebraicType::Builtin(BuiltinType::U32) => AlgebraicValue::U32(value as u32),
+        AlgebraicType::Builtin(BuiltinType::I64) => AlgebraicValue::I64(value as i64),
+        AlgebraicType::Builtin(BuiltinType::U64) => AlgebraicValue::U64(value as u64),
+        AlgebraicType::Builtin(BuiltinType::I128) => AlgebraicValue::I128(value),
+        AlgebraicType::Builtin(BuiltinType::U128) => AlgebraicValue::U128(value as u128),
+        _ => panic!("auto-incremented column isn't an integer"),
+    }
+}
+
+/// Returns whether `row` passes the `query!` filter `expr`.
+fn eval_filter(expr: &Expr, row: &ProductValue) -> bool {
+    match expr {
+        Expr::Cmp(Cmp {
+            op,
+            args: CmpArgs { lhs_field, rhs },
+        }) => {
+            let lhs = &row.elements[*lhs_field as usize];
+            let rhs = match rhs {
+                Rhs::Value(value) => value,
+                Rhs::Field(field) => &row.elements[*field as usize],
+            };
+            match op {
+                OpCmp::Eq => lhs == rhs,
+                OpCmp::NotEq => lhs != rhs,
+                OpCmp::Lt => lhs < rhs,
+                OpCmp::LtEq => lhs <= rhs,
+                OpCmp::Gt => lhs > rhs,
+                OpCmp::GtEq => lhs >= rhs,
+            }
+        }
+        Expr::Logic(Logic { lhs, op, rhs }) => match op {
+            OpLogic::And => eval_filter(lhs, row) && eval_filter(rhs, row),
+            OpLogic::Or => eval_filter(lhs, row) || eval_filter(rhs, row),
+        },
+        Expr::Unary(Unary { op: OpUnary::Not, arg }) => !eval_filter(arg, row),
+    }
+}
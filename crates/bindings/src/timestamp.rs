@@ -1,7 +1,8 @@
 //! Defines a `Timestamp` abstraction.
 
+use std::fmt;
 use std::ops::{Add, Sub};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, impl_st};
 
@@ -25,6 +26,18 @@ impl Timestamp {
     /// The timestamp 0 micro seconds since the UNIX epoch.
     pub const UNIX_EPOCH: Self = Timestamp { micros_since_epoch: 0 };
 
+    /// Returns the timestamp `micros` micro seconds after the UNIX epoch.
+    pub const fn from_micros_since_epoch(micros: u64) -> Self {
+        Timestamp {
+            micros_since_epoch: micros,
+        }
+    }
+
+    /// Returns the number of micro seconds between the UNIX epoch and this timestamp.
+    pub const fn micros_since_epoch(&self) -> u64 {
+        self.micros_since_epoch
+    }
+
     /// Returns a timestamp of how many micros have passed right now since UNIX epoch.
     ///
     /// Panics if not in the context of a reducer.
@@ -52,6 +65,22 @@ impl Timestamp {
         }
     }
 
+    /// Returns the duration between an `earlier` timestamp and this one,
+    /// or zero when `earlier` isn't before `self`.
+    pub fn saturating_duration_since(&self, earlier: Timestamp) -> Duration {
+        self.duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns whether this timestamp is before `other`.
+    pub fn is_before(&self, other: Timestamp) -> bool {
+        *self < other
+    }
+
+    /// Returns whether this timestamp is after `other`.
+    pub fn is_after(&self, other: Timestamp) -> bool {
+        *self > other
+    }
+
     /// Returns a timestamp with `duration` added to `self`.
     ///
     /// Returns `None` when a `u64` is overflowed.
@@ -89,6 +118,61 @@ impl Sub<Duration> for Timestamp {
     }
 }
 
+/// The error when converting a time before the UNIX epoch, or too far after it, to a [`Timestamp`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimestampOutOfRange;
+
+impl fmt::Display for TimestampOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("time out of range for a timestamp")
+    }
+}
+
+impl std::error::Error for TimestampOutOfRange {}
+
+impl From<Timestamp> for SystemTime {
+    fn from(ts: Timestamp) -> Self {
+        SystemTime::UNIX_EPOCH + Duration::from_micros(ts.micros_since_epoch)
+    }
+}
+
+impl TryFrom<SystemTime> for Timestamp {
+    type Error = TimestampOutOfRange;
+
+    fn try_from(time: SystemTime) -> Result<Self, Self::Error> {
+        let since_epoch = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| TimestampOutOfRange)?;
+        Timestamp::UNIX_EPOCH
+            .checked_add(since_epoch)
+            .ok_or(TimestampOutOfRange)
+    }
+}
+
+/// Panics if the timestamp is after the latest time `chrono` can represent, in the year 262143.
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(ts: Timestamp) -> Self {
+        let secs = (ts.micros_since_epoch / 1_000_000) as i64;
+        let nanos = (ts.micros_since_epoch % 1_000_000) as u32 * 1_000;
+        let naive = chrono::NaiveDateTime::from_timestamp_opt(secs, nanos).expect("timestamp out of range for chrono");
+        Self::from_utc(naive, chrono::Utc)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::DateTime<chrono::Utc>> for Timestamp {
+    type Error = TimestampOutOfRange;
+
+    fn try_from(time: chrono::DateTime<chrono::Utc>) -> Result<Self, Self::Error> {
+        let secs = u64::try_from(time.timestamp()).map_err(|_| TimestampOutOfRange)?;
+        secs.checked_mul(1_000_000)
+            .and_then(|micros| micros.checked_add(time.timestamp_subsec_micros().into()))
+            .map(Timestamp::from_micros_since_epoch)
+            .ok_or(TimestampOutOfRange)
+    }
+}
+
 impl_st!([] Timestamp, _ts => spacetimedb_lib::AlgebraicType::U64);
 impl_deserialize!([] Timestamp, de => u64::deserialize(de).map(|m| Self { micros_since_epoch: m }));
 impl_serialize!([] Timestamp, (self, ser) => self.micros_since_epoch.serialize(ser));
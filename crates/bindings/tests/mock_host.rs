@@ -0,0 +1,373 @@
+//! Runs a module's reducers natively against the mock host of the `testing` feature.
+
+use std::time::Duration;
+
+use spacetimedb::spacetimedb_lib::bsatn;
+use spacetimedb::testing::{self, FaultPlan};
+use spacetimedb::{
+    spacetimedb, AlgebraicValue, Identity, PrimaryKeyTable, ReducerContext, TableHandle, TableType, Timestamp,
+};
+
+#[spacetimedb(table)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Person {
+    #[primarykey]
+    #[autoinc]
+    id: u32,
+    #[unique]
+    name: String,
+    age: u8,
+    joined: Timestamp,
+}
+
+#[spacetimedb(reducer)]
+pub fn add_person(_ctx: ReducerContext, name: String, age: u8) -> Result<(), String> {
+    Person::insert(Person {
+        id: 0,
+        name,
+        age,
+        joined: Timestamp::now(),
+    })
+    .map(drop)
+    .map_err(|e| e.to_string())
+}
+
+#[spacetimedb(reducer)]
+pub fn birthday(_ctx: ReducerContext, name: String) -> Result<(), String> {
+    let mut person = Person::filter_by_name(&name).ok_or("no such person")?;
+    person.age += 1;
+    let id = person.id;
+    Person::update_by_id(&id, person);
+    Ok(())
+}
+
+#[spacetimedb(table, soft_delete)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    #[unique]
+    id: u32,
+    text: String,
+}
+
+#[spacetimedb(reducer)]
+pub fn delete_note(_ctx: ReducerContext, id: u32) -> Result<(), String> {
+    Note::delete_by_id(&id)
+        .then_some(())
+        .ok_or_else(|| "no such note".into())
+}
+
+/// Adds a person a second after being called, with the name it was called with, capitalized.
+#[spacetimedb(reducer)]
+pub async fn add_person_later(ctx: ReducerContext, name: String) -> Result<(), String> {
+    let name: String = name.to_uppercase();
+    spacetimedb::sleep(Duration::from_secs(1)).await;
+    add_person(ctx, name, 0)
+}
+
+fn ctx(micros: u64) -> ReducerContext {
+    testing::reducer_context(Identity::__dummy(), Timestamp::from_micros_since_epoch(micros))
+}
+
+#[test]
+fn reducers_change_the_tables() {
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    testing::call_reducer(ctx(2), |ctx| add_person(ctx, "Bob".into(), 40)).unwrap();
+    testing::call_reducer(ctx(3), |ctx| birthday(ctx, "Alice".into())).unwrap();
+
+    let mut people: Vec<_> = Person::iter().collect();
+    people.sort_by_key(|person| person.id);
+    assert_eq!(
+        people,
+        [
+            Person {
+                id: 1,
+                name: "Alice".into(),
+                age: 31,
+                joined: Timestamp::from_micros_since_epoch(1),
+            },
+            Person {
+                id: 2,
+                name: "Bob".into(),
+                age: 40,
+                joined: Timestamp::from_micros_since_epoch(2),
+            },
+        ]
+    );
+
+    assert!(Person::delete_by_id(&2));
+    assert!(!Person::delete_by_id(&2));
+    assert_eq!(Person::iter().count(), 1);
+}
+
+#[test]
+fn unique_columns_are_enforced() {
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    assert!(testing::call_reducer(ctx(2), |ctx| add_person(ctx, "Alice".into(), 31)).is_err());
+    assert!(testing::call_reducer(ctx(3), |ctx| birthday(ctx, "Bob".into())).is_err());
+    assert_eq!(Person::filter_by_name(&"Alice".into()).unwrap().age, 30);
+}
+
+#[test]
+fn unique_violations_name_their_column() {
+    let person = |name: &str| Person {
+        id: 0,
+        name: name.into(),
+        age: 30,
+        joined: Timestamp::UNIX_EPOCH,
+    };
+    Person::insert(person("Alice")).unwrap();
+    let err = Person::insert(person("Alice")).unwrap_err();
+    assert_eq!((err.table, err.column.as_deref()), ("Person", Some("name")));
+    assert_eq!(
+        err.to_string(),
+        "not able to insert into table Person; duplicate value in unique column name"
+    );
+
+    // An injected violation doesn't say which column it's on.
+    testing::inject_faults(FaultPlan {
+        insert_exists: 1.0,
+        ..FaultPlan::default()
+    });
+    let err = Person::insert(person("Bob")).unwrap_err();
+    assert_eq!(err.column, None);
+    assert_eq!(
+        err.to_string(),
+        "not able to insert into table Person; duplicate unique column"
+    );
+    testing::clear_faults();
+}
+
+#[test]
+fn rows_are_looked_up_by_primary_key() {
+    /// Takes the row `pk` out of any table with a primary key.
+    fn take<T: PrimaryKeyTable>(pk: &T::PrimaryKey) -> Option<T> {
+        let row = T::get(pk)?;
+        assert!(T::delete(pk));
+        Some(row)
+    }
+
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    assert_eq!(<Person as PrimaryKeyTable>::PRIMARY_KEY_COLUMN, 0);
+    assert_eq!(Person::get(&1).unwrap().name, "Alice");
+    assert_eq!(Person::get(&2), None);
+
+    assert_eq!(take::<Person>(&1).unwrap().name, "Alice");
+    assert_eq!(take::<Person>(&1), None);
+    assert!(!Person::delete(&1));
+    assert_eq!(Person::iter().count(), 0);
+}
+
+#[test]
+fn tables_found_by_name_are_read_and_written_dynamically() {
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    assert!(TableHandle::by_name("Nobody").is_none());
+    let people = TableHandle::by_name("Person").unwrap();
+    assert_eq!(people.name(), "Person");
+    assert_eq!(people.table_id(), Person::table_id());
+    let columns: Vec<_> = people.columns().map(|(name, _)| name.unwrap()).collect();
+    assert_eq!(columns, ["id", "name", "age", "joined"]);
+    let name = people.column_id("name").unwrap();
+    assert_eq!(people.column_id("height"), None);
+
+    // Insert a copy of Alice named Bob, whose id is assigned on insert.
+    let mut bob = people.iter_dyn().next().unwrap();
+    bob.elements[0] = AlgebraicValue::U32(0);
+    bob.elements[1] = AlgebraicValue::String("Bob".into());
+    let bob = people.insert_dyn(bob).unwrap();
+    assert_eq!(bob.elements[0], AlgebraicValue::U32(2));
+    assert_eq!(Person::filter_by_id(&2).unwrap().name, "Bob");
+
+    let found = people.filter_dyn(name, &AlgebraicValue::String("Bob".into())).unwrap();
+    assert_eq!(found, [bob]);
+    assert_eq!(
+        people.delete_dyn(name, &AlgebraicValue::String("Bob".into())).unwrap(),
+        1
+    );
+    assert_eq!(people.iter_dyn().count(), 1);
+}
+
+#[test]
+fn projections_yield_only_their_columns() {
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    testing::call_reducer(ctx(2), |ctx| add_person(ctx, "Bob".into(), 40)).unwrap();
+
+    let mut ages: Vec<_> = Person::iter_cols::<(person_cols::Age, person_cols::Name)>().collect();
+    ages.sort();
+    assert_eq!(ages, [(30, "Alice".to_owned()), (40, "Bob".to_owned())]);
+
+    // The rows deleted from a soft-deleting table are left out.
+    Note::insert(Note {
+        id: 1,
+        text: "milk".into(),
+    })
+    .unwrap();
+    Note::insert(Note {
+        id: 2,
+        text: "eggs".into(),
+    })
+    .unwrap();
+    testing::call_reducer(ctx(3), |ctx| delete_note(ctx, 1)).unwrap();
+    let texts: Vec<_> = Note::iter_cols::<(note_cols::Text,)>().collect();
+    assert_eq!(texts, [("eggs".to_owned(),)]);
+}
+
+#[test]
+fn rows_are_moved_all_or_nothing() {
+    for (name, age) in [("Alice", 30), ("Bob", 40), ("Carol", 50)] {
+        testing::call_reducer(ctx(1), |ctx| add_person(ctx, name.into(), age)).unwrap();
+    }
+    let to_note = |person: Person| Note {
+        id: person.id,
+        text: person.name,
+    };
+
+    // Carol's note can't be inserted, so neither Bob nor Carol is moved.
+    Note::insert(Note {
+        id: 3,
+        text: "taken".into(),
+    })
+    .unwrap();
+    assert!(spacetimedb::move_rows(|person: &Person| person.age > 35, to_note).is_err());
+    assert_eq!(Person::iter().count(), 3);
+    assert_eq!(Note::iter().count(), 1);
+
+    testing::call_reducer(ctx(2), |ctx| delete_note(ctx, 3)).unwrap();
+    assert_eq!(Note::purge_deleted(), 1);
+    let moved = spacetimedb::move_rows(|person: &Person| person.age > 35, to_note).unwrap();
+    assert_eq!(moved, 2);
+    let names: Vec<_> = Person::iter().map(|person| person.name).collect();
+    assert_eq!(names, ["Alice"]);
+    let mut notes: Vec<_> = Note::iter().map(|note| note.text).collect();
+    notes.sort();
+    assert_eq!(notes, ["Bob", "Carol"]);
+}
+
+#[test]
+fn each_call_has_its_own_stash() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Caller(&'static str);
+
+    testing::call_reducer(ctx(1), |ctx| {
+        let stash = ctx.extensions();
+        assert_eq!(stash.get::<Caller>(), None);
+        assert_eq!(stash.insert(Caller("Alice")), None);
+        assert_eq!(stash.insert(Caller("Bob")), Some(Caller("Alice")));
+        assert_eq!(stash.get_or_insert_with(|| Caller("Carol")), Caller("Bob"));
+        // Values are keyed by type, so a `u32` doesn't replace a `Caller`.
+        assert_eq!(stash.get_or_insert_with(|| 7u32), 7);
+        assert!(stash.contains::<Caller>() && stash.contains::<u32>());
+        assert_eq!(stash.remove::<Caller>(), Some(Caller("Bob")));
+        assert!(!stash.contains::<Caller>());
+    });
+
+    // Nothing carries over to the next call.
+    testing::call_reducer(ctx(2), |ctx| {
+        assert!(!ctx.extensions().contains::<u32>());
+    });
+}
+
+#[test]
+fn soft_deleted_rows_are_kept_until_purged() {
+    let note = |id: u32, text: &str| Note { id, text: text.into() };
+    Note::insert(note(1, "milk")).unwrap();
+    Note::insert(note(2, "eggs")).unwrap();
+
+    testing::call_reducer(ctx(5), |ctx| delete_note(ctx, 1)).unwrap();
+    // A deleted row is hidden, but keeps its unique value.
+    assert!(testing::call_reducer(ctx(6), |ctx| delete_note(ctx, 1)).is_err());
+    assert_eq!(Note::iter().collect::<Vec<_>>(), [note(2, "eggs")]);
+    assert_eq!(Note::filter_by_id(&1), None);
+    assert_eq!(
+        Note::iter_deleted().collect::<Vec<_>>(),
+        [(note(1, "milk"), Timestamp::from_micros_since_epoch(5))]
+    );
+    assert!(Note::insert(note(1, "bread")).is_err());
+
+    assert!(Note::restore_by_id(&1));
+    assert!(!Note::restore_by_id(&1));
+    assert_eq!(Note::filter_by_id(&1), Some(note(1, "milk")));
+    assert_eq!(Note::iter_deleted().count(), 0);
+
+    testing::call_reducer(ctx(7), |ctx| delete_note(ctx, 1)).unwrap();
+    testing::call_reducer(ctx(8), |ctx| delete_note(ctx, 2)).unwrap();
+    assert_eq!(Note::purge_deleted(), 2);
+    assert_eq!(Note::iter_deleted().count(), 0);
+    Note::insert(note(1, "bread")).unwrap();
+    assert_eq!(Note::iter().collect::<Vec<_>>(), [note(1, "bread")]);
+}
+
+#[test]
+fn each_test_starts_out_empty() {
+    assert_eq!(Person::iter().count(), 0);
+    testing::call_reducer(ctx(1), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+    testing::reset();
+    assert_eq!(Person::iter().count(), 0);
+}
+
+#[test]
+fn injected_faults_are_the_same_on_each_run() {
+    let plan = FaultPlan {
+        seed: 7,
+        insert_exists: 0.5,
+        ..FaultPlan::default()
+    };
+    let run = || -> Vec<bool> {
+        testing::reset();
+        testing::inject_faults(plan.clone());
+        (0..20u8)
+            .map(|i| testing::call_reducer(ctx(1), |ctx| add_person(ctx, i.to_string(), i)).is_ok())
+            .collect()
+    };
+    let added = run();
+    assert_eq!(run(), added);
+    assert!(added.contains(&true) && added.contains(&false), "{added:?}");
+    assert_eq!(Person::iter().count(), added.iter().filter(|&&ok| ok).count());
+
+    testing::clear_faults();
+    testing::call_reducer(ctx(2), |ctx| add_person(ctx, "Alice".into(), 30)).unwrap();
+}
+
+#[test]
+fn injected_faults_cut_iterators_short_and_delay_schedules() {
+    for name in ["Alice", "Bob", "Carol"] {
+        testing::call_reducer(ctx(1), |ctx| add_person(ctx, name.into(), 30)).unwrap();
+    }
+    testing::inject_faults(FaultPlan {
+        seed: 1,
+        partial_iter: 1.0,
+        max_schedule_delay_micros: 500,
+        ..FaultPlan::default()
+    });
+    assert!(Person::iter().count() < 3);
+
+    spacetimedb::schedule_at!(
+        Timestamp::from_micros_since_epoch(1000),
+        add_person(_, "Dave".into(), 1)
+    );
+    let scheduled = testing::scheduled_reducers();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].name, "add_person");
+    assert!((1000..=1500).contains(&scheduled[0].time), "{}", scheduled[0].time);
+
+    testing::clear_faults();
+    assert_eq!(Person::iter().count(), 3);
+}
+
+#[test]
+fn async_reducers_resume_after_an_await() {
+    testing::call_reducer(ctx(1), |ctx| add_person_later(ctx, "Alice".into())).unwrap();
+    assert_eq!(Person::iter().count(), 0);
+    // The variables in scope are saved until the continuation resumes the reducer.
+    assert_eq!(__add_person_later_suspended::iter().count(), 1);
+
+    let scheduled = testing::scheduled_reducers();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].name, "__add_person_later_resume");
+    assert_eq!(scheduled[0].time, 1_000_001);
+    let id: u64 = bsatn::from_slice(&scheduled[0].args).unwrap();
+
+    testing::call_reducer(ctx(scheduled[0].time), |ctx| __add_person_later_resume(ctx, id)).unwrap();
+    let person = Person::filter_by_name(&"ALICE".into()).unwrap();
+    assert_eq!(person.joined, Timestamp::from_micros_since_epoch(1_000_001));
+    assert_eq!(__add_person_later_suspended::iter().count(), 0);
+}
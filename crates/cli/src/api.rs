@@ -1,7 +1,6 @@
 use reqwest::header::IntoHeaderName;
 use reqwest::{header, Client, RequestBuilder};
 use serde::Deserialize;
-use serde_json::value::RawValue;
 
 use spacetimedb_lib::sats::ProductType;
 
@@ -55,22 +54,14 @@ impl ClientApi {
     pub fn sql(&self) -> RequestBuilder {
         self.client
             .post(format!("{}/database/sql/{}", self.con.host, self.con.address))
+            .query(&[("format", "typed")])
     }
 }
 
+/// A result of the `/sql` route in its `typed` format,
+/// with rows in the canonical JSON encoding of [`sats::json`](spacetimedb_lib::sats::json).
 #[derive(Debug, Clone, Deserialize)]
-pub struct StmtResultJson<'a> {
+pub struct StmtResultJson {
     pub schema: ProductType,
-    #[serde(borrow)]
-    pub rows: Vec<&'a RawValue>,
-}
-
-pub fn from_json_seed<'de, T: serde::de::DeserializeSeed<'de>>(
-    s: &'de str,
-    seed: T,
-) -> Result<T::Value, serde_json::Error> {
-    let mut de = serde_json::Deserializer::from_str(s);
-    let out = seed.deserialize(&mut de)?;
-    de.end()?;
-    Ok(out)
+    pub rows: Vec<Vec<serde_json::Value>>,
 }
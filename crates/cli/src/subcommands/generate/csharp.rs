@@ -6,7 +6,7 @@ use convert_case::{Case, Casing};
 use spacetimedb_lib::sats::{
     AlgebraicType, AlgebraicType::Builtin, AlgebraicTypeRef, ArrayType, BuiltinType, MapType, ProductType, SumType,
 };
-use spacetimedb_lib::{ColumnIndexAttribute, ProductTypeElement, ReducerDef, TableDef};
+use spacetimedb_lib::{ColumnIndexAttribute, EventDef, ProductTypeElement, ReducerDef, TableDef};
 
 use super::code_indenter::CodeIndenter;
 use super::{GenCtx, GenItem, INDENT};
@@ -1447,6 +1447,102 @@ pub fn autogen_csharp_reducer(ctx: &GenCtx, reducer: &ReducerDef, namespace: &st
     output.into_inner()
 }
 
+pub fn autogen_csharp_event(ctx: &GenCtx, event: &EventDef, namespace: &str) -> String {
+    let event_name = &*event.name;
+    let event_name_pascal_case = event_name.to_case(Case::Pascal);
+    let ty = AlgebraicType::Ref(event.ty);
+    let type_name = ty_fmt(ctx, &ty, namespace);
+
+    let mut output = CodeIndenter::new(String::new());
+
+    writeln!(
+        output,
+        "// THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE"
+    )
+    .unwrap();
+    writeln!(output, "// WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(output).unwrap();
+
+    writeln!(output, "using System;").unwrap();
+    writeln!(output, "using ClientApi;").unwrap();
+    if namespace != "SpacetimeDB" {
+        writeln!(output, "using SpacetimeDB;").unwrap();
+    }
+
+    writeln!(output).unwrap();
+
+    writeln!(output, "namespace {}", namespace).unwrap();
+    writeln!(output, "{{").unwrap();
+    {
+        indent_scope!(output);
+
+        writeln!(output, "public static partial class Events").unwrap();
+        writeln!(output, "{{").unwrap();
+        {
+            indent_scope!(output);
+
+            writeln!(
+                output,
+                "public delegate void {event_name_pascal_case}Handler({type_name} {});",
+                event_name.to_case(Case::Camel)
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "public static event {event_name_pascal_case}Handler On{event_name_pascal_case}Event;"
+            )
+            .unwrap();
+
+            writeln!(output).unwrap();
+
+            writeln!(output, "[EmittedEventCallback(EventName = \"{event_name}\")]").unwrap();
+            writeln!(
+                output,
+                "public static void On{event_name_pascal_case}(ClientApi.EmittedEvent emittedEvent)"
+            )
+            .unwrap();
+            writeln!(output, "{{").unwrap();
+            {
+                indent_scope!(output);
+
+                writeln!(output, "if(On{event_name_pascal_case}Event != null)").unwrap();
+                writeln!(output, "{{").unwrap();
+                {
+                    indent_scope!(output);
+
+                    writeln!(output, "var bsatnBytes = emittedEvent.Data;").unwrap();
+                    writeln!(output, "using var ms = new System.IO.MemoryStream();").unwrap();
+                    writeln!(output, "ms.SetLength(bsatnBytes.Length);").unwrap();
+                    writeln!(output, "bsatnBytes.CopyTo(ms.GetBuffer(), 0);").unwrap();
+                    writeln!(output, "ms.Position = 0;").unwrap();
+                    writeln!(output, "using var reader = new System.IO.BinaryReader(ms);").unwrap();
+                    writeln!(
+                        output,
+                        "var value = SpacetimeDB.SATS.AlgebraicValue.Deserialize({}, reader);",
+                        convert_algebraic_type(ctx, &ty, namespace)
+                    )
+                    .unwrap();
+                    writeln!(
+                        output,
+                        "On{event_name_pascal_case}Event({});",
+                        convert_type(ctx, 0, &ty, "value", namespace)
+                    )
+                    .unwrap();
+                }
+                // Closing brace for if event is registered
+                writeln!(output, "}}").unwrap();
+            }
+            // Closing brace for Event parsing function
+            writeln!(output, "}}").unwrap();
+        }
+        // Closing brace for class
+        writeln!(output, "}}").unwrap();
+    }
+    writeln!(output, "}}").unwrap();
+
+    output.into_inner()
+}
+
 pub fn autogen_csharp_globals(items: &[GenItem], namespace: &str) -> Vec<Vec<(String, String)>> {
     let reducers: Vec<&ReducerDef> = items
         .iter()
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -6,7 +7,7 @@ use clap::ArgAction::SetTrue;
 use convert_case::{Case, Casing};
 use duct::cmd;
 use spacetimedb_lib::sats::{AlgebraicType, Typespace};
-use spacetimedb_lib::{bsatn, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias};
+use spacetimedb_lib::{bsatn, EventDef, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias};
 use wasmtime::{AsContext, Caller, ExternType};
 
 mod code_indenter;
@@ -14,6 +15,7 @@ pub mod csharp;
 pub mod python;
 pub mod rust;
 pub mod typescript;
+pub mod unreal;
 mod util;
 
 const INDENT: &str = "\t";
@@ -131,10 +133,11 @@ pub enum Language {
     TypeScript,
     Python,
     Rust,
+    Unreal,
 }
 impl clap::ValueEnum for Language {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Csharp, Self::TypeScript, Self::Python, Self::Rust]
+        &[Self::Csharp, Self::TypeScript, Self::Python, Self::Rust, Self::Unreal]
     }
     fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
         match self {
@@ -142,6 +145,7 @@ impl clap::ValueEnum for Language {
             Self::TypeScript => Some(clap::builder::PossibleValue::new("typescript").aliases(["ts", "TS"])),
             Self::Python => Some(clap::builder::PossibleValue::new("python").aliases(["py", "PY"])),
             Self::Rust => Some(clap::builder::PossibleValue::new("rust").aliases(["rs", "RS"])),
+            Self::Unreal => Some(clap::builder::PossibleValue::new("unreal").aliases(["ue", "cpp", "c++"])),
         }
     }
 }
@@ -149,6 +153,8 @@ impl clap::ValueEnum for Language {
 pub struct GenCtx {
     typespace: Typespace,
     names: Vec<Option<String>>,
+    /// The types of the values returned by the reducers that return one, by reducer name.
+    reducer_returns: HashMap<String, AlgebraicType>,
 }
 
 pub fn generate<'a>(wasm_file: &'a Path, lang: Language, namespace: &'a str) -> anyhow::Result<Vec<(String, String)>> {
@@ -170,6 +176,7 @@ fn generate_globals(ctx: &GenCtx, lang: Language, namespace: &str, items: &[GenI
         Language::TypeScript => typescript::autogen_typescript_globals(ctx, items),
         Language::Python => python::autogen_python_globals(ctx, items),
         Language::Rust => rust::autogen_rust_globals(ctx, items),
+        Language::Unreal => unreal::autogen_unreal_globals(ctx, items),
     }
 }
 
@@ -183,17 +190,42 @@ pub fn extract_from_moduledef(module: ModuleDef) -> (GenCtx, impl Iterator<Item
     let mut names = vec![None; typespace.types.len()];
     let name_info = itertools::chain!(
         tables.iter().map(|t| (t.data, &t.name)),
-        misc_exports
-            .iter()
-            .map(|MiscModuleExport::TypeAlias(a)| (a.ty, &a.name)),
+        misc_exports.iter().filter_map(|exp| match exp {
+            MiscModuleExport::TypeAlias(a) => Some((a.ty, &a.name)),
+            // An event's type is named by its own alias.
+            MiscModuleExport::ReadOnlyReducer(_)
+            | MiscModuleExport::TableAccessHint(_)
+            | MiscModuleExport::Event(_)
+            | MiscModuleExport::ReducerAllow(_)
+            | MiscModuleExport::TableTtl(_)
+            | MiscModuleExport::ReducerCooldown(_)
+            | MiscModuleExport::ReducerReturn(_)
+            | MiscModuleExport::DeferredUnique(_)
+            | MiscModuleExport::ReducerPriority(_)
+            | MiscModuleExport::HttpRoute(_)
+            | MiscModuleExport::ColumnCompression(_)
+            | MiscModuleExport::RowVersion(_) => None,
+        }),
     );
     for (typeref, name) in name_info {
         names[typeref.idx()] = Some(name.clone())
     }
 
-    let ctx = GenCtx { typespace, names };
+    let reducer_returns = misc_exports
+        .iter()
+        .filter_map(|exp| match exp {
+            MiscModuleExport::ReducerReturn(r) => Some((r.reducer_name.clone(), r.ty.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let ctx = GenCtx {
+        typespace,
+        names,
+        reducer_returns,
+    };
     let iter = itertools::chain!(
-        misc_exports.into_iter().map(GenItem::from_misc_export),
+        misc_exports.into_iter().filter_map(GenItem::from_misc_export),
         tables.into_iter().map(GenItem::Table),
         reducers.into_iter().map(GenItem::Reducer),
     );
@@ -204,12 +236,36 @@ pub enum GenItem {
     Table(TableDef),
     TypeAlias(TypeAlias),
     Reducer(ReducerDef),
+    Event(EventDef),
 }
 
 impl GenItem {
-    fn from_misc_export(exp: MiscModuleExport) -> Self {
+    fn from_misc_export(exp: MiscModuleExport) -> Option<Self> {
         match exp {
-            MiscModuleExport::TypeAlias(a) => Self::TypeAlias(a),
+            MiscModuleExport::TypeAlias(a) => Some(Self::TypeAlias(a)),
+            // Clients call read-only reducers just like any other reducer.
+            MiscModuleExport::ReadOnlyReducer(_) => None,
+            // Access hints only matter to the host.
+            MiscModuleExport::TableAccessHint(_) => None,
+            MiscModuleExport::Event(e) => Some(Self::Event(e)),
+            // The host checks the caller's roles, so clients call these reducers as any other.
+            MiscModuleExport::ReducerAllow(_) => None,
+            // The host expires the rows, which clients see as deletes.
+            MiscModuleExport::TableTtl(_) => None,
+            // The host rejects the calls made too soon, which clients see as failed reducer calls.
+            MiscModuleExport::ReducerCooldown(_) => None,
+            // Collected into the `GenCtx`, as they're generated along with their reducers.
+            MiscModuleExport::ReducerReturn(_) => None,
+            // The host checks the constraint when the transaction commits, which clients don't see.
+            MiscModuleExport::DeferredUnique(_) => None,
+            // The host orders the calls to run, which clients only see in how soon they're answered.
+            MiscModuleExport::ReducerPriority(_) => None,
+            // The host serves the endpoints over HTTP, apart from the clients' connections.
+            MiscModuleExport::HttpRoute(_) => None,
+            // The host compresses the values in memory, and clients get them as they were inserted.
+            MiscModuleExport::ColumnCompression(_) => None,
+            // The version is an ordinary column of the table, which the host sets on updates.
+            MiscModuleExport::RowVersion(_) => None,
         }
     }
 
@@ -219,6 +275,7 @@ impl GenItem {
             Language::TypeScript => self.generate_typescript(ctx),
             Language::Python => self.generate_python(ctx),
             Language::Rust => self.generate_rust(ctx),
+            Language::Unreal => self.generate_unreal(ctx),
         }
     }
 
@@ -245,6 +302,38 @@ impl GenItem {
                 let name = reducer.name.to_case(Case::Snake);
                 Some((name + "_reducer.rs", code))
             }
+            // The event's type is generated from its alias; its dispatch goes in the globals.
+            GenItem::Event(_) => None,
+        }
+    }
+
+    fn generate_unreal(&self, ctx: &GenCtx) -> Option<(String, String)> {
+        match self {
+            GenItem::Table(table) => {
+                let code = unreal::autogen_unreal_table(ctx, table);
+                let name = table.name.replace("r#", "").to_case(Case::Pascal);
+                Some((name + ".h", code))
+            }
+            GenItem::TypeAlias(TypeAlias { name, ty }) => {
+                let filename = name.replace("r#", "").replace('.', "").to_case(Case::Pascal);
+                let code = match &ctx.typespace[*ty] {
+                    AlgebraicType::Sum(sum) => unreal::autogen_unreal_sum(ctx, name, sum),
+                    AlgebraicType::Product(prod) => unreal::autogen_unreal_tuple(ctx, name, prod),
+                    _ => return None,
+                };
+                Some((filename + ".h", code))
+            }
+            GenItem::Reducer(reducer) if reducer.name == "__init__" => None,
+            GenItem::Reducer(reducer) => {
+                let code = unreal::autogen_unreal_reducer(ctx, reducer);
+                let name = reducer.name.to_case(Case::Pascal);
+                Some((name + "Reducer.h", code))
+            }
+            GenItem::Event(event) => {
+                let code = unreal::autogen_unreal_event(ctx, event);
+                let name = event.name.replace("r#", "").to_case(Case::Pascal);
+                Some((name + "Event.h", code))
+            }
         }
     }
 
@@ -276,6 +365,11 @@ impl GenItem {
                 let name = reducer.name.to_case(Case::Snake);
                 Some((name + "_reducer.py", code))
             }
+            GenItem::Event(event) => {
+                let code = python::autogen_python_event(ctx, event);
+                let name = event.name.to_case(Case::Snake);
+                Some((name + "_event.py", code))
+            }
         }
     }
 
@@ -307,6 +401,11 @@ impl GenItem {
                 let name = reducer.name.to_case(Case::Snake);
                 Some((name + "_reducer.ts", code))
             }
+            GenItem::Event(event) => {
+                let code = typescript::autogen_typescript_event(ctx, event);
+                let name = event.name.to_case(Case::Snake);
+                Some((name + "_event.ts", code))
+            }
         }
     }
 
@@ -336,6 +435,11 @@ impl GenItem {
                 let pascalcase = reducer.name.to_case(Case::Pascal);
                 Some((pascalcase + "Reducer.cs", code))
             }
+            GenItem::Event(event) => {
+                let code = csharp::autogen_csharp_event(ctx, event, namespace);
+                let pascalcase = event.name.to_case(Case::Pascal);
+                Some((pascalcase + "Event.cs", code))
+            }
         }
     }
 }
@@ -382,6 +486,29 @@ fn extract_descriptions(wasm_file: &Path) -> anyhow::Result<ModuleDef> {
             }
         },
     )?;
+    linker.func_wrap(
+        "spacetime",
+        "_console_log_structured",
+        |caller: Caller<'_, WasmCtx>,
+         _level: u32,
+         _target: u32,
+         _target_len: u32,
+         _filename: u32,
+         _filename_len: u32,
+         _line_number: u32,
+         message: u32,
+         message_len: u32,
+         _fields: u32,
+         _fields_len: u32| {
+            let mem = caller.data().mem.unwrap();
+            let slice = mem.deref_slice(&caller, message, message_len);
+            if let Some(slice) = slice {
+                println!("from wasm: {}", String::from_utf8_lossy(slice));
+            } else {
+                println!("tried to print from wasm but out of bounds")
+            }
+        },
+    )?;
     linker.func_wrap("spacetime", "_buffer_alloc", WasmCtx::buffer_alloc)?;
     let instance = linker.instantiate(&mut store, &module)?;
     let memory = Memory {
@@ -451,6 +578,7 @@ fn format_files(generated_files: Vec<PathBuf>, lang: Language) -> anyhow::Result
         Language::Csharp => {}
         Language::TypeScript => {}
         Language::Python => {}
+        Language::Unreal => {}
     }
 
     Ok(())
@@ -3,7 +3,7 @@ use super::util::fmt_fn;
 use convert_case::{Case, Casing};
 use spacetimedb_lib::{
     sats::{AlgebraicType::Builtin, AlgebraicTypeRef, ArrayType, BuiltinType, MapType},
-    AlgebraicType, ColumnIndexAttribute, ProductType, ProductTypeElement, ReducerDef, SumType, TableDef,
+    AlgebraicType, ColumnIndexAttribute, EventDef, ProductType, ProductTypeElement, ReducerDef, SumType, TableDef,
 };
 use std::fmt::{self, Write};
 
@@ -657,6 +657,68 @@ pub fn autogen_python_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String {
     output.into_inner()
 }
 
+pub fn autogen_python_event(ctx: &GenCtx, event: &EventDef) -> String {
+    let ty = AlgebraicType::Ref(event.ty);
+    let mut output = CodeIndenter::new(String::new());
+
+    writeln!(
+        output,
+        "# THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE",
+    )
+    .unwrap();
+    writeln!(output, "# WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(output).unwrap();
+
+    writeln!(output, "from typing import Callable").unwrap();
+    writeln!(output).unwrap();
+
+    writeln!(
+        output,
+        "from spacetimedb_sdk.spacetimedb_client import SpacetimeDBClient"
+    )
+    .unwrap();
+
+    writeln!(output).unwrap();
+
+    let mut imports = Vec::new();
+    _generate_imports(ctx, &ty, &mut imports);
+    for import in imports {
+        writeln!(output, "{import}").unwrap();
+    }
+    writeln!(output).unwrap();
+
+    writeln!(output, "event_name = \"{}\"", event.name).unwrap();
+    writeln!(output).unwrap();
+
+    writeln!(
+        output,
+        "def register_on_{}(callback: Callable[[{}], None]):",
+        event.name.to_case(Case::Snake),
+        ty_fmt(ctx, &ty, "")
+    )
+    .unwrap();
+    {
+        indent_scope!(output);
+
+        writeln!(
+            output,
+            "SpacetimeDBClient.instance._register_event(\"{}\", callback)",
+            event.name
+        )
+        .unwrap();
+    }
+    writeln!(output).unwrap();
+
+    writeln!(output, "def _decode_event(data):").unwrap();
+    {
+        indent_scope!(output);
+
+        writeln!(output, "return {}", convert_type(ctx, 0, &ty, "data", "")).unwrap();
+    }
+
+    output.into_inner()
+}
+
 pub fn autogen_python_globals(_ctx: &GenCtx, _items: &[GenItem]) -> Vec<Vec<(String, String)>> {
     vec![] //TODO
 }
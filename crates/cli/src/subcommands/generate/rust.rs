@@ -5,7 +5,7 @@ use spacetimedb_lib::sats::{
     AlgebraicType, AlgebraicTypeRef, ArrayType, BuiltinType, MapType, ProductType, ProductTypeElement, SumType,
     SumTypeVariant,
 };
-use spacetimedb_lib::{ColumnIndexAttribute, ReducerDef, TableDef};
+use spacetimedb_lib::{ColumnIndexAttribute, EventDef, ReducerDef, TableDef};
 use std::collections::HashSet;
 use std::fmt::Write;
 
@@ -588,6 +588,27 @@ pub fn autogen_rust_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String {
 
     out.newline();
 
+    if let Some(ret_ty) = ctx.reducer_returns.get(&reducer.name) {
+        write!(
+            out,
+            "impl spacetimedb_sdk::reducer::ReducerWithReturn for {} ",
+            type_name
+        )
+        .unwrap();
+        out.delimited_block(
+            "{",
+            |out| {
+                // Refer to the return type by path, as it may also be imported for the arguments.
+                write!(out, "type Return = ").unwrap();
+                write_type(&|r| type_path(ctx, r), out, ret_ty);
+                writeln!(out, ";").unwrap();
+            },
+            "}\n",
+        );
+
+        out.newline();
+    }
+
     // Function definition for the convenient caller, which takes normal args, constructs
     // an instance of the struct, and calls `invoke` on it.
     writeln!(out, "{}", ALLOW_UNUSED).unwrap();
@@ -798,8 +819,13 @@ pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String,
 
     out.newline();
 
+    // Implement `EventType` for each event.
+    print_event_type_impls(ctx, out, items);
+
+    out.newline();
+
     // Define `fn handle_event`.
-    print_handle_event_defn(out, items);
+    print_handle_event_defn(ctx, out, items);
 
     out.newline();
 
@@ -847,6 +873,15 @@ fn iter_module_names(items: &[GenItem]) -> impl Iterator<Item = String> + '_ {
         GenItem::Table(table) => Some(table.name.to_case(Case::Snake)),
         GenItem::TypeAlias(ty) => Some(ty.name.to_case(Case::Snake)),
         GenItem::Reducer(reducer) => (!is_init(reducer)).then_some(reducer_module_name(reducer)),
+        // The event's type is in the module of its alias.
+        GenItem::Event(_) => None,
+    })
+}
+
+fn iter_event_items(items: &[GenItem]) -> impl Iterator<Item = &EventDef> {
+    items.iter().filter_map(|item| match item {
+        GenItem::Event(event) => Some(event),
+        _ => None,
     })
 }
 
@@ -960,15 +995,63 @@ fn print_handle_resubscribe_defn(out: &mut Indenter, items: &[GenItem]) {
     );
 }
 
+/// Implement `EventType` for the type of each event the module emits.
+fn print_event_type_impls(ctx: &GenCtx, out: &mut Indenter, items: &[GenItem]) {
+    for event in iter_event_items(items) {
+        out.delimited_block(
+            &format!(
+                "impl spacetimedb_sdk::event::EventType for {} {{",
+                type_name(ctx, event.ty)
+            ),
+            |out| writeln!(out, "const EVENT_NAME: &'static str = {:?};", event.name).unwrap(),
+            "}\n",
+        );
+    }
+}
+
 /// Define the `handle_event` function,
-/// which dispatches on the reducer name in an `Event`
+/// which dispatches the events emitted in an `Event` on their names
+/// to `ReducerCallbacks::handle_emitted_event_of_type`,
+/// then dispatches on the reducer name
 /// to `ReducerCallbacks::handle_event_of_type` with an appropriate type argument.
-fn print_handle_event_defn(out: &mut Indenter, items: &[GenItem]) {
+fn print_handle_event_defn(ctx: &GenCtx, out: &mut Indenter, items: &[GenItem]) {
+    let has_events = iter_event_items(items).next().is_some();
     // Like `handle_table_update`, muffle unused warning for `handle_event`.
     writeln!(out, "{}", ALLOW_UNUSED).unwrap();
     out.delimited_block(
-        "fn handle_event(event: Event, reducer_callbacks: &mut ReducerCallbacks, state: Arc<ClientCache>) -> Option<Arc<AnyReducerEvent>> {",
+        &format!(
+            "fn handle_event({}event: Event, reducer_callbacks: &mut ReducerCallbacks, state: Arc<ClientCache>) -> Option<Arc<AnyReducerEvent>> {{",
+            if has_events { "mut " } else { "" },
+        ),
         |out| {
+            if has_events {
+                out.delimited_block(
+                    "for emitted in std::mem::take(&mut event.emitted_events) {",
+                    |out| {
+                        out.delimited_block(
+                            "match &emitted.name[..] {",
+                            |out| {
+                                for event in iter_event_items(items) {
+                                    writeln!(
+                                        out,
+                                        "{:?} => reducer_callbacks.handle_emitted_event_of_type::<{}>(emitted, state.clone()),",
+                                        event.name,
+                                        type_name(ctx, event.ty),
+                                    )
+                                    .unwrap();
+                                }
+                                writeln!(
+                                    out,
+                                    "unknown => spacetimedb_sdk::log::error!(\"Emitted an unknown event: {{:?}}\", unknown),",
+                                )
+                                .unwrap();
+                            },
+                            "}\n",
+                        );
+                    },
+                    "}\n",
+                );
+            }
             out.delimited_block(
                 "let Some(function_call) = &event.function_call else {",
                 |out| writeln!(out, "spacetimedb_sdk::log::warn!(\"Received Event with None function_call\"); return None;")
@@ -979,10 +1062,16 @@ fn print_handle_event_defn(out: &mut Indenter, items: &[GenItem]) {
                 "match &function_call.reducer[..] {",
                 |out| {
                     for reducer in iter_reducer_items(items) {
+                        let handle = if ctx.reducer_returns.contains_key(&reducer.name) {
+                            "handle_event_with_return_of_type"
+                        } else {
+                            "handle_event_of_type"
+                        };
                         writeln!(
                             out,
-                            "{:?} => reducer_callbacks.handle_event_of_type::<{}::{}, ReducerEvent>(event, state, ReducerEvent::{}),",
+                            "{:?} => reducer_callbacks.{}::<{}::{}, ReducerEvent>(event, state, ReducerEvent::{}),",
                             reducer.name,
+                            handle,
                             reducer_module_name(reducer),
                             reducer_type_name(reducer),
                             reducer_variant_name(reducer),
@@ -1079,6 +1168,12 @@ fn module_name(name: &str) -> String {
     name.to_case(Case::Snake)
 }
 
+/// The path of the type `r` from a sibling module, e.g., `super::point::Point`.
+fn type_path(ctx: &GenCtx, r: AlgebraicTypeRef) -> String {
+    let type_name = type_name(ctx, r);
+    format!("super::{}::{}", module_name(&type_name), type_name)
+}
+
 fn generate_imports(ctx: &GenCtx, imports: &mut Imports, ty: &AlgebraicType) {
     match ty {
         AlgebraicType::Builtin(BuiltinType::Array(ArrayType { elem_ty })) => generate_imports(ctx, imports, elem_ty),
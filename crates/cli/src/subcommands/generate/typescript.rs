@@ -1,5 +1,6 @@
 use super::util::fmt_fn;
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Write};
 
 use convert_case::{Case, Casing};
@@ -7,7 +8,7 @@ use spacetimedb_lib::sats::{
     AlgebraicType, AlgebraicType::Builtin, AlgebraicTypeRef, ArrayType, BuiltinType, MapType, ProductType,
     ProductTypeElement, SumType, SumTypeVariant,
 };
-use spacetimedb_lib::{ColumnIndexAttribute, ReducerDef, TableDef};
+use spacetimedb_lib::{ColumnIndexAttribute, EventDef, ReducerDef, TableDef, TypeAlias};
 
 use super::code_indenter::CodeIndenter;
 use super::{GenCtx, GenItem, INDENT};
@@ -1308,6 +1309,147 @@ pub fn autogen_typescript_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String
     output.into_inner()
 }
 
-pub fn autogen_typescript_globals(_ctx: &GenCtx, _items: &[GenItem]) -> Vec<Vec<(String, String)>> {
-    vec![] //TODO
+pub fn autogen_typescript_event(ctx: &GenCtx, event: &EventDef) -> String {
+    let event_name = &*event.name;
+    let event_name_pascal_case = event_name.to_case(Case::Pascal);
+    let ty = AlgebraicType::Ref(event.ty);
+    let type_name = ty_fmt(ctx, &ty, "");
+
+    let mut output = CodeIndenter::new(String::new());
+
+    writeln!(
+        output,
+        "// THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE"
+    )
+    .unwrap();
+    writeln!(output, "// WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(output).unwrap();
+
+    writeln!(output, "// @ts-ignore").unwrap();
+    writeln!(
+        output,
+        "import {{ __SPACETIMEDB__, AlgebraicType, AlgebraicValue }} from \"@clockworklabs/spacetimedb-sdk\";"
+    )
+    .unwrap();
+
+    let mut imports = Vec::new();
+    _generate_imports(ctx, &ty, &mut imports, None);
+    for import in imports {
+        writeln!(output, "// @ts-ignore").unwrap();
+        writeln!(output, "{import}").unwrap();
+    }
+
+    writeln!(output).unwrap();
+
+    writeln!(output, "export class {event_name_pascal_case}Event").unwrap();
+    writeln!(output, "{{").unwrap();
+    {
+        indent_scope!(output);
+
+        writeln!(output, "public static eventName = \"{event_name}\";").unwrap();
+        writeln!(output).unwrap();
+
+        writeln!(output, "public static getAlgebraicType(): AlgebraicType").unwrap();
+        writeln!(output, "{{").unwrap();
+        {
+            indent_scope!(output);
+            writeln!(output, "return {};", convert_algebraic_type(ctx, &ty, "")).unwrap();
+        }
+        writeln!(output, "}}").unwrap();
+        writeln!(output).unwrap();
+
+        writeln!(output, "public static deserialize(value: AlgebraicValue): {type_name}").unwrap();
+        writeln!(output, "{{").unwrap();
+        {
+            indent_scope!(output);
+            writeln!(output, "return {};", convert_type(ctx, 0, &ty, "value", "")).unwrap();
+        }
+        writeln!(output, "}}").unwrap();
+        writeln!(output).unwrap();
+
+        writeln!(output, "public static on(callback: (event: {type_name}) => void)").unwrap();
+        writeln!(output, "{{").unwrap();
+        {
+            indent_scope!(output);
+
+            writeln!(output, "if (__SPACETIMEDB__.spacetimeDBClient) {{").unwrap();
+            writeln!(
+                output,
+                "\t__SPACETIMEDB__.spacetimeDBClient.on(\"event:{event_name_pascal_case}\", callback);"
+            )
+            .unwrap();
+            writeln!(output, "}}").unwrap();
+        }
+        writeln!(output, "}}").unwrap();
+    }
+    // Closing brace for class
+    writeln!(output, "}}").unwrap();
+
+    writeln!(output).unwrap();
+
+    writeln!(
+        output,
+        "__SPACETIMEDB__.events.set(\"{event_name_pascal_case}\", {event_name_pascal_case}Event);"
+    )
+    .unwrap();
+
+    writeln!(output, "if (__SPACETIMEDB__.spacetimeDBClient) {{").unwrap();
+    {
+        indent_scope!(output);
+
+        writeln!(output, "__SPACETIMEDB__.spacetimeDBClient.registerEvent(\"{event_name_pascal_case}\", {event_name_pascal_case}Event);").unwrap();
+    }
+    writeln!(output, "}}").unwrap();
+
+    writeln!(output, "\nexport default {event_name_pascal_case}Event").unwrap();
+
+    output.into_inner()
+}
+
+/// The file, without its extension, generated for `item`,
+/// or `None` if it doesn't generate one.
+///
+/// This must match the names chosen by `GenItem::generate_typescript`.
+fn typescript_module_name(ctx: &GenCtx, item: &GenItem) -> Option<String> {
+    match item {
+        GenItem::Table(table) => Some(table.name.to_case(Case::Snake)),
+        GenItem::TypeAlias(TypeAlias { name, ty }) => match &ctx.typespace[*ty] {
+            AlgebraicType::Sum(_) => Some(name.replace('.', "").to_case(Case::Snake)),
+            AlgebraicType::Product(_) => Some(name.to_case(Case::Snake)),
+            _ => None,
+        },
+        GenItem::Reducer(reducer) if reducer.name == "__init__" => None,
+        GenItem::Reducer(reducer) => Some(reducer.name.to_case(Case::Snake) + "_reducer"),
+        GenItem::Event(event) => Some(event.name.to_case(Case::Snake) + "_event"),
+    }
+}
+
+/// Generates `index.ts`, which re-exports every generated table, type, reducer and event,
+/// so a client can import the whole module from one place.
+///
+/// Importing it also evaluates every generated file,
+/// registering all the tables, reducers and events with the SDK,
+/// which otherwise only happens for the files the client imports itself.
+pub fn autogen_typescript_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String, String)>> {
+    let mut output = CodeIndenter::new(String::new());
+
+    writeln!(
+        output,
+        "// THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE"
+    )
+    .unwrap();
+    writeln!(output, "// WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(output).unwrap();
+
+    // A table's row type is also a type alias, generated to the same file.
+    let module_names: BTreeSet<_> = items
+        .iter()
+        .filter_map(|item| typescript_module_name(ctx, item))
+        .collect();
+    for module_name in module_names {
+        writeln!(output, "// @ts-ignore").unwrap();
+        writeln!(output, "export * from \"./{module_name}\";").unwrap();
+    }
+
+    vec![vec![("index.ts".to_string(), output.into_inner())]]
 }
@@ -0,0 +1,909 @@
+//! Generates C++ client bindings for Unreal Engine.
+//!
+//! Each table and product type becomes a `USTRUCT`,
+//! each sum type a `UENUM` if all its variants are units, and otherwise a tagged `USTRUCT`,
+//! each reducer a `USTRUCT` of its arguments which can encode a call to it,
+//! and each event a struct holding the delegate to broadcast the event to once decoded.
+//! All of them get a specialization of `SpacetimeDB::TBsatn` to (de)serialize them as BSATN,
+//! which is defined, along with the specializations for the builtin types,
+//! in the `SpacetimeDBBsatn.h` header generated with them.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use convert_case::{Case, Casing};
+use spacetimedb_lib::sats::{
+    AlgebraicType, AlgebraicTypeRef, ArrayType, BuiltinType, MapType, ProductType, ProductTypeElement, SumType,
+};
+use spacetimedb_lib::{EventDef, ReducerDef, TableDef};
+
+use super::code_indenter::CodeIndenter;
+use super::{GenCtx, GenItem};
+
+type Indenter = CodeIndenter<String>;
+
+/// The header defining `TBsatn` and the types the generated code shares.
+const BSATN_HEADER: &str = "SpacetimeDBBsatn";
+
+/// Print `header` on its own line, then a block in braces with its body written by `f`,
+/// followed by `after` on the line of the closing brace.
+fn braced(out: &mut Indenter, header: &str, f: impl FnOnce(&mut Indenter), after: &str) {
+    writeln!(out, "{header}").unwrap();
+    writeln!(out, "{{").unwrap();
+    out.indent(1);
+    f(out);
+    out.dedent(1);
+    writeln!(out, "}}{after}").unwrap();
+}
+
+/// Print the comment, `#pragma` and includes starting a header,
+/// which includes `{file_name}.generated.h` unless `file_name` is `None`, for a header without reflected types.
+fn print_file_header(ctx: &GenCtx, out: &mut Indenter, file_name: Option<&str>, refs: &BTreeSet<AlgebraicTypeRef>) {
+    writeln!(
+        out,
+        "// THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE"
+    )
+    .unwrap();
+    writeln!(out, "// WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#pragma once").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#include \"CoreMinimal.h\"").unwrap();
+    writeln!(out, "#include \"{BSATN_HEADER}.h\"").unwrap();
+    for r in refs {
+        writeln!(out, "#include \"{}.h\"", header_name(ctx, *r)).unwrap();
+    }
+    if let Some(file_name) = file_name {
+        // The Unreal Header Tool requires this to be the last include.
+        writeln!(out, "#include \"{file_name}.generated.h\"").unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+/// The name of `name` as an Unreal type, without its `F` or `E` prefix.
+fn base_name(name: &str) -> String {
+    name.replace("r#", "").replace('.', "").to_case(Case::Pascal)
+}
+
+fn header_name(ctx: &GenCtx, typeref: AlgebraicTypeRef) -> String {
+    base_name(ctx.names[typeref.idx()].as_deref().expect("TypeRefs should have names"))
+}
+
+/// Is `ty` a sum type whose variants are all units,
+/// which is generated as a `UENUM` rather than a `USTRUCT`?
+fn is_plain_enum(ty: &AlgebraicType) -> bool {
+    matches!(ty, AlgebraicType::Sum(sum) if sum.variants.iter().all(|v| v.is_unit()))
+}
+
+fn type_name(ctx: &GenCtx, typeref: AlgebraicTypeRef) -> String {
+    let prefix = if is_plain_enum(&ctx.typespace[typeref]) {
+        "E"
+    } else {
+        "F"
+    };
+    prefix.to_owned() + &header_name(ctx, typeref)
+}
+
+fn variant_name(name: Option<&str>, i: usize) -> String {
+    name.map_or_else(|| format!("Variant{i}"), |name| name.to_case(Case::Pascal))
+}
+
+fn field_name(name: Option<&str>, i: usize) -> String {
+    name.map_or_else(|| format!("Field{i}"), |name| name.to_case(Case::Pascal))
+}
+
+fn cpp_type(ctx: &GenCtx, ty: &AlgebraicType) -> String {
+    match ty {
+        AlgebraicType::Product(prod) if prod.is_identity() => "FSpacetimeDBIdentity".into(),
+        AlgebraicType::Product(_) => unimplemented!("anonymous product types aren't supported"),
+        AlgebraicType::Sum(sum) => match sum.as_option() {
+            Some(some_ty) => format!("TOptional<{}>", cpp_type(ctx, some_ty)),
+            None => unimplemented!("anonymous sum types aren't supported"),
+        },
+        AlgebraicType::Builtin(b) => match b {
+            BuiltinType::Bool => "bool".into(),
+            BuiltinType::I8 => "int8".into(),
+            BuiltinType::U8 => "uint8".into(),
+            BuiltinType::I16 => "int16".into(),
+            BuiltinType::U16 => "uint16".into(),
+            BuiltinType::I32 => "int32".into(),
+            BuiltinType::U32 => "uint32".into(),
+            BuiltinType::I64 => "int64".into(),
+            BuiltinType::U64 => "uint64".into(),
+            BuiltinType::I128 => "FSpacetimeDBInt128".into(),
+            BuiltinType::U128 => "FSpacetimeDBUInt128".into(),
+            BuiltinType::F32 => "float".into(),
+            BuiltinType::F64 => "double".into(),
+            BuiltinType::String => "FString".into(),
+            BuiltinType::Array(ArrayType { elem_ty }) => format!("TArray<{}>", cpp_type(ctx, elem_ty)),
+            BuiltinType::Map(MapType { key_ty, ty }) => {
+                format!("TMap<{}, {}>", cpp_type(ctx, key_ty), cpp_type(ctx, ty))
+            }
+        },
+        AlgebraicType::Ref(r) => type_name(ctx, *r),
+    }
+}
+
+/// How a field of some type is exposed to Unreal's reflection.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+enum Reflection {
+    /// Not a `UPROPERTY`, e.g., a `TOptional`.
+    None,
+    /// A `UPROPERTY`, but of a type Blueprints don't support, e.g., `uint16`.
+    Property,
+    /// A `UPROPERTY` usable from Blueprints.
+    Blueprint,
+}
+
+fn reflection(ty: &AlgebraicType) -> Reflection {
+    match ty {
+        AlgebraicType::Product(_) | AlgebraicType::Ref(_) => Reflection::Blueprint,
+        AlgebraicType::Sum(_) => Reflection::None,
+        AlgebraicType::Builtin(b) => match b {
+            BuiltinType::Bool
+            | BuiltinType::U8
+            | BuiltinType::I32
+            | BuiltinType::I64
+            | BuiltinType::I128
+            | BuiltinType::U128
+            | BuiltinType::F32
+            | BuiltinType::F64
+            | BuiltinType::String => Reflection::Blueprint,
+            BuiltinType::I8 | BuiltinType::I16 | BuiltinType::U16 | BuiltinType::U32 | BuiltinType::U64 => {
+                Reflection::Property
+            }
+            // Properties can't be containers of containers.
+            BuiltinType::Array(ArrayType { elem_ty }) if is_container(elem_ty) => Reflection::None,
+            BuiltinType::Array(ArrayType { elem_ty }) => reflection(elem_ty),
+            BuiltinType::Map(MapType { key_ty, ty }) if is_container(key_ty) || is_container(ty) => Reflection::None,
+            BuiltinType::Map(MapType { key_ty, ty }) => reflection(key_ty).min(reflection(ty)),
+        },
+    }
+}
+
+fn is_container(ty: &AlgebraicType) -> bool {
+    matches!(ty, AlgebraicType::Builtin(BuiltinType::Array(_) | BuiltinType::Map(_)))
+}
+
+/// The initializer of a field of type `ty`, if it needs one to not be left uninitialized.
+fn field_init(ctx: &GenCtx, ty: &AlgebraicType) -> Option<String> {
+    match ty {
+        AlgebraicType::Builtin(BuiltinType::Bool) => Some("false".into()),
+        AlgebraicType::Builtin(
+            BuiltinType::I8
+            | BuiltinType::U8
+            | BuiltinType::I16
+            | BuiltinType::U16
+            | BuiltinType::I32
+            | BuiltinType::U32
+            | BuiltinType::I64
+            | BuiltinType::U64
+            | BuiltinType::F32
+            | BuiltinType::F64,
+        ) => Some("0".into()),
+        AlgebraicType::Ref(r) => match &ctx.typespace[*r] {
+            AlgebraicType::Sum(sum) if is_plain_enum(&ctx.typespace[*r]) => Some(format!(
+                "{}::{}",
+                type_name(ctx, *r),
+                variant_name(sum.variants[0].name(), 0)
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn print_field(ctx: &GenCtx, out: &mut Indenter, ty: &AlgebraicType, name: &str) {
+    match reflection(ty) {
+        Reflection::None => {}
+        Reflection::Property => writeln!(out, "UPROPERTY(EditAnywhere)").unwrap(),
+        Reflection::Blueprint => writeln!(out, "UPROPERTY(EditAnywhere, BlueprintReadWrite)").unwrap(),
+    }
+    match field_init(ctx, ty) {
+        Some(init) => writeln!(out, "{} {name} = {init};", cpp_type(ctx, ty)).unwrap(),
+        None => writeln!(out, "{} {name};", cpp_type(ctx, ty)).unwrap(),
+    }
+}
+
+/// Collects the types referred to by `ty`, whose headers must be included to use it.
+fn collect_refs(ty: &AlgebraicType, refs: &mut BTreeSet<AlgebraicTypeRef>) {
+    match ty {
+        AlgebraicType::Product(prod) => prod.elements.iter().for_each(|e| collect_refs(&e.algebraic_type, refs)),
+        AlgebraicType::Sum(sum) => sum.variants.iter().for_each(|v| collect_refs(&v.algebraic_type, refs)),
+        AlgebraicType::Builtin(BuiltinType::Array(ArrayType { elem_ty })) => collect_refs(elem_ty, refs),
+        AlgebraicType::Builtin(BuiltinType::Map(MapType { key_ty, ty })) => {
+            collect_refs(key_ty, refs);
+            collect_refs(ty, refs);
+        }
+        AlgebraicType::Builtin(_) => {}
+        AlgebraicType::Ref(r) => {
+            refs.insert(*r);
+        }
+    }
+}
+
+fn element_refs(elements: &[ProductTypeElement]) -> BTreeSet<AlgebraicTypeRef> {
+    let mut refs = BTreeSet::new();
+    elements.iter().for_each(|e| collect_refs(&e.algebraic_type, &mut refs));
+    refs
+}
+
+/// Print the specialization of `TBsatn` for `type_name`, with `Write` and `Read` written by `write` and `read`,
+/// inside the `SpacetimeDB` namespace.
+fn print_bsatn_specialization(
+    out: &mut Indenter,
+    type_name: &str,
+    params: (&str, &str, &str),
+    write: impl FnOnce(&mut Indenter),
+    read: impl FnOnce(&mut Indenter),
+) {
+    let (out_param, in_param, value_param) = params;
+    writeln!(out, "namespace SpacetimeDB").unwrap();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "template<>").unwrap();
+    braced(
+        out,
+        &format!("struct TBsatn<{type_name}>"),
+        |out| {
+            braced(
+                out,
+                &format!("static void Write(TArray<uint8>&{out_param}, const {type_name}&{value_param})"),
+                write,
+                "",
+            );
+            writeln!(out).unwrap();
+            braced(
+                out,
+                &format!("static bool Read(FBsatnReader&{in_param}, {type_name}&{value_param})"),
+                read,
+                "",
+            );
+        },
+        ";",
+    );
+    writeln!(out, "}}").unwrap();
+}
+
+/// Print the specialization of `TBsatn` for the struct `type_name` with the fields `elements`,
+/// which are (de)serialized in order, as a product.
+fn print_product_bsatn(ctx: &GenCtx, out: &mut Indenter, type_name: &str, elements: &[ProductTypeElement]) {
+    // Name the parameters only if they're used, to avoid warnings.
+    let params = if elements.is_empty() {
+        ("", "", "")
+    } else {
+        (" Out", " In", " Value")
+    };
+    print_bsatn_specialization(
+        out,
+        type_name,
+        params,
+        |out| {
+            for (i, elem) in elements.iter().enumerate() {
+                writeln!(
+                    out,
+                    "TBsatn<{}>::Write(Out, Value.{});",
+                    cpp_type(ctx, &elem.algebraic_type),
+                    field_name(elem.name(), i),
+                )
+                .unwrap();
+            }
+        },
+        |out| {
+            for (i, elem) in elements.iter().enumerate() {
+                writeln!(
+                    out,
+                    "if (!TBsatn<{}>::Read(In, Value.{})) return false;",
+                    cpp_type(ctx, &elem.algebraic_type),
+                    field_name(elem.name(), i),
+                )
+                .unwrap();
+            }
+            writeln!(out, "return true;").unwrap();
+        },
+    );
+}
+
+fn print_struct_defn(
+    ctx: &GenCtx,
+    out: &mut Indenter,
+    type_name: &str,
+    elements: &[ProductTypeElement],
+    extra: impl FnOnce(&mut Indenter),
+) {
+    writeln!(out, "USTRUCT(BlueprintType)").unwrap();
+    braced(
+        out,
+        &format!("struct {type_name}"),
+        |out| {
+            writeln!(out, "GENERATED_BODY()").unwrap();
+            for (i, elem) in elements.iter().enumerate() {
+                writeln!(out).unwrap();
+                print_field(ctx, out, &elem.algebraic_type, &field_name(elem.name(), i));
+            }
+            extra(out);
+        },
+        ";",
+    );
+}
+
+pub fn autogen_unreal_tuple(ctx: &GenCtx, name: &str, product: &ProductType) -> String {
+    let mut output = CodeIndenter::new(String::new());
+    let out = &mut output;
+
+    let file_name = base_name(name);
+    let type_name = format!("F{file_name}");
+
+    print_file_header(ctx, out, Some(&file_name), &element_refs(&product.elements));
+    print_struct_defn(ctx, out, &type_name, &product.elements, |_| {});
+    writeln!(out).unwrap();
+    print_product_bsatn(ctx, out, &type_name, &product.elements);
+
+    output.into_inner()
+}
+
+pub fn autogen_unreal_table(ctx: &GenCtx, table: &TableDef) -> String {
+    let product = ctx.typespace[table.data]
+        .as_product()
+        .expect("a table's row type should be a product");
+    autogen_unreal_tuple(ctx, &table.name, product)
+}
+
+pub fn autogen_unreal_sum(ctx: &GenCtx, name: &str, sum: &SumType) -> String {
+    let mut output = CodeIndenter::new(String::new());
+    let out = &mut output;
+
+    let file_name = base_name(name);
+    let mut refs = BTreeSet::new();
+    sum.variants
+        .iter()
+        .for_each(|v| collect_refs(&v.algebraic_type, &mut refs));
+    print_file_header(ctx, out, Some(&file_name), &refs);
+
+    let is_plain = sum.variants.iter().all(|v| v.is_unit());
+    let tag_name = if is_plain {
+        format!("E{file_name}")
+    } else {
+        format!("E{file_name}Tag")
+    };
+    let variant_names: Vec<_> = (sum.variants.iter().enumerate())
+        .map(|(i, v)| variant_name(v.name(), i))
+        .collect();
+    let num_variants = variant_names.len();
+
+    writeln!(out, "UENUM(BlueprintType)").unwrap();
+    braced(
+        out,
+        &format!("enum class {tag_name} : uint8"),
+        |out| {
+            for variant in &variant_names {
+                writeln!(out, "{variant},").unwrap();
+            }
+        },
+        ";",
+    );
+    writeln!(out).unwrap();
+
+    let read_tag = |out: &mut Indenter, dest: &str| {
+        writeln!(out, "uint8 Tag;").unwrap();
+        writeln!(out, "if (!In.ReadByte(Tag) || Tag >= {num_variants}) return false;").unwrap();
+        writeln!(out, "{dest} = static_cast<{tag_name}>(Tag);").unwrap();
+    };
+
+    if is_plain {
+        print_bsatn_specialization(
+            out,
+            &tag_name,
+            (" Out", " In", " Value"),
+            |out| writeln!(out, "Out.Add(static_cast<uint8>(Value));").unwrap(),
+            |out| {
+                read_tag(out, "Value");
+                writeln!(out, "return true;").unwrap();
+            },
+        );
+        return output.into_inner();
+    }
+
+    let type_name = format!("F{file_name}");
+    let payloads: Vec<_> = (sum.variants.iter().zip(&variant_names))
+        .filter(|(variant, _)| !variant.is_unit())
+        .map(|(variant, name)| (&variant.algebraic_type, name))
+        .collect();
+
+    writeln!(out, "USTRUCT(BlueprintType)").unwrap();
+    braced(
+        out,
+        &format!("struct {type_name}"),
+        |out| {
+            writeln!(out, "GENERATED_BODY()").unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "UPROPERTY(EditAnywhere, BlueprintReadWrite)").unwrap();
+            writeln!(out, "{tag_name} Tag = {tag_name}::{};", variant_names[0]).unwrap();
+            for (ty, name) in &payloads {
+                writeln!(out).unwrap();
+                writeln!(out, "/** The value of the `{name}` variant, if `Tag` is `{name}`. */").unwrap();
+                print_field(ctx, out, ty, name);
+            }
+        },
+        ";",
+    );
+    writeln!(out).unwrap();
+
+    print_bsatn_specialization(
+        out,
+        &type_name,
+        (" Out", " In", " Value"),
+        |out| {
+            writeln!(out, "Out.Add(static_cast<uint8>(Value.Tag));").unwrap();
+            braced(
+                out,
+                "switch (Value.Tag)",
+                |out| {
+                    for (ty, name) in &payloads {
+                        writeln!(out, "case {tag_name}::{name}:").unwrap();
+                        writeln!(out, "\tTBsatn<{}>::Write(Out, Value.{name});", cpp_type(ctx, ty)).unwrap();
+                        writeln!(out, "\tbreak;").unwrap();
+                    }
+                    writeln!(out, "default:").unwrap();
+                    writeln!(out, "\tbreak;").unwrap();
+                },
+                "",
+            );
+        },
+        |out| {
+            read_tag(out, "Value.Tag");
+            braced(
+                out,
+                "switch (Value.Tag)",
+                |out| {
+                    for (ty, name) in &payloads {
+                        writeln!(out, "case {tag_name}::{name}:").unwrap();
+                        writeln!(out, "\treturn TBsatn<{}>::Read(In, Value.{name});", cpp_type(ctx, ty)).unwrap();
+                    }
+                    writeln!(out, "default:").unwrap();
+                    writeln!(out, "\treturn true;").unwrap();
+                },
+                "",
+            );
+        },
+    );
+
+    output.into_inner()
+}
+
+pub fn autogen_unreal_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String {
+    let mut output = CodeIndenter::new(String::new());
+    let out = &mut output;
+
+    let file_name = reducer.name.to_case(Case::Pascal) + "Reducer";
+    let type_name = format!("F{file_name}");
+
+    print_file_header(ctx, out, Some(&file_name), &element_refs(&reducer.args));
+
+    writeln!(out, "/** The arguments of a call to the reducer `{}`. */", reducer.name).unwrap();
+    print_struct_defn(ctx, out, &type_name, &reducer.args, |out| {
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "static constexpr const TCHAR* ReducerName = TEXT({:?});",
+            reducer.name
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "/** Encodes a message calling the reducer with these arguments, to send as a binary WebSocket frame. */"
+        )
+        .unwrap();
+        writeln!(out, "TArray<uint8> ToMessage() const;").unwrap();
+    });
+    writeln!(out).unwrap();
+    print_product_bsatn(ctx, out, &type_name, &reducer.args);
+    writeln!(out).unwrap();
+
+    braced(
+        out,
+        &format!("inline TArray<uint8> {type_name}::ToMessage() const"),
+        |out| {
+            writeln!(out, "TArray<uint8> Args;").unwrap();
+            writeln!(out, "SpacetimeDB::TBsatn<{type_name}>::Write(Args, *this);").unwrap();
+            writeln!(out, "return SpacetimeDB::EncodeFunctionCall(ReducerName, Args);").unwrap();
+        },
+        "",
+    );
+
+    output.into_inner()
+}
+
+pub fn autogen_unreal_event(ctx: &GenCtx, event: &EventDef) -> String {
+    let mut output = CodeIndenter::new(String::new());
+    let out = &mut output;
+
+    let file_name = base_name(&event.name);
+    let event_type = type_name(ctx, event.ty);
+    let delegate_name = format!("FOn{file_name}");
+    let type_name = format!("F{file_name}Event");
+
+    // The struct and the delegate aren't reflected, so there's no `.generated.h` to include.
+    print_file_header(ctx, out, None, &BTreeSet::from([event.ty]));
+
+    writeln!(
+        out,
+        "DECLARE_MULTICAST_DELEGATE_OneParam({delegate_name}, const {event_type}&);"
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(
+        out,
+        "/** The event `{}`, which reducers emit to the clients. */",
+        event.name
+    )
+    .unwrap();
+    braced(
+        out,
+        &format!("struct {type_name}"),
+        |out| {
+            writeln!(out, "static constexpr const TCHAR* EventName = TEXT({:?});", event.name).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "/** The callbacks to run whenever a reducer emits the event. */").unwrap();
+            braced(
+                out,
+                &format!("static {delegate_name}& On{file_name}()"),
+                |out| {
+                    writeln!(out, "static {delegate_name} Delegate;").unwrap();
+                    writeln!(out, "return Delegate;").unwrap();
+                },
+                "",
+            );
+            writeln!(out).unwrap();
+            writeln!(out, "/**").unwrap();
+            writeln!(
+                out,
+                " * Decodes the `data` of an `EmittedEvent` named `EventName` and broadcasts it to `On{file_name}`,"
+            )
+            .unwrap();
+            writeln!(out, " * returning false if it isn't a valid event.").unwrap();
+            writeln!(out, " */").unwrap();
+            braced(
+                out,
+                "static bool Dispatch(const TArray<uint8>& Data)",
+                |out| {
+                    writeln!(out, "{event_type} Event;").unwrap();
+                    writeln!(out, "if (!SpacetimeDB::FromBsatn(Data, Event)) return false;").unwrap();
+                    writeln!(out, "On{file_name}().Broadcast(Event);").unwrap();
+                    writeln!(out, "return true;").unwrap();
+                },
+                "",
+            );
+        },
+        ";",
+    );
+
+    output.into_inner()
+}
+
+/// The definitions shared by all the generated files,
+/// which don't depend on the module.
+const BSATN_HEADER_BODY: &str = r#"/** An `Identity`, which identifies a client or a database. */
+USTRUCT(BlueprintType)
+struct FSpacetimeDBIdentity
+{
+	GENERATED_BODY()
+
+	UPROPERTY(EditAnywhere, BlueprintReadWrite)
+	TArray<uint8> Bytes;
+};
+
+/** A signed 128-bit integer, which Unreal has no type for. */
+USTRUCT(BlueprintType)
+struct FSpacetimeDBInt128
+{
+	GENERATED_BODY()
+
+	UPROPERTY(EditAnywhere)
+	uint64 Low = 0;
+
+	UPROPERTY(EditAnywhere)
+	int64 High = 0;
+};
+
+/** An unsigned 128-bit integer, which Unreal has no type for. */
+USTRUCT(BlueprintType)
+struct FSpacetimeDBUInt128
+{
+	GENERATED_BODY()
+
+	UPROPERTY(EditAnywhere)
+	uint64 Low = 0;
+
+	UPROPERTY(EditAnywhere)
+	uint64 High = 0;
+};
+
+namespace SpacetimeDB
+{
+/** Reads BSATN-encoded values from a buffer. */
+struct FBsatnReader
+{
+	explicit FBsatnReader(const TArray<uint8>& InData) : Data(InData) {}
+
+	bool ReadBytes(void* Dest, int32 Num)
+	{
+		if (Num < 0 || Data.Num() - Pos < Num) return false;
+		FMemory::Memcpy(Dest, Data.GetData() + Pos, Num);
+		Pos += Num;
+		return true;
+	}
+
+	bool ReadByte(uint8& Byte)
+	{
+		return ReadBytes(&Byte, 1);
+	}
+
+	bool IsAtEnd() const
+	{
+		return Pos == Data.Num();
+	}
+
+	const TArray<uint8>& Data;
+	int32 Pos = 0;
+};
+
+/**
+ * (De)serializes values of type `T` as BSATN.
+ *
+ * `Write` appends the encoding of a value to a buffer,
+ * and `Read` decodes a value, returning false if the input is invalid.
+ */
+template<typename T>
+struct TBsatn;
+
+// Numbers are little-endian in BSATN, as they are on all the platforms Unreal supports.
+#define SPACETIMEDB_BSATN_NUMBER(Type) \
+	template<> \
+	struct TBsatn<Type> \
+	{ \
+		static void Write(TArray<uint8>& Out, Type Value) { Out.Append(reinterpret_cast<const uint8*>(&Value), sizeof(Type)); } \
+		static bool Read(FBsatnReader& In, Type& Value) { return In.ReadBytes(&Value, sizeof(Type)); } \
+	};
+
+SPACETIMEDB_BSATN_NUMBER(int8)
+SPACETIMEDB_BSATN_NUMBER(uint8)
+SPACETIMEDB_BSATN_NUMBER(int16)
+SPACETIMEDB_BSATN_NUMBER(uint16)
+SPACETIMEDB_BSATN_NUMBER(int32)
+SPACETIMEDB_BSATN_NUMBER(uint32)
+SPACETIMEDB_BSATN_NUMBER(int64)
+SPACETIMEDB_BSATN_NUMBER(uint64)
+SPACETIMEDB_BSATN_NUMBER(float)
+SPACETIMEDB_BSATN_NUMBER(double)
+
+#undef SPACETIMEDB_BSATN_NUMBER
+
+template<>
+struct TBsatn<bool>
+{
+	static void Write(TArray<uint8>& Out, bool Value) { Out.Add(Value ? 1 : 0); }
+	static bool Read(FBsatnReader& In, bool& Value)
+	{
+		uint8 Byte;
+		if (!In.ReadByte(Byte) || Byte > 1) return false;
+		Value = Byte == 1;
+		return true;
+	}
+};
+
+template<>
+struct TBsatn<FSpacetimeDBInt128>
+{
+	static void Write(TArray<uint8>& Out, const FSpacetimeDBInt128& Value)
+	{
+		TBsatn<uint64>::Write(Out, Value.Low);
+		TBsatn<int64>::Write(Out, Value.High);
+	}
+	static bool Read(FBsatnReader& In, FSpacetimeDBInt128& Value)
+	{
+		return TBsatn<uint64>::Read(In, Value.Low) && TBsatn<int64>::Read(In, Value.High);
+	}
+};
+
+template<>
+struct TBsatn<FSpacetimeDBUInt128>
+{
+	static void Write(TArray<uint8>& Out, const FSpacetimeDBUInt128& Value)
+	{
+		TBsatn<uint64>::Write(Out, Value.Low);
+		TBsatn<uint64>::Write(Out, Value.High);
+	}
+	static bool Read(FBsatnReader& In, FSpacetimeDBUInt128& Value)
+	{
+		return TBsatn<uint64>::Read(In, Value.Low) && TBsatn<uint64>::Read(In, Value.High);
+	}
+};
+
+/** Strings are their length, as a `uint32`, followed by their UTF-8 bytes. */
+template<>
+struct TBsatn<FString>
+{
+	static void Write(TArray<uint8>& Out, const FString& Value)
+	{
+		FTCHARToUTF8 Utf8(*Value);
+		TBsatn<uint32>::Write(Out, Utf8.Length());
+		Out.Append(reinterpret_cast<const uint8*>(Utf8.Get()), Utf8.Length());
+	}
+	static bool Read(FBsatnReader& In, FString& Value)
+	{
+		uint32 Len;
+		if (!TBsatn<uint32>::Read(In, Len) || Len > uint32(In.Data.Num() - In.Pos)) return false;
+		FUTF8ToTCHAR Chars(reinterpret_cast<const ANSICHAR*>(In.Data.GetData() + In.Pos), Len);
+		Value = FString(Chars.Length(), Chars.Get());
+		In.Pos += Len;
+		return true;
+	}
+};
+
+/** Arrays are their length, as a `uint32`, followed by their elements. */
+template<typename T>
+struct TBsatn<TArray<T>>
+{
+	static void Write(TArray<uint8>& Out, const TArray<T>& Value)
+	{
+		TBsatn<uint32>::Write(Out, Value.Num());
+		for (const T& Elem : Value)
+		{
+			TBsatn<T>::Write(Out, Elem);
+		}
+	}
+	static bool Read(FBsatnReader& In, TArray<T>& Value)
+	{
+		uint32 Len;
+		if (!TBsatn<uint32>::Read(In, Len) || Len > uint32(In.Data.Num() - In.Pos)) return false;
+		Value.SetNum(Len);
+		for (T& Elem : Value)
+		{
+			if (!TBsatn<T>::Read(In, Elem)) return false;
+		}
+		return true;
+	}
+};
+
+/** Maps are their length, as a `uint32`, followed by their keys and values. */
+template<typename K, typename V>
+struct TBsatn<TMap<K, V>>
+{
+	static void Write(TArray<uint8>& Out, const TMap<K, V>& Value)
+	{
+		TBsatn<uint32>::Write(Out, Value.Num());
+		for (const TPair<K, V>& Pair : Value)
+		{
+			TBsatn<K>::Write(Out, Pair.Key);
+			TBsatn<V>::Write(Out, Pair.Value);
+		}
+	}
+	static bool Read(FBsatnReader& In, TMap<K, V>& Value)
+	{
+		uint32 Len;
+		if (!TBsatn<uint32>::Read(In, Len) || Len > uint32(In.Data.Num() - In.Pos)) return false;
+		Value.Empty(Len);
+		for (uint32 i = 0; i < Len; i++)
+		{
+			K Key;
+			V Val;
+			if (!TBsatn<K>::Read(In, Key) || !TBsatn<V>::Read(In, Val)) return false;
+			Value.Add(MoveTemp(Key), MoveTemp(Val));
+		}
+		return true;
+	}
+};
+
+/** Options are a sum of `some`, with tag 0, and `none`, with tag 1. */
+template<typename T>
+struct TBsatn<TOptional<T>>
+{
+	static void Write(TArray<uint8>& Out, const TOptional<T>& Value)
+	{
+		if (Value.IsSet())
+		{
+			Out.Add(0);
+			TBsatn<T>::Write(Out, Value.GetValue());
+		}
+		else
+		{
+			Out.Add(1);
+		}
+	}
+	static bool Read(FBsatnReader& In, TOptional<T>& Value)
+	{
+		uint8 Tag;
+		if (!In.ReadByte(Tag)) return false;
+		if (Tag == 1)
+		{
+			Value.Reset();
+			return true;
+		}
+		T Some;
+		if (Tag != 0 || !TBsatn<T>::Read(In, Some)) return false;
+		Value = MoveTemp(Some);
+		return true;
+	}
+};
+
+template<>
+struct TBsatn<FSpacetimeDBIdentity>
+{
+	static void Write(TArray<uint8>& Out, const FSpacetimeDBIdentity& Value)
+	{
+		TBsatn<TArray<uint8>>::Write(Out, Value.Bytes);
+	}
+	static bool Read(FBsatnReader& In, FSpacetimeDBIdentity& Value)
+	{
+		return TBsatn<TArray<uint8>>::Read(In, Value.Bytes);
+	}
+};
+
+/** Decodes a whole buffer as a `T`, e.g., a row of a table, returning false if it isn't one. */
+template<typename T>
+bool FromBsatn(const TArray<uint8>& Data, T& Value)
+{
+	FBsatnReader In(Data);
+	return TBsatn<T>::Read(In, Value) && In.IsAtEnd();
+}
+
+inline void WriteVarint(TArray<uint8>& Out, uint32 Value)
+{
+	while (Value >= 0x80)
+	{
+		Out.Add(uint8(Value) | 0x80);
+		Value >>= 7;
+	}
+	Out.Add(uint8(Value));
+}
+
+/**
+ * Encodes the protobuf `Message` of the binary WebSocket protocol
+ * which calls the reducer named `Reducer` with the BSATN-encoded `Args`.
+ */
+inline TArray<uint8> EncodeFunctionCall(const TCHAR* Reducer, const TArray<uint8>& Args)
+{
+	FTCHARToUTF8 Name(Reducer);
+
+	// FunctionCall { reducer = 1; argBytes = 2; }
+	TArray<uint8> Call;
+	Call.Add(0x0A);
+	WriteVarint(Call, Name.Length());
+	Call.Append(reinterpret_cast<const uint8*>(Name.Get()), Name.Length());
+	Call.Add(0x12);
+	WriteVarint(Call, Args.Num());
+	Call.Append(Args);
+
+	// Message { functionCall = 1; }
+	TArray<uint8> Message;
+	Message.Add(0x0A);
+	WriteVarint(Message, Call.Num());
+	Message.Append(Call);
+	return Message;
+}
+}
+"#;
+
+/// Generates `SpacetimeDBBsatn.h`, which the other generated files include.
+pub fn autogen_unreal_globals(_ctx: &GenCtx, _items: &[GenItem]) -> Vec<Vec<(String, String)>> {
+    let mut output = CodeIndenter::new(String::new());
+    let out = &mut output;
+
+    writeln!(
+        out,
+        "// THIS FILE IS AUTOMATICALLY GENERATED BY SPACETIMEDB. EDITS TO THIS FILE"
+    )
+    .unwrap();
+    writeln!(out, "// WILL NOT BE SAVED. MODIFY TABLES IN RUST INSTEAD.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#pragma once").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#include \"CoreMinimal.h\"").unwrap();
+    writeln!(out, "#include \"{BSATN_HEADER}.generated.h\"").unwrap();
+    writeln!(out).unwrap();
+    out.write_str(BSATN_HEADER_BODY).unwrap();
+
+    vec![vec![(format!("{BSATN_HEADER}.h"), output.into_inner())]]
+}
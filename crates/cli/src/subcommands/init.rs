@@ -78,6 +78,22 @@ fn check_for_dotnet() -> bool {
     false
 }
 
+fn check_for_npm() -> bool {
+    let npm = if std::env::consts::OS == "windows" {
+        "npm.cmd"
+    } else {
+        "npm"
+    };
+    if find_executable(npm).is_some() {
+        return true;
+    }
+    println!(
+        "{}",
+        "Warning: You have created an AssemblyScript project, but you are missing npm. Check out https://nodejs.org/en/download for installation instructions.\n".yellow()
+    );
+    false
+}
+
 fn check_for_git() -> bool {
     match std::env::consts::OS {
         "linux" | "freebsd" | "netbsd" | "openbsd" | "solaris" => {
@@ -138,6 +154,7 @@ pub async fn exec(_: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
     match project_lang {
         ModuleLanguage::Rust => exec_init_rust(args).await,
         ModuleLanguage::Csharp => exec_init_csharp(args).await,
+        ModuleLanguage::AssemblyScript => exec_init_assemblyscript(args).await,
     }
 }
 
@@ -199,6 +216,36 @@ pub async fn exec_init_csharp(args: &ArgMatches) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn exec_init_assemblyscript(args: &ArgMatches) -> anyhow::Result<()> {
+    let project_path = args.get_one::<PathBuf>("project-path").unwrap();
+
+    let export_files = vec![
+        (include_str!("project/assemblyscript/package._json"), "package.json"),
+        (include_str!("project/assemblyscript/asconfig._json"), "asconfig.json"),
+        (include_str!("project/assemblyscript/index._ts"), "assembly/index.ts"),
+        (include_str!("project/assemblyscript/_gitignore"), ".gitignore"),
+    ];
+
+    // Check all dependencies
+    check_for_npm();
+    check_for_git();
+
+    for data_file in export_files {
+        let path = project_path.join(data_file.1);
+
+        create_directory(path.parent().unwrap())?;
+
+        std::fs::write(path, data_file.0)?;
+    }
+
+    println!(
+        "{}",
+        format!("Project successfully created at path: {}", project_path.display()).green()
+    );
+
+    Ok(())
+}
+
 fn create_directory(path: &Path) -> Result<(), anyhow::Error> {
     std::fs::create_dir_all(path).context("Failed to create directory")
 }
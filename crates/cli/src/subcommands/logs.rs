@@ -37,6 +37,24 @@ pub fn cli() -> clap::Command {
                 .help("A flag indicating whether or not to follow the logs")
                 .long_help("A flag that causes logs to not stop when end of the log file is reached, but rather to wait for additional data to be appended to the input."),
         )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .value_parser(["error", "warn", "info", "debug", "trace", "panic"])
+                .help("Only print lines of this level or less verbose"),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .help("Only print lines logged from this module path or one nested in it"),
+        )
+        .arg(
+            Arg::new("field")
+                .long("field")
+                .action(ArgAction::Append)
+                .value_name("KEY=VALUE")
+                .help("Only print lines logged with this field value; can be given more than once"),
+        )
         .after_help("Run `spacetime help logs` for more detailed information.\n")
 }
 
@@ -62,6 +80,8 @@ struct Record<'a> {
     #[serde(borrow)]
     message: Cow<'a, str>,
     trace: Option<Vec<BacktraceFrame<'a>>>,
+    #[serde(default)]
+    fields: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(serde::Deserialize)]
@@ -73,15 +93,23 @@ pub struct BacktraceFrame<'a> {
 }
 
 #[derive(serde::Serialize)]
-struct LogsParams {
+struct LogsParams<'a> {
     num_lines: Option<u32>,
     follow: bool,
+    level: Option<&'a str>,
+    target: Option<&'a str>,
+    fields: Option<String>,
 }
 
 pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::Error> {
     let num_lines = args.get_one::<u32>("num_lines").copied();
     let database = args.get_one::<String>("database").unwrap();
     let follow = args.get_flag("follow");
+    let level = args.get_one::<String>("level").map(|s| s.as_str());
+    let target = args.get_one::<String>("target").map(|s| s.as_str());
+    let fields = args
+        .get_many::<String>("field")
+        .map(|fields| fields.map(|s| s.as_str()).collect::<Vec<_>>().join(","));
 
     let cloned_config = config.clone();
     let identity = cloned_config.resolve_name_to_identity(args.get_one::<String>("identity").map(|x| x.as_str()))?;
@@ -92,7 +120,13 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
     let address = database_address(&config, database).await?;
 
     // TODO: num_lines should default to like 10 if follow is specified?
-    let query_parms = LogsParams { num_lines, follow };
+    let query_parms = LogsParams {
+        num_lines,
+        follow,
+        level,
+        target,
+        fields,
+    };
 
     let builder = reqwest::Client::new().get(format!("{}/database/logs/{}", config.get_host_url(), address));
     let builder = add_auth_header_opt(builder, &auth_header);
@@ -153,7 +187,17 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
             }
             out.reset()?;
         }
-        writeln!(out, ": {}", record.message)?;
+        write!(out, ": {}", record.message)?;
+        for (key, value) in &record.fields {
+            out.set_color(&dimmed)?;
+            write!(out, " {key}=")?;
+            out.reset()?;
+            match value {
+                serde_json::Value::String(s) => write!(out, "{s}")?,
+                value => write!(out, "{value}")?,
+            }
+        }
+        writeln!(out)?;
         if let Some(trace) = &record.trace {
             for frame in trace {
                 write!(out, "    in ")?;
@@ -1,16 +1,17 @@
 use anyhow::bail;
+use clap::parser::ValueSource;
 use clap::Arg;
 use clap::ArgAction::SetTrue;
 use clap::ArgMatches;
 use reqwest::Url;
 use spacetimedb_lib::name::PublishOp;
-use spacetimedb_lib::name::{is_address, parse_domain_name, PublishResult};
+use spacetimedb_lib::name::{is_address, parse_domain_name, PublishResult, TableChange, UpdatePlan};
 use std::fs;
 use std::path::PathBuf;
 
 use crate::config::Config;
-use crate::util::init_default;
 use crate::util::{add_auth_header_opt, get_auth_header};
+use crate::util::{init_default, ModuleLanguage};
 
 pub fn cli() -> clap::Command {
     clap::Command::new("publish")
@@ -19,7 +20,7 @@ pub fn cli() -> clap::Command {
             Arg::new("host_type")
                 .long("host-type")
                 .short('t')
-                .value_parser(["wasmer"])
+                .value_parser(["wasmer", "wasmtime", "assemblyscript"])
                 .default_value("wasmer")
                 .help("The type of host that should be for hosting this module"),
         )
@@ -31,6 +32,13 @@ pub fn cli() -> clap::Command {
                 .action(SetTrue)
                 .help("When publishing a new module to an existing address, also delete all tables associated with the database"),
         )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .action(SetTrue)
+                .conflicts_with("clear_database")
+                .help("Check the module against the existing database and show what publishing it would change, without publishing it"),
+        )
         .arg(
             Arg::new("path_to_project")
                 .value_parser(clap::value_parser!(PathBuf))
@@ -95,15 +103,23 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
     let identity = cloned_config.resolve_name_to_identity(args.get_one::<String>("identity").map(|s| s.as_str()))?;
     let name_or_address = args.get_one::<String>("name|address");
     let path_to_project = args.get_one::<PathBuf>("path_to_project").unwrap();
-    let host_type = args.get_one::<String>("host_type").unwrap();
+    let mut host_type = args.get_one::<String>("host_type").unwrap().as_str();
     let clear_database = args.get_flag("clear_database");
+    let dry_run = args.get_flag("dry_run");
     let trace_log = args.get_flag("trace_log");
     let anon_identity = args.get_flag("anon_identity");
     let skip_clippy = args.get_flag("skip_clippy");
     let build_debug = args.get_flag("debug");
 
+    // AssemblyScript modules only run on their own host type, so it's the default for them.
+    if args.value_source("host_type") == Some(ValueSource::DefaultValue)
+        && crate::util::detect_module_language(path_to_project) == ModuleLanguage::AssemblyScript
+    {
+        host_type = "assemblyscript";
+    }
+
     let mut query_params = Vec::<(&str, &str)>::new();
-    query_params.push(("host_type", host_type.as_str()));
+    query_params.push(("host_type", host_type));
     query_params.push(("register_tld", "true"));
 
     // If a domain or address was provided, we should locally make sure it looks correct and
@@ -130,6 +146,13 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
         query_params.push(("trace_log", "true"));
     }
 
+    if dry_run {
+        if name_or_address.is_none() {
+            bail!("A dry run needs the name or address of the database to check the module against");
+        }
+        query_params.push(("dry_run", "true"));
+    }
+
     let path_to_wasm = crate::tasks::build(path_to_project, skip_clippy, build_debug)?;
     let program_bytes = fs::read(path_to_wasm)?;
 
@@ -177,6 +200,17 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
                 println!("{} database with address: {}", op, address);
             }
         }
+        PublishResult::DryRun { domain, address, plan } => {
+            match domain {
+                Some(domain) => println!("Checked against database with domain: {}, address: {}", domain, address),
+                None => println!("Checked against database with address: {}", address),
+            }
+            print_update_plan(&plan);
+            if !plan.is_compatible() {
+                bail!("Publishing this module would be rejected, as it changes or removes existing tables");
+            }
+            println!("Nothing was published.");
+        }
         PublishResult::TldNotRegistered { domain } => {
             return Err(anyhow::anyhow!(
                 "The top level domain that you provided is not registered.\n\
@@ -221,3 +255,36 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
 
     Ok(())
 }
+
+fn print_update_plan(plan: &UpdatePlan) {
+    if plan.created_tables.is_empty()
+        && plan.created_indexes.is_empty()
+        && plan.added_columns.is_empty()
+        && plan.changed_tables.is_empty()
+        && plan.orphaned_tables.is_empty()
+    {
+        println!("No tables would change.");
+    }
+    for table in &plan.created_tables {
+        println!("  + {}", table);
+    }
+    for index in &plan.created_indexes {
+        println!("  + index {}", index);
+    }
+    for column in &plan.added_columns {
+        println!("  + column {}", column);
+    }
+    for TableChange {
+        table_name,
+        differences,
+    } in &plan.changed_tables
+    {
+        println!("  ~ {}: {}", table_name, differences.join(", "));
+    }
+    for table in &plan.orphaned_tables {
+        println!("  - {}", table);
+    }
+    if plan.calls_update_reducer {
+        println!("The __update__ reducer would be called.");
+    }
+}
@@ -1,8 +1,7 @@
-use crate::api::{from_json_seed, ClientApi, Connection, StmtResultJson};
+use crate::api::{ClientApi, Connection, StmtResultJson};
 use clap::{Arg, ArgAction, ArgGroup, ArgMatches};
 use reqwest::RequestBuilder;
-use spacetimedb_lib::de::serde::SeedWrapper;
-use spacetimedb_lib::sats::{satn, Typespace};
+use spacetimedb_lib::sats::{json, satn, Typespace};
 use tabled::builder::Builder;
 use tabled::Style;
 
@@ -98,10 +97,14 @@ pub(crate) async fn run_sql(builder: RequestBuilder, sql: &str) -> Result<(), an
         let typespace = Typespace::default();
         let ty = typespace.with_type(schema);
         for row in rows {
-            let row = from_json_seed(row.get(), SeedWrapper(ty))?;
+            let row = schema
+                .elements
+                .iter()
+                .zip(row)
+                .map(|(e, v)| json::from_value(ty.with(&e.algebraic_type), v))
+                .collect::<Result<Vec<_>, _>>()?;
             builder.add_record(
-                row.elements
-                    .iter()
+                row.iter()
                     .zip(&schema.elements)
                     .map(|(v, e)| satn::PsqlWrapper(ty.with(&e.algebraic_type).with_value(v))),
             );
@@ -0,0 +1,38 @@
+use duct::cmd;
+use std::path::{Path, PathBuf};
+
+pub(crate) fn build_assemblyscript(project_path: &Path, build_debug: bool) -> anyhow::Result<PathBuf> {
+    // The targets are those of the `asconfig.json` created by `spacetime init --lang assemblyscript`.
+    let target = if build_debug { "debug" } else { "release" };
+    let output_path = project_path.join("build").join(format!("{target}.wasm"));
+
+    // delete existing wasm file if exists
+    if output_path.exists() {
+        std::fs::remove_file(&output_path)?;
+    }
+
+    let npm = if cfg!(windows) { "npm.cmd" } else { "npm" };
+    let npx = if cfg!(windows) { "npx.cmd" } else { "npx" };
+
+    if !project_path.join("node_modules").exists() {
+        run(cmd!(npm, "install").dir(project_path))?;
+    }
+    run(cmd!(npx, "asc", "assembly/index.ts", "--target", target).dir(project_path))?;
+
+    // check if file exists
+    if !output_path.exists() {
+        anyhow::bail!("Failed to build project");
+    }
+
+    Ok(output_path)
+}
+
+fn run(expr: duct::Expression) -> anyhow::Result<()> {
+    match expr.run() {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("Failed to build project. npm not found in path. Please install Node.js.")
+        }
+        Err(error) => anyhow::bail!("Failed to build project. {}", error),
+    }
+}
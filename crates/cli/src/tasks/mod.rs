@@ -4,6 +4,7 @@ use crate::util::{self, ModuleLanguage};
 
 use crate::tasks::rust::build_rust;
 
+use self::assemblyscript::build_assemblyscript;
 use self::csharp::build_csharp;
 
 pub(crate) fn build(project_path: &Path, skip_clippy: bool, build_debug: bool) -> anyhow::Result<PathBuf> {
@@ -11,8 +12,10 @@ pub(crate) fn build(project_path: &Path, skip_clippy: bool, build_debug: bool) -
     match lang {
         ModuleLanguage::Rust => build_rust(project_path, skip_clippy, build_debug),
         ModuleLanguage::Csharp => build_csharp(project_path, build_debug),
+        ModuleLanguage::AssemblyScript => build_assemblyscript(project_path, build_debug),
     }
 }
 
+pub mod assemblyscript;
 pub mod csharp;
 pub mod rust;
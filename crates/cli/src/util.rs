@@ -235,15 +235,22 @@ pub const VALID_PROTOCOLS: [&str; 2] = ["http", "https"];
 pub enum ModuleLanguage {
     Csharp,
     Rust,
+    AssemblyScript,
 }
 impl clap::ValueEnum for ModuleLanguage {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Csharp, Self::Rust]
+        &[Self::Csharp, Self::Rust, Self::AssemblyScript]
     }
     fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
         match self {
             Self::Csharp => Some(clap::builder::PossibleValue::new("csharp").aliases(["c#", "cs", "C#", "CSharp"])),
             Self::Rust => Some(clap::builder::PossibleValue::new("rust").aliases(["rs", "Rust"])),
+            Self::AssemblyScript => Some(clap::builder::PossibleValue::new("assemblyscript").aliases([
+                "as",
+                "AssemblyScript",
+                "typescript",
+                "ts",
+            ])),
         }
     }
 }
@@ -253,6 +260,8 @@ pub fn detect_module_language(path_to_project: &Path) -> ModuleLanguage {
     // check for Cargo.toml
     if path_to_project.join("Cargo.toml").exists() {
         ModuleLanguage::Rust
+    } else if path_to_project.join("asconfig.json").exists() {
+        ModuleLanguage::AssemblyScript
     } else {
         ModuleLanguage::Csharp
     }
@@ -42,3 +42,60 @@ fn test_typescript_codegen_output() {
         insta::assert_toml_snapshot!(outfiles);
     });
 }
+
+#[test]
+fn test_unreal_codegen_output() {
+    let path = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../target/wasm32-unknown-unknown/release/rust_wasm_test.wasm"
+    ));
+    if !path.exists() {
+        eprintln!("rust_wasm_test isn't built, skipping");
+        return;
+    }
+    use spacetimedb_cli::generate;
+    println!("{}", path.to_str().unwrap());
+    let outfiles: HashMap<_, _> = generate::generate(path, generate::Language::Unreal, "SpacetimeDB")
+        .unwrap()
+        .into_iter()
+        .collect();
+    let file = |name: &str| {
+        outfiles
+            .get(name)
+            .unwrap_or_else(|| panic!("{name} wasn't generated"))
+            .as_str()
+    };
+
+    // The header every other one includes is generated along with them.
+    assert!(file("SpacetimeDBBsatn.h").contains("struct FBsatnReader"));
+
+    let test_a = file("TestA.h");
+    assert!(test_a.contains("#include \"SpacetimeDBBsatn.h\""));
+    assert!(test_a.contains("#include \"TestA.generated.h\""));
+    assert!(test_a.contains("struct FTestA"));
+    assert!(test_a.contains("uint32 X = 0;"));
+    assert!(test_a.contains("FString Z;"));
+    assert!(test_a.contains("struct TBsatn<FTestA>"));
+    assert!(test_a.contains("if (!TBsatn<FString>::Read(In, Value.Z)) return false;"));
+
+    // A sum type of units is a `UENUM`, which the types using it include.
+    let test_c = file("NamespaceTestC.h");
+    assert!(test_c.contains("enum class ENamespaceTestC : uint8"));
+    assert!(test_c.contains("Foo,"));
+    assert!(test_c.contains("Bar,"));
+    let test_d = file("TestD.h");
+    assert!(test_d.contains("#include \"NamespaceTestC.h\""));
+    assert!(test_d.contains("TOptional<ENamespaceTestC> TestC;"));
+
+    let add_player = file("AddPlayerReducer.h");
+    assert!(add_player.contains("struct FAddPlayerReducer"));
+    assert!(add_player.contains("ReducerName = TEXT(\"add_player\");"));
+    assert!(add_player.contains("TBsatn<FString>::Write(Out, Value.Name);"));
+    let test = file("TestReducer.h");
+    assert!(test.contains("#include \"TestA.h\""));
+    assert!(test.contains("FTestA Arg;"));
+    assert!(test.contains("ENamespaceTestC Arg3 = ENamespaceTestC::Foo;"));
+
+    // The init reducer isn't for clients to call.
+    assert!(!outfiles.contains_key("InitReducer.h"));
+}
@@ -1,8 +1,9 @@
 use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 use axum::extract::rejection::{TypedHeaderRejection, TypedHeaderRejectionReason};
-use axum::extract::Query;
+use axum::extract::{ConnectInfo, Query};
 use axum::headers::authorization::Credentials;
 use axum::headers::{self, authorization};
 use axum::response::IntoResponse;
@@ -10,12 +11,19 @@ use axum::TypedHeader;
 use bytes::BytesMut;
 use http::{request, HeaderValue, StatusCode};
 use serde::Deserialize;
+use spacetimedb::address::Address;
+use spacetimedb::auth::guest::GUEST_CONFIG;
 use spacetimedb::auth::identity::{
-    decode_token, encode_token, DecodingKey, EncodingKey, JwtError, JwtErrorKind, SpacetimeIdentityClaims,
+    decode_token, encode_guest_token, encode_token, DecodingKey, EncodingKey, JwtError, JwtErrorKind,
+    SpacetimeIdentityClaims, SqlAccess, TokenScope,
 };
+use spacetimedb::auth::oidc::OidcError;
+use spacetimedb::client::check_guest_rate_limit;
 use spacetimedb::host::EnergyDiff;
 use spacetimedb::identity::Identity;
 
+use crate::routes::database::rate_limited_response;
+use crate::util::XForwardedFor;
 use crate::{log_and_500, ControlNodeDelegate};
 
 // Yes, this is using basic auth. See the below issues.
@@ -47,6 +55,10 @@ impl authorization::Credentials for SpacetimeCreds {
 }
 
 impl SpacetimeCreds {
+    pub fn from_token(token: &str) -> Self {
+        let headers::Authorization(basic) = headers::Authorization::basic(TOKEN_USERNAME, token);
+        Self(basic)
+    }
     pub fn token(&self) -> &str {
         self.0.password()
     }
@@ -55,18 +67,24 @@ impl SpacetimeCreds {
     }
     pub fn encode_token(private_key: &EncodingKey, identity: Identity) -> Result<Self, JwtError> {
         let token = encode_token(private_key, identity)?;
-        let headers::Authorization(basic) = headers::Authorization::basic(TOKEN_USERNAME, &token);
-        Ok(Self(basic))
+        Ok(Self::from_token(&token))
     }
 }
 
 pub struct SpacetimeAuth {
     pub creds: SpacetimeCreds,
     pub identity: Identity,
+    /// The capabilities the token is restricted to, if it's a scoped token.
+    pub scope: Option<TokenScope>,
+    /// Whether the identity is a guest, which expires along with its token unless promoted.
+    pub guest: bool,
 }
 
 pub struct SpacetimeAuthHeader {
     pub auth: Option<SpacetimeAuth>,
+    /// The address of the client, which the guests minted for it are counted against,
+    /// as forwarded by the peer of the connection if it's a trusted proxy, and the peer's otherwise.
+    client_ip: Option<IpAddr>,
 }
 
 #[derive(Deserialize)]
@@ -74,27 +92,55 @@ pub struct TokenQueryParam {
     token: String,
 }
 
+#[derive(Deserialize)]
+pub struct OidcTokenQueryParam {
+    oidc_token: String,
+}
+
 #[async_trait::async_trait]
 impl<S: ControlNodeDelegate + Send + Sync> axum::extract::FromRequestParts<S> for SpacetimeAuthHeader {
     type Rejection = AuthorizationRejection;
     async fn from_request_parts(parts: &mut request::Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let forwarded = axum::TypedHeader::<XForwardedFor>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|axum::TypedHeader(XForwardedFor(ip))| ip);
+        let client_ip = GUEST_CONFIG.client_ip(peer, forwarded);
+
+        // A token of a third-party OpenID Connect provider comes as a bearer token,
+        // or in the `oidc_token` query parameter for websockets from browsers, which can't set headers.
+        let oidc_token =
+            match axum::TypedHeader::<headers::Authorization<authorization::Bearer>>::from_request_parts(parts, state)
+                .await
+            {
+                Ok(axum::TypedHeader(headers::Authorization(bearer))) => Some(bearer.token().to_owned()),
+                Err(_) => Query::<OidcTokenQueryParam>::from_request_parts(parts, state)
+                    .await
+                    .ok()
+                    .map(|Query(query)| query.oidc_token),
+            };
+        if let Some(token) = oidc_token {
+            let auth = SpacetimeAuth::verify_oidc(&token, state).await?;
+            return Ok(Self {
+                auth: Some(auth),
+                client_ip,
+            });
+        }
+
         match (
             axum::TypedHeader::from_request_parts(parts, state).await,
             Query::<TokenQueryParam>::from_request_parts(parts, state).await,
         ) {
             (Ok(axum::TypedHeader(headers::Authorization(creds @ SpacetimeCreds { .. }))), _) => {
-                let claims = creds
-                    .decode_token(state.public_key())
-                    .map_err(|e| AuthorizationRejection {
-                        reason: AuthorizationRejectionReason::Jwt(e.into_kind()),
-                    })?;
-                let auth = SpacetimeAuth {
-                    creds,
-                    identity: Identity::from_hex(claims.hex_identity).map_err(|_| AuthorizationRejection {
-                        reason: AuthorizationRejectionReason::CantDecodeAuthorizationToken,
-                    })?,
-                };
-                Ok(Self { auth: Some(auth) })
+                let auth = SpacetimeAuth::verify(creds, state).await?;
+                Ok(Self {
+                    auth: Some(auth),
+                    client_ip,
+                })
             }
             (_, Ok(Query(query))) => {
                 let header =
@@ -104,22 +150,15 @@ impl<S: ControlNodeDelegate + Send + Sync> axum::extract::FromRequestParts<S> fo
                 let creds = SpacetimeCreds(authorization::Basic::decode(&header).ok_or(AuthorizationRejection {
                     reason: AuthorizationRejectionReason::CantDecodeAuthorizationToken,
                 })?);
-                let claims = creds
-                    .decode_token(state.public_key())
-                    .map_err(|e| AuthorizationRejection {
-                        reason: AuthorizationRejectionReason::Jwt(e.into_kind()),
-                    })?;
-                let auth = SpacetimeAuth {
-                    creds,
-                    identity: Identity::from_hex(claims.hex_identity).map_err(|_| AuthorizationRejection {
-                        reason: AuthorizationRejectionReason::CantDecodeAuthorizationToken,
-                    })?,
-                };
-                Ok(Self { auth: Some(auth) })
+                let auth = SpacetimeAuth::verify(creds, state).await?;
+                Ok(Self {
+                    auth: Some(auth),
+                    client_ip,
+                })
             }
             (Err(e), Err(_)) => match e.reason() {
                 // Leave it to handlers to decide on unauthorized requests.
-                TypedHeaderRejectionReason::Missing => Ok(Self { auth: None }),
+                TypedHeaderRejectionReason::Missing => Ok(Self { auth: None, client_ip }),
                 _ => Err(AuthorizationRejection {
                     reason: AuthorizationRejectionReason::Header(e),
                 }),
@@ -145,11 +184,24 @@ impl IntoResponse for AuthorizationRejection {
         const INVALID: (StatusCode, &str) = (StatusCode::BAD_REQUEST, "Authorization is invalid: malformed token");
         // Sensible fallback if no auth header is present.
         const REQUIRED: (StatusCode, &str) = (StatusCode::UNAUTHORIZED, "Authorization required");
+        // The scoped token was revoked by its identity.
+        const REVOKED: (StatusCode, &str) = (StatusCode::UNAUTHORIZED, "Authorization failed: token was revoked");
+        // The OpenID Connect token wasn't issued by any of the providers this instance trusts.
+        const UNTRUSTED: (StatusCode, &str) = (
+            StatusCode::UNAUTHORIZED,
+            "Authorization failed: token not issued by a trusted provider",
+        );
+        // The OpenID Connect token was issued by a trusted provider, but is expired, or for another client.
+        const OIDC_INVALID: (StatusCode, &str) = (StatusCode::UNAUTHORIZED, "Authorization failed: invalid OIDC token");
 
         log::trace!("Authorization rejection: {:?}", self.reason);
 
         match self.reason {
             AuthorizationRejectionReason::Jwt(JwtErrorKind::InvalidSignature) => ROTATED.into_response(),
+            AuthorizationRejectionReason::RevokedToken => REVOKED.into_response(),
+            AuthorizationRejectionReason::TokenLookup => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            AuthorizationRejectionReason::Oidc(OidcError::UnknownIssuer) => UNTRUSTED.into_response(),
+            AuthorizationRejectionReason::Oidc(OidcError::Jwt(_)) => OIDC_INVALID.into_response(),
             AuthorizationRejectionReason::Header(rejection) => match rejection.reason() {
                 TypedHeaderRejectionReason::Missing => REQUIRED.into_response(),
                 _ => rejection.into_response(),
@@ -165,17 +217,138 @@ enum AuthorizationRejectionReason {
     Header(TypedHeaderRejection),
     MalformedTokenQueryString,
     CantDecodeAuthorizationToken,
+    RevokedToken,
+    TokenLookup,
+    Oidc(OidcError),
 }
 
 impl SpacetimeAuth {
     pub async fn alloc(ctx: &(impl ControlNodeDelegate + ?Sized)) -> axum::response::Result<Self> {
         let identity = ctx.alloc_spacetime_identity().await.map_err(log_and_500)?;
         let creds = SpacetimeCreds::encode_token(ctx.private_key(), identity).map_err(log_and_500)?;
-        Ok(Self { creds, identity })
+        Ok(Self {
+            creds,
+            identity,
+            scope: None,
+            guest: false,
+        })
+    }
+
+    /// Mints a guest identity for a client at `client_ip`, if known,
+    /// refusing it with `429 Too Many Requests` once too many have been minted for that address.
+    ///
+    /// The guest's token expires after the configured TTL, see [`GUEST_CONFIG`].
+    pub async fn alloc_guest(
+        ctx: &(impl ControlNodeDelegate + ?Sized),
+        client_ip: Option<IpAddr>,
+    ) -> axum::response::Result<Self> {
+        check_guest_rate_limit(client_ip).map_err(rate_limited_response)?;
+        let identity = ctx.alloc_spacetime_identity().await.map_err(log_and_500)?;
+        let token = encode_guest_token(ctx.private_key(), identity, GUEST_CONFIG.token_ttl).map_err(log_and_500)?;
+        Ok(Self {
+            creds: SpacetimeCreds::from_token(&token),
+            identity,
+            scope: None,
+            guest: true,
+        })
+    }
+
+    /// Decodes the token in `creds`, checking that it hasn't been revoked if it's a scoped token.
+    pub(crate) async fn verify(
+        creds: SpacetimeCreds,
+        ctx: &(impl ControlNodeDelegate + ?Sized),
+    ) -> Result<Self, AuthorizationRejection> {
+        let reject = |reason| AuthorizationRejection { reason };
+        let claims = creds
+            .decode_token(ctx.public_key())
+            .map_err(|e| reject(AuthorizationRejectionReason::Jwt(e.into_kind())))?;
+        if let Some(token_id) = claims.token_id {
+            let token = ctx.get_api_token(token_id).await.map_err(|e| {
+                log::error!("internal error: {e:#}");
+                reject(AuthorizationRejectionReason::TokenLookup)
+            })?;
+            match token {
+                Some(token) if !token.revoked && token.identity == claims.hex_identity => {}
+                _ => return Err(reject(AuthorizationRejectionReason::RevokedToken)),
+            }
+        }
+        let identity = Identity::from_hex(claims.hex_identity)
+            .map_err(|_| reject(AuthorizationRejectionReason::CantDecodeAuthorizationToken))?;
+        Ok(Self {
+            creds,
+            identity,
+            scope: claims.scope,
+            guest: claims.guest,
+        })
+    }
+
+    /// Verifies `token` as an ID token of one of the trusted OpenID Connect providers,
+    /// authenticating its user as the identity they were given when first seen.
+    ///
+    /// The user is handed a token of this instance for that identity, which they may use from then on.
+    pub(crate) async fn verify_oidc(
+        token: &str,
+        ctx: &(impl ControlNodeDelegate + ?Sized),
+    ) -> Result<Self, AuthorizationRejection> {
+        let reject = |reason| AuthorizationRejection { reason };
+        let internal = |e: &dyn std::fmt::Display| {
+            log::error!("internal error: {e:#}");
+            reject(AuthorizationRejectionReason::TokenLookup)
+        };
+        let claims = ctx
+            .oidc_issuers()
+            .verify(token)
+            .map_err(|e| reject(AuthorizationRejectionReason::Oidc(e)))?;
+        let identity = ctx
+            .get_or_create_oidc_identity(&claims.iss, &claims.sub)
+            .await
+            .map_err(|e| internal(&e))?;
+        let creds = SpacetimeCreds::encode_token(ctx.private_key(), identity).map_err(|e| internal(&e))?;
+        Ok(Self {
+            creds,
+            identity,
+            scope: None,
+            guest: false,
+        })
+    }
+
+    /// Rejects a scoped token, or that of a guest, for actions that need the full power of the identity,
+    /// like publishing a database or issuing new tokens.
+    pub fn require_unscoped(&self) -> axum::response::Result<()> {
+        if self.guest {
+            return Err((StatusCode::FORBIDDEN, "This action can't be done by a guest identity").into());
+        }
+        match self.scope {
+            None => Ok(()),
+            Some(_) => Err((StatusCode::FORBIDDEN, "This action can't be done with a scoped token").into()),
+        }
+    }
+
+    pub fn require_database(&self, address: &Address) -> axum::response::Result<()> {
+        match &self.scope {
+            Some(scope) if !scope.allows_database(address) => {
+                Err((StatusCode::FORBIDDEN, "Token is not allowed to access this database").into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn require_reducer(&self, reducer: &str) -> axum::response::Result<()> {
+        match &self.scope {
+            Some(scope) if !scope.allows_reducer(reducer) => {
+                Err((StatusCode::FORBIDDEN, "Token is not allowed to call this reducer").into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The SQL queries the token may run.
+    pub fn sql_access(&self) -> SqlAccess {
+        self.scope.as_ref().map_or(SqlAccess::ReadWrite, |scope| scope.sql)
     }
 
     pub fn into_headers(self) -> (TypedHeader<SpacetimeIdentity>, TypedHeader<SpacetimeIdentityToken>) {
-        let Self { creds, identity } = self;
+        let Self { creds, identity, .. } = self;
         (
             TypedHeader(SpacetimeIdentity(identity)),
             TypedHeader(SpacetimeIdentityToken(creds)),
@@ -189,16 +362,23 @@ impl SpacetimeAuthHeader {
     }
 
     /// Given an authorization header we will try to get the identity and token from the auth header (as JWT).
-    /// If there is no JWT in the auth header we will create a new identity and token and return it.
+    /// If there is no JWT in the auth header we will create a new identity and token and return it,
+    /// which is a guest identity if guests are enabled.
     pub async fn get_or_create(
         self,
         ctx: &(impl ControlNodeDelegate + ?Sized),
     ) -> axum::response::Result<SpacetimeAuth> {
-        match self.get() {
+        match self.auth {
             Some(auth) => Ok(auth),
+            None if GUEST_CONFIG.enabled => SpacetimeAuth::alloc_guest(ctx, self.client_ip).await,
             None => SpacetimeAuth::alloc(ctx).await,
         }
     }
+
+    /// The address of the client, as forwarded by a trusted proxy or else of the peer of the connection, if known.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
 }
 
 pub struct SpacetimeIdentity(pub Identity);
@@ -233,6 +413,23 @@ impl headers::Header for SpacetimeIdentityToken {
     }
 }
 
+/// The id of the trace of the work done on behalf of a request, to find its spans by.
+pub struct SpacetimeTraceId(pub String);
+impl headers::Header for SpacetimeTraceId {
+    fn name() -> &'static http::HeaderName {
+        static NAME: http::HeaderName = http::HeaderName::from_static("spacetime-trace-id");
+        &NAME
+    }
+
+    fn decode<'i, I: Iterator<Item = &'i HeaderValue>>(_values: &mut I) -> Result<Self, headers::Error> {
+        unimplemented!()
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        values.extend([self.0.as_str().try_into().unwrap()])
+    }
+}
+
 pub struct SpacetimeEnergyUsed(pub EnergyDiff);
 impl headers::Header for SpacetimeEnergyUsed {
     fn name() -> &'static http::HeaderName {
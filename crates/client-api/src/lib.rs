@@ -2,10 +2,12 @@ use async_trait::async_trait;
 use axum::extract::FromRef;
 use http::StatusCode;
 use spacetimedb::address::Address;
-use spacetimedb::auth::identity::{DecodingKey, EncodingKey};
+use spacetimedb::auth::identity::{ApiToken, DecodingKey, EncodingKey};
+use spacetimedb::auth::oidc::OidcIssuers;
 use spacetimedb::client::ClientActorIndex;
 use spacetimedb::control_db::ControlDb;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
+use spacetimedb::db::datastore::locking_tx_datastore::Quota;
 use spacetimedb::hash::Hash;
 use spacetimedb::host::UpdateDatabaseResult;
 use spacetimedb::host::{EnergyQuanta, HostController};
@@ -15,8 +17,9 @@ use spacetimedb::messages::worker_db::DatabaseInstanceState;
 use spacetimedb::module_host_context::ModuleHostContext;
 use spacetimedb::object_db::ObjectDb;
 use spacetimedb::sendgrid_controller::SendGridController;
-use spacetimedb_lib::name::DomainName;
+use spacetimedb_lib::name::{DomainName, UpdatePlan};
 mod auth;
+pub mod pg_wire;
 pub mod routes;
 pub mod util;
 use std::sync::Arc;
@@ -54,6 +57,9 @@ pub trait ControlStateDelegate: Send + Sync {
     async fn get_database_instances(&self) -> spacetimedb::control_db::Result<Vec<DatabaseInstance>>;
 
     async fn get_leader_database_instance_by_database(&self, database_id: u64) -> Option<DatabaseInstance>;
+
+    /// Returns the identity that `identity` has been linked to, if any.
+    async fn get_linked_identity(&self, identity: &Identity) -> spacetimedb::control_db::Result<Option<Identity>>;
 }
 
 #[async_trait]
@@ -77,8 +83,28 @@ pub trait ControlCtx: ControlNodeDelegate + Send + Sync {
         num_replicas: u32,
     ) -> Result<Option<UpdateDatabaseResult>, anyhow::Error>;
 
+    /// Load the program at `program_bytes_address` and return what updating the database to it would do,
+    /// without doing it, or `None` if there's no such database.
+    async fn plan_database_update(
+        &self,
+        address: &Address,
+        program_bytes_address: &Hash,
+    ) -> Result<Option<UpdatePlan>, anyhow::Error>;
+
     async fn delete_database(&self, address: &Address) -> Result<(), anyhow::Error>;
 
+    /// Unload the instances of a database from memory, closing their module hosts and their databases,
+    /// until they're resumed, or used again by a connection or request.
+    ///
+    /// Their data and logs are kept on disk.
+    async fn pause_database(&self, address: &Address) -> Result<(), anyhow::Error>;
+
+    /// Load the instances of a paused database back into memory and start their module hosts.
+    async fn resume_database(&self, address: &Address) -> Result<(), anyhow::Error>;
+
+    /// Replace the quota of a database, which applies to its instances right away.
+    async fn set_quota(&self, address: &Address, quota: &Quota) -> Result<(), anyhow::Error>;
+
     fn object_db(&self) -> &ObjectDb;
     fn control_db(&self) -> &ControlDb;
     fn sendgrid_controller(&self) -> Option<&SendGridController>;
@@ -92,8 +118,20 @@ pub trait ControlNodeDelegate: Send + Sync {
 
     async fn withdraw_energy(&self, identity: &Identity, amount: EnergyQuanta) -> spacetimedb::control_db::Result<()>;
 
+    async fn get_api_token(&self, id: u64) -> spacetimedb::control_db::Result<Option<ApiToken>>;
+
+    /// Returns the identity of the user `subject` of the OpenID Connect provider `issuer`,
+    /// allocating a new one the first time they're seen.
+    async fn get_or_create_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> spacetimedb::control_db::Result<Identity>;
+
     fn public_key(&self) -> &DecodingKey;
     fn private_key(&self) -> &EncodingKey;
+    /// The OpenID Connect providers whose tokens clients may authenticate with.
+    fn oidc_issuers(&self) -> &OidcIssuers;
 }
 
 pub struct ArcEnv<T: ?Sized>(pub Arc<T>);
@@ -129,12 +167,27 @@ impl<T: ControlNodeDelegate + ?Sized> ControlNodeDelegate for ArcEnv<T> {
         self.0.withdraw_energy(identity, amount).await
     }
 
+    async fn get_api_token(&self, id: u64) -> spacetimedb::control_db::Result<Option<ApiToken>> {
+        self.0.get_api_token(id).await
+    }
+
+    async fn get_or_create_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> spacetimedb::control_db::Result<Identity> {
+        self.0.get_or_create_oidc_identity(issuer, subject).await
+    }
+
     fn public_key(&self) -> &DecodingKey {
         self.0.public_key()
     }
     fn private_key(&self) -> &EncodingKey {
         self.0.private_key()
     }
+    fn oidc_issuers(&self) -> &OidcIssuers {
+        self.0.oidc_issuers()
+    }
 }
 
 #[async_trait]
@@ -151,12 +204,27 @@ impl<T: ControlNodeDelegate + ?Sized> ControlNodeDelegate for Arc<T> {
         (**self).withdraw_energy(identity, amount).await
     }
 
+    async fn get_api_token(&self, id: u64) -> spacetimedb::control_db::Result<Option<ApiToken>> {
+        (**self).get_api_token(id).await
+    }
+
+    async fn get_or_create_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> spacetimedb::control_db::Result<Identity> {
+        (**self).get_or_create_oidc_identity(issuer, subject).await
+    }
+
     fn public_key(&self) -> &DecodingKey {
         (**self).public_key()
     }
     fn private_key(&self) -> &EncodingKey {
         (**self).private_key()
     }
+    fn oidc_issuers(&self) -> &OidcIssuers {
+        (**self).oidc_issuers()
+    }
 }
 
 pub fn log_and_500(e: impl std::fmt::Display) -> StatusCode {
@@ -0,0 +1,1037 @@
+//! A frontend speaking enough of the [Postgres wire protocol] for `psql`, BI tools and ORMs
+//! to run read-only SQL queries against a database.
+//!
+//! Clients connect with the name or address of the database as the database name,
+//! and a SpacetimeDB token as the password. The user name is ignored.
+//!
+//! Both the simple and the extended query protocols are supported,
+//! but without query parameters, and with results only in the text format.
+//! There's no TLS, so this should only be exposed on trusted networks.
+//!
+//! [Postgres wire protocol]: https://www.postgresql.org/docs/current/protocol.html
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Context;
+use bytes::{BufMut, BytesMut};
+use serde_json::Value;
+use spacetimedb::auth::identity::SqlAccess;
+use spacetimedb::error::DBError;
+use spacetimedb::sql::execute::{describe_read_only, execute_read_only};
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::relation::MemTable;
+use spacetimedb_lib::sats::{self, AlgebraicType, AlgebraicValue, BuiltinType, BuiltinValue, WithTypespace};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::auth::{SpacetimeAuth, SpacetimeCreds};
+use crate::util::NameOrAddress;
+use crate::WorkerCtx;
+
+/// Protocol version 3.0, the only one there is.
+const PROTOCOL_VERSION: i32 = 196608;
+const SSL_REQUEST_CODE: i32 = 80877103;
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+/// The longest message accepted from a client.
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// The SQLSTATE codes of the errors sent to clients.
+mod sqlstate {
+    pub const PROTOCOL_VIOLATION: &str = "08P01";
+    pub const FEATURE_NOT_SUPPORTED: &str = "0A000";
+    pub const INVALID_PASSWORD: &str = "28P01";
+    pub const INVALID_SQL_STATEMENT_NAME: &str = "26000";
+    pub const INVALID_CURSOR_NAME: &str = "34000";
+    pub const INVALID_CATALOG_NAME: &str = "3D000";
+    pub const SYNTAX_ERROR_OR_ACCESS_RULE_VIOLATION: &str = "42000";
+    pub const SYNTAX_ERROR: &str = "42601";
+    pub const INSUFFICIENT_PRIVILEGE: &str = "42501";
+    pub const CANNOT_CONNECT_NOW: &str = "57P03";
+    pub const INTERNAL_ERROR: &str = "XX000";
+}
+
+/// The OIDs of the Postgres types that columns are sent as.
+mod oid {
+    pub const BOOL: i32 = 16;
+    pub const BYTEA: i32 = 17;
+    pub const INT8: i32 = 20;
+    pub const INT2: i32 = 21;
+    pub const INT4: i32 = 23;
+    pub const TEXT: i32 = 25;
+    pub const JSON: i32 = 114;
+    pub const FLOAT4: i32 = 700;
+    pub const FLOAT8: i32 = 701;
+    pub const NUMERIC: i32 = 1700;
+}
+
+/// Accepts Postgres connections on `listener`, serving each on its own task.
+pub async fn serve(ctx: Arc<dyn WorkerCtx>, listener: TcpListener) -> std::io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = Connection::new(stream).run(&*ctx).await {
+                log::debug!("Postgres connection from {peer} failed: {e:#}");
+            }
+        });
+    }
+}
+
+/// An error reported to the client in an `ErrorResponse`.
+#[derive(Debug)]
+struct PgError {
+    /// The SQLSTATE code of the error.
+    code: &'static str,
+    message: String,
+}
+
+impl PgError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn malformed() -> Self {
+        Self::new(sqlstate::PROTOCOL_VIOLATION, "Malformed message")
+    }
+
+    fn internal(e: impl fmt::Display) -> Self {
+        log::error!("internal error: {e:#}");
+        Self::new(sqlstate::INTERNAL_ERROR, "Internal error")
+    }
+}
+
+impl fmt::Display for PgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for PgError {}
+
+impl From<DBError> for PgError {
+    fn from(err: DBError) -> Self {
+        match err.get_auth_error() {
+            Some(auth_err) => Self::new(sqlstate::INSUFFICIENT_PRIVILEGE, auth_err.to_string()),
+            None => Self::new(sqlstate::SYNTAX_ERROR_OR_ACCESS_RULE_VIOLATION, err.to_string()),
+        }
+    }
+}
+
+/// Reads the fields of a message from a client.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], PgError> {
+        if self.0.len() < n {
+            return Err(PgError::malformed());
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, PgError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, PgError> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, PgError> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a null-terminated string.
+    fn cstr(&mut self) -> Result<&'a str, PgError> {
+        let end = self.0.iter().position(|&b| b == 0).ok_or_else(PgError::malformed)?;
+        let s = std::str::from_utf8(&self.0[..end]).map_err(|_| PgError::malformed())?;
+        self.0 = &self.0[end + 1..];
+        Ok(s)
+    }
+}
+
+fn put_cstr(buf: &mut BytesMut, s: &str) {
+    buf.put_slice(s.as_bytes());
+    buf.put_u8(0);
+}
+
+/// A client that has connected to a database.
+struct Session {
+    instance_id: u64,
+    auth: AuthCtx,
+    /// The SQL of the statements prepared by `Parse` messages, by name.
+    statements: HashMap<String, String>,
+    /// The results of the statements bound by `Bind` messages, by portal name.
+    portals: HashMap<String, Vec<MemTable>>,
+}
+
+impl Session {
+    /// Authenticates the client with `token`,
+    /// and connects it to the database named in the startup `params`.
+    async fn open(ctx: &dyn WorkerCtx, params: &HashMap<String, String>, token: &str) -> Result<Self, PgError> {
+        let auth = SpacetimeAuth::verify(SpacetimeCreds::from_token(token), ctx)
+            .await
+            .map_err(|_| PgError::new(sqlstate::INVALID_PASSWORD, "Authorization failed: invalid token"))?;
+        if auth.sql_access() == SqlAccess::None {
+            return Err(PgError::new(
+                sqlstate::INSUFFICIENT_PRIVILEGE,
+                "Token is not allowed to run SQL queries",
+            ));
+        }
+
+        let name = params
+            .get("database")
+            .ok_or_else(|| PgError::new(sqlstate::INVALID_CATALOG_NAME, "No database given"))?;
+        let no_such_database = || PgError::new(sqlstate::INVALID_CATALOG_NAME, format!("No such database: {name}"));
+        let address = match NameOrAddress::from(name.clone()).try_resolve(ctx).await {
+            Ok(Ok(resolved)) => *resolved.address(),
+            _ => return Err(no_such_database()),
+        };
+        auth.require_database(&address).map_err(|_| {
+            PgError::new(
+                sqlstate::INSUFFICIENT_PRIVILEGE,
+                "Token is not allowed to access this database",
+            )
+        })?;
+        let database = ctx
+            .get_database_by_address(&address)
+            .await
+            .map_err(PgError::internal)?
+            .ok_or_else(no_such_database)?;
+        let instance_id = ctx
+            .get_leader_database_instance_by_database(database.id)
+            .await
+            .ok_or_else(|| {
+                PgError::new(
+                    sqlstate::CANNOT_CONNECT_NOW,
+                    "Database instance not scheduled to this node yet.",
+                )
+            })?
+            .id;
+
+        let auth = AuthCtx::new(database.identity, auth.identity);
+        let host = ctx.host_controller();
+        if host.get_module_host(instance_id).is_err() {
+            let dbic = ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(PgError::internal)?;
+            host.spawn_module_host(dbic).await.map_err(PgError::internal)?;
+        }
+
+        Ok(Self {
+            instance_id,
+            auth,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+        })
+    }
+
+    fn portal(&self, name: &str) -> Result<&[MemTable], PgError> {
+        self.portals
+            .get(name)
+            .map(|results| &results[..])
+            .ok_or_else(|| PgError::new(sqlstate::INVALID_CURSOR_NAME, format!("Unknown portal {name:?}")))
+    }
+
+    fn run(&self, ctx: &dyn WorkerCtx, sql: &str) -> Result<Vec<MemTable>, PgError> {
+        Ok(execute_read_only(
+            ctx.database_instance_context_controller(),
+            self.instance_id,
+            sql.to_owned(),
+            self.auth,
+        )?)
+    }
+
+    /// Returns the results of `sql` with their schema but without rows, without running it.
+    fn describe(&self, ctx: &dyn WorkerCtx, sql: &str) -> Result<Vec<MemTable>, PgError> {
+        Ok(describe_read_only(
+            ctx.database_instance_context_controller(),
+            self.instance_id,
+            sql.to_owned(),
+            self.auth,
+        )?)
+    }
+}
+
+/// Whether `sql` has no statements, which Postgres answers with an `EmptyQueryResponse`.
+fn is_empty_query(sql: &str) -> bool {
+    sql.split(';').all(|s| s.trim().is_empty())
+}
+
+/// The OID and size of the Postgres type that a column of type `ty` is sent as.
+///
+/// Options are sent as their value, or `NULL` for `none`.
+/// Integers that don't fit in an `int8` are `numeric`,
+/// and types with no counterpart in Postgres are `json`.
+fn pg_type(ty: &AlgebraicType) -> (i32, i16) {
+    let ty = option_of(ty).unwrap_or(ty);
+    if ty.is_bytes() {
+        return (oid::BYTEA, -1);
+    }
+    match ty {
+        AlgebraicType::Builtin(ty) => match ty {
+            BuiltinType::Bool => (oid::BOOL, 1),
+            BuiltinType::I8 | BuiltinType::U8 | BuiltinType::I16 => (oid::INT2, 2),
+            BuiltinType::U16 | BuiltinType::I32 => (oid::INT4, 4),
+            BuiltinType::U32 | BuiltinType::I64 => (oid::INT8, 8),
+            BuiltinType::U64 | BuiltinType::I128 | BuiltinType::U128 => (oid::NUMERIC, -1),
+            BuiltinType::F32 => (oid::FLOAT4, 4),
+            BuiltinType::F64 => (oid::FLOAT8, 8),
+            BuiltinType::String => (oid::TEXT, -1),
+            BuiltinType::Array(_) | BuiltinType::Map(_) => (oid::JSON, -1),
+        },
+        _ => (oid::JSON, -1),
+    }
+}
+
+/// The type of the value of `ty`, if it's an option.
+fn option_of(ty: &AlgebraicType) -> Option<&AlgebraicType> {
+    match ty {
+        AlgebraicType::Sum(sum) => sum.as_option(),
+        _ => None,
+    }
+}
+
+/// The text format of `value`, of type `ty`, as the Postgres type given by [`pg_type`],
+/// or `None` for `NULL`.
+fn text_value(ty: &AlgebraicType, value: &AlgebraicValue) -> Option<String> {
+    if let Some(some_ty) = option_of(ty) {
+        let sum = value.as_sum()?;
+        return if sum.tag == 0 {
+            text_value(some_ty, &sum.value)
+        } else {
+            None
+        };
+    }
+    Some(match (pg_type(ty).0, value.as_builtin()) {
+        (oid::BOOL, Some(BuiltinValue::Bool(b))) => String::from(if *b { "t" } else { "f" }),
+        (oid::FLOAT4, Some(BuiltinValue::F32(v))) => float_text(f32::from(*v)),
+        (oid::FLOAT8, Some(BuiltinValue::F64(v))) => float_text(f64::from(*v)),
+        (oid::JSON, _) => sats::json::to_value(WithTypespace::empty(ty), value).to_string(),
+        // Byte arrays are hex strings in canonical JSON.
+        (oid::BYTEA, _) => match sats::json::to_value(WithTypespace::empty(ty), value) {
+            Value::String(hex) => format!("\\x{hex}"),
+            value => value.to_string(),
+        },
+        _ => match sats::json::to_value(WithTypespace::empty(ty), value) {
+            Value::String(s) => s,
+            value => value.to_string(),
+        },
+    })
+}
+
+/// Formats a float as Postgres does, which spells out the values that aren't finite.
+fn float_text<F: fmt::Display + Into<f64> + Copy>(v: F) -> String {
+    let f: f64 = v.into();
+    if f.is_nan() {
+        "NaN".to_owned()
+    } else if f == f64::INFINITY {
+        "Infinity".to_owned()
+    } else if f == f64::NEG_INFINITY {
+        "-Infinity".to_owned()
+    } else {
+        v.to_string()
+    }
+}
+
+/// Reads the names of the portal and the statement of a `Bind` message,
+/// rejecting it if it has parameters or asks for results in the binary format.
+fn bind_names(body: &[u8]) -> Result<(&str, &str), PgError> {
+    let mut fields = Fields(body);
+    let portal = fields.cstr()?;
+    let statement = fields.cstr()?;
+    let param_formats = fields.i16()?;
+    fields.take(2 * param_formats.max(0) as usize)?;
+    if fields.i16()? != 0 {
+        return Err(PgError::new(
+            sqlstate::FEATURE_NOT_SUPPORTED,
+            "Query parameters aren't supported",
+        ));
+    }
+    for _ in 0..fields.i16()? {
+        if fields.i16()? != 0 {
+            return Err(PgError::new(
+                sqlstate::FEATURE_NOT_SUPPORTED,
+                "Only results in the text format are supported",
+            ));
+        }
+    }
+    Ok((portal, statement))
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    /// The messages to be sent on the next flush.
+    out: BytesMut,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            out: BytesMut::new(),
+        }
+    }
+
+    async fn run(mut self, ctx: &dyn WorkerCtx) -> anyhow::Result<()> {
+        let Some(mut session) = self.startup(ctx).await? else {
+            return Ok(());
+        };
+
+        self.serve_messages(&mut session, |conn, session, tag, body| match tag {
+            b'Q' => conn.simple_query(ctx, session, body),
+            b'P' => conn.parse(session, body),
+            b'B' => conn.bind(ctx, session, body),
+            b'D' => conn.describe(ctx, session, body),
+            b'E' => conn.execute(session, body),
+            b'C' => conn.close(session, body),
+            _ => Err(PgError::new(
+                sqlstate::PROTOCOL_VIOLATION,
+                format!("Unsupported message type {:?}", tag as char),
+            )),
+        })
+        .await
+    }
+
+    /// Answers the messages of an authenticated client with `handle` until it disconnects,
+    /// taking care of `Sync`, `Flush` and `Terminate`, and of the errors `handle` returns.
+    async fn serve_messages(
+        &mut self,
+        session: &mut Session,
+        mut handle: impl FnMut(&mut Self, &mut Session, u8, &[u8]) -> Result<(), PgError>,
+    ) -> anyhow::Result<()> {
+        // After an error in the extended query protocol, messages are skipped until a `Sync`.
+        let mut skipping = false;
+        while let Some((tag, body)) = self.read_message().await? {
+            let res = match tag {
+                b'X' => return Ok(()),
+                b'S' => {
+                    skipping = false;
+                    session.portals.clear();
+                    self.ready();
+                    self.flush().await?;
+                    continue;
+                }
+                b'H' => {
+                    self.flush().await?;
+                    continue;
+                }
+                b'Q' => handle(self, session, tag, &body),
+                _ if skipping => continue,
+                _ => handle(self, session, tag, &body),
+            };
+            if let Err(err) = res {
+                self.send_error(&err);
+                skipping = tag != b'Q';
+            }
+            if tag == b'Q' {
+                self.ready();
+                self.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Negotiates the connection, returning the session if the client authenticated.
+    async fn startup(&mut self, ctx: &dyn WorkerCtx) -> anyhow::Result<Option<Session>> {
+        let params = loop {
+            let body = self.read_body().await?;
+            let mut fields = Fields(&body);
+            match fields.i32()? {
+                SSL_REQUEST_CODE | GSSENC_REQUEST_CODE => {
+                    // Neither is supported, and the client can go on without them.
+                    self.stream.get_mut().write_all(b"N").await?;
+                }
+                // Queries can't be cancelled.
+                CANCEL_REQUEST_CODE => return Ok(None),
+                PROTOCOL_VERSION => {
+                    let mut params = HashMap::new();
+                    loop {
+                        let key = fields.cstr()?;
+                        if key.is_empty() {
+                            break;
+                        }
+                        params.insert(key.to_owned(), fields.cstr()?.to_owned());
+                    }
+                    break params;
+                }
+                version => {
+                    let err = PgError::new(
+                        sqlstate::FEATURE_NOT_SUPPORTED,
+                        format!("Unsupported protocol version {}.{}", version >> 16, version & 0xffff),
+                    );
+                    self.send_error(&err);
+                    self.flush().await?;
+                    return Ok(None);
+                }
+            }
+        };
+
+        // Ask for the token as a cleartext password.
+        self.send(b'R', |buf| buf.put_i32(3));
+        self.flush().await?;
+        let token = match self.read_message().await?.context("Connection closed during startup")? {
+            (b'p', body) => Fields(&body).cstr()?.to_owned(),
+            (tag, _) => anyhow::bail!("Expected a password message, got {:?}", tag as char),
+        };
+
+        match Session::open(ctx, &params, &token).await {
+            Ok(session) => {
+                self.send(b'R', |buf| buf.put_i32(0));
+                for (key, value) in [
+                    ("server_version", "14.0"),
+                    ("server_encoding", "UTF8"),
+                    ("client_encoding", "UTF8"),
+                    ("DateStyle", "ISO, MDY"),
+                    ("integer_datetimes", "on"),
+                    ("standard_conforming_strings", "on"),
+                ] {
+                    self.send(b'S', |buf| {
+                        put_cstr(buf, key);
+                        put_cstr(buf, value);
+                    });
+                }
+                self.ready();
+                self.flush().await?;
+                Ok(Some(session))
+            }
+            Err(err) => {
+                self.send_error(&err);
+                self.flush().await?;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn read_message(&mut self) -> anyhow::Result<Option<(u8, Vec<u8>)>> {
+        let tag = match self.stream.read_u8().await {
+            Ok(tag) => tag,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some((tag, self.read_body().await?)))
+    }
+
+    /// Reads the length-prefixed body of a message.
+    async fn read_body(&mut self) -> anyhow::Result<Vec<u8>> {
+        let len = self.stream.read_i32().await?;
+        let len = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_sub(4))
+            .filter(|&len| len <= MAX_MESSAGE_LEN)
+            .with_context(|| format!("Invalid message length {len}"))?;
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await?;
+        Ok(body)
+    }
+
+    /// Queues a message with the body written by `write`.
+    fn send(&mut self, tag: u8, write: impl FnOnce(&mut BytesMut)) {
+        self.out.put_u8(tag);
+        let len_at = self.out.len();
+        self.out.put_i32(0);
+        write(&mut self.out);
+        let len = (self.out.len() - len_at) as i32;
+        self.out[len_at..len_at + 4].copy_from_slice(&len.to_be_bytes());
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.get_mut().write_all(&self.out).await?;
+        self.out.clear();
+        Ok(())
+    }
+
+    fn ready(&mut self) {
+        // Always idle, as there are no transactions across queries.
+        self.send(b'Z', |buf| buf.put_u8(b'I'));
+    }
+
+    fn send_error(&mut self, err: &PgError) {
+        self.send(b'E', |buf| {
+            for (field, value) in [
+                (b'S', "ERROR"),
+                (b'V', "ERROR"),
+                (b'C', err.code),
+                (b'M', err.message.as_str()),
+            ] {
+                buf.put_u8(field);
+                put_cstr(buf, value);
+            }
+            buf.put_u8(0);
+        });
+    }
+
+    /// Describes the rows of `result`, or that there are none for an empty query.
+    fn send_description(&mut self, result: Option<&MemTable>) {
+        match result {
+            Some(result) => self.send_row_description(result),
+            None => self.send(b'n', |_| {}),
+        }
+    }
+
+    fn send_row_description(&mut self, result: &MemTable) {
+        let schema = result.head.ty();
+        self.send(b'T', |buf| {
+            buf.put_i16(schema.elements.len() as i16);
+            for (i, column) in schema.elements.iter().enumerate() {
+                let (oid, len) = pg_type(&column.algebraic_type);
+                match &column.name {
+                    Some(name) => put_cstr(buf, name),
+                    None => put_cstr(buf, &i.to_string()),
+                }
+                // No table OID or column number.
+                buf.put_i32(0);
+                buf.put_i16(0);
+                buf.put_i32(oid);
+                buf.put_i16(len);
+                // No type modifier, and the text format.
+                buf.put_i32(-1);
+                buf.put_i16(0);
+            }
+        });
+    }
+
+    fn send_rows(&mut self, result: &MemTable) {
+        let schema = result.head.ty();
+        for row in &result.data {
+            self.send(b'D', |buf| {
+                buf.put_i16(row.elements.len() as i16);
+                for (column, value) in schema.elements.iter().zip(&row.elements) {
+                    match text_value(&column.algebraic_type, value) {
+                        Some(text) => {
+                            buf.put_i32(text.len() as i32);
+                            buf.put_slice(text.as_bytes());
+                        }
+                        None => buf.put_i32(-1),
+                    }
+                }
+            });
+        }
+        self.send(b'C', |buf| put_cstr(buf, &format!("SELECT {}", result.data.len())));
+    }
+
+    fn simple_query(&mut self, ctx: &dyn WorkerCtx, session: &Session, body: &[u8]) -> Result<(), PgError> {
+        let sql = Fields(body).cstr()?;
+        if is_empty_query(sql) {
+            self.send(b'I', |_| {});
+            return Ok(());
+        }
+        for result in session.run(ctx, sql)? {
+            self.send_row_description(&result);
+            self.send_rows(&result);
+        }
+        Ok(())
+    }
+
+    fn parse(&mut self, session: &mut Session, body: &[u8]) -> Result<(), PgError> {
+        let mut fields = Fields(body);
+        let name = fields.cstr()?;
+        let sql = fields.cstr()?;
+        // The types of the parameters are ignored, as parameters are rejected when bound.
+        session.statements.insert(name.to_owned(), sql.to_owned());
+        self.send(b'1', |_| {});
+        Ok(())
+    }
+
+    /// Binds a prepared statement to a portal, running it right away,
+    /// as the results of a read-only query are the same when it's executed.
+    fn bind(&mut self, ctx: &dyn WorkerCtx, session: &mut Session, body: &[u8]) -> Result<(), PgError> {
+        let (portal, statement) = bind_names(body)?;
+        let sql = session.statements.get(statement).ok_or_else(|| {
+            PgError::new(
+                sqlstate::INVALID_SQL_STATEMENT_NAME,
+                format!("Unknown prepared statement {statement:?}"),
+            )
+        })?;
+        let results = if is_empty_query(sql) {
+            vec![]
+        } else {
+            session.run(ctx, sql)?
+        };
+        if results.len() > 1 {
+            return Err(PgError::new(
+                sqlstate::SYNTAX_ERROR,
+                "Cannot insert multiple commands into a prepared statement",
+            ));
+        }
+        session.portals.insert(portal.to_owned(), results);
+        self.send(b'2', |_| {});
+        Ok(())
+    }
+
+    fn describe(&mut self, ctx: &dyn WorkerCtx, session: &Session, body: &[u8]) -> Result<(), PgError> {
+        let mut fields = Fields(body);
+        let kind = fields.u8()?;
+        let name = fields.cstr()?;
+        match kind {
+            b'S' => {
+                let sql = session.statements.get(name).ok_or_else(|| {
+                    PgError::new(
+                        sqlstate::INVALID_SQL_STATEMENT_NAME,
+                        format!("Unknown prepared statement {name:?}"),
+                    )
+                })?;
+                // The statement is compiled for the schema of its results, but not run.
+                let results = if is_empty_query(sql) {
+                    vec![]
+                } else {
+                    session.describe(ctx, sql)?
+                };
+                // There are never any parameters.
+                self.send(b't', |buf| buf.put_i16(0));
+                self.send_description(results.first());
+            }
+            b'P' => self.send_description(session.portal(name)?.first()),
+            _ => return Err(PgError::malformed()),
+        }
+        Ok(())
+    }
+
+    /// Sends the rows of a portal.
+    /// Its results are always sent in full, as if no row limit were given.
+    fn execute(&mut self, session: &Session, body: &[u8]) -> Result<(), PgError> {
+        let name = Fields(body).cstr()?;
+        match session.portal(name)?.first() {
+            Some(result) => self.send_rows(result),
+            None => self.send(b'I', |_| {}),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, session: &mut Session, body: &[u8]) -> Result<(), PgError> {
+        let mut fields = Fields(body);
+        let kind = fields.u8()?;
+        let name = fields.cstr()?;
+        match kind {
+            b'S' => {
+                session.statements.remove(name);
+            }
+            b'P' => {
+                session.portals.remove(name);
+            }
+            _ => return Err(PgError::malformed()),
+        }
+        self.send(b'3', |_| {});
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_lib::auth::StAccess;
+    use spacetimedb_lib::relation::Header;
+    use spacetimedb_lib::sats::{product, ProductType};
+
+    /// Returns a connection on the server end of a local socket, and the client end.
+    async fn connect() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (Connection::new(server), client)
+    }
+
+    fn message(tag: u8, write: impl FnOnce(&mut BytesMut)) -> Vec<u8> {
+        let mut body = BytesMut::new();
+        write(&mut body);
+        let mut buf = vec![tag];
+        buf.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    async fn recv(client: &mut TcpStream) -> (u8, Vec<u8>) {
+        let tag = client.read_u8().await.unwrap();
+        let len = client.read_i32().await.unwrap();
+        let mut body = vec![0; len as usize - 4];
+        client.read_exact(&mut body).await.unwrap();
+        (tag, body)
+    }
+
+    /// The SQLSTATE code of an `ErrorResponse`.
+    fn error_code(body: &[u8]) -> String {
+        let mut fields = Fields(body);
+        loop {
+            match fields.u8().unwrap() {
+                0 => panic!("No code in the error"),
+                b'C' => return fields.cstr().unwrap().to_owned(),
+                _ => fields.cstr().unwrap(),
+            };
+        }
+    }
+
+    fn session() -> Session {
+        Session {
+            instance_id: 0,
+            auth: AuthCtx::for_testing(),
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut fields = Fields(b"\x07\x00\x02\x00\x00\x00\x03ab\x00\x00rest");
+        assert_eq!(fields.u8().unwrap(), 7);
+        assert_eq!(fields.i16().unwrap(), 2);
+        assert_eq!(fields.i32().unwrap(), 3);
+        assert_eq!(fields.cstr().unwrap(), "ab");
+        assert_eq!(fields.cstr().unwrap(), "");
+        assert_eq!(fields.take(4).unwrap(), b"rest");
+
+        let malformed = |err: PgError| assert_eq!(err.code, sqlstate::PROTOCOL_VIOLATION);
+        malformed(Fields(b"").u8().unwrap_err());
+        malformed(Fields(b"\x00").i16().unwrap_err());
+        malformed(Fields(b"\x00\x00\x00").i32().unwrap_err());
+        malformed(Fields(b"unterminated").cstr().unwrap_err());
+        malformed(Fields(b"\xff\x00").cstr().unwrap_err());
+    }
+
+    #[test]
+    fn test_bind_names() {
+        let bind = |params: i16, result_format: i16| {
+            let mut body = BytesMut::new();
+            put_cstr(&mut body, "portal");
+            put_cstr(&mut body, "statement");
+            body.put_i16(1);
+            body.put_i16(0);
+            body.put_i16(params);
+            for _ in 0..params {
+                body.put_i32(-1);
+            }
+            body.put_i16(1);
+            body.put_i16(result_format);
+            body.to_vec()
+        };
+        assert_eq!(bind_names(&bind(0, 0)).unwrap(), ("portal", "statement"));
+        assert_eq!(
+            bind_names(&bind(1, 0)).unwrap_err().code,
+            sqlstate::FEATURE_NOT_SUPPORTED
+        );
+        assert_eq!(
+            bind_names(&bind(0, 1)).unwrap_err().code,
+            sqlstate::FEATURE_NOT_SUPPORTED
+        );
+        assert_eq!(bind_names(b"portal\0").unwrap_err().code, sqlstate::PROTOCOL_VIOLATION);
+    }
+
+    #[test]
+    fn test_is_empty_query() {
+        assert!(is_empty_query(""));
+        assert!(is_empty_query(" ; ;\n"));
+        assert!(!is_empty_query("SELECT * FROM t;"));
+    }
+
+    #[test]
+    fn test_pg_type() {
+        assert_eq!(pg_type(&AlgebraicType::Bool), (oid::BOOL, 1));
+        assert_eq!(pg_type(&AlgebraicType::U8), (oid::INT2, 2));
+        assert_eq!(pg_type(&AlgebraicType::U16), (oid::INT4, 4));
+        assert_eq!(pg_type(&AlgebraicType::U32), (oid::INT8, 8));
+        assert_eq!(pg_type(&AlgebraicType::U64), (oid::NUMERIC, -1));
+        assert_eq!(pg_type(&AlgebraicType::F64), (oid::FLOAT8, 8));
+        assert_eq!(pg_type(&AlgebraicType::String), (oid::TEXT, -1));
+        assert_eq!(pg_type(&AlgebraicType::bytes()), (oid::BYTEA, -1));
+        assert_eq!(pg_type(&AlgebraicType::array(AlgebraicType::U32)), (oid::JSON, -1));
+        assert_eq!(pg_type(&AlgebraicType::option(AlgebraicType::I32)), (oid::INT4, 4));
+    }
+
+    #[test]
+    fn test_text_value() {
+        let text = |ty: AlgebraicType, value: AlgebraicValue| text_value(&ty, &value);
+        assert_eq!(text(AlgebraicType::Bool, AlgebraicValue::Bool(true)).unwrap(), "t");
+        assert_eq!(text(AlgebraicType::Bool, AlgebraicValue::Bool(false)).unwrap(), "f");
+        assert_eq!(text(AlgebraicType::I8, AlgebraicValue::I8(-3)).unwrap(), "-3");
+        assert_eq!(
+            text(AlgebraicType::U64, AlgebraicValue::U64(u64::MAX)).unwrap(),
+            u64::MAX.to_string()
+        );
+        assert_eq!(
+            text(AlgebraicType::F32, AlgebraicValue::F32(1.5.into())).unwrap(),
+            "1.5"
+        );
+        assert_eq!(
+            text(AlgebraicType::F64, AlgebraicValue::F64(f64::NAN.into())).unwrap(),
+            "NaN"
+        );
+        assert_eq!(
+            text(AlgebraicType::F64, AlgebraicValue::F64(f64::NEG_INFINITY.into())).unwrap(),
+            "-Infinity"
+        );
+        assert_eq!(
+            text(AlgebraicType::String, AlgebraicValue::String("it's".into())).unwrap(),
+            "it's"
+        );
+        assert_eq!(
+            text(AlgebraicType::bytes(), AlgebraicValue::Bytes(vec![0xde, 0xad])).unwrap(),
+            "\\xdead"
+        );
+        assert_eq!(
+            text(
+                AlgebraicType::array(AlgebraicType::U32),
+                AlgebraicValue::ArrayOf(vec![1u32, 2])
+            )
+            .unwrap(),
+            "[1,2]"
+        );
+
+        let option = AlgebraicType::option(AlgebraicType::I32);
+        assert_eq!(
+            text(option.clone(), AlgebraicValue::OptionSome(AlgebraicValue::I32(4))).unwrap(),
+            "4"
+        );
+        assert_eq!(text(option, AlgebraicValue::OptionNone()), None);
+    }
+
+    #[tokio::test]
+    async fn test_framing() {
+        let (mut conn, mut client) = connect().await;
+
+        conn.send(b'C', |buf| put_cstr(buf, "SELECT 1"));
+        conn.send(b'I', |_| {});
+        assert_eq!(&conn.out[..], b"C\x00\x00\x00\x0dSELECT 1\x00I\x00\x00\x00\x04");
+        conn.flush().await.unwrap();
+        assert!(conn.out.is_empty());
+        assert_eq!(recv(&mut client).await, (b'C', b"SELECT 1\0".to_vec()));
+        assert_eq!(recv(&mut client).await, (b'I', vec![]));
+
+        client
+            .write_all(&message(b'Q', |buf| put_cstr(buf, "SELECT 1")))
+            .await
+            .unwrap();
+        assert_eq!(conn.read_message().await.unwrap(), Some((b'Q', b"SELECT 1\0".to_vec())));
+
+        // A length that doesn't count itself, and one past the limit.
+        client.write_all(&3i32.to_be_bytes()).await.unwrap();
+        assert!(conn.read_body().await.is_err());
+        client
+            .write_all(&(MAX_MESSAGE_LEN as i32 + 5).to_be_bytes())
+            .await
+            .unwrap();
+        assert!(conn.read_body().await.is_err());
+
+        drop(client);
+        assert_eq!(conn.read_message().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_extended_query() {
+        let (mut conn, mut client) = connect().await;
+        let mut session = session();
+        let ty = ProductType::from_iter([("id", AlgebraicType::U32), ("name", AlgebraicType::String)]);
+        let rows = [product!(1u32, "a"), product!(2u32, "b")];
+        let table = MemTable::new(&Header::from(ty), StAccess::Public, &rows);
+
+        let bind = |params: i16| {
+            message(b'B', |buf| {
+                put_cstr(buf, "");
+                put_cstr(buf, "s");
+                buf.put_i16(0);
+                buf.put_i16(params);
+                for _ in 0..params {
+                    buf.put_i32(-1);
+                }
+                buf.put_i16(0);
+            })
+        };
+        let execute = |portal: &str| {
+            message(b'E', |buf| {
+                put_cstr(buf, portal);
+                buf.put_i32(0);
+            })
+        };
+        let sync = message(b'S', |_| {});
+        let messages = [
+            message(b'P', |buf| {
+                put_cstr(buf, "s");
+                put_cstr(buf, "SELECT * FROM t");
+                buf.put_i16(0);
+            }),
+            bind(0),
+            message(b'D', |buf| {
+                buf.put_u8(b'P');
+                put_cstr(buf, "");
+            }),
+            execute(""),
+            sync.clone(),
+            // The portal is closed by the `Sync`, and the messages after the error are skipped.
+            execute(""),
+            bind(0),
+            sync.clone(),
+            bind(1),
+            sync.clone(),
+            // A simple query is answered even while skipping.
+            execute("missing"),
+            message(b'Q', |buf| put_cstr(buf, "")),
+            sync.clone(),
+            message(b'C', |buf| {
+                buf.put_u8(b'S');
+                put_cstr(buf, "s");
+            }),
+            sync,
+            message(b'X', |_| {}),
+        ];
+        client.write_all(&messages.concat()).await.unwrap();
+
+        // Binds statements to the rows of `table` in place of running them.
+        conn.serve_messages(&mut session, |conn, session, tag, body| match tag {
+            b'P' => conn.parse(session, body),
+            b'B' => {
+                let (portal, statement) = bind_names(body)?;
+                assert!(session.statements.contains_key(statement));
+                session.portals.insert(portal.to_owned(), vec![table.clone()]);
+                conn.send(b'2', |_| {});
+                Ok(())
+            }
+            b'D' => {
+                let mut fields = Fields(body);
+                assert_eq!(fields.u8()?, b'P');
+                conn.send_description(session.portal(fields.cstr()?)?.first());
+                Ok(())
+            }
+            b'E' => conn.execute(session, body),
+            b'C' => conn.close(session, body),
+            b'Q' => {
+                conn.send(b'I', |_| {});
+                Ok(())
+            }
+            _ => panic!("Unexpected message {:?}", tag as char),
+        })
+        .await
+        .unwrap();
+        assert!(session.statements.is_empty(), "Closed");
+        drop(conn);
+
+        let mut responses = Vec::new();
+        while let Ok(tag) = client.read_u8().await {
+            let len = client.read_i32().await.unwrap();
+            let mut body = vec![0; len as usize - 4];
+            client.read_exact(&mut body).await.unwrap();
+            responses.push((tag, body));
+        }
+        let tags: Vec<u8> = responses.iter().map(|(tag, _)| *tag).collect();
+        assert_eq!(tags, b"12TDDCZEZEZEIZZ3Z");
+
+        let (_, description) = &responses[2];
+        let mut fields = Fields(description);
+        assert_eq!(fields.i16().unwrap(), 2);
+        assert_eq!(fields.cstr().unwrap(), "id");
+        fields.take(6).unwrap();
+        assert_eq!(fields.i32().unwrap(), oid::INT8);
+
+        assert_eq!(responses[3].1, b"\x00\x02\x00\x00\x00\x011\x00\x00\x00\x01a");
+        assert_eq!(responses[5].1, b"SELECT 2\0");
+        assert_eq!(error_code(&responses[7].1), sqlstate::INVALID_CURSOR_NAME);
+        assert_eq!(error_code(&responses[9].1), sqlstate::FEATURE_NOT_SUPPORTED);
+        assert_eq!(error_code(&responses[11].1), sqlstate::INVALID_CURSOR_NAME);
+    }
+}
@@ -1,15 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use axum::body::Bytes;
-use axum::extract::{DefaultBodyLimit, FromRef, Path, Query, State};
+use axum::extract::{BodyStream, DefaultBodyLimit, FromRef, Path, Query, State};
 use axum::response::{ErrorResponse, IntoResponse};
 use axum::{headers, TypedHeader};
 use futures::StreamExt;
-use http::StatusCode;
+use http::{HeaderMap, StatusCode};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use spacetimedb::host::EntityDef;
+use spacetimedb::host::ModuleHost;
 use spacetimedb::host::ReducerArgs;
 use spacetimedb::host::ReducerCallError;
 use spacetimedb::host::ReducerOutcome;
@@ -18,18 +19,27 @@ use spacetimedb_lib::name;
 use spacetimedb_lib::name::DomainName;
 use spacetimedb_lib::name::DomainParsingError;
 use spacetimedb_lib::name::PublishOp;
-use spacetimedb_lib::sats::WithTypespace;
+use spacetimedb_lib::sats::ser::serde::SerializeWrapper;
+use spacetimedb_lib::sats::{AlgebraicType, WithTypespace};
+use spacetimedb_lib::{HttpHandler, HttpMethod};
+use tracing::Instrument;
 
 use crate::auth::{
     SpacetimeAuth, SpacetimeAuthHeader, SpacetimeEnergyUsed, SpacetimeExecutionDurationMicros, SpacetimeIdentity,
-    SpacetimeIdentityToken,
+    SpacetimeIdentityToken, SpacetimeTraceId,
 };
 use spacetimedb::address::Address;
-use spacetimedb::database_logger::DatabaseLogger;
+use spacetimedb::client::{check_rate_limit, RateLimited};
+use spacetimedb::database_logger::{DatabaseLogger, LogFilter};
+use spacetimedb::db::datastore::locking_tx_datastore::Quota;
+use spacetimedb::error::DBError;
+use spacetimedb::hash::Hash;
 use spacetimedb::host::DescribedEntityType;
 use spacetimedb::identity::Identity;
-use spacetimedb::json::client_api::StmtResultJson;
-use spacetimedb::messages::control_db::{Database, DatabaseInstance, HostType};
+use spacetimedb::json::client_api::{StmtResultJson, TypedStmtResultJson};
+use spacetimedb::json::module_schema::ModuleSchemaJson;
+use spacetimedb::messages::control_db::{Database, DatabaseInstance, HostType, Webhook};
+use spacetimedb::util::new_trace_id;
 
 use super::identity::IdentityForUrl;
 use crate::util::{ByteStringBody, NameOrAddress};
@@ -53,6 +63,26 @@ pub struct CallParams {
     reducer: String,
 }
 
+/// A `429 Too Many Requests` response for a call refused by the rate limiter,
+/// with a `Retry-After` header in whole seconds and the exact delay in the body.
+pub(crate) fn rate_limited_response(RateLimited { retry_after }: RateLimited) -> ErrorResponse {
+    let retry_after_secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(http::header::RETRY_AFTER, retry_after_secs.to_string())],
+        axum::Json(json!({
+            "error": "rate_limited",
+            "retry_after_ms": retry_after.as_millis() as u64,
+        })),
+    )
+        .into()
+}
+
+/// Calls `reducer` with the arguments in the request body, given as a JSON array of them,
+/// or as a JSON object of them keyed by their names.
+///
+/// Arguments which don't match the reducer's parameter types are rejected with `400 Bad Request`,
+/// naming the offending argument and its expected type.
 pub async fn call(
     State(worker_ctx): State<Arc<dyn WorkerCtx>>,
     auth: SpacetimeAuthHeader,
@@ -62,14 +92,18 @@ pub async fn call(
     }): Path<CallParams>,
     ByteStringBody(body): ByteStringBody,
 ) -> axum::response::Result<impl IntoResponse> {
-    let SpacetimeAuth {
-        identity: caller_identity,
-        creds: caller_identity_token,
-    } = auth.get_or_create(&*worker_ctx).await?;
+    let auth = auth.get_or_create(&*worker_ctx).await?;
 
     let args = ReducerArgs::Json(body);
 
     let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
+    auth.require_reducer(&reducer)?;
+    let SpacetimeAuth {
+        identity: caller_identity,
+        creds: caller_identity_token,
+        ..
+    } = auth;
     let database = worker_ctx_find_database(&*worker_ctx, &address).await?.ok_or_else(|| {
         log::error!("Could not find database: {}", address.to_hex());
         (StatusCode::NOT_FOUND, "No such database.")
@@ -83,6 +117,7 @@ pub async fn call(
             "Database instance not scheduled to this node yet.",
         ))?;
     let instance_id = database_instance.id;
+    check_rate_limit(caller_identity, instance_id).map_err(rate_limited_response)?;
     let host = worker_ctx.host_controller();
 
     let module = match host.get_module_host(instance_id) {
@@ -95,7 +130,13 @@ pub async fn call(
             host.spawn_module_host(dbic).await.map_err(log_and_500)?
         }
     };
-    let result = match module.call_reducer(caller_identity, None, &reducer, args).await {
+    let trace_id = new_trace_id();
+    let span = tracing::info_span!("http_call", %trace_id, %reducer);
+    let result = match module
+        .call_reducer(caller_identity, None, &reducer, args)
+        .instrument(span)
+        .await
+    {
         Ok(rcr) => rcr,
         Err(e) => {
             let status_code = match e {
@@ -108,6 +149,14 @@ pub async fn call(
                     log::debug!("Attempt to call non-existent reducer {}", reducer);
                     StatusCode::NOT_FOUND
                 }
+                ReducerCallError::NotAllowed => {
+                    log::debug!("Unauthorized attempt to call reducer {}", reducer);
+                    StatusCode::FORBIDDEN
+                }
+                ReducerCallError::Cooldown { .. } => {
+                    log::debug!("Attempt to call reducer {} during its cooldown", reducer);
+                    StatusCode::TOO_MANY_REQUESTS
+                }
             };
 
             log::debug!("Error while invoking reducer {:#}", e);
@@ -115,13 +164,21 @@ pub async fn call(
         }
     };
 
-    let (status, body) = reducer_outcome_response(&identity, &reducer, result.outcome);
+    let (status, body) = match result.return_value {
+        // A reducer only returns a value when it commits, which is then the body of the response.
+        Some(value) => (
+            StatusCode::OK,
+            serde_json::to_string(SerializeWrapper::from_ref(&value)).map_err(log_and_500)?,
+        ),
+        None => reducer_outcome_response(&identity, &reducer, result.outcome),
+    };
     Ok((
         status,
         TypedHeader(SpacetimeIdentity(caller_identity)),
         TypedHeader(SpacetimeIdentityToken(caller_identity_token)),
         TypedHeader(SpacetimeEnergyUsed(result.energy_used)),
         TypedHeader(SpacetimeExecutionDurationMicros(result.execution_duration)),
+        TypedHeader(SpacetimeTraceId(trace_id)),
         body,
     ))
 }
@@ -147,6 +204,211 @@ fn reducer_outcome_response(identity: &Identity, reducer: &str, outcome: Reducer
     }
 }
 
+#[derive(Deserialize)]
+pub struct MergeIdentityParams {
+    name_or_address: NameOrAddress,
+    old_identity: IdentityForUrl,
+}
+
+/// Lets the database move what belongs to `old_identity` over to the caller,
+/// which it has been linked to, through the module's `identity_merged` reducer.
+///
+/// A module without that reducer has nothing to move, so that's not an error.
+pub async fn merge_identity(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(MergeIdentityParams {
+        name_or_address,
+        old_identity,
+    }): Path<MergeIdentityParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let auth = auth_or_unauth(auth)?;
+    let old_identity = old_identity.into();
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
+
+    let linked = worker_ctx
+        .get_linked_identity(&old_identity)
+        .await
+        .map_err(log_and_500)?;
+    if linked != Some(auth.identity) {
+        return Err((StatusCode::FORBIDDEN, "The identity isn't linked to the caller").into());
+    }
+
+    let database = worker_ctx_find_database(&*worker_ctx, &address).await?.ok_or_else(|| {
+        log::error!("Could not find database: {}", address.to_hex());
+        (StatusCode::NOT_FOUND, "No such database.")
+    })?;
+    let identity = database.identity;
+    let instance_id = worker_ctx
+        .get_leader_database_instance_by_database(database.id)
+        .await
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Database instance not scheduled to this node yet.",
+        ))?
+        .id;
+    let host = worker_ctx.host_controller();
+    let module = match host.get_module_host(instance_id) {
+        Ok(m) => m,
+        Err(_) => {
+            let dbic = worker_ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
+        }
+    };
+
+    let result = module
+        .call_identity_merged(old_identity, auth.identity)
+        .await
+        .map_err(|e| match e {
+            ReducerCallError::NoSuchModule(_) => ErrorResponse::from((StatusCode::NOT_FOUND, "No such module.")),
+            e => log_and_500(e).into(),
+        })?;
+    Ok(match result {
+        Some(result) => reducer_outcome_response(&identity, "identity_merged", result.outcome),
+        None => (StatusCode::OK, "".to_owned()),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct HttpRouteParams {
+    name_or_address: NameOrAddress,
+    path: String,
+}
+
+/// Serves a request to one of the HTTP routes the module declares.
+///
+/// A route served by a read-only reducer calls it with the parameters of the path and query as its arguments,
+/// by name, and responds with its return value as JSON.
+/// Parameters that aren't arguments of the reducer are ignored.
+///
+/// A route served by a SQL query responds with its rows, as JSON objects keyed by column name.
+/// The query is run as the database's owner, as it's the module that chose to serve its results.
+pub async fn http_route(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(HttpRouteParams { name_or_address, path }): Path<HttpRouteParams>,
+    Query(query): Query<HashMap<String, String>>,
+    method: http::Method,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<axum::response::Response> {
+    let method = match method {
+        http::Method::GET => HttpMethod::Get,
+        http::Method::POST => HttpMethod::Post,
+        _ => return Err(StatusCode::METHOD_NOT_ALLOWED.into()),
+    };
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    let database = worker_ctx_find_database(&*worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+    let owner_identity = database.identity;
+    let instance_id = worker_ctx
+        .get_leader_database_instance_by_database(database.id)
+        .await
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Database instance not scheduled to this node yet.",
+        ))?
+        .id;
+    let host = worker_ctx.host_controller();
+    let module = match host.get_module_host(instance_id) {
+        Ok(m) => m,
+        Err(_) => {
+            let dbic = worker_ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
+        }
+    };
+
+    let info = module.info();
+    let route = info
+        .http_routes
+        .find(method, &path)
+        .ok_or((StatusCode::NOT_FOUND, "No such route."))?;
+    let reducer = match route.handler {
+        HttpHandler::Reducer(reducer) => reducer.clone(),
+        HttpHandler::Sql(sql) => {
+            let auth = AuthCtx::new(owner_identity, owner_identity);
+            let results = execute_read_only(
+                worker_ctx.database_instance_context_controller(),
+                instance_id,
+                sql.clone(),
+                auth,
+            )
+            .map_err(sql_error)?;
+            let rows: Vec<serde_json::Map<String, Value>> = results
+                .into_iter()
+                .last()
+                .map(|result| {
+                    let TypedStmtResultJson { columns, rows, .. } =
+                        TypedStmtResultJson::new(&result.head.ty(), result.data);
+                    rows.into_iter()
+                        .map(|row| columns.iter().map(|col| col.name.clone()).zip(row).collect())
+                        .collect()
+                })
+                .unwrap_or_default();
+            return Ok(axum::Json(rows).into_response());
+        }
+    };
+
+    // Each parameter is given as JSON, unless it's the argument of a string, which is given as is.
+    let schema = info.reducers.get(&reducer).ok_or(StatusCode::NOT_FOUND)?;
+    let args = route
+        .params
+        .iter()
+        .copied()
+        .chain(query.iter().map(|(name, value)| (&**name, &**value)))
+        .filter_map(|(name, value)| {
+            let arg = schema.args.iter().find(|arg| arg.name() == Some(name))?;
+            let value = if arg.algebraic_type == AlgebraicType::String {
+                Value::String(value.to_owned())
+            } else {
+                serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+            };
+            Some((name.to_owned(), value))
+        })
+        // The parameters of the path come first, so they win over those of the query.
+        .fold(serde_json::Map::new(), |mut args, (name, value)| {
+            args.entry(name).or_insert(value);
+            args
+        });
+    let args = ReducerArgs::Json(Value::Object(args).to_string().into());
+
+    let auth = auth.get_or_create(&*worker_ctx).await?;
+    auth.require_database(&address)?;
+    auth.require_reducer(&reducer)?;
+    check_rate_limit(auth.identity, instance_id).map_err(rate_limited_response)?;
+    let result = module
+        .call_reducer(auth.identity, None, &reducer, args)
+        .await
+        .map_err(|e| match e {
+            ReducerCallError::Args(_) => {
+                ErrorResponse::from((StatusCode::BAD_REQUEST, format!("{:#}", anyhow::anyhow!(e))))
+            }
+            ReducerCallError::NoSuchModule(_) | ReducerCallError::NoSuchReducer => StatusCode::NOT_FOUND.into(),
+            ReducerCallError::NotAllowed => StatusCode::FORBIDDEN.into(),
+            ReducerCallError::Cooldown { .. } => StatusCode::TOO_MANY_REQUESTS.into(),
+        })?;
+    let body = match (result.return_value, result.outcome) {
+        (Some(value), _) => serde_json::to_string(SerializeWrapper::from_ref(&value)).map_err(log_and_500)?,
+        // A reducer that returns nothing has nothing to say but `null`.
+        (None, ReducerOutcome::Committed) => "null".to_owned(),
+        (None, outcome) => return Ok(reducer_outcome_response(&owner_identity, &reducer, outcome).into_response()),
+    };
+    Ok((
+        StatusCode::OK,
+        [(http::header::CONTENT_TYPE, "application/json")],
+        TypedHeader(SpacetimeIdentity(auth.identity)),
+        TypedHeader(SpacetimeIdentityToken(auth.creds)),
+        body,
+    )
+        .into_response())
+}
+
 #[derive(Debug)]
 pub enum DBCallErr {
     HandlerError(ErrorResponse),
@@ -157,7 +419,10 @@ pub enum DBCallErr {
 use chrono::Utc;
 use rand::Rng;
 use spacetimedb::auth::identity::encode_token;
-use spacetimedb::sql::execute::execute;
+use spacetimedb::auth::identity::SqlAccess;
+use spacetimedb::sql::copy::{copy_from, CopyError};
+use spacetimedb::sql::execute::{execute, execute_read_only};
+use spacetimedb::sql::export::{export, ExportFormat};
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::name::{DnsLookupResponse, InsertDomainResult, PublishResult};
 use spacetimedb_lib::recovery::{RecoveryCode, RecoveryCodeResponse};
@@ -185,6 +450,7 @@ async fn extract_db_call_info(
     address: &Address,
 ) -> Result<DatabaseInformation, ErrorResponse> {
     let auth = auth.get_or_create(ctx).await?;
+    auth.require_database(address)?;
 
     let database = worker_ctx_find_database(ctx, address).await?.ok_or_else(|| {
         log::error!("Could not find database: {}", address.to_hex());
@@ -346,6 +612,46 @@ pub async fn catalog(
     ))
 }
 
+#[derive(Deserialize)]
+pub struct ModuleSchemaParams {
+    name_or_address: NameOrAddress,
+}
+/// Renders the complete schema of the database's module as a versioned JSON document,
+/// see [`ModuleSchemaJson`].
+pub async fn module_schema(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(ModuleSchemaParams { name_or_address }): Path<ModuleSchemaParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    let database = worker_ctx_find_database(&*worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+
+    let call_info = extract_db_call_info(&*worker_ctx, auth, &address).await?;
+
+    let instance_id = call_info.database_instance.id;
+    let host = worker_ctx.host_controller();
+    let module = match host.get_module_host(instance_id) {
+        Ok(m) => m,
+        Err(_) => {
+            let dbic = worker_ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
+        }
+    };
+    let schema = ModuleSchemaJson::new(module.info());
+
+    Ok((
+        StatusCode::OK,
+        TypedHeader(SpacetimeIdentity(call_info.auth.identity)),
+        TypedHeader(SpacetimeIdentityToken(call_info.auth.creds)),
+        axum::Json(schema),
+    ))
+}
+
 #[derive(Deserialize)]
 pub struct InfoParams {
     name_or_address: NameOrAddress,
@@ -361,6 +667,8 @@ pub async fn info(
 
     let host_type = match database.host_type {
         HostType::Wasmer => "wasmer",
+        HostType::Wasmtime => "wasmtime",
+        HostType::AssemblyScript => "assemblyscript",
     };
     let response_json = json!({
         "address": database.address.to_hex(),
@@ -382,6 +690,35 @@ pub struct LogsQuery {
     num_lines: Option<u32>,
     #[serde(default)]
     follow: bool,
+    /// Only the lines of this level or less verbose.
+    level: Option<String>,
+    /// Only the lines logged from this module path or one nested in it.
+    target: Option<String>,
+    /// Only the lines with these field values, as comma-separated `key=value` pairs.
+    fields: Option<String>,
+}
+
+impl LogsQuery {
+    fn filter(&self) -> Result<LogFilter, String> {
+        let level = self.level.as_deref().map(str::parse).transpose()?;
+        let fields = self
+            .fields
+            .as_deref()
+            .into_iter()
+            .flat_map(|fields| fields.split(','))
+            .map(|field| {
+                let (key, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected a field as `key=value`, got `{field}`"))?;
+                Ok((key.to_owned(), value.to_owned()))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(LogFilter {
+            level,
+            target: self.target.clone(),
+            fields,
+        })
+    }
 }
 
 fn auth_or_unauth(auth: SpacetimeAuthHeader) -> axum::response::Result<SpacetimeAuth> {
@@ -392,9 +729,12 @@ fn auth_or_unauth(auth: SpacetimeAuthHeader) -> axum::response::Result<Spacetime
 pub async fn logs(
     State(worker_ctx): State<Arc<dyn WorkerCtx>>,
     Path(LogsParams { name_or_address }): Path<LogsParams>,
-    Query(LogsQuery { num_lines, follow }): Query<LogsQuery>,
+    Query(query): Query<LogsQuery>,
     auth: SpacetimeAuthHeader,
 ) -> axum::response::Result<impl IntoResponse> {
+    let filter = query.filter().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let LogsQuery { num_lines, follow, .. } = query;
+
     // You should not be able to read the logs from a database that you do not own
     // so, unless you are the owner, this will fail.
     // TODO: This returns `UNAUTHORIZED` on failure,
@@ -404,6 +744,7 @@ pub async fn logs(
     let auth = auth_or_unauth(auth)?;
 
     let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
     let database = worker_ctx_find_database(&*worker_ctx, &address)
         .await?
         .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
@@ -430,7 +771,7 @@ pub async fn logs(
     let instance_id = database_instance.id;
 
     let filepath = DatabaseLogger::filepath(&address, instance_id);
-    let lines = DatabaseLogger::read_latest(&filepath, num_lines).await;
+    let lines = DatabaseLogger::read_latest(&filepath, num_lines, &filter).await;
 
     let body = if follow {
         let host = worker_ctx.host_controller();
@@ -448,7 +789,9 @@ pub async fn logs(
 
         let stream = tokio_stream::wrappers::BroadcastStream::new(log_rx).filter_map(move |x| {
             std::future::ready(match x {
-                Ok(log) => Some(log),
+                Ok(log) => std::str::from_utf8(&log)
+                    .map_or(false, |line| filter.matches(line))
+                    .then_some(log),
                 Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(skipped)) => {
                     log::trace!("Skipped {} lines in log for module {}", skipped, address.to_hex());
                     None
@@ -473,6 +816,301 @@ pub async fn logs(
     ))
 }
 
+#[derive(Deserialize)]
+pub struct ChangesParams {
+    name_or_address: NameOrAddress,
+}
+
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    /// The comma-separated names of the tables to stream the changes of, or all of them if absent.
+    tables: Option<String>,
+}
+
+/// Stream the row-level changes of every transaction committed by a reducer from now on,
+/// in commit order, as a line of JSON per changed row.
+///
+/// If the client can't keep up, the stream is cut off rather than skipping changes.
+pub async fn changes(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(ChangesParams { name_or_address }): Path<ChangesParams>,
+    Query(ChangesQuery { tables }): Query<ChangesQuery>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    // Like the logs, the changes to private tables are only for the owner of the database,
+    // and not for its scoped tokens, which may not even be allowed to query its public tables.
+    let auth = auth_or_unauth(auth)?;
+
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
+    auth.require_unscoped()?;
+    let database = worker_ctx_find_database(&*worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+
+    if database.identity != auth.identity {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Identity does not own database, expected: {} got: {}",
+                database.identity.to_hex(),
+                auth.identity.to_hex()
+            ),
+        )
+            .into());
+    }
+
+    let database_instance = worker_ctx
+        .get_leader_database_instance_by_database(database.id)
+        .await
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Database instance not scheduled to this node yet.",
+        ))?;
+    let instance_id = database_instance.id;
+
+    let host = worker_ctx.host_controller();
+    let module = match host.get_module_host(instance_id) {
+        Ok(m) => m,
+        Err(_) => {
+            let dbic = worker_ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
+        }
+    };
+    let changes_rx = module.subscription().subscribe_to_changes();
+
+    let tables = tables.map(|tables| tables.split(',').map(|t| t.trim().to_owned()).collect::<Vec<_>>());
+    let stream = tokio_stream::wrappers::BroadcastStream::new(changes_rx).filter_map(move |x| {
+        std::future::ready(match x {
+            Ok(changes) => {
+                let mut lines = Vec::new();
+                let filter = |table: &str| tables.as_ref().map_or(true, |tables| tables.iter().any(|t| t == table));
+                for change in changes.to_json(filter) {
+                    serde_json::to_writer(&mut lines, &change).unwrap();
+                    lines.push(b'\n');
+                }
+                (!lines.is_empty()).then(|| Ok(Bytes::from(lines)))
+            }
+            Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(skipped)) => {
+                log::warn!(
+                    "Change stream of module {} fell {} transactions behind",
+                    address.to_hex(),
+                    skipped
+                );
+                Some(Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("the change stream fell {skipped} transactions behind"),
+                )))
+            }
+        })
+    });
+
+    Ok((
+        StatusCode::OK,
+        TypedHeader(headers::CacheControl::new().with_no_cache()),
+        TypedHeader(headers::ContentType::from(mime_ndjson())),
+        axum::body::StreamBody::new(stream),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct BlobParams {
+    name_or_address: NameOrAddress,
+    key: String,
+}
+
+/// Returns the module host of `database`, spawning it if it isn't running on this node yet.
+async fn database_module_host(worker_ctx: &dyn WorkerCtx, database: Database) -> axum::response::Result<ModuleHost> {
+    let instance_id = worker_ctx
+        .get_leader_database_instance_by_database(database.id)
+        .await
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Database instance not scheduled to this node yet.",
+        ))?
+        .id;
+    let host = worker_ctx.host_controller();
+    let module = match host.get_module_host(instance_id) {
+        Ok(m) => m,
+        Err(_) => {
+            let dbic = worker_ctx
+                .load_module_host_context(database, instance_id)
+                .await
+                .map_err(log_and_500)?;
+            host.spawn_module_host(dbic).await.map_err(log_and_500)?
+        }
+    };
+    Ok(module)
+}
+
+fn blob_etag(hash: &Hash) -> headers::ETag {
+    format!("\"{}\"", hash.to_hex()).parse().unwrap()
+}
+
+fn blob_error(err: DBError) -> ErrorResponse {
+    match err {
+        DBError::BlobTooLarge { .. } => (StatusCode::PAYLOAD_TOO_LARGE, err.to_string()).into(),
+        err => sql_error(err),
+    }
+}
+
+/// Serves the blob stored under `key`, with its hash as its `ETag`.
+///
+/// Blobs are static assets, e.g. avatars, so anyone may fetch them, without credentials.
+pub async fn get_blob(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(BlobParams { name_or_address, key }): Path<BlobParams>,
+    if_none_match: Option<TypedHeader<headers::IfNoneMatch>>,
+) -> axum::response::Result<axum::response::Response> {
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    let database = worker_ctx_find_database(&*worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    let (hash, data) = module
+        .get_blob(&key)
+        .map_err(log_and_500)?
+        .ok_or((StatusCode::NOT_FOUND, "No such blob."))?;
+    let etag = blob_etag(&hash);
+    if let Some(TypedHeader(if_none_match)) = if_none_match {
+        if !if_none_match.precondition_passes(&etag) {
+            return Ok((StatusCode::NOT_MODIFIED, TypedHeader(etag)).into_response());
+        }
+    }
+    Ok((
+        StatusCode::OK,
+        TypedHeader(etag),
+        TypedHeader(headers::CacheControl::new().with_no_cache()),
+        TypedHeader(headers::ContentType::octet_stream()),
+        data,
+    )
+        .into_response())
+}
+
+/// Stores the body of the request as the blob under `key`, replacing any stored under it before.
+///
+/// Only the owner of the database may store blobs this way, while its module may in reducers.
+pub async fn put_blob(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(BlobParams { name_or_address, key }): Path<BlobParams>,
+    auth: SpacetimeAuthHeader,
+    body: Bytes,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_worker_database(&*worker_ctx, name_or_address, auth).await?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    let hash = module.put_blob(&key, &body).map_err(blob_error)?;
+    Ok((
+        TypedHeader(blob_etag(&hash)),
+        axum::Json(json!({ "hash": hash.to_hex() })),
+    ))
+}
+
+/// Deletes the blob stored under `key`.
+///
+/// Only the owner of the database may delete blobs this way, while its module may in reducers.
+pub async fn delete_blob(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(BlobParams { name_or_address, key }): Path<BlobParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_worker_database(&*worker_ctx, name_or_address, auth).await?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    if !module.delete_blob(&key).map_err(blob_error)? {
+        return Err((StatusCode::NOT_FOUND, "No such blob.").into());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct VacuumParams {
+    name_or_address: NameOrAddress,
+}
+
+/// Compacts the storage of the database and rebuilds its indexes, responding with the space reclaimed.
+///
+/// Only the owner of the database may vacuum it, as with `VACUUM` in SQL.
+pub async fn vacuum(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(VacuumParams { name_or_address }): Path<VacuumParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_worker_database(&*worker_ctx, name_or_address, auth).await?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    let report = module.vacuum().map_err(log_and_500)?;
+    Ok(axum::Json(report))
+}
+
+#[derive(Deserialize)]
+pub struct CallLogParams {
+    name_or_address: NameOrAddress,
+}
+
+#[derive(Deserialize)]
+pub struct SetCallLogQueryParams {
+    enabled: bool,
+}
+
+/// Responds with the host calls recently made by the reducers of the database, oldest first,
+/// and whether they're being recorded.
+///
+/// Only the owner of the database may read them, as they reveal what its reducers do.
+pub async fn get_call_log(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(CallLogParams { name_or_address }): Path<CallLogParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_worker_database(&*worker_ctx, name_or_address, auth).await?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    let (enabled, calls) = module.call_log().map_err(log_and_500)?;
+    Ok(axum::Json(json!({ "enabled": enabled, "calls": calls })))
+}
+
+/// Turns the recording of the host calls made by the reducers of the database on or off,
+/// which lasts until the database is restarted.
+///
+/// Turning it on clears the calls recorded before.
+pub async fn set_call_log(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(CallLogParams { name_or_address }): Path<CallLogParams>,
+    Query(SetCallLogQueryParams { enabled }): Query<SetCallLogQueryParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_worker_database(&*worker_ctx, name_or_address, auth).await?;
+    let module = database_module_host(&*worker_ctx, database).await?;
+
+    module.set_call_log_enabled(enabled);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Resolve the database of `name_or_address`, if it's owned by the identity of `auth`.
+///
+/// As with [`owned_database`], this takes an unscoped token.
+async fn owned_worker_database(
+    worker_ctx: &dyn WorkerCtx,
+    name_or_address: NameOrAddress,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<Database> {
+    let auth = auth_or_unauth(auth)?;
+    let address = name_or_address.resolve(worker_ctx).await?.into();
+    auth.require_database(&address)?;
+    auth.require_unscoped()?;
+    let database = worker_ctx_find_database(worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+    if database.identity != auth.identity {
+        return Err((StatusCode::BAD_REQUEST, "Identity does not own this database.").into());
+    }
+    Ok(database)
+}
+
 fn mime_ndjson() -> mime::Mime {
     "application/x-ndjson".parse().unwrap()
 }
@@ -490,25 +1128,125 @@ pub struct SqlParams {
 }
 
 #[derive(Deserialize)]
-pub struct SqlQueryParams {}
+pub struct SqlQueryParams {
+    format: Option<SqlFormat>,
+}
+
+/// How the results of the `/sql` route are encoded.
+///
+/// Unless given by the `format` query parameter,
+/// this is chosen from the `Accept` header, defaulting to `Sats`.
+#[derive(Deserialize, Default, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlFormat {
+    /// The schema of each result and its rows in SATS-JSON.
+    #[default]
+    Sats,
+    /// The column names and types of each result, its schema,
+    /// and its rows in the canonical JSON encoding of [`sats::json`](spacetimedb_lib::sats::json).
+    #[serde(alias = "json")]
+    Typed,
+    /// The rows of a single query as CSV, streamed as they're read.
+    Csv,
+    /// The rows of a single query as an Arrow IPC stream, streamed as they're read.
+    Arrow,
+    /// The rows of a single query as a Parquet file, streamed a row group at a time.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl SqlFormat {
+    fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        let accept = headers.get(http::header::ACCEPT)?.to_str().ok()?;
+        accept.split(',').find_map(|media_type| {
+            let media_type = media_type.split(';').next().unwrap_or_default().trim();
+            if media_type == ExportFormat::Csv.media_type() {
+                Some(Self::Csv)
+            } else if media_type == ExportFormat::Arrow.media_type() {
+                Some(Self::Arrow)
+            } else {
+                #[cfg(feature = "parquet")]
+                if media_type == ExportFormat::Parquet.media_type() {
+                    return Some(Self::Parquet);
+                }
+                None
+            }
+        })
+    }
+}
 
 pub async fn sql(
     State(worker_ctx): State<Arc<dyn WorkerCtx>>,
     Path(SqlParams { name_or_address }): Path<SqlParams>,
-    Query(SqlQueryParams {}): Query<SqlQueryParams>,
+    Query(SqlQueryParams { format }): Query<SqlQueryParams>,
+    headers: HeaderMap,
     auth: SpacetimeAuthHeader,
     body: String,
-) -> axum::response::Result<impl IntoResponse> {
+) -> axum::response::Result<axum::response::Response> {
+    let format = format.or_else(|| SqlFormat::from_accept(&headers)).unwrap_or_default();
+
     // Anyone is authorized to execute SQL queries. The SQL engine will determine
     // which queries this identity is allowed to execute against the database.
     let auth = auth.get_or_create(&*worker_ctx).await?;
 
     let address = name_or_address.resolve(&*worker_ctx).await?.into();
-    let database = worker_ctx_find_database(&*worker_ctx, &address)
+    auth.require_database(&address)?;
+    let run_sql = match auth.sql_access() {
+        SqlAccess::None => return Err((StatusCode::FORBIDDEN, "Token is not allowed to run SQL queries").into()),
+        SqlAccess::ReadOnly => execute_read_only,
+        SqlAccess::ReadWrite => execute,
+    };
+    let (auth, instance_id) = sql_database_instance(&*worker_ctx, &address, auth.identity).await?;
+
+    let typed = match format {
+        SqlFormat::Sats => false,
+        SqlFormat::Typed => true,
+        SqlFormat::Csv => return export_sql(worker_ctx, instance_id, body, auth, ExportFormat::Csv).await,
+        SqlFormat::Arrow => return export_sql(worker_ctx, instance_id, body, auth, ExportFormat::Arrow).await,
+        #[cfg(feature = "parquet")]
+        SqlFormat::Parquet => return export_sql(worker_ctx, instance_id, body, auth, ExportFormat::Parquet).await,
+    };
+
+    let results = run_sql(
+        worker_ctx.database_instance_context_controller(),
+        instance_id,
+        body,
+        auth,
+    )
+    .map_err(sql_error)?;
+
+    let response = if typed {
+        let json = results
+            .into_iter()
+            .map(|result| TypedStmtResultJson::new(&result.head.ty(), result.data))
+            .collect::<Vec<_>>();
+        axum::Json(json).into_response()
+    } else {
+        let json = results
+            .into_iter()
+            .map(|result| StmtResultJson {
+                schema: result.head.ty(),
+                rows: result.data.into_iter().map(|x| x.elements).collect::<Vec<_>>(),
+            })
+            .collect::<Vec<_>>();
+        axum::Json(json).into_response()
+    };
+
+    Ok((StatusCode::OK, response).into_response())
+}
+
+/// Find the leader instance of the database at `address` for `caller` to run SQL in,
+/// spawning its module host if it isn't running yet.
+async fn sql_database_instance(
+    worker_ctx: &dyn WorkerCtx,
+    address: &Address,
+    caller: Identity,
+) -> axum::response::Result<(AuthCtx, u64)> {
+    let database = worker_ctx_find_database(worker_ctx, address)
         .await?
         .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
 
-    let auth = AuthCtx::new(database.identity, auth.identity);
+    let auth = AuthCtx::new(database.identity, caller);
     log::debug!("auth: {auth:?}");
     let database_instance = worker_ctx
         .get_leader_database_instance_by_database(database.id)
@@ -531,34 +1269,181 @@ pub async fn sql(
         }
     };
 
-    let results = match execute(
-        worker_ctx.database_instance_context_controller(),
-        instance_id,
-        body,
-        auth,
-    ) {
-        Ok(results) => results,
-        Err(err) => {
-            log::warn!("{}", err);
-            return if let Some(auth_err) = err.get_auth_error() {
-                let err = format!("{auth_err}");
-                Err((StatusCode::UNAUTHORIZED, err).into())
-            } else {
-                let err = format!("{err}");
-                Err((StatusCode::BAD_REQUEST, err).into())
-            };
+    Ok((auth, instance_id))
+}
+
+fn sql_error(err: DBError) -> ErrorResponse {
+    log::warn!("{}", err);
+    if let Some(auth_err) = err.get_auth_error() {
+        let err = format!("{auth_err}");
+        (StatusCode::UNAUTHORIZED, err).into()
+    } else if let Some(quota_err) = err.get_quota_exceeded() {
+        let body = json!({
+            "error": "quota_exceeded",
+            "message": quota_err.to_string(),
+            "limit": quota_err.limit,
+            "max": quota_err.max,
+        });
+        (StatusCode::FORBIDDEN, axum::Json(body)).into()
+    } else {
+        let err = format!("{err}");
+        (StatusCode::BAD_REQUEST, err).into()
+    }
+}
+
+/// How many chunks of an export can be encoded ahead of the client reading them.
+const EXPORT_CHUNKS_IN_FLIGHT: usize = 4;
+/// The size of the chunks an export is sent in.
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Sends the bytes written to it down a channel, blocking while the channel is full.
+struct ChunkSender(tokio::sync::mpsc::Sender<Result<Bytes, DBError>>);
+
+impl std::io::Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "export response was dropped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CopyQueryParams {
+    /// The `COPY ... FROM STDIN` statement whose rows are the body of the request.
+    sql: String,
+}
+
+/// How many chunks of the body of a `COPY` can be received ahead of its rows being inserted.
+const COPY_CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Reads the bytes received down a channel, blocking while the channel is empty.
+struct ChunkReceiver {
+    rx: tokio::sync::mpsc::Receiver<std::io::Result<Bytes>>,
+    chunk: Bytes,
+}
+
+impl std::io::Read for ChunkReceiver {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.chunk = chunk?,
+                None => return Ok(0),
+            }
         }
-    };
+        let read = self.chunk.split_to(buf.len().min(self.chunk.len()));
+        buf[..read.len()].copy_from_slice(&read);
+        Ok(read.len())
+    }
+}
 
-    let json = results
-        .into_iter()
-        .map(|result| StmtResultJson {
-            schema: result.head.ty(),
-            rows: result.data.into_iter().map(|x| x.elements).collect::<Vec<_>>(),
-        })
-        .collect::<Vec<_>>();
+/// Runs the `COPY ... FROM STDIN` statement in the `sql` query parameter,
+/// inserting the rows of the request body as they're received.
+///
+/// Responds with how many rows were copied, along with the error if the copy stopped part of the way through.
+pub async fn copy(
+    State(worker_ctx): State<Arc<dyn WorkerCtx>>,
+    Path(SqlParams { name_or_address }): Path<SqlParams>,
+    Query(CopyQueryParams { sql }): Query<CopyQueryParams>,
+    auth: SpacetimeAuthHeader,
+    mut body: BodyStream,
+) -> axum::response::Result<axum::response::Response> {
+    let auth = auth.get_or_create(&*worker_ctx).await?;
+
+    let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
+    if auth.sql_access() != SqlAccess::ReadWrite {
+        return Err((StatusCode::FORBIDDEN, "Token is not allowed to write with SQL").into());
+    }
+    let (auth, instance_id) = sql_database_instance(&*worker_ctx, &address, auth.identity).await?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(COPY_CHUNKS_IN_FLIGHT);
+    let copying = tokio::task::spawn_blocking(move || {
+        let input = std::io::BufReader::new(ChunkReceiver {
+            rx,
+            chunk: Bytes::new(),
+        });
+        copy_from(
+            worker_ctx.database_instance_context_controller(),
+            instance_id,
+            &sql,
+            auth,
+            input,
+        )
+    });
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err));
+        let failed = chunk.is_err();
+        // The copy stops reading early if it fails.
+        if tx.send(chunk).await.is_err() || failed {
+            break;
+        }
+    }
+    drop(tx);
+
+    match copying.await.map_err(log_and_500)? {
+        Ok(copied) => Ok(axum::Json(json!({ "copied": copied })).into_response()),
+        Err(CopyError { copied: 0, error }) => Err(sql_error(error)),
+        Err(CopyError { copied, error }) => {
+            log::warn!("SQL copy failed after {copied} rows: {error}");
+            let body = json!({ "copied": copied, "error": error.to_string() });
+            Ok((StatusCode::BAD_REQUEST, axum::Json(body)).into_response())
+        }
+    }
+}
+
+/// Streams the results of the query `sql_text` in `format`,
+/// encoding them on a blocking thread as the client reads them.
+///
+/// The read transaction of the query stays open until the client has read the whole export.
+async fn export_sql(
+    worker_ctx: Arc<dyn WorkerCtx>,
+    instance_id: u64,
+    sql_text: String,
+    auth: AuthCtx,
+    format: ExportFormat,
+) -> axum::response::Result<axum::response::Response> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(EXPORT_CHUNKS_IN_FLIGHT);
+    tokio::task::spawn_blocking(move || {
+        let out = std::io::BufWriter::with_capacity(EXPORT_CHUNK_SIZE, ChunkSender(tx.clone()));
+        let res = export(
+            worker_ctx.database_instance_context_controller(),
+            instance_id,
+            sql_text,
+            auth,
+            format,
+            out,
+        );
+        if let Err(err) = res {
+            let _ = tx.blocking_send(Err(err));
+        }
+    });
 
-    Ok((StatusCode::OK, axum::Json(json)))
+    // Nothing is written until the query is known to run,
+    // so an error as the first message can still be reported with a status code.
+    let first = match rx.recv().await {
+        Some(Err(err)) => return Err(sql_error(err)),
+        first => first,
+    };
+    let stream = futures::stream::iter(first)
+        .chain(tokio_stream::wrappers::ReceiverStream::new(rx))
+        .map(|chunk| {
+            chunk.map_err(|err| {
+                log::warn!("SQL export failed: {err}");
+                std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+            })
+        });
+
+    Ok((
+        StatusCode::OK,
+        [(http::header::CONTENT_TYPE, format.media_type())],
+        axum::body::StreamBody::new(stream),
+    )
+        .into_response())
 }
 
 #[derive(Deserialize)]
@@ -625,6 +1510,7 @@ pub async fn register_tld(
     // You should not be able to publish to a database that you do not own
     // so, unless you are the owner, this will fail, hence not using get_or_create
     let auth = auth_or_bad_request(auth)?;
+    auth.require_unscoped()?;
 
     let tld = tld.parse::<DomainName>().map_err(DomainParsingRejection)?.into();
     let result = ctx
@@ -764,6 +1650,9 @@ pub struct PublishDatabaseQueryParams {
     trace_log: Option<bool>,
     #[serde(default)]
     register_tld: bool,
+    /// Check the module against the existing database and report what publishing it would do, without doing it.
+    #[serde(default)]
+    dry_run: bool,
 }
 
 #[cfg(not(feature = "tracelogging"))]
@@ -789,11 +1678,26 @@ pub async fn publish(
         clear,
         trace_log,
         register_tld,
+        dry_run,
     } = query_params;
 
     // You should not be able to publish to a database that you do not own
     // so, unless you are the owner, this will fail.
     let auth = auth_or_bad_request(auth)?;
+    auth.require_unscoped()?;
+
+    if dry_run {
+        if clear {
+            return Err((StatusCode::BAD_REQUEST, "A dry run can't clear the database.").into());
+        }
+        let name_or_address = name_or_address.ok_or((
+            StatusCode::BAD_REQUEST,
+            "A dry run needs the name or address of an existing database.",
+        ))?;
+        return plan_publish(&*ctx, name_or_address, auth.identity, body)
+            .await
+            .map(axum::Json);
+    }
 
     let specified_address = matches!(name_or_address, Some(NameOrAddress::Address(_)));
 
@@ -928,6 +1832,39 @@ pub async fn publish(
     Ok(axum::Json(response))
 }
 
+/// Load the module in `body` and check it against the database of `name_or_address`, which `identity` must own,
+/// returning what publishing it would do to the database, without publishing it.
+async fn plan_publish(
+    ctx: &dyn ControlCtx,
+    name_or_address: NameOrAddress,
+    identity: Identity,
+    body: Bytes,
+) -> axum::response::Result<PublishResult> {
+    let db_address: Address = name_or_address.resolve(ctx).await?.into();
+    let database = control_ctx_find_database(ctx, &db_address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+    if database.identity != identity {
+        return Err((StatusCode::BAD_REQUEST, "Identity does not own this database.").into());
+    }
+
+    let program_bytes_addr = ctx.object_db().insert_object(body.into()).map_err(log_and_500)?;
+    let plan = ctx
+        .plan_database_update(&db_address, &program_bytes_addr)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to load the module: {e:#}")))?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+
+    Ok(PublishResult::DryRun {
+        domain: match name_or_address {
+            NameOrAddress::Address(_) => None,
+            NameOrAddress::Name(name) => Some(name),
+        },
+        address: db_address.to_hex(),
+        plan,
+    })
+}
+
 #[derive(Deserialize)]
 pub struct DeleteDatabaseParams {
     address: Address,
@@ -939,6 +1876,7 @@ pub async fn delete_database(
     auth: SpacetimeAuthHeader,
 ) -> axum::response::Result<impl IntoResponse> {
     let auth = auth_or_bad_request(auth)?;
+    auth.require_unscoped()?;
 
     match control_ctx_find_database(&*ctx, &address).await? {
         Some(db) => {
@@ -955,6 +1893,157 @@ pub async fn delete_database(
     }
 }
 
+#[derive(Deserialize)]
+pub struct WebhooksParams {
+    name_or_address: NameOrAddress,
+}
+
+/// Resolve the database of `name_or_address`, if it's owned by the identity of `auth`.
+///
+/// Only an unscoped token may manage a database, as scopes only grant access to its data.
+async fn owned_database(
+    ctx: &dyn ControlCtx,
+    name_or_address: NameOrAddress,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<Database> {
+    let auth = auth_or_bad_request(auth)?;
+    auth.require_unscoped()?;
+    let address = name_or_address.resolve(ctx).await?.into();
+    auth.require_database(&address)?;
+    let database = control_ctx_find_database(ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+    if database.identity != auth.identity {
+        return Err((StatusCode::BAD_REQUEST, "Identity does not own this database.").into());
+    }
+    Ok(database)
+}
+
+/// The webhooks of a database, without their secrets.
+pub async fn get_webhooks(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(WebhooksParams { name_or_address }): Path<WebhooksParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_database(&*ctx, name_or_address, auth).await?;
+    let webhooks = ctx.control_db().get_webhooks(&database.address).map_err(log_and_500)?;
+    Ok(axum::Json(webhooks))
+}
+
+/// Replace the webhooks of a database with those in the body, a JSON array.
+///
+/// They take effect from the next transaction the database commits.
+pub async fn set_webhooks(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(WebhooksParams { name_or_address }): Path<WebhooksParams>,
+    auth: SpacetimeAuthHeader,
+    axum::Json(webhooks): axum::Json<Vec<Webhook>>,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_database(&*ctx, name_or_address, auth).await?;
+
+    let mut names = HashSet::new();
+    for webhook in &webhooks {
+        if webhook.name.is_empty() || !names.insert(&webhook.name) {
+            return Err((StatusCode::BAD_REQUEST, "Webhook names must be unique and non-empty.").into());
+        }
+        let is_http = webhook
+            .url
+            .parse::<http::Uri>()
+            .map_or(false, |uri| matches!(uri.scheme_str(), Some("http" | "https")));
+        if !is_http {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("The URL of webhook `{}` is not an http(s) URL.", webhook.name),
+            )
+                .into());
+        }
+    }
+
+    ctx.control_db()
+        .set_webhooks(&database.address, &webhooks)
+        .await
+        .map_err(log_and_500)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct LifecycleParams {
+    name_or_address: NameOrAddress,
+}
+
+/// Unload a database from memory until it's resumed, or a client connects to it or calls it.
+///
+/// Its data and logs are kept, and its clients are disconnected.
+/// Like resuming it, this takes an unscoped token of its owner.
+pub async fn pause(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(LifecycleParams { name_or_address }): Path<LifecycleParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_database(&*ctx, name_or_address, auth).await?;
+    ctx.pause_database(&database.address).await.map_err(log_and_500)?;
+    Ok(())
+}
+
+/// Load a paused database back into memory, without waiting for a client to use it.
+pub async fn resume(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(LifecycleParams { name_or_address }): Path<LifecycleParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_database(&*ctx, name_or_address, auth).await?;
+    ctx.resume_database(&database.address).await.map_err(log_and_500)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct QuotaParams {
+    address: Address,
+}
+
+/// The quota of a database, with `null` for each unlimited resource.
+///
+/// Only the owner of the database may read it.
+pub async fn get_quota(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(QuotaParams { address }): Path<QuotaParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let database = owned_database(&*ctx, NameOrAddress::Address(address), auth).await?;
+    let quota = ctx.control_db().get_quota(&database.address).map_err(log_and_500)?;
+    Ok(axum::Json(quota))
+}
+
+/// The identity of the operator of the node, who alone may set the quotas of databases,
+/// from the `SPACETIMEDB_QUOTA_ADMIN` environment variable, if set to an identity in hex.
+fn quota_admin() -> Option<Identity> {
+    let hex = std::env::var("SPACETIMEDB_QUOTA_ADMIN").ok()?;
+    Identity::from_hex(&hex)
+        .map_err(|e| log::warn!("Ignoring invalid SPACETIMEDB_QUOTA_ADMIN {hex:?}: {e}"))
+        .ok()
+}
+
+/// Replace the quota of a database with the one in the body.
+///
+/// Tables and rows the database already has are kept, even if they're over the new quota.
+pub async fn set_quota(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(QuotaParams { address }): Path<QuotaParams>,
+    auth: SpacetimeAuthHeader,
+    axum::Json(quota): axum::Json<Quota>,
+) -> axum::response::Result<impl IntoResponse> {
+    // Like energy pricing, quotas are up to the operator of the node rather than the owner of the database,
+    // so unless the node names its operator, no one may set them through the API.
+    let auth = auth_or_unauth(auth)?;
+    auth.require_unscoped()?;
+    if quota_admin() != Some(auth.identity) {
+        return Err((StatusCode::FORBIDDEN, "Only the operator of the node may set quotas.").into());
+    }
+
+    ctx.set_quota(&address, &quota).await.map_err(log_and_500)?;
+    Ok(axum::Json(quota))
+}
+
 #[derive(Deserialize)]
 pub struct SetNameQueryParams {
     domain: String,
@@ -973,6 +2062,7 @@ pub async fn set_name(
     auth: SpacetimeAuthHeader,
 ) -> axum::response::Result<impl IntoResponse> {
     let auth = auth_or_bad_request(auth)?;
+    auth.require_unscoped()?;
 
     let database = ctx
         .control_db()
@@ -1020,6 +2110,10 @@ where
         .route("/confirm_recovery_code", get(confirm_recovery_code))
         .route("/publish", post(publish).layer(DefaultBodyLimit::disable()))
         .route("/delete/:address", post(delete_database))
+        .route("/webhooks/:name_or_address", get(get_webhooks).post(set_webhooks))
+        .route("/pause/:name_or_address", post(pause))
+        .route("/resume/:name_or_address", post(resume))
+        .route("/quota/:address", get(get_quota).post(set_quota))
 }
 
 pub fn worker_routes<S>() -> axum::Router<S>
@@ -1033,7 +2127,18 @@ where
         .route("/call/:name_or_address/:reducer", post(call))
         .route("/schema/:name_or_address/:entity_type/:entity", get(describe))
         .route("/schema/:name_or_address", get(catalog))
+        .route("/module_schema/:name_or_address", get(module_schema))
         .route("/info/:name_or_address", get(info))
         .route("/logs/:name_or_address", get(logs))
+        .route("/changes/:name_or_address", get(changes))
         .route("/sql/:name_or_address", post(sql))
+        .route("/copy/:name_or_address", post(copy))
+        .route("/merge_identity/:name_or_address/:old_identity", post(merge_identity))
+        .route("/vacuum/:name_or_address", post(vacuum))
+        .route("/call_log/:name_or_address", get(get_call_log).post(set_call_log))
+        .route("/http/:name_or_address/*path", get(http_route).post(http_route))
+        .route(
+            "/blob/:name_or_address/*key",
+            get(get_blob).put(put_blob).delete(delete_blob),
+        )
 }
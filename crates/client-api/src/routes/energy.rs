@@ -6,7 +6,9 @@ use http::StatusCode;
 use serde::Deserialize;
 use serde_json::json;
 
+use spacetimedb::address::Address;
 use spacetimedb::host::EnergyQuanta;
+use spacetimedb::messages::control_db::EnergyPricing;
 use spacetimedb_lib::Identity;
 
 use crate::auth::SpacetimeAuthHeader;
@@ -86,6 +88,59 @@ pub async fn set_energy_balance(
     Ok(axum::Json(response_json))
 }
 
+#[derive(Deserialize)]
+pub struct PricingParams {
+    address: Address,
+}
+
+#[derive(Deserialize)]
+pub struct PricingQueryParams {
+    /// The reducer to get or set the pricing of,
+    /// or all of the database's reducers that aren't priced individually if `None`.
+    reducer: Option<String>,
+}
+
+pub async fn get_energy_pricing(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(PricingParams { address }): Path<PricingParams>,
+    Query(PricingQueryParams { reducer }): Query<PricingQueryParams>,
+) -> axum::response::Result<impl IntoResponse> {
+    let pricing = ctx
+        .control_db()
+        .get_energy_pricing(&address, reducer.as_deref().unwrap_or_default())
+        .map_err(log_and_500)?
+        .unwrap_or_default();
+    Ok(axum::Json(pricing))
+}
+
+pub async fn set_energy_pricing(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(PricingParams { address }): Path<PricingParams>,
+    Query(PricingQueryParams { reducer }): Query<PricingQueryParams>,
+    auth: SpacetimeAuthHeader,
+    axum::Json(pricing): axum::Json<EnergyPricing>,
+) -> axum::response::Result<impl IntoResponse> {
+    // Like energy balances, pricing is up to the operator of the node,
+    // so for now no one is authorized to set it through the API.
+    let Some(auth) = auth.auth else {
+        return Err(StatusCode::UNAUTHORIZED.into());
+    };
+    if auth.identity != Identity::__dummy() {
+        return Err(StatusCode::UNAUTHORIZED.into());
+    }
+
+    if pricing.per_instruction == 0 {
+        return Err((StatusCode::BAD_REQUEST, "The price per instruction must be at least 1").into());
+    }
+
+    ctx.control_db()
+        .set_energy_pricing(&address, reducer.as_deref(), pricing)
+        .await
+        .map_err(log_and_500)?;
+
+    Ok(axum::Json(pricing))
+}
+
 pub fn router<S>() -> axum::Router<S>
 where
     S: ControlNodeDelegate + Clone + 'static,
@@ -95,4 +150,5 @@ where
     axum::Router::new()
         .route("/:identity", get(get_energy_balance))
         .route("/:identity", post(set_energy_balance))
+        .route("/pricing/:address", get(get_energy_pricing).post(set_energy_pricing))
 }
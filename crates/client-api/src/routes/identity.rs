@@ -4,11 +4,11 @@ use axum::extract::{FromRef, Path, Query, State};
 use axum::response::IntoResponse;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
-use spacetimedb::auth::identity::encode_token_with_expiry;
+use spacetimedb::auth::identity::{encode_scoped_token, encode_token_with_expiry, ApiToken, TokenScope};
 use spacetimedb_lib::de::serde::DeserializeWrapper;
 use spacetimedb_lib::Identity;
 
-use crate::auth::{SpacetimeAuth, SpacetimeAuthHeader};
+use crate::auth::{SpacetimeAuth, SpacetimeAuthHeader, SpacetimeCreds};
 use crate::{log_and_500, ControlCtx, ControlNodeDelegate};
 
 #[derive(Deserialize)]
@@ -41,6 +41,55 @@ pub async fn create_identity(
     Ok(axum::Json(identity_response))
 }
 
+/// Mints a guest identity, whose token expires unless it's promoted with `/identity/:identity/promote`.
+pub async fn create_guest_identity(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let auth = SpacetimeAuth::alloc_guest(&*ctx, auth.client_ip()).await?;
+    Ok(axum::Json(CreateIdentityResponse {
+        identity: auth.identity.to_hex(),
+        token: auth.creds.token().to_owned(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PromoteGuestParams {
+    identity: IdentityForUrl,
+}
+
+/// Promotes the guest `identity` to a durable one, handing it a token that doesn't expire,
+/// optionally associating it with `email`.
+///
+/// The identity itself is kept, so the rows modules keyed on it stay the guest's.
+pub async fn promote_guest(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(PromoteGuestParams { identity }): Path<PromoteGuestParams>,
+    Query(CreateIdentityQueryParams { email }): Query<CreateIdentityQueryParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let identity = identity.into();
+    let auth = auth.get().ok_or(StatusCode::UNAUTHORIZED)?;
+    if auth.identity != identity {
+        return Err(StatusCode::UNAUTHORIZED.into());
+    }
+    if !auth.guest {
+        return Err((StatusCode::BAD_REQUEST, "Only a guest identity can be promoted").into());
+    }
+
+    if let Some(email) = email {
+        ctx.control_db()
+            .associate_email_spacetime_identity(identity, email.as_str())
+            .await
+            .map_err(log_and_500)?;
+    }
+    let creds = SpacetimeCreds::encode_token(ctx.private_key(), identity).map_err(log_and_500)?;
+    Ok(axum::Json(CreateIdentityResponse {
+        identity: identity.to_hex(),
+        token: creds.token().to_owned(),
+    }))
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct GetIdentityResponse {
     identities: Vec<GetIdentityResponseEntry>,
@@ -136,6 +185,7 @@ pub async fn set_email(
     if auth.identity != identity {
         return Err(StatusCode::UNAUTHORIZED.into());
     }
+    auth.require_unscoped()?;
 
     ctx.control_db()
         .associate_email_spacetime_identity(identity, email.as_str())
@@ -184,6 +234,7 @@ pub async fn create_websocket_token(
 ) -> axum::response::Result<impl IntoResponse> {
     match auth.auth {
         Some(auth) => {
+            auth.require_unscoped()?;
             let token = encode_token_with_expiry(ctx.private_key(), auth.identity, Some(60)).map_err(log_and_500)?;
             Ok(axum::Json(WebsocketTokenResponse { token }))
         }
@@ -191,6 +242,137 @@ pub async fn create_websocket_token(
     }
 }
 
+#[derive(Deserialize)]
+pub struct TokensParams {
+    identity: IdentityForUrl,
+}
+
+/// Checks that `auth` is an unscoped token of `identity`, which alone may manage its scoped tokens.
+fn auth_for_tokens(auth: SpacetimeAuthHeader, identity: Identity) -> axum::response::Result<SpacetimeAuth> {
+    let auth = auth.get().ok_or(StatusCode::UNAUTHORIZED)?;
+    if auth.identity != identity {
+        return Err(StatusCode::UNAUTHORIZED.into());
+    }
+    auth.require_unscoped()?;
+    Ok(auth)
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateTokenResponse {
+    id: u64,
+    token: String,
+}
+
+pub async fn create_token(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(TokensParams { identity }): Path<TokensParams>,
+    auth: SpacetimeAuthHeader,
+    axum::Json(scope): axum::Json<TokenScope>,
+) -> axum::response::Result<impl IntoResponse> {
+    let identity = identity.into();
+    auth_for_tokens(auth, identity)?;
+
+    let api_token = ctx
+        .control_db()
+        .insert_api_token(identity, scope)
+        .await
+        .map_err(log_and_500)?;
+    let token = encode_scoped_token(ctx.private_key(), identity, &api_token).map_err(log_and_500)?;
+    Ok(axum::Json(CreateTokenResponse {
+        id: api_token.id,
+        token,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetTokensResponse {
+    tokens: Vec<ApiToken>,
+}
+
+pub async fn get_tokens(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(TokensParams { identity }): Path<TokensParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let identity = identity.into();
+    auth_for_tokens(auth, identity)?;
+
+    let tokens = ctx
+        .control_db()
+        .get_api_tokens_for_identity(&identity)
+        .map_err(log_and_500)?;
+    Ok(axum::Json(GetTokensResponse { tokens }))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeTokenParams {
+    identity: IdentityForUrl,
+    token_id: u64,
+}
+
+pub async fn revoke_token(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(RevokeTokenParams { identity, token_id }): Path<RevokeTokenParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse> {
+    let identity: Identity = identity.into();
+    auth_for_tokens(auth, identity)?;
+
+    // Only the identity a token was issued for may revoke it.
+    let token = ctx
+        .control_db()
+        .get_api_token(token_id)
+        .map_err(log_and_500)?
+        .filter(|token| token.identity == identity.to_hex())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    ctx.control_db().revoke_api_token(token.id).await.map_err(log_and_500)?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct LinkIdentityParams {
+    identity: IdentityForUrl,
+}
+
+#[derive(Deserialize)]
+pub struct LinkIdentityRequest {
+    /// A token of the identity to link, proving that the caller holds it.
+    token: String,
+}
+
+/// Links the identity of the token in the request body to `identity`,
+/// e.g. once a player who started out anonymous signs in with an account.
+///
+/// Both tokens must be unscoped, as the linked identity gives up its place for good,
+/// though the linked one may be that of a guest.
+/// The databases it has used learn of it through their `identity_merged` reducer,
+/// which the new identity has each of them call with `/database/merge_identity`.
+pub async fn link_identity(
+    State(ctx): State<Arc<dyn ControlCtx>>,
+    Path(LinkIdentityParams { identity }): Path<LinkIdentityParams>,
+    auth: SpacetimeAuthHeader,
+    axum::Json(LinkIdentityRequest { token }): axum::Json<LinkIdentityRequest>,
+) -> axum::response::Result<impl IntoResponse> {
+    let identity = identity.into();
+    auth_for_tokens(auth, identity)?;
+
+    let old = SpacetimeAuth::verify(SpacetimeCreds::from_token(&token), &*ctx).await?;
+    if old.scope.is_some() {
+        return Err((StatusCode::FORBIDDEN, "A scoped token's identity can't be linked").into());
+    }
+    if old.identity == identity {
+        return Err((StatusCode::BAD_REQUEST, "An identity can't be linked to itself").into());
+    }
+
+    match ctx.control_db().link_identity(old.identity, identity).await {
+        Ok(()) => Ok(()),
+        Err(e @ spacetimedb::control_db::Error::IdentityAlreadyLinked(_)) => {
+            Err((StatusCode::CONFLICT, e.to_string()).into())
+        }
+        Err(e) => Err(log_and_500(e).into()),
+    }
+}
+
 pub fn router<S>() -> axum::Router<S>
 where
     S: ControlNodeDelegate + Clone + 'static,
@@ -199,7 +381,12 @@ where
     use axum::routing::{get, post};
     axum::Router::new()
         .route("/", get(get_identity).post(create_identity))
+        .route("/guest", post(create_guest_identity))
         .route("/websocket_token", post(create_websocket_token))
         .route("/:identity/set-email", post(set_email))
         .route("/:identity/databases", get(get_databases))
+        .route("/:identity/tokens", get(get_tokens).post(create_token))
+        .route("/:identity/tokens/:token_id/revoke", post(revoke_token))
+        .route("/:identity/link", post(link_identity))
+        .route("/:identity/promote", post(promote_guest))
 }
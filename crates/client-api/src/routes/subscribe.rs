@@ -1,19 +1,23 @@
+use std::io::Write;
 use std::mem;
 use std::pin::pin;
 use std::sync::Arc;
 use std::time::Duration;
 
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::response::IntoResponse;
 use axum::TypedHeader;
+use flate2::write::GzEncoder;
 use futures::{SinkExt, StreamExt};
 use http::{HeaderValue, StatusCode};
 use serde::Deserialize;
 use spacetimedb::client::messages::{IdentityTokenMessage, ServerMessage};
-use spacetimedb::client::{ClientActorId, ClientClosed, ClientConnection, DataMessage, MessageHandleError, Protocol};
+use spacetimedb::client::{
+    ClientActorId, ClientClosed, ClientConnection, DataMessage, MessageHandleError, Outgoing, Protocol,
+    SendQueueReceiver,
+};
 use spacetimedb::host::NoSuchModule;
 use spacetimedb::util::future_queue;
-use tokio::sync::mpsc;
 
 use crate::auth::{SpacetimeAuthHeader, SpacetimeIdentity, SpacetimeIdentityToken};
 use crate::util::websocket::{
@@ -32,9 +36,52 @@ pub struct SubscribeParams {
     pub name_or_address: NameOrAddress,
 }
 
+#[derive(Deserialize)]
+pub struct SubscribeQueryParams {
+    /// Send the rows updated in place as the columns that changed, rather than a delete and an insert.
+    #[serde(default)]
+    pub column_diffs: bool,
+    /// Send the rows matching the client's queries in chunks, followed by a caught up marker,
+    /// rather than in a single subscription update.
+    #[serde(default)]
+    pub chunked_snapshots: bool,
+    /// How to compress the messages sent to the client.
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+/// The compression of the messages sent to a client, negotiated when it connects.
+///
+/// Once a client has asked for compression, every message it's sent is a binary frame,
+/// whose first byte is `0` if the rest of it is the message as it would otherwise be sent,
+/// i.e., JSON text or protobuf, or `1` if it's that message compressed with gzip.
+/// Only messages of at least [`MIN_COMPRESSED_SIZE`] bytes are compressed.
+#[derive(Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl Compression {
+    /// The tag of a message that isn't compressed.
+    const TAG_NONE: u8 = 0;
+    /// The tag of a message compressed with gzip.
+    const TAG_GZIP: u8 = 1;
+}
+
+/// The size below which a message is sent uncompressed, as compressing it wouldn't be worth the time.
+pub const MIN_COMPRESSED_SIZE: usize = 1024;
+
 pub async fn handle_websocket(
     State(worker_ctx): State<Arc<dyn WorkerCtx>>,
     Path(SubscribeParams { name_or_address }): Path<SubscribeParams>,
+    Query(SubscribeQueryParams {
+        column_diffs,
+        chunked_snapshots,
+        compression,
+    }): Query<SubscribeQueryParams>,
     forwarded_for: Option<TypedHeader<XForwardedFor>>,
     auth: SpacetimeAuthHeader,
     ws: WebSocketUpgrade,
@@ -42,6 +89,7 @@ pub async fn handle_websocket(
     let auth = auth.get_or_create(&*worker_ctx).await?;
 
     let address = name_or_address.resolve(&*worker_ctx).await?.into();
+    auth.require_database(&address)?;
 
     let (res, ws_upgrade, protocol) =
         ws.select_protocol([(BIN_PROTOCOL, Protocol::Binary), (TEXT_PROTOCOL, Protocol::Text)]);
@@ -62,6 +110,7 @@ pub async fn handle_websocket(
     let instance_id = database_instance.id;
 
     let identity_token = auth.creds.token().to_owned();
+    let scope = auth.scope.clone();
 
     let host = worker_ctx.host_controller();
     let module = match host.get_module_host(instance_id) {
@@ -103,8 +152,19 @@ pub async fn handle_websocket(
             None => log::debug!("New client connected from unknown ip"),
         }
 
-        let actor = |client, sendrx| ws_client_actor(client, ws, sendrx);
-        let client = match ClientConnection::spawn(client_id, protocol, instance_id, module, actor).await {
+        let actor = |client, sendrx| ws_client_actor(client, ws, sendrx, compression);
+        let client = match ClientConnection::spawn(
+            client_id,
+            protocol,
+            column_diffs,
+            chunked_snapshots,
+            instance_id,
+            module,
+            scope,
+            actor,
+        )
+        .await
+        {
             Ok(s) => s,
             Err(NoSuchModule) => {
                 // debug here should be fine because we *just* found a module, so this should be really rare
@@ -136,7 +196,12 @@ pub async fn handle_websocket(
 
 const LIVELINESS_TIMEOUT: Duration = Duration::from_secs(60);
 
-async fn ws_client_actor(client: ClientConnection, mut ws: WebSocketStream, mut sendrx: mpsc::Receiver<DataMessage>) {
+async fn ws_client_actor(
+    client: ClientConnection,
+    mut ws: WebSocketStream,
+    mut sendrx: SendQueueReceiver,
+    compression: Compression,
+) {
     let mut liveness_check_interval = tokio::time::interval(LIVELINESS_TIMEOUT);
     let mut got_pong = true;
     // TODO: do we want this to have a fixed capacity? or should it be unbounded
@@ -160,21 +225,35 @@ async fn ws_client_actor(client: ClientConnection, mut ws: WebSocketStream, mut
                 // the client sent us a close frame
                 None => break,
             },
-            Some(message) = sendrx.recv() => {
-                if closed {
-                    // TODO: this isn't great. when we receive a close request from the peer,
-                    //       tungstenite doesn't let us send any new messages on the socket,
-                    //       even though the websocket RFC allows it. should we fork tungstenite?
-                    log::info!("dropping message due to ws already being closed: {message:?}");
-                } else {
-                    // TODO: I think we can be smarter about feeding messages here?
-                    if let Err(error) = ws.send(datamsg_to_wsmsg(message)).await {
-                        log::warn!("Websocket send error: {error}")
+            Some(outgoing) = sendrx.recv() => {
+                match outgoing {
+                    Outgoing::Message(message) => {
+                        if closed {
+                            // TODO: this isn't great. when we receive a close request from the peer,
+                            //       tungstenite doesn't let us send any new messages on the socket,
+                            //       even though the websocket RFC allows it. should we fork tungstenite?
+                            log::info!("dropping message due to ws already being closed: {message:?}");
+                        } else {
+                            // TODO: I think we can be smarter about feeding messages here?
+                            if let Err(error) = ws.send(encode_message(message, compression)).await {
+                                log::warn!("Websocket send error: {error}")
+                            }
+                        }
+                    }
+                    // the client caught up after some of its updates were dropped
+                    Outgoing::Resync => {
+                        let _ = client.resync();
+                    }
+                    Outgoing::Disconnect => {
+                        log::warn!("client {} fell behind on its messages, disconnecting", client.id);
+                        if let Err(e) = ws.close(Some(CloseFrame { code: CloseCode::Policy, reason: "too slow to keep up with its messages".into() })).await {
+                            log::warn!("error closing: {e:#}")
+                        }
                     }
                 }
                 continue;
             }
-            () = client.module.exited() => {
+            () = client.module.retired() => {
                 if let Err(e) = ws.close(Some(CloseFrame { code: CloseCode::Away, reason: "module exited".into() })).await {
                     log::warn!("error closing: {e:#}")
                 }
@@ -200,7 +279,7 @@ async fn ws_client_actor(client: ClientConnection, mut ws: WebSocketStream, mut
                     if let MessageHandleError::Execution(err) = e {
                         log::error!("{err:#}");
                         let msg = err.serialize(client.protocol);
-                        if let Err(error) = ws.send(datamsg_to_wsmsg(msg)).await {
+                        if let Err(error) = ws.send(encode_message(msg, compression)).await {
                             log::warn!("Websocket send error: {error}")
                         }
                         continue;
@@ -244,7 +323,7 @@ async fn ws_client_actor(client: ClientConnection, mut ws: WebSocketStream, mut
     sendrx.close();
 
     // ignore NoSuchModule; if the module's already closed, that's fine
-    let _ = client.module.subscription().remove_subscriber(client.id);
+    let _ = client.module.current().subscription().remove_subscriber(client.id);
     let _ = client
         .module
         .call_identity_connected_disconnected(client.id.identity, false)
@@ -277,3 +356,71 @@ fn datamsg_to_wsmsg(msg: DataMessage) -> WsMessage {
         DataMessage::Binary(bin) => WsMessage::Binary(bin),
     }
 }
+
+/// Encodes `msg` for a client that negotiated `compression`.
+fn encode_message(msg: DataMessage, compression: Compression) -> WsMessage {
+    if compression == Compression::None {
+        return datamsg_to_wsmsg(msg);
+    }
+    let bytes = match msg {
+        DataMessage::Text(text) => text.into_bytes(),
+        DataMessage::Binary(bin) => bin,
+    };
+    let mut frame = Vec::with_capacity(bytes.len() + 1);
+    if bytes.len() < MIN_COMPRESSED_SIZE {
+        frame.push(Compression::TAG_NONE);
+        frame.extend_from_slice(&bytes);
+        return WsMessage::Binary(frame);
+    }
+    frame.push(Compression::TAG_GZIP);
+    let mut encoder = GzEncoder::new(frame, flate2::Compression::fast());
+    // Writing to a `Vec` can't fail.
+    encoder.write_all(&bytes).unwrap();
+    WsMessage::Binary(encoder.finish().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn messages_are_sent_as_is_without_compression() {
+        let msg = encode_message(DataMessage::Text("hello".into()), Compression::None);
+        assert_eq!(msg, WsMessage::Text("hello".into()));
+        let msg = encode_message(DataMessage::Binary(vec![1, 2, 3]), Compression::None);
+        assert_eq!(msg, WsMessage::Binary(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn small_messages_are_tagged_but_not_compressed() {
+        let msg = encode_message(DataMessage::Text("hello".into()), Compression::Gzip);
+        assert_eq!(msg, WsMessage::Binary(b"\0hello".to_vec()));
+    }
+
+    #[test]
+    fn large_messages_are_compressed() {
+        let text = "a".repeat(MIN_COMPRESSED_SIZE);
+        let WsMessage::Binary(frame) = encode_message(DataMessage::Text(text.clone()), Compression::Gzip) else {
+            panic!("a compressed message isn't binary");
+        };
+        assert_eq!(frame[0], Compression::TAG_GZIP);
+        assert!(frame.len() < text.len());
+        let mut decompressed = String::new();
+        GzDecoder::new(&frame[1..]).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, text);
+    }
+
+    #[test]
+    fn compression_is_named_in_lowercase() {
+        assert_eq!(
+            serde_json::from_str::<Compression>(r#""gzip""#).unwrap(),
+            Compression::Gzip
+        );
+        assert_eq!(
+            serde_json::from_str::<Compression>(r#""none""#).unwrap(),
+            Compression::None
+        );
+    }
+}
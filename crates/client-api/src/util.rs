@@ -53,7 +53,8 @@ impl headers::Header for XForwardedFor {
     fn decode<'i, I: Iterator<Item = &'i HeaderValue>>(values: &mut I) -> Result<Self, headers::Error> {
         let val = values.next().ok_or_else(headers::Error::invalid)?;
         let val = val.to_str().map_err(|_| headers::Error::invalid())?;
-        let (first, _) = val.split_once(',').ok_or_else(headers::Error::invalid)?;
+        // The first address is that of the client, followed by those of any proxies in between.
+        let first = val.split(',').next().unwrap_or_default();
         let ip = first.trim().parse().map_err(|_| headers::Error::invalid())?;
         Ok(XForwardedFor(ip))
     }
@@ -123,18 +124,22 @@ impl NameOrAddress {
     }
 }
 
+impl From<String> for NameOrAddress {
+    fn from(s: String) -> Self {
+        if let Ok(addr) = Address::from_hex(&s) {
+            NameOrAddress::Address(addr)
+        } else {
+            NameOrAddress::Name(s)
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for NameOrAddress {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        String::deserialize(deserializer).map(|s| {
-            if let Ok(addr) = Address::from_hex(&s) {
-                NameOrAddress::Address(addr)
-            } else {
-                NameOrAddress::Name(s)
-            }
-        })
+        String::deserialize(deserializer).map(NameOrAddress::from)
     }
 }
 
@@ -0,0 +1,111 @@
+//! Guest identities, minted for clients that connect without a token.
+//!
+//! A guest's token expires after [`GuestConfig::token_ttl`], and the identity is forgotten with it,
+//! unless the guest is promoted to a durable identity first, keeping the same [`Identity`] value,
+//! so that the rows a module keyed on it stay theirs.
+//! Guests are minted at a limited rate for each IP address, see [`check_guest_rate_limit`],
+//! which is that of the peer of the connection, unless it's one of the [`GuestConfig::trusted_proxies`].
+//!
+//! [`Identity`]: crate::identity::Identity
+//! [`check_guest_rate_limit`]: crate::client::check_guest_rate_limit
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+/// How long a guest's token is valid, unless set otherwise.
+const DEFAULT_GUEST_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct GuestConfig {
+    /// Whether the clients that connect without a token are given guest identities,
+    /// rather than durable ones.
+    pub enabled: bool,
+    /// How long a guest's token is valid.
+    pub token_ttl: Duration,
+    /// The addresses of the proxies in front of this node,
+    /// whose `X-Forwarded-For` header gives the address of the client they forward.
+    ///
+    /// The header of any other peer is ignored, as it could claim to be any address.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for GuestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token_ttl: DEFAULT_GUEST_TOKEN_TTL,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl GuestConfig {
+    /// Reads the config from the `SPACETIMEDB_GUEST_IDENTITIES` environment variable, `true` or `false`,
+    /// `SPACETIMEDB_GUEST_TOKEN_TTL`, e.g. `12h`, and `SPACETIMEDB_TRUSTED_PROXIES`,
+    /// a comma-separated list of IP addresses.
+    ///
+    /// Those that aren't set, or are invalid, are left at their defaults.
+    fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            enabled: env_var("SPACETIMEDB_GUEST_IDENTITIES", str::parse).unwrap_or(default.enabled),
+            token_ttl: env_var("SPACETIMEDB_GUEST_TOKEN_TTL", humantime::parse_duration)
+                .filter(|ttl| !ttl.is_zero())
+                .unwrap_or(default.token_ttl),
+            trusted_proxies: env_var("SPACETIMEDB_TRUSTED_PROXIES", |value| {
+                value.split(',').map(|ip| ip.trim().parse::<IpAddr>()).collect()
+            })
+            .unwrap_or(default.trusted_proxies),
+        }
+    }
+
+    /// Returns the address of the client connected from `peer`, whose guests are counted against it.
+    ///
+    /// That's the address `forwarded` by `peer` in its `X-Forwarded-For` header if it's a trusted proxy,
+    /// and `peer` itself otherwise.
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded: Option<IpAddr>) -> Option<IpAddr> {
+        match peer {
+            Some(peer) if self.trusted_proxies.contains(&peer) => forwarded.or(Some(peer)),
+            _ => peer,
+        }
+    }
+}
+
+fn env_var<T, E: std::fmt::Display>(name: &str, parse: impl FnOnce(&str) -> Result<T, E>) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    parse(&value)
+        .map_err(|e| log::warn!("Ignoring invalid {name} {value:?}: {e}"))
+        .ok()
+}
+
+/// The guest config of this node, read from the environment.
+pub static GUEST_CONFIG: Lazy<GuestConfig> = Lazy::new(GuestConfig::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip() {
+        let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+        let config = GuestConfig {
+            trusted_proxies: vec![ip("10.0.0.1").unwrap()],
+            ..GuestConfig::default()
+        };
+        // A trusted proxy forwards the address of the client...
+        assert_eq!(config.client_ip(ip("10.0.0.1"), ip("203.0.113.7")), ip("203.0.113.7"));
+        assert_eq!(config.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+        // ...which any other peer can't claim.
+        assert_eq!(
+            config.client_ip(ip("198.51.100.2"), ip("203.0.113.7")),
+            ip("198.51.100.2")
+        );
+        assert_eq!(
+            GuestConfig::default().client_ip(ip("10.0.0.1"), ip("203.0.113.7")),
+            ip("10.0.0.1")
+        );
+        assert_eq!(config.client_ip(None, ip("203.0.113.7")), None);
+    }
+}
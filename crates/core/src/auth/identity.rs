@@ -1,3 +1,4 @@
+use crate::address::Address;
 use crate::identity::Identity;
 use jsonwebtoken::{decode, encode, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
@@ -15,6 +16,64 @@ pub struct SpacetimeIdentityClaims {
     #[serde_as(as = "serde_with::TimestampSeconds")]
     pub iat: SystemTime,
     pub exp: Option<u64>,
+    /// The id of the [`ApiToken`] this token was issued as, if it's a scoped token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<u64>,
+    /// The capabilities the token is restricted to.
+    /// A token without a scope has the full power of its identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<TokenScope>,
+    /// Whether the token is of a guest identity, which expires along with it unless promoted,
+    /// see [`guest`](super::guest).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub guest: bool,
+}
+
+/// The capabilities a scoped token is restricted to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenScope {
+    /// The databases the token may be used with, or any database if `None`.
+    #[serde(default)]
+    pub databases: Option<Vec<Address>>,
+    /// The reducers the token may call, or any reducer if `None`.
+    #[serde(default)]
+    pub reducers: Option<Vec<String>>,
+    #[serde(default)]
+    pub sql: SqlAccess,
+}
+
+impl TokenScope {
+    pub fn allows_database(&self, address: &Address) -> bool {
+        self.databases.as_ref().map_or(true, |dbs| dbs.contains(address))
+    }
+
+    pub fn allows_reducer(&self, reducer: &str) -> bool {
+        self.reducers
+            .as_ref()
+            .map_or(true, |rs| rs.iter().any(|r| r == reducer))
+    }
+}
+
+/// The SQL queries a scoped token may run.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlAccess {
+    #[default]
+    None,
+    /// Only queries that don't write to the database.
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A scoped token issued for an identity, as recorded in the control database.
+///
+/// The token itself isn't stored, only what's needed to check and revoke it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: u64,
+    pub identity: String,
+    pub scope: TokenScope,
+    pub revoked: bool,
 }
 
 /// Encode a JWT token using a private_key and an identity. Expiry is set in absolute seconds,
@@ -27,6 +86,30 @@ pub fn encode_token_with_expiry(
     private_key: &EncodingKey,
     identity: Identity,
     expiry: Option<u64>,
+) -> Result<String, JwtError> {
+    encode_claims(private_key, identity, expiry, None, false)
+}
+
+/// Encode a JWT token for the guest `identity`, which expires after `ttl`.
+pub fn encode_guest_token(private_key: &EncodingKey, identity: Identity, ttl: Duration) -> Result<String, JwtError> {
+    encode_claims(private_key, identity, Some(ttl.as_secs()), None, true)
+}
+
+/// Encode a JWT token for `identity` that only grants the capabilities of `token`'s scope.
+pub fn encode_scoped_token(
+    private_key: &EncodingKey,
+    identity: Identity,
+    token: &ApiToken,
+) -> Result<String, JwtError> {
+    encode_claims(private_key, identity, None, Some(token), false)
+}
+
+fn encode_claims(
+    private_key: &EncodingKey,
+    identity: Identity,
+    expiry: Option<u64>,
+    token: Option<&ApiToken>,
+    guest: bool,
 ) -> Result<String, JwtError> {
     let header = Header::new(jsonwebtoken::Algorithm::ES256);
 
@@ -42,6 +125,9 @@ pub fn encode_token_with_expiry(
         hex_identity: identity.to_hex(),
         iat: SystemTime::now(),
         exp: expiry,
+        token_id: token.map(|t| t.id),
+        scope: token.map(|t| t.scope.clone()),
+        guest,
     };
     encode(&header, &claims, private_key)
 }
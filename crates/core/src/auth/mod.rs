@@ -1 +1,3 @@
+pub mod guest;
 pub mod identity;
+pub mod oidc;
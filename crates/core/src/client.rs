@@ -5,10 +5,14 @@ mod client_connection;
 mod client_connection_index;
 mod message_handlers;
 pub mod messages;
+mod rate_limit;
+mod send_queue;
 
 pub use client_connection::{ClientClosed, ClientConnection, ClientConnectionSender, DataMessage, Protocol};
 pub use client_connection_index::ClientActorIndex;
 pub use message_handlers::MessageHandleError;
+pub use rate_limit::{check_guest_rate_limit, check_rate_limit, RateLimitConfig, RateLimited, RateLimiter};
+pub use send_queue::{Outgoing, SendQueueConfig, SendQueueReceiver, SlowClientPolicy};
 
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub struct ClientActorId {
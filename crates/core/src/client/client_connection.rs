@@ -1,12 +1,17 @@
 use std::ops::Deref;
+use std::sync::Arc;
 
+use crate::auth::identity::{SqlAccess, TokenScope};
+use crate::host::module_host::DatabaseUpdate;
 use crate::host::{ModuleHost, NoSuchModule, ReducerArgs, ReducerCallError, ReducerCallResult};
-use crate::protobuf::client_api::Subscribe;
+use crate::protobuf::client_api::{OneOffQuery, Subscribe, SubscribeQuery, UnsubscribeQuery};
 use crate::worker_metrics::{CONNECTED_CLIENTS, WEBSOCKET_SENT, WEBSOCKET_SENT_MSG_SIZE};
 use futures::prelude::*;
-use tokio::sync::mpsc;
 
-use super::messages::ServerMessage;
+use super::messages::{
+    CachedMessage, ServerMessage, SubscriptionSnapshot, SubscriptionUpdateMessage, TransactionUpdateMessage,
+};
+use super::send_queue::{self, SendQueue, SendQueueConfig, SendQueueReceiver, SEND_QUEUE_CONFIG};
 use super::{message_handlers, ClientActorId, MessageHandleError};
 
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
@@ -19,7 +24,11 @@ pub enum Protocol {
 pub struct ClientConnectionSender {
     pub id: ClientActorId,
     pub protocol: Protocol,
-    sendtx: mpsc::Sender<DataMessage>,
+    /// Whether the client wants the rows updated in place sent as the columns that changed.
+    pub column_diffs: bool,
+    /// Whether the client wants the rows matching its queries sent in chunks.
+    pub chunked_snapshots: bool,
+    queue: SendQueue,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -28,19 +37,84 @@ pub struct ClientClosed;
 
 impl ClientConnectionSender {
     pub fn dummy(id: ClientActorId, protocol: Protocol) -> Self {
-        let (sendtx, _) = mpsc::channel(1);
-        Self { id, protocol, sendtx }
+        Self::dummy_with_receiver(id, protocol).0
+    }
+
+    /// Like [`Self::dummy`], along with the receiving end of its queue, to see what the client is sent.
+    pub(crate) fn dummy_with_receiver(id: ClientActorId, protocol: Protocol) -> (Self, SendQueueReceiver) {
+        let (queue, rx) = send_queue::channel(id.identity, protocol, SendQueueConfig::default());
+        let sender = Self {
+            id,
+            protocol,
+            column_diffs: false,
+            chunked_snapshots: false,
+            queue,
+        };
+        (sender, rx)
     }
 
     pub fn send_message(&self, message: impl ServerMessage) -> impl Future<Output = Result<(), ClientClosed>> + '_ {
         self.send(message.serialize(self.protocol))
     }
 
+    /// Waits for room in the client's queue, and then queues `message`.
+    ///
+    /// This is for replies to the client's own requests,
+    /// while the updates of its subscriptions are queued without waiting, by [`Self::send_subscription_update`]
+    /// and [`Self::send_transaction_update`].
     pub async fn send(&self, message: DataMessage) -> Result<(), ClientClosed> {
         let bytes_len = message.len();
+        self.queue.send(self.id.identity, message).await?;
+        self.count_sent(bytes_len);
+        Ok(())
+    }
 
-        self.sendtx.send(message).await.map_err(|_| ClientClosed)?;
+    /// Queues the subscription update `message`, without waiting for room in the client's queue.
+    ///
+    /// If the client has fallen behind, the update is dropped or the client disconnected, as configured.
+    /// Unless disconnected, the client is sent the rows matched by its queries afresh once it catches up.
+    pub fn send_subscription_update(&self, message: impl ServerMessage) -> Result<(), ClientClosed> {
+        let message = message.serialize(self.protocol);
+        let bytes_len = message.len();
+        if self.queue.send_update(self.id.identity, message, None)? {
+            self.count_sent(bytes_len);
+        }
+        Ok(())
+    }
 
+    /// Queues every row matching the client's queries, as one subscription update, or in chunks if it asked for that,
+    /// like [`Self::send_subscription_update`].
+    pub fn send_snapshot(&self, database_update: DatabaseUpdate) -> Result<(), ClientClosed> {
+        if !self.chunked_snapshots {
+            return self.send_subscription_update(SubscriptionUpdateMessage { database_update });
+        }
+        self.queue
+            .send_snapshot(self.id.identity, SubscriptionSnapshot::new(database_update))?;
+        Ok(())
+    }
+
+    /// Queues the transaction update `message`, without waiting for room in the client's queue.
+    ///
+    /// If the client has fallen behind, the update is dropped, held back to be merged with those that follow,
+    /// or the client disconnected, as configured.
+    pub fn send_transaction_update(
+        &self,
+        message: &mut CachedMessage<TransactionUpdateMessage<'_>>,
+    ) -> Result<(), ClientClosed> {
+        let data = message.serialize(self.protocol);
+        let bytes_len = data.len();
+        // The updates of the rows as the columns that changed can't be merged.
+        let update = (!self.column_diffs).then(|| {
+            let TransactionUpdateMessage { event, database_update } = message.message();
+            (&**event, database_update)
+        });
+        if self.queue.send_update(self.id.identity, data, update)? {
+            self.count_sent(bytes_len);
+        }
+        Ok(())
+    }
+
+    fn count_sent(&self, bytes_len: usize) {
         WEBSOCKET_SENT
             .with_label_values(&[self.id.identity.to_hex().as_str()])
             .inc();
@@ -48,8 +122,6 @@ impl ClientConnectionSender {
         WEBSOCKET_SENT_MSG_SIZE
             .with_label_values(&[self.id.identity.to_hex().as_str()])
             .observe(bytes_len as f64);
-
-        Ok(())
     }
 }
 
@@ -58,7 +130,11 @@ impl ClientConnectionSender {
 pub struct ClientConnection {
     sender: ClientConnectionSender,
     pub database_instance_id: u64,
+    /// The module the client connected to. Its calls follow it when it's swapped for a new version,
+    /// but anything else should go to [`ModuleHost::current`].
     pub module: ModuleHost,
+    /// The capabilities of the token the client connected with, if it's a scoped token.
+    pub scope: Option<Arc<TokenScope>>,
 }
 
 impl Deref for ClientConnection {
@@ -102,15 +178,19 @@ impl From<Vec<u8>> for DataMessage {
 
 impl ClientConnection {
     /// Returns an error if ModuleHost closed
+    #[allow(clippy::too_many_arguments)]
     pub async fn spawn<F, Fut>(
         id: ClientActorId,
         protocol: Protocol,
+        column_diffs: bool,
+        chunked_snapshots: bool,
         database_instance_id: u64,
         module: ModuleHost,
+        scope: Option<TokenScope>,
         actor: F,
     ) -> Result<ClientConnection, NoSuchModule>
     where
-        F: FnOnce(ClientConnection, mpsc::Receiver<DataMessage>) -> Fut,
+        F: FnOnce(ClientConnection, SendQueueReceiver) -> Fut,
         Fut: Future<Output = ()> + Send + 'static,
     {
         // Add this client as a subscriber
@@ -119,14 +199,20 @@ impl ClientConnection {
         // them and stuff. Not right now though.
         module.call_identity_connected_disconnected(id.identity, true).await?;
 
-        // Buffer up to 64 client messages
-        let (sendtx, sendrx) = mpsc::channel::<DataMessage>(64);
+        let (queue, sendrx) = send_queue::channel(id.identity, protocol, *SEND_QUEUE_CONFIG);
 
-        let sender = ClientConnectionSender { id, protocol, sendtx };
+        let sender = ClientConnectionSender {
+            id,
+            protocol,
+            column_diffs,
+            chunked_snapshots,
+            queue,
+        };
         let this = Self {
             sender,
             database_instance_id,
             module,
+            scope: scope.map(Arc::new),
         };
 
         let actor_fut = actor(this.clone(), sendrx);
@@ -144,6 +230,7 @@ impl ClientConnection {
             sender: ClientConnectionSender::dummy(id, protocol),
             database_instance_id,
             module,
+            scope: None,
         }
     }
 
@@ -159,13 +246,58 @@ impl ClientConnection {
         message_handlers::handle(self, message.into())
     }
 
+    /// Returns whether the client's token allows it to call `reducer`.
+    pub fn allows_reducer(&self, reducer: &str) -> bool {
+        self.scope.as_ref().map_or(true, |scope| scope.allows_reducer(reducer))
+    }
+
+    /// Returns whether the client's token allows it to run SQL queries.
+    pub fn allows_sql(&self) -> bool {
+        self.scope.as_ref().map_or(true, |scope| scope.sql != SqlAccess::None)
+    }
+
     pub async fn call_reducer(&self, reducer: &str, args: ReducerArgs) -> Result<ReducerCallResult, ReducerCallError> {
         self.module
             .call_reducer(self.id.identity, Some(self.sender()), reducer, args)
             .await
     }
 
+    pub async fn call_reducers(
+        &self,
+        calls: Vec<(&str, ReducerArgs)>,
+    ) -> Result<Vec<Result<ReducerCallResult, ReducerCallError>>, NoSuchModule> {
+        self.module
+            .call_reducers(self.id.identity, Some(self.sender()), calls)
+            .await
+    }
+
     pub fn subscribe(&self, subscription: Subscribe) -> Result<(), NoSuchModule> {
-        self.module.subscription().add_subscriber(self.sender(), subscription)
+        self.module
+            .current()
+            .subscription()
+            .add_subscriber(self.sender(), subscription)
+    }
+
+    pub fn subscribe_query(&self, query: SubscribeQuery) -> Result<(), NoSuchModule> {
+        self.module
+            .current()
+            .subscription()
+            .add_named_query(self.sender(), query)
+    }
+
+    pub fn unsubscribe_query(&self, query: UnsubscribeQuery) -> Result<(), NoSuchModule> {
+        self.module
+            .current()
+            .subscription()
+            .remove_named_query(self.sender(), query.name)
+    }
+
+    /// Sends the client the rows matched by its queries afresh, after it fell behind on their updates.
+    pub fn resync(&self) -> Result<(), NoSuchModule> {
+        self.module.current().subscription().resync(self.sender())
+    }
+
+    pub fn one_off_query(&self, query: OneOffQuery) -> Result<(), NoSuchModule> {
+        self.module.current().subscription().one_off_query(self.sender(), query)
     }
 }
@@ -1,16 +1,21 @@
+use std::borrow::Cow;
 use std::time::Duration;
 
 use crate::host::module_host::{EventStatus, ModuleEvent, ModuleFunctionCall};
-use crate::host::{EnergyDiff, ReducerArgs, Timestamp};
+use crate::host::{EnergyDiff, ReducerArgs, ReducerOutcome, Timestamp};
 use crate::identity::Identity;
-use crate::protobuf::client_api::{message, FunctionCall, Message, Subscribe};
+use crate::protobuf::client_api::{
+    message, BatchCall, FunctionCall, Message, OneOffQuery, Subscribe, SubscribeQuery, UnsubscribeQuery,
+};
+use crate::util::new_trace_id;
 use crate::worker_metrics::{WEBSOCKET_REQUESTS, WEBSOCKET_REQUEST_MSG_SIZE};
 use bytes::Bytes;
 use bytestring::ByteString;
 use prost::Message as _;
+use tracing::Instrument;
 
-use super::messages::{ServerMessage, TransactionUpdateMessage};
-use super::{ClientConnection, DataMessage};
+use super::messages::{BatchCallResultMessage, OneOffQueryResultMessage, ServerMessage, TransactionUpdateMessage};
+use super::{check_rate_limit, ClientConnection, DataMessage};
 
 #[derive(thiserror::Error, Debug)]
 pub enum MessageHandleError {
@@ -39,9 +44,10 @@ pub async fn handle(client: &ClientConnection, message: DataMessage) -> Result<(
         .with_label_values(&[format!("{}", client.database_instance_id).as_str(), message_kind])
         .inc();
 
+    let span = tracing::info_span!("client_message", trace_id = %new_trace_id(), client = %client.id, message_kind);
     match message {
-        DataMessage::Text(message) => handle_text(client, message).await,
-        DataMessage::Binary(message_buf) => handle_binary(client, message_buf).await,
+        DataMessage::Text(message) => handle_text(client, message).instrument(span).await,
+        DataMessage::Binary(message_buf) => handle_binary(client, message_buf).instrument(span).await,
     }
 }
 
@@ -52,7 +58,17 @@ async fn handle_binary(client: &ClientConnection, message_buf: Vec<u8>) -> Resul
             let args = ReducerArgs::Bsatn(arg_bytes.into());
             DecodedMessage::Call { reducer, args }
         }
+        Some(message::Type::BatchCall(BatchCall { request_id, calls })) => {
+            let calls = calls
+                .into_iter()
+                .map(|FunctionCall { reducer, arg_bytes }| (Cow::Owned(reducer), ReducerArgs::Bsatn(arg_bytes.into())))
+                .collect();
+            DecodedMessage::BatchCall { request_id, calls }
+        }
         Some(message::Type::Subscribe(subscription)) => DecodedMessage::Subscribe(subscription),
+        Some(message::Type::SubscribeQuery(query)) => DecodedMessage::SubscribeQuery(query),
+        Some(message::Type::UnsubscribeQuery(query)) => DecodedMessage::UnsubscribeQuery(query),
+        Some(message::Type::OneOffQuery(query)) => DecodedMessage::OneOffQuery(query),
         _ => return Err(MessageHandleError::InvalidMessage),
     };
 
@@ -67,11 +83,33 @@ async fn handle_text(client: &ClientConnection, message: String) -> Result<(), M
         #[serde(rename = "call")]
         Call {
             #[serde(borrow, rename = "fn")]
-            func: std::borrow::Cow<'a, str>,
+            func: Cow<'a, str>,
             args: &'a serde_json::value::RawValue,
         },
+        #[serde(rename = "batch_call")]
+        BatchCall {
+            request_id: u32,
+            #[serde(borrow)]
+            calls: Vec<Call<'a>>,
+        },
         #[serde(rename = "subscribe")]
-        Subscribe { query_strings: Vec<String> },
+        Subscribe {
+            query_strings: Vec<String>,
+            #[serde(default)]
+            resume_from_tx_offset: u64,
+        },
+        #[serde(rename = "subscribe_query")]
+        SubscribeQuery { name: String, query_string: String },
+        #[serde(rename = "unsubscribe_query")]
+        UnsubscribeQuery { name: String },
+        #[serde(rename = "one_off_query")]
+        OneOffQuery { request_id: u32, query_string: String },
+    }
+    #[derive(serde::Deserialize)]
+    struct Call<'a> {
+        #[serde(borrow, rename = "fn")]
+        func: Cow<'a, str>,
+        args: &'a serde_json::value::RawValue,
     }
 
     let message = ByteString::from(message);
@@ -81,7 +119,31 @@ async fn handle_text(client: &ClientConnection, message: String) -> Result<(), M
             let args = ReducerArgs::Json(message.slice_ref(args.get()));
             DecodedMessage::Call { reducer: func, args }
         }
-        Message::Subscribe { query_strings } => DecodedMessage::Subscribe(Subscribe { query_strings }),
+        Message::BatchCall { request_id, calls } => {
+            let calls = calls
+                .into_iter()
+                .map(|call| (call.func, ReducerArgs::Json(message.slice_ref(call.args.get()))))
+                .collect();
+            DecodedMessage::BatchCall { request_id, calls }
+        }
+        Message::Subscribe {
+            query_strings,
+            resume_from_tx_offset,
+        } => DecodedMessage::Subscribe(Subscribe {
+            query_strings,
+            resume_from_tx_offset,
+        }),
+        Message::SubscribeQuery { name, query_string } => {
+            DecodedMessage::SubscribeQuery(SubscribeQuery { name, query_string })
+        }
+        Message::UnsubscribeQuery { name } => DecodedMessage::UnsubscribeQuery(UnsubscribeQuery { name }),
+        Message::OneOffQuery {
+            request_id,
+            query_string,
+        } => DecodedMessage::OneOffQuery(OneOffQuery {
+            request_id,
+            query_string,
+        }),
     };
 
     msg.handle(client).await?;
@@ -90,18 +152,85 @@ async fn handle_text(client: &ClientConnection, message: String) -> Result<(), M
 }
 
 enum DecodedMessage<'a> {
-    Call { reducer: &'a str, args: ReducerArgs },
+    Call {
+        reducer: &'a str,
+        args: ReducerArgs,
+    },
+    BatchCall {
+        request_id: u32,
+        calls: Vec<(Cow<'a, str>, ReducerArgs)>,
+    },
     Subscribe(Subscribe),
+    SubscribeQuery(SubscribeQuery),
+    UnsubscribeQuery(UnsubscribeQuery),
+    OneOffQuery(OneOffQuery),
 }
 
 impl DecodedMessage<'_> {
     async fn handle(self, client: &ClientConnection) -> Result<(), MessageExecutionError> {
         let res = match self {
+            DecodedMessage::Call { reducer, .. } if !client.allows_reducer(reducer) => Err((
+                Some(reducer),
+                anyhow::anyhow!("token is not allowed to call reducer {reducer}"),
+            )),
             DecodedMessage::Call { reducer, args } => {
-                let res = client.call_reducer(reducer, args).await;
-                res.map(drop).map_err(|e| (Some(reducer), e.into()))
+                let res = match check_rate_limit(client.id.identity, client.database_instance_id) {
+                    Ok(()) => client.call_reducer(reducer, args).await.map(drop).map_err(Into::into),
+                    Err(e) => Err(e.into()),
+                };
+                res.map_err(|e: anyhow::Error| (Some(reducer), e))
+            }
+            DecodedMessage::BatchCall { request_id, calls } => {
+                let (reducers, args): (Vec<_>, Vec<_>) = calls.into_iter().unzip();
+                // The calls the client may not make are refused in place, without running them.
+                let mut refused = Vec::with_capacity(reducers.len());
+                let mut to_run = Vec::new();
+                for (reducer, args) in reducers.iter().zip(args) {
+                    let checked = if !client.allows_reducer(reducer) {
+                        Err(format!("token is not allowed to call reducer {reducer}"))
+                    } else {
+                        check_rate_limit(client.id.identity, client.database_instance_id).map_err(|e| e.to_string())
+                    };
+                    match checked {
+                        Ok(()) => {
+                            to_run.push((&**reducer, args));
+                            refused.push(None);
+                        }
+                        Err(errmsg) => refused.push(Some(ReducerOutcome::Failed(errmsg))),
+                    }
+                }
+                match client.call_reducers(to_run).await {
+                    Ok(ran) => {
+                        let mut ran = ran.into_iter();
+                        let results = refused
+                            .into_iter()
+                            .map(|outcome| {
+                                outcome.unwrap_or_else(|| match ran.next().expect("a result for each call") {
+                                    Ok(res) => res.outcome,
+                                    Err(e) => ReducerOutcome::Failed(e.to_string()),
+                                })
+                            })
+                            .collect();
+                        // If the client is gone, there's no one to tell.
+                        let _ = client
+                            .send_message(BatchCallResultMessage { request_id, results })
+                            .await;
+                        Ok(())
+                    }
+                    Err(e) => Err((None, e.into())),
+                }
             }
             DecodedMessage::Subscribe(subscription) => client.subscribe(subscription).map_err(|e| (None, e.into())),
+            DecodedMessage::SubscribeQuery(query) => client.subscribe_query(query).map_err(|e| (None, e.into())),
+            DecodedMessage::UnsubscribeQuery(query) => client.unsubscribe_query(query).map_err(|e| (None, e.into())),
+            DecodedMessage::OneOffQuery(OneOffQuery { request_id, .. }) if !client.allows_sql() => {
+                let result = Err("token is not allowed to run SQL queries".to_owned());
+                let _ = client
+                    .send_message(OneOffQueryResultMessage { request_id, result })
+                    .await;
+                Ok(())
+            }
+            DecodedMessage::OneOffQuery(query) => client.one_off_query(query).map_err(|e| (None, e.into())),
         };
         res.map_err(|(reducer, err)| MessageExecutionError {
             reducer: reducer.map(str::to_owned),
@@ -133,6 +262,8 @@ impl MessageExecutionError {
             status: EventStatus::Failed(format!("{:#}", self.err)),
             energy_quanta_used: EnergyDiff::ZERO,
             host_execution_duration: Duration::ZERO,
+            emitted_events: Vec::new(),
+            return_value: None,
         }
     }
 }
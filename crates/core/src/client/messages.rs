@@ -1,9 +1,20 @@
+use std::collections::VecDeque;
+
 use prost::Message as _;
+use spacetimedb_lib::relation::MemTable;
+use spacetimedb_sats::buffer::BufWriter;
 
-use crate::host::module_host::{DatabaseUpdate, EventStatus, ModuleEvent};
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, EventStatus, ModuleEvent, TableOp};
+use crate::host::ReducerOutcome;
 use crate::identity::Identity;
-use crate::json::client_api::{EventJson, FunctionCallJson, IdentityTokenJson, MessageJson, TransactionUpdateJson};
-use crate::protobuf::client_api::{event, message, Event, FunctionCall, IdentityToken, Message, TransactionUpdate};
+use crate::json::client_api::{
+    BatchCallResultJson, CallResultJson, EmittedEventJson, EventJson, FunctionCallJson, IdentityTokenJson, MessageJson,
+    OneOffQueryResultJson, StmtResultJson, SubscriptionCaughtUpJson, TransactionUpdateJson,
+};
+use crate::protobuf::client_api::{
+    event, message, BatchCallResult, CallResult, EmittedEvent, Event, FunctionCall, IdentityToken, Message,
+    OneOffQueryResult, OneOffTable, SubscriptionCaughtUp, TransactionUpdate,
+};
 
 use super::{DataMessage, Protocol};
 
@@ -66,6 +77,15 @@ impl ServerMessage for TransactionUpdateMessage<'_> {
             },
             energy_quanta_used: event.energy_quanta_used.0,
             message: errmsg,
+            emitted_events: event
+                .emitted_events
+                .iter()
+                .map(|emitted| EmittedEventJson {
+                    name: emitted.name.clone(),
+                    data: emitted.value.clone(),
+                })
+                .collect(),
+            return_value: event.return_value.clone(),
         };
 
         let subscription_update = database_update.into_json();
@@ -94,6 +114,27 @@ impl ServerMessage for TransactionUpdateMessage<'_> {
             message: errmsg,
             energy_quanta_used: event.energy_quanta_used.0 as i64,
             host_execution_duration_micros: event.host_execution_duration.as_micros() as u64,
+            emitted_events: event
+                .emitted_events
+                .iter()
+                .map(|emitted| {
+                    let mut data = Vec::new();
+                    emitted.value.encode(&mut data);
+                    EmittedEvent {
+                        name: emitted.name.clone(),
+                        data,
+                    }
+                })
+                .collect(),
+            return_value: event
+                .return_value
+                .as_ref()
+                .map(|value| {
+                    let mut data = Vec::new();
+                    value.encode(&mut data);
+                    data
+                })
+                .unwrap_or_default(),
         };
 
         let subscription_update = database_update.into_protobuf();
@@ -142,6 +183,283 @@ impl ServerMessage for SubscriptionUpdateMessage {
     }
 }
 
+/// The end of the updates sent to a client that resumed its subscription, bringing it to `tx_offset`.
+pub struct SubscriptionResumedMessage {
+    pub tx_offset: u64,
+}
+
+impl ServerMessage for SubscriptionResumedMessage {
+    fn serialize_text(self) -> MessageJson {
+        let mut update = DatabaseUpdate::default().into_json();
+        update.tx_offset = Some(self.tx_offset);
+        update.resumed = true;
+        MessageJson::SubscriptionUpdate(update)
+    }
+
+    fn serialize_binary(self) -> Message {
+        let mut update = DatabaseUpdate::default().into_protobuf();
+        update.tx_offset = self.tx_offset;
+        update.resumed = true;
+        Message {
+            r#type: Some(message::Type::SubscriptionUpdate(update)),
+        }
+    }
+}
+
+/// The size, in bytes of the rows' BSATN encoding, that each chunk of a [`SubscriptionSnapshot`] is filled up to.
+pub const SNAPSHOT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// The rows matching a client's queries, sent as a series of chunks of about [`SNAPSHOT_CHUNK_SIZE`] each,
+/// followed by a caught up marker, to clients that asked for them in chunks.
+///
+/// The chunks are only serialized as they're sent,
+/// so that the whole of a large snapshot isn't held serialized per client.
+#[derive(Debug)]
+pub struct SubscriptionSnapshot {
+    /// The tables yet to be sent, the first of which may have been sent in part.
+    ///
+    /// The rows of each table are reversed, so that each chunk is split off the end.
+    tables: VecDeque<DatabaseTableUpdate>,
+    /// The tx offset of the snapshot, sent with the caught up marker.
+    tx_offset: Option<u64>,
+    caught_up: bool,
+}
+
+impl SubscriptionSnapshot {
+    pub fn new(database_update: DatabaseUpdate) -> Self {
+        let tables = database_update
+            .tables
+            .into_iter()
+            .map(|mut table| {
+                table.ops.reverse();
+                table
+            })
+            .collect();
+        Self {
+            tables,
+            tx_offset: database_update.tx_offset,
+            caught_up: false,
+        }
+    }
+
+    /// Returns the next chunk, or the caught up marker after the last one, or `None` once that's been returned.
+    pub fn next_message(&mut self, protocol: Protocol) -> Option<DataMessage> {
+        if !self.tables.is_empty() {
+            let database_update = self.next_chunk();
+            return Some(SubscriptionChunkMessage { database_update }.serialize(protocol));
+        }
+        if self.caught_up {
+            return None;
+        }
+        self.caught_up = true;
+        let tx_offset = self.tx_offset;
+        Some(SubscriptionCaughtUpMessage { tx_offset }.serialize(protocol))
+    }
+
+    fn next_chunk(&mut self) -> DatabaseUpdate {
+        let mut tables = Vec::new();
+        let mut size = 0;
+        while size < SNAPSHOT_CHUNK_SIZE {
+            let Some(table) = self.tables.front_mut() else {
+                break;
+            };
+            let taken = table
+                .ops
+                .iter()
+                .rev()
+                .take_while(|op| {
+                    if size >= SNAPSHOT_CHUNK_SIZE {
+                        return false;
+                    }
+                    size += encoded_len(op);
+                    true
+                })
+                .count();
+            if taken == table.ops.len() {
+                let mut table = self.tables.pop_front().unwrap();
+                table.ops.reverse();
+                tables.push(table);
+            } else {
+                let mut ops = table.ops.split_off(table.ops.len() - taken);
+                ops.reverse();
+                tables.push(DatabaseTableUpdate {
+                    table_id: table.table_id,
+                    table_name: table.table_name.clone(),
+                    ops,
+                    updates: Vec::new(),
+                });
+            }
+        }
+        DatabaseUpdate {
+            tables,
+            tx_offset: None,
+        }
+    }
+}
+
+/// Returns the size of the BSATN encoding of the row of `op`, and its `row_pk`.
+fn encoded_len(op: &TableOp) -> usize {
+    struct CountWriter(usize);
+    impl BufWriter for CountWriter {
+        fn put_slice(&mut self, slice: &[u8]) {
+            self.0 += slice.len();
+        }
+    }
+    let mut count = CountWriter(op.row_pk.len());
+    op.row.encode(&mut count);
+    count.0
+}
+
+struct SubscriptionChunkMessage {
+    database_update: DatabaseUpdate,
+}
+
+impl ServerMessage for SubscriptionChunkMessage {
+    fn serialize_text(self) -> MessageJson {
+        MessageJson::SubscriptionChunk(self.database_update.into_json())
+    }
+
+    fn serialize_binary(self) -> Message {
+        Message {
+            r#type: Some(message::Type::SubscriptionChunk(self.database_update.into_protobuf())),
+        }
+    }
+}
+
+struct SubscriptionCaughtUpMessage {
+    tx_offset: Option<u64>,
+}
+
+impl ServerMessage for SubscriptionCaughtUpMessage {
+    fn serialize_text(self) -> MessageJson {
+        let tx_offset = self.tx_offset;
+        MessageJson::SubscriptionCaughtUp(SubscriptionCaughtUpJson { tx_offset })
+    }
+
+    fn serialize_binary(self) -> Message {
+        let tx_offset = self.tx_offset.unwrap_or(0);
+        Message {
+            r#type: Some(message::Type::SubscriptionCaughtUp(SubscriptionCaughtUp { tx_offset })),
+        }
+    }
+}
+
+/// The outcomes of the reducers called by a `BatchCall`, in the order they were requested.
+pub struct BatchCallResultMessage {
+    pub request_id: u32,
+    pub results: Vec<ReducerOutcome>,
+}
+
+impl ServerMessage for BatchCallResultMessage {
+    fn serialize_text(self) -> MessageJson {
+        let results = self
+            .results
+            .into_iter()
+            .map(|outcome| {
+                let (status, message) = match outcome {
+                    ReducerOutcome::Committed => ("committed", String::new()),
+                    ReducerOutcome::Failed(errmsg) => ("failed", errmsg),
+                    ReducerOutcome::BudgetExceeded => ("out_of_energy", String::new()),
+                };
+                CallResultJson {
+                    status: status.to_string(),
+                    message,
+                }
+            })
+            .collect();
+        MessageJson::BatchCallResult(BatchCallResultJson {
+            request_id: self.request_id,
+            results,
+        })
+    }
+
+    fn serialize_binary(self) -> Message {
+        let results = self
+            .results
+            .into_iter()
+            .map(|outcome| {
+                let (status, message) = match outcome {
+                    ReducerOutcome::Committed => (event::Status::Committed, String::new()),
+                    ReducerOutcome::Failed(errmsg) => (event::Status::Failed, errmsg),
+                    ReducerOutcome::BudgetExceeded => (event::Status::OutOfEnergy, String::new()),
+                };
+                CallResult {
+                    status: status.into(),
+                    message,
+                }
+            })
+            .collect();
+        Message {
+            r#type: Some(message::Type::BatchCallResult(BatchCallResult {
+                request_id: self.request_id,
+                results,
+            })),
+        }
+    }
+}
+
+pub struct OneOffQueryResultMessage {
+    pub request_id: u32,
+    /// The results of the queries, or the message with which they failed.
+    pub result: Result<Vec<MemTable>, String>,
+}
+
+impl ServerMessage for OneOffQueryResultMessage {
+    fn serialize_text(self) -> MessageJson {
+        let (error, results) = match self.result {
+            Ok(results) => (None, results),
+            Err(errmsg) => (Some(errmsg), Vec::new()),
+        };
+        let results = results
+            .into_iter()
+            .map(|result| StmtResultJson {
+                schema: result.head.ty(),
+                rows: result.data.into_iter().map(|x| x.elements).collect(),
+            })
+            .collect();
+        MessageJson::OneOffQueryResult(OneOffQueryResultJson {
+            request_id: self.request_id,
+            error,
+            results,
+        })
+    }
+
+    fn serialize_binary(self) -> Message {
+        let (error, results) = match self.result {
+            Ok(results) => (String::new(), results),
+            Err(errmsg) => (errmsg, Vec::new()),
+        };
+        let tables = results
+            .into_iter()
+            .map(|result| {
+                let mut schema = Vec::new();
+                result.head.ty().encode(&mut schema);
+                let rows = result
+                    .data
+                    .into_iter()
+                    .map(|row| {
+                        let mut row_bytes = Vec::new();
+                        row.encode(&mut row_bytes);
+                        row_bytes
+                    })
+                    .collect();
+                OneOffTable {
+                    table_name: result.head.table_name,
+                    schema,
+                    rows,
+                }
+            })
+            .collect();
+        Message {
+            r#type: Some(message::Type::OneOffQueryResult(OneOffQueryResult {
+                request_id: self.request_id,
+                error,
+                tables,
+            })),
+        }
+    }
+}
+
 pub struct CachedMessage<M> {
     msg: M,
     text: Option<String>,
@@ -156,6 +474,10 @@ impl<M> CachedMessage<M> {
             binary: None,
         }
     }
+
+    pub fn message(&self) -> &M {
+        &self.msg
+    }
 }
 
 impl<M> CachedMessage<M>
@@ -177,3 +499,82 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::product;
+
+    #[test]
+    fn test_batch_call_result() {
+        let results = || BatchCallResultMessage {
+            request_id: 7,
+            results: vec![
+                ReducerOutcome::Committed,
+                ReducerOutcome::Failed("no such person".into()),
+            ],
+        };
+
+        let json = serde_json::to_value(results().serialize_text()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"BatchCallResult": {"request_id": 7, "results": [
+                {"status": "committed", "message": ""},
+                {"status": "failed", "message": "no such person"},
+            ]}})
+        );
+
+        let Some(message::Type::BatchCallResult(result)) = results().serialize_binary().r#type else {
+            panic!("expected a batch call result");
+        };
+        assert_eq!(result.request_id, 7);
+        let statuses: Vec<_> = result.results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [event::Status::Committed as i32, event::Status::Failed as i32]
+        );
+        assert_eq!(result.results[1].message, "no such person");
+    }
+
+    #[test]
+    fn test_snapshot_chunks() {
+        // Rows big enough that two fill a chunk.
+        let row = |i: u32| product![i, "x".repeat(SNAPSHOT_CHUNK_SIZE / 2)];
+        let table = |table_id, rows: std::ops::Range<u32>| DatabaseTableUpdate {
+            table_id,
+            table_name: format!("table{table_id}"),
+            ops: rows
+                .map(|i| TableOp {
+                    op_type: 1,
+                    row_pk: i.to_le_bytes().to_vec(),
+                    row: row(i),
+                })
+                .collect(),
+            updates: Vec::new(),
+        };
+        let mut snapshot = SubscriptionSnapshot::new(DatabaseUpdate {
+            tables: vec![table(0, 0..3), table(1, 3..4)],
+            tx_offset: None,
+        });
+
+        let mut chunks = Vec::new();
+        while !snapshot.tables.is_empty() {
+            let chunk = snapshot.next_chunk();
+            let rows: Vec<_> = chunk
+                .tables
+                .iter()
+                .map(|table| {
+                    (
+                        table.table_id,
+                        table.ops.iter().map(|op| op.row_pk[0]).collect::<Vec<_>>(),
+                    )
+                })
+                .collect();
+            chunks.push(rows);
+        }
+        assert_eq!(chunks, [vec![(0, vec![0, 1])], vec![(0, vec![2]), (1, vec![3])]]);
+
+        assert!(snapshot.next_message(Protocol::Text).is_some());
+        assert!(snapshot.next_message(Protocol::Text).is_none());
+    }
+}
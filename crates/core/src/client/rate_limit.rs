@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::identity::Identity;
+use crate::worker_metrics::{GUESTS_RATE_LIMITED, REDUCER_CALLS_RATE_LIMITED};
+
+/// The limit on the reducer calls each identity can make to each database.
+#[derive(Debug, Copy, Clone)]
+pub struct RateLimitConfig {
+    /// How many calls are allowed per second, on average.
+    pub calls_per_second: f64,
+    /// How many calls can be made at once, after a period of inactivity.
+    pub burst: f64,
+}
+
+impl RateLimitConfig {
+    /// Reads the limit from the `SPACETIMEDB_REDUCER_RATE_LIMIT` environment variable,
+    /// in calls per second, and `SPACETIMEDB_REDUCER_RATE_BURST`,
+    /// which defaults to one second's worth of calls.
+    ///
+    /// Returns `None`, i.e., no limit, if the rate isn't set or isn't a positive number.
+    fn from_env() -> Option<Self> {
+        Self::from_env_vars("SPACETIMEDB_REDUCER_RATE_LIMIT", "SPACETIMEDB_REDUCER_RATE_BURST")
+    }
+
+    /// Reads the limit from the environment variables `rate_var`, in calls per second,
+    /// and `burst_var`, which defaults to one second's worth of calls.
+    fn from_env_vars(rate_var: &str, burst_var: &str) -> Option<Self> {
+        let parse = |var: &str| {
+            let value = std::env::var(var).ok()?;
+            match value.parse::<f64>() {
+                Ok(n) if n > 0.0 => Some(n),
+                _ => {
+                    log::warn!("Ignoring invalid {var} {value:?}");
+                    None
+                }
+            }
+        };
+        let calls_per_second = parse(rate_var)?;
+        let burst = parse(burst_var).unwrap_or(calls_per_second).max(1.0);
+        Some(Self {
+            calls_per_second,
+            burst,
+        })
+    }
+}
+
+/// A reducer call was refused because the caller exceeded its rate limit.
+#[derive(thiserror::Error, Debug, Copy, Clone)]
+#[error("rate limited, retry after {}ms", retry_after.as_millis())]
+pub struct RateLimited {
+    /// How long until the call would be allowed.
+    pub retry_after: Duration,
+}
+
+/// A token bucket, holding the calls that can still be made right away.
+#[derive(Debug, Copy, Clone)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn full(config: &RateLimitConfig, now: Instant) -> Self {
+        Self {
+            tokens: config.burst,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, config: &RateLimitConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.calls_per_second).min(config.burst);
+        self.refilled_at = now;
+    }
+
+    /// Takes a token for a call at `now`, or returns how long until one is available.
+    fn take(&mut self, config: &RateLimitConfig, now: Instant) -> Result<(), Duration> {
+        self.refill(config, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / config.calls_per_second))
+        }
+    }
+
+    fn is_full(&self, config: &RateLimitConfig) -> bool {
+        self.tokens >= config.burst
+    }
+}
+
+/// Past this many buckets, the full ones are dropped, as they're the same as new ones.
+const MAX_IDLE_BUCKETS: usize = 4096;
+
+/// The most buckets a limiter keeps, past which new keys are refused until some can be dropped,
+/// so that a flood of keys, e.g. spoofed addresses, can't grow it without bound.
+const MAX_BUCKETS: usize = 65536;
+
+/// How often a limiter that's at [`MAX_BUCKETS`] looks for buckets to drop.
+const FULL_PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The buckets of a [`KeyedRateLimiter`].
+struct Buckets<K> {
+    buckets: HashMap<K, Bucket>,
+    /// How many buckets there can be before the full ones are dropped,
+    /// twice as many as were left the last time, so that dropping them is amortized over the inserts.
+    prune_at: usize,
+    /// When the full buckets were last dropped.
+    pruned_at: Instant,
+}
+
+impl<K: Eq + Hash> Buckets<K> {
+    fn new(now: Instant) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            prune_at: MAX_IDLE_BUCKETS,
+            pruned_at: now,
+        }
+    }
+
+    /// Makes room for the bucket of a new key, returning how long until there may be some if there's none.
+    fn make_room(&mut self, config: &RateLimitConfig, now: Instant) -> Result<(), Duration> {
+        let len = self.buckets.len();
+        let at_cap = len >= MAX_BUCKETS;
+        let since_pruned = now.saturating_duration_since(self.pruned_at);
+        if len >= self.prune_at && (!at_cap || since_pruned >= FULL_PRUNE_INTERVAL) {
+            self.buckets.retain(|_, bucket| {
+                bucket.refill(config, now);
+                !bucket.is_full(config)
+            });
+            self.prune_at = (self.buckets.len() * 2).clamp(MAX_IDLE_BUCKETS, MAX_BUCKETS);
+            self.pruned_at = now;
+        }
+        if self.buckets.len() >= MAX_BUCKETS {
+            let since_pruned = now.saturating_duration_since(self.pruned_at);
+            return Err(FULL_PRUNE_INTERVAL
+                .saturating_sub(since_pruned)
+                .max(Duration::from_millis(1)));
+        }
+        Ok(())
+    }
+}
+
+/// Limits what each `K` does to the same rate, with a bucket of its own.
+struct KeyedRateLimiter<K> {
+    config: Option<RateLimitConfig>,
+    buckets: Mutex<Buckets<K>>,
+}
+
+impl<K: Eq + Hash> KeyedRateLimiter<K> {
+    fn new(config: Option<RateLimitConfig>) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(Buckets::new(Instant::now())),
+        }
+    }
+
+    /// Counts an action of `key`, returning how long until it'd be allowed if `key` has exceeded its limit,
+    /// or if the limiter has no room for the bucket of a new `key`.
+    fn take(&self, key: K) -> Result<(), Duration> {
+        let Some(config) = &self.config else {
+            return Ok(());
+        };
+        self.take_at(config, key, Instant::now())
+    }
+
+    fn take_at(&self, config: &RateLimitConfig, key: K, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock();
+        if !buckets.buckets.contains_key(&key) {
+            buckets.make_room(config, now)?;
+        }
+        buckets
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::full(config, now))
+            .take(config, now)
+    }
+}
+
+/// Limits the reducer calls of each identity to each database instance on this node.
+pub struct RateLimiter {
+    limiter: KeyedRateLimiter<(Identity, u64)>,
+}
+
+/// The limiter shared by the HTTP and WebSocket APIs, configured from the environment.
+static RATE_LIMITER: Lazy<RateLimiter> = Lazy::new(|| RateLimiter::new(RateLimitConfig::from_env()));
+
+impl RateLimiter {
+    pub fn new(config: Option<RateLimitConfig>) -> Self {
+        Self {
+            limiter: KeyedRateLimiter::new(config),
+        }
+    }
+
+    /// Counts a call by `caller_identity` to the database instance `instance_id`,
+    /// returning an error if the caller has exceeded its limit.
+    pub fn check(&self, caller_identity: Identity, instance_id: u64) -> Result<(), RateLimited> {
+        self.limiter
+            .take((caller_identity, instance_id))
+            .map_err(|retry_after| {
+                REDUCER_CALLS_RATE_LIMITED
+                    .with_label_values(&[&instance_id.to_string()])
+                    .inc();
+                RateLimited { retry_after }
+            })
+    }
+}
+
+/// The limit on the guest identities minted for each IP address, unless set otherwise:
+/// ten at once, and then one a minute.
+const DEFAULT_GUEST_RATE_LIMIT: RateLimitConfig = RateLimitConfig {
+    calls_per_second: 1.0 / 60.0,
+    burst: 10.0,
+};
+
+/// Limits the guest identities minted for each IP address,
+/// configured from `SPACETIMEDB_GUEST_RATE_LIMIT`, in guests per second, and `SPACETIMEDB_GUEST_RATE_BURST`.
+///
+/// The guests of clients whose address isn't known share a single limit.
+static GUEST_RATE_LIMITER: Lazy<KeyedRateLimiter<Option<IpAddr>>> = Lazy::new(|| {
+    let config = RateLimitConfig::from_env_vars("SPACETIMEDB_GUEST_RATE_LIMIT", "SPACETIMEDB_GUEST_RATE_BURST");
+    KeyedRateLimiter::new(Some(config.unwrap_or(DEFAULT_GUEST_RATE_LIMIT)))
+});
+
+/// Counts the minting of a guest identity for a client at `ip`, if known, against the limit on guests.
+pub fn check_guest_rate_limit(ip: Option<IpAddr>) -> Result<(), RateLimited> {
+    GUEST_RATE_LIMITER.take(ip).map_err(|retry_after| {
+        GUESTS_RATE_LIMITED.inc();
+        RateLimited { retry_after }
+    })
+}
+
+/// Counts a call by `caller_identity` to the database instance `instance_id`
+/// against the node's rate limit, if any.
+///
+/// This should be checked before scheduling a reducer requested by a client,
+/// but not for calls made by the host itself, like scheduled reducers.
+pub fn check_rate_limit(caller_identity: Identity, instance_id: u64) -> Result<(), RateLimited> {
+    RATE_LIMITER.check(caller_identity, instance_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let config = RateLimitConfig {
+            calls_per_second: 2.0,
+            burst: 3.0,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::full(&config, start);
+        for _ in 0..3 {
+            assert!(bucket.take(&config, start).is_ok());
+        }
+        assert_eq!(bucket.take(&config, start), Err(Duration::from_millis(500)));
+
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.take(&config, later).is_ok());
+        assert!(bucket.take(&config, later).is_err());
+
+        let much_later = later + Duration::from_secs(60);
+        bucket.refill(&config, much_later);
+        assert!(bucket.is_full(&config));
+    }
+
+    #[test]
+    fn test_keyed() {
+        let config = RateLimitConfig {
+            calls_per_second: 1.0 / 60.0,
+            burst: 2.0,
+        };
+        let limiter = KeyedRateLimiter::new(Some(config));
+        let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+        assert!(limiter.take(ip("10.0.0.1")).is_ok());
+        assert!(limiter.take(ip("10.0.0.1")).is_ok());
+        assert!(limiter.take(ip("10.0.0.1")).is_err());
+        // Each key has a bucket of its own.
+        assert!(limiter.take(ip("10.0.0.2")).is_ok());
+        assert!(limiter.take(None).is_ok());
+    }
+
+    #[test]
+    fn test_bucket_cap() {
+        let config = RateLimitConfig {
+            calls_per_second: 1.0,
+            burst: 2.0,
+        };
+        let limiter = KeyedRateLimiter::new(Some(config));
+        let start = Instant::now();
+        // Each key is left with a bucket that isn't full, so none can be dropped...
+        for key in 0..MAX_BUCKETS {
+            assert!(limiter.take_at(&config, key, start).is_ok());
+        }
+        assert!(limiter.buckets.lock().buckets.len() <= MAX_BUCKETS);
+        // ...and a new key is refused, while the known ones keep their limit.
+        assert!(limiter.take_at(&config, MAX_BUCKETS, start).is_err());
+        assert!(limiter.take_at(&config, 0, start).is_ok());
+
+        // Once the buckets have refilled, they're dropped to make room.
+        let later = start + Duration::from_secs(2);
+        assert!(limiter.take_at(&config, MAX_BUCKETS, later).is_ok());
+        assert_eq!(limiter.buckets.lock().buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_limited_calls_are_counted_per_instance() {
+        let limiter = RateLimiter::new(Some(RateLimitConfig {
+            calls_per_second: 1.0 / 60.0,
+            burst: 1.0,
+        }));
+        let instance_id = u64::MAX;
+        let counter = REDUCER_CALLS_RATE_LIMITED.with_label_values(&[&instance_id.to_string()]);
+        let before = counter.get();
+        assert!(limiter.check(Identity::__dummy(), instance_id).is_ok());
+        assert!(limiter.check(Identity::__dummy(), instance_id).is_err());
+        assert_eq!(counter.get(), before + 1);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limiter = RateLimiter::new(None);
+        for _ in 0..100 {
+            assert!(limiter.check(Identity::__dummy(), 0).is_ok());
+        }
+    }
+}
@@ -0,0 +1,434 @@
+//! The queue of the messages waiting to be sent to a client,
+//! and what's done when the client doesn't read them as fast as its subscriptions produce them.
+
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{self, error::TryRecvError, error::TrySendError};
+
+use super::messages::{ServerMessage, SubscriptionSnapshot, TransactionUpdateMessage};
+use super::{ClientClosed, DataMessage, Protocol};
+use crate::host::module_host::{DatabaseUpdate, ModuleEvent};
+use crate::identity::Identity;
+use crate::worker_metrics::{CLIENT_QUEUE_BYTES, CLIENT_QUEUE_OVERFLOWS, CLIENT_UPDATES_SKIPPED};
+
+/// What's done once a client has fallen behind, i.e., its queue has no room for an update.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SlowClientPolicy {
+    /// Close the connection, so that the client has to connect and subscribe again.
+    Disconnect,
+    /// Drop the updates until the client has caught up,
+    /// and then send it the rows matched by its queries afresh, as a subscription update.
+    DropAndResync,
+    /// Hold back the transaction updates until the client has caught up,
+    /// and then send them merged into one, with the event of the last of them.
+    ///
+    /// The clients sent column diffs, and those that fall behind on a subscription update,
+    /// are resynced instead, as their updates can't be merged.
+    Coalesce,
+}
+
+impl SlowClientPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Disconnect => "disconnect",
+            Self::DropAndResync => "drop_and_resync",
+            Self::Coalesce => "coalesce",
+        }
+    }
+}
+
+/// The limits on the messages waiting to be sent to each client, and what's done with a client exceeding them.
+#[derive(Debug, Copy, Clone)]
+pub struct SendQueueConfig {
+    /// How many messages may wait to be sent to a client.
+    pub max_len: usize,
+    /// How many bytes of messages may wait to be sent to a client.
+    ///
+    /// A single message larger than this is still queued, if the queue is empty.
+    pub max_bytes: usize,
+    pub policy: SlowClientPolicy,
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_len: 64,
+            max_bytes: 16 * 1024 * 1024,
+            policy: SlowClientPolicy::DropAndResync,
+        }
+    }
+}
+
+impl SendQueueConfig {
+    /// Reads the limits from the `SPACETIMEDB_CLIENT_QUEUE_LEN` and `SPACETIMEDB_CLIENT_QUEUE_BYTES`
+    /// environment variables, and the policy from `SPACETIMEDB_SLOW_CLIENT_POLICY`,
+    /// one of `disconnect`, `drop_and_resync` or `coalesce`.
+    ///
+    /// Those that aren't set, or are invalid, are left at their defaults.
+    fn from_env() -> Self {
+        let default = Self::default();
+        let parse = |var: &str, default: usize| {
+            let Ok(value) = std::env::var(var) else {
+                return default;
+            };
+            match value.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    log::warn!("Ignoring invalid {var} {value:?}");
+                    default
+                }
+            }
+        };
+        let policy = match std::env::var("SPACETIMEDB_SLOW_CLIENT_POLICY") {
+            Err(_) => default.policy,
+            Ok(value) => match &*value {
+                "disconnect" => SlowClientPolicy::Disconnect,
+                "drop_and_resync" => SlowClientPolicy::DropAndResync,
+                "coalesce" => SlowClientPolicy::Coalesce,
+                _ => {
+                    log::warn!("Ignoring invalid SPACETIMEDB_SLOW_CLIENT_POLICY {value:?}");
+                    default.policy
+                }
+            },
+        };
+        Self {
+            max_len: parse("SPACETIMEDB_CLIENT_QUEUE_LEN", default.max_len),
+            max_bytes: parse("SPACETIMEDB_CLIENT_QUEUE_BYTES", default.max_bytes),
+            policy,
+        }
+    }
+}
+
+/// The queue config of the clients of this node, read from the environment.
+pub(super) static SEND_QUEUE_CONFIG: Lazy<SendQueueConfig> = Lazy::new(SendQueueConfig::from_env);
+
+/// What's owed to a client that has fallen behind.
+#[derive(Debug)]
+enum Behind {
+    /// Its connection is to be closed.
+    Disconnect,
+    /// It's to be sent the rows matched by its queries afresh.
+    Resync,
+    /// It's to be sent the transaction updates held back, merged, if any.
+    Coalesced(Option<(ModuleEvent, DatabaseUpdate)>),
+}
+
+/// The state of a queue shared by its senders and its receiver.
+#[derive(Debug, Default)]
+struct Backlog {
+    /// The total size of the messages in the queue.
+    bytes: usize,
+    /// What's owed to the client, if it has fallen behind and not caught up since.
+    behind: Option<Behind>,
+}
+
+/// What waits in a client's queue.
+#[derive(Debug)]
+enum Queued {
+    Message(DataMessage),
+    /// A snapshot for a client that asked for it in chunks, which are serialized as they're sent.
+    ///
+    /// It isn't counted against the limit on bytes, as it's not serialized yet.
+    Snapshot(SubscriptionSnapshot),
+}
+
+impl Queued {
+    fn len(&self) -> usize {
+        match self {
+            Self::Message(message) => message.len(),
+            Self::Snapshot(_) => 0,
+        }
+    }
+}
+
+/// Returns a queue of messages to send to the client `identity`, limited by `config`.
+pub(super) fn channel(
+    identity: Identity,
+    protocol: Protocol,
+    config: SendQueueConfig,
+) -> (SendQueue, SendQueueReceiver) {
+    let (tx, rx) = mpsc::channel(config.max_len);
+    let backlog = Arc::new(Mutex::new(Backlog::default()));
+    let tx = SendQueue {
+        tx,
+        backlog: backlog.clone(),
+        config,
+    };
+    let rx = SendQueueReceiver {
+        rx,
+        backlog,
+        snapshot: None,
+        identity,
+        protocol,
+    };
+    (tx, rx)
+}
+
+/// The sending end of a client's queue.
+#[derive(Debug, Clone)]
+pub(super) struct SendQueue {
+    tx: mpsc::Sender<Queued>,
+    backlog: Arc<Mutex<Backlog>>,
+    config: SendQueueConfig,
+}
+
+impl SendQueue {
+    /// Waits for room in the queue, and then queues `message`, whether or not the client has fallen behind.
+    ///
+    /// This is for the replies to the client's own requests, which aren't subject to the policy.
+    pub async fn send(&self, identity: Identity, message: DataMessage) -> Result<(), ClientClosed> {
+        let permit = self.tx.reserve().await.map_err(|_| ClientClosed)?;
+        self.backlog.lock().bytes += message.len();
+        CLIENT_QUEUE_BYTES
+            .with_label_values(&[&identity.to_hex()])
+            .add(message.len() as i64);
+        permit.send(Queued::Message(message));
+        Ok(())
+    }
+
+    /// Queues the update `message` without waiting, if the client isn't behind and there's room for it,
+    /// returning whether it was queued.
+    ///
+    /// Otherwise, the client is behind, and the update is handled by the policy,
+    /// being merged into those held back if it's a transaction update that can be, i.e., `update` is given.
+    pub fn send_update(
+        &self,
+        identity: Identity,
+        message: DataMessage,
+        update: Option<(&ModuleEvent, &DatabaseUpdate)>,
+    ) -> Result<bool, ClientClosed> {
+        self.queue_update(identity, Queued::Message(message), update)
+    }
+
+    /// Queues `snapshot` without waiting, like a subscription update, returning whether it was queued.
+    pub fn send_snapshot(&self, identity: Identity, snapshot: SubscriptionSnapshot) -> Result<bool, ClientClosed> {
+        self.queue_update(identity, Queued::Snapshot(snapshot), None)
+    }
+
+    fn queue_update(
+        &self,
+        identity: Identity,
+        queued: Queued,
+        update: Option<(&ModuleEvent, &DatabaseUpdate)>,
+    ) -> Result<bool, ClientClosed> {
+        let mut backlog = self.backlog.lock();
+        if backlog.behind.is_none() {
+            let len = queued.len();
+            if backlog.bytes == 0 || backlog.bytes + len <= self.config.max_bytes {
+                match self.tx.try_send(queued) {
+                    Ok(()) => {
+                        backlog.bytes += len;
+                        CLIENT_QUEUE_BYTES
+                            .with_label_values(&[&identity.to_hex()])
+                            .add(len as i64);
+                        return Ok(true);
+                    }
+                    Err(TrySendError::Closed(_)) => return Err(ClientClosed),
+                    Err(TrySendError::Full(_)) => {}
+                }
+            }
+            CLIENT_QUEUE_OVERFLOWS
+                .with_label_values(&[&identity.to_hex(), self.config.policy.as_str()])
+                .inc();
+            backlog.behind = Some(match self.config.policy {
+                SlowClientPolicy::Disconnect => Behind::Disconnect,
+                SlowClientPolicy::DropAndResync => Behind::Resync,
+                SlowClientPolicy::Coalesce => Behind::Coalesced(None),
+            });
+        }
+
+        CLIENT_UPDATES_SKIPPED.with_label_values(&[&identity.to_hex()]).inc();
+        if let Some(Behind::Coalesced(held)) = &mut backlog.behind {
+            match update {
+                Some((event, update)) => merge_held(held, event, update),
+                // Only transaction updates can be merged, so the client has to be resynced.
+                None => backlog.behind = Some(Behind::Resync),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Merges the transaction update of `event` into those `held` back,
+/// keeping the events the reducers emitted in all of them.
+fn merge_held(held: &mut Option<(ModuleEvent, DatabaseUpdate)>, event: &ModuleEvent, update: &DatabaseUpdate) {
+    if let Some((held_event, held_update)) = held {
+        let mut emitted_events = std::mem::take(&mut held_event.emitted_events);
+        emitted_events.extend(event.emitted_events.iter().cloned());
+        *held_event = ModuleEvent {
+            emitted_events,
+            ..event.clone()
+        };
+        held_update.merge(update);
+        return;
+    }
+    *held = Some((event.clone(), update.clone()));
+}
+
+/// What a client's queue hands out to be sent to it.
+#[derive(Debug)]
+pub enum Outgoing {
+    /// A message to send to the client.
+    Message(DataMessage),
+    /// The client has caught up after falling behind,
+    /// and is to be sent the rows matched by its queries afresh, with [`ClientConnection::resync`].
+    ///
+    /// [`ClientConnection::resync`]: super::ClientConnection::resync
+    Resync,
+    /// The client has fallen behind, and its connection is to be closed.
+    Disconnect,
+}
+
+/// The receiving end of a client's queue.
+pub struct SendQueueReceiver {
+    rx: mpsc::Receiver<Queued>,
+    backlog: Arc<Mutex<Backlog>>,
+    /// The snapshot being sent, whose remaining chunks go out before anything queued after it.
+    snapshot: Option<SubscriptionSnapshot>,
+    identity: Identity,
+    protocol: Protocol,
+}
+
+impl SendQueueReceiver {
+    /// Receives what's next to be sent to the client,
+    /// or `None` once the queue has been closed and is empty, or every sender has been dropped.
+    ///
+    /// This is cancel safe.
+    pub async fn recv(&mut self) -> Option<Outgoing> {
+        loop {
+            // The chunks are serialized without the lock held, so as not to hold up the senders.
+            if let Some(snapshot) = &mut self.snapshot {
+                if let Some(message) = snapshot.next_message(self.protocol) {
+                    return Some(Outgoing::Message(message));
+                }
+                self.snapshot = None;
+            }
+            let queued = {
+                let mut backlog = self.backlog.lock();
+                if let Some(Behind::Disconnect) = backlog.behind {
+                    backlog.behind = None;
+                    drop(backlog);
+                    self.rx.close();
+                    return Some(Outgoing::Disconnect);
+                }
+                match self.rx.try_recv() {
+                    Ok(queued) => Some(queued),
+                    // The client has caught up.
+                    Err(TryRecvError::Empty) => match backlog.behind.take() {
+                        Some(Behind::Resync) => return Some(Outgoing::Resync),
+                        Some(Behind::Coalesced(Some((mut event, database_update)))) => {
+                            drop(backlog);
+                            let message = TransactionUpdateMessage {
+                                event: &mut event,
+                                database_update,
+                            };
+                            return Some(Outgoing::Message(message.serialize(self.protocol)));
+                        }
+                        Some(Behind::Coalesced(None) | Behind::Disconnect) | None => None,
+                    },
+                    Err(TryRecvError::Disconnected) => return None,
+                }
+            };
+            let queued = match queued {
+                Some(queued) => queued,
+                None => self.rx.recv().await?,
+            };
+            match queued {
+                Queued::Message(message) => {
+                    self.backlog.lock().bytes -= message.len();
+                    return Some(self.dequeued(message));
+                }
+                Queued::Snapshot(snapshot) => self.snapshot = Some(snapshot),
+            }
+        }
+    }
+
+    fn dequeued(&self, message: DataMessage) -> Outgoing {
+        CLIENT_QUEUE_BYTES
+            .with_label_values(&[&self.identity.to_hex()])
+            .sub(message.len() as i64);
+        Outgoing::Message(message)
+    }
+
+    /// Closes the queue, without dropping the messages already in it.
+    pub fn close(&mut self) {
+        self.rx.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(policy: SlowClientPolicy) -> (SendQueue, SendQueueReceiver) {
+        let config = SendQueueConfig {
+            max_len: 2,
+            max_bytes: 10,
+            policy,
+        };
+        channel(Identity::__dummy(), Protocol::Text, config)
+    }
+
+    fn update(queue: &SendQueue, text: &str) -> bool {
+        let message = DataMessage::Text(text.to_owned());
+        queue.send_update(Identity::__dummy(), message, None).unwrap()
+    }
+
+    async fn recv_text(rx: &mut SendQueueReceiver) -> String {
+        match rx.recv().await {
+            Some(Outgoing::Message(DataMessage::Text(text))) => text,
+            other => panic!("expected a text message, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let (tx, mut rx) = queue(SlowClientPolicy::DropAndResync);
+        // A message larger than the limit is queued on its own.
+        assert!(update(&tx, "a very long message"));
+        assert!(!update(&tx, "b"));
+        assert_eq!(recv_text(&mut rx).await, "a very long message");
+        assert!(matches!(rx.recv().await, Some(Outgoing::Resync)));
+
+        // Once caught up, the client is sent updates again, up to the limit on their number.
+        assert!(update(&tx, "c"));
+        assert!(update(&tx, "d"));
+        assert!(!update(&tx, "e"));
+        // Replies aren't dropped, and are sent ahead of the resync.
+        let reply = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.send(Identity::__dummy(), DataMessage::Text("f".to_owned())).await }
+        });
+        assert_eq!(recv_text(&mut rx).await, "c");
+        reply.await.unwrap().unwrap();
+        assert_eq!(recv_text(&mut rx).await, "d");
+        assert_eq!(recv_text(&mut rx).await, "f");
+        assert!(matches!(rx.recv().await, Some(Outgoing::Resync)));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let (tx, mut rx) = queue(SlowClientPolicy::DropAndResync);
+        let snapshot = SubscriptionSnapshot::new(DatabaseUpdate::default());
+        assert!(tx.send_snapshot(Identity::__dummy(), snapshot).unwrap());
+        assert!(update(&tx, "a"));
+        // The whole of the snapshot is sent before what was queued after it.
+        assert_eq!(recv_text(&mut rx).await, r#"{"SubscriptionCaughtUp":{}}"#);
+        assert_eq!(recv_text(&mut rx).await, "a");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let (tx, mut rx) = queue(SlowClientPolicy::Disconnect);
+        assert!(update(&tx, "a"));
+        assert!(update(&tx, "b"));
+        assert!(!update(&tx, "c"));
+        assert!(matches!(rx.recv().await, Some(Outgoing::Disconnect)));
+        // The messages already queued can still be drained, but no more are taken.
+        assert_eq!(recv_text(&mut rx).await, "a");
+        let message = DataMessage::Text("d".to_owned());
+        assert!(tx.send_update(Identity::__dummy(), message, None).is_err());
+    }
+}
@@ -1,9 +1,13 @@
 use crate::address::Address;
 
+use crate::auth::identity::{ApiToken, TokenScope};
+use crate::db::datastore::locking_tx_datastore::Quota;
 use crate::hash::hash_bytes;
 use crate::host::EnergyQuanta;
 use crate::identity::Identity;
-use crate::messages::control_db::{Database, DatabaseInstance, EnergyBalance, IdentityEmail, Node};
+use crate::messages::control_db::{
+    Database, DatabaseInstance, EnergyBalance, EnergyPricing, IdentityEmail, Node, Webhook,
+};
 use crate::stdb_path;
 
 use spacetimedb_lib::name::{DomainName, DomainParsingError, InsertDomainResult, RegisterTldResult, Tld, TldRef};
@@ -13,6 +17,7 @@ use spacetimedb_sats::bsatn;
 #[cfg(test)]
 mod tests;
 
+#[derive(Clone)]
 pub struct ControlDb {
     db: sled::Db,
 }
@@ -39,6 +44,8 @@ pub enum Error {
     ConnectionError(),
     #[error(transparent)]
     JSONDeserializationError(#[from] serde_json::Error),
+    #[error("identity {0} is already linked to another identity")]
+    IdentityAlreadyLinked(Identity),
 }
 
 impl From<sled::Error> for Error {
@@ -281,6 +288,100 @@ impl ControlDb {
         Ok(result)
     }
 
+    /// Records a new scoped token for `identity`, returning it with its freshly allocated id.
+    pub async fn insert_api_token(&self, identity: Identity, scope: TokenScope) -> Result<ApiToken> {
+        let tree = self.db.open_tree("api_tokens")?;
+        let token = ApiToken {
+            id: self.db.generate_id()?,
+            identity: identity.to_hex(),
+            scope,
+            revoked: false,
+        };
+        tree.insert(token.id.to_be_bytes(), serde_json::to_vec(&token)?)?;
+        Ok(token)
+    }
+
+    pub fn get_api_token(&self, id: u64) -> Result<Option<ApiToken>> {
+        let tree = self.db.open_tree("api_tokens")?;
+        match tree.get(id.to_be_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the scoped tokens issued for `identity`, including revoked ones.
+    pub fn get_api_tokens_for_identity(&self, identity: &Identity) -> Result<Vec<ApiToken>> {
+        let identity = identity.to_hex();
+        let tree = self.db.open_tree("api_tokens")?;
+        let mut tokens = Vec::new();
+        for entry in tree.iter() {
+            let (_, value) = entry?;
+            let token: ApiToken = serde_json::from_slice(&value)?;
+            if token.identity == identity {
+                tokens.push(token);
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Marks the token `id` as revoked, so that it's no longer accepted.
+    ///
+    /// Returns `false` if there is no such token.
+    pub async fn revoke_api_token(&self, id: u64) -> Result<bool> {
+        let tree = self.db.open_tree("api_tokens")?;
+        let Some(mut token) = self.get_api_token(id)? else {
+            return Ok(false);
+        };
+        token.revoked = true;
+        tree.insert(id.to_be_bytes(), serde_json::to_vec(&token)?)?;
+        Ok(true)
+    }
+
+    /// Links the identity `old` to `new`, which takes its place from now on,
+    /// along with that of any identities linked to `old` before.
+    ///
+    /// An identity can only be linked once, and not to one that has been linked itself.
+    pub async fn link_identity(&self, old: Identity, new: Identity) -> Result<()> {
+        let tree = self.db.open_tree("identity_links")?;
+        for identity in [old, new] {
+            if tree.contains_key(identity.as_bytes())? {
+                return Err(Error::IdentityAlreadyLinked(identity));
+            }
+        }
+        for entry in tree.iter() {
+            let (key, value) = entry?;
+            if *value == old.as_bytes()[..] {
+                tree.insert(key, new.as_bytes())?;
+            }
+        }
+        tree.insert(old.as_bytes(), new.as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the identity that `identity` has been linked to, if any.
+    pub fn get_linked_identity(&self, identity: &Identity) -> Result<Option<Identity>> {
+        let tree = self.db.open_tree("identity_links")?;
+        Ok(tree.get(identity.as_bytes())?.map(|value| Identity::from_slice(&value)))
+    }
+
+    /// Returns the identity of the user `subject` of the OpenID Connect provider `issuer`,
+    /// allocating a new one the first time they sign in.
+    pub async fn get_or_create_oidc_identity(&self, issuer: &str, subject: &str) -> Result<Identity> {
+        let tree = self.db.open_tree("oidc_identities")?;
+        let key = oidc_identity_key(issuer, subject);
+        if let Some(value) = tree.get(&key)? {
+            return Ok(Identity::from_slice(&value));
+        }
+        let identity = self.alloc_spacetime_identity().await?;
+        // The user may have signed in twice at once, in which case the first one wins.
+        match tree.compare_and_swap(&key, None as Option<&[u8]>, Some(identity.as_bytes()))? {
+            Ok(()) => Ok(identity),
+            Err(e) => Ok(Identity::from_slice(
+                &e.current.expect("an OIDC identity is never removed"),
+            )),
+        }
+    }
+
     pub async fn get_databases(&self) -> Result<Vec<Database>> {
         let tree = self.db.open_tree("database")?;
         let mut databases = Vec::new();
@@ -547,6 +648,76 @@ impl ControlDb {
         }
     }
 
+    /// Returns the pricing of `reducer` in the database at `address`,
+    /// falling back to the pricing of all of the database's reducers,
+    /// or `None` if neither was set.
+    pub fn get_energy_pricing(&self, address: &Address, reducer: &str) -> Result<Option<EnergyPricing>> {
+        let tree = self.db.open_tree("energy_pricing")?;
+        for key in [
+            energy_pricing_key(address, Some(reducer)),
+            energy_pricing_key(address, None),
+        ] {
+            if let Some(value) = tree.get(key.as_bytes())? {
+                return Ok(Some(bsatn::from_slice(&value)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sets the pricing of `reducer` in the database at `address`,
+    /// or of all of its reducers that aren't priced individually if `reducer` is `None`.
+    pub async fn set_energy_pricing(
+        &self,
+        address: &Address,
+        reducer: Option<&str>,
+        pricing: EnergyPricing,
+    ) -> Result<()> {
+        let tree = self.db.open_tree("energy_pricing")?;
+        let buf = bsatn::to_vec(&pricing).unwrap();
+        tree.insert(energy_pricing_key(address, reducer).as_bytes(), buf)?;
+        Ok(())
+    }
+
+    /// Returns the webhooks of the database at `address`.
+    pub fn get_webhooks(&self, address: &Address) -> Result<Vec<Webhook>> {
+        let tree = self.db.open_tree("webhooks")?;
+        match tree.get(address.as_slice())? {
+            Some(value) => Ok(bsatn::from_slice(&value)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Replaces the webhooks of the database at `address`.
+    pub async fn set_webhooks(&self, address: &Address, webhooks: &[Webhook]) -> Result<()> {
+        let tree = self.db.open_tree("webhooks")?;
+        if webhooks.is_empty() {
+            tree.remove(address.as_slice())?;
+        } else {
+            tree.insert(address.as_slice(), bsatn::to_vec(webhooks).unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the quota of the database at `address`, unlimited if none was set.
+    pub fn get_quota(&self, address: &Address) -> Result<Quota> {
+        let tree = self.db.open_tree("quotas")?;
+        match tree.get(address.as_slice())? {
+            Some(value) => Ok(bsatn::from_slice(&value)?),
+            None => Ok(Quota::default()),
+        }
+    }
+
+    /// Replaces the quota of the database at `address`.
+    pub async fn set_quota(&self, address: &Address, quota: &Quota) -> Result<()> {
+        let tree = self.db.open_tree("quotas")?;
+        if *quota == Quota::default() {
+            tree.remove(address.as_slice())?;
+        } else {
+            tree.insert(address.as_slice(), bsatn::to_vec(quota).unwrap())?;
+        }
+        Ok(())
+    }
+
     /// Update the stored current budget for a identity.
     /// Note: this function is for the stored budget only and should *only* be called by functions in
     /// `control_budget`, where a cached copy is stored along with business logic for managing it.
@@ -557,3 +728,16 @@ impl ControlDb {
         Ok(())
     }
 }
+
+/// The key of a user in the `oidc_identities` tree.
+///
+/// An issuer is a URL, which can't have a newline in it.
+fn oidc_identity_key(issuer: &str, subject: &str) -> String {
+    format!("{issuer}\n{subject}")
+}
+
+/// The key of a pricing in the `energy_pricing` tree,
+/// where the pricing of all of a database's reducers has an empty reducer name.
+fn energy_pricing_key(address: &Address, reducer: Option<&str>) -> String {
+    format!("{}/{}", address.to_hex(), reducer.unwrap_or_default())
+}
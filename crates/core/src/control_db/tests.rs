@@ -92,3 +92,180 @@ async fn test_domain() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_api_tokens() -> anyhow::Result<()> {
+    let tmp = TempDir::new("api-tokens")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let scope = TokenScope {
+        reducers: Some(vec!["say_hello".into()]),
+        ..TokenScope::default()
+    };
+    let token = cdb.insert_api_token(*ALICE, scope.clone()).await?;
+    cdb.insert_api_token(*BOB, TokenScope::default()).await?;
+
+    let alices = cdb.get_api_tokens_for_identity(&ALICE)?;
+    assert_eq!(alices.len(), 1);
+    assert_eq!(alices[0].scope, scope);
+    assert!(!alices[0].revoked);
+
+    assert!(cdb.revoke_api_token(token.id).await?);
+    assert!(cdb.get_api_token(token.id)?.unwrap().revoked);
+    assert!(!cdb.revoke_api_token(u64::MAX).await?);
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_identity_links() -> anyhow::Result<()> {
+    let tmp = TempDir::new("identity-links")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let carol = Identity::from_hashing_bytes("carol");
+    assert_eq!(cdb.get_linked_identity(&ALICE)?, None);
+
+    cdb.link_identity(*ALICE, *BOB).await?;
+    assert_eq!(cdb.get_linked_identity(&ALICE)?, Some(*BOB));
+    assert!(matches!(
+        cdb.link_identity(*ALICE, carol).await,
+        Err(Error::IdentityAlreadyLinked(_))
+    ));
+    assert!(matches!(
+        cdb.link_identity(carol, *ALICE).await,
+        Err(Error::IdentityAlreadyLinked(_))
+    ));
+
+    // Linking Bob in turn carries Alice over with him.
+    cdb.link_identity(*BOB, carol).await?;
+    assert_eq!(cdb.get_linked_identity(&ALICE)?, Some(carol));
+    assert_eq!(cdb.get_linked_identity(&BOB)?, Some(carol));
+    assert_eq!(cdb.get_linked_identity(&carol)?, None);
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oidc_identities() -> anyhow::Result<()> {
+    let tmp = TempDir::new("oidc-identities")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let issuer = "https://accounts.example.com";
+    let player = cdb.get_or_create_oidc_identity(issuer, "player-1").await?;
+    assert_eq!(cdb.get_or_create_oidc_identity(issuer, "player-1").await?, player);
+    assert_ne!(cdb.get_or_create_oidc_identity(issuer, "player-2").await?, player);
+    assert_ne!(
+        cdb.get_or_create_oidc_identity("https://other.example.com", "player-1")
+            .await?,
+        player
+    );
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_energy_pricing() -> anyhow::Result<()> {
+    let tmp = TempDir::new("energy-pricing")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let addr = Address::from_arr(&[0; 16]);
+    assert_eq!(cdb.get_energy_pricing(&addr, "move")?, None);
+
+    let database_pricing = EnergyPricing {
+        per_row_scanned: 10,
+        ..EnergyPricing::default()
+    };
+    let reducer_pricing = EnergyPricing {
+        per_byte_written: 5,
+        ..EnergyPricing::default()
+    };
+    cdb.set_energy_pricing(&addr, None, database_pricing).await?;
+    cdb.set_energy_pricing(&addr, Some("move"), reducer_pricing).await?;
+
+    assert_eq!(cdb.get_energy_pricing(&addr, "move")?, Some(reducer_pricing));
+    assert_eq!(cdb.get_energy_pricing(&addr, "attack")?, Some(database_pricing));
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_webhooks() -> anyhow::Result<()> {
+    let tmp = TempDir::new("webhooks")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let addr = Address::from_arr(&[0; 16]);
+    assert!(cdb.get_webhooks(&addr)?.is_empty());
+
+    let webhooks = [Webhook {
+        name: "orders".into(),
+        url: "https://example.com/hook".into(),
+        reducers: vec![],
+        tables: vec!["Order".into()],
+        secret: Some("hunter2".into()),
+    }];
+    cdb.set_webhooks(&addr, &webhooks).await?;
+    assert_eq!(cdb.get_webhooks(&addr)?, webhooks);
+
+    cdb.set_webhooks(&addr, &[]).await?;
+    assert!(cdb.get_webhooks(&addr)?.is_empty());
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_quotas() -> anyhow::Result<()> {
+    let tmp = TempDir::new("quotas")?;
+
+    let cdb = tokio::task::spawn_blocking({
+        let path = tmp.path().to_path_buf();
+        move || ControlDb::at(path)
+    })
+    .await??;
+
+    let addr = Address::from_arr(&[0; 16]);
+    assert_eq!(cdb.get_quota(&addr)?, Quota::default());
+
+    let quota = Quota {
+        max_tables: Some(16),
+        max_rows: None,
+        max_bytes: Some(1 << 30),
+    };
+    cdb.set_quota(&addr, &quota).await?;
+    assert_eq!(cdb.get_quota(&addr)?, quota);
+
+    cdb.set_quota(&addr, &Quota::default()).await?;
+    assert_eq!(cdb.get_quota(&addr)?, Quota::default());
+    let _ = tmp.close().ok(); // force tmp to not be dropped until here
+
+    Ok(())
+}
@@ -6,10 +6,12 @@ use crate::db::ostorage::sled_object_db::SledObjectDB;
 use crate::db::ostorage::ObjectDB;
 use crate::db::relational_db::RelationalDB;
 use crate::db::Storage;
+use crate::host::call_log::CallLog;
 use crate::identity::Identity;
 use crate::messages::control_db::Database;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
 
 #[derive(Clone)]
 pub struct DatabaseInstanceContext {
@@ -20,6 +22,11 @@ pub struct DatabaseInstanceContext {
     pub address: Address,
     pub logger: Arc<Mutex<DatabaseLogger>>,
     pub relational_db: Arc<RelationalDB>,
+    /// The host calls made by the reducers of the database, recorded while debugging it.
+    pub call_log: Arc<CallLog>,
+    /// Notified when a transaction of the database that sent messages to other databases commits,
+    /// for the host to deliver them.
+    pub outbox: Arc<Notify>,
 }
 
 impl DatabaseInstanceContext {
@@ -85,6 +92,8 @@ impl DatabaseInstanceContext {
             address,
             logger: Arc::new(Mutex::new(DatabaseLogger::open(log_path))),
             relational_db: Arc::new(RelationalDB::open(db_path, message_log, odb).unwrap()),
+            call_log: Arc::default(),
+            outbox: Arc::default(),
         })
     }
 
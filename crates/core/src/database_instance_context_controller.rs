@@ -30,4 +30,28 @@ impl DatabaseInstanceContextController {
         let mut contexts = self.contexts.lock().unwrap();
         contexts.remove(&database_instance_id)
     }
+
+    /// Remove the context of a database instance, if nothing but the controller holds on to it,
+    /// so that its database is closed once the returned context is dropped.
+    ///
+    /// Returns `Ok(None)` if there's no context for the instance.
+    pub fn remove_unused(
+        &self,
+        database_instance_id: u64,
+    ) -> Result<Option<(Arc<DatabaseInstanceContext>, Scheduler)>, ContextInUse> {
+        let mut contexts = self.contexts.lock().unwrap();
+        let Some((dbic, _)) = contexts.get(&database_instance_id) else {
+            return Ok(None);
+        };
+        // Anything using the database got it from the controller,
+        // so while the lock is held, nothing can start using it.
+        if Arc::strong_count(dbic) > 1 || Arc::strong_count(&dbic.relational_db) > 1 {
+            return Err(ContextInUse);
+        }
+        Ok(contexts.remove(&database_instance_id))
+    }
 }
+
+#[derive(thiserror::Error, Debug)]
+#[error("the database instance is still in use")]
+pub struct ContextInUse;
@@ -1,13 +1,76 @@
 use crate::address::Address;
+use once_cell::sync::Lazy;
+use spacetimedb_lib::logging::LogField;
 use std::fs::OpenOptions;
 use std::fs::{self, File};
-use std::io::{prelude::*, SeekFrom};
+use std::io::{self, prelude::*, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
 
+/// The file the lines of a module log are appended to.
+///
+/// When it grows too large or old, it's renamed after the time, in milliseconds since the unix epoch,
+/// and a new one is started.
+const CURRENT_LOG_FILE: &str = "0.log";
+/// The size the current file of a module log is rotated at when there's no smaller `max_size`.
+const DEFAULT_ROTATE_SIZE: u64 = 64 * 1024 * 1024;
+/// Roughly how many files the `max_size` and `max_age` of a module log are split across.
+const ROTATED_FILES: u32 = 4;
+
+/// How much of each module log is kept, from the `SPACETIMEDB_MODULE_LOG_MAX_SIZE` (in bytes)
+/// and `SPACETIMEDB_MODULE_LOG_MAX_AGE` (e.g. `7days`) environment variables.
+pub static MODULE_LOG_RETENTION: Lazy<LogRetention> = Lazy::new(|| LogRetention {
+    max_size: env_var("SPACETIMEDB_MODULE_LOG_MAX_SIZE", str::parse),
+    max_age: env_var("SPACETIMEDB_MODULE_LOG_MAX_AGE", |age| {
+        humantime::parse_duration(age).map_err(|e| e.to_string())
+    }),
+});
+
+fn env_var<T, E: std::fmt::Display>(name: &str, parse: impl FnOnce(&str) -> Result<T, E>) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    parse(&value)
+        .map_err(|e| log::warn!("Ignoring invalid {name} {value:?}: {e}"))
+        .ok()
+}
+
+/// How much of a module log is kept, its oldest lines being deleted first.
+///
+/// Lines are deleted a whole rotated file at a time,
+/// so a log can be up to a rotated file larger or older than the limits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogRetention {
+    pub max_size: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl LogRetention {
+    fn rotate_size(&self) -> u64 {
+        self.max_size
+            .map_or(DEFAULT_ROTATE_SIZE, |max_size| max_size / ROTATED_FILES as u64)
+            .clamp(1, DEFAULT_ROTATE_SIZE)
+    }
+
+    fn rotate_age(&self) -> Option<Duration> {
+        self.max_age.map(|max_age| max_age / ROTATED_FILES)
+    }
+}
+
+/// The disk space used by a module log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogUsage {
+    pub bytes: u64,
+    pub files: u32,
+}
+
 pub struct DatabaseLogger {
+    root: PathBuf,
     file: File,
+    /// The size of the current file.
+    size: u64,
+    /// When the current file was started, as far as this logger knows.
+    started_at: SystemTime,
     pub tx: broadcast::Sender<bytes::Bytes>,
 }
 
@@ -35,6 +98,36 @@ impl From<u8> for LogLevel {
     }
 }
 
+impl LogLevel {
+    /// How verbose the level is, from a panic, the least verbose, to trace.
+    fn verbosity(self) -> u8 {
+        match self {
+            LogLevel::Panic => 0,
+            LogLevel::Error => 1,
+            LogLevel::Warn => 2,
+            LogLevel::Info => 3,
+            LogLevel::Debug => 4,
+            LogLevel::Trace => 5,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match &*s.to_ascii_lowercase() {
+            "error" => LogLevel::Error,
+            "warn" => LogLevel::Warn,
+            "info" => LogLevel::Info,
+            "debug" => LogLevel::Debug,
+            "trace" => LogLevel::Trace,
+            "panic" => LogLevel::Panic,
+            _ => return Err(format!("unknown log level `{s}`")),
+        })
+    }
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(serde::Serialize, Copy, Clone)]
 pub struct Record<'a> {
@@ -42,6 +135,59 @@ pub struct Record<'a> {
     pub filename: Option<&'a str>,
     pub line_number: Option<u32>,
     pub message: &'a str,
+    /// The key-value pairs the record was logged with, written as an object.
+    #[serde(serialize_with = "serialize_fields", skip_serializing_if = "<[_]>::is_empty")]
+    pub fields: &'a [LogField],
+}
+
+fn serialize_fields<S: serde::Serializer>(fields: &&[LogField], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(fields.iter().map(|field| (&field.key, &field.value)))
+}
+
+/// Which lines of a module log to read: those of a level at most as verbose as `level`,
+/// from the module path `target` or one nested in it,
+/// and whose fields have the given values.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    pub level: Option<LogLevel>,
+    pub target: Option<String>,
+    /// Pairs of a field key and the value it must have, as written in the log.
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogFilter {
+    pub fn is_empty(&self) -> bool {
+        self.level.is_none() && self.target.is_none() && self.fields.is_empty()
+    }
+
+    /// Returns whether the log line `line` passes the filter.
+    pub fn matches(&self, line: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+            return false;
+        };
+        if let Some(max_level) = self.level {
+            let level = record["level"]
+                .as_str()
+                .and_then(|level| level.parse::<LogLevel>().ok());
+            if !level.map_or(false, |level| level.verbosity() <= max_level.verbosity()) {
+                return false;
+            }
+        }
+        if let Some(target) = &self.target {
+            let nested_in = |t: &str| t == target || t.strip_prefix(&**target).map_or(false, |t| t.starts_with("::"));
+            if !record["target"].as_str().map_or(false, nested_in) {
+                return false;
+            }
+        }
+        self.fields.iter().all(|(key, value)| match &record["fields"][key] {
+            serde_json::Value::String(s) => s == value,
+            serde_json::Value::Null => false,
+            v => v.to_string() == *value,
+        })
+    }
 }
 
 pub trait BacktraceProvider {
@@ -135,19 +281,110 @@ impl DatabaseLogger {
         fs::create_dir_all(root).unwrap();
 
         let mut filepath = PathBuf::from(root);
-        filepath.push(&PathBuf::from_str("0.log").unwrap());
+        filepath.push(&PathBuf::from_str(CURRENT_LOG_FILE).unwrap());
 
         let file = OpenOptions::new().create(true).append(true).open(&filepath).unwrap();
+        let metadata = file.metadata().unwrap();
         let (tx, _) = broadcast::channel(64);
-        Self { file, tx }
+        Self {
+            root: root.to_owned(),
+            file,
+            size: metadata.len(),
+            started_at: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+            tx,
+        }
+    }
+
+    /// The files of the module log in `root`, from the oldest to the current one.
+    fn log_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(root)? {
+            let path = entry?.path();
+            let Some(millis) = path.file_stem().and_then(|stem| stem.to_str()?.parse::<u64>().ok()) else {
+                continue;
+            };
+            if path.extension().map_or(false, |ext| ext == "log") {
+                // The current file, `0.log`, is the newest.
+                files.push((if millis == 0 { u64::MAX } else { millis }, path));
+            }
+        }
+        files.sort_unstable();
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Renames the current file after the time `now`, and starts a new one.
+    fn rotate(&mut self, now: SystemTime) -> io::Result<()> {
+        self.file.flush()?;
+        let mut millis = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let mut rotated = self.root.join(format!("{millis}.log"));
+        while rotated.exists() {
+            millis += 1;
+            rotated = self.root.join(format!("{millis}.log"));
+        }
+        let current = self.root.join(CURRENT_LOG_FILE);
+        fs::rename(&current, rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(current)?;
+        self.size = 0;
+        self.started_at = now;
+        Ok(())
+    }
+
+    /// Rotates the current file if it has grown too large or old for `retention`,
+    /// and deletes the oldest rotated files past its limits,
+    /// returning the disk space the log uses after that.
+    pub fn enforce_retention(&mut self, retention: &LogRetention, now: SystemTime) -> io::Result<LogUsage> {
+        let age = now.duration_since(self.started_at).unwrap_or_default();
+        let too_old = retention.rotate_age().map_or(false, |max_age| age >= max_age);
+        if self.size >= retention.rotate_size() || (self.size > 0 && too_old) {
+            self.rotate(now)?;
+        }
+
+        let mut files = Vec::new();
+        for path in Self::log_files(&self.root)? {
+            if path.ends_with(CURRENT_LOG_FILE) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+        if let Some(max_age) = retention.max_age {
+            let expired = |modified: &SystemTime| now.duration_since(*modified).map_or(false, |age| age > max_age);
+            for (path, ..) in files.iter().filter(|(.., modified)| expired(modified)) {
+                fs::remove_file(path)?;
+            }
+            files.retain(|(.., modified)| !expired(modified));
+        }
+        let mut bytes = self.size + files.iter().map(|(_, len, _)| len).sum::<u64>();
+        if let Some(max_size) = retention.max_size {
+            let mut oldest = files.iter();
+            while bytes > max_size {
+                let Some((path, len, _)) = oldest.next() else { break };
+                fs::remove_file(path)?;
+                bytes -= len;
+            }
+            let deleted = files.len() - oldest.len();
+            files.drain(..deleted);
+        }
+
+        Ok(LogUsage {
+            bytes,
+            files: files.len() as u32 + 1,
+        })
     }
 
     pub fn _delete(&mut self) {
         self.file.set_len(0).unwrap();
         self.file.seek(SeekFrom::End(0)).unwrap();
+        self.size = 0;
     }
 
-    pub fn write(&mut self, level: LogLevel, &record: &Record<'_>, bt: &dyn BacktraceProvider) {
+    pub fn write(&mut self, level: LogLevel, record: &Record<'_>, bt: &dyn BacktraceProvider) {
+        self.write_line(Self::format_line(level, record, bt))
+    }
+
+    /// Formats `record` as a line of the log, ending with a newline,
+    /// capturing the backtrace of `bt` for a panic.
+    pub fn format_line(level: LogLevel, &record: &Record<'_>, bt: &dyn BacktraceProvider) -> String {
         let (trace, frames);
         let event = match level {
             LogLevel::Error => LogEvent::Error(record),
@@ -163,8 +400,14 @@ impl DatabaseLogger {
         };
         let mut buf = serde_json::to_string(&event).unwrap();
         buf.push('\n');
-        self.file.write_all(buf.as_bytes()).unwrap();
-        let _ = self.tx.send(buf.into());
+        buf
+    }
+
+    /// Appends `line`, as formatted by [`Self::format_line`], to the log.
+    pub fn write_line(&mut self, line: String) {
+        self.file.write_all(line.as_bytes()).unwrap();
+        self.size += line.len() as u64;
+        let _ = self.tx.send(line.into());
     }
 
     pub async fn _read_all(root: &Path) -> String {
@@ -173,21 +416,129 @@ impl DatabaseLogger {
         tokio::fs::read_to_string(&filepath).await.unwrap()
     }
 
-    pub async fn read_latest(root: &Path, num_lines: Option<u32>) -> String {
-        let filepath = root.join("0.log");
+    /// Reads the last `num_lines` lines of the log passing `filter`, or all of them,
+    /// from the rotated files as well as the current one.
+    pub async fn read_latest(root: &Path, num_lines: Option<u32>, filter: &LogFilter) -> String {
+        let files = Self::log_files(root).unwrap_or_default();
 
-        // TODO: Read backwards from the end of the file to only read in the latest lines
-        let text = tokio::fs::read_to_string(&filepath).await.expect("reading file");
+        // TODO: Read backwards from the end of each file to only read in the latest lines
+        let mut remaining = num_lines.map(|n| n as usize);
+        let mut chunks = Vec::new();
+        for path in files.iter().rev() {
+            if remaining == Some(0) {
+                break;
+            }
+            let text = match tokio::fs::read_to_string(path).await {
+                Ok(text) => text,
+                // Deleted by `enforce_retention` since listing the files.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => panic!("reading file: {e}"),
+            };
+            let lines = text
+                .split_inclusive('\n')
+                .filter(|line| filter.matches(line))
+                .collect::<Vec<_>>();
+            let skip = remaining.map_or(0, |n| lines.len().saturating_sub(n));
+            if let Some(n) = &mut remaining {
+                *n -= lines.len() - skip;
+            }
+            chunks.push(lines[skip..].concat());
+        }
+        chunks.reverse();
+        chunks.concat()
+    }
+}
 
-        let Some(num_lines) = num_lines else { return text };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_lib::logging::LogValue;
 
-        let off_from_end = text
-            .split_inclusive('\n')
-            .rev()
-            .take(num_lines as usize)
-            .map(|line| line.len())
-            .sum::<usize>();
+    fn line(level: LogLevel, target: &str, fields: &[LogField]) -> String {
+        let record = Record {
+            target: Some(target),
+            filename: None,
+            line_number: None,
+            message: "spawned",
+            fields,
+        };
+        let event = match level {
+            LogLevel::Info => LogEvent::Info(record),
+            LogLevel::Debug => LogEvent::Debug(record),
+            _ => LogEvent::Error(record),
+        };
+        serde_json::to_string(&event).unwrap()
+    }
+
+    #[test]
+    fn filters_by_level_target_and_field() {
+        let fields = [
+            LogField {
+                key: "player".into(),
+                value: LogValue::U64(7),
+            },
+            LogField {
+                key: "zone".into(),
+                value: LogValue::Str("north".into()),
+            },
+        ];
+        let info = line(LogLevel::Info, "game::spawn", &fields);
+        let debug = line(LogLevel::Debug, "gameplay", &[]);
+        assert!(info.contains(r#""fields":{"player":7,"zone":"north"}"#));
+        assert!(!debug.contains("fields"));
+
+        let filter = |level: Option<&str>, target: Option<&str>, fields: &[(&str, &str)]| LogFilter {
+            level: level.map(|l| l.parse().unwrap()),
+            target: target.map(Into::into),
+            fields: fields.iter().map(|&(k, v)| (k.into(), v.into())).collect(),
+        };
+        assert!(filter(None, None, &[]).matches(&debug));
+        assert!(filter(Some("info"), None, &[]).matches(&info));
+        assert!(!filter(Some("info"), None, &[]).matches(&debug));
+        assert!(filter(None, Some("game"), &[]).matches(&info));
+        assert!(!filter(None, Some("game"), &[]).matches(&debug));
+        assert!(filter(None, None, &[("player", "7"), ("zone", "north")]).matches(&info));
+        assert!(!filter(None, None, &[("player", "8")]).matches(&info));
+        assert!(!filter(None, None, &[("player", "7")]).matches(&debug));
+    }
+
+    #[tokio::test]
+    async fn rotates_and_deletes_old_files() {
+        let root = tempdir::TempDir::new("stdb_module_log").unwrap();
+        let mut logger = DatabaseLogger::open(root.path());
+        let write = |logger: &mut DatabaseLogger, message: &str| {
+            let record = Record {
+                target: None,
+                filename: None,
+                line_number: None,
+                message,
+                fields: &[],
+            };
+            logger.write(LogLevel::Info, &record, &());
+        };
+        // Small enough that each line is rotated into a file of its own.
+        let retention = LogRetention {
+            max_size: Some(100),
+            max_age: Some(Duration::from_secs(3600)),
+        };
+        let start = SystemTime::now();
+        for (i, message) in ["one", "two", "three"].into_iter().enumerate() {
+            write(&mut logger, message);
+            let usage = logger
+                .enforce_retention(&retention, start + Duration::from_secs(i as u64))
+                .unwrap();
+            assert!(usage.bytes <= 100, "{usage:?}");
+        }
+        let lines = DatabaseLogger::read_latest(root.path(), None, &LogFilter::default()).await;
+        assert_eq!(lines.lines().count(), 2, "{lines}");
+        assert!(lines.ends_with("\"message\":\"three\"}\n"), "{lines}");
+        let last = DatabaseLogger::read_latest(root.path(), Some(1), &LogFilter::default()).await;
+        assert_eq!(last.lines().count(), 1);
 
-        text[text.len() - off_from_end..].to_owned()
+        // Once older than `max_age`, the rotated files are deleted.
+        let usage = logger
+            .enforce_retention(&retention, start + Duration::from_secs(7200))
+            .unwrap();
+        assert_eq!(usage, LogUsage { bytes: 0, files: 1 });
     }
 }
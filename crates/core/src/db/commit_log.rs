@@ -6,13 +6,16 @@ use super::{
 };
 use crate::{
     db::{
-        datastore::{locking_tx_datastore::RowId, traits::TxOp},
+        datastore::{
+            locking_tx_datastore::{Locking, RowId},
+            traits::TxOp,
+        },
         messages::{
             transaction::Transaction,
             write::{Operation, Write},
         },
     },
-    error::DBError,
+    error::{DBError, DatabaseError},
 };
 use spacetimedb_lib::hash::hash_bytes;
 use std::sync::Arc;
@@ -59,6 +62,50 @@ impl CommitLog {
         }
     }
 
+    /// The bytes and number of segments of the message log, if there is one.
+    pub fn disk_usage(&self) -> Option<(u64, usize)> {
+        let mlog = self.mlog.as_ref()?.lock().unwrap();
+        Some((mlog.size(), mlog.segment_count()))
+    }
+
+    /// The number of transactions appended to the log so far.
+    pub fn tx_offset(&self) -> u64 {
+        let unwritten_commit = self.unwritten_commit.lock().unwrap();
+        unwritten_commit.min_tx_offset + unwritten_commit.transactions.len() as u64
+    }
+
+    /// Replay the first `tx_offset` transactions of the message log into a freshly bootstrapped datastore,
+    /// rebuilding the state the database was in right after them.
+    pub fn replay_until(&self, tx_offset: u64) -> Result<Locking, DBError> {
+        let mlog = self.mlog.as_ref().ok_or(DatabaseError::NoCommitLog)?;
+        let datastore = Locking::bootstrap()?;
+
+        let mut replayed = 0;
+        {
+            let mlog = mlog.lock().unwrap();
+            'replay: for message in mlog.iter() {
+                let (commit, _) = Commit::decode(message);
+                for transaction in commit.transactions {
+                    if replayed == tx_offset {
+                        break 'replay;
+                    }
+                    datastore.replay_transaction(&transaction, self.odb.clone())?;
+                    replayed += 1;
+                }
+            }
+        }
+        if replayed < tx_offset {
+            return Err(DatabaseError::TxOffsetOutOfRange {
+                tx_offset,
+                len: replayed,
+            }
+            .into());
+        }
+
+        datastore.rebuild_state_after_replay()?;
+        Ok(datastore)
+    }
+
     fn generate_commit<D: MutTxDatastore<RowId = RowId>>(&self, tx_data: &TxData, _datastore: &D) -> Option<Vec<u8>> {
         // We are not creating a commit for empty transactions.
         // The reason for this is that empty transactions get encoded as 0 bytes,
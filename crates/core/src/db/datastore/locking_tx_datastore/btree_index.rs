@@ -3,9 +3,10 @@ use crate::{
     db::datastore::traits::{IndexId, IndexSchema},
     error::DBError,
 };
-use spacetimedb_lib::{data_key::ToDataKey, DataKey};
-use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use spacetimedb_lib::{data_key::ToDataKey, fulltext, DataKey, IndexType};
+use spacetimedb_sats::{AlgebraicValue, BuiltinValue, ProductValue};
 use std::{
+    borrow::Cow,
     collections::{btree_set, BTreeSet},
     ops::{Bound, RangeBounds},
 };
@@ -24,6 +25,22 @@ impl IndexKey {
             row_id: RowId(row_id),
         }
     }
+
+    /// Returns the approximate size of the key in memory, in bytes.
+    fn size(&self) -> usize {
+        let heap = match &self.value {
+            AlgebraicValue::Builtin(BuiltinValue::String(s)) => s.len(),
+            AlgebraicValue::Builtin(BuiltinValue::Array { .. } | BuiltinValue::Map { .. })
+            | AlgebraicValue::Sum(_)
+            | AlgebraicValue::Product(_) => {
+                let mut bytes = Vec::new();
+                self.value.encode(&mut bytes);
+                bytes.len()
+            }
+            AlgebraicValue::Builtin(_) => 0,
+        };
+        std::mem::size_of::<Self>() + heap
+    }
 }
 
 pub struct BTreeIndexIter<'a> {
@@ -54,39 +71,77 @@ impl Iterator for BTreeIndexRangeIter<'_> {
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct BTreeIndex {
     pub(crate) index_id: IndexId,
     pub(crate) table_id: u32,
     pub(crate) col_id: u32,
     pub(crate) name: String,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
     idx: BTreeSet<IndexKey>,
+    /// The approximate size of the keys in memory, in bytes.
+    bytes: usize,
 }
 
 impl BTreeIndex {
-    pub(crate) fn new(index_id: IndexId, table_id: u32, col_id: u32, name: String, is_unique: bool) -> Self {
+    pub(crate) fn new(
+        index_id: IndexId,
+        table_id: u32,
+        col_id: u32,
+        name: String,
+        is_unique: bool,
+        index_type: IndexType,
+    ) -> Self {
         Self {
             index_id,
             table_id,
             col_id,
             name,
             is_unique,
+            index_type,
             idx: BTreeSet::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Returns the values `col_value` is stored under in the [BTreeIndex].
+    ///
+    /// A [IndexType::FullText] index stores a string once per distinct token,
+    /// any other index stores the value as is.
+    fn keys(&self, col_value: &AlgebraicValue) -> Vec<AlgebraicValue> {
+        match (self.index_type, col_value.as_string()) {
+            (IndexType::FullText, Some(text)) => fulltext::tokenize(text)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(AlgebraicValue::String)
+                .collect(),
+            _ => vec![col_value.clone()],
         }
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
         let col_value = row.get_field(self.col_id as usize, None)?;
-        let key = IndexKey::from_row(col_value, row.to_data_key());
-        self.idx.insert(key);
+        let row_id = RowId(row.to_data_key());
+        for value in self.keys(col_value) {
+            let key = IndexKey { value, row_id };
+            let size = key.size();
+            if self.idx.insert(key) {
+                self.bytes += size;
+            }
+        }
         Ok(())
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
-        let key = IndexKey::from_row(col_value, row_id.0);
-        self.idx.remove(&key);
+        for value in self.keys(col_value) {
+            let key = IndexKey { value, row_id: *row_id };
+            if self.idx.remove(&key) {
+                self.bytes -= key.size();
+            }
+        }
     }
 
     #[tracing::instrument(skip_all)]
@@ -107,12 +162,34 @@ impl BTreeIndex {
             .then(|| self.seek(row.get_field(self.col_id as usize, None).unwrap()))
     }
 
+    /// Returns the number of keys in the [BTreeIndex],
+    /// which is more than the number of rows for a [IndexType::FullText] index.
+    pub(crate) fn len(&self) -> usize {
+        self.idx.len()
+    }
+
+    /// Returns the approximate size of the keys in memory, in bytes,
+    /// not counting the nodes of the tree that hold them.
+    pub(crate) fn approx_bytes(&self) -> usize {
+        self.bytes
+    }
+
     /// Returns `true` if the [BTreeIndex] contains a value for the specified `value`.
     #[tracing::instrument(skip_all)]
     pub(crate) fn contains_any(&self, value: &AlgebraicValue) -> bool {
         self.seek(value).next().is_some()
     }
 
+    /// Returns the smallest value stored for more than one row, if any.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn first_duplicate(&self) -> Option<&AlgebraicValue> {
+        self.idx
+            .iter()
+            .zip(self.idx.iter().skip(1))
+            .find(|(a, b)| a.value == b.value)
+            .map(|(a, _)| &a.value)
+    }
+
     /// Returns an iterator over the `RowId`s in the [BTreeIndex]
     #[tracing::instrument(skip_all)]
     pub(crate) fn scan(&self) -> BTreeIndexIter<'_> {
@@ -142,7 +219,7 @@ impl BTreeIndex {
     ///
     /// For a unique index this will always yield at most one `RowId`.
     #[tracing::instrument(skip_all)]
-    pub(crate) fn seek<'a>(&'a self, value: &'a AlgebraicValue) -> BTreeIndexRangeIter<'a> {
+    pub(crate) fn seek(&self, value: &AlgebraicValue) -> BTreeIndexRangeIter<'_> {
         let k_start = IndexKey::from_row(value, DataKey::min_datakey());
         let k_end = IndexKey::from_row(value, DataKey::max_datakey());
         BTreeIndexRangeIter {
@@ -150,11 +227,44 @@ impl BTreeIndex {
         }
     }
 
+    /// Returns an iterator over the [IndexType::FullText] index that yields
+    /// the `RowId`s of all rows that have the first token of `query`.
+    ///
+    /// These are only candidates: the caller still has to check the row
+    /// against the whole `query` with [fulltext::matches].
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn search(&self, query: &str) -> BTreeIndexRangeIter<'_> {
+        // Tokens are never empty, so a `query` without any tokens finds no rows.
+        let token = fulltext::tokenize(query).next().unwrap_or_default();
+        self.seek(&AlgebraicValue::String(token))
+    }
+
+    /// Returns an iterator over the [IndexType::Spatial] index that yields
+    /// the `RowId`s of all rows with a point between `min` and `max`,
+    /// as ordered by `Ord for AlgebraicValue`, that is, by `x` and then by `y`.
+    ///
+    /// This covers the whole box, but also points outside of it,
+    /// so the caller still has to check the row with [spatial::in_box](spacetimedb_lib::spatial::in_box).
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn search_box(&self, min: &AlgebraicValue, max: &AlgebraicValue) -> BTreeIndexRangeIter<'_> {
+        self.scan_range(min..=max)
+    }
+
+    /// Rebuilds the tree of keys, packing its nodes full,
+    /// as deleting keys can leave them as little as half full.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn rebuild(&mut self) {
+        self.idx = std::mem::take(&mut self.idx).into_iter().collect();
+    }
+
     /// Construct the [BTreeIndex] from the rows.
     #[tracing::instrument(skip_all)]
-    pub(crate) fn build_from_rows<'a>(&mut self, rows: impl Iterator<Item = &'a ProductValue>) -> Result<(), DBError> {
+    pub(crate) fn build_from_rows<'a>(
+        &mut self,
+        rows: impl Iterator<Item = Cow<'a, ProductValue>>,
+    ) -> Result<(), DBError> {
         for row in rows {
-            self.insert(row)?;
+            self.insert(&row)?;
         }
         Ok(())
     }
@@ -168,6 +278,7 @@ impl From<&BTreeIndex> for IndexSchema {
             col_id: x.col_id,
             is_unique: x.is_unique,
             index_name: x.name.clone(),
+            index_type: x.index_type,
         }
     }
 }
@@ -0,0 +1,272 @@
+//! The compression of string columns in memory, as declared by a module with `#[compress]`.
+//!
+//! The rows of a [`Table`](super::table::Table) in memory hold a stand-in for each value of a compressed column:
+//! its index in the column's dictionary, as a `u32`, or its zstd frame, as bytes.
+//! The table decodes its rows as it hands them out, so that indexes, queries and the rows evicted to disk
+//! only ever see the values as they were inserted.
+
+use crate::db::datastore::traits::ColId;
+use spacetimedb_lib::Compression;
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+/// The zstd level the values are compressed at, that of the `zstd` command line.
+const ZSTD_LEVEL: i32 = 3;
+
+/// A compressed column of a table, with the size of its values in the rows in memory.
+#[derive(Clone)]
+pub(crate) struct ColumnCodec {
+    pub(crate) col_id: ColId,
+    pub(crate) codec: Compression,
+    dictionary: Dictionary,
+    /// The size of the values, as inserted, in bytes.
+    raw_bytes: u64,
+    /// The size of the stand-ins for the values, in bytes.
+    stand_in_bytes: u64,
+}
+
+impl ColumnCodec {
+    pub(crate) fn new(col_id: ColId, codec: Compression) -> Self {
+        Self {
+            col_id,
+            codec,
+            dictionary: Dictionary::default(),
+            raw_bytes: 0,
+            stand_in_bytes: 0,
+        }
+    }
+
+    /// Returns the size of the values in the rows in memory, as inserted, in bytes.
+    pub(crate) fn raw_bytes(&self) -> u64 {
+        self.raw_bytes
+    }
+
+    /// Returns the size of the values in the rows in memory, as stored, in bytes,
+    /// including the dictionary they index into, if any.
+    pub(crate) fn stored_bytes(&self) -> u64 {
+        self.stand_in_bytes + self.dictionary.bytes
+    }
+
+    /// Returns the size of the values in the column's dictionary, if any, in bytes.
+    pub(crate) fn dictionary_bytes(&self) -> u64 {
+        self.dictionary.bytes
+    }
+
+    /// Returns the approximate size of the space the column's dictionary holds but doesn't use, in bytes.
+    pub(crate) fn spare_bytes(&self) -> usize {
+        self.dictionary.spare_bytes()
+    }
+
+    /// Releases the spare capacity of the column's dictionary.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.dictionary.shrink_to_fit()
+    }
+
+    /// Returns the stand-in for `value`, counting it as held by a row.
+    ///
+    /// Values that aren't strings are returned as is, though the host only compresses string columns.
+    fn encode(&mut self, value: AlgebraicValue) -> AlgebraicValue {
+        let value = match value.into_string() {
+            Ok(value) => value,
+            Err(value) => return value,
+        };
+        self.raw_bytes += value.len() as u64;
+        let stand_in = match self.codec {
+            Compression::Dictionary => AlgebraicValue::U32(self.dictionary.intern(value)),
+            Compression::Zstd => AlgebraicValue::Bytes(
+                zstd::encode_all(value.as_bytes(), ZSTD_LEVEL).expect("compressing into memory can't fail"),
+            ),
+        };
+        self.stand_in_bytes += stand_in_size(&stand_in);
+        stand_in
+    }
+
+    /// Returns the value `stand_in` stands in for.
+    fn decode(&self, stand_in: &AlgebraicValue) -> AlgebraicValue {
+        match (self.codec, stand_in.as_u32(), stand_in.as_bytes()) {
+            (Compression::Dictionary, Some(&code), _) => AlgebraicValue::String(self.dictionary.get(code).to_owned()),
+            (Compression::Zstd, _, Some(frame)) => {
+                let value = zstd::decode_all(&frame[..]).expect("invalid zstd frame in a compressed column");
+                AlgebraicValue::String(String::from_utf8(value).expect("a compressed column holds non-UTF-8 bytes"))
+            }
+            _ => stand_in.clone(),
+        }
+    }
+
+    /// Stops counting `stand_in` as held by a row, as that row is leaving memory.
+    fn release(&mut self, stand_in: &AlgebraicValue) {
+        let raw_len = match (self.codec, stand_in.as_u32(), stand_in.as_bytes()) {
+            (Compression::Dictionary, Some(&code), _) => self.dictionary.release(code),
+            (Compression::Zstd, _, Some(_)) => self.decode(stand_in).as_string().map_or(0, |value| value.len()),
+            _ => return,
+        };
+        self.raw_bytes -= raw_len as u64;
+        self.stand_in_bytes -= stand_in_size(stand_in);
+    }
+}
+
+fn stand_in_size(stand_in: &AlgebraicValue) -> u64 {
+    stand_in.as_bytes().map_or(4, |frame| frame.len() as u64)
+}
+
+/// Returns `row` with the values of the compressed columns replaced by their stand-ins.
+pub(crate) fn encode_row(codecs: &mut [ColumnCodec], mut row: ProductValue) -> ProductValue {
+    for codec in codecs {
+        if let Some(value) = row.elements.get_mut(codec.col_id.0 as usize) {
+            let taken = std::mem::replace(value, AlgebraicValue::U32(0));
+            *value = codec.encode(taken);
+        }
+    }
+    row
+}
+
+/// Returns `row` with the stand-ins of the compressed columns replaced by the values they stand in for.
+pub(crate) fn decode_row<'a>(codecs: &[ColumnCodec], row: &'a ProductValue) -> Cow<'a, ProductValue> {
+    if codecs.is_empty() {
+        return Cow::Borrowed(row);
+    }
+    let mut row = row.clone();
+    for codec in codecs {
+        if let Some(value) = row.elements.get_mut(codec.col_id.0 as usize) {
+            *value = codec.decode(value);
+        }
+    }
+    Cow::Owned(row)
+}
+
+/// Like [`decode_row`], for a row that's owned.
+pub(crate) fn into_decoded_row(codecs: &[ColumnCodec], row: ProductValue) -> ProductValue {
+    if codecs.is_empty() {
+        return row;
+    }
+    decode_row(codecs, &row).into_owned()
+}
+
+/// Stops counting the stand-ins of `row` as held by a row, as it's leaving memory.
+pub(crate) fn release_row(codecs: &mut [ColumnCodec], row: &ProductValue) {
+    for codec in codecs {
+        if let Some(value) = row.elements.get(codec.col_id.0 as usize) {
+            codec.release(value);
+        }
+    }
+}
+
+/// The distinct values of a column, each stored once, with the number of rows holding it.
+#[derive(Clone, Default)]
+struct Dictionary {
+    codes: HashMap<Arc<str>, u32>,
+    entries: Vec<Option<Entry>>,
+    /// The codes of the entries that were released, to reuse first.
+    free: Vec<u32>,
+    /// The size of the values, in bytes.
+    bytes: u64,
+}
+
+#[derive(Clone)]
+struct Entry {
+    value: Arc<str>,
+    refs: u64,
+}
+
+impl Dictionary {
+    /// Returns the code of `value`, adding it to the dictionary if it isn't there yet.
+    fn intern(&mut self, value: String) -> u32 {
+        if let Some(&code) = self.codes.get(&*value) {
+            self.entries[code as usize].as_mut().unwrap().refs += 1;
+            return code;
+        }
+        self.bytes += value.len() as u64;
+        let entry = Entry {
+            value: value.into(),
+            refs: 1,
+        };
+        let code = match self.free.pop() {
+            Some(code) => code,
+            None => {
+                self.entries.push(None);
+                (self.entries.len() - 1) as u32
+            }
+        };
+        self.codes.insert(entry.value.clone(), code);
+        self.entries[code as usize] = Some(entry);
+        code
+    }
+
+    /// Returns the approximate size of the space the dictionary holds but doesn't use, in bytes:
+    /// that of the entries of the values released, and of the spare capacity of its collections.
+    fn spare_bytes(&self) -> usize {
+        let entry = std::mem::size_of::<Option<Entry>>();
+        (self.entries.capacity() - self.entries.len() + self.free.len()) * entry
+            + self.free.capacity() * std::mem::size_of::<u32>()
+            + (self.codes.capacity() - self.codes.len()) * std::mem::size_of::<(Arc<str>, u32)>()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.codes.shrink_to_fit();
+        self.entries.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    fn get(&self, code: u32) -> &str {
+        &self.entries[code as usize]
+            .as_ref()
+            .expect("unknown dictionary code")
+            .value
+    }
+
+    /// Drops a reference to the value of `code`, removing it once there are none left,
+    /// and returns the size of the value in bytes.
+    fn release(&mut self, code: u32) -> usize {
+        let slot = &mut self.entries[code as usize];
+        let entry = slot.as_mut().expect("unknown dictionary code");
+        let len = entry.value.len();
+        entry.refs -= 1;
+        if entry.refs == 0 {
+            self.codes.remove(&entry.value);
+            *slot = None;
+            self.free.push(code);
+            self.bytes -= len as u64;
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::product;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut codecs = vec![
+            ColumnCodec::new(ColId(1), Compression::Dictionary),
+            ColumnCodec::new(ColId(2), Compression::Zstd),
+        ];
+        let description = "a sword, sharp and shiny; ".repeat(20);
+        let row = product![1u32, "sword", description.clone()];
+
+        let stored = encode_row(&mut codecs, row.clone());
+        assert_eq!(stored.elements[1], AlgebraicValue::U32(0));
+        assert!(stored.elements[2].as_bytes().is_some());
+        assert_eq!(decode_row(&codecs, &stored).into_owned(), row);
+
+        // A repeated value is stored once.
+        let other = encode_row(&mut codecs, product![2u32, "sword", "a sword".to_string()]);
+        assert_eq!(other.elements[1], AlgebraicValue::U32(0));
+        assert_eq!(codecs[0].raw_bytes(), 10);
+        assert_eq!(codecs[0].stored_bytes(), 4 + 4 + 5);
+        assert!(codecs[1].stored_bytes() < codecs[1].raw_bytes());
+
+        // Released values leave the dictionary with the last row holding them.
+        release_row(&mut codecs, &stored);
+        release_row(&mut codecs, &other);
+        assert_eq!((codecs[0].raw_bytes(), codecs[0].stored_bytes()), (0, 0));
+        assert_eq!((codecs[1].raw_bytes(), codecs[1].stored_bytes()), (0, 0));
+        let shield = encode_row(&mut codecs, product![3u32, "shield", String::new()]);
+        assert_eq!(shield.elements[1], AlgebraicValue::U32(0));
+        assert_eq!(
+            decode_row(&codecs, &shield).elements[1],
+            AlgebraicValue::String("shield".into())
+        );
+    }
+}
@@ -1,27 +1,46 @@
 mod btree_index;
+mod compression;
+mod online_index;
+mod quota;
 mod sequence;
+mod spill;
 mod table;
+mod vacuum;
+pub use self::online_index::IndexBuild;
+pub use self::quota::Quota;
+pub use self::spill::MemoryBudget;
+pub use self::vacuum::VacuumReport;
 use self::{
     btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    online_index::IndexBuildLog,
     sequence::Sequence,
-    table::Table,
+    table::{RowsIter, SpilledRows, Table},
 };
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
-    ops::RangeBounds,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::{Deref, DerefMut, RangeBounds},
     sync::Arc,
     vec,
 };
 
 use super::{
     system_tables::{
-        StColumnRow, StIndexRow, StSequenceRow, StTableRow, INDEX_ID_SEQUENCE_ID, SEQUENCE_ID_SEQUENCE_ID,
-        ST_COLUMNS_ID, ST_COLUMNS_ROW_TYPE, ST_INDEXES_ID, ST_INDEX_ROW_TYPE, ST_SEQUENCES_ID, ST_SEQUENCE_ROW_TYPE,
-        ST_TABLES_ID, ST_TABLE_ROW_TYPE, TABLE_ID_SEQUENCE_ID,
+        StColumnCompressionRow, StColumnRow, StConstraintRow, StContentionRow, StDiskUsageRow, StIndexRow,
+        StLargeValueRow, StSequenceRow, StStorageRow, StTableRow, StTableVersionRow, StWebhookDeadLetterRow,
+        INDEX_ID_SEQUENCE_ID, SEQUENCE_ID_SEQUENCE_ID, ST_BLOBS_ID, ST_BLOBS_ROW_TYPE, ST_COLUMNS_ID,
+        ST_COLUMNS_ROW_TYPE, ST_COLUMN_COMPRESSION_ID, ST_COLUMN_COMPRESSION_ROW_TYPE, ST_COLUMN_STATS_ID,
+        ST_COLUMN_STATS_ROW_TYPE, ST_CONSTRAINTS_ID, ST_CONSTRAINT_ROW_TYPE, ST_CONTENTION_ID, ST_CONTENTION_ROW_TYPE,
+        ST_DISK_USAGE_ID, ST_DISK_USAGE_ROW_TYPE, ST_INBOX_ID, ST_INBOX_ROW_TYPE, ST_INDEXES_ID, ST_INDEX_ROW_TYPE,
+        ST_LARGE_VALUES_ID, ST_LARGE_VALUES_ROW_TYPE, ST_OUTBOX_ID, ST_OUTBOX_ROW_TYPE, ST_REDUCER_COOLDOWN_ID,
+        ST_REDUCER_COOLDOWN_ROW_TYPE, ST_ROLES_ID, ST_ROLES_ROW_TYPE, ST_ROLE_MEMBERS_ID, ST_ROLE_MEMBERS_ROW_TYPE,
+        ST_SEQUENCES_ID, ST_SEQUENCE_ROW_TYPE, ST_STORAGE_ID, ST_STORAGE_ROW_TYPE, ST_TABLES_ID, ST_TABLE_ACL_ID,
+        ST_TABLE_ACL_ROW_TYPE, ST_TABLE_ROW_TYPE, ST_TABLE_VERSION_ID, ST_TABLE_VERSION_ROW_TYPE,
+        ST_WEBHOOK_DEAD_LETTER_ID, ST_WEBHOOK_DEAD_LETTER_ROW_TYPE, TABLE_ID_SEQUENCE_ID,
     },
     traits::{
-        self, ColId, DataRow, IndexDef, IndexId, IndexSchema, MutTx, MutTxDatastore, SequenceDef, SequenceId, TableDef,
-        TableId, TableSchema, TxData, TxDatastore,
+        self, ColId, DataRow, IndexDef, IndexId, IndexSchema, MutTx, MutTxDatastore, SavepointId, SequenceDef,
+        SequenceId, TableDef, TableId, TableSchema, TxData, TxDatastore,
     },
 };
 
@@ -30,19 +49,29 @@ use crate::{
     db::datastore::traits::{TxOp, TxRecord},
     db::{
         datastore::{
-            system_tables::{st_columns_schema, st_indexes_schema, st_sequences_schema, st_table_schema},
-            traits::ColumnSchema,
+            system_tables::{
+                st_blobs_schema, st_column_compression_schema, st_column_stats_schema, st_columns_schema,
+                st_constraints_schema, st_contention_schema, st_disk_usage_schema, st_inbox_schema, st_indexes_schema,
+                st_large_values_schema, st_outbox_schema, st_reducer_cooldown_schema, st_role_members_schema,
+                st_roles_schema, st_sequences_schema, st_storage_schema, st_table_acl_schema, st_table_schema,
+                st_table_version_schema, st_webhook_dead_letter_schema,
+            },
+            traits::{ColumnDef, ColumnSchema},
         },
         messages::{transaction::Transaction, write::Operation},
         ostorage::ObjectDB,
     },
-    error::{DBError, IndexError, TableError},
+    error::{DBError, IndexError, QuotaLimit, TableError},
+};
+use once_cell::sync::Lazy;
+use parking_lot::{
+    lock_api::{ArcRwLockReadGuard, ArcRwLockWriteGuard},
+    Mutex, RawRwLock, RwLock,
 };
-use parking_lot::{lock_api::ArcMutexGuard, Mutex, RawMutex};
 use spacetimedb_lib::{
     auth::{StAccess, StTableType},
     data_key::ToDataKey,
-    DataKey,
+    fulltext, spatial, AccessHint, Compression, DataKey, IndexType, LargeBytesRef,
 };
 use spacetimedb_sats::{
     AlgebraicType, AlgebraicValue, BuiltinType, BuiltinValue, ProductType, ProductTypeElement, ProductValue,
@@ -104,25 +133,544 @@ impl DataRef {
     }
 }
 
+/// The lock a transaction holds on the datastore:
+/// a read-only transaction shares it with the others, while a transaction that writes holds it alone.
+enum TxLock {
+    Read(ArcRwLockReadGuard<RawRwLock, Inner>),
+    Write(ArcRwLockWriteGuard<RawRwLock, Inner>),
+}
+
+impl Deref for TxLock {
+    type Target = Inner;
+    fn deref(&self) -> &Inner {
+        match self {
+            Self::Read(lock) => lock,
+            Self::Write(lock) => lock,
+        }
+    }
+}
+
+impl DerefMut for TxLock {
+    fn deref_mut(&mut self) -> &mut Inner {
+        match self {
+            Self::Read(_) => panic!("A read-only transaction can't write to the datastore."),
+            Self::Write(lock) => lock,
+        }
+    }
+}
+
 pub struct MutTxId {
-    lock: ArcMutexGuard<RawMutex, Inner>,
+    lock: TxLock,
+    /// The savepoints of the transaction, in the order they were taken,
+    /// each with the length of the undo log of its [`TxState`] when it was.
+    savepoints: Vec<(SavepointId, usize)>,
+    /// The id of the next savepoint, so that the ids of released savepoints aren't reused.
+    next_savepoint_id: u32,
+    /// The number of transactions that had been committed when this one began.
+    begin_offset: u64,
+    /// The tables this transaction has read from,
+    /// which must not have been written by a transaction committed after it began.
+    read_tables: Mutex<BTreeSet<TableId>>,
+    /// Whether this transaction has read the offset of the commit log,
+    /// which every transaction committed after it began changes.
+    read_offset: bool,
+}
+
+impl MutTxId {
+    /// Releases the lock on the datastore until the transaction is [resumed](SuspendedMutTx::resume),
+    /// so that other transactions can run in the meantime.
+    ///
+    /// When the transaction commits, it's checked for conflicts with the transactions
+    /// that were committed while it was suspended, and discarded if there are any.
+    pub fn suspend(mut self) -> SuspendedMutTx {
+        let TxLock::Write(lock) = &mut self.lock else {
+            panic!("A read-only transaction can't be suspended.");
+        };
+        let tx_state = lock.tx_state.take().unwrap();
+        let memory = std::mem::take(&mut lock.memory);
+        SuspendedMutTx {
+            inner: ArcRwLockWriteGuard::rwlock(lock).clone(),
+            tx_state,
+            memory,
+            savepoints: self.savepoints,
+            next_savepoint_id: self.next_savepoint_id,
+            begin_offset: self.begin_offset,
+            read_tables: self.read_tables,
+            read_offset: self.read_offset,
+        }
+    }
+
+    /// Returns whether this is a read-only transaction, begun with [`traits::Tx::begin_tx`],
+    /// which shares the lock on the datastore with the other read-only transactions.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.lock, TxLock::Read(_))
+    }
+
+    /// Records that this transaction has read the offset of the commit log,
+    /// so that it conflicts with every transaction committed after it began.
+    pub fn record_offset_read(&mut self) {
+        self.read_offset = true;
+    }
+
+    fn record_read(&self, table_id: TableId) {
+        // A write to a read-mostly table conflicts with every transaction anyway.
+        if self.lock.committed_state.access_hint(&table_id) != Some(AccessHint::ReadMostly) {
+            self.read_tables.lock().insert(table_id);
+        }
+    }
+
+    /// Removes `savepoint` and all the savepoints taken after it,
+    /// rolling back the changes made since it if `rollback`.
+    ///
+    /// The undo log stops being recorded once the last savepoint is removed.
+    fn take_savepoint(&mut self, savepoint: SavepointId, rollback: bool) -> super::Result<()> {
+        let index = self
+            .savepoints
+            .iter()
+            .position(|(id, _)| *id == savepoint)
+            .ok_or(DBError::SavepointNotFound(savepoint))?;
+        let len = self.savepoints.drain(index..).next().unwrap().1;
+        let tx_state = self.lock.tx_state.as_mut().unwrap();
+        if rollback {
+            tx_state.undo_to(len);
+        }
+        if self.savepoints.is_empty() {
+            tx_state.undo_log = None;
+        }
+        Ok(())
+    }
+}
+
+/// A transaction that doesn't hold the lock on the datastore, see [`MutTxId::suspend`].
+pub struct SuspendedMutTx {
+    inner: Arc<RwLock<Inner>>,
+    tx_state: TxState,
+    memory: BTreeMap<DataKey, Arc<Vec<u8>>>,
+    savepoints: Vec<(SavepointId, usize)>,
+    next_savepoint_id: u32,
+    begin_offset: u64,
+    read_tables: Mutex<BTreeSet<TableId>>,
+    read_offset: bool,
+}
+
+impl SuspendedMutTx {
+    /// Waits for the lock on the datastore and continues the transaction.
+    pub fn resume(self) -> MutTxId {
+        let mut lock = self.inner.write_arc();
+        if lock.tx_state.is_some() {
+            panic!("The previous transaction was not properly rolled back, committed or suspended.");
+        }
+        lock.tx_state = Some(self.tx_state);
+        lock.memory = self.memory;
+        MutTxId {
+            lock: TxLock::Write(lock),
+            savepoints: self.savepoints,
+            next_savepoint_id: self.next_savepoint_id,
+            begin_offset: self.begin_offset,
+            read_tables: self.read_tables,
+            read_offset: self.read_offset,
+        }
+    }
 }
 
 struct CommittedState {
     tables: HashMap<TableId, Table>,
+    /// The number of transactions committed so far.
+    commit_offset: u64,
+    /// For each table, the `commit_offset` of the last transaction that wrote to it.
+    table_commit_offsets: HashMap<TableId, u64>,
+    /// The `commit_offset` of the last transaction that wrote to a table
+    /// every transaction conflicts with, and that table.
+    ///
+    /// These are the system tables that define the schema of the database,
+    /// and the tables hinted as [`AccessHint::ReadMostly`].
+    exclusive_commit: Option<(u64, TableId)>,
+    /// The access hints of the module, by table name.
+    access_hints: HashMap<String, AccessHint>,
+    /// The rows of `st_contention`, by table.
+    contention: HashMap<TableId, StContentionRow>,
+    /// The rows of `st_table_version`, by table.
+    table_versions: HashMap<TableId, StTableVersionRow>,
+    /// The limit on the rows kept in memory, if any.
+    memory_budget: Option<MemoryBudget>,
+    /// The limits on the user tables and rows of the database.
+    quota: Quota,
+    /// The writes committed to the tables on which an [`IndexBuild`] is running.
+    index_builds: Vec<IndexBuildLog>,
+    /// The names of the unique constraints checked when a transaction commits,
+    /// rather than as each row is inserted.
+    deferred_constraints: HashSet<String>,
+    /// The version columns the datastore bumps on every update of a row,
+    /// each with the key column identifying the row, by table name.
+    row_versions: HashMap<String, (ColId, ColId)>,
+    /// The number of committed rows that reference each row of `st_large_values`.
+    large_value_refs: HashMap<DataKey, u64>,
+    /// The columns compressed in memory, with their codecs, by table name.
+    column_compression: HashMap<String, Vec<(ColId, Compression)>>,
+    /// The rows of `st_column_compression`, by table.
+    compression_stats: HashMap<TableId, Vec<StColumnCompressionRow>>,
 }
 
 impl CommittedState {
     fn new() -> Self {
-        Self { tables: HashMap::new() }
+        Self {
+            tables: HashMap::new(),
+            commit_offset: 0,
+            table_commit_offsets: HashMap::new(),
+            exclusive_commit: None,
+            access_hints: HashMap::new(),
+            contention: HashMap::new(),
+            table_versions: HashMap::new(),
+            memory_budget: None,
+            quota: Quota::default(),
+            index_builds: Vec::new(),
+            deferred_constraints: HashSet::new(),
+            row_versions: HashMap::new(),
+            large_value_refs: HashMap::new(),
+            column_compression: HashMap::new(),
+            compression_stats: HashMap::new(),
+        }
+    }
+
+    fn access_hint(&self, table_id: &TableId) -> Option<AccessHint> {
+        let table = self.tables.get(table_id)?;
+        self.access_hints.get(&table.schema.table_name).copied()
+    }
+
+    /// Returns whether inserts into `table_id` can't conflict with other inserts,
+    /// because it's hinted as [`AccessHint::AppendOnly`] and has no unique constraints to check.
+    fn is_append_only(&self, table_id: &TableId) -> bool {
+        self.access_hint(table_id) == Some(AccessHint::AppendOnly)
+            && !self.tables[table_id].indexes.values().any(|index| index.is_unique)
+    }
+
+    /// Returns the table on which a transaction that began at `begin_offset`,
+    /// and that read from `read_tables` and wrote `tx_state`,
+    /// conflicts with a transaction committed since, if any.
+    ///
+    /// A change to the schema or to a read-mostly table conflicts with every transaction,
+    /// as does a write to any table the transaction read from or wrote to,
+    /// except that inserts into an append-only table don't conflict with each other.
+    fn conflicts_with(
+        &self,
+        begin_offset: u64,
+        read_tables: &BTreeSet<TableId>,
+        tx_state: &TxState,
+    ) -> Option<TableId> {
+        if self.commit_offset == begin_offset {
+            return None;
+        }
+        if let Some((offset, table_id)) = self.exclusive_commit {
+            if offset > begin_offset {
+                return Some(table_id);
+            }
+        }
+        let written_since = |table_id: &&TableId| {
+            self.table_commit_offsets
+                .get(*table_id)
+                .map_or(false, |&offset| offset > begin_offset)
+        };
+        let inserted_tables = tx_state
+            .insert_tables
+            .iter()
+            .filter(|(table_id, table)| {
+                !self.is_append_only(table_id) || table.indexes.values().any(|index| index.is_unique)
+            })
+            .map(|(table_id, _)| table_id);
+        read_tables
+            .iter()
+            .chain(inserted_tables)
+            .chain(tx_state.delete_tables.keys())
+            .find(written_since)
+            .copied()
+    }
+
+    /// Checks the unique constraints deferred to the commit of `tx_state`:
+    /// the rows it inserts mustn't share a value with each other,
+    /// nor with a committed row it doesn't delete.
+    fn check_deferred_constraints(&self, tx_state: &TxState) -> super::Result<()> {
+        if self.deferred_constraints.is_empty() {
+            return Ok(());
+        }
+        for (table_id, insert_table) in &tx_state.insert_tables {
+            let deleted = tx_state.delete_tables.get(table_id);
+            let is_kept = |row_id: &RowId| deleted.map_or(true, |deleted| !deleted.contains(row_id));
+            let committed_table = self.tables.get(table_id);
+            let deferred_indexes = insert_table
+                .indexes
+                .values()
+                .filter(|index| index.is_unique && self.deferred_constraints.contains(&index.name));
+            for index in deferred_indexes {
+                let committed_index = committed_table.and_then(|table| table.indexes.get(&ColId(index.col_id)));
+                for row in insert_table.scan_rows() {
+                    let value = row.get_field(index.col_id as usize, None)?;
+                    let inserted = index.seek(value).filter(is_kept).count();
+                    let committed = committed_index.map_or(0, |index| index.seek(value).filter(is_kept).count());
+                    if inserted + committed > 1 {
+                        return Err(IndexError::UniqueConstraintViolation {
+                            constraint_name: index.name.clone(),
+                            table_name: insert_table.schema.table_name.clone(),
+                            col_id: index.col_id,
+                            col_name: insert_table.schema.columns[index.col_id as usize].col_name.clone(),
+                            value: value.clone(),
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records that a transaction that wrote `tx_state` has been committed.
+    fn record_commit(&mut self, tx_state: &TxState) {
+        self.commit_offset += 1;
+        let tables = tx_state.insert_tables.keys().chain(tx_state.delete_tables.keys());
+        for table_id in tables.copied().collect::<BTreeSet<_>>() {
+            self.table_commit_offsets.insert(table_id, self.commit_offset);
+            // `st_sequences` is also written when a sequence allocates more values,
+            // which doesn't change the schema.
+            if matches!(table_id, ST_TABLES_ID | ST_COLUMNS_ID | ST_INDEXES_ID)
+                || self.access_hint(&table_id) == Some(AccessHint::ReadMostly)
+            {
+                self.exclusive_commit = Some((self.commit_offset, table_id));
+            }
+            self.update_contention(table_id, |row| row.commits += 1);
+        }
+    }
+
+    /// Updates the row of `table_id` in `st_contention` with `f`.
+    ///
+    /// The row is changed in place rather than through a transaction,
+    /// so the change is neither logged nor a cause of conflicts.
+    fn update_contention(&mut self, table_id: TableId, f: impl FnOnce(&mut StContentionRow)) {
+        let row = self.contention.entry(table_id).or_insert(StContentionRow {
+            table_id: table_id.0,
+            ..Default::default()
+        });
+        let old_row = ProductValue::from(&*row);
+        f(row);
+        let new_row = ProductValue::from(&*row);
+        if let Some(st_contention) = self.tables.get_mut(&ST_CONTENTION_ID) {
+            st_contention.delete(&RowId(old_row.to_data_key()));
+            st_contention.insert(RowId(new_row.to_data_key()), new_row);
+        }
+    }
+
+    /// Counts a change to the rows of each of `table_ids` in `st_table_version`.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so the change is neither logged nor a cause of conflicts.
+    fn bump_table_versions(&mut self, table_ids: impl IntoIterator<Item = TableId>) {
+        for table_id in table_ids.into_iter().collect::<BTreeSet<_>>() {
+            let row = self.table_versions.entry(table_id).or_insert(StTableVersionRow {
+                table_id: table_id.0,
+                ..Default::default()
+            });
+            let old_row = ProductValue::from(&*row);
+            row.version += 1;
+            let new_row = ProductValue::from(&*row);
+            if let Some(st_table_version) = self.tables.get_mut(&ST_TABLE_VERSION_ID) {
+                st_table_version.delete(&RowId(old_row.to_data_key()));
+                st_table_version.insert(RowId(new_row.to_data_key()), new_row);
+            }
+        }
+    }
+
+    /// Returns the number of committed transactions that changed the rows of `table_id`.
+    fn table_version(&self, table_id: &TableId) -> u64 {
+        self.table_versions.get(table_id).map_or(0, |row| row.version)
+    }
+
+    /// Replaces the rows of `st_constraints` with those derived from the rows of `st_indexes`.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so it only reflects the indexes committed so far.
+    fn rebuild_constraints(&mut self) -> super::Result<()> {
+        let Some(st_indexes) = self.tables.get(&ST_INDEXES_ID) else {
+            return Ok(());
+        };
+        let mut rows = Vec::new();
+        for row in st_indexes.scan_rows() {
+            let index = StIndexRow::try_from(&*row)?;
+            if let Some(constraint) = StConstraintRow::for_index(&index) {
+                rows.push(ProductValue::from(&constraint));
+            }
+        }
+        let st_constraints =
+            self.get_or_create_table(ST_CONSTRAINTS_ID, &ST_CONSTRAINT_ROW_TYPE, &st_constraints_schema());
+        let old_rows = st_constraints
+            .scan_rows()
+            .map(|row| RowId(row.to_data_key()))
+            .collect::<Vec<_>>();
+        for row_id in &old_rows {
+            st_constraints.delete(row_id);
+        }
+        for row in rows {
+            st_constraints.insert(RowId(row.to_data_key()), row);
+        }
+        Ok(())
+    }
+
+    /// Counts the committed rows that reference each row of `st_large_values`.
+    fn rebuild_large_value_refs(&mut self) {
+        let mut refs = HashMap::new();
+        for table in self.tables.values().filter(|table| has_large_values(&table.row_type)) {
+            for row in table.scan_rows() {
+                for key in large_value_keys(&table.row_type, &row) {
+                    *refs.entry(key).or_insert(0) += 1;
+                }
+            }
+        }
+        self.large_value_refs = refs;
+    }
+
+    /// Returns the number of committed rows that reference the row of `st_large_values` under `key`,
+    /// once `change` is applied to it.
+    fn large_value_refs_after(&self, key: &DataKey, change: i64) -> i64 {
+        self.large_value_refs.get(key).map_or(0, |&refs| refs as i64) + change
+    }
+
+    /// Applies the `changes` a committed transaction made to the references to `st_large_values`.
+    fn apply_large_value_ref_changes(&mut self, changes: HashMap<DataKey, i64>) {
+        for (key, change) in changes {
+            match self.large_value_refs_after(&key, change) {
+                refs if refs > 0 => self.large_value_refs.insert(key, refs as u64),
+                _ => self.large_value_refs.remove(&key),
+            };
+        }
+    }
+
+    /// Compresses the columns of the tables in memory as `column_compression` says, by table name,
+    /// and only those.
+    fn set_column_compression(&mut self, column_compression: HashMap<String, Vec<(ColId, Compression)>>) {
+        self.column_compression = column_compression;
+        for table in self.tables.values_mut() {
+            let columns = self.column_compression.get(&table.schema.table_name);
+            table.set_compression(columns.map_or(&[][..], Vec::as_slice));
+        }
+        self.update_compression_stats();
+    }
+
+    /// Brings the rows of `st_column_compression` up to date with the compressed columns of the tables.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so the change is neither logged nor a cause of conflicts.
+    fn update_compression_stats(&mut self) {
+        let table_ids = self
+            .tables
+            .iter()
+            .filter(|(_, table)| !table.codecs.is_empty())
+            .map(|(table_id, _)| *table_id)
+            .chain(self.compression_stats.keys().copied())
+            .collect::<BTreeSet<_>>();
+        for table_id in table_ids {
+            let rows = self.tables.get(&table_id).map_or_else(Vec::new, |table| {
+                table
+                    .codecs
+                    .iter()
+                    .map(|codec| StColumnCompressionRow {
+                        table_id: table_id.0,
+                        col_id: codec.col_id.0,
+                        codec: codec.codec,
+                        raw_bytes: codec.raw_bytes(),
+                        stored_bytes: codec.stored_bytes(),
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let old_rows = self.compression_stats.remove(&table_id).unwrap_or_default();
+            if old_rows != rows {
+                if let Some(st_column_compression) = self.tables.get_mut(&ST_COLUMN_COMPRESSION_ID) {
+                    for row in &old_rows {
+                        st_column_compression.delete(&RowId(ProductValue::from(row).to_data_key()));
+                    }
+                    for row in &rows {
+                        let row = ProductValue::from(row);
+                        st_column_compression.insert(RowId(row.to_data_key()), row);
+                    }
+                }
+            }
+            if !rows.is_empty() {
+                self.compression_stats.insert(table_id, rows);
+            }
+        }
+    }
+
+    /// Evicts rows to disk until the rows in memory fit in the memory budget, if there is one.
+    ///
+    /// The rows of the tables written to least recently are evicted first,
+    /// and down to three quarters of the budget, so that the commits that follow
+    /// don't each have to evict a few rows.
+    /// The rows of system tables are never evicted, except for `st_large_values`,
+    /// whose rows are only ever read one at a time.
+    fn enforce_memory_budget(&mut self) -> super::Result<()> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(());
+        };
+        let resident_bytes: usize = self.tables.values().map(|table| table.resident_bytes).sum();
+        if resident_bytes <= budget.max_bytes {
+            return Ok(());
+        }
+        let mut to_free = resident_bytes - budget.max_bytes / 4 * 3;
+
+        let table_commit_offsets = &self.table_commit_offsets;
+        let mut tables = self
+            .tables
+            .iter_mut()
+            .filter(|(table_id, table)| {
+                table.schema.table_type != StTableType::System || **table_id == ST_LARGE_VALUES_ID
+            })
+            .collect::<Vec<_>>();
+        tables.sort_by_key(|(table_id, _)| table_commit_offsets.get(table_id).copied().unwrap_or(0));
+        for (_, table) in tables {
+            if to_free == 0 {
+                break;
+            }
+            to_free -= table.evict(&budget.spill_file, to_free)?.min(to_free);
+        }
+        Ok(())
+    }
+
+    /// Compacts the committed tables, see [`Table::vacuum`], the maps kept by table,
+    /// and the file of the rows evicted to disk, if any, returning the space reclaimed.
+    fn vacuum(&mut self) -> super::Result<VacuumReport> {
+        let mut memory_bytes: usize = self.tables.values_mut().map(Table::vacuum).sum();
+        memory_bytes += vacuum::shrink_map(&mut self.tables)
+            + vacuum::shrink_map(&mut self.table_commit_offsets)
+            + vacuum::shrink_map(&mut self.contention)
+            + vacuum::shrink_map(&mut self.table_versions)
+            + vacuum::shrink_map(&mut self.large_value_refs)
+            + vacuum::shrink_map(&mut self.compression_stats);
+        let spill_bytes = match &self.memory_budget {
+            Some(budget) => budget.spill_file.compact(
+                self.tables
+                    .values_mut()
+                    .flat_map(|table| table.spilled_slots_mut(&budget.spill_file))
+                    .collect(),
+            )?,
+            None => 0,
+        };
+        Ok(VacuumReport {
+            memory_bytes: memory_bytes as u64,
+            spill_bytes,
+        })
     }
 
     fn get_or_create_table(&mut self, table_id: TableId, row_type: &ProductType, schema: &TableSchema) -> &mut Table {
-        self.tables.entry(table_id).or_insert_with(|| Table {
-            row_type: row_type.clone(),
-            schema: schema.clone(),
-            rows: BTreeMap::new(),
-            indexes: HashMap::new(),
+        self.tables.entry(table_id).or_insert_with(|| {
+            let mut table = Table {
+                row_type: row_type.clone(),
+                schema: schema.clone(),
+                rows: BTreeMap::new(),
+                spilled: SpilledRows::default(),
+                resident_bytes: 0,
+                indexes: HashMap::new(),
+                codecs: Vec::new(),
+            };
+            if let Some(columns) = self.column_compression.get(&schema.table_name) {
+                table.set_compression(columns);
+            }
+            table
         })
     }
 
@@ -131,9 +679,26 @@ impl CommittedState {
     }
 
     fn merge(&mut self, tx_state: TxState, memory: BTreeMap<DataKey, Arc<Vec<u8>>>) -> TxData {
-        let mut tx_data = TxData { records: vec![] };
+        let mut tx_data = TxData {
+            records: vec![],
+            tx_offset: None,
+        };
+        // The indexes built by an `IndexBuild` already hold the committed rows,
+        // so they're added before the writes of the transaction, which then update them like any index.
+        for index in tx_state.built_indexes {
+            if let Some(table) = self.get_table(&TableId(index.table_id)) {
+                table.schema.indexes.push(IndexSchema::from(&index));
+                table.indexes.insert(ColId(index.col_id), index);
+            }
+        }
         for (table_id, table) in tx_state.insert_tables {
             let commit_table = self.get_or_create_table(table_id, &table.row_type, &table.schema);
+            // Columns added by the transaction change the layout of the committed rows too,
+            // which it has rewritten in the new one.
+            if table.row_type.elements.len() > commit_table.row_type.elements.len() {
+                commit_table.row_type = table.row_type.clone();
+                commit_table.schema.columns = table.schema.columns.clone();
+            }
             tx_data.records.extend(table.rows.into_iter().map(|(row_id, row)| {
                 commit_table.insert(row_id, row.clone());
                 let pv = row;
@@ -179,6 +744,10 @@ impl CommittedState {
                 }
             }
         }
+        self.index_builds.retain(IndexBuildLog::is_live);
+        for log in &mut self.index_builds {
+            log.record(&tx_data.records);
+        }
         tx_data
     }
 
@@ -194,6 +763,25 @@ impl CommittedState {
             None
         }
     }
+
+    pub fn index_search<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        query: &str,
+    ) -> Option<BTreeIndexRangeIter<'a>> {
+        self.tables.get(table_id)?.index_search(*col_id, query)
+    }
+
+    pub fn index_search_box<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        min: &AlgebraicValue,
+        max: &AlgebraicValue,
+    ) -> Option<BTreeIndexRangeIter<'a>> {
+        self.tables.get(table_id)?.index_search_box(*col_id, min, max)
+    }
 }
 
 /// `TxState` tracks all of the modifications made during a particular transaction.
@@ -226,6 +814,28 @@ struct TxState {
     /// For each table,  additions have
     insert_tables: HashMap<TableId, Table>,
     delete_tables: HashMap<TableId, BTreeSet<RowId>>,
+    /// The indexes built by an [`IndexBuild`], which are added to their tables on commit.
+    built_indexes: Vec<BTreeIndex>,
+    /// The changes made since the first savepoint of the transaction, in order,
+    /// or `None` when it has no savepoints.
+    undo_log: Option<Vec<Undo>>,
+}
+
+/// A change made to a [`TxState`] after a savepoint, which rolling back to it reverts.
+enum Undo {
+    /// A row was added to the insert table of its table.
+    Insert(TableId, RowId),
+    /// A row the transaction inserted was removed from the insert table of its table.
+    DeleteInserted(TableId, RowId, ProductValue),
+    /// A committed row was added to the delete table of its table.
+    DeleteCommitted(TableId, RowId),
+    /// A committed row the transaction deleted was inserted again, so removed from the delete table.
+    Reinsert(TableId, RowId),
+    /// The insert table of a table was created or had its schema changed,
+    /// and was this before, if it existed.
+    Table(TableId, Option<Table>),
+    /// An index was added to `built_indexes`.
+    BuiltIndex,
 }
 
 /// Represents whether a row has been previously committed, inserted
@@ -249,6 +859,58 @@ impl TxState {
         Self {
             insert_tables: HashMap::new(),
             delete_tables: HashMap::new(),
+            built_indexes: Vec::new(),
+            undo_log: None,
+        }
+    }
+
+    /// Records the change made by `undo` if the transaction has savepoints.
+    fn record(&mut self, undo: impl FnOnce(&Self) -> Undo) {
+        if self.undo_log.is_some() {
+            let undo = undo(self);
+            self.undo_log.as_mut().unwrap().push(undo);
+        }
+    }
+
+    /// Records the insert table of `table_id` as it is now, before its creation or a change to its schema.
+    fn record_table(&mut self, table_id: TableId) {
+        self.record(|tx_state| Undo::Table(table_id, tx_state.insert_tables.get(&table_id).cloned()));
+    }
+
+    /// Reverts the changes recorded after the first `len` ones.
+    fn undo_to(&mut self, len: usize) {
+        let Some(undo_log) = self.undo_log.as_mut() else {
+            return;
+        };
+        let undone = undo_log.drain(len..).collect::<Vec<_>>();
+        for undo in undone.into_iter().rev() {
+            match undo {
+                Undo::Insert(table_id, row_id) => {
+                    if let Some(table) = self.insert_tables.get_mut(&table_id) {
+                        table.delete(&row_id);
+                    }
+                }
+                Undo::DeleteInserted(table_id, row_id, row) => {
+                    if let Some(table) = self.insert_tables.get_mut(&table_id) {
+                        table.insert(row_id, row);
+                    }
+                }
+                Undo::DeleteCommitted(table_id, row_id) => {
+                    self.get_or_create_delete_table(table_id).remove(&row_id);
+                }
+                Undo::Reinsert(table_id, row_id) => {
+                    self.get_or_create_delete_table(table_id).insert(row_id);
+                }
+                Undo::Table(table_id, Some(table)) => {
+                    self.insert_tables.insert(table_id, table);
+                }
+                Undo::Table(table_id, None) => {
+                    self.insert_tables.remove(&table_id);
+                }
+                Undo::BuiltIndex => {
+                    self.built_indexes.pop();
+                }
+            }
         }
     }
 
@@ -260,7 +922,8 @@ impl TxState {
             return RowState::Absent;
         };
         table
-            .get_row(row_id)
+            .rows
+            .get(row_id)
             .map(|pv| RowState::Insert(pv.clone()))
             .unwrap_or(RowState::Absent)
     }
@@ -272,7 +935,14 @@ impl TxState {
         let Some(table) = self.insert_tables.get(table_id) else {
             return None;
         };
-        table.get_row(row_id)
+        // Rows inserted by a transaction are never evicted.
+        table.rows.get(row_id)
+    }
+
+    pub fn is_deleted(&self, table_id: &TableId, row_id: &RowId) -> bool {
+        self.delete_tables
+            .get(table_id)
+            .map_or(false, |deleted| deleted.contains(row_id))
     }
 
     pub fn get_insert_table_mut(&mut self, table_id: &TableId) -> Option<&mut Table> {
@@ -303,6 +973,35 @@ impl TxState {
     ) -> Option<BTreeIndexRangeIter<'a>> {
         self.insert_tables.get(table_id)?.index_seek(*col_id, value)
     }
+
+    /// When there's a full-text index on `col_id`,
+    /// returns an iterator over the [BTreeIndex] that yields the `RowId`s
+    /// of the rows inserted in this transaction that may match `query`.
+    ///
+    /// When there is no full-text index this returns `None`.
+    pub fn index_search<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        query: &str,
+    ) -> Option<BTreeIndexRangeIter<'a>> {
+        self.insert_tables.get(table_id)?.index_search(*col_id, query)
+    }
+
+    /// When there's a spatial index on `col_id`,
+    /// returns an iterator over the [BTreeIndex] that yields the `RowId`s
+    /// of the rows inserted in this transaction that may be in the box from `min` to `max`.
+    ///
+    /// When there is no spatial index this returns `None`.
+    pub fn index_search_box<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        min: &AlgebraicValue,
+        max: &AlgebraicValue,
+    ) -> Option<BTreeIndexRangeIter<'a>> {
+        self.insert_tables.get(table_id)?.index_search_box(*col_id, min, max)
+    }
 }
 
 struct SequencesState {
@@ -342,6 +1041,13 @@ impl Inner {
         }
     }
 
+    /// Returns the state of the running transaction,
+    /// which is that of a transaction without changes for a read-only one.
+    fn tx_state(&self) -> &TxState {
+        static NO_CHANGES: Lazy<TxState> = Lazy::new(TxState::new);
+        self.tx_state.as_ref().unwrap_or(&NO_CHANGES)
+    }
+
     fn bootstrap_system_table(&mut self, schema: TableSchema) -> Result<(), DBError> {
         let table_id = schema.table_id;
         let table_name = &schema.table_name;
@@ -354,11 +1060,11 @@ impl Inner {
             table_id,
             table_name: &table_name,
             table_type: StTableType::System,
-            table_access: StAccess::Public,
+            table_access: schema.table_access,
         };
         let row: ProductValue = (&row).into();
         let data_key = row.to_data_key();
-        st_tables.rows.insert(RowId(data_key), row);
+        st_tables.insert(RowId(data_key), row);
 
         // Insert the columns into st_columns
         for (i, col) in schema.columns.iter().enumerate() {
@@ -375,7 +1081,7 @@ impl Inner {
                 let st_columns =
                     self.committed_state
                         .get_or_create_table(ST_COLUMNS_ID, &ST_COLUMNS_ROW_TYPE, &st_columns_schema());
-                st_columns.rows.insert(RowId(data_key), row);
+                st_columns.insert(RowId(data_key), row);
             }
 
             // If any columns are auto incrementing, we need to create a sequence
@@ -405,7 +1111,7 @@ impl Inner {
                 };
                 let row = ProductValue::from(&row);
                 let data_key = row.to_data_key();
-                st_sequences.rows.insert(RowId(data_key), row);
+                st_sequences.insert(RowId(data_key), row);
             }
         }
 
@@ -420,10 +1126,11 @@ impl Inner {
                 col_id: index.col_id,
                 index_name: &index.index_name,
                 is_unique: index.is_unique,
+                index_type: index.index_type,
             };
             let row = ProductValue::from(&row);
             let data_key = row.to_data_key();
-            st_indexes.rows.insert(RowId(data_key), row);
+            st_indexes.insert(RowId(data_key), row);
         }
 
         Ok(())
@@ -431,7 +1138,7 @@ impl Inner {
 
     fn build_sequence_state(&mut self) -> super::Result<()> {
         let st_sequences = self.committed_state.tables.get(&ST_SEQUENCES_ID).unwrap();
-        let rows = st_sequences.scan_rows().cloned().collect::<Vec<_>>();
+        let rows = st_sequences.scan_rows().map(Cow::into_owned).collect::<Vec<_>>();
         for row in rows {
             let sequence = StSequenceRow::try_from(&row)?;
             let schema = (&sequence).into();
@@ -444,7 +1151,7 @@ impl Inner {
 
     fn build_indexes(&mut self) -> super::Result<()> {
         let st_indexes = self.committed_state.tables.get(&ST_INDEXES_ID).unwrap();
-        let rows = st_indexes.scan_rows().cloned().collect::<Vec<_>>();
+        let rows = st_indexes.scan_rows().map(Cow::into_owned).collect::<Vec<_>>();
         for row in rows {
             let index_row = StIndexRow::try_from(&row)?;
             let table = self.committed_state.get_table(&TableId(index_row.table_id)).unwrap();
@@ -454,6 +1161,7 @@ impl Inner {
                 index_row.col_id,
                 index_row.index_name.into(),
                 index_row.is_unique,
+                index_row.index_type,
             );
             index.build_from_rows(table.scan_rows())?;
             table.indexes.insert(ColId(index_row.col_id), index);
@@ -466,7 +1174,7 @@ impl Inner {
     /// been created. This function ensures that they are created.
     fn build_missing_tables(&mut self) -> super::Result<()> {
         let st_tables = self.committed_state.tables.get(&ST_TABLES_ID).unwrap();
-        let rows = st_tables.scan_rows().cloned().collect::<Vec<_>>();
+        let rows = st_tables.scan_rows().map(Cow::into_owned).collect::<Vec<_>>();
         for row in rows {
             let table_row = StTableRow::try_from(&row)?;
             let table_id = TableId(table_row.table_id);
@@ -480,6 +1188,9 @@ impl Inner {
                         schema,
                         indexes: HashMap::new(),
                         rows: BTreeMap::new(),
+                        spilled: SpilledRows::default(),
+                        resident_bytes: 0,
+                        codecs: Vec::new(),
                     },
                 );
             }
@@ -631,6 +1342,9 @@ impl Inner {
         if table_name_is_system(table_name) {
             return Err(TableError::System(table_name.into()).into());
         }
+        if table_schema.table_type == StTableType::User {
+            self.check_table_quota()?;
+        }
         // Insert the table row into st_tables
         // NOTE: Because st_tables has a unique index on table_name, this will
         // fail if the table already exists.
@@ -689,19 +1403,71 @@ impl Inner {
         Ok(TableId(table_id))
     }
 
+    /// Returns an error if the database can't have another user table under its quota.
+    fn check_table_quota(&self) -> super::Result<()> {
+        let quota = self.committed_state.quota;
+        if quota.max_tables.is_none() {
+            return Ok(());
+        }
+        let mut tables = 0;
+        for row in self.iter(&ST_TABLES_ID)? {
+            if StTableRow::try_from(row.view())?.table_type == StTableType::User {
+                tables += 1;
+            }
+        }
+        quota.check(QuotaLimit::Tables, tables + 1)?;
+        Ok(())
+    }
+
+    /// Returns an error if the database can't have another user row of `row_bytes` bytes under its quota.
+    ///
+    /// The rows this transaction deleted make room for new rows right away,
+    /// but their bytes only once it's committed.
+    fn check_row_quota(&self, row_bytes: usize) -> super::Result<()> {
+        let quota = self.committed_state.quota;
+        if quota.max_rows.is_none() && quota.max_bytes.is_none() {
+            return Ok(());
+        }
+        let is_user_table = |table: &Table| table.schema.table_type == StTableType::User;
+        let tx_state = self.tx_state.as_ref().unwrap();
+
+        let (mut rows, mut bytes) = (1, row_bytes as u64);
+        for table in self.committed_state.tables.values().filter(|t| is_user_table(t)) {
+            rows += table.row_count() as u64;
+            bytes += table.stored_bytes() as u64;
+        }
+        for table in tx_state.insert_tables.values().filter(|t| is_user_table(t)) {
+            rows += table.row_count() as u64;
+            bytes += table.stored_bytes() as u64;
+        }
+        for (table_id, deleted) in &tx_state.delete_tables {
+            if self.committed_state.tables.get(table_id).map_or(false, is_user_table) {
+                rows = rows.saturating_sub(deleted.len() as u64);
+            }
+        }
+        quota.check(QuotaLimit::Rows, rows)?;
+        quota.check(QuotaLimit::Bytes, bytes)?;
+        Ok(())
+    }
+
     fn create_table_internal(
         &mut self,
         table_id: TableId,
         row_type: ProductType,
         schema: TableSchema,
     ) -> super::Result<()> {
-        self.tx_state.as_mut().unwrap().insert_tables.insert(
+        let tx_state = self.tx_state.as_mut().unwrap();
+        tx_state.record_table(table_id);
+        tx_state.insert_tables.insert(
             table_id,
             Table {
                 row_type,
                 schema,
                 indexes: HashMap::new(),
                 rows: BTreeMap::new(),
+                spilled: SpilledRows::default(),
+                resident_bytes: 0,
+                codecs: Vec::new(),
             },
         );
         Ok(())
@@ -791,6 +1557,7 @@ impl Inner {
                 index_name: el.index_name.into(),
                 is_unique: el.is_unique,
                 index_id: el.index_id,
+                index_type: el.index_type,
             };
             indexes.push(index_schema);
         }
@@ -845,7 +1612,31 @@ impl Inner {
         // Delete the table and its rows and indexes from memory.
         // TODO: This needs to not remove it from the committed state, because it can still be rolled back.
         // We will have to store the deletion in the TxState and then apply it to the CommittedState in commit.
-        self.committed_state.tables.remove(&table_id);
+        let Some(table) = self.committed_state.tables.remove(&table_id) else {
+            return Ok(());
+        };
+        self.committed_state.update_compression_stats();
+
+        // Like its rows, the references they held to large values are gone at once,
+        // and the values no other row references are deleted.
+        let mut dropped_refs = HashMap::new();
+        for row in table.scan_rows() {
+            for key in large_value_keys(&table.row_type, &row) {
+                *dropped_refs.entry(key).or_insert(0) -= 1;
+            }
+        }
+        if dropped_refs.is_empty() {
+            return Ok(());
+        }
+        let keys = dropped_refs.keys().copied().collect::<Vec<_>>();
+        self.committed_state.apply_large_value_ref_changes(dropped_refs);
+        let changes = self.large_value_ref_changes();
+        for key in keys {
+            let change = changes.get(&key).copied().unwrap_or(0);
+            if self.committed_state.large_value_refs_after(&key, change) <= 0 {
+                self.delete_row_internal(&ST_LARGE_VALUES_ID, &RowId(key));
+            }
+        }
         Ok(())
     }
 
@@ -865,28 +1656,97 @@ impl Inner {
         Ok(())
     }
 
-    fn table_id_from_name(&self, table_name: &str) -> super::Result<Option<TableId>> {
-        let table_name_col: ColId = ColId(1);
-        self.iter_by_col_eq(
-            &ST_TABLES_ID,
-            &table_name_col,
-            &AlgebraicValue::String(table_name.to_owned()),
-        )
-        .map(|mut iter| {
-            iter.next()
-                .map(|row| TableId(*row.view().elements[0].as_u32().unwrap()))
-        })
-    }
+    /// Adds `columns` after the existing columns of the table `table_id`,
+    /// setting them to `None` in the rows it already has, so they must all be `Option`s.
+    ///
+    /// The rows are rewritten in the new layout by this transaction,
+    /// so that every row of the table has all of its columns once it commits.
+    fn add_columns(&mut self, table_id: TableId, columns: Vec<ColumnDef>) -> super::Result<()> {
+        let schema = self.schema_for_table(table_id)?;
+        if table_name_is_system(&schema.table_name) {
+            return Err(TableError::System(schema.table_name).into());
+        }
 
-    fn table_name_from_id(&self, table_id: TableId) -> super::Result<Option<String>> {
-        let table_id_col: ColId = ColId(0);
-        self.iter_by_col_eq(&ST_TABLES_ID, &table_id_col, &AlgebraicValue::U32(table_id.0))
-            .map(|mut iter| {
-                iter.next()
-                    .map(|row| row.view().elements[1].as_string().unwrap().to_owned())
+        // Record the new columns in st_columns.
+        let first_col_id = schema.columns.len() as u32;
+        let added = columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, col)| ColumnSchema {
+                table_id: table_id.0,
+                col_id: first_col_id + i as u32,
+                col_name: col.col_name,
+                col_type: col.col_type,
+                is_autoinc: false,
             })
-    }
-
+            .collect::<Vec<_>>();
+        for col in &added {
+            let row = StColumnRow {
+                table_id: table_id.0,
+                col_id: col.col_id,
+                col_name: &col.col_name,
+                col_type: col.col_type.clone(),
+                is_autoinc: false,
+            };
+            self.insert(ST_COLUMNS_ID, (&row).into())?;
+        }
+
+        // Without an in-memory table, its layout is read back from st_columns.
+        if self.get_row_type(&table_id).is_none() {
+            return Ok(());
+        }
+
+        // Take the rows out of the table in their old layout...
+        let rows = self.iter(&table_id)?.map(|row| row.view().clone()).collect::<Vec<_>>();
+        for row in &rows {
+            self.delete(&table_id, &RowId(row.to_data_key()))?;
+        }
+
+        // ...change the layout of the table...
+        self.ensure_insert_table(table_id)?;
+        let tx_state = self.tx_state.as_mut().unwrap();
+        tx_state.record_table(table_id);
+        let insert_table = tx_state.get_insert_table_mut(&table_id).unwrap();
+        for col in added {
+            insert_table.row_type.elements.push(ProductTypeElement {
+                name: None,
+                algebraic_type: col.col_type.clone(),
+            });
+            insert_table.schema.columns.push(col);
+        }
+
+        // ...and put them back in the new one.
+        let new_cols = insert_table.row_type.elements.len() - first_col_id as usize;
+        for mut row in rows {
+            row.elements
+                .extend(std::iter::repeat_with(AlgebraicValue::OptionNone).take(new_cols));
+            self.insert_row_internal(table_id, row)?;
+        }
+        Ok(())
+    }
+
+    fn table_id_from_name(&self, table_name: &str) -> super::Result<Option<TableId>> {
+        let table_name_col: ColId = ColId(1);
+        self.iter_by_col_eq(
+            &ST_TABLES_ID,
+            &table_name_col,
+            &AlgebraicValue::String(table_name.to_owned()),
+        )
+        .map(|mut iter| {
+            iter.next()
+                .map(|row| TableId(*row.view().elements[0].as_u32().unwrap()))
+        })
+    }
+
+    fn table_name_from_id(&self, table_id: TableId) -> super::Result<Option<String>> {
+        let table_id_col: ColId = ColId(0);
+        self.iter_by_col_eq(&ST_TABLES_ID, &table_id_col, &AlgebraicValue::U32(table_id.0))
+            .map(|mut iter| {
+                iter.next()
+                    .map(|row| row.view().elements[1].as_string().unwrap().to_owned())
+            })
+    }
+
     fn create_index(&mut self, index: IndexDef) -> super::Result<IndexId> {
         log::trace!(
             "INDEX CREATING: {} for table: {} and col: {}",
@@ -895,7 +1755,30 @@ impl Inner {
             index.col_id
         );
 
-        // Insert the index row into st_indexes
+        if matches!(index.index_type, IndexType::FullText | IndexType::Spatial) {
+            let row_type = self.row_type_for_table(TableId(index.table_id))?;
+            check_index_column(&index, &row_type)?;
+        }
+
+        let index_id = self.insert_index_row(&index)?;
+
+        // Create the index in memory
+        if !self.table_exists(&TableId(index.table_id)) {
+            return Err(TableError::IdNotFound(index.table_id).into());
+        }
+        self.create_index_internal(index_id, &index)?;
+
+        log::trace!(
+            "INDEX CREATED: {} for table: {} and col: {}",
+            index.name,
+            index.table_id,
+            index.col_id
+        );
+        Ok(index_id)
+    }
+
+    /// Inserts the row of `index` into st_indexes, returning its generated id.
+    fn insert_index_row(&mut self, index: &IndexDef) -> super::Result<IndexId> {
         // NOTE: Because st_indexes has a unique index on index_name, this will
         // fail if the index already exists.
         let row = StIndexRow {
@@ -904,25 +1787,59 @@ impl Inner {
             col_id: index.col_id,
             index_name: &index.name,
             is_unique: index.is_unique,
+            index_type: index.index_type,
         };
         let index_id = StIndexRow::try_from(&self.insert(ST_INDEXES_ID, (&row).into())?)?.index_id;
+        Ok(IndexId(index_id))
+    }
 
-        // Create the index in memory
-        if !self.table_exists(&TableId(index.table_id)) {
-            return Err(TableError::IdNotFound(index.table_id).into());
+    /// Creates the index built by `build`, catching it up with the writes committed during the build,
+    /// and adds it to its table when the transaction commits.
+    fn create_built_index(&mut self, build: IndexBuild) -> super::Result<IndexId> {
+        let IndexBuild {
+            def, mut index, log, ..
+        } = build;
+        let table_id = TableId(def.table_id);
+        let pos = self
+            .committed_state
+            .index_builds
+            .iter()
+            .position(|build_log| build_log.is_for(&log))
+            .expect("the log of a live index build was discarded");
+        self.committed_state.index_builds.swap_remove(pos).replay(&mut index)?;
+
+        if !self.table_exists(&table_id) {
+            return Err(TableError::IdNotFound(def.table_id).into());
+        }
+        if let Some(value) = index.is_unique.then(|| index.first_duplicate()).flatten() {
+            let schema = self.schema_for_table(table_id)?;
+            return Err(IndexError::UniqueConstraintViolation {
+                constraint_name: def.name.clone(),
+                table_name: schema.table_name.clone(),
+                col_id: def.col_id,
+                col_name: schema.columns[def.col_id as usize].col_name.clone(),
+                value: value.clone(),
+            }
+            .into());
         }
-        self.create_index_internal(IndexId(index_id), &index)?;
+
+        let index_id = self.insert_index_row(&def)?;
+        index.index_id = index_id;
+        let tx_state = self.tx_state.as_mut().unwrap();
+        tx_state.record(|_| Undo::BuiltIndex);
+        tx_state.built_indexes.push(index);
 
         log::trace!(
-            "INDEX CREATED: {} for table: {} and col: {}",
-            index.name,
-            index.table_id,
-            index.col_id
+            "INDEX CREATED ONLINE: {} for table: {} and col: {}",
+            def.name,
+            def.table_id,
+            def.col_id
         );
-        Ok(IndexId(index_id))
+        Ok(index_id)
     }
 
     fn create_index_internal(&mut self, index_id: IndexId, index: &IndexDef) -> super::Result<()> {
+        self.tx_state.as_mut().unwrap().record_table(TableId(index.table_id));
         let insert_table = if let Some(insert_table) = self
             .tx_state
             .as_mut()
@@ -940,6 +1857,9 @@ impl Inner {
                     schema,
                     indexes: HashMap::new(),
                     rows: BTreeMap::new(),
+                    spilled: SpilledRows::default(),
+                    resident_bytes: 0,
+                    codecs: Vec::new(),
                 },
             );
             self.tx_state
@@ -955,6 +1875,7 @@ impl Inner {
             index.col_id,
             index.name.to_string(),
             index.is_unique,
+            index.index_type,
         );
         insert_index.build_from_rows(insert_table.scan_rows())?;
 
@@ -969,6 +1890,7 @@ impl Inner {
             index_name: index.name.to_string(),
             is_unique: index.is_unique,
             index_id: index_id.0,
+            index_type: index.index_type,
         });
 
         insert_table.indexes.insert(ColId(index.col_id), insert_index);
@@ -1044,7 +1966,7 @@ impl Inner {
     }
 
     fn contains_row(&self, table_id: &TableId, row_id: &RowId) -> RowState {
-        match self.tx_state.as_ref().unwrap().get_row_op(table_id, row_id) {
+        match self.tx_state().get_row_op(table_id, row_id) {
             RowState::Committed(_) => unreachable!("a row cannot be committed in a tx state"),
             RowState::Insert(pv) => return RowState::Insert(pv),
             RowState::Delete => return RowState::Delete,
@@ -1054,9 +1976,9 @@ impl Inner {
             .committed_state
             .tables
             .get(table_id)
-            .and_then(|table| table.rows.get(row_id))
+            .and_then(|table| table.get_row(row_id))
         {
-            Some(pv) => RowState::Committed(pv.clone()),
+            Some(pv) => RowState::Committed(pv.into_owned()),
             None => RowState::Absent,
         }
     }
@@ -1155,10 +2077,81 @@ impl Inner {
                 }
             }
         }
+        if let Some(&(col_id, key_col_id)) = self.committed_state.row_versions.get(&schema.table_name) {
+            let version = self.replaced_row_version(table_id, &row, col_id, key_col_id);
+            row.elements[col_id.0 as usize] = AlgebraicValue::U64(version.map_or(0, |v| v.wrapping_add(1)));
+        }
         self.insert_row_internal(table_id, row.clone())?;
         Ok(row)
     }
 
+    /// Returns the version in `col_id` of the committed row this transaction deleted
+    /// that has the same key in `key_col_id` as `row`, if any.
+    fn replaced_row_version(
+        &self,
+        table_id: TableId,
+        row: &ProductValue,
+        col_id: ColId,
+        key_col_id: ColId,
+    ) -> Option<u64> {
+        let deleted = self.tx_state.as_ref().unwrap().delete_tables.get(&table_id)?;
+        let table = self.committed_state.tables.get(&table_id)?;
+        let key = row.elements.get(key_col_id.0 as usize)?;
+        let row_id = table
+            .indexes
+            .get(&key_col_id)?
+            .seek(key)
+            .find(|row_id| deleted.contains(row_id))?;
+        table
+            .get_row(&row_id)?
+            .elements
+            .get(col_id.0 as usize)?
+            .as_u64()
+            .copied()
+    }
+
+    /// Creates the insert table of `table_id` in the tx state, if it doesn't have one yet,
+    /// based on the table in the committed state.
+    ///
+    /// If the table does not exist in the committed state either, it doesn't exist in the database.
+    fn ensure_insert_table(&mut self, table_id: TableId) -> super::Result<()> {
+        if self.tx_state.as_ref().unwrap().get_insert_table(&table_id).is_some() {
+            return Ok(());
+        }
+        let Some(committed_table) = self.committed_state.tables.get(&table_id) else {
+            return Err(TableError::IdNotFound(table_id.0).into());
+        };
+        let table = Table {
+            row_type: committed_table.row_type.clone(),
+            schema: committed_table.get_schema().clone(),
+            indexes: committed_table
+                .indexes
+                .iter()
+                .map(|(col_id, index)| {
+                    (
+                        *col_id,
+                        BTreeIndex::new(
+                            index.index_id,
+                            index.table_id,
+                            index.col_id,
+                            index.name.clone(),
+                            index.is_unique,
+                            index.index_type,
+                        ),
+                    )
+                })
+                .collect::<HashMap<_, _>>(),
+            rows: BTreeMap::new(),
+            spilled: SpilledRows::default(),
+            resident_bytes: 0,
+            codecs: Vec::new(),
+        };
+        let tx_state = self.tx_state.as_mut().unwrap();
+        tx_state.record_table(table_id);
+        tx_state.insert_tables.insert(table_id, table);
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all)]
     fn insert_row_internal(&mut self, table_id: TableId, row: ProductValue) -> super::Result<()> {
         let mut bytes = Vec::new();
@@ -1166,55 +2159,39 @@ impl Inner {
         let data_key = DataKey::from_data(&bytes);
         let row_id = RowId(data_key);
 
-        // If the table does exist in the tx state, we need to create it based on the table in the
-        // committed state. If the table does not exist in the committed state, it doesn't exist
-        // in the database.
-        let insert_table = if let Some(table) = self.tx_state.as_ref().unwrap().get_insert_table(&table_id) {
-            table
-        } else {
-            let Some(committed_table) = self.committed_state.tables.get(&table_id) else {
-                return Err(TableError::IdNotFound(table_id.0).into());
-            };
-            let table = Table {
-                row_type: committed_table.row_type.clone(),
-                schema: committed_table.get_schema().clone(),
-                indexes: committed_table
-                    .indexes
-                    .iter()
-                    .map(|(col_id, index)| {
-                        (
-                            *col_id,
-                            BTreeIndex::new(
-                                index.index_id,
-                                index.table_id,
-                                index.col_id,
-                                index.name.clone(),
-                                index.is_unique,
-                            ),
-                        )
-                    })
-                    .collect::<HashMap<_, _>>(),
-                rows: BTreeMap::new(),
-            };
-            self.tx_state.as_mut().unwrap().insert_tables.insert(table_id, table);
-            self.tx_state.as_ref().unwrap().get_insert_table(&table_id).unwrap()
-        };
+        self.ensure_insert_table(table_id)?;
+        let insert_table = self.tx_state.as_ref().unwrap().get_insert_table(&table_id).unwrap();
+
+        let is_user_table = insert_table.schema.table_type == StTableType::User;
 
-        // Check unique constraints
+        // A row can only reference the large values stored before it.
+        if let Some(key) = large_value_keys(&insert_table.row_type, &row).find(|key| !self.has_large_value(key)) {
+            return Err(DBError::LargeValueNotFound(key));
+        }
+
+        // Check unique constraints, except for those deferred to the commit.
+        let deferred = &self.committed_state.deferred_constraints;
         for index in insert_table.indexes.values() {
+            if deferred.contains(&index.name) {
+                continue;
+            }
             if index.violates_unique_constraint(&row) {
                 let value = row.get_field(index.col_id as usize, None).unwrap();
                 return Err(IndexError::UniqueConstraintViolation {
                     constraint_name: index.name.clone(),
                     table_name: insert_table.schema.table_name.clone(),
+                    col_id: index.col_id,
                     col_name: insert_table.schema.columns[index.col_id as usize].col_name.clone(),
                     value: value.clone(),
                 }
                 .into());
             }
         }
-        if let Some(table) = self.committed_state.tables.get_mut(&table_id) {
+        if let Some(table) = self.committed_state.tables.get(&table_id) {
             for index in table.indexes.values() {
+                if deferred.contains(&index.name) {
+                    continue;
+                }
                 let Some(violators) = index.get_rows_that_violate_unique_constraint(&row) else {
                     continue;
                 };
@@ -1225,6 +2202,7 @@ impl Inner {
                             return Err(IndexError::UniqueConstraintViolation {
                                 constraint_name: index.name.clone(),
                                 table_name: table.schema.table_name.clone(),
+                                col_id: index.col_id,
                                 col_name: table.schema.columns[index.col_id as usize].col_name.clone(),
                                 value: value.clone(),
                             }
@@ -1235,6 +2213,7 @@ impl Inner {
                         return Err(IndexError::UniqueConstraintViolation {
                             constraint_name: index.name.clone(),
                             table_name: table.schema.table_name.clone(),
+                            col_id: index.col_id,
                             col_name: table.schema.columns[index.col_id as usize].col_name.clone(),
                             value: value.clone(),
                         }
@@ -1244,6 +2223,18 @@ impl Inner {
             }
         }
 
+        // Re-inserting a row this transaction deleted just keeps it, so it takes no more room.
+        let was_deleted = self
+            .tx_state
+            .as_ref()
+            .unwrap()
+            .delete_tables
+            .get(&table_id)
+            .map_or(false, |deleted| deleted.contains(&row_id));
+        if is_user_table && !was_deleted {
+            self.check_row_quota(bytes.len())?;
+        }
+
         // Now that we have checked all the constraints, we can perform the actual insertion.
         {
             let tx_state = self.tx_state.as_mut().unwrap();
@@ -1264,6 +2255,7 @@ impl Inner {
             // If the row was just deleted in this transaction and we are re-inserting it now,
             // we're done. Otherwise we have to add the row to the insert table, and into our memory.
             if row_was_previously_deleted {
+                tx_state.record(|_| Undo::Reinsert(table_id, row_id));
                 return Ok(());
             }
 
@@ -1278,7 +2270,11 @@ impl Inner {
                 .into());
             }
 
+            let is_new = !insert_table.rows.contains_key(&row_id);
             insert_table.insert(row_id, row);
+            if is_new {
+                tx_state.record(|_| Undo::Insert(table_id, row_id));
+            }
 
             match data_key {
                 DataKey::Data(_) => (),
@@ -1295,7 +2291,7 @@ impl Inner {
         if !self.table_exists(table_id) {
             return Err(TableError::IdNotFound(table_id.0).into());
         }
-        match self.tx_state.as_ref().unwrap().get_row_op(table_id, row_id) {
+        match self.tx_state().get_row_op(table_id, row_id) {
             RowState::Committed(_) => unreachable!("a row cannot be committed in a tx state"),
             RowState::Insert(row) => {
                 return Ok(Some(DataRef::new(row)));
@@ -1310,7 +2306,7 @@ impl Inner {
             .tables
             .get(table_id)
             .and_then(|table| table.get_row(row_id))
-            .map(|row| DataRef::new(row.clone())))
+            .map(|row| DataRef::new(row.into_owned())))
     }
 
     fn get_row_type(&self, table_id: &TableId) -> Option<&ProductType> {
@@ -1352,19 +2348,20 @@ impl Inner {
             RowState::Committed(_) => {
                 // If the row is present because of a previously committed transaction,
                 // we need to add it to the appropriate delete_table.
-                self.tx_state
-                    .as_mut()
-                    .unwrap()
-                    .get_or_create_delete_table(*table_id)
-                    .insert(*row_id);
+                let tx_state = self.tx_state.as_mut().unwrap();
+                tx_state.get_or_create_delete_table(*table_id).insert(*row_id);
+                tx_state.record(|_| Undo::DeleteCommitted(*table_id, *row_id));
                 // True because we did delete the row.
                 true
             }
             RowState::Insert(_) => {
                 // If the row is present because of a an insertion in this transaction,
                 // we need to remove it from the appropriate insert_table.
-                let insert_table = self.tx_state.as_mut().unwrap().get_insert_table_mut(table_id).unwrap();
-                insert_table.delete(row_id);
+                let tx_state = self.tx_state.as_mut().unwrap();
+                let insert_table = tx_state.get_insert_table_mut(table_id).unwrap();
+                if let Some(row) = insert_table.delete(row_id) {
+                    tx_state.record(|_| Undo::DeleteInserted(*table_id, *row_id, row));
+                }
                 // True because we did delete a row.
                 true
             }
@@ -1390,6 +2387,98 @@ impl Inner {
         Ok(Some(count))
     }
 
+    /// Stores `data` in `st_large_values`, unless it's already there or short enough to be held inline,
+    /// returning the key a [`LargeBytesRef`] to it holds.
+    ///
+    /// A value that no row references is deleted when the transaction commits.
+    fn put_large_value(&mut self, data: &[u8]) -> super::Result<DataKey> {
+        if let key @ DataKey::Data(_) = DataKey::from_data(data) {
+            return Ok(key);
+        }
+        let row = ProductValue::from(&StLargeValueRow { data });
+        let key = row.to_data_key();
+        if !self.has_large_value(&key) {
+            self.insert_row_internal(ST_LARGE_VALUES_ID, row)?;
+        }
+        Ok(key)
+    }
+
+    /// Returns the value a [`LargeBytesRef`] holding `key` references, if it's stored.
+    fn get_large_value(&self, key: DataKey) -> super::Result<Option<Vec<u8>>> {
+        if let DataKey::Data(data) = key {
+            return Ok(Some(data.to_vec()));
+        }
+        let Some(row) = self.get(&ST_LARGE_VALUES_ID, &RowId(key))? else {
+            return Ok(None);
+        };
+        Ok(Some(StLargeValueRow::try_from(row.view())?.data.to_vec()))
+    }
+
+    fn has_large_value(&self, key: &DataKey) -> bool {
+        let row_id = RowId(*key);
+        match self.tx_state().get_row(&ST_LARGE_VALUES_ID, &row_id) {
+            Some(_) => true,
+            None if self.tx_state().is_deleted(&ST_LARGE_VALUES_ID, &row_id) => false,
+            None => self
+                .committed_state
+                .tables
+                .get(&ST_LARGE_VALUES_ID)
+                .map_or(false, |table| table.contains_row(&row_id)),
+        }
+    }
+
+    /// Returns how the transaction changes the number of rows that reference each row of `st_large_values`.
+    fn large_value_ref_changes(&self) -> HashMap<DataKey, i64> {
+        let tx_state = self.tx_state.as_ref().unwrap();
+        let mut changes = HashMap::new();
+        for table in tx_state.insert_tables.values() {
+            if !has_large_values(&table.row_type) {
+                continue;
+            }
+            for row in table.rows.values() {
+                for key in large_value_keys(&table.row_type, row) {
+                    *changes.entry(key).or_insert(0) += 1;
+                }
+            }
+        }
+        for (table_id, row_ids) in &tx_state.delete_tables {
+            let Some(table) = self.committed_state.tables.get(table_id) else {
+                continue;
+            };
+            if !has_large_values(&table.row_type) {
+                continue;
+            }
+            for row in row_ids.iter().filter_map(|row_id| table.get_row(row_id)) {
+                for key in large_value_keys(&table.row_type, &row) {
+                    *changes.entry(key).or_insert(0) -= 1;
+                }
+            }
+        }
+        changes
+    }
+
+    /// Deletes the rows of `st_large_values` that no row will reference once the transaction commits,
+    /// given the `changes` it makes to the references to them.
+    fn delete_unreferenced_large_values(&mut self, changes: &HashMap<DataKey, i64>) {
+        let stored = self
+            .tx_state
+            .as_ref()
+            .unwrap()
+            .get_insert_table(&ST_LARGE_VALUES_ID)
+            .into_iter()
+            .flat_map(|table| table.rows.keys().map(|row_id| row_id.0));
+        let unreferenced = stored
+            .chain(changes.keys().copied())
+            .filter(|key| {
+                let change = changes.get(key).copied().unwrap_or(0);
+                self.committed_state.large_value_refs_after(key, change) <= 0
+            })
+            .collect::<Vec<_>>();
+        for key in unreferenced {
+            self.delete_row_internal(&ST_LARGE_VALUES_ID, &RowId(key));
+        }
+    }
+
     fn iter(&self, table_id: &TableId) -> super::Result<Iter> {
         if self.table_exists(table_id) {
             return Ok(Iter::new(*table_id, self));
@@ -1436,14 +2525,14 @@ impl Inner {
             .and_then(|tx_state| tx_state.index_seek(table_id, col_id, value))
         {
             // The current transaction has modified this table, and the table is indexed.
-            let tx_state = self.tx_state.as_ref().unwrap();
+            let tx_state = self.tx_state();
             Ok(IterByColEq::Index(IndexIterByColEq {
                 value,
                 col_id: *col_id,
                 iter: IndexSeekIterInner {
                     table_id: *table_id,
                     tx_state,
-                    inserted_rows,
+                    inserted_rows: Some(inserted_rows),
                     committed_rows: self.committed_state.index_seek(table_id, col_id, value),
                     committed_state: &self.committed_state,
                 },
@@ -1454,7 +2543,7 @@ impl Inner {
             match self.committed_state.index_seek(table_id, col_id, value) {
                 Some(committed_rows) => Ok(IterByColEq::CommittedIndex(CommittedIndexIterByColEq {
                     table_id: *table_id,
-                    tx_state: self.tx_state.as_ref().unwrap(),
+                    tx_state: self.tx_state(),
                     committed_state: &self.committed_state,
                     committed_rows,
                 })),
@@ -1467,10 +2556,136 @@ impl Inner {
         }
     }
 
-    fn commit(&mut self) -> super::Result<Option<TxData>> {
+    /// Returns an iterator,
+    /// yielding every row in the table identified by `table_id`,
+    /// where the string identified by `col_id` [matches](fulltext::matches) `query`.
+    fn iter_by_col_match<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        query: &'a str,
+    ) -> super::Result<IterByColMatch> {
+        // A full-text index yields the rows that have the first token of the query,
+        // which then still have to be checked against the rest of the query.
+        let tx_state = self.tx_state();
+        match self.committed_state.index_search(table_id, col_id, query) {
+            Some(committed_rows) => Ok(IterByColMatch::Index(IndexIterByColMatch {
+                query,
+                col_id: *col_id,
+                iter: IndexSeekIterInner {
+                    table_id: *table_id,
+                    tx_state,
+                    inserted_rows: tx_state.index_search(table_id, col_id, query),
+                    committed_rows: Some(committed_rows),
+                    committed_state: &self.committed_state,
+                },
+            })),
+            None => match tx_state.index_search(table_id, col_id, query) {
+                // The index was only added in the current transaction.
+                Some(inserted_rows) => Ok(IterByColMatch::Index(IndexIterByColMatch {
+                    query,
+                    col_id: *col_id,
+                    iter: IndexSeekIterInner {
+                        table_id: *table_id,
+                        tx_state,
+                        inserted_rows: Some(inserted_rows),
+                        committed_rows: None,
+                        committed_state: &self.committed_state,
+                    },
+                })),
+                None => Ok(IterByColMatch::Scan(ScanIterByColMatch {
+                    query,
+                    col_id: *col_id,
+                    scan_iter: self.iter(table_id)?,
+                })),
+            },
+        }
+    }
+
+    /// Returns an iterator,
+    /// yielding every row in the table identified by `table_id`,
+    /// where the point identified by `col_id` is [in the box](spatial::in_box) from `min` to `max`.
+    fn iter_by_col_box<'a>(
+        &'a self,
+        table_id: &TableId,
+        col_id: &ColId,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> super::Result<IterByColBox> {
+        // A spatial index yields the rows ordered between the corners,
+        // which then still have to be checked against the box.
+        let tx_state = self.tx_state();
+        let committed_rows = self.committed_state.index_search_box(table_id, col_id, min, max);
+        let inserted_rows = tx_state.index_search_box(table_id, col_id, min, max);
+        if committed_rows.is_none() && inserted_rows.is_none() {
+            return Ok(IterByColBox::Scan(ScanIterByColBox {
+                min,
+                max,
+                col_id: *col_id,
+                scan_iter: self.iter(table_id)?,
+            }));
+        }
+        Ok(IterByColBox::Index(IndexIterByColBox {
+            min,
+            max,
+            col_id: *col_id,
+            iter: IndexSeekIterInner {
+                table_id: *table_id,
+                tx_state,
+                inserted_rows,
+                committed_rows,
+                committed_state: &self.committed_state,
+            },
+        }))
+    }
+
+    /// Commits the current transaction, which began at `begin_offset` and read from `read_tables`,
+    /// and from the offset of the commit log if `read_offset`,
+    /// or rolls it back and returns `None` if it conflicts with a transaction committed since.
+    ///
+    /// Only a [suspended](MutTxId::suspend) transaction can conflict,
+    /// since no other transaction can commit while the lock is held.
+    fn commit(
+        &mut self,
+        begin_offset: u64,
+        read_tables: &BTreeSet<TableId>,
+        read_offset: bool,
+    ) -> super::Result<Option<TxData>> {
+        let large_value_ref_changes = self.large_value_ref_changes();
+        self.delete_unreferenced_large_values(&large_value_ref_changes);
         let tx_state = self.tx_state.take().unwrap();
         let memory = std::mem::take(&mut self.memory);
+        if read_offset && self.committed_state.commit_offset != begin_offset {
+            return Ok(None);
+        }
+        if let Some(table_id) = self
+            .committed_state
+            .conflicts_with(begin_offset, read_tables, &tx_state)
+        {
+            self.committed_state
+                .update_contention(table_id, |row| row.conflicts += 1);
+            return Ok(None);
+        }
+        // Returning the error drops `tx_state`, rolling the transaction back.
+        self.committed_state.check_deferred_constraints(&tx_state)?;
+        self.committed_state.record_commit(&tx_state);
+        let indexes_changed =
+            tx_state.insert_tables.contains_key(&ST_INDEXES_ID) || tx_state.delete_tables.contains_key(&ST_INDEXES_ID);
         let tx_data = self.committed_state.merge(tx_state, memory);
+        self.committed_state
+            .apply_large_value_ref_changes(large_value_ref_changes);
+        // Only the tables whose rows are written to the message log count as changed,
+        // so that replaying it arrives at the same versions.
+        self.committed_state
+            .bump_table_versions(tx_data.records.iter().map(|record| record.table_id));
+        if indexes_changed {
+            self.committed_state.rebuild_constraints()?;
+        }
+        // The transaction is committed either way, so there's no error to return.
+        if let Err(e) = self.committed_state.enforce_memory_budget() {
+            log::error!("Failed to evict rows to disk: {e}");
+        }
+        self.committed_state.update_compression_stats();
         Ok(Some(tx_data))
     }
 
@@ -1480,9 +2695,49 @@ impl Inner {
     }
 }
 
+/// Returns whether the rows of type `row_type` have [`LargeBytesRef`] columns.
+fn has_large_values(row_type: &ProductType) -> bool {
+    row_type.elements.iter().any(|col| is_large_bytes(&col.algebraic_type))
+}
+
+fn is_large_bytes(ty: &AlgebraicType) -> bool {
+    matches!(ty, AlgebraicType::Product(ty) if ty.is_large_bytes())
+}
+
+/// Returns the keys of the rows of `st_large_values` referenced by the [`LargeBytesRef`] columns of `row`,
+/// leaving out the values short enough to be held inline by their keys.
+fn large_value_keys<'a>(row_type: &'a ProductType, row: &'a ProductValue) -> impl Iterator<Item = DataKey> + 'a {
+    row_type
+        .elements
+        .iter()
+        .zip(&row.elements)
+        .filter(|(col, _)| is_large_bytes(&col.algebraic_type))
+        .filter_map(|(_, value)| LargeBytesRef::key_of_value(value))
+        .filter(|key| matches!(key, DataKey::Hash(_)))
+}
+
+/// Checks that `index` can be created on its column of a table whose rows are of type `row_type`.
+fn check_index_column(index: &IndexDef, row_type: &ProductType) -> super::Result<()> {
+    let col_type = row_type
+        .elements
+        .get(index.col_id as usize)
+        .map(|col| &col.algebraic_type);
+    match index.index_type {
+        // A full-text index stores the tokens of a string, which are not unique.
+        IndexType::FullText if index.is_unique || col_type != Some(&AlgebraicType::String) => {
+            Err(IndexError::FullTextColumn(index.clone()).into())
+        }
+        // A spatial index stores points, and many entities can share a position.
+        IndexType::Spatial if index.is_unique || !col_type.map_or(false, spatial::is_point) => {
+            Err(IndexError::SpatialColumn(index.clone()).into())
+        }
+        _ => Ok(()),
+    }
+}
+
 #[derive(Clone)]
 pub struct Locking {
-    inner: Arc<Mutex<Inner>>,
+    inner: Arc<RwLock<Inner>>,
 }
 
 impl Locking {
@@ -1508,6 +2763,88 @@ impl Locking {
         datastore.bootstrap_system_table(st_columns_schema())?;
         datastore.bootstrap_system_table(st_indexes_schema())?;
         datastore.bootstrap_system_table(st_sequences_schema())?;
+        datastore.bootstrap_system_table(st_contention_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_CONTENTION_ID,
+            &ST_CONTENTION_ROW_TYPE,
+            &st_contention_schema(),
+        );
+        datastore.bootstrap_system_table(st_constraints_schema())?;
+        datastore.committed_state.rebuild_constraints()?;
+        datastore.bootstrap_system_table(st_webhook_dead_letter_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_WEBHOOK_DEAD_LETTER_ID,
+            &ST_WEBHOOK_DEAD_LETTER_ROW_TYPE,
+            &st_webhook_dead_letter_schema(),
+        );
+        datastore.bootstrap_system_table(st_disk_usage_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_DISK_USAGE_ID,
+            &ST_DISK_USAGE_ROW_TYPE,
+            &st_disk_usage_schema(),
+        );
+        // Unlike the tables above, the role and ACL tables are changed through transactions, so their rows are logged.
+        // They're created here for the databases that predate them, whose message log never mentions them.
+        datastore.bootstrap_system_table(st_roles_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_ROLES_ID, &ST_ROLES_ROW_TYPE, &st_roles_schema());
+        datastore.bootstrap_system_table(st_role_members_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_ROLE_MEMBERS_ID,
+            &ST_ROLE_MEMBERS_ROW_TYPE,
+            &st_role_members_schema(),
+        );
+        datastore.bootstrap_system_table(st_table_acl_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_TABLE_ACL_ID, &ST_TABLE_ACL_ROW_TYPE, &st_table_acl_schema());
+        datastore.bootstrap_system_table(st_reducer_cooldown_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_REDUCER_COOLDOWN_ID,
+            &ST_REDUCER_COOLDOWN_ROW_TYPE,
+            &st_reducer_cooldown_schema(),
+        );
+        datastore.bootstrap_system_table(st_column_stats_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_COLUMN_STATS_ID,
+            &ST_COLUMN_STATS_ROW_TYPE,
+            &st_column_stats_schema(),
+        );
+        datastore.bootstrap_system_table(st_table_version_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_TABLE_VERSION_ID,
+            &ST_TABLE_VERSION_ROW_TYPE,
+            &st_table_version_schema(),
+        );
+        datastore.bootstrap_system_table(st_blobs_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_BLOBS_ID, &ST_BLOBS_ROW_TYPE, &st_blobs_schema());
+        datastore.bootstrap_system_table(st_large_values_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_LARGE_VALUES_ID,
+            &ST_LARGE_VALUES_ROW_TYPE,
+            &st_large_values_schema(),
+        );
+        datastore.bootstrap_system_table(st_column_compression_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_COLUMN_COMPRESSION_ID,
+            &ST_COLUMN_COMPRESSION_ROW_TYPE,
+            &st_column_compression_schema(),
+        );
+        datastore.bootstrap_system_table(st_storage_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_STORAGE_ID, &ST_STORAGE_ROW_TYPE, &st_storage_schema());
+        datastore.bootstrap_system_table(st_outbox_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_OUTBOX_ID, &ST_OUTBOX_ROW_TYPE, &st_outbox_schema());
+        datastore.bootstrap_system_table(st_inbox_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_INBOX_ID, &ST_INBOX_ROW_TYPE, &st_inbox_schema());
 
         // The database tables are now initialized with the correct data.
         // Now we have to build our in memory structures.
@@ -1517,47 +2854,304 @@ impl Locking {
         log::trace!("DATABASE:BOOTSTRAPPING SYSTEM TABLES DONE");
 
         Ok(Locking {
-            inner: Arc::new(Mutex::new(datastore)),
+            inner: Arc::new(RwLock::new(datastore)),
         })
     }
 
-    /// The purpose of this is to rebuild the state of the datastore
-    /// after having inserted all of rows from the message log.
-    /// This is necessary because, for example, inserting a row into `st_table`
-    /// is not equivalent to calling `create_table`.
-    /// There may eventually be better way to do this, but this will have to do for now.
-    pub fn rebuild_state_after_replay(&self) -> Result<(), DBError> {
-        let mut inner = self.inner.lock();
+    /// Starts building `index` on a committed table,
+    /// without holding the lock on the datastore for the length of the build.
+    ///
+    /// The returned [`IndexBuild`] is run by calling [`IndexBuild::scan_chunk`] until it returns `false`,
+    /// then the index is created by [`Self::create_built_index_mut_tx`].
+    /// Transactions can write to the table throughout.
+    pub fn begin_index_build(&self, index: IndexDef) -> super::Result<IndexBuild> {
+        let log = Arc::new(());
+        {
+            let mut inner = self.inner.write();
+            let table = inner
+                .committed_state
+                .tables
+                .get(&TableId(index.table_id))
+                .ok_or(TableError::IdNotFound(index.table_id))?;
+            // Some system tables are written in place rather than by committing, which the log would miss.
+            if table.schema.table_type == StTableType::System {
+                return Err(TableError::System(table.schema.table_name.clone()).into());
+            }
+            if table.row_type.elements.len() <= index.col_id as usize {
+                return Err(IndexError::ColumnNotFound(index).into());
+            }
+            check_index_column(&index, &table.row_type)?;
+            inner
+                .committed_state
+                .index_builds
+                .push(IndexBuildLog::new(TableId(index.table_id), &log));
+        }
+        let btree = BTreeIndex::new(
+            IndexId(0), // Set when the index is created.
+            index.table_id,
+            index.col_id,
+            index.name.clone(),
+            index.is_unique,
+            index.index_type,
+        );
+        Ok(IndexBuild::new(index, btree, log, self.inner.clone()))
+    }
 
-        // `build_missing_tables` must be called before indexes.
-        // Honestly this should maybe just be one big procedure.
-        // See John Carmack's philosophy on this.
-        inner.build_missing_tables()?;
-        inner.build_indexes()?;
-        inner.build_sequence_state()?;
+    /// Builds `index` on a committed table, scanning its rows a chunk at a time,
+    /// so that transactions can run between the chunks.
+    ///
+    /// This must not be called while holding a transaction.
+    pub fn build_index(&self, index: IndexDef) -> super::Result<IndexBuild> {
+        let mut build = self.begin_index_build(index)?;
+        while build.scan_chunk()? {}
+        Ok(build)
+    }
 
-        Ok(())
+    /// Creates the index built by `build` in `tx`,
+    /// applying the writes committed to its table since the build began.
+    ///
+    /// Unlike [`MutTxDatastore::create_index_mut_tx`], this doesn't scan the table,
+    /// so the lock is only held for as long as it takes to catch up with those writes.
+    pub fn create_built_index_mut_tx(&self, tx: &mut MutTxId, build: IndexBuild) -> super::Result<IndexId> {
+        tx.lock.create_built_index(build)
     }
 
-    pub fn replay_transaction(
-        &self,
-        transaction: &Transaction,
-        odb: Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>,
-    ) -> Result<(), DBError> {
-        let mut inner = self.inner.lock();
-        for write in &transaction.writes {
-            let table_id = TableId(write.set_id);
-            let schema = inner.schema_for_table(table_id)?;
-            let row_type = inner.row_type_for_table(table_id)?;
-            let table = inner.committed_state.tables.entry(table_id).or_insert(Table {
-                row_type: row_type.clone(),
-                schema,
-                indexes: HashMap::new(),
-                rows: BTreeMap::new(),
-            });
+    /// Stores `data` out of the rows that will reference it, returning the key they hold.
+    ///
+    /// Values are stored once, however many rows reference them, and deleted with the last of those rows.
+    pub fn put_large_value_mut_tx(&self, tx: &mut MutTxId, data: &[u8]) -> super::Result<DataKey> {
+        tx.lock.put_large_value(data)
+    }
+
+    /// Returns the value stored under `key` by [`Self::put_large_value_mut_tx`], if any.
+    pub fn get_large_value_mut_tx(&self, tx: &MutTxId, key: DataKey) -> super::Result<Option<Vec<u8>>> {
+        tx.lock.get_large_value(key)
+    }
+
+    /// Replaces the access hints of the tables, given by table name,
+    /// which choose how transactions on those tables conflict.
+    pub fn set_access_hints(&self, access_hints: HashMap<String, AccessHint>) {
+        self.inner.write().committed_state.access_hints = access_hints;
+    }
+
+    /// Replaces the columns compressed in memory, with their codecs, given by table name,
+    /// compressing the rows already in memory accordingly.
+    ///
+    /// The compression ratio of each column is kept in `st_column_compression`.
+    pub fn set_column_compression(&self, column_compression: HashMap<String, Vec<(ColId, Compression)>>) {
+        self.inner
+            .lock()
+            .committed_state
+            .set_column_compression(column_compression);
+    }
+
+    /// Replaces the unique constraints, given by name, that are checked when a transaction commits
+    /// rather than as each row is inserted, so that a transaction can, e.g., swap the values of two rows.
+    pub fn set_deferred_constraints(&self, constraint_names: HashSet<String>) {
+        self.inner.write().committed_state.deferred_constraints = constraint_names;
+    }
+
+    /// Replaces the version columns bumped on every update of a row,
+    /// each with the key column identifying the row, by table name.
+    ///
+    /// A row inserted by the transaction that deleted the committed row with its key
+    /// gets the deleted row's version plus one, wrapping around, and any other row gets 0.
+    pub fn set_row_versions(&self, row_versions: HashMap<String, (ColId, ColId)>) {
+        self.inner.write().committed_state.row_versions = row_versions;
+    }
+
+    /// Limits the committed rows kept in memory to `budget`,
+    /// evicting the rest to disk.
+    ///
+    /// This should be set before replaying the message log,
+    /// so that a database larger than memory can be opened.
+    pub fn set_memory_budget(&self, budget: MemoryBudget) -> Result<(), DBError> {
+        let mut inner = self.inner.write();
+        inner.committed_state.memory_budget = Some(budget);
+        inner.committed_state.enforce_memory_budget()
+    }
+
+    /// Compacts the storage of the committed tables and rebuilds their indexes,
+    /// returning the space reclaimed.
+    ///
+    /// As `tx` holds the lock on the datastore, no other transaction runs in the meantime.
+    /// Only how the rows are stored changes, so `tx` can still be rolled back.
+    pub fn vacuum_mut_tx(&self, tx: &mut MutTxId) -> Result<VacuumReport, DBError> {
+        tx.lock.committed_state.vacuum()
+    }
+
+    /// Replaces the limits on the user tables and rows of the database.
+    ///
+    /// They're checked as tables are created and rows are inserted,
+    /// so a database already over a new quota keeps its tables and rows.
+    pub fn set_quota(&self, quota: Quota) {
+        self.inner.write().committed_state.quota = quota;
+    }
+
+    /// Adds `row` to `st_webhook_dead_letter`.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so the row is neither logged nor a cause of conflicts.
+    pub fn record_webhook_dead_letter(&self, row: &StWebhookDeadLetterRow) {
+        let mut inner = self.inner.write();
+        if let Some(table) = inner.committed_state.get_table(&ST_WEBHOOK_DEAD_LETTER_ID) {
+            let row = ProductValue::from(row);
+            table.insert(RowId(row.to_data_key()), row);
+        }
+    }
+
+    /// Replaces the rows of `st_disk_usage` with `rows`.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so the rows are neither logged nor a cause of conflicts.
+    pub fn record_disk_usage(&self, rows: &[StDiskUsageRow]) {
+        let mut inner = self.inner.write();
+        if let Some(table) = inner.committed_state.get_table(&ST_DISK_USAGE_ID) {
+            let old_rows = table
+                .scan_rows()
+                .map(|row| RowId(row.to_data_key()))
+                .collect::<Vec<_>>();
+            for row_id in &old_rows {
+                table.delete(row_id);
+            }
+            for row in rows {
+                let row = ProductValue::from(row);
+                table.insert(RowId(row.to_data_key()), row);
+            }
+        }
+    }
+
+    /// Replaces the rows of `st_storage` with the space used by each committed table and index,
+    /// and by the commit log, whose bytes and number of segments are `commit_log`, if there's one,
+    /// and returns them.
+    ///
+    /// Like `st_contention`, the table is changed in place rather than through a transaction,
+    /// so the rows are neither logged nor a cause of conflicts.
+    pub fn record_storage(&self, commit_log: Option<(u64, usize)>, measured_at: u64) -> Vec<StStorageRow> {
+        let mut inner = self.inner.write();
+        let mut tables = inner.committed_state.tables.iter().collect::<Vec<_>>();
+        tables.sort_by_key(|(table_id, _)| **table_id);
+        let mut rows = Vec::new();
+        for (table_id, table) in tables {
+            rows.push(StStorageRow {
+                kind: "table".into(),
+                table_id: table_id.0,
+                name: table.schema.table_name.clone(),
+                bytes: table.memory_bytes() as u64,
+                entries: table.rows.len() as u64,
+                measured_at,
+            });
+            let mut indexes = table.indexes.values().collect::<Vec<_>>();
+            indexes.sort_by_key(|index| index.index_id);
+            rows.extend(indexes.into_iter().map(|index| StStorageRow {
+                kind: "index".into(),
+                table_id: table_id.0,
+                name: index.name.clone(),
+                bytes: index.approx_bytes() as u64,
+                entries: index.len() as u64,
+                measured_at,
+            }));
+        }
+        if let Some((bytes, segments)) = commit_log {
+            rows.push(StStorageRow {
+                kind: "commit_log".into(),
+                table_id: 0,
+                name: "commit_log".into(),
+                bytes,
+                entries: segments as u64,
+                measured_at,
+            });
+        }
+
+        if let Some(table) = inner.committed_state.get_table(&ST_STORAGE_ID) {
+            let old_rows = table
+                .scan_rows()
+                .map(|row| RowId(row.to_data_key()))
+                .collect::<Vec<_>>();
+            for row_id in &old_rows {
+                table.delete(row_id);
+            }
+            for row in &rows {
+                let row = ProductValue::from(row);
+                table.insert(RowId(row.to_data_key()), row);
+            }
+        }
+        rows
+    }
+
+    /// The purpose of this is to rebuild the state of the datastore
+    /// after having inserted all of rows from the message log.
+    /// This is necessary because, for example, inserting a row into `st_table`
+    /// is not equivalent to calling `create_table`.
+    /// There may eventually be better way to do this, but this will have to do for now.
+    pub fn rebuild_state_after_replay(&self) -> Result<(), DBError> {
+        let mut inner = self.inner.write();
+
+        // `build_missing_tables` must be called before indexes.
+        // Honestly this should maybe just be one big procedure.
+        // See John Carmack's philosophy on this.
+        inner.build_missing_tables()?;
+        inner.build_indexes()?;
+        inner.build_sequence_state()?;
+        inner.committed_state.rebuild_constraints()?;
+        inner.committed_state.rebuild_large_value_refs();
+
+        Ok(())
+    }
+
+    pub fn replay_transaction(
+        &self,
+        transaction: &Transaction,
+        odb: Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>,
+    ) -> Result<(), DBError> {
+        let mut inner = self.inner.write();
+        // The columns added to a table by the transaction change the layout of its rows in it,
+        // so they're applied before any of them are decoded.
+        for write in &transaction.writes {
+            if write.set_id != ST_COLUMNS_ID.0 || !matches!(write.operation, Operation::Insert) {
+                continue;
+            }
+            let row = match write.data_key {
+                DataKey::Data(data) => ProductValue::decode(&ST_COLUMNS_ROW_TYPE, &mut &data[..]),
+                DataKey::Hash(hash) => {
+                    let data = odb.lock().unwrap().get(hash).unwrap();
+                    ProductValue::decode(&ST_COLUMNS_ROW_TYPE, &mut &data[..])
+                }
+            }
+            .unwrap_or_else(|_| panic!("Couldn't decode st_columns row from message log"));
+            let col = StColumnRow::try_from(&row)?;
+            if let Some(table) = inner.committed_state.tables.get_mut(&TableId(col.table_id)) {
+                if col.col_id as usize >= table.row_type.elements.len() {
+                    table.row_type.elements.push(ProductTypeElement {
+                        name: None,
+                        algebraic_type: col.col_type.clone(),
+                    });
+                    table.schema.columns.push(ColumnSchema {
+                        table_id: col.table_id,
+                        col_id: col.col_id,
+                        col_name: col.col_name.to_string(),
+                        col_type: col.col_type,
+                        is_autoinc: col.is_autoinc,
+                    });
+                }
+            }
+        }
+        for write in &transaction.writes {
+            let table_id = TableId(write.set_id);
+            let schema = inner.schema_for_table(table_id)?;
+            let row_type = inner.row_type_for_table(table_id)?;
+            let table = inner.committed_state.tables.entry(table_id).or_insert(Table {
+                row_type: row_type.clone(),
+                schema,
+                indexes: HashMap::new(),
+                rows: BTreeMap::new(),
+                spilled: SpilledRows::default(),
+                resident_bytes: 0,
+                codecs: Vec::new(),
+            });
             match write.operation {
                 Operation::Delete => {
-                    table.rows.remove(&RowId(write.data_key));
+                    table.delete(&RowId(write.data_key));
                 }
                 Operation::Insert => {
                     let product_value = match write.data_key {
@@ -1571,11 +3165,14 @@ impl Locking {
                             })
                         }
                     };
-                    table.rows.insert(RowId(write.data_key), product_value);
+                    table.insert(RowId(write.data_key), product_value);
                 }
             }
         }
-        Ok(())
+        inner
+            .committed_state
+            .bump_table_versions(transaction.writes.iter().map(|write| TableId(write.set_id)));
+        inner.committed_state.enforce_memory_budget()
     }
 }
 
@@ -1595,8 +3192,21 @@ impl DataRow for Locking {
 impl traits::Tx for Locking {
     type TxId = MutTxId;
 
+    /// Begins a read-only transaction, which only waits for the transaction writing to the datastore, if any,
+    /// and runs alongside the other read-only transactions.
+    ///
+    /// It sees the committed state, and panics if it's written to.
     fn begin_tx(&self) -> Self::TxId {
-        self.begin_mut_tx()
+        let inner = self.inner.read_arc();
+        let begin_offset = inner.committed_state.commit_offset;
+        MutTxId {
+            lock: TxLock::Read(inner),
+            savepoints: Vec::new(),
+            next_savepoint_id: 0,
+            begin_offset,
+            read_tables: Mutex::default(),
+            read_offset: false,
+        }
     }
 
     fn release_tx(&self, tx: Self::TxId) {
@@ -1626,7 +3236,7 @@ enum ScanStage<'a> {
         iter: std::collections::btree_map::Iter<'a, RowId, ProductValue>,
     },
     Committed {
-        iter: std::collections::btree_map::Iter<'a, RowId, ProductValue>,
+        iter: RowsIter<'a>,
     },
 }
 
@@ -1637,11 +3247,8 @@ impl Iterator for Iter<'_> {
             match &mut self.stage {
                 ScanStage::Start => {
                     if let Some(table) = self.inner.committed_state.tables.get(&self.table_id) {
-                        self.stage = ScanStage::Committed {
-                            iter: table.rows.iter(),
-                        };
-                    } else if let Some(table) = self.inner.tx_state.as_ref().unwrap().insert_tables.get(&self.table_id)
-                    {
+                        self.stage = ScanStage::Committed { iter: table.iter() };
+                    } else if let Some(table) = self.inner.tx_state().insert_tables.get(&self.table_id) {
                         self.stage = ScanStage::CurrentTx {
                             iter: table.rows.iter(),
                         };
@@ -1659,10 +3266,10 @@ impl Iterator for Iter<'_> {
                             Some(RowState::Insert(_)) => (), // Do nothing, we'll get it in the next stage
                             Some(RowState::Delete) => (),    // Skip it, it's been deleted
                             Some(RowState::Absent) => {
-                                return Some(DataRef::new(row.clone()));
+                                return Some(DataRef::new(row.into_owned()));
                             }
                             None => {
-                                return Some(DataRef::new(row.clone()));
+                                return Some(DataRef::new(row.into_owned()));
                             }
                         }
                     }
@@ -1763,14 +3370,14 @@ struct IndexSeekIterInner<'a> {
     table_id: TableId,
     tx_state: &'a TxState,
     committed_state: &'a CommittedState,
-    inserted_rows: BTreeIndexRangeIter<'a>,
+    inserted_rows: Option<BTreeIndexRangeIter<'a>>,
     committed_rows: Option<BTreeIndexRangeIter<'a>>,
 }
 
 impl Iterator for IndexSeekIterInner<'_> {
     type Item = DataRef;
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(row_id) = self.inserted_rows.next() {
+        if let Some(row_id) = self.inserted_rows.as_mut().and_then(|i| i.next()) {
             return Some(DataRef::new(
                 self.tx_state.get_row(&self.table_id, &row_id).unwrap().clone(),
             ));
@@ -1817,10 +3424,129 @@ impl Iterator for CommittedIndexIterByColEq<'_> {
     }
 }
 
+/// An iterator returned from `iter_by_col_match`. This yields up all
+/// rows in a table which have a string column that matches a full-text query.
+pub enum IterByColMatch<'a> {
+    /// When the column in question does not have a full-text index.
+    Scan(ScanIterByColMatch<'a>),
+
+    /// When the column has a full-text index.
+    Index(IndexIterByColMatch<'a>),
+}
+
+impl Iterator for IterByColMatch<'_> {
+    type Item = DataRef;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IterByColMatch::Scan(search) => search.next(),
+            IterByColMatch::Index(search) => search.next(),
+        }
+    }
+}
+
+/// Returns whether the string at `col_id` in `data_ref` matches `query`.
+fn row_matches(data_ref: &DataRef, col_id: ColId, query: &str) -> bool {
+    let row = data_ref.view();
+    row.elements[col_id.0 as usize]
+        .as_string()
+        .map_or(false, |text| fulltext::matches(text, query))
+}
+
+pub struct ScanIterByColMatch<'a> {
+    scan_iter: Iter<'a>,
+    col_id: ColId,
+    query: &'a str,
+}
+
+impl Iterator for ScanIterByColMatch<'_> {
+    type Item = DataRef;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (col_id, query) = (self.col_id, self.query);
+        self.scan_iter.find(|data_ref| row_matches(data_ref, col_id, query))
+    }
+}
+
+pub struct IndexIterByColMatch<'a> {
+    iter: IndexSeekIterInner<'a>,
+    col_id: ColId,
+    query: &'a str,
+}
+
+impl Iterator for IndexIterByColMatch<'_> {
+    type Item = DataRef;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (col_id, query) = (self.col_id, self.query);
+        self.iter.find(|data_ref| row_matches(data_ref, col_id, query))
+    }
+}
+
+/// An iterator returned from `iter_by_col_box`. This yields up all
+/// rows in a table which have a point column within a box.
+pub enum IterByColBox<'a> {
+    /// When the column in question does not have a spatial index.
+    Scan(ScanIterByColBox<'a>),
+
+    /// When the column has a spatial index.
+    Index(IndexIterByColBox<'a>),
+}
+
+impl Iterator for IterByColBox<'_> {
+    type Item = DataRef;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IterByColBox::Scan(search) => search.next(),
+            IterByColBox::Index(search) => search.next(),
+        }
+    }
+}
+
+pub struct ScanIterByColBox<'a> {
+    scan_iter: Iter<'a>,
+    col_id: ColId,
+    min: &'a AlgebraicValue,
+    max: &'a AlgebraicValue,
+}
+
+impl Iterator for ScanIterByColBox<'_> {
+    type Item = DataRef;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (col_id, min, max) = (self.col_id, self.min, self.max);
+        self.scan_iter
+            .find(|data_ref| spatial::in_box(&data_ref.view().elements[col_id.0 as usize], min, max))
+    }
+}
+
+pub struct IndexIterByColBox<'a> {
+    iter: IndexSeekIterInner<'a>,
+    col_id: ColId,
+    min: &'a AlgebraicValue,
+    max: &'a AlgebraicValue,
+}
+
+impl Iterator for IndexIterByColBox<'_> {
+    type Item = DataRef;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (col_id, min, max) = (self.col_id, self.min, self.max);
+        self.iter
+            .find(|data_ref| spatial::in_box(&data_ref.view().elements[col_id.0 as usize], min, max))
+    }
+}
+
 /// Retrieve a commited row. Panics if `table_id` and `row_id` do not identify an actually
 /// present row.
 fn get_committed_row(state: &CommittedState, table_id: &TableId, row_id: &RowId) -> DataRef {
-    DataRef::new(state.tables.get(table_id).unwrap().get_row(row_id).unwrap().clone())
+    let row = state.tables.get(table_id).unwrap().get_row(row_id).unwrap();
+    DataRef::new(row.into_owned())
 }
 
 pub enum IterByColRange<'a, R: RangeBounds<AlgebraicValue>> {
@@ -1864,6 +3590,8 @@ impl TxDatastore for Locking {
     type Iter<'a> = Iter<'a> where Self: 'a;
     type IterByColRange<'a, R: std::ops::RangeBounds<spacetimedb_sats::AlgebraicValue>> = IterByColRange<'a, R> where Self: 'a;
     type IterByColEq<'a> = IterByColEq<'a> where Self: 'a;
+    type IterByColMatch<'a> = IterByColMatch<'a> where Self: 'a;
+    type IterByColBox<'a> = IterByColBox<'a> where Self: 'a;
 
     fn iter_tx<'a>(&'a self, tx: &'a Self::TxId, table_id: TableId) -> super::Result<Self::Iter<'a>> {
         self.iter_mut_tx(tx, table_id)
@@ -1889,6 +3617,27 @@ impl TxDatastore for Locking {
         self.iter_by_col_eq_mut_tx(tx, table_id, col_id, value)
     }
 
+    fn iter_by_col_match_tx<'a>(
+        &'a self,
+        tx: &'a Self::TxId,
+        table_id: TableId,
+        col_id: ColId,
+        query: &'a str,
+    ) -> super::Result<Self::IterByColMatch<'a>> {
+        self.iter_by_col_match_mut_tx(tx, table_id, col_id, query)
+    }
+
+    fn iter_by_col_box_tx<'a>(
+        &'a self,
+        tx: &'a Self::TxId,
+        table_id: TableId,
+        col_id: ColId,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> super::Result<Self::IterByColBox<'a>> {
+        self.iter_by_col_box_mut_tx(tx, table_id, col_id, min, max)
+    }
+
     fn get_tx<'a>(
         &'a self,
         tx: &'a Self::TxId,
@@ -1903,20 +3652,52 @@ impl traits::MutTx for Locking {
     type MutTxId = MutTxId;
 
     fn begin_mut_tx(&self) -> Self::MutTxId {
-        let mut inner = self.inner.lock_arc();
+        let mut inner = self.inner.write_arc();
         if inner.tx_state.is_some() {
             panic!("The previous transaction was not properly rolled back or committed.");
         }
         inner.tx_state = Some(TxState::new());
-        MutTxId { lock: inner }
+        let begin_offset = inner.committed_state.commit_offset;
+        MutTxId {
+            lock: TxLock::Write(inner),
+            savepoints: Vec::new(),
+            next_savepoint_id: 0,
+            begin_offset,
+            read_tables: Mutex::default(),
+            read_offset: false,
+        }
     }
 
     fn rollback_mut_tx(&self, mut tx: Self::MutTxId) {
-        tx.lock.rollback();
+        if !tx.is_read_only() {
+            tx.lock.rollback();
+        }
     }
 
     fn commit_mut_tx(&self, mut tx: Self::MutTxId) -> super::Result<Option<TxData>> {
-        tx.lock.commit()
+        tx.lock
+            .commit(tx.begin_offset, tx.read_tables.get_mut(), tx.read_offset)
+    }
+
+    /// Takes a savepoint by marking the position in the undo log of `tx`,
+    /// which records the inserts and deletes made from then on, so that they can be reverted.
+    ///
+    /// Like a rollback, rolling back to a savepoint doesn't undo
+    /// the advances of sequences or the changes to the committed state made by dropping tables and indexes.
+    fn savepoint_mut_tx(&self, tx: &mut Self::MutTxId) -> SavepointId {
+        let id = SavepointId(tx.next_savepoint_id);
+        tx.next_savepoint_id += 1;
+        let undo_log = tx.lock.tx_state.as_mut().unwrap().undo_log.get_or_insert_with(Vec::new);
+        tx.savepoints.push((id, undo_log.len()));
+        id
+    }
+
+    fn rollback_to_savepoint_mut_tx(&self, tx: &mut Self::MutTxId, savepoint: SavepointId) -> super::Result<()> {
+        tx.take_savepoint(savepoint, true)
+    }
+
+    fn release_savepoint_mut_tx(&self, tx: &mut Self::MutTxId, savepoint: SavepointId) -> super::Result<()> {
+        tx.take_savepoint(savepoint, false)
     }
 }
 
@@ -1960,6 +3741,15 @@ impl MutTxDatastore for Locking {
         tx.lock.rename_table(table_id, new_name)
     }
 
+    fn add_columns_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+    ) -> super::Result<()> {
+        tx.lock.add_columns(table_id, columns)
+    }
+
     fn table_id_exists(&self, tx: &Self::MutTxId, table_id: &TableId) -> bool {
         tx.lock.table_exists(table_id)
     }
@@ -1972,6 +3762,16 @@ impl MutTxDatastore for Locking {
         tx.lock.table_name_from_id(table_id)
     }
 
+    /// Reading the version of a table counts as reading the table,
+    /// so a transaction committed since conflicts if it changed the table.
+    fn table_version_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> super::Result<u64> {
+        if !tx.lock.table_exists(&table_id) {
+            return Err(TableError::IdNotFound(table_id.0).into());
+        }
+        tx.record_read(table_id);
+        Ok(tx.lock.committed_state.table_version(&table_id))
+    }
+
     fn create_index_mut_tx(&self, tx: &mut Self::MutTxId, index: IndexDef) -> super::Result<IndexId> {
         tx.lock.create_index(index)
     }
@@ -2005,6 +3805,7 @@ impl MutTxDatastore for Locking {
     }
 
     fn iter_mut_tx<'a>(&'a self, tx: &'a Self::MutTxId, table_id: TableId) -> super::Result<Self::Iter<'a>> {
+        tx.record_read(table_id);
         tx.lock.iter(&table_id)
     }
 
@@ -2015,6 +3816,7 @@ impl MutTxDatastore for Locking {
         col_id: ColId,
         range: R,
     ) -> super::Result<Self::IterByColRange<'a, R>> {
+        tx.record_read(table_id);
         tx.lock.iter_by_col_range(&table_id, &col_id, range)
     }
 
@@ -2025,15 +3827,40 @@ impl MutTxDatastore for Locking {
         col_id: ColId,
         value: &'a spacetimedb_sats::AlgebraicValue,
     ) -> super::Result<Self::IterByColEq<'a>> {
+        tx.record_read(table_id);
         tx.lock.iter_by_col_eq(&table_id, &col_id, value)
     }
 
+    fn iter_by_col_match_mut_tx<'a>(
+        &'a self,
+        tx: &'a Self::MutTxId,
+        table_id: TableId,
+        col_id: ColId,
+        query: &'a str,
+    ) -> super::Result<Self::IterByColMatch<'a>> {
+        tx.record_read(table_id);
+        tx.lock.iter_by_col_match(&table_id, &col_id, query)
+    }
+
+    fn iter_by_col_box_mut_tx<'a>(
+        &'a self,
+        tx: &'a Self::MutTxId,
+        table_id: TableId,
+        col_id: ColId,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> super::Result<Self::IterByColBox<'a>> {
+        tx.record_read(table_id);
+        tx.lock.iter_by_col_box(&table_id, &col_id, min, max)
+    }
+
     fn get_mut_tx<'a>(
         &'a self,
         tx: &'a Self::MutTxId,
         table_id: TableId,
         row_id: Self::RowId,
     ) -> super::Result<Option<Self::DataRef>> {
+        tx.record_read(table_id);
         tx.lock.get(&table_id, &row_id)
     }
 
@@ -2043,6 +3870,8 @@ impl MutTxDatastore for Locking {
         table_id: TableId,
         row_id: Self::RowId,
     ) -> super::Result<bool> {
+        // Whether the row was deleted depends on the rows in the table.
+        tx.record_read(table_id);
         tx.lock.delete(&table_id, &row_id)
     }
 
@@ -2052,6 +3881,7 @@ impl MutTxDatastore for Locking {
         table_id: TableId,
         relation: R,
     ) -> super::Result<Option<u32>> {
+        tx.record_read(table_id);
         tx.lock.delete_by_rel(&table_id, relation)
     }
 
@@ -2061,30 +3891,42 @@ impl MutTxDatastore for Locking {
         table_id: TableId,
         row: spacetimedb_sats::ProductValue,
     ) -> super::Result<ProductValue> {
+        // An insert checks the unique constraints against the rows in the table,
+        // unless it's an append-only table, which has none.
+        if !tx.lock.committed_state.is_append_only(&table_id) {
+            tx.record_read(table_id);
+        }
         tx.lock.insert(table_id, row)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ColId, Locking, StTableRow};
+    use super::{online_index::INDEX_BUILD_CHUNK, ColId, Locking, MemoryBudget, MutTxId, Quota, StTableRow};
     use crate::{
         db::datastore::{
             locking_tx_datastore::{
-                StColumnRow, StIndexRow, StSequenceRow, ST_COLUMNS_ID, ST_INDEXES_ID, ST_SEQUENCES_ID, ST_TABLES_ID,
+                StColumnCompressionRow, StColumnRow, StConstraintRow, StContentionRow, StIndexRow, StSequenceRow,
+                StStorageRow, StTableVersionRow, ST_COLUMNS_ID, ST_COLUMN_COMPRESSION_ID, ST_CONSTRAINTS_ID,
+                ST_CONTENTION_ID, ST_INDEXES_ID, ST_LARGE_VALUES_ID, ST_SEQUENCES_ID, ST_STORAGE_ID, ST_TABLES_ID,
+                ST_TABLE_VERSION_ID,
             },
             traits::{
-                ColumnDef, ColumnSchema, DataRow, IndexDef, IndexSchema, MutTx, MutTxDatastore, TableDef, TableSchema,
+                ColumnDef, ColumnSchema, DataRow, IndexDef, IndexSchema, MutTx, MutTxDatastore, TableDef, TableId,
+                TableSchema, Tx,
             },
         },
-        error::{DBError, IndexError},
+        error::{DBError, IndexError, QuotaLimit},
     };
     use itertools::Itertools;
     use spacetimedb_lib::{
         auth::{StAccess, StTableType},
         error::ResultTest,
+        AccessHint, Compression, DataKey, IndexType,
     };
-    use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductValue};
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductTypeElement, ProductValue};
+    use std::collections::HashMap;
+    use tempdir::TempDir;
 
     fn get_datastore() -> super::super::Result<Locking> {
         Locking::bootstrap()
@@ -2116,12 +3958,14 @@ mod tests {
                     col_id: 0,
                     name: "id_idx".into(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0, // Ignored
                     col_id: 1,
                     name: "name_idx".into(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
             ],
             table_type: StTableType::User,
@@ -2146,6 +3990,22 @@ mod tests {
                 StTableRow { table_id: 1, table_name: "st_columns".to_string(), table_type: StTableType::System, table_access: StAccess::Public },
                 StTableRow { table_id: 2, table_name: "st_sequence".to_string(), table_type: StTableType::System, table_access: StAccess::Public},
                 StTableRow { table_id: 3, table_name: "st_indexes".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
+                StTableRow { table_id: u32::MAX - 15, table_name: "st_inbox".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 14, table_name: "st_outbox".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 13, table_name: "st_storage".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
+                StTableRow { table_id: u32::MAX - 12, table_name: "st_column_compression".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
+                StTableRow { table_id: u32::MAX - 11, table_name: "st_large_values".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 10, table_name: "st_blobs".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 9, table_name: "st_table_version".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
+                StTableRow { table_id: u32::MAX - 8, table_name: "st_column_stats".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 7, table_name: "st_reducer_cooldown".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 6, table_name: "st_table_acl".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 5, table_name: "st_role_members".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 4, table_name: "st_roles".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 3, table_name: "st_disk_usage".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 2, table_name: "st_webhook_dead_letter".to_string() , table_type: StTableType::System, table_access: StAccess::Private},
+                StTableRow { table_id: u32::MAX - 1, table_name: "st_constraints".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
+                StTableRow { table_id: u32::MAX, table_name: "st_contention".to_string() , table_type: StTableType::System, table_access: StAccess::Public},
             ]
         );
         let column_rows = datastore
@@ -2175,7 +4035,7 @@ mod tests {
                 StColumnRow { table_id: 2, col_id: 4, col_name: "increment".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
                 StColumnRow { table_id: 2, col_id: 5, col_name: "start".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
                 StColumnRow { table_id: 2, col_id: 6, col_name: "min_value".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
-                StColumnRow { table_id: 2, col_id: 7, col_name: "max_malue".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
+                StColumnRow { table_id: 2, col_id: 7, col_name: "max_value".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
                 StColumnRow { table_id: 2, col_id: 8, col_name: "allocated".to_string(), col_type: AlgebraicType::I128, is_autoinc: false },
 
                 StColumnRow { table_id: 3, col_id: 0, col_name: "index_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: true },
@@ -2183,6 +4043,81 @@ mod tests {
                 StColumnRow { table_id: 3, col_id: 2, col_name: "col_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
                 StColumnRow { table_id: 3, col_id: 3, col_name: "index_name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
                 StColumnRow { table_id: 3, col_id: 4, col_name: "is_unique".to_string(), col_type: AlgebraicType::Bool, is_autoinc: false },
+                StColumnRow { table_id: 3, col_id: 5, col_name: "index_type".to_string(), col_type: AlgebraicType::U8, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 15, col_id: 0, col_name: "sender".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 15, col_id: 1, col_name: "last_msg_id".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 14, col_id: 0, col_name: "msg_id".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 14, col_id: 1, col_name: "target".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 14, col_id: 2, col_name: "reducer_name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 14, col_id: 3, col_name: "args".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 14, col_id: 4, col_name: "sent_at".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 14, col_id: 5, col_name: "delivered".to_string(), col_type: AlgebraicType::Bool, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 13, col_id: 0, col_name: "kind".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 13, col_id: 1, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 13, col_id: 2, col_name: "name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 13, col_id: 3, col_name: "bytes".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 13, col_id: 4, col_name: "entries".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 13, col_id: 5, col_name: "measured_at".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 12, col_id: 0, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 12, col_id: 1, col_name: "col_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 12, col_id: 2, col_name: "codec".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 12, col_id: 3, col_name: "raw_bytes".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 12, col_id: 4, col_name: "stored_bytes".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 11, col_id: 0, col_name: "data".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 10, col_id: 0, col_name: "key".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 10, col_id: 1, col_name: "hash".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 10, col_id: 2, col_name: "data".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 9, col_id: 0, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 9, col_id: 1, col_name: "version".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 8, col_id: 0, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 8, col_id: 1, col_name: "col_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 8, col_id: 2, col_name: "row_count".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 8, col_id: 3, col_name: "distinct_count".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 8, col_id: 4, col_name: "min".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 8, col_id: 5, col_name: "max".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 7, col_id: 0, col_name: "identity".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 7, col_id: 1, col_name: "reducer_name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 7, col_id: 2, col_name: "last_call".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 6, col_id: 0, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 6, col_id: 1, col_name: "role".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 6, col_id: 2, col_name: "access".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 5, col_id: 0, col_name: "identity".to_string(), col_type: AlgebraicType::bytes(), is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 5, col_id: 1, col_name: "role".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 4, col_id: 0, col_name: "role".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 3, col_id: 0, col_name: "component".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 3, col_id: 1, col_name: "bytes".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 3, col_id: 2, col_name: "files".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 3, col_id: 3, col_name: "measured_at".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 2, col_id: 0, col_name: "webhook_name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 2, col_id: 1, col_name: "url".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 2, col_id: 2, col_name: "timestamp".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 2, col_id: 3, col_name: "attempts".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 2, col_id: 4, col_name: "error".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 2, col_id: 5, col_name: "payload".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX - 1, col_id: 0, col_name: "constraint_name".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 1, col_id: 1, col_name: "constraint_type".to_string(), col_type: AlgebraicType::String, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 1, col_id: 2, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 1, col_id: 3, col_name: "col_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX - 1, col_id: 4, col_name: "index_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+
+                StColumnRow { table_id: u32::MAX, col_id: 0, col_name: "table_id".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX, col_id: 1, col_name: "commits".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
+                StColumnRow { table_id: u32::MAX, col_id: 2, col_name: "conflicts".to_string(), col_type: AlgebraicType::U64, is_autoinc: false },
             ]
         );
         let index_rows = datastore
@@ -2194,10 +4129,10 @@ mod tests {
         assert_eq!(
             index_rows,
             vec![
-                StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true },
-                StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true },
-                StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true },
-                StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true },
+                StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+                StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+                StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+                StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
             ]
         );
         let sequence_rows = datastore
@@ -2214,6 +4149,21 @@ mod tests {
                 StSequenceRow { sequence_id: 2, sequence_name: "index_id_seq".to_string(), table_id: 3, col_id: 0, increment: 1, start: 4, min_value: 1, max_value: 4294967295, allocated: 4096 },
             ]
         );
+        let constraint_rows = datastore
+            .iter_mut_tx(&tx, ST_CONSTRAINTS_ID)?
+            .map(|x| StConstraintRow::try_from(x.view()).unwrap().to_owned())
+            .sorted_by_key(|x| x.index_id)
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(
+            constraint_rows,
+            vec![
+                StConstraintRow { constraint_name: "table_id_idx".to_string(), constraint_type: "unique".to_string(), table_id: 0, col_id: 0, index_id: 0 },
+                StConstraintRow { constraint_name: "index_id_idx".to_string(), constraint_type: "unique".to_string(), table_id: 3, col_id: 0, index_id: 1 },
+                StConstraintRow { constraint_name: "sequences_id_idx".to_string(), constraint_type: "unique".to_string(), table_id: 2, col_id: 0, index_id: 2 },
+                StConstraintRow { constraint_name: "table_name_idx".to_string(), constraint_type: "unique".to_string(), table_id: 0, col_id: 1, index_id: 3 },
+            ]
+        );
         datastore.rollback_mut_tx(tx);
         Ok(())
     }
@@ -2330,8 +4280,8 @@ mod tests {
                 ColumnSchema { table_id: 4, col_id: 2, col_name: "age".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
             ],
             indexes: vec![
-                IndexSchema { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true },
-                IndexSchema { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true },
+                IndexSchema { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+                IndexSchema { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
             ],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -2358,8 +4308,8 @@ mod tests {
                 ColumnSchema { table_id: 4, col_id: 2, col_name: "age".to_string(), col_type: AlgebraicType::U32, is_autoinc: false },
             ],
             indexes: vec![
-                IndexSchema { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true },
-                IndexSchema { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true },
+                IndexSchema { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+                IndexSchema { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
             ],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -2482,7 +4432,453 @@ mod tests {
     }
 
     #[test]
-    fn test_insert_commit_delete_insert() -> ResultTest<()> {
+    fn test_rollback_to_savepoint() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let schema = basic_table_schema();
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        let row = |name: &str| {
+            ProductValue::from_iter(vec![
+                AlgebraicValue::U32(0), // 0 will be ignored.
+                AlgebraicValue::String(name.to_string()),
+                AlgebraicValue::U32(18),
+            ])
+        };
+        let names = |tx: &MutTxId| -> ResultTest<Vec<String>> {
+            Ok(datastore
+                .iter_mut_tx(tx, table_id)?
+                .map(|r| r.view().field_as_str(1, None).unwrap().to_string())
+                .sorted()
+                .collect())
+        };
+        datastore.insert_mut_tx(&mut tx, table_id, row("Foo"))?;
+
+        let savepoint = datastore.savepoint_mut_tx(&mut tx);
+        datastore.insert_mut_tx(&mut tx, table_id, row("Bar"))?;
+        let nested = datastore.savepoint_mut_tx(&mut tx);
+        datastore.insert_mut_tx(&mut tx, table_id, row("Baz"))?;
+        assert_eq!(names(&tx)?, ["Bar", "Baz", "Foo"]);
+        datastore.rollback_to_savepoint_mut_tx(&mut tx, savepoint)?;
+        assert_eq!(names(&tx)?, ["Foo"]);
+
+        // Rolling back to `savepoint` released the savepoints taken after it.
+        let result = datastore.rollback_to_savepoint_mut_tx(&mut tx, nested);
+        assert!(matches!(result, Err(DBError::SavepointNotFound(_))));
+
+        let savepoint = datastore.savepoint_mut_tx(&mut tx);
+        datastore.insert_mut_tx(&mut tx, table_id, row("Qux"))?;
+        datastore.release_savepoint_mut_tx(&mut tx, savepoint)?;
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(names(&tx)?, ["Foo", "Qux"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint_deletes() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let row = |name: &str| {
+            ProductValue::from_iter(vec![
+                AlgebraicValue::U32(0), // 0 will be ignored.
+                AlgebraicValue::String(name.to_string()),
+                AlgebraicValue::U32(18),
+            ])
+        };
+        let names = |tx: &MutTxId| -> ResultTest<Vec<String>> {
+            Ok(datastore
+                .iter_mut_tx(tx, table_id)?
+                .map(|r| r.view().field_as_str(1, None).unwrap().to_string())
+                .sorted()
+                .collect())
+        };
+        let foo = datastore.insert_mut_tx(&mut tx, table_id, row("Foo"))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let bar = datastore.insert_mut_tx(&mut tx, table_id, row("Bar"))?;
+        let savepoint = datastore.savepoint_mut_tx(&mut tx);
+        // Delete a committed row and a row inserted before the savepoint,
+        // then insert the committed one again and a new row.
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [foo.clone(), bar])?;
+        datastore.insert_mut_tx(&mut tx, table_id, foo.clone())?;
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [foo])?;
+        datastore.insert_mut_tx(&mut tx, table_id, row("Baz"))?;
+        assert_eq!(names(&tx)?, ["Baz"]);
+        datastore.rollback_to_savepoint_mut_tx(&mut tx, savepoint)?;
+        assert_eq!(names(&tx)?, ["Bar", "Foo"]);
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(names(&tx)?, ["Bar", "Foo"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_txs_share_the_lock() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let row = ProductValue::from_iter(vec![
+            AlgebraicValue::U32(0), // 0 will be ignored.
+            AlgebraicValue::String("Foo".to_string()),
+            AlgebraicValue::U32(18),
+        ]);
+        datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        datastore.commit_mut_tx(tx)?;
+
+        // Both hold the lock at once, which would deadlock were it exclusive.
+        let first = datastore.begin_tx();
+        let second = datastore.begin_tx();
+        assert!(first.is_read_only() && second.is_read_only());
+        assert_eq!(datastore.iter_mut_tx(&first, table_id)?.count(), 1);
+        assert_eq!(datastore.iter_mut_tx(&second, table_id)?.count(), 1);
+        datastore.release_tx(first);
+        datastore.release_tx(second);
+
+        let tx = datastore.begin_mut_tx();
+        assert!(!tx.is_read_only());
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_suspended_tx_conflicts() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let foo = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let mut schema = basic_table_schema();
+        schema.table_name = "Bar".into();
+        schema.columns[0].is_autoinc = false;
+        schema.indexes.clear();
+        let bar = datastore.create_table_mut_tx(&mut tx, schema)?;
+        datastore.commit_mut_tx(tx)?;
+        let row = |id: u32, name: &str| {
+            product![
+                AlgebraicValue::U32(id),
+                AlgebraicValue::String(name.into()),
+                AlgebraicValue::U32(18)
+            ]
+        };
+
+        // Writes to different tables don't conflict.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, foo, row(0, "Foo"))?;
+        let suspended = tx.suspend();
+        let mut other = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut other, bar, row(1, "Bar"))?;
+        assert!(datastore.commit_mut_tx(other)?.is_some());
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_some());
+
+        // Reading a table that was written while suspended conflicts.
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, foo)?.count(), 1);
+        let suspended = tx.suspend();
+        let mut other = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut other, foo, row(0, "Baz"))?;
+        assert!(datastore.commit_mut_tx(other)?.is_some());
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_none());
+
+        // The conflicting transaction didn't commit anything.
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, foo)?.count(), 2);
+        assert_eq!(datastore.iter_mut_tx(&tx, bar)?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset_read_conflicts() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let foo = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let mut schema = basic_table_schema();
+        schema.table_name = "Bar".into();
+        schema.columns[0].is_autoinc = false;
+        schema.indexes.clear();
+        let bar = datastore.create_table_mut_tx(&mut tx, schema)?;
+        datastore.commit_mut_tx(tx)?;
+        let row = |id: u32, name: &str| {
+            product![
+                AlgebraicValue::U32(id),
+                AlgebraicValue::String(name.into()),
+                AlgebraicValue::U32(18)
+            ]
+        };
+
+        // Having read the offset, a transaction conflicts with a write to any other table.
+        let mut tx = datastore.begin_mut_tx();
+        tx.record_offset_read();
+        datastore.insert_mut_tx(&mut tx, foo, row(0, "Foo"))?;
+        let suspended = tx.suspend();
+        let mut other = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut other, bar, row(1, "Bar"))?;
+        assert!(datastore.commit_mut_tx(other)?.is_some());
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_none());
+
+        // But not when nothing was committed while it was suspended.
+        let mut tx = datastore.begin_mut_tx();
+        tx.record_offset_read();
+        datastore.insert_mut_tx(&mut tx, foo, row(0, "Foo"))?;
+        let suspended = tx.suspend();
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_some());
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, foo)?.count(), 1);
+        assert_eq!(datastore.iter_mut_tx(&tx, bar)?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_access_hints() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let foo = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let mut schema = basic_table_schema();
+        schema.table_name = "Bar".into();
+        schema.columns[0].is_autoinc = false;
+        schema.indexes.clear();
+        let bar = datastore.create_table_mut_tx(&mut tx, schema)?;
+        datastore.commit_mut_tx(tx)?;
+        datastore.set_access_hints(HashMap::from([
+            ("Foo".to_string(), AccessHint::ReadMostly),
+            ("Bar".to_string(), AccessHint::AppendOnly),
+        ]));
+        let row = |id: u32, name: &str| {
+            product![
+                AlgebraicValue::U32(id),
+                AlgebraicValue::String(name.into()),
+                AlgebraicValue::U32(18)
+            ]
+        };
+
+        // Inserts into an append-only table don't conflict.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, bar, row(0, "Foo"))?;
+        let suspended = tx.suspend();
+        let mut other = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut other, bar, row(1, "Bar"))?;
+        assert!(datastore.commit_mut_tx(other)?.is_some());
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_some());
+
+        // A write to a read-mostly table conflicts, even with a transaction that never read it.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, bar, row(2, "Baz"))?;
+        let suspended = tx.suspend();
+        let mut other = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut other, foo, row(0, "Foo"))?;
+        assert!(datastore.commit_mut_tx(other)?.is_some());
+        assert!(datastore.commit_mut_tx(suspended.resume())?.is_none());
+
+        // The commits, including the one that created the tables, and the conflict are counted.
+        let tx = datastore.begin_mut_tx();
+        let contention = datastore
+            .iter_mut_tx(&tx, ST_CONTENTION_ID)?
+            .map(|x| StContentionRow::try_from(x.view()).unwrap())
+            .filter(|x| x.table_id == foo.0 || x.table_id == bar.0)
+            .sorted_by_key(|x| x.table_id)
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(
+            contention,
+            vec![
+                StContentionRow { table_id: foo.0, commits: 2, conflicts: 1 },
+                StContentionRow { table_id: bar.0, commits: 3, conflicts: 0 },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_versions() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.commit_mut_tx(tx)?;
+        let row = |name: &str| {
+            product![
+                AlgebraicValue::U32(0),
+                AlgebraicValue::String(name.into()),
+                AlgebraicValue::U32(18)
+            ]
+        };
+
+        // Creating a table doesn't change its rows.
+        let mut tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.table_version_mut_tx(&tx, table_id)?, 0);
+        datastore.insert_mut_tx(&mut tx, table_id, row("Foo"))?;
+        datastore.commit_mut_tx(tx)?;
+
+        // A transaction doesn't count its own changes, nor does a rolled back one count.
+        let mut tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.table_version_mut_tx(&tx, table_id)?, 1);
+        datastore.insert_mut_tx(&mut tx, table_id, row("Bar"))?;
+        assert_eq!(datastore.table_version_mut_tx(&tx, table_id)?, 1);
+        datastore.rollback_mut_tx(tx);
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, row("Baz"))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.table_version_mut_tx(&tx, table_id)?, 2);
+        let versions = datastore
+            .iter_mut_tx(&tx, ST_TABLE_VERSION_ID)?
+            .map(|x| StTableVersionRow::try_from(x.view()).unwrap())
+            .filter(|x| x.table_id == table_id.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            vec![StTableVersionRow {
+                table_id: table_id.0,
+                version: 2
+            }]
+        );
+        assert!(datastore.table_version_mut_tx(&tx, TableId(table_id.0 + 1)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_budget() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let datastore = get_datastore()?;
+        datastore.set_memory_budget(MemoryBudget::new(64, tmp_dir.path().join("spill"))?)?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        for i in 1..=10 {
+            let row = product![
+                AlgebraicValue::U32(i),
+                AlgebraicValue::String(format!("Foo{i}")),
+                AlgebraicValue::U32(18)
+            ];
+            datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        }
+        datastore.commit_mut_tx(tx)?;
+
+        // Most of the rows have been evicted, but they can all still be read.
+        assert!(datastore.inner.read().committed_state.tables[&table_id].resident_bytes <= 64);
+        let mut tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, table_id)?.count(), 10);
+        let name = AlgebraicValue::String("Foo3".into());
+        let rows = datastore
+            .iter_by_col_eq_mut_tx(&tx, table_id, ColId(1), &name)?
+            .map(|row| row.view().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 1);
+
+        // And deleted.
+        assert_eq!(datastore.delete_by_rel_mut_tx(&mut tx, table_id, rows)?, Some(1));
+        datastore.commit_mut_tx(tx)?;
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, table_id)?.count(), 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let spill_path = tmp_dir.path().join("spill");
+        let datastore = get_datastore()?;
+        datastore.set_memory_budget(MemoryBudget::new(64, &spill_path)?)?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let row = |i: u32| product![i, format!("Foo{i}"), 18u32];
+        for i in 1..=10 {
+            datastore.insert_mut_tx(&mut tx, table_id, row(i))?;
+        }
+        datastore.commit_mut_tx(tx)?;
+        let spilled_bytes = |datastore: &Locking| {
+            let inner = datastore.inner.read();
+            let table = &inner.committed_state.tables[&table_id];
+            (table.stored_bytes() - table.resident_bytes) as u64
+        };
+
+        // Half the rows are deleted, at least two of which had been evicted.
+        let spilled_before = spilled_bytes(&datastore);
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, (1..=5).map(row))?;
+        datastore.commit_mut_tx(tx)?;
+        let spilled_after = spilled_bytes(&datastore);
+        assert!(spilled_after < spilled_before);
+        assert_eq!(std::fs::metadata(&spill_path)?.len(), spilled_before);
+
+        let mut tx = datastore.begin_mut_tx();
+        let report = datastore.vacuum_mut_tx(&mut tx)?;
+        datastore.commit_mut_tx(tx)?;
+        assert_eq!(report.spill_bytes, spilled_before - spilled_after);
+        assert_eq!(std::fs::metadata(&spill_path)?.len(), spilled_after);
+
+        // The rows left can all still be read, and found through the indexes.
+        let tx = datastore.begin_mut_tx();
+        let rows = datastore
+            .iter_mut_tx(&tx, table_id)?
+            .map(|r| r.view().clone())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(rows, (6..=10).map(row).collect::<Vec<_>>());
+        for i in 6..=10 {
+            let name = AlgebraicValue::String(format!("Foo{i}"));
+            let found = datastore
+                .iter_by_col_eq_mut_tx(&tx, table_id, ColId(1), &name)?
+                .map(|r| r.view().clone())
+                .collect::<Vec<_>>();
+            assert_eq!(found, [row(i)]);
+        }
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quota() -> ResultTest<()> {
+        fn exceeded<T>(result: Result<T, DBError>, limit: QuotaLimit) -> bool {
+            matches!(result, Err(DBError::QuotaExceeded(e)) if e.limit == limit)
+        }
+        let datastore = get_datastore()?;
+        datastore.set_quota(Quota {
+            max_tables: Some(1),
+            max_rows: Some(2),
+            max_bytes: None,
+        });
+
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let bar = TableDef {
+            table_name: "Bar".into(),
+            ..basic_table_schema()
+        };
+        assert!(exceeded(
+            datastore.create_table_mut_tx(&mut tx, bar),
+            QuotaLimit::Tables
+        ));
+
+        let row = |i: u32| {
+            product![
+                AlgebraicValue::U32(i),
+                AlgebraicValue::String(format!("Foo{i}")),
+                AlgebraicValue::U32(18)
+            ]
+        };
+        datastore.insert_mut_tx(&mut tx, table_id, row(1))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, row(2))?;
+        assert!(exceeded(
+            datastore.insert_mut_tx(&mut tx, table_id, row(3)),
+            QuotaLimit::Rows
+        ));
+        // A deleted row makes room for another right away.
+        assert_eq!(datastore.delete_by_rel_mut_tx(&mut tx, table_id, [row(1)])?, Some(1));
+        datastore.insert_mut_tx(&mut tx, table_id, row(3))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(datastore.iter_mut_tx(&tx, table_id)?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_commit_delete_insert() -> ResultTest<()> {
         let datastore = get_datastore()?;
         let mut tx = datastore.begin_mut_tx();
         let schema = basic_table_schema();
@@ -2587,6 +4983,7 @@ mod tests {
             Err(DBError::Index(IndexError::UniqueConstraintViolation {
                 constraint_name: _,
                 table_name: _,
+                col_id: _,
                 col_name: _,
                 value: _,
             })) => (),
@@ -2626,6 +5023,7 @@ mod tests {
             Err(DBError::Index(IndexError::UniqueConstraintViolation {
                 constraint_name: _,
                 table_name: _,
+                col_id: _,
                 col_name: _,
                 value: _,
             })) => (),
@@ -2708,13 +5106,13 @@ mod tests {
             .collect::<Vec<_>>();
         #[rustfmt::skip]
         assert_eq!(index_rows, vec![
-            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 6, table_id: 4, col_id: 2, index_name: "age_idx".to_string(), is_unique: true },
+            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 6, table_id: 4, col_id: 2, index_name: "age_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
         ]);
         let row = ProductValue::from_iter(vec![
             AlgebraicValue::U32(0), // 0 will be ignored.
@@ -2726,6 +5124,7 @@ mod tests {
             Err(DBError::Index(IndexError::UniqueConstraintViolation {
                 constraint_name: _,
                 table_name: _,
+                col_id: _,
                 col_name: _,
                 value: _,
             })) => (),
@@ -2765,6 +5164,7 @@ mod tests {
             col_id: 2,
             name: "age_idx".to_string(),
             is_unique: true,
+            index_type: IndexType::BTree,
         };
         datastore.create_index_mut_tx(&mut tx, index_def)?;
         datastore.commit_mut_tx(tx)?;
@@ -2776,13 +5176,13 @@ mod tests {
             .collect::<Vec<_>>();
         #[rustfmt::skip]
         assert_eq!(index_rows, vec![
-            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 6, table_id: 4, col_id: 2, index_name: "age_idx".to_string(), is_unique: true },
+            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 6, table_id: 4, col_id: 2, index_name: "age_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
         ]);
         let row = ProductValue::from_iter(vec![
             AlgebraicValue::U32(0), // 0 will be ignored.
@@ -2794,6 +5194,7 @@ mod tests {
             Err(DBError::Index(IndexError::UniqueConstraintViolation {
                 constraint_name: _,
                 table_name: _,
+                col_id: _,
                 col_name: _,
                 value: _,
             })) => (),
@@ -2814,6 +5215,212 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_constraints_follow_indexes() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let index_def = IndexDef {
+            table_id: table_id.0,
+            col_id: 2,
+            name: "age_idx".to_string(),
+            is_unique: false,
+            index_type: IndexType::BTree,
+        };
+        datastore.create_index_mut_tx(&mut tx, index_def)?;
+        let name_idx = datastore.index_id_from_name_mut_tx(&tx, "name_idx")?.unwrap();
+        datastore.drop_index_mut_tx(&mut tx, name_idx)?;
+        datastore.commit_mut_tx(tx)?;
+
+        // Only the unique index that's left is a constraint.
+        let tx = datastore.begin_mut_tx();
+        let constraint_rows = datastore
+            .iter_mut_tx(&tx, ST_CONSTRAINTS_ID)?
+            .map(|x| StConstraintRow::try_from(x.view()).unwrap().to_owned())
+            .filter(|x| x.table_id == table_id.0)
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(constraint_rows, vec![
+            StConstraintRow { constraint_name: "id_idx".to_string(), constraint_type: "unique".to_string(), table_id: table_id.0, col_id: 0, index_id: 4 },
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_index_online() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        let rows = (0..2 * INDEX_BUILD_CHUNK as u32 + 10).map(|i| product![0u32, format!("{i}"), i % 10]);
+        for row in rows {
+            datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        }
+        datastore.commit_mut_tx(tx)?;
+
+        let age_idx = IndexDef {
+            table_id: table_id.0,
+            col_id: 2,
+            name: "age_idx".to_string(),
+            is_unique: false,
+            index_type: IndexType::BTree,
+        };
+        let mut build = datastore.begin_index_build(age_idx)?;
+        assert!(build.scan_chunk()?);
+
+        // Write to the table halfway through the scan.
+        let mut tx = datastore.begin_mut_tx();
+        let age_3 = datastore
+            .iter_by_col_eq_mut_tx(&tx, table_id, ColId(2), &AlgebraicValue::U32(3))?
+            .map(|r| r.view().clone())
+            .collect::<Vec<_>>();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, age_3)?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![0u32, "new", 3u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        while build.scan_chunk()? {}
+        let mut tx = datastore.begin_mut_tx();
+        datastore.create_built_index_mut_tx(&mut tx, build)?;
+        datastore.commit_mut_tx(tx)?;
+
+        let inner = datastore.inner.read();
+        let table = &inner.committed_state.tables[&table_id];
+        let index = &table.indexes[&ColId(2)];
+        assert_eq!(index.scan().count(), table.row_count());
+        let age_3 = index
+            .seek(&AlgebraicValue::U32(3))
+            .map(|row_id| table.get_row(&row_id).unwrap().elements[1].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(age_3, [AlgebraicValue::String("new".into())]);
+        assert!(table.schema.indexes.iter().any(|index| index.index_name == "age_idx"));
+        assert!(inner.committed_state.index_builds.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_unique_index_online() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![0u32, "Foo", 18u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let age_idx = IndexDef {
+            table_id: table_id.0,
+            col_id: 2,
+            name: "age_idx".to_string(),
+            is_unique: true,
+            index_type: IndexType::BTree,
+        };
+        let build = datastore.build_index(age_idx)?;
+
+        // A duplicate committed during the build is caught when the index is created.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, product![0u32, "Bar", 18u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let result = datastore.create_built_index_mut_tx(&mut tx, build);
+        assert!(matches!(
+            result,
+            Err(DBError::Index(IndexError::UniqueConstraintViolation { .. }))
+        ));
+        datastore.rollback_mut_tx(tx);
+
+        // An index can't be built online on a system table.
+        let st_idx = IndexDef {
+            table_id: ST_TABLES_ID.0,
+            col_id: 2,
+            name: "st_table_type_idx".to_string(),
+            is_unique: false,
+            index_type: IndexType::BTree,
+        };
+        assert!(datastore.build_index(st_idx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deferred_unique_constraint() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Foo", 18u32])?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Bar", 19u32])?;
+        datastore.commit_mut_tx(tx)?;
+        datastore.set_deferred_constraints(["name_idx".to_string()].into());
+
+        // Swapping the names of the two rows goes through a duplicate name, which is allowed until the commit.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![1u32, "Foo", 18u32]])?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Bar", 18u32])?;
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![2u32, "Bar", 19u32]])?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Foo", 19u32])?;
+        assert!(datastore.commit_mut_tx(tx)?.is_some());
+
+        // A duplicate that's still there at the commit rolls the transaction back.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, product![3u32, "Foo", 20u32])?;
+        assert!(matches!(
+            datastore.commit_mut_tx(tx),
+            Err(DBError::Index(IndexError::UniqueConstraintViolation { .. }))
+        ));
+
+        // The constraints that aren't deferred are still checked on insert.
+        let mut tx = datastore.begin_mut_tx();
+        assert!(datastore
+            .insert_mut_tx(&mut tx, table_id, product![1u32, "Baz", 20u32])
+            .is_err());
+        let rows = datastore
+            .iter_mut_tx(&tx, table_id)?
+            .map(|r| r.view().clone())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [product![1u32, "Bar", 18u32], product![2u32, "Foo", 19u32]]);
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_version() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut schema = basic_table_schema();
+        schema.columns[2].col_type = AlgebraicType::U64;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        // Inserted before the versions are bumped, to see the bump wrap around.
+        datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Baz", u64::MAX])?;
+        datastore.commit_mut_tx(tx)?;
+        datastore.set_row_versions([("Foo".to_string(), (ColId(2), ColId(0)))].into());
+
+        // A new row starts at 0, whatever its version.
+        let mut tx = datastore.begin_mut_tx();
+        let row = datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Foo", 7u64])?;
+        assert_eq!(row, product![1u32, "Foo", 0u64]);
+        datastore.commit_mut_tx(tx)?;
+
+        // A row replacing one with the same key gets its version plus one.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![1u32, "Foo", 0u64]])?;
+        let row = datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Bar", 0u64])?;
+        assert_eq!(row, product![1u32, "Bar", 1u64]);
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![2u32, "Baz", u64::MAX]])?;
+        let row = datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Baz", 0u64])?;
+        assert_eq!(row, product![2u32, "Baz", 0u64]);
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        let rows = datastore
+            .iter_mut_tx(&tx, table_id)?
+            .map(|r| r.view().clone())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [product![1u32, "Bar", 1u64], product![2u32, "Baz", 0u64]]);
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
     #[test]
     fn test_create_index_post_rollback() -> ResultTest<()> {
         let datastore = get_datastore()?;
@@ -2844,12 +5451,12 @@ mod tests {
             .collect::<Vec<_>>();
         #[rustfmt::skip]
         assert_eq!(index_rows, vec![
-            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true },
-            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true },
+            StIndexRow { index_id: 0, table_id: 0, col_id: 0, index_name: "table_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 1, table_id: 3, col_id: 0, index_name: "index_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 2, table_id: 2, col_id: 0, index_name: "sequences_id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 3, table_id: 0, col_id: 1, index_name: "table_name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 4, table_id: 4, col_id: 0, index_name: "id_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
+            StIndexRow { index_id: 5, table_id: 4, col_id: 1, index_name: "name_idx".to_string(), is_unique: true, index_type: IndexType::BTree },
         ]);
         let row = ProductValue::from_iter(vec![
             AlgebraicValue::U32(0), // 0 will be ignored.
@@ -2933,6 +5540,362 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_add_columns() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let schema = basic_table_schema();
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        let row = ProductValue::from_iter(vec![
+            AlgebraicValue::U32(0), // 0 will be ignored.
+            AlgebraicValue::String("Foo".to_string()),
+            AlgebraicValue::U32(18),
+        ]);
+        datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let email = ColumnDef {
+            col_name: "email".into(),
+            col_type: AlgebraicType::option(AlgebraicType::String),
+            is_autoinc: false,
+        };
+        datastore.add_columns_mut_tx(&mut tx, table_id, vec![email])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let schema = datastore.schema_for_table_mut_tx(&tx, table_id)?;
+        assert_eq!(
+            schema.columns.iter().map(|col| &*col.col_name).collect::<Vec<_>>(),
+            ["id", "name", "age", "email"]
+        );
+        // The existing row has no value for the new column...
+        let rows = datastore
+            .iter_mut_tx(&tx, table_id)?
+            .map(|r| r.view().clone())
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(rows, vec![
+            ProductValue::from_iter(vec![
+                AlgebraicValue::U32(1),
+                AlgebraicValue::String("Foo".to_string()),
+                AlgebraicValue::U32(18),
+                AlgebraicValue::OptionNone(),
+            ])
+        ]);
+        // ...and it's still the one with its unique name.
+        let row = ProductValue::from_iter(vec![
+            AlgebraicValue::U32(0),
+            AlgebraicValue::String("Foo".to_string()),
+            AlgebraicValue::U32(21),
+            AlgebraicValue::OptionNone(),
+        ]);
+        assert!(datastore.insert_mut_tx(&mut tx, table_id, row).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fulltext_index_search() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let schema = TableDef {
+            indexes: vec![IndexDef::fulltext("name_idx".into(), 0, 1)],
+            ..basic_table_schema()
+        };
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        for (name, age) in [("Iron Sword", 18), ("Wooden Sword", 20), ("Iron Shield", 30)] {
+            let row = ProductValue::from_iter(vec![
+                AlgebraicValue::U32(0), // 0 will be ignored.
+                AlgebraicValue::String(name.to_string()),
+                AlgebraicValue::U32(age),
+            ]);
+            datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        }
+        datastore.commit_mut_tx(tx)?;
+
+        // Search both the committed rows and the ones inserted in this tx.
+        let mut tx = datastore.begin_mut_tx();
+        let row = ProductValue::from_iter(vec![
+            AlgebraicValue::U32(0), // 0 will be ignored.
+            AlgebraicValue::String("Sword of IRON".to_string()),
+            AlgebraicValue::U32(40),
+        ]);
+        datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        let search = |query: &str| -> ResultTest<Vec<String>> {
+            Ok(datastore
+                .iter_by_col_match_mut_tx(&tx, table_id, ColId(1), query)?
+                .map(|r| r.view().field_as_str(1, None).unwrap().to_string())
+                .sorted()
+                .collect())
+        };
+        assert_eq!(search("sword iron")?, ["Iron Sword", "Sword of IRON"]);
+        assert_eq!(search("shield")?, ["Iron Shield"]);
+        assert_eq!(search("axe")?, Vec::<String>::new());
+        assert_eq!(search("")?, Vec::<String>::new());
+
+        // A full-text index can't be unique.
+        let index_def = IndexDef {
+            is_unique: true,
+            ..IndexDef::fulltext("name_unique_idx".into(), table_id.0, 1)
+        };
+        let result = datastore.create_index_mut_tx(&mut tx, index_def);
+        assert!(matches!(result, Err(DBError::Index(IndexError::FullTextColumn(_)))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_spatial_index_search_box() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let point_type = AlgebraicType::product(vec![AlgebraicType::I32.into(), AlgebraicType::I32.into()]);
+        let schema = TableDef {
+            table_name: "Entity".into(),
+            columns: vec![
+                ColumnDef {
+                    col_name: "id".into(),
+                    col_type: AlgebraicType::U32,
+                    is_autoinc: false,
+                },
+                ColumnDef {
+                    col_name: "pos".into(),
+                    col_type: point_type,
+                    is_autoinc: false,
+                },
+            ],
+            indexes: vec![IndexDef::spatial("pos_idx".into(), 0, 1)],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        let entity = |id: u32, x: i32, y: i32| ProductValue::from_iter(vec![id.into(), product![x, y].into()]);
+        for row in [entity(1, 0, 0), entity(2, 5, 20), entity(3, 5, 5), entity(4, 11, 3)] {
+            datastore.insert_mut_tx(&mut tx, table_id, row)?;
+        }
+        datastore.commit_mut_tx(tx)?;
+
+        // Search both the committed rows and the ones inserted in this tx.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, entity(5, 10, 10))?;
+        let (min, max) = (product![0, 0].into(), product![10, 10].into());
+        let ids = datastore
+            .iter_by_col_box_mut_tx(&tx, table_id, ColId(1), &min, &max)?
+            .map(|r| r.view().field_as_u32(0, None).unwrap())
+            .sorted()
+            .collect::<Vec<_>>();
+        // `2` is ordered between the corners, but is outside of the box.
+        assert_eq!(ids, [1, 3, 5]);
+
+        // A spatial index must be on a point.
+        let index_def = IndexDef::spatial("id_idx".into(), table_id.0, 0);
+        let result = datastore.create_index_mut_tx(&mut tx, index_def);
+        assert!(matches!(result, Err(DBError::Index(IndexError::SpatialColumn(_)))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_values() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let large_bytes = AlgebraicType::product(vec![ProductTypeElement::new_named(
+            AlgebraicType::bytes(),
+            "__large_bytes_key",
+        )]);
+        let schema = TableDef {
+            table_name: "Avatar".into(),
+            columns: vec![
+                ColumnDef {
+                    col_name: "id".into(),
+                    col_type: AlgebraicType::U32,
+                    is_autoinc: false,
+                },
+                ColumnDef {
+                    col_name: "image".into(),
+                    col_type: large_bytes,
+                    is_autoinc: false,
+                },
+            ],
+            indexes: vec![],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = datastore.create_table_mut_tx(&mut tx, schema)?;
+        let avatar = |id: u32, key: DataKey| {
+            product![
+                id,
+                AlgebraicValue::Product(product![AlgebraicValue::Bytes(key.to_bytes())])
+            ]
+        };
+        let stored_values =
+            |tx: &MutTxId| -> ResultTest<usize> { Ok(datastore.iter_mut_tx(tx, ST_LARGE_VALUES_ID)?.count()) };
+
+        // Two rows referencing the same value share it.
+        let image = vec![7; 100];
+        let key = datastore.put_large_value_mut_tx(&mut tx, &image)?;
+        assert!(matches!(key, DataKey::Hash(_)));
+        datastore.insert_mut_tx(&mut tx, table_id, avatar(1, key))?;
+        assert_eq!(datastore.put_large_value_mut_tx(&mut tx, &image)?, key);
+        datastore.insert_mut_tx(&mut tx, table_id, avatar(2, key))?;
+        // A value no row references is gone once the transaction commits.
+        datastore.put_large_value_mut_tx(&mut tx, &[8; 100])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        assert_eq!(stored_values(&tx)?, 1);
+        assert_eq!(datastore.get_large_value_mut_tx(&tx, key)?, Some(image));
+        // Short values are held inline by their keys.
+        let key_inline = datastore.put_large_value_mut_tx(&mut tx, b"tiny")?;
+        assert!(matches!(key_inline, DataKey::Data(_)));
+        assert_eq!(
+            datastore.get_large_value_mut_tx(&tx, key_inline)?,
+            Some(b"tiny".to_vec())
+        );
+        assert_eq!(stored_values(&tx)?, 1);
+        // A row can't reference a value that isn't stored.
+        let missing = DataKey::from_data([9; 100]);
+        assert!(matches!(
+            datastore.insert_mut_tx(&mut tx, table_id, avatar(3, missing)),
+            Err(DBError::LargeValueNotFound(_))
+        ));
+        // The value is deleted with the last row that references it.
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [avatar(1, key)])?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        assert_eq!(stored_values(&tx)?, 1);
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [avatar(2, key)])?;
+        datastore.commit_mut_tx(tx)?;
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(stored_values(&tx)?, 0);
+        assert_eq!(datastore.get_large_value_mut_tx(&tx, key)?, None);
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_compression() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Foo", 18u32])?;
+        datastore.commit_mut_tx(tx)?;
+        // The rows already in memory are compressed along with those inserted afterwards.
+        datastore.set_column_compression([("Foo".to_string(), vec![(ColId(1), Compression::Dictionary)])].into());
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Bar", 19u32])?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![3u32, "Bar", 20u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        // Scans and index lookups see the values as inserted.
+        let tx = datastore.begin_mut_tx();
+        let rows = datastore
+            .iter_mut_tx(&tx, table_id)?
+            .map(|r| r.view().clone())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                product![1u32, "Foo", 18u32],
+                product![2u32, "Bar", 19u32],
+                product![3u32, "Bar", 20u32]
+            ]
+        );
+        let bar = AlgebraicValue::String("Bar".into());
+        assert_eq!(
+            datastore.iter_by_col_eq_mut_tx(&tx, table_id, ColId(1), &bar)?.count(),
+            2
+        );
+        let stats = datastore
+            .iter_mut_tx(&tx, ST_COLUMN_COMPRESSION_ID)?
+            .map(|r| StColumnCompressionRow::try_from(r.view()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stats,
+            [StColumnCompressionRow {
+                table_id: table_id.0,
+                col_id: 1,
+                codec: Compression::Dictionary,
+                raw_bytes: 9,
+                // A code for each row, and each distinct value once.
+                stored_bytes: 3 * 4 + 6,
+            }]
+        );
+        datastore.rollback_mut_tx(tx);
+
+        // Deleted rows no longer count.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![1u32, "Foo", 18u32]])?;
+        datastore.commit_mut_tx(tx)?;
+        let tx = datastore.begin_mut_tx();
+        let stats = datastore
+            .iter_mut_tx(&tx, ST_COLUMN_COMPRESSION_ID)?
+            .map(|r| StColumnCompressionRow::try_from(r.view()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!((stats[0].raw_bytes, stats[0].stored_bytes), (6, 2 * 4 + 3));
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_storage() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let mut tx = datastore.begin_mut_tx();
+        let table_id = datastore.create_table_mut_tx(&mut tx, basic_table_schema())?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![1u32, "Foo", 18u32])?;
+        datastore.insert_mut_tx(&mut tx, table_id, product![2u32, "Bar", 19u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let storage_of = |datastore: &Locking| -> ResultTest<Vec<StStorageRow>> {
+            let tx = datastore.begin_mut_tx();
+            let rows = datastore
+                .iter_mut_tx(&tx, ST_STORAGE_ID)?
+                .map(|r| StStorageRow::try_from(r.view()).unwrap())
+                .filter(|row| row.table_id == table_id.0 || row.kind == "commit_log")
+                .sorted_by(|a, b| (&a.kind, &a.name).cmp(&(&b.kind, &b.name)))
+                .collect::<Vec<_>>();
+            datastore.rollback_mut_tx(tx);
+            Ok(rows)
+        };
+
+        let recorded = datastore.record_storage(Some((1024, 2)), 7);
+        let rows = storage_of(&datastore)?;
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|row| recorded.contains(row) && row.measured_at == 7));
+        let entries_of = |rows: &[StStorageRow]| rows.iter().map(|row| (row.name.clone(), row.entries)).collect_vec();
+        assert_eq!(
+            entries_of(&rows),
+            [
+                ("commit_log".to_string(), 2),
+                ("id_idx".to_string(), 2),
+                ("name_idx".to_string(), 2),
+                ("Foo".to_string(), 2),
+            ]
+        );
+        let [commit_log, id_idx, name_idx, table] = &rows[..] else {
+            unreachable!()
+        };
+        assert_eq!(commit_log.bytes, 1024);
+        assert!(table.bytes > 0);
+        // The keys of `name_idx` hold the strings on top of what the keys of `id_idx` hold.
+        assert_eq!(name_idx.bytes, id_idx.bytes + 6);
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [product![1u32, "Foo", 18u32]])?;
+        datastore.commit_mut_tx(tx)?;
+        datastore.record_storage(None, 8);
+        let after = storage_of(&datastore)?;
+        assert_eq!(
+            entries_of(&after),
+            [
+                ("id_idx".to_string(), 1),
+                ("name_idx".to_string(), 1),
+                ("Foo".to_string(), 1)
+            ]
+        );
+        assert_eq!(after[0].bytes, id_idx.bytes / 2);
+        assert!(after[2].bytes < table.bytes);
+        Ok(())
+    }
+
     // TODO: Add the following tests
     // - Create index with unique constraint and immediately insert a row that violates the constraint before committing.
     // - Create a tx that inserts 2000 rows with an autoinc column
@@ -0,0 +1,133 @@
+//! Building an index on a table without holding the lock on the datastore for the whole build.
+//!
+//! An [`IndexBuild`] scans the committed rows of the table a chunk at a time,
+//! taking the lock only to read each chunk, and indexes them without it.
+//! Meanwhile, every commit appends its writes to the table to the [`IndexBuildLog`] of the build.
+//! Once the scan is done, [`Locking::create_built_index_mut_tx`](super::Locking::create_built_index_mut_tx)
+//! replays the log into the index, which is then added to the table when the transaction commits.
+//!
+//! The index ends up with exactly the rows committed at that point:
+//! the last write to a row during the build, if any, is in the log and decides whether it's indexed,
+//! and a row that wasn't written to was seen by the scan if and only if it exists.
+
+use super::{btree_index::BTreeIndex, Inner, RowId};
+use crate::{
+    db::datastore::traits::{IndexDef, TableId, TxOp, TxRecord},
+    error::{DBError, TableError},
+};
+use parking_lot::RwLock;
+use spacetimedb_sats::ProductValue;
+use std::sync::{Arc, Weak};
+
+/// The number of rows an [`IndexBuild`] reads each time it takes the lock.
+pub(crate) const INDEX_BUILD_CHUNK: usize = 1024;
+
+/// An index being built on a committed table,
+/// see [`Locking::begin_index_build`](super::Locking::begin_index_build).
+pub struct IndexBuild {
+    pub(crate) def: IndexDef,
+    pub(crate) index: BTreeIndex,
+    /// Keeps the [`IndexBuildLog`] of the build alive.
+    pub(crate) log: Arc<()>,
+    /// The last row indexed by the scan, if any.
+    after: Option<RowId>,
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl IndexBuild {
+    pub(super) fn new(def: IndexDef, index: BTreeIndex, log: Arc<()>, inner: Arc<RwLock<Inner>>) -> Self {
+        Self {
+            def,
+            index,
+            log,
+            after: None,
+            inner,
+        }
+    }
+
+    /// Indexes the next [`INDEX_BUILD_CHUNK`] rows of the table,
+    /// returning whether there were any left.
+    ///
+    /// The lock on the datastore is only held while the rows are read,
+    /// so this must not be called while holding a transaction.
+    pub fn scan_chunk(&mut self) -> Result<bool, DBError> {
+        let rows = {
+            let inner = self.inner.read();
+            let table = inner
+                .committed_state
+                .tables
+                .get(&TableId(self.def.table_id))
+                .ok_or(TableError::IdNotFound(self.def.table_id))?;
+            table.rows_after(self.after.as_ref(), INDEX_BUILD_CHUNK)
+        };
+        let Some(last) = rows.last().map(|(row_id, _)| *row_id) else {
+            return Ok(false);
+        };
+        for (_, row) in &rows {
+            self.index.insert(row)?;
+        }
+        self.after = Some(last);
+        Ok(true)
+    }
+}
+
+/// A write to a table committed during an [`IndexBuild`].
+#[derive(Clone)]
+pub(crate) enum BufferedWrite {
+    Insert(ProductValue),
+    Delete(RowId, ProductValue),
+}
+
+/// The writes committed to a table since an [`IndexBuild`] on it began.
+pub(crate) struct IndexBuildLog {
+    table_id: TableId,
+    /// The writes, in the order they were committed.
+    writes: Vec<BufferedWrite>,
+    /// Dead once the [`IndexBuild`] is dropped, at which point the log can be discarded.
+    build: Weak<()>,
+}
+
+impl IndexBuildLog {
+    pub(crate) fn new(table_id: TableId, build: &Arc<()>) -> Self {
+        Self {
+            table_id,
+            writes: Vec::new(),
+            build: Arc::downgrade(build),
+        }
+    }
+
+    pub(crate) fn is_live(&self) -> bool {
+        self.build.strong_count() > 0
+    }
+
+    /// Returns whether this is the log of the build holding `build`.
+    pub(crate) fn is_for(&self, build: &Arc<()>) -> bool {
+        Weak::ptr_eq(&self.build, &Arc::downgrade(build))
+    }
+
+    /// Appends the writes to the table among `records`, those of a transaction being committed.
+    pub(crate) fn record(&mut self, records: &[TxRecord]) {
+        let writes = records
+            .iter()
+            .filter(|record| record.table_id == self.table_id)
+            .map(|record| match record.op {
+                TxOp::Insert(_) => BufferedWrite::Insert(record.product_value.clone()),
+                TxOp::Delete => BufferedWrite::Delete(RowId(record.key), record.product_value.clone()),
+            });
+        self.writes.extend(writes);
+    }
+
+    /// Applies the writes to `index`, in the order they were committed.
+    pub(crate) fn replay(self, index: &mut BTreeIndex) -> Result<(), DBError> {
+        for write in self.writes {
+            match write {
+                BufferedWrite::Insert(row) => index.insert(&row)?,
+                BufferedWrite::Delete(row_id, row) => {
+                    let col_value = row.get_field(index.col_id as usize, None)?;
+                    index.delete(col_value, &row_id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use crate::error::{QuotaExceeded, QuotaLimit};
+use spacetimedb_sats::de::Deserialize;
+use spacetimedb_sats::ser::Serialize;
+
+/// The limits on what a database can store, each unlimited if `None`.
+///
+/// Only user tables and their rows count towards them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, serde::Serialize, serde::Deserialize)]
+pub struct Quota {
+    #[serde(default)]
+    pub max_tables: Option<u64>,
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+    /// The most bytes of encoded rows, whether in memory or evicted to disk.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+impl Quota {
+    pub fn max(&self, limit: QuotaLimit) -> Option<u64> {
+        match limit {
+            QuotaLimit::Tables => self.max_tables,
+            QuotaLimit::Rows => self.max_rows,
+            QuotaLimit::Bytes => self.max_bytes,
+        }
+    }
+
+    /// Returns an error if `used` is more than the quota allows of `limit`.
+    pub fn check(&self, limit: QuotaLimit, used: u64) -> Result<(), QuotaExceeded> {
+        match self.max(limit) {
+            Some(max) if used > max => Err(QuotaExceeded { limit, max }),
+            _ => Ok(()),
+        }
+    }
+}
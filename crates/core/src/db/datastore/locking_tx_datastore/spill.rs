@@ -0,0 +1,119 @@
+use parking_lot::Mutex;
+use spacetimedb_sats::{ProductType, ProductValue};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// The limit on the memory taken by the committed rows of a database,
+/// past which the rows of its coldest tables are evicted to a [`SpillFile`].
+pub struct MemoryBudget {
+    /// The most bytes of encoded rows to keep in memory.
+    pub(crate) max_bytes: usize,
+    pub(crate) spill_file: Arc<SpillFile>,
+}
+
+impl MemoryBudget {
+    /// Limits the committed rows in memory to `max_bytes`,
+    /// spilling the rest to a file at `path`.
+    ///
+    /// The file only ever holds rows that are also in the message log,
+    /// so it's truncated rather than read back when the database is reopened.
+    pub fn new(max_bytes: usize, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            max_bytes,
+            spill_file: Arc::new(SpillFile::create(path)?),
+        })
+    }
+}
+
+/// Where an evicted row is in a [`SpillFile`].
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SpillSlot {
+    offset: u64,
+    len: u32,
+}
+
+impl SpillSlot {
+    /// Returns the size of the row, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        self.len as usize
+    }
+}
+
+/// An append-only file of evicted rows, encoded in BSATN.
+///
+/// The space of rows that are deleted after being evicted isn't reclaimed
+/// until the database is vacuumed or reopened.
+pub(crate) struct SpillFile {
+    file: Mutex<File>,
+}
+
+impl SpillFile {
+    fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    /// Appends `row` to the file, returning where it was written.
+    pub(crate) fn write(&self, row: &ProductValue) -> io::Result<SpillSlot> {
+        let mut bytes = Vec::new();
+        row.encode(&mut bytes);
+        let mut file = self.file.lock();
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(&bytes)?;
+        Ok(SpillSlot {
+            offset,
+            len: bytes.len() as u32,
+        })
+    }
+
+    /// Moves the rows at `slots`, which must be all the rows still held in the file,
+    /// to its start, updating their slots, and truncates the file after them.
+    ///
+    /// Returns the number of bytes reclaimed.
+    pub(crate) fn compact(&self, mut slots: Vec<&mut SpillSlot>) -> io::Result<u64> {
+        slots.sort_by_key(|slot| slot.offset);
+        let mut file = self.file.lock();
+        let len = file.seek(SeekFrom::End(0))?;
+        let mut end = 0;
+        let mut bytes = Vec::new();
+        for slot in slots {
+            // A row is only ever moved towards the start, over rows that were moved already or deleted.
+            if slot.offset != end {
+                bytes.resize(slot.len as usize, 0);
+                file.seek(SeekFrom::Start(slot.offset))?;
+                file.read_exact(&mut bytes)?;
+                file.seek(SeekFrom::Start(end))?;
+                file.write_all(&bytes)?;
+                slot.offset = end;
+            }
+            end += slot.len as u64;
+        }
+        file.set_len(end)?;
+        Ok(len - end)
+    }
+
+    /// Reads the row of type `row_type` at `slot` back into memory.
+    ///
+    /// Panics if the row can't be read,
+    /// as the rows of a table are expected to always be available.
+    pub(crate) fn read(&self, slot: SpillSlot, row_type: &ProductType) -> ProductValue {
+        let mut bytes = vec![0; slot.len as usize];
+        {
+            let mut file = self.file.lock();
+            file.seek(SeekFrom::Start(slot.offset))
+                .and_then(|_| file.read_exact(&mut bytes))
+                .unwrap_or_else(|e| panic!("Couldn't read spilled row at offset {}: {e}", slot.offset));
+        }
+        ProductValue::decode(row_type, &mut &bytes[..])
+            .unwrap_or_else(|_| panic!("Couldn't decode spilled row to {:?}", row_type))
+    }
+}
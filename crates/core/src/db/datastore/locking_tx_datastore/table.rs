@@ -1,19 +1,57 @@
 use super::{
     btree_index::{BTreeIndex, BTreeIndexIter, BTreeIndexRangeIter},
+    compression::{decode_row, encode_row, into_decoded_row, release_row, ColumnCodec},
+    spill::{SpillFile, SpillSlot},
     RowId,
 };
 use crate::db::datastore::traits::{ColId, TableSchema};
+use spacetimedb_lib::{Compression, DataKey, IndexType};
 use spacetimedb_sats::{AlgebraicValue, ProductType, ProductValue};
 use std::{
-    collections::{BTreeMap, HashMap},
-    ops::RangeBounds,
+    borrow::Cow,
+    collections::{btree_map, BTreeMap, HashMap},
+    io,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
 };
 
+#[derive(Clone)]
 pub(crate) struct Table {
     pub(crate) row_type: ProductType,
     pub(crate) schema: TableSchema,
     pub(crate) indexes: HashMap<ColId, BTreeIndex>,
+    /// The rows that are in memory.
+    ///
+    /// The values of the compressed columns are stored as their stand-ins, see [`ColumnCodec`],
+    /// so the rows are read through [`Table::get_row`] and [`Table::iter`], which decode them.
     pub(crate) rows: BTreeMap<RowId, ProductValue>,
+    /// The rows that have been evicted to disk, see [`MemoryBudget`](super::MemoryBudget).
+    pub(crate) spilled: SpilledRows,
+    /// The size of the encoding of the rows in memory, in bytes.
+    pub(crate) resident_bytes: usize,
+    /// The columns compressed in memory.
+    pub(crate) codecs: Vec<ColumnCodec>,
+}
+
+/// The rows of a table that have been evicted to a [`SpillFile`].
+#[derive(Clone, Default)]
+pub(crate) struct SpilledRows {
+    file: Option<Arc<SpillFile>>,
+    slots: BTreeMap<RowId, SpillSlot>,
+    /// The size of the encoding of the rows, in bytes.
+    bytes: usize,
+}
+
+/// Returns the size of the encoding of `row`, whose id is `row_id`, in bytes.
+fn row_size(row_id: &RowId, row: &ProductValue) -> usize {
+    match &row_id.0 {
+        DataKey::Data(data) => data.len(),
+        DataKey::Hash(_) => {
+            let mut bytes = Vec::new();
+            row.encode(&mut bytes);
+            bytes.len()
+        }
+    }
 }
 
 impl Table {
@@ -26,11 +64,27 @@ impl Table {
         for (_, index) in self.indexes.iter_mut() {
             index.insert(&row).unwrap();
         }
-        self.rows.insert(row_id, row);
+        let row = encode_row(&mut self.codecs, row);
+        self.resident_bytes += row_size(&row_id, &row);
+        // The same row inserted again replaces itself, so only one of them holds its compressed values.
+        if let Some(old_row) = self.rows.insert(row_id, row) {
+            self.resident_bytes -= row_size(&row_id, &old_row);
+            release_row(&mut self.codecs, &old_row);
+        }
     }
 
     pub(crate) fn delete(&mut self, row_id: &RowId) -> Option<ProductValue> {
-        let row = self.rows.remove(row_id)?;
+        let row = match self.rows.remove(row_id) {
+            Some(row) => {
+                self.resident_bytes -= row_size(row_id, &row);
+                self.take_resident(row)
+            }
+            None => {
+                let slot = self.spilled.slots.remove(row_id)?;
+                self.spilled.bytes -= slot.size();
+                self.read_spilled(slot)
+            }
+        };
         for (col_id, index) in self.indexes.iter_mut() {
             let col_value = row.get_field(col_id.0 as usize, None).unwrap();
             index.delete(col_value, row_id)
@@ -38,8 +92,137 @@ impl Table {
         Some(row)
     }
 
-    pub(crate) fn get_row(&self, row_id: &RowId) -> Option<&ProductValue> {
-        self.rows.get(row_id)
+    /// Returns the row identified by `row_id`,
+    /// which is read back from disk if it has been evicted.
+    pub(crate) fn get_row(&self, row_id: &RowId) -> Option<Cow<'_, ProductValue>> {
+        if let Some(row) = self.rows.get(row_id) {
+            return Some(decode_row(&self.codecs, row));
+        }
+        let slot = self.spilled.slots.get(row_id)?;
+        Some(Cow::Owned(self.read_spilled(*slot)))
+    }
+
+    /// Returns whether the row identified by `row_id` is in the table, without reading it back from disk.
+    pub(crate) fn contains_row(&self, row_id: &RowId) -> bool {
+        self.rows.contains_key(row_id) || self.spilled.slots.contains_key(row_id)
+    }
+
+    /// Returns the decoded `row`, which was just removed from the rows in memory.
+    fn take_resident(&mut self, row: ProductValue) -> ProductValue {
+        if self.codecs.is_empty() {
+            return row;
+        }
+        let decoded = decode_row(&self.codecs, &row).into_owned();
+        release_row(&mut self.codecs, &row);
+        decoded
+    }
+
+    /// Compresses the `columns` in memory, and only those, with their codecs,
+    /// re-encoding the rows in memory if that changes how they're stored.
+    pub(crate) fn set_compression(&mut self, columns: &[(ColId, Compression)]) {
+        let unchanged = self.codecs.len() == columns.len()
+            && self
+                .codecs
+                .iter()
+                .zip(columns)
+                .all(|(codec, &(col_id, compression))| codec.col_id == col_id && codec.codec == compression);
+        if unchanged {
+            return;
+        }
+        self.reencode(
+            columns
+                .iter()
+                .map(|&(col_id, compression)| ColumnCodec::new(col_id, compression))
+                .collect(),
+        );
+    }
+
+    /// Replaces the codecs of the compressed columns with `codecs`, re-encoding the rows in memory.
+    fn reencode(&mut self, codecs: Vec<ColumnCodec>) {
+        let old_codecs = std::mem::replace(&mut self.codecs, codecs);
+        self.resident_bytes = 0;
+        self.rows = std::mem::take(&mut self.rows)
+            .into_iter()
+            .map(|(row_id, row)| {
+                let row = encode_row(&mut self.codecs, into_decoded_row(&old_codecs, row));
+                self.resident_bytes += row_size(&row_id, &row);
+                (row_id, row)
+            })
+            .collect();
+    }
+
+    /// Rebuilds the rows and indexes in memory, and the dictionaries of the compressed columns,
+    /// so that they no longer hold on to the space of the rows deleted since they were built.
+    ///
+    /// Returns the approximate number of bytes reclaimed, which only counts what the dictionaries held spare:
+    /// the nodes of the trees of rows and keys are packed too, but their space isn't measured.
+    pub(crate) fn vacuum(&mut self) -> usize {
+        let spare_before: usize = self.codecs.iter().map(ColumnCodec::spare_bytes).sum();
+        if self.codecs.is_empty() {
+            self.rows = std::mem::take(&mut self.rows).into_iter().collect();
+        } else {
+            let codecs = self
+                .codecs
+                .iter()
+                .map(|codec| ColumnCodec::new(codec.col_id, codec.codec))
+                .collect();
+            self.reencode(codecs);
+            self.codecs.iter_mut().for_each(ColumnCodec::shrink_to_fit);
+        }
+        self.spilled.slots = std::mem::take(&mut self.spilled.slots).into_iter().collect();
+        for index in self.indexes.values_mut() {
+            index.rebuild();
+        }
+        let spare_after: usize = self.codecs.iter().map(ColumnCodec::spare_bytes).sum();
+        spare_before.saturating_sub(spare_after)
+    }
+
+    /// Returns where each of the rows evicted to `file` is in it, to be moved by [`SpillFile::compact`].
+    pub(crate) fn spilled_slots_mut(&mut self, file: &Arc<SpillFile>) -> impl Iterator<Item = &mut SpillSlot> {
+        let in_file = self.spilled.file.as_ref().map_or(false, |f| Arc::ptr_eq(f, file));
+        self.spilled.slots.values_mut().filter(move |_| in_file)
+    }
+
+    fn read_spilled(&self, slot: SpillSlot) -> ProductValue {
+        let file = self.spilled.file.as_ref().expect("evicted rows without a spill file");
+        file.read(slot, &self.row_type)
+    }
+
+    /// Evicts rows from memory to `file` until at least `bytes` bytes have been freed
+    /// or none are left, returning the number of bytes freed.
+    pub(crate) fn evict(&mut self, file: &Arc<SpillFile>, bytes: usize) -> io::Result<usize> {
+        self.spilled.file.get_or_insert_with(|| file.clone());
+        let mut freed = 0;
+        while freed < bytes {
+            let Some((row_id, row)) = self.rows.pop_first() else {
+                break;
+            };
+            freed += row_size(&row_id, &row);
+            // Evicted rows are stored as inserted, as they're only read back one at a time.
+            let row = self.take_resident(row);
+            let slot = file.write(&row)?;
+            self.spilled.bytes += slot.size();
+            self.spilled.slots.insert(row_id, slot);
+        }
+        self.resident_bytes -= freed;
+        Ok(freed)
+    }
+
+    /// Returns the number of rows, in memory or evicted to disk.
+    pub(crate) fn row_count(&self) -> usize {
+        self.rows.len() + self.spilled.slots.len()
+    }
+
+    /// Returns the size of the encoding of the rows, in memory or evicted to disk, in bytes.
+    pub(crate) fn stored_bytes(&self) -> usize {
+        self.resident_bytes + self.spilled.bytes
+    }
+
+    /// Returns the approximate size of the rows in memory, in bytes,
+    /// including the dictionaries of their compressed columns but not their indexes.
+    pub(crate) fn memory_bytes(&self) -> usize {
+        let dictionaries: u64 = self.codecs.iter().map(ColumnCodec::dictionary_bytes).sum();
+        self.resident_bytes + dictionaries as usize
     }
 
     pub(crate) fn get_row_type(&self) -> &ProductType {
@@ -50,11 +233,45 @@ impl Table {
         &self.schema
     }
 
-    pub(crate) fn scan_rows(&self) -> impl Iterator<Item = &ProductValue> {
-        self.rows.values()
+    pub(crate) fn scan_rows(&self) -> impl Iterator<Item = Cow<'_, ProductValue>> {
+        self.iter().map(|(_, row)| row)
+    }
+
+    /// Returns an iterator over the rows in memory, then the rows evicted to disk.
+    pub(crate) fn iter(&self) -> RowsIter<'_> {
+        RowsIter {
+            table: self,
+            resident: self.rows.iter(),
+            spilled: self.spilled.slots.iter(),
+        }
+    }
+
+    /// Returns up to `limit` rows, in memory or evicted to disk, in the order of their ids,
+    /// starting after the row `after`, if any.
+    pub(crate) fn rows_after(&self, after: Option<&RowId>, limit: usize) -> Vec<(RowId, ProductValue)> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut row_ids = self
+            .rows
+            .range((start, Bound::Unbounded))
+            .map(|(row_id, _)| *row_id)
+            .take(limit)
+            .chain(
+                self.spilled
+                    .slots
+                    .range((start, Bound::Unbounded))
+                    .map(|(row_id, _)| *row_id)
+                    .take(limit),
+            )
+            .collect::<Vec<_>>();
+        row_ids.sort();
+        row_ids.truncate(limit);
+        row_ids
+            .into_iter()
+            .filter_map(|row_id| Some((row_id, self.get_row(&row_id)?.into_owned())))
+            .collect()
     }
 
-    /// When there's an index for `col_id`,
+    /// When there's an index for `col_id` that stores the column's values as is,
     /// returns an iterator over the [`BTreeIndex`] that yields all the `RowId`s
     /// that match the specified `value` in the indexed column.
     ///
@@ -66,7 +283,35 @@ impl Table {
         col_id: ColId,
         value: &'a AlgebraicValue,
     ) -> Option<BTreeIndexRangeIter<'a>> {
-        self.indexes.get(&col_id).map(|index| index.seek(value))
+        self.indexes
+            .get(&col_id)
+            .filter(|index| index.index_type != IndexType::FullText)
+            .map(|index| index.seek(value))
+    }
+
+    /// When there's a [`IndexType::FullText`] index for `col_id`,
+    /// returns an iterator over the [`BTreeIndex`] that yields the `RowId`s
+    /// of the candidate rows for `query`.
+    pub(crate) fn index_search(&self, col_id: ColId, query: &str) -> Option<BTreeIndexRangeIter<'_>> {
+        self.indexes
+            .get(&col_id)
+            .filter(|index| index.index_type == IndexType::FullText)
+            .map(|index| index.search(query))
+    }
+
+    /// When there's a [`IndexType::Spatial`] index for `col_id`,
+    /// returns an iterator over the [`BTreeIndex`] that yields the `RowId`s
+    /// of the candidate rows for the box from `min` to `max`.
+    pub(crate) fn index_search_box(
+        &self,
+        col_id: ColId,
+        min: &AlgebraicValue,
+        max: &AlgebraicValue,
+    ) -> Option<BTreeIndexRangeIter<'_>> {
+        self.indexes
+            .get(&col_id)
+            .filter(|index| index.index_type == IndexType::Spatial)
+            .map(|index| index.search_box(min, max))
     }
 
     pub(crate) fn _index_scan(&self, col_id: ColId) -> BTreeIndexIter<'_> {
@@ -81,3 +326,22 @@ impl Table {
         self.indexes.get(&col_id).unwrap().scan_range(range)
     }
 }
+
+/// An iterator over the rows of a [`Table`], with their ids.
+pub(crate) struct RowsIter<'a> {
+    table: &'a Table,
+    resident: btree_map::Iter<'a, RowId, ProductValue>,
+    spilled: btree_map::Iter<'a, RowId, SpillSlot>,
+}
+
+impl<'a> Iterator for RowsIter<'a> {
+    type Item = (&'a RowId, Cow<'a, ProductValue>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((row_id, row)) = self.resident.next() {
+            return Some((row_id, decode_row(&self.table.codecs, row)));
+        }
+        let (row_id, slot) = self.spilled.next()?;
+        Some((row_id, Cow::Owned(self.table.read_spilled(*slot))))
+    }
+}
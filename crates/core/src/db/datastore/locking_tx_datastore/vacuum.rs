@@ -0,0 +1,18 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// The space reclaimed by vacuuming a database, see [`Locking::vacuum_mut_tx`](super::Locking::vacuum_mut_tx).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct VacuumReport {
+    /// The approximate number of bytes of memory reclaimed,
+    /// not counting the space of the nodes of the trees of rows and keys, which isn't measured.
+    pub memory_bytes: u64,
+    /// The number of bytes by which the file of the rows evicted to disk shrank.
+    pub spill_bytes: u64,
+}
+
+/// Releases the spare capacity of `map`, returning the approximate number of bytes released.
+pub(super) fn shrink_map<K: Eq + Hash, V>(map: &mut HashMap<K, V>) -> usize {
+    let capacity = map.capacity();
+    map.shrink_to_fit();
+    (capacity - map.capacity()) * std::mem::size_of::<(K, V)>()
+}
@@ -1,7 +1,8 @@
 use super::traits::{ColumnSchema, IndexSchema, SequenceId, SequenceSchema, TableId, TableSchema};
 use crate::error::{DBError, TableError};
 use once_cell::sync::Lazy;
-use spacetimedb_lib::auth::{StAccess, StTableType};
+use spacetimedb_lib::auth::{StAccess, StRoleAccess, StTableType};
+use spacetimedb_lib::{Address, Compression, Hash, Identity, IndexType};
 use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductType, ProductValue};
 
 /// The static ID of the table that defines tables
@@ -13,10 +14,67 @@ pub(crate) const ST_SEQUENCES_ID: TableId = TableId(2);
 /// The static ID of the table that defines indexes
 pub(crate) const ST_INDEXES_ID: TableId = TableId(3);
 
+// The system tables added since the first four are counted down from the last ID rather than up from the next one,
+// so as not to shift the IDs allocated to the tables of existing databases.
+/// The static ID of the table that counts commits and conflicts per table.
+pub(crate) const ST_CONTENTION_ID: TableId = TableId(u32::MAX);
+/// The static ID of the table that lists the constraints on columns.
+pub(crate) const ST_CONSTRAINTS_ID: TableId = TableId(u32::MAX - 1);
+/// The static ID of the table of webhook deliveries that were given up on.
+pub(crate) const ST_WEBHOOK_DEAD_LETTER_ID: TableId = TableId(u32::MAX - 2);
+/// The static ID of the table of the disk space used by the database.
+pub(crate) const ST_DISK_USAGE_ID: TableId = TableId(u32::MAX - 3);
+/// The static ID of the table of roles.
+pub(crate) const ST_ROLES_ID: TableId = TableId(u32::MAX - 4);
+/// The static ID of the table of the roles granted to identities.
+pub(crate) const ST_ROLE_MEMBERS_ID: TableId = TableId(u32::MAX - 5);
+/// The static ID of the table of the access roles have to tables.
+pub(crate) const ST_TABLE_ACL_ID: TableId = TableId(u32::MAX - 6);
+/// The static ID of the table of the last calls to reducers with a cooldown.
+pub(crate) const ST_REDUCER_COOLDOWN_ID: TableId = TableId(u32::MAX - 7);
+/// The static ID of the table of the statistics on columns used by the query planner.
+pub(crate) const ST_COLUMN_STATS_ID: TableId = TableId(u32::MAX - 8);
+/// The static ID of the table that counts the transactions that changed each table.
+pub(crate) const ST_TABLE_VERSION_ID: TableId = TableId(u32::MAX - 9);
+/// The static ID of the table of the blobs stored by the database.
+pub(crate) const ST_BLOBS_ID: TableId = TableId(u32::MAX - 10);
+/// The static ID of the table of the byte strings stored out of the rows that reference them.
+pub(crate) const ST_LARGE_VALUES_ID: TableId = TableId(u32::MAX - 11);
+/// The static ID of the table of the compression ratios of the compressed columns.
+pub(crate) const ST_COLUMN_COMPRESSION_ID: TableId = TableId(u32::MAX - 12);
+/// The static ID of the table of the space used by each table and index, and by the commit log.
+pub(crate) const ST_STORAGE_ID: TableId = TableId(u32::MAX - 13);
+/// The static ID of the table of the messages the database sends to the reducers of other databases.
+pub(crate) const ST_OUTBOX_ID: TableId = TableId(u32::MAX - 14);
+/// The static ID of the table of the last message the database received from each database.
+pub(crate) const ST_INBOX_ID: TableId = TableId(u32::MAX - 15);
+
 pub(crate) const ST_TABLES_NAME: &str = "st_table";
 pub(crate) const ST_COLUMNS_NAME: &str = "st_columns";
 pub(crate) const ST_SEQUENCES_NAME: &str = "st_sequence";
 pub(crate) const ST_INDEXES_NAME: &str = "st_indexes";
+pub(crate) const ST_CONTENTION_NAME: &str = "st_contention";
+pub(crate) const ST_CONSTRAINTS_NAME: &str = "st_constraints";
+pub(crate) const ST_WEBHOOK_DEAD_LETTER_NAME: &str = "st_webhook_dead_letter";
+pub(crate) const ST_DISK_USAGE_NAME: &str = "st_disk_usage";
+pub(crate) const ST_ROLES_NAME: &str = "st_roles";
+pub(crate) const ST_ROLE_MEMBERS_NAME: &str = "st_role_members";
+pub(crate) const ST_TABLE_ACL_NAME: &str = "st_table_acl";
+pub(crate) const ST_REDUCER_COOLDOWN_NAME: &str = "st_reducer_cooldown";
+pub(crate) const ST_COLUMN_STATS_NAME: &str = "st_column_stats";
+pub(crate) const ST_TABLE_VERSION_NAME: &str = "st_table_version";
+pub(crate) const ST_BLOBS_NAME: &str = "st_blobs";
+pub(crate) const ST_LARGE_VALUES_NAME: &str = "st_large_values";
+pub(crate) const ST_COLUMN_COMPRESSION_NAME: &str = "st_column_compression";
+pub(crate) const ST_STORAGE_NAME: &str = "st_storage";
+pub(crate) const ST_OUTBOX_NAME: &str = "st_outbox";
+pub(crate) const ST_INBOX_NAME: &str = "st_inbox";
+
+/// The role held by the owner of a database, and only by it, which isn't listed in [ST_ROLES_NAME].
+pub(crate) const OWNER_ROLE: &str = "owner";
+
+/// The `constraint_type` of the constraints in [ST_CONSTRAINTS_NAME] enforced by a unique index.
+pub(crate) const CONSTRAINT_TYPE_UNIQUE: &str = "unique";
 
 pub(crate) const TABLE_ID_SEQUENCE_ID: SequenceId = SequenceId(0);
 pub(crate) const SEQUENCE_ID_SEQUENCE_ID: SequenceId = SequenceId(1);
@@ -79,6 +137,7 @@ pub enum StIndexFields {
     ColId = 2,
     IndexName = 3,
     IsUnique = 4,
+    IndexType = 5,
 }
 
 impl StIndexFields {
@@ -90,6 +149,7 @@ impl StIndexFields {
             StIndexFields::ColId => "col_id",
             StIndexFields::IndexName => "index_name",
             StIndexFields::IsUnique => "is_unique",
+            StIndexFields::IndexType => "index_type",
         }
     }
 }
@@ -116,8 +176,8 @@ impl StSequenceFields {
             StSequenceFields::SequenceName => "sequence_name",
             StSequenceFields::TableId => "table_id",
             StSequenceFields::ColId => "col_id",
-            StSequenceFields::Start => "increment",
-            StSequenceFields::Increment => "start",
+            StSequenceFields::Increment => "increment",
+            StSequenceFields::Start => "start",
             StSequenceFields::MinValue => "min_value",
             StSequenceFields::MaxValue => "max_value",
             StSequenceFields::Allocated => "allocated",
@@ -141,6 +201,7 @@ pub fn st_table_schema() -> TableSchema {
                 col_id: StTableFields::TableId as u32,
                 index_name: "table_id_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
             IndexSchema {
                 index_id: ST_TABLE_NAME_INDEX_ID,
@@ -148,6 +209,7 @@ pub fn st_table_schema() -> TableSchema {
                 col_id: StTableFields::TableName as u32,
                 index_name: "table_name_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
         ],
         columns: vec![
@@ -246,9 +308,9 @@ pub static ST_COLUMNS_ROW_TYPE: Lazy<ProductType> =
 
 /// System Table [ST_INDEXES]
 ///
-/// | index_id: u32 | table_id: u32 | col_id: u32 | index_name: String | is_unique: bool      |
-/// |---------------|---------------|-------------|--------------------|----------------------|
-/// | 1             | 1             | 1           | "ix_sample"        | 0                    |
+/// | index_id: u32 | table_id: u32 | col_id: u32 | index_name: String | is_unique: bool      | index_type: u8 |
+/// |---------------|---------------|-------------|--------------------|----------------------|----------------|
+/// | 1             | 1             | 1           | "ix_sample"        | 0                    | 0              |
 pub fn st_indexes_schema() -> TableSchema {
     TableSchema {
         table_id: ST_INDEXES_ID.0,
@@ -260,6 +322,7 @@ pub fn st_indexes_schema() -> TableSchema {
             col_id: 0,
             index_name: "index_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -297,6 +360,13 @@ pub fn st_indexes_schema() -> TableSchema {
                 col_type: AlgebraicType::Bool,
                 is_autoinc: false,
             },
+            ColumnSchema {
+                table_id: ST_INDEXES_ID.0,
+                col_id: 5,
+                col_name: "index_type".into(),
+                col_type: AlgebraicType::U8,
+                is_autoinc: false,
+            },
         ],
         table_type: StTableType::System,
         table_access: StAccess::Public,
@@ -322,6 +392,7 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
             col_id: 0,
             index_name: "sequences_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -376,7 +447,7 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
             ColumnSchema {
                 table_id: ST_SEQUENCES_ID.0,
                 col_id: 7,
-                col_name: "max_malue".into(),
+                col_name: "max_value".into(),
                 col_type: AlgebraicType::I128,
                 is_autoinc: false,
             },
@@ -396,190 +467,1166 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
 pub static ST_SEQUENCE_ROW_TYPE: Lazy<ProductType> =
     Lazy::new(|| ProductType::from_iter(st_sequences_schema().columns.iter().map(|c| c.col_type.clone())));
 
-pub(crate) fn table_name_is_system(table_name: &str) -> bool {
-    table_name.starts_with("st_")
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_CONTENTION_NAME].
+#[derive(Debug)]
+pub enum StContentionFields {
+    TableId = 0,
+    Commits = 1,
+    Conflicts = 2,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct StTableRow<Name: AsRef<str>> {
-    pub(crate) table_id: u32,
-    pub(crate) table_name: Name,
-    pub(crate) table_type: StTableType,
-    pub(crate) table_access: StAccess,
+impl StContentionFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StContentionFields::TableId => "table_id",
+            StContentionFields::Commits => "commits",
+            StContentionFields::Conflicts => "conflicts",
+        }
+    }
 }
 
-impl<'a> TryFrom<&'a ProductValue> for StTableRow<&'a str> {
-    type Error = DBError;
-    // TODO(cloutiertyler): Noa, can we just decorate `StTableRow` with Deserialize or something instead?
-    fn try_from(row: &'a ProductValue) -> Result<StTableRow<&'a str>, DBError> {
-        let table_id = row.field_as_u32(StTableFields::TableId as usize, None)?;
-        let table_name = row.field_as_str(StTableFields::TableName as usize, None)?;
-        let table_type = row
-            .field_as_str(StTableFields::TableType as usize, None)?
-            .try_into()
-            .map_err(|x: &str| TableError::DecodeField {
-                table: ST_TABLES_NAME.into(),
-                field: StTableFields::TableType.name().into(),
-                expect: format!("`{}` or `{}`", StTableType::System.as_str(), StTableType::User.as_str()),
-                found: x.to_string(),
-            })?;
+/// System Table [ST_CONTENTION_NAME]
+///
+/// Its rows live only in memory and are never written to the message log,
+/// so the counts start over when the database is restarted.
+///
+/// | table_id: u32 | commits: u64 | conflicts: u64 |
+/// |---------------|--------------|----------------|
+/// | 4             | 1200         | 3              |
+pub(crate) fn st_contention_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_CONTENTION_ID.0,
+        table_name: ST_CONTENTION_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_CONTENTION_ID.0,
+                col_id: StContentionFields::TableId as u32,
+                col_name: StContentionFields::TableId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONTENTION_ID.0,
+                col_id: StContentionFields::Commits as u32,
+                col_name: StContentionFields::Commits.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONTENTION_ID.0,
+                col_id: StContentionFields::Conflicts as u32,
+                col_name: StContentionFields::Conflicts.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
 
-        let table_access = row
-            .field_as_str(StTableFields::TablesAccess as usize, None)?
-            .try_into()
-            .map_err(|x: &str| TableError::DecodeField {
-                table: ST_TABLES_NAME.into(),
-                field: StTableFields::TablesAccess.name().into(),
-                expect: format!("`{}` or `{}`", StAccess::Public.as_str(), StAccess::Private.as_str()),
-                found: x.to_string(),
-            })?;
+pub static ST_CONTENTION_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_contention_schema().columns.iter().map(|c| c.col_type.clone())));
 
-        Ok(StTableRow {
-            table_id,
-            table_name,
-            table_type,
-            table_access,
-        })
-    }
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_CONSTRAINTS_NAME].
+#[derive(Debug)]
+pub enum StConstraintFields {
+    ConstraintName = 0,
+    ConstraintType = 1,
+    TableId = 2,
+    ColId = 3,
+    IndexId = 4,
 }
 
-impl StTableRow<&str> {
-    pub fn to_owned(&self) -> StTableRow<String> {
-        StTableRow {
-            table_id: self.table_id,
-            table_name: self.table_name.to_owned(),
-            table_type: self.table_type,
-            table_access: self.table_access,
+impl StConstraintFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StConstraintFields::ConstraintName => "constraint_name",
+            StConstraintFields::ConstraintType => "constraint_type",
+            StConstraintFields::TableId => "table_id",
+            StConstraintFields::ColId => "col_id",
+            StConstraintFields::IndexId => "index_id",
         }
     }
 }
 
-impl<Name: AsRef<str>> From<&StTableRow<Name>> for ProductValue {
-    fn from(x: &StTableRow<Name>) -> Self {
-        product![
-            AlgebraicValue::U32(x.table_id),
-            AlgebraicValue::String(x.table_name.as_ref().to_owned()),
-            AlgebraicValue::String(x.table_type.as_str().into()),
-            AlgebraicValue::String(x.table_access.as_str().into())
-        ]
+/// System Table [ST_CONSTRAINTS_NAME]
+///
+/// Its rows are derived from [ST_INDEXES_NAME] whenever it changes,
+/// one for each unique index, and are never written to the message log.
+///
+/// | constraint_name: String | constraint_type: String | table_id: u32 | col_id: u32 | index_id: u32 |
+/// |-------------------------|-------------------------|---------------|-------------|---------------|
+/// | "customer_id_idx"       | "unique"                | 4             | 0           | 5             |
+pub(crate) fn st_constraints_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_CONSTRAINTS_ID.0,
+        table_name: ST_CONSTRAINTS_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID.0,
+                col_id: StConstraintFields::ConstraintName as u32,
+                col_name: StConstraintFields::ConstraintName.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID.0,
+                col_id: StConstraintFields::ConstraintType as u32,
+                col_name: StConstraintFields::ConstraintType.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID.0,
+                col_id: StConstraintFields::TableId as u32,
+                col_name: StConstraintFields::TableId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID.0,
+                col_id: StConstraintFields::ColId as u32,
+                col_name: StConstraintFields::ColId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID.0,
+                col_id: StConstraintFields::IndexId as u32,
+                col_name: StConstraintFields::IndexId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct StColumnRow<Name: AsRef<str>> {
-    pub(crate) table_id: u32,
-    pub(crate) col_id: u32,
-    pub(crate) col_name: Name,
-    pub(crate) col_type: AlgebraicType,
-    pub(crate) is_autoinc: bool,
+pub static ST_CONSTRAINT_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_constraints_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_WEBHOOK_DEAD_LETTER_NAME].
+#[derive(Debug)]
+pub enum StWebhookDeadLetterFields {
+    WebhookName = 0,
+    Url = 1,
+    Timestamp = 2,
+    Attempts = 3,
+    Error = 4,
+    Payload = 5,
 }
 
-impl StColumnRow<&str> {
-    pub fn to_owned(&self) -> StColumnRow<String> {
-        StColumnRow {
-            table_id: self.table_id,
-            col_id: self.col_id,
-            col_name: self.col_name.to_owned(),
-            col_type: self.col_type.clone(),
-            is_autoinc: self.is_autoinc,
+impl StWebhookDeadLetterFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StWebhookDeadLetterFields::WebhookName => "webhook_name",
+            StWebhookDeadLetterFields::Url => "url",
+            StWebhookDeadLetterFields::Timestamp => "timestamp",
+            StWebhookDeadLetterFields::Attempts => "attempts",
+            StWebhookDeadLetterFields::Error => "error",
+            StWebhookDeadLetterFields::Payload => "payload",
         }
     }
 }
 
-impl<'a> TryFrom<&'a ProductValue> for StColumnRow<&'a str> {
-    type Error = DBError;
-    fn try_from(row: &'a ProductValue) -> Result<StColumnRow<&'a str>, DBError> {
-        let table_id = row.field_as_u32(StColumnFields::TableId as usize, None)?;
-        let col_id = row.field_as_u32(StColumnFields::ColId as usize, None)?;
+/// System Table [ST_WEBHOOK_DEAD_LETTER_NAME]
+///
+/// Like `st_contention`, its rows live only in memory and are never written to the message log,
+/// so it only lists the deliveries given up on since the database was started.
+/// It's private, as the payloads hold the rows of private tables.
+///
+/// | webhook_name: String | url: String                | timestamp: u64   | attempts: u32 | error: String | payload: String |
+/// |----------------------|----------------------------|------------------|---------------|---------------|-----------------|
+/// | "orders"             | "https://example.com/hook" | 1690000000000000 | 8             | "HTTP 503"    | "{...}"         |
+pub(crate) fn st_webhook_dead_letter_schema() -> TableSchema {
+    let column = |field: StWebhookDeadLetterFields, col_type| ColumnSchema {
+        table_id: ST_WEBHOOK_DEAD_LETTER_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_WEBHOOK_DEAD_LETTER_ID.0,
+        table_name: ST_WEBHOOK_DEAD_LETTER_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StWebhookDeadLetterFields::WebhookName, AlgebraicType::String),
+            column(StWebhookDeadLetterFields::Url, AlgebraicType::String),
+            column(StWebhookDeadLetterFields::Timestamp, AlgebraicType::U64),
+            column(StWebhookDeadLetterFields::Attempts, AlgebraicType::U32),
+            column(StWebhookDeadLetterFields::Error, AlgebraicType::String),
+            column(StWebhookDeadLetterFields::Payload, AlgebraicType::String),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
 
-        let bytes = row.field_as_bytes(StColumnFields::ColType as usize, None)?;
-        let col_type =
-            AlgebraicType::decode(&mut &bytes[..]).map_err(|e| TableError::InvalidSchema(table_id, e.into()))?;
+pub static ST_WEBHOOK_DEAD_LETTER_ROW_TYPE: Lazy<ProductType> = Lazy::new(|| {
+    ProductType::from_iter(
+        st_webhook_dead_letter_schema()
+            .columns
+            .iter()
+            .map(|c| c.col_type.clone()),
+    )
+});
 
-        let col_name = row.field_as_str(StColumnFields::ColName as usize, None)?;
-        let is_autoinc = row.field_as_bool(StColumnFields::ColIndexAttribute as usize, None)?;
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_DISK_USAGE_NAME].
+#[derive(Debug)]
+pub enum StDiskUsageFields {
+    Component = 0,
+    Bytes = 1,
+    Files = 2,
+    MeasuredAt = 3,
+}
 
-        Ok(StColumnRow {
-            table_id,
-            col_id,
-            col_name,
-            col_type,
-            is_autoinc,
-        })
+impl StDiskUsageFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StDiskUsageFields::Component => "component",
+            StDiskUsageFields::Bytes => "bytes",
+            StDiskUsageFields::Files => "files",
+            StDiskUsageFields::MeasuredAt => "measured_at",
+        }
     }
 }
 
-impl<Name: AsRef<str>> From<&StColumnRow<Name>> for ProductValue {
-    fn from(x: &StColumnRow<Name>) -> Self {
-        let mut bytes = Vec::new();
-        x.col_type.encode(&mut bytes);
-        product![
-            AlgebraicValue::U32(x.table_id),
-            AlgebraicValue::U32(x.col_id),
-            AlgebraicValue::Bytes(bytes),
-            AlgebraicValue::String(x.col_name.as_ref().to_owned()),
-            AlgebraicValue::Bool(x.is_autoinc),
-        ]
+/// System Table [ST_DISK_USAGE_NAME]
+///
+/// Like `st_contention`, its rows live only in memory and are never written to the message log.
+/// They're replaced each time the retention of the database is enforced.
+///
+/// | component: String | bytes: u64 | files: u32 | measured_at: u64 |
+/// |-------------------|------------|------------|------------------|
+/// | "module_log"      | 52428800   | 4          | 1690000000000000 |
+/// | "commit_log"      | 1073741824 | 2          | 1690000000000000 |
+pub(crate) fn st_disk_usage_schema() -> TableSchema {
+    let column = |field: StDiskUsageFields, col_type| ColumnSchema {
+        table_id: ST_DISK_USAGE_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_DISK_USAGE_ID.0,
+        table_name: ST_DISK_USAGE_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StDiskUsageFields::Component, AlgebraicType::String),
+            column(StDiskUsageFields::Bytes, AlgebraicType::U64),
+            column(StDiskUsageFields::Files, AlgebraicType::U32),
+            column(StDiskUsageFields::MeasuredAt, AlgebraicType::U64),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct StIndexRow<Name: AsRef<str>> {
-    pub(crate) index_id: u32,
-    pub(crate) table_id: u32,
-    pub(crate) col_id: u32,
-    pub(crate) index_name: Name,
-    pub(crate) is_unique: bool,
+pub static ST_DISK_USAGE_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_disk_usage_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_ROLES_NAME].
+#[derive(Debug)]
+pub enum StRolesFields {
+    Role = 0,
 }
 
-impl StIndexRow<&str> {
-    pub fn to_owned(&self) -> StIndexRow<String> {
-        StIndexRow {
-            index_id: self.index_id,
-            table_id: self.table_id,
-            col_id: self.col_id,
-            index_name: self.index_name.to_owned(),
-            is_unique: self.is_unique,
+impl StRolesFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StRolesFields::Role => "role",
         }
     }
 }
 
-impl<'a> TryFrom<&'a ProductValue> for StIndexRow<&'a str> {
-    type Error = DBError;
-    fn try_from(row: &'a ProductValue) -> Result<StIndexRow<&'a str>, DBError> {
-        let index_id = row.field_as_u32(StIndexFields::IndexId as usize, None)?;
-        let table_id = row.field_as_u32(StIndexFields::TableId as usize, None)?;
-        let col_id = row.field_as_u32(StIndexFields::ColId as usize, None)?;
-        let index_name = row.field_as_str(StIndexFields::IndexName as usize, None)?;
-        let is_unique = row.field_as_bool(StIndexFields::IsUnique as usize, None)?;
-        Ok(StIndexRow {
-            index_id,
-            table_id,
-            col_id,
-            index_name,
-            is_unique,
-        })
+/// System Table [ST_ROLES_NAME]
+///
+/// The roles of the database, which are created by granting them to an identity.
+/// The `owner` role isn't listed, as it's always held by the database's owner, and only by it.
+///
+/// | role: String |
+/// |--------------|
+/// | "moderator"  |
+pub(crate) fn st_roles_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_ROLES_ID.0,
+        table_name: ST_ROLES_NAME.into(),
+        indexes: vec![],
+        columns: vec![ColumnSchema {
+            table_id: ST_ROLES_ID.0,
+            col_id: StRolesFields::Role as u32,
+            col_name: StRolesFields::Role.name().into(),
+            col_type: AlgebraicType::String,
+            is_autoinc: false,
+        }],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
     }
 }
 
-impl<Name: AsRef<str>> From<&StIndexRow<Name>> for ProductValue {
-    fn from(x: &StIndexRow<Name>) -> Self {
-        product![
-            AlgebraicValue::U32(x.index_id),
-            AlgebraicValue::U32(x.table_id),
-            AlgebraicValue::U32(x.col_id),
-            AlgebraicValue::String(x.index_name.as_ref().to_string()),
-            AlgebraicValue::Bool(x.is_unique)
-        ]
+pub static ST_ROLES_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_roles_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_ROLE_MEMBERS_NAME].
+#[derive(Debug)]
+pub enum StRoleMembersFields {
+    Identity = 0,
+    Role = 1,
+}
+
+impl StRoleMembersFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StRoleMembersFields::Identity => "identity",
+            StRoleMembersFields::Role => "role",
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct StSequenceRow<Name: AsRef<str>> {
-    pub(crate) sequence_id: u32,
-    pub(crate) sequence_name: Name,
-    pub(crate) table_id: u32,
-    pub(crate) col_id: u32,
+/// System Table [ST_ROLE_MEMBERS_NAME]
+///
+/// Each row grants a role of [ST_ROLES_NAME] to an identity,
+/// with `GRANT <role> TO '<identity>'`, until `REVOKE <role> FROM '<identity>'`.
+///
+/// | identity: bytes | role: String |
+/// |-----------------|--------------|
+/// | 0x93dd...       | "moderator"  |
+pub(crate) fn st_role_members_schema() -> TableSchema {
+    let column = |field: StRoleMembersFields, col_type| ColumnSchema {
+        table_id: ST_ROLE_MEMBERS_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_ROLE_MEMBERS_ID.0,
+        table_name: ST_ROLE_MEMBERS_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StRoleMembersFields::Identity, AlgebraicType::bytes()),
+            column(StRoleMembersFields::Role, AlgebraicType::String),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_ROLE_MEMBERS_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_role_members_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_TABLE_ACL_NAME].
+#[derive(Debug)]
+pub enum StTableAclFields {
+    TableId = 0,
+    Role = 1,
+    Access = 2,
+}
+
+impl StTableAclFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StTableAclFields::TableId => "table_id",
+            StTableAclFields::Role => "role",
+            StTableAclFields::Access => "access",
+        }
+    }
+}
+
+/// System Table [ST_TABLE_ACL_NAME]
+///
+/// Each row sets the access a role of [ST_ROLES_NAME] has to a table,
+/// with `GRANT <privileges> ON <table> TO <role>` and `REVOKE <privileges> ON <table> FROM <role>`.
+///
+/// Once a table is listed here, the roles of its rows decide who, besides the owner, can read and write it,
+/// regardless of its [StAccess], so a role with `none` access is listed to keep the others out.
+///
+/// | table_id: u32 | role: String | access: String |
+/// |---------------|--------------|----------------|
+/// | 4             | "dashboard"  | "read"         |
+pub(crate) fn st_table_acl_schema() -> TableSchema {
+    let column = |field: StTableAclFields, col_type| ColumnSchema {
+        table_id: ST_TABLE_ACL_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_TABLE_ACL_ID.0,
+        table_name: ST_TABLE_ACL_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StTableAclFields::TableId, AlgebraicType::U32),
+            column(StTableAclFields::Role, AlgebraicType::String),
+            column(StTableAclFields::Access, AlgebraicType::String),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_TABLE_ACL_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_table_acl_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_REDUCER_COOLDOWN_NAME].
+#[derive(Debug)]
+pub enum StReducerCooldownFields {
+    Identity = 0,
+    ReducerName = 1,
+    LastCall = 2,
+}
+
+impl StReducerCooldownFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StReducerCooldownFields::Identity => "identity",
+            StReducerCooldownFields::ReducerName => "reducer_name",
+            StReducerCooldownFields::LastCall => "last_call",
+        }
+    }
+}
+
+/// System Table [ST_REDUCER_COOLDOWN_NAME]
+///
+/// Each row is the time of the last call an identity made to a reducer declared with a `cooldown`,
+/// in microseconds since the unix epoch, which the host admits the next call from.
+///
+/// | identity: bytes | reducer_name: String | last_call: u64   |
+/// |-----------------|----------------------|------------------|
+/// | 0x93dd...       | "send_message"       | 1694623532146810 |
+pub(crate) fn st_reducer_cooldown_schema() -> TableSchema {
+    let column = |field: StReducerCooldownFields, col_type| ColumnSchema {
+        table_id: ST_REDUCER_COOLDOWN_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_REDUCER_COOLDOWN_ID.0,
+        table_name: ST_REDUCER_COOLDOWN_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StReducerCooldownFields::Identity, AlgebraicType::bytes()),
+            column(StReducerCooldownFields::ReducerName, AlgebraicType::String),
+            column(StReducerCooldownFields::LastCall, AlgebraicType::U64),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_REDUCER_COOLDOWN_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_reducer_cooldown_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_COLUMN_STATS_NAME].
+#[derive(Debug)]
+pub enum StColumnStatsFields {
+    TableId = 0,
+    ColId = 1,
+    RowCount = 2,
+    DistinctCount = 3,
+    Min = 4,
+    Max = 5,
+}
+
+impl StColumnStatsFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StColumnStatsFields::TableId => "table_id",
+            StColumnStatsFields::ColId => "col_id",
+            StColumnStatsFields::RowCount => "row_count",
+            StColumnStatsFields::DistinctCount => "distinct_count",
+            StColumnStatsFields::Min => "min",
+            StColumnStatsFields::Max => "max",
+        }
+    }
+}
+
+/// System Table [ST_COLUMN_STATS_NAME]
+///
+/// Each row is the statistics on a column as of the last `ANALYZE` of its table.
+/// `min` and `max` are the BSATN encoding of the smallest and largest value,
+/// and are empty if the table has no rows.
+///
+/// It's private, as the smallest and largest values of a private table are not for everyone to see.
+///
+/// | table_id | col_id | row_count | distinct_count | min: bytes | max: bytes |
+/// |----------|--------|-----------|----------------|------------|------------|
+/// | 4        | 0      | 1000      | 998            | 0x01000000 | 0xe8030000 |
+pub(crate) fn st_column_stats_schema() -> TableSchema {
+    let column = |field: StColumnStatsFields, col_type| ColumnSchema {
+        table_id: ST_COLUMN_STATS_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_COLUMN_STATS_ID.0,
+        table_name: ST_COLUMN_STATS_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StColumnStatsFields::TableId, AlgebraicType::U32),
+            column(StColumnStatsFields::ColId, AlgebraicType::U32),
+            column(StColumnStatsFields::RowCount, AlgebraicType::U64),
+            column(StColumnStatsFields::DistinctCount, AlgebraicType::U64),
+            column(StColumnStatsFields::Min, AlgebraicType::bytes()),
+            column(StColumnStatsFields::Max, AlgebraicType::bytes()),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_COLUMN_STATS_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_column_stats_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_TABLE_VERSION_NAME].
+#[derive(Debug)]
+pub enum StTableVersionFields {
+    TableId = 0,
+    Version = 1,
+}
+
+impl StTableVersionFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StTableVersionFields::TableId => "table_id",
+            StTableVersionFields::Version => "version",
+        }
+    }
+}
+
+/// System Table [ST_TABLE_VERSION_NAME]
+///
+/// The `version` of a table is the number of committed transactions that changed its rows,
+/// so a client that last saw version 41 of a table need only fetch it again once it's past that.
+/// A table that has never been changed has no row, i.e., is at version 0.
+///
+/// Like `st_contention`, its rows live only in memory and are never written to the message log,
+/// but they're counted again as the message log is replayed, so a version never goes back.
+///
+/// | table_id: u32 | version: u64 |
+/// |---------------|--------------|
+/// | 4             | 42           |
+pub(crate) fn st_table_version_schema() -> TableSchema {
+    let column = |field: StTableVersionFields, col_type| ColumnSchema {
+        table_id: ST_TABLE_VERSION_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_TABLE_VERSION_ID.0,
+        table_name: ST_TABLE_VERSION_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StTableVersionFields::TableId, AlgebraicType::U32),
+            column(StTableVersionFields::Version, AlgebraicType::U64),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_TABLE_VERSION_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_table_version_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_BLOBS_NAME].
+#[derive(Debug)]
+pub enum StBlobsFields {
+    Key = 0,
+    Hash = 1,
+    Data = 2,
+}
+
+impl StBlobsFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StBlobsFields::Key => "key",
+            StBlobsFields::Hash => "hash",
+            StBlobsFields::Data => "data",
+        }
+    }
+}
+
+/// System Table [ST_BLOBS_NAME]
+///
+/// Each row is a small binary object stored under a `key`, e.g., an avatar or a map,
+/// with the hash of its `data`, which clients can compare to the one they have.
+///
+/// It's private, as the blobs are fetched over HTTP rather than queried.
+///
+/// | key: String         | hash: bytes | data: bytes |
+/// |---------------------|-------------|-------------|
+/// | "avatars/alice.png" | 0x4e1f...   | 0x89504e... |
+pub(crate) fn st_blobs_schema() -> TableSchema {
+    let column = |field: StBlobsFields, col_type| ColumnSchema {
+        table_id: ST_BLOBS_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_BLOBS_ID.0,
+        table_name: ST_BLOBS_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StBlobsFields::Key, AlgebraicType::String),
+            column(StBlobsFields::Hash, AlgebraicType::bytes()),
+            column(StBlobsFields::Data, AlgebraicType::bytes()),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_BLOBS_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_blobs_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_LARGE_VALUES_NAME].
+#[derive(Debug)]
+pub enum StLargeValuesFields {
+    Data = 0,
+}
+
+impl StLargeValuesFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StLargeValuesFields::Data => "data",
+        }
+    }
+}
+
+/// System Table [ST_LARGE_VALUES_NAME]
+///
+/// Each row is a byte string referenced by the `LargeBytesRef` columns of other rows,
+/// which hold the [`DataKey`](spacetimedb_lib::DataKey) of the row rather than the bytes,
+/// so that a value is stored, and written to the message log, only once.
+/// A row is deleted by the transaction that drops the last reference to it.
+///
+/// It's private, as the values are read through the rows that reference them.
+///
+/// | data: bytes |
+/// |-------------|
+/// | 0x89504e... |
+pub(crate) fn st_large_values_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_LARGE_VALUES_ID.0,
+        table_name: ST_LARGE_VALUES_NAME.into(),
+        indexes: vec![],
+        columns: vec![ColumnSchema {
+            table_id: ST_LARGE_VALUES_ID.0,
+            col_id: StLargeValuesFields::Data as u32,
+            col_name: StLargeValuesFields::Data.name().into(),
+            col_type: AlgebraicType::bytes(),
+            is_autoinc: false,
+        }],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_LARGE_VALUES_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_large_values_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_COLUMN_COMPRESSION_NAME].
+#[derive(Debug)]
+pub enum StColumnCompressionFields {
+    TableId = 0,
+    ColId = 1,
+    Codec = 2,
+    RawBytes = 3,
+    StoredBytes = 4,
+}
+
+impl StColumnCompressionFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StColumnCompressionFields::TableId => "table_id",
+            StColumnCompressionFields::ColId => "col_id",
+            StColumnCompressionFields::Codec => "codec",
+            StColumnCompressionFields::RawBytes => "raw_bytes",
+            StColumnCompressionFields::StoredBytes => "stored_bytes",
+        }
+    }
+}
+
+/// System Table [ST_COLUMN_COMPRESSION_NAME]
+///
+/// Each row is a column compressed in memory, with the size of its values in the rows in memory,
+/// as inserted and as stored, in bytes, the ratio of which is the column's compression ratio.
+/// The size as stored includes the dictionary of a `dictionary` column.
+///
+/// Like `st_contention`, its rows live only in memory and are never written to the message log.
+///
+/// | table_id: u32 | col_id: u32 | codec: String | raw_bytes: u64 | stored_bytes: u64 |
+/// |---------------|-------------|---------------|----------------|-------------------|
+/// | 4             | 1           | "dictionary"  | 1048576        | 65536             |
+pub(crate) fn st_column_compression_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_COLUMN_COMPRESSION_ID.0,
+        table_name: ST_COLUMN_COMPRESSION_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_COLUMN_COMPRESSION_ID.0,
+                col_id: StColumnCompressionFields::TableId as u32,
+                col_name: StColumnCompressionFields::TableId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_COLUMN_COMPRESSION_ID.0,
+                col_id: StColumnCompressionFields::ColId as u32,
+                col_name: StColumnCompressionFields::ColId.name().into(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_COLUMN_COMPRESSION_ID.0,
+                col_id: StColumnCompressionFields::Codec as u32,
+                col_name: StColumnCompressionFields::Codec.name().into(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_COLUMN_COMPRESSION_ID.0,
+                col_id: StColumnCompressionFields::RawBytes as u32,
+                col_name: StColumnCompressionFields::RawBytes.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_COLUMN_COMPRESSION_ID.0,
+                col_id: StColumnCompressionFields::StoredBytes as u32,
+                col_name: StColumnCompressionFields::StoredBytes.name().into(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_COLUMN_COMPRESSION_ROW_TYPE: Lazy<ProductType> = Lazy::new(|| {
+    ProductType::from_iter(
+        st_column_compression_schema()
+            .columns
+            .iter()
+            .map(|c| c.col_type.clone()),
+    )
+});
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_STORAGE_NAME].
+#[derive(Debug)]
+pub enum StStorageFields {
+    Kind = 0,
+    TableId = 1,
+    Name = 2,
+    Bytes = 3,
+    Entries = 4,
+    MeasuredAt = 5,
+}
+
+impl StStorageFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StStorageFields::Kind => "kind",
+            StStorageFields::TableId => "table_id",
+            StStorageFields::Name => "name",
+            StStorageFields::Bytes => "bytes",
+            StStorageFields::Entries => "entries",
+            StStorageFields::MeasuredAt => "measured_at",
+        }
+    }
+}
+
+/// System Table [ST_STORAGE_NAME]
+///
+/// The approximate space used by the database, a row per table, per index, and for the commit log:
+/// - a `table` row is the size of the table's rows in memory, and their number,
+///   not counting the rows evicted to disk;
+/// - an `index` row is the size of the index's keys in memory, and their number;
+/// - the `commit_log` row, whose `table_id` is 0, is the size of the commit log on disk,
+///   and its number of segments.
+///
+/// Like `st_contention`, its rows live only in memory and are never written to the message log.
+/// They're replaced each time the retention of the database is enforced.
+///
+/// | kind: String | table_id: u32 | name: String     | bytes: u64 | entries: u64 | measured_at: u64 |
+/// |--------------|---------------|------------------|------------|--------------|------------------|
+/// | "table"      | 4             | "Player"         | 1048576    | 10000        | 1690000000000000 |
+/// | "index"      | 4             | "Player_id_idx"  | 480000     | 10000        | 1690000000000000 |
+/// | "commit_log" | 0             | "commit_log"     | 1073741824 | 2            | 1690000000000000 |
+pub(crate) fn st_storage_schema() -> TableSchema {
+    let column = |field: StStorageFields, col_type| ColumnSchema {
+        table_id: ST_STORAGE_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_STORAGE_ID.0,
+        table_name: ST_STORAGE_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StStorageFields::Kind, AlgebraicType::String),
+            column(StStorageFields::TableId, AlgebraicType::U32),
+            column(StStorageFields::Name, AlgebraicType::String),
+            column(StStorageFields::Bytes, AlgebraicType::U64),
+            column(StStorageFields::Entries, AlgebraicType::U64),
+            column(StStorageFields::MeasuredAt, AlgebraicType::U64),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_STORAGE_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_storage_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_OUTBOX_NAME].
+#[derive(Debug)]
+pub enum StOutboxFields {
+    MsgId = 0,
+    Target = 1,
+    ReducerName = 2,
+    Args = 3,
+    SentAt = 4,
+    Delivered = 5,
+}
+
+impl StOutboxFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StOutboxFields::MsgId => "msg_id",
+            StOutboxFields::Target => "target",
+            StOutboxFields::ReducerName => "reducer_name",
+            StOutboxFields::Args => "args",
+            StOutboxFields::SentAt => "sent_at",
+            StOutboxFields::Delivered => "delivered",
+        }
+    }
+}
+
+/// System Table [ST_OUTBOX_NAME]
+///
+/// Each row is a call of a reducer of the database at the address `target`, with its BSATN encoded `args`,
+/// which a reducer of this database sent in its transaction, for the host to deliver once.
+/// `msg_id` increases with each message sent, and is what the target recognizes a message it has received by.
+///
+/// A message stays once delivered until a later one to the same target is,
+/// so that the next `msg_id` can be taken from the greatest.
+///
+/// | msg_id: u64 | target: bytes | reducer_name: String | args: bytes | sent_at: u64     | delivered: bool |
+/// |-------------|---------------|----------------------|-------------|------------------|-----------------|
+/// | 7           | 0x2e7c...     | "join_match"         | 0x0100...   | 1694623532146810 | false           |
+pub(crate) fn st_outbox_schema() -> TableSchema {
+    let column = |field: StOutboxFields, col_type| ColumnSchema {
+        table_id: ST_OUTBOX_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_OUTBOX_ID.0,
+        table_name: ST_OUTBOX_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StOutboxFields::MsgId, AlgebraicType::U64),
+            column(StOutboxFields::Target, AlgebraicType::bytes()),
+            column(StOutboxFields::ReducerName, AlgebraicType::String),
+            column(StOutboxFields::Args, AlgebraicType::bytes()),
+            column(StOutboxFields::SentAt, AlgebraicType::U64),
+            column(StOutboxFields::Delivered, AlgebraicType::Bool),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_OUTBOX_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_outbox_schema().columns.iter().map(|c| c.col_type.clone())));
+
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+/// The fields that define the internal table [ST_INBOX_NAME].
+#[derive(Debug)]
+pub enum StInboxFields {
+    Sender = 0,
+    LastMsgId = 1,
+}
+
+impl StInboxFields {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StInboxFields::Sender => "sender",
+            StInboxFields::LastMsgId => "last_msg_id",
+        }
+    }
+}
+
+/// System Table [ST_INBOX_NAME]
+///
+/// Each row is the `msg_id` of the last message the database at the address `sender` delivered to this one,
+/// recorded in the transaction of the reducer it called, so that a message delivered again is recognized.
+///
+/// | sender: bytes | last_msg_id: u64 |
+/// |---------------|------------------|
+/// | 0x93dd...     | 7                |
+pub(crate) fn st_inbox_schema() -> TableSchema {
+    let column = |field: StInboxFields, col_type| ColumnSchema {
+        table_id: ST_INBOX_ID.0,
+        col_id: field as u32,
+        col_name: field.name().into(),
+        col_type,
+        is_autoinc: false,
+    };
+    TableSchema {
+        table_id: ST_INBOX_ID.0,
+        table_name: ST_INBOX_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            column(StInboxFields::Sender, AlgebraicType::bytes()),
+            column(StInboxFields::LastMsgId, AlgebraicType::U64),
+        ],
+        table_type: StTableType::System,
+        table_access: StAccess::Private,
+    }
+}
+
+pub static ST_INBOX_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_inbox_schema().columns.iter().map(|c| c.col_type.clone())));
+
+pub(crate) fn table_name_is_system(table_name: &str) -> bool {
+    table_name.starts_with("st_")
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StTableRow<Name: AsRef<str>> {
+    pub(crate) table_id: u32,
+    pub(crate) table_name: Name,
+    pub(crate) table_type: StTableType,
+    pub(crate) table_access: StAccess,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StTableRow<&'a str> {
+    type Error = DBError;
+    // TODO(cloutiertyler): Noa, can we just decorate `StTableRow` with Deserialize or something instead?
+    fn try_from(row: &'a ProductValue) -> Result<StTableRow<&'a str>, DBError> {
+        let table_id = row.field_as_u32(StTableFields::TableId as usize, None)?;
+        let table_name = row.field_as_str(StTableFields::TableName as usize, None)?;
+        let table_type = row
+            .field_as_str(StTableFields::TableType as usize, None)?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_TABLES_NAME.into(),
+                field: StTableFields::TableType.name().into(),
+                expect: format!("`{}` or `{}`", StTableType::System.as_str(), StTableType::User.as_str()),
+                found: x.to_string(),
+            })?;
+
+        let table_access = row
+            .field_as_str(StTableFields::TablesAccess as usize, None)?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_TABLES_NAME.into(),
+                field: StTableFields::TablesAccess.name().into(),
+                expect: format!("`{}` or `{}`", StAccess::Public.as_str(), StAccess::Private.as_str()),
+                found: x.to_string(),
+            })?;
+
+        Ok(StTableRow {
+            table_id,
+            table_name,
+            table_type,
+            table_access,
+        })
+    }
+}
+
+impl StTableRow<&str> {
+    pub fn to_owned(&self) -> StTableRow<String> {
+        StTableRow {
+            table_id: self.table_id,
+            table_name: self.table_name.to_owned(),
+            table_type: self.table_type,
+            table_access: self.table_access,
+        }
+    }
+}
+
+impl<Name: AsRef<str>> From<&StTableRow<Name>> for ProductValue {
+    fn from(x: &StTableRow<Name>) -> Self {
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::String(x.table_name.as_ref().to_owned()),
+            AlgebraicValue::String(x.table_type.as_str().into()),
+            AlgebraicValue::String(x.table_access.as_str().into())
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StColumnRow<Name: AsRef<str>> {
+    pub(crate) table_id: u32,
+    pub(crate) col_id: u32,
+    pub(crate) col_name: Name,
+    pub(crate) col_type: AlgebraicType,
+    pub(crate) is_autoinc: bool,
+}
+
+impl StColumnRow<&str> {
+    pub fn to_owned(&self) -> StColumnRow<String> {
+        StColumnRow {
+            table_id: self.table_id,
+            col_id: self.col_id,
+            col_name: self.col_name.to_owned(),
+            col_type: self.col_type.clone(),
+            is_autoinc: self.is_autoinc,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StColumnRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StColumnRow<&'a str>, DBError> {
+        let table_id = row.field_as_u32(StColumnFields::TableId as usize, None)?;
+        let col_id = row.field_as_u32(StColumnFields::ColId as usize, None)?;
+
+        let bytes = row.field_as_bytes(StColumnFields::ColType as usize, None)?;
+        let col_type =
+            AlgebraicType::decode(&mut &bytes[..]).map_err(|e| TableError::InvalidSchema(table_id, e.into()))?;
+
+        let col_name = row.field_as_str(StColumnFields::ColName as usize, None)?;
+        let is_autoinc = row.field_as_bool(StColumnFields::ColIndexAttribute as usize, None)?;
+
+        Ok(StColumnRow {
+            table_id,
+            col_id,
+            col_name,
+            col_type,
+            is_autoinc,
+        })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StColumnRow<Name>> for ProductValue {
+    fn from(x: &StColumnRow<Name>) -> Self {
+        let mut bytes = Vec::new();
+        x.col_type.encode(&mut bytes);
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U32(x.col_id),
+            AlgebraicValue::Bytes(bytes),
+            AlgebraicValue::String(x.col_name.as_ref().to_owned()),
+            AlgebraicValue::Bool(x.is_autoinc),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StIndexRow<Name: AsRef<str>> {
+    pub(crate) index_id: u32,
+    pub(crate) table_id: u32,
+    pub(crate) col_id: u32,
+    pub(crate) index_name: Name,
+    pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
+}
+
+impl StIndexRow<&str> {
+    pub fn to_owned(&self) -> StIndexRow<String> {
+        StIndexRow {
+            index_id: self.index_id,
+            table_id: self.table_id,
+            col_id: self.col_id,
+            index_name: self.index_name.to_owned(),
+            is_unique: self.is_unique,
+            index_type: self.index_type,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StIndexRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StIndexRow<&'a str>, DBError> {
+        let index_id = row.field_as_u32(StIndexFields::IndexId as usize, None)?;
+        let table_id = row.field_as_u32(StIndexFields::TableId as usize, None)?;
+        let col_id = row.field_as_u32(StIndexFields::ColId as usize, None)?;
+        let index_name = row.field_as_str(StIndexFields::IndexName as usize, None)?;
+        let is_unique = row.field_as_bool(StIndexFields::IsUnique as usize, None)?;
+        let index_type = row.field_as_u8(StIndexFields::IndexType as usize, None)?;
+        let index_type = index_type.try_into().map_err(|()| TableError::DecodeField {
+            table: ST_INDEXES_NAME.into(),
+            field: StIndexFields::IndexType.name().into(),
+            expect: format!(
+                "`{}`, `{}`, `{}` or `{}`",
+                IndexType::BTree as u8,
+                IndexType::Hash as u8,
+                IndexType::FullText as u8,
+                IndexType::Spatial as u8
+            ),
+            found: index_type.to_string(),
+        })?;
+        Ok(StIndexRow {
+            index_id,
+            table_id,
+            col_id,
+            index_name,
+            is_unique,
+            index_type,
+        })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StIndexRow<Name>> for ProductValue {
+    fn from(x: &StIndexRow<Name>) -> Self {
+        product![
+            AlgebraicValue::U32(x.index_id),
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U32(x.col_id),
+            AlgebraicValue::String(x.index_name.as_ref().to_string()),
+            AlgebraicValue::Bool(x.is_unique),
+            AlgebraicValue::U8(x.index_type as u8)
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StSequenceRow<Name: AsRef<str>> {
+    pub(crate) sequence_id: u32,
+    pub(crate) sequence_name: Name,
+    pub(crate) table_id: u32,
+    pub(crate) col_id: u32,
     pub(crate) increment: i128,
     pub(crate) start: i128,
     pub(crate) min_value: i128,
@@ -660,3 +1707,553 @@ impl<'a> From<&StSequenceRow<&'a str>> for SequenceSchema {
         }
     }
 }
+
+/// The number of transactions that wrote to a table,
+/// and the number that were rolled back because of a conflict on it.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StContentionRow {
+    pub(crate) table_id: u32,
+    pub(crate) commits: u64,
+    pub(crate) conflicts: u64,
+}
+
+impl TryFrom<&ProductValue> for StContentionRow {
+    type Error = DBError;
+    fn try_from(row: &ProductValue) -> Result<StContentionRow, DBError> {
+        let table_id = row.field_as_u32(StContentionFields::TableId as usize, None)?;
+        let commits = row.field_as_u64(StContentionFields::Commits as usize, None)?;
+        let conflicts = row.field_as_u64(StContentionFields::Conflicts as usize, None)?;
+        Ok(StContentionRow {
+            table_id,
+            commits,
+            conflicts,
+        })
+    }
+}
+
+impl From<&StContentionRow> for ProductValue {
+    fn from(x: &StContentionRow) -> Self {
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U64(x.commits),
+            AlgebraicValue::U64(x.conflicts),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StConstraintRow<Name: AsRef<str>> {
+    pub(crate) constraint_name: Name,
+    pub(crate) constraint_type: Name,
+    pub(crate) table_id: u32,
+    pub(crate) col_id: u32,
+    pub(crate) index_id: u32,
+}
+
+impl StConstraintRow<&str> {
+    pub fn to_owned(&self) -> StConstraintRow<String> {
+        StConstraintRow {
+            constraint_name: self.constraint_name.to_owned(),
+            constraint_type: self.constraint_type.to_owned(),
+            table_id: self.table_id,
+            col_id: self.col_id,
+            index_id: self.index_id,
+        }
+    }
+
+    /// Returns the constraint enforced by `index`, if any.
+    pub fn for_index<'a>(index: &StIndexRow<&'a str>) -> Option<StConstraintRow<&'a str>> {
+        index.is_unique.then_some(StConstraintRow {
+            constraint_name: index.index_name,
+            constraint_type: CONSTRAINT_TYPE_UNIQUE,
+            table_id: index.table_id,
+            col_id: index.col_id,
+            index_id: index.index_id,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StConstraintRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StConstraintRow<&'a str>, DBError> {
+        let constraint_name = row.field_as_str(StConstraintFields::ConstraintName as usize, None)?;
+        let constraint_type = row.field_as_str(StConstraintFields::ConstraintType as usize, None)?;
+        let table_id = row.field_as_u32(StConstraintFields::TableId as usize, None)?;
+        let col_id = row.field_as_u32(StConstraintFields::ColId as usize, None)?;
+        let index_id = row.field_as_u32(StConstraintFields::IndexId as usize, None)?;
+        Ok(StConstraintRow {
+            constraint_name,
+            constraint_type,
+            table_id,
+            col_id,
+            index_id,
+        })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StConstraintRow<Name>> for ProductValue {
+    fn from(x: &StConstraintRow<Name>) -> Self {
+        product![
+            AlgebraicValue::String(x.constraint_name.as_ref().to_owned()),
+            AlgebraicValue::String(x.constraint_type.as_ref().to_owned()),
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U32(x.col_id),
+            AlgebraicValue::U32(x.index_id),
+        ]
+    }
+}
+
+/// A webhook delivery that failed on every attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StWebhookDeadLetterRow {
+    pub webhook_name: String,
+    pub url: String,
+    /// The time of the transaction the delivery was for, in microseconds since the unix epoch.
+    pub timestamp: u64,
+    pub attempts: u32,
+    /// Why the last attempt failed.
+    pub error: String,
+    /// The JSON body of the delivery.
+    pub payload: String,
+}
+
+impl From<&StWebhookDeadLetterRow> for ProductValue {
+    fn from(x: &StWebhookDeadLetterRow) -> Self {
+        product![
+            AlgebraicValue::String(x.webhook_name.clone()),
+            AlgebraicValue::String(x.url.clone()),
+            AlgebraicValue::U64(x.timestamp),
+            AlgebraicValue::U32(x.attempts),
+            AlgebraicValue::String(x.error.clone()),
+            AlgebraicValue::String(x.payload.clone()),
+        ]
+    }
+}
+
+/// The disk space used by a part of a database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StDiskUsageRow {
+    /// What the space is used by, e.g. `module_log` or `commit_log`.
+    pub component: String,
+    pub bytes: u64,
+    pub files: u32,
+    /// When the space was measured, in microseconds since the unix epoch.
+    pub measured_at: u64,
+}
+
+impl From<&StDiskUsageRow> for ProductValue {
+    fn from(x: &StDiskUsageRow) -> Self {
+        product![
+            AlgebraicValue::String(x.component.clone()),
+            AlgebraicValue::U64(x.bytes),
+            AlgebraicValue::U32(x.files),
+            AlgebraicValue::U64(x.measured_at),
+        ]
+    }
+}
+
+/// The approximate space used by a table, an index or the commit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StStorageRow {
+    /// What the space is used by, `table`, `index` or `commit_log`.
+    pub kind: String,
+    /// The table, or the table of the index, or 0 for the commit log.
+    pub table_id: u32,
+    /// The name of the table or index, or `commit_log`.
+    pub name: String,
+    pub bytes: u64,
+    /// The number of rows of a table, of keys of an index, or of segments of the commit log.
+    pub entries: u64,
+    /// When the space was measured, in microseconds since the unix epoch.
+    pub measured_at: u64,
+}
+
+impl TryFrom<&ProductValue> for StStorageRow {
+    type Error = DBError;
+    fn try_from(row: &ProductValue) -> Result<StStorageRow, DBError> {
+        Ok(StStorageRow {
+            kind: row.field_as_str(StStorageFields::Kind as usize, None)?.to_owned(),
+            table_id: row.field_as_u32(StStorageFields::TableId as usize, None)?,
+            name: row.field_as_str(StStorageFields::Name as usize, None)?.to_owned(),
+            bytes: row.field_as_u64(StStorageFields::Bytes as usize, None)?,
+            entries: row.field_as_u64(StStorageFields::Entries as usize, None)?,
+            measured_at: row.field_as_u64(StStorageFields::MeasuredAt as usize, None)?,
+        })
+    }
+}
+
+impl From<&StStorageRow> for ProductValue {
+    fn from(x: &StStorageRow) -> Self {
+        product![
+            AlgebraicValue::String(x.kind.clone()),
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::String(x.name.clone()),
+            AlgebraicValue::U64(x.bytes),
+            AlgebraicValue::U64(x.entries),
+            AlgebraicValue::U64(x.measured_at),
+        ]
+    }
+}
+
+/// A role of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StRoleRow<Name: AsRef<str>> {
+    pub role: Name,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StRoleRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StRoleRow<&'a str>, DBError> {
+        let role = row.field_as_str(StRolesFields::Role as usize, None)?;
+        Ok(StRoleRow { role })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StRoleRow<Name>> for ProductValue {
+    fn from(x: &StRoleRow<Name>) -> Self {
+        product![AlgebraicValue::String(x.role.as_ref().to_owned())]
+    }
+}
+
+/// A role granted to an identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StRoleMemberRow<Name: AsRef<str>> {
+    pub identity: Identity,
+    pub role: Name,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StRoleMemberRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StRoleMemberRow<&'a str>, DBError> {
+        let identity = row.field_as_bytes(StRoleMembersFields::Identity as usize, None)?;
+        let identity = Identity::from_slice(identity);
+        let role = row.field_as_str(StRoleMembersFields::Role as usize, None)?;
+        Ok(StRoleMemberRow { identity, role })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StRoleMemberRow<Name>> for ProductValue {
+    fn from(x: &StRoleMemberRow<Name>) -> Self {
+        product![
+            AlgebraicValue::Bytes(x.identity.as_bytes().to_vec()),
+            AlgebraicValue::String(x.role.as_ref().to_owned()),
+        ]
+    }
+}
+
+/// The access a role has to a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StTableAclRow<Name: AsRef<str>> {
+    pub table_id: u32,
+    pub role: Name,
+    pub access: StRoleAccess,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StTableAclRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StTableAclRow<&'a str>, DBError> {
+        let table_id = row.field_as_u32(StTableAclFields::TableId as usize, None)?;
+        let role = row.field_as_str(StTableAclFields::Role as usize, None)?;
+        let access = row
+            .field_as_str(StTableAclFields::Access as usize, None)?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_TABLE_ACL_NAME.into(),
+                field: StTableAclFields::Access.name().into(),
+                expect: format!(
+                    "`{}`, `{}` or `{}`",
+                    StRoleAccess::None.as_str(),
+                    StRoleAccess::Read.as_str(),
+                    StRoleAccess::Write.as_str()
+                ),
+                found: x.to_string(),
+            })?;
+        Ok(StTableAclRow { table_id, role, access })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StTableAclRow<Name>> for ProductValue {
+    fn from(x: &StTableAclRow<Name>) -> Self {
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::String(x.role.as_ref().to_owned()),
+            AlgebraicValue::String(x.access.as_str().to_owned()),
+        ]
+    }
+}
+
+/// The last call an identity made to a reducer with a cooldown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StReducerCooldownRow<Name: AsRef<str>> {
+    pub identity: Identity,
+    pub reducer_name: Name,
+    /// When the call was made, in microseconds since the unix epoch.
+    pub last_call: u64,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StReducerCooldownRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StReducerCooldownRow<&'a str>, DBError> {
+        let identity = row.field_as_bytes(StReducerCooldownFields::Identity as usize, None)?;
+        let identity = Identity::from_slice(identity);
+        let reducer_name = row.field_as_str(StReducerCooldownFields::ReducerName as usize, None)?;
+        let last_call = row.field_as_u64(StReducerCooldownFields::LastCall as usize, None)?;
+        Ok(StReducerCooldownRow {
+            identity,
+            reducer_name,
+            last_call,
+        })
+    }
+}
+
+impl<Name: AsRef<str>> From<&StReducerCooldownRow<Name>> for ProductValue {
+    fn from(x: &StReducerCooldownRow<Name>) -> Self {
+        product![
+            AlgebraicValue::Bytes(x.identity.as_bytes().to_vec()),
+            AlgebraicValue::String(x.reducer_name.as_ref().to_owned()),
+            AlgebraicValue::U64(x.last_call),
+        ]
+    }
+}
+
+/// The statistics on a column, with its smallest and largest values still encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StColumnStatsRow<Bytes: AsRef<[u8]>> {
+    pub table_id: u32,
+    pub col_id: u32,
+    pub row_count: u64,
+    /// An estimate of the number of distinct values.
+    pub distinct_count: u64,
+    /// The BSATN encoding of the smallest value, empty if there are no rows.
+    pub min: Bytes,
+    /// The BSATN encoding of the largest value, empty if there are no rows.
+    pub max: Bytes,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StColumnStatsRow<&'a [u8]> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StColumnStatsRow<&'a [u8]>, DBError> {
+        let table_id = row.field_as_u32(StColumnStatsFields::TableId as usize, None)?;
+        let col_id = row.field_as_u32(StColumnStatsFields::ColId as usize, None)?;
+        let row_count = row.field_as_u64(StColumnStatsFields::RowCount as usize, None)?;
+        let distinct_count = row.field_as_u64(StColumnStatsFields::DistinctCount as usize, None)?;
+        let min = row.field_as_bytes(StColumnStatsFields::Min as usize, None)?;
+        let max = row.field_as_bytes(StColumnStatsFields::Max as usize, None)?;
+        Ok(StColumnStatsRow {
+            table_id,
+            col_id,
+            row_count,
+            distinct_count,
+            min,
+            max,
+        })
+    }
+}
+
+impl<Bytes: AsRef<[u8]>> From<&StColumnStatsRow<Bytes>> for ProductValue {
+    fn from(x: &StColumnStatsRow<Bytes>) -> Self {
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U32(x.col_id),
+            AlgebraicValue::U64(x.row_count),
+            AlgebraicValue::U64(x.distinct_count),
+            AlgebraicValue::Bytes(x.min.as_ref().to_vec()),
+            AlgebraicValue::Bytes(x.max.as_ref().to_vec()),
+        ]
+    }
+}
+
+/// The number of committed transactions that changed the rows of a table.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StTableVersionRow {
+    pub(crate) table_id: u32,
+    pub(crate) version: u64,
+}
+
+impl TryFrom<&ProductValue> for StTableVersionRow {
+    type Error = DBError;
+    fn try_from(row: &ProductValue) -> Result<StTableVersionRow, DBError> {
+        let table_id = row.field_as_u32(StTableVersionFields::TableId as usize, None)?;
+        let version = row.field_as_u64(StTableVersionFields::Version as usize, None)?;
+        Ok(StTableVersionRow { table_id, version })
+    }
+}
+
+impl From<&StTableVersionRow> for ProductValue {
+    fn from(x: &StTableVersionRow) -> Self {
+        product![AlgebraicValue::U32(x.table_id), AlgebraicValue::U64(x.version)]
+    }
+}
+
+/// A blob, stored under its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StBlobRow<Key: AsRef<str>, Bytes: AsRef<[u8]>> {
+    pub key: Key,
+    pub hash: Hash,
+    pub data: Bytes,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StBlobRow<&'a str, &'a [u8]> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StBlobRow<&'a str, &'a [u8]>, DBError> {
+        let key = row.field_as_str(StBlobsFields::Key as usize, None)?;
+        let hash = row.field_as_bytes(StBlobsFields::Hash as usize, None)?;
+        let hash = Hash::from_slice(hash);
+        let data = row.field_as_bytes(StBlobsFields::Data as usize, None)?;
+        Ok(StBlobRow { key, hash, data })
+    }
+}
+
+impl<Key: AsRef<str>, Bytes: AsRef<[u8]>> From<&StBlobRow<Key, Bytes>> for ProductValue {
+    fn from(x: &StBlobRow<Key, Bytes>) -> Self {
+        product![
+            AlgebraicValue::String(x.key.as_ref().to_owned()),
+            AlgebraicValue::Bytes(x.hash.to_vec()),
+            AlgebraicValue::Bytes(x.data.as_ref().to_vec()),
+        ]
+    }
+}
+
+/// A byte string stored out of the rows that reference it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StLargeValueRow<Bytes: AsRef<[u8]>> {
+    pub data: Bytes,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StLargeValueRow<&'a [u8]> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StLargeValueRow<&'a [u8]>, DBError> {
+        let data = row.field_as_bytes(StLargeValuesFields::Data as usize, None)?;
+        Ok(StLargeValueRow { data })
+    }
+}
+
+impl<Bytes: AsRef<[u8]>> From<&StLargeValueRow<Bytes>> for ProductValue {
+    fn from(x: &StLargeValueRow<Bytes>) -> Self {
+        product![AlgebraicValue::Bytes(x.data.as_ref().to_vec())]
+    }
+}
+
+/// The compression ratio of a column compressed in memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StColumnCompressionRow {
+    pub(crate) table_id: u32,
+    pub(crate) col_id: u32,
+    pub(crate) codec: Compression,
+    pub(crate) raw_bytes: u64,
+    pub(crate) stored_bytes: u64,
+}
+
+impl TryFrom<&ProductValue> for StColumnCompressionRow {
+    type Error = DBError;
+    fn try_from(row: &ProductValue) -> Result<StColumnCompressionRow, DBError> {
+        let table_id = row.field_as_u32(StColumnCompressionFields::TableId as usize, None)?;
+        let col_id = row.field_as_u32(StColumnCompressionFields::ColId as usize, None)?;
+        let codec = row
+            .field_as_str(StColumnCompressionFields::Codec as usize, None)?
+            .try_into()
+            .map_err(|x: &str| TableError::DecodeField {
+                table: ST_COLUMN_COMPRESSION_NAME.into(),
+                field: StColumnCompressionFields::Codec.name().into(),
+                expect: format!(
+                    "`{}` or `{}`",
+                    Compression::Dictionary.as_str(),
+                    Compression::Zstd.as_str()
+                ),
+                found: x.to_string(),
+            })?;
+        let raw_bytes = row.field_as_u64(StColumnCompressionFields::RawBytes as usize, None)?;
+        let stored_bytes = row.field_as_u64(StColumnCompressionFields::StoredBytes as usize, None)?;
+        Ok(StColumnCompressionRow {
+            table_id,
+            col_id,
+            codec,
+            raw_bytes,
+            stored_bytes,
+        })
+    }
+}
+
+impl From<&StColumnCompressionRow> for ProductValue {
+    fn from(x: &StColumnCompressionRow) -> Self {
+        product![
+            AlgebraicValue::U32(x.table_id),
+            AlgebraicValue::U32(x.col_id),
+            AlgebraicValue::String(x.codec.as_str().to_owned()),
+            AlgebraicValue::U64(x.raw_bytes),
+            AlgebraicValue::U64(x.stored_bytes),
+        ]
+    }
+}
+
+/// A message to a reducer of another database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StOutboxRow<Name: AsRef<str>, Bytes: AsRef<[u8]>> {
+    pub msg_id: u64,
+    pub target: Address,
+    pub reducer_name: Name,
+    pub args: Bytes,
+    /// When the message was sent, in microseconds since the unix epoch.
+    pub sent_at: u64,
+    pub delivered: bool,
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StOutboxRow<&'a str, &'a [u8]> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StOutboxRow<&'a str, &'a [u8]>, DBError> {
+        let msg_id = row.field_as_u64(StOutboxFields::MsgId as usize, None)?;
+        let target = row.field_as_bytes(StOutboxFields::Target as usize, None)?;
+        let target = Address::from_slice(target);
+        let reducer_name = row.field_as_str(StOutboxFields::ReducerName as usize, None)?;
+        let args = row.field_as_bytes(StOutboxFields::Args as usize, None)?;
+        let sent_at = row.field_as_u64(StOutboxFields::SentAt as usize, None)?;
+        let delivered = row.field_as_bool(StOutboxFields::Delivered as usize, None)?;
+        Ok(StOutboxRow {
+            msg_id,
+            target,
+            reducer_name,
+            args,
+            sent_at,
+            delivered,
+        })
+    }
+}
+
+impl<Name: AsRef<str>, Bytes: AsRef<[u8]>> From<&StOutboxRow<Name, Bytes>> for ProductValue {
+    fn from(x: &StOutboxRow<Name, Bytes>) -> Self {
+        product![
+            AlgebraicValue::U64(x.msg_id),
+            AlgebraicValue::Bytes(x.target.as_slice().to_vec()),
+            AlgebraicValue::String(x.reducer_name.as_ref().to_owned()),
+            AlgebraicValue::Bytes(x.args.as_ref().to_vec()),
+            AlgebraicValue::U64(x.sent_at),
+            AlgebraicValue::Bool(x.delivered),
+        ]
+    }
+}
+
+/// The last message a database received from another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StInboxRow {
+    pub sender: Address,
+    pub last_msg_id: u64,
+}
+
+impl TryFrom<&ProductValue> for StInboxRow {
+    type Error = DBError;
+    fn try_from(row: &ProductValue) -> Result<StInboxRow, DBError> {
+        let sender = row.field_as_bytes(StInboxFields::Sender as usize, None)?;
+        let sender = Address::from_slice(sender);
+        let last_msg_id = row.field_as_u64(StInboxFields::LastMsgId as usize, None)?;
+        Ok(StInboxRow { sender, last_msg_id })
+    }
+}
+
+impl From<&StInboxRow> for ProductValue {
+    fn from(x: &StInboxRow) -> Self {
+        product![
+            AlgebraicValue::Bytes(x.sender.as_slice().to_vec()),
+            AlgebraicValue::U64(x.last_msg_id),
+        ]
+    }
+}
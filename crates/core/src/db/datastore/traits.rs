@@ -2,7 +2,7 @@ use crate::db::relational_db::ST_TABLES_ID;
 use core::fmt;
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::relation::{DbTable, FieldName, FieldOnly, Header, TableField};
-use spacetimedb_lib::DataKey;
+use spacetimedb_lib::{DataKey, IndexType};
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductTypeElement, ProductValue};
 use spacetimedb_vm::expr::SourceExpr;
 use std::{ops::RangeBounds, sync::Arc};
@@ -18,6 +18,9 @@ pub struct ColId(pub(crate) u32);
 pub struct IndexId(pub(crate) u32);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SequenceId(pub(crate) u32);
+/// The `id` of a savepoint taken in a mutable transaction.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct SavepointId(pub(crate) u32);
 
 impl TableId {
     pub fn from_u32_for_testing(id: u32) -> Self {
@@ -31,6 +34,12 @@ impl fmt::Display for SequenceId {
     }
 }
 
+impl fmt::Display for SavepointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SequenceSchema {
     pub(crate) sequence_id: u32,
@@ -64,6 +73,7 @@ pub struct IndexSchema {
     pub(crate) col_id: u32,
     pub(crate) index_name: String,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 /// This type is just the [IndexSchema] without the autoinc fields
@@ -73,6 +83,7 @@ pub struct IndexDef {
     pub(crate) col_id: u32,
     pub(crate) name: String,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 impl IndexDef {
@@ -82,6 +93,23 @@ impl IndexDef {
             name,
             is_unique,
             table_id,
+            index_type: IndexType::BTree,
+        }
+    }
+
+    /// A non-unique [IndexType::Spatial] index on the point column `col_id`.
+    pub fn spatial(name: String, table_id: u32, col_id: u32) -> Self {
+        Self {
+            index_type: IndexType::Spatial,
+            ..Self::new(name, table_id, col_id, false)
+        }
+    }
+
+    /// A non-unique [IndexType::FullText] index on the string column `col_id`.
+    pub fn fulltext(name: String, table_id: u32, col_id: u32) -> Self {
+        Self {
+            index_type: IndexType::FullText,
+            ..Self::new(name, table_id, col_id, false)
         }
     }
 }
@@ -93,6 +121,7 @@ impl From<IndexSchema> for IndexDef {
             col_id: value.col_id,
             name: value.index_name,
             is_unique: value.is_unique,
+            index_type: value.index_type,
         }
     }
 }
@@ -314,6 +343,9 @@ pub struct TxRecord {
 /// A record of all the operations within a transaction.
 pub struct TxData {
     pub(crate) records: Vec<TxRecord>,
+    /// The number of transactions in the commit log up to and including this one,
+    /// or `None` if it wrote nothing, and so isn't in the log.
+    pub(crate) tx_offset: Option<u64>,
 }
 
 pub trait Data: Into<ProductValue> {
@@ -342,6 +374,14 @@ pub trait MutTx {
     fn begin_mut_tx(&self) -> Self::MutTxId;
     fn rollback_mut_tx(&self, tx: Self::MutTxId);
     fn commit_mut_tx(&self, tx: Self::MutTxId) -> Result<Option<TxData>>;
+
+    /// Takes a savepoint of the changes made so far in `tx`.
+    fn savepoint_mut_tx(&self, tx: &mut Self::MutTxId) -> SavepointId;
+    /// Undoes the changes made in `tx` since `savepoint` was taken,
+    /// releasing it and all the savepoints taken after it.
+    fn rollback_to_savepoint_mut_tx(&self, tx: &mut Self::MutTxId, savepoint: SavepointId) -> Result<()>;
+    /// Releases `savepoint` and all the savepoints taken after it, keeping the changes made since.
+    fn release_savepoint_mut_tx(&self, tx: &mut Self::MutTxId, savepoint: SavepointId) -> Result<()>;
 }
 
 pub trait TxDatastore: DataRow + Tx {
@@ -357,6 +397,14 @@ pub trait TxDatastore: DataRow + Tx {
     where
         Self: 'a;
 
+    type IterByColMatch<'a>: Iterator<Item = Self::DataRef>
+    where
+        Self: 'a;
+
+    type IterByColBox<'a>: Iterator<Item = Self::DataRef>
+    where
+        Self: 'a;
+
     fn iter_tx<'a>(&'a self, tx: &'a Self::TxId, table_id: TableId) -> Result<Self::Iter<'a>>;
 
     fn iter_by_col_range_tx<'a, R: RangeBounds<AlgebraicValue>>(
@@ -375,6 +423,23 @@ pub trait TxDatastore: DataRow + Tx {
         value: &'a AlgebraicValue,
     ) -> Result<Self::IterByColEq<'a>>;
 
+    fn iter_by_col_match_tx<'a>(
+        &'a self,
+        tx: &'a Self::TxId,
+        table_id: TableId,
+        col_id: ColId,
+        query: &'a str,
+    ) -> Result<Self::IterByColMatch<'a>>;
+
+    fn iter_by_col_box_tx<'a>(
+        &'a self,
+        tx: &'a Self::TxId,
+        table_id: TableId,
+        col_id: ColId,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> Result<Self::IterByColBox<'a>>;
+
     fn get_tx<'a>(
         &'a self,
         tx: &'a Self::TxId,
@@ -390,9 +455,13 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
     fn schema_for_table_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<TableSchema>;
     fn drop_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId) -> Result<()>;
     fn rename_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId, new_name: &str) -> Result<()>;
+    fn add_columns_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId, columns: Vec<ColumnDef>) -> Result<()>;
     fn table_id_exists(&self, tx: &Self::MutTxId, table_id: &TableId) -> bool;
     fn table_id_from_name_mut_tx(&self, tx: &Self::MutTxId, table_name: &str) -> Result<Option<TableId>>;
     fn table_name_from_id_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<Option<String>>;
+    /// Returns the number of committed transactions that changed the rows of `table_id`,
+    /// not counting `tx` itself.
+    fn table_version_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<u64>;
     fn get_all_tables_mut_tx(&self, tx: &Self::MutTxId) -> super::Result<Vec<TableSchema>> {
         let mut tables = Vec::new();
         let table_rows = self.iter_mut_tx(tx, TableId(ST_TABLES_ID))?.collect::<Vec<_>>();
@@ -441,6 +510,21 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
         col_id: ColId,
         value: &'a AlgebraicValue,
     ) -> Result<Self::IterByColEq<'a>>;
+    fn iter_by_col_match_mut_tx<'a>(
+        &'a self,
+        tx: &'a Self::MutTxId,
+        table_id: TableId,
+        col_id: ColId,
+        query: &'a str,
+    ) -> Result<Self::IterByColMatch<'a>>;
+    fn iter_by_col_box_mut_tx<'a>(
+        &'a self,
+        tx: &'a Self::MutTxId,
+        table_id: TableId,
+        col_id: ColId,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> Result<Self::IterByColBox<'a>>;
     fn get_mut_tx<'a>(
         &'a self,
         tx: &'a Self::MutTxId,
@@ -187,6 +187,10 @@ impl MessageLog {
         self.total_size
     }
 
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
     pub fn get_root(&self) -> PathBuf {
         self.root.clone()
     }
@@ -1,3 +1,4 @@
+pub mod column_stats;
 pub mod commit_log;
 pub mod cursor;
 pub mod datastore;
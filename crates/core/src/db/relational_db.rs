@@ -1,30 +1,60 @@
+use super::column_stats::{ColumnStats, ColumnStatsBuilder};
 use super::commit_log::CommitLog;
-use super::datastore::locking_tx_datastore::{Data, DataRef, Iter, IterByColEq, IterByColRange, MutTxId, RowId};
+use super::datastore::locking_tx_datastore::{
+    Data, DataRef, Iter, IterByColBox, IterByColEq, IterByColMatch, IterByColRange, MutTxId, RowId,
+};
 use super::datastore::traits::{
-    ColId, DataRow, IndexDef, IndexId, MutTx, MutTxDatastore, SequenceDef, SequenceId, TableDef, TableId, TableSchema,
-    TxData,
+    ColId, ColumnDef, DataRow, IndexDef, IndexId, MutTx, MutTxDatastore, SavepointId, SequenceDef, SequenceId,
+    TableDef, TableId, TableSchema, Tx, TxData,
 };
 use super::message_log::MessageLog;
 use super::ostorage::memory_object_db::MemoryObjectDB;
 use super::relational_operators::Relation;
+use crate::address::Address;
 use crate::db::db_metrics::{RDB_DELETE_BY_REL_TIME, RDB_DROP_TABLE_TIME, RDB_INSERT_TIME, RDB_ITER_TIME};
 use crate::db::messages::commit::Commit;
 use crate::db::ostorage::hashmap_object_db::HashMapObjectDB;
 use crate::db::ostorage::ObjectDB;
 use crate::error::{DBError, DatabaseError, TableError};
-use crate::hash::Hash;
+use crate::hash::{hash_bytes, Hash};
+use crate::identity::Identity;
 use crate::util::prometheus_handle::HistogramVecHandle;
 use fs2::FileExt;
+use once_cell::sync::Lazy;
 use prometheus::HistogramVec;
-use spacetimedb_lib::ColumnIndexAttribute;
-use spacetimedb_lib::{data_key::ToDataKey, PrimaryKey};
+use spacetimedb_lib::auth::StRoleAccess;
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::{data_key::ToDataKey, DataKey, PrimaryKey};
+use spacetimedb_lib::{AccessHint, ColumnIndexAttribute, Compression, MAX_BLOB_SIZE};
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File};
 use std::ops::RangeBounds;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::datastore::locking_tx_datastore::{Locking, MemoryBudget, Quota, VacuumReport};
+use super::datastore::system_tables::{
+    StBlobRow, StColumnStatsRow, StDiskUsageRow, StInboxRow, StOutboxRow, StReducerCooldownRow, StRoleMemberRow,
+    StRoleRow, StStorageRow, StTableAclRow, StWebhookDeadLetterRow, ST_BLOBS_ID, ST_COLUMN_STATS_ID, ST_INBOX_ID,
+    ST_OUTBOX_ID, ST_REDUCER_COOLDOWN_ID, ST_ROLES_ID, ST_ROLE_MEMBERS_ID, ST_TABLE_ACL_ID,
+};
 
-use super::datastore::locking_tx_datastore::Locking;
+/// The most bytes of committed rows each database keeps in memory, if limited,
+/// from the `SPACETIMEDB_MEMORY_BUDGET` environment variable.
+///
+/// The rows past the budget are evicted to a `spill` file in the directory of the database.
+static MEMORY_BUDGET: Lazy<Option<usize>> = Lazy::new(|| {
+    let budget = std::env::var("SPACETIMEDB_MEMORY_BUDGET").ok()?;
+    match budget.parse() {
+        Ok(max_bytes) => Some(max_bytes),
+        Err(e) => {
+            log::warn!("Ignoring invalid SPACETIMEDB_MEMORY_BUDGET {budget:?}: {e}");
+            None
+        }
+    }
+});
 
 /// Starts histogram prometheus measurements for `table_id`.
 fn measure(hist: &'static HistogramVec, table_id: u32) {
@@ -48,7 +78,11 @@ pub struct RelationalDB {
     // TODO(cloutiertyler): This should not be public
     pub(crate) inner: Locking,
     commit_log: CommitLog,
-    _lock: Arc<File>,
+    /// Held while committing a transaction and appending it to the commit log,
+    /// so that concurrent transactions are appended in the order they were committed.
+    commit_lock: Arc<Mutex<()>>,
+    /// `None` for a database rebuilt by [`RelationalDB::as_of`], which has no files of its own.
+    _lock: Option<Arc<File>>,
 }
 
 impl DataRow for RelationalDB {
@@ -87,6 +121,10 @@ impl RelationalDB {
             .map_err(|err| DatabaseError::DatabasedOpened(root.to_path_buf(), err.into()))?;
 
         let datastore = Locking::bootstrap()?;
+        // An in-memory database, which has no message log, doesn't write to disk at all.
+        if let (Some(max_bytes), Some(_)) = (*MEMORY_BUDGET, &message_log) {
+            datastore.set_memory_budget(MemoryBudget::new(max_bytes, root.join("spill"))?)?;
+        }
         let unwritten_commit = {
             let mut transaction_offset = 0;
             let mut last_commit_offset = None;
@@ -143,13 +181,35 @@ impl RelationalDB {
         let db = Self {
             inner: datastore,
             commit_log,
-            _lock: Arc::new(lock),
+            commit_lock: Arc::default(),
+            _lock: Some(Arc::new(lock)),
         };
 
         log::trace!("DATABASE: OPENED");
         Ok(db)
     }
 
+    /// Rebuild the state of the database right after its first `tx_offset` transactions
+    /// into a new in-memory database, by replaying its commit log.
+    ///
+    /// The returned database isn't backed by any files, so changes made to it are lost.
+    pub fn as_of(&self, tx_offset: u64) -> Result<Self, DBError> {
+        let inner = self.commit_log.replay_until(tx_offset)?;
+        let odb: Box<dyn ObjectDB + Send> = Box::<MemoryObjectDB>::default();
+        let unwritten_commit = Commit {
+            parent_commit_hash: None,
+            commit_offset: 0,
+            min_tx_offset: tx_offset,
+            transactions: Vec::new(),
+        };
+        Ok(Self {
+            inner,
+            commit_log: CommitLog::new(None, Arc::new(Mutex::new(odb)), unwritten_commit),
+            commit_lock: Arc::default(),
+            _lock: None,
+        })
+    }
+
     // pub fn reset_hard(&mut self, message_log: Arc<Mutex<MessageLog>>) -> Result<(), DBError> {
     //     log::warn!("DATABASE: RESET");
 
@@ -205,6 +265,537 @@ impl RelationalDB {
         Ok(AlgebraicValue::decode(&schema, &mut &bytes[..])?)
     }
 
+    /// Sets the access hints the module declared for its tables, by table name.
+    pub fn set_access_hints(&self, access_hints: HashMap<String, AccessHint>) {
+        self.inner.set_access_hints(access_hints)
+    }
+
+    /// Sets the columns the module declared as compressed in memory, with their codecs, by table name.
+    pub fn set_column_compression(&self, column_compression: HashMap<String, Vec<(ColId, Compression)>>) {
+        self.inner.set_column_compression(column_compression)
+    }
+
+    /// Sets the unique constraints, by name, that the module declared as checked on commit.
+    pub fn set_deferred_constraints(&self, constraint_names: HashSet<String>) {
+        self.inner.set_deferred_constraints(constraint_names)
+    }
+
+    /// Sets the version columns the module declared, each with the key column of its table, by table name.
+    pub fn set_row_versions(&self, row_versions: HashMap<String, (ColId, ColId)>) {
+        self.inner.set_row_versions(row_versions)
+    }
+
+    /// Sets the limits on the user tables and rows of the database.
+    pub fn set_quota(&self, quota: Quota) {
+        self.inner.set_quota(quota)
+    }
+
+    /// Records a webhook delivery that was given up on in `st_webhook_dead_letter`.
+    pub fn record_webhook_dead_letter(&self, row: &StWebhookDeadLetterRow) {
+        self.inner.record_webhook_dead_letter(row)
+    }
+
+    /// Replaces the rows of `st_disk_usage` with `rows`.
+    pub fn record_disk_usage(&self, rows: &[StDiskUsageRow]) {
+        self.inner.record_disk_usage(rows)
+    }
+
+    /// Replaces the rows of `st_storage` with the space used by each table and index, and by the commit log,
+    /// as measured at `measured_at`, and returns them.
+    pub fn record_storage(&self, measured_at: u64) -> Vec<StStorageRow> {
+        self.inner.record_storage(self.commit_log_usage(), measured_at)
+    }
+
+    /// Returns the roles granted to `identity` in `st_role_members`.
+    pub fn roles_of(&self, tx: &MutTxId, identity: Identity) -> Result<Vec<String>, DBError> {
+        let mut roles = Vec::new();
+        for row in self.iter(tx, ST_ROLE_MEMBERS_ID.0)? {
+            let row = StRoleMemberRow::try_from(row.view())?;
+            if row.identity == identity {
+                roles.push(row.role.to_owned());
+            }
+        }
+        Ok(roles)
+    }
+
+    /// Whether the caller of `auth` holds one of `roles`.
+    ///
+    /// The owner of the database holds every role, including `owner`, which only it holds,
+    /// while other identities hold the roles granted to them.
+    pub fn has_any_role(&self, tx: &MutTxId, auth: AuthCtx, roles: &[String]) -> Result<bool, DBError> {
+        if auth.caller == auth.owner {
+            return Ok(true);
+        }
+        Ok(self.roles_of(tx, auth.caller)?.iter().any(|role| roles.contains(role)))
+    }
+
+    /// Grants `role` to `identity`, adding it to `st_roles` if it's new.
+    ///
+    /// Returns whether `identity` didn't hold it already.
+    pub fn grant_role(&self, tx: &mut MutTxId, role: &str, identity: Identity) -> Result<bool, DBError> {
+        if self.roles_of(tx, identity)?.iter().any(|r| r == role) {
+            return Ok(false);
+        }
+        self.ensure_role(tx, role)?;
+        self.insert(tx, ST_ROLE_MEMBERS_ID.0, (&StRoleMemberRow { identity, role }).into())?;
+        Ok(true)
+    }
+
+    /// Adds `role` to `st_roles` if it isn't there yet.
+    fn ensure_role(&self, tx: &mut MutTxId, role: &str) -> Result<(), DBError> {
+        for row in self.iter(tx, ST_ROLES_ID.0)? {
+            if StRoleRow::try_from(row.view())?.role == role {
+                return Ok(());
+            }
+        }
+        self.insert(tx, ST_ROLES_ID.0, (&StRoleRow { role }).into())?;
+        Ok(())
+    }
+
+    /// Revokes `role` from `identity`, leaving it in `st_roles`.
+    ///
+    /// Returns whether `identity` held it.
+    pub fn revoke_role(&self, tx: &mut MutTxId, role: &str, identity: Identity) -> Result<bool, DBError> {
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_ROLE_MEMBERS_ID.0)? {
+            let member = StRoleMemberRow::try_from(row.view())?;
+            if member.identity == identity && member.role == role {
+                rows.push(row.view().clone());
+            }
+        }
+        Ok(self.delete_by_rel(tx, ST_ROLE_MEMBERS_ID.0, rows)?.unwrap_or_default() > 0)
+    }
+
+    /// Returns the rows of `st_table_acl` for `table_id`, as pairs of role and access.
+    fn table_acl(&self, tx: &MutTxId, table_id: u32) -> Result<Vec<(String, StRoleAccess)>, DBError> {
+        let mut acl = Vec::new();
+        for row in self.iter(tx, ST_TABLE_ACL_ID.0)? {
+            let row = StTableAclRow::try_from(row.view())?;
+            if row.table_id == table_id {
+                acl.push((row.role.to_owned(), row.access));
+            }
+        }
+        Ok(acl)
+    }
+
+    /// The access the caller of `auth` has to `table_id` through its roles.
+    ///
+    /// Returns `None` for a table without rows in `st_table_acl`,
+    /// which is then governed by its [`spacetimedb_lib::auth::StAccess`] alone.
+    /// The owner of the database may always write to every table.
+    pub fn table_access(&self, tx: &MutTxId, auth: AuthCtx, table_id: u32) -> Result<Option<StRoleAccess>, DBError> {
+        if auth.caller == auth.owner {
+            return Ok(Some(StRoleAccess::Write));
+        }
+        let acl = self.table_acl(tx, table_id)?;
+        if acl.is_empty() {
+            return Ok(None);
+        }
+        let roles = self.roles_of(tx, auth.caller)?;
+        let access = acl
+            .into_iter()
+            .filter(|(role, _)| roles.contains(role))
+            .map(|(_, access)| access)
+            .max()
+            .unwrap_or(StRoleAccess::None);
+        Ok(Some(access))
+    }
+
+    /// Raises the access of `role` to `table_id` to at least `access`,
+    /// adding `role` to `st_roles` if it's new.
+    pub fn grant_table_access(
+        &self,
+        tx: &mut MutTxId,
+        table_id: u32,
+        role: &str,
+        access: StRoleAccess,
+    ) -> Result<(), DBError> {
+        let current = self.role_table_access(tx, table_id, role)?;
+        if current.map_or(true, |current| current < access) {
+            self.ensure_role(tx, role)?;
+            self.set_table_access(tx, table_id, role, access)?;
+        }
+        Ok(())
+    }
+
+    /// Lowers the access of `role` to `table_id` below `access`.
+    ///
+    /// This lists `table_id` in `st_table_acl` even if `role` had no access to it yet,
+    /// so the table stops being governed by its [`spacetimedb_lib::auth::StAccess`].
+    pub fn revoke_table_access(
+        &self,
+        tx: &mut MutTxId,
+        table_id: u32,
+        role: &str,
+        access: StRoleAccess,
+    ) -> Result<(), DBError> {
+        let below = match access {
+            StRoleAccess::None | StRoleAccess::Read => StRoleAccess::None,
+            StRoleAccess::Write => StRoleAccess::Read,
+        };
+        let current = self.role_table_access(tx, table_id, role)?;
+        let access = current.map_or(below, |current| current.min(below));
+        if current != Some(access) {
+            self.ensure_role(tx, role)?;
+            self.set_table_access(tx, table_id, role, access)?;
+        }
+        Ok(())
+    }
+
+    fn role_table_access(&self, tx: &MutTxId, table_id: u32, role: &str) -> Result<Option<StRoleAccess>, DBError> {
+        Ok(self
+            .table_acl(tx, table_id)?
+            .into_iter()
+            .find(|(r, _)| r == role)
+            .map(|(_, access)| access))
+    }
+
+    /// Replaces the row of `role` for `table_id` in `st_table_acl`.
+    fn set_table_access(
+        &self,
+        tx: &mut MutTxId,
+        table_id: u32,
+        role: &str,
+        access: StRoleAccess,
+    ) -> Result<(), DBError> {
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_TABLE_ACL_ID.0)? {
+            let acl = StTableAclRow::try_from(row.view())?;
+            if acl.table_id == table_id && acl.role == role {
+                rows.push(row.view().clone());
+            }
+        }
+        self.delete_by_rel(tx, ST_TABLE_ACL_ID.0, rows)?;
+        let row = StTableAclRow { table_id, role, access };
+        self.insert(tx, ST_TABLE_ACL_ID.0, (&row).into())?;
+        Ok(())
+    }
+
+    /// Records in `st_reducer_cooldown` that `identity` called `reducer_name` at `now`,
+    /// in microseconds since the unix epoch,
+    /// unless its last call to it was less than `cooldown` before.
+    ///
+    /// Returns how much longer `identity` has to wait if the call is too soon, in which case nothing is recorded.
+    pub fn record_reducer_call(
+        &self,
+        tx: &mut MutTxId,
+        identity: Identity,
+        reducer_name: &str,
+        now: u64,
+        cooldown: Duration,
+    ) -> Result<Option<Duration>, DBError> {
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_REDUCER_COOLDOWN_ID.0)? {
+            let last = StReducerCooldownRow::try_from(row.view())?;
+            if last.identity != identity || last.reducer_name != reducer_name {
+                continue;
+            }
+            let elapsed = Duration::from_micros(now.saturating_sub(last.last_call));
+            if elapsed < cooldown {
+                return Ok(Some(cooldown - elapsed));
+            }
+            rows.push(row.view().clone());
+        }
+        self.delete_by_rel(tx, ST_REDUCER_COOLDOWN_ID.0, rows)?;
+        let row = StReducerCooldownRow {
+            identity,
+            reducer_name,
+            last_call: now,
+        };
+        self.insert(tx, ST_REDUCER_COOLDOWN_ID.0, (&row).into())?;
+        Ok(None)
+    }
+
+    /// Adds to `st_outbox` a call of `reducer_name` of the database at `target` with the BSATN encoded `args`,
+    /// sent at `now`, in microseconds since the unix epoch.
+    ///
+    /// Returns the `msg_id` of the message, greater than that of every message sent before.
+    pub fn enqueue_message(
+        &self,
+        tx: &mut MutTxId,
+        target: Address,
+        reducer_name: &str,
+        args: &[u8],
+        now: u64,
+    ) -> Result<u64, DBError> {
+        let mut last_msg_id = 0;
+        for row in self.iter(tx, ST_OUTBOX_ID.0)? {
+            last_msg_id = last_msg_id.max(StOutboxRow::try_from(row.view())?.msg_id);
+        }
+        let row = StOutboxRow {
+            msg_id: last_msg_id + 1,
+            target,
+            reducer_name,
+            args,
+            sent_at: now,
+            delivered: false,
+        };
+        self.insert(tx, ST_OUTBOX_ID.0, (&row).into())?;
+        Ok(row.msg_id)
+    }
+
+    /// Returns the messages of `st_outbox` not delivered yet, in the order they were sent.
+    pub fn pending_messages(&self, tx: &MutTxId) -> Result<Vec<StOutboxRow<String, Vec<u8>>>, DBError> {
+        let mut messages = Vec::new();
+        for row in self.iter(tx, ST_OUTBOX_ID.0)? {
+            let message = StOutboxRow::try_from(row.view())?;
+            if !message.delivered {
+                messages.push(StOutboxRow {
+                    msg_id: message.msg_id,
+                    target: message.target,
+                    reducer_name: message.reducer_name.to_owned(),
+                    args: message.args.to_vec(),
+                    sent_at: message.sent_at,
+                    delivered: false,
+                });
+            }
+        }
+        messages.sort_by_key(|message| message.msg_id);
+        Ok(messages)
+    }
+
+    /// Marks the message `msg_id` to `target` as delivered in `st_outbox`,
+    /// deleting the messages to `target` sent before it.
+    pub fn ack_message(&self, tx: &mut MutTxId, target: Address, msg_id: u64) -> Result<(), DBError> {
+        let mut rows = Vec::new();
+        let mut acked = None;
+        for row in self.iter(tx, ST_OUTBOX_ID.0)? {
+            let message = StOutboxRow::try_from(row.view())?;
+            if message.target != target || message.msg_id > msg_id {
+                continue;
+            }
+            if message.msg_id == msg_id && !message.delivered {
+                acked = Some(StOutboxRow {
+                    msg_id,
+                    target,
+                    reducer_name: message.reducer_name.to_owned(),
+                    args: message.args.to_vec(),
+                    sent_at: message.sent_at,
+                    delivered: true,
+                });
+            }
+            rows.push(row.view().clone());
+        }
+        let Some(acked) = acked else {
+            return Ok(());
+        };
+        self.delete_by_rel(tx, ST_OUTBOX_ID.0, rows)?;
+        self.insert(tx, ST_OUTBOX_ID.0, (&acked).into())?;
+        Ok(())
+    }
+
+    /// Returns the `msg_id` of the last message received from the database at `sender`,
+    /// or 0 if it never sent one.
+    pub fn last_message_received(&self, tx: &MutTxId, sender: Address) -> Result<u64, DBError> {
+        for row in self.iter(tx, ST_INBOX_ID.0)? {
+            let inbox = StInboxRow::try_from(row.view())?;
+            if inbox.sender == sender {
+                return Ok(inbox.last_msg_id);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Records in `st_inbox` that the message `msg_id` from the database at `sender` was received.
+    pub fn record_message_received(&self, tx: &mut MutTxId, sender: Address, msg_id: u64) -> Result<(), DBError> {
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_INBOX_ID.0)? {
+            if StInboxRow::try_from(row.view())?.sender == sender {
+                rows.push(row.view().clone());
+            }
+        }
+        self.delete_by_rel(tx, ST_INBOX_ID.0, rows)?;
+        let row = StInboxRow {
+            sender,
+            last_msg_id: msg_id,
+        };
+        self.insert(tx, ST_INBOX_ID.0, (&row).into())?;
+        Ok(())
+    }
+
+    /// Deletes up to `limit` rows of `table_name` whose `u64` in `column` is before `before`.
+    ///
+    /// Returns how many were deleted, which is 0 for a table that doesn't exist (yet).
+    pub fn delete_expired(
+        &self,
+        tx: &mut MutTxId,
+        table_name: &str,
+        column: &str,
+        before: u64,
+        limit: usize,
+    ) -> Result<usize, DBError> {
+        let Some(table_id) = self.table_id_from_name(tx, table_name)? else {
+            return Ok(0);
+        };
+        let schema = self.schema_for_table(tx, table_id)?;
+        let col_id = schema
+            .columns
+            .iter()
+            .find(|col| col.col_name == column)
+            .ok_or_else(|| TableError::NotFound(format!("{table_name}.{column}")))?
+            .col_id as usize;
+
+        let mut rows = Vec::new();
+        for row in self.iter(tx, table_id)? {
+            if rows.len() == limit {
+                break;
+            }
+            if row.view().field_as_u64(col_id, None)? < before {
+                rows.push(row.view().clone());
+            }
+        }
+        Ok(self.delete_by_rel(tx, table_id, rows)?.unwrap_or_default() as usize)
+    }
+
+    /// Computes the statistics on every column of `table_id` from its current rows,
+    /// replacing its previous ones in `st_column_stats`.
+    pub fn analyze(&self, tx: &mut MutTxId, table_id: u32) -> Result<Vec<ColumnStats>, DBError> {
+        let schema = self.schema_for_table(tx, table_id)?;
+        let mut builders = vec![ColumnStatsBuilder::default(); schema.columns.len()];
+        for row in self.iter(tx, table_id)? {
+            for (builder, value) in builders.iter_mut().zip(&row.view().elements) {
+                builder.add(value);
+            }
+        }
+        let stats = builders
+            .into_iter()
+            .zip(&schema.columns)
+            .map(|(builder, col)| builder.finish(table_id, col.col_id))
+            .collect::<Vec<_>>();
+
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_COLUMN_STATS_ID.0)? {
+            if StColumnStatsRow::try_from(row.view())?.table_id == table_id {
+                rows.push(row.view().clone());
+            }
+        }
+        self.delete_by_rel(tx, ST_COLUMN_STATS_ID.0, rows)?;
+        let encode = |value: &Option<AlgebraicValue>| {
+            let mut bytes = Vec::new();
+            if let Some(value) = value {
+                value.encode(&mut bytes);
+            }
+            bytes
+        };
+        for col in &stats {
+            let row = StColumnStatsRow {
+                table_id,
+                col_id: col.col_id,
+                row_count: col.row_count,
+                distinct_count: col.distinct_count,
+                min: encode(&col.min),
+                max: encode(&col.max),
+            };
+            self.insert(tx, ST_COLUMN_STATS_ID.0, (&row).into())?;
+        }
+        Ok(stats)
+    }
+
+    /// Compacts the storage of the committed tables and rebuilds their indexes,
+    /// returning the space reclaimed, see [`Locking::vacuum_mut_tx`].
+    pub fn vacuum(&self, tx: &mut MutTxId) -> Result<VacuumReport, DBError> {
+        self.inner.vacuum_mut_tx(tx)
+    }
+
+    /// Returns the statistics on the columns of `table_id` as of its last [`Self::analyze`],
+    /// ordered by column, which are empty if it was never analyzed.
+    pub fn column_stats(&self, tx: &MutTxId, table_id: u32) -> Result<Vec<ColumnStats>, DBError> {
+        let mut stats = Vec::new();
+        for row in self.iter(tx, ST_COLUMN_STATS_ID.0)? {
+            let row = StColumnStatsRow::try_from(row.view())?;
+            if row.table_id != table_id {
+                continue;
+            }
+            let decode = |bytes: &[u8]| {
+                (!bytes.is_empty())
+                    .then(|| self.decode_column(tx, table_id, row.col_id, bytes))
+                    .transpose()
+            };
+            stats.push(ColumnStats {
+                table_id,
+                col_id: row.col_id,
+                row_count: row.row_count,
+                distinct_count: row.distinct_count,
+                min: decode(row.min)?,
+                max: decode(row.max)?,
+            });
+        }
+        stats.sort_by_key(|col| col.col_id);
+        Ok(stats)
+    }
+
+    /// Stores `data` under `key` in `st_blobs`, replacing the blob stored under it before, if any.
+    ///
+    /// Returns the hash of `data`, or an error if it's over [`MAX_BLOB_SIZE`].
+    pub fn put_blob(&self, tx: &mut MutTxId, key: &str, data: &[u8]) -> Result<Hash, DBError> {
+        if data.len() > MAX_BLOB_SIZE {
+            return Err(DBError::BlobTooLarge {
+                len: data.len(),
+                max: MAX_BLOB_SIZE,
+            });
+        }
+        self.delete_blob(tx, key)?;
+        let row = StBlobRow {
+            key,
+            hash: hash_bytes(data),
+            data,
+        };
+        self.insert(tx, ST_BLOBS_ID.0, (&row).into())?;
+        Ok(row.hash)
+    }
+
+    /// Returns the hash and the data of the blob stored under `key` in `st_blobs`, if any.
+    pub fn get_blob(&self, tx: &MutTxId, key: &str) -> Result<Option<(Hash, Vec<u8>)>, DBError> {
+        for row in self.iter(tx, ST_BLOBS_ID.0)? {
+            let blob = StBlobRow::try_from(row.view())?;
+            if blob.key == key {
+                return Ok(Some((blob.hash, blob.data.to_vec())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Deletes the blob stored under `key` in `st_blobs`, returning whether there was one.
+    pub fn delete_blob(&self, tx: &mut MutTxId, key: &str) -> Result<bool, DBError> {
+        let mut rows = Vec::new();
+        for row in self.iter(tx, ST_BLOBS_ID.0)? {
+            if StBlobRow::try_from(row.view())?.key == key {
+                rows.push(row.view().clone());
+            }
+        }
+        let found = !rows.is_empty();
+        self.delete_by_rel(tx, ST_BLOBS_ID.0, rows)?;
+        Ok(found)
+    }
+
+    /// Stores `data` out of the rows that will reference it through a `LargeBytesRef` column,
+    /// returning the key they hold.
+    pub fn put_large_value(&self, tx: &mut MutTxId, data: &[u8]) -> Result<DataKey, DBError> {
+        self.inner.put_large_value_mut_tx(tx, data)
+    }
+
+    /// Returns the value stored under `key` by [`Self::put_large_value`], if any.
+    pub fn get_large_value(&self, tx: &MutTxId, key: DataKey) -> Result<Option<Vec<u8>>, DBError> {
+        self.inner.get_large_value_mut_tx(tx, key)
+    }
+
+    /// The bytes and number of segments of the message log on disk,
+    /// or `None` for a database without one.
+    pub fn commit_log_usage(&self) -> Option<(u64, usize)> {
+        self.commit_log.disk_usage()
+    }
+
+    /// The number of transactions with changes committed to the commit log before `tx`.
+    ///
+    /// Reading it makes `tx` conflict with any transaction committed after it began,
+    /// so that it's distinct for each transaction that commits changes.
+    pub fn tx_offset(&self, tx: &mut MutTxId) -> u64 {
+        tx.record_offset_read();
+        // Waits for the transactions committed to the datastore to be appended to the commit log.
+        let _commit_lock = self.commit_lock.lock().unwrap();
+        self.commit_log.tx_offset()
+    }
+
     /// Begin a transaction.
     ///
     /// **Note**: this call **must** be paired with [`Self::rollback_tx`] or
@@ -215,19 +806,61 @@ impl RelationalDB {
         self.inner.begin_mut_tx()
     }
 
+    /// Begin a read-only transaction,
+    /// which shares the lock on the datastore with the other read-only transactions,
+    /// so that they run alongside each other, and only wait for transactions that write.
+    ///
+    /// **Note**: this call **must** be paired with [`Self::release_tx`],
+    /// which, unlike [`Self::commit_tx`], never writes to the commit log.
+    /// Writing in a read-only transaction panics.
+    pub fn begin_read_only_tx(&self) -> MutTxId {
+        log::trace!("BEGIN READ-ONLY TX");
+        self.inner.begin_tx()
+    }
+
+    pub fn release_tx(&self, tx: MutTxId) {
+        log::trace!("RELEASE TX");
+        self.inner.release_tx(tx)
+    }
+
     pub fn rollback_tx(&self, tx: MutTxId) {
         log::trace!("ROLLBACK TX");
         self.inner.rollback_mut_tx(tx)
     }
+    #[tracing::instrument(skip_all, fields(tx_offset))]
     pub fn commit_tx(&self, tx: MutTxId) -> Result<Option<(TxData, Option<usize>)>, DBError> {
         log::trace!("COMMIT TX");
-        if let Some(tx_data) = self.inner.commit_mut_tx(tx)? {
+        let _commit_lock = self.commit_lock.lock().unwrap();
+        if let Some(mut tx_data) = self.inner.commit_mut_tx(tx)? {
             let bytes_written = self.commit_log.append_tx(&tx_data, &self.inner)?;
+            tx_data.tx_offset = bytes_written.map(|_| self.commit_log.tx_offset());
+            if let Some(tx_offset) = tx_data.tx_offset {
+                tracing::Span::current().record("tx_offset", tx_offset);
+            }
             return Ok(Some((tx_data, bytes_written)));
         }
         Ok(None)
     }
 
+    /// Take a savepoint of the changes made so far in `tx`.
+    pub fn savepoint(&self, tx: &mut MutTxId) -> SavepointId {
+        log::trace!("SAVEPOINT");
+        self.inner.savepoint_mut_tx(tx)
+    }
+
+    /// Undo the changes made in `tx` since `savepoint` was taken,
+    /// releasing it and all the savepoints taken after it.
+    pub fn rollback_to_savepoint(&self, tx: &mut MutTxId, savepoint: SavepointId) -> Result<(), DBError> {
+        log::trace!("ROLLBACK TO SAVEPOINT {savepoint}");
+        self.inner.rollback_to_savepoint_mut_tx(tx, savepoint)
+    }
+
+    /// Release `savepoint` and all the savepoints taken after it, keeping the changes made since.
+    pub fn release_savepoint(&self, tx: &mut MutTxId, savepoint: SavepointId) -> Result<(), DBError> {
+        log::trace!("RELEASE SAVEPOINT {savepoint}");
+        self.inner.release_savepoint_mut_tx(tx, savepoint)
+    }
+
     /// Run a fallible function in a transaction.
     ///
     /// If the supplied function returns `Ok`, the transaction is automatically
@@ -303,6 +936,14 @@ impl RelationalDB {
         self.inner.rename_table_mut_tx(tx, TableId(table_id), new_name)
     }
 
+    /// Adds `columns` after the existing columns of the table, set to `None` in its current rows.
+    /// They must all be `Option`s, which the callers check when planning the change.
+    ///
+    /// Unlike renaming the table, this rewrites all of its rows.
+    pub fn add_columns(&self, tx: &mut MutTxId, table_id: u32, columns: Vec<ColumnDef>) -> Result<(), DBError> {
+        self.inner.add_columns_mut_tx(tx, TableId(table_id), columns)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn table_id_from_name(&self, tx: &MutTxId, table_name: &str) -> Result<Option<u32>, DBError> {
         self.inner
@@ -322,6 +963,13 @@ impl RelationalDB {
         self.inner.table_name_from_id_mut_tx(tx, TableId(table_id))
     }
 
+    /// Returns the number of committed transactions that changed the rows of the table `table_id`,
+    /// as listed in `st_table_version`, not counting `tx` itself.
+    #[tracing::instrument(skip_all)]
+    pub fn table_version(&self, tx: &MutTxId, table_id: u32) -> Result<u64, DBError> {
+        self.inner.table_version_mut_tx(tx, TableId(table_id))
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn column_attrs(
         &self,
@@ -367,6 +1015,19 @@ impl RelationalDB {
         self.inner.create_index_mut_tx(tx, index)
     }
 
+    /// Like [`Self::create_index`], but builds the index on the committed rows
+    /// without holding the lock on the database for the length of the build,
+    /// so that other transactions can write to the table in the meantime.
+    ///
+    /// The index is then created in a transaction of its own,
+    /// which only catches it up with the writes committed during the build,
+    /// so this must not be called while holding a transaction.
+    #[tracing::instrument(skip(self))]
+    pub fn create_index_online(&self, index: IndexDef) -> Result<IndexId, DBError> {
+        let build = self.inner.build_index(index)?;
+        self.with_auto_commit(|tx| self.inner.create_built_index_mut_tx(tx, build))
+    }
+
     /// Removes the [index::BTreeIndex] from the database by their `index_id`
     #[tracing::instrument(skip(self, tx))]
     pub fn drop_index(&self, tx: &mut MutTxId, index_id: IndexId) -> Result<(), DBError> {
@@ -398,6 +1059,41 @@ impl RelationalDB {
             .iter_by_col_eq_mut_tx(tx, TableId(table_id), ColId(col_id), value)
     }
 
+    /// Returns an iterator,
+    /// yielding every row in the table identified by `table_id`,
+    /// where the string column identified by `col_id` matches the full-text `query`.
+    ///
+    /// Matching is defined by [`spacetimedb_lib::fulltext::matches`].
+    #[tracing::instrument(skip(self, tx))]
+    pub fn iter_by_col_match<'a>(
+        &'a self,
+        tx: &'a mut MutTxId,
+        table_id: u32,
+        col_id: u32,
+        query: &'a str,
+    ) -> Result<IterByColMatch<'a>, DBError> {
+        self.inner
+            .iter_by_col_match_mut_tx(tx, TableId(table_id), ColId(col_id), query)
+    }
+
+    /// Returns an iterator,
+    /// yielding every row in the table identified by `table_id`,
+    /// where the point column identified by `col_id` is within the box from `min` to `max`.
+    ///
+    /// Being within the box is defined by [`spacetimedb_lib::spatial::in_box`].
+    #[tracing::instrument(skip(self, tx))]
+    pub fn iter_by_col_box<'a>(
+        &'a self,
+        tx: &'a mut MutTxId,
+        table_id: u32,
+        col_id: u32,
+        min: &'a AlgebraicValue,
+        max: &'a AlgebraicValue,
+    ) -> Result<IterByColBox<'a>, DBError> {
+        self.inner
+            .iter_by_col_box_mut_tx(tx, TableId(table_id), ColId(col_id), min, max)
+    }
+
     /// Returns an iterator,
     /// yielding every row in the table identified by `table_id`,
     /// where the column data identified by `col_id` matches what is within `range`.
@@ -514,11 +1210,15 @@ pub(crate) mod tests_utils {
 mod tests {
 
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     use crate::db::datastore::system_tables::StIndexRow;
+    use crate::db::datastore::system_tables::StRoleRow;
     use crate::db::datastore::system_tables::StSequenceRow;
     use crate::db::datastore::system_tables::StTableRow;
     use crate::db::datastore::system_tables::ST_INDEXES_ID;
+    use crate::db::datastore::system_tables::ST_OUTBOX_ID;
+    use crate::db::datastore::system_tables::ST_ROLES_ID;
     use crate::db::datastore::system_tables::ST_SEQUENCES_ID;
     use crate::db::datastore::traits::ColumnDef;
     use crate::db::datastore::traits::IndexDef;
@@ -527,13 +1227,17 @@ mod tests {
     use crate::db::relational_db::ST_TABLES_ID;
 
     use super::RelationalDB;
+    use crate::address::Address;
+    use crate::db::datastore::locking_tx_datastore::MutTxId;
     use crate::db::relational_db::make_default_ostorage;
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::error::{DBError, DatabaseError, IndexError};
+    use crate::identity::Identity;
     use spacetimedb_lib::auth::StAccess;
     use spacetimedb_lib::auth::StTableType;
     use spacetimedb_lib::error::ResultTest;
-    use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType};
+    use spacetimedb_lib::identity::AuthCtx;
+    use spacetimedb_lib::{AlgebraicType, AlgebraicValue, IndexType, ProductType};
     use spacetimedb_sats::product;
 
     #[test]
@@ -674,6 +1378,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_only_tx() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from_iter([("my_col", AlgebraicType::I32)]));
+        schema.table_name = "MyTable".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(1)])?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        let offset = stdb.tx_offset(&mut tx);
+        stdb.rollback_tx(tx);
+
+        let tx = stdb.begin_read_only_tx();
+        assert!(tx.is_read_only());
+        let rows = stdb
+            .iter(&tx, table_id)?
+            .map(|r| *r.view().elements[0].as_i32().unwrap())
+            .collect::<Vec<i32>>();
+        assert_eq!(rows, vec![1]);
+        stdb.release_tx(tx);
+
+        // Nothing was written to the commit log.
+        let mut tx = stdb.begin_tx();
+        assert_eq!(stdb.tx_offset(&mut tx), offset);
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
     #[test]
     fn test_filter_range_pre_commit() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -857,6 +1592,7 @@ mod tests {
                 col_id: 0,
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: false,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -899,6 +1635,7 @@ mod tests {
                 col_id: 0,
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -946,6 +1683,7 @@ mod tests {
                 col_id: 0,
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1009,18 +1747,21 @@ mod tests {
                     col_id: 0,
                     name: "MyTable_col1_idx".to_string(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0,
                     col_id: 2,
                     name: "MyTable_col3_idx".to_string(),
                     is_unique: false,
+                    index_type: IndexType::BTree,
                 },
                 IndexDef {
                     table_id: 0,
                     col_id: 3,
                     name: "MyTable_col4_idx".to_string(),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
             ],
             table_type: StTableType::User,
@@ -1079,6 +1820,7 @@ mod tests {
                 col_id: 0,
                 name: "MyTable_my_col_idx".to_string(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }],
             table_type: StTableType::User,
             table_access: StAccess::Public,
@@ -1101,6 +1843,223 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_grant_revoke_role() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let bob = Identity::from_hashing_bytes(b"bob");
+        let mut tx = stdb.begin_tx();
+        assert!(stdb.grant_role(&mut tx, "admin", alice)?);
+        assert!(stdb.grant_role(&mut tx, "moderator", alice)?);
+        assert!(stdb.grant_role(&mut tx, "moderator", bob)?);
+        assert!(!stdb.grant_role(&mut tx, "moderator", bob)?);
+
+        assert_eq!(stdb.roles_of(&tx, alice)?, ["admin", "moderator"]);
+        assert_eq!(stdb.roles_of(&tx, bob)?, ["moderator"]);
+        let roles = stdb
+            .iter(&tx, ST_ROLES_ID.0)?
+            .map(|row| StRoleRow::try_from(row.view()).map(|row| row.role.to_owned()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(roles, ["admin", "moderator"]);
+
+        let admin = ["admin".to_string()];
+        assert!(stdb.has_any_role(&tx, AuthCtx::new(owner, alice), &admin)?);
+        assert!(!stdb.has_any_role(&tx, AuthCtx::new(owner, bob), &admin)?);
+        assert!(stdb.has_any_role(&tx, AuthCtx::new(owner, owner), &admin)?);
+
+        assert!(stdb.revoke_role(&mut tx, "moderator", alice)?);
+        assert!(!stdb.revoke_role(&mut tx, "moderator", alice)?);
+        assert_eq!(stdb.roles_of(&tx, alice)?, ["admin"]);
+        assert_eq!(stdb.roles_of(&tx, bob)?, ["moderator"]);
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_reducer_call() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let bob = Identity::from_hashing_bytes(b"bob");
+        let cooldown = Duration::from_millis(500);
+        let mut tx = stdb.begin_tx();
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, alice, "chat", 1_000_000, cooldown)?,
+            None
+        );
+        // Too soon for `alice`, but not for `bob`, nor for another reducer.
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, alice, "chat", 1_200_000, cooldown)?,
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, bob, "chat", 1_200_000, cooldown)?,
+            None
+        );
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, alice, "move", 1_200_000, cooldown)?,
+            None
+        );
+        // The rejected call doesn't restart the window.
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, alice, "chat", 1_500_000, cooldown)?,
+            None
+        );
+        assert_eq!(
+            stdb.record_reducer_call(&mut tx, alice, "chat", 1_900_000, cooldown)?,
+            Some(Duration::from_millis(100))
+        );
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_outbox() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let alice = Address::from_arr(&[1; 16]);
+        let bob = Address::from_arr(&[2; 16]);
+        let mut tx = stdb.begin_tx();
+        assert_eq!(stdb.enqueue_message(&mut tx, alice, "greet", b"a", 1)?, 1);
+        assert_eq!(stdb.enqueue_message(&mut tx, bob, "greet", b"b", 2)?, 2);
+        assert_eq!(stdb.enqueue_message(&mut tx, alice, "greet", b"c", 3)?, 3);
+        let pending = |tx: &MutTxId| -> ResultTest<Vec<u64>> {
+            Ok(stdb.pending_messages(tx)?.iter().map(|msg| msg.msg_id).collect())
+        };
+        assert_eq!(pending(&tx)?, [1, 2, 3]);
+
+        // The last delivered message to a target stays to keep the ids increasing.
+        stdb.ack_message(&mut tx, alice, 1)?;
+        stdb.ack_message(&mut tx, alice, 3)?;
+        assert_eq!(pending(&tx)?, [2]);
+        assert_eq!(stdb.iter(&tx, ST_OUTBOX_ID.0)?.count(), 2);
+        assert_eq!(stdb.enqueue_message(&mut tx, bob, "greet", b"d", 4)?, 4);
+
+        assert_eq!(stdb.last_message_received(&tx, alice)?, 0);
+        stdb.record_message_received(&mut tx, alice, 3)?;
+        stdb.record_message_received(&mut tx, alice, 4)?;
+        stdb.record_message_received(&mut tx, bob, 1)?;
+        assert_eq!(stdb.last_message_received(&tx, alice)?, 4);
+        assert_eq!(stdb.last_message_received(&tx, bob)?, 1);
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_expired() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from_iter([
+            ("id", AlgebraicType::U32),
+            ("created_at", AlgebraicType::U64),
+        ]));
+        schema.table_name = "Log".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        for (id, created_at) in [(1, 10), (2, 20), (3, 30), (4, 40)] {
+            stdb.insert(
+                &mut tx,
+                table_id,
+                product![AlgebraicValue::U32(id), AlgebraicValue::U64(created_at)],
+            )?;
+        }
+
+        assert_eq!(stdb.delete_expired(&mut tx, "Log", "created_at", 35, 2)?, 2);
+        assert_eq!(stdb.delete_expired(&mut tx, "Log", "created_at", 35, 2)?, 1);
+        assert_eq!(stdb.delete_expired(&mut tx, "Log", "created_at", 35, 2)?, 0);
+        let ids = stdb
+            .iter(&tx, table_id)?
+            .map(|r| *r.view().elements[0].as_u32().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [4]);
+
+        assert_eq!(stdb.delete_expired(&mut tx, "Missing", "created_at", 35, 2)?, 0);
+        assert!(stdb.delete_expired(&mut tx, "Log", "missing", 35, 2).is_err());
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_access() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let bob = Identity::from_hashing_bytes(b"bob");
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from_iter([("my_col", AlgebraicType::I32)]));
+        schema.table_name = "MyTable".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.grant_role(&mut tx, "dashboard", alice)?;
+
+        let as_alice = AuthCtx::new(owner, alice);
+        let as_bob = AuthCtx::new(owner, bob);
+        assert_eq!(stdb.table_access(&tx, as_alice, table_id)?, None);
+        assert_eq!(
+            stdb.table_access(&tx, AuthCtx::new(owner, owner), table_id)?,
+            Some(StRoleAccess::Write)
+        );
+
+        stdb.grant_table_access(&mut tx, table_id, "dashboard", StRoleAccess::Write)?;
+        stdb.grant_table_access(&mut tx, table_id, "dashboard", StRoleAccess::Read)?;
+        assert_eq!(stdb.table_access(&tx, as_alice, table_id)?, Some(StRoleAccess::Write));
+        assert_eq!(stdb.table_access(&tx, as_bob, table_id)?, Some(StRoleAccess::None));
+
+        stdb.revoke_table_access(&mut tx, table_id, "dashboard", StRoleAccess::Write)?;
+        assert_eq!(stdb.table_access(&tx, as_alice, table_id)?, Some(StRoleAccess::Read));
+        stdb.revoke_table_access(&mut tx, table_id, "dashboard", StRoleAccess::Read)?;
+        assert_eq!(stdb.table_access(&tx, as_alice, table_id)?, Some(StRoleAccess::None));
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut schema = TableDef::from(ProductType::from_iter([
+            ("id", AlgebraicType::U32),
+            ("name", AlgebraicType::String),
+        ]));
+        schema.table_name = "Person".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        assert!(stdb.column_stats(&tx, table_id)?.is_empty());
+
+        for (id, name) in [(3, "Alice"), (1, "Bob"), (2, "Alice")] {
+            stdb.insert(
+                &mut tx,
+                table_id,
+                product![AlgebraicValue::U32(id), AlgebraicValue::String(name.into())],
+            )?;
+        }
+        let stats = stdb.analyze(&mut tx, table_id)?;
+        assert_eq!(stdb.column_stats(&tx, table_id)?, stats);
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].row_count, stats[0].distinct_count), (3, 3));
+        assert_eq!(stats[0].min, Some(AlgebraicValue::U32(1)));
+        assert_eq!(stats[0].max, Some(AlgebraicValue::U32(3)));
+        assert_eq!(stats[1].distinct_count, 2);
+        assert_eq!(stats[1].min, Some(AlgebraicValue::String("Alice".into())));
+
+        // Analyzing again replaces the previous statistics.
+        let rows = stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect::<Vec<_>>();
+        stdb.delete_by_rel(&mut tx, table_id, rows)?;
+        let stats = stdb.analyze(&mut tx, table_id)?;
+        assert_eq!(stdb.column_stats(&tx, table_id)?, stats);
+        assert_eq!((stats[0].row_count, stats[0].min.clone()), (0, None));
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
     // #[test]
     // fn test_rename_column() -> ResultTest<()> {
     //     let (mut stdb, _tmp_dir) = make_test_db()?;
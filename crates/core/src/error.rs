@@ -1,15 +1,16 @@
 use crate::client::ClientActorId;
-use crate::db::datastore::traits::{IndexDef, IndexId};
+use crate::db::datastore::traits::{IndexDef, IndexId, SavepointId};
 use hex::FromHexError;
 use spacetimedb_lib::buffer::DecodeError;
 use spacetimedb_lib::error::{LibError, RelationError};
 use spacetimedb_lib::relation::FieldName;
-use spacetimedb_lib::{PrimaryKey, ProductValue};
+use spacetimedb_lib::{DataKey, PrimaryKey, ProductValue};
 use spacetimedb_sats::product_value::InvalidFieldError;
 use spacetimedb_sats::satn::Satn;
 use spacetimedb_sats::AlgebraicValue;
 use spacetimedb_vm::errors::{ErrorKind, ErrorLang, ErrorVm};
 use spacetimedb_vm::expr::Crud;
+use std::fmt;
 use std::num::ParseIntError;
 use std::path::PathBuf;
 use std::sync::{MutexGuard, PoisonError};
@@ -60,10 +61,15 @@ pub enum IndexError {
     IndexAlreadyExists(IndexDef, String),
     #[error("Column not found: {0:?}")]
     ColumnNotFound(IndexDef),
+    #[error("Full-text index must be on a non-unique string column: {0:?}")]
+    FullTextColumn(IndexDef),
+    #[error("Spatial index must be on a non-unique point column: {0:?}")]
+    SpatialColumn(IndexDef),
     #[error("Unique constraint violation '{}' in table '{}': column: '{}' value: {}", constraint_name, table_name, col_name, value.to_satn())]
     UniqueConstraintViolation {
         constraint_name: String,
         table_name: String,
+        col_id: u32,
         col_name: String,
         value: AlgebraicValue,
     },
@@ -83,6 +89,12 @@ pub enum SubscriptionError {
     Empty,
     #[error("Queries with side effects not allowed: {0:?}")]
     SideEffect(Crud),
+    #[error("Subscriptions can only join two tables on one column, selecting the columns of the first, as in `SELECT a.* FROM a JOIN b ON a.x = b.y`")]
+    UnsupportedJoin,
+    #[error("Subscriptions can only page through the whole rows of one table in some order, as in `SELECT * FROM t ORDER BY score DESC LIMIT 10`")]
+    UnsupportedWindow,
+    #[error("Subscriptions can't read from a view like `{0}`, whose rows aren't stored")]
+    View(String),
 }
 
 #[derive(Error, Debug)]
@@ -112,12 +124,42 @@ pub enum PlanError {
     VmError(#[from] ErrorVm),
 }
 
+/// A limit of a database's [`Quota`](crate::db::datastore::locking_tx_datastore::Quota).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaLimit {
+    Tables,
+    Rows,
+    Bytes,
+}
+
+impl fmt::Display for QuotaLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tables => "tables",
+            Self::Rows => "rows",
+            Self::Bytes => "bytes of rows",
+        })
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[error("the database's quota allows at most {max} {limit}")]
+pub struct QuotaExceeded {
+    pub limit: QuotaLimit,
+    pub max: u64,
+}
+
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error("Database instance not found: {0}")]
     NotFound(u64),
     #[error("Database is already opened. Path:`{0}`. Error:{1}")]
     DatabasedOpened(PathBuf, anyhow::Error),
+    #[error("Database has no commit log to replay")]
+    NoCommitLog,
+    #[error("Tx offset {tx_offset} is past the end of the commit log, which has {len} transactions")]
+    TxOffsetOutOfRange { tx_offset: u64, len: u64 },
 }
 
 #[derive(Error, Debug)]
@@ -132,6 +174,8 @@ pub enum DBError {
     Sequence2(#[from] crate::db::datastore::locking_tx_datastore::SequenceError),
     #[error("IndexError: {0}")]
     Index(#[from] IndexError),
+    #[error("Savepoint with ID `{0}` not found.")]
+    SavepointNotFound(SavepointId),
     #[error("IOError: {0}.")]
     IoError(#[from] std::io::Error),
     #[error("ParseIntError: {0}.")]
@@ -164,6 +208,12 @@ pub enum DBError {
     },
     #[error("SqlError: {error}, executing: `{sql}`")]
     Plan { sql: String, error: PlanError },
+    #[error("QuotaExceeded: {0}")]
+    QuotaExceeded(#[from] QuotaExceeded),
+    #[error("BlobTooLarge: the blob is {len} bytes, over the limit of {max}")]
+    BlobTooLarge { len: usize, max: usize },
+    #[error("LargeValueNotFound: no large value is stored under {0:?}")]
+    LargeValueNotFound(DataKey),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -177,6 +227,15 @@ impl DBError {
         }
         None
     }
+
+    /// Returns the quota this error is for exceeding, if any, even if it was raised while running a query.
+    pub fn get_quota_exceeded(&self) -> Option<&QuotaExceeded> {
+        match self {
+            Self::QuotaExceeded(err) => Some(err),
+            Self::Vm(ErrorVm::Other(err)) | Self::Other(err) => err.downcast_ref::<DBError>()?.get_quota_exceeded(),
+            _ => None,
+        }
+    }
 }
 
 impl From<InvalidFieldError> for DBError {
@@ -211,16 +270,24 @@ pub enum NodesError {
     DecodeFilter(#[source] DecodeError),
     #[error("table with provided name or id doesn't exist")]
     TableNotFound,
+    #[error("reducer with provided name doesn't exist")]
+    ReducerNotFound,
     #[error("Primary key {0:?} not found")]
     PrimaryKeyNotFound(PrimaryKey),
     #[error("row with column of given value not found")]
     ColumnValueNotFound,
     #[error("range of rows not found")]
     RangeNotFound,
+    #[error("no blob is stored under the given key")]
+    BlobNotFound,
+    #[error("no large value is stored under the given key")]
+    LargeValueNotFound,
     #[error("column is out of bounds")]
     BadColumn,
     #[error("can't perform operation; not inside transaction")]
     NotInTransaction,
+    #[error("can't perform write; the transaction is read-only")]
+    ReadOnlyTransaction,
     #[error("table with name {0:?} already exists")]
     AlreadyExists(String),
     #[error("table with name `{0}` start with 'st_' and that is reserved for internal system tables.")]
@@ -0,0 +1,118 @@
+//! The log of the host calls made by the reducers of a database,
+//! kept in a ring buffer while it's turned on to debug why a reducer is slow or wrong.
+//!
+//! The log is only held in memory, and so turned off again when the database is restarted.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// A host call made by a reducer, as recorded in the [`CallLog`].
+#[derive(Clone, Debug, Serialize)]
+pub struct HostCallRecord {
+    /// When the call was made, in microseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// The reducer making the call.
+    pub reducer: String,
+    /// The host call, e.g., `insert` or `iter_by_col_eq`.
+    pub call: &'static str,
+    /// The table the call operated on, if any.
+    pub table_id: Option<u32>,
+    /// The name of that table, filled in when the log is read.
+    pub table_name: Option<String>,
+    /// The number of rows the call read or wrote.
+    pub rows: u64,
+    /// The number of bytes passed to or returned by the call.
+    pub bytes: u64,
+    /// How long the call took, in microseconds.
+    pub duration_micros: u64,
+    /// Whether the call failed, or, for an `iter`, was dropped before its end,
+    /// in which case `rows` and `bytes` are 0.
+    pub failed: bool,
+}
+
+/// The most recent host calls made by the reducers of a database, recorded only while turned on.
+#[derive(Default)]
+pub struct CallLog {
+    enabled: AtomicBool,
+    records: Mutex<VecDeque<HostCallRecord>>,
+}
+
+impl CallLog {
+    /// The number of calls kept, past which the oldest ones are dropped.
+    pub const CAPACITY: usize = 10_000;
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turns the log on or off.
+    ///
+    /// Turning it on clears the calls recorded previously, while turning it off keeps them around to be read.
+    pub fn set_enabled(&self, enabled: bool) {
+        if enabled && !self.enabled.swap(true, Ordering::Relaxed) {
+            self.records.lock().clear();
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Records `record` if the log is on, dropping the oldest call if it's full.
+    pub fn record(&self, record: HostCallRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut records = self.records.lock();
+        if records.len() == Self::CAPACITY {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Returns the calls recorded, oldest first.
+    pub fn records(&self) -> Vec<HostCallRecord> {
+        self.records.lock().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(rows: u64) -> HostCallRecord {
+        HostCallRecord {
+            timestamp: 0,
+            reducer: "add".into(),
+            call: "insert",
+            table_id: Some(4096),
+            table_name: None,
+            rows,
+            bytes: 8,
+            duration_micros: 1,
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn test_call_log_ring_buffer() {
+        let log = CallLog::default();
+        log.record(record(0));
+        assert!(log.records().is_empty(), "calls are only recorded while the log is on");
+
+        log.set_enabled(true);
+        for rows in 0..CallLog::CAPACITY as u64 + 2 {
+            log.record(record(rows));
+        }
+        let records = log.records();
+        assert_eq!(records.len(), CallLog::CAPACITY);
+        assert_eq!(records[0].rows, 2);
+
+        log.set_enabled(false);
+        log.record(record(0));
+        assert_eq!(log.records().len(), CallLog::CAPACITY);
+
+        log.set_enabled(true);
+        assert!(log.records().is_empty(), "turning the log on starts it afresh");
+    }
+}
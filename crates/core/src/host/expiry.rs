@@ -0,0 +1,41 @@
+//! Deleting the rows of the tables declared with a TTL,
+//! as in `#[spacetimedb(table, ttl = "24h", ttl_column = "created_at")]`, once they expire.
+//!
+//! The rows are deleted in batches, each in a normal transaction of its own,
+//! so subscribers see the deletes, and reducers run in between the batches.
+
+use std::time::Duration;
+
+use crate::host::ModuleHost;
+
+/// How often the expired rows of a database are looked for.
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Delete the expired rows of the tables of `module` every [`EXPIRE_INTERVAL`], until it exits.
+pub fn spawn_expirer(module: ModuleHost) {
+    if module.info().table_ttls.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::select! {
+            () = expire(&module) => {}
+            () = module.exited() => {}
+        }
+    });
+}
+
+async fn expire(module: &ModuleHost) {
+    let mut interval = tokio::time::interval(EXPIRE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        // Keep deleting until none are left, so a backlog clears batch by batch.
+        loop {
+            match module.expire_rows().await {
+                Ok(0) | Err(_) => break,
+                Ok(deleted) => log::debug!("Deleted {deleted} expired rows"),
+            }
+        }
+    }
+}
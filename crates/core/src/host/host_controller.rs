@@ -1,24 +1,39 @@
+use crate::database_instance_context::DatabaseInstanceContext;
+use crate::db::relational_db::{open_db, RelationalDB};
 use crate::hash::hash_bytes;
-use crate::host::wasmer;
+use crate::host::{wasmer, wasmtime};
 use crate::messages::control_db::HostType;
 use crate::module_host_context::ModuleHostContext;
 use anyhow::Context;
 use serde::Serialize;
-use std::collections::HashMap;
+use spacetimedb_lib::auth::StTableType;
+use spacetimedb_lib::name::UpdatePlan;
+use spacetimedb_lib::AlgebraicValue;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::ops::Sub;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tempdir::TempDir;
 
 use super::module_host::{
     Catalog, EntityDef, EventStatus, ModuleHost, ModuleStarter, NoSuchModule, UpdateDatabaseResult,
 };
 use super::scheduler::SchedulerStarter;
+use super::wasm_common::module_host_actor::plan_update;
+use super::webhooks::{self, NoWebhooks, WebhookSource};
+use super::{expiry, outbox, retention};
 use super::{EnergyMonitor, NullEnergyMonitor, ReducerArgs};
 
+/// How long the calls in flight to a module being swapped for a new version have to finish.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the new version of a module has to update the database before the swap is rolled back.
+const UPDATE_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub struct HostController {
-    modules: Mutex<HashMap<u64, ModuleHost>>,
+    modules: Arc<Mutex<HashMap<u64, ModuleHost>>>,
     pub energy_monitor: Arc<dyn EnergyMonitor>,
+    webhook_source: Arc<dyn WebhookSource>,
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Serialize, Debug)]
@@ -122,6 +137,8 @@ pub struct ReducerCallResult {
     pub outcome: ReducerOutcome,
     pub energy_used: EnergyDiff,
     pub execution_duration: Duration,
+    /// The value the reducer returned, if it returns one and committed.
+    pub return_value: Option<AlgebraicValue>,
 }
 
 #[derive(Clone, Debug)]
@@ -152,15 +169,17 @@ impl From<&EventStatus> for ReducerOutcome {
 }
 
 pub struct UpdateOutcome {
-    pub module_host: ModuleHost,
+    /// The module running the new version, or `None` if the update failed and was rolled back.
+    pub module_host: Option<ModuleHost>,
     pub update_result: UpdateDatabaseResult,
 }
 
 impl HostController {
-    pub fn new(energy_monitor: Arc<impl EnergyMonitor>) -> Self {
+    pub fn new(energy_monitor: Arc<impl EnergyMonitor>, webhook_source: Arc<impl WebhookSource>) -> Self {
         Self {
-            modules: Mutex::new(HashMap::new()),
+            modules: Arc::default(),
             energy_monitor,
+            webhook_source,
         }
     }
 
@@ -183,20 +202,124 @@ impl HostController {
         Ok(())
     }
 
+    /// Swap the running module of the instance, if any, for the new version in `module_host_context`,
+    /// without dropping the clients connected to it.
+    ///
+    /// The running module stops accepting calls and the calls in flight are let finish,
+    /// then the new version updates the database. If that's rejected, fails or times out,
+    /// the tables it created are dropped and the old version carries on,
+    /// taking the calls held off in the meantime. Otherwise, those go to the new version,
+    /// and the clients are moved over to it, with their subscriptions evaluated afresh.
+    ///
+    /// An `__update__` reducer that times out can't be interrupted, so the rollback waits for it,
+    /// and any changes it commits to the existing tables are kept.
     pub async fn update_module_host(
         &self,
         module_host_context: ModuleHostContext,
     ) -> Result<UpdateOutcome, anyhow::Error> {
-        let module_host = self.spawn_module_host(module_host_context).await?;
-        // TODO: see init_module_host
-        let update_result = module_host.update_database().await?;
+        let key = module_host_context.dbic.database_instance_id;
+        let address = module_host_context.dbic.address;
+        let dbic = module_host_context.dbic.clone();
+        let relational_db = dbic.relational_db.clone();
+
+        let (module_host, start_module, start_scheduler) =
+            tokio::task::block_in_place(|| Self::make_module_host(module_host_context, self.energy_monitor.clone()))?;
+
+        let old_module = self.get_module_host(key).ok();
+        let drained = match &old_module {
+            Some(old_module) => match tokio::time::timeout(DRAIN_TIMEOUT, old_module.drain()).await {
+                Ok(drained) => Some(drained),
+                Err(_) => anyhow::bail!("timed out waiting for the calls to the running module to finish"),
+            },
+            None => None,
+        };
+
+        start_module.start();
+        let tables_before = user_tables(&relational_db)?;
+        let update_result = match tokio::time::timeout(UPDATE_TIMEOUT, module_host.update_database()).await {
+            Ok(update_result) => update_result,
+            Err(_) => Err(anyhow::anyhow!(
+                "timed out waiting for the new module to update the database"
+            )),
+        };
+
+        let took_effect = matches!(&update_result, Ok(Ok(success)) if success.committed());
+        if !took_effect {
+            module_host.exit().await;
+            if let Err(e) = drop_tables_not_in(&relational_db, &tables_before) {
+                log::error!("Failed to drop the tables created by a rolled back update: {e:#}");
+            }
+            drop(drained);
+            return update_result.map(|update_result| UpdateOutcome {
+                module_host: None,
+                update_result,
+            });
+        }
+        let update_result = update_result?;
+
+        let replaced = self.modules.lock().unwrap().insert(key, module_host.clone());
+        if let Some(old_module) = &old_module {
+            old_module.swap_for(module_host.clone());
+            if old_module
+                .subscription()
+                .hand_over(module_host.subscription())
+                .await
+                .is_err()
+            {
+                log::warn!("The subscriptions of the old module were gone before they could be handed over");
+            }
+        }
+        for old_module in old_module.iter().chain(&replaced) {
+            old_module.exit().await;
+        }
+        start_scheduler.start(&module_host, address)?;
+        webhooks::spawn_dispatcher(module_host.clone(), address, relational_db, self.webhook_source.clone());
+        outbox::spawn_coordinator(module_host.clone(), dbic.clone(), self.modules.clone());
+        retention::spawn_enforcer(module_host.clone(), dbic);
+        expiry::spawn_expirer(module_host.clone());
+        drop(drained);
 
         Ok(UpdateOutcome {
-            module_host,
+            module_host: Some(module_host),
             update_result,
         })
     }
 
+    /// Load the new version of a module in `module_host_context` and return what updating its database to it would do,
+    /// without doing it, as [`update_module_host`](Self::update_module_host) would then.
+    ///
+    /// As loading a module configures the database it's loaded on, the new version is loaded on an empty database
+    /// in memory, and only its tables are compared to those of the database in `module_host_context`.
+    /// The module running on that database, if any, isn't disturbed.
+    pub async fn plan_module_update(
+        &self,
+        module_host_context: ModuleHostContext,
+    ) -> Result<UpdatePlan, anyhow::Error> {
+        let relational_db = module_host_context.dbic.relational_db.clone();
+        let scratch_dir = TempDir::new("stdb_plan_update")?;
+        let dbic = Arc::new(DatabaseInstanceContext {
+            relational_db: Arc::new(open_db(scratch_dir.path(), true)?),
+            call_log: Arc::default(),
+            outbox: Arc::default(),
+            ..(*module_host_context.dbic).clone()
+        });
+        let module_host_context = ModuleHostContext {
+            dbic,
+            ..module_host_context
+        };
+
+        let (module_host, start_module, _) =
+            tokio::task::block_in_place(|| Self::make_module_host(module_host_context, self.energy_monitor.clone()))?;
+        let tx = relational_db.begin_tx();
+        let plan = plan_update(module_host.info(), &relational_db, &tx);
+        relational_db.rollback_tx(tx);
+
+        start_module.start();
+        module_host.exit().await;
+        drop(scratch_dir);
+        Ok(plan?.0)
+    }
+
     pub async fn add_module_host(&self, module_host_context: ModuleHostContext) -> Result<ModuleHost, anyhow::Error> {
         let module_host = self.spawn_module_host(module_host_context).await?;
         // module_host.init_function(); ??
@@ -215,6 +338,9 @@ impl HostController {
     /// of whether the OS has been restarted.
     pub async fn spawn_module_host(&self, module_host_context: ModuleHostContext) -> Result<ModuleHost, anyhow::Error> {
         let key = module_host_context.dbic.database_instance_id;
+        let address = module_host_context.dbic.address;
+        let dbic = module_host_context.dbic.clone();
+        let relational_db = dbic.relational_db.clone();
 
         let (module_host, start_module, start_scheduler) =
             tokio::task::block_in_place(|| Self::make_module_host(module_host_context, self.energy_monitor.clone()))?;
@@ -224,7 +350,11 @@ impl HostController {
             old_module.exit().await
         }
         start_module.start();
-        start_scheduler.start(&module_host)?;
+        start_scheduler.start(&module_host, address)?;
+        webhooks::spawn_dispatcher(module_host.clone(), address, relational_db, self.webhook_source.clone());
+        outbox::spawn_coordinator(module_host.clone(), dbic.clone(), self.modules.clone());
+        retention::spawn_enforcer(module_host.clone(), dbic);
+        expiry::spawn_expirer(module_host.clone());
 
         Ok(module_host)
     }
@@ -242,6 +372,29 @@ impl HostController {
                 mhc.scheduler,
                 energy_monitor,
             )?),
+            HostType::Wasmtime if wasmtime::is_component(&mhc.program_bytes) => {
+                ModuleHost::spawn(wasmtime::make_component_actor(
+                    mhc.dbic,
+                    module_hash,
+                    &mhc.program_bytes,
+                    mhc.scheduler,
+                    energy_monitor,
+                )?)
+            }
+            HostType::Wasmtime => ModuleHost::spawn(wasmtime::make_actor(
+                mhc.dbic,
+                module_hash,
+                &mhc.program_bytes,
+                mhc.scheduler,
+                energy_monitor,
+            )?),
+            HostType::AssemblyScript => ModuleHost::spawn(wasmtime::make_assemblyscript_actor(
+                mhc.dbic,
+                module_hash,
+                &mhc.program_bytes,
+                mhc.scheduler,
+                energy_monitor,
+            )?),
         };
         Ok((module_host, module_starter, mhc.scheduler_starter))
     }
@@ -286,6 +439,32 @@ impl HostController {
 
 impl Default for HostController {
     fn default() -> Self {
-        Self::new(Arc::new(NullEnergyMonitor))
+        Self::new(Arc::new(NullEnergyMonitor), Arc::new(NoWebhooks))
     }
 }
+
+/// The names of the user tables of the database.
+fn user_tables(stdb: &RelationalDB) -> anyhow::Result<HashSet<String>> {
+    let tx = stdb.begin_tx();
+    let tables = stdb.get_all_tables(&tx).map(|tables| {
+        tables
+            .into_iter()
+            .filter(|schema| schema.table_type == StTableType::User)
+            .map(|schema| schema.table_name)
+            .collect()
+    });
+    stdb.rollback_tx(tx);
+    Ok(tables?)
+}
+
+/// Drop the user tables of the database whose names aren't in `keep`.
+fn drop_tables_not_in(stdb: &RelationalDB, keep: &HashSet<String>) -> anyhow::Result<()> {
+    stdb.with_auto_commit::<_, _, anyhow::Error>(|tx| {
+        for schema in stdb.get_all_tables(tx)? {
+            if schema.table_type == StTableType::User && !keep.contains(&schema.table_name) {
+                stdb.drop_table(tx, schema.table_id)?;
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,126 @@
+//! The HTTP endpoints a module declares, which the host serves under the database's address.
+//!
+//! A route's path is matched a segment at a time, where a segment starting with `:` matches any segment,
+//! whose value is then the parameter of that name. Empty segments, e.g. of a trailing `/`, are ignored.
+
+use spacetimedb_lib::{HttpHandler, HttpMethod, HttpRoute};
+
+/// The routes of a module.
+#[derive(Debug, Default)]
+pub struct HttpRoutes {
+    routes: Vec<HttpRoute>,
+}
+
+/// A route matched by a request, with the values of the parameters of its path, by name.
+#[derive(Debug)]
+pub struct RouteMatch<'a> {
+    pub handler: &'a HttpHandler,
+    pub params: Vec<(&'a str, &'a str)>,
+}
+
+impl HttpRoutes {
+    pub fn new(routes: Vec<HttpRoute>) -> Self {
+        Self { routes }
+    }
+
+    /// Returns the route matching a request for `path` with `method`, if any.
+    ///
+    /// Where several routes match, the one with the fewest parameters wins,
+    /// so that `/players/top` is preferred over `/players/:id`.
+    pub fn find<'a>(&'a self, method: HttpMethod, path: &'a str) -> Option<RouteMatch<'a>> {
+        self.routes
+            .iter()
+            .filter(|route| route.method == method)
+            .filter_map(|route| {
+                let params = match_path(&route.path, path)?;
+                Some(RouteMatch {
+                    handler: &route.handler,
+                    params,
+                })
+            })
+            .min_by_key(|m| m.params.len())
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Returns the shape of `pattern`, in which all the parameters look alike,
+/// so that two routes with the same shape match the same requests.
+pub(crate) fn route_shape(pattern: &str) -> Vec<&str> {
+    segments(pattern)
+        .map(|segment| if segment.starts_with(':') { ":" } else { segment })
+        .collect()
+}
+
+/// Matches `path` against `pattern`, returning the values of its parameters if it matches.
+fn match_path<'a>(pattern: &'a str, path: &'a str) -> Option<Vec<(&'a str, &'a str)>> {
+    let mut pattern = segments(pattern);
+    let mut path = segments(path);
+    let mut params = Vec::new();
+    loop {
+        match (pattern.next(), path.next()) {
+            (None, None) => return Some(params),
+            (Some(expected), Some(segment)) => match expected.strip_prefix(':') {
+                Some(name) => params.push((name, segment)),
+                None if expected == segment => {}
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: HttpMethod, path: &str, reducer: &str) -> HttpRoute {
+        HttpRoute {
+            method,
+            path: path.to_owned(),
+            handler: HttpHandler::Reducer(reducer.to_owned()),
+        }
+    }
+
+    fn reducer_of(m: &RouteMatch<'_>) -> &str {
+        match m.handler {
+            HttpHandler::Reducer(name) => name,
+            HttpHandler::Sql(_) => panic!("expected a reducer"),
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let routes = HttpRoutes::new(vec![
+            route(HttpMethod::Get, "/players/:id", "player"),
+            route(HttpMethod::Get, "/players/top", "top_players"),
+            route(HttpMethod::Post, "/players/:id/kick", "kick"),
+            route(HttpMethod::Get, "/", "index"),
+        ]);
+
+        let m = routes.find(HttpMethod::Get, "/players/42").unwrap();
+        assert_eq!(reducer_of(&m), "player");
+        assert_eq!(m.params, [("id", "42")]);
+
+        let m = routes.find(HttpMethod::Get, "players/top/").unwrap();
+        assert_eq!(reducer_of(&m), "top_players");
+        assert!(m.params.is_empty());
+
+        let m = routes.find(HttpMethod::Post, "/players/7/kick").unwrap();
+        assert_eq!(reducer_of(&m), "kick");
+        assert_eq!(m.params, [("id", "7")]);
+
+        assert_eq!(reducer_of(&routes.find(HttpMethod::Get, "").unwrap()), "index");
+        assert!(routes.find(HttpMethod::Post, "/players/42").is_none());
+        assert!(routes.find(HttpMethod::Get, "/players/42/kick").is_none());
+        assert!(routes.find(HttpMethod::Get, "/players").is_none());
+    }
+
+    #[test]
+    fn test_route_shape() {
+        assert_eq!(route_shape("/players/:id/"), route_shape("players/:name"));
+        assert_ne!(route_shape("/players/:id"), route_shape("/players/top"));
+    }
+}
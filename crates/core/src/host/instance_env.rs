@@ -1,19 +1,26 @@
+use once_cell::sync::OnceCell;
 use parking_lot::{Mutex, MutexGuard};
 use prometheus::HistogramVec;
-use spacetimedb_lib::{bsatn, ProductValue};
-use std::ops::DerefMut;
+use spacetimedb_lib::{bsatn, DataKey, IndexType, ProductValue};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
+use crate::address::Address;
 use crate::database_instance_context::DatabaseInstanceContext;
-use crate::database_logger::{BacktraceProvider, LogLevel, Record};
-use crate::db::datastore::locking_tx_datastore::MutTxId;
-use crate::db::datastore::traits::{DataRow, IndexDef};
+use crate::database_logger::{BacktraceProvider, DatabaseLogger, LogLevel, Record};
+use crate::db::datastore::locking_tx_datastore::{MutTxId, SuspendedMutTx};
+use crate::db::datastore::traits::{DataRow, IndexDef, SavepointId};
 use crate::error::{IndexError, NodesError};
+use crate::hash::Hash;
+use crate::messages::control_db::EnergyPricing;
 use crate::util::prometheus_handle::HistogramVecHandle;
 use crate::util::ResultInspectExt;
 use crate::worker_metrics::{INSTANCE_ENV_DELETE_BY_COL_EQ, INSTANCE_ENV_INSERT};
 
+use super::call_log::{CallLog, HostCallRecord};
+use super::module_host::ModuleInfo;
 use super::scheduler::{ScheduleError, ScheduledReducerId, Scheduler};
 use super::timestamp::Timestamp;
 use super::tracelog::instance_trace::TraceLog;
@@ -31,11 +38,241 @@ pub struct InstanceEnv {
     pub scheduler: Scheduler,
     pub tx: TxSlot,
     pub trace_log: Option<Arc<Mutex<TraceLog>>>,
+    pub energy: EnergyMeter,
+    pub events: EventBuffer,
+    pub return_value: ReturnSlot,
+    /// The description of the module running in the instance,
+    /// set once the host has extracted it from the module.
+    pub module_info: Arc<OnceCell<Arc<ModuleInfo>>>,
+    /// The name of the reducer running in the instance, for the call log of the database.
+    pub current_reducer: Arc<Mutex<String>>,
+    /// Whether the reducer running in the instance sent messages to other databases,
+    /// for the host to have them delivered once its transaction commits.
+    pub sent_messages: Arc<AtomicBool>,
+    pub schedules: ScheduleBuffer,
+    pub logs: LogBuffer,
+}
+
+/// The energy spent by a reducer on the host's operations, at the prices set for it.
+///
+/// The energy is kept until the host takes it from the reducer's budget,
+/// which is metered by the wasm runtime in points.
+#[derive(Clone, Default)]
+pub struct EnergyMeter {
+    inner: Arc<Mutex<MeterState>>,
+}
+
+#[derive(Default)]
+struct MeterState {
+    pricing: EnergyPricing,
+    /// The energy spent since it was last taken.
+    pending: u64,
+}
+
+impl EnergyMeter {
+    /// Prices the host's operations at `pricing` from now on, e.g., for the next reducer call.
+    pub fn reset(&self, pricing: EnergyPricing) {
+        *self.inner.lock() = MeterState { pricing, pending: 0 };
+    }
+
+    pub fn pricing(&self) -> EnergyPricing {
+        self.inner.lock().pricing
+    }
+
+    fn charge(&self, amount: usize, price: impl FnOnce(&EnergyPricing) -> u64) {
+        let mut state = self.inner.lock();
+        let cost = price(&state.pricing).saturating_mul(amount as u64);
+        state.pending = state.pending.saturating_add(cost);
+    }
+
+    fn charge_bytes_written(&self, bytes: usize) {
+        self.charge(bytes, |p| p.per_byte_written)
+    }
+
+    fn charge_rows_scanned(&self, rows: usize) {
+        self.charge(rows, |p| p.per_row_scanned)
+    }
+
+    /// Takes the energy spent since the last call, in points of the wasm meter, rounded up.
+    pub fn take_pending_points(&self) -> u64 {
+        let mut state = self.inner.lock();
+        let pending = std::mem::take(&mut state.pending);
+        let per_point = state.pricing.per_instruction.max(1);
+        pending / per_point + u64::from(pending % per_point != 0)
+    }
+}
+
+/// The events emitted by the reducer running in an instance,
+/// as the name of each event's type and the event encoded as BSATN.
+///
+/// The events are kept until the host takes them,
+/// to deliver them with the reducer's transaction if it commits.
+#[derive(Clone, Default)]
+pub struct EventBuffer {
+    inner: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+}
+
+impl EventBuffer {
+    fn push(&self, name: String, data: Vec<u8>) {
+        self.inner.lock().push((name, data));
+    }
+
+    /// Takes the events emitted since the last call.
+    pub fn take(&self) -> Vec<(String, Vec<u8>)> {
+        std::mem::take(&mut *self.inner.lock())
+    }
+}
+
+/// The value returned by the reducer running in an instance, encoded as BSATN.
+///
+/// The value is kept until the host takes it,
+/// to deliver it to the caller with the reducer's transaction if it commits.
+#[derive(Clone, Default)]
+pub struct ReturnSlot {
+    inner: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl ReturnSlot {
+    fn set(&self, data: Vec<u8>) {
+        *self.inner.lock() = Some(data);
+    }
+
+    /// Takes the value set since the last call, if any.
+    pub fn take(&self) -> Option<Vec<u8>> {
+        self.inner.lock().take()
+    }
+}
+
+/// The reducers scheduled and canceled by the reducer running in an instance.
+///
+/// The operations are kept until the host applies them to the scheduler,
+/// once the reducer's transaction commits, or discards them.
+#[derive(Clone, Default)]
+pub struct ScheduleBuffer {
+    inner: Arc<Mutex<Vec<ScheduleOp>>>,
+}
+
+enum ScheduleOp {
+    Schedule {
+        id: ScheduledReducerId,
+        reducer: String,
+        args: Vec<u8>,
+        at: Timestamp,
+    },
+    Cancel(ScheduledReducerId),
+}
+
+impl ScheduleBuffer {
+    fn push(&self, op: ScheduleOp) {
+        self.inner.lock().push(op);
+    }
+
+    /// Discards the operations buffered since the last call.
+    pub fn discard(&self) {
+        self.inner.lock().clear();
+    }
+
+    /// Applies the operations buffered since the last call to `scheduler`, in order.
+    pub fn apply(&self, scheduler: &Scheduler) {
+        for op in std::mem::take(&mut *self.inner.lock()) {
+            match op {
+                ScheduleOp::Schedule { id, reducer, args, at } => {
+                    if let Err(e) = scheduler.schedule_reserved(id, reducer, args, at) {
+                        log::error!("scheduling a reducer failed: {e}");
+                    }
+                }
+                ScheduleOp::Cancel(id) => scheduler.cancel(id),
+            }
+        }
+    }
+}
+
+/// The lines logged by the reducer running in an instance,
+/// while the host [holds](Self::hold) them back from the database's log.
+///
+/// The host holds the lines of a reducer run that may be discarded and run again,
+/// so that only the lines of the run that isn't discarded end up in the log.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl LogBuffer {
+    /// Holds back the lines logged from now on, discarding any held before.
+    pub fn hold(&self) {
+        *self.inner.lock() = Some(Vec::new());
+    }
+
+    /// Stops holding back lines, returning the lines held since the last [`Self::hold`].
+    pub fn release(&self) -> Vec<String> {
+        self.inner.lock().take().unwrap_or_default()
+    }
+
+    /// Holds back `line` if lines are being held, or else returns it.
+    fn push(&self, line: String) -> Option<String> {
+        match &mut *self.inner.lock() {
+            Some(lines) => {
+                lines.push(line);
+                None
+            }
+            None => Some(line),
+        }
+    }
+}
+
+/// A host call made by the reducer running in an instance, timed for the call log of the database.
+///
+/// The call is recorded once dropped, if the log was on when it was made,
+/// as failed unless it was [finished](Self::finish) first.
+struct LoggedCall {
+    started: Option<LoggedCallStart>,
+    call: &'static str,
+    table_id: Option<u32>,
+    rows: usize,
+    bytes: usize,
+    failed: bool,
+}
+
+struct LoggedCallStart {
+    instant: Instant,
+    timestamp: Timestamp,
+    log: Arc<CallLog>,
+    reducer: String,
+}
+
+impl LoggedCall {
+    /// Marks the call as done, having read or written `rows` rows and passed `bytes` bytes.
+    fn finish(mut self, rows: usize, bytes: usize) {
+        self.rows = rows;
+        self.bytes = bytes;
+        self.failed = false;
+    }
+}
+
+impl Drop for LoggedCall {
+    fn drop(&mut self) {
+        let Some(start) = self.started.take() else {
+            return;
+        };
+        start.log.record(HostCallRecord {
+            timestamp: start.timestamp.0,
+            reducer: start.reducer,
+            call: self.call,
+            table_id: self.table_id,
+            table_name: None,
+            rows: self.rows as u64,
+            bytes: self.bytes as u64,
+            duration_micros: start.instant.elapsed().as_micros() as u64,
+            failed: self.failed,
+        });
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct TxSlot {
-    inner: Arc<Mutex<Option<MutTxId>>>,
+    inner: Arc<Mutex<Option<SlotTx>>>,
+    /// Whether the transaction in the slot is read-only, in which case writes are rejected.
+    read_only: Arc<AtomicBool>,
 }
 
 // Generic 'instance environment' delegated to from various host types.
@@ -50,6 +287,14 @@ impl InstanceEnv {
             scheduler,
             tx: TxSlot::default(),
             trace_log,
+            energy: EnergyMeter::default(),
+            events: EventBuffer::default(),
+            return_value: ReturnSlot::default(),
+            module_info: Arc::default(),
+            current_reducer: Arc::default(),
+            sent_messages: Arc::default(),
+            schedules: ScheduleBuffer::default(),
+            logs: LogBuffer::default(),
         }
     }
 
@@ -60,21 +305,75 @@ impl InstanceEnv {
         args: Vec<u8>,
         time: Timestamp,
     ) -> Result<ScheduledReducerId, ScheduleError> {
-        self.scheduler.schedule(reducer, args, time)
+        if self.tx.is_read_only() {
+            return Err(ScheduleError::ReadOnlyTransaction);
+        }
+        let id = self.scheduler.reserve_id()?;
+        self.schedules.push(ScheduleOp::Schedule {
+            id,
+            reducer,
+            args,
+            at: time,
+        });
+        Ok(id)
     }
 
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(&self, id: ScheduledReducerId) {
-        self.scheduler.cancel(id)
+    pub fn cancel_reducer(&self, id: ScheduledReducerId) -> Result<(), ScheduleError> {
+        if self.tx.is_read_only() {
+            return Err(ScheduleError::ReadOnlyTransaction);
+        }
+        self.schedules.push(ScheduleOp::Cancel(id));
+        Ok(())
     }
 
     fn get_tx(&self) -> Result<impl DerefMut<Target = MutTxId> + '_, GetTxError> {
         self.tx.get()
     }
 
+    /// Like [`Self::get_tx`], but errors when the transaction is read-only.
+    fn get_tx_for_write(&self) -> Result<impl DerefMut<Target = MutTxId> + '_, NodesError> {
+        if self.tx.is_read_only() {
+            return Err(NodesError::ReadOnlyTransaction);
+        }
+        Ok(self.get_tx()?)
+    }
+
+    /// Emits an event of the type `name`, encoded as BSATN in `data`,
+    /// which is charged for as bytes written, as it's sent to the subscribed clients.
+    #[tracing::instrument(skip_all)]
+    pub fn emit_event(&self, name: String, data: Vec<u8>) {
+        self.energy.charge_bytes_written(data.len());
+        self.events.push(name, data);
+    }
+
+    /// Sets the value the running reducer returns to its caller, encoded as BSATN in `data`,
+    /// replacing any value set before.
+    /// It's charged for as bytes written, as it's sent to the caller.
+    #[tracing::instrument(skip_all)]
+    pub fn set_return_value(&self, data: Vec<u8>) {
+        self.energy.charge_bytes_written(data.len());
+        self.return_value.set(data);
+    }
+
+    /// Returns the arguments of the reducer `name`, as a BSATN encoded `ProductType`,
+    /// with the type references into the module's typespace resolved,
+    /// unless the arguments are of a recursive type.
+    #[tracing::instrument(skip_all)]
+    pub fn describe_reducer(&self, name: &str) -> Result<Vec<u8>, NodesError> {
+        let info = self.module_info.get().ok_or(NodesError::ReducerNotFound)?;
+        let reducer = info.reducers.get(name).ok_or(NodesError::ReducerNotFound)?;
+        let args = ProductType::new(reducer.args.clone());
+        let args = info.typespace.with_type(&args).resolve_refs().unwrap_or(args);
+        Ok(bsatn::to_vec(&args).unwrap())
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn console_log(&self, level: LogLevel, record: &Record, bt: &dyn BacktraceProvider) {
-        self.dbic.logger.lock().unwrap().write(level, record, bt);
+        let line = DatabaseLogger::format_line(level, record, bt);
+        if let Some(line) = self.logs.push(line) {
+            self.dbic.logger.lock().unwrap().write_line(line);
+        }
         log::trace!("MOD({}): {}", self.dbic.address.to_abbreviated_hex(), record.message);
     }
 
@@ -94,11 +393,32 @@ impl InstanceEnv {
         }
     }
 
+    /// Starts timing the host call `call` on the table `table_id`, if any,
+    /// for the call log of the database, if it's on.
+    fn log_call(&self, call: &'static str, table_id: Option<u32>) -> LoggedCall {
+        let log = &self.dbic.call_log;
+        let started = log.is_enabled().then(|| LoggedCallStart {
+            instant: Instant::now(),
+            timestamp: Timestamp::now(),
+            log: log.clone(),
+            reducer: self.current_reducer.lock().clone(),
+        });
+        LoggedCall {
+            started,
+            call,
+            table_id,
+            rows: 0,
+            bytes: 0,
+            failed: true,
+        }
+    }
+
     pub fn insert(&self, table_id: u32, buffer: &[u8]) -> Result<ProductValue, NodesError> {
+        let call = self.log_call("insert", Some(table_id));
         let measure = self.measure(table_id, &INSTANCE_ENV_INSERT);
 
         let stdb = &*self.dbic.relational_db;
-        let tx = &mut *self.get_tx()?;
+        let tx = &mut *self.get_tx_for_write()?;
 
         let ret = stdb
             .insert_bytes_as_row(tx, table_id, buffer)
@@ -106,6 +426,7 @@ impl InstanceEnv {
                 crate::error::DBError::Index(IndexError::UniqueConstraintViolation {
                     constraint_name: _,
                     table_name: _,
+                    col_id: _,
                     col_name: _,
                     value: _,
                 }) => {}
@@ -118,6 +439,7 @@ impl InstanceEnv {
                     }
                 }
             })?;
+        self.energy.charge_bytes_written(buffer.len());
 
         self.with_trace_log(|l| {
             l.insert(
@@ -128,6 +450,7 @@ impl InstanceEnv {
             )
         });
 
+        call.finish(1, buffer.len());
         Ok(ret)
     }
 
@@ -182,10 +505,11 @@ impl InstanceEnv {
     /// Returns an error if no columns were deleted or if the column wasn't found.
     #[tracing::instrument(skip_all)]
     pub fn delete_by_col_eq(&self, table_id: u32, col_id: u32, value: &[u8]) -> Result<u32, NodesError> {
+        let call = self.log_call("delete_by_col_eq", Some(table_id));
         let measure = self.measure(table_id, &INSTANCE_ENV_DELETE_BY_COL_EQ);
 
         let stdb = &*self.dbic.relational_db;
-        let tx = &mut *self.get_tx()?;
+        let tx = &mut *self.get_tx_for_write()?;
 
         // Interpret the `value` using the schema of the column.
         let eq_value = stdb.decode_column(tx, table_id, col_id, value)?;
@@ -211,49 +535,45 @@ impl InstanceEnv {
             )
         });
 
+        call.finish(count as usize, value.len());
         Ok(count)
     }
 
-    /*
+    /// Deletes all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` is within the range from `start`, inclusive, to `end`, exclusive.
+    ///
+    /// The bounds are decoded to `AlgebraicValue`s according to the column's schema,
+    /// and compared to the column by `Ord for AlgebraicValue`.
+    ///
+    /// This call was dropped from the ABI in 3.0, and is only linked for modules built against the previous ABI.
+    /// Returns an error if no rows were deleted or if the column wasn't found.
     #[tracing::instrument(skip_all)]
-    pub fn delete_range(
-        &self,
-        table_id: u32,
-        col_id: u32,
-        start_buffer: &[u8],
-        end_buffer: &[u8],
-    ) -> Result<u32, NodesError> {
-        let measure = self.measure(table_id, &INSTANCE_ENV_DELETE_RANGE);
-
+    pub fn delete_range(&self, table_id: u32, col_id: u32, start: &[u8], end: &[u8]) -> Result<u32, NodesError> {
+        let call = self.log_call("delete_range", Some(table_id));
+        let bounds_len = start.len() + end.len();
         let stdb = &*self.dbic.relational_db;
-        let tx = &mut *self.get_tx()?;
-
-        let col_type = stdb.schema_for_column(tx, table_id, col_id)?;
+        let tx = &mut *self.get_tx_for_write()?;
 
-        let decode = |b: &[u8]| AlgebraicValue::decode(&col_type, &mut &b[..]).map_err(NodesError::DecodeValue);
-        let start = decode(start_buffer)?;
-        let end = decode(end_buffer)?;
+        // Interpret the bounds using the schema of the column.
+        let start = stdb.decode_column(tx, table_id, col_id, start)?;
+        let end = stdb.decode_column(tx, table_id, col_id, end)?;
 
-        let range = stdb.range_scan(tx, table_id, col_id, start..end)?;
-        let range = range.map(|x| stdb.data_to_owned(x).into()).collect::<Vec<_>>();
+        let range = stdb
+            .iter_by_col_range(tx, table_id, col_id, start..end)?
+            .map(|row| stdb.data_to_owned(row).into())
+            .collect::<Vec<ProductValue>>();
 
-        let count = stdb.delete_in(tx, table_id, range)?.ok_or(NodesError::RangeNotFound)?;
-
-        self.with_trace_log(|l| {
-            l.delete_range(
-                measure.start_instant.unwrap(),
-                measure.elapsed(),
-                table_id,
-                col_id,
-                start_buffer.into(),
-                end_buffer.into(),
-                count,
-            )
-        });
+        let count = stdb
+            .delete_by_rel(tx, table_id, range)
+            .inspect_err_(|e| log::error!("delete_range(table_id: {table_id}): {e}"))?
+            .filter(|&count| count > 0)
+            .ok_or(NodesError::RangeNotFound)?;
 
+        call.finish(count as usize, bounds_len);
         Ok(count)
     }
 
+    /*
     #[tracing::instrument(skip_all)]
     pub fn create_table(&self, _table_name: &str, _schema_bytes: &[u8]) -> Result<u32, NodesError> {
         // let now = SystemTime::now();
@@ -321,15 +641,17 @@ impl InstanceEnv {
         let now = SystemTime::now();
 
         let stdb = &*self.dbic.relational_db;
-        let tx = &mut *self.get_tx()?;
+        let tx = &mut *self.get_tx_for_write()?;
 
         // TODO(george) This check should probably move towards src/db/index, but right
         // now the API is pretty hardwired towards btrees.
         //
         // TODO(george) Dedup the constant here.
-        match index_type {
-            0 => (),
+        let ty = match index_type {
+            0 => IndexType::BTree,
             1 => todo!("Hash indexes not yet supported"),
+            2 => IndexType::FullText,
+            3 => IndexType::Spatial,
             _ => return Err(NodesError::BadIndexType(index_type)),
         };
 
@@ -349,6 +671,7 @@ impl InstanceEnv {
             col_id,
             name: index_name.clone(),
             is_unique,
+            index_type: ty,
         };
 
         stdb.create_index(tx, index)?;
@@ -369,6 +692,7 @@ impl InstanceEnv {
     /// according to the column's schema and then `Ord for AlgebraicValue`.
     #[tracing::instrument(skip_all)]
     pub fn iter_by_col_eq(&self, table_id: u32, col_id: u32, value: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("iter_by_col_eq", Some(table_id));
         let stdb = &*self.dbic.relational_db;
         let tx = &mut *self.get_tx()?;
 
@@ -379,9 +703,66 @@ impl InstanceEnv {
         // Concatenate and return these rows using bsatn encoding.
         let results = stdb.iter_by_col_eq(tx, table_id, col_id, &value)?;
         let mut bytes = Vec::new();
+        let mut rows = 0;
+        for result in results {
+            bsatn::to_writer(&mut bytes, result.view()).unwrap();
+            rows += 1;
+        }
+        self.energy.charge_rows_scanned(rows);
+        call.finish(rows, bytes.len());
+        Ok(bytes)
+    }
+
+    /// Finds all rows in the table identified by `table_id`
+    /// where the string column identified by `col_id` matches the full-text `query`.
+    ///
+    /// These rows are returned concatenated with each row bsatn encoded.
+    ///
+    /// Matching is defined by [`spacetimedb_lib::fulltext::matches`].
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_match(&self, table_id: u32, col_id: u32, query: &str) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("iter_by_col_match", Some(table_id));
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let results = stdb.iter_by_col_match(tx, table_id, col_id, query)?;
+        let mut bytes = Vec::new();
+        let mut rows = 0;
         for result in results {
             bsatn::to_writer(&mut bytes, result.view()).unwrap();
+            rows += 1;
         }
+        self.energy.charge_rows_scanned(rows);
+        call.finish(rows, bytes.len());
+        Ok(bytes)
+    }
+
+    /// Finds all rows in the table identified by `table_id`
+    /// where the point column identified by `col_id` is within the box from `min` to `max`.
+    ///
+    /// These rows are returned concatenated with each row bsatn encoded.
+    ///
+    /// The corners are decoded to `AlgebraicValue`s according to the column's schema,
+    /// and being within the box is defined by [`spacetimedb_lib::spatial::in_box`].
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_box(&self, table_id: u32, col_id: u32, min: &[u8], max: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("iter_by_col_box", Some(table_id));
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        // Interpret the corners using the schema of the column.
+        let min = stdb.decode_column(tx, table_id, col_id, min)?;
+        let max = stdb.decode_column(tx, table_id, col_id, max)?;
+
+        let results = stdb.iter_by_col_box(tx, table_id, col_id, &min, &max)?;
+        let mut bytes = Vec::new();
+        let mut rows = 0;
+        for result in results {
+            bsatn::to_writer(&mut bytes, result.view()).unwrap();
+            rows += 1;
+        }
+        self.energy.charge_rows_scanned(rows);
+        call.finish(rows, bytes.len());
         Ok(bytes)
     }
 
@@ -392,6 +773,8 @@ impl InstanceEnv {
         // Cheap Arc clones to untie the returned iterator from our own lifetime.
         let relational_db = self.dbic.relational_db.clone();
         let tx = self.tx.clone();
+        let energy = self.energy.clone();
+        let call = self.log_call("iter", Some(table_id));
 
         // For now, just send buffers over a certain fixed size.
         fn should_yield_buf(buf: &Vec<u8>) -> bool {
@@ -409,13 +792,20 @@ impl InstanceEnv {
             yield_!(buf);
 
             let mut buf = Vec::new();
+            let mut rows = 0;
+            let mut bytes = 0;
             for row in stdb.iter(tx, table_id)? {
                 if should_yield_buf(&buf) {
+                    bytes += buf.len();
                     yield_!(buf);
                     buf = Vec::new();
                 }
                 row.view().encode(&mut buf);
+                rows += 1;
             }
+            energy.charge_rows_scanned(rows);
+            bytes += buf.len();
+            call.finish(rows, bytes);
             if !buf.is_empty() {
                 yield_!(buf)
             }
@@ -439,6 +829,8 @@ impl InstanceEnv {
     pub fn iter_filtered(&self, table_id: u32, filter: &[u8]) -> Result<impl Iterator<Item = Vec<u8>>, NodesError> {
         use spacetimedb_lib::filter;
 
+        let call = self.log_call("iter_filtered", Some(table_id));
+
         fn filter_to_column_op(table_name: &str, filter: filter::Expr) -> ColumnOp {
             match filter {
                 filter::Expr::Cmp(filter::Cmp {
@@ -487,14 +879,317 @@ impl InstanceEnv {
             Code::Table(table) => table,
             _ => unreachable!("query should always return a table"),
         };
-        Ok(std::iter::once(bsatn::to_vec(&row_type))
+        self.energy.charge_rows_scanned(results.data.len());
+        let rows = results.data.len();
+        let bufs = std::iter::once(bsatn::to_vec(&row_type))
             .chain(results.data.into_iter().map(|row| bsatn::to_vec(&row)))
-            .map(|bytes| bytes.expect("encoding algebraic values should never fail")))
+            .map(|bytes| bytes.expect("encoding algebraic values should never fail"))
+            .collect::<Vec<_>>();
+        call.finish(rows, bufs.iter().map(Vec::len).sum());
+        Ok(bufs.into_iter())
+    }
+
+    /// Like [`Self::iter`], but with each row projected onto the columns `cols`, in that order,
+    /// so that only those columns are encoded.
+    ///
+    /// The first buffer is the schema of the projection,
+    /// and the rows follow in buffers of at least 64 KiB, but for the last.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_projected(&self, table_id: u32, cols: &[u8]) -> Result<impl Iterator<Item = Vec<u8>>, NodesError> {
+        const SIZE: usize = 64 * 1024;
+
+        let call = self.log_call("iter_projected", Some(table_id));
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.tx.get()?;
+
+        let row_type = stdb.row_schema_for_table(tx, table_id)?;
+        let elements = cols
+            .iter()
+            .map(|&col| row_type.elements.get(col as usize).cloned())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(NodesError::BadColumn)?;
+        let mut schema = Vec::new();
+        ProductType::new(elements).encode(&mut schema);
+
+        let mut bufs = vec![schema];
+        let mut buf = Vec::new();
+        let mut rows = 0;
+        for row in stdb.iter(tx, table_id)? {
+            if buf.len() >= SIZE {
+                bufs.push(std::mem::take(&mut buf));
+            }
+            let row = row.view();
+            for &col in cols {
+                bsatn::to_writer(&mut buf, &row.elements[col as usize]).unwrap();
+            }
+            rows += 1;
+        }
+        self.energy.charge_rows_scanned(rows);
+        if !buf.is_empty() {
+            bufs.push(buf);
+        }
+        call.finish(rows, bufs.iter().map(Vec::len).sum());
+        Ok(bufs.into_iter())
+    }
+
+    /// Moves rows from the table `src_table_id` to the table `dst_table_id`,
+    /// deleting the rows in `src_rows` and inserting those in `dst_rows`,
+    /// both of which are BSATN encoded rows, concatenated.
+    ///
+    /// Either all the rows are moved or, on error, none are.
+    /// Errors if a row of `src_rows` isn't in its table,
+    /// or if a row of `dst_rows` can't be inserted, e.g., as it violates a unique constraint.
+    ///
+    /// Returns the number of rows deleted from `src_table_id`.
+    #[tracing::instrument(skip_all)]
+    pub fn move_rows(
+        &self,
+        src_table_id: u32,
+        src_rows: &[u8],
+        dst_table_id: u32,
+        dst_rows: &[u8],
+    ) -> Result<u32, NodesError> {
+        let call = self.log_call("move_rows", Some(src_table_id));
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx_for_write()?;
+
+        let decode_rows = |tx: &MutTxId, table_id: u32, mut bytes: &[u8]| -> Result<Vec<ProductValue>, NodesError> {
+            let row_type = stdb.row_schema_for_table(tx, table_id)?;
+            let mut rows = Vec::new();
+            while !bytes.is_empty() {
+                rows.push(ProductValue::decode(&row_type, &mut bytes).map_err(NodesError::DecodeRow)?);
+            }
+            Ok(rows)
+        };
+        let deletes = decode_rows(tx, src_table_id, src_rows)?;
+        let inserts = decode_rows(tx, dst_table_id, dst_rows)?;
+        let count = deletes.len() as u32;
+        let rows = deletes.len() + inserts.len();
+
+        // Undo the deletes when an insert fails, so that no row is lost.
+        let savepoint = stdb.savepoint(tx);
+        let res = (|| -> Result<u32, NodesError> {
+            let deleted = stdb
+                .delete_by_rel(tx, src_table_id, deletes)
+                .inspect_err_(|e| log::error!("move_rows(src_table_id: {src_table_id}): {e}"))?;
+            if deleted != Some(count) {
+                return Err(NodesError::ColumnValueNotFound);
+            }
+            for row in inserts {
+                stdb.insert(tx, dst_table_id, row)?;
+            }
+            Ok(count)
+        })();
+        match res {
+            Ok(_) => stdb.release_savepoint(tx, savepoint)?,
+            Err(_) => stdb.rollback_to_savepoint(tx, savepoint)?,
+        }
+        self.energy.charge_bytes_written(dst_rows.len());
+        if res.is_ok() {
+            call.finish(rows, src_rows.len() + dst_rows.len());
+        }
+        res
+    }
+
+    /// Returns the offset in the commit log of the current transaction,
+    /// i.e., the number of transactions with changes committed before it.
+    ///
+    /// Each transaction that commits changes gets a distinct, and increasing, offset.
+    #[tracing::instrument(skip_all)]
+    pub fn tx_offset(&self) -> Result<u64, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        Ok(stdb.tx_offset(tx))
+    }
+
+    /// Returns the version of the table identified by `table_id`,
+    /// the number of committed transactions that changed its rows,
+    /// not counting the changes of the current transaction.
+    #[tracing::instrument(skip_all)]
+    pub fn table_version(&self, table_id: u32) -> Result<u64, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        Ok(stdb.table_version(tx, table_id)?)
+    }
+
+    /// Returns the bsatn-encoded layout of the rows stored in the table identified by `table_id`,
+    /// which [`Self::iter`] sends ahead of them.
+    #[tracing::instrument(skip_all)]
+    pub fn table_row_type(&self, table_id: u32) -> Result<Vec<u8>, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let mut buf = Vec::new();
+        stdb.row_schema_for_table(tx, table_id)?.encode(&mut buf);
+        Ok(buf)
+    }
+
+    /// Stores the blob `data` under `key`, replacing the blob stored under it before, if any.
+    ///
+    /// Returns the hash of `data`, or an error if it's over the size limit.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_put(&self, key: &str, data: &[u8]) -> Result<Hash, NodesError> {
+        let call = self.log_call("blob_put", None);
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx_for_write()?;
+
+        let hash = stdb.put_blob(tx, key, data)?;
+        self.energy.charge_bytes_written(data.len());
+        call.finish(1, data.len());
+        Ok(hash)
+    }
+
+    /// Returns the blob stored under `key`, or an error if there's none.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_get(&self, key: &str) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("blob_get", None);
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let (_, data) = stdb.get_blob(tx, key)?.ok_or(NodesError::BlobNotFound)?;
+        call.finish(1, data.len());
+        Ok(data)
+    }
+
+    /// Deletes the blob stored under `key`, or errors if there's none.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_delete(&self, key: &str) -> Result<(), NodesError> {
+        let call = self.log_call("blob_delete", None);
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx_for_write()?;
+
+        if !stdb.delete_blob(tx, key)? {
+            return Err(NodesError::BlobNotFound);
+        }
+        call.finish(1, 0);
+        Ok(())
+    }
+
+    /// Sends a call of the reducer `reducer` of the database at `target` with the BSATN encoded `args`,
+    /// to be delivered once, if and when the current transaction commits.
+    #[tracing::instrument(skip_all)]
+    pub fn send_message(&self, target: Address, reducer: &str, args: &[u8]) -> Result<(), NodesError> {
+        let call = self.log_call("send_message", None);
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx_for_write()?;
+
+        stdb.enqueue_message(tx, target, reducer, args, Timestamp::now().0)?;
+        self.sent_messages.store(true, Ordering::Relaxed);
+        self.energy.charge_bytes_written(reducer.len() + args.len());
+        call.finish(1, args.len());
+        Ok(())
+    }
+
+    /// Stores `data` out of the rows that will reference it through a `LargeBytesRef` column,
+    /// returning the encoded key they hold.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_put(&self, data: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("large_bytes_put", None);
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx_for_write()?;
+
+        let key = stdb.put_large_value(tx, data)?;
+        self.energy.charge_bytes_written(data.len());
+        call.finish(1, data.len());
+        Ok(key.to_bytes())
+    }
+
+    /// Returns the value stored under the encoded `key`, or an error if there's none.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_get(&self, key: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let call = self.log_call("large_bytes_get", None);
+        let key = DataKey::decode(&mut &key[..]).map_err(NodesError::DecodeValue)?;
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let data = stdb.get_large_value(tx, key)?.ok_or(NodesError::LargeValueNotFound)?;
+        call.finish(1, data.len());
+        Ok(data)
+    }
+
+    /// Takes a savepoint of the changes made so far in the current transaction,
+    /// returning the savepoint's id.
+    #[tracing::instrument(skip_all)]
+    pub fn savepoint(&self) -> Result<u32, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let SavepointId(id) = stdb.savepoint(tx);
+        Ok(id)
+    }
+
+    /// Undoes the changes made in the current transaction since the savepoint `id` was taken,
+    /// releasing it and all the savepoints taken after it.
+    ///
+    /// Errors if there's no such savepoint, e.g., because it was already released.
+    #[tracing::instrument(skip_all)]
+    pub fn rollback_to_savepoint(&self, id: u32) -> Result<(), NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        stdb.rollback_to_savepoint(tx, SavepointId(id))?;
+        Ok(())
+    }
+
+    /// Releases the savepoint `id` and all the savepoints taken after it,
+    /// keeping the changes made since.
+    ///
+    /// Errors if there's no such savepoint, e.g., because it was already released.
+    #[tracing::instrument(skip_all)]
+    pub fn release_savepoint(&self, id: u32) -> Result<(), NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        stdb.release_savepoint(tx, SavepointId(id))?;
+        Ok(())
+    }
+}
+
+/// A transaction in a [`TxSlot`].
+enum SlotTx {
+    /// The transaction holds the lock on the datastore for as long as it's in the slot.
+    Held(MutTxId),
+    /// The transaction only holds the lock on the datastore during calls into the host,
+    /// so that transactions in other slots can run while the module is running.
+    Suspended(SuspendedMutTx),
+    /// A suspended transaction during a call into the host.
+    Resumed(MutTxId),
+}
+
+impl SlotTx {
+    fn into_tx(self) -> MutTxId {
+        match self {
+            Self::Held(tx) | Self::Resumed(tx) => tx,
+            Self::Suspended(tx) => tx.resume(),
+        }
+    }
+
+    fn into_suspended(self) -> SuspendedMutTx {
+        match self {
+            Self::Held(tx) | Self::Resumed(tx) => tx.suspend(),
+            Self::Suspended(tx) => tx,
+        }
     }
 }
 
 impl TxSlot {
     pub fn set<T>(&self, tx: MutTxId, f: impl FnOnce() -> T) -> (MutTxId, T) {
+        let (tx, res) = self.set_slot_tx(SlotTx::Held(tx), f);
+        (tx.into_tx(), res)
+    }
+
+    /// Like [`Self::set`], but the transaction is [suspended](MutTxId::suspend) while `f` runs,
+    /// except during calls to [`Self::get`], and is returned suspended.
+    ///
+    /// The transaction may then conflict with transactions committed in the meantime.
+    pub fn set_suspended<T>(&self, tx: MutTxId, f: impl FnOnce() -> T) -> (SuspendedMutTx, T) {
+        let (tx, res) = self.set_slot_tx(SlotTx::Suspended(tx.suspend()), f);
+        (tx.into_suspended(), res)
+    }
+
+    fn set_slot_tx<T>(&self, tx: SlotTx, f: impl FnOnce() -> T) -> (SlotTx, T) {
         let prev = self.inner.lock().replace(tx);
         assert!(prev.is_none(), "reentrant TxSlot::set");
         let remove_tx = || self.inner.lock().take();
@@ -506,8 +1201,58 @@ impl TxSlot {
         (tx, res)
     }
 
+    /// Like [`Self::set`], but the transaction is read-only while `f` runs.
+    pub fn set_read_only<T>(&self, tx: MutTxId, f: impl FnOnce() -> T) -> (MutTxId, T) {
+        self.read_only.store(true, Ordering::Relaxed);
+        scopeguard::defer! { self.read_only.store(false, Ordering::Relaxed); }
+        self.set(tx, f)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
     pub fn get(&self) -> Result<impl DerefMut<Target = MutTxId> + '_, GetTxError> {
-        MutexGuard::try_map(self.inner.lock(), |map| map.as_mut()).map_err(|_| GetTxError)
+        let mut slot = self.inner.lock();
+        match slot.take().ok_or(GetTxError)? {
+            SlotTx::Suspended(tx) => *slot = Some(SlotTx::Resumed(tx.resume())),
+            tx => *slot = Some(tx),
+        }
+        Ok(TxGuard { slot })
+    }
+}
+
+/// Gives access to the transaction in a [`TxSlot`],
+/// suspending it again when dropped if it was [suspended](TxSlot::set_suspended).
+struct TxGuard<'a> {
+    slot: MutexGuard<'a, Option<SlotTx>>,
+}
+
+impl Deref for TxGuard<'_> {
+    type Target = MutTxId;
+    fn deref(&self) -> &MutTxId {
+        match self.slot.as_ref() {
+            Some(SlotTx::Held(tx) | SlotTx::Resumed(tx)) => tx,
+            _ => unreachable!("the transaction is resumed while guarded"),
+        }
+    }
+}
+
+impl DerefMut for TxGuard<'_> {
+    fn deref_mut(&mut self) -> &mut MutTxId {
+        match self.slot.as_mut() {
+            Some(SlotTx::Held(tx) | SlotTx::Resumed(tx)) => tx,
+            _ => unreachable!("the transaction is resumed while guarded"),
+        }
+    }
+}
+
+impl Drop for TxGuard<'_> {
+    fn drop(&mut self) {
+        *self.slot = match self.slot.take() {
+            Some(SlotTx::Resumed(tx)) => Some(SlotTx::Suspended(tx.suspend())),
+            tx => tx,
+        };
     }
 }
 
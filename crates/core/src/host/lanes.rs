@@ -0,0 +1,200 @@
+//! The queue in which the calls into a module wait to be run, in one [`Lane`] per priority.
+//!
+//! The module runs the call at the front of the highest lane with any,
+//! but once a call has been overtaken by [`MAX_OVERTAKEN`] calls from higher lanes, it runs next,
+//! so that a steady stream of high priority calls delays the lower ones, but doesn't starve them.
+
+use spacetimedb_lib::Lane;
+use tokio::sync::mpsc;
+
+/// The number of lanes.
+const LANES: usize = 3;
+
+/// How many calls from higher lanes may run ahead of a call waiting at the front of its lane.
+const MAX_OVERTAKEN: u32 = 16;
+
+/// Returns a queue holding up to `capacity` calls in each lane.
+pub(super) fn channel<T>(capacity: usize) -> (LaneSender<T>, LaneReceiver<T>) {
+    let (high_tx, high_rx) = mpsc::channel(capacity);
+    let (normal_tx, normal_rx) = mpsc::channel(capacity);
+    let (low_tx, low_rx) = mpsc::channel(capacity);
+    let tx = LaneSender {
+        txs: [high_tx, normal_tx, low_tx],
+    };
+    let rx = LaneReceiver {
+        rxs: [high_rx, normal_rx, low_rx],
+        heads: [None, None, None],
+        overtaken: [0; LANES],
+    };
+    (tx, rx)
+}
+
+#[derive(Debug)]
+pub(super) struct LaneSender<T> {
+    txs: [mpsc::Sender<T>; LANES],
+}
+
+// Not derived, as that would require `T: Clone`.
+impl<T> Clone for LaneSender<T> {
+    fn clone(&self) -> Self {
+        Self { txs: self.txs.clone() }
+    }
+}
+
+impl<T> LaneSender<T> {
+    /// Returns the sending end of `lane`.
+    pub fn lane(&self, lane: Lane) -> &mpsc::Sender<T> {
+        &self.txs[lane as usize]
+    }
+
+    /// Waits for the receiver to be closed or dropped.
+    pub async fn closed(&self) {
+        // The lanes are closed together, so any one of them will do.
+        self.lane(Lane::Normal).closed().await
+    }
+
+    pub fn downgrade(&self) -> WeakLaneSender<T> {
+        let [high, normal, low] = &self.txs;
+        WeakLaneSender {
+            txs: [high.downgrade(), normal.downgrade(), low.downgrade()],
+        }
+    }
+}
+
+pub(super) struct WeakLaneSender<T> {
+    txs: [mpsc::WeakSender<T>; LANES],
+}
+
+impl<T> WeakLaneSender<T> {
+    pub fn upgrade(&self) -> Option<LaneSender<T>> {
+        let [high, normal, low] = &self.txs;
+        Some(LaneSender {
+            txs: [high.upgrade()?, normal.upgrade()?, low.upgrade()?],
+        })
+    }
+}
+
+pub(super) struct LaneReceiver<T> {
+    rxs: [mpsc::Receiver<T>; LANES],
+    /// The call at the front of each lane, once taken off its channel.
+    heads: [Option<T>; LANES],
+    /// How many calls from higher lanes have run while the call at the front of each lane waited.
+    overtaken: [u32; LANES],
+}
+
+impl<T> LaneReceiver<T> {
+    /// Blocks the current thread until there's a call in any lane, returning the one to run next,
+    /// or `None` if the queue has been closed and drained.
+    ///
+    /// Like [`mpsc::Receiver::blocking_recv`], this panics if called within an asynchronous context.
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        loop {
+            for (rx, head) in self.rxs.iter_mut().zip(&mut self.heads) {
+                if head.is_none() {
+                    *head = rx.try_recv().ok();
+                }
+            }
+            if let Some(lane) = self.next_lane() {
+                return self.take(lane);
+            }
+
+            // Every lane is empty, so wait for a call in any of them,
+            // and then look again, as others may have come in meanwhile.
+            let [high, normal, low] = &mut self.rxs;
+            let (lane, call) = tokio::runtime::Handle::current().block_on(async {
+                tokio::select! {
+                    Some(call) = high.recv() => Some((Lane::High, call)),
+                    Some(call) = normal.recv() => Some((Lane::Normal, call)),
+                    Some(call) = low.recv() => Some((Lane::Low, call)),
+                    else => None,
+                }
+            })?;
+            self.heads[lane as usize] = Some(call);
+        }
+    }
+
+    /// Closes every lane, without dropping the calls already in them.
+    pub fn close(&mut self) {
+        self.rxs.iter_mut().for_each(mpsc::Receiver::close);
+    }
+
+    /// Returns the lane to run the next call from: the highest one whose call has been overtaken too often,
+    /// if any, or else the highest one with a call.
+    fn next_lane(&self) -> Option<usize> {
+        let waiting = || (0..LANES).filter(|&lane| self.heads[lane].is_some());
+        waiting()
+            .find(|&lane| self.overtaken[lane] >= MAX_OVERTAKEN)
+            .or_else(|| waiting().next())
+    }
+
+    /// Takes the call at the front of `lane`, counting it against those waiting in lower lanes.
+    fn take(&mut self, lane: usize) -> Option<T> {
+        for lower in lane + 1..LANES {
+            if self.heads[lower].is_some() {
+                self.overtaken[lower] += 1;
+            }
+        }
+        self.overtaken[lane] = 0;
+        self.heads[lane].take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_all(tx: &LaneSender<(Lane, usize)>, lane: Lane, n: usize) {
+        for i in 0..n {
+            tx.lane(lane).try_send((lane, i)).unwrap();
+        }
+    }
+
+    fn recv_all(mut rx: LaneReceiver<(Lane, usize)>) -> Vec<(Lane, usize)> {
+        rx.close();
+        std::iter::from_fn(|| rx.blocking_recv()).collect()
+    }
+
+    #[test]
+    fn test_higher_lanes_first() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+        let (tx, rx) = channel(8);
+        send_all(&tx, Lane::Low, 2);
+        send_all(&tx, Lane::Normal, 2);
+        send_all(&tx, Lane::High, 2);
+
+        let lanes: Vec<_> = recv_all(rx).into_iter().map(|(lane, _)| lane).collect();
+        let expected = [Lane::High, Lane::High, Lane::Normal, Lane::Normal, Lane::Low, Lane::Low];
+        assert_eq!(lanes, expected);
+    }
+
+    #[test]
+    fn test_no_starvation() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+        let (tx, rx) = channel(64);
+        send_all(&tx, Lane::Low, 1);
+        send_all(&tx, Lane::High, 40);
+
+        let calls = recv_all(rx);
+        assert_eq!(calls.len(), 41);
+        let low = calls.iter().position(|&(lane, _)| lane == Lane::Low).unwrap();
+        assert_eq!(low, MAX_OVERTAKEN as usize);
+        // The high lane is still run in order.
+        let high: Vec<_> = calls
+            .iter()
+            .filter(|(lane, _)| *lane == Lane::High)
+            .map(|&(_, i)| i)
+            .collect();
+        assert_eq!(high, (0..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_waits_for_a_call() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (tx, mut rx) = channel(8);
+        let recv = rt.spawn_blocking(move || rx.blocking_recv());
+        rt.block_on(tx.lane(Lane::Low).send((Lane::Low, 0))).unwrap();
+        assert_eq!(rt.block_on(recv).unwrap(), Some((Lane::Low, 0)));
+    }
+}
@@ -1,25 +1,38 @@
 use std::time::Duration;
 
-use anyhow::Context;
 use bytes::Bytes;
 use bytestring::ByteString;
-use spacetimedb_lib::de::serde::SeedWrapper;
-use spacetimedb_lib::de::DeserializeSeed;
+use itertools::Itertools;
 use spacetimedb_lib::{bsatn, Hash, Identity};
 use spacetimedb_lib::{ProductValue, ReducerDef};
-use spacetimedb_sats::WithTypespace;
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_sats::json::{self, PathSegment};
+use spacetimedb_sats::{AlgebraicType, ProductType, WithTypespace};
 
+use crate::address::Address;
+use crate::messages::control_db::EnergyPricing;
+
+pub mod call_log;
+pub mod expiry;
 mod host_controller;
+pub mod http_routes;
+mod lanes;
 pub(crate) mod module_host;
+pub mod outbox;
 pub use module_host::{UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess};
 pub mod scheduler;
 mod wasmer;
+mod wasmtime;
 
 // Visible for integration testing.
 pub mod instance_env;
+pub mod retention;
+mod timer_wheel;
 mod timestamp;
 pub mod tracelog;
 mod wasm_common;
+pub(crate) use wasm_common::module_host_actor::schema_for;
+pub mod webhooks;
 
 pub use host_controller::{
     DescribedEntityType, EnergyDiff, EnergyQuanta, HostController, ReducerCallResult, ReducerOutcome, UpdateOutcome,
@@ -44,7 +57,7 @@ impl ReducerArgs {
     fn _into_tuple(self, schema: WithTypespace<'_, ReducerDef>) -> anyhow::Result<ArgsTuple> {
         Ok(match self {
             ReducerArgs::Json(json) => ArgsTuple {
-                tuple: from_json_seed(&json, SeedWrapper(ReducerDef::deserialize(schema)))?,
+                tuple: args_from_json(&json, schema)?,
                 bsatn: None,
                 json: Some(json),
             },
@@ -103,17 +116,50 @@ pub struct InvalidReducerArguments {
 
 pub use module_host::{EntityDef, ReducerCallError};
 
-fn from_json_seed<'de, T: serde::de::DeserializeSeed<'de>>(s: &'de str, seed: T) -> anyhow::Result<T::Value> {
-    let mut de = serde_json::Deserializer::from_str(s);
-    let mut track = serde_path_to_error::Track::new();
-    let out = seed
-        .deserialize(serde_path_to_error::Deserializer::new(&mut de, &mut track))
-        .context(track.path())?;
-    de.end()?;
-    Ok(out)
+/// Deserializes the arguments of the reducer `schema` from a JSON array of them,
+/// or from a JSON object of them keyed by their names.
+///
+/// Errors name the argument that failed to deserialize and the type it should have had.
+fn args_from_json(json: &str, schema: WithTypespace<'_, ReducerDef>) -> anyhow::Result<ProductValue> {
+    let args = &schema.ty().args;
+    let args_ty = AlgebraicType::Product(ProductType::new(args.clone()));
+    let out = json::from_str_seed(schema.with(&args_ty), json).map_err(|err| {
+        let arg = err.path().first().and_then(|segment| match segment {
+            PathSegment::Index(index) => args.get(*index).map(|arg| (*index, arg)),
+            // Unnamed arguments are keyed by their position.
+            PathSegment::Key(key) => args
+                .iter()
+                .enumerate()
+                .find(|(i, arg)| arg.name().map_or(key == &i.to_string(), |name| name == key)),
+        });
+        let path = err.path().iter().map(|segment| segment.to_string()).join(".");
+        let err = anyhow::Error::new(err);
+        match arg {
+            Some((i, arg)) => {
+                // Show the definition of a named type rather than its ref.
+                let ty = match &arg.algebraic_type {
+                    AlgebraicType::Ref(r) => schema.typespace().get(*r).unwrap_or(&arg.algebraic_type),
+                    ty => ty,
+                };
+                err.context(format!(
+                    "argument {} ({}) should have type {}, at `{}`",
+                    i,
+                    arg.name().unwrap_or("unnamed"),
+                    fmt_algebraic_type(ty),
+                    path,
+                ))
+            }
+            None => err.context(format!(
+                "expected a JSON array of {} arguments, or an object of them by name",
+                args.len()
+            )),
+        }
+    })?;
+    Ok(out.into_product().expect("decoded the arguments as a product"))
 }
 
 pub struct EnergyMonitorFingerprint<'a> {
+    pub database_address: Address,
     pub module_hash: Hash,
     pub module_identity: Identity,
     pub caller_identity: Identity,
@@ -122,6 +168,10 @@ pub struct EnergyMonitorFingerprint<'a> {
 
 pub trait EnergyMonitor: Send + Sync + 'static {
     fn reducer_budget(&self, fingerprint: &EnergyMonitorFingerprint<'_>) -> EnergyQuanta;
+    /// The prices of the resources the reducer uses, which its budget is spent on.
+    fn reducer_pricing(&self, _fingerprint: &EnergyMonitorFingerprint<'_>) -> EnergyPricing {
+        EnergyPricing::default()
+    }
     fn record(&self, fingerprint: &EnergyMonitorFingerprint<'_>, energy_used: EnergyDiff, execution_duration: Duration);
 }
 
@@ -147,3 +197,47 @@ impl EnergyMonitor for NullEnergyMonitor {
     ) {
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::{product, ProductTypeElement, Typespace};
+
+    fn reducer() -> ReducerDef {
+        ReducerDef {
+            name: "transfer".into(),
+            args: vec![
+                ProductTypeElement::new_named(AlgebraicType::String, "to"),
+                ProductTypeElement::new_named(AlgebraicType::U64, "amount"),
+            ],
+        }
+    }
+
+    #[test]
+    fn json_args_as_array_or_object() {
+        let typespace = Typespace::default();
+        let reducer = reducer();
+        let schema = typespace.with_type(&reducer);
+
+        let expected = product!["alice", 5u64];
+        assert_eq!(args_from_json(r#"["alice", 5]"#, schema).unwrap(), expected);
+        assert_eq!(
+            args_from_json(r#"{"amount": 5, "to": "alice"}"#, schema).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn json_args_error_names_argument() {
+        let typespace = Typespace::default();
+        let reducer = reducer();
+        let schema = typespace.with_type(&reducer);
+
+        let err = args_from_json(r#"["alice", "five"]"#, schema).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(msg.contains("argument 1 (amount) should have type U64"), "{msg}");
+
+        let err = args_from_json(r#"{"to": "alice", "amount": -1}"#, schema).unwrap_err();
+        assert!(format!("{err:#}").contains("(amount)"));
+    }
+}
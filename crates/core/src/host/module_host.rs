@@ -1,6 +1,15 @@
-use super::{ArgsTuple, EnergyDiff, InvalidReducerArguments, ReducerArgs, ReducerCallResult, Timestamp};
+use super::call_log::{CallLog, HostCallRecord};
+use super::http_routes::HttpRoutes;
+use super::lanes::{self, LaneReceiver, LaneSender, WeakLaneSender};
+use super::outbox::ReceivedMessage;
+use super::wasm_common::IDENTITY_MERGED_DUNDER;
+use super::{
+    ArgsTuple, EnergyDiff, InvalidReducerArguments, ReducerArgs, ReducerCallResult, ReducerOutcome, Timestamp,
+};
+use crate::address::Address;
 use crate::client::ClientConnectionSender;
 use crate::database_logger::LogLevel;
+use crate::db::datastore::locking_tx_datastore::VacuumReport;
 use crate::db::datastore::traits::{TableId, TxData, TxOp};
 use crate::db::relational_db::RelationalDB;
 use crate::error::DBError;
@@ -11,17 +20,25 @@ use crate::protobuf::client_api::{table_row_operation, SubscriptionUpdate, Table
 use crate::subscription::module_subscription_actor::ModuleSubscriptionManager;
 use base64::{engine::general_purpose::STANDARD as BASE_64_STD, Engine as _};
 use indexmap::IndexMap;
-use spacetimedb_lib::{ReducerDef, TableDef};
-use spacetimedb_sats::{ProductValue, Typespace, WithTypespace};
-use std::collections::HashMap;
+use once_cell::sync::OnceCell;
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::{Lane, ReducerDef, TableDef, TableTtl};
+use spacetimedb_sats::{
+    bsatn, AlgebraicType, AlgebraicTypeRef, AlgebraicValue, ProductValue, Typespace, WithTypespace,
+};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{oneshot, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Debug, Default, Clone)]
 pub struct DatabaseUpdate {
     pub tables: Vec<DatabaseTableUpdate>,
+    /// For the writes of a committed transaction,
+    /// the number of transactions in the commit log up to and including it.
+    pub tx_offset: Option<u64>,
 }
 
 impl DatabaseUpdate {
@@ -32,6 +49,38 @@ impl DatabaseUpdate {
         false
     }
 
+    /// Appends the operations of `other`, an update that follows this one,
+    /// where an operation undoing one of this update cancels out with it,
+    /// like the delete of a row this update inserts.
+    ///
+    /// This is for updates without the rows updated in place, which are left as they are.
+    pub fn merge(&mut self, other: &DatabaseUpdate) {
+        for table in &other.tables {
+            let Some(merged) = self.tables.iter_mut().find(|t| t.table_id == table.table_id) else {
+                self.tables.push(table.clone());
+                continue;
+            };
+            let mut ops: IndexMap<Vec<u8>, TableOp> = std::mem::take(&mut merged.ops)
+                .into_iter()
+                .map(|op| (op.row_pk.clone(), op))
+                .collect();
+            for op in &table.ops {
+                match ops.entry(op.row_pk.clone()) {
+                    indexmap::map::Entry::Occupied(undone) if undone.get().op_type != op.op_type => {
+                        undone.shift_remove();
+                    }
+                    indexmap::map::Entry::Occupied(mut entry) => *entry.get_mut() = op.clone(),
+                    indexmap::map::Entry::Vacant(entry) => {
+                        entry.insert(op.clone());
+                    }
+                }
+            }
+            merged.ops = ops.into_values().collect();
+        }
+        self.tables.retain(|table| !table.ops.is_empty());
+        self.tx_offset = other.tx_offset.or(self.tx_offset);
+    }
+
     pub fn from_writes(stdb: &RelationalDB, tx_data: &TxData) -> Self {
         let mut map: HashMap<TableId, Vec<TableOp>> = HashMap::new();
         //TODO: This should be wrapped with .auto_commit
@@ -72,11 +121,15 @@ impl DatabaseUpdate {
                 table_id: table_id.0,
                 table_name,
                 ops: table_row_operations,
+                updates: Vec::new(),
             });
         }
         stdb.rollback_tx(tx);
 
-        DatabaseUpdate { tables: table_updates }
+        DatabaseUpdate {
+            tables: table_updates,
+            tx_offset: tx_data.tx_offset,
+        }
     }
 
     pub fn into_protobuf(self) -> SubscriptionUpdate {
@@ -101,11 +154,26 @@ impl DatabaseUpdate {
                                 },
                                 row_pk: op.row_pk,
                                 row: row_bytes,
+                                old_row_pk: Vec::new(),
+                                column_mask: Vec::new(),
                             }
                         })
+                        .chain(table.updates.into_iter().map(|update| {
+                            let mut row_bytes = Vec::new();
+                            update.columns.encode(&mut row_bytes);
+                            TableRowOperation {
+                                op: table_row_operation::OperationType::Update.into(),
+                                row_pk: update.row_pk,
+                                row: row_bytes,
+                                old_row_pk: update.old_row_pk,
+                                column_mask: update.column_mask,
+                            }
+                        }))
                         .collect(),
                 })
                 .collect(),
+            tx_offset: self.tx_offset.unwrap_or(0),
+            resumed: false,
         }
     }
 
@@ -132,11 +200,22 @@ impl DatabaseUpdate {
                                 },
                                 row_pk,
                                 row: op.row.elements,
+                                old_row_pk: None,
+                                column_mask: None,
                             }
                         })
+                        .chain(table.updates.into_iter().map(|update| TableRowOperationJson {
+                            op: "update".into(),
+                            row_pk: BASE_64_STD.encode(&update.row_pk),
+                            row: update.columns.elements,
+                            old_row_pk: Some(BASE_64_STD.encode(&update.old_row_pk)),
+                            column_mask: Some(BASE_64_STD.encode(&update.column_mask)),
+                        }))
                         .collect(),
                 })
                 .collect(),
+            tx_offset: self.tx_offset,
+            resumed: false,
         }
     }
 }
@@ -146,6 +225,44 @@ pub struct DatabaseTableUpdate {
     pub table_id: u32,
     pub table_name: String,
     pub ops: Vec<TableOp>,
+    /// The rows updated in place, as the columns that changed.
+    ///
+    /// This is only filled in by [`DatabaseTableUpdate::diff_updates`],
+    /// for clients that opted into column diffs.
+    pub updates: Vec<RowUpdate>,
+}
+
+impl DatabaseTableUpdate {
+    /// Replaces each delete and insert of rows with the same value in the unique column `key_col`
+    /// by an update of the columns that changed between them.
+    pub fn diff_updates(&mut self, key_col: usize) {
+        let mut deletes = HashMap::new();
+        for (i, op) in self.ops.iter().enumerate() {
+            if let (0, Some(key)) = (op.op_type, op.row.elements.get(key_col)) {
+                deletes.insert(key, i);
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (i, op) in self.ops.iter().enumerate() {
+            if op.op_type != 1 {
+                continue;
+            }
+            if let Some(delete) = op.row.elements.get(key_col).and_then(|key| deletes.remove(key)) {
+                pairs.push((delete, i));
+            }
+        }
+        if pairs.is_empty() {
+            return;
+        }
+
+        let mut ops = std::mem::take(&mut self.ops).into_iter().map(Some).collect::<Vec<_>>();
+        for (delete, insert) in pairs {
+            let (old, new) = (ops[delete].take().unwrap(), ops[insert].take().unwrap());
+            self.updates.push(RowUpdate::diff(old, new));
+        }
+        self.ops = ops.into_iter().flatten().collect();
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -155,6 +272,39 @@ pub struct TableOp {
     pub row: ProductValue,
 }
 
+/// An update of a row, as the values of the columns that changed.
+#[derive(Debug, Clone)]
+pub struct RowUpdate {
+    /// The `row_pk` of the row before the update.
+    pub old_row_pk: Vec<u8>,
+    /// The `row_pk` of the row after the update.
+    pub row_pk: Vec<u8>,
+    /// A bitmask of the columns that changed,
+    /// where the first column is the lowest bit of the first byte.
+    pub column_mask: Vec<u8>,
+    /// The new values of the columns that changed, in column order.
+    pub columns: ProductValue,
+}
+
+impl RowUpdate {
+    fn diff(old: TableOp, new: TableOp) -> Self {
+        let mut column_mask = vec![0; (new.row.elements.len() + 7) / 8];
+        let mut columns = Vec::new();
+        for (i, value) in new.row.elements.into_iter().enumerate() {
+            if old.row.elements.get(i) != Some(&value) {
+                column_mask[i / 8] |= 1 << (i % 8);
+                columns.push(value);
+            }
+        }
+        Self {
+            old_row_pk: old.row_pk,
+            row_pk: new.row_pk,
+            column_mask,
+            columns: ProductValue { elements: columns },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum EventStatus {
     Committed(DatabaseUpdate),
@@ -185,6 +335,18 @@ pub struct ModuleEvent {
     pub status: EventStatus,
     pub energy_quanta_used: EnergyDiff,
     pub host_execution_duration: Duration,
+    /// The events emitted by the reducer, which are only delivered if it committed.
+    pub emitted_events: Vec<EmittedEvent>,
+    /// The value returned by the reducer to its caller, which is only delivered if it committed.
+    pub return_value: Option<AlgebraicValue>,
+}
+
+/// An event emitted by a reducer, of one of the event types of the module.
+#[derive(Debug, Clone)]
+pub struct EmittedEvent {
+    /// The name of the event's type.
+    pub name: String,
+    pub value: AlgebraicValue,
 }
 
 #[derive(Debug)]
@@ -197,10 +359,17 @@ enum ModuleHostCommand {
     CallReducer {
         caller_identity: Identity,
         client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
         reducer_id: usize,
         args: ArgsTuple,
         respond_to: oneshot::Sender<ReducerCallResult>,
     },
+    CallReducers {
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        calls: Vec<(usize, ArgsTuple)>,
+        respond_to: oneshot::Sender<Vec<ReducerCallResult>>,
+    },
     InitDatabase {
         args: ArgsTuple,
         respond_to: oneshot::Sender<anyhow::Result<ReducerCallResult>>,
@@ -221,6 +390,9 @@ enum ModuleHostCommand {
         log_level: LogLevel,
         message: String,
     },
+    ExpireRows {
+        respond_to: oneshot::Sender<usize>,
+    },
 }
 
 impl ModuleHostCommand {
@@ -234,10 +406,17 @@ impl ModuleHostCommand {
             ModuleHostCommand::CallReducer {
                 caller_identity,
                 client,
+                message,
                 reducer_id,
                 args,
                 respond_to,
-            } => actor.call_reducer(caller_identity, client, reducer_id, args, respond_to),
+            } => actor.call_reducer(caller_identity, client, message, reducer_id, args, respond_to),
+            ModuleHostCommand::CallReducers {
+                caller_identity,
+                client,
+                calls,
+                respond_to,
+            } => actor.call_reducers(caller_identity, client, calls, respond_to),
             ModuleHostCommand::InitDatabase { args, respond_to } => actor.init_database(args, respond_to),
             ModuleHostCommand::UpdateDatabase { respond_to } => actor.update_database(respond_to),
             #[cfg(feature = "tracelogging")]
@@ -253,13 +432,15 @@ impl ModuleHostCommand {
                 log_level,
                 message,
             } => actor.inject_logs(respond_to, log_level, message),
+            ModuleHostCommand::ExpireRows { respond_to } => actor.expire_rows(respond_to),
         }
     }
 }
 
 #[derive(Debug)]
 enum CmdOrExit {
-    Cmd(ModuleHostCommand),
+    /// A command, and the span it was sent from, which it's run in.
+    Cmd(ModuleHostCommand, tracing::Span),
     Exit,
 }
 
@@ -269,22 +450,56 @@ pub struct ModuleInfo {
     pub module_hash: Hash,
     pub typespace: Typespace,
     pub reducers: IndexMap<String, ReducerDef>,
+    /// The names of the reducers that run in a read-only transaction.
+    pub read_only_reducers: HashSet<String>,
+    /// The roles allowed to call each of the reducers restricted to some, by reducer name.
+    pub reducer_roles: HashMap<String, Vec<String>>,
+    /// How long a caller has to wait between calls to each of the reducers declared with a cooldown, by reducer name.
+    pub reducer_cooldowns: HashMap<String, Duration>,
+    /// The lane the calls to each of the reducers declared with a priority wait in, by reducer name.
+    pub reducer_priorities: HashMap<String, Lane>,
+    /// The tables whose rows expire, and after how long.
+    pub table_ttls: Vec<TableTtl>,
+    /// The event types that reducers can emit, by name.
+    pub event_types: HashMap<String, AlgebraicTypeRef>,
+    /// The types of the values returned by the reducers that return one, by reducer name.
+    pub reducer_returns: HashMap<String, AlgebraicType>,
+    /// The HTTP endpoints the module serves under the database's address.
+    pub http_routes: HttpRoutes,
+    /// The names the module gave to the types of its typespace.
+    pub type_aliases: HashMap<AlgebraicTypeRef, String>,
     pub catalog: HashMap<String, EntityDef>,
     pub log_tx: tokio::sync::broadcast::Sender<bytes::Bytes>,
     pub subscription: ModuleSubscriptionManager,
+    /// The database of the module, in whose `st_role_members` the roles of callers are looked up.
+    pub relational_db: Arc<RelationalDB>,
+    /// The host calls made by the reducers of the module, recorded while debugging it.
+    pub call_log: Arc<CallLog>,
+    /// The address of the database of the module, which the messages other databases send to it are addressed to.
+    pub address: Address,
 }
 
 pub trait ModuleHostActor: Send + 'static {
     fn info(&self) -> Arc<ModuleInfo>;
     fn call_connect_disconnect(&mut self, caller_identity: Identity, connected: bool, respond_to: oneshot::Sender<()>);
+    /// Runs the reducer `reducer_id`, recording `message`, if any, as received in its transaction.
     fn call_reducer(
         &mut self,
         caller_identity: Identity,
         client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
         reducer_id: usize,
         args: ArgsTuple,
         respond_to: oneshot::Sender<ReducerCallResult>,
     );
+    /// Runs the reducers of `calls` in order, with no other reducer running in between.
+    fn call_reducers(
+        &mut self,
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        calls: Vec<(usize, ArgsTuple)>,
+        respond_to: oneshot::Sender<Vec<ReducerCallResult>>,
+    );
     fn init_database(&mut self, args: ArgsTuple, respond_to: oneshot::Sender<Result<ReducerCallResult, anyhow::Error>>);
     fn update_database(&mut self, respond_to: oneshot::Sender<Result<UpdateDatabaseResult, anyhow::Error>>);
     #[cfg(feature = "tracelogging")]
@@ -292,18 +507,26 @@ pub trait ModuleHostActor: Send + 'static {
     #[cfg(feature = "tracelogging")]
     fn stop_trace(&mut self) -> Result<(), anyhow::Error>;
     fn inject_logs(&self, respond_to: oneshot::Sender<()>, log_level: LogLevel, message: String);
+    /// Deletes a batch of the rows that outlived the TTL of their table, responding with how many.
+    fn expire_rows(&mut self, respond_to: oneshot::Sender<usize>);
     fn close(self);
 }
 
 #[derive(Debug, Clone)]
 pub struct ModuleHost {
     info: Arc<ModuleInfo>,
-    tx: mpsc::Sender<CmdOrExit>,
+    tx: LaneSender<CmdOrExit>,
+    /// Held for reading by each call into the module, and for writing while it's drained to be swapped.
+    calls: Arc<RwLock<()>>,
+    /// The module this one was swapped for, once it has been.
+    successor: Arc<OnceCell<ModuleHost>>,
 }
 
 pub struct WeakModuleHost {
     info: Arc<ModuleInfo>,
-    tx: mpsc::WeakSender<CmdOrExit>,
+    tx: WeakLaneSender<CmdOrExit>,
+    calls: Arc<RwLock<()>>,
+    successor: Arc<OnceCell<ModuleHost>>,
 }
 
 pub type UpdateDatabaseResult = Result<UpdateDatabaseSuccess, UpdateDatabaseError>;
@@ -320,6 +543,15 @@ pub struct UpdateDatabaseSuccess {
     pub migrate_results: Vec<ReducerCallResult>,
 }
 
+impl UpdateDatabaseSuccess {
+    /// Whether the `__update__` reducer committed, or there's none.
+    pub fn committed(&self) -> bool {
+        self.update_result
+            .as_ref()
+            .map_or(true, |res| matches!(res.outcome, ReducerOutcome::Committed))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum UpdateDatabaseError {
     #[error("incompatible schema changes for: {tables:?}")]
@@ -340,6 +572,10 @@ pub enum ReducerCallError {
     NoSuchModule(#[from] NoSuchModule),
     #[error("no such reducer")]
     NoSuchReducer,
+    #[error("the caller holds none of the roles allowed to call the reducer")]
+    NotAllowed,
+    #[error("the caller called the reducer too recently, it can call it again in {}ms", retry_after.as_millis())]
+    Cooldown { retry_after: Duration },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -364,20 +600,26 @@ impl ModuleStarter {
 
 impl ModuleHost {
     pub fn spawn(actor: impl ModuleHostActor) -> (Self, ModuleStarter) {
-        let (tx, rx) = mpsc::channel(8);
+        let (tx, rx) = lanes::channel(8);
         let (start_tx, start_rx) = oneshot::channel();
         let info = actor.info();
         tokio::task::spawn_blocking(|| {
             let _ = start_rx.blocking_recv();
             Self::run_actor(rx, actor)
         });
-        (ModuleHost { info, tx }, ModuleStarter { tx: start_tx })
+        let module_host = ModuleHost {
+            info,
+            tx,
+            calls: Default::default(),
+            successor: Default::default(),
+        };
+        (module_host, ModuleStarter { tx: start_tx })
     }
 
-    fn run_actor(mut rx: mpsc::Receiver<CmdOrExit>, mut actor: impl ModuleHostActor) {
+    fn run_actor(mut rx: LaneReceiver<CmdOrExit>, mut actor: impl ModuleHostActor) {
         while let Some(command) = rx.blocking_recv() {
             match command {
-                CmdOrExit::Cmd(command) => command.dispatch(&mut actor),
+                CmdOrExit::Cmd(command, span) => span.in_scope(|| command.dispatch(&mut actor)),
                 CmdOrExit::Exit => rx.close(),
             }
         }
@@ -394,10 +636,59 @@ impl ModuleHost {
         &self.info.subscription
     }
 
+    /// The module calls to this one go to: the one it was last swapped for, if any.
+    pub fn current(&self) -> &ModuleHost {
+        let mut module = self;
+        while let Some(successor) = module.successor.get() {
+            module = successor;
+        }
+        module
+    }
+
+    /// Waits for the module to accept calls, returning the module to make them to,
+    /// which is the one it was swapped for if that happened in the meantime,
+    /// and a permit that holds off draining it until the calls are done.
+    async fn admit(&self) -> (&ModuleHost, RwLockReadGuard<'_, ()>) {
+        let mut module = self;
+        loop {
+            let permit = module.calls.read().await;
+            match module.successor.get() {
+                Some(successor) => module = successor,
+                None => return (module, permit),
+            }
+        }
+    }
+
+    /// Stops the module accepting calls, and waits for the calls in flight to finish.
+    ///
+    /// Calls made until the returned guard is dropped wait for it,
+    /// and then go to the module this one was swapped for in the meantime, if any.
+    pub async fn drain(&self) -> RwLockWriteGuard<'_, ()> {
+        self.calls.write().await
+    }
+
+    /// Send the calls to this module to `successor` from now on.
+    ///
+    /// This doesn't exit the module, which is up to the caller once its clients have been moved over.
+    pub fn swap_for(&self, successor: ModuleHost) {
+        if self.successor.set(successor).is_err() {
+            log::warn!("module was already swapped for a new version");
+        }
+    }
+
     async fn call<T>(&self, f: impl FnOnce(oneshot::Sender<T>) -> ModuleHostCommand) -> Result<T, NoSuchModule> {
-        let permit = self.tx.reserve().await.map_err(|_| NoSuchModule)?;
+        self.call_in(Lane::Normal, f).await
+    }
+
+    /// Like [`Self::call`], with the command waiting in `lane` to be run.
+    async fn call_in<T>(
+        &self,
+        lane: Lane,
+        f: impl FnOnce(oneshot::Sender<T>) -> ModuleHostCommand,
+    ) -> Result<T, NoSuchModule> {
+        let permit = self.tx.lane(lane).reserve().await.map_err(|_| NoSuchModule)?;
         let (tx, rx) = oneshot::channel();
-        permit.send(CmdOrExit::Cmd(f(tx)));
+        permit.send(CmdOrExit::Cmd(f(tx), tracing::Span::current()));
         Ok(rx.await.expect("task panicked"))
     }
 
@@ -406,12 +697,14 @@ impl ModuleHost {
         caller_identity: Identity,
         connected: bool,
     ) -> Result<(), NoSuchModule> {
-        self.call(|respond_to| ModuleHostCommand::CallConnectDisconnect {
-            caller_identity,
-            connected,
-            respond_to,
-        })
-        .await
+        let (module, _permit) = self.admit().await;
+        module
+            .call(|respond_to| ModuleHostCommand::CallConnectDisconnect {
+                caller_identity,
+                connected,
+                respond_to,
+            })
+            .await
     }
 
     pub async fn call_reducer(
@@ -421,6 +714,141 @@ impl ModuleHost {
         reducer_name: &str,
         args: ReducerArgs,
     ) -> Result<ReducerCallResult, ReducerCallError> {
+        self.call_reducer_with_message(caller_identity, client, None, reducer_name, args)
+            .await
+    }
+
+    /// Calls the reducer `reducer_name` with the `message` another database sent to it,
+    /// as `caller_identity`, the identity of that database.
+    ///
+    /// The message is recorded as received in `st_inbox` in the transaction of the reducer,
+    /// or in one of its own if the reducer doesn't commit one, as it's then not to be delivered again.
+    pub async fn deliver_message(
+        &self,
+        caller_identity: Identity,
+        message: ReceivedMessage,
+        reducer_name: &str,
+        args: ReducerArgs,
+    ) -> Result<ReducerCallResult, ReducerCallError> {
+        self.call_reducer_with_message(caller_identity, None, Some(message), reducer_name, args)
+            .await
+    }
+
+    async fn call_reducer_with_message(
+        &self,
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
+        reducer_name: &str,
+        args: ReducerArgs,
+    ) -> Result<ReducerCallResult, ReducerCallError> {
+        let (module, _permit) = self.admit().await;
+        let (reducer_id, args) = module.resolve_reducer_call(caller_identity, reducer_name, args).await?;
+
+        let lane = module.lane_of(reducer_name);
+        module
+            .call_in(lane, |respond_to| ModuleHostCommand::CallReducer {
+                caller_identity,
+                client,
+                message,
+                reducer_id,
+                args,
+                respond_to,
+            })
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Calls the module's `identity_merged` reducer, to move what belongs to `old` over to `new`,
+    /// in a single transaction.
+    ///
+    /// The reducer is called as the module's owner, the only caller it allows,
+    /// so that no client can claim the rows of another.
+    /// Returns `None` if the module has no such reducer, as there's then nothing to move.
+    pub async fn call_identity_merged(
+        &self,
+        old: Identity,
+        new: Identity,
+    ) -> Result<Option<ReducerCallResult>, ReducerCallError> {
+        // A module swapped in since may have added or dropped the reducer.
+        if !self.current().info.reducers.contains_key(IDENTITY_MERGED_DUNDER) {
+            return Ok(None);
+        }
+        // The arguments are a product of the two identities, which BSATN encodes one after the other.
+        let mut args = bsatn::to_vec(&old).unwrap();
+        bsatn::to_writer(&mut args, &new).unwrap();
+        let args = ReducerArgs::Bsatn(args.into());
+        self.call_reducer(self.info.identity, None, IDENTITY_MERGED_DUNDER, args)
+            .await
+            .map(Some)
+    }
+
+    /// Runs the reducers of `calls` one after another, as a single unit of work,
+    /// so that no other reducer runs between them.
+    ///
+    /// Each reducer runs in its own transaction, and a call that fails doesn't stop the ones after it.
+    /// The calls wait to be run in the highest lane of their reducers.
+    /// The results are in the order of `calls`; a call with an unknown reducer
+    /// or invalid arguments is an error in its place, and isn't run.
+    pub async fn call_reducers(
+        &self,
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        calls: Vec<(&str, ReducerArgs)>,
+    ) -> Result<Vec<Result<ReducerCallResult, ReducerCallError>>, NoSuchModule> {
+        let (module, _permit) = self.admit().await;
+        let mut resolved = Vec::with_capacity(calls.len());
+        let mut results = Vec::with_capacity(calls.len());
+        // The lanes are ordered from the highest, so this ends up at the highest of the calls.
+        let mut lane = Lane::Low;
+        for (reducer_name, args) in calls {
+            match module.resolve_reducer_call(caller_identity, reducer_name, args).await {
+                Ok(call) => {
+                    lane = lane.min(module.lane_of(reducer_name));
+                    resolved.push(call);
+                    results.push(None);
+                }
+                Err(e) => results.push(Some(Err(e))),
+            }
+        }
+
+        let ran = if resolved.is_empty() {
+            Vec::new()
+        } else {
+            module
+                .call_in(lane, |respond_to| ModuleHostCommand::CallReducers {
+                    caller_identity,
+                    client,
+                    calls: resolved,
+                    respond_to,
+                })
+                .await?
+        };
+        let mut ran = ran.into_iter();
+        Ok(results
+            .into_iter()
+            .map(|res| res.unwrap_or_else(|| Ok(ran.next().expect("a result for each call"))))
+            .collect())
+    }
+
+    /// Returns the lane the calls to the reducer `reducer_name` wait in.
+    fn lane_of(&self, reducer_name: &str) -> Lane {
+        self.info
+            .reducer_priorities
+            .get(reducer_name)
+            .copied()
+            .unwrap_or(Lane::Normal)
+    }
+
+    /// Looks up the reducer `reducer_name` and checks its `args`,
+    /// and that `caller_identity` is allowed to call it and isn't in its cooldown,
+    /// logging to the module's log if any is wrong.
+    async fn resolve_reducer_call(
+        &self,
+        caller_identity: Identity,
+        reducer_name: &str,
+        args: ReducerArgs,
+    ) -> Result<(usize, ArgsTuple), ReducerCallError> {
         let found_reducer = self
             .info
             .reducers
@@ -429,7 +857,7 @@ impl ModuleHost {
         let (reducer_id, _, schema) = match found_reducer {
             Ok(ok) => ok,
             Err(err) => {
-                let _ = self.inject_logs(LogLevel::Error, format!(
+                let _ = self.log(LogLevel::Error, format!(
                     "External attempt to call nonexistent reducer \"{}\" failed. Have you run `spacetime generate` recently?",
                     reducer_name
                 )).await;
@@ -437,11 +865,21 @@ impl ModuleHost {
             }
         };
 
+        if !self.is_allowed(caller_identity, reducer_name) {
+            let _ = self
+                .log(
+                    LogLevel::Error,
+                    format!("External attempt to call reducer \"{reducer_name}\" failed, the caller {caller_identity} isn't allowed to."),
+                )
+                .await;
+            return Err(ReducerCallError::NotAllowed);
+        }
+
         let args = args.into_tuple(self.info.typespace.with_type(schema));
         let args = match args {
             Ok(ok) => ok,
             Err(err) => {
-                let _ = self.inject_logs(LogLevel::Error, format!(
+                let _ = self.log(LogLevel::Error, format!(
                     "External attempt to call reducer \"{}\" failed, invalid arguments.\nThis is likely due to a mismatched client schema, have you run `spacetime generate` recently?",
                     reducer_name,
                 )).await;
@@ -449,44 +887,154 @@ impl ModuleHost {
             }
         };
 
-        self.call(|respond_to| ModuleHostCommand::CallReducer {
-            caller_identity,
-            client,
-            reducer_id,
-            args,
-            respond_to,
+        if let Some(retry_after) = self.check_cooldown(caller_identity, reducer_name) {
+            let _ = self
+                .log(
+                    LogLevel::Error,
+                    format!("External attempt to call reducer \"{reducer_name}\" failed, the caller {caller_identity} is in its cooldown."),
+                )
+                .await;
+            return Err(ReducerCallError::Cooldown { retry_after });
+        }
+
+        Ok((reducer_id, args))
+    }
+
+    /// Whether `caller_identity` holds one of the roles allowed to call `reducer_name`,
+    /// if the module restricts it to some, as [`RelationalDB::has_any_role`] decides.
+    fn is_allowed(&self, caller_identity: Identity, reducer_name: &str) -> bool {
+        let Some(allowed) = self.info.reducer_roles.get(reducer_name) else {
+            return true;
+        };
+        let db = &self.info.relational_db;
+        let tx = db.begin_read_only_tx();
+        let res = db.has_any_role(&tx, AuthCtx::new(self.info.identity, caller_identity), allowed);
+        db.release_tx(tx);
+        res.unwrap_or_else(|e| {
+            log::error!("Failed to look up the roles of {caller_identity}: {e}");
+            false
         })
-        .await
-        .map_err(Into::into)
+    }
+
+    /// Records the call of `caller_identity` to `reducer_name` in `st_reducer_cooldown`,
+    /// if the module declared the reducer with a cooldown,
+    /// returning how much longer the caller has to wait if its last call was too recent.
+    ///
+    /// The module's own calls, e.g. of scheduled reducers, have no cooldown.
+    fn check_cooldown(&self, caller_identity: Identity, reducer_name: &str) -> Option<Duration> {
+        let &cooldown = self.info.reducer_cooldowns.get(reducer_name)?;
+        if caller_identity == self.info.identity {
+            return None;
+        }
+        let db = &self.info.relational_db;
+        let now = Timestamp::now().0;
+        db.with_auto_commit(|tx| db.record_reducer_call(tx, caller_identity, reducer_name, now, cooldown))
+            .unwrap_or_else(|e| {
+                log::error!("Failed to record the call of {caller_identity} to {reducer_name}: {e}");
+                Some(cooldown)
+            })
     }
 
     pub fn catalog(&self) -> Catalog {
         Catalog(self.info.clone())
     }
 
+    /// Stores the blob `data` under `key` in a transaction of its own,
+    /// returning its hash, as [`RelationalDB::put_blob`] does.
+    pub fn put_blob(&self, key: &str, data: &[u8]) -> Result<Hash, DBError> {
+        let db = &self.info.relational_db;
+        db.with_auto_commit(|tx| db.put_blob(tx, key, data))
+    }
+
+    /// Returns the hash and the data of the blob stored under `key`, if any.
+    pub fn get_blob(&self, key: &str) -> Result<Option<(Hash, Vec<u8>)>, DBError> {
+        let db = &self.info.relational_db;
+        let tx = db.begin_read_only_tx();
+        let res = db.get_blob(&tx, key);
+        db.release_tx(tx);
+        res
+    }
+
+    /// Deletes the blob stored under `key` in a transaction of its own, returning whether there was one.
+    pub fn delete_blob(&self, key: &str) -> Result<bool, DBError> {
+        let db = &self.info.relational_db;
+        db.with_auto_commit(|tx| db.delete_blob(tx, key))
+    }
+
+    /// Compacts the storage of the database in a transaction of its own,
+    /// returning the space reclaimed, as [`RelationalDB::vacuum`] does.
+    pub fn vacuum(&self) -> Result<VacuumReport, DBError> {
+        let db = &self.info.relational_db;
+        db.with_auto_commit(|tx| db.vacuum(tx))
+    }
+
+    /// Turns the log of the host calls made by the reducers of the module on or off.
+    pub fn set_call_log_enabled(&self, enabled: bool) {
+        self.info.call_log.set_enabled(enabled)
+    }
+
+    /// Returns whether the log of host calls is on, and the calls recorded, oldest first,
+    /// with the names of their tables filled in.
+    pub fn call_log(&self) -> Result<(bool, Vec<HostCallRecord>), DBError> {
+        let mut records = self.info.call_log.records();
+        let db = &self.info.relational_db;
+        let tx = db.begin_read_only_tx();
+        let mut table_names = HashMap::new();
+        let res = records.iter_mut().try_for_each(|record| -> Result<(), DBError> {
+            if let Some(table_id) = record.table_id {
+                let name = match table_names.entry(table_id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(db.table_name_from_id(&tx, table_id)?),
+                };
+                record.table_name = name.clone();
+            }
+            Ok(())
+        });
+        db.release_tx(tx);
+        res.map(|()| (self.info.call_log.is_enabled(), records))
+    }
+
     pub fn subscribe_to_logs(&self) -> anyhow::Result<tokio::sync::broadcast::Receiver<bytes::Bytes>> {
         Ok(self.info().log_tx.subscribe())
     }
 
     pub async fn init_database(&self, args: ReducerArgs) -> Result<ReducerCallResult, InitDatabaseError> {
-        let args = match self.catalog().get_reducer("__init__") {
+        let (module, _permit) = self.admit().await;
+        let args = match module.catalog().get_reducer("__init__") {
             Some(schema) => args.into_tuple(schema)?,
             _ => ArgsTuple::default(),
         };
-        self.call(|respond_to| ModuleHostCommand::InitDatabase { args, respond_to })
+        module
+            .call(|respond_to| ModuleHostCommand::InitDatabase { args, respond_to })
             .await?
             .map_err(InitDatabaseError::Other)
     }
 
     pub async fn update_database(&self) -> Result<UpdateDatabaseResult, anyhow::Error> {
-        self.call(|respond_to| ModuleHostCommand::UpdateDatabase { respond_to })
+        let (module, _permit) = self.admit().await;
+        module
+            .call(|respond_to| ModuleHostCommand::UpdateDatabase { respond_to })
             .await?
             .map_err(Into::into)
     }
 
+    /// Deletes a batch of the rows that outlived the TTL of their table, in a transaction of its own,
+    /// returning how many were deleted.
+    ///
+    /// As background work, this waits in the low lane, behind the calls to reducers of a higher priority.
+    pub async fn expire_rows(&self) -> Result<usize, NoSuchModule> {
+        let (module, _permit) = self.admit().await;
+        if module.info.table_ttls.is_empty() {
+            return Ok(0);
+        }
+        module
+            .call_in(Lane::Low, |respond_to| ModuleHostCommand::ExpireRows { respond_to })
+            .await
+    }
+
     pub async fn exit(&self) {
         // if we can't send, it's already closed :P
-        if self.tx.send(CmdOrExit::Exit).await.is_ok() {
+        if self.tx.lane(Lane::Normal).send(CmdOrExit::Exit).await.is_ok() {
             self.tx.closed().await;
         }
     }
@@ -495,18 +1043,42 @@ impl ModuleHost {
         self.tx.closed().await
     }
 
+    /// Resolves once the module has exited, unless it was swapped for a new version,
+    /// in which case it resolves once that one has.
+    pub async fn retired(&self) {
+        let mut module = self;
+        loop {
+            module.exited().await;
+            match module.successor.get() {
+                Some(successor) => module = successor,
+                None => return,
+            }
+        }
+    }
+
     #[cfg(feature = "tracelogging")]
     pub async fn get_trace(&self) -> Result<Option<bytes::Bytes>, NoSuchModule> {
-        self.call(|respond_to| ModuleHostCommand::GetTrace { respond_to }).await
+        let (module, _permit) = self.admit().await;
+        module
+            .call(|respond_to| ModuleHostCommand::GetTrace { respond_to })
+            .await
     }
 
     #[cfg(feature = "tracelogging")]
     pub async fn stop_trace(&self) -> Result<(), anyhow::Error> {
-        self.call(|respond_to| ModuleHostCommand::StopTrace { respond_to })
+        let (module, _permit) = self.admit().await;
+        module
+            .call(|respond_to| ModuleHostCommand::StopTrace { respond_to })
             .await?
     }
 
     pub async fn inject_logs(&self, log_level: LogLevel, message: String) -> Result<(), NoSuchModule> {
+        let (module, _permit) = self.admit().await;
+        module.log(log_level, message).await
+    }
+
+    /// Like [`Self::inject_logs`], for use while already admitted, where waiting to be could deadlock.
+    async fn log(&self, log_level: LogLevel, message: String) -> Result<(), NoSuchModule> {
         self.call(|respond_to| ModuleHostCommand::InjectLogs {
             respond_to,
             log_level,
@@ -519,6 +1091,8 @@ impl ModuleHost {
         WeakModuleHost {
             info: self.info.clone(),
             tx: self.tx.downgrade(),
+            calls: self.calls.clone(),
+            successor: self.successor.clone(),
         }
     }
 }
@@ -529,6 +1103,8 @@ impl WeakModuleHost {
         Some(ModuleHost {
             info: self.info.clone(),
             tx,
+            calls: self.calls.clone(),
+            successor: self.successor.clone(),
         })
     }
 }
@@ -578,3 +1154,87 @@ impl Catalog {
             .map(|(name, e)| (&**name, self.0.typespace.with_type(e)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::product;
+
+    fn op(op_type: u8, row: ProductValue) -> TableOp {
+        TableOp {
+            op_type,
+            row_pk: RelationalDB::pk_for_row(&row).to_bytes(),
+            row,
+        }
+    }
+
+    #[test]
+    fn test_diff_updates() {
+        let mut table = DatabaseTableUpdate {
+            table_id: 0,
+            table_name: "player".to_string(),
+            ops: vec![
+                op(0, product![1u32, "alice", 0i32, 0i32]),
+                op(0, product![2u32, "bob", 5i32, 5i32]),
+                op(1, product![1u32, "alice", 3i32, 0i32]),
+                op(1, product![3u32, "carol", 1i32, 1i32]),
+            ],
+            updates: vec![],
+        };
+        table.diff_updates(0);
+
+        assert_eq!(table.ops.len(), 2);
+        assert_eq!(table.ops[0].row, product![2u32, "bob", 5i32, 5i32]);
+        assert_eq!(table.ops[1].row, product![3u32, "carol", 1i32, 1i32]);
+
+        let [update] = &table.updates[..] else {
+            panic!("expected a single update, got {:?}", table.updates);
+        };
+        assert_eq!(update.column_mask, vec![0b0100]);
+        assert_eq!(update.columns, product![3i32]);
+        assert_eq!(
+            update.old_row_pk,
+            RelationalDB::pk_for_row(&product![1u32, "alice", 0i32, 0i32]).to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let update = |tx_offset, ops| DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id: 0,
+                table_name: "player".to_string(),
+                ops,
+                updates: vec![],
+            }],
+            tx_offset: Some(tx_offset),
+        };
+        let mut merged = update(
+            1,
+            vec![
+                op(1, product![1u32, "alice"]),
+                op(0, product![2u32, "bob"]),
+                op(1, product![3u32, "carol"]),
+            ],
+        );
+        merged.merge(&update(
+            2,
+            vec![
+                op(0, product![1u32, "alice"]),
+                op(1, product![1u32, "alice2"]),
+                op(1, product![2u32, "bob"]),
+            ],
+        ));
+
+        assert_eq!(merged.tx_offset, Some(2));
+        let rows: Vec<_> = merged.tables[0].ops.iter().map(|op| (op.op_type, &op.row)).collect();
+        assert_eq!(rows, [(1, &product![3u32, "carol"]), (1, &product![1u32, "alice2"])]);
+
+        // A table whose operations all cancel out is left out.
+        merged.merge(&update(
+            3,
+            vec![op(0, product![3u32, "carol"]), op(0, product![1u32, "alice2"])],
+        ));
+        assert!(merged.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Messages between the databases of a node: a reducer of one database calling a reducer of another,
+//! atomically with its own transaction, and exactly once.
+//!
+//! A reducer sends a message by adding it to `st_outbox` in its transaction,
+//! so the message exists if and only if the transaction commits.
+//! The host then delivers the messages of each database in the order they were sent,
+//! by calling the reducer of the target with them, and marks each delivered in `st_outbox` once it's been called.
+//!
+//! The target records the `msg_id` of the last message it received from each database in `st_inbox`,
+//! in the transaction of the reducer the message called, or in one of its own if the reducer didn't commit.
+//! A message delivered again, because the host stopped before marking it delivered, is then recognized,
+//! and only marked delivered. A reducer that fails on a message has received it all the same.
+//!
+//! A message waits, with exponential backoff, while the target isn't running on the node,
+//! or its caller is in the cooldown of the reducer.
+//! A message the target can never accept, e.g., to a reducer it doesn't have, is logged and dropped.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::address::Address;
+use crate::database_instance_context::DatabaseInstanceContext;
+use crate::database_logger::LogLevel;
+use crate::db::datastore::system_tables::StOutboxRow;
+use crate::error::DBError;
+use crate::host::module_host::ReducerCallError;
+use crate::host::{ModuleHost, ReducerArgs};
+
+/// How long to wait before delivering again the messages that couldn't be, doubled for each retry after.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A message a database received from another, as recorded in `st_inbox` when the reducer it called runs.
+#[derive(Debug, Clone, Copy)]
+pub struct ReceivedMessage {
+    pub sender: Address,
+    pub msg_id: u64,
+}
+
+/// Deliver the messages the database of `dbic` sends to the databases running in `modules`,
+/// until `module` exits.
+pub fn spawn_coordinator(
+    module: ModuleHost,
+    dbic: Arc<DatabaseInstanceContext>,
+    modules: Arc<Mutex<HashMap<u64, ModuleHost>>>,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+            () = coordinate(&module, &dbic, &modules) => {}
+            () = module.exited() => {}
+        }
+    });
+}
+
+async fn coordinate(module: &ModuleHost, dbic: &DatabaseInstanceContext, modules: &Mutex<HashMap<u64, ModuleHost>>) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        // The messages left over from before the module started are delivered right away.
+        let delivered_all = deliver_pending(module, dbic, modules).await.unwrap_or_else(|e| {
+            log::error!("Failed to deliver the messages of {}: {e}", dbic.address.to_hex());
+            false
+        });
+        if delivered_all {
+            backoff = INITIAL_BACKOFF;
+            dbic.outbox.notified().await;
+        } else {
+            tokio::select! {
+                () = dbic.outbox.notified() => {}
+                () = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Delivers the messages of `st_outbox` not delivered yet, returning whether all were.
+///
+/// A message that can't be delivered holds up the ones sent after it to the same target.
+async fn deliver_pending(
+    module: &ModuleHost,
+    dbic: &DatabaseInstanceContext,
+    modules: &Mutex<HashMap<u64, ModuleHost>>,
+) -> Result<bool, DBError> {
+    let stdb = &dbic.relational_db;
+    let tx = stdb.begin_read_only_tx();
+    let pending = stdb.pending_messages(&tx);
+    stdb.release_tx(tx);
+
+    let mut held_up = Vec::new();
+    for message in pending? {
+        if held_up.contains(&message.target) {
+            continue;
+        }
+        if !deliver(module, dbic, modules, &message).await? {
+            held_up.push(message.target);
+        }
+    }
+    Ok(held_up.is_empty())
+}
+
+/// Calls the reducer of the target of `message` with it, unless it already received it,
+/// and marks it delivered, returning whether it was.
+async fn deliver(
+    module: &ModuleHost,
+    dbic: &DatabaseInstanceContext,
+    modules: &Mutex<HashMap<u64, ModuleHost>>,
+    message: &StOutboxRow<String, Vec<u8>>,
+) -> Result<bool, DBError> {
+    let target = modules
+        .lock()
+        .unwrap()
+        .values()
+        .find(|target| target.info().address == message.target)
+        .cloned();
+    let Some(target) = target else {
+        return Ok(false);
+    };
+
+    let target_db = &target.info().relational_db;
+    let tx = target_db.begin_read_only_tx();
+    let last_received = target_db.last_message_received(&tx, dbic.address);
+    target_db.release_tx(tx);
+
+    if message.msg_id > last_received? {
+        let received = ReceivedMessage {
+            sender: dbic.address,
+            msg_id: message.msg_id,
+        };
+        let args = ReducerArgs::Bsatn(message.args.clone().into());
+        match target
+            .deliver_message(dbic.identity, received, &message.reducer_name, args)
+            .await
+        {
+            Ok(_) => {}
+            Err(ReducerCallError::NoSuchModule(_) | ReducerCallError::Cooldown { .. }) => return Ok(false),
+            Err(e) => {
+                let _ = module
+                    .inject_logs(
+                        LogLevel::Error,
+                        format!(
+                            "Dropped the message to reducer \"{}\" of {}: {e}",
+                            message.reducer_name,
+                            message.target.to_hex()
+                        ),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    let stdb = &dbic.relational_db;
+    stdb.with_auto_commit(|tx| stdb.ack_message(tx, message.target, message.msg_id))?;
+    Ok(true)
+}
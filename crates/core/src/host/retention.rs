@@ -0,0 +1,77 @@
+//! Keeping the module log of each database within the retention configured for the node,
+//! and recording the disk space it and the commit log use in `st_disk_usage`,
+//! and the space used by each table and index in `st_storage` and in the worker metrics.
+//!
+//! The segments of the commit log are only measured, never deleted,
+//! as a database is rebuilt at startup by replaying its whole commit log.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::database_instance_context::DatabaseInstanceContext;
+use crate::database_logger::MODULE_LOG_RETENTION;
+use crate::db::datastore::system_tables::StDiskUsageRow;
+use crate::host::{ModuleHost, Timestamp};
+use crate::worker_metrics::STORAGE_BYTES;
+
+/// How often the retention of each database is enforced.
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Enforce the retention of the database of `dbic` every [`ENFORCE_INTERVAL`], until `module` exits.
+pub fn spawn_enforcer(module: ModuleHost, dbic: Arc<DatabaseInstanceContext>) {
+    tokio::spawn(async move {
+        tokio::select! {
+            () = enforce(&dbic) => {}
+            () = module.exited() => {}
+        }
+    });
+}
+
+async fn enforce(dbic: &DatabaseInstanceContext) {
+    let mut interval = tokio::time::interval(ENFORCE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let logger = dbic.logger.clone();
+        let now = SystemTime::now();
+        let log_usage =
+            tokio::task::spawn_blocking(move || logger.lock().unwrap().enforce_retention(&MODULE_LOG_RETENTION, now))
+                .await;
+
+        let measured_at = Timestamp::now().0;
+        let mut rows = Vec::new();
+        match log_usage {
+            Ok(Ok(usage)) => rows.push(StDiskUsageRow {
+                component: "module_log".into(),
+                bytes: usage.bytes,
+                files: usage.files,
+                measured_at,
+            }),
+            Ok(Err(e)) => log::warn!(
+                "Failed to enforce the retention of the module log of {}: {e}",
+                dbic.address.to_hex()
+            ),
+            Err(e) => log::error!(
+                "Enforcing the retention of the module log of {} panicked: {e}",
+                dbic.address.to_hex()
+            ),
+        }
+        if let Some((bytes, segments)) = dbic.relational_db.commit_log_usage() {
+            rows.push(StDiskUsageRow {
+                component: "commit_log".into(),
+                bytes,
+                files: segments as u32,
+                measured_at,
+            });
+        }
+        dbic.relational_db.record_disk_usage(&rows);
+
+        let address = dbic.address.to_hex();
+        for row in dbic.relational_db.record_storage(measured_at) {
+            STORAGE_BYTES
+                .with_label_values(&[&address, &row.kind, &row.name])
+                .set(row.bytes as i64);
+        }
+    }
+}
@@ -1,15 +1,15 @@
 use std::path::Path;
 
-use futures::StreamExt;
-use rustc_hash::FxHashMap;
 use sled::transaction::{ConflictableTransactionError::Abort as TxAbort, TransactionError};
 use spacetimedb_lib::bsatn;
 use spacetimedb_lib::bsatn::ser::BsatnError;
 use tokio::sync::mpsc;
-use tokio_util::time::{delay_queue, DelayQueue};
 
 use super::module_host::WeakModuleHost;
+use super::timer_wheel::TimerWheel;
 use super::{ModuleHost, ReducerArgs, ReducerCallError, Timestamp};
+use crate::address::Address;
+use crate::worker_metrics::{SCHEDULED_REDUCERS, SCHEDULED_REDUCER_BATCH_SIZE, SCHEDULED_REDUCER_DELAY};
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct ScheduledReducerId(pub u64);
@@ -71,29 +71,39 @@ impl Scheduler {
     pub fn clear(&self) {
         self.db.clear().unwrap()
     }
+
+    /// Write the scheduled reducers to disk, rather than waiting for the next periodic flush.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
 }
 
 impl SchedulerStarter {
     // TODO(cloutiertyler): This whole start dance is scuffed, but I don't have
     // time to make it better right now.
-    pub fn start(self, module_host: &ModuleHost) -> anyhow::Result<()> {
-        let mut queue = DelayQueue::new();
+    pub fn start(self, module_host: &ModuleHost, address: Address) -> anyhow::Result<()> {
+        let mut wheel = TimerWheel::new(now_tick());
 
         for entry in self.db.iter() {
             let (k, v) = entry?;
             let get_u64 = |b: &[u8]| u64::from_le_bytes(b.try_into().unwrap());
             let id = ScheduledReducerId(get_u64(&k));
             let at = Timestamp(get_u64(&v[..8]));
-            queue.insert(id, at.to_duration_from_now());
+            wheel.insert(id, tick_of(at));
         }
 
+        let address = address.to_abbreviated_hex();
+        SCHEDULED_REDUCERS
+            .with_label_values(&[&address])
+            .set(wheel.len() as i64);
         tokio::spawn(
             SchedulerActor {
                 rx: self.rx,
-                queue,
-                key_map: FxHashMap::default(),
+                wheel,
                 db: self.db,
                 module_host: module_host.downgrade(),
+                address,
             }
             .run(),
         );
@@ -102,43 +112,27 @@ impl SchedulerStarter {
     }
 }
 
-/// The maximum `Duration` into the future that we can schedule a reducer.
-///
-/// `tokio_utils::time::DelayQueue`, as of version 0.7.8,
-/// limits its scheduling to at most approx. 2 years into the future.
-/// More specifically, they define:
-/// ```ignore
-/// const NUM_LEVELS: usize = 6;
-/// const MAX_DURATION: u64 = (1 << (6 * NUM_LEVELS)) - 1;
-/// ```
-/// These specific incantations have to do with the internal representation
-/// of `DelayQueue`.
+/// The length of a tick of the [TimerWheel] of a scheduler, in micro seconds.
 ///
-/// Unfortunately, rather than returning an `Err`
-/// if the requested duration is longer than `MAX_DURATION`,
-/// `DelayQueue` will panic.
-/// We can't allow users to crash SpacetimeDB
-/// by scheduling a reducer in the distant future,
-/// so we have to re-derive their maximum delay
-/// and check against it ourselves.
-///
-/// The exact range of delays supported by `DelayQueue` may change in the future,
-/// but (hopefully) it won't ever shrink, as that would be a user-visible regression.
-/// If `DelayQueue` extends to support a larger range,
-/// we may reject some long-delayed schedule calls which could succeed,
-/// but we will never permit a schedule attempt which will panic.
-const MAX_SCHEDULE_DELAY: std::time::Duration = std::time::Duration::from_millis(
-    // Equal to 64^6 - 1 milliseconds, which is 2.177589 years.
-    (1 << (6 * 6)) - 1,
-);
+/// A scheduled reducer is called no earlier than its time, but up to a tick later.
+const TICK_MICROS: u64 = 1_000;
+
+/// Returns the tick of the [TimerWheel] at which to call a reducer scheduled `at`.
+fn tick_of(at: Timestamp) -> u64 {
+    at.0.saturating_add(TICK_MICROS - 1) / TICK_MICROS
+}
+
+/// Returns the tick of the [TimerWheel] that has started by now.
+fn now_tick() -> u64 {
+    Timestamp::now().0 / TICK_MICROS
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum ScheduleError {
-    #[error("Unable to schedule with long delay at {0:?}")]
-    DelayTooLong(Timestamp),
-
     #[error("Unable to generate a ScheduledReducerId: {0:?}")]
     IdTransactionError(#[from] TransactionError<BsatnError>),
+    #[error("can't schedule or cancel a reducer; the transaction is read-only")]
+    ReadOnlyTransaction,
 }
 
 impl Scheduler {
@@ -148,50 +142,43 @@ impl Scheduler {
         bsatn_args: Vec<u8>,
         at: Timestamp,
     ) -> Result<ScheduledReducerId, ScheduleError> {
-        // Check that `at` is within `tokio_utils::time::DelayQueue`'s accepted time-range.
-        //
-        // `DelayQueue` uses a sliding window,
-        // and there may be some non-zero delay between this check
-        // and the actual call to `DelayQueue::insert`.
-        //
-        // Assuming a monotonic clock,
-        // this means we may reject some otherwise acceptable schedule calls.
-        //
-        // If `Timestamp::to_duration_from_now` is not monotonic,
-        // i.e. `std::time::SystemTime` is not monotonic,
-        // `DelayQueue::insert` may panic.
-        // This will happen if a module attempts to schedule a reducer
-        // with a delay just before the two-year limit,
-        // and the system clock is adjusted backwards
-        // after the check but before scheduling so that after the adjustment,
-        // the delay is beyond the two-year limit.
-        //
-        // We could avoid this edge case by scheduling in terms of the monotonic `Instant`,
-        // rather than `SystemTime`,
-        // but we don't currently have a meaningful way
-        // to convert a `Timestamp` into an `Instant`.
-        let delay = at.to_duration_from_now();
-        if delay >= MAX_SCHEDULE_DELAY {
-            return Err(ScheduleError::DelayTooLong(at));
-        }
+        let id = self.reserve_id()?;
+        self.schedule_reserved(id, reducer, bsatn_args, at)?;
+        Ok(id)
+    }
+
+    /// Generates the id of a reducer to [schedule](Self::schedule_reserved) later,
+    /// e.g., once the transaction scheduling it commits.
+    pub fn reserve_id(&self) -> Result<ScheduledReducerId, ScheduleError> {
+        let id: Result<_, TransactionError<BsatnError>> =
+            self.db.transaction(|tx| Ok(ScheduledReducerId(tx.generate_id()?)));
+        Ok(id?)
+    }
 
+    /// Schedules `reducer` under the `id` [reserved](Self::reserve_id) for it.
+    pub fn schedule_reserved(
+        &self,
+        id: ScheduledReducerId,
+        reducer: String,
+        bsatn_args: Vec<u8>,
+        at: Timestamp,
+    ) -> Result<(), ScheduleError> {
         let reducer = ScheduledReducer {
             at,
             reducer,
             bsatn_args,
         };
 
-        let id = self.db.transaction(|tx| {
-            let id = tx.generate_id()?;
+        self.db.transaction(|tx| {
             let reducer = bsatn::to_vec(&reducer).map_err(TxAbort)?;
-            tx.insert(&id.to_le_bytes(), reducer)?;
-            Ok(ScheduledReducerId(id))
+            tx.insert(&id.0.to_le_bytes(), reducer)?;
+            Ok(())
         })?;
 
         // if the actor has exited, it's fine to ignore; it means that the host actor calling
         // schedule will exit soon as well, and it'll be scheduled to run when the module host restarts
         let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule { id, at }));
-        Ok(id)
+        Ok(())
     }
 
     pub fn cancel(&self, id: ScheduledReducerId) {
@@ -219,15 +206,25 @@ impl Scheduler {
 
 struct SchedulerActor {
     rx: mpsc::UnboundedReceiver<MsgOrExit<SchedulerMessage>>,
-    queue: DelayQueue<ScheduledReducerId>,
-    key_map: FxHashMap<ScheduledReducerId, delay_queue::Key>,
+    wheel: TimerWheel<ScheduledReducerId>,
     db: sled::Db,
     module_host: WeakModuleHost,
+    /// The abbreviated address of the database, as a label of the metrics.
+    address: String,
 }
 
 impl SchedulerActor {
     async fn run(mut self) {
         loop {
+            let next_tick = self.wheel.next_tick();
+            let due = async {
+                match next_tick {
+                    Some(tick) => {
+                        tokio::time::sleep(Timestamp(tick.saturating_mul(TICK_MICROS)).to_duration_from_now()).await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
             tokio::select! {
                 msg = self.rx.recv() => match msg {
                     Some(MsgOrExit::Msg(msg)) => self.handle_message(msg),
@@ -235,58 +232,68 @@ impl SchedulerActor {
                     // already been stored in the database
                     Some(MsgOrExit::Exit) | None => break,
                 },
-                Some(scheduled) = self.queue.next() => {
-                    let id = scheduled.into_inner();
-                    self.key_map.remove(&id);
-                    self.handle_queued(id).await;
+                () = due => {
+                    let ids = self.wheel.advance(now_tick());
+                    if !ids.is_empty() {
+                        self.handle_due(ids);
+                    }
                 }
             }
+            SCHEDULED_REDUCERS
+                .with_label_values(&[&self.address])
+                .set(self.wheel.len() as i64);
         }
     }
 
     fn handle_message(&mut self, msg: SchedulerMessage) {
         match msg {
-            SchedulerMessage::Schedule { id, at } => {
-                let key = self.queue.insert(id, at.to_duration_from_now());
-                self.key_map.insert(id, key);
-            }
+            SchedulerMessage::Schedule { id, at } => self.wheel.insert(id, tick_of(at)),
             SchedulerMessage::Cancel { id } => {
-                if let Some(key) = self.key_map.remove(&id) {
-                    self.queue.remove(&key);
-                }
+                self.wheel.remove(id);
             }
         }
     }
 
-    async fn handle_queued(&self, id: ScheduledReducerId) {
+    /// Calls the reducers due, in a batch, from a single task.
+    fn handle_due(&self, ids: Vec<ScheduledReducerId>) {
         let Some(module_host) = self.module_host.upgrade() else {
             return;
         };
-        let Some(scheduled) = self.db.get(id.0.to_le_bytes()).unwrap() else {
-            return;
-        };
-        let scheduled: ScheduledReducer = bsatn::from_slice(&scheduled).unwrap();
+        SCHEDULED_REDUCER_BATCH_SIZE
+            .with_label_values(&[&self.address])
+            .observe(ids.len() as f64);
         let db = self.db.clone();
+        let address = self.address.clone();
         tokio::spawn(async move {
             let identity = module_host.info().identity;
-            // TODO: pass a logical "now" timestamp to this reducer call, but there's some
-            //       intricacies to get right (how much drift to tolerate? what kind of tokio::time::MissedTickBehavior do we want?)
-            let res = module_host
-                .call_reducer(
-                    identity,
-                    None,
-                    &scheduled.reducer,
-                    ReducerArgs::Bsatn(scheduled.bsatn_args.into()),
-                )
-                .await;
-            if !matches!(res, Err(ReducerCallError::NoSuchModule(_))) {
-                // if we didn't actually call the reducer because the module exited, leave
-                // the ScheduledReducer in the database for when the module restarts
+            for id in ids {
+                let Some(scheduled) = db.get(id.0.to_le_bytes()).unwrap() else {
+                    continue;
+                };
+                let scheduled: ScheduledReducer = bsatn::from_slice(&scheduled).unwrap();
+                let delay = Timestamp::now().0.saturating_sub(scheduled.at.0);
+                SCHEDULED_REDUCER_DELAY
+                    .with_label_values(&[&address])
+                    .observe(delay as f64 / 1e6);
+                // TODO: pass a logical "now" timestamp to this reducer call, but there's some
+                //       intricacies to get right (how much drift to tolerate? what kind of tokio::time::MissedTickBehavior do we want?)
+                let res = module_host
+                    .call_reducer(
+                        identity,
+                        None,
+                        &scheduled.reducer,
+                        ReducerArgs::Bsatn(scheduled.bsatn_args.into()),
+                    )
+                    .await;
+                if matches!(res, Err(ReducerCallError::NoSuchModule(_))) {
+                    // if we didn't actually call the reducer because the module exited, leave
+                    // it and the rest of the batch in the database for when the module restarts
+                    break;
+                }
                 let _ = db.remove(id.0.to_le_bytes());
-            }
-            match res {
-                Ok(_) => {}
-                Err(e) => log::error!("invoking scheduled reducer failed: {e:#}"),
+                if let Err(e) = res {
+                    log::error!("invoking scheduled reducer failed: {e:#}");
+                }
             }
         });
     }
@@ -0,0 +1,257 @@
+//! A hierarchical timer wheel, in which the [scheduler](super::scheduler) keeps the reducers it's to call.
+//!
+//! Time is counted in ticks, and the wheel has [`LEVELS`] levels of [`SLOTS`] slots each,
+//! a slot of a level spanning as many ticks as the whole level below it.
+//! A timer is put in the lowest level where its deadline and the current tick only differ within a slot,
+//! and moved down a level whenever the current tick reaches the start of its slot,
+//! so inserting, cancelling and firing a timer take constant time, however many there are.
+//! Timers further ahead than the wheel spans wait in an ordered map until they're within reach.
+
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use rustc_hash::FxHashMap;
+
+/// The number of bits of a tick that select a slot in a level.
+const SLOT_BITS: u32 = 6;
+/// The number of slots in a level.
+const SLOTS: usize = 1 << SLOT_BITS;
+/// The number of levels of the wheel.
+const LEVELS: usize = 6;
+/// The number of ticks the wheel spans.
+const RANGE: u64 = 1 << (SLOT_BITS as usize * LEVELS);
+
+struct Level<T> {
+    /// A bit for each slot, set if it has timers.
+    occupied: u64,
+    /// The timers of each slot, with their deadlines.
+    slots: Vec<Vec<(T, u64)>>,
+}
+
+impl<T> Level<T> {
+    fn new() -> Self {
+        Self {
+            occupied: 0,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+        }
+    }
+}
+
+pub struct TimerWheel<T> {
+    /// The tick up to which the timers have been fired.
+    elapsed: u64,
+    levels: Vec<Level<T>>,
+    /// The timers beyond the span of the wheel, by deadline.
+    overflow: BTreeMap<u64, Vec<T>>,
+    /// The timers whose deadline has passed, yet to be returned by [`TimerWheel::advance`].
+    ready: Vec<(T, u64)>,
+    /// The deadline of each timer that hasn't been fired nor removed.
+    ///
+    /// A removed timer is only dropped from here, and skipped when its slot is reached.
+    deadlines: FxHashMap<T, u64>,
+}
+
+impl<T: Copy + Eq + Hash> TimerWheel<T> {
+    /// Returns an empty wheel whose current tick is `now`.
+    pub fn new(now: u64) -> Self {
+        Self {
+            elapsed: now,
+            levels: (0..LEVELS).map(|_| Level::new()).collect(),
+            overflow: BTreeMap::new(),
+            ready: Vec::new(),
+            deadlines: FxHashMap::default(),
+        }
+    }
+
+    /// Returns the number of timers yet to fire.
+    pub fn len(&self) -> usize {
+        self.deadlines.len()
+    }
+
+    /// Adds the timer `id`, to fire at the tick `deadline`, replacing the one with the same `id`, if any.
+    ///
+    /// A `deadline` that has already passed fires on the next [`TimerWheel::advance`].
+    pub fn insert(&mut self, id: T, deadline: u64) {
+        self.deadlines.insert(id, deadline);
+        self.place(id, deadline);
+    }
+
+    /// Removes the timer `id`, returning whether it was yet to fire.
+    pub fn remove(&mut self, id: T) -> bool {
+        self.deadlines.remove(&id).is_some()
+    }
+
+    /// Returns the tick at which [`TimerWheel::advance`] should next be called, if there are any timers.
+    ///
+    /// A timer may not fire then, but only be moved closer to firing.
+    pub fn next_tick(&self) -> Option<u64> {
+        if !self.ready.is_empty() {
+            return Some(self.elapsed);
+        }
+        let slot = self.next_slot().map(|(_, _, start)| start);
+        let overflow = self.overflow.keys().next().map(|&deadline| deadline & !(RANGE - 1));
+        slot.or(overflow)
+    }
+
+    /// Moves the current tick up to `now`, returning the timers whose deadline has passed,
+    /// in the order of their deadlines, except for those that had already passed when inserted, which come first.
+    pub fn advance(&mut self, now: u64) -> Vec<T> {
+        let mut fired = Vec::new();
+        loop {
+            self.refill();
+            for (id, deadline) in std::mem::take(&mut self.ready) {
+                if self.is_live(id, deadline) {
+                    self.deadlines.remove(&id);
+                    fired.push(id);
+                }
+            }
+
+            if let Some((level, slot, start)) = self.next_slot().filter(|&(_, _, start)| start <= now) {
+                // The timers of the slot are either due, or moved to a lower level.
+                self.elapsed = start;
+                let level = &mut self.levels[level];
+                level.occupied &= !(1 << slot);
+                let timers = std::mem::take(&mut level.slots[slot]);
+                for (id, deadline) in timers {
+                    if self.is_live(id, deadline) {
+                        self.place(id, deadline);
+                    }
+                }
+                continue;
+            }
+
+            // The wheel is empty, but some timers may be within reach once the current tick gets to their span.
+            match self.overflow.keys().next().map(|&deadline| deadline & !(RANGE - 1)) {
+                Some(start) if start <= now && start > self.elapsed => self.elapsed = start,
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    return fired;
+                }
+            }
+        }
+    }
+
+    /// Returns whether the timer `id` is still to fire at `deadline`.
+    fn is_live(&self, id: T, deadline: u64) -> bool {
+        self.deadlines.get(&id) == Some(&deadline)
+    }
+
+    /// Puts the timer `id` in the slot for its `deadline`, relative to the current tick.
+    fn place(&mut self, id: T, deadline: u64) {
+        if deadline <= self.elapsed {
+            self.ready.push((id, deadline));
+            return;
+        }
+        // The highest bits that differ between the current tick and the deadline select the level.
+        let masked = (self.elapsed ^ deadline) | (SLOTS as u64 - 1);
+        let level = (63 - masked.leading_zeros()) as usize / SLOT_BITS as usize;
+        if level >= LEVELS {
+            self.overflow.entry(deadline).or_default().push(id);
+            return;
+        }
+        let slot = (deadline >> (SLOT_BITS as usize * level)) as usize & (SLOTS - 1);
+        let level = &mut self.levels[level];
+        level.occupied |= 1 << slot;
+        level.slots[slot].push((id, deadline));
+    }
+
+    /// Moves the timers that have come within the span of the wheel into it.
+    fn refill(&mut self) {
+        while let Some(entry) = self.overflow.first_entry() {
+            if (*entry.key() ^ self.elapsed) >= RANGE {
+                break;
+            }
+            let (deadline, ids) = entry.remove_entry();
+            for id in ids {
+                if self.is_live(id, deadline) {
+                    self.place(id, deadline);
+                }
+            }
+        }
+    }
+
+    /// Returns the level and slot with timers that the current tick reaches first, and the tick that starts it.
+    ///
+    /// The slots of a level before that of the current tick are always empty,
+    /// as their timers were moved down or fired when it passed them.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .filter(|(_, level)| level.occupied != 0)
+            .map(|(i, level)| {
+                let slot = level.occupied.trailing_zeros() as usize;
+                let slot_range = 1u64 << (SLOT_BITS as usize * i);
+                let level_range = slot_range << SLOT_BITS;
+                let start = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+                (i, slot, start)
+            })
+            .min_by_key(|&(i, _, start)| (start, i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fires_in_order() {
+        let mut wheel = TimerWheel::new(1_000);
+        for (id, deadline) in [(1, 1_500), (2, 1_001), (3, 70_000), (4, 5_000_000)] {
+            wheel.insert(id, deadline);
+        }
+        assert_eq!(wheel.len(), 4);
+
+        assert!(wheel.advance(1_000).is_empty());
+        assert_eq!(wheel.advance(1_500), [2, 1]);
+        assert!(wheel.advance(69_999).is_empty());
+        assert_eq!(wheel.advance(10_000_000), [3, 4]);
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_tick(), None);
+    }
+
+    #[test]
+    fn test_remove_and_past_deadlines() {
+        let mut wheel = TimerWheel::new(100);
+        wheel.insert(1, 200);
+        wheel.insert(2, 300);
+        wheel.insert(3, 50);
+        assert_eq!(wheel.next_tick(), Some(100));
+
+        assert!(wheel.remove(2));
+        assert!(!wheel.remove(2));
+        assert_eq!(wheel.advance(100), [3]);
+
+        // Moving a timer fires it once, at its new deadline.
+        wheel.insert(1, 400);
+        assert!(wheel.advance(300).is_empty());
+        assert_eq!(wheel.advance(400), [1]);
+        assert_eq!(wheel.len(), 0);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut wheel = TimerWheel::new(0);
+        let far = RANGE * 3 + 42;
+        wheel.insert(1, far);
+        wheel.insert(2, 10);
+        assert_eq!(wheel.next_tick(), Some(10));
+        assert_eq!(wheel.advance(10), [2]);
+        assert_eq!(wheel.next_tick(), Some(RANGE * 3));
+
+        assert!(wheel.advance(far - 1).is_empty());
+        assert_eq!(wheel.advance(far), [1]);
+    }
+
+    #[test]
+    fn test_many_in_a_batch() {
+        let mut wheel = TimerWheel::new(0);
+        for id in 0..100_000u64 {
+            wheel.insert(id, 1 + id % 5_000);
+        }
+        let fired = wheel.advance(2_500);
+        assert_eq!(fired.len(), 50_000);
+        assert!(fired.iter().all(|id| id % 5_000 < 2_500));
+        assert_eq!(wheel.advance(5_000).len(), 50_000);
+    }
+}
@@ -1,8 +1,9 @@
 #![allow(clippy::too_many_arguments)]
 
 use crate::host::Timestamp;
+use crate::identity::Identity;
 use crate::messages::instance_db_trace_log::{
-    CreateIndex, DeleteByColEq, GetTableId, Insert, InstanceEvent, InstanceEventType,
+    CallReducer, CreateIndex, DeleteByColEq, GetTableId, Insert, InstanceEvent, InstanceEventType,
 };
 use flate2::write::GzEncoder;
 use flate2::Compression;
@@ -226,4 +227,23 @@ impl TraceLog {
         });
         self.write_event(start_time, duration, event)
     }
+
+    /// Record a call to a reducer, so that it can be replayed against another build of the module.
+    pub fn call_reducer(
+        &mut self,
+        start_time: SystemTime,
+        duration: Duration,
+        reducer_name: String,
+        caller_identity: Identity,
+        timestamp: Timestamp,
+        args: Vec<u8>,
+    ) {
+        let event = InstanceEventType::CallReducer(CallReducer {
+            reducer_name,
+            caller_identity,
+            timestamp,
+            args,
+        });
+        self.write_event(start_time, duration, event)
+    }
 }
@@ -1,2 +1,3 @@
 pub mod instance_trace;
 pub mod replay;
+pub mod simulate;
@@ -23,6 +23,7 @@ pub enum ReplayEventType {
     Iter(Vec<u8>),
     GetTableId(u32),
     CreateIndex,
+    CallReducer,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -54,6 +55,7 @@ impl From<InstanceEventType> for ReplayEventType {
             InstanceEventType::GetTableId(event) => Self::GetTableId(event.result_table_id),
             InstanceEventType::Iter(event) => Self::Iter(event.result_bytes),
             InstanceEventType::CreateIndex(_) => Self::CreateIndex,
+            InstanceEventType::CallReducer(_) => Self::CallReducer,
         }
     }
 }
@@ -70,25 +72,40 @@ pub struct ReplayTracelog<'a, R> {
 
 impl<'a, R: Read> ReplayTracelog<'a, R> {
     fn try_next(&mut self) -> anyhow::Result<Option<(ReplayEvent, ReplayEvent)>> {
-        let mut len_byte: [u8; 8] = [0; 8];
-        let prefix_result = self.reader.read_exact(&mut len_byte[..]);
-        match prefix_result {
-            Ok(()) => {}
-            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
-            Err(e) => return Err(e.into()),
-        }
-        let compressed_event_len = usize::from_le_bytes(len_byte);
-
-        let mut d = GzDecoder::new(self.reader.by_ref().take(compressed_event_len as u64));
-        let mut event_buffer = vec![];
-        d.read_to_end(&mut event_buffer)?;
-
-        let old_event: InstanceEvent = bsatn::from_slice(&event_buffer)?;
+        let old_event = loop {
+            match read_event(&mut self.reader)? {
+                // The sys calls the reducer made are in the log by themselves.
+                Some(InstanceEvent {
+                    r#type: InstanceEventType::CallReducer(_),
+                    ..
+                }) => continue,
+                Some(event) => break event,
+                None => return Ok(None),
+            }
+        };
         let new_event = execute_event(self.instance_env, &old_event.r#type)?;
         Ok(Some((old_event.into(), new_event)))
     }
 }
 
+/// Read the next event of a trace log, or `None` at its end.
+pub fn read_event(reader: &mut impl Read) -> anyhow::Result<Option<InstanceEvent>> {
+    let mut len_byte: [u8; 8] = [0; 8];
+    let prefix_result = reader.read_exact(&mut len_byte[..]);
+    match prefix_result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let compressed_event_len = usize::from_le_bytes(len_byte);
+
+    let mut d = GzDecoder::new(reader.take(compressed_event_len as u64));
+    let mut event_buffer = vec![];
+    d.read_to_end(&mut event_buffer)?;
+
+    Ok(Some(bsatn::from_slice(&event_buffer)?))
+}
+
 impl<'a, R: Read> Iterator for ReplayTracelog<'a, R> {
     type Item = anyhow::Result<(ReplayEvent, ReplayEvent)>;
 
@@ -152,6 +169,7 @@ fn execute_event(instance_env: &InstanceEnv, event: &InstanceEventType) -> anyho
             instance_env.create_index(ci.index_name.clone(), ci.table_id, ci.index_type as u8, col_ids)?;
             ReplayEventType::CreateIndex
         }
+        InstanceEventType::CallReducer(_) => ReplayEventType::CallReducer,
     };
     Ok(ReplayEvent {
         duration: start_time.elapsed().unwrap(),
@@ -0,0 +1,285 @@
+//! Replays the reducer calls recorded in a trace log against two builds of a module,
+//! each into a fresh in-memory database, and diffs the datastore state they end up with.
+//!
+//! Unlike [`replay_report`](super::replay::replay_report), which re-executes the recorded sys calls,
+//! this runs the reducers themselves, so it can tell whether a new build would have left
+//! the database in the same state as the old one given the same traffic.
+
+use crate::address::Address;
+use crate::database_instance_context::DatabaseInstanceContext;
+use crate::db::Storage;
+use crate::hash::hash_bytes;
+use crate::host::scheduler::Scheduler;
+use crate::host::{wasmer, ModuleHost, NullEnergyMonitor, ReducerArgs, ReducerOutcome};
+use crate::identity::Identity;
+use crate::messages::instance_db_trace_log::{CallReducer, InstanceEventType};
+use anyhow::Context;
+use serde::Serialize;
+use spacetimedb_lib::auth::StTableType;
+use spacetimedb_sats::ProductValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use tempdir::TempDir;
+
+use super::replay::read_event;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulationReport {
+    pub old_module_hash: String,
+    pub new_module_hash: String,
+    /// The number of reducer calls replayed against each build.
+    pub replayed: usize,
+    /// The calls which had a different outcome under each build, in replay order.
+    pub calls: Vec<CallDivergence>,
+    /// The tables whose rows differ between the builds, sorted by name.
+    pub tables: Vec<TableDivergence>,
+}
+
+impl SimulationReport {
+    pub fn diverged(&self) -> bool {
+        !self.calls.is_empty() || !self.tables.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CallDivergence {
+    /// The position of the call in the trace log.
+    pub index: usize,
+    pub reducer: String,
+    pub old_outcome: String,
+    pub new_outcome: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TableDivergence {
+    pub table_name: String,
+    /// The number of rows in the table under the old build, or `None` if it has no such table.
+    pub old_row_count: Option<usize>,
+    /// The number of rows in the table under the new build, or `None` if it has no such table.
+    pub new_row_count: Option<usize>,
+    pub only_in_old: Vec<ProductValue>,
+    pub only_in_new: Vec<ProductValue>,
+}
+
+/// The state a build of the module was left in after replaying the trace.
+struct SimulatedBuild {
+    module_hash: String,
+    outcomes: Vec<String>,
+    tables: BTreeMap<String, BTreeSet<ProductValue>>,
+}
+
+/// Replay the reducer calls of the trace log in `reader` against `old_program` and `new_program`,
+/// and report where the two builds diverge.
+///
+/// Each build gets a fresh in-memory database owned by `owner`, initialized with no arguments.
+/// The calls are made in the order they were recorded, with their original callers and arguments,
+/// but run at the current time rather than their recorded timestamp.
+/// Reducers scheduled during the replay don't run; if they ran in the recording,
+/// their calls are in the trace log like any other.
+pub async fn simulate(
+    reader: &mut impl Read,
+    owner: Identity,
+    old_program: &[u8],
+    new_program: &[u8],
+) -> anyhow::Result<SimulationReport> {
+    let mut calls = Vec::new();
+    while let Some(event) = read_event(reader)? {
+        if let InstanceEventType::CallReducer(call) = event.r#type {
+            calls.push(call);
+        }
+    }
+
+    let root = TempDir::new("stdb_simulate")?;
+    let old = simulate_build(&root.path().join("old"), 0, owner, old_program, &calls)
+        .await
+        .context("replaying against the old build")?;
+    let new = simulate_build(&root.path().join("new"), 1, owner, new_program, &calls)
+        .await
+        .context("replaying against the new build")?;
+
+    let divergent_calls = calls
+        .iter()
+        .zip(old.outcomes.iter().zip(&new.outcomes))
+        .enumerate()
+        .filter(|(_, (_, (old, new)))| old != new)
+        .map(|(index, (call, (old, new)))| CallDivergence {
+            index,
+            reducer: call.reducer_name.clone(),
+            old_outcome: old.clone(),
+            new_outcome: new.clone(),
+        })
+        .collect();
+
+    Ok(SimulationReport {
+        old_module_hash: old.module_hash,
+        new_module_hash: new.module_hash,
+        replayed: calls.len(),
+        calls: divergent_calls,
+        tables: diff_tables(&old.tables, &new.tables),
+    })
+}
+
+async fn simulate_build(
+    root: &Path,
+    instance_id: u64,
+    owner: Identity,
+    program_bytes: &[u8],
+    calls: &[CallReducer],
+) -> anyhow::Result<SimulatedBuild> {
+    let dbic = DatabaseInstanceContext::new(
+        Storage::Memory,
+        instance_id,
+        instance_id,
+        false,
+        owner,
+        Address::from_arr(&[0; 16]),
+        root.join("database"),
+        &root.join("module_logs"),
+    );
+    let module_hash = hash_bytes(program_bytes);
+    let scheduler = Scheduler::dummy(&root.join("scheduler"));
+    let (module_host, module_starter) = tokio::task::block_in_place(|| {
+        let actor = wasmer::make_actor(
+            dbic.clone(),
+            module_hash,
+            program_bytes,
+            scheduler,
+            Arc::new(NullEnergyMonitor),
+        )?;
+        anyhow::Ok(ModuleHost::spawn(actor))
+    })?;
+    module_starter.start();
+
+    let result = replay_calls(&module_host, &dbic, calls).await;
+    module_host.exit().await;
+    let (outcomes, tables) = result?;
+
+    Ok(SimulatedBuild {
+        module_hash: module_hash.to_hex(),
+        outcomes,
+        tables,
+    })
+}
+
+async fn replay_calls(
+    module_host: &ModuleHost,
+    dbic: &DatabaseInstanceContext,
+    calls: &[CallReducer],
+) -> anyhow::Result<(Vec<String>, BTreeMap<String, BTreeSet<ProductValue>>)> {
+    module_host
+        .init_database(ReducerArgs::Nullary)
+        .await?
+        .outcome
+        .into_result()
+        .context("init reducer failed")?;
+
+    let mut outcomes = Vec::with_capacity(calls.len());
+    for call in calls {
+        let args = ReducerArgs::Bsatn(call.args.clone().into());
+        let outcome = match module_host
+            .call_reducer(call.caller_identity, None, &call.reducer_name, args)
+            .await
+        {
+            Ok(rcr) => match rcr.outcome {
+                ReducerOutcome::Committed => "committed".to_owned(),
+                ReducerOutcome::Failed(e) => format!("failed: {e}"),
+                ReducerOutcome::BudgetExceeded => "budget exceeded".to_owned(),
+            },
+            Err(e) => format!("not called: {e}"),
+        };
+        outcomes.push(outcome);
+    }
+
+    Ok((outcomes, user_tables(dbic)?))
+}
+
+/// Collect the rows of every user table of the database, by table name.
+fn user_tables(dbic: &DatabaseInstanceContext) -> anyhow::Result<BTreeMap<String, BTreeSet<ProductValue>>> {
+    let stdb = &*dbic.relational_db;
+    let tx = stdb.begin_tx();
+    let tables = (|| {
+        let mut tables = BTreeMap::new();
+        for schema in stdb.get_all_tables(&tx)? {
+            if schema.table_type != StTableType::User {
+                continue;
+            }
+            let rows = stdb.iter(&tx, schema.table_id)?.map(|row| row.view().clone()).collect();
+            tables.insert(schema.table_name, rows);
+        }
+        anyhow::Ok(tables)
+    })();
+    stdb.rollback_tx(tx);
+    tables
+}
+
+fn diff_tables(
+    old: &BTreeMap<String, BTreeSet<ProductValue>>,
+    new: &BTreeMap<String, BTreeSet<ProductValue>>,
+) -> Vec<TableDivergence> {
+    let empty = BTreeSet::new();
+    let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old_rows = old.get(name);
+            let new_rows = new.get(name);
+            if old_rows == new_rows {
+                return None;
+            }
+            let (old_set, new_set) = (old_rows.unwrap_or(&empty), new_rows.unwrap_or(&empty));
+            Some(TableDivergence {
+                table_name: name.clone(),
+                old_row_count: old_rows.map(BTreeSet::len),
+                new_row_count: new_rows.map(BTreeSet::len),
+                only_in_old: old_set.difference(new_set).cloned().collect(),
+                only_in_new: new_set.difference(old_set).cloned().collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::AlgebraicValue;
+
+    fn rows(values: &[u32]) -> BTreeSet<ProductValue> {
+        values
+            .iter()
+            .map(|&v| ProductValue::from_iter([AlgebraicValue::U32(v)]))
+            .collect()
+    }
+
+    #[test]
+    fn diff_tables_reports_only_divergent_tables() {
+        let old = BTreeMap::from([
+            ("Same".to_owned(), rows(&[1, 2])),
+            ("Changed".to_owned(), rows(&[1, 2, 3])),
+            ("Dropped".to_owned(), rows(&[])),
+        ]);
+        let new = BTreeMap::from([
+            ("Same".to_owned(), rows(&[2, 1])),
+            ("Changed".to_owned(), rows(&[2, 3, 4])),
+            ("Added".to_owned(), rows(&[5])),
+        ]);
+
+        let diff = diff_tables(&old, &new);
+        let names = diff.iter().map(|t| t.table_name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Added", "Changed", "Dropped"]);
+
+        let added = &diff[0];
+        assert_eq!((added.old_row_count, added.new_row_count), (None, Some(1)));
+        assert_eq!(added.only_in_new, Vec::from_iter(rows(&[5])));
+
+        let changed = &diff[1];
+        assert_eq!((changed.old_row_count, changed.new_row_count), (Some(3), Some(3)));
+        assert_eq!(changed.only_in_old, Vec::from_iter(rows(&[1])));
+        assert_eq!(changed.only_in_new, Vec::from_iter(rows(&[4])));
+
+        let dropped = &diff[2];
+        assert_eq!((dropped.old_row_count, dropped.new_row_count), (Some(0), None));
+        assert!(dropped.only_in_old.is_empty());
+    }
+}
@@ -17,6 +17,10 @@ pub const INIT_DUNDER: &str = "__init__";
 pub const UPDATE_DUNDER: &str = "__update__";
 pub const IDENTITY_CONNECTED_DUNDER: &str = "__identity_connected__";
 pub const IDENTITY_DISCONNECTED_DUNDER: &str = "__identity_disconnected__";
+/// the reducer with this name re-keys the rows of an identity that has been linked to another one
+pub const IDENTITY_MERGED_DUNDER: &str = "__identity_merged__";
+/// the name under which the host's deletion of expired rows is broadcast, which modules don't export
+pub const EXPIRE_DUNDER: &str = "__expire__";
 
 pub const STDB_ABI_SYM: &str = "SPACETIME_ABI_VERSION";
 pub const STDB_ABI_IS_ADDR_SYM: &str = "SPACETIME_ABI_VERSION_IS_ADDR";
@@ -70,6 +74,7 @@ macro_rules! type_eq {
     };
 }
 type_eq!(wasmer::Type);
+type_eq!(wasmtime::ValType);
 
 #[derive(Debug)]
 pub struct FuncSig<T: AsRef<[WasmType]>> {
@@ -102,6 +107,25 @@ impl FuncSigLike for wasmer::ExternType {
     }
 }
 
+impl<T: AsRef<[WasmType]>> PartialEq<FuncSig<T>> for wasmtime::ExternType {
+    fn eq(&self, other: &FuncSig<T>) -> bool {
+        self.func().map_or(false, |f| {
+            f.params().eq(other.params.as_ref()) && f.results().eq(other.results.as_ref())
+        })
+    }
+}
+impl FuncSigLike for wasmtime::ExternType {
+    fn to_func_sig(&self) -> Option<BoxFuncSig> {
+        self.func().map(|f| FuncSig {
+            params: f.params().map(Into::into).collect(),
+            results: f.results().map(Into::into).collect(),
+        })
+    }
+    fn is_memory(&self) -> bool {
+        matches!(self, wasmtime::ExternType::Memory(_))
+    }
+}
+
 pub trait FuncSigLike: PartialEq<StaticFuncSig> {
     fn to_func_sig(&self) -> Option<BoxFuncSig>;
     fn is_memory(&self) -> bool;
@@ -302,12 +326,28 @@ pub mod errnos {
     /// Error code for when a unique constraint is violated.
     pub const UNIQUE_ALREADY_EXISTS: u16 = 3;
 
+    /// Error code for a savepoint that doesn't exist, e.g., because it was already released.
+    pub const NO_SUCH_SAVEPOINT: u16 = 4;
+
+    /// Error code for a table or row that would take the database over its quota.
+    pub const QUOTA_EXCEEDED: u16 = 5;
+
+    /// Error code for a reducer that the module doesn't define.
+    pub const NO_SUCH_REDUCER: u16 = 6;
+
+    /// Error code for a blob over the size limit.
+    pub const BLOB_TOO_LARGE: u16 = 7;
+
     macro_rules! errnos {
         ($mac:ident) => {
             $mac! {
                 NO_SUCH_TABLE => "No such table",
                 LOOKUP_NOT_FOUND => "Value or range provided not found in table",
                 UNIQUE_ALREADY_EXISTS => "Value with given unique identifier already exists",
+                NO_SUCH_SAVEPOINT => "No such savepoint",
+                QUOTA_EXCEEDED => "The database's quota was exceeded",
+                NO_SUCH_REDUCER => "No such reducer",
+                BLOB_TOO_LARGE => "The blob is over the size limit",
             }
         };
     }
@@ -321,17 +361,36 @@ pub mod errnos {
 pub fn err_to_errno(err: &NodesError) -> Option<u16> {
     match err {
         NodesError::TableNotFound => Some(errnos::NO_SUCH_TABLE),
-        NodesError::PrimaryKeyNotFound(_) | NodesError::ColumnValueNotFound | NodesError::RangeNotFound => {
-            Some(errnos::LOOKUP_NOT_FOUND)
-        }
+        NodesError::ReducerNotFound => Some(errnos::NO_SUCH_REDUCER),
+        NodesError::PrimaryKeyNotFound(_)
+        | NodesError::ColumnValueNotFound
+        | NodesError::RangeNotFound
+        | NodesError::BlobNotFound
+        | NodesError::LargeValueNotFound => Some(errnos::LOOKUP_NOT_FOUND),
         NodesError::AlreadyExists(_) => Some(errnos::UNIQUE_ALREADY_EXISTS),
         NodesError::Internal(internal) => match **internal {
             DBError::Index(IndexError::UniqueConstraintViolation {
                 constraint_name: _,
                 table_name: _,
+                col_id: _,
                 col_name: _,
                 value: _,
             }) => Some(errnos::UNIQUE_ALREADY_EXISTS),
+            DBError::SavepointNotFound(_) => Some(errnos::NO_SUCH_SAVEPOINT),
+            DBError::QuotaExceeded(_) => Some(errnos::QUOTA_EXCEEDED),
+            DBError::BlobTooLarge { .. } => Some(errnos::BLOB_TOO_LARGE),
+            DBError::LargeValueNotFound(_) => Some(errnos::LOOKUP_NOT_FOUND),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the id of the column whose unique constraint `err` reports a violation of, if it does.
+pub fn unique_conflict(err: &NodesError) -> Option<u32> {
+    match err {
+        NodesError::Internal(internal) => match **internal {
+            DBError::Index(IndexError::UniqueConstraintViolation { col_id, .. }) => Some(col_id),
             _ => None,
         },
         _ => None,
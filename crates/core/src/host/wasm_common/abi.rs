@@ -92,6 +92,29 @@ pub fn determine_spacetime_abi(wasm_module: &[u8]) -> Result<VersionTuple, AbiVe
     Ok(VersionTuple::from_u32(ver))
 }
 
+/// How a host runs a module, given the ABI version the module was built against.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AbiSupport {
+    /// The module expects the ABI the host implements, or an older minor version of it.
+    Native,
+    /// The module expects the previous major version of the ABI,
+    /// which the host runs it against by linking shims for the calls dropped since,
+    /// so that upgrading a host doesn't require republishing every module at once.
+    Previous,
+}
+
+/// Decides how a host implementing the ABI version `implement` runs a module built against `got`,
+/// or returns an error if it can't.
+pub fn negotiate(implement: VersionTuple, got: VersionTuple) -> Result<AbiSupport, AbiVersionError> {
+    if implement.supports(got) {
+        Ok(AbiSupport::Native)
+    } else if got.major.checked_add(1) == Some(implement.major) {
+        Ok(AbiSupport::Previous)
+    } else {
+        Err(AbiVersionError::UnsupportedVersion { got, implement })
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AbiVersionError {
     #[error("module doesn't indicate spacetime ABI version")]
@@ -101,3 +124,26 @@ pub enum AbiVersionError {
     #[error("abi version {got} is not supported (host implements {implement})")]
     UnsupportedVersion { got: VersionTuple, implement: VersionTuple },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let host = VersionTuple::new(3, 15);
+        assert_eq!(negotiate(host, VersionTuple::new(3, 15)).unwrap(), AbiSupport::Native);
+        assert_eq!(negotiate(host, VersionTuple::new(3, 0)).unwrap(), AbiSupport::Native);
+        assert_eq!(negotiate(host, VersionTuple::new(2, 4)).unwrap(), AbiSupport::Previous);
+        for got in [
+            VersionTuple::new(3, 16),
+            VersionTuple::new(1, 0),
+            VersionTuple::new(4, 0),
+        ] {
+            assert!(matches!(
+                negotiate(host, got),
+                Err(AbiVersionError::UnsupportedVersion { .. })
+            ));
+        }
+    }
+}
@@ -1,26 +1,40 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::db::datastore::traits::{ColumnDef, IndexDef, TableDef, TableSchema};
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::{ColId, ColumnDef, IndexDef, TableDef, TableSchema};
+use crate::db::relational_db::RelationalDB;
 use crate::host::scheduler::Scheduler;
 use anyhow::Context;
 use bytes::Bytes;
-use parking_lot::{Condvar, Mutex};
+use once_cell::sync::Lazy;
+use parking_lot::{lock_api::ArcMutexGuard, Condvar, Mutex, RawMutex};
 use spacetimedb_lib::buffer::DecodeError;
-use spacetimedb_lib::{bsatn, IndexType, ModuleDef};
+use spacetimedb_lib::de::DeserializeSeed;
+use spacetimedb_lib::name::{TableChange, UpdatePlan};
+use spacetimedb_lib::{
+    bsatn, sats, AlgebraicType, AlgebraicValue, ColumnCompression, DeferredUnique, EventDef, HttpHandler, HttpRoute,
+    IndexType, MiscModuleExport, ModuleDef, ReducerAllow, ReducerCooldown, ReducerPriority, ReducerReturn, RowVersion,
+    TableAccessHint, TableTtl, TypeAlias,
+};
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
 use tokio::sync::oneshot;
 
 use crate::client::ClientConnectionSender;
 use crate::database_instance_context::DatabaseInstanceContext;
 use crate::database_logger::{DatabaseLogger, LogLevel, Record};
+use crate::error::DBError;
 use crate::hash::Hash;
+use crate::host::http_routes::{route_shape, HttpRoutes};
 use crate::host::instance_env::InstanceEnv;
 use crate::host::module_host::{
-    DatabaseUpdate, EventStatus, ModuleEvent, ModuleFunctionCall, ModuleHostActor, ModuleInfo, UpdateDatabaseError,
-    UpdateDatabaseResult, UpdateDatabaseSuccess,
+    DatabaseUpdate, EmittedEvent, EventStatus, ModuleEvent, ModuleFunctionCall, ModuleHostActor, ModuleInfo,
+    UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess,
 };
+use crate::host::outbox::ReceivedMessage;
 use crate::host::tracelog::instance_trace::TraceLog;
 use crate::host::{
     ArgsTuple, EnergyDiff, EnergyMonitor, EnergyMonitorFingerprint, EnergyQuanta, EntityDef, ReducerCallResult,
@@ -35,6 +49,39 @@ use super::*;
 const MSG_CHANNEL_CAP: usize = 8;
 const MSG_CHANNEL_TIMEOUT: Duration = Duration::from_millis(500);
 
+/// Whether reducers run concurrently on up to [`MAX_INSTANCES`] instances of the module,
+/// rather than one at a time, in the order they were called,
+/// which is only the case if the `SPACETIMEDB_CONCURRENT_REDUCERS` environment variable is set.
+static CONCURRENT_REDUCERS: Lazy<bool> = Lazy::new(|| std::env::var_os("SPACETIMEDB_CONCURRENT_REDUCERS").is_some());
+
+/// The most instances of a module that run reducers at once,
+/// 8 unless set by the `SPACETIMEDB_MAX_INSTANCES` environment variable.
+static MAX_INSTANCES: Lazy<usize> = Lazy::new(|| env_count("SPACETIMEDB_MAX_INSTANCES", 8).max(1));
+
+/// How many instances of a module are kept instantiated and waiting, on top of those running reducers,
+/// so that a burst of calls doesn't wait for new ones to be instantiated,
+/// 2 unless set by the `SPACETIMEDB_SPARE_INSTANCES` environment variable.
+///
+/// They're instantiated in the background, each on its own thread, when the module starts,
+/// and whenever a call takes one of them, until there are [`MAX_INSTANCES`].
+///
+/// Spares are opt-in along with [`CONCURRENT_REDUCERS`]:
+/// otherwise a single instance runs every call, in order, and none are kept on top of it.
+static SPARE_INSTANCES: Lazy<usize> = Lazy::new(|| env_count("SPACETIMEDB_SPARE_INSTANCES", 2));
+
+fn env_count(var: &str, default: usize) -> usize {
+    let Ok(count) = std::env::var(var) else {
+        return default;
+    };
+    count.parse().unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid {var} {count:?}: {e}");
+        default
+    })
+}
+
+/// Held from the commit of a reducer until its event is broadcast.
+type CommitOrderGuard = ArcMutexGuard<RawMutex, ()>;
+
 pub trait WasmModule: Send + 'static {
     type Instance: WasmInstance;
     type InstancePre: WasmInstancePre<Instance = Self::Instance>;
@@ -43,6 +90,15 @@ pub trait WasmModule: Send + 'static {
     fn get_export(&self, s: &str) -> Option<Self::ExternType>;
     fn for_each_export<E>(&self, f: impl FnMut(&str, &Self::ExternType) -> Result<(), E>) -> Result<(), E>;
 
+    /// The functions the module exports for the host to call, checked to have the right signatures.
+    fn func_names(&self) -> Result<FuncNames, ValidationError> {
+        FuncNames::check_required(|name| self.get_export(name))?;
+        let mut func_names = FuncNames::default();
+        self.for_each_export(|sym, ty| func_names.update_from_general(sym, ty))?;
+        func_names.preinits.sort_unstable();
+        Ok(func_names)
+    }
+
     fn instantiate_pre(&self) -> Result<Self::InstancePre, InitializationError>;
 }
 
@@ -97,13 +153,12 @@ struct InstanceSeed<T: WasmInstancePre> {
     module: T,
     worker_database_instance: Arc<DatabaseInstanceContext>,
     event_tx: SubscriptionEventSender,
-    #[allow(dead_code)]
-    // Don't warn about 'trace_log' below when tracelogging feature isn't enabled.
     trace_log: Option<Arc<Mutex<TraceLog>>>,
     scheduler: Scheduler,
     func_names: Arc<FuncNames>,
     info: Arc<ModuleInfo>,
     energy_monitor: Arc<dyn EnergyMonitor>,
+    commit_order: Arc<Mutex<()>>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -134,6 +189,381 @@ pub enum DescribeError {
     RuntimeError(anyhow::Error),
     #[error("invalid buffer")]
     BadBuffer,
+    #[error("invalid ttl of table {table:?}: {reason}")]
+    Ttl { table: String, reason: &'static str },
+    #[error("invalid deferred unique constraint of table {table:?}: {reason}")]
+    DeferredUnique { table: String, reason: &'static str },
+    #[error("invalid compressed column of table {table:?}: {reason}")]
+    ColumnCompression { table: String, reason: &'static str },
+    #[error("invalid row version of table {table:?}: {reason}")]
+    RowVersion { table: String, reason: &'static str },
+    #[error("invalid http route {path:?}: {reason}")]
+    HttpRoute { path: String, reason: &'static str },
+}
+
+/// How many expired rows are deleted in each transaction.
+const EXPIRY_BATCH: usize = 1000;
+
+/// Checks that the table of `ttl` exists and has a `u64` column, i.e. a `Timestamp`, to expire its rows from.
+fn check_ttl(
+    typespace: &sats::Typespace,
+    tables: &[spacetimedb_lib::TableDef],
+    ttl: &TableTtl,
+) -> Result<(), DescribeError> {
+    let err = |reason| DescribeError::Ttl {
+        table: ttl.table_name.clone(),
+        reason,
+    };
+    let table = tables
+        .iter()
+        .find(|table| table.name == ttl.table_name)
+        .ok_or_else(|| err("no such table"))?;
+    let column = typespace
+        .get(table.data)
+        .and_then(|ty| match ty {
+            AlgebraicType::Product(row) => Some(row),
+            _ => None,
+        })
+        .and_then(|row| row.elements.iter().find(|el| el.name() == Some(&*ttl.column)))
+        .ok_or_else(|| err("no such column"))?;
+    if column.algebraic_type != AlgebraicType::U64 {
+        return Err(err("the column isn't a Timestamp"));
+    }
+    Ok(())
+}
+
+/// Checks that each of the `routes` is served by a read-only reducer or a SQL query,
+/// and that no two of them match the same requests.
+fn check_http_routes(routes: &[HttpRoute], read_only_reducers: &HashSet<String>) -> Result<(), DescribeError> {
+    let mut shapes = HashSet::new();
+    for route in routes {
+        let err = |reason| DescribeError::HttpRoute {
+            path: route.path.clone(),
+            reason,
+        };
+        if let HttpHandler::Reducer(name) = &route.handler {
+            if !read_only_reducers.contains(name) {
+                return Err(err("the reducer isn't read-only"));
+            }
+        }
+        if !shapes.insert((route.method, route_shape(&route.path))) {
+            return Err(err("another route matches the same requests"));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of the unique index `deferred` refers to,
+/// which [`schema_for`] gives the same name.
+fn deferred_constraint_name(
+    typespace: &sats::Typespace,
+    tables: &[spacetimedb_lib::TableDef],
+    deferred: &DeferredUnique,
+) -> Result<String, DescribeError> {
+    let err = |reason| DescribeError::DeferredUnique {
+        table: deferred.table_name.clone(),
+        reason,
+    };
+    let table = tables
+        .iter()
+        .find(|table| table.name == deferred.table_name)
+        .ok_or_else(|| err("no such table"))?;
+    let is_unique = table
+        .column_attrs
+        .get(deferred.col_id as usize)
+        .map_or(false, |attr| attr.is_unique());
+    if !is_unique {
+        return Err(err("the column isn't unique"));
+    }
+    if let Some(index) = table.indexes.iter().find(|index| index.col_ids == [deferred.col_id]) {
+        return Ok(index.name.clone());
+    }
+    let col_name = typespace
+        .get(table.data)
+        .and_then(|ty| match ty {
+            AlgebraicType::Product(row) => row.elements.get(deferred.col_id as usize),
+            _ => None,
+        })
+        .and_then(|column| column.name())
+        .ok_or_else(|| err("no such column"))?;
+    Ok(format!("{}_{}_unique", table.name, col_name))
+}
+
+/// Returns the definition the database creates the table `table` of a module with,
+/// its types resolved in the module's `typespace`.
+pub(crate) fn schema_for(typespace: &sats::Typespace, table: &spacetimedb_lib::TableDef) -> anyhow::Result<TableDef> {
+    let schema = typespace
+        .with_type(&table.data)
+        .resolve_refs()
+        .context("recursive types not yet supported")?;
+    let schema = schema.into_product().ok().context("table not a product type?")?;
+    anyhow::ensure!(
+        table.column_attrs.len() == schema.elements.len(),
+        "mismatched number of columns"
+    );
+    let columns: Vec<ColumnDef> = std::iter::zip(&schema.elements, &table.column_attrs)
+        .map(|(ty, attr)| {
+            Ok(ColumnDef {
+                col_name: ty.name.clone().context("column without name")?,
+                col_type: ty.algebraic_type.clone(),
+                is_autoinc: attr.is_autoinc(),
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let mut indexes = Vec::new();
+    for (col_id, col) in columns.iter().enumerate() {
+        let mut index_for_column = None;
+        for index in table.indexes.iter() {
+            let [index_col_id] = *index.col_ids else {
+                anyhow::bail!("multi-column indexes not yet supported")
+            };
+            if index_col_id as usize != col_id {
+                continue;
+            }
+            index_for_column = Some(index);
+            break;
+        }
+
+        let col_attr = table.column_attrs.get(col_id).context("invalid column id")?;
+        // If there's an index defined for this column already, use it
+        // making sure that it is unique if the column has a unique constraint
+        if let Some(index) = index_for_column {
+            match index.ty {
+                IndexType::BTree => {}
+                // TODO
+                IndexType::Hash => anyhow::bail!("hash indexes not yet supported"),
+                IndexType::FullText if col_attr.is_unique() => {
+                    anyhow::bail!("full-text indexes can't be unique")
+                }
+                IndexType::FullText => {}
+                IndexType::Spatial if col_attr.is_unique() => {
+                    anyhow::bail!("spatial indexes can't be unique")
+                }
+                IndexType::Spatial => {}
+            }
+            let index = IndexDef {
+                table_id: 0, // Will be ignored
+                col_id: col_id as u32,
+                name: index.name.clone(),
+                is_unique: col_attr.is_unique(),
+                index_type: index.ty,
+            };
+            indexes.push(index);
+        } else if col_attr.is_unique() {
+            // If you didn't find an index, but the column is unique then create a unique btree index
+            // anyway.
+            let index = IndexDef {
+                table_id: 0, // Will be ignored
+                col_id: col_id as u32,
+                name: format!("{}_{}_unique", table.name, col.col_name),
+                is_unique: true,
+                index_type: IndexType::BTree,
+            };
+            indexes.push(index);
+        }
+    }
+
+    Ok(TableDef {
+        table_name: table.name.clone(),
+        columns,
+        indexes,
+        table_type: table.table_type,
+        table_access: table.table_access,
+    })
+}
+
+/// Compares the tables the module of `info` declares to those of the database in `tx`,
+/// returning what updating the database to the module would do,
+/// along with the definitions of the tables and of the indexes on existing tables the update would create,
+/// and of the columns it would add to existing tables.
+pub(crate) fn plan_update(
+    info: &ModuleInfo,
+    stdb: &RelationalDB,
+    tx: &MutTxId,
+) -> anyhow::Result<(UpdatePlan, Vec<TableDef>, Vec<IndexDef>, Vec<(u32, Vec<ColumnDef>)>)> {
+    let mut known_tables: BTreeMap<String, TableSchema> = stdb
+        .get_all_tables(tx)?
+        .into_iter()
+        .map(|schema| (schema.table_name.clone(), schema))
+        .collect();
+    let mut tables: Vec<_> = info.catalog.values().filter_map(EntityDef::as_table).collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut plan = UpdatePlan {
+        calls_update_reducer: info.reducers.contains_key(UPDATE_DUNDER),
+        ..UpdatePlan::default()
+    };
+    let mut new_tables = Vec::new();
+    let mut new_indexes = Vec::new();
+    let mut new_columns = Vec::new();
+    for table in tables {
+        let mut proposed_schema = schema_for(&info.typespace, table)?;
+        if let Some(known_schema) = known_tables.remove(&table.name) {
+            // If the table is known, we also know its id. Update the
+            // index definitions so the `TableDef` of both schemas is
+            // equivalent.
+            for index in proposed_schema.indexes.iter_mut() {
+                index.table_id = known_schema.table_id;
+            }
+            // An index added to a known table is built by the update, rather than changing the table.
+            let (known_indexes, added_indexes): (Vec<_>, Vec<_>) = std::mem::take(&mut proposed_schema.indexes)
+                .into_iter()
+                .partition(|index| known_schema.indexes.iter().any(|known| known.index_name == index.name));
+            proposed_schema.indexes = known_indexes;
+            for index in added_indexes {
+                plan.created_indexes.push(index.name.clone());
+                new_indexes.push(index);
+            }
+            let table_id = known_schema.table_id;
+            let known_schema = TableDef::from(known_schema);
+            // `Option` columns added at the end of the table are added to its rows as `None`,
+            // rather than changing the table.
+            let known_len = known_schema.columns.len();
+            let added = proposed_schema.columns.get(known_len..).unwrap_or_default();
+            if !added.is_empty() && added.iter().all(is_addable_column) {
+                let added = proposed_schema.columns.split_off(known_len);
+                if known_schema == proposed_schema {
+                    plan.added_columns
+                        .extend(added.iter().map(|column| format!("{}.{}", table.name, column.col_name)));
+                    new_columns.push((table_id, added));
+                    continue;
+                }
+                proposed_schema.columns.extend(added);
+            }
+            if known_schema != proposed_schema {
+                plan.changed_tables.push(TableChange {
+                    table_name: table.name.clone(),
+                    differences: table_differences(&known_schema, &proposed_schema),
+                });
+            }
+        } else {
+            plan.created_tables.push(table.name.clone());
+            new_tables.push(proposed_schema);
+        }
+    }
+    plan.orphaned_tables = known_tables
+        .into_keys()
+        .filter(|name| !name.starts_with("st_"))
+        .collect();
+    Ok((plan, new_tables, new_indexes, new_columns))
+}
+
+/// Whether `column` can be added to a table that has rows, which it can if it's an `Option`,
+/// as they're `None` in the rows, but not if it's auto-incremented.
+fn is_addable_column(column: &ColumnDef) -> bool {
+    !column.is_autoinc && matches!(&column.col_type, AlgebraicType::Sum(sum) if sum.as_option().is_some())
+}
+
+/// Describes how the definition `proposed` of a table differs from its definition `known` in the database.
+fn table_differences(known: &TableDef, proposed: &TableDef) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (col_id, column) in proposed.columns.iter().enumerate() {
+        let Some(known) = known.columns.get(col_id) else {
+            differences.push(format!("column `{}` added", column.col_name));
+            continue;
+        };
+        if known.col_name != column.col_name {
+            differences.push(format!("column `{}` renamed to `{}`", known.col_name, column.col_name));
+        }
+        if known.col_type != column.col_type {
+            differences.push(format!(
+                "column `{}` changed from {} to {}",
+                column.col_name,
+                fmt_algebraic_type(&known.col_type),
+                fmt_algebraic_type(&column.col_type)
+            ));
+        }
+        if known.is_autoinc != column.is_autoinc {
+            let now = if column.is_autoinc { "now" } else { "no longer" };
+            differences.push(format!("column `{}` is {now} auto-incremented", column.col_name));
+        }
+    }
+    for column in known.columns.iter().skip(proposed.columns.len()) {
+        differences.push(format!("column `{}` removed", column.col_name));
+    }
+    for index in &proposed.indexes {
+        if known
+            .indexes
+            .iter()
+            .any(|known| known.name == index.name && known != index)
+        {
+            differences.push(format!("index `{}` changed", index.name));
+        }
+    }
+    for index in &known.indexes {
+        if !proposed.indexes.iter().any(|proposed| proposed.name == index.name) {
+            differences.push(format!("index `{}` removed", index.name));
+        }
+    }
+    if known.table_access != proposed.table_access {
+        differences.push(format!(
+            "access changed from {:?} to {:?}",
+            known.table_access, proposed.table_access
+        ));
+    }
+    differences
+}
+
+/// Checks that the column of `compression` exists and is a string column, the only kind that can be compressed.
+fn check_column_compression(
+    typespace: &sats::Typespace,
+    tables: &[spacetimedb_lib::TableDef],
+    compression: &ColumnCompression,
+) -> Result<(), DescribeError> {
+    let err = |reason| DescribeError::ColumnCompression {
+        table: compression.table_name.clone(),
+        reason,
+    };
+    let table = tables
+        .iter()
+        .find(|table| table.name == compression.table_name)
+        .ok_or_else(|| err("no such table"))?;
+    let column = typespace
+        .get(table.data)
+        .and_then(|ty| match ty {
+            AlgebraicType::Product(row) => row.elements.get(compression.col_id as usize),
+            _ => None,
+        })
+        .ok_or_else(|| err("no such column"))?;
+    if column.algebraic_type != AlgebraicType::String {
+        return Err(err("the column isn't a String"));
+    }
+    Ok(())
+}
+
+/// Checks that the table of `version` exists, with a `u64` version column and a unique key column.
+fn check_row_version(
+    typespace: &sats::Typespace,
+    tables: &[spacetimedb_lib::TableDef],
+    version: &RowVersion,
+) -> Result<(), DescribeError> {
+    let err = |reason| DescribeError::RowVersion {
+        table: version.table_name.clone(),
+        reason,
+    };
+    let table = tables
+        .iter()
+        .find(|table| table.name == version.table_name)
+        .ok_or_else(|| err("no such table"))?;
+    let column = typespace
+        .get(table.data)
+        .and_then(|ty| match ty {
+            AlgebraicType::Product(row) => row.elements.get(version.col_id as usize),
+            _ => None,
+        })
+        .ok_or_else(|| err("no such column"))?;
+    if column.algebraic_type != AlgebraicType::U64 {
+        return Err(err("the column isn't a u64"));
+    }
+    let is_unique = table
+        .column_attrs
+        .get(version.key_col_id as usize)
+        .map_or(false, |attr| attr.is_unique());
+    if !is_unique {
+        return Err(err("the key column isn't unique"));
+    }
+    Ok(())
 }
 
 impl<T: WasmModule> WasmModuleHostActor<T> {
@@ -151,10 +581,7 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
         };
         let log_tx = database_instance_context.logger.lock().unwrap().tx.clone();
 
-        FuncNames::check_required(|name| module.get_export(name))?;
-        let mut func_names = FuncNames::default();
-        module.for_each_export(|sym, ty| func_names.update_from_general(sym, ty))?;
-        func_names.preinits.sort_unstable();
+        let func_names = module.func_names()?;
 
         let owner_identity = database_instance_context.identity;
         let relational_db = database_instance_context.relational_db.clone();
@@ -172,8 +599,82 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             typespace,
             tables,
             reducers,
-            misc_exports: _,
+            misc_exports,
         } = desc;
+        let mut read_only_reducers = HashSet::new();
+        let mut reducer_roles = HashMap::new();
+        let mut reducer_cooldowns = HashMap::new();
+        let mut reducer_priorities = HashMap::new();
+        let mut access_hints = HashMap::new();
+        let mut event_types = HashMap::new();
+        let mut reducer_returns = HashMap::new();
+        let mut type_aliases = HashMap::new();
+        let mut table_ttls = Vec::new();
+        let mut deferred_constraints = HashSet::new();
+        let mut column_compression = HashMap::<_, Vec<_>>::new();
+        let mut row_versions = HashMap::new();
+        let mut http_routes = Vec::new();
+        for exp in misc_exports {
+            match exp {
+                MiscModuleExport::ReadOnlyReducer(name) => {
+                    read_only_reducers.insert(name);
+                }
+                MiscModuleExport::TableAccessHint(TableAccessHint { table_name, hint }) => {
+                    access_hints.insert(table_name, hint);
+                }
+                MiscModuleExport::Event(EventDef { name, ty }) => {
+                    event_types.insert(name, ty);
+                }
+                MiscModuleExport::TypeAlias(TypeAlias { name, ty }) => {
+                    type_aliases.insert(ty, name);
+                }
+                MiscModuleExport::ReducerAllow(ReducerAllow { reducer_name, roles }) => {
+                    reducer_roles.insert(reducer_name, roles);
+                }
+                MiscModuleExport::ReducerCooldown(ReducerCooldown {
+                    reducer_name,
+                    cooldown_micros,
+                }) => {
+                    reducer_cooldowns.insert(reducer_name, Duration::from_micros(cooldown_micros));
+                }
+                MiscModuleExport::ReducerPriority(ReducerPriority { reducer_name, lane }) => {
+                    reducer_priorities.insert(reducer_name, lane);
+                }
+                MiscModuleExport::ReducerReturn(ReducerReturn { reducer_name, ty }) => {
+                    reducer_returns.insert(reducer_name, ty);
+                }
+                MiscModuleExport::TableTtl(ttl) => {
+                    check_ttl(&typespace, &tables, &ttl)?;
+                    table_ttls.push(ttl);
+                }
+                MiscModuleExport::DeferredUnique(deferred) => {
+                    deferred_constraints.insert(deferred_constraint_name(&typespace, &tables, &deferred)?);
+                }
+                MiscModuleExport::HttpRoute(route) => http_routes.push(route),
+                MiscModuleExport::ColumnCompression(compression) => {
+                    check_column_compression(&typespace, &tables, &compression)?;
+                    column_compression
+                        .entry(compression.table_name)
+                        .or_default()
+                        .push((ColId(compression.col_id.into()), compression.codec));
+                }
+                MiscModuleExport::RowVersion(version) => {
+                    check_row_version(&typespace, &tables, &version)?;
+                    let cols = (ColId(version.col_id.into()), ColId(version.key_col_id.into()));
+                    row_versions.insert(version.table_name, cols);
+                }
+            }
+        }
+        // The reducers are only known to be read-only once all the exports are in.
+        check_http_routes(&http_routes, &read_only_reducers)?;
+        database_instance_context.relational_db.set_access_hints(access_hints);
+        database_instance_context
+            .relational_db
+            .set_deferred_constraints(deferred_constraints);
+        database_instance_context
+            .relational_db
+            .set_column_compression(column_compression);
+        database_instance_context.relational_db.set_row_versions(row_versions);
         let catalog = itertools::chain(
             tables.into_iter().map(|x| (x.name.clone(), EntityDef::Table(x))),
             reducers.iter().map(|x| (x.name.clone(), EntityDef::Reducer(x.clone()))),
@@ -186,10 +687,23 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             module_hash,
             typespace,
             reducers,
+            read_only_reducers,
+            reducer_roles,
+            reducer_cooldowns,
+            reducer_priorities,
+            table_ttls,
+            event_types,
+            reducer_returns,
+            http_routes: HttpRoutes::new(http_routes),
+            type_aliases,
             catalog,
             log_tx,
             subscription,
+            relational_db: database_instance_context.relational_db.clone(),
+            call_log: database_instance_context.call_log.clone(),
+            address: database_instance_context.address,
         });
+        let _ = instance.instance_env().module_info.set(info.clone());
 
         let func_names = Arc::new(func_names);
         let instance_seed = InstanceSeed {
@@ -201,10 +715,17 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             trace_log,
             scheduler,
             energy_monitor,
+            commit_order: Arc::default(),
         };
         let instance = instance_seed.make_from_instance(instance);
-        let instances = JobPool::new(instance_seed, MSG_CHANNEL_CAP);
+        let (max_instances, spare_instances) = if *CONCURRENT_REDUCERS {
+            (*MAX_INSTANCES, *SPARE_INSTANCES)
+        } else {
+            (1, 0)
+        };
+        let instances = JobPool::new(instance_seed, MSG_CHANNEL_CAP, max_instances, spare_instances);
         instances.spawn_from_runner(instance);
+        instances.warm_up();
 
         Ok(Self { instances })
     }
@@ -223,6 +744,7 @@ impl<T: WasmInstancePre> JobRunnerSeed for InstanceSeed<T> {
             self.scheduler.clone(),
             self.trace_log.clone(),
         );
+        let _ = env.module_info.set(self.info.clone());
         // this shouldn't fail, since we already called module.create_instance()
         // before and it didn't error, and ideally they should be deterministic
         let mut instance = self
@@ -241,6 +763,8 @@ impl<T: WasmInstancePre> InstanceSeed<T> {
             info: self.info.clone(),
             event_tx: self.event_tx.clone(),
             energy_monitor: self.energy_monitor.clone(),
+            commit_order: self.commit_order.clone(),
+            trace_log: self.trace_log.clone(),
             trapped: false,
         }
     }
@@ -264,16 +788,28 @@ struct JobPool<S: JobRunnerSeed> {
 struct JobPoolData<S> {
     seed: S,
     nthreads: Mutex<usize>,
+    /// The number of threads waiting for a job, or making the runner to wait with.
+    idle: AtomicUsize,
+    max_threads: usize,
+    /// How many idle threads to keep, while there are fewer than `max_threads`.
+    spare_threads: usize,
     cvar: Condvar,
 }
 
 impl<S: JobRunnerSeed> JobPool<S> {
-    fn new(seed: S, cap: usize) -> Self {
+    fn new(seed: S, cap: usize, max_threads: usize, spare_threads: usize) -> Self {
         let (tx, rx) = crossbeam_channel::bounded(cap);
         let nthreads = Mutex::new(0);
         let cvar = Condvar::new();
         JobPool {
-            shared: Arc::new(JobPoolData { seed, nthreads, cvar }),
+            shared: Arc::new(JobPoolData {
+                seed,
+                nthreads,
+                idle: AtomicUsize::new(0),
+                max_threads,
+                spare_threads,
+                cvar,
+            }),
             rx,
             tx,
         }
@@ -283,10 +819,46 @@ impl<S: JobRunnerSeed> JobPool<S> {
         &self.shared.seed
     }
 
-    fn spawn_from_runner(&self, mut runner: S::Runner) {
-        let shared = self.shared.clone();
-        let rx = self.rx.clone();
-        *shared.nthreads.lock() += 1;
+    fn spawn_from_runner(&self, runner: S::Runner) {
+        Self::spawn_thread(&self.shared, &self.rx, Some(runner));
+    }
+
+    /// Spawn a thread, making its runner on it, unless there are already `max_threads`.
+    fn spawn(&self) {
+        Self::spawn_thread(&self.shared, &self.rx, None);
+    }
+
+    /// Spawn threads until `spare_threads` of them are idle, or there are `max_threads`.
+    fn warm_up(&self) {
+        Self::top_up(&self.shared, &self.rx);
+    }
+
+    fn top_up(shared: &Arc<JobPoolData<S>>, rx: &crossbeam_channel::Receiver<S::Job>) {
+        while shared.idle.load(Ordering::Acquire) < shared.spare_threads {
+            if !Self::spawn_thread(shared, rx, None) {
+                break;
+            }
+        }
+    }
+
+    /// Spawn a thread running the jobs of `rx` with `runner`, or one it makes itself,
+    /// returning whether it did, which it doesn't if there are already `max_threads`,
+    /// except to run a `runner` that's already been made.
+    fn spawn_thread(
+        shared: &Arc<JobPoolData<S>>,
+        rx: &crossbeam_channel::Receiver<S::Job>,
+        runner: Option<S::Runner>,
+    ) -> bool {
+        {
+            let mut nthreads = shared.nthreads.lock();
+            if runner.is_none() && *nthreads >= shared.max_threads {
+                return false;
+            }
+            *nthreads += 1;
+            shared.idle.fetch_add(1, Ordering::AcqRel);
+        }
+        let shared = shared.clone();
+        let rx = rx.clone();
         tokio::task::spawn_blocking(move || {
             scopeguard::defer! {
                 let mut nthreads = shared.nthreads.lock();
@@ -295,25 +867,27 @@ impl<S: JobRunnerSeed> JobPool<S> {
                     shared.cvar.notify_one();
                 }
             }
+            let mut runner = runner.unwrap_or_else(|| shared.seed.make_runner());
             while let Ok(job) = rx.recv() {
-                match runner.run(job) {
-                    ControlFlow::Continue(()) => {}
-                    ControlFlow::Break(()) => runner = shared.seed.make_runner(),
+                shared.idle.fetch_sub(1, Ordering::AcqRel);
+                Self::top_up(&shared, &rx);
+                let flow = runner.run(job);
+                if let ControlFlow::Break(()) = flow {
+                    runner = shared.seed.make_runner();
                 }
+                shared.idle.fetch_add(1, Ordering::AcqRel);
             }
+            shared.idle.fetch_sub(1, Ordering::AcqRel);
         });
-    }
-    fn spawn(&self) {
-        self.spawn_from_runner(self.seed().make_runner())
+        true
     }
 
     fn send(&self, mut job: S::Job) {
-        if true {
+        if self.shared.max_threads == 1 {
             // this can never actually be a SendError, since we're holding
             // onto a msg_rx ourselves, so the channel won't close
             let _ = self.tx.send(job);
         } else {
-            // TODO: implement reducer retries and have multiple instance threads
             loop {
                 match self.tx.send_timeout(job, MSG_CHANNEL_TIMEOUT) {
                     Ok(()) => break,
@@ -321,12 +895,9 @@ impl<S: JobRunnerSeed> JobPool<S> {
                     // onto a msg_rx ourselves, so the channel won't close
                     Err(err) => {
                         job = err.into_inner();
-                        let instance_count = *self.shared.nthreads.lock();
                         // TODO: better heuristics
                         // e.g. figure out when we should cull instances due to lack of use
-                        if instance_count < 8 {
-                            self.spawn()
-                        }
+                        self.spawn()
                     }
                 }
             }
@@ -393,20 +964,36 @@ impl<T: WasmModule> ModuleHostActor for WasmModuleHostActor<T> {
         &mut self,
         caller_identity: Identity,
         client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
         reducer_id: usize,
-
         args: ArgsTuple,
         respond_to: oneshot::Sender<ReducerCallResult>,
     ) {
         self.instances.send(InstanceMessage::CallReducer {
             caller_identity,
             client,
+            message,
             reducer_id,
             args,
             respond_to,
         })
     }
 
+    fn call_reducers(
+        &mut self,
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        calls: Vec<(usize, ArgsTuple)>,
+        respond_to: oneshot::Sender<Vec<ReducerCallResult>>,
+    ) {
+        self.instances.send(InstanceMessage::CallReducers {
+            caller_identity,
+            client,
+            calls,
+            respond_to,
+        })
+    }
+
     fn inject_logs(&self, respond_to: oneshot::Sender<()>, log_level: LogLevel, message: String) {
         self.instances.send(InstanceMessage::InjectLogs {
             respond_to,
@@ -415,6 +1002,10 @@ impl<T: WasmModule> ModuleHostActor for WasmModuleHostActor<T> {
         })
     }
 
+    fn expire_rows(&mut self, respond_to: oneshot::Sender<usize>) {
+        self.instances.send(InstanceMessage::ExpireRows { respond_to })
+    }
+
     fn close(self) {
         self.instances.seed().scheduler.close();
         self.instances.join()
@@ -444,6 +1035,7 @@ impl SystemLogger<'_> {
             filename: Some("spacetimedb"),
             line_number: None,
             message,
+            fields: &[],
         }
     }
 }
@@ -454,6 +1046,8 @@ struct WasmInstanceActor<T: WasmInstance> {
     info: Arc<ModuleInfo>,
     event_tx: SubscriptionEventSender,
     energy_monitor: Arc<dyn EnergyMonitor>,
+    commit_order: Arc<Mutex<()>>,
+    trace_log: Option<Arc<Mutex<TraceLog>>>,
     trapped: bool,
 }
 
@@ -483,11 +1077,35 @@ impl<T: WasmInstance> JobRunner for WasmInstanceActor<T> {
             InstanceMessage::CallReducer {
                 caller_identity,
                 client,
+                message,
                 reducer_id,
                 args,
                 respond_to,
             } => {
-                let _ = respond_to.send(self.call_reducer(caller_identity, client, reducer_id, args));
+                let _ = respond_to.send(self.call_reducer(caller_identity, client, message, reducer_id, args));
+            }
+            InstanceMessage::CallReducers {
+                caller_identity,
+                client,
+                calls,
+                respond_to,
+            } => {
+                let results = calls
+                    .into_iter()
+                    .map(|(reducer_id, args)| {
+                        // A trapped instance can't run the rest of the batch.
+                        if self.trapped {
+                            return ReducerCallResult {
+                                outcome: ReducerOutcome::Failed("not run: an earlier call in the batch trapped".into()),
+                                energy_used: EnergyDiff::ZERO,
+                                execution_duration: Duration::ZERO,
+                                return_value: None,
+                            };
+                        }
+                        self.call_reducer(caller_identity, client.clone(), None, reducer_id, args)
+                    })
+                    .collect();
+                let _ = respond_to.send(results);
             }
             InstanceMessage::UpdateDatabase { respond_to } => {
                 let _ = respond_to.send(self.update_database());
@@ -504,11 +1122,15 @@ impl<T: WasmInstance> JobRunner for WasmInstanceActor<T> {
                         filename: Some("external"),
                         line_number: None,
                         message: &message,
+                        fields: &[],
                     },
                     &(),
                 );
                 let _ = respond_to.send(());
             }
+            InstanceMessage::ExpireRows { respond_to } => {
+                let _ = respond_to.send(self.expire_rows());
+            }
         }
         if self.trapped {
             ControlFlow::Break(())
@@ -528,7 +1150,7 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
         let stdb = &*self.database_instance_context().relational_db;
         stdb.with_auto_commit::<_, _, anyhow::Error>(|tx| {
             for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
-                let schema = self.schema_for(table)?;
+                let schema = schema_for(&self.info.typespace, table)?;
                 stdb.create_table(tx, schema)
                     .with_context(|| format!("failed to create table {}", table.name))?;
             }
@@ -540,11 +1162,12 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
             .info
             .reducers
             .get_index_of(INIT_DUNDER)
-            .map(|id| self.call_reducer(self.database_instance_context().identity, None, id, args))
+            .map(|id| self.call_reducer(self.database_instance_context().identity, None, None, id, args))
             .unwrap_or(ReducerCallResult {
                 outcome: ReducerOutcome::Committed,
                 energy_used: EnergyDiff::ZERO,
                 execution_duration: Duration::ZERO,
+                return_value: None,
             });
 
         Ok(rcr)
@@ -554,64 +1177,57 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
     fn update_database(&mut self) -> Result<UpdateDatabaseResult, anyhow::Error> {
         let stdb = &*self.database_instance_context().relational_db;
 
-        let mut tainted = vec![];
-        stdb.with_auto_commit::<_, _, anyhow::Error>(|tx| {
-            let mut known_tables: BTreeMap<String, TableSchema> = stdb
-                .get_all_tables(tx)?
-                .into_iter()
-                .map(|schema| (schema.table_name.clone(), schema))
-                .collect();
-
-            let mut new_tables = Vec::new();
-            for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
-                let mut proposed_schema = self.schema_for(table)?;
-                if let Some(known_schema) = known_tables.remove(&table.name) {
-                    // If the table is known, we also know its id. Update the
-                    // index definitions so the `TableDef` of both schemas is
-                    // equivalent.
-                    for index in proposed_schema.indexes.iter_mut() {
-                        index.table_id = known_schema.table_id;
-                    }
-                    let known_schema = TableDef::from(known_schema);
-                    if known_schema != proposed_schema {
-                        self.system_logger()
-                            .warn(&format!("stored and proposed schema of `{}` differ", table.name));
-                        tainted.push(table.name.to_owned());
-                    } else {
-                        // Table unchanged
-                    }
-                } else {
-                    new_tables.push((table, proposed_schema));
-                }
+        let (plan, new_indexes) = stdb.with_auto_commit::<_, _, anyhow::Error>(|tx| {
+            let (plan, new_tables, new_indexes, new_columns) = plan_update(&self.info, stdb, tx)?;
+            for change in &plan.changed_tables {
+                self.system_logger().warn(&format!(
+                    "stored and proposed schema of `{}` differ: {}",
+                    change.table_name,
+                    change.differences.join(", ")
+                ));
             }
             // We may at some point decide to drop orphaned tables automatically,
             // but for now it's an incompatible schema change
-            for orphan in known_tables.into_keys() {
-                if !orphan.starts_with("st_") {
-                    self.system_logger()
-                        .warn(format!("Orphaned table: {}", orphan).as_str());
-                    tainted.push(orphan);
-                }
+            for orphan in &plan.orphaned_tables {
+                self.system_logger()
+                    .warn(format!("Orphaned table: {}", orphan).as_str());
             }
-            if tainted.is_empty() {
-                for (table, schema) in new_tables {
+            if plan.is_compatible() {
+                for schema in new_tables {
+                    let table_name = schema.table_name.clone();
                     stdb.create_table(tx, schema)
-                        .with_context(|| format!("failed to create table {}", table.name))?;
+                        .with_context(|| format!("failed to create table {}", table_name))?;
+                }
+                for (table_id, columns) in new_columns {
+                    stdb.add_columns(tx, table_id, columns)
+                        .with_context(|| format!("failed to add columns to table {}", table_id))?;
                 }
             }
 
-            Ok(())
+            Ok((plan, new_indexes))
         })?;
-        if !tainted.is_empty() {
+        if !plan.is_compatible() {
             self.system_logger()
                 .error("module update rejected due to schema mismatch");
-            return Ok(Err(UpdateDatabaseError::IncompatibleSchema { tables: tainted }));
+            let tables = itertools::chain(
+                plan.changed_tables.into_iter().map(|change| change.table_name),
+                plan.orphaned_tables,
+            )
+            .collect();
+            return Ok(Err(UpdateDatabaseError::IncompatibleSchema { tables }));
+        }
+        // Built outside of a transaction, so that reducers can write to their tables in the meantime.
+        for index in new_indexes {
+            let index_name = index.name.clone();
+            stdb.create_index_online(index)
+                .with_context(|| format!("failed to create index {}", index_name))?;
         }
 
         let update_result = self.info.reducers.get_index_of(UPDATE_DUNDER).map(|id| {
             self.call_reducer(
                 self.database_instance_context().identity,
                 None,
+                None,
                 id,
                 ArgsTuple::default(),
             )
@@ -623,11 +1239,15 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
         }))
     }
 
-    #[tracing::instrument(skip_all)]
+    #[tracing::instrument(
+        skip_all,
+        fields(reducer = %self.info.reducers[reducer_id].name, caller = %caller_identity.to_hex())
+    )]
     fn call_reducer(
         &mut self,
         caller_identity: Identity,
         client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
         reducer_id: usize,
         mut args: ArgsTuple,
     ) -> ReducerCallResult {
@@ -639,18 +1259,32 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
 
         log::trace!("Calling reducer {}", reducerdef.name);
 
-        let (status, energy) = self.execute(InstanceOp::Reducer {
+        let (status, energy, commit_order) = self.execute(InstanceOp::Reducer {
             id: reducer_id,
             sender: &caller_identity,
             timestamp,
             arg_bytes: args.get_bsatn().clone(),
+            message,
         });
 
         let execution_duration = start_instant.elapsed();
 
+        let reducerdef = &self.info.reducers[reducer_id];
+        if let Some(trace_log) = &self.trace_log {
+            trace_log.lock().call_reducer(
+                timestamp.to_systemtime(),
+                execution_duration,
+                reducerdef.name.clone(),
+                caller_identity,
+                timestamp,
+                args.get_bsatn().to_vec(),
+            );
+        }
+
         let outcome = ReducerOutcome::from(&status);
+        let emitted_events = self.take_emitted_events(&status);
+        let return_value = self.take_return_value(&reducerdef.name, &status);
 
-        let reducerdef = &self.info.reducers[reducer_id];
         let event = ModuleEvent {
             timestamp,
             caller_identity,
@@ -661,13 +1295,17 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
             status,
             energy_quanta_used: energy.used,
             host_execution_duration: execution_duration,
+            emitted_events,
+            return_value: return_value.clone(),
         };
         self.event_tx.broadcast_event_blocking(client.as_ref(), event);
+        drop(commit_order);
 
         ReducerCallResult {
             outcome,
             energy_used: energy.used,
             execution_duration,
+            return_value,
         }
     }
 
@@ -686,7 +1324,7 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
 
         let timestamp = Timestamp::now();
 
-        let (status, energy) = self.execute(InstanceOp::ConnDisconn {
+        let (status, energy, commit_order) = self.execute(InstanceOp::ConnDisconn {
             conn: connected,
             sender: &identity,
             timestamp,
@@ -697,6 +1335,9 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
         } else {
             IDENTITY_DISCONNECTED_DUNDER
         };
+        let emitted_events = self.take_emitted_events(&status);
+        // Connect and disconnect hooks return nothing to anyone.
+        self.instance.instance_env().return_value.take();
 
         // TODO(cloutiertyler): We need to think about how to handle this special
         // function. Is this just an autogenerated reducer? In the future if I call
@@ -712,12 +1353,140 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
             caller_identity: identity,
             energy_quanta_used: energy.used,
             host_execution_duration: start_instant.elapsed(),
+            emitted_events,
+            return_value: None,
         };
         self.event_tx.broadcast_event_blocking(None, event);
+        drop(commit_order);
     }
 
+    /// Deletes up to [`EXPIRY_BATCH`] rows that outlived the TTL of their table,
+    /// in a transaction that's broadcast to subscribers as a call to [`EXPIRE_DUNDER`] by the module itself.
+    ///
+    /// Returns how many rows were deleted.
     #[tracing::instrument(skip_all)]
-    fn execute(&mut self, op: InstanceOp<'_>) -> (EventStatus, EnergyStats) {
+    fn expire_rows(&mut self) -> usize {
+        let start_instant = Instant::now();
+        let timestamp = Timestamp::now();
+
+        let commit_order = self.commit_order.lock_arc();
+        let stdb = &*self.database_instance_context().relational_db;
+        let mut tx = stdb.begin_tx();
+        let mut deleted = 0;
+        for TableTtl {
+            table_name,
+            ttl_micros,
+            column,
+        } in &self.info.table_ttls
+        {
+            let before = timestamp.0.saturating_sub(*ttl_micros);
+            match stdb.delete_expired(&mut tx, table_name, column, before, EXPIRY_BATCH - deleted) {
+                Ok(n) => deleted += n,
+                Err(e) => {
+                    log::error!("Failed to delete the expired rows of {table_name}: {e}");
+                    stdb.rollback_tx(tx);
+                    return 0;
+                }
+            }
+            if deleted == EXPIRY_BATCH {
+                break;
+            }
+        }
+        if deleted == 0 {
+            stdb.rollback_tx(tx);
+            return 0;
+        }
+
+        let Some((tx_data, _)) = stdb.commit_tx(tx).unwrap() else {
+            log::debug!("Deleting expired rows conflicted with a concurrent transaction");
+            return 0;
+        };
+        let event = ModuleEvent {
+            timestamp,
+            caller_identity: self.info.identity,
+            function_call: ModuleFunctionCall {
+                reducer: EXPIRE_DUNDER.to_string(),
+                args: ArgsTuple::default(),
+            },
+            status: EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data)),
+            energy_quanta_used: EnergyDiff::ZERO,
+            host_execution_duration: start_instant.elapsed(),
+            emitted_events: Vec::new(),
+            return_value: None,
+        };
+        self.event_tx.broadcast_event_blocking(None, event);
+        drop(commit_order);
+        deleted
+    }
+
+    /// Takes the events emitted by the last call into the instance,
+    /// decoded with their types, if the call committed.
+    ///
+    /// An event of an unknown type or that doesn't match its type is logged and dropped.
+    fn take_emitted_events(&self, status: &EventStatus) -> Vec<EmittedEvent> {
+        let events = self.instance.instance_env().events.take();
+        if !matches!(status, EventStatus::Committed(_)) {
+            return Vec::new();
+        }
+        events
+            .into_iter()
+            .filter_map(|(name, data)| {
+                let Some(&ty) = self.info.event_types.get(&name) else {
+                    log::warn!("Reducer emitted an event of unknown type {name:?}");
+                    return None;
+                };
+                let ty = AlgebraicType::Ref(ty);
+                match self
+                    .info
+                    .typespace
+                    .with_type(&ty)
+                    .deserialize(bsatn::Deserializer::new(&mut &data[..]))
+                {
+                    Ok(value) => Some(EmittedEvent { name, value }),
+                    Err(e) => {
+                        log::warn!("Reducer emitted an invalid event of type {name:?}: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Takes the value returned by the last call into the instance, a call of the reducer `name`,
+    /// decoded with the reducer's return type, if the call committed.
+    ///
+    /// A value of a reducer that doesn't declare a return type, or that doesn't match it, is logged and dropped.
+    fn take_return_value(&self, name: &str, status: &EventStatus) -> Option<AlgebraicValue> {
+        let data = self.instance.instance_env().return_value.take()?;
+        if !matches!(status, EventStatus::Committed(_)) {
+            return None;
+        }
+        let Some(ty) = self.info.reducer_returns.get(name) else {
+            log::warn!("Reducer {name:?} returned a value but declares no return type");
+            return None;
+        };
+        match self
+            .info
+            .typespace
+            .with_type(ty)
+            .deserialize(bsatn::Deserializer::new(&mut &data[..]))
+        {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("Reducer {name:?} returned an invalid value: {e}");
+                None
+            }
+        }
+    }
+
+    /// Runs `op` in a transaction of its own, committing it if the call succeeds.
+    ///
+    /// With [`CONCURRENT_REDUCERS`], the transaction is suspended while the call runs,
+    /// and a call that conflicts with a transaction committed meanwhile is run again,
+    /// holding the lock on the datastore throughout.
+    /// When the call commits, this also returns a guard to hold until its event is broadcast.
+    #[tracing::instrument(skip_all)]
+    fn execute(&mut self, op: InstanceOp<'_>) -> (EventStatus, EnergyStats, Option<CommitOrderGuard>) {
         let address = &self.database_instance_context().address.to_abbreviated_hex();
         let func_ident = match op {
             InstanceOp::Reducer { id, .. } => &*self.info.reducers[id].name,
@@ -731,7 +1500,14 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
         };
         REDUCER_COUNT.with_label_values(&[address, func_ident]).inc();
 
+        // The host calls the reducer makes are logged under its name, while the call log is on.
+        let mut current_reducer = self.instance.instance_env().current_reducer.lock();
+        current_reducer.clear();
+        current_reducer.push_str(func_ident);
+        drop(current_reducer);
+
         let energy_fingerprint = EnergyMonitorFingerprint {
+            database_address: self.database_instance_context().address,
             module_hash: self.info.module_hash,
             module_identity: self.info.identity,
             caller_identity: match op {
@@ -740,195 +1516,233 @@ impl<T: WasmInstance> WasmInstanceActor<T> {
             reducer_name: func_ident,
         };
 
-        let budget = self.energy_monitor.reducer_budget(&energy_fingerprint);
-
-        let tx = self.database_instance_context().relational_db.begin_tx();
-
-        let tx_slot = self.instance.instance_env().tx.clone();
-        let (tx, result) = tx_slot.set(tx, || match op {
-            InstanceOp::Reducer {
-                id,
-                sender,
-                timestamp,
-                arg_bytes,
-            } => self
-                .instance
-                .call_reducer(id, budget, sender.as_bytes(), timestamp, arg_bytes),
-            InstanceOp::ConnDisconn {
-                conn,
-                sender,
-                timestamp,
-            } => self
-                .instance
-                .call_connect_disconnect(conn, budget, sender.as_bytes(), timestamp),
-        });
-
-        let ExecuteResult {
-            energy,
-            execution_duration,
-            call_result,
-        } = result;
-
-        self.energy_monitor
-            .record(&energy_fingerprint, energy.used, execution_duration);
-
-        const FRAME_LEN_60FPS: Duration = match Duration::from_secs(1).checked_div(60) {
-            Some(d) => d,
-            None => unreachable!(),
-        };
-        if execution_duration > FRAME_LEN_60FPS {
-            // If we can't get your reducer done in a single frame
-            // we should debug it.
-            log::debug!("Long running reducer {func_ident:?} took {execution_duration:?} to execute");
-        } else {
-            log::trace!("Reducer {func_ident:?} ran: {execution_duration:?}, {:?}", energy.used);
-        }
-
-        REDUCER_COMPUTE_TIME
-            .with_label_values(&[address, func_ident])
-            .observe(execution_duration.as_secs_f64());
-
-        // If you can afford to take 500 ms for a transaction
-        // you can afford to generate a flamegraph. Fix your stuff.
-        // if duration.as_millis() > 500 {
-        //     if let Ok(report) = guard.report().build() {
-        //         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        //         let file = std::fs::File::create(format!("flamegraphs/flamegraph-{}.svg", now.as_millis())).unwrap();
-        //         report.flamegraph(file).unwrap();
-        //     };
-        // }
-
-        let stdb = &*self.database_instance_context().relational_db;
-        let status = match call_result {
-            Err(err) => {
-                stdb.rollback_tx(tx);
-
-                T::log_traceback("reducer", func_ident, &err);
-
-                // discard this instance
-                self.trapped = true;
+        // A read-only reducer needs neither a mutable transaction nor a commit.
+        // It shares the lock on the datastore with other readers throughout, so that it reads a consistent state.
+        let read_only = self.info.read_only_reducers.contains(func_ident);
+        // Other reducers run concurrently, if enabled, unless they conflict with a reducer committed in the meantime,
+        // in which case they're run again holding the lock throughout, which can't conflict.
+        let mut concurrent = !read_only && *CONCURRENT_REDUCERS;
+
+        loop {
+            let budget = self.energy_monitor.reducer_budget(&energy_fingerprint);
+            // The wasm meter counts points of execution, so the budget is given to it in those,
+            // and the host's operations are charged to it in those as well.
+            let pricing = self.energy_monitor.reducer_pricing(&energy_fingerprint);
+            self.instance.instance_env().energy.reset(pricing);
+            // The events of a run that conflicted are emitted again by running it again.
+            self.instance.instance_env().events.take();
+            self.instance.instance_env().return_value.take();
+            self.instance
+                .instance_env()
+                .sent_messages
+                .store(false, Ordering::Relaxed);
+            // The reducers scheduled and canceled by a run are only applied once its transaction commits,
+            // and its log lines are held back while it may be discarded, so a run that conflicted leaves no trace.
+            self.instance.instance_env().schedules.discard();
+            if concurrent {
+                self.instance.instance_env().logs.hold();
+            }
+            let per_point = pricing.per_instruction.max(1) as i128;
+            let budget = EnergyQuanta(budget.0 / per_point);
+
+            // The commit order is always taken before the lock on the datastore,
+            // as whoever holds it may be waiting for the datastore to broadcast their event.
+            let mut commit_order = (!read_only && !concurrent).then(|| self.commit_order.lock_arc());
+
+            let relational_db = &self.database_instance_context().relational_db;
+            let tx = if read_only {
+                relational_db.begin_read_only_tx()
+            } else {
+                relational_db.begin_tx()
+            };
+
+            let tx_slot = self.instance.instance_env().tx.clone();
+            let call = || match op.clone() {
+                InstanceOp::Reducer {
+                    id,
+                    sender,
+                    timestamp,
+                    arg_bytes,
+                    message: _,
+                } => self
+                    .instance
+                    .call_reducer(id, budget, sender.as_bytes(), timestamp, arg_bytes),
+                InstanceOp::ConnDisconn {
+                    conn,
+                    sender,
+                    timestamp,
+                } => self
+                    .instance
+                    .call_connect_disconnect(conn, budget, sender.as_bytes(), timestamp),
+            };
+            let (tx, result) = if read_only {
+                tx_slot.set_read_only(tx, call)
+            } else if concurrent {
+                let (tx, result) = tx_slot.set_suspended(tx, call);
+                commit_order = Some(self.commit_order.lock_arc());
+                (tx.resume(), result)
+            } else {
+                tx_slot.set(tx, call)
+            };
+
+            let ExecuteResult {
+                energy,
+                execution_duration,
+                call_result,
+            } = result;
+            let energy = EnergyStats {
+                used: EnergyDiff(energy.used.0.saturating_mul(per_point)),
+                remaining: EnergyQuanta(energy.remaining.0.saturating_mul(per_point)),
+            };
+
+            const FRAME_LEN_60FPS: Duration = match Duration::from_secs(1).checked_div(60) {
+                Some(d) => d,
+                None => unreachable!(),
+            };
+            if execution_duration > FRAME_LEN_60FPS {
+                // If we can't get your reducer done in a single frame
+                // we should debug it.
+                log::debug!("Long running reducer {func_ident:?} took {execution_duration:?} to execute");
+            } else {
+                log::trace!("Reducer {func_ident:?} ran: {execution_duration:?}, {:?}", energy.used);
+            }
 
-                if energy.remaining == EnergyQuanta::ZERO {
-                    EventStatus::OutOfEnergy
-                } else {
-                    EventStatus::Failed("The Wasm instance encountered a fatal error.".into())
+            REDUCER_COMPUTE_TIME
+                .with_label_values(&[address, func_ident])
+                .observe(execution_duration.as_secs_f64());
+
+            // If you can afford to take 500 ms for a transaction
+            // you can afford to generate a flamegraph. Fix your stuff.
+            // if duration.as_millis() > 500 {
+            //     if let Ok(report) = guard.report().build() {
+            //         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            //         let file = std::fs::File::create(format!("flamegraphs/flamegraph-{}.svg", now.as_millis())).unwrap();
+            //         report.flamegraph(file).unwrap();
+            //     };
+            // }
+
+            let stdb = &*self.database_instance_context().relational_db;
+            let mut received = false;
+            let (status, commit_order) = match call_result {
+                Err(err) => {
+                    stdb.rollback_tx(tx);
+
+                    T::log_traceback("reducer", func_ident, &err);
+
+                    // discard this instance
+                    self.trapped = true;
+
+                    let status = if energy.remaining == EnergyQuanta::ZERO {
+                        EventStatus::OutOfEnergy
+                    } else {
+                        EventStatus::Failed("The Wasm instance encountered a fatal error.".into())
+                    };
+                    (status, None)
                 }
-            }
-            Ok(Err(errmsg)) => {
-                stdb.rollback_tx(tx);
+                Ok(Err(errmsg)) => {
+                    stdb.rollback_tx(tx);
 
-                log::info!("reducer returned error: {errmsg}");
+                    log::info!("reducer returned error: {errmsg}");
 
-                EventStatus::Failed(errmsg.into())
-            }
-            Ok(Ok(())) => {
-                if let Some((tx_data, bytes_written)) = stdb.commit_tx(tx).unwrap() {
-                    // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
-                    // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
-                    // We should track the commit sizes instead internally to the CommitLog probably.
-                    if let Some(bytes_written) = bytes_written {
-                        REDUCER_WRITE_SIZE
-                            .with_label_values(&[address, func_ident])
-                            .observe(bytes_written as f64);
+                    (EventStatus::Failed(errmsg.into()), None)
+                }
+                Ok(Ok(())) if read_only => {
+                    stdb.release_tx(tx);
+                    (EventStatus::Committed(DatabaseUpdate::default()), None)
+                }
+                Ok(Ok(())) => {
+                    let mut tx = tx;
+                    // Received in the transaction of the reducer, so that it's only ever received once.
+                    let recorded = match &op {
+                        InstanceOp::Reducer {
+                            message: Some(message), ..
+                        } => stdb
+                            .record_message_received(&mut tx, message.sender, message.msg_id)
+                            .map(|()| received = true),
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = recorded {
+                        stdb.rollback_tx(tx);
+
+                        log::error!("Failed to record the message of reducer {func_ident:?} as received: {e}");
+
+                        (
+                            EventStatus::Failed("Failed to record the message as received.".into()),
+                            None,
+                        )
+                    } else if let Some((tx_data, bytes_written)) = stdb.commit_tx(tx).unwrap() {
+                        // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
+                        // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
+                        // We should track the commit sizes instead internally to the CommitLog probably.
+                        if let Some(bytes_written) = bytes_written {
+                            REDUCER_WRITE_SIZE
+                                .with_label_values(&[address, func_ident])
+                                .observe(bytes_written as f64);
+                        }
+                        if self.instance.instance_env().sent_messages.load(Ordering::Relaxed) {
+                            self.database_instance_context().outbox.notify_one();
+                        }
+                        let instance_env = self.instance.instance_env();
+                        instance_env.schedules.apply(&instance_env.scheduler);
+                        let status = EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data));
+                        // Held until the event is broadcast, so that subscribers see the commits in order.
+                        (status, commit_order)
+                    } else {
+                        log::debug!(
+                            "Reducer {func_ident:?} conflicted with a concurrent transaction, running it again"
+                        );
+                        self.instance.instance_env().logs.release();
+                        concurrent = false;
+                        continue;
                     }
-                    EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data))
-                } else {
-                    todo!("Write skew, you need to implement retries my man, T-dawg.");
                 }
-            }
-        };
-        (status, energy)
-    }
-
-    // Helpers - NOT API
-
-    fn schema_for(&self, table: &spacetimedb_lib::TableDef) -> anyhow::Result<TableDef> {
-        let schema = self
-            .info
-            .typespace
-            .with_type(&table.data)
-            .resolve_refs()
-            .context("recursive types not yet supported")?;
-        let schema = schema.into_product().ok().context("table not a product type?")?;
-        anyhow::ensure!(
-            table.column_attrs.len() == schema.elements.len(),
-            "mismatched number of columns"
-        );
-        let columns: Vec<ColumnDef> = std::iter::zip(&schema.elements, &table.column_attrs)
-            .map(|(ty, attr)| {
-                Ok(ColumnDef {
-                    col_name: ty.name.clone().context("column without name")?,
-                    col_type: ty.algebraic_type.clone(),
-                    is_autoinc: attr.is_autoinc(),
-                })
-            })
-            .collect::<anyhow::Result<_>>()?;
-
-        let mut indexes = Vec::new();
-        for (col_id, col) in columns.iter().enumerate() {
-            let mut index_for_column = None;
-            for index in table.indexes.iter() {
-                let [index_col_id] = *index.col_ids else {
-                    anyhow::bail!("multi-column indexes not yet supported")
-                };
-                if index_col_id as usize != col_id {
-                    continue;
+            };
+
+            // Only the run that isn't discarded is charged for and logged.
+            self.energy_monitor
+                .record(&energy_fingerprint, energy.used, execution_duration);
+            let lines = self.instance.instance_env().logs.release();
+            if !lines.is_empty() {
+                let mut logger = self.database_instance_context().logger.lock().unwrap();
+                for line in lines {
+                    logger.write_line(line);
                 }
-                index_for_column = Some(index);
-                break;
             }
 
-            let col_attr = table.column_attrs.get(col_id).context("invalid column id")?;
-            // If there's an index defined for this column already, use it
-            // making sure that it is unique if the column has a unique constraint
-            if let Some(index) = index_for_column {
-                match index.ty {
-                    IndexType::BTree => {}
-                    // TODO
-                    IndexType::Hash => anyhow::bail!("hash indexes not yet supported"),
+            // A message whose reducer didn't commit a transaction isn't delivered again either.
+            if let InstanceOp::Reducer {
+                message: Some(message), ..
+            } = &op
+            {
+                if !received {
+                    let res = stdb.with_auto_commit::<_, _, DBError>(|tx| {
+                        stdb.record_message_received(tx, message.sender, message.msg_id)
+                    });
+                    if let Err(e) = res {
+                        log::error!("Failed to record message {} as received: {e}", message.msg_id);
+                    }
                 }
-                let index = IndexDef {
-                    table_id: 0, // Will be ignored
-                    col_id: col_id as u32,
-                    name: index.name.clone(),
-                    is_unique: col_attr.is_unique(),
-                };
-                indexes.push(index);
-            } else if col_attr.is_unique() {
-                // If you didn't find an index, but the column is unique then create a unique btree index
-                // anyway.
-                let index = IndexDef {
-                    table_id: 0, // Will be ignored
-                    col_id: col_id as u32,
-                    name: format!("{}_{}_unique", table.name, col.col_name),
-                    is_unique: true,
-                };
-                indexes.push(index);
             }
+            return (status, energy, commit_order);
         }
-
-        Ok(TableDef {
-            table_name: table.name.clone(),
-            columns,
-            indexes,
-            table_type: table.table_type,
-            table_access: table.table_access,
-        })
     }
 
+    // Helpers - NOT API
+
     fn system_logger(&self) -> SystemLogger {
         let inner = self.database_instance_context().logger.lock().unwrap();
         SystemLogger { inner }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum InstanceOp<'a> {
     Reducer {
         id: usize,
         sender: &'a Identity,
         timestamp: Timestamp,
         arg_bytes: Bytes,
+        message: Option<ReceivedMessage>,
     },
     ConnDisconn {
         conn: bool,
@@ -950,10 +1764,17 @@ enum InstanceMessage {
     CallReducer {
         caller_identity: Identity,
         client: Option<ClientConnectionSender>,
+        message: Option<ReceivedMessage>,
         reducer_id: usize,
         args: ArgsTuple,
         respond_to: oneshot::Sender<ReducerCallResult>,
     },
+    CallReducers {
+        caller_identity: Identity,
+        client: Option<ClientConnectionSender>,
+        calls: Vec<(usize, ArgsTuple)>,
+        respond_to: oneshot::Sender<Vec<ReducerCallResult>>,
+    },
     UpdateDatabase {
         respond_to: oneshot::Sender<Result<UpdateDatabaseResult, anyhow::Error>>,
     },
@@ -962,4 +1783,73 @@ enum InstanceMessage {
         log_level: LogLevel,
         message: String,
     },
+    ExpireRows {
+        respond_to: oneshot::Sender<usize>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes runners that do nothing, counting how many it made.
+    #[derive(Default)]
+    struct CountingSeed {
+        made: AtomicUsize,
+    }
+
+    struct NoopRunner;
+
+    impl JobRunnerSeed for CountingSeed {
+        type Runner = NoopRunner;
+        type Job = ();
+        fn make_runner(&self) -> NoopRunner {
+            self.made.fetch_add(1, Ordering::AcqRel);
+            NoopRunner
+        }
+    }
+
+    impl JobRunner for NoopRunner {
+        type Job = ();
+        fn run(&mut self, (): ()) -> ControlFlow<()> {
+            ControlFlow::Continue(())
+        }
+    }
+
+    /// Waits for the pool to have made `count` runners, which it does on threads of its own.
+    async fn wait_for_runners(pool: &JobPool<CountingSeed>, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.seed().made.load(Ordering::Acquire) < count {
+            assert!(Instant::now() < deadline, "the pool didn't make {count} runners");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spare_is_ready_after_startup() {
+        let pool = JobPool::new(CountingSeed::default(), MSG_CHANNEL_CAP, 4, 2);
+        pool.spawn_from_runner(NoopRunner);
+        pool.warm_up();
+
+        // The runner made along with the module is one of the two idle ones, so only the other is made.
+        wait_for_runners(&pool, 1).await;
+        assert_eq!(*pool.shared.nthreads.lock(), 2);
+
+        // A call taking one of them has another made in its place.
+        pool.send(());
+        wait_for_runners(&pool, 2).await;
+        assert_eq!(*pool.shared.nthreads.lock(), 3);
+
+        tokio::task::spawn_blocking(move || pool.join()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_spares_past_max() {
+        let pool = JobPool::new(CountingSeed::default(), MSG_CHANNEL_CAP, 1, 2);
+        pool.spawn_from_runner(NoopRunner);
+        pool.warm_up();
+        assert_eq!(*pool.shared.nthreads.lock(), 1);
+        assert_eq!(pool.seed().made.load(Ordering::Acquire), 0);
+        tokio::task::spawn_blocking(move || pool.join()).await.unwrap();
+    }
 }
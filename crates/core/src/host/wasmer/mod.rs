@@ -10,6 +10,7 @@ use crate::database_instance_context::DatabaseInstanceContext;
 use crate::error::NodesError;
 use crate::hash::Hash;
 
+mod module_cache;
 mod opcode_cost;
 mod wasm_instance_env;
 mod wasmer_module;
@@ -49,18 +50,26 @@ pub fn make_actor(
     let engine = EngineBuilder::new(compiler_config).engine();
 
     let store = Store::new(&engine);
-    let module = Module::new(&store, program_bytes).map_err(|e| ModuleCreationError::WasmCompileError(e.into()))?;
+    let cache = module_cache::module_cache();
+    let module = match cache.and_then(|cache| cache.get(&store, &module_hash)) {
+        Some(module) => module,
+        None => {
+            let module =
+                Module::new(&store, program_bytes).map_err(|e| ModuleCreationError::WasmCompileError(e.into()))?;
+            if let Some(cache) = cache {
+                cache.insert(&module_hash, &module);
+            }
+            module
+        }
+    };
 
     let abi = abi::determine_spacetime_abi(program_bytes)?;
-
-    if !WasmerModule::IMPLEMENTED_ABI.supports(abi) {
-        return Err(ModuleCreationError::Abi(abi::AbiVersionError::UnsupportedVersion {
-            implement: WasmerModule::IMPLEMENTED_ABI,
-            got: abi,
-        }));
+    let abi_support = abi::negotiate(WasmerModule::IMPLEMENTED_ABI, abi)?;
+    if abi_support == abi::AbiSupport::Previous {
+        log::info!("linking the shims for a module built against ABI {abi}");
     }
 
-    let module = WasmerModule::new(module, engine);
+    let module = WasmerModule::new(module, engine, abi_support);
 
     WasmModuleHostActor::new(dbic, module_hash, module, scheduler, energy_monitor).map_err(Into::into)
 }
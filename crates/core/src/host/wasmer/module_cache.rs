@@ -0,0 +1,170 @@
+//! A cache on disk of the modules compiled by Wasmer, keyed by the hash of their program,
+//! so a database that's restarted, or republished with a program it ran before, skips compiling it.
+//!
+//! The cache is kept per version of the engine, since the code it compiles to is only good for the
+//! engine that compiled it, and the caches of other versions are removed when it's opened.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use wasmer::{Module, Store};
+
+use crate::hash::{hash_bytes, Hash, HASH_SIZE};
+use crate::stdb_path;
+
+/// The version of the engine compiling modules, which the compiled code is only good for.
+///
+/// Besides the versions of SpacetimeDB and Wasmer, its last part is bumped when the way modules
+/// are compiled changes, like the compiler settings or the energy cost of each operation.
+const ENGINE_VERSION: &str = concat!("spacetimedb-", env!("CARGO_PKG_VERSION"), "-wasmer-3.1-cranelift-1");
+
+/// The most bytes of compiled modules kept in the cache, 1 GiB unless set by the
+/// `SPACETIMEDB_MODULE_CACHE_SIZE` environment variable, which disables the cache if `0`.
+static MAX_BYTES: Lazy<u64> = Lazy::new(|| {
+    const DEFAULT: u64 = 1 << 30;
+    let Ok(max_bytes) = std::env::var("SPACETIMEDB_MODULE_CACHE_SIZE") else {
+        return DEFAULT;
+    };
+    max_bytes.parse().unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid SPACETIMEDB_MODULE_CACHE_SIZE {max_bytes:?}: {e}");
+        DEFAULT
+    })
+});
+
+static MODULE_CACHE: Lazy<Option<ModuleCache>> = Lazy::new(|| {
+    if *MAX_BYTES == 0 {
+        return None;
+    }
+    ModuleCache::open(&stdb_path("worker_node/module_cache"), *MAX_BYTES)
+        .map_err(|e| log::warn!("Not caching compiled modules, as the cache couldn't be opened: {e}"))
+        .ok()
+});
+
+/// The cache of compiled modules, unless it's disabled.
+pub(super) fn module_cache() -> Option<&'static ModuleCache> {
+    MODULE_CACHE.as_ref()
+}
+
+pub(super) struct ModuleCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ModuleCache {
+    /// Open the cache in `root`, removing those of other engine versions.
+    fn open(root: &Path, max_bytes: u64) -> io::Result<Self> {
+        let dir = root.join(ENGINE_VERSION);
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            if entry.file_name() != ENGINE_VERSION {
+                log::info!("Removing the compiled modules of {:?}", entry.file_name());
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(Self { dir, max_bytes })
+    }
+
+    fn path(&self, program_hash: &Hash) -> PathBuf {
+        self.dir.join(program_hash.to_hex())
+    }
+
+    /// The module compiled from the program with the hash `program_hash`, if it's cached.
+    ///
+    /// An entry that's corrupt, or can't be loaded, is removed, so the program is compiled again.
+    pub fn get(&self, store: &Store, program_hash: &Hash) -> Option<Module> {
+        let path = self.path(program_hash);
+        let entry = match fs::read(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                log::warn!("Failed to read the compiled module {}: {e}", path.display());
+                return None;
+            }
+        };
+        let (checksum, artifact) = entry.split_at(HASH_SIZE.min(entry.len()));
+        let module = if checksum == hash_bytes(artifact).as_slice() {
+            // SAFETY: the artifact was serialized by `insert`, with an engine of the same version,
+            // and the checksum rules out it having been corrupted since.
+            unsafe { Module::deserialize(store, artifact) }.map_err(|e| e.to_string())
+        } else {
+            Err("checksum mismatch".to_owned())
+        };
+        match module {
+            Ok(module) => Some(module),
+            Err(e) => {
+                log::warn!("Discarding the compiled module {}: {e}", path.display());
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Cache `module`, compiled from the program with the hash `program_hash`,
+    /// then remove the oldest entries until the cache fits in its size limit.
+    pub fn insert(&self, program_hash: &Hash, module: &Module) {
+        if let Err(e) = self.try_insert(program_hash, module) {
+            log::warn!("Failed to cache the compiled module {}: {e:#}", program_hash.to_hex());
+        }
+    }
+
+    fn try_insert(&self, program_hash: &Hash, module: &Module) -> anyhow::Result<()> {
+        let artifact = module.serialize()?;
+        let mut entry = Vec::with_capacity(HASH_SIZE + artifact.len());
+        entry.extend_from_slice(hash_bytes(&artifact).as_slice());
+        entry.extend_from_slice(&artifact);
+
+        // Written aside and then moved into place, so a reader never sees a partial entry.
+        let path = self.path(program_hash);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, entry)?;
+        fs::rename(&tmp_path, &path)?;
+
+        self.evict()?;
+        Ok(())
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            entries.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_unstable();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn discards_other_versions_and_corrupt_entries() {
+        let root = TempDir::new("module_cache").unwrap();
+        let old_version = root.path().join("spacetimedb-0.0.0-wasmer-3.0-cranelift-1");
+        fs::create_dir_all(&old_version).unwrap();
+
+        let cache = ModuleCache::open(root.path(), 1 << 20).unwrap();
+        assert!(!old_version.exists());
+
+        let program_hash = hash_bytes(b"program");
+        assert!(cache.get(&Store::default(), &program_hash).is_none());
+
+        fs::write(cache.path(&program_hash), b"not a compiled module").unwrap();
+        assert!(cache.get(&Store::default(), &program_hash).is_none());
+        assert!(!cache.path(&program_hash).exists());
+    }
+}
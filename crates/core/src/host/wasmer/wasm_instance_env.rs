@@ -1,20 +1,30 @@
 #![allow(clippy::too_many_arguments)]
 
+use crate::address::Address;
 use crate::database_logger::{BacktraceFrame, BacktraceProvider, ModuleBacktrace, Record};
 use crate::host::scheduler::{ScheduleError, ScheduledReducerId};
 use crate::host::timestamp::Timestamp;
-use crate::host::wasm_common::{err_to_errno, AbiRuntimeError, BufferIdx, BufferIterIdx, BufferIters, Buffers};
+use crate::host::wasm_common::{
+    err_to_errno, unique_conflict, AbiRuntimeError, BufferIdx, BufferIterIdx, BufferIters, Buffers,
+};
 use bytes::Bytes;
 use itertools::Itertools;
-use wasmer::{FunctionEnvMut, MemoryAccessError, RuntimeError, ValueType, WasmPtr};
+use spacetimedb_lib::bsatn;
+use spacetimedb_lib::hash::HASH_SIZE;
+use spacetimedb_lib::logging::LogField;
+use wasmer::{FunctionEnvMut, Instance, MemoryAccessError, RuntimeError, ValueType, WasmPtr};
+use wasmer_middlewares::metering as wasmer_metering;
 
 use crate::host::instance_env::InstanceEnv;
 
+use super::wasmer_module::get_remaining_points;
 use super::{Mem, WasmError};
 
 pub(super) struct WasmInstanceEnv {
     pub instance_env: InstanceEnv,
     pub mem: Option<Mem>,
+    /// The instance this is the environment of, whose energy meter is charged for the host's operations.
+    pub instance: Option<Instance>,
     pub buffers: Buffers,
     pub iters: BufferIters,
 }
@@ -39,6 +49,29 @@ impl WasmInstanceEnv {
         self.mem.clone().expect("Initialized memory")
     }
 
+    /// Returns a reference to the instance, assumed to be initialized.
+    fn instance(&self) -> Instance {
+        self.instance.clone().expect("Initialized instance")
+    }
+
+    /// Takes the energy spent on the host's operations since the last call
+    /// from the budget of the current call into the module.
+    ///
+    /// Errors if that exhausts the budget, which traps the module like running out of energy would.
+    fn charge_energy(caller: &mut FunctionEnvMut<'_, Self>) -> RtResult<()> {
+        let points = caller.data().instance_env.energy.take_pending_points();
+        if points == 0 {
+            return Ok(());
+        }
+        let instance = caller.data().instance();
+        let remaining = get_remaining_points(caller, &instance);
+        wasmer_metering::set_remaining_points(caller, &instance, remaining.saturating_sub(points));
+        if remaining < points {
+            return Err(RuntimeError::new("out of energy"));
+        }
+        Ok(())
+    }
+
     /// Call the function `f` with the name `func`.
     /// The function `f` is provided with the callers environment and the host's memory.
     ///
@@ -49,10 +82,13 @@ impl WasmInstanceEnv {
         func: &'static str,
         f: impl FnOnce(FunctionEnvMut<'_, Self>, &Mem) -> WasmResult<()>,
     ) -> RtResult<u16> {
-        // Call `f` with the caller and a handle to the memory.
+        // Call `f` with the caller and a handle to the memory,
+        // paying for what it did even if it failed.
         // Bail if there were no errors.
         let mem = caller.data().mem();
-        let Err(err) = f(caller.as_mut(), &mem) else {
+        let res = f(caller.as_mut(), &mem);
+        Self::charge_energy(&mut caller)?;
+        let Err(err) = res else {
             return Ok(0);
         };
 
@@ -131,10 +167,10 @@ impl WasmInstanceEnv {
                 .instance_env
                 .schedule(name, args, Timestamp(time))
                 .map_err(|e| match e {
-                    ScheduleError::DelayTooLong(_) => RuntimeError::new("requested delay is too long"),
                     ScheduleError::IdTransactionError(_) => {
                         RuntimeError::new("transaction to acquire ScheduleReducerId failed")
                     }
+                    e @ ScheduleError::ReadOnlyTransaction => RuntimeError::new(e.to_string()),
                 })?;
             Ok(id)
         })
@@ -145,8 +181,87 @@ impl WasmInstanceEnv {
     ///
     /// This assumes that the reducer hasn't already been executed.
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) {
-        caller.data().instance_env.cancel_reducer(ScheduledReducerId(id))
+    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) -> RtResult<()> {
+        caller
+            .data()
+            .instance_env
+            .cancel_reducer(ScheduledReducerId(id))
+            .map_err(|e| RuntimeError::new(e.to_string()))
+    }
+
+    /// Emits an event of the type named by the UTF-8 slice `(name, name_len)` in WASM memory,
+    /// encoded as BSATN in the byte slice `(data, data_len)`.
+    ///
+    /// The event is delivered to the subscribed clients if the reducer's transaction commits.
+    #[tracing::instrument(skip_all)]
+    pub fn emit_event(
+        caller: FunctionEnvMut<'_, Self>,
+        name: WasmPtr<u8>,
+        name_len: u32,
+        data: WasmPtr<u8>,
+        data_len: u32,
+    ) -> RtResult<u16> {
+        Self::cvt(caller, "emit_event", |caller, mem| {
+            let name = Self::read_string(&caller, mem, name, name_len)?;
+            let data = mem.read_bytes(&caller, data, data_len)?;
+            caller.data().instance_env.emit_event(name, data);
+            Ok(())
+        })
+    }
+
+    /// Sets the value the reducer returns to its caller,
+    /// encoded as BSATN in the byte slice `(data, data_len)` in WASM memory.
+    ///
+    /// The value is delivered with the reducer's transaction if it commits.
+    #[tracing::instrument(skip_all)]
+    pub fn set_return_value(caller: FunctionEnvMut<'_, Self>, data: WasmPtr<u8>, data_len: u32) -> RtResult<u16> {
+        Self::cvt(caller, "set_return_value", |caller, mem| {
+            let data = mem.read_bytes(&caller, data, data_len)?;
+            caller.data().instance_env.set_return_value(data);
+            Ok(())
+        })
+    }
+
+    /// Sends a call of the reducer named by the UTF-8 slice `(reducer, reducer_len)` in WASM memory
+    /// of the database whose 16 byte address is at the WASM pointer `address`,
+    /// with its arguments encoded as BSATN in the byte slice `(args, args_len)`.
+    ///
+    /// The call is made once, if and when the reducer's transaction commits.
+    #[tracing::instrument(skip_all)]
+    pub fn send_message(
+        caller: FunctionEnvMut<'_, Self>,
+        address: WasmPtr<u8>,
+        reducer: WasmPtr<u8>,
+        reducer_len: u32,
+        args: WasmPtr<u8>,
+        args_len: u32,
+    ) -> RtResult<u16> {
+        Self::cvt(caller, "send_message", |caller, mem| {
+            let address = Address::from_slice(mem.read_bytes(&caller, address, 16)?);
+            let reducer = Self::read_string(&caller, mem, reducer, reducer_len)?;
+            let args = mem.read_bytes(&caller, args, args_len)?;
+            caller.data().instance_env.send_message(address, &reducer, &args)?;
+            Ok(())
+        })
+    }
+
+    /// Describes the arguments of the reducer named by the UTF-8 slice `(name, name_len)` in WASM memory,
+    /// as a BSATN encoded `ProductType` written to a new buffer,
+    /// whose id is written to the `out` pointer.
+    ///
+    /// Errors with `NO_SUCH_REDUCER` if the module doesn't define such a reducer.
+    #[tracing::instrument(skip_all)]
+    pub fn describe_reducer(
+        caller: FunctionEnvMut<'_, Self>,
+        name: WasmPtr<u8>,
+        name_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "describe_reducer", out, |mut caller, mem| {
+            let name = Self::read_string(&caller, mem, name, name_len)?;
+            let args = caller.data().instance_env.describe_reducer(&name)?;
+            Ok(caller.data_mut().buffers.insert(args.into()))
+        })
     }
 
     /// Log at `level` a `message` occuring in `filename:line_number` with `target`.
@@ -163,6 +278,68 @@ impl WasmInstanceEnv {
         line_number: u32,
         message: WasmPtr<u8>,
         message_len: u32,
+    ) {
+        Self::write_log(
+            caller,
+            level,
+            target,
+            target_len,
+            filename,
+            filename_len,
+            line_number,
+            message,
+            message_len,
+            WasmPtr::null(),
+            0,
+        )
+    }
+
+    /// Log at `level` a `message` occuring in `filename:line_number` with `target`,
+    /// and the key-value pairs `(fields, fields_len)`, bsatn encoded as a `Vec<LogField>`.
+    ///
+    /// The strings are interpreted as in [`Self::console_log`].
+    /// Fields that can't be decoded are left out of the record.
+    #[tracing::instrument(skip_all)]
+    pub fn console_log_structured(
+        caller: FunctionEnvMut<'_, Self>,
+        level: u8,
+        target: WasmPtr<u8>,
+        target_len: u32,
+        filename: WasmPtr<u8>,
+        filename_len: u32,
+        line_number: u32,
+        message: WasmPtr<u8>,
+        message_len: u32,
+        fields: WasmPtr<u8>,
+        fields_len: u32,
+    ) {
+        Self::write_log(
+            caller,
+            level,
+            target,
+            target_len,
+            filename,
+            filename_len,
+            line_number,
+            message,
+            message_len,
+            fields,
+            fields_len,
+        )
+    }
+
+    fn write_log(
+        caller: FunctionEnvMut<'_, Self>,
+        level: u8,
+        target: WasmPtr<u8>,
+        target_len: u32,
+        filename: WasmPtr<u8>,
+        filename_len: u32,
+        line_number: u32,
+        message: WasmPtr<u8>,
+        message_len: u32,
+        fields: WasmPtr<u8>,
+        fields_len: u32,
     ) {
         let mem = caller.data().mem();
 
@@ -184,11 +361,19 @@ impl WasmInstanceEnv {
             // The line number cannot be `u32::MAX` as this represents `Option::None`.
             let line_number = (line_number != u32::MAX).then_some(line_number);
 
+            let fields: Vec<LogField> = if fields.is_null() {
+                Vec::new()
+            } else {
+                let bytes = mem.read_bytes(&caller, fields, fields_len)?;
+                bsatn::from_slice(&bytes).unwrap_or_default()
+            };
+
             let record = Record {
                 target: target.as_deref(),
                 filename: filename.as_deref(),
                 line_number,
                 message: &message,
+                fields: &fields,
             };
 
             // Write the log record to the `DatabaseLogger` in the database instance context (dbic).
@@ -210,14 +395,48 @@ impl WasmInstanceEnv {
         row_ptr: WasmPtr<u8>,
         row_len: u32,
     ) -> RtResult<u16> {
-        Self::cvt(caller, "insert", |caller, mem| {
+        Self::insert_reporting(caller, "insert", table_id, row_ptr, row_len, None)
+    }
+
+    /// Like [`Self::insert`], but when the row violates a unique constraint,
+    /// the id of the column of that constraint is also written to the pointer `conflict`.
+    #[tracing::instrument(skip_all)]
+    pub fn try_insert(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        row_ptr: WasmPtr<u8>,
+        row_len: u32,
+        conflict: WasmPtr<u32>,
+    ) -> RtResult<u16> {
+        Self::insert_reporting(caller, "try_insert", table_id, row_ptr, row_len, Some(conflict))
+    }
+
+    /// Inserts the row `(row_ptr, row_len)` for `insert` and `try_insert`,
+    /// reporting the column of a violated unique constraint to `conflict`, if given.
+    fn insert_reporting(
+        caller: FunctionEnvMut<'_, Self>,
+        func: &'static str,
+        table_id: u32,
+        row_ptr: WasmPtr<u8>,
+        row_len: u32,
+        conflict: Option<WasmPtr<u32>>,
+    ) -> RtResult<u16> {
+        Self::cvt(caller, func, |caller, mem| {
             // Read the row from WASM memory into a buffer.
             let mut row_buffer = mem.read_bytes(&caller, row_ptr, row_len)?;
 
             // Insert the row into the DB. We get back the decoded version.
             // Then re-encode and write that back into WASM memory at `row_ptr`.
             // We're doing this because of autoinc.
-            let new_row = caller.data().instance_env.insert(table_id, &row_buffer)?;
+            let new_row = match caller.data().instance_env.insert(table_id, &row_buffer) {
+                Ok(new_row) => new_row,
+                Err(err) => {
+                    if let (Some(conflict), Some(col_id)) = (conflict, unique_conflict(&err)) {
+                        conflict.write(&mem.view(&caller), col_id)?;
+                    }
+                    return Err(err.into());
+                }
+            };
             row_buffer.clear();
             new_row.encode(&mut row_buffer);
             assert_eq!(
@@ -255,6 +474,38 @@ impl WasmInstanceEnv {
         })
     }
 
+    /// Moves rows from the table identified by `src_table_id` to the one identified by `dst_table_id`,
+    /// deleting the rows in the byte slice `(src_rows, src_rows_len)`
+    /// and inserting those in `(dst_rows, dst_rows_len)`, both in WASM memory.
+    /// The rows of each are bsatn encoded and then concatenated.
+    ///
+    /// Either all the rows are moved or, on error, none are.
+    ///
+    /// The number of rows deleted is written to the WASM pointer `out`.
+    ///
+    /// Returns an error if a row to delete wasn't found or if a row couldn't be inserted.
+    #[tracing::instrument(skip_all)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn move_rows(
+        caller: FunctionEnvMut<'_, Self>,
+        src_table_id: u32,
+        src_rows: WasmPtr<u8>,
+        src_rows_len: u32,
+        dst_table_id: u32,
+        dst_rows: WasmPtr<u8>,
+        dst_rows_len: u32,
+        out: WasmPtr<u32>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "move_rows", out, |caller, mem| {
+            let src_rows = mem.read_bytes(&caller, src_rows, src_rows_len)?;
+            let dst_rows = mem.read_bytes(&caller, dst_rows, dst_rows_len)?;
+            Ok(caller
+                .data()
+                .instance_env
+                .move_rows(src_table_id, &src_rows, dst_table_id, &dst_rows)?)
+        })
+    }
+
     /*
     /// Deletes the primary key pointed to at by `pk` in the table identified by `table_id`.
     #[tracing::instrument(skip_all)]
@@ -268,8 +519,11 @@ impl WasmInstanceEnv {
             Ok(())
         })
     }
+    */
 
     /// Deletes the row pointed to at by `row` in the table identified by `table_id`.
+    ///
+    /// A shim for modules built against the previous ABI, moving the row to nowhere.
     #[tracing::instrument(skip_all)]
     pub fn delete_value(
         caller: FunctionEnvMut<'_, Self>,
@@ -280,11 +534,18 @@ impl WasmInstanceEnv {
         Self::cvt(caller, "delete_value", |caller, mem| {
             // Read the row from WASM memory.
             let row = mem.read_bytes(&caller, row, row_len)?;
-            caller.data().instance_env.delete_value(table_id, &row)?;
+            caller.data().instance_env.move_rows(table_id, &row, table_id, &[])?;
             Ok(())
         })
     }
 
+    /// Deletes all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` is within the range
+    /// from the byte string `range_start` to `range_end`, both in WASM memory.
+    ///
+    /// The number of rows deleted is written to the WASM pointer `out`.
+    ///
+    /// A shim for modules built against the previous ABI.
     #[tracing::instrument(skip_all)]
     pub fn delete_range(
         caller: FunctionEnvMut<'_, Self>,
@@ -307,6 +568,7 @@ impl WasmInstanceEnv {
         })
     }
 
+    /*
     /// Create a table with `name`, a UTF-8 slice in WASM memory lasting `name_len` bytes,
     /// and with the table's `schema` in a slice in WASM memory lasting `schema_len` bytes.
     ///
@@ -426,6 +688,236 @@ impl WasmInstanceEnv {
         })
     }
 
+    /// Finds all rows in the table identified by `table_id`,
+    /// where the string column, identified by `col_id`,
+    /// matches the full-text query, in WASM memory, pointed to at by `query`.
+    ///
+    /// Matching is defined by [`spacetimedb_lib::fulltext::matches`].
+    /// Note that `query` must point to valid UTF-8 or a `RuntimeError` will occur.
+    ///
+    /// The rows found are bsatn encoded and then concatenated.
+    /// The resulting byte string from the concatenation is written
+    /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_match(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        query: WasmPtr<u8>,
+        query_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "iter_by_col_match", out, |mut caller, mem| {
+            // Read the query as a string from WASM memory.
+            let query = Self::read_string(&caller, mem, query, query_len)?;
+
+            // Find the relevant rows.
+            let data = caller.data().instance_env.iter_by_col_match(table_id, col_id, &query)?;
+
+            // Insert the encoded + concatenated rows into a new buffer and return its id.
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Finds all rows in the table identified by `table_id`,
+    /// where the point column, identified by `col_id`,
+    /// is within the box from `min` to `max`, edges included.
+    ///
+    /// The corners are byte strings in WASM memory, pointed to at by `min` and `max`,
+    /// which are decoded to `AlgebraicValue`s according to the column's schema.
+    /// Being within the box is defined by [`spacetimedb_lib::spatial::in_box`].
+    ///
+    /// The rows found are bsatn encoded and then concatenated.
+    /// The resulting byte string from the concatenation is written
+    /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+    #[tracing::instrument(skip_all)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn iter_by_col_box(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        min: WasmPtr<u8>,
+        min_len: u32,
+        max: WasmPtr<u8>,
+        max_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "iter_by_col_box", out, |mut caller, mem| {
+            // Read the corners from WASM memory.
+            let min = mem.read_bytes(&caller, min, min_len)?;
+            let max = mem.read_bytes(&caller, max, max_len)?;
+
+            // Find the relevant rows.
+            let data = caller
+                .data()
+                .instance_env
+                .iter_by_col_box(table_id, col_id, &min, &max)?;
+
+            // Insert the encoded + concatenated rows into a new buffer and return its id.
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Writes the energy left in the budget of the current reducer call to the WASM pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn remaining_energy(caller: FunctionEnvMut<'_, Self>, out: WasmPtr<u64>) -> RtResult<u16> {
+        Self::cvt_ret(caller, "remaining_energy", out, |mut caller, _mem| {
+            let instance = caller.data().instance();
+            let points = get_remaining_points(&mut caller, &instance);
+            let per_point = caller.data().instance_env.energy.pricing().per_instruction.max(1);
+            Ok(points.saturating_mul(per_point))
+        })
+    }
+
+    /// Writes the offset in the commit log of the current transaction to the WASM pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn tx_offset(caller: FunctionEnvMut<'_, Self>, out: WasmPtr<u64>) -> RtResult<u16> {
+        Self::cvt_ret(caller, "tx_offset", out, |caller, _mem| {
+            Ok(caller.data().instance_env.tx_offset()?)
+        })
+    }
+
+    /// Writes the version of the table identified by `table_id` to the WASM pointer `out`.
+    ///
+    /// Returns an error if the table doesn't exist.
+    #[tracing::instrument(skip_all)]
+    pub fn table_version(caller: FunctionEnvMut<'_, Self>, table_id: u32, out: WasmPtr<u64>) -> RtResult<u16> {
+        Self::cvt_ret(caller, "table_version", out, |caller, _mem| {
+            Ok(caller.data().instance_env.table_version(table_id)?)
+        })
+    }
+
+    /// Writes the bsatn-encoded layout of the rows stored in the table identified by `table_id`
+    /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+    ///
+    /// Returns an error if the table doesn't exist.
+    #[tracing::instrument(skip_all)]
+    pub fn table_row_type(caller: FunctionEnvMut<'_, Self>, table_id: u32, out: WasmPtr<BufferIdx>) -> RtResult<u16> {
+        Self::cvt_ret(caller, "table_row_type", out, |mut caller, _mem| {
+            let data = caller.data().instance_env.table_row_type(table_id)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Stores the blob in the byte slice `(data, data_len)` in WASM memory
+    /// under the key named by the UTF-8 slice `(key, key_len)`,
+    /// replacing the blob stored under it before, if any.
+    ///
+    /// The hash of the blob, of `HASH_SIZE` bytes, is written to the WASM pointer `out`.
+    ///
+    /// Errors with `BLOB_TOO_LARGE` if the blob is over the size limit.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_put(
+        caller: FunctionEnvMut<'_, Self>,
+        key: WasmPtr<u8>,
+        key_len: u32,
+        data: WasmPtr<u8>,
+        data_len: u32,
+        out: WasmPtr<u8>,
+    ) -> RtResult<u16> {
+        Self::cvt(caller, "blob_put", |caller, mem| {
+            let key = Self::read_string(&caller, mem, key, key_len)?;
+            let data = mem.read_bytes(&caller, data, data_len)?;
+            let hash = caller.data().instance_env.blob_put(&key, &data)?;
+            mem.set_bytes(&caller, out, HASH_SIZE as u32, hash.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Reads the blob stored under the key named by the UTF-8 slice `(key, key_len)` in WASM memory
+    /// into a new buffer, whose id is written to the `out` pointer.
+    ///
+    /// Errors with `LOOKUP_NOT_FOUND` if no blob is stored under the key.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_get(
+        caller: FunctionEnvMut<'_, Self>,
+        key: WasmPtr<u8>,
+        key_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "blob_get", out, |mut caller, mem| {
+            let key = Self::read_string(&caller, mem, key, key_len)?;
+            let data = caller.data().instance_env.blob_get(&key)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Deletes the blob stored under the key named by the UTF-8 slice `(key, key_len)` in WASM memory.
+    ///
+    /// Errors with `LOOKUP_NOT_FOUND` if no blob is stored under the key.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_delete(caller: FunctionEnvMut<'_, Self>, key: WasmPtr<u8>, key_len: u32) -> RtResult<u16> {
+        Self::cvt(caller, "blob_delete", |caller, mem| {
+            let key = Self::read_string(&caller, mem, key, key_len)?;
+            Ok(caller.data().instance_env.blob_delete(&key)?)
+        })
+    }
+
+    /// Stores the bytes of the slice `(data, data_len)` in WASM memory out of the rows that will reference them,
+    /// writing the id of a new buffer holding the encoded key those rows hold to the `out` pointer.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_put(
+        caller: FunctionEnvMut<'_, Self>,
+        data: WasmPtr<u8>,
+        data_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "large_bytes_put", out, |mut caller, mem| {
+            let data = mem.read_bytes(&caller, data, data_len)?;
+            let key = caller.data().instance_env.large_bytes_put(&data)?;
+            Ok(caller.data_mut().buffers.insert(key.into()))
+        })
+    }
+
+    /// Reads the bytes stored under the encoded key in the slice `(key, key_len)` in WASM memory
+    /// into a new buffer, whose id is written to the `out` pointer.
+    ///
+    /// Errors with `LOOKUP_NOT_FOUND` if no bytes are stored under the key.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_get(
+        caller: FunctionEnvMut<'_, Self>,
+        key: WasmPtr<u8>,
+        key_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "large_bytes_get", out, |mut caller, mem| {
+            let key = mem.read_bytes(&caller, key, key_len)?;
+            let data = caller.data().instance_env.large_bytes_get(&key)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Takes a savepoint of the changes made so far in the current transaction,
+    /// writing the savepoint's id to the WASM pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn savepoint(caller: FunctionEnvMut<'_, Self>, out: WasmPtr<u32>) -> RtResult<u16> {
+        Self::cvt_ret(caller, "savepoint", out, |caller, _mem| {
+            Ok(caller.data().instance_env.savepoint()?)
+        })
+    }
+
+    /// Undoes the changes made in the current transaction since the savepoint `id` was taken,
+    /// releasing it and all the savepoints taken after it.
+    ///
+    /// Errors with `NO_SUCH_SAVEPOINT` if there's no such savepoint, e.g., because it was already released.
+    #[tracing::instrument(skip_all)]
+    pub fn rollback_to_savepoint(caller: FunctionEnvMut<'_, Self>, id: u32) -> RtResult<u16> {
+        Self::cvt(caller, "rollback_to_savepoint", |caller, _mem| {
+            Ok(caller.data().instance_env.rollback_to_savepoint(id)?)
+        })
+    }
+
+    /// Releases the savepoint `id` and all the savepoints taken after it,
+    /// keeping the changes made since.
+    ///
+    /// Errors with `NO_SUCH_SAVEPOINT` if there's no such savepoint, e.g., because it was already released.
+    #[tracing::instrument(skip_all)]
+    pub fn release_savepoint(caller: FunctionEnvMut<'_, Self>, id: u32) -> RtResult<u16> {
+        Self::cvt(caller, "release_savepoint", |caller, _mem| {
+            Ok(caller.data().instance_env.release_savepoint(id)?)
+        })
+    }
+
     /// Start iteration on each row, as bytes, of a table identified by `table_id`.
     ///
     /// The iterator is registered in the host environment
@@ -475,6 +967,36 @@ impl WasmInstanceEnv {
         })
     }
 
+    /// Like [`WasmInstanceEnv::iter_start`], start iteration on each row of a table identified by `table_id`,
+    /// but with each row projected onto the columns in `(cols, cols_len)`, read from WASM memory.
+    ///
+    /// Only those columns are encoded, in the order given,
+    /// and the schema yielded first is that of the projection.
+    ///
+    /// The iterator is registered in the host environment
+    /// under an assigned index which is written to the `out` pointer provided.
+    // #[tracing::instrument(skip_all)]
+    pub fn iter_start_projected(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        cols: WasmPtr<u8>,
+        cols_len: u32,
+        out: WasmPtr<BufferIterIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "iter_start_projected", out, |mut caller, _mem| {
+            // Read the slice `(cols, cols_len)`.
+            let cols = caller.data().mem().read_bytes(&caller, cols, cols_len)?;
+
+            // Construct the iterator.
+            let iter = caller.data().instance_env.iter_projected(table_id, &cols)?;
+            let iter = iter.map(Bytes::from).map(Ok).collect::<Vec<_>>().into_iter();
+
+            // Register the iterator and get back the index to write to `out`.
+            // Calls to the iterator are done through dynamic dispatch.
+            Ok(caller.data_mut().iters.insert(Box::new(iter)))
+        })
+    }
+
     /// Advances the registered iterator with the index given by `iter_key`.
     ///
     /// On success, the next element (the row as bytes) is written to a buffer.
@@ -11,7 +11,7 @@ use wasmer::{
 };
 use wasmer_middlewares::metering as wasmer_metering;
 
-fn get_remaining_points(ctx: &mut impl AsStoreMut, instance: &Instance) -> u64 {
+pub(super) fn get_remaining_points(ctx: &mut impl AsStoreMut, instance: &Instance) -> u64 {
     let remaining_points = wasmer_metering::get_remaining_points(ctx, instance);
     match remaining_points {
         wasmer_metering::MeteringPoints::Remaining(x) => x,
@@ -38,26 +38,36 @@ fn log_traceback(func_type: &str, func: &str, e: &RuntimeError) {
 pub struct WasmerModule {
     module: Module,
     engine: Engine,
+    abi_support: abi::AbiSupport,
 }
 
 impl WasmerModule {
-    pub fn new(module: Module, engine: Engine) -> Self {
-        WasmerModule { module, engine }
+    pub fn new(module: Module, engine: Engine, abi_support: abi::AbiSupport) -> Self {
+        WasmerModule {
+            module,
+            engine,
+            abi_support,
+        }
     }
 
-    pub const IMPLEMENTED_ABI: abi::VersionTuple = abi::VersionTuple::new(3, 0);
+    pub const IMPLEMENTED_ABI: abi::VersionTuple = abi::VersionTuple::new(3, 16);
 
     fn imports(&self, store: &mut Store, env: &FunctionEnv<WasmInstanceEnv>) -> Imports {
         const _: () = assert!(WasmerModule::IMPLEMENTED_ABI.eq(spacetimedb_lib::MODULE_ABI_VERSION));
-        imports! {
+        let mut imports = imports! {
             "spacetime" => {
                 "_schedule_reducer" => Function::new_typed_with_env(store, env, WasmInstanceEnv::schedule_reducer),
                 "_cancel_reducer" => Function::new_typed_with_env(store, env, WasmInstanceEnv::cancel_reducer),
+                "_emit_event" => Function::new_typed_with_env(store, env, WasmInstanceEnv::emit_event),
+                "_set_return_value" => Function::new_typed_with_env(store, env, WasmInstanceEnv::set_return_value),
+                "_send_message" => Function::new_typed_with_env(store, env, WasmInstanceEnv::send_message),
+                "_describe_reducer" => Function::new_typed_with_env(store, env, WasmInstanceEnv::describe_reducer),
                 "_delete_by_col_eq" => Function::new_typed_with_env(
                     store,
                     env,
                     WasmInstanceEnv::delete_by_col_eq,
                 ),
+                "_move_rows" => Function::new_typed_with_env(store, env, WasmInstanceEnv::move_rows),
                 /*
                 "_delete_pk" => Function::new_typed_with_env(
                     store,
@@ -80,6 +90,11 @@ impl WasmerModule {
                     env,
                     WasmInstanceEnv::insert,
                 ),
+                "_try_insert" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::try_insert,
+                ),
                 /*
                 "_create_table" => Function::new_typed_with_env(
                     store,
@@ -102,6 +117,36 @@ impl WasmerModule {
                     env,
                     WasmInstanceEnv::iter_by_col_eq,
                 ),
+                "_iter_by_col_match" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::iter_by_col_match,
+                ),
+                "_iter_by_col_box" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::iter_by_col_box,
+                ),
+                "_savepoint" => Function::new_typed_with_env(store, env, WasmInstanceEnv::savepoint),
+                "_remaining_energy" => Function::new_typed_with_env(store, env, WasmInstanceEnv::remaining_energy),
+                "_tx_offset" => Function::new_typed_with_env(store, env, WasmInstanceEnv::tx_offset),
+                "_table_version" => Function::new_typed_with_env(store, env, WasmInstanceEnv::table_version),
+                "_table_row_type" => Function::new_typed_with_env(store, env, WasmInstanceEnv::table_row_type),
+                "_blob_put" => Function::new_typed_with_env(store, env, WasmInstanceEnv::blob_put),
+                "_blob_get" => Function::new_typed_with_env(store, env, WasmInstanceEnv::blob_get),
+                "_blob_delete" => Function::new_typed_with_env(store, env, WasmInstanceEnv::blob_delete),
+                "_large_bytes_put" => Function::new_typed_with_env(store, env, WasmInstanceEnv::large_bytes_put),
+                "_large_bytes_get" => Function::new_typed_with_env(store, env, WasmInstanceEnv::large_bytes_get),
+                "_rollback_to_savepoint" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::rollback_to_savepoint,
+                ),
+                "_release_savepoint" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::release_savepoint,
+                ),
                 "_iter_start" => Function::new_typed_with_env(
                     store,
                     env,
@@ -112,6 +157,11 @@ impl WasmerModule {
                     env,
                     WasmInstanceEnv::iter_start_filtered
                 ),
+                "_iter_start_projected" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::iter_start_projected
+                ),
                 "_iter_next" => Function::new_typed_with_env(
                     store,
                     env,
@@ -127,11 +177,30 @@ impl WasmerModule {
                     env,
                     WasmInstanceEnv::console_log
                 ),
+                "_console_log_structured" => Function::new_typed_with_env(
+                    store,
+                    env,
+                    WasmInstanceEnv::console_log_structured
+                ),
                 "_buffer_len" => Function::new_typed_with_env(store, env, WasmInstanceEnv::buffer_len),
                 "_buffer_consume" => Function::new_typed_with_env(store, env, WasmInstanceEnv::buffer_consume),
                 "_buffer_alloc" => Function::new_typed_with_env(store, env, WasmInstanceEnv::buffer_alloc),
             }
+        };
+        // The calls the previous major version of the ABI has and this one dropped.
+        if self.abi_support == abi::AbiSupport::Previous {
+            imports.define(
+                "spacetime",
+                "_delete_value",
+                Function::new_typed_with_env(store, env, WasmInstanceEnv::delete_value),
+            );
+            imports.define(
+                "spacetime",
+                "_delete_range",
+                Function::new_typed_with_env(store, env, WasmInstanceEnv::delete_range),
+            );
         }
+        imports
     }
 }
 
@@ -165,6 +234,7 @@ impl module_host_actor::WasmInstancePre for WasmerModule {
         let env = WasmInstanceEnv {
             instance_env: env,
             mem: None,
+            instance: None,
             buffers: Default::default(),
             iters: Default::default(),
         };
@@ -175,6 +245,7 @@ impl module_host_actor::WasmInstancePre for WasmerModule {
 
         let mem = Mem::extract(&instance.exports).unwrap();
         env.as_mut(&mut store).mem = Some(mem);
+        env.as_mut(&mut store).instance = Some(instance.clone());
 
         // Note: this budget is just for initializers
         let budget = EnergyQuanta::DEFAULT_BUDGET.as_points();
@@ -0,0 +1,47 @@
+//! The imports the AssemblyScript runtime expects from its environment,
+//! linked in addition to the `spacetime` ABI for modules of [`HostType::AssemblyScript`].
+//!
+//! [`HostType::AssemblyScript`]: crate::messages::control_db::HostType::AssemblyScript
+
+use super::wasm_instance_env::WasmInstanceEnv;
+use wasmtime::{Caller, Linker};
+
+pub(super) fn link_imports(linker: &mut Linker<WasmInstanceEnv>) -> anyhow::Result<()> {
+    linker.func_wrap("env", "abort", abort)?;
+    Ok(())
+}
+
+/// Called by the AssemblyScript runtime when the module fails,
+/// e.g., on a failed `assert`, a `throw`, or an out of bounds access.
+///
+/// The module can't continue from there, so this traps with the message and location of the failure.
+fn abort(
+    caller: Caller<'_, WasmInstanceEnv>,
+    message: u32,
+    file_name: u32,
+    line: u32,
+    column: u32,
+) -> anyhow::Result<()> {
+    let message = read_string(&caller, message).unwrap_or_else(|| "<unknown>".into());
+    let file_name = read_string(&caller, file_name).unwrap_or_else(|| "<unknown>".into());
+    Err(anyhow::anyhow!("abort: {message} at {file_name}:{line}:{column}"))
+}
+
+/// Reads the AssemblyScript string at `ptr`,
+/// which is UTF-16 encoded and preceded by its length in bytes.
+///
+/// Returns `None` for a null pointer, or a string that isn't within memory,
+/// including when the module aborts before its memory is known.
+fn read_string(caller: &Caller<'_, WasmInstanceEnv>, ptr: u32) -> Option<String> {
+    let mem = caller.data().mem?;
+    let len_ptr = ptr.checked_sub(4)?;
+    let len = mem.read_bytes(caller, len_ptr, 4).ok()?;
+    let len = u32::from_le_bytes(len.try_into().unwrap());
+    let bytes = mem.read_bytes(caller, ptr, len).ok()?;
+    let units = bytes.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    Some(
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+    )
+}
@@ -0,0 +1,459 @@
+//! Modules compiled as WebAssembly components, against the `spacetime-module` world of
+//! `bindings-sys/wit/spacetime.wit`, rather than as core modules against the raw `spacetime` ABI.
+//!
+//! The host functions of the world are implemented on top of the same [`InstanceEnv`]
+//! as those of the raw ABI, and a component is run by the same [`WasmModuleHostActor`]
+//! as a core module, so the two only differ in how values cross the boundary.
+//!
+//! [`WasmModuleHostActor`]: crate::host::wasm_common::module_host_actor::WasmModuleHostActor
+
+use bytes::Bytes;
+use itertools::Itertools;
+use spacetimedb_lib::bsatn;
+use spacetimedb_lib::logging::LogField;
+use wasmtime::component::{Component, InstancePre, Linker};
+use wasmtime::Store;
+
+use super::{get_remaining_points, set_remaining_points, CALL_TIMEOUT_TICKS};
+use crate::address::Address;
+use crate::database_logger::Record;
+use crate::error::NodesError;
+use crate::host::instance_env::InstanceEnv;
+use crate::host::scheduler::ScheduledReducerId;
+use crate::host::wasm_common::module_host_actor::{self, DescribeError, InitializationError};
+use crate::host::wasm_common::{
+    err_to_errno, unique_conflict, AbiRuntimeError, BufferIterIdx, BufferIters, FuncNames, ValidationError,
+};
+use crate::host::{EnergyQuanta, Timestamp};
+
+wasmtime::component::bindgen!({
+    path: "../bindings-sys/wit",
+    world: "spacetime-module",
+});
+
+/// The data of the store of a component instance.
+pub(super) struct ComponentEnv {
+    instance_env: InstanceEnv,
+    iters: BufferIters,
+}
+
+type HostResult<T> = anyhow::Result<Result<T, host::Errno>>;
+
+/// Convert the result of a host call to what's returned to the component:
+/// the errno of an error that has one, or a trap for any other error.
+fn cvt<T>(func: &'static str, res: Result<T, NodesError>) -> HostResult<T> {
+    match res {
+        Ok(ret) => Ok(Ok(ret)),
+        Err(err) => match err_to_errno(&err) {
+            Some(errno) => {
+                log::info!("abi call to {func} returned a normal error: {err:#}");
+                Ok(Err(errno))
+            }
+            None => Err(AbiRuntimeError { func, err }.into()),
+        },
+    }
+}
+
+impl host::Host for ComponentEnv {
+    fn get_table_id(&mut self, name: String) -> HostResult<u32> {
+        cvt("get_table_id", self.instance_env.get_table_id(name))
+    }
+
+    fn create_index(&mut self, index_name: String, table_id: u32, index_type: u8, col_ids: Vec<u8>) -> HostResult<()> {
+        let res = self
+            .instance_env
+            .create_index(index_name, table_id, index_type, col_ids);
+        cvt("create_index", res)
+    }
+
+    fn insert(&mut self, table_id: u32, row: Vec<u8>) -> HostResult<Vec<u8>> {
+        let res = self.instance_env.insert(table_id, &row).map(|new_row| {
+            let mut row = Vec::with_capacity(row.len());
+            new_row.encode(&mut row);
+            row
+        });
+        cvt("insert", res)
+    }
+
+    fn try_insert(
+        &mut self,
+        table_id: u32,
+        row: Vec<u8>,
+    ) -> anyhow::Result<Result<Vec<u8>, (host::Errno, Option<u32>)>> {
+        let res = self.instance_env.insert(table_id, &row);
+        let conflict = res.as_ref().err().and_then(unique_conflict);
+        let res = res.map(|new_row| {
+            let mut row = Vec::with_capacity(row.len());
+            new_row.encode(&mut row);
+            row
+        });
+        Ok(cvt("try_insert", res)?.map_err(|errno| (errno, conflict)))
+    }
+
+    fn delete_by_col_eq(&mut self, table_id: u32, col_id: u32, value: Vec<u8>) -> HostResult<u32> {
+        cvt(
+            "delete_by_col_eq",
+            self.instance_env.delete_by_col_eq(table_id, col_id, &value),
+        )
+    }
+
+    fn move_rows(
+        &mut self,
+        src_table_id: u32,
+        src_rows: Vec<u8>,
+        dst_table_id: u32,
+        dst_rows: Vec<u8>,
+    ) -> HostResult<u32> {
+        cvt(
+            "move_rows",
+            self.instance_env
+                .move_rows(src_table_id, &src_rows, dst_table_id, &dst_rows),
+        )
+    }
+
+    fn iter_by_col_eq(&mut self, table_id: u32, col_id: u32, value: Vec<u8>) -> HostResult<Vec<u8>> {
+        cvt(
+            "iter_by_col_eq",
+            self.instance_env.iter_by_col_eq(table_id, col_id, &value),
+        )
+    }
+
+    fn iter_by_col_match(&mut self, table_id: u32, col_id: u32, query: String) -> HostResult<Vec<u8>> {
+        cvt(
+            "iter_by_col_match",
+            self.instance_env.iter_by_col_match(table_id, col_id, &query),
+        )
+    }
+
+    fn iter_by_col_box(&mut self, table_id: u32, col_id: u32, min: Vec<u8>, max: Vec<u8>) -> HostResult<Vec<u8>> {
+        cvt(
+            "iter_by_col_box",
+            self.instance_env.iter_by_col_box(table_id, col_id, &min, &max),
+        )
+    }
+
+    fn iter_start(&mut self, table_id: u32) -> HostResult<u32> {
+        let iter = self.instance_env.iter(table_id);
+        // TODO: make it so the above iterator doesn't lock the database for its whole lifetime
+        let iter = iter.map_ok(Bytes::from).collect::<Vec<_>>().into_iter();
+        Ok(Ok(self.iters.insert(Box::new(iter)).0))
+    }
+
+    fn iter_start_filtered(&mut self, table_id: u32, filter: Vec<u8>) -> HostResult<u32> {
+        let iter = match self.instance_env.iter_filtered(table_id, &filter) {
+            // TODO: make it so the above iterator doesn't lock the database for its whole lifetime
+            Ok(iter) => iter.map(Bytes::from).map(Ok).collect::<Vec<_>>().into_iter(),
+            Err(err) => return cvt("iter_start_filtered", Err(err)),
+        };
+        Ok(Ok(self.iters.insert(Box::new(iter)).0))
+    }
+
+    fn iter_start_projected(&mut self, table_id: u32, cols: Vec<u8>) -> HostResult<u32> {
+        let iter = match self.instance_env.iter_projected(table_id, &cols) {
+            Ok(iter) => iter.map(Bytes::from).map(Ok).collect::<Vec<_>>().into_iter(),
+            Err(err) => return cvt("iter_start_projected", Err(err)),
+        };
+        Ok(Ok(self.iters.insert(Box::new(iter)).0))
+    }
+
+    fn iter_next(&mut self, iter: u32) -> HostResult<Option<Vec<u8>>> {
+        let iter = self
+            .iters
+            .get_mut(BufferIterIdx(iter))
+            .ok_or_else(|| anyhow::anyhow!("no such iterator"))?;
+        cvt("iter_next", iter.next().transpose().map(|row| row.map(Vec::from)))
+    }
+
+    fn iter_drop(&mut self, iter: u32) -> HostResult<()> {
+        self.iters
+            .take(BufferIterIdx(iter))
+            .ok_or_else(|| anyhow::anyhow!("no such iterator"))?;
+        Ok(Ok(()))
+    }
+
+    fn tx_offset(&mut self) -> HostResult<u64> {
+        cvt("tx_offset", self.instance_env.tx_offset())
+    }
+
+    fn table_version(&mut self, table_id: u32) -> HostResult<u64> {
+        cvt("table_version", self.instance_env.table_version(table_id))
+    }
+
+    fn table_row_type(&mut self, table_id: u32) -> HostResult<Vec<u8>> {
+        cvt("table_row_type", self.instance_env.table_row_type(table_id))
+    }
+
+    fn blob_put(&mut self, key: String, data: Vec<u8>) -> HostResult<Vec<u8>> {
+        cvt(
+            "blob_put",
+            self.instance_env.blob_put(&key, &data).map(|hash| hash.to_vec()),
+        )
+    }
+
+    fn blob_get(&mut self, key: String) -> HostResult<Vec<u8>> {
+        cvt("blob_get", self.instance_env.blob_get(&key))
+    }
+
+    fn blob_delete(&mut self, key: String) -> HostResult<()> {
+        cvt("blob_delete", self.instance_env.blob_delete(&key))
+    }
+
+    fn large_bytes_put(&mut self, data: Vec<u8>) -> HostResult<Vec<u8>> {
+        cvt("large_bytes_put", self.instance_env.large_bytes_put(&data))
+    }
+
+    fn large_bytes_get(&mut self, key: Vec<u8>) -> HostResult<Vec<u8>> {
+        cvt("large_bytes_get", self.instance_env.large_bytes_get(&key))
+    }
+
+    fn savepoint(&mut self) -> HostResult<u32> {
+        cvt("savepoint", self.instance_env.savepoint())
+    }
+
+    fn rollback_to_savepoint(&mut self, id: u32) -> HostResult<()> {
+        cvt("rollback_to_savepoint", self.instance_env.rollback_to_savepoint(id))
+    }
+
+    fn release_savepoint(&mut self, id: u32) -> HostResult<()> {
+        cvt("release_savepoint", self.instance_env.release_savepoint(id))
+    }
+
+    fn schedule_reducer(&mut self, name: String, args: Vec<u8>, time: u64) -> anyhow::Result<u64> {
+        let ScheduledReducerId(id) = self.instance_env.schedule(name, args, Timestamp(time))?;
+        Ok(id)
+    }
+
+    fn cancel_reducer(&mut self, id: u64) -> anyhow::Result<()> {
+        Ok(self.instance_env.cancel_reducer(ScheduledReducerId(id))?)
+    }
+
+    fn emit_event(&mut self, name: String, data: Vec<u8>) -> HostResult<()> {
+        self.instance_env.emit_event(name, data);
+        Ok(Ok(()))
+    }
+
+    fn set_return_value(&mut self, data: Vec<u8>) -> HostResult<()> {
+        self.instance_env.set_return_value(data);
+        Ok(Ok(()))
+    }
+
+    fn send_message(&mut self, address: Vec<u8>, reducer: String, args: Vec<u8>) -> HostResult<()> {
+        let address: [u8; 16] = address
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("address must be 16 bytes"))?;
+        cvt(
+            "send_message",
+            self.instance_env
+                .send_message(Address::from_arr(&address), &reducer, &args),
+        )
+    }
+
+    fn describe_reducer(&mut self, name: String) -> HostResult<Vec<u8>> {
+        cvt("describe_reducer", self.instance_env.describe_reducer(&name))
+    }
+
+    fn console_log(
+        &mut self,
+        level: u8,
+        target: Option<String>,
+        filename: Option<String>,
+        line_number: Option<u32>,
+        message: String,
+        fields: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let fields: Vec<LogField> = bsatn::from_slice(&fields).unwrap_or_default();
+        let record = Record {
+            target: target.as_deref(),
+            filename: filename.as_deref(),
+            line_number,
+            message: &message,
+            fields: &fields,
+        };
+        // The host functions of a component don't have access to its store to capture a backtrace.
+        self.instance_env.console_log(level.into(), &record, &());
+        Ok(())
+    }
+}
+
+/// Take the energy spent on the host's operations during a call from the budget the call had left,
+/// as the host functions of a component are only charged for once the call returns.
+///
+/// Errors if that exhausts the budget, which is reported like the call running out of fuel.
+fn settle_energy(store: &mut Store<ComponentEnv>) -> anyhow::Result<()> {
+    let points = store.data().instance_env.energy.take_pending_points();
+    let remaining = get_remaining_points(&mut *store);
+    set_remaining_points(&mut *store, remaining.saturating_sub(points));
+    anyhow::ensure!(remaining >= points, wasmtime::Trap::OutOfFuel);
+    Ok(())
+}
+
+/// Arm the meters of a store for a call into the component,
+/// which is stopped once it runs through `budget` or [`CALL_TIMEOUT`](super::CALL_TIMEOUT).
+fn arm(store: &mut Store<ComponentEnv>, budget: u64) {
+    set_remaining_points(&mut *store, budget);
+    store.set_epoch_deadline(CALL_TIMEOUT_TICKS);
+}
+
+pub struct WasmtimeComponent {
+    component: Component,
+    linker: Linker<ComponentEnv>,
+}
+
+impl WasmtimeComponent {
+    pub(super) fn new(component: Component, linker: Linker<ComponentEnv>) -> Self {
+        WasmtimeComponent { component, linker }
+    }
+
+    pub(super) fn link_imports(linker: &mut Linker<ComponentEnv>) -> anyhow::Result<()> {
+        SpacetimeModule::add_to_linker(linker, |env: &mut ComponentEnv| env)
+    }
+}
+
+impl module_host_actor::WasmModule for WasmtimeComponent {
+    type Instance = ComponentInstance;
+    type InstancePre = ComponentInstancePre;
+
+    // A component's exports are typed by its world, so there's no core signature to check.
+    type ExternType = wasmtime::ExternType;
+
+    fn get_export(&self, _s: &str) -> Option<Self::ExternType> {
+        None
+    }
+
+    fn for_each_export<E>(&self, _f: impl FnMut(&str, &Self::ExternType) -> Result<(), E>) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// The world requires every export, and linking checks their types, so there's nothing to validate.
+    fn func_names(&self) -> Result<FuncNames, ValidationError> {
+        Ok(FuncNames {
+            conn: true,
+            disconn: true,
+            preinits: vec![],
+        })
+    }
+
+    fn instantiate_pre(&self) -> Result<Self::InstancePre, InitializationError> {
+        let pre = self
+            .linker
+            .instantiate_pre(&self.component)
+            .map_err(InitializationError::Instantiation)?;
+        Ok(ComponentInstancePre { pre })
+    }
+}
+
+pub struct ComponentInstancePre {
+    pre: InstancePre<ComponentEnv>,
+}
+
+impl module_host_actor::WasmInstancePre for ComponentInstancePre {
+    type Instance = ComponentInstance;
+
+    fn instantiate(&self, env: InstanceEnv, _func_names: &FuncNames) -> Result<Self::Instance, InitializationError> {
+        let env = ComponentEnv {
+            instance_env: env,
+            iters: Default::default(),
+        };
+        let mut store = Store::new(&super::ENGINE, env);
+
+        // Note: this budget is just for initializers
+        arm(&mut store, EnergyQuanta::DEFAULT_BUDGET.as_points());
+
+        let (bindings, _) =
+            SpacetimeModule::instantiate_pre(&mut store, &self.pre).map_err(InitializationError::Instantiation)?;
+
+        let setup = |err| InitializationError::RuntimeError {
+            err,
+            func: "setup".to_owned(),
+        };
+        bindings
+            .call_setup(&mut store)
+            .map_err(setup)?
+            .map_err(|e| InitializationError::Setup(e.into()))?;
+
+        Ok(ComponentInstance { store, bindings })
+    }
+}
+
+pub struct ComponentInstance {
+    store: Store<ComponentEnv>,
+    bindings: SpacetimeModule,
+}
+
+impl module_host_actor::WasmInstance for ComponentInstance {
+    fn extract_descriptions(&mut self) -> Result<Bytes, DescribeError> {
+        arm(&mut self.store, EnergyQuanta::DEFAULT_BUDGET.as_points());
+        let desc = self.bindings.call_describe_module(&mut self.store).map_err(|err| {
+            super::wasmtime_module::log_traceback("describer", "describe-module", &err);
+            DescribeError::RuntimeError(err)
+        })?;
+        Ok(desc.into())
+    }
+
+    fn instance_env(&self) -> &InstanceEnv {
+        &self.store.data().instance_env
+    }
+
+    type Trap = anyhow::Error;
+
+    fn call_reducer(
+        &mut self,
+        reducer_id: usize,
+        budget: EnergyQuanta,
+        sender: &[u8; 32],
+        timestamp: Timestamp,
+        arg_bytes: Bytes,
+    ) -> module_host_actor::ExecuteResult<Self::Trap> {
+        call_tx_function(&mut self.store, budget, |store| {
+            self.bindings
+                .call_call_reducer(store, reducer_id as u32, sender, timestamp.0, &arg_bytes)
+        })
+    }
+
+    fn call_connect_disconnect(
+        &mut self,
+        connect: bool,
+        budget: EnergyQuanta,
+        sender: &[u8; 32],
+        timestamp: Timestamp,
+    ) -> module_host_actor::ExecuteResult<Self::Trap> {
+        call_tx_function(&mut self.store, budget, |store| {
+            if connect {
+                self.bindings.call_identity_connected(store, sender, timestamp.0)
+            } else {
+                self.bindings.call_identity_disconnected(store, sender, timestamp.0)
+            }
+        })
+    }
+
+    fn log_traceback(func_type: &str, func: &str, trap: &Self::Trap) {
+        super::wasmtime_module::log_traceback(func_type, func, trap)
+    }
+}
+
+fn call_tx_function(
+    store: &mut Store<ComponentEnv>,
+    budget: EnergyQuanta,
+    call: impl FnOnce(&mut Store<ComponentEnv>) -> anyhow::Result<Result<(), String>>,
+) -> module_host_actor::ExecuteResult<anyhow::Error> {
+    let budget = budget.as_points();
+    arm(store, budget);
+
+    let start = std::time::Instant::now();
+    let result = call(store);
+    let settled = settle_energy(store);
+    let result = result.and_then(|res| {
+        settled?;
+        Ok(res.map_err(Into::into))
+    });
+    let duration = start.elapsed();
+    let remaining = get_remaining_points(&mut *store);
+    module_host_actor::ExecuteResult {
+        energy: module_host_actor::EnergyStats {
+            used: EnergyQuanta::from_points(budget) - EnergyQuanta::from_points(remaining),
+            remaining: EnergyQuanta::from_points(remaining),
+        },
+        execution_duration: duration,
+        call_result: result,
+    }
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use wasmtime::{AsContext, AsContextMut, Engine, Linker, Module};
+
+use crate::database_instance_context::DatabaseInstanceContext;
+use crate::error::NodesError;
+use crate::hash::Hash;
+
+mod assemblyscript;
+mod component;
+mod wasm_instance_env;
+mod wasmtime_module;
+
+use component::WasmtimeComponent;
+use wasmtime_module::WasmtimeModule;
+
+use super::module_host::ModuleHostActor;
+use super::scheduler::Scheduler;
+use super::wasm_common::{abi, module_host_actor::WasmModuleHostActor, ModuleCreationError};
+use super::EnergyMonitor;
+
+/// How often the epoch of the engine is advanced,
+/// which is the granularity at which a call into a module can be interrupted.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// How long a single call into a module may run before it's interrupted,
+/// independently of how much energy it has left.
+const CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The number of epoch ticks a call into a module may run for.
+const CALL_TIMEOUT_TICKS: u64 = (CALL_TIMEOUT.as_millis() / EPOCH_TICK.as_millis()) as u64;
+
+/// The engine shared by every module, metering energy with fuel
+/// and interrupting runaway calls with epochs, advanced by a background thread.
+static ENGINE: Lazy<Engine> = Lazy::new(|| {
+    let mut config = wasmtime::Config::new();
+    config
+        .cranelift_opt_level(wasmtime::OptLevel::Speed)
+        .consume_fuel(true)
+        .epoch_interruption(true)
+        .wasm_component_model(true);
+    let engine = Engine::new(&config).unwrap();
+
+    let ticker = engine.clone();
+    std::thread::Builder::new()
+        .name("wasmtime-epoch".into())
+        .spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        })
+        .unwrap();
+
+    engine
+});
+
+pub fn make_actor(
+    dbic: Arc<DatabaseInstanceContext>,
+    module_hash: Hash,
+    program_bytes: &[u8],
+    scheduler: Scheduler,
+    energy_monitor: Arc<dyn EnergyMonitor>,
+) -> Result<impl ModuleHostActor, ModuleCreationError> {
+    make_actor_with(dbic, module_hash, program_bytes, scheduler, energy_monitor, |_| Ok(()))
+}
+
+/// Like [`make_actor`], for a module written in AssemblyScript,
+/// which also imports the environment its runtime expects.
+pub fn make_assemblyscript_actor(
+    dbic: Arc<DatabaseInstanceContext>,
+    module_hash: Hash,
+    program_bytes: &[u8],
+    scheduler: Scheduler,
+    energy_monitor: Arc<dyn EnergyMonitor>,
+) -> Result<impl ModuleHostActor, ModuleCreationError> {
+    make_actor_with(
+        dbic,
+        module_hash,
+        program_bytes,
+        scheduler,
+        energy_monitor,
+        assemblyscript::link_imports,
+    )
+}
+
+fn make_actor_with(
+    dbic: Arc<DatabaseInstanceContext>,
+    module_hash: Hash,
+    program_bytes: &[u8],
+    scheduler: Scheduler,
+    energy_monitor: Arc<dyn EnergyMonitor>,
+    link_env: fn(&mut Linker<wasm_instance_env::WasmInstanceEnv>) -> anyhow::Result<()>,
+) -> Result<impl ModuleHostActor, ModuleCreationError> {
+    let module = Module::new(&ENGINE, program_bytes).map_err(ModuleCreationError::WasmCompileError)?;
+
+    let abi = abi::determine_spacetime_abi(program_bytes)?;
+    let abi_support = abi::negotiate(WasmtimeModule::IMPLEMENTED_ABI, abi)?;
+
+    let mut linker = Linker::new(&ENGINE);
+    WasmtimeModule::link_imports(&mut linker).map_err(ModuleCreationError::WasmCompileError)?;
+    if abi_support == abi::AbiSupport::Previous {
+        log::info!("linking the shims for a module built against ABI {abi}");
+        WasmtimeModule::link_previous_abi_imports(&mut linker).map_err(ModuleCreationError::WasmCompileError)?;
+    }
+    link_env(&mut linker).map_err(ModuleCreationError::WasmCompileError)?;
+
+    let module = WasmtimeModule::new(module, linker);
+
+    WasmModuleHostActor::new(dbic, module_hash, module, scheduler, energy_monitor).map_err(Into::into)
+}
+
+/// Whether `program_bytes` is a component, rather than a core module.
+///
+/// Both start with the same magic number, followed by a version and a layer, which is `1` for a component.
+pub fn is_component(program_bytes: &[u8]) -> bool {
+    program_bytes.starts_with(b"\0asm") && program_bytes.get(6..8) == Some(&[1, 0][..])
+}
+
+/// Like [`make_actor`], for a module compiled as a component against the world of `bindings-sys/wit`.
+pub fn make_component_actor(
+    dbic: Arc<DatabaseInstanceContext>,
+    module_hash: Hash,
+    program_bytes: &[u8],
+    scheduler: Scheduler,
+    energy_monitor: Arc<dyn EnergyMonitor>,
+) -> Result<impl ModuleHostActor, ModuleCreationError> {
+    let component =
+        wasmtime::component::Component::new(&ENGINE, program_bytes).map_err(ModuleCreationError::WasmCompileError)?;
+
+    let mut linker = wasmtime::component::Linker::new(&ENGINE);
+    WasmtimeComponent::link_imports(&mut linker).map_err(ModuleCreationError::WasmCompileError)?;
+
+    let module = WasmtimeComponent::new(component, linker);
+
+    WasmModuleHostActor::new(dbic, module_hash, module, scheduler, energy_monitor).map_err(Into::into)
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+enum WasmError {
+    Db(#[from] NodesError),
+    Wasm(#[from] anyhow::Error),
+}
+
+/// The energy left in the budget of the current call into the module, in points of the meter.
+fn get_remaining_points(mut store: impl AsContextMut) -> u64 {
+    // Consuming no fuel is how wasmtime reports the fuel that's left.
+    store.as_context_mut().consume_fuel(0).unwrap_or(0)
+}
+
+/// Set the energy left in the budget of the current call into the module to `points`.
+fn set_remaining_points(mut store: impl AsContextMut, points: u64) {
+    let mut store = store.as_context_mut();
+    let remaining = get_remaining_points(&mut store);
+    // Fuel can't exceed `i64::MAX`, which is still far more than any budget.
+    let points = points.min(i64::MAX as u64);
+    if points < remaining {
+        store.consume_fuel(remaining - points).unwrap();
+    } else if points > remaining {
+        store.add_fuel(points - remaining).unwrap();
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Mem {
+    pub memory: wasmtime::Memory,
+}
+
+impl Mem {
+    fn extract(instance: &wasmtime::Instance, store: impl AsContextMut) -> anyhow::Result<Self> {
+        let memory = instance
+            .get_memory(store, "memory")
+            .ok_or_else(|| anyhow::anyhow!("no memory export called \"memory\""))?;
+        Ok(Self { memory })
+    }
+
+    /// Reads a slice of bytes starting from `ptr` and lasting `len` bytes into a `Vec<u8>`.
+    ///
+    /// Returns an error if the slice isn't within the memory.
+    fn read_bytes(&self, store: &impl AsContext, ptr: u32, len: u32) -> anyhow::Result<Vec<u8>> {
+        let start = ptr as usize;
+        let bytes = self.memory.data(store).get(start..start + len as usize);
+        bytes.map(<[u8]>::to_vec).ok_or_else(out_of_bounds)
+    }
+
+    /// Writes `data` to the memory starting from `ptr`.
+    ///
+    /// Returns an error if the slice isn't within the memory.
+    fn set_bytes(&self, store: &mut impl AsContextMut, ptr: u32, data: &[u8]) -> anyhow::Result<()> {
+        let start = ptr as usize;
+        let bytes = self.memory.data_mut(store).get_mut(start..start + data.len());
+        bytes.ok_or_else(out_of_bounds)?.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+fn out_of_bounds() -> anyhow::Error {
+    anyhow::Error::new(wasmtime::Trap::MemoryOutOfBounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Instance, Store, Trap};
+
+    /// A module exporting `spin`, which loops forever:
+    /// `(module (func (export "spin") (loop (br 0))))`.
+    const SPIN: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x07, 0x08, 0x01, 0x04, b's', b'p', b'i', b'n', 0x00, 0x00, // export section
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b, // code section
+    ];
+
+    /// Calls `spin` with `budget` points of energy and `ticks` epoch ticks, returning the trap that stopped it.
+    fn spin(budget: u64, ticks: u64) -> Trap {
+        let module = Module::new(&ENGINE, SPIN).unwrap();
+        let mut store = Store::new(&ENGINE, ());
+        set_remaining_points(&mut store, budget);
+        store.set_epoch_deadline(ticks);
+        let instance = Instance::new(&mut store, &module, &[]).unwrap();
+        let spin = instance.get_typed_func::<(), ()>(&mut store, "spin").unwrap();
+        let err = spin.call(&mut store, ()).unwrap_err();
+        *err.downcast_ref::<Trap>().unwrap()
+    }
+
+    #[test]
+    fn components_are_told_from_core_modules() {
+        assert!(!is_component(SPIN));
+        // The preamble of a component: magic, version `0x0d` and layer `1`.
+        assert!(is_component(&[0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00]));
+        assert!(!is_component(b"not wasm"));
+        assert!(!is_component(b"\0asm"));
+    }
+
+    #[test]
+    fn component_imports_link() {
+        let mut linker = wasmtime::component::Linker::new(&ENGINE);
+        WasmtimeComponent::link_imports(&mut linker).unwrap();
+    }
+
+    #[test]
+    fn remaining_points() {
+        let mut store = Store::new(&ENGINE, ());
+        assert_eq!(get_remaining_points(&mut store), 0);
+        set_remaining_points(&mut store, 100);
+        assert_eq!(get_remaining_points(&mut store), 100);
+        set_remaining_points(&mut store, 40);
+        assert_eq!(get_remaining_points(&mut store), 40);
+        set_remaining_points(&mut store, u64::MAX);
+        assert_eq!(get_remaining_points(&mut store), i64::MAX as u64);
+    }
+
+    #[test]
+    fn runaway_call_runs_out_of_fuel() {
+        assert_eq!(spin(10_000, CALL_TIMEOUT_TICKS), Trap::OutOfFuel);
+    }
+
+    #[test]
+    fn runaway_call_is_interrupted() {
+        assert_eq!(spin(i64::MAX as u64, 1), Trap::Interrupt);
+    }
+}
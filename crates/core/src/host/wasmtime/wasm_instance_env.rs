@@ -0,0 +1,832 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::address::Address;
+use crate::database_logger::{BacktraceFrame, BacktraceProvider, ModuleBacktrace, Record};
+use crate::host::scheduler::{ScheduleError, ScheduledReducerId};
+use crate::host::timestamp::Timestamp;
+use crate::host::wasm_common::{
+    err_to_errno, unique_conflict, AbiRuntimeError, BufferIdx, BufferIterIdx, BufferIters, Buffers,
+};
+use anyhow::anyhow;
+use bytes::Bytes;
+use itertools::Itertools;
+use spacetimedb_lib::bsatn;
+use spacetimedb_lib::logging::LogField;
+use wasmtime::{AsContext, Caller, StoreContext, WasmBacktrace};
+
+use crate::host::instance_env::InstanceEnv;
+
+use super::{get_remaining_points, set_remaining_points, Mem, WasmError};
+
+/// The environment of an instance, the data of its `Store`,
+/// through which the host functions imported by the module reach the database.
+///
+/// The host functions mirror those of the Wasmer host, see `host::wasmer::wasm_instance_env`,
+/// except that pointers into WASM memory are plain `u32` offsets.
+pub(super) struct WasmInstanceEnv {
+    pub instance_env: InstanceEnv,
+    pub mem: Option<Mem>,
+    pub buffers: Buffers,
+    pub iters: BufferIters,
+}
+
+type WasmResult<T> = Result<T, WasmError>;
+
+/// A value written by a host function to a pointer into WASM memory, as its little-endian bytes.
+trait WasmPointee {
+    type Bytes: AsRef<[u8]>;
+    fn to_le_bytes(self) -> Self::Bytes;
+}
+
+impl WasmPointee for u32 {
+    type Bytes = [u8; 4];
+    fn to_le_bytes(self) -> Self::Bytes {
+        u32::to_le_bytes(self)
+    }
+}
+
+impl WasmPointee for u64 {
+    type Bytes = [u8; 8];
+    fn to_le_bytes(self) -> Self::Bytes {
+        u64::to_le_bytes(self)
+    }
+}
+
+impl WasmPointee for BufferIdx {
+    type Bytes = [u8; 4];
+    fn to_le_bytes(self) -> Self::Bytes {
+        self.0.to_le_bytes()
+    }
+}
+
+impl WasmPointee for BufferIterIdx {
+    type Bytes = [u8; 4];
+    fn to_le_bytes(self) -> Self::Bytes {
+        self.0.to_le_bytes()
+    }
+}
+
+impl WasmInstanceEnv {
+    /// Returns the memory, assumed to be initialized.
+    pub fn mem(&self) -> Mem {
+        self.mem.expect("Initialized memory")
+    }
+
+    /// Takes the energy spent on the host's operations since the last call
+    /// from the budget of the current call into the module.
+    ///
+    /// Errors if that exhausts the budget, which traps the module like running out of fuel would.
+    fn charge_energy(caller: &mut Caller<'_, Self>) -> anyhow::Result<()> {
+        let points = caller.data().instance_env.energy.take_pending_points();
+        if points == 0 {
+            return Ok(());
+        }
+        let remaining = get_remaining_points(&mut *caller);
+        set_remaining_points(&mut *caller, remaining.saturating_sub(points));
+        if remaining < points {
+            return Err(anyhow::Error::new(wasmtime::Trap::OutOfFuel));
+        }
+        Ok(())
+    }
+
+    /// Call the function `f` with the name `func`.
+    /// The function `f` is provided with the caller and the instance's memory.
+    ///
+    /// Some database errors are logged but are otherwise regarded as `Ok(_)`.
+    /// See `err_to_errno` for a list.
+    fn cvt(
+        mut caller: Caller<'_, Self>,
+        func: &'static str,
+        f: impl FnOnce(&mut Caller<'_, Self>, Mem) -> WasmResult<()>,
+    ) -> anyhow::Result<u32> {
+        // Call `f` with the caller and the memory,
+        // paying for what it did even if it failed.
+        // Bail if there were no errors.
+        let mem = caller.data().mem();
+        let res = f(&mut caller, mem);
+        Self::charge_energy(&mut caller)?;
+        let Err(err) = res else {
+            return Ok(0);
+        };
+
+        // Handle any errors.
+        Err(match err {
+            WasmError::Db(err) => match err_to_errno(&err) {
+                Some(errno) => {
+                    log::info!("abi call to {func} returned a normal error: {err:#}");
+                    return Ok(errno.into());
+                }
+                None => anyhow::Error::new(AbiRuntimeError { func, err }),
+            },
+            WasmError::Wasm(err) => err,
+        })
+    }
+
+    /// Call the function `f` with any return value being written to the pointer `out`.
+    ///
+    /// Otherwise, `cvt_ret` (this function) behaves as `cvt`.
+    fn cvt_ret<T: WasmPointee>(
+        caller: Caller<'_, Self>,
+        func: &'static str,
+        out: u32,
+        f: impl FnOnce(&mut Caller<'_, Self>, Mem) -> WasmResult<T>,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, func, |caller, mem| {
+            let ret = f(caller, mem)?;
+            Ok(mem.set_bytes(caller, out, ret.to_le_bytes().as_ref())?)
+        })
+    }
+
+    /// Reads a string from WASM memory starting at `ptr` and lasting `len` bytes.
+    ///
+    /// Returns an error if there were memory access issues
+    /// or if the string was not valid UTF-8.
+    fn read_string(caller: &Caller<'_, Self>, mem: Mem, ptr: u32, len: u32) -> anyhow::Result<String> {
+        let bytes = mem.read_bytes(caller, ptr, len)?;
+        String::from_utf8(bytes).map_err(|_| anyhow!("name must be utf8"))
+    }
+
+    /// Schedule the reducer `(name, name_len)` to be executed asynchronously,
+    /// passing it `(args, args_len)`, at the given `time`,
+    /// writing the id assigned to the scheduled call to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn schedule_reducer(
+        caller: Caller<'_, Self>,
+        name: u32,
+        name_len: u32,
+        args: u32,
+        args_len: u32,
+        time: u64,
+        out: u32,
+    ) -> anyhow::Result<()> {
+        Self::cvt_ret(caller, "schedule_reducer", out, |caller, mem| {
+            let name = Self::read_string(caller, mem, name, name_len)?;
+            let args = mem.read_bytes(caller, args, args_len)?;
+            let ScheduledReducerId(id) = caller
+                .data()
+                .instance_env
+                .schedule(name, args, Timestamp(time))
+                .map_err(|e| match e {
+                    ScheduleError::IdTransactionError(_) => anyhow!("transaction to acquire ScheduleReducerId failed"),
+                    e @ ScheduleError::ReadOnlyTransaction => e.into(),
+                })?;
+            Ok(id)
+        })
+        .map(|_| ())
+    }
+
+    /// Cancel a reducer that was scheduled with `id`.
+    #[tracing::instrument(skip_all)]
+    pub fn cancel_reducer(caller: Caller<'_, Self>, id: u64) -> anyhow::Result<()> {
+        Ok(caller.data().instance_env.cancel_reducer(ScheduledReducerId(id))?)
+    }
+
+    /// Emits an event of the type named by the UTF-8 slice `(name, name_len)`,
+    /// encoded as BSATN in the byte slice `(data, data_len)`.
+    #[tracing::instrument(skip_all)]
+    pub fn emit_event(
+        caller: Caller<'_, Self>,
+        name: u32,
+        name_len: u32,
+        data: u32,
+        data_len: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, "emit_event", |caller, mem| {
+            let name = Self::read_string(caller, mem, name, name_len)?;
+            let data = mem.read_bytes(caller, data, data_len)?;
+            caller.data().instance_env.emit_event(name, data);
+            Ok(())
+        })
+    }
+
+    /// Sets the value the reducer returns to its caller, encoded as BSATN in the byte slice `(data, data_len)`.
+    #[tracing::instrument(skip_all)]
+    pub fn set_return_value(caller: Caller<'_, Self>, data: u32, data_len: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "set_return_value", |caller, mem| {
+            let data = mem.read_bytes(caller, data, data_len)?;
+            caller.data().instance_env.set_return_value(data);
+            Ok(())
+        })
+    }
+
+    /// Sends a call of the reducer named by the UTF-8 slice `(reducer, reducer_len)`
+    /// of the database whose 16 byte address is at the pointer `address`,
+    /// with its arguments encoded as BSATN in the byte slice `(args, args_len)`.
+    #[tracing::instrument(skip_all)]
+    pub fn send_message(
+        caller: Caller<'_, Self>,
+        address: u32,
+        reducer: u32,
+        reducer_len: u32,
+        args: u32,
+        args_len: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, "send_message", |caller, mem| {
+            let address = Address::from_slice(mem.read_bytes(caller, address, 16)?);
+            let reducer = Self::read_string(caller, mem, reducer, reducer_len)?;
+            let args = mem.read_bytes(caller, args, args_len)?;
+            caller.data().instance_env.send_message(address, &reducer, &args)?;
+            Ok(())
+        })
+    }
+
+    /// Describes the arguments of the reducer named by the UTF-8 slice `(name, name_len)`,
+    /// as a BSATN encoded `ProductType` written to a new buffer,
+    /// whose id is written to `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn describe_reducer(caller: Caller<'_, Self>, name: u32, name_len: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "describe_reducer", out, |caller, mem| {
+            let name = Self::read_string(caller, mem, name, name_len)?;
+            let args = caller.data().instance_env.describe_reducer(&name)?;
+            Ok(caller.data_mut().buffers.insert(args.into()))
+        })
+    }
+
+    /// Log at `level` a `message` occuring in `filename:line_number` with `target`.
+    #[tracing::instrument(skip_all)]
+    pub fn console_log(
+        caller: Caller<'_, Self>,
+        level: u32,
+        target: u32,
+        target_len: u32,
+        filename: u32,
+        filename_len: u32,
+        line_number: u32,
+        message: u32,
+        message_len: u32,
+    ) {
+        Self::write_log(
+            caller,
+            level,
+            target,
+            target_len,
+            filename,
+            filename_len,
+            line_number,
+            message,
+            message_len,
+            0,
+            0,
+        )
+    }
+
+    /// Log at `level` a `message` occuring in `filename:line_number` with `target`,
+    /// and the key-value pairs `(fields, fields_len)`, bsatn encoded as a `Vec<LogField>`.
+    #[tracing::instrument(skip_all)]
+    pub fn console_log_structured(
+        caller: Caller<'_, Self>,
+        level: u32,
+        target: u32,
+        target_len: u32,
+        filename: u32,
+        filename_len: u32,
+        line_number: u32,
+        message: u32,
+        message_len: u32,
+        fields: u32,
+        fields_len: u32,
+    ) {
+        Self::write_log(
+            caller,
+            level,
+            target,
+            target_len,
+            filename,
+            filename_len,
+            line_number,
+            message,
+            message_len,
+            fields,
+            fields_len,
+        )
+    }
+
+    fn write_log(
+        caller: Caller<'_, Self>,
+        level: u32,
+        target: u32,
+        target_len: u32,
+        filename: u32,
+        filename_len: u32,
+        line_number: u32,
+        message: u32,
+        message_len: u32,
+        fields: u32,
+        fields_len: u32,
+    ) {
+        let mem = caller.data().mem();
+
+        // Reads a string lossily from the slice `(ptr, len)` in WASM memory.
+        let read_str = |ptr, len| {
+            mem.read_bytes(&caller, ptr, len)
+                .map(crate::util::string_from_utf8_lossy_owned)
+        };
+
+        // Reads as string optionally, unless `ptr` is null.
+        let read_opt_str = |ptr: u32, len| (ptr != 0).then(|| read_str(ptr, len)).transpose();
+
+        let _ = (|| -> anyhow::Result<_> {
+            let target = read_opt_str(target, target_len)?;
+            let filename = read_opt_str(filename, filename_len)?;
+            let message = read_str(message, message_len)?;
+
+            // The line number cannot be `u32::MAX` as this represents `Option::None`.
+            let line_number = (line_number != u32::MAX).then_some(line_number);
+
+            let fields: Vec<LogField> = if fields == 0 {
+                Vec::new()
+            } else {
+                let bytes = mem.read_bytes(&caller, fields, fields_len)?;
+                bsatn::from_slice(&bytes).unwrap_or_default()
+            };
+
+            let record = Record {
+                target: target.as_deref(),
+                filename: filename.as_deref(),
+                line_number,
+                message: &message,
+                fields: &fields,
+            };
+
+            caller
+                .data()
+                .instance_env
+                .console_log((level as u8).into(), &record, &caller.as_context());
+            Ok(())
+        })();
+    }
+
+    /// Insert a row, into the table identified by `table_id`,
+    /// where the row is read from the byte slice `(row_ptr, row_len)` in WASM memory,
+    /// and written back with any autoinc columns filled in.
+    #[tracing::instrument(skip_all)]
+    pub fn insert(caller: Caller<'_, Self>, table_id: u32, row_ptr: u32, row_len: u32) -> anyhow::Result<u32> {
+        Self::insert_reporting(caller, "insert", table_id, row_ptr, row_len, None)
+    }
+
+    /// Like [`Self::insert`], but when the row violates a unique constraint,
+    /// the id of the column of that constraint is also written to the pointer `conflict`.
+    #[tracing::instrument(skip_all)]
+    pub fn try_insert(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        row_ptr: u32,
+        row_len: u32,
+        conflict: u32,
+    ) -> anyhow::Result<u32> {
+        Self::insert_reporting(caller, "try_insert", table_id, row_ptr, row_len, Some(conflict))
+    }
+
+    /// Inserts the row `(row_ptr, row_len)` for `insert` and `try_insert`,
+    /// reporting the column of a violated unique constraint to `conflict`, if given.
+    fn insert_reporting(
+        caller: Caller<'_, Self>,
+        func: &'static str,
+        table_id: u32,
+        row_ptr: u32,
+        row_len: u32,
+        conflict: Option<u32>,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, func, |caller, mem| {
+            let mut row_buffer = mem.read_bytes(caller, row_ptr, row_len)?;
+            let new_row = match caller.data().instance_env.insert(table_id, &row_buffer) {
+                Ok(new_row) => new_row,
+                Err(err) => {
+                    if let (Some(conflict), Some(col_id)) = (conflict, unique_conflict(&err)) {
+                        mem.set_bytes(caller, conflict, &col_id.to_le_bytes())?;
+                    }
+                    return Err(err.into());
+                }
+            };
+            row_buffer.clear();
+            new_row.encode(&mut row_buffer);
+            assert_eq!(
+                row_buffer.len(),
+                row_len as usize,
+                "autoinc'd row is different encoded size from original row"
+            );
+            mem.set_bytes(caller, row_ptr, &row_buffer)?;
+            Ok(())
+        })
+    }
+
+    /// Deletes all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` matches the byte string `(value, value_len)`,
+    /// writing the number of rows deleted to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn delete_by_col_eq(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        value: u32,
+        value_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "delete_by_col_eq", out, |caller, mem| {
+            let value = mem.read_bytes(caller, value, value_len)?;
+            Ok(caller.data().instance_env.delete_by_col_eq(table_id, col_id, &value)?)
+        })
+    }
+
+    /// Moves rows from the table `src_table_id` to the table `dst_table_id`,
+    /// deleting the concatenated rows in `(src_rows, src_rows_len)` and inserting those in `(dst_rows, dst_rows_len)`,
+    /// writing the number of rows deleted to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn move_rows(
+        caller: Caller<'_, Self>,
+        src_table_id: u32,
+        src_rows: u32,
+        src_rows_len: u32,
+        dst_table_id: u32,
+        dst_rows: u32,
+        dst_rows_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "move_rows", out, |caller, mem| {
+            let src_rows = mem.read_bytes(caller, src_rows, src_rows_len)?;
+            let dst_rows = mem.read_bytes(caller, dst_rows, dst_rows_len)?;
+            Ok(caller
+                .data()
+                .instance_env
+                .move_rows(src_table_id, &src_rows, dst_table_id, &dst_rows)?)
+        })
+    }
+
+    /// Deletes the row `(row, row_len)` from the table identified by `table_id`.
+    ///
+    /// A shim for modules built against the previous ABI, moving the row to nowhere.
+    #[tracing::instrument(skip_all)]
+    pub fn delete_value(caller: Caller<'_, Self>, table_id: u32, row: u32, row_len: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "delete_value", |caller, mem| {
+            let row = mem.read_bytes(caller, row, row_len)?;
+            caller.data().instance_env.move_rows(table_id, &row, table_id, &[])?;
+            Ok(())
+        })
+    }
+
+    /// Deletes all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` is within the range from `(start, start_len)` to `(end, end_len)`,
+    /// writing the number of rows deleted to the pointer `out`.
+    ///
+    /// A shim for modules built against the previous ABI.
+    #[tracing::instrument(skip_all)]
+    pub fn delete_range(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        start: u32,
+        start_len: u32,
+        end: u32,
+        end_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "delete_range", out, |caller, mem| {
+            let start = mem.read_bytes(caller, start, start_len)?;
+            let end = mem.read_bytes(caller, end, end_len)?;
+            Ok(caller
+                .data()
+                .instance_env
+                .delete_range(table_id, col_id, &start, &end)?)
+        })
+    }
+
+    /// Queries the `table_id` associated with the table named by the UTF-8 slice `(name, name_len)`,
+    /// writing it to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn get_table_id(caller: Caller<'_, Self>, name: u32, name_len: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "get_table_id", out, |caller, mem| {
+            let name = Self::read_string(caller, mem, name, name_len)?;
+            Ok(caller.data().instance_env.get_table_id(name)?)
+        })
+    }
+
+    /// Creates an index named by `(index_name, index_name_len)` of type `index_type`
+    /// on the columns `(col_ids, col_len)` of the table identified by `table_id`.
+    #[tracing::instrument(skip_all)]
+    pub fn create_index(
+        caller: Caller<'_, Self>,
+        index_name: u32,
+        index_name_len: u32,
+        table_id: u32,
+        index_type: u32,
+        col_ids: u32,
+        col_len: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, "create_index", |caller, mem| {
+            let index_name = Self::read_string(caller, mem, index_name, index_name_len)?;
+            let cols = mem.read_bytes(caller, col_ids, col_len)?;
+            caller
+                .data()
+                .instance_env
+                .create_index(index_name, table_id, index_type as u8, cols)?;
+            Ok(())
+        })
+    }
+
+    /// Finds all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` matches the byte string `(val, val_len)`,
+    /// writing the id of a buffer of the rows, bsatn encoded and concatenated, to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_eq(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        val: u32,
+        val_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_by_col_eq", out, |caller, mem| {
+            let value = mem.read_bytes(caller, val, val_len)?;
+            let data = caller.data().instance_env.iter_by_col_eq(table_id, col_id, &value)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Like [`Self::iter_by_col_eq`], but finds the rows whose string column
+    /// matches the full-text query `(query, query_len)`.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_match(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        query: u32,
+        query_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_by_col_match", out, |caller, mem| {
+            let query = Self::read_string(caller, mem, query, query_len)?;
+            let data = caller.data().instance_env.iter_by_col_match(table_id, col_id, &query)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Like [`Self::iter_by_col_eq`], but finds the rows whose point column
+    /// is within the box from `(min, min_len)` to `(max, max_len)`, edges included.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_box(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        min: u32,
+        min_len: u32,
+        max: u32,
+        max_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_by_col_box", out, |caller, mem| {
+            let min = mem.read_bytes(caller, min, min_len)?;
+            let max = mem.read_bytes(caller, max, max_len)?;
+            let data = caller
+                .data()
+                .instance_env
+                .iter_by_col_box(table_id, col_id, &min, &max)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Writes the energy left in the budget of the current reducer call to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn remaining_energy(caller: Caller<'_, Self>, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "remaining_energy", out, |caller, _mem| {
+            let points = get_remaining_points(&mut *caller);
+            let per_point = caller.data().instance_env.energy.pricing().per_instruction.max(1);
+            Ok(points.saturating_mul(per_point))
+        })
+    }
+
+    /// Writes the offset in the commit log of the current transaction to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn tx_offset(caller: Caller<'_, Self>, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "tx_offset", out, |caller, _mem| {
+            Ok(caller.data().instance_env.tx_offset()?)
+        })
+    }
+
+    /// Writes the version of the table identified by `table_id` to the pointer `out`.
+    ///
+    /// Returns an error if the table doesn't exist.
+    #[tracing::instrument(skip_all)]
+    pub fn table_version(caller: Caller<'_, Self>, table_id: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "table_version", out, |caller, _mem| {
+            Ok(caller.data().instance_env.table_version(table_id)?)
+        })
+    }
+
+    /// Writes the id of a buffer of the bsatn-encoded layout of the rows stored
+    /// in the table identified by `table_id` to the pointer `out`.
+    ///
+    /// Returns an error if the table doesn't exist.
+    #[tracing::instrument(skip_all)]
+    pub fn table_row_type(caller: Caller<'_, Self>, table_id: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "table_row_type", out, |caller, _mem| {
+            let data = caller.data().instance_env.table_row_type(table_id)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Stores the blob `(data, data_len)` under the key named by the UTF-8 slice `(key, key_len)`,
+    /// writing its hash to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_put(
+        caller: Caller<'_, Self>,
+        key: u32,
+        key_len: u32,
+        data: u32,
+        data_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt(caller, "blob_put", |caller, mem| {
+            let key = Self::read_string(caller, mem, key, key_len)?;
+            let data = mem.read_bytes(caller, data, data_len)?;
+            let hash = caller.data().instance_env.blob_put(&key, &data)?;
+            mem.set_bytes(caller, out, hash.as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Reads the blob stored under the key named by the UTF-8 slice `(key, key_len)` into a new buffer,
+    /// whose id is written to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_get(caller: Caller<'_, Self>, key: u32, key_len: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "blob_get", out, |caller, mem| {
+            let key = Self::read_string(caller, mem, key, key_len)?;
+            let data = caller.data().instance_env.blob_get(&key)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Deletes the blob stored under the key named by the UTF-8 slice `(key, key_len)`.
+    #[tracing::instrument(skip_all)]
+    pub fn blob_delete(caller: Caller<'_, Self>, key: u32, key_len: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "blob_delete", |caller, mem| {
+            let key = Self::read_string(caller, mem, key, key_len)?;
+            Ok(caller.data().instance_env.blob_delete(&key)?)
+        })
+    }
+
+    /// Stores the bytes of the slice `(data, data_len)` out of the rows that will reference them,
+    /// writing the id of a new buffer holding the encoded key those rows hold to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_put(caller: Caller<'_, Self>, data: u32, data_len: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "large_bytes_put", out, |caller, mem| {
+            let data = mem.read_bytes(caller, data, data_len)?;
+            let key = caller.data().instance_env.large_bytes_put(&data)?;
+            Ok(caller.data_mut().buffers.insert(key.into()))
+        })
+    }
+
+    /// Reads the bytes stored under the encoded key in the slice `(key, key_len)` into a new buffer,
+    /// whose id is written to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn large_bytes_get(caller: Caller<'_, Self>, key: u32, key_len: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "large_bytes_get", out, |caller, mem| {
+            let key = mem.read_bytes(caller, key, key_len)?;
+            let data = caller.data().instance_env.large_bytes_get(&key)?;
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
+    /// Takes a savepoint of the current transaction, writing its id to the pointer `out`.
+    #[tracing::instrument(skip_all)]
+    pub fn savepoint(caller: Caller<'_, Self>, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "savepoint", out, |caller, _mem| {
+            Ok(caller.data().instance_env.savepoint()?)
+        })
+    }
+
+    /// Undoes the changes made in the current transaction since the savepoint `id` was taken.
+    #[tracing::instrument(skip_all)]
+    pub fn rollback_to_savepoint(caller: Caller<'_, Self>, id: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "rollback_to_savepoint", |caller, _mem| {
+            Ok(caller.data().instance_env.rollback_to_savepoint(id)?)
+        })
+    }
+
+    /// Releases the savepoint `id`, keeping the changes made since.
+    #[tracing::instrument(skip_all)]
+    pub fn release_savepoint(caller: Caller<'_, Self>, id: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "release_savepoint", |caller, _mem| {
+            Ok(caller.data().instance_env.release_savepoint(id)?)
+        })
+    }
+
+    /// Start iteration on each row, as bytes, of the table identified by `table_id`,
+    /// writing the id of the iterator to the pointer `out`.
+    pub fn iter_start(caller: Caller<'_, Self>, table_id: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_start", out, |caller, _mem| {
+            let iter = caller.data().instance_env.iter(table_id);
+            // TODO: make it so the above iterator doesn't lock the database for its whole lifetime
+            let iter = iter.map_ok(Bytes::from).collect::<Vec<_>>().into_iter();
+            Ok(caller.data_mut().iters.insert(Box::new(iter)))
+        })
+    }
+
+    /// Like [`Self::iter_start`], but only over the rows matching `(filter, filter_len)`,
+    /// encoded in the embedded language defined by `spacetimedb_lib::filter::Expr`.
+    pub fn iter_start_filtered(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        filter: u32,
+        filter_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_start_filtered", out, |caller, mem| {
+            let filter = mem.read_bytes(caller, filter, filter_len)?;
+            let iter = caller.data().instance_env.iter_filtered(table_id, &filter)?;
+            // TODO: make it so the above iterator doesn't lock the database for its whole lifetime
+            let iter = iter.map(Bytes::from).map(Ok).collect::<Vec<_>>().into_iter();
+            Ok(caller.data_mut().iters.insert(Box::new(iter)))
+        })
+    }
+
+    /// Like [`Self::iter_start`], but with each row projected onto the columns in `(cols, cols_len)`,
+    /// so that only those columns are encoded, in that order.
+    pub fn iter_start_projected(
+        caller: Caller<'_, Self>,
+        table_id: u32,
+        cols: u32,
+        cols_len: u32,
+        out: u32,
+    ) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_start_projected", out, |caller, mem| {
+            let cols = mem.read_bytes(caller, cols, cols_len)?;
+            let iter = caller.data().instance_env.iter_projected(table_id, &cols)?;
+            let iter = iter.map(Bytes::from).map(Ok).collect::<Vec<_>>().into_iter();
+            Ok(caller.data_mut().iters.insert(Box::new(iter)))
+        })
+    }
+
+    /// Advances the iterator `iter_key`, writing the id of a buffer of the next row to the pointer `out`,
+    /// or an invalid buffer id if there are no rows left.
+    pub fn iter_next(caller: Caller<'_, Self>, iter_key: u32, out: u32) -> anyhow::Result<u32> {
+        Self::cvt_ret(caller, "iter_next", out, |caller, _mem| {
+            let data_mut = caller.data_mut();
+            let iter = data_mut
+                .iters
+                .get_mut(BufferIterIdx(iter_key))
+                .ok_or_else(|| anyhow!("no such iterator"))?;
+            match iter.next() {
+                Some(Ok(buf)) => Ok(data_mut.buffers.insert(buf)),
+                Some(Err(err)) => Err(err.into()),
+                None => Ok(BufferIdx::INVALID),
+            }
+        })
+    }
+
+    /// Drops the iterator `iter_key`.
+    pub fn iter_drop(caller: Caller<'_, Self>, iter_key: u32) -> anyhow::Result<u32> {
+        Self::cvt(caller, "iter_drop", |caller, _mem| {
+            caller
+                .data_mut()
+                .iters
+                .take(BufferIterIdx(iter_key))
+                .ok_or_else(|| anyhow!("no such iterator").into())
+                .map(drop)
+        })
+    }
+
+    /// Returns the length of the `buffer`.
+    pub fn buffer_len(caller: Caller<'_, Self>, buffer: u32) -> anyhow::Result<u32> {
+        caller
+            .data()
+            .buffers
+            .get(BufferIdx(buffer))
+            .map(|b| b.len() as u32)
+            .ok_or_else(|| anyhow!("no such buffer"))
+    }
+
+    /// Consumes the `buffer` and moves its contents into the slice `(ptr, len)`.
+    pub fn buffer_consume(mut caller: Caller<'_, Self>, buffer: u32, ptr: u32, len: u32) -> anyhow::Result<()> {
+        let buf = caller
+            .data_mut()
+            .buffers
+            .take(BufferIdx(buffer))
+            .ok_or_else(|| anyhow!("no such buffer"))?;
+        anyhow::ensure!(buf.len() == len as usize, "buffer is not of length {len}");
+        caller.data().mem().set_bytes(&mut caller, ptr, &buf)
+    }
+
+    /// Creates a buffer with the contents of the slice `(data, data_len)`, returning its id.
+    pub fn buffer_alloc(mut caller: Caller<'_, Self>, data: u32, data_len: u32) -> anyhow::Result<u32> {
+        let buf = caller.data().mem().read_bytes(&caller, data, data_len)?;
+        Ok(caller.data_mut().buffers.insert(buf.into()).0)
+    }
+}
+
+impl BacktraceProvider for StoreContext<'_, WasmInstanceEnv> {
+    fn capture(&self) -> Box<dyn ModuleBacktrace> {
+        Box::new(WasmBacktrace::capture(self))
+    }
+}
+
+impl ModuleBacktrace for WasmBacktrace {
+    fn frames(&self) -> Vec<BacktraceFrame<'_>> {
+        self.frames()
+            .iter()
+            .map(|f| BacktraceFrame {
+                module_name: f.module_name(),
+                func_name: f.func_name(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,324 @@
+use super::wasm_instance_env::WasmInstanceEnv;
+use super::{get_remaining_points, set_remaining_points, Mem, CALL_TIMEOUT_TICKS};
+use crate::host::instance_env::InstanceEnv;
+use crate::host::wasm_common::module_host_actor::{DescribeError, InitializationError};
+use crate::host::wasm_common::*;
+use crate::host::{EnergyQuanta, Timestamp};
+use bytes::Bytes;
+use wasmtime::{Instance, InstancePre, Linker, Module, Store, TypedFunc, WasmBacktrace, WasmParams};
+
+pub(super) fn log_traceback(func_type: &str, func: &str, e: &anyhow::Error) {
+    log::info!("{} \"{}\" runtime error: {}", func_type, func, e);
+    if let Some(bt) = e.downcast_ref::<WasmBacktrace>() {
+        let frames = bt.frames();
+        let frames_len = frames.len();
+        for (i, frame) in frames.iter().enumerate() {
+            log::info!(
+                "  Frame #{}: {:?}::{}",
+                frames_len - i,
+                frame.module_name(),
+                rustc_demangle::demangle(frame.func_name().unwrap_or("<func>"))
+            );
+        }
+    }
+}
+
+/// Arm the meters of a store for a call into the module,
+/// which is stopped once it runs through `budget` or [`CALL_TIMEOUT`](super::CALL_TIMEOUT).
+fn arm(store: &mut Store<WasmInstanceEnv>, budget: u64) {
+    set_remaining_points(&mut *store, budget);
+    store.set_epoch_deadline(CALL_TIMEOUT_TICKS);
+}
+
+pub struct WasmtimeModule {
+    module: Module,
+    linker: Linker<WasmInstanceEnv>,
+}
+
+impl WasmtimeModule {
+    pub(super) fn new(module: Module, linker: Linker<WasmInstanceEnv>) -> Self {
+        WasmtimeModule { module, linker }
+    }
+
+    pub const IMPLEMENTED_ABI: abi::VersionTuple = abi::VersionTuple::new(3, 16);
+
+    pub(super) fn link_imports(linker: &mut Linker<WasmInstanceEnv>) -> anyhow::Result<()> {
+        const _: () = assert!(WasmtimeModule::IMPLEMENTED_ABI.eq(spacetimedb_lib::MODULE_ABI_VERSION));
+        linker
+            .func_wrap("spacetime", "_schedule_reducer", WasmInstanceEnv::schedule_reducer)?
+            .func_wrap("spacetime", "_cancel_reducer", WasmInstanceEnv::cancel_reducer)?
+            .func_wrap("spacetime", "_emit_event", WasmInstanceEnv::emit_event)?
+            .func_wrap("spacetime", "_set_return_value", WasmInstanceEnv::set_return_value)?
+            .func_wrap("spacetime", "_send_message", WasmInstanceEnv::send_message)?
+            .func_wrap("spacetime", "_describe_reducer", WasmInstanceEnv::describe_reducer)?
+            .func_wrap("spacetime", "_delete_by_col_eq", WasmInstanceEnv::delete_by_col_eq)?
+            .func_wrap("spacetime", "_move_rows", WasmInstanceEnv::move_rows)?
+            .func_wrap("spacetime", "_insert", WasmInstanceEnv::insert)?
+            .func_wrap("spacetime", "_try_insert", WasmInstanceEnv::try_insert)?
+            .func_wrap("spacetime", "_get_table_id", WasmInstanceEnv::get_table_id)?
+            .func_wrap("spacetime", "_create_index", WasmInstanceEnv::create_index)?
+            .func_wrap("spacetime", "_iter_by_col_eq", WasmInstanceEnv::iter_by_col_eq)?
+            .func_wrap("spacetime", "_iter_by_col_match", WasmInstanceEnv::iter_by_col_match)?
+            .func_wrap("spacetime", "_iter_by_col_box", WasmInstanceEnv::iter_by_col_box)?
+            .func_wrap("spacetime", "_savepoint", WasmInstanceEnv::savepoint)?
+            .func_wrap("spacetime", "_remaining_energy", WasmInstanceEnv::remaining_energy)?
+            .func_wrap("spacetime", "_tx_offset", WasmInstanceEnv::tx_offset)?
+            .func_wrap("spacetime", "_table_version", WasmInstanceEnv::table_version)?
+            .func_wrap("spacetime", "_table_row_type", WasmInstanceEnv::table_row_type)?
+            .func_wrap("spacetime", "_blob_put", WasmInstanceEnv::blob_put)?
+            .func_wrap("spacetime", "_blob_get", WasmInstanceEnv::blob_get)?
+            .func_wrap("spacetime", "_blob_delete", WasmInstanceEnv::blob_delete)?
+            .func_wrap("spacetime", "_large_bytes_put", WasmInstanceEnv::large_bytes_put)?
+            .func_wrap("spacetime", "_large_bytes_get", WasmInstanceEnv::large_bytes_get)?
+            .func_wrap(
+                "spacetime",
+                "_rollback_to_savepoint",
+                WasmInstanceEnv::rollback_to_savepoint,
+            )?
+            .func_wrap("spacetime", "_release_savepoint", WasmInstanceEnv::release_savepoint)?
+            .func_wrap("spacetime", "_iter_start", WasmInstanceEnv::iter_start)?
+            .func_wrap(
+                "spacetime",
+                "_iter_start_filtered",
+                WasmInstanceEnv::iter_start_filtered,
+            )?
+            .func_wrap(
+                "spacetime",
+                "_iter_start_projected",
+                WasmInstanceEnv::iter_start_projected,
+            )?
+            .func_wrap("spacetime", "_iter_next", WasmInstanceEnv::iter_next)?
+            .func_wrap("spacetime", "_iter_drop", WasmInstanceEnv::iter_drop)?
+            .func_wrap("spacetime", "_console_log", WasmInstanceEnv::console_log)?
+            .func_wrap(
+                "spacetime",
+                "_console_log_structured",
+                WasmInstanceEnv::console_log_structured,
+            )?
+            .func_wrap("spacetime", "_buffer_len", WasmInstanceEnv::buffer_len)?
+            .func_wrap("spacetime", "_buffer_consume", WasmInstanceEnv::buffer_consume)?
+            .func_wrap("spacetime", "_buffer_alloc", WasmInstanceEnv::buffer_alloc)?;
+        Ok(())
+    }
+
+    /// Links the calls the previous major version of the ABI has and this one dropped,
+    /// in addition to those of [`Self::link_imports`], for a module built against [`abi::AbiSupport::Previous`].
+    pub(super) fn link_previous_abi_imports(linker: &mut Linker<WasmInstanceEnv>) -> anyhow::Result<()> {
+        linker
+            .func_wrap("spacetime", "_delete_value", WasmInstanceEnv::delete_value)?
+            .func_wrap("spacetime", "_delete_range", WasmInstanceEnv::delete_range)?;
+        Ok(())
+    }
+}
+
+impl module_host_actor::WasmModule for WasmtimeModule {
+    type Instance = WasmtimeInstance;
+    type InstancePre = WasmtimeInstancePre;
+
+    type ExternType = wasmtime::ExternType;
+
+    fn get_export(&self, s: &str) -> Option<Self::ExternType> {
+        self.module.get_export(s)
+    }
+
+    fn for_each_export<E>(&self, mut f: impl FnMut(&str, &Self::ExternType) -> Result<(), E>) -> Result<(), E> {
+        self.module.exports().try_for_each(|exp| f(exp.name(), &exp.ty()))
+    }
+
+    fn instantiate_pre(&self) -> Result<Self::InstancePre, InitializationError> {
+        let pre = self
+            .linker
+            .instantiate_pre(&self.module)
+            .map_err(InitializationError::Instantiation)?;
+        Ok(WasmtimeInstancePre { pre })
+    }
+}
+
+/// A module with its imports resolved, ready to be instantiated cheaply.
+pub struct WasmtimeInstancePre {
+    pre: InstancePre<WasmInstanceEnv>,
+}
+
+impl module_host_actor::WasmInstancePre for WasmtimeInstancePre {
+    type Instance = WasmtimeInstance;
+
+    fn instantiate(&self, env: InstanceEnv, func_names: &FuncNames) -> Result<Self::Instance, InitializationError> {
+        let env = WasmInstanceEnv {
+            instance_env: env,
+            mem: None,
+            buffers: Default::default(),
+            iters: Default::default(),
+        };
+        let mut store = Store::new(self.pre.module().engine(), env);
+
+        // Note: this budget is just for initializers
+        arm(&mut store, EnergyQuanta::DEFAULT_BUDGET.as_points());
+
+        let instance = self
+            .pre
+            .instantiate(&mut store)
+            .map_err(InitializationError::Instantiation)?;
+
+        let mem = Mem::extract(&instance, &mut store).map_err(InitializationError::Instantiation)?;
+        store.data_mut().mem = Some(mem);
+
+        for preinit in &func_names.preinits {
+            let func = instance.get_typed_func::<(), ()>(&mut store, preinit).unwrap();
+            func.call(&mut store, ())
+                .map_err(|err| InitializationError::RuntimeError {
+                    err,
+                    func: preinit.clone(),
+                })?;
+        }
+
+        if let Ok(init) = instance.get_typed_func::<(), u32>(&mut store, SETUP_DUNDER) {
+            match init.call(&mut store, ()).map(BufferIdx) {
+                Ok(errbuf) if errbuf.is_invalid() => {}
+                Ok(errbuf) => {
+                    let errbuf = store
+                        .data_mut()
+                        .buffers
+                        .take(errbuf)
+                        .unwrap_or_else(|| "unknown error".as_bytes().into());
+                    let errbuf = crate::util::string_from_utf8_lossy_owned(errbuf.into()).into();
+                    return Err(InitializationError::Setup(errbuf));
+                }
+                Err(err) => {
+                    return Err(InitializationError::RuntimeError {
+                        err,
+                        func: SETUP_DUNDER.to_owned(),
+                    });
+                }
+            }
+        }
+
+        Ok(WasmtimeInstance { store, instance })
+    }
+}
+
+pub struct WasmtimeInstance {
+    store: Store<WasmInstanceEnv>,
+    instance: Instance,
+}
+
+impl module_host_actor::WasmInstance for WasmtimeInstance {
+    fn extract_descriptions(&mut self) -> Result<Bytes, DescribeError> {
+        let store = &mut self.store;
+        let describer = self
+            .instance
+            .get_typed_func::<(), u32>(&mut *store, DESCRIBE_MODULE_DUNDER)
+            .map_err(|_| DescribeError::Signature)?;
+
+        let start = std::time::Instant::now();
+        log::trace!("Start describer \"{}\"...", DESCRIBE_MODULE_DUNDER);
+        arm(store, EnergyQuanta::DEFAULT_BUDGET.as_points());
+        let result = describer.call(&mut *store, ()).map(BufferIdx);
+        let duration = start.elapsed();
+        log::trace!(
+            "Describer \"{}\" ran: {} us",
+            DESCRIBE_MODULE_DUNDER,
+            duration.as_micros()
+        );
+        let buf = result.map_err(|err| {
+            log_traceback("describer", DESCRIBE_MODULE_DUNDER, &err);
+            DescribeError::RuntimeError(err)
+        })?;
+        let bytes = store.data_mut().buffers.take(buf).ok_or(DescribeError::BadBuffer)?;
+        store.data_mut().buffers.clear();
+        Ok(bytes)
+    }
+
+    fn instance_env(&self) -> &InstanceEnv {
+        &self.store.data().instance_env
+    }
+
+    type Trap = anyhow::Error;
+
+    fn call_reducer(
+        &mut self,
+        reducer_id: usize,
+        budget: EnergyQuanta,
+        sender: &[u8; 32],
+        timestamp: Timestamp,
+        arg_bytes: Bytes,
+    ) -> module_host_actor::ExecuteResult<Self::Trap> {
+        self.call_tx_function::<(u32, u32, u64, u32), 2>(
+            CALL_REDUCER_DUNDER,
+            budget,
+            [sender.to_vec().into(), arg_bytes],
+            |func, store, [sender, args]| func.call(store, (reducer_id as u32, sender.0, timestamp.0, args.0)),
+        )
+    }
+
+    fn call_connect_disconnect(
+        &mut self,
+        connect: bool,
+        budget: EnergyQuanta,
+        sender: &[u8; 32],
+        timestamp: Timestamp,
+    ) -> module_host_actor::ExecuteResult<Self::Trap> {
+        self.call_tx_function::<(u32, u64), 1>(
+            if connect {
+                IDENTITY_CONNECTED_DUNDER
+            } else {
+                IDENTITY_DISCONNECTED_DUNDER
+            },
+            budget,
+            [sender.to_vec().into()],
+            |func, store, [sender]| func.call(store, (sender.0, timestamp.0)),
+        )
+    }
+
+    fn log_traceback(func_type: &str, func: &str, trap: &Self::Trap) {
+        log_traceback(func_type, func, trap)
+    }
+}
+
+impl WasmtimeInstance {
+    fn call_tx_function<Args: WasmParams, const N_BUFS: usize>(
+        &mut self,
+        reducer_symbol: &str,
+        budget: EnergyQuanta,
+        bufs: [Bytes; N_BUFS],
+        call: impl FnOnce(TypedFunc<Args, u32>, &mut Store<WasmInstanceEnv>, [BufferIdx; N_BUFS]) -> anyhow::Result<u32>,
+    ) -> module_host_actor::ExecuteResult<anyhow::Error> {
+        let store = &mut self.store;
+        let budget = budget.as_points();
+        arm(store, budget);
+
+        let reduce = self
+            .instance
+            .get_typed_func::<Args, u32>(&mut *store, reducer_symbol)
+            .expect("invalid reducer");
+
+        let bufs = bufs.map(|data| store.data_mut().buffers.insert(data));
+
+        let start = std::time::Instant::now();
+        log::trace!("Start reducer \"{}\"...", reducer_symbol);
+        let result = call(reduce, store, bufs).and_then(|errbuf| {
+            let errbuf = BufferIdx(errbuf);
+            Ok(if errbuf.is_invalid() {
+                Ok(())
+            } else {
+                let errmsg = store
+                    .data_mut()
+                    .buffers
+                    .take(errbuf)
+                    .ok_or_else(|| anyhow::anyhow!("invalid buffer handle"))?;
+                Err(crate::util::string_from_utf8_lossy_owned(errmsg.into()).into())
+            })
+        });
+        store.data_mut().buffers.clear();
+        let duration = start.elapsed();
+        let remaining = get_remaining_points(&mut *store);
+        let energy = module_host_actor::EnergyStats {
+            used: EnergyQuanta::from_points(budget) - EnergyQuanta::from_points(remaining),
+            remaining: EnergyQuanta::from_points(remaining),
+        };
+        module_host_actor::ExecuteResult {
+            energy,
+            execution_duration: duration,
+            call_result: result,
+        }
+    }
+}
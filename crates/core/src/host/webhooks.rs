@@ -0,0 +1,309 @@
+//! Webhooks: POSTing the changes of each committed transaction of a database
+//! to the URLs configured for it, in commit order.
+//!
+//! Each webhook has its own delivery queue, so a slow or failing endpoint only holds up its own deliveries.
+//! A delivery that fails is retried with exponential backoff, and after [`MAX_ATTEMPTS`]
+//! it's given up on and recorded in `st_webhook_dead_letter`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::Serialize;
+use spacetimedb_lib::Identity;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::address::Address;
+use crate::control_db::ControlDb;
+use crate::db::datastore::system_tables::StWebhookDeadLetterRow;
+use crate::db::relational_db::RelationalDB;
+use crate::host::{ModuleHost, Timestamp};
+use crate::messages::control_db::Webhook;
+use crate::subscription::cdc::{RowChangeJson, TransactionChanges};
+
+/// The number of times a delivery is attempted before it's dead-lettered.
+pub const MAX_ATTEMPTS: u32 = 8;
+/// How long to wait before the first retry of a delivery, doubled for each retry after.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How long to wait for an endpoint to respond to a delivery.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to check whether a database without webhooks has been given some.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How many deliveries can wait on a webhook before new ones are dead-lettered right away.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// The header holding the hex HMAC-SHA256 of the body of a delivery, as `sha256=<hex>`,
+/// if the webhook has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Spacetime-Signature";
+/// The header holding the name of the webhook a delivery is for.
+pub const WEBHOOK_HEADER: &str = "X-Spacetime-Webhook";
+
+/// Where the host looks up the webhooks of a database.
+///
+/// It's consulted for every committed transaction, so changes apply from the next one,
+/// except that a database without webhooks only checks for new ones every few seconds.
+pub trait WebhookSource: Send + Sync + 'static {
+    fn webhooks(&self, database_address: &Address) -> Vec<Webhook>;
+}
+
+impl WebhookSource for ControlDb {
+    fn webhooks(&self, database_address: &Address) -> Vec<Webhook> {
+        self.get_webhooks(database_address).unwrap_or_else(|e| {
+            log::error!("Failed to get the webhooks of {}: {e}", database_address.to_hex());
+            vec![]
+        })
+    }
+}
+
+/// A source of no webhooks.
+pub struct NoWebhooks;
+
+impl WebhookSource for NoWebhooks {
+    fn webhooks(&self, _database_address: &Address) -> Vec<Webhook> {
+        vec![]
+    }
+}
+
+/// The body of a delivery.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    webhook: &'a str,
+    database: String,
+    tx_offset: Option<u64>,
+    timestamp: Timestamp,
+    reducer: &'a str,
+    sender: Identity,
+    changes: Vec<RowChangeJson<'a>>,
+}
+
+struct Delivery {
+    webhook: Webhook,
+    timestamp: Timestamp,
+    payload: String,
+}
+
+/// Returns whether `changes` are delivered to `webhook`.
+fn accepts(webhook: &Webhook, changes: &TransactionChanges) -> bool {
+    if webhook.reducers.is_empty() && webhook.tables.is_empty() {
+        return true;
+    }
+    webhook.reducers.contains(&changes.reducer)
+        || changes
+            .tables
+            .iter()
+            .any(|table| !table.changes.is_empty() && webhook.tables.contains(&table.table_name))
+}
+
+fn payload(database_address: &Address, webhook: &Webhook, changes: &TransactionChanges) -> String {
+    let filter = |table: &str| webhook.tables.is_empty() || webhook.tables.iter().any(|t| t == table);
+    let payload = WebhookPayload {
+        webhook: &webhook.name,
+        database: database_address.to_hex(),
+        tx_offset: changes.tx_offset,
+        timestamp: changes.timestamp,
+        reducer: &changes.reducer,
+        sender: changes.sender,
+        changes: changes.to_json(filter).collect(),
+    };
+    serde_json::to_string(&payload).unwrap()
+}
+
+/// The value of the [`SIGNATURE_HEADER`] of a delivery of `body` signed with `secret`.
+fn signature(secret: &str, body: &str) -> anyhow::Result<String> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    let hmac = signer.sign_oneshot_to_vec(body.as_bytes())?;
+    Ok(format!("sha256={}", hex::encode(hmac)))
+}
+
+/// Deliver the changes of the transactions `module` commits to the webhooks `source` has for the database,
+/// until the module exits.
+pub fn spawn_dispatcher(
+    module: ModuleHost,
+    database_address: Address,
+    relational_db: Arc<RelationalDB>,
+    source: Arc<dyn WebhookSource>,
+) {
+    tokio::spawn(async move {
+        tokio::select! {
+            () = dispatch(&module, database_address, relational_db, source) => {}
+            () = module.exited() => {}
+        }
+    });
+}
+
+async fn dispatch(
+    module: &ModuleHost,
+    database_address: Address,
+    relational_db: Arc<RelationalDB>,
+    source: Arc<dyn WebhookSource>,
+) {
+    let client = reqwest::Client::new();
+    loop {
+        // Collecting the changes of each transaction isn't free,
+        // so they're only subscribed to while the database has webhooks.
+        while source.webhooks(&database_address).is_empty() {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+        }
+        let mut changes_rx = module.subscription().subscribe_to_changes();
+        let mut queues: HashMap<String, mpsc::Sender<Delivery>> = HashMap::new();
+        loop {
+            let changes = match changes_rx.recv().await {
+                Ok(changes) => changes,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    log::warn!(
+                        "Webhooks of {} missed {n} transactions that were committed faster than they were dispatched",
+                        database_address.to_hex()
+                    );
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+
+            let webhooks = source.webhooks(&database_address);
+            if webhooks.is_empty() {
+                break;
+            }
+            queues.retain(|name, _| webhooks.iter().any(|webhook| &webhook.name == name));
+            for webhook in webhooks {
+                if !accepts(&webhook, &changes) {
+                    continue;
+                }
+                let delivery = Delivery {
+                    payload: payload(&database_address, &webhook, &changes),
+                    timestamp: changes.timestamp,
+                    webhook,
+                };
+                let queue = queues.entry(delivery.webhook.name.clone()).or_insert_with(|| {
+                    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                    tokio::spawn(deliver_queued(client.clone(), relational_db.clone(), rx));
+                    tx
+                });
+                if let Err(mpsc::error::TrySendError::Full(delivery)) = queue.try_send(delivery) {
+                    dead_letter(&relational_db, delivery, 0, "the delivery queue is full".into());
+                }
+            }
+        }
+    }
+}
+
+/// Deliver the deliveries of a webhook, one at a time, until its queue is dropped.
+async fn deliver_queued(client: reqwest::Client, relational_db: Arc<RelationalDB>, mut rx: mpsc::Receiver<Delivery>) {
+    while let Some(delivery) = rx.recv().await {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match deliver(&client, &delivery).await {
+                Ok(()) => break,
+                Err(e) => e,
+            };
+            if attempts == MAX_ATTEMPTS {
+                log::warn!(
+                    "Giving up on a delivery to webhook {} after {attempts} attempts: {error:#}",
+                    delivery.webhook.name
+                );
+                dead_letter(&relational_db, delivery, attempts, format!("{error:#}"));
+                break;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+async fn deliver(client: &reqwest::Client, delivery: &Delivery) -> anyhow::Result<()> {
+    let mut request = client
+        .post(&delivery.webhook.url)
+        .timeout(REQUEST_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(WEBHOOK_HEADER, &delivery.webhook.name);
+    if let Some(secret) = &delivery.webhook.secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, &delivery.payload)?);
+    }
+    let response = request.body(delivery.payload.clone()).send().await?;
+    let status = response.status();
+    anyhow::ensure!(status.is_success(), "the endpoint responded with {status}");
+    Ok(())
+}
+
+fn dead_letter(relational_db: &RelationalDB, delivery: Delivery, attempts: u32, error: String) {
+    relational_db.record_webhook_dead_letter(&StWebhookDeadLetterRow {
+        webhook_name: delivery.webhook.name,
+        url: delivery.webhook.url,
+        timestamp: delivery.timestamp.0,
+        attempts,
+        error,
+        payload: delivery.payload,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::cdc::{ChangeOp, RowChange, TableChanges};
+    use spacetimedb_sats::product;
+
+    fn webhook(reducers: &[&str], tables: &[&str]) -> Webhook {
+        Webhook {
+            name: "hook".into(),
+            url: "http://localhost/hook".into(),
+            reducers: reducers.iter().map(|&s| s.into()).collect(),
+            tables: tables.iter().map(|&s| s.into()).collect(),
+            secret: None,
+        }
+    }
+
+    fn changes() -> TransactionChanges {
+        let insert = |row| RowChange {
+            op: ChangeOp::Insert,
+            old_row: None,
+            new_row: Some(row),
+        };
+        TransactionChanges {
+            tx_offset: Some(7),
+            timestamp: Timestamp(1),
+            reducer: "place_order".into(),
+            sender: Identity::__dummy(),
+            tables: vec![
+                TableChanges {
+                    table_name: "Order".into(),
+                    changes: vec![insert(product!(1u32))],
+                },
+                TableChanges {
+                    table_name: "Stock".into(),
+                    changes: vec![insert(product!(2u32))],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn filters_by_reducer_or_table() {
+        let changes = changes();
+        assert!(accepts(&webhook(&[], &[]), &changes));
+        assert!(accepts(&webhook(&["place_order"], &[]), &changes));
+        assert!(accepts(&webhook(&["cancel_order"], &["Stock"]), &changes));
+        assert!(!accepts(&webhook(&["cancel_order"], &["Customer"]), &changes));
+
+        let address = Address::from_arr(&[0; 16]);
+        let json: serde_json::Value =
+            serde_json::from_str(&payload(&address, &webhook(&[], &["Stock"]), &changes)).unwrap();
+        assert_eq!(json["tx_offset"], 7);
+        assert_eq!(json["changes"].as_array().unwrap().len(), 1);
+        assert_eq!(json["changes"][0]["table"], "Stock");
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // The example of RFC 4231, test case 2.
+        assert_eq!(
+            signature("Jefe", "what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
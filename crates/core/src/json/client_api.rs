@@ -1,7 +1,9 @@
 use bytestring::ByteString;
 use serde::Serialize;
-use spacetimedb_lib::AlgebraicValue;
-use spacetimedb_lib::ProductType;
+use serde_json::Value;
+use spacetimedb_lib::sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_lib::sats::{self, WithTypespace};
+use spacetimedb_lib::{AlgebraicValue, ProductType, ProductValue};
 
 use serde_with::serde_as;
 
@@ -34,6 +36,10 @@ pub enum MessageJson {
     Event(EventJson),
     TransactionUpdate(TransactionUpdateJson),
     IdentityToken(IdentityTokenJson),
+    BatchCallResult(BatchCallResultJson),
+    OneOffQueryResult(OneOffQueryResultJson),
+    SubscriptionChunk(SubscriptionUpdateJson),
+    SubscriptionCaughtUp(SubscriptionCaughtUpJson),
 }
 
 impl MessageJson {
@@ -66,15 +72,32 @@ pub struct TableUpdateJson {
 pub struct TableRowOperationJson {
     pub op: String,
     pub row_pk: String,
+    /// For an update, only the values of the columns that changed.
     #[serde_as(as = "Vec<Sats>")]
     pub row: Vec<AlgebraicValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_row_pk: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column_mask: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SubscriptionUpdateJson {
     pub table_updates: Vec<TableUpdateJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_offset: Option<u64>,
+    /// Whether this ends the updates sent to a client that resumed its subscription.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub resumed: bool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionCaughtUpJson {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_offset: Option<u64>,
+}
+
+#[serde_as]
 #[derive(Debug, Clone, Serialize)]
 pub struct EventJson {
     pub timestamp: u64,
@@ -83,6 +106,19 @@ pub struct EventJson {
     pub function_call: FunctionCallJson,
     pub energy_quanta_used: i128,
     pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub emitted_events: Vec<EmittedEventJson>,
+    #[serde_as(as = "Option<Sats>")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub return_value: Option<AlgebraicValue>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct EmittedEventJson {
+    pub name: String,
+    #[serde_as(as = "Sats")]
+    pub data: AlgebraicValue,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -91,6 +127,18 @@ pub struct TransactionUpdateJson {
     pub subscription_update: SubscriptionUpdateJson,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct CallResultJson {
+    pub status: String, // committed, failed, out_of_energy
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchCallResultJson {
+    pub request_id: u32,
+    pub results: Vec<CallResultJson>,
+}
+
 #[serde_as]
 #[derive(Debug, Clone, Serialize)]
 pub struct StmtResultJson {
@@ -98,3 +146,108 @@ pub struct StmtResultJson {
     #[serde_as(as = "Vec<Vec<Sats>>")]
     pub rows: Vec<Vec<AlgebraicValue>>,
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OneOffQueryResultJson {
+    pub request_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub results: Vec<StmtResultJson>,
+}
+
+/// A column of a [`TypedStmtResultJson`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnJson {
+    pub name: String,
+    /// The SATS type of the column, e.g., `U32` or `Array<String>`.
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// The result of a SQL statement as plain JSON,
+/// for clients that can't interpret SATS-encoded rows with their schema.
+///
+/// Values are in the canonical JSON encoding of [`sats::json`],
+/// which clients that can interpret SATS may decode with `schema`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypedStmtResultJson {
+    pub columns: Vec<ColumnJson>,
+    pub schema: ProductType,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl TypedStmtResultJson {
+    pub fn new(schema: &ProductType, rows: impl IntoIterator<Item = ProductValue>) -> Self {
+        let columns = schema
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(i, col)| ColumnJson {
+                name: col.name.clone().unwrap_or_else(|| i.to_string()),
+                ty: fmt_algebraic_type(&col.algebraic_type).to_string(),
+            })
+            .collect();
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                schema
+                    .elements
+                    .iter()
+                    .zip(&row.elements)
+                    .map(|(col, value)| sats::json::to_value(WithTypespace::empty(&col.algebraic_type), value))
+                    .collect()
+            })
+            .collect();
+        Self {
+            columns,
+            schema: schema.clone(),
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use spacetimedb_lib::sats::product;
+    use spacetimedb_lib::AlgebraicType;
+
+    #[test]
+    fn test_typed_json() {
+        let schema = ProductType::from_iter([
+            ("id", AlgebraicType::U128),
+            ("name", AlgebraicType::option(AlgebraicType::String)),
+            ("scores", AlgebraicType::array(AlgebraicType::I32)),
+        ]);
+        let rows = vec![
+            product![
+                AlgebraicValue::U128(u128::MAX),
+                AlgebraicValue::OptionSome(AlgebraicValue::String("alice".into())),
+                AlgebraicValue::ArrayOf(vec![1, 2])
+            ],
+            product![
+                AlgebraicValue::U128(7),
+                AlgebraicValue::OptionNone(),
+                AlgebraicValue::ArrayOf(Vec::<i32>::new())
+            ],
+        ];
+        let result = TypedStmtResultJson::new(&schema, rows);
+
+        assert_eq!(
+            serde_json::to_value(&result.columns).unwrap(),
+            json!([
+                { "name": "id", "type": "U128" },
+                { "name": "name", "type": "(some: String | none: ())" },
+                { "name": "scores", "type": "Array<I32>" },
+            ])
+        );
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![json!(u128::MAX.to_string()), json!("alice"), json!([1, 2])],
+                vec![json!("7"), Value::Null, json!([])],
+            ]
+        );
+    }
+}
@@ -1,2 +1,3 @@
 pub mod client_api;
 pub mod control_db;
+pub mod module_schema;
@@ -0,0 +1,250 @@
+//! A JSON description of the schema of a published module,
+//! for codegen and documentation tools which don't link the SpacetimeDB crates.
+//!
+//! The layout of the document is stable within a [`MODULE_SCHEMA_VERSION`]:
+//! fields may be added, but none are removed or change meaning without bumping it.
+
+use serde::Serialize;
+use spacetimedb_lib::{AlgebraicType, IndexType, ReducerDef, TableDef};
+use spacetimedb_sats::{AlgebraicTypeRef, Typespace};
+
+use crate::host::module_host::{EntityDef, ModuleInfo};
+
+/// The version of the layout of [`ModuleSchemaJson`].
+pub const MODULE_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleSchemaJson {
+    pub version: u32,
+    pub module_hash: String,
+    /// Every type of the module's typespace, in the order of their refs.
+    pub types: Vec<TypeJson>,
+    /// The tables of the module, sorted by name.
+    pub tables: Vec<TableJson>,
+    /// The reducers of the module, in the order the module defines them.
+    pub reducers: Vec<ReducerJson>,
+    /// The events reducers can emit, sorted by name.
+    pub events: Vec<EventJson>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeJson {
+    /// The ref other types use to refer to this one, as `{ "Ref": <ref> }`.
+    #[serde(rename = "ref")]
+    pub type_ref: u32,
+    /// The name the module gave to the type, if any.
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: AlgebraicType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableJson {
+    pub name: String,
+    /// The ref of the product type of the table's rows.
+    pub type_ref: u32,
+    pub table_type: &'static str,
+    pub access: &'static str,
+    pub columns: Vec<ColumnJson>,
+    pub indexes: Vec<IndexJson>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnJson {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: AlgebraicType,
+    pub unique: bool,
+    pub autoinc: bool,
+    pub primary_key: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexJson {
+    pub name: String,
+    pub index_type: &'static str,
+    /// The names of the indexed columns.
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReducerJson {
+    pub name: String,
+    pub read_only: bool,
+    pub args: Vec<ArgJson>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArgJson {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: AlgebraicType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventJson {
+    pub name: String,
+    pub type_ref: u32,
+}
+
+impl ModuleSchemaJson {
+    pub fn new(info: &ModuleInfo) -> Self {
+        let types = info
+            .typespace
+            .types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                let type_ref = i as u32;
+                TypeJson {
+                    type_ref,
+                    name: info.type_aliases.get(&AlgebraicTypeRef(type_ref)).cloned(),
+                    ty: ty.clone(),
+                }
+            })
+            .collect();
+
+        let mut tables = info
+            .catalog
+            .values()
+            .filter_map(|entity| match entity {
+                EntityDef::Table(table) => Some(table_json(&info.typespace, table)),
+                EntityDef::Reducer(_) => None,
+            })
+            .collect::<Vec<_>>();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let reducers = info
+            .reducers
+            .values()
+            .map(|reducer| reducer_json(reducer, info.read_only_reducers.contains(&reducer.name)))
+            .collect();
+
+        let mut events = info
+            .event_types
+            .iter()
+            .map(|(name, ty)| EventJson {
+                name: name.clone(),
+                type_ref: ty.0,
+            })
+            .collect::<Vec<_>>();
+        events.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            version: MODULE_SCHEMA_VERSION,
+            module_hash: info.module_hash.to_hex(),
+            types,
+            tables,
+            reducers,
+            events,
+        }
+    }
+}
+
+fn table_json(typespace: &Typespace, table: &TableDef) -> TableJson {
+    let elements = typespace
+        .get(table.data)
+        .and_then(|ty| ty.as_product())
+        .map_or(&[][..], |row| &row.elements);
+    let col_name = |col_id: usize| {
+        elements
+            .get(col_id)
+            .and_then(|el| el.name.clone())
+            .unwrap_or_else(|| col_id.to_string())
+    };
+
+    let columns = elements
+        .iter()
+        .enumerate()
+        .map(|(col_id, el)| {
+            let attr = table.column_attrs.get(col_id).copied().unwrap_or_default();
+            ColumnJson {
+                name: col_name(col_id),
+                ty: el.algebraic_type.clone(),
+                unique: attr.is_unique(),
+                autoinc: attr.is_autoinc(),
+                primary_key: attr.is_primary(),
+            }
+        })
+        .collect();
+    let indexes = table
+        .indexes
+        .iter()
+        .map(|index| IndexJson {
+            name: index.name.clone(),
+            index_type: index_type_str(index.ty),
+            columns: index.col_ids.iter().map(|&col_id| col_name(col_id as usize)).collect(),
+        })
+        .collect();
+
+    TableJson {
+        name: table.name.clone(),
+        type_ref: table.data.0,
+        table_type: table.table_type.as_str(),
+        access: table.table_access.as_str(),
+        columns,
+        indexes,
+    }
+}
+
+fn reducer_json(reducer: &ReducerDef, read_only: bool) -> ReducerJson {
+    ReducerJson {
+        name: reducer.name.clone(),
+        read_only,
+        args: reducer
+            .args
+            .iter()
+            .map(|arg| ArgJson {
+                name: arg.name.clone(),
+                ty: arg.algebraic_type.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn index_type_str(ty: IndexType) -> &'static str {
+    match ty {
+        IndexType::BTree => "btree",
+        IndexType::Hash => "hash",
+        IndexType::FullText => "fulltext",
+        IndexType::Spatial => "spatial",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_lib::{ColumnIndexAttribute, IndexDef};
+    use spacetimedb_sats::{ProductType, ProductTypeElement};
+
+    #[test]
+    fn table_columns_and_indexes() {
+        let row = ProductType::new(vec![
+            ProductTypeElement::new_named(AlgebraicType::U64, "id"),
+            ProductTypeElement::new_named(AlgebraicType::String, "name"),
+        ]);
+        let typespace = Typespace::new(vec![AlgebraicType::Product(row)]);
+        let table = TableDef {
+            name: "Person".into(),
+            data: AlgebraicTypeRef(0),
+            column_attrs: vec![ColumnIndexAttribute::PrimaryKeyAuto, ColumnIndexAttribute::UnSet],
+            indexes: vec![IndexDef {
+                name: "name_idx".into(),
+                ty: IndexType::BTree,
+                col_ids: vec![1],
+            }],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+
+        let json = serde_json::to_value(table_json(&typespace, &table)).unwrap();
+        assert_eq!(json["columns"][0]["name"], "id");
+        assert_eq!(json["columns"][0]["primary_key"], true);
+        assert_eq!(json["columns"][0]["autoinc"], true);
+        assert_eq!(json["columns"][1]["unique"], false);
+        assert_eq!(json["indexes"][0]["index_type"], "btree");
+        assert_eq!(json["indexes"][0]["columns"], serde_json::json!(["name"]));
+        assert_eq!(json["access"], "public");
+    }
+}
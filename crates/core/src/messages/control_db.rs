@@ -20,6 +20,50 @@ pub struct EnergyBalance {
     pub balance: i128,
 }
 
+/// The prices, in energy, of the resources a reducer uses.
+///
+/// A database can be given a pricing for all of its reducers,
+/// and a pricing for specific reducers which takes precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, serde::Serialize, serde::Deserialize)]
+pub struct EnergyPricing {
+    /// The energy per point of wasm execution, as metered by the cost of each instruction.
+    /// Must be at least 1.
+    pub per_instruction: u64,
+    /// The energy per byte of the rows a reducer inserts.
+    pub per_byte_written: u64,
+    /// The energy per row a reducer reads from a table.
+    pub per_row_scanned: u64,
+}
+
+impl Default for EnergyPricing {
+    /// Prices only execution, at the metered cost of each instruction.
+    fn default() -> Self {
+        Self {
+            per_instruction: 1,
+            per_byte_written: 0,
+            per_row_scanned: 0,
+        }
+    }
+}
+
+/// A URL a database POSTs the changes of its committed transactions to.
+///
+/// A transaction is delivered if it was committed by one of `reducers`
+/// or changed one of `tables`, or if both filters are empty.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, serde::Serialize, serde::Deserialize)]
+pub struct Webhook {
+    /// Identifies the webhook among those of the database.
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub reducers: Vec<String>,
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// The key deliveries are signed with, using HMAC-SHA256, if any.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+}
+
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Database {
     pub id: u64,
@@ -68,4 +112,7 @@ pub struct NodeStatus {
 #[repr(i32)]
 pub enum HostType {
     Wasmer = 0,
+    Wasmtime = 1,
+    /// A module written in AssemblyScript, run on wasmtime.
+    AssemblyScript = 2,
 }
@@ -2,6 +2,7 @@ use spacetimedb_sats::de::Deserialize;
 use spacetimedb_sats::ser::Serialize;
 
 use crate::host::Timestamp;
+use crate::identity::Identity;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Insert {
@@ -62,6 +63,15 @@ pub struct CreateIndex {
     pub index_type: u32,
     pub col_ids: Vec<u32>,
 }
+/// A call to a reducer, recorded after the sys calls it made.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CallReducer {
+    pub reducer_name: String,
+    pub caller_identity: Identity,
+    pub timestamp: Timestamp,
+    /// The BSATN-encoded arguments of the call.
+    pub args: Vec<u8>,
+}
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InstanceEvent {
     pub event_start_epoch_micros: Timestamp,
@@ -81,4 +91,5 @@ pub enum InstanceEventType {
     GetTableId(GetTableId),
     Iter(Iter),
     CreateIndex(CreateIndex),
+    CallReducer(CallReducer),
 }
@@ -1,25 +1,33 @@
-use spacetimedb_lib::auth::{StAccess, StTableType};
+use spacetimedb_lib::auth::{StAccess, StRoleAccess, StTableType};
 use spacetimedb_lib::error::RelationError;
 use spacetimedb_lib::table::{ColumnDef, ProductTypeMeta};
-use spacetimedb_lib::ColumnIndexAttribute;
-use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductTypeElement};
+use spacetimedb_lib::{spatial, ColumnIndexAttribute, IndexType};
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductTypeElement, ProductValue};
 use sqlparser::ast::{
-    Assignment, BinaryOperator, ColumnDef as SqlColumnDef, ColumnOption, DataType, ExactNumberInfo, Expr as SqlExpr,
-    GeneratedAs, HiveDistributionStyle, Ident, JoinConstraint, JoinOperator, ObjectName, ObjectType, Query, Select,
-    SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value, Values,
+    Action, Assignment, BinaryOperator, ColumnDef as SqlColumnDef, ColumnOption, DataType, ExactNumberInfo,
+    Expr as SqlExpr, Function, FunctionArg, FunctionArgExpr, GeneratedAs, GrantObjects, HiveDistributionStyle, Ident,
+    JoinConstraint, JoinOperator, ObjectName, ObjectType, Offset, OrderByExpr as SqlOrderByExpr, Privileges, Query,
+    Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value, Values,
 };
 use sqlparser::dialect::PostgreSqlDialect;
-use sqlparser::parser::Parser;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Location, Token, Tokenizer};
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::system_tables::OWNER_ROLE;
 use crate::db::datastore::traits::{MutTxDatastore, TableId, TableSchema};
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
+use crate::identity::Identity;
+use crate::sql::copy::{read_rows, CopyFrom};
+use crate::sql::information_schema;
 use spacetimedb_lib::relation::{extract_table_field, FieldExpr, FieldName};
 use spacetimedb_vm::errors::ErrorVm;
-use spacetimedb_vm::expr::{ColumnOp, DbType, Expr};
-use spacetimedb_vm::operator::{OpCmp, OpLogic, OpQuery};
+use spacetimedb_vm::expr::{ColumnOp, DbType, Expr, OrderByExpr};
+use spacetimedb_vm::operator::{OpCmp, OpLogic, OpMath, OpQuery};
 use spacetimedb_vm::ops::parse::parse;
 
 /// Simplify to detect features of the syntax we don't support yet
@@ -121,6 +129,12 @@ impl Selection {
         x.clauses.push(cmp);
         x
     }
+
+    pub fn with_op(self, op: ColumnOp) -> Self {
+        let mut x = self;
+        x.clauses.push(op);
+        x
+    }
 }
 
 pub struct OnExpr {
@@ -144,11 +158,26 @@ pub struct FromField {
 pub struct From {
     pub root: TableSchema,
     pub join: Option<Vec<Join>>,
+    /// The rows of `root`, if it's one of the [information_schema] views rather than a table.
+    pub view_rows: Option<Vec<ProductValue>>,
 }
 
 impl From {
     pub fn new(root: TableSchema) -> Self {
-        Self { root, join: None }
+        Self {
+            root,
+            join: None,
+            view_rows: None,
+        }
+    }
+
+    /// Reads from a view, with the schema `root` and the rows `rows`, rather than from a table.
+    pub fn new_view(root: TableSchema, rows: Vec<ProductValue>) -> Self {
+        Self {
+            root,
+            join: None,
+            view_rows: Some(rows),
+        }
     }
 
     pub fn with_inner_join(self, rhs: TableSchema, on: OnExpr) -> Self {
@@ -232,6 +261,9 @@ pub enum SqlAst {
         from: From,
         project: Vec<Column>,
         selection: Option<Selection>,
+        order_by: Vec<OrderByExpr>,
+        offset: Option<u64>,
+        limit: Option<u64>,
     },
     Insert {
         table: TableSchema,
@@ -258,6 +290,36 @@ pub enum SqlAst {
         kind: DbType,
         table_access: StAccess,
     },
+    CreateIndex {
+        table_id: u32,
+        name: String,
+        col_id: u32,
+        is_unique: bool,
+        index_type: IndexType,
+        if_not_exists: bool,
+    },
+    Grant {
+        role: String,
+        identity: Identity,
+    },
+    Revoke {
+        role: String,
+        identity: Identity,
+    },
+    GrantAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    RevokeAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    Analyze {
+        table_ids: Vec<u32>,
+    },
+    Vacuum,
 }
 
 fn extract_field(table: &From, of: &SqlExpr) -> Result<Option<ProductTypeElement>, PlanError> {
@@ -271,6 +333,23 @@ fn extract_field(table: &From, of: &SqlExpr) -> Result<Option<ProductTypeElement
             let f = table.resolve_field(&col_name)?;
             Ok(Some(f.column.column))
         }
+        SqlExpr::Nested(x) => extract_field(table, x),
+        // The type of time arithmetic, so `expires_at - created_at > '1h'` parses `'1h'` as a duration.
+        SqlExpr::BinaryOp {
+            left,
+            op: op @ (BinaryOperator::Plus | BinaryOperator::Minus),
+            right,
+        } => {
+            let lhs = extract_field(table, left)?;
+            let rhs = extract_field(table, right)?;
+            Ok(match (op, lhs, rhs) {
+                // A timestamp minus a timestamp is a duration.
+                (BinaryOperator::Minus, _, Some(rhs)) if rhs.algebraic_type == AlgebraicType::U64 => {
+                    Some(ProductTypeElement::new(AlgebraicType::I64, rhs.name))
+                }
+                (_, lhs, rhs) => lhs.or(rhs),
+            })
+        }
         _ => Ok(None),
     }
 }
@@ -299,6 +378,184 @@ fn infer_number(field: Option<&ProductTypeElement>, value: &str, is_long: bool)
     }
 }
 
+/// Parses `value` as the name of a variant of the C-like enum in `field`, like in `status = 'Active'`.
+///
+/// Returns `None` if `field` is not a C-like enum, so the value is kept as a plain string.
+fn infer_variant(field: Option<&ProductTypeElement>, value: &str) -> Result<Option<AlgebraicValue>, PlanError> {
+    let Some(field) = field else {
+        return Ok(None);
+    };
+    match &field.algebraic_type {
+        AlgebraicType::Sum(ty) if ty.is_simple_enum() => {
+            match ty.variants.iter().position(|variant| variant.has_name(value)) {
+                Some(tag) => Ok(Some(AlgebraicValue::sum(tag as u8, AlgebraicValue::UNIT))),
+                None => Err(PlanError::Unstructured(format!(
+                    "Unknown variant `{value}` for enum field `{}`",
+                    field.name.as_deref().unwrap_or("?")
+                ))),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parses `value` as an RFC 3339 timestamp, like in `created_at > '2023-08-01T12:00:00Z'`,
+/// when `field` is a `u64` column, as timestamps are stored as micro seconds since the UNIX epoch.
+///
+/// Returns `None` if `field` is not a `u64` column or `value` is not a timestamp,
+/// so the value is kept as a plain string.
+fn infer_timestamp(field: Option<&ProductTypeElement>, value: &str) -> Option<AlgebraicValue> {
+    if field?.algebraic_type != AlgebraicType::U64 {
+        return None;
+    }
+    let time = humantime::parse_rfc3339_weak(value).ok()?;
+    let micros = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_micros();
+    micros.try_into().ok().map(AlgebraicValue::U64)
+}
+
+/// Parses `value` as a duration, like in `last_used + '1h 30m'`,
+/// when `field` is an `i64` column, as durations are stored as micro seconds.
+///
+/// Returns `None` if `field` is not an `i64` column or `value` is not a duration,
+/// so the value is kept as a plain string.
+fn infer_duration(field: Option<&ProductTypeElement>, value: &str) -> Option<AlgebraicValue> {
+    if field?.algebraic_type != AlgebraicType::I64 {
+        return None;
+    }
+    let duration = humantime::parse_duration(value).ok()?;
+    duration.as_micros().try_into().ok().map(AlgebraicValue::I64)
+}
+
+/// Resolves the argument `arg` of the function `fun`, that must be a field.
+fn compile_function_field(table: &From, fun: &str, arg: &SqlExpr) -> Result<FromField, PlanError> {
+    match arg {
+        SqlExpr::Identifier(name) => table.resolve_field(&name.value),
+        SqlExpr::CompoundIdentifier(ident) => table.resolve_field(&compound_ident(ident)),
+        x => Err(PlanError::Unsupported {
+            feature: format!("`{fun}` expects a field, but got `{x}`"),
+        }),
+    }
+}
+
+/// Compiles a function call.
+///
+/// For now, only `variant_of(field)`, `array_contains(field, value)`, `match(field, 'query')`
+/// and `in_box(field, min_x, min_y, max_x, max_y)` are supported.
+fn compile_function(table: &From, f: Function) -> Result<ColumnOp, PlanError> {
+    let Function {
+        name,
+        args,
+        over,
+        distinct,
+        special: _,
+        order_by,
+    } = f;
+    unsupported!("Function", over, distinct, order_by);
+
+    let fun = name.to_string().to_lowercase();
+    let args: Vec<_> = args
+        .into_iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => Ok(arg),
+            x => Err(PlanError::Unsupported {
+                feature: format!("Function argument `{x}` in `{fun}`"),
+            }),
+        })
+        .collect::<Result<_, _>>()?;
+
+    match (fun.as_str(), args.as_slice()) {
+        ("variant_of", [arg]) => {
+            let field = compile_function_field(table, &fun, arg)?;
+            let ty = &field.column.column.algebraic_type;
+            if !ty.is_sum() {
+                return Err(PlanError::Unstructured(format!(
+                    "`{fun}` expects a field of sum type, but `{}` is `{}`",
+                    field.field,
+                    fmt_algebraic_type(ty)
+                )));
+            }
+            Ok(ColumnOp::VariantOf(field.field))
+        }
+        ("array_contains", [array, value]) => {
+            let field = compile_function_field(table, &fun, array)?;
+            let ty = &field.column.column.algebraic_type;
+            let Some(elem_ty) = ty.as_builtin().and_then(|ty| ty.as_array()).map(|ty| &*ty.elem_ty) else {
+                return Err(PlanError::Unstructured(format!(
+                    "`{fun}` expects a field of array type, but `{}` is `{}`",
+                    field.field,
+                    fmt_algebraic_type(ty)
+                )));
+            };
+            // The value gets the type of the elements, so `array_contains(scores, 1)` compares `1` as its type.
+            let elem = ProductTypeElement::new(elem_ty.clone(), field.column.column.name.clone());
+            let value = compile_expr_field(table, Some(&elem), value.clone())?;
+
+            Ok(ColumnOp::Contains {
+                array: field.field,
+                value,
+            })
+        }
+        ("match", [text, query]) => {
+            let field = compile_function_field(table, &fun, text)?;
+            let ty = &field.column.column.algebraic_type;
+            if *ty != AlgebraicType::String {
+                return Err(PlanError::Unstructured(format!(
+                    "`{fun}` expects a field of string type, but `{}` is `{}`",
+                    field.field,
+                    fmt_algebraic_type(ty)
+                )));
+            }
+            let query = match query {
+                SqlExpr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => s.clone(),
+                x => {
+                    return Err(PlanError::Unsupported {
+                        feature: format!("`{fun}` expects a string query, but got `{x}`"),
+                    })
+                }
+            };
+
+            Ok(ColumnOp::Match {
+                field: field.field,
+                query,
+            })
+        }
+        ("in_box", [point, min_x, min_y, max_x, max_y]) => {
+            let field = compile_function_field(table, &fun, point)?;
+            let ty = &field.column.column.algebraic_type;
+            let Some(ty) = ty.as_product().filter(|_| spatial::is_point(ty)) else {
+                return Err(PlanError::Unstructured(format!(
+                    "`{fun}` expects a field with two numbers, but `{}` is `{}`",
+                    field.field,
+                    fmt_algebraic_type(ty)
+                )));
+            };
+            // The coordinates get the types of the point's fields, so `in_box(pos, 0, 0, 1.5, 1.5)` works for floats.
+            let coord = |coord: &SqlExpr, elem: &ProductTypeElement| match compile_expr_field(
+                table,
+                Some(elem),
+                coord.clone(),
+            )? {
+                FieldExpr::Value(x) => Ok(x),
+                FieldExpr::Name(x) => Err(PlanError::Unsupported {
+                    feature: format!("`{fun}` expects numbers for the box, but got `{x}`"),
+                }),
+            };
+            let (x, y) = (&ty.elements[0], &ty.elements[1]);
+            let min = product![coord(min_x, x)?, coord(min_y, y)?].into();
+            let max = product![coord(max_x, x)?, coord(max_y, y)?].into();
+
+            Ok(ColumnOp::InBox {
+                field: field.field,
+                min,
+                max,
+            })
+        }
+        _ => Err(PlanError::Unsupported {
+            feature: format!("Function `{name}` with {} argument(s)", args.len()),
+        }),
+    }
+}
+
 /// Compiles a [SqlExpr] expression into a [ColumnOp]
 fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlExpr) -> Result<ColumnOp, PlanError> {
     Ok(ColumnOp::Field(match of {
@@ -309,8 +566,12 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
         }
         SqlExpr::Value(x) => FieldExpr::Value(match x {
             Value::Number(value, is_long) => infer_number(field, &value, is_long)?,
-            Value::SingleQuotedString(s) => AlgebraicValue::String(s),
-            Value::DoubleQuotedString(s) => AlgebraicValue::String(s),
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => match infer_variant(field, &s)? {
+                Some(variant) => variant,
+                None => infer_timestamp(field, &s)
+                    .or_else(|| infer_duration(field, &s))
+                    .unwrap_or(AlgebraicValue::String(s)),
+            },
             Value::Boolean(x) => AlgebraicValue::Bool(x),
             Value::Null => AlgebraicValue::OptionNone(),
             x => {
@@ -319,6 +580,20 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
                 })
             }
         }),
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::Plus,
+            right,
+        } => {
+            return compile_math(table, OpMath::Add, left, right);
+        }
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::Minus,
+            right,
+        } => {
+            return compile_math(table, OpMath::Minus, left, right);
+        }
         SqlExpr::BinaryOp { left, op, right } => {
             let (op, lhs, rhs) = compile_bin_op(table, op, left, right)?;
 
@@ -327,6 +602,9 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
         SqlExpr::Nested(x) => {
             return compile_expr_value(table, field, *x);
         }
+        SqlExpr::Function(f) => {
+            return compile_function(table, f);
+        }
         x => {
             return Err(PlanError::Unsupported {
                 feature: format!("Unsupported expression: {x}"),
@@ -396,6 +674,27 @@ fn compile_bin_op(
     Ok((op, lhs, rhs))
 }
 
+/// Compiles the time arithmetic `lhs + rhs` or `lhs - rhs`, like `expires_at - created_at`.
+///
+/// When `lhs` is a timestamp, a value in `rhs` is parsed as a duration, like in `last_used + '1h'`.
+fn compile_math(
+    table: &From,
+    op: OpMath,
+    lhs: Box<sqlparser::ast::Expr>,
+    rhs: Box<sqlparser::ast::Expr>,
+) -> Result<ColumnOp, PlanError> {
+    let field_lhs = extract_field(table, &lhs)?;
+    let field_rhs = extract_field(table, &rhs)?;
+    let duration = field_lhs.as_ref().and_then(|field| match field.algebraic_type {
+        AlgebraicType::U64 => Some(ProductTypeElement::new(AlgebraicType::I64, field.name.clone())),
+        _ => None,
+    });
+    let lhs = compile_expr_value(table, field_rhs.as_ref(), *lhs)?;
+    let rhs = compile_expr_value(table, duration.as_ref().or(field_lhs.as_ref()), *rhs)?;
+
+    Ok(ColumnOp::math(op, lhs, rhs))
+}
+
 fn _compile_where(table: &From, filter: SqlExpr, selection: Selection) -> Result<Option<Selection>, PlanError> {
     match filter {
         SqlExpr::BinaryOp { left, op, right } => {
@@ -404,6 +703,7 @@ fn _compile_where(table: &From, filter: SqlExpr, selection: Selection) -> Result
             Ok(Some(selection.with_cmp(op, lhs, rhs)))
         }
         SqlExpr::Nested(x) => _compile_where(table, *x, selection),
+        SqlExpr::Function(f) => Ok(Some(selection.with_op(compile_function(table, f)?))),
         x => Err(PlanError::Unsupported {
             feature: format!("Unsupported in WHERE: {x}."),
         }),
@@ -450,8 +750,11 @@ fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Res
     };
 
     let t = compile_table_factor(root_table.relation.clone())?;
-    let base = find_table(db, tx, t)?;
-    let mut base = From::new(base);
+    let view = information_schema::find_view(db, tx, &t.name).map_err(|e| PlanError::DatabaseInternal(Box::new(e)))?;
+    let mut base = match view {
+        Some((schema, rows)) => From::new_view(schema, rows),
+        None => From::new(find_table(db, tx, t)?),
+    };
 
     for join in &root_table.joins {
         match &join.join_operator {
@@ -464,6 +767,15 @@ fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Res
                         let expr = compile_expr_value(&base, None, x.clone())?;
                         match expr {
                             ColumnOp::Field(_) => {}
+                            x @ (ColumnOp::VariantOf(_)
+                            | ColumnOp::Contains { .. }
+                            | ColumnOp::Match { .. }
+                            | ColumnOp::InBox { .. }
+                            | ColumnOp::Math { .. }) => {
+                                return Err(PlanError::Unsupported {
+                                    feature: format!("Can't use {x} as JOIN clause"),
+                                });
+                            }
                             ColumnOp::Cmp { op, lhs, rhs } => {
                                 let op = match op {
                                     OpQuery::Cmp(op) => op,
@@ -554,8 +866,46 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
     }
 }
 
-/// Compiles the `SELECT ...` clause
-fn compile_select(db: &RelationalDB, tx: &MutTxId, select: Select) -> Result<SqlAst, PlanError> {
+/// Compiles the `ORDER BY ...` clause
+fn compile_order_by(from: &From, order_by: Vec<SqlOrderByExpr>) -> Result<Vec<OrderByExpr>, PlanError> {
+    let mut cols = Vec::with_capacity(order_by.len());
+    for col in order_by {
+        unsupported!("ORDER BY", col.nulls_first);
+        let field = match col.expr {
+            SqlExpr::Identifier(ident) => from.resolve_field(&ident.value)?.field,
+            SqlExpr::CompoundIdentifier(ident) => from.resolve_field(&compound_ident(&ident))?.field,
+            x => {
+                return Err(PlanError::Unsupported {
+                    feature: format!("ORDER BY the expression {x}"),
+                })
+            }
+        };
+        cols.push(OrderByExpr::new(field, col.asc == Some(false)));
+    }
+    Ok(cols)
+}
+
+/// Compiles the count of rows of a `LIMIT` or `OFFSET` clause, which must be a non-negative integer.
+fn compile_row_count(clause: &str, expr: SqlExpr) -> Result<u64, PlanError> {
+    match expr {
+        SqlExpr::Value(Value::Number(value, _)) => value.parse().map_err(|_| PlanError::Unsupported {
+            feature: format!("{clause} {value}"),
+        }),
+        x => Err(PlanError::Unsupported {
+            feature: format!("{clause} {x}"),
+        }),
+    }
+}
+
+/// Compiles the `SELECT ...` clause, along with the `ORDER BY`, `OFFSET` and `LIMIT` of its query
+fn compile_select(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    select: Select,
+    order_by: Vec<SqlOrderByExpr>,
+    offset: Option<Offset>,
+    limit: Option<SqlExpr>,
+) -> Result<SqlAst, PlanError> {
     let from = compile_from(db, tx, &select.from)?;
     // SELECT ...
     let mut project = Vec::new();
@@ -565,25 +915,23 @@ fn compile_select(db: &RelationalDB, tx: &MutTxId, select: Select) -> Result<Sql
     }
 
     let selection = compile_where(&from, select.selection)?;
+    let order_by = compile_order_by(&from, order_by)?;
+    let offset = offset.map(|x| compile_row_count("OFFSET", x.value)).transpose()?;
+    let limit = limit.map(|x| compile_row_count("LIMIT", x)).transpose()?;
 
     Ok(SqlAst::Select {
         from,
         project,
         selection,
+        order_by,
+        offset,
+        limit,
     })
 }
 
 /// Compiles any `query` clause (currently only `SELECT...`)
 fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst, PlanError> {
-    unsupported!(
-        "SELECT",
-        query.order_by,
-        query.fetch,
-        query.limit,
-        query.offset,
-        query.locks,
-        query.with
-    );
+    unsupported!("SELECT", query.fetch, query.locks, query.with);
 
     match *query.body {
         SetExpr::Select(select) => {
@@ -598,7 +946,7 @@ fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst
                 select.sort_by
             );
 
-            compile_select(db, tx, *select)
+            compile_select(db, tx, *select, query.order_by, query.offset, query.limit)
         }
         SetExpr::Query(_) => Err(PlanError::Unsupported {
             feature: "Query".into(),
@@ -844,6 +1192,71 @@ fn compile_create_table(table: Table, cols: Vec<SqlColumnDef>) -> Result<SqlAst,
     })
 }
 
+/// Compiles the `CREATE [UNIQUE] INDEX <name> ON <table> [USING <type>] (<column>)` clause
+///
+/// An index is on a single column, and `<type>` is `btree` (the default), `fulltext` or `spatial`.
+fn compile_create_index(
+    table: TableSchema,
+    name: ObjectName,
+    using: Option<Ident>,
+    columns: Vec<SqlOrderByExpr>,
+    is_unique: bool,
+    if_not_exists: bool,
+) -> Result<SqlAst, PlanError> {
+    let index_type = match using {
+        None => IndexType::BTree,
+        Some(using) if using.value.eq_ignore_ascii_case("btree") => IndexType::BTree,
+        Some(using) if using.value.eq_ignore_ascii_case("fulltext") => IndexType::FullText,
+        Some(using) if using.value.eq_ignore_ascii_case("spatial") => IndexType::Spatial,
+        Some(using) => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Index USING {using}"),
+            })
+        }
+    };
+    if is_unique && index_type != IndexType::BTree {
+        return Err(PlanError::Unsupported {
+            feature: format!("UNIQUE {index_type:?} index"),
+        });
+    }
+
+    let column = match &columns[..] {
+        [column] => column,
+        _ => {
+            return Err(PlanError::Unsupported {
+                feature: "Index on several columns".into(),
+            })
+        }
+    };
+    unsupported!("CREATE INDEX", column.asc, column.nulls_first);
+    let col_name = match &column.expr {
+        SqlExpr::Identifier(ident) => &ident.value,
+        x => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Index on the expression {x}"),
+            })
+        }
+    };
+    let col_id = table
+        .columns
+        .iter()
+        .find(|col| &col.col_name == col_name)
+        .ok_or_else(|| PlanError::UnknownField {
+            field: FieldName::named(&table.table_name, col_name),
+            tables: vec![table.table_name.clone()],
+        })?
+        .col_id;
+
+    Ok(SqlAst::CreateIndex {
+        table_id: table.table_id,
+        name: name.to_string(),
+        col_id,
+        is_unique,
+        index_type,
+        if_not_exists,
+    })
+}
+
 /// Compiles the `DROP ...` clause
 fn compile_drop(name: &ObjectName, kind: ObjectType) -> Result<SqlAst, PlanError> {
     let kind = match kind {
@@ -864,6 +1277,82 @@ fn compile_drop(name: &ObjectName, kind: ObjectType) -> Result<SqlAst, PlanError
     })
 }
 
+/// Compiles the tables, roles & access of a `GRANT ... ON ... TO ...` or `REVOKE ... ON ... FROM ...` clause
+///
+/// `SELECT` maps to [StRoleAccess::Read], while `INSERT`, `UPDATE` & `DELETE` map to [StRoleAccess::Write].
+/// A grant raises the roles to the highest of the privileges, while a revoke lowers them below the lowest.
+fn compile_table_access(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    privileges: Privileges,
+    objects: GrantObjects,
+    grantees: Vec<Ident>,
+    is_grant: bool,
+) -> Result<(Vec<u32>, Vec<String>, StRoleAccess), PlanError> {
+    let levels = match privileges {
+        Privileges::All { .. } => vec![StRoleAccess::Read, StRoleAccess::Write],
+        Privileges::Actions(actions) => actions
+            .into_iter()
+            .map(|action| match action {
+                Action::Select { columns: None } => Ok(StRoleAccess::Read),
+                Action::Insert { columns: None } | Action::Update { columns: None } | Action::Delete => {
+                    Ok(StRoleAccess::Write)
+                }
+                x => Err(PlanError::Unsupported {
+                    feature: format!("Privilege {x}"),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let access = if is_grant {
+        levels.into_iter().max()
+    } else {
+        levels.into_iter().min()
+    }
+    .ok_or_else(|| PlanError::Unstructured("Missing privileges.".into()))?;
+
+    let table_ids = match objects {
+        GrantObjects::Tables(tables) => tables
+            .into_iter()
+            .map(|name| find_table(db, tx, Table::new(name)).map(|t| t.table_id))
+            .collect::<Result<Vec<_>, _>>()?,
+        x => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Privileges on {x}"),
+            })
+        }
+    };
+
+    let roles = grantees
+        .into_iter()
+        .map(|role| {
+            if role.value == OWNER_ROLE {
+                Err(PlanError::Unstructured(format!(
+                    "The access of the `{OWNER_ROLE}` role can't be changed"
+                )))
+            } else {
+                Ok(role.value)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((table_ids, roles, access))
+}
+
+/// Compiles the table of an `ANALYZE [<table>]` statement, which is every user table if it's omitted
+fn compile_analyze(db: &RelationalDB, tx: &MutTxId, table: Option<String>) -> Result<SqlAst, PlanError> {
+    let table_ids = match table {
+        Some(name) => vec![find_table(db, tx, Table { name })?.table_id],
+        None => db
+            .get_all_tables(tx)?
+            .into_iter()
+            .filter(|t| t.table_type == StTableType::User)
+            .map(|t| t.table_id)
+            .collect(),
+    };
+    Ok(SqlAst::Analyze { table_ids })
+}
+
 /// Compiles a `SQL` clause
 fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> Result<SqlAst, PlanError> {
     match statement {
@@ -1002,6 +1491,17 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
             let table = Table::new(name);
             compile_create_table(table, columns)
         }
+        Statement::CreateIndex {
+            name,
+            table_name,
+            using,
+            columns,
+            unique,
+            if_not_exists,
+        } => {
+            let table = find_table(db, tx, Table::new(table_name))?;
+            compile_create_index(table, name, using, columns, unique, if_not_exists)
+        }
         Statement::Drop {
             object_type,
             if_exists,
@@ -1026,23 +1526,437 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
             };
             compile_drop(name, object_type)
         }
+        Statement::Grant {
+            privileges,
+            objects,
+            grantees,
+            with_grant_option,
+            granted_by,
+        } => {
+            unsupported!("GRANT", with_grant_option, granted_by);
+
+            let (table_ids, roles, access) = compile_table_access(db, tx, privileges, objects, grantees, true)?;
+            Ok(SqlAst::GrantAccess {
+                table_ids,
+                roles,
+                access,
+            })
+        }
+        Statement::Revoke {
+            privileges,
+            objects,
+            grantees,
+            granted_by,
+            cascade,
+        } => {
+            unsupported!("REVOKE", granted_by, cascade);
+
+            let (table_ids, roles, access) = compile_table_access(db, tx, privileges, objects, grantees, false)?;
+            Ok(SqlAst::RevokeAccess {
+                table_ids,
+                roles,
+                access,
+            })
+        }
+        // The rows are only split off the statement by [parse_transactions].
+        copy @ Statement::Copy { .. } => compile_copy(db, tx, copy, ""),
         x => Err(PlanError::Unsupported {
             feature: format!("Syntax {x}"),
         }),
     }
 }
 
-/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [PostgreSqlDialect]
-pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<SqlAst>, DBError> {
+/// Compiles a `COPY ... FROM STDIN` into an `INSERT` of the rows given along with it as `data`,
+/// in the format of the statement.
+fn compile_copy(db: &RelationalDB, tx: &MutTxId, statement: Statement, data: &str) -> Result<SqlAst, PlanError> {
+    let copy = CopyFrom::from_statement(statement)?;
+    let table = find_table(
+        db,
+        tx,
+        Table {
+            name: copy.table.clone(),
+        },
+    )?;
+    let values = read_rows(&table, &copy, data.as_bytes())?;
+    let columns = table
+        .columns
+        .iter()
+        .map(|col| FieldName::named(&table.table_name, &col.col_name))
+        .collect();
+    Ok(SqlAst::Insert { table, columns, values })
+}
+
+/// Parses a `sql` string into its statements using a SQL parser with [PostgreSqlDialect]
+pub(crate) fn parse_sql(sql_text: &str) -> Result<Vec<Statement>, DBError> {
     let dialect = PostgreSqlDialect {};
-    let ast = Parser::parse_sql(&dialect, sql_text).map_err(|error| DBError::SqlParser {
+    Parser::parse_sql(&dialect, sql_text).map_err(|error| DBError::SqlParser {
+        sql: sql_text.to_string(),
+        error,
+    })
+}
+
+/// A statement of a `sql` string.
+pub(crate) enum SqlStatement {
+    /// A statement parsed by [Parser].
+    Parsed(Statement),
+    /// `GRANT <role> TO '<identity>'`, which [Parser] doesn't support.
+    Grant { role: String, identity: Identity },
+    /// `REVOKE <role> FROM '<identity>'`, which [Parser] doesn't support.
+    Revoke { role: String, identity: Identity },
+    /// `ANALYZE [<table>]`, which [Parser] only supports as `ANALYZE TABLE <table>` with Hive's options.
+    Analyze { table: Option<String> },
+    /// `VACUUM`, which [Parser] doesn't support.
+    Vacuum,
+    /// A `COPY ... FROM STDIN` parsed by [Parser], with the rows that follow it,
+    /// which [Parser] would only read in the text format.
+    Copy { statement: Statement, data: String },
+}
+
+/// What [strip_unsupported] split off a statement of a `sql` string.
+enum Stripped {
+    /// Nothing, the whole statement is left to [Parser].
+    Nothing,
+    /// The `AS OF <tx_offset>` clause ending the statement.
+    AsOf(u64),
+    /// The whole statement, a `GRANT` or `REVOKE` of a role, an `ANALYZE` or a `VACUUM`.
+    Statement(SqlStatement),
+    /// The rows following a `COPY ... FROM STDIN`, up to the line `\.`, the statement being left to [Parser].
+    CopyData(String),
+}
+
+/// Splits what [Parser] doesn't support off the statements of a `sql` string:
+/// the `AS OF <tx_offset>` clauses ending them, the `GRANT` and `REVOKE` statements of roles,
+/// the `ANALYZE` and `VACUUM` statements, and the rows following a `COPY ... FROM STDIN`.
+///
+/// Returns the `sql` without them, and what was split off each of its statements.
+fn strip_unsupported(sql_text: &str) -> Result<(String, Vec<Stripped>), DBError> {
+    let plan_err = |error| DBError::Plan {
+        sql: sql_text.to_string(),
+        error,
+    };
+    let is_keyword = |token: &Token, keyword: &str| match token {
+        Token::Word(w) => w.quote_style.is_none() && w.value.eq_ignore_ascii_case(keyword),
+        _ => false,
+    };
+
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, sql_text)
+        .tokenize_with_location()
+        .map_err(|error| DBError::SqlParser {
+            sql: sql_text.to_string(),
+            error: ParserError::TokenizerError(error.to_string()),
+        })?;
+
+    // The byte offset in `sql_text` of a token's location, whose column counts chars.
+    let line_starts = std::iter::once(0)
+        .chain(sql_text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect::<Vec<_>>();
+    let byte_offset = |location: &Location| {
+        let line_start = line_starts[location.line as usize - 1];
+        let line = &sql_text[line_start..];
+        line_start
+            + line
+                .char_indices()
+                .nth(location.column as usize - 1)
+                .map_or(line.len(), |(i, _)| i)
+    };
+
+    let mut stripped = String::with_capacity(sql_text.len());
+    let mut copied_to = 0;
+    let mut split_off = Vec::new();
+    let mut statement_start = 0;
+    for i in 0..=tokens.len() {
+        if i < statement_start || (i < tokens.len() && tokens[i].token != Token::SemiColon) {
+            continue;
+        }
+        let body_start = statement_start;
+        let body = &tokens[body_start..i];
+        statement_start = i + 1;
+        // Cuts the tokens `from..=to` of `body` out of the `sql`.
+        let mut strip = |from: usize, to: usize| {
+            let cut_start = byte_offset(&body[from].location);
+            let cut_end = tokens
+                .get(body_start + to + 1)
+                .map_or(sql_text.len(), |t| byte_offset(&t.location));
+            stripped.push_str(&sql_text[copied_to..cut_start]);
+            copied_to = cut_end;
+        };
+
+        let words = (0..body.len())
+            .filter(|&j| !matches!(body[j].token, Token::Whitespace(_)))
+            .collect::<Vec<_>>();
+        match words[..] {
+            // Empty statements are skipped by the parser.
+            [] => {}
+            [verb, role, prep, id]
+                if (is_keyword(&body[verb].token, "GRANT") && is_keyword(&body[prep].token, "TO"))
+                    || (is_keyword(&body[verb].token, "REVOKE") && is_keyword(&body[prep].token, "FROM")) =>
+            {
+                let role = match &body[role].token {
+                    Token::Word(w) if w.value == OWNER_ROLE => {
+                        return Err(plan_err(PlanError::Unstructured(format!(
+                            "The `{OWNER_ROLE}` role is held by the owner of the database only"
+                        ))))
+                    }
+                    Token::Word(w) => w.value.clone(),
+                    token => {
+                        return Err(plan_err(PlanError::Unstructured(format!(
+                            "Expected a role, found `{token}`"
+                        ))))
+                    }
+                };
+                let identity = match &body[id].token {
+                    Token::SingleQuotedString(hex) => Identity::from_hex(hex)
+                        .map_err(|_| plan_err(PlanError::Unstructured(format!("Invalid identity `{hex}`"))))?,
+                    token => {
+                        return Err(plan_err(PlanError::Unstructured(format!(
+                            "Expected a quoted identity, found `{token}`"
+                        ))))
+                    }
+                };
+                split_off.push(Stripped::Statement(if is_keyword(&body[verb].token, "GRANT") {
+                    SqlStatement::Grant { role, identity }
+                } else {
+                    SqlStatement::Revoke { role, identity }
+                }));
+                // Leaves an empty statement, which the parser skips.
+                strip(verb, id);
+            }
+            [verb, ref table @ ..] if table.len() <= 1 && is_keyword(&body[verb].token, "ANALYZE") => {
+                let table = match table.first().map(|&table| &body[table].token) {
+                    None => None,
+                    Some(Token::Word(w)) => Some(w.value.clone()),
+                    Some(token) => {
+                        return Err(plan_err(PlanError::Unstructured(format!(
+                            "Expected a table after `ANALYZE`, found `{token}`"
+                        ))))
+                    }
+                };
+                split_off.push(Stripped::Statement(SqlStatement::Analyze { table }));
+                strip(verb, *words.last().unwrap());
+            }
+            [verb] if is_keyword(&body[verb].token, "VACUUM") => {
+                split_off.push(Stripped::Statement(SqlStatement::Vacuum));
+                strip(verb, verb);
+            }
+            [verb, ..]
+                if i < tokens.len()
+                    && is_keyword(&body[verb].token, "COPY")
+                    && words.iter().any(|&j| is_keyword(&body[j].token, "STDIN")) =>
+            {
+                // The rows start on the line after the `;` and end at the line `\.`, or at the end of the `sql`.
+                let end = (i + 1..tokens.len().saturating_sub(1))
+                    .find(|&j| tokens[j].token == Token::Backslash && tokens[j + 1].token == Token::Period);
+                let data_start = tokens.get(i + 1).map_or(sql_text.len(), |t| byte_offset(&t.location));
+                let data_end = end.map_or(sql_text.len(), |j| byte_offset(&tokens[j].location));
+                let data = &sql_text[data_start..data_end];
+                let data = match data.split_once('\n') {
+                    Some((rest_of_line, data)) if rest_of_line.trim().is_empty() => data,
+                    _ => data,
+                };
+                split_off.push(Stripped::CopyData(data.to_string()));
+                // Leaves the statement ended by `\.`, so that the parser goes on to the statements after it.
+                stripped.push_str(&sql_text[copied_to..data_start]);
+                stripped.push_str("\\.;");
+                statement_start = end.map_or(tokens.len() + 1, |j| j + 2);
+                copied_to = tokens
+                    .get(statement_start)
+                    .map_or(sql_text.len(), |t| byte_offset(&t.location));
+            }
+            [.., as_, of, offset] if is_keyword(&body[as_].token, "AS") && is_keyword(&body[of].token, "OF") => {
+                let tx_offset = match &body[offset].token {
+                    Token::Number(n, false) => n
+                        .parse()
+                        .map_err(|_| plan_err(PlanError::Unstructured(format!("Invalid tx offset `{n}`"))))?,
+                    Token::SingleQuotedString(_) => {
+                        return Err(plan_err(PlanError::Unsupported {
+                            feature: "AS OF a timestamp, as the commit log doesn't record when transactions committed"
+                                .into(),
+                        }))
+                    }
+                    token => {
+                        return Err(plan_err(PlanError::Unstructured(format!(
+                            "Expected a tx offset after `AS OF`, found `{token}`"
+                        ))))
+                    }
+                };
+                split_off.push(Stripped::AsOf(tx_offset));
+                strip(as_, offset);
+            }
+            _ => split_off.push(Stripped::Nothing),
+        }
+    }
+    stripped.push_str(&sql_text[copied_to..]);
+    Ok((stripped, split_off))
+}
+
+/// The statements of a `sql` string that run in the same transaction.
+pub(crate) struct SqlTx {
+    pub(crate) statements: Vec<SqlStatement>,
+    /// Whether the transaction ends with `ROLLBACK`, so its changes are discarded.
+    pub(crate) rollback: bool,
+    /// For a query ending in `AS OF <tx_offset>`, the number of transactions of the commit log
+    /// after which the state it queries was in.
+    pub(crate) as_of: Option<u64>,
+}
+
+/// Splits the statements of a `sql` string into transactions at `BEGIN`, `COMMIT` and `ROLLBACK`.
+///
+/// The statements between `BEGIN` and `COMMIT` (or `ROLLBACK`) run in a transaction of their own,
+/// and so do the consecutive statements outside of such a block.
+/// A `sql` string without `BEGIN` is then a single transaction.
+///
+/// A query ending in `AS OF <tx_offset>` is a transaction of its own, and can't be in a block.
+/// So is a `CREATE INDEX` outside of a block, for its index to be built without holding the lock.
+pub(crate) fn parse_transactions(sql_text: &str) -> Result<Vec<SqlTx>, DBError> {
+    let plan_err = |error| DBError::Plan {
         sql: sql_text.to_string(),
         error,
-    })?;
+    };
+
+    let (stripped, split_off) = strip_unsupported(sql_text)?;
+    let parsed = parse_sql(&stripped)?;
+    if parsed.len()
+        != split_off
+            .iter()
+            .filter(|x| !matches!(x, Stripped::Statement(_)))
+            .count()
+    {
+        return Err(plan_err(PlanError::Unstructured(
+            "Could not match `AS OF` clauses to statements".into(),
+        )));
+    }
+    let mut parsed = parsed.into_iter();
 
     let mut results = Vec::new();
-    for statement in ast {
-        let plan_result = compile_statement(db, tx, statement);
+    let mut statements = Vec::new();
+    // Whether we are between `BEGIN` and `COMMIT`.
+    let mut in_block = false;
+    for split_off in split_off {
+        let (statement, as_of) = match split_off {
+            Stripped::Statement(statement) => {
+                statements.push(statement);
+                continue;
+            }
+            Stripped::AsOf(tx_offset) => (parsed.next().unwrap(), Some(tx_offset)),
+            Stripped::Nothing => (parsed.next().unwrap(), None),
+            Stripped::CopyData(data) => {
+                let statement = parsed.next().unwrap();
+                statements.push(SqlStatement::Copy { statement, data });
+                continue;
+            }
+        };
+        if as_of.is_some() {
+            if !matches!(statement, Statement::Query(_)) {
+                return Err(plan_err(PlanError::Unsupported {
+                    feature: format!("AS OF on `{statement}`, which isn't a query"),
+                }));
+            }
+            if in_block {
+                return Err(plan_err(PlanError::Unsupported {
+                    feature: "AS OF inside of a `BEGIN` block".into(),
+                }));
+            }
+            if !statements.is_empty() {
+                results.push(SqlTx {
+                    statements: std::mem::take(&mut statements),
+                    rollback: false,
+                    as_of: None,
+                });
+            }
+            results.push(SqlTx {
+                statements: vec![SqlStatement::Parsed(statement)],
+                rollback: false,
+                as_of,
+            });
+            continue;
+        }
+        match statement {
+            Statement::StartTransaction { modes, .. } => {
+                if !modes.is_empty() {
+                    return Err(plan_err(PlanError::Unsupported {
+                        feature: "BEGIN with transaction modes".into(),
+                    }));
+                }
+                if in_block {
+                    return Err(plan_err(PlanError::Unstructured("Nested `BEGIN`".into())));
+                }
+                if !statements.is_empty() {
+                    results.push(SqlTx {
+                        statements: std::mem::take(&mut statements),
+                        rollback: false,
+                        as_of: None,
+                    });
+                }
+                in_block = true;
+            }
+            Statement::Commit { .. } | Statement::Rollback { .. } if !in_block => {
+                return Err(plan_err(PlanError::Unstructured(format!(
+                    "`{statement}` without `BEGIN`"
+                ))));
+            }
+            Statement::Commit { chain } | Statement::Rollback { chain } => {
+                if chain {
+                    return Err(plan_err(PlanError::Unsupported {
+                        feature: format!("{statement}"),
+                    }));
+                }
+                results.push(SqlTx {
+                    statements: std::mem::take(&mut statements),
+                    rollback: matches!(statement, Statement::Rollback { .. }),
+                    as_of: None,
+                });
+                in_block = false;
+            }
+            statement @ Statement::CreateIndex { .. } if !in_block => {
+                if !statements.is_empty() {
+                    results.push(SqlTx {
+                        statements: std::mem::take(&mut statements),
+                        rollback: false,
+                        as_of: None,
+                    });
+                }
+                results.push(SqlTx {
+                    statements: vec![SqlStatement::Parsed(statement)],
+                    rollback: false,
+                    as_of: None,
+                });
+            }
+            statement => statements.push(SqlStatement::Parsed(statement)),
+        }
+    }
+    if in_block {
+        return Err(plan_err(PlanError::Unstructured(
+            "`BEGIN` without `COMMIT` or `ROLLBACK`".into(),
+        )));
+    }
+    if !statements.is_empty() {
+        results.push(SqlTx {
+            statements,
+            rollback: false,
+            as_of: None,
+        });
+    }
+    Ok(results)
+}
+
+/// Compiles the `statements` of a `sql` string into a `Vec<SqlAst>`
+pub(crate) fn compile_statements(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    statements: Vec<SqlStatement>,
+) -> Result<Vec<SqlAst>, DBError> {
+    let mut results = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let plan_result = match statement {
+            SqlStatement::Parsed(statement) => compile_statement(db, tx, statement),
+            SqlStatement::Grant { role, identity } => Ok(SqlAst::Grant { role, identity }),
+            SqlStatement::Revoke { role, identity } => Ok(SqlAst::Revoke { role, identity }),
+            SqlStatement::Analyze { table } => compile_analyze(db, tx, table),
+            SqlStatement::Vacuum => Ok(SqlAst::Vacuum),
+            SqlStatement::Copy { statement, data } => compile_copy(db, tx, statement, &data),
+        };
         let query = match plan_result {
             Ok(plan) => plan,
             Err(error) => {
@@ -1056,3 +1970,9 @@ pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) ->
     }
     Ok(results)
 }
+
+/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [PostgreSqlDialect]
+pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<SqlAst>, DBError> {
+    let statements = parse_sql(sql_text)?.into_iter().map(SqlStatement::Parsed).collect();
+    compile_statements(db, tx, sql_text, statements)
+}
@@ -4,19 +4,33 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::datastore::traits::TableSchema;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
-use crate::sql::ast::{compile_to_ast, Column, From, Join, Selection, SqlAst};
+use crate::sql::ast::{compile_statements, compile_to_ast, Column, From, Join, Selection, SqlAst, SqlStatement};
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::relation::{self, DbTable, FieldExpr, FieldName, Header};
 use spacetimedb_lib::table::ProductTypeMeta;
 use spacetimedb_sats::ProductType;
-use spacetimedb_vm::dsl::{db_table, db_table_raw, query};
-use spacetimedb_vm::expr::{ColumnOp, CrudExpr, DbType, Expr, QueryExpr, SourceExpr};
+use spacetimedb_vm::dsl::{db_table, db_table_raw, mem_table, query};
+use spacetimedb_vm::expr::{ColumnOp, CrudExpr, DbType, Expr, OrderByExpr, QueryExpr, SourceExpr};
 use spacetimedb_vm::operator::OpCmp;
 
 /// Compile the `SQL` expression into a `ast`
 pub fn compile_sql(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<CrudExpr>, DBError> {
     let ast = compile_to_ast(db, tx, sql_text)?;
+    compile_ast(sql_text, ast)
+}
+
+/// Compile the `statements` of a [SqlTx](crate::sql::ast::SqlTx) into a `ast`
+pub(crate) fn compile_sql_statements(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    statements: Vec<SqlStatement>,
+) -> Result<Vec<CrudExpr>, DBError> {
+    let ast = compile_statements(db, tx, sql_text, statements)?;
+    compile_ast(sql_text, ast)
+}
 
+fn compile_ast(sql_text: &str, ast: Vec<SqlAst>) -> Result<Vec<CrudExpr>, DBError> {
     let mut results = Vec::with_capacity(ast.len());
 
     for sql in ast {
@@ -49,8 +63,23 @@ fn check_field(table: &From, field: &FieldExpr) -> Result<(), PlanError> {
 }
 
 fn check_field_column(table: &From, field: &ColumnOp) -> Result<(), PlanError> {
-    if let ColumnOp::Field(field) = field {
-        check_field(table, field)?;
+    match field {
+        ColumnOp::Field(field) => check_field(table, field)?,
+        ColumnOp::VariantOf(field) => {
+            table.resolve_field(&field.to_string())?;
+        }
+        ColumnOp::Contains { array, value } => {
+            table.resolve_field(&array.to_string())?;
+            check_field(table, value)?;
+        }
+        ColumnOp::Match { field, .. } | ColumnOp::InBox { field, .. } => {
+            table.resolve_field(&field.to_string())?;
+        }
+        ColumnOp::Math { lhs, rhs, .. } => {
+            check_field_column(table, lhs)?;
+            check_field_column(table, rhs)?;
+        }
+        ColumnOp::Cmp { .. } => {}
     }
     Ok(())
 }
@@ -59,6 +88,11 @@ fn check_field_column(table: &From, field: &ColumnOp) -> Result<(), PlanError> {
 fn check_cmp_expr(table: &From, expr: &ColumnOp) -> Result<(), PlanError> {
     match expr {
         ColumnOp::Field(field) => check_field(table, field)?,
+        ColumnOp::VariantOf(_)
+        | ColumnOp::Contains { .. }
+        | ColumnOp::Match { .. }
+        | ColumnOp::InBox { .. }
+        | ColumnOp::Math { .. } => check_field_column(table, expr)?,
         ColumnOp::Cmp { op: _, lhs, rhs } => {
             check_field_column(table, lhs)?;
             check_field_column(table, rhs)?;
@@ -81,7 +115,14 @@ fn compile_where(q: QueryExpr, table: &From, filter: Selection) -> Result<QueryE
 }
 
 /// Compiles a `SELECT ...` clause
-fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection>) -> Result<QueryExpr, PlanError> {
+fn compile_select(
+    mut table: From,
+    project: Vec<Column>,
+    selection: Option<Selection>,
+    order_by: Vec<OrderByExpr>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+) -> Result<QueryExpr, PlanError> {
     let mut not_found = Vec::with_capacity(project.len());
     let mut col_ids = Vec::new();
     //Match columns to their tables...
@@ -112,13 +153,16 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
         });
     }
 
-    let mut q = query(db_table_raw(
-        ProductType::from(&table.root),
-        &table.root.table_name,
-        table.root.table_id,
-        table.root.table_type,
-        table.root.table_access,
-    ));
+    let mut q = match table.view_rows.take() {
+        Some(rows) => query(mem_table(Header::from(&table.root), rows)),
+        None => query(db_table_raw(
+            ProductType::from(&table.root),
+            &table.root.table_name,
+            table.root.table_id,
+            table.root.table_type,
+            table.root.table_access,
+        )),
+    };
 
     if let Some(ref joins) = table.join {
         for join in joins {
@@ -138,6 +182,13 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
     if let Some(filter) = selection {
         q = compile_where(q, &table, filter)?;
     }
+    q = q.with_order_by(&order_by);
+    if let Some(offset) = offset {
+        q = q.with_offset(offset);
+    }
+    if let Some(limit) = limit {
+        q = q.with_limit(limit);
+    }
     //Is important to project at the end, so joins, filters see fields that are not projected
     q = q.with_project(&col_ids);
 
@@ -252,7 +303,10 @@ fn compile_statement(statement: SqlAst) -> Result<CrudExpr, PlanError> {
             from,
             project,
             selection,
-        } => CrudExpr::Query(compile_select(from, project, selection)?),
+            order_by,
+            offset,
+            limit,
+        } => CrudExpr::Query(compile_select(from, project, selection, order_by, offset, limit)?),
         SqlAst::Insert { table, columns, values } => compile_insert(table, columns, values)?,
         SqlAst::Update {
             table,
@@ -271,6 +325,43 @@ fn compile_statement(statement: SqlAst) -> Result<CrudExpr, PlanError> {
             kind,
             table_access,
         } => compile_drop(name, kind, table_access)?,
+        SqlAst::CreateIndex {
+            table_id,
+            name,
+            col_id,
+            is_unique,
+            index_type,
+            if_not_exists,
+        } => CrudExpr::CreateIndex {
+            table_id,
+            name,
+            col_id,
+            is_unique,
+            index_type,
+            if_not_exists,
+        },
+        SqlAst::Grant { role, identity } => CrudExpr::Grant { role, identity },
+        SqlAst::Revoke { role, identity } => CrudExpr::Revoke { role, identity },
+        SqlAst::GrantAccess {
+            table_ids,
+            roles,
+            access,
+        } => CrudExpr::GrantAccess {
+            table_ids,
+            roles,
+            access,
+        },
+        SqlAst::RevokeAccess {
+            table_ids,
+            roles,
+            access,
+        } => CrudExpr::RevokeAccess {
+            table_ids,
+            roles,
+            access,
+        },
+        SqlAst::Analyze { table_ids } => CrudExpr::Analyze { table_ids },
+        SqlAst::Vacuum => CrudExpr::Vacuum,
     };
 
     Ok(q)
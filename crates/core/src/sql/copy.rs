@@ -0,0 +1,547 @@
+//! `COPY <table> [(<column>, ...)] FROM STDIN [WITH] [(FORMAT csv | json [, HEADER [<bool>]])]`,
+//! which bulk-inserts the rows read from a stream rather than given in the statement.
+//!
+//! The rows are inserted in transactions of [`BATCH_ROWS`], checking the constraints of the table
+//! and the access of the caller to it as `INSERT` does, but without planning a statement per row.
+//! Through the `/sql` route, the rows instead follow the `;` ending the statement, up to a line `\.`,
+//! and are inserted in the transaction of the statement.
+//! As they're tokenized with the statements around them, they can't have an unmatched quote there.
+//!
+//! Values are in the canonical JSON encoding of [`spacetimedb_sats::json`]:
+//!
+//! - With `FORMAT csv`, the default, each record of the CSV is a row, whose fields are its columns in order.
+//!   A field is the JSON of its value, except that strings and byte arrays are given without quotes,
+//!   and that an empty field without quotes is `null`, i.e., `none` for an option.
+//!   With `HEADER`, the first record is skipped.
+//! - With `FORMAT json`, each line that isn't blank is a row,
+//!   as a JSON object of its columns by name or an array of them in order.
+
+use std::io::BufRead;
+
+use serde_json::Value;
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::relation::FieldExpr;
+use spacetimedb_sats::json::{self, JsonError};
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue, BuiltinType, ProductType, ProductTypeElement, WithTypespace};
+use spacetimedb_vm::expr::{CrudExpr, SourceExpr};
+use sqlparser::ast::{CopyOption, CopySource, CopyTarget, Statement};
+
+use crate::database_instance_context_controller::DatabaseInstanceContextController;
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::TableSchema;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, DatabaseError, PlanError};
+use crate::sql::ast::parse_sql;
+use crate::sql::execute::execute_sql;
+
+/// Rows inserted per transaction.
+const BATCH_ROWS: usize = 4096;
+
+/// The encoding of the rows of a `COPY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// CSV, as specified by RFC 4180, starting with a header record to skip if `header` is set.
+    Csv { header: bool },
+    /// A JSON object or array per line.
+    Json,
+}
+
+/// A parsed `COPY ... FROM STDIN` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyFrom {
+    pub table: String,
+    /// The columns of the rows in the order they're given, or `None` for all of them in the table's order.
+    pub columns: Option<Vec<String>>,
+    pub format: CopyFormat,
+}
+
+/// A `COPY` that failed after its first `copied` rows were committed.
+#[derive(thiserror::Error, Debug)]
+#[error("{error}, after {copied} rows were copied")]
+pub struct CopyError {
+    pub copied: u64,
+    pub error: DBError,
+}
+
+impl CopyFrom {
+    /// Returns the `COPY ... FROM STDIN` parsed as `statement`, checking it only has the options supported.
+    ///
+    /// The rows given to [Parser](sqlparser::parser::Parser) after the statement are rejected,
+    /// as it reads them in the text format, rather than the format of the statement.
+    pub(crate) fn from_statement(statement: Statement) -> Result<Self, PlanError> {
+        let Statement::Copy {
+            source,
+            to,
+            target,
+            options,
+            legacy_options,
+            values,
+        } = statement
+        else {
+            return Err(PlanError::Unstructured("Expected a `COPY ... FROM STDIN`".into()));
+        };
+        let (table_name, columns) = match source {
+            CopySource::Table { table_name, columns } if !to => (table_name, columns),
+            _ => {
+                return Err(PlanError::Unsupported {
+                    feature: "COPY TO, rather than an export of a query from the `/sql` route".into(),
+                })
+            }
+        };
+        if target != CopyTarget::Stdin {
+            return Err(PlanError::Unsupported {
+                feature: "COPY FROM anything but STDIN".into(),
+            });
+        }
+        if !legacy_options.is_empty() {
+            return Err(PlanError::Unsupported {
+                feature: "COPY with options outside of parentheses".into(),
+            });
+        }
+        if !values.is_empty() {
+            return Err(PlanError::Unstructured(
+                "Unexpected rows after the `;` ending `COPY ... FROM STDIN`".into(),
+            ));
+        }
+
+        let mut format = CopyFormat::Csv { header: false };
+        let mut header = None;
+        for option in options {
+            match option {
+                CopyOption::Format(name) => {
+                    format = match name.value.to_ascii_lowercase().as_str() {
+                        "csv" => CopyFormat::Csv { header: false },
+                        "json" => CopyFormat::Json,
+                        other => {
+                            return Err(PlanError::Unsupported {
+                                feature: format!("COPY in the format `{other}`"),
+                            })
+                        }
+                    }
+                }
+                CopyOption::Header(h) => header = Some(h),
+                option => {
+                    return Err(PlanError::Unsupported {
+                        feature: format!("COPY with the option `{option}`"),
+                    })
+                }
+            }
+        }
+        match (&mut format, header) {
+            (CopyFormat::Csv { header }, Some(h)) => *header = h,
+            (CopyFormat::Json, Some(_)) => {
+                return Err(PlanError::Unsupported {
+                    feature: "HEADER with FORMAT json".into(),
+                })
+            }
+            (_, None) => {}
+        }
+        Ok(CopyFrom {
+            table: table_name.to_string(),
+            columns: (!columns.is_empty()).then(|| columns.into_iter().map(|col| col.value).collect()),
+            format,
+        })
+    }
+}
+
+/// Parses the `COPY ... FROM STDIN` statement `sql_text`, whose rows are read from elsewhere.
+pub fn parse_copy(sql_text: &str) -> Result<CopyFrom, DBError> {
+    // The parser requires the `;` ending the statement, after which it reads the rows given inline.
+    let mut statements = parse_sql(&format!("{};", sql_text.trim_end().trim_end_matches(';')))?;
+    let copy = match (statements.pop(), statements.is_empty()) {
+        (Some(statement), true) => CopyFrom::from_statement(statement),
+        _ => Err(PlanError::Unstructured(
+            "Expected a single `COPY ... FROM STDIN`".into(),
+        )),
+    };
+    copy.map_err(|error| DBError::Plan {
+        sql: sql_text.to_string(),
+        error,
+    })
+}
+
+/// Reads the rows of `copy` into the table of `schema` from `input` at once,
+/// for a `COPY` whose rows are given along with it.
+pub(crate) fn read_rows(
+    schema: &TableSchema,
+    copy: &CopyFrom,
+    mut input: impl BufRead,
+) -> Result<Vec<Vec<FieldExpr>>, PlanError> {
+    let reader = RowReader::new(schema, copy)?;
+    let mut rows = Vec::new();
+    let mut lines = 0;
+    while let Some(row) = reader
+        .read_row(&mut input, &mut lines)
+        .map_err(|e| PlanError::Unstructured(e.to_string()))?
+    {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Runs the `COPY ... FROM STDIN` statement `sql_text` in the specified `database_instance_id`,
+/// reading its rows from `input`.
+///
+/// Returns how many rows were copied.
+pub fn copy_from(
+    db_inst_ctx_controller: &DatabaseInstanceContextController,
+    database_instance_id: u64,
+    sql_text: &str,
+    auth: AuthCtx,
+    input: impl BufRead,
+) -> Result<u64, CopyError> {
+    if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
+        copy_from_reader(&database_instance_context.relational_db, sql_text, auth, input)
+    } else {
+        Err(CopyError {
+            copied: 0,
+            error: DatabaseError::NotFound(database_instance_id).into(),
+        })
+    }
+}
+
+pub(crate) fn copy_from_reader(
+    db: &RelationalDB,
+    sql_text: &str,
+    auth: AuthCtx,
+    mut input: impl BufRead,
+) -> Result<u64, CopyError> {
+    let mut copied = 0;
+    let err = |copied, error| CopyError { copied, error };
+
+    let copy = parse_copy(sql_text).map_err(|e| err(0, e))?;
+    let tx = db.begin_tx();
+    let schema = table_schema(db, &tx, &copy.table);
+    db.rollback_tx(tx);
+    let schema = schema.map_err(|error| {
+        err(
+            0,
+            DBError::Plan {
+                sql: sql_text.to_string(),
+                error,
+            },
+        )
+    })?;
+    let rows = RowReader::new(&schema, &copy).map_err(|error| {
+        err(
+            0,
+            DBError::Plan {
+                sql: sql_text.to_string(),
+                error,
+            },
+        )
+    })?;
+
+    let mut batch = Vec::with_capacity(BATCH_ROWS);
+    let mut lines = 0;
+    loop {
+        let row = rows.read_row(&mut input, &mut lines).map_err(|e| err(copied, e))?;
+        let done = row.is_none();
+        batch.extend(row);
+        if batch.len() == BATCH_ROWS || (done && !batch.is_empty()) {
+            let len = batch.len() as u64;
+            let insert = CrudExpr::Insert {
+                source: SourceExpr::from(&schema),
+                rows: std::mem::take(&mut batch),
+            };
+            let mut tx = db.begin_tx();
+            let res = execute_sql(db, &mut tx, vec![insert], auth);
+            db.finish_tx(tx, res).map_err(|e| err(copied, e))?;
+            copied += len;
+        }
+        if done {
+            return Ok(copied);
+        }
+    }
+}
+
+fn table_schema(db: &RelationalDB, tx: &MutTxId, table: &str) -> Result<TableSchema, PlanError> {
+    let table_id = db
+        .table_id_from_name(tx, table)
+        .map_err(|e| PlanError::DatabaseInternal(Box::new(e)))?
+        .ok_or_else(|| PlanError::UnknownTable { table: table.into() })?;
+    db.schema_for_table(tx, table_id)
+        .map_err(|e| PlanError::DatabaseInternal(Box::new(e)))
+}
+
+/// Reads the rows of a `COPY` into a table, in the order of its columns.
+struct RowReader {
+    format: CopyFormat,
+    /// The type of the rows as they're read, with their columns in the order they're given.
+    ty: AlgebraicType,
+    /// The position in a row as it's read of each column of the table.
+    positions: Vec<usize>,
+}
+
+impl RowReader {
+    fn new(schema: &TableSchema, copy: &CopyFrom) -> Result<Self, PlanError> {
+        let columns = match &copy.columns {
+            None => schema.columns.iter().collect::<Vec<_>>(),
+            Some(names) => {
+                let columns = names
+                    .iter()
+                    .map(|name| {
+                        schema.columns.iter().find(|col| col.col_name == *name).ok_or_else(|| {
+                            PlanError::Unstructured(format!("Unknown column `{name}` of `{}`", copy.table))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(missing) = schema
+                    .columns
+                    .iter()
+                    .find(|col| columns.iter().filter(|c| c.col_id == col.col_id).count() != 1)
+                {
+                    return Err(PlanError::Unstructured(format!(
+                        "COPY must list every column of `{}` once, but lists `{}` {} times",
+                        copy.table,
+                        missing.col_name,
+                        columns.iter().filter(|c| c.col_id == missing.col_id).count(),
+                    )));
+                }
+                columns
+            }
+        };
+        let positions = schema
+            .columns
+            .iter()
+            .map(|col| columns.iter().position(|c| c.col_id == col.col_id).unwrap())
+            .collect();
+        let ty = AlgebraicType::Product(ProductType::new(
+            columns
+                .iter()
+                .map(|col| ProductTypeElement::new_named(col.col_type.clone(), col.col_name.clone()))
+                .collect(),
+        ));
+        Ok(Self {
+            format: copy.format,
+            ty,
+            positions,
+        })
+    }
+
+    /// Reads the next row from `input`, counting the `lines` read,
+    /// and returns its columns in the order of the table, or `None` at the end of the input.
+    fn read_row(&self, input: &mut impl BufRead, lines: &mut u64) -> Result<Option<Vec<FieldExpr>>, DBError> {
+        let at_line = |line: u64, err: JsonError| DBError::Other(anyhow::anyhow!("Invalid row at line {line}: {err}"));
+        let ty = WithTypespace::empty(&self.ty);
+        let row = match self.format {
+            CopyFormat::Csv { header } => loop {
+                let first_line = *lines + 1;
+                let Some(record) = read_csv_record(input, lines)? else {
+                    return Ok(None);
+                };
+                if header && first_line == 1 {
+                    continue;
+                }
+                break csv_row(ty, record).map_err(|e| at_line(first_line, e))?;
+            },
+            CopyFormat::Json => loop {
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                *lines += 1;
+                if !line.trim().is_empty() {
+                    break json::from_str_seed(ty, &line).map_err(|e| at_line(*lines, e))?;
+                }
+            },
+        };
+        let AlgebraicValue::Product(row) = row else {
+            unreachable!("rows are decoded as products");
+        };
+        let mut values = row.elements;
+        let row = self
+            .positions
+            .iter()
+            .map(|&i| FieldExpr::Value(std::mem::replace(&mut values[i], AlgebraicValue::UNIT)))
+            .collect();
+        Ok(Some(row))
+    }
+}
+
+/// Decodes the `record` of the fields of a row of the type `ty`, which are `None` when empty and unquoted.
+fn csv_row(ty: WithTypespace<'_, AlgebraicType>, record: Vec<Option<String>>) -> Result<AlgebraicValue, JsonError> {
+    let AlgebraicType::Product(product) = ty.ty() else {
+        unreachable!("rows are products");
+    };
+    // Extra fields are left for `from_value` to reject, and missing ones to fill in for options.
+    let col_types = product.elements.iter().map(|col| Some(&col.algebraic_type));
+    let fields = record
+        .into_iter()
+        .zip(col_types.chain(std::iter::repeat(None)))
+        .map(|(field, col_ty)| match field {
+            None => Value::Null,
+            Some(field) if col_ty.map_or(true, is_unquoted) => Value::String(field),
+            // Simple enums and non-finite floats are strings without quotes, too.
+            Some(field) => serde_json::from_str(&field).unwrap_or(Value::String(field)),
+        })
+        .collect();
+    json::from_value(ty, &Value::Array(fields))
+}
+
+/// Returns whether values of `ty` are given in CSV without quotes, which strings and byte arrays are.
+fn is_unquoted(ty: &AlgebraicType) -> bool {
+    let ty = match ty {
+        AlgebraicType::Sum(sum) => sum.as_option().unwrap_or(ty),
+        ty => ty,
+    };
+    matches!(ty, AlgebraicType::Builtin(BuiltinType::String)) || ty.is_bytes()
+}
+
+/// Reads a record of CSV from `input`, counting the `lines` read,
+/// or returns `None` at the end of the input or at the line `\.` marking the end of the data.
+///
+/// The fields of the record are `None` when empty and unquoted, as `COPY` of Postgres reads them as `NULL`.
+fn read_csv_record(input: &mut impl BufRead, lines: &mut u64) -> std::io::Result<Option<Vec<Option<String>>>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    *lines += 1;
+    if line.trim_end_matches(['\r', '\n']) == "\\." {
+        return Ok(None);
+    }
+
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    loop {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if in_quotes && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' if in_quotes => in_quotes = false,
+                '"' if field.is_empty() && !quoted => (quoted, in_quotes) = (true, true),
+                ',' if !in_quotes => {
+                    record.push((quoted || !field.is_empty()).then(|| std::mem::take(&mut field)));
+                    quoted = false;
+                }
+                '\r' | '\n' if !in_quotes => break,
+                c => field.push(c),
+            }
+        }
+        if !in_quotes {
+            break;
+        }
+        // A quoted field goes on over the next line.
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "a quoted field of the CSV is never closed",
+            ));
+        }
+        *lines += 1;
+    }
+    record.push((quoted || !field.is_empty()).then_some(field));
+    Ok(Some(record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::sql::execute::{run, run_transactions};
+    use crate::vm::tests::create_table_with_rows;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::product;
+
+    fn create_data(db: &RelationalDB) -> ResultTest<()> {
+        let mut tx = db.begin_tx();
+        let head = ProductType::from_iter([
+            ("id", AlgebraicType::U64),
+            ("name", AlgebraicType::String),
+            ("nick", AlgebraicType::option(AlgebraicType::String)),
+        ]);
+        create_table_with_rows(db, &mut tx, "person", head, &[])?;
+        db.commit_tx(tx)?;
+        Ok(())
+    }
+
+    fn people(db: &RelationalDB) -> ResultTest<Vec<spacetimedb_sats::ProductValue>> {
+        let mut tx = db.begin_tx();
+        let mut result = run(db, &mut tx, "SELECT * FROM person", AuthCtx::for_testing())?;
+        db.rollback_tx(tx);
+        let mut rows = result.remove(0).data;
+        rows.sort();
+        Ok(rows)
+    }
+
+    #[test]
+    fn test_parse_copy() -> ResultTest<()> {
+        assert_eq!(
+            parse_copy("COPY person (name, id) FROM STDIN WITH (FORMAT csv, HEADER);")?,
+            CopyFrom {
+                table: "person".into(),
+                columns: Some(vec!["name".into(), "id".into()]),
+                format: CopyFormat::Csv { header: true },
+            }
+        );
+        assert_eq!(
+            parse_copy("copy person from stdin (format json)")?.format,
+            CopyFormat::Json
+        );
+        assert!(parse_copy("COPY person FROM '/etc/passwd'").is_err());
+        assert!(parse_copy("COPY person FROM STDIN WITH (FORMAT json, HEADER)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_csv() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let csv = "nick,name,id\r\nal,alice,1\r\n,\"bob, \"\"the\nbuilder\"\"\",2\r\n\\.\r\nignored\r\n";
+        let sql = "COPY person (nick, name, id) FROM STDIN WITH (FORMAT csv, HEADER true)";
+        let copied = copy_from_reader(&db, sql, AuthCtx::for_testing(), csv.as_bytes())?;
+        assert_eq!(copied, 2);
+        assert_eq!(
+            people(&db)?,
+            [
+                product!(1u64, "alice", AlgebraicValue::OptionSome("al".into())),
+                product!(2u64, "bob, \"the\nbuilder\"", AlgebraicValue::OptionNone()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_json_stops_at_invalid_row() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let json = "{\"id\": 1, \"name\": \"alice\", \"nick\": \"al\"}\n\n[2, \"bob\", null]\n[\"three\", \"carol\"]\n";
+        let sql = "COPY person FROM STDIN WITH (FORMAT json)";
+        let err = copy_from_reader(&db, sql, AuthCtx::for_testing(), json.as_bytes()).unwrap_err();
+        assert_eq!(err.copied, 0);
+        assert!(err.to_string().contains("line 4"), "{err}");
+        assert!(people(&db)?.is_empty());
+
+        let json = &json[..json.rfind('[').unwrap()];
+        assert_eq!(copy_from_reader(&db, sql, AuthCtx::for_testing(), json.as_bytes())?, 2);
+        assert_eq!(people(&db)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_inline() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let sql =
+            "COPY person FROM STDIN WITH (FORMAT csv);\n1,alice,al\n2,bob,\n\\.\nSELECT * FROM person WHERE id = 2";
+        let result = run_transactions(&db, sql, AuthCtx::for_testing(), false)?;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].data, [product!(2u64, "bob", AlgebraicValue::OptionNone())]);
+        assert_eq!(people(&db)?.len(), 2);
+
+        let sql = "COPY person FROM STDIN WITH (FORMAT json);\n[3, \"carol\", null]\n";
+        run_transactions(&db, sql, AuthCtx::for_testing(), false)?;
+        assert_eq!(people(&db)?.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! The `CREATE TABLE` and `CREATE INDEX` statements of a schema, as text.
+//!
+//! The statements are in the dialect [`compile_sql`](super::compiler::compile_sql) accepts,
+//! so running them creates the same tables, columns and indexes,
+//! which makes the effective schema of a module reviewable without publishing it.
+//! As a `CREATE INDEX` looks up its table when it's compiled,
+//! it has to run in a later batch of statements than the `CREATE TABLE` of its table.
+
+use std::fmt::Write;
+
+use spacetimedb_lib::{IndexType, ModuleDef};
+use spacetimedb_sats::algebraic_type::fmt::fmt_algebraic_type;
+use spacetimedb_sats::{AlgebraicType, BuiltinType};
+
+use crate::db::datastore::traits::{IndexDef, TableDef};
+use crate::error::PlanError;
+use crate::host::schema_for;
+
+/// Returns the statements creating the tables of `module`, in the order it declares them.
+pub fn module_ddl(module: &ModuleDef) -> anyhow::Result<String> {
+    let mut ddl = String::new();
+    for table in &module.tables {
+        if !ddl.is_empty() {
+            ddl.push('\n');
+        }
+        ddl += &table_ddl(&schema_for(&module.typespace, table)?)?;
+    }
+    Ok(ddl)
+}
+
+/// Returns the `CREATE TABLE` statement of `table`, followed by a `CREATE INDEX` statement for each of its indexes,
+/// each on a line of its own.
+///
+/// An auto-incremented column is declared `GENERATED BY DEFAULT AS IDENTITY`,
+/// which also creates the unique index on it, so that index isn't created by a statement of its own.
+pub fn table_ddl(table: &TableDef) -> Result<String, PlanError> {
+    let mut columns = Vec::with_capacity(table.columns.len());
+    for column in &table.columns {
+        let unsupported = || PlanError::Unsupported {
+            feature: format!(
+                "Column {}.{} of type {}",
+                table.table_name,
+                column.col_name,
+                fmt_algebraic_type(&column.col_type)
+            ),
+        };
+        let (ty, is_null) = match &column.col_type {
+            AlgebraicType::Sum(sum) => sum.as_option().map_or((&column.col_type, false), |ty| (ty, true)),
+            ty => (ty, false),
+        };
+        let mut def = format!("{} {}", column.col_name, sql_type(ty).ok_or_else(unsupported)?);
+        if is_null {
+            def += " NULL";
+        }
+        if column.is_autoinc {
+            def += " GENERATED BY DEFAULT AS IDENTITY";
+        }
+        columns.push(def);
+    }
+
+    let mut ddl = format!("CREATE TABLE {} ({});\n", table.table_name, columns.join(", "));
+    for index in &table.indexes {
+        if !is_identity_index(table, index) {
+            writeln!(ddl, "{}", index_ddl(table, index)).unwrap();
+        }
+    }
+    Ok(ddl)
+}
+
+/// Returns whether `index` is the one `GENERATED BY DEFAULT AS IDENTITY` creates on its column.
+fn is_identity_index(table: &TableDef, index: &IndexDef) -> bool {
+    let column = &table.columns[index.col_id as usize];
+    column.is_autoinc && index.is_unique && index.index_type == IndexType::BTree
+}
+
+fn index_ddl(table: &TableDef, index: &IndexDef) -> String {
+    let unique = if index.is_unique { "UNIQUE " } else { "" };
+    let using = match index.index_type {
+        IndexType::BTree | IndexType::Hash => "",
+        IndexType::FullText => " USING fulltext",
+        IndexType::Spatial => " USING spatial",
+    };
+    let column = &table.columns[index.col_id as usize].col_name;
+    format!(
+        "CREATE {unique}INDEX {} ON {}{using} ({column});",
+        index.name, table.table_name
+    )
+}
+
+/// The SQL name of the type `ty`, or `None` if a column of that type can't be created by `CREATE TABLE`.
+fn sql_type(ty: &AlgebraicType) -> Option<String> {
+    let name = match ty {
+        AlgebraicType::Builtin(ty) => match ty {
+            BuiltinType::Bool => "BOOLEAN",
+            BuiltinType::I8 => "TINYINT",
+            BuiltinType::U8 => "TINYINT UNSIGNED",
+            BuiltinType::I16 => "SMALLINT",
+            BuiltinType::U16 => "SMALLINT UNSIGNED",
+            BuiltinType::I32 => "INTEGER",
+            BuiltinType::U32 => "INTEGER UNSIGNED",
+            BuiltinType::I64 => "BIGINT",
+            BuiltinType::U64 => "BIGINT UNSIGNED",
+            BuiltinType::F32 => "REAL",
+            BuiltinType::F64 => "DOUBLE",
+            BuiltinType::String => "TEXT",
+            BuiltinType::Array(array) => return Some(format!("{}[]", sql_type(&array.elem_ty)?)),
+            BuiltinType::I128 | BuiltinType::U128 | BuiltinType::Map(_) => return None,
+        },
+        AlgebraicType::Sum(sum) if sum.is_simple_enum() => {
+            let mut values = Vec::with_capacity(sum.variants.len());
+            for variant in &sum.variants {
+                values.push(format!("'{}'", variant.name.as_ref()?.replace('\'', "''")));
+            }
+            return Some(format!("ENUM({})", values.join(", ")));
+        }
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::datastore::traits::ColumnDef;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::sql::execute::run;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_lib::identity::AuthCtx;
+
+    fn column(col_name: &str, col_type: AlgebraicType, is_autoinc: bool) -> ColumnDef {
+        ColumnDef {
+            col_name: col_name.into(),
+            col_type,
+            is_autoinc,
+        }
+    }
+
+    #[test]
+    fn test_table_ddl_roundtrip() -> ResultTest<()> {
+        let table = TableDef {
+            table_name: "Item".into(),
+            columns: vec![
+                column("id", AlgebraicType::U64, true),
+                column("name", AlgebraicType::String, false),
+                column("nick", AlgebraicType::option(AlgebraicType::String), false),
+                column("tags", AlgebraicType::array(AlgebraicType::String), false),
+                column(
+                    "kind",
+                    AlgebraicType::simple_enum(["sword", "shield"].into_iter()),
+                    false,
+                ),
+                column("bio", AlgebraicType::String, false),
+            ],
+            indexes: vec![
+                IndexDef::new("Item_id_unique".into(), 0, 0, true),
+                IndexDef::new("Item_name_unique".into(), 0, 1, true),
+                IndexDef::fulltext("Item_bio".into(), 0, 5),
+            ],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let ddl = table_ddl(&table)?;
+        assert_eq!(
+            ddl,
+            "CREATE TABLE Item (id BIGINT UNSIGNED GENERATED BY DEFAULT AS IDENTITY, name TEXT, nick TEXT NULL, \
+             tags TEXT[], kind ENUM('sword', 'shield'), bio TEXT);\n\
+             CREATE UNIQUE INDEX Item_name_unique ON Item (name);\n\
+             CREATE INDEX Item_bio ON Item USING fulltext (bio);\n"
+        );
+
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        for statement in ddl.lines() {
+            run(&db, &mut tx, statement, AuthCtx::for_testing())?;
+        }
+        let table_id = db.table_id_from_name(&tx, "Item")?.unwrap();
+        let created = TableDef::from(db.schema_for_table(&tx, table_id)?);
+        db.rollback_tx(tx);
+
+        assert_eq!(created.columns, table.columns);
+        let mut indexes: Vec<_> = created
+            .indexes
+            .iter()
+            .map(|x| (x.col_id, x.is_unique, x.index_type))
+            .collect();
+        indexes.sort();
+        assert_eq!(
+            indexes,
+            [
+                (0, true, IndexType::BTree),
+                (1, true, IndexType::BTree),
+                (5, false, IndexType::FullText)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_ddl_unsupported() {
+        let table = TableDef {
+            table_name: "Player".into(),
+            columns: vec![column("balance", AlgebraicType::U128, false)],
+            indexes: vec![],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        assert!(matches!(table_ddl(&table), Err(PlanError::Unsupported { .. })));
+    }
+}
@@ -1,14 +1,18 @@
+use spacetimedb_lib::error::AuthError;
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::MemTable;
 use spacetimedb_lib::{ProductType, ProductValue};
+use spacetimedb_vm::errors::ErrorVm;
 use spacetimedb_vm::eval::run_ast;
 use spacetimedb_vm::expr::{CodeResult, CrudExpr, Expr};
 
 use crate::database_instance_context_controller::DatabaseInstanceContextController;
 use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::IndexDef;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, DatabaseError};
-use crate::sql::compiler::compile_sql;
+use crate::sql::ast::{parse_transactions, SqlTx};
+use crate::sql::compiler::{compile_sql, compile_sql_statements};
 use crate::vm::DbProgram;
 
 pub struct StmtResult {
@@ -27,9 +31,39 @@ pub fn execute(
     auth: AuthCtx,
 ) -> Result<Vec<MemTable>, DBError> {
     if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
-        database_instance_context
-            .relational_db
-            .with_auto_commit(|tx| run(&database_instance_context.relational_db, tx, &sql_text, auth))
+        run_transactions(&database_instance_context.relational_db, &sql_text, auth, false)
+    } else {
+        Err(DatabaseError::NotFound(database_instance_id).into())
+    }
+}
+
+/// Run a `SQL` query in the specified `database_instance_id`,
+/// rejecting it without running any of it if a statement writes to the database.
+pub fn execute_read_only(
+    db_inst_ctx_controller: &DatabaseInstanceContextController,
+    database_instance_id: u64,
+    sql_text: String,
+    auth: AuthCtx,
+) -> Result<Vec<MemTable>, DBError> {
+    if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
+        run_transactions(&database_instance_context.relational_db, &sql_text, auth, true)
+    } else {
+        Err(DatabaseError::NotFound(database_instance_id).into())
+    }
+}
+
+/// Describe the results of the `SQL` query in the specified `database_instance_id` without running it,
+/// rejecting it if a statement writes to the database.
+///
+/// The returned tables have the header of each result but no rows.
+pub fn describe_read_only(
+    db_inst_ctx_controller: &DatabaseInstanceContextController,
+    database_instance_id: u64,
+    sql_text: String,
+    auth: AuthCtx,
+) -> Result<Vec<MemTable>, DBError> {
+    if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
+        describe_transactions(&database_instance_context.relational_db, &sql_text, auth)
     } else {
         Err(DatabaseError::NotFound(database_instance_id).into())
     }
@@ -83,6 +117,7 @@ pub fn execute_sql(
 }
 
 /// Run the `SQL` string using the `auth` credentials
+#[cfg(test)]
 pub(crate) fn run(
     db: &RelationalDB,
     tx: &mut MutTxId,
@@ -93,16 +128,139 @@ pub(crate) fn run(
     execute_sql(db, tx, ast, auth)
 }
 
+/// Run the `SQL` string using the `auth` credentials,
+/// with a transaction for each `BEGIN; ...; COMMIT` block and one for each run of statements outside of them.
+///
+/// A transaction that fails is rolled back and stops the run,
+/// but the transactions before it stay committed.
+/// The results of a transaction ending in `ROLLBACK` are returned, but its changes are discarded.
+///
+/// With `read_only`, a statement that isn't a query fails its transaction before it runs.
+///
+/// A query ending in `AS OF <tx_offset>` runs against the state of the database right after
+/// its first `tx_offset` transactions, rebuilt in memory by replaying its commit log.
+///
+/// A `CREATE INDEX` in a transaction of its own builds the index without holding the lock,
+/// see [`RelationalDB::create_index_online`].
+pub(crate) fn run_transactions(
+    db: &RelationalDB,
+    sql_text: &str,
+    auth: AuthCtx,
+    read_only: bool,
+) -> Result<Vec<MemTable>, DBError> {
+    let mut result = Vec::new();
+    for SqlTx {
+        statements,
+        rollback,
+        as_of,
+    } in parse_transactions(sql_text)?
+    {
+        let past_db = as_of.map(|tx_offset| db.as_of(tx_offset)).transpose()?;
+        let db = past_db.as_ref().unwrap_or(db);
+        let rollback = rollback || past_db.is_some();
+
+        let mut tx = db.begin_tx();
+        let ast = compile_sql_statements(db, &tx, sql_text, statements).and_then(|ast| {
+            if read_only && ast.iter().any(|x| !matches!(x, CrudExpr::Query(_))) {
+                return Err(DBError::VmUser(ErrorVm::Auth(AuthError::ReadOnly).into()));
+            }
+            Ok(ast)
+        });
+        let res = match ast {
+            // A `CREATE INDEX` of its own is built without holding the lock, which it can't be in `tx`.
+            Ok(ast) if !rollback && matches!(&ast[..], [CrudExpr::CreateIndex { .. }]) => {
+                let index = index_to_create(db, &tx, ast.into_iter().next().unwrap(), auth);
+                db.rollback_tx(tx);
+                if let Some(index) = index? {
+                    db.create_index_online(index)?;
+                }
+                Ok(Vec::new())
+            }
+            ast => {
+                let res = ast.and_then(|ast| execute_sql(db, &mut tx, ast, auth));
+                if rollback {
+                    db.rollback_tx(tx);
+                    res
+                } else {
+                    db.finish_tx(tx, res)
+                }
+            }
+        };
+        result.extend(res?);
+    }
+    Ok(result)
+}
+
+/// Describe the results of the `SQL` string as [run_transactions] with `read_only` would return them,
+/// compiling each statement against the current schema of the database, even with `AS OF`.
+pub(crate) fn describe_transactions(
+    db: &RelationalDB,
+    sql_text: &str,
+    auth: AuthCtx,
+) -> Result<Vec<MemTable>, DBError> {
+    let mut result = Vec::new();
+    for SqlTx { statements, .. } in parse_transactions(sql_text)? {
+        let mut tx = db.begin_tx();
+        let res = compile_sql_statements(db, &tx, sql_text, statements).and_then(|ast| {
+            let p = DbProgram::new(db, &mut tx, auth);
+            ast.into_iter()
+                .map(|x| match x {
+                    CrudExpr::Query(q) => {
+                        let table_access = q.source.table_access();
+                        let head = p.describe_query(q).map_err(|err| DBError::VmUser(err.into()))?;
+                        Ok(MemTable::new(&head, table_access, &[]))
+                    }
+                    _ => Err(DBError::VmUser(ErrorVm::Auth(AuthError::ReadOnly).into())),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        });
+        db.rollback_tx(tx);
+        result.extend(res?);
+    }
+    Ok(result)
+}
+
+/// Returns the index `CREATE INDEX` creates, unless it exists and the statement allows for that,
+/// checking that the caller may create indexes.
+fn index_to_create(db: &RelationalDB, tx: &MutTxId, ast: CrudExpr, auth: AuthCtx) -> Result<Option<IndexDef>, DBError> {
+    let CrudExpr::CreateIndex {
+        table_id,
+        name,
+        col_id,
+        is_unique,
+        index_type,
+        if_not_exists,
+    } = ast
+    else {
+        unreachable!("not a `CREATE INDEX`");
+    };
+    if auth.owner != auth.caller {
+        let action = "create indexes".into();
+        return Err(DBError::VmUser(ErrorVm::Auth(AuthError::OwnerOnly { action }).into()));
+    }
+    if if_not_exists && db.index_id_from_name(tx, &name)?.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(IndexDef {
+        table_id,
+        col_id,
+        name,
+        is_unique,
+        index_type,
+    }))
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::db::relational_db::{ST_TABLES_ID, ST_TABLES_NAME};
+    use crate::identity::Identity;
     use crate::vm::tests::create_table_with_rows;
     use spacetimedb_lib::auth::{StAccess, StTableType};
     use spacetimedb_lib::error::ResultTest;
     use spacetimedb_lib::relation::Header;
-    use spacetimedb_sats::{product, AlgebraicType, BuiltinType, ProductType};
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, BuiltinType, ProductType, SumTypeVariant};
     use spacetimedb_vm::dsl::{mem_table, scalar};
     use spacetimedb_vm::eval::create_game_data;
     use tempdir::TempDir;
@@ -237,6 +395,72 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_select_index_catalogs() -> ResultTest<()> {
+        let (db, _, _tmp_dir) = create_data(0)?;
+        let mut tx = db.begin_tx();
+        run_for_testing(
+            &db,
+            &mut tx,
+            "CREATE TABLE c (inventory_id BIGINT GENERATED BY DEFAULT AS IDENTITY)",
+        )?;
+        db.commit_tx(tx)?;
+
+        let mut tx = db.begin_tx();
+        let table_id = db.table_id_from_name(&tx, "c")?.unwrap();
+        let schema = db.schema_for_table(&tx, table_id)?;
+        let index = schema.indexes.first().unwrap();
+
+        let mut query = |sql: &str| -> ResultTest<Vec<ProductValue>> {
+            let mut result = run_for_testing(&db, &mut tx, &sql.replace("{table_id}", &table_id.to_string()))?;
+            assert_eq!(result.len(), 1, "Not return results");
+            Ok(result.remove(0).data)
+        };
+
+        let indexes = query("SELECT index_id, index_name FROM st_indexes WHERE table_id = {table_id}")?;
+        assert_eq!(indexes, vec![product!(index.index_id, index.index_name.clone())]);
+
+        let constraints =
+            query("SELECT constraint_name, constraint_type FROM st_constraints WHERE table_id = {table_id}")?;
+        assert_eq!(constraints, vec![product!(index.index_name.clone(), "unique")]);
+
+        let sequences = query("SELECT col_id, increment FROM st_sequence WHERE table_id = {table_id}")?;
+        assert_eq!(sequences, vec![product!(0u32, 1i128)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_information_schema() -> ResultTest<()> {
+        let (db, _, _tmp_dir) = create_data(1)?;
+        let mut tx = db.begin_tx();
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT table_name, table_type FROM information_schema.tables WHERE table_schema = 'public'",
+        )?;
+        assert_eq!(result[0].data, vec![product!("inventory", "BASE TABLE")]);
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT column_name, ordinal_position, data_type, is_nullable FROM INFORMATION_SCHEMA.COLUMNS WHERE table_name = 'inventory'",
+        )?;
+        assert_eq!(
+            result[0].data,
+            vec![
+                product!("inventory_id", 1u32, "bigint unsigned", "NO"),
+                product!("name", 2u32, "text", "NO"),
+            ]
+        );
+
+        // Views can't be written to.
+        assert!(run_for_testing(&db, &mut tx, "DELETE FROM information_schema.tables").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_select_column() -> ResultTest<()> {
         let (db, table, _tmp_dir) = create_data(1)?;
@@ -346,6 +570,233 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_where_enum() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let status = AlgebraicType::simple_enum(["Active", "Banned"].into_iter());
+        let shape = AlgebraicType::sum(vec![
+            SumTypeVariant::new_named(AlgebraicType::F32, "Circle"),
+            SumTypeVariant::new_named(AlgebraicType::product(vec![]), "Point"),
+        ]);
+        let head = ProductType::from_iter([("id", AlgebraicType::U64), ("status", status), ("shape", shape)]);
+        let active = AlgebraicValue::sum(0, AlgebraicValue::UNIT);
+        let banned = AlgebraicValue::sum(1, AlgebraicValue::UNIT);
+        let circle = AlgebraicValue::sum(0, AlgebraicValue::F32(1.0f32.into()));
+        let point = AlgebraicValue::sum(1, AlgebraicValue::UNIT);
+        let rows = vec![
+            product!(1u64, active.clone(), circle),
+            product!(2u64, banned, point.clone()),
+            product!(3u64, active, point),
+        ];
+        create_table_with_rows(&db, &mut tx, "player", head, &rows)?;
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM player WHERE status = 'Active'")?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(1u64), product!(3u64)], "C-like enum");
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM player WHERE variant_of(shape) = 'Point'")?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(2u64), product!(3u64)], "Sum with payload");
+
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT id FROM player WHERE status = 'Unknown'").is_err(),
+            "Unknown variant"
+        );
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT id FROM player WHERE variant_of(id) = 'Point'").is_err(),
+            "Not a sum type"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_timestamp() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from_iter([("id", AlgebraicType::U64), ("created_at", AlgebraicType::U64)]);
+        // 2023-08-01T00:00:00Z and 2023-08-02T00:00:00Z, in micro seconds since the UNIX epoch.
+        let rows = vec![
+            product!(1u64, 1_690_848_000_000_000u64),
+            product!(2u64, 1_690_934_400_000_000u64),
+        ];
+        create_table_with_rows(&db, &mut tx, "event", head, &rows)?;
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM event WHERE created_at > '2023-08-01T12:00:00Z'",
+        )?;
+        assert_eq!(result[0].data, vec![product!(2u64)], "RFC 3339");
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM event WHERE created_at = '2023-08-01 00:00:00'",
+        )?;
+        assert_eq!(result[0].data, vec![product!(1u64)], "Without a `T` or `Z`");
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_time_math() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from_iter([
+            ("id", AlgebraicType::U64),
+            ("used_at", AlgebraicType::U64),
+            ("cooldown", AlgebraicType::I64),
+        ]);
+        // Used at 2023-08-01T00:00:00Z for 1 hour and at 2023-08-02T00:00:00Z for 1 day.
+        let rows = vec![
+            product!(1u64, 1_690_848_000_000_000u64, 3_600_000_000i64),
+            product!(2u64, 1_690_934_400_000_000u64, 86_400_000_000i64),
+        ];
+        create_table_with_rows(&db, &mut tx, "ability", head, &rows)?;
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM ability WHERE used_at + cooldown < '2023-08-01T12:00:00Z'",
+        )?;
+        assert_eq!(result[0].data, vec![product!(1u64)], "Timestamp + Duration");
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM ability WHERE used_at - '1h' >= '2023-08-01T12:00:00Z'",
+        )?;
+        assert_eq!(result[0].data, vec![product!(2u64)], "Timestamp - Duration literal");
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM ability WHERE '2023-08-02T00:00:00Z' - used_at > '12h'",
+        )?;
+        assert_eq!(result[0].data, vec![product!(1u64)], "Timestamp - Timestamp");
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM ability WHERE cooldown - '1h' = 0")?;
+        assert_eq!(result[0].data, vec![product!(1u64)], "Duration - Duration");
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_array_contains() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from_iter([
+            ("id", AlgebraicType::U64),
+            ("tags", AlgebraicType::array(AlgebraicType::String)),
+            ("scores", AlgebraicType::array(AlgebraicType::U32)),
+        ]);
+        let rows = vec![
+            product!(
+                1u64,
+                AlgebraicValue::ArrayOf(vec!["pvp".to_string()]),
+                AlgebraicValue::ArrayOf(vec![1u32, 2])
+            ),
+            product!(
+                2u64,
+                AlgebraicValue::ArrayOf(Vec::<String>::new()),
+                AlgebraicValue::ArrayOf(vec![2u32])
+            ),
+            product!(
+                3u64,
+                AlgebraicValue::ArrayOf(vec!["pve".to_string(), "pvp".to_string()]),
+                AlgebraicValue::ArrayOf(vec![3u32])
+            ),
+        ];
+        create_table_with_rows(&db, &mut tx, "guild", head, &rows)?;
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM guild WHERE array_contains(tags, 'pvp')")?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(1u64), product!(3u64)], "Array of strings");
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM guild WHERE ARRAY_CONTAINS(scores, 2) AND id > 1",
+        )?;
+        assert_eq!(result[0].data, vec![product!(2u64)], "Array of numbers");
+
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT id FROM guild WHERE array_contains(id, 1)").is_err(),
+            "Not an array"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_match() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from_iter([("id", AlgebraicType::U64), ("name", AlgebraicType::String)]);
+        let rows = vec![
+            product!(1u64, "Iron Sword"),
+            product!(2u64, "Wooden Sword"),
+            product!(3u64, "Sword of IRON"),
+        ];
+        create_table_with_rows(&db, &mut tx, "item", head, &rows)?;
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM item WHERE MATCH(name, 'iron sword')")?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(1u64), product!(3u64)], "All the tokens");
+
+        let result = run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT id FROM item WHERE match(name, 'wooden') OR id = 3",
+        )?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(2u64), product!(3u64)], "Combined with other filters");
+
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT id FROM item WHERE MATCH(id, '1')").is_err(),
+            "Not a string"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_in_box() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let point = AlgebraicType::Product(ProductType::from_iter([
+            ("x", AlgebraicType::I32),
+            ("y", AlgebraicType::I32),
+        ]));
+        let head = ProductType::from_iter([("id", AlgebraicType::U64), ("pos", point)]);
+        let rows = vec![
+            product!(1u64, product!(3i32, 4i32)),
+            product!(2u64, product!(10i32, 0i32)),
+            product!(3u64, product!(3i32, 11i32)),
+            product!(4u64, product!(-1i32, 4i32)),
+        ];
+        create_table_with_rows(&db, &mut tx, "entity", head, &rows)?;
+
+        let result = run_for_testing(&db, &mut tx, "SELECT id FROM entity WHERE in_box(pos, 0, 0, 10, 10)")?;
+        let mut ids = result[0].data.clone();
+        ids.sort();
+        assert_eq!(ids, vec![product!(1u64), product!(2u64)], "Edges included");
+
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT id FROM entity WHERE in_box(id, 0, 0, 10, 10)").is_err(),
+            "Not a point"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_inner_join() -> ResultTest<()> {
         let data = create_game_data();
@@ -405,6 +856,39 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_order_by_limit() -> ResultTest<()> {
+        let (db, _, _tmp_dir) = create_data(5)?;
+        let mut tx = db.begin_tx();
+
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT inventory_id FROM inventory WHERE inventory_id > 1 ORDER BY inventory_id DESC LIMIT 2",
+        )?[0];
+        let head = ProductType::from_iter([("inventory_id", BuiltinType::U64)]);
+        let input = mem_table(head.clone(), [product!(5u64), product!(4u64)]);
+        assert_eq!(input.as_without_table_name(), result.as_without_table_name(), "Top 2");
+
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT inventory_id FROM inventory ORDER BY name LIMIT 2 OFFSET 3",
+        )?[0];
+        let input = mem_table(head, [product!(4u64), product!(5u64)]);
+        assert_eq!(input.as_without_table_name(), result.as_without_table_name(), "Page 2");
+
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT * FROM inventory ORDER BY missing").is_err(),
+            "Unknown field"
+        );
+        assert!(
+            run_for_testing(&db, &mut tx, "SELECT * FROM inventory LIMIT -1").is_err(),
+            "Negative limit"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_insert() -> ResultTest<()> {
         let (db, mut input, _tmp_dir) = create_data(1)?;
@@ -588,6 +1072,54 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_drop_index() -> ResultTest<()> {
+        let (db, _, _tmp_dir) = create_data(2)?;
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let auth = AuthCtx::for_current(owner);
+        let indexes = || -> ResultTest<Vec<(String, u32, bool)>> {
+            let tx = db.begin_tx();
+            let table_id = db.table_id_from_name(&tx, "inventory")?.unwrap();
+            let schema = db.schema_for_table(&tx, table_id);
+            db.rollback_tx(tx);
+            Ok(schema?
+                .indexes
+                .into_iter()
+                .map(|x| (x.index_name, x.col_id, x.is_unique))
+                .collect())
+        };
+        let run = |sql: &str| run_transactions(&db, sql, auth, false);
+
+        // Built online, then looked up through.
+        run("CREATE UNIQUE INDEX inventory_id_idx ON inventory (inventory_id)")?;
+        assert_eq!(indexes()?, [("inventory_id_idx".to_string(), 0, true)]);
+        assert_eq!(run("SELECT * FROM inventory WHERE inventory_id = 1")?[0].data.len(), 1);
+        assert!(run("INSERT INTO inventory (inventory_id, name) VALUES (1, 'again')").is_err());
+
+        // Created in the transaction of its block, which discards it.
+        run("BEGIN; CREATE INDEX name_idx ON inventory (name); ROLLBACK")?;
+        assert_eq!(indexes()?.len(), 1);
+
+        assert!(run("CREATE INDEX inventory_id_idx ON inventory (name)").is_err());
+        run("CREATE INDEX IF NOT EXISTS inventory_id_idx ON inventory (name)")?;
+        assert_eq!(indexes()?.len(), 1);
+
+        assert!(run("CREATE INDEX name_idx ON inventory (missing)").is_err());
+        assert!(run("CREATE INDEX name_idx ON inventory (inventory_id, name)").is_err());
+        assert!(run("CREATE UNIQUE INDEX name_idx ON inventory USING fulltext (name)").is_err());
+        let sql = "CREATE INDEX name_idx ON inventory (name)";
+        let err = run_transactions(&db, sql, AuthCtx::new(owner, alice), false).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        assert_eq!(indexes()?.len(), 1);
+
+        run("CREATE INDEX name_idx ON inventory USING fulltext (name)")?;
+        run("DROP INDEX inventory_id_idx")?;
+        assert_eq!(indexes()?, [("name_idx".to_string(), 1, false)]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_column_constraints() -> ResultTest<()> {
         let (db, _, _tmp_dir) = create_data(0)?;
@@ -670,4 +1202,324 @@ SELECT * FROM inventory",
 
         Ok(())
     }
+
+    #[test]
+    fn test_transactions() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(2)?;
+        let auth = AuthCtx::for_testing();
+        let names = || -> ResultTest<Vec<ProductValue>> {
+            let result = run_transactions(&db, "SELECT name FROM inventory", auth, false)?;
+            let mut names = result[0].data.clone();
+            names.sort();
+            Ok(names)
+        };
+
+        let result = run_transactions(
+            &db,
+            "BEGIN;
+UPDATE inventory SET name = 'moved' WHERE inventory_id = 1;
+DELETE FROM inventory WHERE inventory_id = 2;
+SELECT * FROM inventory;
+COMMIT",
+            auth,
+            false,
+        )?;
+        assert_eq!(result[0].data.len(), 1, "Sees its own changes");
+        assert_eq!(names()?, vec![product!("moved")], "Committed");
+
+        run_transactions(
+            &db,
+            "BEGIN; DELETE FROM inventory WHERE inventory_id = 1; ROLLBACK",
+            auth,
+            false,
+        )?;
+        assert_eq!(names()?, vec![product!("moved")], "Rolled back");
+
+        let result = run_transactions(
+            &db,
+            "INSERT INTO inventory (inventory_id, name) VALUES (3, 'kept');
+BEGIN;
+INSERT INTO inventory (inventory_id, name) VALUES (4, 'lost');
+SELECT * FROM unknown;
+COMMIT",
+            auth,
+            false,
+        );
+        assert!(result.is_err(), "Unknown table");
+        assert_eq!(
+            names()?,
+            vec![product!("kept"), product!("moved")],
+            "Only the failed transaction is rolled back"
+        );
+
+        assert!(run_transactions(&db, "BEGIN; SELECT * FROM inventory", auth, false).is_err());
+        assert!(run_transactions(&db, "SELECT * FROM inventory; COMMIT", auth, false).is_err());
+        assert!(run_transactions(&db, "BEGIN; BEGIN; COMMIT", auth, false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_of() -> ResultTest<()> {
+        // The table and its rows are the first transaction.
+        let (db, _input, _tmp_dir) = create_data(2)?;
+        let auth = AuthCtx::for_testing();
+        run_transactions(
+            &db,
+            "UPDATE inventory SET name = 'it''s moved' WHERE inventory_id = 1",
+            auth,
+            false,
+        )?;
+        run_transactions(&db, "DELETE FROM inventory WHERE inventory_id = 2", auth, false)?;
+
+        let names = |sql: &str| -> ResultTest<Vec<ProductValue>> {
+            let result = run_transactions(&db, sql, auth, false)?;
+            let mut names = result[0].data.clone();
+            names.sort();
+            Ok(names)
+        };
+        assert_eq!(
+            names("SELECT name FROM inventory AS OF 1")?,
+            vec![product!("health1"), product!("health2")]
+        );
+        assert_eq!(
+            names("SELECT name FROM inventory WHERE name = 'it''s moved' as of 2;")?,
+            vec![product!("it's moved")]
+        );
+        assert_eq!(names("SELECT name FROM inventory")?, vec![product!("it's moved")]);
+
+        let result = run_transactions(
+            &db,
+            "SELECT * FROM inventory AS OF 1; SELECT * FROM inventory",
+            auth,
+            false,
+        )?;
+        assert_eq!((result[0].data.len(), result[1].data.len()), (2, 1));
+
+        assert!(run_transactions(&db, "SELECT * FROM inventory AS OF 0", auth, false).is_err());
+        assert!(run_transactions(&db, "SELECT * FROM inventory AS OF 4", auth, false).is_err());
+        assert!(run_transactions(&db, "SELECT * FROM inventory AS OF '2023-07-01'", auth, false).is_err());
+        assert!(run_transactions(&db, "DELETE FROM inventory AS OF 1", auth, false).is_err());
+        assert!(run_transactions(&db, "BEGIN; SELECT * FROM inventory AS OF 1; COMMIT", auth, false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(1)?;
+        let auth = AuthCtx::for_testing();
+
+        let result = run_transactions(&db, "SELECT * FROM inventory", auth, true)?;
+        assert_eq!(result[0].data.len(), 1);
+
+        let err = run_transactions(
+            &db,
+            "SELECT * FROM inventory; DELETE FROM inventory WHERE inventory_id = 1",
+            auth,
+            true,
+        )
+        .unwrap_err();
+        assert!(err.get_auth_error().is_some());
+
+        let result = run_transactions(&db, "SELECT * FROM inventory", auth, true)?;
+        assert_eq!(result[0].data.len(), 1, "Nothing was deleted");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(1)?;
+        let auth = AuthCtx::for_testing();
+
+        for sql in [
+            "SELECT * FROM inventory",
+            "SELECT name FROM inventory WHERE inventory_id = 1",
+            "SELECT 1 FROM inventory",
+            "SELECT * FROM inventory; SELECT inventory_id FROM inventory",
+        ] {
+            let described = describe_transactions(&db, sql, auth)?;
+            let result = run_transactions(&db, sql, auth, true)?;
+            assert_eq!(described.len(), result.len(), "{sql}");
+            for (described, result) in described.iter().zip(&result) {
+                assert_eq!(described.head.ty(), result.head.ty(), "{sql}");
+                assert!(described.data.is_empty(), "{sql}");
+            }
+        }
+
+        let err = describe_transactions(&db, "DELETE FROM inventory WHERE inventory_id = 1", auth).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        let result = run_transactions(&db, "SELECT * FROM inventory", auth, true)?;
+        assert_eq!(result[0].data.len(), 1, "Nothing was deleted");
+
+        assert!(describe_transactions(&db, "SELECT * FROM unknown", auth).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grant_revoke() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(1)?;
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let roles_of_alice = || -> ResultTest<Vec<String>> {
+            let tx = db.begin_tx();
+            let roles = db.roles_of(&tx, alice);
+            db.rollback_tx(tx);
+            Ok(roles?)
+        };
+
+        let auth = AuthCtx::for_current(owner);
+        let sql = format!(
+            "GRANT moderator TO '{}'; GRANT \"Admin\" TO '{}'",
+            alice.to_hex(),
+            alice.to_hex()
+        );
+        run_transactions(&db, &sql, auth, false)?;
+        assert_eq!(roles_of_alice()?, ["moderator", "Admin"]);
+
+        let sql = format!("BEGIN; REVOKE moderator FROM '{}'; ROLLBACK", alice.to_hex());
+        run_transactions(&db, &sql, auth, false)?;
+        assert_eq!(roles_of_alice()?, ["moderator", "Admin"], "The revoke was rolled back");
+
+        let sql = format!("SELECT * FROM inventory; REVOKE moderator FROM '{}'", alice.to_hex());
+        let result = run_transactions(&db, &sql, auth, false)?;
+        assert_eq!(result[0].data.len(), 1);
+        assert_eq!(roles_of_alice()?, ["Admin"]);
+
+        let sql = format!("REVOKE \"Admin\" FROM '{}'", alice.to_hex());
+        let err = run_transactions(&db, &sql, AuthCtx::new(owner, alice), false).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        assert!(run_transactions(&db, &sql, auth, true)
+            .unwrap_err()
+            .get_auth_error()
+            .is_some());
+        assert_eq!(roles_of_alice()?, ["Admin"]);
+
+        let sql = format!("GRANT owner TO '{}'", alice.to_hex());
+        assert!(run_transactions(&db, &sql, auth, false).is_err());
+        assert!(run_transactions(&db, "GRANT moderator TO 'alice'", auth, false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze() -> ResultTest<()> {
+        let (db, input, _tmp_dir) = create_data(10)?;
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let auth = AuthCtx::for_current(owner);
+        let inventory_stats = || -> ResultTest<_> {
+            let tx = db.begin_tx();
+            let table_id = db.table_id_from_name(&tx, "inventory")?.unwrap();
+            let stats = db.column_stats(&tx, table_id);
+            db.rollback_tx(tx);
+            Ok(stats?)
+        };
+
+        assert!(inventory_stats()?.is_empty());
+        run_transactions(&db, "ANALYZE inventory", auth, false)?;
+        let stats = inventory_stats()?;
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].row_count, 10);
+        assert_eq!(stats[0].min, Some(AlgebraicValue::U64(1)));
+        assert_eq!(stats[0].max, Some(AlgebraicValue::U64(10)));
+
+        // Without a table, it analyzes every table, and the queries still return the same rows.
+        let result = run_transactions(&db, "INSERT INTO inventory (inventory_id, name) VALUES (11, 'test'); ANALYZE; SELECT * FROM inventory WHERE inventory_id = 3", auth, false)?;
+        assert_eq!(inventory_stats()?[0].row_count, 11);
+        assert_eq!(result[0].data, [input.data[2].clone()]);
+
+        assert!(run_transactions(&db, "ANALYZE missing", auth, false).is_err());
+        let err = run_transactions(&db, "ANALYZE", AuthCtx::new(owner, alice), false).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        assert!(run_transactions(&db, "ANALYZE", auth, true)
+            .unwrap_err()
+            .get_auth_error()
+            .is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum() -> ResultTest<()> {
+        let (db, input, _tmp_dir) = create_data(10)?;
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let auth = AuthCtx::for_current(owner);
+
+        // It reports the space reclaimed, and the queries that follow still return the same rows.
+        let result = run_transactions(
+            &db,
+            "DELETE FROM inventory WHERE inventory_id > 5; VACUUM; SELECT * FROM inventory",
+            auth,
+            false,
+        )?;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].head.fields.len(), 2);
+        assert_eq!(result[0].data.len(), 1);
+        let mut rows = result[1].data.clone();
+        rows.sort();
+        assert_eq!(rows, input.data[..5]);
+
+        let err = run_transactions(&db, "VACUUM", AuthCtx::new(owner, alice), false).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        assert!(run_transactions(&db, "VACUUM", auth, true)
+            .unwrap_err()
+            .get_auth_error()
+            .is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_acl() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(1)?;
+        let owner = Identity::from_hashing_bytes(b"owner");
+        let alice = Identity::from_hashing_bytes(b"alice");
+        let bob = Identity::from_hashing_bytes(b"bob");
+        let auth = AuthCtx::for_current(owner);
+        let as_alice = AuthCtx::new(owner, alice);
+        let as_bob = AuthCtx::new(owner, bob);
+
+        run_transactions(&db, "CREATE TABLE _feedback (message TEXT)", auth, false)?;
+        let insert_feedback = "INSERT INTO _feedback (message) VALUES ('more maps')";
+        let err = run_transactions(&db, insert_feedback, as_alice, false).unwrap_err();
+        assert!(err.get_auth_error().is_some(), "A private table isn't listed yet");
+
+        let sql = format!(
+            "GRANT dashboard TO '{}'; GRANT SELECT ON inventory TO dashboard; GRANT INSERT ON _feedback TO dashboard",
+            alice.to_hex()
+        );
+        run_transactions(&db, &sql, auth, false)?;
+        assert!(
+            run_transactions(&db, "GRANT SELECT ON inventory TO dashboard", as_alice, false)
+                .unwrap_err()
+                .get_auth_error()
+                .is_some()
+        );
+
+        let result = run_transactions(&db, "SELECT * FROM inventory", as_alice, false)?;
+        assert_eq!(result[0].data.len(), 1);
+        let err = run_transactions(&db, "DELETE FROM inventory", as_alice, false).unwrap_err();
+        assert!(err.get_auth_error().is_some(), "Reading doesn't allow writing");
+        run_transactions(&db, insert_feedback, as_alice, false)?;
+        let err = run_transactions(&db, "SELECT * FROM inventory", as_bob, false).unwrap_err();
+        assert!(
+            err.get_auth_error().is_some(),
+            "A listed table is closed to other roles"
+        );
+
+        run_transactions(&db, "REVOKE SELECT ON inventory FROM dashboard", auth, false)?;
+        let err = run_transactions(&db, "SELECT * FROM inventory", as_alice, false).unwrap_err();
+        assert!(err.get_auth_error().is_some());
+        let result = run_transactions(&db, "SELECT * FROM _feedback", auth, false)?;
+        assert_eq!(result[0].data.len(), 1);
+
+        assert!(run_transactions(&db, "GRANT SELECT ON inventory TO owner", auth, false).is_err());
+        assert!(run_transactions(&db, "GRANT SELECT ON missing TO dashboard", auth, false).is_err());
+
+        Ok(())
+    }
 }
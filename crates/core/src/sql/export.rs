@@ -0,0 +1,591 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Float32Builder, Float64Builder, Int16Builder, Int32Builder, Int64Builder,
+    Int8Builder, StringBuilder, UInt16Builder, UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+#[cfg(feature = "parquet")]
+use parquet::{
+    arrow::{arrow_to_parquet_schema, ArrowWriter},
+    basic::Compression,
+    errors::ParquetError,
+    file::properties::WriterProperties,
+    schema::types::SchemaDescriptor,
+};
+use serde_json::Value;
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::relation::Table;
+use spacetimedb_sats::{
+    json, AlgebraicType, AlgebraicValue, ArrayValue, BuiltinType, BuiltinValue, ProductType, ProductValue,
+    WithTypespace,
+};
+use spacetimedb_vm::errors::ErrorVm;
+use spacetimedb_vm::expr::{AuthAccess, CrudExpr, QueryCode, SourceExpr};
+use spacetimedb_vm::rel_ops::RelOps;
+
+use crate::database_instance_context_controller::DatabaseInstanceContextController;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, DatabaseError, PlanError};
+use crate::sql::compiler::compile_sql;
+use crate::vm::build_query;
+
+/// An encoding for exporting the results of a query.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values, with a header of column names.
+    Csv,
+    /// The Apache Arrow IPC streaming format.
+    Arrow,
+    /// The Apache Parquet file format, compressed with Snappy.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The media type of the format.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+            #[cfg(feature = "parquet")]
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// Rows per Arrow record batch.
+const BATCH_ROWS: usize = 8192;
+
+/// Rows per Parquet row group, which is buffered in memory until it's full.
+#[cfg(feature = "parquet")]
+const ROW_GROUP_ROWS: usize = 16 * BATCH_ROWS;
+
+/// Writes the results of the `SQL` query `sql_text` in the specified `database_instance_id` to `out`,
+/// encoding each row as it's read from the database rather than collecting them first.
+///
+/// Nothing is written if the query can't be run,
+/// but an error can still be returned after some rows have been.
+pub fn export(
+    db_inst_ctx_controller: &DatabaseInstanceContextController,
+    database_instance_id: u64,
+    sql_text: String,
+    auth: AuthCtx,
+    format: ExportFormat,
+    out: impl Write + Send,
+) -> Result<(), DBError> {
+    if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
+        export_query(&database_instance_context.relational_db, &sql_text, auth, format, out)
+    } else {
+        Err(DatabaseError::NotFound(database_instance_id).into())
+    }
+}
+
+pub(crate) fn export_query(
+    db: &RelationalDB,
+    sql_text: &str,
+    auth: AuthCtx,
+    format: ExportFormat,
+    out: impl Write + Send,
+) -> Result<(), DBError> {
+    let mut tx = db.begin_tx();
+    let res = compile_sql(db, &tx, sql_text).and_then(|mut ast| {
+        let query = match (ast.pop(), ast.is_empty()) {
+            (Some(CrudExpr::Query(query)), true) => query,
+            _ => {
+                return Err(DBError::Plan {
+                    sql: sql_text.to_string(),
+                    error: PlanError::Unsupported {
+                        feature: "Exporting anything but the results of a single query".into(),
+                    },
+                })
+            }
+        };
+        let table = match query.source {
+            SourceExpr::MemTable(x) => Table::MemTable(x),
+            SourceExpr::DbTable(x) => Table::DbTable(x),
+        };
+        let query = QueryCode {
+            table,
+            query: query.query,
+        };
+        query
+            .check_auth(auth.owner, auth.caller)
+            .map_err(|err| DBError::VmUser(ErrorVm::Auth(err).into()))?;
+
+        let mut rows = build_query(db, &mut tx, query)?;
+        let mut encoder = Encoder::new(format, &rows.head().ty(), out)?;
+        while let Some(row) = rows.next()? {
+            encoder.write_row(&row.data)?;
+        }
+        encoder.finish()
+    });
+    db.rollback_tx(tx);
+    res
+}
+
+enum Encoder<W: Write + Send> {
+    Csv(CsvEncoder<W>),
+    Arrow(ArrowEncoder<W>),
+}
+
+impl<W: Write + Send> Encoder<W> {
+    fn new(format: ExportFormat, schema: &ProductType, out: W) -> Result<Self, DBError> {
+        Ok(match format {
+            ExportFormat::Csv => Self::Csv(CsvEncoder::new(schema, out)?),
+            ExportFormat::Arrow => Self::Arrow(ArrowEncoder::new(schema, |schema| BatchWriter::ipc(out, schema))?),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => {
+                Self::Arrow(ArrowEncoder::new(schema, |schema| BatchWriter::parquet(out, schema))?)
+            }
+        })
+    }
+
+    fn write_row(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        match self {
+            Self::Csv(x) => x.write_row(row),
+            Self::Arrow(x) => x.write_row(row),
+        }
+    }
+
+    fn finish(self) -> Result<(), DBError> {
+        match self {
+            Self::Csv(x) => x.finish(),
+            Self::Arrow(x) => x.finish(),
+        }
+    }
+}
+
+/// The name of the `i`th column of `schema`.
+fn column_name(schema: &ProductType, i: usize) -> String {
+    schema.elements[i].name.clone().unwrap_or_else(|| i.to_string())
+}
+
+/// Formats `value`, of type `ty`, as text:
+/// strings as they are, `none` as the empty string, and anything else as its [canonical JSON](json).
+fn text_of(ty: &AlgebraicType, value: &AlgebraicValue) -> String {
+    match json::to_value(WithTypespace::empty(ty), value) {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Writes rows as CSV, as specified by RFC 4180.
+struct CsvEncoder<W> {
+    out: W,
+    types: Vec<AlgebraicType>,
+}
+
+impl<W: Write> CsvEncoder<W> {
+    fn new(schema: &ProductType, out: W) -> Result<Self, DBError> {
+        let mut this = Self {
+            out,
+            types: schema.elements.iter().map(|e| e.algebraic_type.clone()).collect(),
+        };
+        this.write_record((0..schema.elements.len()).map(|i| column_name(schema, i)))?;
+        Ok(this)
+    }
+
+    fn write_record(&mut self, fields: impl Iterator<Item = String>) -> Result<(), DBError> {
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+            if field.contains([',', '"', '\r', '\n']) {
+                write!(self.out, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.out.write_all(field.as_bytes())?;
+            }
+        }
+        self.out.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        let fields = self
+            .types
+            .iter()
+            .zip(&row.elements)
+            .map(|(ty, value)| text_of(ty, value))
+            .collect::<Vec<_>>();
+        self.write_record(fields.into_iter())
+    }
+
+    fn finish(mut self) -> Result<(), DBError> {
+        Ok(self.out.flush()?)
+    }
+}
+
+fn arrow_error(err: ArrowError) -> DBError {
+    DBError::Other(err.into())
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_error(err: ParquetError) -> DBError {
+    DBError::Other(err.into())
+}
+
+/// Returns the Arrow schema of rows of the type `schema`, and the columns to build batches of them in.
+fn arrow_schema(schema: &ProductType) -> (SchemaRef, Vec<ArrowColumn>) {
+    let (fields, columns): (Vec<_>, Vec<_>) = schema
+        .elements
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let column = ArrowColumn::new(&e.algebraic_type);
+            let field = Field::new(column_name(schema, i), column.data_type(), column.nullable);
+            (field, column)
+        })
+        .unzip();
+    (Arc::new(Schema::new(fields)), columns)
+}
+
+/// Returns the schema of a Parquet file of rows of the type `schema`, as an export would write it.
+///
+/// Columns are typed as in [Arrow](ArrowColumn), with options as optional columns.
+#[cfg(feature = "parquet")]
+pub fn parquet_schema(schema: &ProductType) -> Result<SchemaDescriptor, DBError> {
+    arrow_to_parquet_schema(&arrow_schema(schema).0).map_err(parquet_error)
+}
+
+/// Where an [`ArrowEncoder`] writes its record batches.
+enum BatchWriter<W: Write + Send> {
+    Ipc(StreamWriter<W>),
+    #[cfg(feature = "parquet")]
+    Parquet(ArrowWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    fn ipc(out: W, schema: &SchemaRef) -> Result<Self, DBError> {
+        StreamWriter::try_new(out, schema).map(Self::Ipc).map_err(arrow_error)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn parquet(out: W, schema: &SchemaRef) -> Result<Self, DBError> {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .build();
+        ArrowWriter::try_new(out, schema.clone(), Some(props))
+            .map(Self::Parquet)
+            .map_err(parquet_error)
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), DBError> {
+        match self {
+            Self::Ipc(w) => w.write(batch).map_err(arrow_error),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(w) => w.write(batch).map_err(parquet_error),
+        }
+    }
+
+    /// Writes the end of the stream or file, and returns the writer it was written to.
+    fn finish(self) -> Result<W, DBError> {
+        match self {
+            Self::Ipc(mut w) => {
+                w.finish().map_err(arrow_error)?;
+                w.into_inner().map_err(arrow_error)
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(w) => w.into_inner().map_err(parquet_error),
+        }
+    }
+}
+
+/// Writes rows as Arrow record batches of [`BATCH_ROWS`],
+/// to an Arrow IPC stream or, with the `parquet` feature, a Parquet file.
+struct ArrowEncoder<W: Write + Send> {
+    writer: BatchWriter<W>,
+    schema: SchemaRef,
+    columns: Vec<ArrowColumn>,
+    rows: usize,
+}
+
+impl<W: Write + Send> ArrowEncoder<W> {
+    fn new(
+        schema: &ProductType,
+        writer: impl FnOnce(&SchemaRef) -> Result<BatchWriter<W>, DBError>,
+    ) -> Result<Self, DBError> {
+        let (schema, columns) = arrow_schema(schema);
+        let writer = writer(&schema)?;
+        Ok(Self {
+            writer,
+            schema,
+            columns,
+            rows: 0,
+        })
+    }
+
+    fn write_row(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        for (column, value) in self.columns.iter_mut().zip(&row.elements) {
+            column.append(value);
+        }
+        self.rows += 1;
+        if self.rows == BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), DBError> {
+        let arrays = self.columns.iter_mut().map(|c| c.finish()).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays).map_err(arrow_error)?;
+        self.writer.write(&batch)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), DBError> {
+        if self.rows > 0 {
+            self.write_batch()?;
+        }
+        Ok(self.writer.finish()?.flush()?)
+    }
+}
+
+/// A column of an Arrow record batch being built.
+///
+/// Options are nullable columns of their value's type.
+/// Types with no counterpart in Arrow are strings:
+/// 128-bit integers in decimal, and anything else as its [canonical JSON](json).
+struct ArrowColumn {
+    /// The type of the column, or of the value of its options if it's nullable.
+    ty: AlgebraicType,
+    nullable: bool,
+    builder: ColumnBuilder,
+}
+
+enum ColumnBuilder {
+    Bool(BooleanBuilder),
+    I8(Int8Builder),
+    U8(UInt8Builder),
+    I16(Int16Builder),
+    U16(UInt16Builder),
+    I32(Int32Builder),
+    U32(UInt32Builder),
+    I64(Int64Builder),
+    U64(UInt64Builder),
+    F32(Float32Builder),
+    F64(Float64Builder),
+    Binary(BinaryBuilder),
+    Utf8(StringBuilder),
+}
+
+/// Runs `$e` on the builder of any variant of [`ColumnBuilder`], bound to `$b`.
+macro_rules! with_builder {
+    ($builder:expr, $b:ident => $e:expr) => {
+        match $builder {
+            ColumnBuilder::Bool($b) => $e,
+            ColumnBuilder::I8($b) => $e,
+            ColumnBuilder::U8($b) => $e,
+            ColumnBuilder::I16($b) => $e,
+            ColumnBuilder::U16($b) => $e,
+            ColumnBuilder::I32($b) => $e,
+            ColumnBuilder::U32($b) => $e,
+            ColumnBuilder::I64($b) => $e,
+            ColumnBuilder::U64($b) => $e,
+            ColumnBuilder::F32($b) => $e,
+            ColumnBuilder::F64($b) => $e,
+            ColumnBuilder::Binary($b) => $e,
+            ColumnBuilder::Utf8($b) => $e,
+        }
+    };
+}
+
+impl ArrowColumn {
+    fn new(ty: &AlgebraicType) -> Self {
+        let (ty, nullable) = match ty {
+            AlgebraicType::Sum(sum) => match sum.as_option() {
+                Some(some_ty) => (some_ty.clone(), true),
+                None => (ty.clone(), false),
+            },
+            _ => (ty.clone(), false),
+        };
+        let builder = match &ty {
+            AlgebraicType::Builtin(BuiltinType::Bool) => ColumnBuilder::Bool(BooleanBuilder::new()),
+            AlgebraicType::Builtin(BuiltinType::I8) => ColumnBuilder::I8(Int8Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::U8) => ColumnBuilder::U8(UInt8Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::I16) => ColumnBuilder::I16(Int16Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::U16) => ColumnBuilder::U16(UInt16Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::I32) => ColumnBuilder::I32(Int32Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::U32) => ColumnBuilder::U32(UInt32Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::I64) => ColumnBuilder::I64(Int64Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::U64) => ColumnBuilder::U64(UInt64Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::F32) => ColumnBuilder::F32(Float32Builder::new()),
+            AlgebraicType::Builtin(BuiltinType::F64) => ColumnBuilder::F64(Float64Builder::new()),
+            ty if ty.is_bytes() => ColumnBuilder::Binary(BinaryBuilder::new()),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        };
+        Self { ty, nullable, builder }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self.builder {
+            ColumnBuilder::Bool(_) => DataType::Boolean,
+            ColumnBuilder::I8(_) => DataType::Int8,
+            ColumnBuilder::U8(_) => DataType::UInt8,
+            ColumnBuilder::I16(_) => DataType::Int16,
+            ColumnBuilder::U16(_) => DataType::UInt16,
+            ColumnBuilder::I32(_) => DataType::Int32,
+            ColumnBuilder::U32(_) => DataType::UInt32,
+            ColumnBuilder::I64(_) => DataType::Int64,
+            ColumnBuilder::U64(_) => DataType::UInt64,
+            ColumnBuilder::F32(_) => DataType::Float32,
+            ColumnBuilder::F64(_) => DataType::Float64,
+            ColumnBuilder::Binary(_) => DataType::Binary,
+            ColumnBuilder::Utf8(_) => DataType::Utf8,
+        }
+    }
+
+    fn append(&mut self, value: &AlgebraicValue) {
+        let value = match value.as_sum() {
+            Some(sum) if self.nullable && sum.tag == 0 => &*sum.value,
+            _ if self.nullable => return with_builder!(&mut self.builder, b => b.append_null()),
+            _ => value,
+        };
+        match (&mut self.builder, value.as_builtin()) {
+            (ColumnBuilder::Bool(b), Some(BuiltinValue::Bool(v))) => b.append_value(*v),
+            (ColumnBuilder::I8(b), Some(BuiltinValue::I8(v))) => b.append_value(*v),
+            (ColumnBuilder::U8(b), Some(BuiltinValue::U8(v))) => b.append_value(*v),
+            (ColumnBuilder::I16(b), Some(BuiltinValue::I16(v))) => b.append_value(*v),
+            (ColumnBuilder::U16(b), Some(BuiltinValue::U16(v))) => b.append_value(*v),
+            (ColumnBuilder::I32(b), Some(BuiltinValue::I32(v))) => b.append_value(*v),
+            (ColumnBuilder::U32(b), Some(BuiltinValue::U32(v))) => b.append_value(*v),
+            (ColumnBuilder::I64(b), Some(BuiltinValue::I64(v))) => b.append_value(*v),
+            (ColumnBuilder::U64(b), Some(BuiltinValue::U64(v))) => b.append_value(*v),
+            (ColumnBuilder::F32(b), Some(BuiltinValue::F32(v))) => b.append_value((*v).into()),
+            (ColumnBuilder::F64(b), Some(BuiltinValue::F64(v))) => b.append_value((*v).into()),
+            (ColumnBuilder::Binary(b), Some(BuiltinValue::Array { val: ArrayValue::U8(v) })) => b.append_value(v),
+            (ColumnBuilder::Utf8(b), _) => b.append_value(text_of(&self.ty, value)),
+            // The value doesn't have the column's type, which the VM should never produce.
+            (b, _) => with_builder!(b, b => b.append_null()),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        with_builder!(&mut self.builder, b => Arc::new(b.finish()) as ArrayRef)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::vm::tests::create_table_with_rows;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use arrow_ipc::reader::StreamReader;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::product;
+
+    fn person_schema() -> ProductType {
+        ProductType::from_iter([
+            ("id", AlgebraicType::U64),
+            ("name", AlgebraicType::String),
+            ("nick", AlgebraicType::option(AlgebraicType::String)),
+        ])
+    }
+
+    fn create_data(db: &RelationalDB) -> ResultTest<()> {
+        let mut tx = db.begin_tx();
+        let head = person_schema();
+        let rows = [
+            product!(1u64, "alice", AlgebraicValue::OptionSome("al".into())),
+            product!(2u64, "bob, \"the builder\"", AlgebraicValue::OptionNone()),
+        ];
+        create_table_with_rows(db, &mut tx, "person", head, &rows)?;
+        db.commit_tx(tx)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_csv() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let mut out = Vec::new();
+        export_query(
+            &db,
+            "SELECT * FROM person",
+            AuthCtx::for_testing(),
+            ExportFormat::Csv,
+            &mut out,
+        )?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "id,name,nick\r\n1,alice,al\r\n2,\"bob, \"\"the builder\"\"\",\r\n"
+        );
+
+        let mut out = Vec::new();
+        let res = export_query(
+            &db,
+            "DELETE FROM person",
+            AuthCtx::for_testing(),
+            ExportFormat::Csv,
+            &mut out,
+        );
+        assert!(res.is_err());
+        assert!(out.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_arrow() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let mut out = Vec::new();
+        export_query(
+            &db,
+            "SELECT * FROM person WHERE id = 2",
+            AuthCtx::for_testing(),
+            ExportFormat::Arrow,
+            &mut out,
+        )?;
+
+        let batches = StreamReader::try_new(&out[..], None)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert!(batch.schema().field(2).is_nullable());
+        assert_eq!(batch.column(0).as_primitive::<UInt64Type>().value(0), 2);
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "bob, \"the builder\"");
+        assert!(batch.column(2).is_null(0));
+        Ok(())
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_export_parquet() -> ResultTest<()> {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let (db, _tmp_dir) = make_test_db()?;
+        create_data(&db)?;
+
+        let mut out = Vec::new();
+        export_query(
+            &db,
+            "SELECT * FROM person",
+            AuthCtx::for_testing(),
+            ExportFormat::Parquet,
+            &mut out,
+        )?;
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(out))?;
+        let schema = parquet_schema(&person_schema())?;
+        assert_eq!(reader.parquet_schema().num_columns(), schema.num_columns());
+        assert!(schema.column(2).self_type().is_optional());
+
+        let batches = reader.build()?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(0).as_primitive::<UInt64Type>().value(1), 2);
+        assert_eq!(batch.column(2).as_string::<i32>().value(0), "al");
+        assert!(batch.column(2).is_null(1));
+        Ok(())
+    }
+}
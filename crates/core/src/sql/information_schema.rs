@@ -0,0 +1,171 @@
+//! The `information_schema` views of the SQL standard,
+//! so tools written for other databases can discover the tables of a database.
+//!
+//! The views aren't stored: their rows are computed from the system tables
+//! when a query reads from them, which it can only do as the root of its `FROM` clause.
+
+use spacetimedb_lib::auth::{StAccess, StTableType};
+use spacetimedb_sats::{product, AlgebraicType, BuiltinType, ProductValue};
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::{ColumnSchema, TableSchema};
+use crate::db::relational_db::RelationalDB;
+use crate::error::DBError;
+
+/// The schema the views are qualified with, as in `information_schema.tables`.
+pub(crate) const INFORMATION_SCHEMA: &str = "information_schema";
+
+/// The `table_schema` of the tables defined by the module.
+const USER_SCHEMA: &str = "public";
+/// The `table_schema` of the system tables, like `st_table`.
+const SYSTEM_SCHEMA: &str = "system";
+
+const TABLES_VIEW: &str = "tables";
+const COLUMNS_VIEW: &str = "columns";
+
+/// Returns the schema and the rows of the view `name`,
+/// or `None` if it doesn't name one of the `information_schema` views.
+pub(crate) fn find_view(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    name: &str,
+) -> Result<Option<(TableSchema, Vec<ProductValue>)>, DBError> {
+    let name = name.to_ascii_lowercase();
+    let Some((INFORMATION_SCHEMA, view)) = name.split_once('.') else {
+        return Ok(None);
+    };
+    let schema = match view {
+        TABLES_VIEW => tables_schema(),
+        COLUMNS_VIEW => columns_schema(),
+        _ => return Ok(None),
+    };
+
+    let mut tables = db
+        .get_all_tables(tx)?
+        .into_iter()
+        .map(|table| {
+            let table_schema = match table.table_type {
+                StTableType::System => SYSTEM_SCHEMA,
+                StTableType::User => USER_SCHEMA,
+            };
+            (table_schema, "BASE TABLE", table)
+        })
+        .collect::<Vec<_>>();
+    tables.sort_by_key(|(_, _, table)| table.table_id);
+    tables.extend([tables_schema(), columns_schema()].map(|view| (INFORMATION_SCHEMA, "VIEW", view)));
+
+    let rows = if view == TABLES_VIEW {
+        tables
+            .iter()
+            .map(|(table_schema, table_type, table)| product!(*table_schema, table.table_name.as_str(), *table_type))
+            .collect()
+    } else {
+        tables
+            .iter()
+            .flat_map(|(table_schema, _, table)| {
+                table.columns.iter().enumerate().map(|(pos, column)| {
+                    let (data_type, is_nullable) = data_type(&column.col_type);
+                    product!(
+                        *table_schema,
+                        table.table_name.as_str(),
+                        column.col_name.as_str(),
+                        pos as u32 + 1,
+                        data_type,
+                        if is_nullable { "YES" } else { "NO" },
+                    )
+                })
+            })
+            .collect()
+    };
+    Ok(Some((schema, rows)))
+}
+
+/// `information_schema.tables`
+///
+/// | table_schema: String | table_name: String | table_type: String |
+/// |----------------------|--------------------|--------------------|
+/// | "public"             | "customers"        | "BASE TABLE"       |
+fn tables_schema() -> TableSchema {
+    view_schema(
+        TABLES_VIEW,
+        &[
+            ("table_schema", AlgebraicType::String),
+            ("table_name", AlgebraicType::String),
+            ("table_type", AlgebraicType::String),
+        ],
+    )
+}
+
+/// `information_schema.columns`
+///
+/// | table_schema: String | table_name: String | column_name: String | ordinal_position: u32 | data_type: String | is_nullable: String |
+/// |----------------------|--------------------|---------------------|-----------------------|-------------------|---------------------|
+/// | "public"             | "customers"        | "id"                | 1                     | "bigint unsigned" | "NO"                |
+fn columns_schema() -> TableSchema {
+    view_schema(
+        COLUMNS_VIEW,
+        &[
+            ("table_schema", AlgebraicType::String),
+            ("table_name", AlgebraicType::String),
+            ("column_name", AlgebraicType::String),
+            ("ordinal_position", AlgebraicType::U32),
+            ("data_type", AlgebraicType::String),
+            ("is_nullable", AlgebraicType::String),
+        ],
+    )
+}
+
+fn view_schema(name: &str, columns: &[(&str, AlgebraicType)]) -> TableSchema {
+    TableSchema {
+        // Views aren't stored, so they have no table of their own.
+        table_id: 0,
+        table_name: name.into(),
+        columns: columns
+            .iter()
+            .enumerate()
+            .map(|(col_id, (col_name, col_type))| ColumnSchema {
+                table_id: 0,
+                col_id: col_id as u32,
+                col_name: (*col_name).into(),
+                col_type: col_type.clone(),
+                is_autoinc: false,
+            })
+            .collect(),
+        indexes: vec![],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+/// The SQL name of the type `ty`, as accepted by `CREATE TABLE`, and whether it's nullable.
+///
+/// Types without an SQL equivalent, like products and sums, are `USER-DEFINED`,
+/// as composite types are in PostgreSQL.
+fn data_type(ty: &AlgebraicType) -> (&'static str, bool) {
+    if let AlgebraicType::Sum(sum) = ty {
+        if let Some(some_ty) = sum.as_option() {
+            return (data_type(some_ty).0, true);
+        }
+    }
+    let name = match ty {
+        AlgebraicType::Builtin(ty) => match ty {
+            BuiltinType::Bool => "boolean",
+            BuiltinType::I8 => "tinyint",
+            BuiltinType::U8 => "tinyint unsigned",
+            BuiltinType::I16 => "smallint",
+            BuiltinType::U16 => "smallint unsigned",
+            BuiltinType::I32 => "integer",
+            BuiltinType::U32 => "integer unsigned",
+            BuiltinType::I64 => "bigint",
+            BuiltinType::U64 => "bigint unsigned",
+            BuiltinType::I128 | BuiltinType::U128 => "numeric",
+            BuiltinType::F32 => "real",
+            BuiltinType::F64 => "double precision",
+            BuiltinType::String => "text",
+            BuiltinType::Array(_) => "ARRAY",
+            BuiltinType::Map(_) => "USER-DEFINED",
+        },
+        _ => "USER-DEFINED",
+    };
+    (name, false)
+}
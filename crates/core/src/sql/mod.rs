@@ -1,3 +1,7 @@
 pub mod ast;
 pub mod compiler;
+pub mod copy;
+pub mod ddl;
 pub mod execute;
+pub mod export;
+pub mod information_schema;
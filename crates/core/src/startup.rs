@@ -52,7 +52,8 @@ pub fn configure_tracing() {
     let subscriber = tracing_subscriber::Registry::default()
         .with(fmt_layer)
         .with(tracy_layer)
-        .with(flame_layer);
+        .with(flame_layer)
+        .with(otlp_layer());
 
     if cfg!(debug_assertions) {
         let (reload_layer, reload_handle) = tracing_subscriber::reload::Layer::new(env_filter_layer);
@@ -72,6 +73,36 @@ pub fn configure_tracing() {
     }
 }
 
+/// A layer exporting spans over OTLP to the collector at `SPACETIMEDB_OTLP_ENDPOINT`, if it's set.
+///
+/// The root span of each request has a `trace_id` attribute, as returned to HTTP clients,
+/// by which the spans of its reducer call, commit and subscription updates can be found.
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>() -> Option<impl tracing_subscriber::Layer<S>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry_otlp::WithExportConfig;
+
+    let endpoint = std::env::var("SPACETIMEDB_OTLP_ENDPOINT").ok()?;
+    let resource = opentelemetry::sdk::Resource::new([opentelemetry::KeyValue::new("service.name", "spacetimedb")]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(opentelemetry::sdk::trace::config().with_resource(resource))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .expect("failed to install the OTLP exporter");
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer() -> Option<tracing_subscriber::layer::Identity> {
+    if std::env::var_os("SPACETIMEDB_OTLP_ENDPOINT").is_some() {
+        eprintln!("SPACETIMEDB_OTLP_ENDPOINT is set, but this build doesn't have the `otlp` feature");
+    }
+    None
+}
+
 fn parse_from_file(file: &Path) -> EnvFilter {
     let conf = std::fs::read_to_string(file).unwrap_or_default();
     let directives = conf
@@ -0,0 +1,195 @@
+//! Change data capture: the row-level changes of committed transactions,
+//! streamed in commit order to external systems mirroring the database.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use spacetimedb_lib::Identity;
+use spacetimedb_sats::ProductValue;
+
+use crate::host::module_host::{DatabaseTableUpdate, ModuleEvent, TableOp};
+use crate::host::Timestamp;
+
+/// How many transactions a slow change stream can fall behind before it's cut off.
+pub const CHANGE_STREAM_CAPACITY: usize = 1024;
+
+/// The changes of a committed transaction, and the reducer call that made them.
+#[derive(Debug, Clone)]
+pub struct TransactionChanges {
+    /// The number of transactions in the commit log up to and including this one.
+    pub tx_offset: Option<u64>,
+    pub timestamp: Timestamp,
+    pub reducer: String,
+    pub sender: Identity,
+    pub tables: Vec<TableChanges>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableChanges {
+    pub table_name: String,
+    pub changes: Vec<RowChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Insert,
+    Delete,
+    Update,
+}
+
+#[derive(Debug, Clone)]
+pub struct RowChange {
+    pub op: ChangeOp,
+    /// The row before the change, for a delete or an update.
+    pub old_row: Option<ProductValue>,
+    /// The row after the change, for an insert or an update.
+    pub new_row: Option<ProductValue>,
+}
+
+/// A line of a change stream: a [`RowChange`], with the transaction it's part of.
+#[derive(Debug, Serialize)]
+pub struct RowChangeJson<'a> {
+    pub tx_offset: Option<u64>,
+    pub timestamp: Timestamp,
+    pub reducer: &'a str,
+    pub sender: Identity,
+    pub table: &'a str,
+    pub op: ChangeOp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_row: Option<&'a ProductValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_row: Option<&'a ProductValue>,
+}
+
+impl TransactionChanges {
+    /// Collect the changes of the committed `event`.
+    ///
+    /// `key_column` gives the unique column of a table, if any, by which
+    /// a deleted and an inserted row are known to be the same row, updated.
+    pub fn new(event: &ModuleEvent, mut key_column: impl FnMut(u32) -> Option<usize>) -> Option<Self> {
+        let update = event.status.database_update()?;
+        let tables = update
+            .tables
+            .iter()
+            .map(|table| TableChanges {
+                table_name: table.table_name.clone(),
+                changes: row_changes(table, key_column(table.table_id)),
+            })
+            .collect();
+        Some(Self {
+            tx_offset: update.tx_offset,
+            timestamp: event.timestamp,
+            reducer: event.function_call.reducer.clone(),
+            sender: event.caller_identity,
+            tables,
+        })
+    }
+
+    /// The changes to the tables accepted by `filter`, as the lines of a change stream.
+    pub fn to_json<'a>(&'a self, filter: impl Fn(&str) -> bool + 'a) -> impl Iterator<Item = RowChangeJson<'a>> + 'a {
+        self.tables
+            .iter()
+            .filter(move |table| filter(&table.table_name))
+            .flat_map(move |table| {
+                table.changes.iter().map(move |change| RowChangeJson {
+                    tx_offset: self.tx_offset,
+                    timestamp: self.timestamp,
+                    reducer: &self.reducer,
+                    sender: self.sender,
+                    table: &table.table_name,
+                    op: change.op,
+                    old_row: change.old_row.as_ref(),
+                    new_row: change.new_row.as_ref(),
+                })
+            })
+    }
+}
+
+/// The changes of `table`, pairing the delete and insert of rows
+/// with the same value in the unique column `key_col` into an update.
+fn row_changes(table: &DatabaseTableUpdate, key_col: Option<usize>) -> Vec<RowChange> {
+    let key = |op: &TableOp| key_col.and_then(|col| op.row.elements.get(col));
+
+    let mut deletes = HashMap::new();
+    for (i, op) in table.ops.iter().enumerate() {
+        if let (0, Some(key)) = (op.op_type, key(op)) {
+            deletes.insert(key, i);
+        }
+    }
+    let mut updated = HashMap::new();
+    for (i, op) in table.ops.iter().enumerate() {
+        if op.op_type != 1 {
+            continue;
+        }
+        if let Some(delete) = key(op).and_then(|key| deletes.remove(key)) {
+            updated.insert(delete, i);
+            updated.insert(i, delete);
+        }
+    }
+
+    table
+        .ops
+        .iter()
+        .enumerate()
+        .filter_map(|(i, op)| match (op.op_type, updated.get(&i)) {
+            // Reported along with the insert.
+            (0, Some(_)) => None,
+            (0, None) => Some(RowChange {
+                op: ChangeOp::Delete,
+                old_row: Some(op.row.clone()),
+                new_row: None,
+            }),
+            (_, Some(&delete)) => Some(RowChange {
+                op: ChangeOp::Update,
+                old_row: Some(table.ops[delete].row.clone()),
+                new_row: Some(op.row.clone()),
+            }),
+            (_, None) => Some(RowChange {
+                op: ChangeOp::Insert,
+                old_row: None,
+                new_row: Some(op.row.clone()),
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::product;
+
+    fn op(op_type: u8, row: ProductValue) -> TableOp {
+        TableOp {
+            op_type,
+            row_pk: vec![],
+            row,
+        }
+    }
+
+    #[test]
+    fn pairs_updates_by_key() {
+        let table = DatabaseTableUpdate {
+            table_id: 0,
+            table_name: "Person".into(),
+            ops: vec![
+                op(0, product!(1u32, "Alice")),
+                op(0, product!(2u32, "Bob")),
+                op(1, product!(1u32, "Alicia")),
+                op(1, product!(3u32, "Carol")),
+            ],
+            updates: vec![],
+        };
+
+        let ops = |key_col| row_changes(&table, key_col).iter().map(|c| c.op).collect::<Vec<_>>();
+        assert_eq!(ops(Some(0)), [ChangeOp::Delete, ChangeOp::Update, ChangeOp::Insert]);
+        assert_eq!(
+            ops(None),
+            [ChangeOp::Delete, ChangeOp::Delete, ChangeOp::Insert, ChangeOp::Insert]
+        );
+
+        let update = &row_changes(&table, Some(0))[1];
+        assert_eq!(update.old_row, Some(product!(1u32, "Alice")));
+        assert_eq!(update.new_row, Some(product!(1u32, "Alicia")));
+    }
+}
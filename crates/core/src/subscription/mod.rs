@@ -1,3 +1,4 @@
+pub mod cdc;
 pub mod module_subscription_actor;
 pub mod query;
 #[allow(clippy::module_inception)] // it's right this isn't ideal :/
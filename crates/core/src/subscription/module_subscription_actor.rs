@@ -1,24 +1,31 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 use super::{
-    query::compile_query,
+    cdc::{TransactionChanges, CHANGE_STREAM_CAPACITY},
+    query::{compile_query, Query},
     subscription::{QuerySet, Subscription},
 };
 use crate::db::datastore::locking_tx_datastore::MutTxId;
-use crate::host::module_host::{EventStatus, ModuleEvent};
-use crate::protobuf::client_api::Subscribe;
+use crate::host::module_host::{DatabaseUpdate, EventStatus, ModuleEvent};
+use crate::protobuf::client_api::{OneOffQuery, Subscribe, SubscribeQuery};
+use crate::sql::execute::run_transactions;
 use crate::{
     client::{
-        messages::{CachedMessage, SubscriptionUpdateMessage, TransactionUpdateMessage},
+        messages::{
+            CachedMessage, OneOffQueryResultMessage, SubscriptionResumedMessage, SubscriptionUpdateMessage,
+            TransactionUpdateMessage,
+        },
         ClientActorId, ClientConnectionSender,
     },
     host::NoSuchModule,
 };
 use crate::{db::relational_db::RelationalDB, error::DBError};
-use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::Identity;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::Instrument;
 
 #[derive(Debug)]
 enum ModuleSubscriptionCommand {
@@ -29,34 +36,69 @@ enum ModuleSubscriptionCommand {
     RemoveSubscriber {
         client_id: ClientActorId,
     },
+    AddNamedQuery {
+        sender: ClientConnectionSender,
+        query: SubscribeQuery,
+    },
+    RemoveNamedQuery {
+        sender: ClientConnectionSender,
+        name: String,
+    },
+    OneOffQuery {
+        sender: ClientConnectionSender,
+        query: OneOffQuery,
+    },
+    HandOver {
+        to: ModuleSubscriptionManager,
+        done: oneshot::Sender<()>,
+    },
+    TakeOver {
+        clients: Vec<(ClientConnectionSender, ClientQueries)>,
+    },
+    Resync {
+        sender: ClientConnectionSender,
+    },
 }
 
+/// The queries of a client, with the names of those added by `SubscribeQuery`.
+type ClientQueries = Vec<(Option<String>, Query)>;
+
+/// How many of the latest transactions with changes are kept, to be replayed to the clients resuming their subscriptions.
+const RESUME_WINDOW: usize = 1024;
+
 #[derive(Debug)]
 enum Command {
     Subscription(ModuleSubscriptionCommand),
-    BroadcastCommitEvent { event: ModuleEvent },
+    /// A committed event, and the span of the reducer call that committed it.
+    BroadcastCommitEvent {
+        event: ModuleEvent,
+        span: tracing::Span,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct ModuleSubscriptionManager {
     tx: mpsc::UnboundedSender<ModuleSubscriptionCommand>,
+    changes_tx: broadcast::Sender<Arc<TransactionChanges>>,
 }
 
 #[derive(Clone)]
 pub struct SubscriptionEventSender {
-    commit_event_tx: mpsc::UnboundedSender<ModuleEvent>,
+    commit_event_tx: mpsc::UnboundedSender<(ModuleEvent, tracing::Span)>,
 }
 
 impl ModuleSubscriptionManager {
     pub fn spawn(relational_db: Arc<RelationalDB>, owner_identity: Identity) -> (Self, SubscriptionEventSender) {
         let (tx, mut rx) = mpsc::unbounded_channel();
         let (commit_event_tx, mut commit_event_rx) = mpsc::unbounded_channel();
+        let (changes_tx, _) = broadcast::channel(CHANGE_STREAM_CAPACITY);
+        let actor_changes_tx = changes_tx.clone();
         tokio::spawn(async move {
-            let mut actor = ModuleSubscriptionActor::new(relational_db, owner_identity);
+            let mut actor = ModuleSubscriptionActor::new(relational_db, owner_identity, actor_changes_tx);
             loop {
                 let command = tokio::select! {
                     event = commit_event_rx.recv() => match event {
-                        Some(event) => Command::BroadcastCommitEvent { event },
+                        Some((event, span)) => Command::BroadcastCommitEvent { event, span },
                         // the module has exited
                         None => break,
                     },
@@ -67,7 +109,7 @@ impl ModuleSubscriptionManager {
                 }
             }
         });
-        (Self { tx }, SubscriptionEventSender { commit_event_tx })
+        (Self { tx, changes_tx }, SubscriptionEventSender { commit_event_tx })
     }
 
     pub fn add_subscriber(&self, sender: ClientConnectionSender, subscription: Subscribe) -> Result<(), NoSuchModule> {
@@ -81,13 +123,65 @@ impl ModuleSubscriptionManager {
             .send(ModuleSubscriptionCommand::RemoveSubscriber { client_id })
             .map_err(|_| NoSuchModule)
     }
+
+    pub fn add_named_query(&self, sender: ClientConnectionSender, query: SubscribeQuery) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::AddNamedQuery { sender, query })
+            .map_err(|_| NoSuchModule)
+    }
+
+    pub fn remove_named_query(&self, sender: ClientConnectionSender, name: String) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::RemoveNamedQuery { sender, name })
+            .map_err(|_| NoSuchModule)
+    }
+
+    /// Sends the client the rows matched by its queries afresh, as the database has them by then.
+    ///
+    /// This is how a client that fell behind on its updates, some of which were dropped, catches up.
+    pub fn resync(&self, sender: ClientConnectionSender) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::Resync { sender })
+            .map_err(|_| NoSuchModule)
+    }
+
+    /// Runs a read-only query once for the client, sending it the result.
+    ///
+    /// It's run in turn with the subscription updates, so the client has received
+    /// the updates for every transaction the query can see.
+    pub fn one_off_query(&self, sender: ClientConnectionSender, query: OneOffQuery) -> Result<(), NoSuchModule> {
+        self.tx
+            .send(ModuleSubscriptionCommand::OneOffQuery { sender, query })
+            .map_err(|_| NoSuchModule)
+    }
+
+    /// Move the subscriptions of every client to `to`, which sends each of them
+    /// the rows they're subscribed to afresh, as the database has them by then.
+    ///
+    /// This is how the clients of a module stay subscribed when it's swapped for a new version.
+    pub async fn hand_over(&self, to: &ModuleSubscriptionManager) -> Result<(), NoSuchModule> {
+        let (done, handed_over) = oneshot::channel();
+        self.tx
+            .send(ModuleSubscriptionCommand::HandOver { to: to.clone(), done })
+            .map_err(|_| NoSuchModule)?;
+        handed_over.await.map_err(|_| NoSuchModule)
+    }
+
+    /// Receive the changes of each transaction committed by a reducer from now on, in commit order.
+    ///
+    /// A receiver that falls [`CHANGE_STREAM_CAPACITY`] transactions behind misses the oldest of them.
+    pub fn subscribe_to_changes(&self) -> broadcast::Receiver<Arc<TransactionChanges>> {
+        self.changes_tx.subscribe()
+    }
 }
 
 impl SubscriptionEventSender {
     pub async fn broadcast_event(&self, client: Option<&ClientConnectionSender>, mut event: ModuleEvent) {
         match event.status {
             EventStatus::Committed(_) => {
-                self.commit_event_tx.send(event).expect("subscription actor panicked");
+                self.commit_event_tx
+                    .send((event, tracing::Span::current()))
+                    .expect("subscription actor panicked");
             }
             EventStatus::Failed(_) => {
                 if let Some(client) = client {
@@ -112,15 +206,32 @@ impl SubscriptionEventSender {
 struct ModuleSubscriptionActor {
     relational_db: Arc<RelationalDB>,
     subscriptions: Vec<Subscription>,
+    client_queries: HashMap<ClientActorId, ClientQueries>,
     owner_identity: Identity,
+    changes_tx: broadcast::Sender<Arc<TransactionChanges>>,
+    /// The latest events of transactions with changes, in commit order and without gaps between their tx offsets,
+    /// up to [`RESUME_WINDOW`] of them.
+    history: VecDeque<ModuleEvent>,
+}
+
+/// Returns the tx offset of the transaction of `event`, if it committed changes.
+fn tx_offset_of(event: &ModuleEvent) -> Option<u64> {
+    event.status.database_update().and_then(|update| update.tx_offset)
 }
 
 impl ModuleSubscriptionActor {
-    fn new(relational_db: Arc<RelationalDB>, owner_identity: Identity) -> Self {
+    fn new(
+        relational_db: Arc<RelationalDB>,
+        owner_identity: Identity,
+        changes_tx: broadcast::Sender<Arc<TransactionChanges>>,
+    ) -> Self {
         Self {
             relational_db,
             subscriptions: Vec::new(),
+            client_queries: HashMap::new(),
             owner_identity,
+            changes_tx,
+            history: VecDeque::new(),
         }
     }
 
@@ -132,7 +243,24 @@ impl ModuleSubscriptionActor {
             Command::Subscription(ModuleSubscriptionCommand::RemoveSubscriber { client_id }) => {
                 self.remove_subscriber(client_id)
             }
-            Command::BroadcastCommitEvent { event } => self.broadcast_commit_event(event).await?,
+            Command::Subscription(ModuleSubscriptionCommand::AddNamedQuery { sender, query }) => {
+                self.add_named_query(sender, query).await?
+            }
+            Command::Subscription(ModuleSubscriptionCommand::RemoveNamedQuery { sender, name }) => {
+                self.remove_named_query(sender, name).await?
+            }
+            Command::Subscription(ModuleSubscriptionCommand::OneOffQuery { sender, query }) => {
+                self.one_off_query(sender, query).await
+            }
+            Command::Subscription(ModuleSubscriptionCommand::HandOver { to, done }) => {
+                self.hand_over(to);
+                let _ = done.send(());
+            }
+            Command::Subscription(ModuleSubscriptionCommand::TakeOver { clients }) => self.take_over(clients).await?,
+            Command::Subscription(ModuleSubscriptionCommand::Resync { sender }) => self.resync(sender)?,
+            Command::BroadcastCommitEvent { event, span } => {
+                self.broadcast_commit_event(event).instrument(span).await?
+            }
         }
         Ok(())
     }
@@ -152,33 +280,109 @@ impl ModuleSubscriptionActor {
             .map(|query| compile_query(&self.relational_db, tx, &query))
             .collect::<Result<_, _>>()?;
 
-        let sub = match self.subscriptions.iter_mut().find(|s| s.queries == queries) {
-            Some(sub) => {
-                sub.subscribers.push(sender);
-                sub
-            }
-            None => {
-                self.subscriptions.push(Subscription {
-                    queries,
-                    subscribers: vec![sender],
-                });
-                self.subscriptions.last_mut().unwrap()
-            }
+        let resumed = match subscription.resume_from_tx_offset {
+            0 => false,
+            from => self.resume(&sender, &queries, from, tx)?,
+        };
+        let database_update = if resumed {
+            None
+        } else {
+            let mut database_update = queries.eval(&self.relational_db, tx, auth)?;
+            database_update.tx_offset = self.last_tx_offset();
+            Some(database_update)
         };
 
-        let database_update = sub.queries.eval(&self.relational_db, tx, auth)?;
-
-        let sender = sub.subscribers.last().unwrap();
+        self.client_queries
+            .insert(sender.id, queries.0.iter().map(|q| (None, q.clone())).collect());
+        self.join_subscription(sender.clone(), queries);
 
         // NOTE: It is important to send the state in this thread because if you spawn a new
         // thread it's possible for messages to get sent to the client out of order. If you do
         // spawn in another thread messages will need to be buffered until the state is sent out
         // on the wire
-        let _ = sender.send_message(SubscriptionUpdateMessage { database_update }).await;
+        if let Some(database_update) = database_update {
+            let _ = sender.send_snapshot(database_update);
+        }
 
         Ok(())
     }
 
+    /// Sends the client the updates to the rows matching `queries` of the transactions after `from`,
+    /// the tx offset it had got to before reconnecting, followed by the end of its resumed subscription.
+    ///
+    /// Returns `false`, having sent nothing, if some of those transactions are no longer in the history,
+    /// or the queries can't be evaluated against past transactions, and the client has to be sent every row afresh.
+    fn resume(
+        &mut self,
+        sender: &ClientConnectionSender,
+        queries: &QuerySet,
+        from: u64,
+        tx: &mut MutTxId,
+    ) -> Result<bool, DBError> {
+        let (Some(first), Some(last)) = (self.history.front().and_then(tx_offset_of), self.last_tx_offset()) else {
+            return Ok(false);
+        };
+        if from > last || from + 1 < first || !queries.is_replayable() {
+            return Ok(false);
+        }
+
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let mut key_columns = HashMap::new();
+        for event in self.history.iter_mut() {
+            let database_update = event.status.database_update().unwrap();
+            if database_update.tx_offset <= Some(from) {
+                continue;
+            }
+            let mut incr = queries.eval_incr(&self.relational_db, tx, database_update, auth)?;
+            incr.tx_offset = database_update.tx_offset;
+
+            // As for the subscribers when the transaction was broadcast.
+            let for_caller = event.return_value.is_some() && event.caller_identity == sender.id.identity;
+            if incr.tables.is_empty() && event.emitted_events.is_empty() && !for_caller {
+                continue;
+            }
+            if sender.column_diffs {
+                for table in &mut incr.tables {
+                    let key_col = match key_columns.entry(table.table_id) {
+                        Entry::Occupied(e) => *e.get(),
+                        Entry::Vacant(e) => *e.insert(key_column(&self.relational_db, tx, table.table_id)?),
+                    };
+                    if let Some(key_col) = key_col {
+                        table.diff_updates(key_col);
+                    }
+                }
+            }
+
+            let message = TransactionUpdateMessage {
+                event,
+                database_update: incr,
+            };
+            let _ = sender.send_subscription_update(message);
+        }
+        let _ = sender.send_subscription_update(SubscriptionResumedMessage { tx_offset: last });
+        Ok(true)
+    }
+
+    /// Returns the tx offset of the latest transaction with changes broadcast, if any since the actor started.
+    fn last_tx_offset(&self) -> Option<u64> {
+        self.history.back().and_then(tx_offset_of)
+    }
+
+    /// Adds the event of a transaction just broadcast to the history, if it committed changes,
+    /// dropping the oldest beyond [`RESUME_WINDOW`], or all of them if some transaction wasn't broadcast.
+    fn remember(&mut self, event: ModuleEvent) {
+        let Some(tx_offset) = tx_offset_of(&event) else {
+            return;
+        };
+        if self.last_tx_offset().map_or(false, |last| last + 1 != tx_offset) {
+            self.history.clear();
+        }
+        if self.history.len() == RESUME_WINDOW {
+            self.history.pop_front();
+        }
+        self.history.push_back(event);
+    }
+
     async fn add_subscription(
         &mut self,
         sender: ClientConnectionSender,
@@ -190,46 +394,311 @@ impl ModuleSubscriptionActor {
         self.relational_db.finish_tx(tx, result)
     }
 
+    fn hand_over(&mut self, to: ModuleSubscriptionManager) {
+        let mut clients = Vec::new();
+        for subscription in self.subscriptions.drain(..) {
+            for sender in subscription.subscribers {
+                if let Some(queries) = self.client_queries.remove(&sender.id) {
+                    clients.push((sender, queries));
+                }
+            }
+        }
+        self.client_queries.clear();
+        let _ = to.tx.send(ModuleSubscriptionCommand::TakeOver { clients });
+    }
+
+    async fn _take_over(
+        &mut self,
+        clients: Vec<(ClientConnectionSender, ClientQueries)>,
+        tx: &mut MutTxId,
+    ) -> Result<(), DBError> {
+        for (sender, queries) in clients {
+            let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+            let query_set: QuerySet = queries.iter().map(|(_, q)| q.clone()).collect();
+            let mut database_update = match query_set.eval(&self.relational_db, tx, auth) {
+                Ok(database_update) => database_update,
+                Err(e) => {
+                    log::warn!(
+                        "Dropping the subscription of client {} on taking it over: {e}",
+                        sender.id
+                    );
+                    continue;
+                }
+            };
+            database_update.tx_offset = self.last_tx_offset();
+            self.remove_subscriber(sender.id);
+            self.client_queries.insert(sender.id, queries);
+            self.join_subscription(sender.clone(), query_set);
+            let _ = sender.send_snapshot(database_update);
+        }
+        Ok(())
+    }
+
+    async fn take_over(&mut self, clients: Vec<(ClientConnectionSender, ClientQueries)>) -> Result<(), DBError> {
+        //Split logic to properly handle `Error` + `Tx`
+        let mut tx = self.relational_db.begin_tx();
+        let result = self._take_over(clients, &mut tx).await;
+        self.relational_db.finish_tx(tx, result)
+    }
+
+    fn _resync(&self, sender: ClientConnectionSender, tx: &mut MutTxId) -> Result<(), DBError> {
+        // The client may have unsubscribed, or disconnected, meanwhile.
+        let Some(queries) = self.client_queries.get(&sender.id) else {
+            return Ok(());
+        };
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let query_set: QuerySet = queries.iter().map(|(_, q)| q.clone()).collect();
+        let mut database_update = query_set.eval(&self.relational_db, tx, auth)?;
+        database_update.tx_offset = self.last_tx_offset();
+        let _ = sender.send_snapshot(database_update);
+        Ok(())
+    }
+
+    fn resync(&self, sender: ClientConnectionSender) -> Result<(), DBError> {
+        //Split logic to properly handle `Error` + `Tx`
+        let mut tx = self.relational_db.begin_tx();
+        let result = self._resync(sender, &mut tx);
+        self.relational_db.finish_tx(tx, result)
+    }
+
     fn remove_subscriber(&mut self, client_id: ClientActorId) {
+        self.leave_subscription(client_id);
+        self.client_queries.remove(&client_id);
+    }
+
+    /// Adds the client to the subscription to exactly `queries`, creating it if needed.
+    fn join_subscription(&mut self, sender: ClientConnectionSender, queries: QuerySet) {
+        match self.subscriptions.iter_mut().find(|s| s.queries == queries) {
+            Some(sub) => sub.subscribers.push(sender),
+            None => self.subscriptions.push(Subscription {
+                queries,
+                subscribers: vec![sender],
+            }),
+        }
+    }
+
+    fn leave_subscription(&mut self, client_id: ClientActorId) {
         self.subscriptions.retain_mut(|sub| {
             sub.remove_subscriber(client_id);
             !sub.subscribers.is_empty()
         })
     }
 
+    /// Moves the client to the subscription to its current `client_queries`.
+    fn update_subscription(&mut self, sender: ClientConnectionSender) {
+        self.leave_subscription(sender.id);
+        let queries: QuerySet = match self.client_queries.get(&sender.id) {
+            Some(queries) if !queries.is_empty() => queries.iter().map(|(_, q)| q.clone()).collect(),
+            _ => {
+                self.client_queries.remove(&sender.id);
+                return;
+            }
+        };
+        self.join_subscription(sender, queries);
+    }
+
+    /// Returns the rows matched by `query` that aren't matched by any of `others`.
+    fn eval_only(
+        &self,
+        tx: &mut MutTxId,
+        query: &Query,
+        others: QuerySet,
+        auth: AuthCtx,
+    ) -> Result<DatabaseUpdate, DBError> {
+        let mut rows = QuerySet(vec![query.clone()]).eval(&self.relational_db, tx, auth)?;
+        let seen = others.eval_incr(&self.relational_db, tx, &rows, auth)?;
+        let seen: HashSet<_> = seen
+            .tables
+            .iter()
+            .flat_map(|table| table.ops.iter().map(move |op| (table.table_id, &op.row_pk)))
+            .collect();
+        for table in &mut rows.tables {
+            let table_id = table.table_id;
+            table.ops.retain(|op| !seen.contains(&(table_id, &op.row_pk)));
+        }
+        rows.tables.retain(|table| !table.ops.is_empty());
+        Ok(rows)
+    }
+
+    /// Returns the queries of the client other than the one named `name`.
+    fn other_queries(&self, client_id: ClientActorId, name: &str) -> QuerySet {
+        self.client_queries
+            .get(&client_id)
+            .into_iter()
+            .flatten()
+            .filter(|(n, _)| n.as_deref() != Some(name))
+            .map(|(_, q)| q.clone())
+            .collect()
+    }
+
+    async fn _add_named_query(
+        &mut self,
+        sender: ClientConnectionSender,
+        SubscribeQuery { name, query_string }: SubscribeQuery,
+        tx: &mut MutTxId,
+    ) -> Result<(), DBError> {
+        let query = compile_query(&self.relational_db, tx, &query_string)?;
+        // Replacing a query is removing the old one, then adding the new one.
+        self._remove_named_query(sender.clone(), name.clone(), tx).await?;
+
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let database_update = self.eval_only(tx, &query, self.other_queries(sender.id, &name), auth)?;
+
+        self.client_queries
+            .entry(sender.id)
+            .or_default()
+            .push((Some(name), query));
+        self.update_subscription(sender.clone());
+
+        let _ = sender.send_subscription_update(SubscriptionUpdateMessage { database_update });
+
+        Ok(())
+    }
+
+    async fn add_named_query(&mut self, sender: ClientConnectionSender, query: SubscribeQuery) -> Result<(), DBError> {
+        let mut tx = self.relational_db.begin_tx();
+        let result = self._add_named_query(sender, query, &mut tx).await;
+        self.relational_db.finish_tx(tx, result)
+    }
+
+    async fn one_off_query(
+        &self,
+        sender: ClientConnectionSender,
+        OneOffQuery {
+            request_id,
+            query_string,
+        }: OneOffQuery,
+    ) {
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let result = run_transactions(&self.relational_db, &query_string, auth, true).map_err(|e| e.to_string());
+        let _ = sender
+            .send_message(OneOffQueryResultMessage { request_id, result })
+            .await;
+    }
+
+    async fn _remove_named_query(
+        &mut self,
+        sender: ClientConnectionSender,
+        name: String,
+        tx: &mut MutTxId,
+    ) -> Result<(), DBError> {
+        let Some(queries) = self.client_queries.get_mut(&sender.id) else {
+            return Ok(());
+        };
+        let Some(i) = queries.iter().position(|(n, _)| n.as_deref() == Some(&name)) else {
+            // The client isn't subscribed to a query by that name, so there's nothing to delete.
+            return Ok(());
+        };
+        let (_, query) = queries.remove(i);
+
+        let auth = AuthCtx::new(self.owner_identity, sender.id.identity);
+        let mut database_update = self.eval_only(tx, &query, self.other_queries(sender.id, &name), auth)?;
+        for table in &mut database_update.tables {
+            for op in &mut table.ops {
+                op.op_type = 0; // Delete
+            }
+        }
+        self.update_subscription(sender.clone());
+
+        let _ = sender.send_subscription_update(SubscriptionUpdateMessage { database_update });
+
+        Ok(())
+    }
+
+    async fn remove_named_query(&mut self, sender: ClientConnectionSender, name: String) -> Result<(), DBError> {
+        let mut tx = self.relational_db.begin_tx();
+        let result = self._remove_named_query(sender, name, &mut tx).await;
+        self.relational_db.finish_tx(tx, result)
+    }
+
     async fn _broadcast_commit_event(&mut self, mut event: ModuleEvent, tx: &mut MutTxId) -> Result<(), DBError> {
-        let futures = FuturesUnordered::new();
         let auth = AuthCtx::new(self.owner_identity, event.caller_identity);
+        let mut key_columns = HashMap::new();
+
+        if self.changes_tx.receiver_count() > 0 {
+            let mut key_column_err = None;
+            let changes = TransactionChanges::new(&event, |table_id| {
+                let key_col = match key_columns.entry(table_id) {
+                    Entry::Occupied(e) => Ok(*e.get()),
+                    Entry::Vacant(e) => key_column(&self.relational_db, tx, table_id).map(|col| *e.insert(col)),
+                };
+                key_col.unwrap_or_else(|e| {
+                    key_column_err = Some(e);
+                    None
+                })
+            });
+            if let Some(e) = key_column_err {
+                return Err(e);
+            }
+            if let Some(changes) = changes.filter(|changes| !changes.tables.is_empty()) {
+                let _ = self.changes_tx.send(Arc::new(changes));
+            }
+        }
 
         for subscription in &mut self.subscriptions {
             let database_update = event.status.database_update().unwrap();
-            let incr = subscription
+            let mut incr = subscription
                 .queries
                 .eval_incr(&self.relational_db, tx, database_update, auth)?;
+            incr.tx_offset = database_update.tx_offset;
 
-            if incr.tables.is_empty() {
+            // Events are delivered to every subscriber, even those whose rows didn't change,
+            // and so is a returned value to the subscriptions of its caller.
+            let for_caller = event.return_value.is_some()
+                && subscription
+                    .subscribers
+                    .iter()
+                    .any(|s| s.id.identity == event.caller_identity);
+            if incr.tables.is_empty() && event.emitted_events.is_empty() && !for_caller {
                 continue;
             }
 
+            let wants_diffs = subscription.subscribers.iter().any(|s| s.column_diffs);
+            let diffed = if wants_diffs {
+                let mut diffed = incr.clone();
+                for table in &mut diffed.tables {
+                    let key_col = match key_columns.entry(table.table_id) {
+                        Entry::Occupied(e) => *e.get(),
+                        Entry::Vacant(e) => *e.insert(key_column(&self.relational_db, tx, table.table_id)?),
+                    };
+                    if let Some(key_col) = key_col {
+                        table.diff_updates(key_col);
+                    }
+                }
+                Some(diffed)
+            } else {
+                None
+            };
+
             let message = TransactionUpdateMessage {
                 event: &mut event,
                 database_update: incr,
             };
             let mut message = CachedMessage::new(message);
 
-            for subscriber in &subscription.subscribers {
-                // rustc realllly doesn't like subscriber.send_message(message) here for weird
-                // lifetime reasons, even though it would be sound
-                let message = message.serialize(subscriber.protocol);
-                futures.push(subscriber.send(message).map(drop))
+            // A client that can't keep up has its updates dropped or held back, rather than holding up the others.
+            for subscriber in subscription.subscribers.iter().filter(|s| !s.column_diffs) {
+                let _ = subscriber.send_transaction_update(&mut message);
             }
-        }
 
-        futures.collect::<()>().await;
+            if let Some(database_update) = diffed {
+                let message = TransactionUpdateMessage {
+                    event: &mut event,
+                    database_update,
+                };
+                let mut message = CachedMessage::new(message);
 
+                for subscriber in subscription.subscribers.iter().filter(|s| s.column_diffs) {
+                    let _ = subscriber.send_transaction_update(&mut message);
+                }
+            }
+        }
+
+        self.remember(event);
         Ok(())
     }
 
+    #[tracing::instrument(skip_all)]
     async fn broadcast_commit_event(&mut self, event: ModuleEvent) -> Result<(), DBError> {
         //Split logic to properly handle `Error` + `Tx`
         let mut tx = self.relational_db.begin_tx();
@@ -237,3 +706,255 @@ impl ModuleSubscriptionActor {
         self.relational_db.finish_tx(tx, result)
     }
 }
+
+/// Returns the first unique column of the table `table_id`, if any,
+/// by which a deleted and an inserted row are known to be the same row, updated.
+fn key_column(relational_db: &RelationalDB, tx: &MutTxId, table_id: u32) -> Result<Option<usize>, DBError> {
+    let schema = relational_db.schema_for_table(tx, table_id)?;
+    Ok(schema
+        .indexes
+        .iter()
+        .filter(|index| index.is_unique)
+        .map(|index| index.col_id as usize)
+        .min())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientName, DataMessage, Outgoing, Protocol, SendQueueReceiver};
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::host::module_host::{DatabaseTableUpdate, EmittedEvent, ModuleFunctionCall, TableOp};
+    use crate::host::{EnergyDiff, Timestamp};
+    use crate::vm::tests::create_table_with_rows;
+    use serde_json::Value;
+    use spacetimedb_lib::data_key::ToDataKey;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_sats::{product, AlgebraicValue, BuiltinType, ProductType, ProductValue};
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    const QUERY: &str = "SELECT * FROM inventory WHERE inventory_id > 1";
+
+    /// Returns an actor over a database with the table `inventory` of `rows`, and the table's id.
+    fn actor(rows: &[ProductValue]) -> ResultTest<(ModuleSubscriptionActor, u32, TempDir)> {
+        let (db, tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let head = ProductType::from_iter([("inventory_id", BuiltinType::U64), ("name", BuiltinType::String)]);
+        let table_id = create_table_with_rows(&db, &mut tx, "inventory", head, rows)?;
+        db.commit_tx(tx)?;
+        let (changes_tx, _) = broadcast::channel(1);
+        let actor = ModuleSubscriptionActor::new(Arc::new(db), Identity::__dummy(), changes_tx);
+        Ok((actor, table_id, tmp_dir))
+    }
+
+    /// The event of the transaction at `tx_offset`, which inserted the item `inventory_id`.
+    fn insert(table_id: u32, tx_offset: u64, inventory_id: u64) -> ModuleEvent {
+        let row = product!(inventory_id, "item");
+        let table = DatabaseTableUpdate {
+            table_id,
+            table_name: "inventory".into(),
+            ops: vec![TableOp {
+                op_type: 1,
+                row_pk: row.to_data_key().to_bytes(),
+                row,
+            }],
+            updates: vec![],
+        };
+        ModuleEvent {
+            timestamp: Timestamp::now(),
+            caller_identity: Identity::__dummy(),
+            function_call: ModuleFunctionCall {
+                reducer: "insert".into(),
+                args: Default::default(),
+            },
+            status: EventStatus::Committed(DatabaseUpdate {
+                tables: vec![table],
+                tx_offset: Some(tx_offset),
+            }),
+            energy_quanta_used: EnergyDiff::ZERO,
+            host_execution_duration: Duration::ZERO,
+            emitted_events: Vec::new(),
+            return_value: None,
+        }
+    }
+
+    /// Returns the client `name`, and the receiving end of its queue.
+    fn client(name: u64) -> (ClientConnectionSender, SendQueueReceiver) {
+        let id = ClientActorId {
+            identity: Identity::__dummy(),
+            name: ClientName(name),
+        };
+        ClientConnectionSender::dummy_with_receiver(id, Protocol::Text)
+    }
+
+    /// Subscribes a new client to [`QUERY`], resuming from `from`, and returns what it's sent.
+    async fn subscribe(actor: &mut ModuleSubscriptionActor, from: u64) -> ResultTest<Vec<Value>> {
+        let (sender, mut rx) = client(from);
+        let subscription = Subscribe {
+            query_strings: vec![QUERY.into()],
+            resume_from_tx_offset: from,
+        };
+        actor.add_subscription(sender, subscription).await?;
+        Ok(received(&mut rx).await)
+    }
+
+    async fn received(rx: &mut SendQueueReceiver) -> Vec<Value> {
+        // The messages already queued are still received.
+        rx.close();
+        let mut messages = Vec::new();
+        while let Some(outgoing) = rx.recv().await {
+            match outgoing {
+                Outgoing::Message(DataMessage::Text(text)) => messages.push(serde_json::from_str(&text).unwrap()),
+                other => panic!("expected a text message, got {other:?}"),
+            }
+        }
+        messages
+    }
+
+    /// Returns the types of the row operations of a subscription update.
+    fn ops(message: &Value) -> Vec<&str> {
+        let tables = message["SubscriptionUpdate"]["table_updates"].as_array().unwrap();
+        tables
+            .iter()
+            .flat_map(|table| table["table_row_operations"].as_array().unwrap())
+            .map(|op| op["op"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn named_queries_send_only_the_rows_of_no_other_query() -> ResultTest<()> {
+        let rows = [product!(1u64, "health"), product!(2u64, "mana"), product!(3u64, "gold")];
+        let (mut actor, _, _tmp_dir) = actor(&rows)?;
+        let (sender, mut rx) = client(0);
+        let add = |name: &str, query_string: &str| SubscribeQuery {
+            name: name.into(),
+            query_string: query_string.into(),
+        };
+
+        actor
+            .add_named_query(sender.clone(), add("all", "SELECT * FROM inventory"))
+            .await?;
+        actor.add_named_query(sender.clone(), add("some", QUERY)).await?;
+        actor.remove_named_query(sender.clone(), "all".into()).await?;
+        actor.remove_named_query(sender.clone(), "some".into()).await?;
+        // The client is no longer subscribed to anything.
+        assert!(actor.subscriptions.is_empty());
+        assert!(!actor.client_queries.contains_key(&sender.id));
+
+        let messages = received(&mut rx).await;
+        assert_eq!(messages.len(), 4);
+        assert_eq!(ops(&messages[0]), ["insert"; 3]);
+        assert!(ops(&messages[1]).is_empty());
+        assert_eq!(ops(&messages[2]), ["delete"]);
+        assert_eq!(ops(&messages[3]), ["delete"; 2]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn one_off_queries_only_read() -> ResultTest<()> {
+        let rows = [product!(1u64, "health"), product!(2u64, "mana"), product!(3u64, "gold")];
+        let (actor, _, _tmp_dir) = actor(&rows)?;
+        let (sender, mut rx) = client(0);
+        let query = |request_id, query_string: &str| OneOffQuery {
+            request_id,
+            query_string: query_string.into(),
+        };
+
+        actor.one_off_query(sender.clone(), query(1, QUERY)).await;
+        actor
+            .one_off_query(sender.clone(), query(2, "DELETE FROM inventory"))
+            .await;
+        actor.one_off_query(sender, query(3, "SELECT * FROM inventory")).await;
+
+        let messages = received(&mut rx).await;
+        let results: Vec<_> = messages.iter().map(|message| &message["OneOffQueryResult"]).collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["request_id"], 1);
+        assert_eq!(results[0]["results"][0]["rows"].as_array().unwrap().len(), 2);
+        // A query that writes is refused, and has changed nothing.
+        assert_eq!(results[1]["request_id"], 2);
+        assert!(results[1]["error"].is_string());
+        assert_eq!(results[2]["results"][0]["rows"].as_array().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn events_are_sent_to_every_subscriber() -> ResultTest<()> {
+        let (mut actor, table_id, _tmp_dir) = actor(&[])?;
+        let (sender, mut rx) = client(0);
+        let subscription = Subscribe {
+            query_strings: vec![QUERY.into()],
+            resume_from_tx_offset: 0,
+        };
+        actor.add_subscription(sender, subscription).await?;
+
+        // Neither transaction inserts rows matching the query, but the first emits an event.
+        let mut event = insert(table_id, 1, 0);
+        event.emitted_events.push(EmittedEvent {
+            name: "Greeting".into(),
+            value: AlgebraicValue::String("Hello".into()),
+        });
+        actor.broadcast_commit_event(event).await?;
+        actor.broadcast_commit_event(insert(table_id, 2, 0)).await?;
+
+        let messages = received(&mut rx).await;
+        assert_eq!(messages.len(), 2);
+        let update = &messages[1]["TransactionUpdate"];
+        assert_eq!(update["subscription_update"]["tx_offset"], 1);
+        assert!(update["subscription_update"]["table_updates"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+        let emitted = &update["event"]["emitted_events"];
+        assert_eq!(emitted.as_array().unwrap().len(), 1);
+        assert_eq!(emitted[0]["name"], "Greeting");
+        assert_eq!(emitted[0]["data"], "Hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resumes_from_the_history() -> ResultTest<()> {
+        let (mut actor, table_id, _tmp_dir) = actor(&[])?;
+        actor.broadcast_commit_event(insert(table_id, 1, 2)).await?;
+        actor.broadcast_commit_event(insert(table_id, 2, 0)).await?;
+        actor.broadcast_commit_event(insert(table_id, 3, 3)).await?;
+
+        // Only the transactions after the one the client had got to, with rows matching its query, are replayed.
+        let messages = subscribe(&mut actor, 1).await?;
+        assert_eq!(messages.len(), 2);
+        let update = &messages[0]["TransactionUpdate"]["subscription_update"];
+        assert_eq!(update["tx_offset"], 3);
+        assert_eq!(update["table_updates"].as_array().unwrap().len(), 1);
+        let resumed = &messages[1]["SubscriptionUpdate"];
+        assert_eq!(resumed["tx_offset"], 3);
+        assert_eq!(resumed["resumed"], true);
+
+        // A client that's up to date is only told so.
+        let messages = subscribe(&mut actor, 3).await?;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["SubscriptionUpdate"]["resumed"], true);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_every_row_past_the_history() -> ResultTest<()> {
+        let (mut actor, table_id, _tmp_dir) = actor(&[])?;
+        actor.broadcast_commit_event(insert(table_id, 1, 2)).await?;
+        actor.broadcast_commit_event(insert(table_id, 2, 3)).await?;
+        // The transactions in between weren't broadcast, so the history starts over.
+        actor.broadcast_commit_event(insert(table_id, 5, 4)).await?;
+        assert_eq!(actor.history.len(), 1);
+
+        // Neither a client that missed transactions no longer in the history,
+        // nor one ahead of the history, can resume, and both are sent every row.
+        for from in [2, 9] {
+            let messages = subscribe(&mut actor, from).await?;
+            assert_eq!(messages.len(), 1);
+            let update = &messages[0]["SubscriptionUpdate"];
+            assert_eq!(update["tx_offset"], 5);
+            assert!(update.get("resumed").is_none());
+        }
+        Ok(())
+    }
+}
@@ -1,35 +1,394 @@
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, SubscriptionError};
-use crate::host::module_host::DatabaseTableUpdate;
+use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
 use crate::sql::compiler::compile_sql;
 use crate::sql::execute::execute_single_sql;
+use spacetimedb_lib::auth::StAccess;
+use spacetimedb_lib::error::AuthError;
 use spacetimedb_lib::identity::AuthCtx;
-use spacetimedb_lib::relation::{Column, FieldName, MemTable};
-use spacetimedb_sats::AlgebraicType;
-use spacetimedb_vm::expr::{Crud, CrudExpr, DbType, QueryExpr, SourceExpr};
+use spacetimedb_lib::relation::{Column, DbTable, FieldExpr, FieldName, Header, MemTable, RelValueRef};
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductValue};
+use spacetimedb_vm::errors::ErrorVm;
+use spacetimedb_vm::expr::{self, ColumnOp, Crud, CrudExpr, DbType, QueryExpr, SourceExpr};
+use spacetimedb_vm::rel_ops::RowOrder;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 
 pub enum QueryDef {
     Table(String),
     Sql(String),
 }
 
-#[derive(Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 pub struct Query {
     pub queries: Vec<QueryExpr>,
 }
 
-impl Query {
-    pub fn queries_of_table_id<'a>(&'a self, table: &'a DatabaseTableUpdate) -> impl Iterator<Item = QueryExpr> + '_ {
-        self.queries.iter().filter_map(move |x| {
-            if x.source.get_db_table().map(|x| x.table_id) == Some(table.table_id) {
-                let t = to_mem_table(x.clone(), table);
-                Some(t)
-            } else {
-                None
+/// A query that only filters the rows of a single table,
+/// so each row written to the table can be checked against it on its own,
+/// rather than by running the query over a [MemTable] of the rows, as with [`to_mem_table`].
+pub struct RowFilter<'a> {
+    table: &'a DbTable,
+    filters: Vec<&'a ColumnOp>,
+}
+
+impl<'a> RowFilter<'a> {
+    /// Returns the filter that `query` amounts to, if it's only selections over a table.
+    pub fn of(query: &'a QueryExpr) -> Option<Self> {
+        let table = query.source.get_db_table()?;
+        let filters = query
+            .query
+            .iter()
+            .filter_map(|q| match q {
+                expr::Query::Select(op) => Some(Some(op)),
+                // The order of the rows doesn't change which of them match.
+                expr::Query::OrderBy(_) => None,
+                _ => Some(None),
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { table, filters })
+    }
+
+    /// Checks that the caller of `auth` can read the table, as the VM does.
+    pub fn check_auth(&self, auth: AuthCtx) -> Result<(), DBError> {
+        if auth.owner == auth.caller || self.table.table_access == StAccess::Public {
+            return Ok(());
+        }
+        let named = self.table.head.table_name.clone();
+        Err(DBError::VmUser(ErrorVm::Auth(AuthError::TablePrivate { named }).into()))
+    }
+
+    /// Returns whether `row` passes every filter.
+    pub fn matches(&self, row: &ProductValue) -> Result<bool, DBError> {
+        let row = RelValueRef::new(&self.table.head, row);
+        for filter in &self.filters {
+            if !filter.compare(row)? {
+                return Ok(false);
             }
+        }
+        Ok(true)
+    }
+}
+
+/// A query for the rows of a table that join with a row of a second table,
+/// as in `SELECT lhs.* FROM lhs JOIN rhs ON lhs.a = rhs.b WHERE ...`,
+/// where the filters may be on the columns of either table.
+///
+/// Its results are maintained from the rows written to either table,
+/// by looking up the rows they join with in the other one,
+/// rather than by running the whole join again.
+pub struct SemiJoin<'a> {
+    lhs: &'a DbTable,
+    rhs: &'a DbTable,
+    col_lhs: usize,
+    col_rhs: usize,
+    /// The header of a row of `lhs` extended with a row of `rhs`, as seen by the filters.
+    head: Header,
+    filters: Vec<&'a ColumnOp>,
+}
+
+impl<'a> SemiJoin<'a> {
+    /// Returns the join that `query` amounts to, if it's a single join projected to the columns of its source.
+    pub fn of(query: &'a QueryExpr) -> Option<Self> {
+        let lhs = query.source.get_db_table()?;
+        let [expr::Query::JoinInner(join), rest @ .., expr::Query::Project(cols)] = &query.query[..] else {
+            return None;
+        };
+        let rhs = join.rhs.get_db_table()?;
+
+        let is_lhs_row = cols.len() == lhs.head.fields.len()
+            && cols
+                .iter()
+                .zip(&lhs.head.fields)
+                .all(|(col, field)| matches!(col, FieldExpr::Name(name) if *name == field.field));
+        if !is_lhs_row {
+            return None;
+        }
+
+        let filters = rest
+            .iter()
+            .map(|q| match q {
+                expr::Query::Select(op) => Some(op),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            lhs,
+            rhs,
+            col_lhs: lhs.head.column_pos(&join.col_lhs)?,
+            col_rhs: rhs.head.column_pos(&join.col_rhs)?,
+            head: lhs.head.extend(&rhs.head),
+            filters,
         })
     }
+
+    pub fn table_id(&self) -> u32 {
+        self.lhs.table_id
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.lhs.head.table_name
+    }
+
+    /// Checks that the caller of `auth` can read both tables, as the VM does.
+    pub fn check_auth(&self, auth: AuthCtx) -> Result<(), DBError> {
+        for table in [self.lhs, self.rhs] {
+            if auth.owner != auth.caller && table.table_access != StAccess::Public {
+                let named = table.head.table_name.clone();
+                return Err(DBError::VmUser(ErrorVm::Auth(AuthError::TablePrivate { named }).into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether `row` of `lhs` joins with any of `rhs_rows` and passes the filters.
+    fn joins_any<'r>(
+        &self,
+        row: &ProductValue,
+        rhs_rows: impl IntoIterator<Item = &'r ProductValue>,
+    ) -> Result<bool, DBError> {
+        for rhs_row in rhs_rows {
+            if row.elements[self.col_lhs] != rhs_row.elements[self.col_rhs] {
+                continue;
+            }
+            let mut joined = row.clone();
+            joined.elements.extend(rhs_row.elements.iter().cloned());
+            let joined = RelValueRef::new(&self.head, &joined);
+            let mut passes = true;
+            for filter in &self.filters {
+                if !filter.compare(joined)? {
+                    passes = false;
+                    break;
+                }
+            }
+            if passes {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the rows of `lhs` that enter the results, with an `op_type` of 1,
+    /// or leave them, with an `op_type` of 0, because of the rows written in `update`.
+    ///
+    /// `tx` sees the state of the database after the writes.
+    pub fn eval_delta(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &mut MutTxId,
+        update: &DatabaseUpdate,
+    ) -> Result<Vec<(u8, ProductValue)>, DBError> {
+        let ops_of = |table_id| {
+            update
+                .tables
+                .iter()
+                .filter(move |t| t.table_id == table_id)
+                .flat_map(|t| &t.ops)
+        };
+        let (lhs_inserted, lhs_deleted) = partition_ops(ops_of(self.lhs.table_id));
+        let (rhs_inserted, rhs_deleted) = partition_ops(ops_of(self.rhs.table_id));
+        if lhs_inserted.is_empty() && lhs_deleted.is_empty() && rhs_inserted.is_empty() && rhs_deleted.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // The rows of `lhs` whose membership may have changed:
+        // those written, and those that join with a row written to `rhs`.
+        let mut candidates: Vec<ProductValue> = lhs_inserted
+            .iter()
+            .chain(&lhs_deleted)
+            .map(|&row| row.clone())
+            .collect();
+        let mut seen: HashSet<ProductValue> = candidates.iter().cloned().collect();
+        let keys: HashSet<&AlgebraicValue> = rhs_inserted
+            .iter()
+            .chain(&rhs_deleted)
+            .map(|row| &row.elements[self.col_rhs])
+            .collect();
+        for key in keys {
+            for row in lookup(relational_db, tx, self.lhs.table_id, self.col_lhs, key)? {
+                if seen.insert(row.clone()) {
+                    candidates.push(row);
+                }
+            }
+        }
+
+        let mut rhs_rows = HashMap::new();
+        let mut rows = Vec::new();
+        for row in candidates {
+            let key = &row.elements[self.col_lhs];
+            let rhs_after = match rhs_rows.entry(key.clone()) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(lookup(relational_db, tx, self.rhs.table_id, self.col_rhs, key)?),
+            };
+            let rhs_before = rhs_after
+                .iter()
+                .filter(|r| !rhs_inserted.contains(r))
+                .chain(rhs_deleted.iter().copied());
+
+            let was_in = !lhs_inserted.contains(&row) && self.joins_any(&row, rhs_before)?;
+            let is_in = !lhs_deleted.contains(&row) && self.joins_any(&row, rhs_after.iter())?;
+            if was_in != is_in {
+                rows.push((is_in as u8, row));
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// A query for a page of the rows of a single table in some order,
+/// as in `SELECT * FROM t WHERE ... ORDER BY score DESC LIMIT 10 OFFSET 20`,
+/// which rows enter and leave as others are written ahead of them, not just when they're written themselves.
+///
+/// Its results are maintained by scanning the table once for each transaction writing a row that passes its filters,
+/// keeping only the rows that may be in the page before or after the writes, rather than sorting all of them.
+pub struct Window<'a> {
+    filter: RowFilter<'a>,
+    order: RowOrder,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl<'a> Window<'a> {
+    /// Returns the window that `query` amounts to,
+    /// if it's selections over a table, then an order, then an offset, a limit, or both.
+    pub fn of(query: &'a QueryExpr) -> Option<Self> {
+        let table = query.source.get_db_table()?;
+        let at = query.query.iter().position(|q| matches!(q, expr::Query::OrderBy(_)))?;
+        let (filters, [expr::Query::OrderBy(cols), rest @ ..]) = query.query.split_at(at) else {
+            return None;
+        };
+        let filters = filters
+            .iter()
+            .map(|q| match q {
+                expr::Query::Select(op) => Some(op),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        let (offset, limit) = match rest {
+            [expr::Query::Offset(offset)] => (*offset, None),
+            [expr::Query::Limit(limit)] => (0, Some(*limit)),
+            [expr::Query::Offset(offset), expr::Query::Limit(limit)] => (*offset, Some(*limit)),
+            _ => return None,
+        };
+        Some(Self {
+            filter: RowFilter { table, filters },
+            order: RowOrder::new(&table.head, cols).ok()?,
+            offset: offset.try_into().ok()?,
+            limit: limit.map(usize::try_from).transpose().ok()?,
+        })
+    }
+
+    pub fn table_id(&self) -> u32 {
+        self.filter.table.table_id
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.filter.table.head.table_name
+    }
+
+    /// Checks that the caller of `auth` can read the table, as the VM does.
+    pub fn check_auth(&self, auth: AuthCtx) -> Result<(), DBError> {
+        self.filter.check_auth(auth)
+    }
+
+    /// Returns the `rows` that pass the filters.
+    fn passing<'r>(&self, rows: HashSet<&'r ProductValue>) -> Result<HashSet<&'r ProductValue>, DBError> {
+        let mut kept = HashSet::with_capacity(rows.len());
+        for row in rows {
+            if self.filter.matches(row)? {
+                kept.insert(row);
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Returns the rows of the page, in order, out of the `rows` sorted in place.
+    fn page<'r>(&self, rows: &'r mut [ProductValue]) -> &'r [ProductValue] {
+        rows.sort_by(|a, b| self.order.cmp(a, b));
+        let start = self.offset.min(rows.len());
+        let end = self.limit.map_or(rows.len(), |limit| (start + limit).min(rows.len()));
+        &rows[start..end]
+    }
+
+    /// Returns the rows that enter the page, with an `op_type` of 1,
+    /// or leave it, with an `op_type` of 0, because of the rows written in `update`.
+    ///
+    /// `tx` sees the state of the database after the writes.
+    pub fn eval_delta(
+        &self,
+        relational_db: &RelationalDB,
+        tx: &mut MutTxId,
+        update: &DatabaseUpdate,
+    ) -> Result<Vec<(u8, ProductValue)>, DBError> {
+        let ops = update
+            .tables
+            .iter()
+            .filter(|t| t.table_id == self.table_id())
+            .flat_map(|t| &t.ops);
+        let (inserted, deleted) = partition_ops(ops);
+        let inserted = self.passing(inserted)?;
+        let deleted = self.passing(deleted)?;
+        if inserted.is_empty() && deleted.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // The page after the writes is among the first `offset + limit` rows,
+        // and the one before, among those and the next ones up to the number inserted,
+        // once the inserted rows are taken out, and the deleted ones put back.
+        let keep = self.limit.map(|limit| self.offset + limit + inserted.len());
+        let mut rows = Vec::new();
+        for row in relational_db.iter(tx, self.table_id())? {
+            let row = row.view();
+            if !self.filter.matches(row)? {
+                continue;
+            }
+            rows.push(row.clone());
+            if let Some(keep) = keep {
+                if rows.len() >= 2 * keep.max(1) {
+                    rows.select_nth_unstable_by(keep, |a, b| self.order.cmp(a, b));
+                    rows.truncate(keep);
+                }
+            }
+        }
+
+        let mut before: Vec<_> = rows
+            .iter()
+            .filter(|row| !inserted.contains(row))
+            .chain(deleted.iter().copied())
+            .cloned()
+            .collect();
+        let before: HashSet<_> = self.page(&mut before).iter().cloned().collect();
+        let after: HashSet<_> = self.page(&mut rows).iter().cloned().collect();
+
+        let left = before.difference(&after).map(|row| (0, row.clone()));
+        let entered = after.difference(&before).map(|row| (1, row.clone()));
+        Ok(left.chain(entered).collect())
+    }
+}
+
+/// Splits `ops` into the rows inserted and the rows deleted.
+fn partition_ops<'a>(ops: impl Iterator<Item = &'a TableOp>) -> (HashSet<&'a ProductValue>, HashSet<&'a ProductValue>) {
+    let (inserted, deleted): (Vec<_>, Vec<_>) = ops.partition(|op| op.op_type == 1);
+    (
+        inserted.into_iter().map(|op| &op.row).collect(),
+        deleted.into_iter().map(|op| &op.row).collect(),
+    )
+}
+
+/// Returns the rows of the table `table_id` with `value` in the column `col`, using its index if it has one.
+fn lookup(
+    relational_db: &RelationalDB,
+    tx: &mut MutTxId,
+    table_id: u32,
+    col: usize,
+    value: &AlgebraicValue,
+) -> Result<Vec<ProductValue>, DBError> {
+    Ok(relational_db
+        .iter_by_col_eq(tx, table_id, col as u32, value)?
+        .map(|row| row.view().clone())
+        .collect())
 }
 
 pub const OP_TYPE_FIELD_NAME: &str = "__op_type";
@@ -96,9 +455,38 @@ pub fn compile_query(relational_db: &RelationalDB, tx: &MutTxId, input: &str) ->
                 return Err(SubscriptionError::SideEffect(Crud::Create(DbType::Table)).into())
             }
             CrudExpr::Drop { kind, .. } => return Err(SubscriptionError::SideEffect(Crud::Drop(kind)).into()),
+            CrudExpr::CreateIndex { .. } => {
+                return Err(SubscriptionError::SideEffect(Crud::Create(DbType::Index)).into())
+            }
+            CrudExpr::Grant { .. } | CrudExpr::GrantAccess { .. } => {
+                return Err(SubscriptionError::SideEffect(Crud::Grant).into())
+            }
+            CrudExpr::Revoke { .. } | CrudExpr::RevokeAccess { .. } => {
+                return Err(SubscriptionError::SideEffect(Crud::Revoke).into())
+            }
+            CrudExpr::Analyze { .. } => return Err(SubscriptionError::SideEffect(Crud::Analyze).into()),
+            CrudExpr::Vacuum => return Err(SubscriptionError::SideEffect(Crud::Vacuum).into()),
         }
     }
 
+    if let Some(q) = queries.iter().find(|q| q.source.get_db_table().is_none()) {
+        return Err(SubscriptionError::View(q.source.table_name().to_string()).into());
+    }
+
+    let has_join = |q: &QueryExpr| q.query.iter().any(|q| matches!(q, expr::Query::JoinInner(_)));
+    if queries.iter().any(|q| has_join(q) && SemiJoin::of(q).is_none()) {
+        return Err(SubscriptionError::UnsupportedJoin.into());
+    }
+
+    let has_window = |q: &QueryExpr| {
+        q.query
+            .iter()
+            .any(|q| matches!(q, expr::Query::Offset(_) | expr::Query::Limit(_)))
+    };
+    if queries.iter().any(|q| has_window(q) && Window::of(q).is_none()) {
+        return Err(SubscriptionError::UnsupportedWindow.into());
+    }
+
     if !queries.is_empty() {
         Ok(Query { queries })
     } else {
@@ -113,7 +501,7 @@ mod tests {
     use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
     use crate::sql::execute::run;
     use crate::subscription::subscription::QuerySet;
-    use crate::vm::tests::create_table_from_program;
+    use crate::vm::tests::{create_table_from_program, create_table_with_rows};
     use crate::vm::DbProgram;
     use itertools::Itertools;
     use spacetimedb_lib::auth::{StAccess, StTableType};
@@ -152,6 +540,7 @@ mod tests {
             table_id,
             table_name: "inventory".to_string(),
             ops: vec![op.clone()],
+            updates: vec![],
         };
         // For filtering out the hidden field `OP_TYPE_FIELD_NAME`
         let fields = &[
@@ -173,6 +562,7 @@ mod tests {
             table_id,
             table_name: "inventory".to_string(),
             ops: vec![op],
+            updates: vec![],
         };
 
         let q = QueryExpr::new(db_table((&schema).into(), "inventory", table_id))
@@ -221,6 +611,7 @@ mod tests {
             table_id,
             table_name: "_inventory".to_string(),
             ops: vec![op],
+            updates: vec![],
         };
         // For filtering out the hidden field `OP_TYPE_FIELD_NAME`
         let fields = &[
@@ -272,14 +663,17 @@ mod tests {
             table_id,
             table_name: "_inventory".to_string(),
             ops: vec![row1, row2],
+            updates: vec![],
         };
 
         let update = DatabaseUpdate {
             tables: vec![data.clone()],
+            tx_offset: None,
         };
 
         let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
-        assert_eq!(result.tables.len(), 3, "Must return 3 tables");
+        // The updates of the queries whose rows were all returned by a previous one are skipped.
+        assert_eq!(result.tables.len(), 1, "Must return 1 table");
         assert_eq!(
             result.tables.iter().map(|x| x.ops.len()).sum::<usize>(),
             1,
@@ -406,12 +800,17 @@ mod tests {
             table_id,
             table_name: "inventory".to_string(),
             ops: vec![row1, row2],
+            updates: vec![],
         };
 
-        let update = DatabaseUpdate { tables: vec![data] };
+        let update = DatabaseUpdate {
+            tables: vec![data],
+            tx_offset: None,
+        };
 
         let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
-        assert_eq!(result.tables.len(), 3, "Must return 3 tables");
+        // The updates of the queries whose rows were all returned by a previous one are skipped.
+        assert_eq!(result.tables.len(), 1, "Must return 1 table");
         assert_eq!(
             result.tables.iter().map(|x| x.ops.len()).sum::<usize>(),
             1,
@@ -508,4 +907,179 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_row_filter() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let p = &mut DbProgram::new(&db, &mut tx, AuthCtx::for_testing());
+
+        let head = ProductType::from_iter([("inventory_id", BuiltinType::U64), ("name", BuiltinType::String)]);
+        let table_id = create_table_from_program(p, "inventory", head, &[])?;
+        let schema = db.schema_for_table(&tx, table_id).unwrap();
+
+        //SELECT * FROM inventory WHERE inventory_id > 1 AND name = 'mana'
+        let q = QueryExpr::new(db_table((&schema).into(), "inventory", table_id))
+            .with_select_cmp(OpCmp::Gt, FieldName::named("inventory", "inventory_id"), scalar(1u64))
+            .with_select_cmp(OpCmp::Eq, FieldName::named("inventory", "name"), scalar("mana"));
+        let filter = RowFilter::of(&q).expect("a selection is a row filter");
+        assert!(filter.matches(&product!(2u64, "mana"))?);
+        assert!(!filter.matches(&product!(1u64, "mana"))?);
+        assert!(!filter.matches(&product!(2u64, "health"))?);
+
+        // A projection changes the rows, so they aren't checked one by one.
+        let fields = &[FieldName::named("inventory", "name").into()];
+        assert!(RowFilter::of(&q.clone().with_project(fields)).is_none());
+        Ok(())
+    }
+
+    fn insert_op(row: ProductValue) -> TableOp {
+        TableOp {
+            op_type: 1,
+            row_pk: row.to_data_key().to_bytes(),
+            row,
+        }
+    }
+
+    fn update_of(table_id: u32, table_name: &str, ops: Vec<TableOp>) -> DatabaseUpdate {
+        DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id,
+                table_name: table_name.to_string(),
+                ops,
+                updates: vec![],
+            }],
+            tx_offset: None,
+        }
+    }
+
+    //Check that the rows of `entity` enter and leave
+    //```
+    //SELECT entity.* FROM entity JOIN position ON entity.entity_id = position.entity_id WHERE position.x > 5
+    //```
+    // as rows are written to either table
+    #[test]
+    fn test_subscribe_join() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let entity_head = ProductType::from_iter([("entity_id", BuiltinType::U64), ("name", BuiltinType::String)]);
+        let position_head = ProductType::from_iter([("entity_id", BuiltinType::U64), ("x", BuiltinType::I32)]);
+        let entities = [product!(1u64, "a"), product!(2u64, "b")];
+        let entity_id = create_table_with_rows(&db, &mut tx, "entity", entity_head, &entities)?;
+        let position_id = create_table_with_rows(&db, &mut tx, "position", position_head, &[product!(1u64, 10i32)])?;
+
+        let sql =
+            "SELECT entity.* FROM entity JOIN position ON entity.entity_id = position.entity_id WHERE position.x > 5";
+        let query = compile_query(&db, &tx, sql)?;
+        assert!(SemiJoin::of(&query.queries[0]).is_some());
+        let s = QuerySet(vec![query]);
+
+        let initial = s.eval(&db, &mut tx, AuthCtx::for_testing())?;
+        let rows = initial
+            .tables
+            .iter()
+            .flat_map(|t| &t.ops)
+            .map(|op| &op.row)
+            .collect_vec();
+        assert_eq!(rows, [&entities[0]]);
+
+        // A position is added for the second entity, so it joins.
+        let position = product!(2u64, 20i32);
+        db.insert(&mut tx, position_id, position.clone())?;
+        let update = update_of(position_id, "position", vec![insert_op(position.clone())]);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(result.tables.len(), 1);
+        assert_eq!(result.tables[0].table_id, entity_id);
+        assert_eq!(result.tables[0].ops.len(), 1);
+        assert_eq!(result.tables[0].ops[0].op_type, 1);
+        assert_eq!(result.tables[0].ops[0].row, entities[1]);
+
+        // The position no longer passes the filter, so the entity leaves.
+        let moved = product!(2u64, 0i32);
+        db.delete_by_rel(&mut tx, position_id, vec![position.clone()])?;
+        db.insert(&mut tx, position_id, moved.clone())?;
+        let mut ops = vec![insert_op(position), insert_op(moved)];
+        ops[0].op_type = 0;
+        let update = update_of(position_id, "position", ops);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(result.tables[0].ops.len(), 1);
+        assert_eq!(result.tables[0].ops[0].op_type, 0);
+        assert_eq!(result.tables[0].ops[0].row, entities[1]);
+
+        // A new entity without a position doesn't join.
+        let entity = product!(3u64, "c");
+        db.insert(&mut tx, entity_id, entity.clone())?;
+        let update = update_of(entity_id, "entity", vec![insert_op(entity)]);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert!(result.tables.is_empty());
+
+        // Two tables can only be joined when selecting the rows of the first.
+        let sql = "SELECT * FROM entity JOIN position ON entity.entity_id = position.entity_id";
+        assert!(compile_query(&db, &tx, sql).is_err());
+
+        Ok(())
+    }
+
+    // Check the top scores of
+    //```
+    //SELECT * FROM leaderboard WHERE score > 0 ORDER BY score DESC LIMIT 2
+    //```
+    // are kept as rows enter and leave them, even when not written themselves
+    #[test]
+    fn test_subscribe_window() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let head = ProductType::from_iter([("player", BuiltinType::String), ("score", BuiltinType::U32)]);
+        let rows = [product!("a", 10u32), product!("b", 30u32), product!("c", 20u32)];
+        let table_id = create_table_with_rows(&db, &mut tx, "leaderboard", head, &rows)?;
+
+        let sql = "SELECT * FROM leaderboard WHERE score > 0 ORDER BY score DESC LIMIT 2";
+        let query = compile_query(&db, &tx, sql)?;
+        assert!(Window::of(&query.queries[0]).is_some());
+        let s = QuerySet(vec![query]);
+
+        let ops_of = |update: &DatabaseUpdate| {
+            update
+                .tables
+                .iter()
+                .flat_map(|t| &t.ops)
+                .map(|op| (op.op_type, op.row.clone()))
+                .sorted()
+                .collect_vec()
+        };
+
+        let initial = s.eval(&db, &mut tx, AuthCtx::for_testing())?;
+        assert_eq!(ops_of(&initial), [(1, rows[1].clone()), (1, rows[2].clone())]);
+
+        // A new top score pushes the last one out.
+        let top = product!("d", 40u32);
+        db.insert(&mut tx, table_id, top.clone())?;
+        let update = update_of(table_id, "leaderboard", vec![insert_op(top.clone())]);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(ops_of(&result), [(0, rows[2].clone()), (1, top.clone())]);
+
+        // A score below the window changes nothing.
+        let low = product!("e", 5u32);
+        db.insert(&mut tx, table_id, low.clone())?;
+        let update = update_of(table_id, "leaderboard", vec![insert_op(low)]);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert!(ops_of(&result).is_empty());
+
+        // Deleting a top score lets the next one back in.
+        db.delete_by_rel(&mut tx, table_id, vec![top.clone()])?;
+        let mut ops = vec![insert_op(top.clone())];
+        ops[0].op_type = 0;
+        let update = update_of(table_id, "leaderboard", ops);
+        let result = s.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+        assert_eq!(ops_of(&result), [(0, top), (1, rows[2].clone())]);
+
+        // A page needs an order, and whole rows.
+        assert!(compile_query(&db, &tx, "SELECT * FROM leaderboard LIMIT 2").is_err());
+        let sql = "SELECT player FROM leaderboard ORDER BY score LIMIT 2";
+        assert!(compile_query(&db, &tx, sql).is_err());
+
+        Ok(())
+    }
 }
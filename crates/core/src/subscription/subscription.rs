@@ -1,11 +1,13 @@
 use spacetimedb_lib::identity::AuthCtx;
-use spacetimedb_sats::{AlgebraicValue, BuiltinValue};
+use spacetimedb_lib::PrimaryKey;
+use spacetimedb_sats::{AlgebraicValue, BuiltinValue, ProductValue};
+use spacetimedb_vm::expr::QueryExpr;
 use std::collections::HashSet;
 
 use super::query::Query;
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::error::DBError;
-use crate::subscription::query::{run_query, OP_TYPE_FIELD_NAME};
+use crate::subscription::query::{run_query, to_mem_table, RowFilter, SemiJoin, Window, OP_TYPE_FIELD_NAME};
 use crate::{
     client::{ClientActorId, ClientConnectionSender},
     db::relational_db::RelationalDB,
@@ -49,10 +51,26 @@ impl Subscription {
 }
 
 impl QuerySet {
+    /// Returns whether the queries only filter the rows of a table,
+    /// so that their incremental evaluation only depends on the rows written,
+    /// and can be done for a past transaction as well as for the latest.
+    pub fn is_replayable(&self) -> bool {
+        self.0
+            .iter()
+            .flat_map(|query| &query.queries)
+            .all(|q| RowFilter::of(q).is_some())
+    }
+
     /// Incremental evaluation of `rows` that matched the [Query] (aka subscriptions)
     ///
     /// This is equivalent to run a `trigger` on `INSERT/UPDATE/DELETE`, run the [Query] and see if the `row` is matched.
     ///
+    /// Only the rows written by the transaction are evaluated, never the rest of the tables,
+    /// except for the pages of [Window] queries.
+    /// Queries that only filter a table are checked against each written row directly,
+    /// a page of a table in some order is scanned again when a written row may move it,
+    /// while the others are run over a [MemTable](spacetimedb_lib::relation::MemTable) of the written rows.
+    ///
     /// NOTE: The returned `rows` in [DatabaseUpdate] are **deduplicated** so if 2 queries match the same `row`, only one copy is returned.
     #[tracing::instrument(skip_all)]
     pub fn eval_incr(
@@ -62,51 +80,40 @@ impl QuerySet {
         database_update: &DatabaseUpdate,
         auth: AuthCtx,
     ) -> Result<DatabaseUpdate, DBError> {
-        let mut output = DatabaseUpdate { tables: vec![] };
+        let mut output = DatabaseUpdate::default();
         let mut seen = HashSet::new();
 
         for query in &self.0 {
-            for table in database_update.tables.iter().cloned() {
-                for q in query.queries_of_table_id(&table) {
-                    if let Some(result) = run_query(relational_db, tx, &q, auth)?
-                        .into_iter()
-                        .find(|x| !x.data.is_empty())
-                    {
-                        let pos_op_type = result.head.find_pos_by_name(OP_TYPE_FIELD_NAME).unwrap_or_else(|| {
-                            panic!(
-                                "failed to locate `{OP_TYPE_FIELD_NAME}` on `{}`. fields: {:?}",
-                                result.head.table_name,
-                                result.head.fields.iter().map(|x| &x.field).collect::<Vec<_>>()
-                            )
-                        });
-
-                        let mut table_row_operations = table.clone();
-                        table_row_operations.ops.clear();
-                        for mut row in result.data {
-                            //Hack: remove the hidden field OP_TYPE_FIELD_NAME. see `to_mem_table`
-                            // Needs to be done before calculating the PK.
-                            let op_type = if let AlgebraicValue::Builtin(BuiltinValue::U8(op)) =
-                                row.elements.remove(pos_op_type)
-                            {
-                                op
-                            } else {
-                                panic!("Fail to extract `{OP_TYPE_FIELD_NAME}` on `{}`", result.head.table_name)
-                            };
-
-                            let row_pk = RelationalDB::pk_for_row(&row);
-
-                            //Skip rows that are already resolved in a previous subscription...
-                            if seen.contains(&(table.table_id, row_pk)) {
-                                continue;
-                            }
-
-                            seen.insert((table.table_id, row_pk));
+            for q in &query.queries {
+                if let Some(join) = SemiJoin::of(q) {
+                    join.check_auth(auth)?;
+                    let rows = join.eval_delta(relational_db, tx, database_update)?;
+                    push_rows(&mut output, &mut seen, join.table_id(), join.table_name(), rows);
+                    continue;
+                }
+                if let Some(window) = Window::of(q) {
+                    window.check_auth(auth)?;
+                    let rows = window.eval_delta(relational_db, tx, database_update)?;
+                    push_rows(&mut output, &mut seen, window.table_id(), window.table_name(), rows);
+                    continue;
+                }
 
-                            let row_pk = row_pk.to_bytes();
-                            table_row_operations.ops.push(TableOp { op_type, row_pk, row });
+                let table_id = q.source.get_db_table().map(|t| t.table_id);
+                for table in database_update.tables.iter().filter(|t| Some(t.table_id) == table_id) {
+                    let rows = match RowFilter::of(q) {
+                        Some(filter) => {
+                            filter.check_auth(auth)?;
+                            let mut rows = Vec::new();
+                            for op in &table.ops {
+                                if filter.matches(&op.row)? {
+                                    rows.push((op.op_type, op.row.clone()));
+                                }
+                            }
+                            rows
                         }
-                        output.tables.push(table_row_operations);
-                    }
+                        None => run_query_incr(relational_db, tx, to_mem_table(q.clone(), table), auth)?,
+                    };
+                    push_rows(&mut output, &mut seen, table.table_id, &table.table_name, rows);
                 }
             }
         }
@@ -128,7 +135,7 @@ impl QuerySet {
         tx: &mut MutTxId,
         auth: AuthCtx,
     ) -> Result<DatabaseUpdate, DBError> {
-        let mut database_update: DatabaseUpdate = DatabaseUpdate { tables: vec![] };
+        let mut database_update = DatabaseUpdate::default();
         let mut seen = HashSet::new();
 
         for query in &self.0 {
@@ -159,6 +166,7 @@ impl QuerySet {
                                 table_id: t.table_id,
                                 table_name: t.head.table_name.clone(),
                                 ops: table_row_operations,
+                                updates: vec![],
                             });
                         }
                     }
@@ -168,3 +176,176 @@ impl QuerySet {
         Ok(database_update)
     }
 }
+
+/// Adds `rows`, with their `op_type`, to `output` as an update of the table `table_id`,
+/// skipping those already in `seen`.
+fn push_rows(
+    output: &mut DatabaseUpdate,
+    seen: &mut HashSet<(u32, PrimaryKey)>,
+    table_id: u32,
+    table_name: &str,
+    rows: Vec<(u8, ProductValue)>,
+) {
+    if rows.is_empty() {
+        return;
+    }
+
+    let mut ops = Vec::new();
+    for (op_type, row) in rows {
+        let row_pk = RelationalDB::pk_for_row(&row);
+
+        //Skip rows that are already resolved in a previous subscription...
+        if !seen.insert((table_id, row_pk)) {
+            continue;
+        }
+
+        let row_pk = row_pk.to_bytes();
+        ops.push(TableOp { op_type, row_pk, row });
+    }
+    // All the rows may have been resolved by previous subscriptions.
+    if ops.is_empty() {
+        return;
+    }
+
+    output.tables.push(DatabaseTableUpdate {
+        table_id,
+        table_name: table_name.to_owned(),
+        ops,
+        updates: vec![],
+    });
+}
+
+/// Runs `query` over a [MemTable](spacetimedb_lib::relation::MemTable) made by [`to_mem_table`],
+/// returning the matched rows along with their `op_type`.
+fn run_query_incr(
+    relational_db: &RelationalDB,
+    tx: &mut MutTxId,
+    query: QueryExpr,
+    auth: AuthCtx,
+) -> Result<Vec<(u8, ProductValue)>, DBError> {
+    let Some(result) = run_query(relational_db, tx, &query, auth)?
+        .into_iter()
+        .find(|x| !x.data.is_empty())
+    else {
+        return Ok(vec![]);
+    };
+
+    let pos_op_type = result.head.find_pos_by_name(OP_TYPE_FIELD_NAME).unwrap_or_else(|| {
+        panic!(
+            "failed to locate `{OP_TYPE_FIELD_NAME}` on `{}`. fields: {:?}",
+            result.head.table_name,
+            result.head.fields.iter().map(|x| &x.field).collect::<Vec<_>>()
+        )
+    });
+
+    let table_name = &result.head.table_name;
+    Ok(result
+        .data
+        .into_iter()
+        .map(|mut row| {
+            //Hack: remove the hidden field OP_TYPE_FIELD_NAME. see `to_mem_table`
+            // Needs to be done before calculating the PK.
+            let op_type = if let AlgebraicValue::Builtin(BuiltinValue::U8(op)) = row.elements.remove(pos_op_type) {
+                op
+            } else {
+                panic!("Fail to extract `{OP_TYPE_FIELD_NAME}` on `{table_name}`")
+            };
+            (op_type, row)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::vm::tests::create_table_from_program;
+    use crate::vm::DbProgram;
+    use itertools::Itertools;
+    use spacetimedb_lib::data_key::ToDataKey;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_lib::relation::FieldName;
+    use spacetimedb_sats::{product, BuiltinType, ProductType};
+    use spacetimedb_vm::dsl::{db_table, scalar};
+    use spacetimedb_vm::operator::OpCmp;
+
+    fn op(op_type: u8, row: ProductValue) -> TableOp {
+        TableOp {
+            op_type,
+            row_pk: row.to_data_key().to_bytes(),
+            row,
+        }
+    }
+
+    /// Creates the table `inventory`, returning the query
+    /// `SELECT * FROM inventory WHERE inventory_id > 1` over it.
+    fn inventory_query(db: &RelationalDB, tx: &mut MutTxId) -> ResultTest<QueryExpr> {
+        let p = &mut DbProgram::new(db, tx, AuthCtx::for_testing());
+        let head = ProductType::from_iter([("inventory_id", BuiltinType::U64), ("name", BuiltinType::String)]);
+        let table_id = create_table_from_program(p, "inventory", head, &[])?;
+        let schema = db.schema_for_table(tx, table_id).unwrap();
+        Ok(
+            QueryExpr::new(db_table((&schema).into(), "inventory", table_id)).with_select_cmp(
+                OpCmp::Gt,
+                FieldName::named("inventory", "inventory_id"),
+                scalar(1u64),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_run_query_incr() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let q = inventory_query(&db, &mut tx)?;
+
+        let table = DatabaseTableUpdate {
+            table_id: q.source.get_db_table().unwrap().table_id,
+            table_name: "inventory".into(),
+            ops: vec![op(0, product!(1u64, "health")), op(1, product!(2u64, "mana"))],
+            updates: vec![],
+        };
+        // The hidden op type column is taken out of the rows, and returned along with them.
+        let rows = run_query_incr(&db, &mut tx, to_mem_table(q, &table), AuthCtx::for_testing())?;
+        assert_eq!(rows, [(1, product!(2u64, "mana"))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_filter_matches_run_query_incr() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let q = inventory_query(&db, &mut tx)?;
+        let filter = RowFilter::of(&q).unwrap();
+        let table_id = q.source.get_db_table().unwrap().table_id;
+
+        let inserts = vec![op(1, product!(1u64, "health")), op(1, product!(2u64, "mana"))];
+        let deletes = vec![op(0, product!(3u64, "stamina")), op(0, product!(0u64, "gold"))];
+        let mixed = vec![
+            op(0, product!(1u64, "health")),
+            op(0, product!(2u64, "mana")),
+            op(1, product!(2u64, "magic")),
+            op(1, product!(4u64, "armor")),
+        ];
+        for ops in [inserts, deletes, mixed] {
+            let table = DatabaseTableUpdate {
+                table_id,
+                table_name: "inventory".into(),
+                ops,
+                updates: vec![],
+            };
+            let mut fast = Vec::new();
+            for op in &table.ops {
+                if filter.matches(&op.row)? {
+                    fast.push((op.op_type, op.row.clone()));
+                }
+            }
+            let eval = run_query_incr(&db, &mut tx, to_mem_table(q.clone(), &table), AuthCtx::for_testing())?;
+            assert_eq!(
+                fast.into_iter().sorted().collect_vec(),
+                eval.into_iter().sorted().collect_vec()
+            );
+        }
+        Ok(())
+    }
+}
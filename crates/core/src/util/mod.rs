@@ -18,6 +18,15 @@ impl<T, E> ResultInspectExt<T, E> for Result<T, E> {
     }
 }
 
+/// A new id for the trace of the work done on behalf of a request,
+/// as 32 hex digits like the trace ids of W3C trace context.
+///
+/// It's recorded on the root span of the request as `trace_id`,
+/// which the spans of the reducer calls, commits and subscription updates it causes descend from.
+pub fn new_trace_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
 pub(crate) fn string_from_utf8_lossy_owned(v: Vec<u8>) -> String {
     match String::from_utf8_lossy(&v) {
         // SAFETY: from_utf8_lossy() returned Borrowed, which means the original buffer is valid utf8
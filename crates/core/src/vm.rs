@@ -4,17 +4,20 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::datastore::traits::{ColumnDef, IndexDef, IndexId, SequenceId, TableDef};
 use crate::db::relational_db::RelationalDB;
 use crate::error::DBError;
-use spacetimedb_lib::auth::{StAccess, StTableType};
+use spacetimedb_lib::auth::{StAccess, StRoleAccess, StTableType};
+use spacetimedb_lib::error::AuthError;
 use spacetimedb_lib::identity::AuthCtx;
-use spacetimedb_lib::relation::{FieldExpr, Relation};
+use spacetimedb_lib::relation::{DbTable, FieldExpr, FieldName, Relation};
 use spacetimedb_lib::relation::{Header, MemTable, RelIter, RelValue, RowCount, Table};
 use spacetimedb_lib::table::ProductTypeMeta;
-use spacetimedb_sats::ProductValue;
+use spacetimedb_lib::IndexType;
+use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductType, ProductValue};
 use spacetimedb_vm::dsl::mem_table;
 use spacetimedb_vm::env::EnvDb;
 use spacetimedb_vm::errors::ErrorVm;
 use spacetimedb_vm::eval::IterRows;
 use spacetimedb_vm::expr::*;
+use spacetimedb_vm::operator::{OpCmp, OpLogic, OpQuery};
 use spacetimedb_vm::program::{ProgramRef, ProgramVm};
 use spacetimedb_vm::rel_ops::RelOps;
 use std::collections::HashMap;
@@ -26,6 +29,7 @@ pub fn build_query<'a>(
     tx: &'a mut MutTxId,
     query: QueryCode,
 ) -> Result<Box<IterRows<'a>>, ErrorVm> {
+    let query = plan_query(stdb, tx, query)?;
     let q = match &query.table {
         Table::MemTable(x) => SourceExpr::MemTable(x.clone()),
         Table::DbTable(x) => SourceExpr::DbTable(x.clone()),
@@ -93,11 +97,199 @@ pub fn build_query<'a>(
                 )?;
                 Box::new(iter)
             }
+            Query::OrderBy(cols) => Box::new(result.order_by(&cols)?),
+            Query::Offset(offset) => Box::new(result.offset(offset as usize)),
+            Query::Limit(limit) => Box::new(result.limit(limit as usize)),
         };
     }
     Ok(result)
 }
 
+/// The largest estimated fraction of the rows of a table a filter on an indexed column can keep
+/// for [plan_query] to seek them through the index rather than scan the table.
+const SEEK_MAX_SELECTIVITY: f64 = 0.25;
+
+/// Plans `query` from the statistics on the columns of its tables, as of their last `ANALYZE`:
+///
+/// - Its joins are reordered so the tables estimated to have the fewest rows are joined first.
+/// - If it filters on `column = value` for an indexed column of its source table,
+///   its source is the rows sought through the index,
+///   unless `value` is estimated to be in too many rows for that to beat a scan.
+///
+/// Without statistics, the joins keep the order they were written in, and the index is always used.
+fn plan_query(stdb: &RelationalDB, tx: &mut MutTxId, mut query: QueryCode) -> Result<QueryCode, ErrorVm> {
+    reorder_joins(stdb, tx, &mut query)?;
+    if let Table::DbTable(table) = &query.table {
+        if let Some(rows) = seek_source(stdb, tx, table, &query.query)? {
+            query.table = Table::MemTable(MemTable::new(&table.head, table.table_access, &rows));
+        }
+    }
+    Ok(query)
+}
+
+/// Reorders the leading joins of `query` so the tables estimated to have the fewest rows are joined first,
+/// each one after the table its column from the left is from.
+///
+/// The columns of the result are projected back into the order they were written in.
+fn reorder_joins(stdb: &RelationalDB, tx: &MutTxId, query: &mut QueryCode) -> Result<(), ErrorVm> {
+    let joins = query
+        .query
+        .iter()
+        .take_while(|op| matches!(op, Query::JoinInner(_)))
+        .count();
+    if joins < 2 {
+        return Ok(());
+    }
+
+    let source = query.table.head();
+    let mut tables = vec![source.table_name.clone()];
+    let mut columns = source.fields.iter().map(|col| col.field.clone()).collect::<Vec<_>>();
+    let mut estimates = Vec::with_capacity(joins);
+    for op in &query.query[..joins] {
+        let Query::JoinInner(join) = op else { unreachable!() };
+        let head = join.rhs.head();
+        // The columns of a table joined twice are renamed by where it's joined, so keep the order as is.
+        if tables.contains(&head.table_name) {
+            return Ok(());
+        }
+        tables.push(head.table_name.clone());
+        columns.extend(head.fields.iter().map(|col| col.field.clone()));
+        estimates.push(match &join.rhs {
+            SourceExpr::MemTable(x) => Some(x.data.len() as u64),
+            SourceExpr::DbTable(x) => stdb.column_stats(tx, x.table_id)?.first().map(|col| col.row_count),
+        });
+    }
+    if estimates.iter().all(Option::is_none) {
+        return Ok(());
+    }
+
+    let mut pending = query
+        .query
+        .drain(..joins)
+        .zip(estimates)
+        .enumerate()
+        .collect::<Vec<_>>();
+    let mut joined = vec![source.table_name];
+    let mut ordered = Vec::with_capacity(joins);
+    while !pending.is_empty() {
+        // The smallest table that can be joined yet, those without an estimate last, then as written.
+        let next = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, (op, _)))| match op {
+                Query::JoinInner(join) => joined.iter().any(|table| table == join.col_lhs.table()),
+                _ => unreachable!(),
+            })
+            .min_by_key(|(_, (written, (_, rows)))| (rows.is_none(), *rows, *written))
+            .map_or(0, |(i, _)| i);
+        let (written, (op, _)) = pending.remove(next);
+        if let Query::JoinInner(join) = &op {
+            joined.push(join.rhs.head().table_name);
+        }
+        ordered.push((written, op));
+    }
+    let reordered = ordered.windows(2).any(|w| w[0].0 > w[1].0);
+    let rest = std::mem::take(&mut query.query);
+    query.query = ordered.into_iter().map(|(_, op)| op).chain(rest).collect();
+
+    if reordered {
+        let project = columns.into_iter().map(FieldExpr::Name).collect::<Vec<_>>();
+        match query.query.iter_mut().rev().find_map(|op| match op {
+            Query::Project(cols) => Some(cols),
+            _ => None,
+        }) {
+            Some(cols) if cols.is_empty() => *cols = project,
+            Some(_) => {}
+            None => query.query.push(Query::Project(project)),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the rows of the source `table` of a query sought through an index, if [plan_query] should,
+/// for the `ops` of the query.
+fn seek_source(
+    stdb: &RelationalDB,
+    tx: &mut MutTxId,
+    table: &DbTable,
+    ops: &[Query],
+) -> Result<Option<Vec<ProductValue>>, ErrorVm> {
+    let mut eqs = Vec::new();
+    for op in ops {
+        if let Query::Select(cmp) = op {
+            eq_conjuncts(cmp, &mut eqs);
+        }
+    }
+    if eqs.is_empty() {
+        return Ok(None);
+    }
+
+    let indexed = stdb
+        .schema_for_table(tx, table.table_id)?
+        .indexes
+        .iter()
+        .map(|index| index.col_id)
+        .collect::<Vec<_>>();
+    let stats = stdb.column_stats(tx, table.table_id)?;
+    // The column and value to seek, and the estimated number of rows.
+    let mut best: Option<(u32, &AlgebraicValue, Option<f64>)> = None;
+    for (field, value) in eqs {
+        if field.table() != table.head.table_name {
+            continue;
+        }
+        let Some(col_id) = table.head.column_pos(field).map(|pos| pos as u32) else {
+            continue;
+        };
+        if !indexed.contains(&col_id) {
+            continue;
+        }
+        let rows = match stats.iter().find(|col| col.col_id == col_id) {
+            Some(col) if col.eq_selectivity(value) > SEEK_MAX_SELECTIVITY => continue,
+            Some(col) => Some(col.eq_rows(value)),
+            None => None,
+        };
+        let better = match (&best, rows) {
+            (None, _) => true,
+            (Some((_, _, None)), Some(_)) => true,
+            (Some((_, _, Some(best))), Some(rows)) => rows < *best,
+            (Some(_), None) => false,
+        };
+        if better {
+            best = Some((col_id, value, rows));
+        }
+    }
+
+    let Some((col_id, value, _)) = best else {
+        return Ok(None);
+    };
+    let rows = stdb
+        .iter_by_col_eq(tx, table.table_id, col_id, value)?
+        .map(|row| row.view().clone())
+        .collect();
+    Ok(Some(rows))
+}
+
+/// Collects the `field = value` comparisons `cmp` is a conjunction of into `eqs`.
+fn eq_conjuncts<'a>(cmp: &'a ColumnOp, eqs: &mut Vec<(&'a FieldName, &'a AlgebraicValue)>) {
+    let ColumnOp::Cmp { op, lhs, rhs } = cmp else {
+        return;
+    };
+    match op {
+        OpQuery::Logic(OpLogic::And) => {
+            eq_conjuncts(lhs, eqs);
+            eq_conjuncts(rhs, eqs);
+        }
+        OpQuery::Cmp(OpCmp::Eq) => match (&**lhs, &**rhs) {
+            (ColumnOp::Field(FieldExpr::Name(field)), ColumnOp::Field(FieldExpr::Value(value)))
+            | (ColumnOp::Field(FieldExpr::Value(value)), ColumnOp::Field(FieldExpr::Name(field))) => {
+                eqs.push((field, value))
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
 fn get_table<'a>(
     stdb: &'a RelationalDB,
     tx: &'a mut MutTxId,
@@ -114,6 +306,21 @@ fn get_table<'a>(
     })
 }
 
+/// Collects the tables of the database `query` reads from,
+/// where its source table requires `access` and the joined ones [StRoleAccess::Read].
+fn db_tables_of<'a>(query: &'a QueryCode, access: StRoleAccess, tables: &mut Vec<(&'a DbTable, StRoleAccess)>) {
+    if let Table::DbTable(table) = &query.table {
+        tables.push((table, access));
+    }
+    for q in &query.query {
+        if let Query::JoinInner(join) = q {
+            if let Some(table) = join.rhs.get_db_table() {
+                tables.push((table, StRoleAccess::Read));
+            }
+        }
+    }
+}
+
 /// A [ProgramVm] implementation that carry a [RelationalDB] for it
 /// query execution
 pub struct DbProgram<'db, 'tx> {
@@ -137,6 +344,70 @@ impl<'db, 'tx> DbProgram<'db, 'tx> {
         }
     }
 
+    /// Checks the caller may run `query` by the access its roles have to the tables in `st_table_acl`.
+    ///
+    /// Tables that aren't listed there, and statements without tables, are checked by [AuthAccess::check_auth].
+    fn check_access(&self, query: &CrudCode) -> Result<(), ErrorVm> {
+        let mut tables = Vec::new();
+        match query {
+            CrudCode::Query(query) => db_tables_of(query, StRoleAccess::Read, &mut tables),
+            CrudCode::Insert {
+                table: Table::DbTable(table),
+                ..
+            } => tables.push((table, StRoleAccess::Write)),
+            CrudCode::Update { delete, .. } => db_tables_of(delete, StRoleAccess::Write, &mut tables),
+            CrudCode::Delete { query } => db_tables_of(query, StRoleAccess::Write, &mut tables),
+            _ => {}
+        }
+        if tables.is_empty() {
+            return Ok(query.check_auth(self.auth.owner, self.auth.caller)?);
+        }
+
+        for (table, required) in tables {
+            match self.db.table_access(self.tx, self.auth, table.table_id)? {
+                Some(access) if access >= required => {}
+                Some(_) => {
+                    return Err(AuthError::TableAccess {
+                        named: table.head.table_name.clone(),
+                        required,
+                    }
+                    .into())
+                }
+                None if table.table_access == StAccess::Public || self.auth.owner == self.auth.caller => {}
+                None => {
+                    return Err(AuthError::TablePrivate {
+                        named: table.head.table_name.clone(),
+                    }
+                    .into())
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the header of the rows `query` would return, without running it,
+    /// checking that the caller may run it.
+    pub fn describe_query(&self, query: QueryExpr) -> Result<Header, ErrorVm> {
+        let mut head = query.source.head();
+        for q in &query.query {
+            match q {
+                Query::JoinInner(join) => head = head.extend(&join.rhs.head()),
+                Query::Project(cols) if !cols.is_empty() => head = head.project(cols)?,
+                _ => {}
+            }
+        }
+
+        let table = match query.source {
+            SourceExpr::MemTable(x) => Table::MemTable(x),
+            SourceExpr::DbTable(x) => Table::DbTable(x),
+        };
+        self.check_access(&CrudCode::Query(QueryCode {
+            table,
+            query: query.query,
+        }))?;
+        Ok(head)
+    }
+
     fn _eval_query(&mut self, query: QueryCode) -> Result<Code, ErrorVm> {
         let table_access = query.table.table_access();
 
@@ -206,6 +477,7 @@ impl<'db, 'tx> DbProgram<'db, 'tx> {
                     col_id: i as u32,
                     name: format!("{}_{}_idx", table_name, i),
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 });
             }
             cols.push(ColumnDef {
@@ -268,7 +540,7 @@ impl ProgramVm for DbProgram<'_, '_> {
     }
 
     fn eval_query(&mut self, query: CrudCode) -> Result<Code, ErrorVm> {
-        query.check_auth(self.auth.owner, self.auth.caller)?;
+        self.check_access(&query)?;
 
         match query {
             CrudCode::Query(query) => self._eval_query(query),
@@ -310,6 +582,73 @@ impl ProgramVm for DbProgram<'_, '_> {
                 let result = self.drop(&name, kind)?;
                 Ok(result)
             }
+            CrudCode::CreateIndex {
+                table_id,
+                name,
+                col_id,
+                is_unique,
+                index_type,
+                if_not_exists,
+            } => {
+                if !(if_not_exists && self.db.index_id_from_name(self.tx, &name)?.is_some()) {
+                    let index = IndexDef {
+                        table_id,
+                        col_id,
+                        name,
+                        is_unique,
+                        index_type,
+                    };
+                    self.db.create_index(self.tx, index)?;
+                }
+                Ok(Code::Pass)
+            }
+            CrudCode::Grant { role, identity } => {
+                self.db.grant_role(self.tx, &role, identity)?;
+                Ok(Code::Pass)
+            }
+            CrudCode::Revoke { role, identity } => {
+                self.db.revoke_role(self.tx, &role, identity)?;
+                Ok(Code::Pass)
+            }
+            CrudCode::GrantAccess {
+                table_ids,
+                roles,
+                access,
+            } => {
+                for table_id in table_ids {
+                    for role in &roles {
+                        self.db.grant_table_access(self.tx, table_id, role, access)?;
+                    }
+                }
+                Ok(Code::Pass)
+            }
+            CrudCode::RevokeAccess {
+                table_ids,
+                roles,
+                access,
+            } => {
+                for table_id in table_ids {
+                    for role in &roles {
+                        self.db.revoke_table_access(self.tx, table_id, role, access)?;
+                    }
+                }
+                Ok(Code::Pass)
+            }
+            CrudCode::Analyze { table_ids } => {
+                for table_id in table_ids {
+                    self.db.analyze(self.tx, table_id)?;
+                }
+                Ok(Code::Pass)
+            }
+            CrudCode::Vacuum => {
+                let report = self.db.vacuum(self.tx)?;
+                let head = ProductType::from_iter([
+                    ("memory_bytes_reclaimed", AlgebraicType::U64),
+                    ("spill_bytes_reclaimed", AlgebraicType::U64),
+                ]);
+                let row = product![report.memory_bytes, report.spill_bytes];
+                Ok(Code::Table(mem_table(head, [row])))
+            }
         }
     }
 
@@ -470,6 +809,84 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_plan_query() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+        let mut tx = stdb.begin_tx();
+
+        // `big` has 100 rows with a unique `id`, and a `kind` that's 0 in all but one.
+        let head = ProductType::from_iter([("id", BuiltinType::U64), ("kind", BuiltinType::U64)]);
+        let rows: Vec<_> = (0..100u64).map(|i| product!(i, (i == 99) as u64)).collect();
+        let big_id = create_table_with_rows(&stdb, &mut tx, "big", head.clone(), &rows)?;
+        stdb.create_index(&mut tx, IndexDef::new("big_id".into(), big_id, 0, true))?;
+        stdb.create_index(&mut tx, IndexDef::new("big_kind".into(), big_id, 1, false))?;
+        let big = db_table(head, "big", big_id);
+
+        let mut joined = Vec::new();
+        for (name, len) in [("mid", 10u64), ("small", 2)] {
+            let head = ProductType::from_iter([("id", BuiltinType::U64)]);
+            let rows: Vec<_> = (0..len).map(|i| product!(i)).collect();
+            let table_id = create_table_with_rows(&stdb, &mut tx, name, head.clone(), &rows)?;
+            joined.push((db_table(head, name, table_id), table_id));
+        }
+
+        let select = |field: &str, value: u64| QueryCode {
+            table: Table::DbTable(big.clone()),
+            query: vec![Query::Select(ColumnOp::cmp(
+                OpQuery::Cmp(OpCmp::Eq),
+                ColumnOp::Field(FieldExpr::Name(FieldName::named("big", field))),
+                ColumnOp::Field(FieldExpr::Value(AlgebraicValue::U64(value))),
+            ))],
+        };
+        let sought = |tx: &mut MutTxId, query: QueryCode| -> ResultTest<Option<usize>> {
+            Ok(match plan_query(&stdb, tx, query)?.table {
+                Table::MemTable(x) => Some(x.data.len()),
+                Table::DbTable(_) => None,
+            })
+        };
+        // `big JOIN mid ON big.id = mid.id JOIN small ON big.id = small.id`
+        let join = || QueryCode {
+            table: Table::DbTable(big.clone()),
+            query: joined
+                .iter()
+                .map(|(table, _)| {
+                    let name = &table.head.table_name;
+                    Query::JoinInner(JoinExpr::new(
+                        SourceExpr::DbTable(table.clone()),
+                        FieldName::named("big", "id"),
+                        FieldName::named(name, "id"),
+                    ))
+                })
+                .collect(),
+        };
+        let run = |tx: &mut MutTxId, query: QueryCode| -> ResultTest<(Header, Vec<ProductValue>)> {
+            let result = build_query(&stdb, tx, query)?;
+            let head = result.head().clone();
+            Ok((head, result.collect_vec()?))
+        };
+
+        // Without statistics, an index is always used, and joins are kept as written.
+        assert_eq!(sought(&mut tx, select("kind", 0))?, Some(99));
+        assert_eq!(plan_query(&stdb, &mut tx, join())?.query, join().query);
+        let written = run(&mut tx, join())?;
+
+        stdb.analyze(&mut tx, big_id)?;
+        for (_, table_id) in &joined {
+            stdb.analyze(&mut tx, *table_id)?;
+        }
+        assert_eq!(sought(&mut tx, select("id", 5))?, Some(1));
+        // Seeking half of the table is slower than scanning it.
+        assert_eq!(sought(&mut tx, select("kind", 0))?, None);
+
+        let planned = plan_query(&stdb, &mut tx, join())?.query;
+        assert!(matches!(&planned[0], Query::JoinInner(join) if join.rhs.table_name() == "small"));
+        assert!(matches!(&planned[2], Query::Project(cols) if cols.len() == 4));
+        assert_eq!(run(&mut tx, join())?, written);
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
     fn check_catalog(p: &mut DbProgram, name: &str, row: ProductValue, q: QueryExpr, schema: DbTable) {
         let result = run_ast(p, q.into());
 
@@ -579,6 +996,7 @@ pub(crate) mod tests {
                 table_id,
                 col_id: 0,
                 is_unique: true,
+                index_type: IndexType::BTree,
             })
                 .into(),
             q,
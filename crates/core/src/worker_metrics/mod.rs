@@ -1,5 +1,7 @@
 use once_cell::sync::Lazy;
-use prometheus::{Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry};
+use prometheus::{
+    Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+};
 
 pub struct WorkerMetrics {
     registry: Registry,
@@ -8,10 +10,19 @@ pub struct WorkerMetrics {
     websocket_request_msg_size: HistogramVec,
     websocket_sent: IntCounterVec,
     websocket_sent_msg_size: HistogramVec,
+    client_queue_bytes: IntGaugeVec,
+    client_queue_overflows: IntCounterVec,
+    client_updates_skipped: IntCounterVec,
     process_cpu_usage: Gauge,
     reducer_count: IntCounterVec,
     reducer_compute_time: HistogramVec,
     reducer_write_size: HistogramVec,
+    reducer_calls_rate_limited: IntCounterVec,
+    guests_rate_limited: IntCounter,
+    scheduled_reducers: IntGaugeVec,
+    scheduled_reducer_batch_size: HistogramVec,
+    scheduled_reducer_delay: HistogramVec,
+    storage_bytes: IntGaugeVec,
     node_identity_energy_budget_gauge: GaugeVec,
     instance_env_insert: HistogramVec,
     // instance_env_delete_pk: HistogramVec,
@@ -60,6 +71,30 @@ impl WorkerMetrics {
                 &["identity"],
             )
             .unwrap(),
+            client_queue_bytes: IntGaugeVec::new(
+                Opts::new(
+                    "spacetime_worker_client_queue_bytes",
+                    "The size of the messages waiting to be sent to connected clients",
+                ),
+                &["identity"],
+            )
+            .unwrap(),
+            client_queue_overflows: IntCounterVec::new(
+                Opts::new(
+                    "spacetime_worker_client_queue_overflows",
+                    "Number of times a client fell behind, with no room in its queue for an update",
+                ),
+                &["identity", "policy"],
+            )
+            .unwrap(),
+            client_updates_skipped: IntCounterVec::new(
+                Opts::new(
+                    "spacetime_worker_client_updates_skipped",
+                    "Number of updates dropped or held back for clients that fell behind",
+                ),
+                &["identity"],
+            )
+            .unwrap(),
             process_cpu_usage: Gauge::new("spacetime_worker_process_cpu_usage", "CPU usage of the worker process.")
                 .unwrap(),
             reducer_count: IntCounterVec::new(
@@ -83,6 +118,53 @@ impl WorkerMetrics {
                 &["database_address", "reducer_symbol"],
             )
             .unwrap(),
+            reducer_calls_rate_limited: IntCounterVec::new(
+                Opts::new(
+                    "spacetime_worker_reducer_calls_rate_limited",
+                    "Number of reducer calls refused for exceeding the caller's rate limit.",
+                ),
+                // Not labeled by caller, as there may be any number of them.
+                &["instance_id"],
+            )
+            .unwrap(),
+            guests_rate_limited: IntCounter::new(
+                "spacetime_worker_guests_rate_limited",
+                "Number of guest identities refused for exceeding the limit on guests of an IP address.",
+            )
+            .unwrap(),
+            scheduled_reducers: IntGaugeVec::new(
+                Opts::new(
+                    "spacetime_worker_scheduled_reducers",
+                    "Number of reducer calls scheduled and yet to run.",
+                ),
+                &["database_address"],
+            )
+            .unwrap(),
+            scheduled_reducer_batch_size: HistogramVec::new(
+                HistogramOpts::new(
+                    "spacetime_worker_scheduled_reducer_batch_size",
+                    "The number of scheduled reducer calls that fall due together.",
+                )
+                .buckets(prometheus::exponential_buckets(1.0, 4.0, 10).unwrap()),
+                &["database_address"],
+            )
+            .unwrap(),
+            scheduled_reducer_delay: HistogramVec::new(
+                HistogramOpts::new(
+                    "spacetime_worker_scheduled_reducer_delay",
+                    "The time in seconds between when a reducer call was scheduled for and when it ran.",
+                ),
+                &["database_address"],
+            )
+            .unwrap(),
+            storage_bytes: IntGaugeVec::new(
+                Opts::new(
+                    "spacetime_worker_storage_bytes",
+                    "The approximate space used by each table and index in memory, and by the commit log on disk.",
+                ),
+                &["database_address", "kind", "name"],
+            )
+            .unwrap(),
             node_identity_energy_budget_gauge: GaugeVec::new(
                 Opts::new(
                     "spacetime_worker_identity_energy_budget",
@@ -152,6 +234,15 @@ impl WorkerMetrics {
         self.registry
             .register(Box::new(self.websocket_sent_msg_size.clone()))
             .unwrap();
+        self.registry
+            .register(Box::new(self.client_queue_bytes.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.client_queue_overflows.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.client_updates_skipped.clone()))
+            .unwrap();
         self.registry
             .register(Box::new(self.process_cpu_usage.clone()))
             .unwrap();
@@ -162,6 +253,22 @@ impl WorkerMetrics {
         self.registry
             .register(Box::new(self.reducer_write_size.clone()))
             .unwrap();
+        self.registry
+            .register(Box::new(self.reducer_calls_rate_limited.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.guests_rate_limited.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.scheduled_reducers.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.scheduled_reducer_batch_size.clone()))
+            .unwrap();
+        self.registry
+            .register(Box::new(self.scheduled_reducer_delay.clone()))
+            .unwrap();
+        self.registry.register(Box::new(self.storage_bytes.clone())).unwrap();
         self.registry
             .register(Box::new(self.instance_env_insert.clone()))
             .unwrap();
@@ -194,10 +301,19 @@ metrics_delegator!(WEBSOCKET_REQUESTS, websocket_requests: IntCounterVec);
 metrics_delegator!(WEBSOCKET_REQUEST_MSG_SIZE, websocket_request_msg_size: HistogramVec);
 metrics_delegator!(WEBSOCKET_SENT, websocket_sent: IntCounterVec);
 metrics_delegator!(WEBSOCKET_SENT_MSG_SIZE, websocket_sent_msg_size: HistogramVec);
+metrics_delegator!(CLIENT_QUEUE_BYTES, client_queue_bytes: IntGaugeVec);
+metrics_delegator!(CLIENT_QUEUE_OVERFLOWS, client_queue_overflows: IntCounterVec);
+metrics_delegator!(CLIENT_UPDATES_SKIPPED, client_updates_skipped: IntCounterVec);
 metrics_delegator!(PROCESS_CPU_USAGE, process_cpu_usage: Gauge);
 metrics_delegator!(REDUCER_COUNT, reducer_count: IntCounterVec);
 metrics_delegator!(REDUCER_COMPUTE_TIME, reducer_compute_time: HistogramVec);
 metrics_delegator!(REDUCER_WRITE_SIZE, reducer_write_size: HistogramVec);
+metrics_delegator!(REDUCER_CALLS_RATE_LIMITED, reducer_calls_rate_limited: IntCounterVec);
+metrics_delegator!(GUESTS_RATE_LIMITED, guests_rate_limited: IntCounter);
+metrics_delegator!(SCHEDULED_REDUCERS, scheduled_reducers: IntGaugeVec);
+metrics_delegator!(SCHEDULED_REDUCER_BATCH_SIZE, scheduled_reducer_batch_size: HistogramVec);
+metrics_delegator!(SCHEDULED_REDUCER_DELAY, scheduled_reducer_delay: HistogramVec);
+metrics_delegator!(STORAGE_BYTES, storage_bytes: IntGaugeVec);
 metrics_delegator!(
     NODE_IDENTITY_ENERGY_BUDGET_GAUGE,
     node_identity_energy_budget_gauge: GaugeVec
@@ -94,3 +94,49 @@ impl_deserialize!([] StTableType, de => {
         ))
     })
 });
+
+/// The access a role has to a table, as granted with `GRANT` and revoked with `REVOKE`.
+///
+/// Each level includes the ones before it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StRoleAccess {
+    /// Neither reading nor writing
+    None,
+    /// Reading only
+    Read,
+    /// Reading and writing
+    Write,
+}
+
+impl StRoleAccess {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for StRoleAccess {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "none" => Self::None,
+            "read" => Self::Read,
+            "write" => Self::Write,
+            x => return Err(x),
+        })
+    }
+}
+
+impl_serialize!([] StRoleAccess, (self, ser) => ser.serialize_str(self.as_str()));
+impl_deserialize!([] StRoleAccess, de => {
+    let value = de.deserialize_str_slice()?;
+    StRoleAccess::try_from(value).map_err(|x| {
+        Error::custom(format!(
+            "DecodeError for StRoleAccess: `{x}`. Expected 'none' | 'read' | 'write'"
+        ))
+    })
+});
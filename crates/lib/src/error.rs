@@ -1,3 +1,4 @@
+use crate::auth::StRoleAccess;
 use crate::relation::{FieldName, Header};
 use crate::{buffer, AlgebraicType};
 use spacetimedb_sats::product_value::InvalidFieldError;
@@ -112,6 +113,12 @@ pub enum AuthError {
     IndexPrivate { named: String },
     #[error("Sequence `{named}` is private")]
     SequencePrivate { named: String },
+    #[error("Only read-only queries are allowed")]
+    ReadOnly,
+    #[error("Only the owner of the database can {action}")]
+    OwnerOnly { action: String },
+    #[error("Table `{named}` requires `{}` access, which the roles of the caller don't grant", .required.as_str())]
+    TableAccess { named: String, required: StRoleAccess },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -0,0 +1,42 @@
+//! The text analysis shared by full-text indexes and the `MATCH` predicate.
+//!
+//! A text is split into tokens on every character that isn't alphanumeric,
+//! and each token is lowercased, so `"Iron Sword (+2)"` yields `["iron", "sword", "2"]`.
+
+/// Returns an iterator over the tokens of `text`, in order of appearance.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Returns whether `text` has all the tokens of `query`, in any order.
+///
+/// A `query` without any tokens matches nothing.
+pub fn matches(text: &str, query: &str) -> bool {
+    let mut query = tokenize(query).peekable();
+    if query.peek().is_none() {
+        return false;
+    }
+    let text: Vec<_> = tokenize(text).collect();
+    query.all(|token| text.contains(&token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<_> = tokenize("Iron Sword (+2), forged in  Dún-Morogh").collect();
+        assert_eq!(tokens, ["iron", "sword", "2", "forged", "in", "dún", "morogh"]);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("Iron Sword (+2)", "sword"));
+        assert!(matches("Iron Sword (+2)", "SWORD iron"));
+        assert!(!matches("Iron Sword (+2)", "iron shield"));
+        assert!(!matches("Iron Sword (+2)", " "));
+    }
+}
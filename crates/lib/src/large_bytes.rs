@@ -0,0 +1,46 @@
+//! References to byte strings stored out of the rows that hold them.
+//!
+//! A column of type [`LargeBytesRef`] holds the [`DataKey`] of its bytes rather than the bytes themselves.
+//! Up to 31 bytes fit in the key inline; longer ones are stored once by the database, under the hash of their key,
+//! so the rows, and the commit log and subscription updates that carry them, only ever hold a small reference.
+
+use spacetimedb_bindings_macro::{Deserialize, Serialize};
+use spacetimedb_sats::{impl_st, AlgebraicType, AlgebraicValue, ProductTypeElement};
+
+use crate::DataKey;
+
+/// The reference a row holds to a byte string stored out of it, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LargeBytesRef {
+    __large_bytes_key: Vec<u8>,
+}
+
+impl_st!([] LargeBytesRef, _ts => AlgebraicType::product(vec![
+    ProductTypeElement::new_named(AlgebraicType::bytes(), "__large_bytes_key")
+]));
+
+impl LargeBytesRef {
+    /// Returns the reference to the bytes stored under `key`.
+    pub fn new(key: DataKey) -> Self {
+        Self {
+            __large_bytes_key: key.to_bytes(),
+        }
+    }
+
+    /// Returns the key of the bytes, or `None` if the reference is malformed.
+    pub fn key(&self) -> Option<DataKey> {
+        DataKey::decode(&mut &self.__large_bytes_key[..]).ok()
+    }
+
+    /// Returns the key held by `value`, a value of a [`LargeBytesRef`] column,
+    /// or `None` if it isn't such a value.
+    pub fn key_of_value(value: &AlgebraicValue) -> Option<DataKey> {
+        let AlgebraicValue::Product(value) = value else {
+            return None;
+        };
+        match &*value.elements {
+            [key] => DataKey::decode(&mut &key.as_bytes()?[..]).ok(),
+            _ => None,
+        }
+    }
+}
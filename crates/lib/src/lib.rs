@@ -5,7 +5,10 @@ pub use spacetimedb_sats::buffer;
 pub mod address;
 pub mod data_key;
 pub mod filter;
+pub mod fulltext;
 pub mod identity;
+pub mod large_bytes;
+pub mod logging;
 pub use spacetimedb_sats::de;
 pub mod error;
 pub mod hash;
@@ -24,6 +27,7 @@ pub mod auth;
 #[cfg(feature = "serde")]
 pub mod recovery;
 pub mod relation;
+pub mod spatial;
 pub mod table;
 #[cfg(feature = "cli")]
 pub mod util;
@@ -35,13 +39,17 @@ pub use address::Address;
 pub use data_key::DataKey;
 pub use hash::Hash;
 pub use identity::Identity;
+pub use large_bytes::LargeBytesRef;
 pub use primary_key::PrimaryKey;
 pub use type_def::*;
 pub use type_value::{AlgebraicValue, ProductValue};
 
 pub use spacetimedb_sats as sats;
 
-pub const MODULE_ABI_VERSION: VersionTuple = VersionTuple::new(3, 0);
+pub const MODULE_ABI_VERSION: VersionTuple = VersionTuple::new(3, 16);
+
+/// The most bytes a blob stored by a database can have.
+pub const MAX_BLOB_SIZE: usize = 1024 * 1024;
 
 // if it ends up we need more fields in the future, we can split one of them in two
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -184,6 +192,215 @@ pub struct ModuleDef {
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
 pub enum MiscModuleExport {
     TypeAlias(TypeAlias),
+    /// The name of a reducer that only reads from the database,
+    /// so the host runs it in a read-only transaction.
+    ReadOnlyReducer(String),
+    TableAccessHint(TableAccessHint),
+    Event(EventDef),
+    ReducerAllow(ReducerAllow),
+    TableTtl(TableTtl),
+    ReducerCooldown(ReducerCooldown),
+    ReducerReturn(ReducerReturn),
+    DeferredUnique(DeferredUnique),
+    ReducerPriority(ReducerPriority),
+    HttpRoute(HttpRoute),
+    ColumnCompression(ColumnCompression),
+    RowVersion(RowVersion),
+}
+
+/// How long the rows of a table are kept, as declared with
+/// `#[spacetimedb(table, ttl = "24h", ttl_column = "created_at")]`.
+///
+/// The host deletes the rows whose timestamp in `column` is older than `ttl_micros`,
+/// in batches, each in a transaction of its own.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct TableTtl {
+    pub table_name: String,
+    pub ttl_micros: u64,
+    /// The name of the column of microseconds since the UNIX epoch, i.e. a `Timestamp`.
+    pub column: String,
+}
+
+/// The roles allowed to call a reducer, as declared with `#[spacetimedb(reducer, allow = "role")]`.
+///
+/// The host rejects calls from identities that hold none of the roles, without running the reducer.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct ReducerAllow {
+    pub reducer_name: String,
+    /// The names of the roles, e.g., `owner`, which only the database's owner holds.
+    pub roles: Vec<String>,
+}
+
+/// How long a caller has to wait between calls to a reducer,
+/// as declared with `#[spacetimedb(reducer, cooldown = "500ms")]`.
+///
+/// The host rejects the calls made within `cooldown_micros` of the previous one by the same identity,
+/// without running the reducer.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct ReducerCooldown {
+    pub reducer_name: String,
+    pub cooldown_micros: u64,
+}
+
+/// The lane the calls to a reducer wait in to be run,
+/// as declared with `#[spacetimedb(reducer, priority = "high")]`.
+///
+/// Reducers declared without a priority are in [`Lane::Normal`].
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct ReducerPriority {
+    pub reducer_name: String,
+    pub lane: Lane,
+}
+
+/// A lane of the queue in which the calls into a module wait to be run.
+///
+/// The host runs the calls waiting in a higher lane first,
+/// except that a call kept waiting by too many of them in a row is run next,
+/// so the calls in a lower lane are delayed, but never starved.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, de::Deserialize, ser::Serialize)]
+pub enum Lane {
+    /// For latency-sensitive calls, e.g., applying the input of a player.
+    High,
+    Normal,
+    /// For background work, e.g., cleanups and analytics.
+    Low,
+}
+
+/// An HTTP endpoint that the host serves under the database's address,
+/// as declared with `#[spacetimedb(reducer, read_only, http = "GET /leaderboard/:limit")]` on a reducer,
+/// or with `#[spacetimedb(http = "GET /health")]` on a `const` of SQL text.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct HttpRoute {
+    pub method: HttpMethod,
+    /// The path of the endpoint, whose segments starting with `:` match any segment,
+    /// which is then passed to a reducer as the argument of that name.
+    pub path: String,
+    pub handler: HttpHandler,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, de::Deserialize, ser::Serialize)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+/// What serves the requests to an [`HttpRoute`].
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub enum HttpHandler {
+    /// The read-only reducer of this name, called with the parameters of the path and query,
+    /// whose return value is the body of the response.
+    Reducer(String),
+    /// This SQL query, which may only read from the database, whose rows are the body of the response.
+    Sql(String),
+}
+
+/// The type of the value a reducer returns to its caller,
+/// for a reducer declared as returning `Result<T, E>` with `T` other than `()`.
+///
+/// The value is delivered to the caller, encoded as `ty`, if the reducer's transaction commits.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct ReducerReturn {
+    pub reducer_name: String,
+    pub ty: sats::AlgebraicType,
+}
+
+/// A type of event that reducers can emit to the clients subscribed to the module.
+///
+/// Events aren't stored in the database: they're delivered along with
+/// the transaction of the reducer that emitted them, if it commits.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct EventDef {
+    pub name: String,
+    pub ty: sats::AlgebraicTypeRef,
+}
+
+/// A unique column whose constraint is checked when a transaction commits rather than on each insert,
+/// as declared with `#[unique(deferred)]`.
+///
+/// Rows may share a value of the column in the middle of a transaction, e.g., while two rows swap their values.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct DeferredUnique {
+    pub table_name: String,
+    pub col_id: u8,
+}
+
+/// A string column whose values the host compresses in memory,
+/// as declared with `#[compress]` or `#[compress(zstd)]`.
+///
+/// Compression is transparent to reducers and queries, which see the values as they were inserted.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct ColumnCompression {
+    pub table_name: String,
+    pub col_id: u8,
+    pub codec: Compression,
+}
+
+/// A `u64` column the host bumps on every update of a row, as declared with `#[row_version]`.
+///
+/// A row inserted in the same transaction that deleted the row with its key in `key_col_id`
+/// gets the deleted row's version plus one, wrapping around; any other row starts at zero.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct RowVersion {
+    pub table_name: String,
+    pub col_id: u8,
+    pub key_col_id: u8,
+}
+
+/// How the values of a [compressed column](ColumnCompression) are stored in memory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, de::Deserialize, ser::Serialize)]
+pub enum Compression {
+    /// Each distinct value is stored once, in a dictionary, and the rows only hold its index in it.
+    ///
+    /// Suits columns with few distinct values, however short they are, e.g., the names of items.
+    Dictionary,
+    /// Each value is compressed on its own with zstd.
+    ///
+    /// Suits columns of long, repetitive text, e.g., descriptions or serialized documents.
+    Zstd,
+}
+
+impl Compression {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dictionary => "dictionary",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Compression {
+    type Error = &'a str;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "dictionary" => Self::Dictionary,
+            "zstd" => Self::Zstd,
+            x => return Err(x),
+        })
+    }
+}
+
+/// How a module expects a table to be accessed.
+#[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
+pub struct TableAccessHint {
+    pub table_name: String,
+    pub hint: AccessHint,
+}
+
+/// An expected pattern of access to a table,
+/// which the host uses to choose cheaper synchronization for it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, de::Deserialize, ser::Serialize)]
+pub enum AccessHint {
+    /// Rows are inserted, but rarely deleted or updated.
+    ///
+    /// Transactions that only insert into the table don't conflict with each other,
+    /// as long as the table has no unique constraints.
+    AppendOnly,
+    /// Rows are read much more often than they are written.
+    ///
+    /// Reads from the table are cheaper,
+    /// but a write to it conflicts with every concurrent transaction.
+    ReadMostly,
 }
 
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
@@ -201,8 +418,26 @@ pub struct IndexDef {
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
 pub enum IndexType {
-    BTree,
-    Hash,
+    BTree = 0,
+    Hash = 1,
+    /// An inverted index on the [tokens](fulltext::tokenize) of a string column.
+    FullText = 2,
+    /// An index on a [point](spatial::is_point) column, for box queries.
+    Spatial = 3,
+}
+
+impl TryFrom<u8> for IndexType {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Self::BTree),
+            1 => Ok(Self::Hash),
+            2 => Ok(Self::FullText),
+            3 => Ok(Self::Spatial),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
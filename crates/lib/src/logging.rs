@@ -0,0 +1,26 @@
+//! The structured fields of a module log record,
+//! as passed by `log!(target: "game", player = id; "spawned")` in a module.
+
+use spacetimedb_lib::de::Deserialize;
+use spacetimedb_lib::ser::Serialize;
+
+//WARNING: Change this structure(or any of their members) is an ABI change.
+/// A key-value pair of a log record, passed bsatn encoded as a `Vec<LogField>` to `_console_log_structured`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogField {
+    pub key: String,
+    pub value: LogValue,
+}
+
+/// The value of a [`LogField`], keeping the primitive type it was logged with.
+///
+/// Any other value is passed formatted as a string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub enum LogValue {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Str(String),
+}
@@ -77,6 +77,52 @@ pub enum PublishResult {
     /// owned by an identity other than the identity that you provided, then you will receive
     /// this error.
     PermissionDenied { domain: DomainName },
+
+    /// The module was loaded and checked against the database, but not published,
+    /// as publish was asked for a dry run.
+    DryRun {
+        /// `Some` if publish was given a domain name to operate on, `None` otherwise.
+        domain: Option<String>,
+        /// The address of the database the module was checked against.
+        address: String,
+        /// What publishing the module would do to the database.
+        plan: UpdatePlan,
+    },
+}
+
+/// What updating a database to a new version of its module would do.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdatePlan {
+    /// The tables the new version declares that the database doesn't have yet, which the update creates.
+    pub created_tables: Vec<String>,
+    /// The indexes the new version declares on tables the database has, which the update builds.
+    #[serde(default)]
+    pub created_indexes: Vec<String>,
+    /// The `Option` columns the new version adds at the end of tables the database has, as `table.column`,
+    /// which the update adds as `None` to their rows.
+    #[serde(default)]
+    pub added_columns: Vec<String>,
+    /// The tables whose definition in the new version differs from the one in the database.
+    pub changed_tables: Vec<TableChange>,
+    /// The tables of the database that the new version doesn't declare.
+    pub orphaned_tables: Vec<String>,
+    /// Whether the new version has an `__update__` reducer, which the update calls once the tables are created.
+    pub calls_update_reducer: bool,
+}
+
+impl UpdatePlan {
+    /// Whether the update can go ahead, which it can't if it would change or drop a table.
+    pub fn is_compatible(&self) -> bool {
+        self.changed_tables.is_empty() && self.orphaned_tables.is_empty()
+    }
+}
+
+/// How the definition of a table in a new version of a module differs from the one in the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableChange {
+    pub table_name: String,
+    /// The differences, one per column or index, e.g. ``column `age` added``.
+    pub differences: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,76 @@
+//! The geometry shared by spatial indexes and the `in_box` predicate.
+//!
+//! A point is a value of a product type with two numeric fields,
+//! like `struct Position { x: f32, y: f32 }`,
+//! and a box is given by its `min` and `max` corners, which are points as well.
+
+use crate::{AlgebraicType, AlgebraicValue};
+use spacetimedb_sats::BuiltinType;
+
+/// Returns whether values of `ty` are points, i.e., whether `ty` is a product of two numbers.
+pub fn is_point(ty: &AlgebraicType) -> bool {
+    let is_number = |ty: &AlgebraicType| {
+        matches!(
+            ty.as_builtin(),
+            Some(
+                BuiltinType::I8
+                    | BuiltinType::U8
+                    | BuiltinType::I16
+                    | BuiltinType::U16
+                    | BuiltinType::I32
+                    | BuiltinType::U32
+                    | BuiltinType::I64
+                    | BuiltinType::U64
+                    | BuiltinType::I128
+                    | BuiltinType::U128
+                    | BuiltinType::F32
+                    | BuiltinType::F64
+            )
+        )
+    };
+    matches!(ty.as_product(), Some(ty) if ty.elements.len() == 2 && ty.elements.iter().all(|e| is_number(&e.algebraic_type)))
+}
+
+/// Returns whether `point` is within the box from `min` to `max`, edges included.
+///
+/// Values that aren't points of the same dimension are never in the box.
+pub fn in_box(point: &AlgebraicValue, min: &AlgebraicValue, max: &AlgebraicValue) -> bool {
+    let (Some(point), Some(min), Some(max)) = (point.as_product(), min.as_product(), max.as_product()) else {
+        return false;
+    };
+    point.elements.len() == min.elements.len()
+        && point.elements.len() == max.elements.len()
+        && itertools::izip!(&point.elements, &min.elements, &max.elements).all(|(x, min, max)| min <= x && x <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_sats::product;
+
+    fn point(x: i32, y: i32) -> AlgebraicValue {
+        product![x, y].into()
+    }
+
+    fn product_type<const N: usize>(elements: [AlgebraicType; N]) -> AlgebraicType {
+        AlgebraicType::product(elements.into_iter().map(Into::into).collect())
+    }
+
+    #[test]
+    fn test_is_point() {
+        assert!(is_point(&product_type([AlgebraicType::F32, AlgebraicType::F32])));
+        assert!(!is_point(&product_type([AlgebraicType::F32])));
+        assert!(!is_point(&product_type([AlgebraicType::F32, AlgebraicType::String])));
+        assert!(!is_point(&AlgebraicType::U64));
+    }
+
+    #[test]
+    fn test_in_box() {
+        let (min, max) = (point(0, 0), point(10, 5));
+        assert!(in_box(&point(3, 4), &min, &max));
+        assert!(in_box(&point(10, 0), &min, &max));
+        assert!(!in_box(&point(3, 6), &min, &max));
+        assert!(!in_box(&point(-1, 4), &min, &max));
+        assert!(!in_box(&AlgebraicValue::U32(3), &min, &max));
+    }
+}
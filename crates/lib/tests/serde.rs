@@ -1,7 +1,9 @@
+use spacetimedb_lib::bsatn::{self, serde::Serde};
 use spacetimedb_lib::de::serde::SerdeDeserializer;
 use spacetimedb_lib::de::DeserializeSeed;
-use spacetimedb_lib::{AlgebraicType, ProductType, ProductTypeElement, ProductValue, SumType};
-use spacetimedb_sats::{satn::Satn, SumTypeVariant, Typespace, WithTypespace};
+use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType, ProductTypeElement, ProductValue, SumType};
+use spacetimedb_sats::{product, satn::Satn, SumTypeVariant, Typespace, WithTypespace};
+use std::collections::BTreeMap;
 
 macro_rules! de_json_snapshot {
     ($schema:expr, $json:expr) => {
@@ -52,6 +54,51 @@ fn test_json_mappings() {
     de_json_snapshot!(schema, data);
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle { r: f64 },
+    Rect(u32, u32),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct Drawing {
+    name: String,
+    owner: Option<char>,
+    shapes: Vec<Shape>,
+    tags: BTreeMap<String, u8>,
+}
+
+#[test]
+fn test_serde_column_round_trips() {
+    let drawing = Serde(Drawing {
+        name: "doodle".into(),
+        owner: Some('🦀'),
+        shapes: vec![Shape::Point, Shape::Circle { r: 1.5 }, Shape::Rect(2, 3)],
+        tags: [("wip".to_owned(), 1)].into_iter().collect(),
+    });
+    let bytes = bsatn::to_vec(&drawing).unwrap();
+    let decoded: Serde<Drawing> = bsatn::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, drawing);
+}
+
+#[test]
+fn test_serde_encodes_like_sats() {
+    #[derive(serde::Serialize)]
+    struct Row {
+        id: u32,
+        name: Option<String>,
+    }
+
+    let bytes = bsatn::serde::to_vec(&Row {
+        id: 1,
+        name: Some("a".into()),
+    })
+    .unwrap();
+    let row = product![1u32, AlgebraicValue::OptionSome(AlgebraicValue::String("a".into()))];
+    assert_eq!(bytes, bsatn::to_vec(&row).unwrap());
+}
+
 fn tuple<'a>(elems: impl IntoIterator<Item = (&'a str, AlgebraicType)>) -> ProductType {
     ProductType {
         elements: elems
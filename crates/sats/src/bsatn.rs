@@ -5,6 +5,8 @@ use crate::Typespace;
 
 pub mod de;
 pub mod ser;
+#[cfg(feature = "serde")]
+pub mod serde;
 
 pub use de::Deserializer;
 pub use ser::Serializer;
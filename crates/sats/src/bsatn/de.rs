@@ -32,12 +32,12 @@ impl de::Error for DecodeError {
 }
 
 /// Read a length as a `u32` then converted to `usize`.
-fn get_len<'de>(reader: &mut impl BufReader<'de>) -> Result<usize, DecodeError> {
+pub(super) fn get_len<'de>(reader: &mut impl BufReader<'de>) -> Result<usize, DecodeError> {
     Ok(reader.get_u32()? as usize)
 }
 
 /// Read a byte slice from the `reader`.
-fn read_bytes<'a, 'de: 'a>(reader: &'a mut impl BufReader<'de>) -> Result<&'de [u8], DecodeError> {
+pub(super) fn read_bytes<'a, 'de: 'a>(reader: &'a mut impl BufReader<'de>) -> Result<&'de [u8], DecodeError> {
     let len = get_len(reader)?;
     reader.get_slice(len)
 }
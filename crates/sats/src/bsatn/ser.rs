@@ -46,7 +46,7 @@ impl Error for BsatnError {
 /// Writes `len` converted to a `u32` to `writer`.
 ///
 /// Errors if `len` would not fit in a `u32`.
-fn put_len(writer: &mut impl BufWriter, len: usize) -> Result<(), BsatnError> {
+pub(super) fn put_len(writer: &mut impl BufWriter, len: usize) -> Result<(), BsatnError> {
     let len = len.try_into().map_err(|_| BsatnError::custom("len too long"))?;
     writer.put_u32(len);
     Ok(())
@@ -0,0 +1,508 @@
+//! BSATN as a Serde data format,
+//! so that types implementing Serde's traits, but not those of SATS, can be stored, see [`Serde`].
+//!
+//! A value is encoded as the BSATN of the SATS value with the same shape,
+//! e.g., a struct as the product of its fields and an enum as a sum tagged by the variant index.
+//! As BSATN isn't self-describing, `deserialize_any` and the attributes relying on it,
+//! e.g., `#[serde(flatten)]` and `#[serde(untagged)]`, aren't supported.
+
+use super::ser::{put_len, BsatnError};
+use super::{de::get_len, de::read_bytes, DecodeError};
+use crate::buffer::{BufReader, BufWriter};
+use crate::{impl_st, AlgebraicType};
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer};
+use ::serde::ser::{self, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Serialize `value` into the buffered writer `w` in the BSATN format, using its Serde impl.
+pub fn to_writer<W: BufWriter, T: Serialize + ?Sized>(w: &mut W, value: &T) -> Result<(), BsatnError> {
+    value.serialize(Serializer { writer: w })
+}
+
+/// Serialize `value` into a `Vec<u8>` in the BSATN format, using its Serde impl.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, BsatnError> {
+    let mut v = Vec::new();
+    to_writer(&mut v, value)?;
+    Ok(v)
+}
+
+/// Deserialize a `T` from the BSATN format in the buffered `reader`, using its Serde impl.
+pub fn from_reader<'de, T: de::Deserialize<'de>>(reader: &mut impl BufReader<'de>) -> Result<T, DecodeError> {
+    T::deserialize(&mut Deserializer { reader })
+}
+
+/// Deserialize a `T` from the BSATN format in `bytes`, using its Serde impl.
+pub fn from_slice<'de, T: de::Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, DecodeError> {
+    from_reader(&mut &*bytes)
+}
+
+/// A value of a type implementing Serde's traits, stored in SATS as its BSATN bytes.
+///
+/// This allows using a type that doesn't implement [`SpacetimeType`](crate::SpacetimeType),
+/// e.g., one defined by another crate, as a column.
+/// As far as SATS is concerned, the column is an opaque byte array.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Serde<T>(pub T);
+
+impl_st!([T] Serde<T>, _ts => AlgebraicType::bytes());
+
+impl<T: Serialize> crate::ser::Serialize for Serde<T> {
+    fn serialize<S: crate::ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = to_vec(&self.0).map_err(<S::Error as crate::ser::Error>::custom)?;
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de, T: DeserializeOwned> crate::de::Deserialize<'de> for Serde<T> {
+    fn deserialize<D: crate::de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(SerdeVisitor(PhantomData))
+    }
+}
+
+/// Decodes a [`Serde<T>`] from its bytes.
+struct SerdeVisitor<T>(PhantomData<T>);
+
+impl<'de, T: DeserializeOwned> crate::de::SliceVisitor<'de, [u8]> for SerdeVisitor<T> {
+    type Output = Serde<T>;
+
+    fn visit<E: crate::de::Error>(self, bytes: &[u8]) -> Result<Self::Output, E> {
+        from_slice(bytes).map(Serde).map_err(E::custom)
+    }
+}
+
+impl ser::Error for BsatnError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        crate::ser::Error::custom(msg)
+    }
+}
+
+impl de::Error for DecodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecodeError::Other(msg.to_string())
+    }
+}
+
+/// Writes the tag of the variant at `index` to `writer`.
+fn put_tag(writer: &mut impl BufWriter, index: u32) -> Result<(), BsatnError> {
+    let tag = index
+        .try_into()
+        .map_err(|_| <BsatnError as ser::Error>::custom("too many variants"))?;
+    writer.put_u8(tag);
+    Ok(())
+}
+
+/// The length of a sequence or map, which BSATN writes before the elements.
+fn known_len(len: Option<usize>) -> Result<usize, BsatnError> {
+    len.ok_or_else(|| <BsatnError as ser::Error>::custom("the length must be known"))
+}
+
+/// A Serde serializer for the BSATN format.
+struct Serializer<'a, W> {
+    writer: &'a mut W,
+}
+
+impl<W> Serializer<'_, W> {
+    /// Reborrows the serializer.
+    #[inline]
+    fn reborrow(&mut self) -> Serializer<'_, W> {
+        Serializer { writer: self.writer }
+    }
+}
+
+impl<'a, W: BufWriter> ser::Serializer for Serializer<'a, W> {
+    type Ok = ();
+    type Error = BsatnError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), BsatnError> {
+        self.writer.put_u8(v as u8);
+        Ok(())
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), BsatnError> {
+        self.writer.put_i8(v);
+        Ok(())
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), BsatnError> {
+        self.writer.put_i16(v);
+        Ok(())
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), BsatnError> {
+        self.writer.put_i32(v);
+        Ok(())
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), BsatnError> {
+        self.writer.put_i64(v);
+        Ok(())
+    }
+    fn serialize_i128(self, v: i128) -> Result<(), BsatnError> {
+        self.writer.put_i128(v);
+        Ok(())
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), BsatnError> {
+        self.writer.put_u8(v);
+        Ok(())
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), BsatnError> {
+        self.writer.put_u16(v);
+        Ok(())
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), BsatnError> {
+        self.writer.put_u32(v);
+        Ok(())
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), BsatnError> {
+        self.writer.put_u64(v);
+        Ok(())
+    }
+    fn serialize_u128(self, v: u128) -> Result<(), BsatnError> {
+        self.writer.put_u128(v);
+        Ok(())
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), BsatnError> {
+        self.writer.put_u32(v.to_bits());
+        Ok(())
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), BsatnError> {
+        self.writer.put_u64(v.to_bits());
+        Ok(())
+    }
+    fn serialize_char(self, v: char) -> Result<(), BsatnError> {
+        self.writer.put_u32(v.into());
+        Ok(())
+    }
+    fn serialize_str(self, v: &str) -> Result<(), BsatnError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), BsatnError> {
+        put_len(self.writer, v.len())?;
+        self.writer.put_slice(v);
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), BsatnError> {
+        // As `Option<T>` in SATS, `none` is the second variant.
+        self.writer.put_u8(1);
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), BsatnError> {
+        self.writer.put_u8(0);
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), BsatnError> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), BsatnError> {
+        Ok(())
+    }
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<(), BsatnError> {
+        put_tag(self.writer, index)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), BsatnError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), BsatnError> {
+        put_tag(self.writer, index)?;
+        value.serialize(self)
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, BsatnError> {
+        put_len(self.writer, known_len(len)?)?;
+        Ok(self)
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self, BsatnError> {
+        Ok(self)
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, BsatnError> {
+        Ok(self)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, BsatnError> {
+        put_tag(self.writer, index)?;
+        Ok(self)
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, BsatnError> {
+        put_len(self.writer, known_len(len)?)?;
+        Ok(self)
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, BsatnError> {
+        Ok(self)
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, BsatnError> {
+        put_tag(self.writer, index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Implements the Serde traits for serializing the elements of a compound value,
+/// each of which is written in turn.
+macro_rules! serialize_elements {
+    ($($trait:ident :: $method:ident $(($key:ty))?;)*) => {
+        $(impl<W: BufWriter> ser::$trait for Serializer<'_, W> {
+            type Ok = ();
+            type Error = BsatnError;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, $(_key: $key,)? value: &T) -> Result<(), BsatnError> {
+                value.serialize(self.reborrow())
+            }
+
+            fn end(self) -> Result<(), BsatnError> {
+                Ok(())
+            }
+        })*
+    };
+}
+
+serialize_elements! {
+    SerializeSeq::serialize_element;
+    SerializeTuple::serialize_element;
+    SerializeTupleStruct::serialize_field;
+    SerializeTupleVariant::serialize_field;
+    SerializeStruct::serialize_field(&'static str);
+    SerializeStructVariant::serialize_field(&'static str);
+}
+
+impl<W: BufWriter> ser::SerializeMap for Serializer<'_, W> {
+    type Ok = ();
+    type Error = BsatnError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), BsatnError> {
+        key.serialize(self.reborrow())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), BsatnError> {
+        value.serialize(self.reborrow())
+    }
+
+    fn end(self) -> Result<(), BsatnError> {
+        Ok(())
+    }
+}
+
+/// A Serde deserializer for the BSATN format.
+struct Deserializer<'a, R> {
+    reader: &'a mut R,
+}
+
+/// Reads a value of a primitive type with `$get` and passes it to `$visit`.
+macro_rules! deserialize_prim {
+    ($($method:ident => $get:ident, $visit:ident;)*) => {
+        $(fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+            visitor.$visit(self.reader.$get()?)
+        })*
+    };
+}
+
+impl<'de, R: BufReader<'de>> de::Deserializer<'de> for &mut Deserializer<'_, R> {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value, DecodeError> {
+        Err(DecodeError::Other("BSATN is not self-describing".into()))
+    }
+
+    deserialize_prim! {
+        deserialize_i8 => get_i8, visit_i8;
+        deserialize_i16 => get_i16, visit_i16;
+        deserialize_i32 => get_i32, visit_i32;
+        deserialize_i64 => get_i64, visit_i64;
+        deserialize_i128 => get_i128, visit_i128;
+        deserialize_u8 => get_u8, visit_u8;
+        deserialize_u16 => get_u16, visit_u16;
+        deserialize_u32 => get_u32, visit_u32;
+        deserialize_u64 => get_u64, visit_u64;
+        deserialize_u128 => get_u128, visit_u128;
+    }
+
+    fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_bool(self.reader.get_u8()? != 0)
+    }
+    fn deserialize_f32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_f32(f32::from_bits(self.reader.get_u32()?))
+    }
+    fn deserialize_f64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_f64(f64::from_bits(self.reader.get_u64()?))
+    }
+    fn deserialize_char<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let c = self.reader.get_u32()?;
+        let c = char::from_u32(c).ok_or_else(|| DecodeError::Other(format!("invalid char {c:#x}")))?;
+        visitor.visit_char(c)
+    }
+    fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_borrowed_str(core::str::from_utf8(read_bytes(self.reader)?)?)
+    }
+    fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_str(visitor)
+    }
+    fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_borrowed_bytes(read_bytes(self.reader)?)
+    }
+    fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_bytes(visitor)
+    }
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        match self.reader.get_u8()? {
+            0 => visitor.visit_some(self),
+            1 => visitor.visit_none(),
+            _ => Err(DecodeError::InvalidTag),
+        }
+    }
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let len = get_len(self.reader)?;
+        visitor.visit_seq(Elements { de: self, len })
+    }
+    fn deserialize_tuple<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_seq(Elements { de: self, len })
+    }
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+    fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let len = get_len(self.reader)?;
+        visitor.visit_map(Elements { de: self, len })
+    }
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_enum(self)
+    }
+    fn deserialize_identifier<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_any(visitor)
+    }
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The `len` elements, or entries, of a compound value still to read.
+struct Elements<'a, 'b, R> {
+    de: &'a mut Deserializer<'b, R>,
+    len: usize,
+}
+
+impl<'de, R: BufReader<'de>> de::SeqAccess<'de> for Elements<'_, '_, R> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, DecodeError> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, R: BufReader<'de>> de::MapAccess<'de> for Elements<'_, '_, R> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, DecodeError> {
+        de::SeqAccess::next_element_seed(self, seed)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DecodeError> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, R: BufReader<'de>> de::EnumAccess<'de> for &mut Deserializer<'_, R> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), DecodeError> {
+        let tag: de::value::U32Deserializer<DecodeError> = u32::from(self.reader.get_u8()?).into_deserializer();
+        seed.deserialize(tag).map(|variant| (variant, self))
+    }
+}
+
+impl<'de, R: BufReader<'de>> de::VariantAccess<'de> for &mut Deserializer<'_, R> {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), DecodeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, DecodeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
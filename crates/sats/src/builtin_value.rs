@@ -288,6 +288,36 @@ impl ArrayValue {
         Ok(())
     }
 
+    /// Returns whether `val` is an element of the array `self`.
+    ///
+    /// A `val` of a different type than the elements is never contained.
+    pub fn contains(&self, val: &AlgebraicValue) -> bool {
+        fn contains<T: PartialEq>(arr: &[T], val: Option<&T>) -> bool {
+            val.map_or(false, |val| arr.contains(val))
+        }
+
+        match self {
+            ArrayValue::Sum(v) => contains(v, val.as_sum()),
+            ArrayValue::Product(v) => contains(v, val.as_product()),
+            ArrayValue::Bool(v) => contains(v, val.as_bool()),
+            ArrayValue::I8(v) => contains(v, val.as_i8()),
+            ArrayValue::U8(v) => contains(v, val.as_u8()),
+            ArrayValue::I16(v) => contains(v, val.as_i16()),
+            ArrayValue::U16(v) => contains(v, val.as_u16()),
+            ArrayValue::I32(v) => contains(v, val.as_i32()),
+            ArrayValue::U32(v) => contains(v, val.as_u32()),
+            ArrayValue::I64(v) => contains(v, val.as_i64()),
+            ArrayValue::U64(v) => contains(v, val.as_u64()),
+            ArrayValue::I128(v) => contains(v, val.as_i128()),
+            ArrayValue::U128(v) => contains(v, val.as_u128()),
+            ArrayValue::F32(v) => contains(v, val.as_f32()),
+            ArrayValue::F64(v) => contains(v, val.as_f64()),
+            ArrayValue::String(v) => contains(v, val.as_string()),
+            ArrayValue::Array(v) => contains(v, val.as_array()),
+            ArrayValue::Map(v) => contains(v, val.as_map()),
+        }
+    }
+
     /// Returns a cloning iterator on the elements of `self` as `AlgebraicValue`s.
     pub fn iter_cloned(&self) -> ArrayValueIterCloned {
         match self {
@@ -311,6 +341,41 @@ impl ArrayValue {
             ArrayValue::Map(v) => ArrayValueIterCloned::Map(v.iter()),
         }
     }
+
+    /// Returns the array of `elems`, all of the type `elem_ty`,
+    /// or `None` if one of them isn't of that type, or `elem_ty` is a reference.
+    pub fn from_elements(elem_ty: &AlgebraicType, elems: Vec<AlgebraicValue>) -> Option<Self> {
+        fn collect<T>(
+            elems: Vec<AlgebraicValue>,
+            into: fn(AlgebraicValue) -> Result<T, AlgebraicValue>,
+        ) -> Option<Vec<T>> {
+            elems.into_iter().map(|elem| into(elem).ok()).collect()
+        }
+
+        Some(match elem_ty {
+            AlgebraicType::Sum(_) => collect(elems, AlgebraicValue::into_sum)?.into(),
+            AlgebraicType::Product(_) => collect(elems, AlgebraicValue::into_product)?.into(),
+            AlgebraicType::Builtin(ty) => match ty {
+                BuiltinType::Bool => collect(elems, AlgebraicValue::into_bool)?.into(),
+                BuiltinType::I8 => collect(elems, AlgebraicValue::into_i8)?.into(),
+                BuiltinType::U8 => collect(elems, AlgebraicValue::into_u8)?.into(),
+                BuiltinType::I16 => collect(elems, AlgebraicValue::into_i16)?.into(),
+                BuiltinType::U16 => collect(elems, AlgebraicValue::into_u16)?.into(),
+                BuiltinType::I32 => collect(elems, AlgebraicValue::into_i32)?.into(),
+                BuiltinType::U32 => collect(elems, AlgebraicValue::into_u32)?.into(),
+                BuiltinType::I64 => collect(elems, AlgebraicValue::into_i64)?.into(),
+                BuiltinType::U64 => collect(elems, AlgebraicValue::into_u64)?.into(),
+                BuiltinType::I128 => collect(elems, AlgebraicValue::into_i128)?.into(),
+                BuiltinType::U128 => collect(elems, AlgebraicValue::into_u128)?.into(),
+                BuiltinType::F32 => collect(elems, AlgebraicValue::into_f32)?.into(),
+                BuiltinType::F64 => collect(elems, AlgebraicValue::into_f64)?.into(),
+                BuiltinType::String => collect(elems, AlgebraicValue::into_string)?.into(),
+                BuiltinType::Array(_) => collect(elems, AlgebraicValue::into_array)?.into(),
+                BuiltinType::Map(_) => collect(elems, AlgebraicValue::into_map)?.into(),
+            },
+            AlgebraicType::Ref(_) => return None,
+        })
+    }
 }
 
 impl Default for ArrayValue {
@@ -0,0 +1,512 @@
+//! The canonical JSON encoding of SATS values, directed by their types,
+//! for readers and writers of JSON that know nothing of SATS.
+//!
+//! A value is encoded according to its type:
+//!
+//! - `Bool`s are booleans, and integers of up to 64 bits are numbers.
+//! - 128-bit integers are decimal strings, as most JSON parsers can't represent them as numbers.
+//! - Floats are numbers, or the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+//! - Strings are strings, and byte arrays are hex strings.
+//! - Other arrays are arrays, and maps are arrays of `[key, value]` pairs, as keys needn't be strings.
+//! - Products are objects keyed by field name, or by position for unnamed fields.
+//! - Options are their value, or `null` for `none`,
+//!   unless their value is itself an option, in which case they're encoded like other sums.
+//! - Sums whose variants hold no data are the name of the variant,
+//!   and other sums are an object with the name of the variant, or its tag if unnamed, as the only key.
+//!
+//! Decoding also accepts what the serde encoding of SATS produces,
+//! so that clients written against it keep working:
+//! integers as strings, and 128-bit integers as numbers when they fit in 64 bits,
+//! byte arrays as arrays of numbers, maps with string keys as objects,
+//! products as arrays of their fields in order,
+//! and options as other sums, e.g., `{"some": 5}`.
+//! Fields of products which are options can be left out, to mean `none`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::{Map, Number, Value};
+
+use crate::ser::serde::SerializeWrapper;
+use crate::{
+    AlgebraicType, AlgebraicValue, ArrayValue, BuiltinType, BuiltinValue, ProductType, ProductValue, SumType,
+    WithTypespace,
+};
+
+/// Returns the canonical JSON encoding of `value`, of the type `ty`.
+///
+/// A value not of its type is encoded as by the serde encoding of SATS.
+pub fn to_value(ty: WithTypespace<'_, AlgebraicType>, value: &AlgebraicValue) -> Value {
+    let Some(ty) = resolve(ty) else {
+        return untyped_value(value);
+    };
+    match (ty.ty(), value) {
+        (AlgebraicType::Sum(sum_ty), AlgebraicValue::Sum(sum)) => {
+            let Some(variant) = sum_ty.variants.get(sum.tag as usize) else {
+                return untyped_value(value);
+            };
+            if let Some(some_ty) = option_some_type(ty.with(sum_ty)) {
+                return match sum.tag {
+                    0 => to_value(some_ty, &sum.value),
+                    _ => Value::Null,
+                };
+            }
+            let name = variant.name.clone().unwrap_or_else(|| sum.tag.to_string());
+            if sum_ty.is_simple_enum() {
+                Value::String(name)
+            } else {
+                let value = to_value(ty.with(&variant.algebraic_type), &sum.value);
+                Value::Object(Map::from_iter([(name, value)]))
+            }
+        }
+        (AlgebraicType::Product(product_ty), AlgebraicValue::Product(product)) => Value::Object(
+            product_ty
+                .elements
+                .iter()
+                .zip(&product.elements)
+                .enumerate()
+                .map(|(i, (field, value))| {
+                    let name = field.name.clone().unwrap_or_else(|| i.to_string());
+                    (name, to_value(ty.with(&field.algebraic_type), value))
+                })
+                .collect(),
+        ),
+        (AlgebraicType::Builtin(builtin_ty), AlgebraicValue::Builtin(builtin)) => match (builtin_ty, builtin) {
+            (BuiltinType::I128, BuiltinValue::I128(v)) => Value::String(v.to_string()),
+            (BuiltinType::U128, BuiltinValue::U128(v)) => Value::String(v.to_string()),
+            // Going through the shortest decimal of the `f32` encodes `0.1` as such,
+            // rather than as the `f64` closest to the `f32` closest to `0.1`.
+            (BuiltinType::F32, BuiltinValue::F32(v)) => float_to_value(f32::from(*v).to_string().parse().unwrap()),
+            (BuiltinType::F64, BuiltinValue::F64(v)) => float_to_value((*v).into()),
+            (BuiltinType::Array(array_ty), BuiltinValue::Array { val }) => match val {
+                ArrayValue::U8(bytes) => Value::String(hex::encode(bytes)),
+                _ => Value::Array(
+                    val.iter_cloned()
+                        .map(|elem| to_value(ty.with(&*array_ty.elem_ty), &elem))
+                        .collect(),
+                ),
+            },
+            (BuiltinType::Map(map_ty), BuiltinValue::Map { val }) => Value::Array(
+                val.iter()
+                    .map(|(k, v)| {
+                        let k = to_value(ty.with(&*map_ty.key_ty), k);
+                        Value::Array(vec![k, to_value(ty.with(&*map_ty.ty), v)])
+                    })
+                    .collect(),
+            ),
+            // Booleans, integers of up to 64 bits and strings are encoded alike by SATS' serde encoding.
+            _ => untyped_value(value),
+        },
+        _ => untyped_value(value),
+    }
+}
+
+/// Returns the canonical JSON encoding of `value`, of the type `ty`, as a string.
+pub fn to_string(ty: WithTypespace<'_, AlgebraicType>, value: &AlgebraicValue) -> String {
+    to_value(ty, value).to_string()
+}
+
+/// Decodes a value of the type `ty` from its JSON encoding `json`.
+pub fn from_value(ty: WithTypespace<'_, AlgebraicType>, json: &Value) -> Result<AlgebraicValue, JsonError> {
+    let ty = resolve(ty).ok_or_else(|| JsonError::new("the type refers to a type missing from its typespace"))?;
+    match ty.ty() {
+        AlgebraicType::Sum(sum_ty) => sum_from_value(ty.with(sum_ty), json),
+        AlgebraicType::Product(product_ty) => {
+            product_from_value(ty.with(product_ty), json).map(AlgebraicValue::Product)
+        }
+        AlgebraicType::Builtin(builtin_ty) => builtin_from_value(ty.with(builtin_ty), json),
+        AlgebraicType::Ref(_) => unreachable!("`resolve` resolves references"),
+    }
+}
+
+/// Decodes a value of the type `ty` from the JSON text `json`.
+pub fn from_str_seed(ty: WithTypespace<'_, AlgebraicType>, json: &str) -> Result<AlgebraicValue, JsonError> {
+    let json: Value = serde_json::from_str(json).map_err(JsonError::new)?;
+    from_value(ty, &json)
+}
+
+/// An error decoding a value from JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError {
+    path: Vec<PathSegment>,
+    message: String,
+}
+
+/// A step of the path from a JSON document to a value within it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The element at this index of an array.
+    Index(usize),
+    /// The value under this key of an object.
+    Key(String),
+}
+
+impl JsonError {
+    fn new(message: impl fmt::Display) -> Self {
+        Self {
+            path: Vec::new(),
+            message: message.to_string(),
+        }
+    }
+
+    fn expected(what: &str, json: &Value) -> Self {
+        Self::new(format_args!("expected {what}, got `{json}`"))
+    }
+
+    /// Returns `self` for an error within the value at `segment` of its parent.
+    fn at(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    /// Returns the path from the decoded JSON to the value that failed to decode,
+    /// which is empty if the document as a whole did.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if !self.path.is_empty() {
+            f.write_str(", at `")?;
+            for (i, segment) in self.path.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(".")?;
+                }
+                write!(f, "{segment}")?;
+            }
+            f.write_str("`")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{index}"),
+            Self::Key(key) => f.write_str(key),
+        }
+    }
+}
+
+/// Returns `ty` with the references at its top resolved, or `None` if one isn't in the typespace.
+fn resolve(mut ty: WithTypespace<'_, AlgebraicType>) -> Option<WithTypespace<'_, AlgebraicType>> {
+    // A reference can't resolve to itself through more references than there are types.
+    for _ in 0..=ty.typespace().types.len() {
+        match ty.ty() {
+            AlgebraicType::Ref(r) => ty = ty.with(ty.typespace().get(*r)?),
+            _ => return Some(ty),
+        }
+    }
+    None
+}
+
+/// Returns the type of the value of `ty` if it's an option which is encoded as its value or `null`,
+/// i.e., unless that value is itself an option, for which `null` would be ambiguous.
+fn option_some_type(ty: WithTypespace<'_, SumType>) -> Option<WithTypespace<'_, AlgebraicType>> {
+    let some_ty = ty.with(ty.ty().as_option()?);
+    match resolve(some_ty)?.ty() {
+        AlgebraicType::Sum(sum) if sum.as_option().is_some() => None,
+        _ => Some(some_ty),
+    }
+}
+
+/// Returns the serde encoding of SATS of `value`, for values whose type isn't known.
+fn untyped_value(value: &AlgebraicValue) -> Value {
+    serde_json::to_value(SerializeWrapper::from_ref(value)).unwrap_or(Value::Null)
+}
+
+fn float_to_value(f: f64) -> Value {
+    match Number::from_f64(f) {
+        Some(n) => Value::Number(n),
+        None if f.is_nan() => Value::String("NaN".into()),
+        None if f > 0.0 => Value::String("Infinity".into()),
+        None => Value::String("-Infinity".into()),
+    }
+}
+
+fn sum_from_value(ty: WithTypespace<'_, SumType>, json: &Value) -> Result<AlgebraicValue, JsonError> {
+    if let Some(some_ty) = option_some_type(ty) {
+        if json.is_null() {
+            return Ok(AlgebraicValue::OptionNone());
+        }
+        let some = from_value(some_ty, json);
+        // Options may also be encoded as other sums, e.g., `{"some": 5}`.
+        if some.is_ok() || !matches!(json, Value::Object(obj) if obj.len() == 1) {
+            return some.map(AlgebraicValue::OptionSome);
+        }
+    }
+
+    let (key, value) = match json {
+        Value::String(name) => (name, None),
+        Value::Object(obj) if obj.len() == 1 => obj.iter().next().map(|(k, v)| (k, Some(v))).unwrap(),
+        _ => {
+            let expected = "the name of a variant, or an object with a variant as its only key";
+            return Err(JsonError::expected(expected, json));
+        }
+    };
+    let variants = &ty.ty().variants;
+    let tag = variants
+        .iter()
+        .position(|variant| variant.has_name(key))
+        .or_else(|| key.parse().ok().filter(|&tag: &usize| tag < variants.len()))
+        .ok_or_else(|| JsonError::new(format_args!("no variant `{key}`")))?;
+    let variant_ty = ty.with(&variants[tag].algebraic_type);
+    let value = match value {
+        Some(value) => from_value(variant_ty, value).map_err(|e| e.at(PathSegment::Key(key.clone())))?,
+        None => match resolve(variant_ty).map(|ty| ty.ty()) {
+            Some(AlgebraicType::Product(product)) if product.elements.is_empty() => AlgebraicValue::UNIT,
+            _ => {
+                let message = format!("the variant `{key}` holds data, so must be an object with it as the key");
+                return Err(JsonError::new(message));
+            }
+        },
+    };
+    Ok(AlgebraicValue::sum(tag as u8, value))
+}
+
+fn product_from_value(ty: WithTypespace<'_, ProductType>, json: &Value) -> Result<ProductValue, JsonError> {
+    let fields = &ty.ty().elements;
+    let elements = match json {
+        Value::Object(obj) => {
+            let field_key = |i: usize| fields[i].name.clone().unwrap_or_else(|| i.to_string());
+            if let Some(unknown) = obj.keys().find(|k| !(0..fields.len()).any(|i| field_key(i) == **k)) {
+                return Err(JsonError::new(format_args!("unknown field `{unknown}`")));
+            }
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let (key, field_ty) = (field_key(i), ty.with(&field.algebraic_type));
+                    match obj.get(&key) {
+                        Some(value) => from_value(field_ty, value).map_err(|e| e.at(PathSegment::Key(key))),
+                        None => left_out_value(field_ty)
+                            .ok_or_else(|| JsonError::new(format_args!("missing field `{key}`"))),
+                    }
+                })
+                .collect::<Result<_, _>>()?
+        }
+        Value::Array(values) if values.len() <= fields.len() => fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let field_ty = ty.with(&field.algebraic_type);
+                match values.get(i) {
+                    Some(value) => from_value(field_ty, value).map_err(|e| e.at(PathSegment::Index(i))),
+                    None => left_out_value(field_ty).ok_or_else(|| {
+                        let message = format!("expected {} elements, got {}", fields.len(), values.len());
+                        JsonError::new(message)
+                    }),
+                }
+            })
+            .collect::<Result<_, _>>()?,
+        Value::Array(values) => {
+            let message = format!("expected at most {} elements, got {}", fields.len(), values.len());
+            return Err(JsonError::new(message));
+        }
+        _ => {
+            let expected = "an object of the fields by name, or an array of them in order";
+            return Err(JsonError::expected(expected, json));
+        }
+    };
+    Ok(ProductValue { elements })
+}
+
+/// Returns the value of a field of the type `ty` which was left out, which is `none` for options.
+fn left_out_value(ty: WithTypespace<'_, AlgebraicType>) -> Option<AlgebraicValue> {
+    match resolve(ty)?.ty() {
+        AlgebraicType::Sum(sum) if sum.as_option().is_some() => Some(AlgebraicValue::OptionNone()),
+        _ => None,
+    }
+}
+
+fn builtin_from_value(ty: WithTypespace<'_, BuiltinType>, json: &Value) -> Result<AlgebraicValue, JsonError> {
+    Ok(match ty.ty() {
+        BuiltinType::Bool => {
+            AlgebraicValue::Bool(json.as_bool().ok_or_else(|| JsonError::expected("a boolean", json))?)
+        }
+        BuiltinType::I8 => AlgebraicValue::I8(int_from_value(json)?),
+        BuiltinType::U8 => AlgebraicValue::U8(int_from_value(json)?),
+        BuiltinType::I16 => AlgebraicValue::I16(int_from_value(json)?),
+        BuiltinType::U16 => AlgebraicValue::U16(int_from_value(json)?),
+        BuiltinType::I32 => AlgebraicValue::I32(int_from_value(json)?),
+        BuiltinType::U32 => AlgebraicValue::U32(int_from_value(json)?),
+        BuiltinType::I64 => AlgebraicValue::I64(int_from_value(json)?),
+        BuiltinType::U64 => AlgebraicValue::U64(int_from_value(json)?),
+        BuiltinType::I128 => AlgebraicValue::I128(int_from_value(json)?),
+        BuiltinType::U128 => AlgebraicValue::U128(int_from_value(json)?),
+        BuiltinType::F32 => AlgebraicValue::F32(float_from_value::<f32>(json)?.into()),
+        BuiltinType::F64 => AlgebraicValue::F64(float_from_value::<f64>(json)?.into()),
+        BuiltinType::String => {
+            let s = json.as_str().ok_or_else(|| JsonError::expected("a string", json))?;
+            AlgebraicValue::String(s.to_owned())
+        }
+        BuiltinType::Array(array_ty) => {
+            let elem_ty = ty.with(&*array_ty.elem_ty);
+            let resolved_elem_ty = resolve(elem_ty).map_or(&*array_ty.elem_ty, |ty| ty.ty());
+            match json {
+                Value::String(s) if *resolved_elem_ty == AlgebraicType::U8 => {
+                    let bytes = hex::decode(s).map_err(|_| JsonError::expected("a hex string of bytes", json))?;
+                    AlgebraicValue::Bytes(bytes)
+                }
+                Value::Array(elems) => {
+                    let elems = elems
+                        .iter()
+                        .enumerate()
+                        .map(|(i, elem)| from_value(elem_ty, elem).map_err(|e| e.at(PathSegment::Index(i))))
+                        .collect::<Result<_, _>>()?;
+                    let array = ArrayValue::from_elements(resolved_elem_ty, elems);
+                    AlgebraicValue::ArrayOf(array.expect("decoded elements of the wrong type"))
+                }
+                _ => return Err(JsonError::expected("an array", json)),
+            }
+        }
+        BuiltinType::Map(map_ty) => {
+            let (key_ty, value_ty) = (ty.with(&*map_ty.key_ty), ty.with(&*map_ty.ty));
+            let mut map = BTreeMap::new();
+            match json {
+                Value::Array(entries) => {
+                    for (i, entry) in entries.iter().enumerate() {
+                        let at = |e: JsonError| e.at(PathSegment::Index(i));
+                        let Some([key, value]) = entry.as_array().and_then(|pair| <&[Value; 2]>::try_from(&**pair).ok()) else {
+                            return Err(at(JsonError::expected("a `[key, value]` pair", entry)));
+                        };
+                        let key = from_value(key_ty, key).map_err(|e| at(e.at(PathSegment::Index(0))))?;
+                        let value = from_value(value_ty, value).map_err(|e| at(e.at(PathSegment::Index(1))))?;
+                        map.insert(key, value);
+                    }
+                }
+                // A map with keys encoded as strings may also be an object.
+                Value::Object(entries) => {
+                    for (key, value) in entries {
+                        let at = |e: JsonError| e.at(PathSegment::Key(key.clone()));
+                        let decoded_key = from_value(key_ty, &Value::String(key.clone())).map_err(at)?;
+                        map.insert(decoded_key, from_value(value_ty, value).map_err(at)?);
+                    }
+                }
+                _ => return Err(JsonError::expected("an array of `[key, value]` pairs", json)),
+            }
+            AlgebraicValue::map(map)
+        }
+    })
+}
+
+fn int_from_value<T: TryFrom<u64> + TryFrom<i64> + FromStr>(json: &Value) -> Result<T, JsonError> {
+    let int = match json {
+        Value::Number(n) => (n.as_u64().and_then(|n| T::try_from(n).ok())).or_else(|| n.as_i64()?.try_into().ok()),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    int.ok_or_else(|| {
+        let ty = std::any::type_name::<T>();
+        JsonError::expected(
+            &format!("an integer in the range of `{ty}`, as a number or a string"),
+            json,
+        )
+    })
+}
+
+fn float_from_value<T: FromStr>(json: &Value) -> Result<T, JsonError> {
+    let float = match json {
+        // Parsing the decimal of the number as a `T` rounds it to the closest `T` directly.
+        Value::Number(n) => n.to_string().parse().ok(),
+        Value::String(s) if matches!(&**s, "NaN" | "Infinity" | "-Infinity") => s.parse().ok(),
+        _ => None,
+    };
+    float.ok_or_else(|| JsonError::expected("a number, `\"NaN\"`, `\"Infinity\"` or `\"-Infinity\"`", json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{product, ProductTypeElement, SumTypeVariant, Typespace};
+    use serde_json::json;
+
+    fn round_trip(ty: &AlgebraicType, value: AlgebraicValue, json: Value) {
+        let ty = WithTypespace::empty(ty);
+        assert_eq!(to_value(ty, &value), json);
+        assert_eq!(from_value(ty, &json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_json_encoding() {
+        let color = AlgebraicType::simple_enum(["red", "green"].into_iter());
+        let shape = AlgebraicType::sum(vec![
+            SumTypeVariant::new_named(AlgebraicType::F64, "circle"),
+            SumTypeVariant::new(AlgebraicType::U8, None),
+        ]);
+        let ty = AlgebraicType::product(vec![
+            ProductTypeElement::new_named(AlgebraicType::U128, "id"),
+            ProductTypeElement::new_named(AlgebraicType::option(AlgebraicType::String), "name"),
+            ProductTypeElement::new_named(AlgebraicType::bytes(), "hash"),
+            ProductTypeElement::new_named(AlgebraicType::F32, "ratio"),
+            ProductTypeElement::new_named(AlgebraicType::map(AlgebraicType::I8, AlgebraicType::Bool), "flags"),
+            ProductTypeElement::new_named(color, "color"),
+            ProductTypeElement::new(shape, None),
+        ]);
+        let value = product![
+            AlgebraicValue::U128(u128::MAX),
+            AlgebraicValue::OptionNone(),
+            AlgebraicValue::Bytes(vec![0xab, 0x01]),
+            AlgebraicValue::F32(f32::NEG_INFINITY.into()),
+            AlgebraicValue::map([(AlgebraicValue::I8(-1), AlgebraicValue::Bool(true))].into()),
+            AlgebraicValue::sum(1, AlgebraicValue::UNIT),
+            AlgebraicValue::sum(1, AlgebraicValue::U8(7))
+        ];
+        let json = json!({
+            "id": u128::MAX.to_string(),
+            "name": null,
+            "hash": "ab01",
+            "ratio": "-Infinity",
+            "flags": [[-1, true]],
+            "color": "green",
+            "6": { "1": 7 },
+        });
+        round_trip(&ty, value.into(), json);
+
+        let value = AlgebraicValue::OptionSome(AlgebraicValue::OptionNone());
+        let ty = AlgebraicType::option(AlgebraicType::option(AlgebraicType::F32));
+        round_trip(&ty, value, json!({ "some": null }));
+        round_trip(&AlgebraicType::F32, AlgebraicValue::F32(0.1.into()), json!(0.1));
+    }
+
+    #[test]
+    fn test_json_decodes_sats_serde_encoding() {
+        let typespace = Typespace::new(vec![AlgebraicType::product(vec![
+            ProductTypeElement::new_named(AlgebraicType::U64, "x"),
+            ProductTypeElement::new_named(AlgebraicType::option(AlgebraicType::String), "y"),
+        ])]);
+        let ty = AlgebraicType::array(AlgebraicType::Ref(crate::AlgebraicTypeRef(0)));
+        let value = from_str_seed(
+            WithTypespace::new(&typespace, &ty),
+            r#"[[5, {"some": "a"}], {"x": "6"}, [7, {"none": []}]]"#,
+        )
+        .unwrap();
+        let expected = [
+            product![5u64, AlgebraicValue::OptionSome(AlgebraicValue::String("a".into()))],
+            product![6u64, AlgebraicValue::OptionNone()],
+            product![7u64, AlgebraicValue::OptionNone()],
+        ];
+        assert_eq!(value, AlgebraicValue::ArrayOf(expected.to_vec()));
+    }
+
+    #[test]
+    fn test_json_error_path() {
+        let ty = AlgebraicType::product(vec![ProductTypeElement::new_named(
+            AlgebraicType::array(AlgebraicType::U8),
+            "bytes",
+        )]);
+        let err = from_str_seed(WithTypespace::empty(&ty), r#"{"bytes": [1, 256]}"#).unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Key("bytes".into()), PathSegment::Index(1)]);
+        assert_eq!(
+            err.to_string(),
+            "expected an integer in the range of `u8`, as a number or a string, got `256`, at `bytes.1`"
+        );
+    }
+}
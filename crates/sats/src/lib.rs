@@ -7,10 +7,14 @@ pub mod builtin_type;
 pub mod builtin_value;
 pub mod convert;
 pub mod de;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod meta_type;
 pub mod product_type;
 pub mod product_type_element;
 pub mod product_value;
+#[cfg(feature = "proptest")]
+pub mod proptest;
 mod resolve_refs;
 pub mod satn;
 pub mod ser;
@@ -121,6 +125,12 @@ impl<'a, T: ?Sized> WithTypespace<'a, T> {
         Self { typespace, ty }
     }
 
+    /// Wraps `ty` in the empty typespace, for a type without [`AlgebraicTypeRef`]s.
+    pub fn empty(ty: &'a T) -> Self {
+        static EMPTY_TYPESPACE: Typespace = Typespace::new(Vec::new());
+        Self::new(&EMPTY_TYPESPACE, ty)
+    }
+
     /// Returns the object that the context was created with.
     pub const fn ty(&self) -> &'a T {
         self.ty
@@ -53,6 +53,17 @@ impl ProductType {
             _ => false,
         }
     }
+
+    /// Returns whether this is the special case of `spacetimedb_lib::LargeBytesRef`.
+    pub fn is_large_bytes(&self) -> bool {
+        match &*self.elements {
+            [ProductTypeElement {
+                name: Some(name),
+                algebraic_type,
+            }] => name == "__large_bytes_key" && algebraic_type.is_bytes(),
+            _ => false,
+        }
+    }
 }
 
 impl<I: Into<ProductTypeElement>> FromIterator<I> for ProductType {
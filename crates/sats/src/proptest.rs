@@ -0,0 +1,216 @@
+//! Strategies generating SATS types and values for property-based testing with [`proptest`](::proptest),
+//! and helpers asserting that values survive a round trip through BSATN and JSON.
+//!
+//! Crates building on SATS, modules included, can enable the `proptest` feature in their dev-dependencies
+//! to fuzz the encoding of their own types:
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn player_round_trips(name in ".*", score: u32) {
+//!         let player = Player { name, score };
+//!         assert_bsatn_round_trip(&player);
+//!         assert_json_round_trip(&player);
+//!     }
+//! }
+//! ```
+
+use std::fmt::Debug;
+
+use ::proptest::prelude::*;
+use ::proptest::strategy::Union;
+
+use crate::de::serde::{DeserializeWrapper, SerdeDeserializer};
+use crate::de::{DeserializeOwned, DeserializeSeed};
+use crate::ser::serde::SerializeWrapper;
+use crate::ser::Serialize;
+use crate::{
+    bsatn, json, AlgebraicType, AlgebraicValue, ArrayValue, BuiltinType, ProductTypeElement, SumTypeVariant,
+    WithTypespace,
+};
+
+/// How deeply the generated types nest.
+const MAX_DEPTH: u32 = 4;
+/// The most elements of a generated product, variants of a sum, or elements of an array or map.
+const MAX_SIZE: usize = 4;
+
+/// Returns a strategy generating the builtin types other than arrays and maps.
+fn scalar_type() -> impl Strategy<Value = AlgebraicType> {
+    prop_oneof![
+        Just(AlgebraicType::Bool),
+        Just(AlgebraicType::I8),
+        Just(AlgebraicType::U8),
+        Just(AlgebraicType::I16),
+        Just(AlgebraicType::U16),
+        Just(AlgebraicType::I32),
+        Just(AlgebraicType::U32),
+        Just(AlgebraicType::I64),
+        Just(AlgebraicType::U64),
+        Just(AlgebraicType::I128),
+        Just(AlgebraicType::U128),
+        Just(AlgebraicType::F32),
+        Just(AlgebraicType::F64),
+        Just(AlgebraicType::String),
+    ]
+}
+
+/// Returns a strategy generating types as modules declare them,
+/// with named elements and variants, and without [`AlgebraicTypeRef`](crate::AlgebraicTypeRef)s.
+///
+/// The keys of the generated maps are strings, which JSON can encode.
+pub fn algebraic_type() -> impl Strategy<Value = AlgebraicType> {
+    scalar_type().prop_recursive(MAX_DEPTH, 32, MAX_SIZE as u32, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(AlgebraicType::array),
+            inner.clone().prop_map(AlgebraicType::option),
+            inner
+                .clone()
+                .prop_map(|ty| AlgebraicType::map(AlgebraicType::String, ty)),
+            prop::collection::vec(inner.clone(), 0..=MAX_SIZE).prop_map(|tys| {
+                let elements = tys.into_iter().enumerate();
+                let elements = elements.map(|(i, ty)| ProductTypeElement::new_named(ty, format!("field_{i}")));
+                AlgebraicType::product(elements.collect())
+            }),
+            prop::collection::vec(inner, 1..=MAX_SIZE).prop_map(|tys| {
+                let variants = tys.into_iter().enumerate();
+                let variants = variants.map(|(i, ty)| SumTypeVariant::new_named(ty, format!("variant_{i}")));
+                AlgebraicType::sum(variants.collect())
+            }),
+        ]
+    })
+}
+
+/// Returns a strategy generating values of the type `ty`.
+///
+/// The floats generated are finite, as JSON has no encoding for infinities and NaNs.
+///
+/// Panics if `ty` contains an [`AlgebraicTypeRef`](crate::AlgebraicTypeRef), as its typespace isn't known,
+/// or a sum without variants, which has no values.
+pub fn algebraic_value(ty: &AlgebraicType) -> BoxedStrategy<AlgebraicValue> {
+    match ty {
+        AlgebraicType::Sum(sum) => {
+            assert!(!sum.variants.is_empty(), "a sum without variants has no values");
+            let variants = sum.variants.iter().enumerate().map(|(tag, var)| {
+                let tag = tag as u8;
+                algebraic_value(&var.algebraic_type).prop_map(move |val| AlgebraicValue::sum(tag, val))
+            });
+            Union::new(variants).boxed()
+        }
+        AlgebraicType::Product(product) => {
+            let elements = product.elements.iter().map(|el| algebraic_value(&el.algebraic_type));
+            elements.collect::<Vec<_>>().prop_map(AlgebraicValue::product).boxed()
+        }
+        AlgebraicType::Builtin(ty) => match ty {
+            BuiltinType::Bool => any::<bool>().prop_map(AlgebraicValue::Bool).boxed(),
+            BuiltinType::I8 => any::<i8>().prop_map(AlgebraicValue::I8).boxed(),
+            BuiltinType::U8 => any::<u8>().prop_map(AlgebraicValue::U8).boxed(),
+            BuiltinType::I16 => any::<i16>().prop_map(AlgebraicValue::I16).boxed(),
+            BuiltinType::U16 => any::<u16>().prop_map(AlgebraicValue::U16).boxed(),
+            BuiltinType::I32 => any::<i32>().prop_map(AlgebraicValue::I32).boxed(),
+            BuiltinType::U32 => any::<u32>().prop_map(AlgebraicValue::U32).boxed(),
+            BuiltinType::I64 => any::<i64>().prop_map(AlgebraicValue::I64).boxed(),
+            BuiltinType::U64 => any::<u64>().prop_map(AlgebraicValue::U64).boxed(),
+            BuiltinType::I128 => any::<i128>().prop_map(AlgebraicValue::I128).boxed(),
+            BuiltinType::U128 => any::<u128>().prop_map(AlgebraicValue::U128).boxed(),
+            BuiltinType::F32 => (prop::num::f32::NORMAL | prop::num::f32::SUBNORMAL | prop::num::f32::ZERO)
+                .prop_map(|x| AlgebraicValue::F32(x.into()))
+                .boxed(),
+            BuiltinType::F64 => (prop::num::f64::NORMAL | prop::num::f64::SUBNORMAL | prop::num::f64::ZERO)
+                .prop_map(|x| AlgebraicValue::F64(x.into()))
+                .boxed(),
+            BuiltinType::String => any::<String>().prop_map(AlgebraicValue::String).boxed(),
+            BuiltinType::Array(array) => {
+                let elem_ty = (*array.elem_ty).clone();
+                prop::collection::vec(algebraic_value(&elem_ty), 0..=MAX_SIZE)
+                    .prop_map(move |elems| {
+                        let array = ArrayValue::from_elements(&elem_ty, elems);
+                        AlgebraicValue::ArrayOf(array.expect("array element of the wrong type"))
+                    })
+                    .boxed()
+            }
+            BuiltinType::Map(map) => {
+                let (keys, values) = (algebraic_value(&map.key_ty), algebraic_value(&map.ty));
+                prop::collection::btree_map(keys, values, 0..=MAX_SIZE)
+                    .prop_map(AlgebraicValue::map)
+                    .boxed()
+            }
+        },
+        AlgebraicType::Ref(r) => panic!("can't generate values of the type reference {r:?} without its typespace"),
+    }
+}
+
+/// Returns a strategy generating a type, as [`algebraic_type`] does, along with a value of it.
+pub fn typed_value() -> impl Strategy<Value = (AlgebraicType, AlgebraicValue)> {
+    algebraic_type().prop_flat_map(|ty| {
+        let values = algebraic_value(&ty);
+        (Just(ty), values)
+    })
+}
+
+impl Arbitrary for AlgebraicType {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        algebraic_type().boxed()
+    }
+}
+
+/// Generates values of arbitrary types, see [`typed_value`].
+impl Arbitrary for AlgebraicValue {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        typed_value().prop_map(|(_, value)| value).boxed()
+    }
+}
+
+/// Asserts that `value` encodes to BSATN and decodes back to itself.
+#[track_caller]
+pub fn assert_bsatn_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) {
+    let bytes = bsatn::to_vec(value).unwrap_or_else(|e| panic!("failed to encode {value:?} to BSATN: {e}"));
+    let decoded: T = bsatn::from_slice(&bytes).unwrap_or_else(|e| panic!("failed to decode {value:?} from BSATN: {e}"));
+    assert_eq!(&decoded, value, "the BSATN round trip changed the value");
+}
+
+/// Asserts that `value` encodes to JSON, in the serde encoding of SATS, and decodes back to itself.
+#[track_caller]
+pub fn assert_json_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) {
+    let json = serde_json::to_string(SerializeWrapper::from_ref(value))
+        .unwrap_or_else(|e| panic!("failed to encode {value:?} to JSON: {e}"));
+    let DeserializeWrapper(decoded) = serde_json::from_str::<DeserializeWrapper<T>>(&json)
+        .unwrap_or_else(|e| panic!("failed to decode {value:?} from the JSON {json}: {e}"));
+    assert_eq!(&decoded, value, "the JSON round trip changed the value");
+}
+
+/// Asserts that `value`, of the type `ty`, encodes to BSATN and decodes back to itself.
+#[track_caller]
+pub fn assert_value_bsatn_round_trip(ty: &AlgebraicType, value: &AlgebraicValue) {
+    let bytes = bsatn::to_vec(value).unwrap_or_else(|e| panic!("failed to encode {value:?} to BSATN: {e}"));
+    let decoded = AlgebraicValue::decode(ty, &mut &bytes[..])
+        .unwrap_or_else(|e| panic!("failed to decode {value:?} from BSATN: {e}"));
+    assert_eq!(&decoded, value, "the BSATN round trip changed the value");
+}
+
+/// Asserts that `value`, of the type `ty`, encodes to JSON and decodes back to itself,
+/// both in the serde encoding of SATS and in the [canonical encoding](crate::json) the HTTP API uses.
+#[track_caller]
+pub fn assert_value_json_round_trip(ty: &AlgebraicType, value: &AlgebraicValue) {
+    let ty = WithTypespace::empty(ty);
+    let json = json::to_string(ty, value);
+    let decoded = json::from_str_seed(ty, &json)
+        .unwrap_or_else(|e| panic!("failed to decode {value:?} from the canonical JSON {json}: {e}"));
+    assert_eq!(&decoded, value, "the canonical JSON round trip changed the value");
+
+    let json = serde_json::to_string(SerializeWrapper::from_ref(&ty.with_value(value)))
+        .unwrap_or_else(|e| panic!("failed to encode {value:?} to JSON: {e}"));
+
+    let mut de = serde_json::Deserializer::from_str(&json);
+    let decoded = ty
+        .deserialize(SerdeDeserializer::new(&mut de))
+        .map_err(|e| e.0)
+        .and_then(|decoded| de.end().map(|()| decoded))
+        .unwrap_or_else(|e| panic!("failed to decode {value:?} from the JSON {json}: {e}"));
+    assert_eq!(&decoded, value, "the JSON round trip changed the value");
+}
@@ -132,3 +132,16 @@ proptest! {
         prop_assert_eq!(original,parsed, "Original vs Parsed");
     }
 }
+
+/// Decoding is strict: a value written before a trailing option was added to its type
+/// is cut short, rather than read as `none`, which only the bindings do for stored rows.
+#[test]
+fn rejects_missing_trailing_option() {
+    let mut bytes = Vec::new();
+    product!(5u32).encode(&mut bytes);
+    let schema = ProductType::new(vec![
+        ProductTypeElement::new_named(AlgebraicType::U32, "x"),
+        ProductTypeElement::new_named(AlgebraicType::option(AlgebraicType::String), "y"),
+    ]);
+    assert!(ProductValue::decode(&schema, &mut &bytes[..]).is_err());
+}
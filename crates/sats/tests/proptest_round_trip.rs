@@ -0,0 +1,42 @@
+use proptest::prelude::*;
+use spacetimedb_sats::de::Deserialize;
+use spacetimedb_sats::proptest::{
+    assert_bsatn_round_trip, assert_json_round_trip, assert_value_bsatn_round_trip, assert_value_json_round_trip,
+    typed_value,
+};
+use spacetimedb_sats::ser::Serialize;
+use spacetimedb_sats::AlgebraicType;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[sats(crate = spacetimedb_sats)]
+struct Player {
+    name: String,
+    score: u32,
+    friends: Vec<u64>,
+    title: Option<String>,
+}
+
+proptest! {
+    #[test]
+    fn generated_values_round_trip((ty, value) in typed_value()) {
+        assert_value_bsatn_round_trip(&ty, &value);
+        assert_value_json_round_trip(&ty, &value);
+    }
+
+    #[test]
+    fn generated_types_round_trip(ty: AlgebraicType) {
+        assert_bsatn_round_trip(&ty);
+    }
+
+    #[test]
+    fn derived_types_round_trip(
+        name in ".*",
+        score: u32,
+        friends in prop::collection::vec(any::<u64>(), 0..4),
+        title: Option<String>,
+    ) {
+        let player = Player { name, score, friends, title };
+        assert_bsatn_round_trip(&player);
+        assert_json_round_trip(&player);
+    }
+}
@@ -353,7 +353,10 @@ impl BackgroundDbConnection {
     pub(crate) fn subscribe_owned(&self, queries: Vec<String>) -> Result<()> {
         self.send_message(client_api_messages::Message {
             r#type: Some(client_api_messages::message::Type::Subscribe(
-                client_api_messages::Subscribe { query_strings: queries },
+                client_api_messages::Subscribe {
+                    query_strings: queries,
+                    resume_from_tx_offset: 0,
+                },
             )),
         })
         .with_context(|| "Subscribing to new queries")
@@ -18,9 +18,10 @@
 use crate::{
     client_api_messages,
     client_cache::ClientCacheView,
+    event::EventType,
     global_connection::CurrentStateGuard,
     identity::{Credentials, Identity, Token},
-    reducer::{AnyReducerEvent, Reducer, Status},
+    reducer::{AnyReducerEvent, Reducer, ReducerWithReturn, Status},
     table::TableType,
 };
 use anymap::{any::Any, Map};
@@ -137,6 +138,7 @@ impl<Args> std::fmt::Debug for CallbackId<Args> {
 // - `()` -> `()`, for `on_subscription_applied`.
 // - `(T, T, U)` -> `(&T, &T, U)`, for `TableWithPrimaryKey::on_update`.
 // - `(Identity, Status, R)` -> `(&Identity, &Status, &R)`, for `Reducer::on_reducer`.
+// - `(E,)` -> `&E`, for `EventType::on_event`.
 
 impl OwnedArgs for Credentials {
     type Borrowed<'a> = &'a Credentials;
@@ -187,6 +189,29 @@ where
     }
 }
 
+/// The arguments to an on-return callback: the caller, the reducer's arguments and the value it returned.
+///
+/// A struct rather than a tuple, which would overlap with the arguments to on-reducer callbacks.
+pub struct ReturnArgs<R: ReducerWithReturn> {
+    caller_identity: Identity,
+    args: R,
+    value: R::Return,
+}
+
+impl<R: ReducerWithReturn> OwnedArgs for ReturnArgs<R> {
+    type Borrowed<'a> = (&'a Identity, &'a R, &'a R::Return);
+    fn borrow(&self) -> (&Identity, &R, &R::Return) {
+        (&self.caller_identity, &self.args, &self.value)
+    }
+}
+
+impl<E: EventType> OwnedArgs for (E,) {
+    type Borrowed<'a> = &'a E;
+    fn borrow(&self) -> &E {
+        &self.0
+    }
+}
+
 /// A message sent by a `CallbackMap` to its background worker to request some action,
 /// either adding a new callback, removing a previous callback,
 /// or invoking all registered callbacks.
@@ -704,6 +729,53 @@ impl ReducerCallbacks {
         state: ClientCacheView,
         wrap: fn(R) -> ReducerEvent,
     ) -> Option<Arc<AnyReducerEvent>> {
+        let instance = self.handle_reducer_event::<R>(event, state)?;
+        Some(Arc::new(wrap(instance)))
+    }
+
+    /// Like `handle_event_of_type`, for a reducer which returns a value,
+    /// then also parse the value, if any, and invoke any on-return callbacks
+    /// registered for that reducer.
+    ///
+    /// Calls to this method are autogenerated in the `handle_event` function
+    /// for the reducers which return a value. Users should not call this method directly.
+    pub fn handle_event_with_return_of_type<R: ReducerWithReturn, ReducerEvent: Any + Send + Sync>(
+        &mut self,
+        mut event: client_api_messages::Event,
+        state: ClientCacheView,
+        wrap: fn(R) -> ReducerEvent,
+    ) -> Option<Arc<AnyReducerEvent>> {
+        let return_value = std::mem::take(&mut event.return_value);
+        let caller_identity = Identity::from_bytes(event.caller_identity.clone());
+        let instance = self.handle_reducer_event::<R>(event, state.clone())?;
+        // Only a committed run returns a value.
+        if !return_value.is_empty() {
+            match bsatn::from_slice::<R::Return>(&return_value) {
+                Err(e) => log::error!(
+                    "Error while deserializing the value returned by {}: {:?}",
+                    R::REDUCER_NAME,
+                    e
+                ),
+                Ok(value) => self.find_return_callbacks::<R>().invoke(
+                    ReturnArgs {
+                        caller_identity,
+                        args: instance.clone(),
+                        value,
+                    },
+                    state,
+                ),
+            }
+        }
+        Some(Arc::new(wrap(instance)))
+    }
+
+    /// Parse the reducer arguments, caller identity and status of the reducer run described by `event`,
+    /// invoke any on-reducer callbacks registered for that reducer, and return the arguments.
+    fn handle_reducer_event<R: Reducer>(
+        &mut self,
+        event: client_api_messages::Event,
+        state: ClientCacheView,
+    ) -> Option<R> {
         let client_api_messages::Event {
             caller_identity,
             function_call: Some(function_call),
@@ -729,7 +801,7 @@ impl ReducerCallbacks {
                 // TODO: should reducer callbacks' `OwnedArgs` impl take an `Arc<R>` rather than an `R`?
                 self.find_callbacks::<R>()
                     .invoke((identity, status, instance.clone()), state);
-                Some(Arc::new(wrap(instance)))
+                Some(instance)
             }
         }
     }
@@ -764,6 +836,84 @@ impl ReducerCallbacks {
         self.find_callbacks::<R>().remove(id);
     }
 
+    pub(crate) fn find_return_callbacks<R: ReducerWithReturn>(&mut self) -> &mut CallbackMap<ReturnArgs<R>> {
+        self.callbacks
+            .entry::<CallbackMap<ReturnArgs<R>>>()
+            .or_insert_with(|| CallbackMap::spawn(&self.runtime))
+    }
+
+    /// Register an on-return callback to run whenever a run of the reducer `R` returns a value.
+    pub(crate) fn register_on_return<R: ReducerWithReturn>(
+        &mut self,
+        mut callback: impl FnMut(&Identity, &R, &R::Return) + Send + 'static,
+    ) -> CallbackId<ReturnArgs<R>> {
+        self.find_return_callbacks::<R>()
+            .insert(Box::new(move |(caller, args, value): (&Identity, &R, &R::Return)| {
+                callback(caller, args, value)
+            }))
+    }
+
+    /// Register an on-return callback to run at most once
+    /// when a run of the reducer `R` returns a value.
+    pub(crate) fn register_on_return_oneshot<R: ReducerWithReturn>(
+        &mut self,
+        callback: impl FnOnce(&Identity, &R, &R::Return) + Send + 'static,
+    ) -> CallbackId<ReturnArgs<R>> {
+        self.find_return_callbacks::<R>()
+            .insert_oneshot(move |(caller, args, value)| callback(caller, args, value))
+    }
+
+    /// Unregister a previously-registered on-return callback identified by `id`.
+    pub(crate) fn unregister_on_return<R: ReducerWithReturn>(&mut self, id: CallbackId<ReturnArgs<R>>) {
+        self.find_return_callbacks::<R>().remove(id);
+    }
+
+    pub(crate) fn find_event_callbacks<E: EventType>(&mut self) -> &mut CallbackMap<(E,)> {
+        self.callbacks
+            .entry::<CallbackMap<(E,)>>()
+            .or_insert_with(|| CallbackMap::spawn(&self.runtime))
+    }
+
+    /// Parse the event emitted by a reducer, and invoke any on-event callbacks
+    /// registered for its type.
+    ///
+    /// Calls to this method are autogenerated in the `handle_event` function, which
+    /// handles dispatching on the event's name to find the appropriate type `E` to
+    /// `handle_emitted_event_of_type`. Users should not call this method directly.
+    pub fn handle_emitted_event_of_type<E: EventType>(
+        &mut self,
+        emitted: client_api_messages::EmittedEvent,
+        state: ClientCacheView,
+    ) {
+        match bsatn::from_slice::<E>(&emitted.data) {
+            Err(e) => log::error!("Error while deserializing event {}: {:?}", emitted.name, e),
+            Ok(event) => self.find_event_callbacks::<E>().invoke((event,), state),
+        }
+    }
+
+    /// Register an on-event callback to run whenever we receive an emitted event of type `E`.
+    pub(crate) fn register_on_event<E: EventType>(
+        &mut self,
+        mut callback: impl FnMut(&E) + Send + 'static,
+    ) -> CallbackId<(E,)> {
+        self.find_event_callbacks::<E>()
+            .insert(Box::new(move |event: &E| callback(event)))
+    }
+
+    /// Register an on-event callback to run at most once
+    /// when we receive an emitted event of type `E`.
+    pub(crate) fn register_on_event_oneshot<E: EventType>(
+        &mut self,
+        callback: impl FnOnce(&E) + Send + 'static,
+    ) -> CallbackId<(E,)> {
+        self.find_event_callbacks::<E>().insert_oneshot(callback)
+    }
+
+    /// Unregister a previously-registered on-event callback identified by `id`.
+    pub(crate) fn unregister_on_event<E: EventType>(&mut self, id: CallbackId<(E,)>) {
+        self.find_event_callbacks::<E>().remove(id);
+    }
+
     /// Invoke the autogenerated `handle_event` function
     /// to dispatch on the reducer named by `event`,
     /// and invoke `handle_event_of_type` with an appropriate type arg.
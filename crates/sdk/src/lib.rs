@@ -1,6 +1,7 @@
 #[doc(hidden)]
 pub mod callbacks;
 
+pub mod event;
 pub mod identity;
 pub mod reducer;
 pub mod table;
@@ -1,4 +1,4 @@
-use crate::callbacks::CallbackId;
+use crate::callbacks::{CallbackId, ReturnArgs};
 use crate::global_connection::{with_connection, with_reducer_callbacks};
 use crate::identity::Identity;
 use anyhow::Result;
@@ -63,4 +63,52 @@ pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
     }
 }
 
+#[derive(Copy, Clone)]
+pub struct ReturnCallbackId<R: ReducerWithReturn> {
+    id: CallbackId<ReturnArgs<R>>,
+}
+
+/// A reducer which returns a value to its caller.
+///
+/// Types which implement `ReducerWithReturn` are autogenerated by the SpacetimeDB CLI's
+/// `generate` command, for the reducers declared as returning `Result<T, E>`.
+/// Users should not `impl ReducerWithReturn`.
+pub trait ReducerWithReturn: Reducer {
+    /// The type of the value the reducer returns.
+    type Return: DeserializeOwned + Any + Send + Sync + Clone;
+
+    /// Register a callback to run whenever a run of the reducer returns a value,
+    /// with the identity of its caller, its arguments, and the value.
+    ///
+    /// Values are only received for reducers which commit,
+    /// after the `on_reducer` callbacks of the same run.
+    ///
+    /// The returned `ReturnCallbackId` can be passed to `remove_on_return` to
+    /// unregister the callback.
+    fn on_return(callback: impl FnMut(&Identity, &Self, &Self::Return) + Send + 'static) -> ReturnCallbackId<Self> {
+        let id = with_reducer_callbacks(|callbacks| callbacks.register_on_return::<Self>(callback));
+        ReturnCallbackId { id }
+    }
+
+    /// Register a callback to run once, the next time a run of the reducer returns a value.
+    ///
+    /// The `callback` will run at most once, then unregister itself.
+    /// It can also be unregistered by passing the returned `ReturnCallbackId`
+    /// to `remove_on_return`.
+    fn once_on_return(
+        callback: impl FnOnce(&Identity, &Self, &Self::Return) + Send + 'static,
+    ) -> ReturnCallbackId<Self> {
+        let id = with_reducer_callbacks(|callbacks| callbacks.register_on_return_oneshot::<Self>(callback));
+        ReturnCallbackId { id }
+    }
+
+    /// Unregister a previously-registered `on_return` callback.
+    ///
+    /// If `id` does not refer to a currently-registered callback, this operation will do
+    /// nothing.
+    fn remove_on_return(id: ReturnCallbackId<Self>) {
+        with_reducer_callbacks(|callbacks| callbacks.unregister_on_return::<Self>(id.id));
+    }
+}
+
 pub type AnyReducerEvent = dyn Any + Send + Sync;
@@ -1,5 +1,6 @@
 use crate::StandaloneEnv;
 use spacetimedb::host::{EnergyDiff, EnergyMonitor, EnergyMonitorFingerprint, EnergyQuanta};
+use spacetimedb::messages::control_db::EnergyPricing;
 use spacetimedb_client_api::ControlNodeDelegate;
 use std::{
     sync::{Arc, Mutex, Weak},
@@ -30,6 +31,21 @@ impl EnergyMonitor for StandaloneEnergyMonitor {
         EnergyQuanta(i128::max_value())
     }
 
+    fn reducer_pricing(&self, fingerprint: &EnergyMonitorFingerprint<'_>) -> EnergyPricing {
+        let standalone_env = self.inner.lock().unwrap().standalone_env.upgrade();
+        let Some(standalone_env) = standalone_env else {
+            return EnergyPricing::default();
+        };
+        standalone_env
+            .control_db
+            .get_energy_pricing(&fingerprint.database_address, fingerprint.reducer_name)
+            .unwrap_or_else(|e| {
+                log::error!("Failed to get the energy pricing of {}: {e}", fingerprint.reducer_name);
+                None
+            })
+            .unwrap_or_default()
+    }
+
     fn record(
         &self,
         fingerprint: &EnergyMonitorFingerprint<'_>,
@@ -13,11 +13,13 @@ use openssl::ec::{EcGroup, EcKey};
 use openssl::nid::Nid;
 use openssl::pkey::PKey;
 use spacetimedb::address::Address;
-use spacetimedb::auth::identity::{DecodingKey, EncodingKey};
+use spacetimedb::auth::identity::{ApiToken, DecodingKey, EncodingKey};
+use spacetimedb::auth::oidc::OidcIssuers;
 use spacetimedb::client::ClientActorIndex;
 use spacetimedb::control_db::ControlDb;
 use spacetimedb::database_instance_context::DatabaseInstanceContext;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
+use spacetimedb::db::datastore::locking_tx_datastore::Quota;
 use spacetimedb::db::{db_metrics, Storage};
 use spacetimedb::hash::Hash;
 use spacetimedb::host::UpdateOutcome;
@@ -30,13 +32,18 @@ use spacetimedb::module_host_context::ModuleHostContext;
 use spacetimedb::object_db::ObjectDb;
 use spacetimedb::sendgrid_controller::SendGridController;
 use spacetimedb::{stdb_path, worker_metrics};
-use spacetimedb_lib::name::DomainName;
+use spacetimedb_lib::name::{DomainName, UpdatePlan};
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use worker_db::WorkerDb;
 
+/// How long pausing a database instance waits for everything using it to let go of it.
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(30);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct StandaloneEnv {
     worker_db: WorkerDb,
     control_db: ControlDb,
@@ -46,6 +53,8 @@ pub struct StandaloneEnv {
     client_actor_index: ClientActorIndex,
     public_key: DecodingKey,
     private_key: EncodingKey,
+    /// The OpenID Connect providers whose tokens clients may authenticate with.
+    oidc_issuers: OidcIssuers,
 
     /// Whether databases in this environment will be created entirely in memory
     /// or otherwise persist their message log and object store to disk.
@@ -62,9 +71,16 @@ impl StandaloneEnv {
         let db_inst_ctx_controller = DatabaseInstanceContextController::new();
         let control_db = ControlDb::new()?;
         let energy_monitor = Arc::new(StandaloneEnergyMonitor::new());
-        let host_controller = Arc::new(HostController::new(energy_monitor.clone()));
+        let host_controller = Arc::new(HostController::new(
+            energy_monitor.clone(),
+            Arc::new(control_db.clone()),
+        ));
         let client_actor_index = ClientActorIndex::new();
         let (public_key, private_key) = get_or_create_keys()?;
+        let oidc_issuers = match get_key_path("SPACETIMEDB_OIDC_ISSUERS") {
+            Some(path) => OidcIssuers::load(&path)?,
+            None => OidcIssuers::default(),
+        };
         let this = Arc::new(Self {
             worker_db,
             control_db,
@@ -74,6 +90,7 @@ impl StandaloneEnv {
             client_actor_index,
             public_key,
             private_key,
+            oidc_issuers,
             storage,
         });
         energy_monitor.set_standalone_env(this.clone());
@@ -232,6 +249,10 @@ impl spacetimedb_client_api::ControlStateDelegate for StandaloneEnv {
             .get_leader_database_instance_by_database(database_id)
             .await
     }
+
+    async fn get_linked_identity(&self, identity: &Identity) -> spacetimedb::control_db::Result<Option<Identity>> {
+        self.control_db.get_linked_identity(identity)
+    }
 }
 
 #[async_trait::async_trait]
@@ -288,11 +309,47 @@ impl spacetimedb_client_api::ControlCtx for StandaloneEnv {
         let new_database = database.clone();
         self.control_db.update_database(database).await?;
 
-        self.schedule_database(Some(new_database), Some(old_database)).await?;
-        self.update_database_instances(database_id)
+        self.schedule_database(Some(new_database.clone()), Some(old_database.clone()))
+            .await?;
+        let result = self
+            .update_database_instances(database_id)
             .await
             // TODO(kim): this should really only run on the leader instance
-            .map(|mut res| res.pop().flatten())
+            .map(|mut res| res.pop().flatten());
+
+        // The instances have been rolled back to the old program, so it's the one to load from now on.
+        let rolled_back = match &result {
+            Ok(Some(Ok(success))) => !success.committed(),
+            Ok(None) => false,
+            Ok(Some(Err(_))) | Err(_) => true,
+        };
+        if rolled_back {
+            let mut database = new_database;
+            database.program_bytes_address = old_database.program_bytes_address;
+            self.control_db.update_database(database).await?;
+        }
+        result
+    }
+
+    async fn plan_database_update(
+        &self,
+        address: &Address,
+        program_bytes_address: &Hash,
+    ) -> Result<Option<UpdatePlan>, anyhow::Error> {
+        let Some(mut database) = self.control_db.get_database_by_address(address).await? else {
+            return Ok(None);
+        };
+        let instances = self.control_db.get_database_instances_by_database(database.id).await?;
+        let Some(instance) = instances.iter().find(|instance| instance.leader).or(instances.first()) else {
+            anyhow::bail!("database {} has no instances", address.to_hex());
+        };
+
+        database.program_bytes_address = *program_bytes_address;
+        let module_host_context = self.load_module_host_context_inner(database, instance.id).await?;
+        self.host_controller
+            .plan_module_update(module_host_context)
+            .await
+            .map(Some)
     }
 
     async fn delete_database(&self, address: &Address) -> Result<(), anyhow::Error> {
@@ -304,6 +361,45 @@ impl spacetimedb_client_api::ControlCtx for StandaloneEnv {
         Ok(())
     }
 
+    async fn pause_database(&self, address: &Address) -> Result<(), anyhow::Error> {
+        let Some(database) = self.control_db.get_database_by_address(address).await? else {
+            return Ok(());
+        };
+        // An in-memory database has nowhere to keep its data while it's unloaded.
+        if let Storage::Memory = self.storage {
+            anyhow::bail!("databases kept in memory can't be paused");
+        }
+        for instance in self.control_db.get_database_instances_by_database(database.id).await? {
+            self.pause_database_instance(instance.id).await?;
+        }
+        Ok(())
+    }
+
+    async fn resume_database(&self, address: &Address) -> Result<(), anyhow::Error> {
+        let Some(database) = self.control_db.get_database_by_address(address).await? else {
+            return Ok(());
+        };
+        for instance in self.control_db.get_database_instances_by_database(database.id).await? {
+            if self.host_controller.get_module_host(instance.id).is_err() {
+                self.start_module_on_database_instance(database.id, instance.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_quota(&self, address: &Address, quota: &Quota) -> Result<(), anyhow::Error> {
+        self.control_db.set_quota(address, quota).await?;
+        let Some(database) = self.control_db.get_database_by_address(address).await? else {
+            return Ok(());
+        };
+        for instance in self.control_db.get_database_instances_by_database(database.id).await? {
+            if let Some((dbic, _)) = self.db_inst_ctx_controller.get(instance.id) {
+                dbic.relational_db.set_quota(*quota);
+            }
+        }
+        Ok(())
+    }
+
     fn object_db(&self) -> &ObjectDb {
         &self.object_db
     }
@@ -340,12 +436,27 @@ impl spacetimedb_client_api::ControlNodeDelegate for StandaloneEnv {
             .await
     }
 
+    async fn get_api_token(&self, id: u64) -> spacetimedb::control_db::Result<Option<ApiToken>> {
+        self.control_db.get_api_token(id)
+    }
+
+    async fn get_or_create_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> spacetimedb::control_db::Result<Identity> {
+        self.control_db.get_or_create_oidc_identity(issuer, subject).await
+    }
+
     fn public_key(&self) -> &DecodingKey {
         &self.public_key
     }
     fn private_key(&self) -> &EncodingKey {
         &self.private_key
     }
+    fn oidc_issuers(&self) -> &OidcIssuers {
+        &self.oidc_issuers
+    }
 }
 
 impl StandaloneEnv {
@@ -511,6 +622,37 @@ impl StandaloneEnv {
         }
     }
 
+    /// Exit the module host of an instance and close its database once nothing else is using it,
+    /// which a connection that was closed along with the module host may still be, for a little while.
+    ///
+    /// Unlike deleting it, this keeps its scheduled reducers, which run again once it's resumed.
+    async fn pause_database_instance(&self, instance_id: u64) -> Result<(), anyhow::Error> {
+        self.host_controller.delete_module_host(instance_id).await?;
+
+        let deadline = Instant::now() + PAUSE_TIMEOUT;
+        let (dbic, scheduler) = loop {
+            match self.db_inst_ctx_controller.remove_unused(instance_id) {
+                Ok(Some(context)) => break context,
+                // Never loaded since this node started, so there's nothing to unload.
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    if self.host_controller.get_module_host(instance_id).is_ok() {
+                        anyhow::bail!("database instance {instance_id} was resumed while it was being paused");
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(e).with_context(|| format!("failed to pause database instance {instance_id}"));
+                    }
+                    tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+                }
+            }
+        };
+        scheduler.flush()?;
+        scheduler.close();
+        drop((dbic, scheduler));
+        log::info!("Paused database instance {instance_id}");
+        Ok(())
+    }
+
     async fn load_module_host_context(
         &self,
         database_id: u64,
@@ -543,6 +685,8 @@ impl StandaloneEnv {
             } else {
                 let dbic =
                     DatabaseInstanceContext::from_database(self.storage, &database, instance_id, root_db_path.clone());
+                dbic.relational_db
+                    .set_quota(self.control_db.get_quota(&database.address)?);
                 let (scheduler, scheduler_starter) = Scheduler::open(dbic.scheduler_db_path(root_db_path))?;
                 self.db_inst_ctx_controller.insert(dbic.clone(), scheduler.clone());
                 (dbic, (scheduler, scheduler_starter))
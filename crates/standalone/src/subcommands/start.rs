@@ -5,7 +5,9 @@ use clap::ArgAction::SetTrue;
 use clap::{Arg, ArgMatches};
 use spacetimedb::db::{db_metrics, Storage};
 use spacetimedb::{startup, worker_metrics};
-use std::net::TcpListener;
+use spacetimedb_client_api::{pg_wire, WorkerCtx};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
 
 #[cfg(feature = "string")]
 impl From<std::string::String> for OsStr {
@@ -110,7 +112,13 @@ pub fn cli(mode: ProgramMode) -> clap::Command {
                 \n\tSTDB_PATH: The path to the directory that should contain the database files for SpacetimeDB. \
                 \n\tSPACETIMEDB_JWT_PUB_KEY: The path to the public jwt key for verifying identities. \
                 \n\tSPACETIMEDB_JWT_PRIV_KEY: The path to the private jwt key for issuing identities. \
+                \n\tSPACETIMEDB_OIDC_ISSUERS: The path to the JSON file of the OpenID Connect providers whose tokens clients may authenticate with. \
                 \n\tSPACETIMEDB_TRACY: Set to 1 to enable Tracy profiling.\
+                \n\tSPACETIMEDB_MODULE_LOG_MAX_SIZE: The most bytes of module logs to keep per database. \
+                \n\tSPACETIMEDB_MODULE_LOG_MAX_AGE: How long to keep module logs, e.g. `7days`. \
+                \n\tSPACETIMEDB_GUEST_IDENTITIES: Set to true to give clients that connect without a token short-lived guest identities. \
+                \n\tSPACETIMEDB_GUEST_TOKEN_TTL: How long the token of a guest identity is valid, e.g. `12h`. \
+                \n\tSPACETIMEDB_TRUSTED_PROXIES: The comma-separated addresses of the proxies whose X-Forwarded-For header gives the address of a client.\
                 \n\nWarning: If you set a value on the command line, it will override the value set in the environment variable.")
         .arg(
             Arg::new("listen_addr")
@@ -119,6 +127,11 @@ pub fn cli(mode: ProgramMode) -> clap::Command {
                 .default_value(mode.listen_addr())
                 .help(mode.listen_addr_help())
         )
+        .arg(
+            Arg::new("pg_listen_addr")
+                .long("pg-listen-addr")
+                .help("The address and port where SpacetimeDB should accept Postgres wire protocol connections, for read-only SQL queries. Postgres connections are disabled unless this is set.")
+        )
         .arg(log_conf_path_arg)
         .arg(log_dir_path_arg)
         .arg(database_path_arg)
@@ -130,6 +143,35 @@ pub fn cli(mode: ProgramMode) -> clap::Command {
         )
         .arg(jwt_pub_key_path_arg)
         .arg(jwt_priv_key_path_arg)
+        .arg(
+            Arg::new("oidc_issuers_path")
+                .long("oidc-issuers-path")
+                .help("The path to the JSON file of the OpenID Connect providers whose tokens clients may authenticate with, each with its `issuer`, `audiences`, `algorithm` and `public_key` PEM file (SPACETIMEDB_OIDC_ISSUERS)"),
+        )
+        .arg(
+            Arg::new("module_log_max_size")
+                .long("module-log-max-size")
+                .value_parser(clap::value_parser!(u64))
+                .help("The most bytes of module logs to keep per database, deleting the oldest first (SPACETIMEDB_MODULE_LOG_MAX_SIZE)"),
+        )
+        .arg(
+            Arg::new("module_log_max_age")
+                .long("module-log-max-age")
+                .value_parser(humantime::parse_duration)
+                .help("How long to keep the lines of module logs, e.g. `7days` (SPACETIMEDB_MODULE_LOG_MAX_AGE)"),
+        )
+        .arg(
+            Arg::new("guest_identities")
+                .long("guest-identities")
+                .action(SetTrue)
+                .help("Give clients that connect without a token guest identities, which expire unless promoted (SPACETIMEDB_GUEST_IDENTITIES)"),
+        )
+        .arg(
+            Arg::new("guest_token_ttl")
+                .long("guest-token-ttl")
+                .value_parser(humantime::parse_duration)
+                .help("How long the token of a guest identity is valid, e.g. `12h` (SPACETIMEDB_GUEST_TOKEN_TTL)"),
+        )
         .arg(in_memory_arg)
         .after_help(mode.after_help())
 }
@@ -160,12 +202,18 @@ fn read_argument<'a>(args: &'a ArgMatches, arg_name: &str, env_name: &str) -> Op
 
 pub async fn exec(args: &ArgMatches) -> anyhow::Result<()> {
     let listen_addr = args.get_one::<String>("listen_addr").unwrap();
+    let pg_listen_addr = args.get_one::<String>("pg_listen_addr");
     let log_conf_path = read_argument(args, "log_conf_path", "SPACETIMEDB_LOG_CONFIG");
     let log_dir_path = read_argument(args, "log_dir_path", "SPACETIMEDB_LOGS_PATH");
     let stdb_path = read_argument(args, "database_path", "STDB_PATH");
     let jwt_pub_key_path = read_argument(args, "jwt_pub_key_path", "SPACETIMEDB_JWT_PUB_KEY");
     let jwt_priv_key_path = read_argument(args, "jwt_priv_key_path", "SPACETIMEDB_JWT_PRIV_KEY");
+    let oidc_issuers_path = args.get_one::<String>("oidc_issuers_path");
     let enable_tracy = args.get_flag("enable_tracy");
+    let module_log_max_size = args.get_one::<u64>("module_log_max_size");
+    let module_log_max_age = args.get_one::<std::time::Duration>("module_log_max_age");
+    let guest_identities = args.get_flag("guest_identities");
+    let guest_token_ttl = args.get_one::<std::time::Duration>("guest_token_ttl");
     let storage = if args.get_flag("in_memory") {
         Storage::Memory
     } else {
@@ -199,10 +247,36 @@ pub async fn exec(args: &ArgMatches) -> anyhow::Result<()> {
         set_env_with_warning("SPACETIMEDB_JWT_PRIV_KEY", jwt_priv_key_path);
     }
 
+    if let Some(oidc_issuers_path) = oidc_issuers_path {
+        set_env_with_warning("SPACETIMEDB_OIDC_ISSUERS", oidc_issuers_path);
+    }
+
     if enable_tracy {
         set_env_with_warning("SPACETIMEDB_TRACY", "1");
     }
 
+    if let Some(max_size) = module_log_max_size {
+        set_env_with_warning("SPACETIMEDB_MODULE_LOG_MAX_SIZE", &max_size.to_string());
+    }
+
+    if let Some(max_age) = module_log_max_age {
+        set_env_with_warning(
+            "SPACETIMEDB_MODULE_LOG_MAX_AGE",
+            &humantime::format_duration(*max_age).to_string(),
+        );
+    }
+
+    if guest_identities {
+        set_env_with_warning("SPACETIMEDB_GUEST_IDENTITIES", "true");
+    }
+
+    if let Some(ttl) = guest_token_ttl {
+        set_env_with_warning(
+            "SPACETIMEDB_GUEST_TOKEN_TTL",
+            &humantime::format_duration(*ttl).to_string(),
+        );
+    }
+
     startup::configure_tracing();
 
     // Metrics for pieces under worker_node/ related to reducer hosting, etc.
@@ -213,7 +287,28 @@ pub async fn exec(args: &ArgMatches) -> anyhow::Result<()> {
 
     let ctx = spacetimedb_client_api::ArcEnv(StandaloneEnv::init(storage).await?);
 
-    let service = router().with_state(ctx).into_make_service();
+    if let Some(pg_listen_addr) = pg_listen_addr {
+        let listener = tokio::net::TcpListener::bind(pg_listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        log::debug!("Accepting Postgres connections on {local_addr}");
+        if !local_addr.ip().is_loopback() {
+            log::warn!(
+                "Postgres connections are accepted on {local_addr}, which isn't a loopback address. \
+                 They aren't encrypted, so tokens and query results can be read by anyone on the network."
+            );
+        }
+        let worker_ctx: Arc<dyn WorkerCtx> = ctx.0.clone();
+        tokio::spawn(async move {
+            if let Err(e) = pg_wire::serve(worker_ctx, listener).await {
+                log::error!("Stopped accepting Postgres connections: {e}");
+            }
+        });
+    }
+
+    // The address of the peer is what the guests minted for a client are counted against.
+    let service = router()
+        .with_state(ctx)
+        .into_make_service_with_connect_info::<SocketAddr>();
 
     let tcp = TcpListener::bind(listen_addr).unwrap();
     log::debug!("Starting SpacetimeDB listening on {}", tcp.local_addr().unwrap());
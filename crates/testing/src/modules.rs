@@ -1,17 +1,20 @@
 use std::future::Future;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
 
+use axum::headers::authorization::Credentials;
 use spacetimedb::address::Address;
+use spacetimedb::auth::identity::{encode_scoped_token, encode_token, TokenScope};
 use spacetimedb::client::{ClientActorId, ClientConnection, Protocol};
-use spacetimedb::database_logger::DatabaseLogger;
+use spacetimedb::database_logger::{DatabaseLogger, LogFilter};
 use spacetimedb::db::Storage;
 use spacetimedb::hash::hash_bytes;
+use spacetimedb::host::UpdateDatabaseResult;
 
 use spacetimedb::messages::control_db::HostType;
-use spacetimedb_client_api::{ControlCtx, ControlStateDelegate, WorkerCtx};
+use spacetimedb_client_api::{ArcEnv, ControlCtx, ControlNodeDelegate, ControlStateDelegate, WorkerCtx};
 use spacetimedb_standalone::StandaloneEnv;
 use tokio::runtime::{Builder, Runtime};
 
@@ -29,13 +32,22 @@ where
 }
 
 pub fn with_module_async<O, R, F>(name: &str, routine: R)
+where
+    R: FnOnce(ModuleHandle) -> F,
+    F: Future<Output = O>,
+{
+    with_module_async_on(name, HostType::Wasmer, routine)
+}
+
+/// Like [`with_module_async`], running the module on the host `host_type`.
+pub fn with_module_async_on<O, R, F>(name: &str, host_type: HostType, routine: R)
 where
     R: FnOnce(ModuleHandle) -> F,
     F: Future<Output = O>,
 {
     with_runtime(move |runtime| {
         runtime.block_on(async {
-            let module = load_module(name).await;
+            let module = load_module_on(name, host_type).await;
 
             routine(module).await;
         });
@@ -58,29 +70,81 @@ fn module_path(name: &str) -> PathBuf {
     root.join("../../modules").join(name)
 }
 
+/// Whether the module `name` is written in C#, rather than Rust.
+fn is_csharp(name: &str) -> bool {
+    module_path(name).join("StdbModule.csproj").exists()
+}
+
+/// Whether the module `name` is written in AssemblyScript, rather than Rust.
+fn is_assemblyscript(name: &str) -> bool {
+    module_path(name).join("asconfig.json").exists()
+}
+
 fn wasm_path(name: &str) -> PathBuf {
+    if is_csharp(name) {
+        return module_path(name).join("bin/Release/net7.0/StdbModule.wasm");
+    }
+    if is_assemblyscript(name) {
+        return module_path(name).join("build/release.wasm");
+    }
     module_path(name).join(format!(
         "target/wasm32-unknown-unknown/release/{}_module.wasm",
         name.replace('-', "_")
     ))
 }
 
-fn read_module(path: &str) -> Vec<u8> {
+/// Reads the compiled module `path`, e.g., to publish it over HTTP.
+pub fn read_module(path: &str) -> Vec<u8> {
     println!("{}", wasm_path(path).to_str().unwrap());
     std::fs::read(wasm_path(path)).unwrap()
 }
 
-pub fn compile(path: &str) {
-    let path = module_path(path);
-    let output = Command::new("cargo")
-        .current_dir(&path)
-        .args([
+/// Whether the .NET SDK, needed to compile the C# modules, is installed.
+pub fn dotnet_available() -> bool {
+    Command::new("dotnet")
+        .arg("--version")
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+/// Whether npm, needed to compile the AssemblyScript modules, is installed.
+pub fn npm_available() -> bool {
+    Command::new("npm")
+        .arg("--version")
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+pub fn compile(name: &str) {
+    let path = module_path(name);
+    let mut command = if is_csharp(name) {
+        let mut command = Command::new("dotnet");
+        command.args(["publish", "-c", "Release"]);
+        command
+    } else if is_assemblyscript(name) {
+        if !path.join("node_modules").exists() {
+            run_compile(&path, Command::new("npm").arg("install"));
+        }
+        let mut command = Command::new("npx");
+        command.args(["asc", "assembly/index.ts", "--target", "release"]);
+        command
+    } else {
+        let mut command = Command::new("cargo");
+        command.args([
             "build",
             "--target=wasm32-unknown-unknown",
             "--release",
             "--target-dir",
             path.join("target").to_str().unwrap(),
-        ])
+        ]);
+        command
+    };
+    run_compile(&path, &mut command);
+}
+
+fn run_compile(path: &Path, command: &mut Command) {
+    let output = command
+        .current_dir(path)
         .output()
         .expect("Failed to execute process to compile a test depdendency");
 
@@ -99,7 +163,7 @@ pub fn compile(path: &str) {
 #[derive(Clone)]
 pub struct ModuleHandle {
     // Needs to hold a reference to the standalone env.
-    _env: Arc<StandaloneEnv>,
+    env: Arc<StandaloneEnv>,
     pub client: ClientConnection,
     pub db_address: Address,
 }
@@ -120,11 +184,84 @@ impl ModuleHandle {
 
     pub async fn read_log(&self, size: Option<u32>) -> String {
         let filepath = DatabaseLogger::filepath(&self.db_address, self.client.database_instance_id);
-        DatabaseLogger::read_latest(&filepath, size).await
+        DatabaseLogger::read_latest(&filepath, size, &LogFilter::default()).await
+    }
+
+    /// A token of the identity owning the database, restricted to `scope` if any.
+    pub async fn token(&self, scope: Option<TokenScope>) -> String {
+        let identity = self.client.id.identity;
+        match scope {
+            Some(scope) => {
+                let api_token = self.env.control_db().insert_api_token(identity, scope).await.unwrap();
+                encode_scoped_token(self.env.private_key(), identity, &api_token).unwrap()
+            }
+            None => encode_token(self.env.private_key(), identity).unwrap(),
+        }
+    }
+
+    /// Sends a request to the HTTP API of the node, authorized by `token` if any,
+    /// responding with its status and body.
+    pub async fn http(
+        &self,
+        method: http::Method,
+        path: &str,
+        token: Option<&str>,
+        body: hyper::Body,
+    ) -> (http::StatusCode, bytes::Bytes) {
+        let response = self.http_response(method, path, token, body).await;
+        let status = response.status();
+        (status, hyper::body::to_bytes(response.into_body()).await.unwrap())
+    }
+
+    /// Like [`ModuleHandle::http`], responding with the whole response, headers included.
+    pub async fn http_response(
+        &self,
+        method: http::Method,
+        path: &str,
+        token: Option<&str>,
+        body: hyper::Body,
+    ) -> axum::response::Response {
+        use hyper::service::Service;
+
+        let mut request = http::Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            let axum::headers::Authorization(creds) = axum::headers::Authorization::basic("token", token);
+            request = request.header(http::header::AUTHORIZATION, creds.encode());
+        }
+        let request = request
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .unwrap();
+
+        let mut router = spacetimedb_standalone::routes::router().with_state(ArcEnv(self.env.clone()));
+        std::future::poll_fn(|cx| Service::<http::Request<hyper::Body>>::poll_ready(&mut router, cx))
+            .await
+            .unwrap();
+        router.call(request).await.unwrap()
+    }
+
+    /// Updates the database to the module `name`, returning the result of the update.
+    ///
+    /// The calls of the client then go to the new module.
+    pub async fn update(&self, name: &str) -> UpdateDatabaseResult {
+        let program_bytes = read_module(name);
+        let program_bytes_addr = hash_bytes(&program_bytes);
+        self.env.object_db().insert_object(program_bytes).unwrap();
+
+        self.env
+            .update_database(&self.db_address, &program_bytes_addr, 1)
+            .await
+            .unwrap()
+            .expect("the database of the module exists")
     }
 }
 
 pub async fn load_module(name: &str) -> ModuleHandle {
+    load_module_on(name, HostType::Wasmer).await
+}
+
+/// Like [`load_module`], running the module on the host `host_type`.
+pub async fn load_module_on(name: &str, host_type: HostType) -> ModuleHandle {
     // For testing, persist to disk by default, as many tests
     // exercise functionality like restarting the database.
     let storage = Storage::Disk;
@@ -138,8 +275,6 @@ pub async fn load_module(name: &str) -> ModuleHandle {
     let program_bytes_addr = hash_bytes(&program_bytes);
     env.object_db().insert_object(program_bytes).unwrap();
 
-    let host_type = HostType::Wasmer;
-
     env.insert_database(&address, &identity, &program_bytes_addr, host_type, 1, true, false)
         .await
         .unwrap();
@@ -159,7 +294,7 @@ pub async fn load_module(name: &str) -> ModuleHandle {
     // the runtime on which a module was created and then we could add impl
     // for stuff like "get logs" or "get message log"
     ModuleHandle {
-        _env: env,
+        env,
         client: ClientConnection::dummy(client_id, Protocol::Text, instance.id, module),
         db_address: address,
     }
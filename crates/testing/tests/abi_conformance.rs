@@ -0,0 +1,149 @@
+//! Runs the same cases against every guest language, on every host type,
+//! so that the bindings of the guests can't drift apart on the host ABI.
+//!
+//! A case is a reducer that each guest module implements identically,
+//! and which must log the same lines whatever the guest and host.
+
+use serde_json::Value;
+use spacetimedb::messages::control_db::HostType;
+use spacetimedb_testing::modules::{compile, dotnet_available, with_module_async_on};
+
+struct Case {
+    reducer: &'static str,
+    log: &'static [&'static str],
+}
+
+const CASES: &[Case] = &[
+    Case {
+        reducer: "case_insert_and_iter",
+        log: &["rows: 3", "names: a,b,c"],
+    },
+    Case {
+        reducer: "case_iter_cols",
+        log: &["names: a,b,c"],
+    },
+    Case {
+        reducer: "case_filter_by_col",
+        log: &["found: b", "found: none"],
+    },
+    Case {
+        reducer: "case_delete_by_col",
+        log: &["deleted: true", "deleted: false", "rows: 2"],
+    },
+    Case {
+        reducer: "case_unique_violation",
+        log: &["violation: id", "rows: 3"],
+    },
+    Case {
+        reducer: "case_savepoint",
+        log: &["rows: 2", "rows: 3"],
+    },
+    Case {
+        reducer: "case_move_rows",
+        log: &["failed: true", "rows: 3", "moved: 2", "rows: 1"],
+    },
+    Case {
+        reducer: "case_emit_event",
+        log: &["emitted"],
+    },
+    Case {
+        reducer: "case_remaining_energy",
+        log: &["energy: true"],
+    },
+    Case {
+        reducer: "case_tx_offset",
+        log: &["offset: true"],
+    },
+    Case {
+        reducer: "case_table_version",
+        log: &["changed: true", "unchanged: true"],
+    },
+    Case {
+        reducer: "case_table_row_type",
+        log: &["columns: 2", "missing: true"],
+    },
+    Case {
+        reducer: "case_blobs",
+        log: &[
+            "hash: true",
+            "blob: true",
+            "too large: true",
+            "deleted: true",
+            "missing: true",
+        ],
+    },
+    Case {
+        reducer: "case_large_bytes",
+        log: &["inline: true", "stored: true", "missing: true"],
+    },
+    Case {
+        reducer: "case_schedule_and_cancel",
+        log: &["cancelled"],
+    },
+    Case {
+        reducer: "case_describe_reducer",
+        log: &["args: _id,_name", "missing: true", "listed: true", "resolved: true"],
+    },
+];
+
+/// The cases the C# guest doesn't implement,
+/// as the C# runtime, which lives outside this repository, doesn't expose the host calls they need yet:
+/// inserting reports no unique violation to the module, and projected iteration, moving rows, savepoints, events,
+/// the remaining energy, the transaction offset, table versions, table row types, blobs, large bytes,
+/// cancelling a scheduled reducer, and describing reducers have no C# API.
+///
+/// Once the runtime catches up, implement the case in `modules/abi-conformance-cs` and remove it from here.
+const CSHARP_GAPS: &[&str] = &[
+    "case_iter_cols",
+    "case_unique_violation",
+    "case_savepoint",
+    "case_move_rows",
+    "case_emit_event",
+    "case_remaining_energy",
+    "case_tx_offset",
+    "case_table_version",
+    "case_table_row_type",
+    "case_blobs",
+    "case_large_bytes",
+    "case_schedule_and_cancel",
+    "case_describe_reducer",
+];
+
+const HOST_TYPES: [HostType; 2] = [HostType::Wasmer, HostType::Wasmtime];
+
+fn check_conformance(module: &str, skip: &[&str]) {
+    compile(module);
+    for host_type in HOST_TYPES {
+        with_module_async_on(module, host_type, |module| async move {
+            for case in CASES.iter().filter(|case| !skip.contains(&case.reducer)) {
+                module.call_reducer(case.reducer, "[]".into()).await.unwrap();
+
+                let lines = module.read_log(Some(case.log.len() as u32)).await;
+                let messages: Vec<String> = lines
+                    .trim()
+                    .split('\n')
+                    .map(|line| {
+                        let json: Value = serde_json::from_str(line).unwrap();
+                        json["message"].as_str().unwrap().to_owned()
+                    })
+                    .collect();
+
+                assert_eq!(messages, case.log, "{} on {host_type:?}", case.reducer);
+            }
+        });
+    }
+}
+
+#[test]
+fn test_rust_guest_conformance() {
+    check_conformance("abi-conformance", &[]);
+}
+
+#[test]
+fn test_csharp_guest_conformance() {
+    if !dotnet_available() {
+        eprintln!("Skipping the C# guest, as dotnet isn't installed");
+        return;
+    }
+    check_conformance("abi-conformance-cs", CSHARP_GAPS);
+}
@@ -0,0 +1,40 @@
+//! Runs the reducers of a module concurrently, which is set for the whole process,
+//! so these tests are kept apart from the others.
+
+use serde_json::Value;
+use spacetimedb_testing::modules::{compile, with_module_async};
+
+const CALLS: u64 = 20;
+
+#[test]
+fn test_conflicting_reducers_are_serialized() {
+    std::env::set_var("SPACETIMEDB_CONCURRENT_REDUCERS", "1");
+    compile("concurrent-counter");
+    with_module_async("concurrent-counter", |module| async move {
+        let calls: Vec<_> = (0..CALLS)
+            .map(|_| {
+                let module = module.clone();
+                tokio::spawn(async move { module.call_reducer("increment", "[]".into()).await })
+            })
+            .collect();
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+
+        // A call that conflicted with one committed while it ran is run again, and only the run that
+        // commits is logged, so no update is lost nor logged twice, and they're logged in commit order.
+        let lines = module.read_log(Some(CALLS as u32)).await;
+        let messages: Vec<String> = lines
+            .trim()
+            .split('\n')
+            .map(|line| {
+                serde_json::from_str::<Value>(line).unwrap()["message"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        let expected: Vec<String> = (1..=CALLS).map(|count| format!("Count is {count}")).collect();
+        assert_eq!(messages, expected);
+    });
+}
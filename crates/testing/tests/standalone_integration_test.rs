@@ -1,5 +1,9 @@
+use http::{Method, StatusCode};
+use hyper::Body;
 use serde_json::Value;
-use spacetimedb_testing::modules::{compile, with_module_async};
+use spacetimedb::auth::identity::{SqlAccess, TokenScope};
+use spacetimedb::messages::control_db::HostType;
+use spacetimedb_testing::modules::{compile, npm_available, read_module, with_module_async, with_module_async_on};
 
 #[test]
 fn test_calling_a_reducer() {
@@ -42,3 +46,468 @@ fn test_calling_a_reducer_with_private_table() {
         assert_eq!(json["message"], Value::String("Private, World!".to_string()));
     });
 }
+
+#[test]
+fn test_scoped_token_cant_pause_or_resume() {
+    compile("spacetimedb-quickstart");
+    with_module_async("spacetimedb-quickstart", |module| async move {
+        let scope = TokenScope {
+            databases: Some(vec![module.db_address]),
+            reducers: None,
+            sql: SqlAccess::ReadWrite,
+        };
+        let token = module.token(Some(scope)).await;
+
+        for route in ["pause", "resume"] {
+            let path = format!("/database/{route}/{}", module.db_address.to_hex());
+            let (status, _) = module.http(Method::POST, &path, Some(&token), Body::empty()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{route} with a scoped token");
+        }
+
+        // The database wasn't paused.
+        let json = r#"{"call": {"fn": "add", "args": ["Tyrion"]}}"#.to_string();
+        module.send(json).await.unwrap();
+    });
+}
+
+#[test]
+fn test_scoped_token_cant_vacuum() {
+    compile("spacetimedb-quickstart");
+    with_module_async("spacetimedb-quickstart", |module| async move {
+        let scope = TokenScope {
+            databases: Some(vec![module.db_address]),
+            reducers: None,
+            sql: SqlAccess::ReadWrite,
+        };
+        let path = format!("/database/vacuum/{}", module.db_address.to_hex());
+
+        let token = module.token(Some(scope)).await;
+        let (status, _) = module.http(Method::POST, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let token = module.token(None).await;
+        let (status, _) = module.http(Method::POST, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
+
+#[test]
+fn test_scoped_token_cant_use_call_log() {
+    compile("spacetimedb-quickstart");
+    with_module_async("spacetimedb-quickstart", |module| async move {
+        let scope = TokenScope {
+            databases: Some(vec![module.db_address]),
+            reducers: None,
+            sql: SqlAccess::ReadWrite,
+        };
+        let token = module.token(Some(scope)).await;
+        let path = format!("/database/call_log/{}", module.db_address.to_hex());
+
+        let (status, _) = module.http(Method::GET, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let set_path = format!("{path}?enabled=true");
+        let (status, _) = module.http(Method::POST, &set_path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let token = module.token(None).await;
+        let (status, body) = module.http(Method::GET, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["enabled"], Value::Bool(false));
+    });
+}
+
+#[test]
+fn test_scoped_token_cant_stream_changes() {
+    compile("spacetimedb-quickstart");
+    with_module_async("spacetimedb-quickstart", |module| async move {
+        let path = format!("/database/changes/{}", module.db_address.to_hex());
+        for sql in [SqlAccess::None, SqlAccess::ReadOnly, SqlAccess::ReadWrite] {
+            let scope = TokenScope {
+                databases: Some(vec![module.db_address]),
+                reducers: None,
+                sql,
+            };
+            let token = module.token(Some(scope)).await;
+            let (status, _) = module.http(Method::GET, &path, Some(&token), Body::empty()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{sql:?}");
+        }
+    });
+}
+
+#[test]
+fn test_quota_is_for_the_owner_to_read_only() {
+    compile("spacetimedb-quickstart");
+    with_module_async("spacetimedb-quickstart", |module| async move {
+        let path = format!("/database/quota/{}", module.db_address.to_hex());
+
+        let (status, _) = module.http(Method::GET, &path, None, Body::empty()).await;
+        assert!(!status.is_success(), "{status}");
+
+        let token = module.token(None).await;
+        let (status, body) = module.http(Method::GET, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["max_rows"], Value::Null);
+
+        // The node has no operator set in `SPACETIMEDB_QUOTA_ADMIN`, so not even the owner may set it.
+        let quota = Body::from(r#"{"max_rows": 1}"#);
+        let (status, _) = module.http(Method::POST, &path, Some(&token), quota).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    });
+}
+
+#[test]
+fn test_update_adds_option_columns() {
+    compile("schema-upgrade-v1");
+    compile("schema-upgrade-v2");
+    with_module_async("schema-upgrade-v1", |module| async move {
+        module.call_reducer("add", r#"["Tyrion"]"#.into()).await.unwrap();
+
+        let success = module.update("schema-upgrade-v2").await.unwrap();
+        assert!(success.committed());
+
+        module
+            .call_reducer("add", r#"["Sansa", {"some": 14}]"#.into())
+            .await
+            .unwrap();
+        module.call_reducer("say_hello", "[]".into()).await.unwrap();
+        module.call_reducer("find", r#"["Tyrion"]"#.into()).await.unwrap();
+        module.call_reducer("find", r#"["Sansa"]"#.into()).await.unwrap();
+
+        let lines = module.read_log(Some(4)).await;
+        let mut messages: Vec<String> = lines
+            .trim()
+            .split('\n')
+            .map(|line| {
+                serde_json::from_str::<Value>(line).unwrap()["message"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        // The rows are iterated in no particular order.
+        messages[..2].sort();
+        assert_eq!(
+            messages,
+            [
+                "Hello, Sansa (Some(14))!",
+                "Hello, Tyrion (None)!",
+                "Found Tyrion (None)",
+                "Found Sansa (Some(14))",
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_failed_update_is_rolled_back() {
+    compile("schema-upgrade-v1");
+    compile("schema-upgrade-failing");
+    with_module_async("schema-upgrade-v1", |module| async move {
+        module.call_reducer("add", r#"["Tyrion"]"#.into()).await.unwrap();
+
+        let success = module.update("schema-upgrade-failing").await.unwrap();
+        assert!(!success.committed());
+
+        // The old module carries on, with the clients it had, and the table of the new one is gone.
+        module.call_reducer("add", r#"["Sansa"]"#.into()).await.unwrap();
+        let token = module.token(None).await;
+        let path = format!("/database/sql/{}", module.db_address.to_hex());
+        let sql = |query: &'static str| module.http(Method::POST, &path, Some(&token), Body::from(query));
+
+        let (status, body) = sql("SELECT * FROM Person").await;
+        assert_eq!(status, StatusCode::OK);
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["rows"].as_array().unwrap().len(), 2);
+
+        let (status, _) = sql("SELECT * FROM Pet").await;
+        assert!(!status.is_success(), "{status}");
+    });
+}
+
+#[test]
+fn test_dry_run_publish() {
+    compile("schema-upgrade-v1");
+    compile("schema-upgrade-failing");
+    compile("reducer-return");
+    with_module_async("schema-upgrade-v1", |module| async move {
+        let token = module.token(None).await;
+        let path = format!(
+            "/database/publish?name_or_address={}&dry_run=true",
+            module.db_address.to_hex()
+        );
+        let dry_run = |name: &'static str| {
+            let (module, token, path) = (&module, &token, &path);
+            async move {
+                let (status, body) = module
+                    .http(Method::POST, path, Some(token), Body::from(read_module(name)))
+                    .await;
+                assert_eq!(status, StatusCode::OK);
+                serde_json::from_slice::<Value>(&body).unwrap()["DryRun"]["plan"].clone()
+            }
+        };
+
+        // A new table is reported, but neither created nor filled by the update reducer.
+        let plan = dry_run("schema-upgrade-failing").await;
+        assert_eq!(plan["created_tables"], serde_json::json!(["Pet"]));
+        assert_eq!(plan["changed_tables"], serde_json::json!([]));
+        assert_eq!(plan["orphaned_tables"], serde_json::json!([]));
+        assert_eq!(plan["calls_update_reducer"], true);
+
+        // Dropping the unique constraint of `name` changes the table.
+        let plan = dry_run("reducer-return").await;
+        assert_eq!(plan["created_tables"], serde_json::json!([]));
+        let changed = plan["changed_tables"].as_array().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0]["table_name"], "Person");
+        assert!(!changed[0]["differences"].as_array().unwrap().is_empty());
+        assert_eq!(plan["calls_update_reducer"], false);
+
+        // The database still runs the old module, without the new table.
+        module.call_reducer("add", r#"["Tyrion"]"#.into()).await.unwrap();
+        let sql = format!("/database/sql/{}", module.db_address.to_hex());
+        let (status, _) = module
+            .http(Method::POST, &sql, Some(&token), Body::from("SELECT * FROM Pet"))
+            .await;
+        assert!(!status.is_success(), "{status}");
+    });
+}
+
+#[test]
+fn test_row_version() {
+    compile("row-version");
+    with_module_async("row-version", |module| async move {
+        module.call_reducer("open", "[1]".into()).await.unwrap();
+        module.call_reducer("set_balance", "[1, 10, 0]".into()).await.unwrap();
+        module.call_reducer("show", "[1]".into()).await.unwrap();
+        // The version has moved past 0, and there's no account 2.
+        module.call_reducer("set_balance", "[1, 20, 0]".into()).await.unwrap();
+        module.call_reducer("set_balance", "[2, 20, 0]".into()).await.unwrap();
+        // A plain update bumps the version too.
+        module.call_reducer("reset", "[1, 30]".into()).await.unwrap();
+        module.call_reducer("show", "[1]".into()).await.unwrap();
+
+        let lines = module.read_log(Some(6)).await;
+        let messages: Vec<String> = lines
+            .trim()
+            .split('\n')
+            .map(|line| {
+                serde_json::from_str::<Value>(line).unwrap()["message"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Opened 1 at version 0",
+                "Set 1 to 10: true",
+                "1 has 10 at version 1",
+                "Set 1 to 20: false",
+                "Set 2 to 20: false",
+                "1 has 30 at version 2",
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_reducer_return_value() {
+    compile("reducer-return");
+    with_module_async("reducer-return", |module| async move {
+        let token = module.token(None).await;
+        let path = format!("/database/call/{}/add", module.db_address.to_hex());
+        let add = |args: &'static str| module.http(Method::POST, &path, Some(&token), Body::from(args));
+
+        // The value a committed call returns is the body of the response.
+        let (status, body) = add(r#"["Tyrion"]"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), 1);
+        let (status, body) = add(r#"["Sansa"]"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), 2);
+
+        // A failed call returns nothing, but its error.
+        let (status, body) = add(r#"[""]"#).await;
+        assert!(!status.is_success(), "{status}");
+        assert!(String::from_utf8_lossy(&body).contains("a person needs a name"));
+    });
+}
+
+#[test]
+fn test_reducer_hooks() {
+    compile("reducer-hooks");
+    with_module_async("reducer-hooks", |module| async move {
+        let token = module.token(None).await;
+        let (module, token) = (&module, &token);
+        let call = |reducer: &'static str, args: &'static str| async move {
+            let path = format!("/database/call/{}/{reducer}", module.db_address.to_hex());
+            module.http(Method::POST, &path, Some(token), Body::from(args)).await
+        };
+
+        let (status, _) = call("add", r#"["Tyrion"]"#).await;
+        assert_eq!(status, StatusCode::OK);
+        // A call rejected by a hook before the reducer never runs it, but is still reported after.
+        let (status, body) = call("forbidden", "[]").await;
+        assert!(!status.is_success(), "{status}");
+        assert!(String::from_utf8_lossy(&body).contains("forbidden is off limits"));
+
+        let lines = module.read_log(Some(5)).await;
+        let messages: Vec<String> = lines
+            .trim()
+            .split('\n')
+            .map(|line| {
+                serde_json::from_str::<Value>(line).unwrap()["message"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "before add",
+                "adding Tyrion",
+                "after add: ok",
+                "before forbidden",
+                "after forbidden: forbidden is off limits",
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_reducer_panic_is_logged_with_its_call() {
+    compile("reducer-panic");
+    with_module_async("reducer-panic", |module| async move {
+        let token = module.token(None).await;
+        let path = format!("/database/call/{}/add_twice_and_panic", module.db_address.to_hex());
+        let (status, _) = module
+            .http(Method::POST, &path, Some(&token), Body::from(r#"["Tyrion"]"#))
+            .await;
+        assert!(!status.is_success(), "{status}");
+
+        let lines = module.read_log(Some(10)).await;
+        let panic = lines
+            .trim()
+            .split('\n')
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .find(|line| line["message"].as_str().unwrap().contains("one too many"))
+            .expect("the panic wasn't logged");
+        let fields = &panic["fields"];
+        assert_eq!(fields["reducer"], "add_twice_and_panic");
+        // The arguments are identified by the hash of their bsatn encoding, rather than logged.
+        let args = [&6u32.to_le_bytes()[..], b"Tyrion"].concat();
+        assert_eq!(fields["args_hash"], spacetimedb_lib::hash::hash_bytes(args).to_hex());
+        // The two inserts into `Person` are logged as one run.
+        let table_ops = fields["table_ops"].as_str().unwrap();
+        assert!(
+            table_ops.starts_with("insert ") && table_ops.ends_with(" x2") && !table_ops.contains(','),
+            "{table_ops}"
+        );
+    });
+}
+
+#[test]
+fn test_reducer_call_trace_id() {
+    compile("reducer-return");
+    with_module_async("reducer-return", |module| async move {
+        let token = module.token(None).await;
+        let path = &format!("/database/call/{}/add", module.db_address.to_hex());
+        let (module, token) = (&module, &token);
+        let trace_id = |args: &'static str| async move {
+            let response = module
+                .http_response(Method::POST, path, Some(token), Body::from(args))
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            response.headers()["spacetime-trace-id"].to_str().unwrap().to_owned()
+        };
+
+        // Each call is traced on its own, by an id of 32 hex digits.
+        let first = trace_id(r#"["Tyrion"]"#).await;
+        let second = trace_id(r#"["Sansa"]"#).await;
+        for id in [&first, &second] {
+            assert_eq!(id.len(), 32, "{id}");
+            assert!(id.chars().all(|c| c.is_ascii_hexdigit()), "{id}");
+        }
+        assert_ne!(first, second);
+    });
+}
+
+#[test]
+fn test_blobs_over_http() {
+    compile("reducer-return");
+    with_module_async("reducer-return", |module| async move {
+        let token = module.token(None).await;
+        let path = format!("/database/blob/{}/avatars/tyrion.png", module.db_address.to_hex());
+
+        // Only the owner stores blobs, which anyone may fetch.
+        let (status, _) = module.http(Method::PUT, &path, None, Body::from("lion")).await;
+        assert!(!status.is_success(), "{status}");
+        let (status, body) = module.http(Method::PUT, &path, Some(&token), Body::from("lion")).await;
+        assert_eq!(status, StatusCode::OK);
+        let hash = serde_json::from_slice::<Value>(&body).unwrap()["hash"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        let response = module.http_response(Method::GET, &path, None, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[http::header::ETAG], format!("\"{hash}\""));
+        assert_eq!(&hyper::body::to_bytes(response.into_body()).await.unwrap()[..], b"lion");
+
+        let too_large = vec![0; spacetimedb_lib::MAX_BLOB_SIZE + 1];
+        let (status, _) = module
+            .http(Method::PUT, &path, Some(&token), Body::from(too_large))
+            .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let (_, body) = module.http(Method::GET, &path, None, Body::empty()).await;
+        assert_eq!(&body[..], b"lion");
+
+        let (status, _) = module.http(Method::DELETE, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = module.http(Method::DELETE, &path, Some(&token), Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = module.http(Method::GET, &path, None, Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn test_calling_an_assemblyscript_reducer() {
+    if !npm_available() {
+        eprintln!("Skipping the AssemblyScript module, as npm isn't installed");
+        return;
+    }
+    compile("quickstart-assemblyscript");
+    with_module_async_on(
+        "quickstart-assemblyscript",
+        HostType::AssemblyScript,
+        |module| async move {
+            let messages = |lines: String| -> Vec<String> {
+                lines
+                    .trim()
+                    .split('\n')
+                    .map(|line| {
+                        serde_json::from_str::<Value>(line).unwrap()["message"]
+                            .as_str()
+                            .unwrap()
+                            .to_owned()
+                    })
+                    .collect()
+            };
+
+            module.call_reducer("add", r#"["Tyrion"]"#.into()).await.unwrap();
+            assert_eq!(messages(module.read_log(Some(1)).await), ["Inserted Tyrion: true"]);
+
+            // The failed `assert` aborts the reducer, which rolls back its insert.
+            module.call_reducer("fail", "[]".into()).await.unwrap();
+            module.call_reducer("say_hello", "[]".into()).await.unwrap();
+            assert_eq!(
+                messages(module.read_log(Some(2)).await),
+                ["Hello, Tyrion!", "Hello, World!"]
+            );
+        },
+    );
+}
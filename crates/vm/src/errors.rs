@@ -1,6 +1,7 @@
-use crate::operator::{Op, OpLogic};
+use crate::operator::{Op, OpLogic, OpMath};
 use crate::types::Ty;
 use spacetimedb_lib::error::{AuthError, RelationError};
+use spacetimedb_lib::relation::FieldName;
 use spacetimedb_sats::AlgebraicValue;
 use std::fmt;
 use thiserror::Error;
@@ -18,6 +19,14 @@ pub enum ErrorType {
     OpLogic(OpLogic, AlgebraicValue),
     #[error("Field should resolve to `bool`, but it got the value `{{0.to_satn()}}`")]
     FieldBool(AlgebraicValue),
+    #[error("Field `{0}` should resolve to a sum type")]
+    FieldSum(FieldName),
+    #[error("Field `{0}` should resolve to an array")]
+    FieldArray(FieldName),
+    #[error("Field `{0}` should resolve to a string")]
+    FieldString(FieldName),
+    #[error("Math op {0} is not defined for `{1:?}` and `{2:?}`, or overflows")]
+    OpMath(OpMath, AlgebraicValue, AlgebraicValue),
     #[error("Error Parsing `{value}` into type [{ty}]: {err}")]
     Parse { value: String, ty: String, err: String },
 }
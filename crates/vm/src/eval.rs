@@ -133,6 +133,43 @@ fn build_typed<P: ProgramVm>(p: &mut P, node: Expr) -> ExprOpt {
                 kind,
                 table_access,
             })),
+            CrudExpr::CreateIndex {
+                table_id,
+                name,
+                col_id,
+                is_unique,
+                index_type,
+                if_not_exists,
+            } => ExprOpt::Crud(Box::new(CrudExprOpt::CreateIndex {
+                table_id,
+                name,
+                col_id,
+                is_unique,
+                index_type,
+                if_not_exists,
+            })),
+            CrudExpr::Grant { role, identity } => ExprOpt::Crud(Box::new(CrudExprOpt::Grant { role, identity })),
+            CrudExpr::Revoke { role, identity } => ExprOpt::Crud(Box::new(CrudExprOpt::Revoke { role, identity })),
+            CrudExpr::GrantAccess {
+                table_ids,
+                roles,
+                access,
+            } => ExprOpt::Crud(Box::new(CrudExprOpt::GrantAccess {
+                table_ids,
+                roles,
+                access,
+            })),
+            CrudExpr::RevokeAccess {
+                table_ids,
+                roles,
+                access,
+            } => ExprOpt::Crud(Box::new(CrudExprOpt::RevokeAccess {
+                table_ids,
+                roles,
+                access,
+            })),
+            CrudExpr::Analyze { table_ids } => ExprOpt::Crud(Box::new(CrudExprOpt::Analyze { table_ids })),
+            CrudExpr::Vacuum => ExprOpt::Crud(Box::new(CrudExprOpt::Vacuum)),
         },
         x => {
             todo!("{:?}", x)
@@ -298,6 +335,43 @@ fn compile<P: ProgramVm>(p: &mut P, node: ExprOpt) -> Result<Code, ErrorVm> {
                     kind,
                     table_access,
                 }),
+                CrudExprOpt::CreateIndex {
+                    table_id,
+                    name,
+                    col_id,
+                    is_unique,
+                    index_type,
+                    if_not_exists,
+                } => Code::Crud(CrudCode::CreateIndex {
+                    table_id,
+                    name,
+                    col_id,
+                    is_unique,
+                    index_type,
+                    if_not_exists,
+                }),
+                CrudExprOpt::Grant { role, identity } => Code::Crud(CrudCode::Grant { role, identity }),
+                CrudExprOpt::Revoke { role, identity } => Code::Crud(CrudCode::Revoke { role, identity }),
+                CrudExprOpt::GrantAccess {
+                    table_ids,
+                    roles,
+                    access,
+                } => Code::Crud(CrudCode::GrantAccess {
+                    table_ids,
+                    roles,
+                    access,
+                }),
+                CrudExprOpt::RevokeAccess {
+                    table_ids,
+                    roles,
+                    access,
+                } => Code::Crud(CrudCode::RevokeAccess {
+                    table_ids,
+                    roles,
+                    access,
+                }),
+                CrudExprOpt::Analyze { table_ids } => Code::Crud(CrudCode::Analyze { table_ids }),
+                CrudExprOpt::Vacuum => Code::Crud(CrudCode::Vacuum),
             }
         }
         x => todo!("{}", x),
@@ -456,6 +530,9 @@ pub fn build_query(mut result: Box<IterRows>, query: Vec<Query>) -> Result<Box<I
                 )?;
                 Box::new(iter)
             }
+            Query::OrderBy(cols) => Box::new(result.order_by(&cols)?),
+            Query::Offset(offset) => Box::new(result.offset(offset as usize)),
+            Query::Limit(limit) => Box::new(result.limit(limit as usize)),
         };
     }
     Ok(result)
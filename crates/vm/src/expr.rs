@@ -1,7 +1,8 @@
-use spacetimedb_lib::auth::{StAccess, StTableType};
-use spacetimedb_lib::error::AuthError;
+use spacetimedb_lib::auth::{StAccess, StRoleAccess, StTableType};
+use spacetimedb_lib::error::{AuthError, RelationError};
 use spacetimedb_lib::table::ProductTypeMeta;
-use spacetimedb_lib::Identity;
+use spacetimedb_lib::{fulltext, spatial};
+use spacetimedb_lib::{Identity, IndexType};
 use std::collections::HashMap;
 use std::fmt;
 
@@ -10,12 +11,13 @@ use spacetimedb_lib::relation::{
 };
 use spacetimedb_sats::algebraic_type::AlgebraicType;
 use spacetimedb_sats::algebraic_value::AlgebraicValue;
+use spacetimedb_sats::builtin_value::BuiltinValue;
 use spacetimedb_sats::satn::Satn;
 use spacetimedb_sats::{ProductValue, Typespace, WithTypespace};
 
 use crate::errors::{ErrorKind, ErrorLang, ErrorType, ErrorVm};
 use crate::functions::{FunDef, Param};
-use crate::operator::{Op, OpCmp, OpLogic, OpQuery};
+use crate::operator::{Op, OpCmp, OpLogic, OpMath, OpQuery};
 use crate::types::Ty;
 
 /// A `index` into the list of [Fun]
@@ -71,6 +73,31 @@ impl FunctionOpt {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ColumnOp {
     Field(FieldExpr),
+    /// Resolves to the name of the variant stored in a sum-typed column, as in `variant_of(status)`.
+    VariantOf(FieldName),
+    /// Resolves to whether the array in `array` has `value` as an element, as in `array_contains(tags, 'pvp')`.
+    Contains {
+        array: FieldName,
+        value: FieldExpr,
+    },
+    /// Resolves to whether the string in `field` matches the full-text `query`, as in `MATCH(name, 'iron sword')`.
+    Match {
+        field: FieldName,
+        query: String,
+    },
+    /// Resolves to whether the point in `field` is within the box from `min` to `max`, as in `in_box(pos, 0, 0, 10, 10)`.
+    InBox {
+        field: FieldName,
+        min: AlgebraicValue,
+        max: AlgebraicValue,
+    },
+    /// Resolves to the sum or difference of the times `lhs` and `rhs`, as in `expires_at - created_at`,
+    /// where timestamps are `u64`s and durations `i64`s of micro seconds.
+    Math {
+        op: OpMath,
+        lhs: Box<ColumnOp>,
+        rhs: Box<ColumnOp>,
+    },
     Cmp {
         op: OpQuery,
         lhs: Box<ColumnOp>,
@@ -87,9 +114,110 @@ impl ColumnOp {
         }
     }
 
+    pub fn math(op: OpMath, lhs: ColumnOp, rhs: ColumnOp) -> Self {
+        Self::Math {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    /// Returns the position of the column `field` in `row`.
+    fn column_pos(row: RelValueRef, field: &FieldName) -> Result<usize, ErrorLang> {
+        row.head
+            .column_pos(field)
+            .ok_or_else(|| RelationError::FieldNotFound(row.head.clone(), field.clone()).into())
+    }
+
+    /// Returns the name of the variant of the sum value at `field`.
+    ///
+    /// If the variant is unnamed, its tag is returned as a string instead.
+    fn variant_of(row: RelValueRef, field: &FieldName) -> Result<AlgebraicValue, ErrorLang> {
+        let pos = Self::column_pos(row, field)?;
+        let value = row.data.elements[pos]
+            .as_sum()
+            .ok_or_else(|| ErrorType::FieldSum(field.clone()))?;
+
+        let name = row.head.fields[pos]
+            .algebraic_type
+            .as_sum()
+            .and_then(|ty| ty.variants.get(value.tag as usize))
+            .and_then(|variant| variant.name());
+
+        Ok(match name {
+            Some(name) => AlgebraicValue::String(name.into()),
+            None => AlgebraicValue::String(value.tag.to_string()),
+        })
+    }
+
+    /// Returns whether the array at `array` has `value` as an element.
+    fn array_contains(row: RelValueRef, array: &FieldName, value: &FieldExpr) -> Result<bool, ErrorLang> {
+        let pos = Self::column_pos(row, array)?;
+        let array = row.data.elements[pos]
+            .as_array()
+            .ok_or_else(|| ErrorType::FieldArray(array.clone()))?;
+
+        Ok(array.contains(row.get(value)))
+    }
+
+    /// Returns whether the string at `field` [matches](fulltext::matches) `query`.
+    fn text_matches(row: RelValueRef, field: &FieldName, query: &str) -> Result<bool, ErrorLang> {
+        let pos = Self::column_pos(row, field)?;
+        let text = row.data.elements[pos]
+            .as_string()
+            .ok_or_else(|| ErrorType::FieldString(field.clone()))?;
+
+        Ok(fulltext::matches(text, query))
+    }
+
+    /// Returns whether the point at `field` is [in the box](spatial::in_box) from `min` to `max`.
+    fn point_in_box(
+        row: RelValueRef,
+        field: &FieldName,
+        min: &AlgebraicValue,
+        max: &AlgebraicValue,
+    ) -> Result<bool, ErrorLang> {
+        let pos = Self::column_pos(row, field)?;
+        Ok(spatial::in_box(&row.data.elements[pos], min, max))
+    }
+
+    /// Applies `op` to the times `lhs` and `rhs`,
+    /// where a timestamp is a `u64` of micro seconds since the UNIX epoch and a duration an `i64` of micro seconds.
+    ///
+    /// A timestamp minus a timestamp is a duration,
+    /// a timestamp plus or minus a duration is a timestamp,
+    /// and durations add up to durations.
+    fn time_math(op: OpMath, lhs: AlgebraicValue, rhs: AlgebraicValue) -> Result<AlgebraicValue, ErrorLang> {
+        let value = match (op, lhs.as_builtin(), rhs.as_builtin()) {
+            (OpMath::Minus, Some(BuiltinValue::U64(a)), Some(BuiltinValue::U64(b))) => {
+                i64::try_from(*a as i128 - *b as i128).ok().map(AlgebraicValue::I64)
+            }
+            (OpMath::Add, Some(BuiltinValue::U64(a)), Some(BuiltinValue::I64(b))) => {
+                a.checked_add_signed(*b).map(AlgebraicValue::U64)
+            }
+            (OpMath::Minus, Some(BuiltinValue::U64(a)), Some(BuiltinValue::I64(b))) => b
+                .checked_neg()
+                .and_then(|b| a.checked_add_signed(b))
+                .map(AlgebraicValue::U64),
+            (OpMath::Add, Some(BuiltinValue::I64(a)), Some(BuiltinValue::I64(b))) => {
+                a.checked_add(*b).map(AlgebraicValue::I64)
+            }
+            (OpMath::Minus, Some(BuiltinValue::I64(a)), Some(BuiltinValue::I64(b))) => {
+                a.checked_sub(*b).map(AlgebraicValue::I64)
+            }
+            _ => None,
+        };
+        value.ok_or_else(|| ErrorType::OpMath(op, lhs, rhs).into())
+    }
+
     fn reduce(&self, row: RelValueRef, value: &ColumnOp) -> Result<AlgebraicValue, ErrorLang> {
         match value {
             ColumnOp::Field(field) => Ok(row.get(field).clone()),
+            ColumnOp::VariantOf(field) => Self::variant_of(row, field),
+            ColumnOp::Contains { array, value } => Ok(Self::array_contains(row, array, value)?.into()),
+            ColumnOp::Match { field, query } => Ok(Self::text_matches(row, field, query)?.into()),
+            ColumnOp::InBox { field, min, max } => Ok(Self::point_in_box(row, field, min, max)?.into()),
+            ColumnOp::Math { op, lhs, rhs } => Self::time_math(*op, self.reduce(row, lhs)?, self.reduce(row, rhs)?),
             ColumnOp::Cmp { op, lhs, rhs } => Ok(self.compare_bin_op(row, *op, lhs, rhs)?.into()),
         }
     }
@@ -104,6 +232,11 @@ impl ColumnOp {
                     None => Err(ErrorType::FieldBool(field.clone()).into()),
                 }
             }
+            ColumnOp::VariantOf(field) => Err(ErrorType::FieldBool(Self::variant_of(row, field)?).into()),
+            ColumnOp::Contains { array, value } => Self::array_contains(row, array, value),
+            ColumnOp::Match { field, query } => Self::text_matches(row, field, query),
+            ColumnOp::InBox { field, min, max } => Self::point_in_box(row, field, min, max),
+            ColumnOp::Math { .. } => Err(ErrorType::FieldBool(self.reduce(row, value)?).into()),
             ColumnOp::Cmp { op, lhs, rhs } => Ok(self.compare_bin_op(row, *op, lhs, rhs)?),
         }
     }
@@ -141,6 +274,11 @@ impl ColumnOp {
                 let lhs = row.get(field);
                 Ok(*lhs.as_bool().unwrap())
             }
+            ColumnOp::VariantOf(_)
+            | ColumnOp::Contains { .. }
+            | ColumnOp::Match { .. }
+            | ColumnOp::InBox { .. }
+            | ColumnOp::Math { .. } => Ok(self.reduce_bool(row, self)?),
             ColumnOp::Cmp { op, lhs, rhs } => self.compare_bin_op(row, *op, lhs, rhs),
         }
     }
@@ -152,6 +290,21 @@ impl fmt::Display for ColumnOp {
             ColumnOp::Field(x) => {
                 write!(f, "{}", x)
             }
+            ColumnOp::VariantOf(x) => {
+                write!(f, "variant_of({})", x)
+            }
+            ColumnOp::Contains { array, value } => {
+                write!(f, "array_contains({}, {})", array, value)
+            }
+            ColumnOp::Match { field, query } => {
+                write!(f, "match({}, {:?})", field, query)
+            }
+            ColumnOp::InBox { field, min, max } => {
+                write!(f, "in_box({}, {}, {})", field, min.to_satn(), max.to_satn())
+            }
+            ColumnOp::Math { op, lhs, rhs } => {
+                write!(f, "{} {} {}", lhs, op, rhs)
+            }
             ColumnOp::Cmp { op, lhs, rhs } => {
                 write!(f, "{} {} {}", lhs, op, rhs)
             }
@@ -236,6 +389,19 @@ impl JoinExpr {
     }
 }
 
+/// A column to order the rows by, as in `ORDER BY field [ASC|DESC]`.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct OrderByExpr {
+    pub field: FieldName,
+    pub desc: bool,
+}
+
+impl OrderByExpr {
+    pub fn new(field: FieldName, desc: bool) -> Self {
+        Self { field, desc }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 pub enum DbType {
     Table,
@@ -251,6 +417,10 @@ pub enum Crud {
     Delete,
     Create(DbType),
     Drop(DbType),
+    Grant,
+    Revoke,
+    Analyze,
+    Vacuum,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
@@ -278,6 +448,44 @@ pub enum CrudExpr {
         kind: DbType,
         table_access: StAccess,
     },
+    /// Creates the index `name` on the column `col_id` of `table_id`,
+    /// unless `if_not_exists` and there's already an index named `name`.
+    CreateIndex {
+        table_id: u32,
+        name: String,
+        col_id: u32,
+        is_unique: bool,
+        index_type: IndexType,
+        if_not_exists: bool,
+    },
+    /// Grants the role `role` to `identity`.
+    Grant {
+        role: String,
+        identity: Identity,
+    },
+    /// Revokes the role `role` from `identity`.
+    Revoke {
+        role: String,
+        identity: Identity,
+    },
+    /// Raises the access of each of `roles` to each of `table_ids` to at least `access`.
+    GrantAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Lowers the access of each of `roles` to each of `table_ids` below `access`.
+    RevokeAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Refreshes the statistics on the columns of each of `table_ids`.
+    Analyze {
+        table_ids: Vec<u32>,
+    },
+    /// Compacts the storage of the database, returning the space reclaimed.
+    Vacuum,
 }
 
 // impl AuthAccess for CrudExpr {
@@ -303,6 +511,12 @@ pub enum Query {
     Select(ColumnOp),
     Project(Vec<FieldExpr>),
     JoinInner(JoinExpr),
+    /// Orders the rows by each of the columns in turn, then by the whole row, so the order is total.
+    OrderBy(Vec<OrderByExpr>),
+    /// Skips the first rows.
+    Offset(u64),
+    /// Keeps no more than the first rows.
+    Limit(u64),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -354,6 +568,26 @@ impl QueryExpr {
         x.query.push(Query::JoinInner(JoinExpr::new(with.into(), lhs, rhs)));
         x
     }
+
+    pub fn with_order_by(self, cols: &[OrderByExpr]) -> Self {
+        let mut x = self;
+        if !cols.is_empty() {
+            x.query.push(Query::OrderBy(cols.into()));
+        }
+        x
+    }
+
+    pub fn with_offset(self, offset: u64) -> Self {
+        let mut x = self;
+        x.query.push(Query::Offset(offset));
+        x
+    }
+
+    pub fn with_limit(self, limit: u64) -> Self {
+        let mut x = self;
+        x.query.push(Query::Limit(limit));
+        x
+    }
 }
 
 impl AuthAccess for Query {
@@ -444,6 +678,44 @@ pub enum CrudExprOpt {
         kind: DbType,
         table_access: StAccess,
     },
+    /// Creates the index `name` on the column `col_id` of `table_id`,
+    /// unless `if_not_exists` and there's already an index named `name`.
+    CreateIndex {
+        table_id: u32,
+        name: String,
+        col_id: u32,
+        is_unique: bool,
+        index_type: IndexType,
+        if_not_exists: bool,
+    },
+    /// Grants the role `role` to `identity`.
+    Grant {
+        role: String,
+        identity: Identity,
+    },
+    /// Revokes the role `role` from `identity`.
+    Revoke {
+        role: String,
+        identity: Identity,
+    },
+    /// Raises the access of each of `roles` to each of `table_ids` to at least `access`.
+    GrantAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Lowers the access of each of `roles` to each of `table_ids` below `access`.
+    RevokeAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Refreshes the statistics on the columns of each of `table_ids`.
+    Analyze {
+        table_ids: Vec<u32>,
+    },
+    /// Compacts the storage of the database, returning the space reclaimed.
+    Vacuum,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -531,6 +803,22 @@ impl fmt::Display for Query {
             Query::JoinInner(q) => {
                 write!(f, "&inner {} ON {} = {}", q.rhs, q.col_lhs, q.col_rhs)
             }
+            Query::OrderBy(q) => {
+                write!(f, "order by ")?;
+                for (pos, x) in q.iter().enumerate() {
+                    write!(f, "{}{}", x.field, if x.desc { " desc" } else { "" })?;
+                    if pos + 1 < q.len() {
+                        write!(f, ", ")?;
+                    }
+                }
+                Ok(())
+            }
+            Query::Offset(q) => {
+                write!(f, "offset {q}")
+            }
+            Query::Limit(q) => {
+                write!(f, "limit {q}")
+            }
         }
     }
 }
@@ -611,6 +899,13 @@ impl fmt::Display for ExprOpt {
                     CrudExprOpt::Delete { .. } => {}
                     CrudExprOpt::CreateTable { .. } => {}
                     CrudExprOpt::Drop { .. } => {}
+                    CrudExprOpt::CreateIndex { .. } => {}
+                    CrudExprOpt::Grant { .. } => {}
+                    CrudExprOpt::Revoke { .. } => {}
+                    CrudExprOpt::GrantAccess { .. } => {}
+                    CrudExprOpt::RevokeAccess { .. } => {}
+                    CrudExprOpt::Analyze { .. } => {}
+                    CrudExprOpt::Vacuum => {}
                 };
                 Ok(())
             }
@@ -699,6 +994,44 @@ pub enum CrudCode {
         kind: DbType,
         table_access: StAccess,
     },
+    /// Creates the index `name` on the column `col_id` of `table_id`,
+    /// unless `if_not_exists` and there's already an index named `name`.
+    CreateIndex {
+        table_id: u32,
+        name: String,
+        col_id: u32,
+        is_unique: bool,
+        index_type: IndexType,
+        if_not_exists: bool,
+    },
+    /// Grants the role `role` to `identity`.
+    Grant {
+        role: String,
+        identity: Identity,
+    },
+    /// Revokes the role `role` from `identity`.
+    Revoke {
+        role: String,
+        identity: Identity,
+    },
+    /// Raises the access of each of `roles` to each of `table_ids` to at least `access`.
+    GrantAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Lowers the access of each of `roles` to each of `table_ids` below `access`.
+    RevokeAccess {
+        table_ids: Vec<u32>,
+        roles: Vec<String>,
+        access: StRoleAccess,
+    },
+    /// Refreshes the statistics on the columns of each of `table_ids`.
+    Analyze {
+        table_ids: Vec<u32>,
+    },
+    /// Compacts the storage of the database, returning the space reclaimed.
+    Vacuum,
 }
 
 impl AuthAccess for CrudCode {
@@ -740,6 +1073,21 @@ impl AuthAccess for CrudCode {
                     })
                 }
             }
+            CrudCode::CreateIndex { .. } => Err(AuthError::OwnerOnly {
+                action: "create indexes".into(),
+            }),
+            CrudCode::Grant { .. } | CrudCode::Revoke { .. } => Err(AuthError::OwnerOnly {
+                action: "grant and revoke roles".into(),
+            }),
+            CrudCode::GrantAccess { .. } | CrudCode::RevokeAccess { .. } => Err(AuthError::OwnerOnly {
+                action: "grant and revoke access to tables".into(),
+            }),
+            CrudCode::Analyze { .. } => Err(AuthError::OwnerOnly {
+                action: "analyze tables".into(),
+            }),
+            CrudCode::Vacuum => Err(AuthError::OwnerOnly {
+                action: "vacuum the database".into(),
+            }),
         }
     }
 }
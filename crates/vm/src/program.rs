@@ -202,6 +202,18 @@ impl ProgramVm for Program {
             CrudCode::Drop { .. } => {
                 todo!()
             }
+            CrudCode::CreateIndex { .. } => {
+                todo!()
+            }
+            CrudCode::Grant { .. } | CrudCode::Revoke { .. } => {
+                todo!()
+            }
+            CrudCode::GrantAccess { .. } | CrudCode::RevokeAccess { .. } => {
+                todo!()
+            }
+            CrudCode::Analyze { .. } | CrudCode::Vacuum => {
+                todo!()
+            }
         }
     }
 
@@ -1,6 +1,9 @@
 use crate::errors::ErrorVm;
+use crate::expr::OrderByExpr;
+use spacetimedb_lib::error::RelationError;
 use spacetimedb_lib::relation::{FieldExpr, Header, RelValue, RelValueRef, RowCount};
 use spacetimedb_sats::product_value::ProductValue;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 pub(crate) trait ResultExt<T> {
@@ -98,6 +101,48 @@ pub trait RelOps {
         Ok(JoinInner::new(head, self, with, key_lhs, key_rhs, predicate))
     }
 
+    /// Creates an `Iterator` which yields the rows ordered as by [RowOrder].
+    ///
+    /// The rows are all collected on the first call to `next`.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `ORDER BY` clause on SQL.
+    #[inline]
+    fn order_by(self, cols: &[OrderByExpr]) -> Result<OrderBy<Self>, ErrorVm>
+    where
+        Self: Sized,
+    {
+        let order = RowOrder::new(self.head(), cols)?;
+        Ok(OrderBy::new(self, order))
+    }
+
+    /// Creates an `Iterator` which skips the first `offset` rows.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `OFFSET` clause on SQL.
+    #[inline]
+    fn offset(self, offset: usize) -> Offset<Self>
+    where
+        Self: Sized,
+    {
+        Offset::new(self, offset)
+    }
+
+    /// Creates an `Iterator` which yields no more than the first `limit` rows.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `LIMIT` clause on SQL.
+    #[inline]
+    fn limit(self, limit: usize) -> Limit<Self>
+    where
+        Self: Sized,
+    {
+        Limit::new(self, limit)
+    }
+
     /// Utility to collect the results into a [Vec]
     #[inline]
     fn collect_vec(mut self) -> Result<Vec<ProductValue>, ErrorVm>
@@ -299,3 +344,170 @@ where
         }
     }
 }
+
+/// The order of the rows of a [Header] given by a list of [OrderByExpr],
+/// with ties broken by the whole row, so no two distinct rows are equal.
+#[derive(Clone, Debug)]
+pub struct RowOrder {
+    /// The positions of the columns, and whether each is descending.
+    cols: Vec<(usize, bool)>,
+}
+
+impl RowOrder {
+    /// Fails if any of the `cols` is not in `head`.
+    pub fn new(head: &Header, cols: &[OrderByExpr]) -> Result<Self, RelationError> {
+        let cols = cols
+            .iter()
+            .map(|col| match head.column_pos(&col.field) {
+                Some(pos) => Ok((pos, col.desc)),
+                None => Err(RelationError::FieldNotFound(head.clone(), col.field.clone())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { cols })
+    }
+
+    /// Compares the rows `a` and `b`.
+    pub fn cmp(&self, a: &ProductValue, b: &ProductValue) -> Ordering {
+        self.cols
+            .iter()
+            .map(|&(pos, desc)| {
+                let ord = a.elements[pos].cmp(&b.elements[pos]);
+                if desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| a.cmp(b))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OrderBy<I> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    pub(crate) order: RowOrder,
+    sorted: Option<std::vec::IntoIter<RelValue>>,
+}
+
+impl<I: RelOps> OrderBy<I> {
+    pub fn new(iter: I, order: RowOrder) -> OrderBy<I> {
+        OrderBy {
+            head: iter.head().clone(),
+            count: iter.row_count(),
+            iter,
+            order,
+            sorted: None,
+        }
+    }
+}
+
+impl<I: RelOps> RelOps for OrderBy<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.sorted.is_none() {
+            let mut rows = Vec::with_capacity(self.count.min);
+            while let Some(v) = self.iter.next()? {
+                rows.push(v);
+            }
+            rows.sort_by(|a, b| self.order.cmp(&a.data, &b.data));
+            self.sorted = Some(rows.into_iter());
+        }
+        Ok(self.sorted.as_mut().and_then(|rows| rows.next()))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Offset<I> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    pub(crate) offset: usize,
+}
+
+impl<I: RelOps> Offset<I> {
+    pub fn new(iter: I, offset: usize) -> Offset<I> {
+        let count = iter.row_count();
+        let count = RowCount {
+            min: count.min.saturating_sub(offset),
+            max: count.max.map(|max| max.saturating_sub(offset)),
+        };
+        Offset {
+            head: iter.head().clone(),
+            count,
+            iter,
+            offset,
+        }
+    }
+}
+
+impl<I: RelOps> RelOps for Offset<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        while self.offset > 0 {
+            if self.iter.next()?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+        self.iter.next()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limit<I> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    pub(crate) limit: usize,
+}
+
+impl<I: RelOps> Limit<I> {
+    pub fn new(iter: I, limit: usize) -> Limit<I> {
+        let count = iter.row_count();
+        let count = RowCount {
+            min: count.min.min(limit),
+            max: Some(count.max.map_or(limit, |max| max.min(limit))),
+        };
+        Limit {
+            head: iter.head().clone(),
+            count,
+            iter,
+            limit,
+        }
+    }
+}
+
+impl<I: RelOps> RelOps for Limit<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.limit == 0 {
+            return Ok(None);
+        }
+        self.limit -= 1;
+        self.iter.next()
+    }
+}
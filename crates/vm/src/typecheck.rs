@@ -143,6 +143,13 @@ pub(crate) fn check_types(env: &mut EnvTy, ast: &ExprOpt) -> Result<Ty, ErrorTyp
                     //todo: Extract the type from the catalog...
                     Ok(Ty::Unknown)
                 }
+                CrudExprOpt::CreateIndex { .. }
+                | CrudExprOpt::Grant { .. }
+                | CrudExprOpt::Revoke { .. }
+                | CrudExprOpt::GrantAccess { .. }
+                | CrudExprOpt::RevokeAccess { .. }
+                | CrudExprOpt::Analyze { .. }
+                | CrudExprOpt::Vacuum => Ok(Ty::Unknown),
             }
         }
         x => {
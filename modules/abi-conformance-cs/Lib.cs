@@ -0,0 +1,66 @@
+// The C# guest of the ABI conformance suite in `crates/testing/tests/abi_conformance.rs`,
+// implementing the cases of `modules/abi-conformance/src/lib.rs` that the C# runtime supports.
+// Every case must log the same lines as its Rust twin.
+// The cases the C# runtime can't implement yet are listed in `CSHARP_GAPS` of the suite.
+
+using SpacetimeDB.Module;
+using static SpacetimeDB.Runtime;
+
+static partial class Module
+{
+    [SpacetimeDB.Table]
+    public partial struct Item
+    {
+        [SpacetimeDB.Column(ColumnAttrs.Unique)]
+        public uint Id;
+        public string Name;
+    }
+
+    // Leaves exactly the items `1 => a`, `2 => b` and `3 => c` in the table.
+    private static void Reset()
+    {
+        foreach (var item in Item.Iter().ToList())
+        {
+            Item.DeleteById(item.Id);
+        }
+        new Item { Id = 1, Name = "a" }.Insert();
+        new Item { Id = 2, Name = "b" }.Insert();
+        new Item { Id = 3, Name = "c" }.Insert();
+    }
+
+    private static void LogRows()
+    {
+        Log($"rows: {Item.Iter().Count()}");
+    }
+
+    // `bool.ToString()` is capitalized, unlike Rust's `Display` for `bool`.
+    private static string Lower(bool value) => value ? "true" : "false";
+
+    [SpacetimeDB.Reducer("case_insert_and_iter")]
+    public static void CaseInsertAndIter()
+    {
+        Reset();
+        LogRows();
+        var names = Item.Iter().Select(item => item.Name).OrderBy(name => name, StringComparer.Ordinal);
+        Log($"names: {string.Join(",", names)}");
+    }
+
+    [SpacetimeDB.Reducer("case_filter_by_col")]
+    public static void CaseFilterByCol()
+    {
+        Reset();
+        foreach (var id in new uint[] { 2, 4 })
+        {
+            Log($"found: {Item.FilterById(id)?.Name ?? "none"}");
+        }
+    }
+
+    [SpacetimeDB.Reducer("case_delete_by_col")]
+    public static void CaseDeleteByCol()
+    {
+        Reset();
+        Log($"deleted: {Lower(Item.DeleteById(2))}");
+        Log($"deleted: {Lower(Item.DeleteById(2))}");
+        LogRows();
+    }
+}
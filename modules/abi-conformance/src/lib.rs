@@ -0,0 +1,268 @@
+//! The Rust guest of the ABI conformance suite in `crates/testing/tests/abi_conformance.rs`.
+//!
+//! Each `case_*` reducer exercises some host calls and logs what it observed,
+//! in lines every guest of the suite must log identically.
+//! Keep it in sync with `modules/abi-conformance-cs/Lib.cs`.
+
+use std::time::Duration;
+
+use spacetimedb::{spacetimedb, ReducerContext};
+
+#[spacetimedb(table)]
+pub struct Item {
+    #[unique]
+    id: u32,
+    name: String,
+}
+
+#[spacetimedb(table)]
+pub struct ArchivedItem {
+    #[unique]
+    id: u32,
+    name: String,
+}
+
+#[spacetimedb(table)]
+pub struct Attachment {
+    #[unique]
+    id: u32,
+    data: spacetimedb::LargeBytes,
+}
+
+#[spacetimedb(event)]
+pub struct Ping {
+    n: u32,
+}
+
+/// Leaves exactly the items `1 => a`, `2 => b` and `3 => c` in the table.
+fn reset() {
+    for item in Item::iter() {
+        Item::delete_by_id(&item.id);
+    }
+    for (id, name) in [(1, "a"), (2, "b"), (3, "c")] {
+        Item::insert(Item { id, name: name.into() }).unwrap();
+    }
+}
+
+fn log_rows() {
+    log::info!("rows: {}", Item::iter().count());
+}
+
+#[spacetimedb(reducer)]
+pub fn case_insert_and_iter() {
+    reset();
+    log_rows();
+    let mut names = Item::iter().map(|item| item.name).collect::<Vec<_>>();
+    names.sort();
+    log::info!("names: {}", names.join(","));
+}
+
+#[spacetimedb(reducer)]
+pub fn case_iter_cols() {
+    reset();
+    let mut names = Item::iter_cols::<(item_cols::Name,)>()
+        .map(|(name,)| name)
+        .collect::<Vec<_>>();
+    names.sort();
+    log::info!("names: {}", names.join(","));
+}
+
+#[spacetimedb(reducer)]
+pub fn case_filter_by_col() {
+    reset();
+    for id in [2, 4] {
+        let name = Item::filter_by_id(&id).map_or("none".into(), |item| item.name);
+        log::info!("found: {name}");
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn case_delete_by_col() {
+    reset();
+    log::info!("deleted: {}", Item::delete_by_id(&2));
+    log::info!("deleted: {}", Item::delete_by_id(&2));
+    log_rows();
+}
+
+#[spacetimedb(reducer)]
+pub fn case_unique_violation() {
+    reset();
+    let res = Item::insert(Item {
+        id: 1,
+        name: "d".into(),
+    });
+    match res {
+        Ok(_) => log::info!("violation: none"),
+        Err(err) => log::info!("violation: {}", err.column.as_deref().unwrap_or("unknown")),
+    }
+    log_rows();
+}
+
+#[spacetimedb(reducer)]
+pub fn case_savepoint() {
+    reset();
+    let savepoint = spacetimedb::savepoint();
+    Item::delete_by_id(&1);
+    log_rows();
+    savepoint.rollback_to();
+    log_rows();
+}
+
+#[spacetimedb(reducer)]
+pub fn case_move_rows() {
+    reset();
+    for item in ArchivedItem::iter() {
+        ArchivedItem::delete_by_id(&item.id);
+    }
+    let archive = || {
+        spacetimedb::move_rows(
+            |item: &Item| item.id >= 2,
+            |item| ArchivedItem {
+                id: item.id,
+                name: item.name,
+            },
+        )
+    };
+    // The item 3 can't be archived, so the item 2 isn't either.
+    ArchivedItem::insert(ArchivedItem {
+        id: 3,
+        name: "c".into(),
+    })
+    .unwrap();
+    log::info!("failed: {}", archive().is_err());
+    log_rows();
+    ArchivedItem::delete_by_id(&3);
+    log::info!("moved: {}", archive().unwrap());
+    log_rows();
+}
+
+#[spacetimedb(reducer)]
+pub fn case_emit_event() {
+    Ping { n: 1 }.emit();
+    log::info!("emitted");
+}
+
+#[spacetimedb(reducer)]
+pub fn case_remaining_energy() {
+    log::info!("energy: {}", spacetimedb::remaining_energy() > 0);
+}
+
+#[spacetimedb(reducer)]
+pub fn case_tx_offset(ctx: ReducerContext) {
+    // The cases before this one committed changes.
+    log::info!("offset: {}", ctx.tx_offset > 0);
+}
+
+#[spacetimedb(reducer)]
+pub fn case_table_version() {
+    // The cases before this one committed changes to the items.
+    let version = Item::table_version();
+    log::info!("changed: {}", version > 0);
+    reset();
+    log::info!("unchanged: {}", Item::table_version() == version);
+}
+
+#[spacetimedb(reducer)]
+pub fn case_table_row_type() {
+    let table_id = <Item as spacetimedb::TableType>::table_id();
+    let schema = spacetimedb::sys::table_row_type(table_id).unwrap().read();
+    let row_type = spacetimedb::decode_schema(&mut &schema[..]).unwrap();
+    log::info!("columns: {}", row_type.elements.len());
+    log::info!("missing: {}", spacetimedb::sys::table_row_type(u32::MAX).is_err());
+}
+
+#[spacetimedb(reducer)]
+pub fn case_blobs() {
+    let data = b"blob";
+    let hash = spacetimedb::blob_put("case/blob", data).unwrap();
+    log::info!("hash: {}", hash == spacetimedb::spacetimedb_lib::hash::hash_bytes(data));
+    log::info!(
+        "blob: {}",
+        spacetimedb::blob_get("case/blob").as_deref() == Some(&data[..])
+    );
+    let too_large = vec![0; spacetimedb::spacetimedb_lib::MAX_BLOB_SIZE + 1];
+    log::info!(
+        "too large: {}",
+        spacetimedb::blob_put("case/blob", &too_large) == Err(spacetimedb::Errno::BLOB_TOO_LARGE)
+    );
+    log::info!("deleted: {}", spacetimedb::blob_delete("case/blob"));
+    log::info!("missing: {}", spacetimedb::blob_get("case/blob").is_none());
+}
+
+#[spacetimedb(reducer)]
+pub fn case_large_bytes() {
+    Attachment::delete_by_id(&1);
+    Attachment::delete_by_id(&2);
+    let small = vec![1; 8];
+    let large = vec![2; 100];
+    Attachment::insert(Attachment {
+        id: 1,
+        data: small.clone().into(),
+    })
+    .unwrap();
+    Attachment::insert(Attachment {
+        id: 2,
+        data: large.clone().into(),
+    })
+    .unwrap();
+    // Rows read back from the table fetch their bytes from the host.
+    log::info!(
+        "inline: {}",
+        Attachment::filter_by_id(&1).unwrap().data.as_bytes() == small
+    );
+    log::info!(
+        "stored: {}",
+        Attachment::filter_by_id(&2).unwrap().data.as_bytes() == large
+    );
+    let bogus = spacetimedb::spacetimedb_lib::DataKey::from_data([0; 100]).to_bytes();
+    log::info!(
+        "missing: {}",
+        spacetimedb::sys::large_bytes_get(&bogus).err() == Some(spacetimedb::Errno::LOOKUP_NOT_FOUND)
+    );
+}
+
+#[spacetimedb(reducer)]
+pub fn case_schedule_and_cancel() {
+    // `schedule!` discards the token it gets, which is needed here to cancel the call.
+    never::schedule(spacetimedb::rt::schedule_in(Duration::from_secs(3600))).cancel();
+    log::info!("cancelled");
+}
+
+#[spacetimedb(reducer)]
+pub fn case_describe_reducer() {
+    let args = spacetimedb::describe_reducer("take_item").unwrap();
+    let names: Vec<_> = args
+        .elements
+        .iter()
+        .map(|el| el.name.as_deref().unwrap_or("_"))
+        .collect();
+    log::info!("args: {}", names.join(","));
+    log::info!(
+        "missing: {}",
+        spacetimedb::describe_reducer("no_such_reducer").is_none()
+    );
+    log::info!(
+        "listed: {}",
+        spacetimedb::reducers().iter().any(|name| name == "take_item")
+    );
+    // The type of an argument declared by the module is described in full, not as a reference.
+    let args = spacetimedb::describe_reducer("restock").unwrap();
+    log::info!(
+        "resolved: {}",
+        matches!(
+            args.elements[0].algebraic_type,
+            spacetimedb::sats::AlgebraicType::Product(_)
+        )
+    );
+}
+
+#[spacetimedb(reducer)]
+pub fn take_item(_id: u32, _name: String) {}
+
+#[spacetimedb(reducer)]
+pub fn restock(_item: Item) {}
+
+#[spacetimedb(reducer)]
+pub fn never() {
+    log::info!("scheduled call wasn't cancelled");
+}
@@ -0,0 +1,30 @@
+//! The module of `test_conflicting_reducers_are_serialized`,
+//! whose reducers all read and write the same row, so that any two running at once conflict.
+
+use spacetimedb::{println, spacetimedb};
+
+#[spacetimedb(table)]
+pub struct Counter {
+    #[primarykey]
+    id: u32,
+    count: u64,
+}
+
+#[spacetimedb(init)]
+pub fn init() {
+    Counter::insert(Counter { id: 0, count: 0 }).unwrap();
+}
+
+#[spacetimedb(reducer)]
+pub fn increment() {
+    let counter = Counter::filter_by_id(&0).unwrap();
+    // Spin between the read and the write, so that concurrent calls overlap.
+    let mut spin = 0u64;
+    for i in 0..100_000 {
+        spin = spin.wrapping_add(std::hint::black_box(i));
+    }
+    std::hint::black_box(spin);
+    let count = counter.count + 1;
+    Counter::update_by_id(&0, Counter { id: 0, count });
+    println!("Count is {}", count);
+}
@@ -0,0 +1,56 @@
+// The quickstart module, written in AssemblyScript, for the tests of the `assemblyscript` host type.
+
+import {
+  ColumnAttr,
+  Deserializable,
+  Reader,
+  ReducerContext,
+  Table,
+  Writer,
+  column,
+  field,
+  log,
+  reducer,
+} from "@clockworklabs/spacetimedb-assemblyscript/assembly";
+
+export * from "@clockworklabs/spacetimedb-assemblyscript/assembly/exports";
+
+class Person extends Deserializable {
+  id: u32 = 0;
+  name: string = "";
+
+  serialize(w: Writer): void {
+    w.write(this.id);
+    w.write(this.name);
+  }
+
+  deserialize(r: Reader): void {
+    this.id = r.read<u32>();
+    this.name = r.read<string>();
+  }
+}
+
+const people = new Table<Person>("Person", [column<u32>("id", ColumnAttr.IDENTITY), column<string>("name")]);
+
+reducer("add", [field<string>("name")], (ctx: ReducerContext, args: Reader): string | null => {
+  const person = new Person();
+  person.name = args.read<string>();
+  people.insert(person);
+  log(`Inserted ${person.name}: ${person.id != 0}`);
+  return null;
+});
+
+reducer("say_hello", [], (ctx: ReducerContext, args: Reader): string | null => {
+  const all = people.iter();
+  for (let i = 0; i < all.length; i++) {
+    log(`Hello, ${all[i].name}!`);
+  }
+  log("Hello, World!");
+  return null;
+});
+
+reducer("fail", [], (ctx: ReducerContext, args: Reader): string | null => {
+  people.insert(new Person());
+  assert(false, "failed on purpose");
+  return null;
+});
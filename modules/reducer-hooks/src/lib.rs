@@ -0,0 +1,36 @@
+//! The module of `test_reducer_hooks`, which logs around every reducer and keeps some from running.
+
+use spacetimedb::{println, spacetimedb, ReducerCall};
+
+#[spacetimedb(table)]
+pub struct Person {
+    name: String,
+}
+
+#[spacetimedb(before_reducer)]
+fn check(call: &ReducerCall<'_>) -> Result<(), String> {
+    println!("before {}", call.name);
+    if call.name == "forbidden" {
+        return Err(format!("{} is off limits", call.name));
+    }
+    Ok(())
+}
+
+#[spacetimedb(after_reducer)]
+fn report(call: &ReducerCall<'_>, res: Result<(), &str>) {
+    match res {
+        Ok(()) => println!("after {}: ok", call.name),
+        Err(err) => println!("after {}: {err}", call.name),
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn add(name: String) {
+    println!("adding {name}");
+    Person::insert(Person { name });
+}
+
+#[spacetimedb(reducer)]
+pub fn forbidden() {
+    println!("ran forbidden");
+}
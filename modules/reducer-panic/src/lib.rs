@@ -0,0 +1,16 @@
+//! The module of `test_reducer_panic_is_logged_with_its_call`, whose reducer panics midway.
+
+use spacetimedb::spacetimedb;
+
+#[spacetimedb(table)]
+pub struct Person {
+    name: String,
+}
+
+/// Adds the person twice, then panics before the call can succeed.
+#[spacetimedb(reducer)]
+pub fn add_twice_and_panic(name: String) {
+    Person::insert(Person { name: name.clone() });
+    Person::insert(Person { name });
+    panic!("the second person was one too many");
+}
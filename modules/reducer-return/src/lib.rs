@@ -0,0 +1,18 @@
+//! The module of `test_reducer_return_value`, whose reducer returns a value to its caller.
+
+use spacetimedb::spacetimedb;
+
+#[spacetimedb(table)]
+pub struct Person {
+    name: String,
+}
+
+/// Adds a person, returning how many there are now.
+#[spacetimedb(reducer)]
+pub fn add(name: String) -> Result<u32, String> {
+    if name.is_empty() {
+        return Err("a person needs a name".into());
+    }
+    Person::insert(Person { name });
+    Ok(Person::iter().count() as u32)
+}
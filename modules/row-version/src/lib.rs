@@ -0,0 +1,53 @@
+//! The module of `test_row_version`, whose accounts are updated only at the version last read.
+
+use spacetimedb::{println, spacetimedb};
+
+#[spacetimedb(table)]
+pub struct Account {
+    #[primarykey]
+    id: u32,
+    balance: u64,
+    #[row_version]
+    version: u64,
+}
+
+#[spacetimedb(reducer)]
+pub fn open(id: u32) {
+    // The host starts the version at 0, whatever it's inserted as.
+    let account = Account::insert(Account {
+        id,
+        balance: 0,
+        version: 99,
+    })
+    .unwrap();
+    println!("Opened {} at version {}", account.id, account.version);
+}
+
+#[spacetimedb(reducer)]
+pub fn set_balance(id: u32, balance: u64, expected_version: u64) {
+    let account = Account {
+        id,
+        balance,
+        version: 0,
+    };
+    let updated = Account::update_if_version(account, expected_version);
+    println!("Set {} to {}: {}", id, balance, updated);
+}
+
+#[spacetimedb(reducer)]
+pub fn reset(id: u32, balance: u64) {
+    let account = Account {
+        id,
+        balance,
+        version: 0,
+    };
+    Account::update_by_id(&id, account);
+}
+
+#[spacetimedb(reducer)]
+pub fn show(id: u32) {
+    match Account::filter_by_id(&id) {
+        Some(account) => println!("{} has {} at version {}", account.id, account.balance, account.version),
+        None => println!("No {}", id),
+    }
+}
@@ -141,7 +141,7 @@ pub fn add_private(name: String) {
     _Private::insert(_Private { name });
 }
 
-#[spacetimedb(reducer)]
+#[spacetimedb(reducer, read_only)]
 pub fn query_private() {
     for person in _Private::iter() {
         log::info!("Private, {}!", person.name);
@@ -0,0 +1,26 @@
+//! A version of the module of `modules/schema-upgrade-v1` whose update fails after adding a table,
+//! for `test_failed_update_is_rolled_back`.
+
+use spacetimedb::spacetimedb;
+
+#[spacetimedb(table)]
+pub struct Person {
+    #[unique]
+    name: String,
+}
+
+#[spacetimedb(table)]
+pub struct Pet {
+    name: String,
+}
+
+#[spacetimedb(update)]
+pub fn update() {
+    Pet::insert(Pet { name: "Ghost".into() });
+    panic!("The update fails");
+}
+
+#[spacetimedb(reducer)]
+pub fn add(name: String) {
+    Person::insert(Person { name }).unwrap();
+}
@@ -0,0 +1,15 @@
+//! The first version of the module of `test_update_adds_option_columns`,
+//! whose rows `modules/schema-upgrade-v2` reads after adding a column to them.
+
+use spacetimedb::spacetimedb;
+
+#[spacetimedb(table)]
+pub struct Person {
+    #[unique]
+    name: String,
+}
+
+#[spacetimedb(reducer)]
+pub fn add(name: String) {
+    Person::insert(Person { name }).unwrap();
+}
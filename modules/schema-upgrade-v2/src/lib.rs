@@ -0,0 +1,31 @@
+//! The second version of the module of `test_update_adds_option_columns`,
+//! which adds an `Option` column to the table of `modules/schema-upgrade-v1`.
+
+use spacetimedb::{println, spacetimedb};
+
+#[spacetimedb(table)]
+pub struct Person {
+    #[unique]
+    name: String,
+    age: Option<u32>,
+}
+
+#[spacetimedb(reducer)]
+pub fn add(name: String, age: Option<u32>) {
+    Person::insert(Person { name, age }).unwrap();
+}
+
+#[spacetimedb(reducer)]
+pub fn say_hello() {
+    for person in Person::iter() {
+        println!("Hello, {} ({:?})!", person.name, person.age);
+    }
+}
+
+#[spacetimedb(reducer)]
+pub fn find(name: String) {
+    match Person::filter_by_name(&name) {
+        Some(person) => println!("Found {} ({:?})", person.name, person.age),
+        None => println!("No {}", name),
+    }
+}